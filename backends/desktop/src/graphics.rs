@@ -11,22 +11,67 @@ pub use graphics::*;
 /// A graphics backend for SDL2.
 pub struct SdlGraphicsBackend {
   sampler_cache: RwLock<FastHashMap<TextureSampler, u32>>,
+  has_parallel_compile: bool,
 }
 
 impl SdlGraphicsBackend {
   /// Creates a new OpenGL graphics backend.
   pub fn new() -> Self {
-    gl::load_with(|symbol| unsafe {
-      let name = CString::new(symbol).unwrap();
-      sdl2_sys::SDL_GL_GetProcAddress(name.as_ptr() as *const _) as *const _
-    });
+    load_gl_functions();
 
     Self {
       sampler_cache: RwLock::new(FastHashMap::default()),
+      has_parallel_compile: enable_parallel_compile(),
     }
   }
 }
 
+/// `GL_COMPLETION_STATUS_KHR`, from `KHR_parallel_shader_compile`.
+const COMPLETION_STATUS_KHR: gl::types::GLenum = 0x91B1;
+
+/// Loads the OpenGL entry points for the current context.
+fn load_gl_functions() {
+  gl::load_with(|symbol| unsafe {
+    let name = CString::new(symbol).unwrap();
+    sdl2_sys::SDL_GL_GetProcAddress(name.as_ptr() as *const _) as *const _
+  });
+}
+
+/// Lets the driver compile shaders on its own threads, if it supports
+/// `KHR_parallel_shader_compile` (or the ARB version of it).
+fn enable_parallel_compile() -> bool {
+  type MaxShaderCompilerThreads = unsafe extern "system" fn(count: gl::types::GLuint);
+
+  const EXTENSIONS: [(&str, &str); 2] = [
+    ("GL_KHR_parallel_shader_compile", "glMaxShaderCompilerThreadsKHR"),
+    ("GL_ARB_parallel_shader_compile", "glMaxShaderCompilerThreadsARB"),
+  ];
+
+  for (extension, function) in EXTENSIONS {
+    unsafe {
+      let extension = CString::new(extension).unwrap();
+
+      if sdl2_sys::SDL_GL_ExtensionSupported(extension.as_ptr()) != sdl2_sys::SDL_bool::SDL_TRUE {
+        continue;
+      }
+
+      let function = CString::new(function).unwrap();
+      let address = sdl2_sys::SDL_GL_GetProcAddress(function.as_ptr());
+
+      if !address.is_null() {
+        let max_shader_compiler_threads: MaxShaderCompilerThreads = std::mem::transmute(address);
+
+        // let the driver decide how many threads to use
+        max_shader_compiler_threads(u32::MAX);
+      }
+
+      return true;
+    }
+  }
+
+  false
+}
+
 impl GraphicsBackend for SdlGraphicsBackend {
   fn begin_frame(&self) {
     // no-op
@@ -36,6 +81,25 @@ impl GraphicsBackend for SdlGraphicsBackend {
     // no-op
   }
 
+  fn device_status(&self) -> DeviceStatus {
+    // only robust contexts can report a reset
+    if !gl::GetGraphicsResetStatus::is_loaded() {
+      return DeviceStatus::Ready;
+    }
+
+    match unsafe { gl::GetGraphicsResetStatus() } {
+      gl::NO_ERROR => DeviceStatus::Ready,
+      _ => DeviceStatus::Lost,
+    }
+  }
+
+  fn device_reset(&self) {
+    // samplers belonged to the old context
+    self.sampler_cache.write().unwrap().clear();
+
+    load_gl_functions();
+  }
+
   fn clear_color_buffer(&self, color: Color) {
     unsafe {
       gl::ClearColor(color.r, color.g, color.b, color.a);
@@ -118,6 +182,28 @@ impl GraphicsBackend for SdlGraphicsBackend {
     }
   }
 
+  fn set_depth_state(&self, depth_state: DepthState) {
+    unsafe {
+      match depth_state {
+        DepthState::Disabled => gl::Disable(gl::DEPTH_TEST),
+        DepthState::Enabled { comparison, write } => {
+          gl::Enable(gl::DEPTH_TEST);
+          gl::DepthFunc(match comparison {
+            DepthComparison::Never => gl::NEVER,
+            DepthComparison::Less => gl::LESS,
+            DepthComparison::Equal => gl::EQUAL,
+            DepthComparison::LessOrEqual => gl::LEQUAL,
+            DepthComparison::Greater => gl::GREATER,
+            DepthComparison::NotEqual => gl::NOTEQUAL,
+            DepthComparison::GreaterOrEqual => gl::GEQUAL,
+            DepthComparison::Always => gl::ALWAYS,
+          });
+          gl::DepthMask(if write { gl::TRUE } else { gl::FALSE });
+        }
+      }
+    }
+  }
+
   fn set_scissor_mode(&self, scissor_mode: ScissorMode) {
     unsafe {
       match scissor_mode {
@@ -199,6 +285,14 @@ impl GraphicsBackend for SdlGraphicsBackend {
     }
   }
 
+  fn buffer_bind_storage(&self, buffer: BufferId, binding: u32) -> Result<(), BufferError> {
+    unsafe {
+      gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, buffer.into());
+
+      Ok(())
+    }
+  }
+
   fn buffer_delete(&self, buffer: BufferId) -> Result<(), BufferError> {
     unsafe {
       gl::DeleteBuffers(1, &buffer.into());
@@ -373,6 +467,81 @@ impl GraphicsBackend for SdlGraphicsBackend {
     }
   }
 
+  fn texture_initialize_layers(
+    &self,
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    layers: u32,
+    format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    unsafe {
+      let (components, kind) = convert_texture_format(format);
+
+      gl::BindTexture(gl::TEXTURE_2D_ARRAY, texture.into());
+      gl::TexImage3D(
+        gl::TEXTURE_2D_ARRAY,
+        0,
+        components as i32,
+        width as i32,
+        height as i32,
+        layers as i32,
+        0,
+        components,
+        kind,
+        std::ptr::null(),
+      );
+
+      gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+      gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+      gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+      gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+
+      Ok(())
+    }
+  }
+
+  fn texture_write_layer_data(
+    &self,
+    texture: TextureId,
+    region: &Rectangle,
+    layer: u32,
+    pixels: *const u8,
+    pixel_format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    unsafe {
+      let (components, kind) = convert_texture_format(pixel_format);
+
+      gl::BindTexture(gl::TEXTURE_2D_ARRAY, texture.into());
+      gl::TexSubImage3D(
+        gl::TEXTURE_2D_ARRAY,
+        0, // mip level
+        region.left() as i32,
+        region.top() as i32,
+        layer as i32,
+        region.width() as i32,
+        region.height() as i32,
+        1, // depth
+        components,
+        kind,
+        pixels as *const _,
+      );
+
+      Ok(())
+    }
+  }
+
+  fn texture_set_mip_range(&self, texture: TextureId, base_level: usize, max_level: usize) -> Result<(), TextureError> {
+    unsafe {
+      gl::BindTexture(gl::TEXTURE_2D, texture.into());
+
+      gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_BASE_LEVEL, base_level as i32);
+      gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, max_level as i32);
+
+      Ok(())
+    }
+  }
+
   fn texture_delete(&self, texture: TextureId) -> Result<(), TextureError> {
     unsafe {
       gl::DeleteTextures(1, &texture.into());
@@ -388,13 +557,26 @@ impl GraphicsBackend for SdlGraphicsBackend {
   #[allow(clippy::uninit_vec)]
   fn shader_link(&self, shader: ShaderId, shaders: &[ShaderKernel]) -> Result<(), ShaderError> {
     unsafe {
-      let shader = shader.into();
+      gl::UseProgram(shader.into());
+    }
+
+    self.shader_link_async(shader, shaders)?;
 
-      gl::UseProgram(shader);
+    // querying the status waits for the driver to finish
+    unsafe { finish_link(shader.into()) }
+  }
+
+  fn shader_link_async(&self, shader: ShaderId, shaders: &[ShaderKernel]) -> Result<(), ShaderError> {
+    unsafe {
+      let shader = shader.into();
 
-      // compile the shader kernel code
-      let mut shader_ids = Vec::with_capacity(shaders.len());
+      // drop any kernels left over from a link that was never finished
+      for shader_id in attached_shaders(shader) {
+        gl::DetachShader(shader, shader_id);
+        gl::DeleteShader(shader_id);
+      }
 
+      // compile the shader kernel code, without waiting on the result
       for ShaderKernel { kind, code } in shaders {
         let shader_id = gl::CreateShader(match kind {
           ShaderKind::Vertex => gl::VERTEX_SHADER,
@@ -407,61 +589,83 @@ impl GraphicsBackend for SdlGraphicsBackend {
 
         gl::ShaderSource(shader_id, 1, &code, &code_length);
         gl::CompileShader(shader_id);
+        gl::AttachShader(shader, shader_id);
+      }
 
-        let mut compile_status = 0;
-        gl::GetShaderiv(shader_id, gl::COMPILE_STATUS, &mut compile_status);
+      // link the kernels in the main program, keeping the binary for the cache
+      gl::ProgramParameteri(shader, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as i32);
+      gl::LinkProgram(shader);
+    }
 
-        if compile_status == 0 {
-          let mut info_log_length = 0;
-          gl::GetShaderiv(shader_id, gl::INFO_LOG_LENGTH, &mut info_log_length);
+    Ok(())
+  }
 
-          let mut info_log = Vec::with_capacity(info_log_length as usize);
-          info_log.set_len(info_log_length as usize);
+  fn shader_link_status(&self, shader: ShaderId) -> ShaderLinkStatus {
+    unsafe {
+      let shader = shader.into();
 
-          gl::GetShaderInfoLog(
-            shader_id,
-            info_log_length,
-            std::ptr::null_mut(),
-            info_log.as_mut_ptr() as *mut _,
-          );
+      if self.has_parallel_compile {
+        let mut is_complete = 0;
+        gl::GetProgramiv(shader, COMPLETION_STATUS_KHR, &mut is_complete);
 
-          return Err(ShaderError::CompileError(String::from_utf8(info_log).unwrap()));
+        if is_complete == 0 {
+          return ShaderLinkStatus::Compiling;
         }
+      }
 
-        gl::AttachShader(shader, shader_id);
-        shader_ids.push(shader_id);
+      match finish_link(shader) {
+        Ok(()) => ShaderLinkStatus::Linked,
+        Err(ShaderError::CompileError(log)) => ShaderLinkStatus::Failed(log),
+        Err(error) => ShaderLinkStatus::Failed(format!("{error:?}")),
       }
+    }
+  }
 
-      // link the kernels in the main program
-      let mut link_status = 0;
+  fn shader_binary(&self, shader: ShaderId) -> Option<ShaderBinary> {
+    unsafe {
+      let shader = shader.into();
+      let mut length = 0;
 
-      gl::LinkProgram(shader);
-      gl::GetProgramiv(shader, gl::LINK_STATUS, &mut link_status);
+      gl::GetProgramiv(shader, gl::PROGRAM_BINARY_LENGTH, &mut length);
 
-      if link_status == 0 {
-        let mut info_log_length = 0;
-        gl::GetProgramiv(shader, gl::INFO_LOG_LENGTH, &mut info_log_length);
+      if length <= 0 {
+        return None;
+      }
 
-        let mut info_log = Vec::with_capacity(info_log_length as usize);
-        info_log.set_len(info_log_length as usize);
+      let mut data = vec![0u8; length as usize];
+      let mut format = 0;
 
-        gl::GetProgramInfoLog(
-          shader,
-          info_log_length,
-          std::ptr::null_mut(),
-          info_log.as_mut_ptr() as *mut _,
-        );
+      gl::GetProgramBinary(
+        shader,
+        length,
+        std::ptr::null_mut(),
+        &mut format,
+        data.as_mut_ptr() as *mut _,
+      );
 
-        return Err(ShaderError::CompileError(String::from_utf8(info_log).unwrap()));
-      }
+      Some(ShaderBinary { format, data })
+    }
+  }
 
-      // delete the kernels now that we've linked
-      for shader_id in shader_ids {
-        gl::DeleteShader(shader_id);
+  fn shader_link_binary(&self, shader: ShaderId, binary: &ShaderBinary) -> Result<(), ShaderError> {
+    unsafe {
+      let shader = shader.into();
+      let mut link_status = 0;
+
+      gl::ProgramBinary(
+        shader,
+        binary.format,
+        binary.data.as_ptr() as *const _,
+        binary.data.len() as i32,
+      );
+      gl::GetProgramiv(shader, gl::LINK_STATUS, &mut link_status);
+
+      // drivers reject binaries from other versions; callers recompile instead
+      match link_status {
+        0 => Err(ShaderError::UnsupportedBinary),
+        _ => Ok(()),
       }
     }
-
-    Ok(())
   }
 
   fn shader_uniform_location(&self, shader: ShaderId, name: &str) -> Option<usize> {
@@ -545,9 +749,14 @@ impl GraphicsBackend for SdlGraphicsBackend {
             color.a as u32,
           );
         }
-        ShaderUniform::Texture(texture, slot, sampler) => {
+        ShaderUniform::Texture(texture, slot, sampler) | ShaderUniform::LayeredTexture(texture, slot, sampler) => {
+          let target = match value {
+            ShaderUniform::LayeredTexture(..) => gl::TEXTURE_2D_ARRAY,
+            _ => gl::TEXTURE_2D,
+          };
+
           gl::ActiveTexture(gl::TEXTURE0 + *slot as u32);
-          gl::BindTexture(gl::TEXTURE_2D, (*texture).into());
+          gl::BindTexture(target, (*texture).into());
           gl::ProgramUniform1i(shader_id, location as i32, *slot as i32);
 
           if let Some(sampler) = sampler {
@@ -624,6 +833,7 @@ impl GraphicsBackend for SdlGraphicsBackend {
     unsafe {
       gl::MemoryBarrier(match barrier {
         MemoryBarrier::ImageAccess => gl::SHADER_IMAGE_ACCESS_BARRIER_BIT,
+        MemoryBarrier::VertexAttributes => gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT,
       });
 
       Ok(())
@@ -846,6 +1056,87 @@ impl GraphicsBackend for SdlGraphicsBackend {
   }
 }
 
+/// Checks the result of a link, deleting its kernels now they're not needed.
+///
+/// Blocks until the driver has finished compiling and linking.
+unsafe fn finish_link(shader: u32) -> Result<(), ShaderError> {
+  let shader_ids = attached_shaders(shader);
+  let mut result = Ok(());
+
+  // a kernel's compile error explains the link error it causes
+  for &shader_id in &shader_ids {
+    let mut compile_status = 0;
+    gl::GetShaderiv(shader_id, gl::COMPILE_STATUS, &mut compile_status);
+
+    if compile_status == 0 {
+      result = Err(ShaderError::CompileError(shader_info_log(shader_id)));
+      break;
+    }
+  }
+
+  if result.is_ok() {
+    let mut link_status = 0;
+    gl::GetProgramiv(shader, gl::LINK_STATUS, &mut link_status);
+
+    if link_status == 0 {
+      result = Err(ShaderError::CompileError(program_info_log(shader)));
+    }
+  }
+
+  for shader_id in shader_ids {
+    gl::DetachShader(shader, shader_id);
+    gl::DeleteShader(shader_id);
+  }
+
+  result
+}
+
+/// Lists the kernels attached to a program.
+unsafe fn attached_shaders(shader: u32) -> Vec<u32> {
+  let mut count = 0;
+  gl::GetProgramiv(shader, gl::ATTACHED_SHADERS, &mut count);
+
+  let mut shader_ids = vec![0; count.max(0) as usize];
+
+  if count > 0 {
+    gl::GetAttachedShaders(shader, count, std::ptr::null_mut(), shader_ids.as_mut_ptr());
+  }
+
+  shader_ids
+}
+
+unsafe fn shader_info_log(shader_id: u32) -> String {
+  let mut info_log_length = 0;
+  gl::GetShaderiv(shader_id, gl::INFO_LOG_LENGTH, &mut info_log_length);
+
+  let mut info_log = vec![0u8; info_log_length.max(0) as usize];
+
+  gl::GetShaderInfoLog(
+    shader_id,
+    info_log_length,
+    std::ptr::null_mut(),
+    info_log.as_mut_ptr() as *mut _,
+  );
+
+  String::from_utf8_lossy(&info_log).into_owned()
+}
+
+unsafe fn program_info_log(shader: u32) -> String {
+  let mut info_log_length = 0;
+  gl::GetProgramiv(shader, gl::INFO_LOG_LENGTH, &mut info_log_length);
+
+  let mut info_log = vec![0u8; info_log_length.max(0) as usize];
+
+  gl::GetProgramInfoLog(
+    shader,
+    info_log_length,
+    std::ptr::null_mut(),
+    info_log.as_mut_ptr() as *mut _,
+  );
+
+  String::from_utf8_lossy(&info_log).into_owned()
+}
+
 fn convert_texture_format(texture_format: TextureFormat) -> (u32, u32) {
   match texture_format {
     TextureFormat::R8 => (gl::RED, gl::UNSIGNED_BYTE),
@@ -1,5 +1,6 @@
 //! Input handling for SDL.
 
+use common::Vec2;
 pub use input::*;
 use sdl2_sys::{SDL_KeyCode, SDL_Keycode};
 
@@ -68,6 +69,17 @@ impl SdlMouseDevice {
     }
   }
 
+  pub fn on_mouse_move(&mut self, x: i32, y: i32, delta_x: i32, delta_y: i32) {
+    self.events.push(MouseEvent::MouseMove {
+      position: Vec2::new(x as f32, y as f32),
+      delta: Vec2::new(delta_x as f32, delta_y as f32),
+    });
+  }
+
+  pub fn on_mouse_wheel(&mut self, delta: i32) {
+    self.events.push(MouseEvent::MouseWheel { delta: delta as f32 });
+  }
+
   pub fn clear_events(&mut self) {
     self.events.clear();
   }
@@ -84,6 +96,33 @@ fn convert_scancode(scan_code: SDL_Keycode) -> Option<VirtualKey> {
     SDL_KeyCode::SDLK_LEFT => Some(ArrowLeft),
     SDL_KeyCode::SDLK_RIGHT => Some(ArrowRight),
     SDL_KeyCode::SDLK_SPACE => Some(Space),
+    SDL_KeyCode::SDLK_LSHIFT | SDL_KeyCode::SDLK_RSHIFT => Some(Shift),
+    SDL_KeyCode::SDLK_a => Some(A),
+    SDL_KeyCode::SDLK_b => Some(B),
+    SDL_KeyCode::SDLK_c => Some(C),
+    SDL_KeyCode::SDLK_d => Some(D),
+    SDL_KeyCode::SDLK_e => Some(E),
+    SDL_KeyCode::SDLK_f => Some(F),
+    SDL_KeyCode::SDLK_g => Some(G),
+    SDL_KeyCode::SDLK_h => Some(H),
+    SDL_KeyCode::SDLK_i => Some(I),
+    SDL_KeyCode::SDLK_j => Some(J),
+    SDL_KeyCode::SDLK_k => Some(K),
+    SDL_KeyCode::SDLK_l => Some(L),
+    SDL_KeyCode::SDLK_m => Some(M),
+    SDL_KeyCode::SDLK_n => Some(N),
+    SDL_KeyCode::SDLK_o => Some(O),
+    SDL_KeyCode::SDLK_p => Some(P),
+    SDL_KeyCode::SDLK_q => Some(Q),
+    SDL_KeyCode::SDLK_r => Some(R),
+    SDL_KeyCode::SDLK_s => Some(S),
+    SDL_KeyCode::SDLK_t => Some(T),
+    SDL_KeyCode::SDLK_u => Some(U),
+    SDL_KeyCode::SDLK_v => Some(V),
+    SDL_KeyCode::SDLK_w => Some(W),
+    SDL_KeyCode::SDLK_x => Some(X),
+    SDL_KeyCode::SDLK_y => Some(Y),
+    SDL_KeyCode::SDLK_z => Some(Z),
     _ => None,
   }
 }
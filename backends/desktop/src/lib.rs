@@ -3,10 +3,12 @@
 use std::ffi::{c_int, CString};
 
 use sdl2_sys::{
+  SDL_GLContextResetNotification::SDL_GL_CONTEXT_RESET_LOSE_CONTEXT,
   SDL_GLattr::{
     SDL_GL_CONTEXT_FLAGS, SDL_GL_CONTEXT_MAJOR_VERSION, SDL_GL_CONTEXT_MINOR_VERSION, SDL_GL_CONTEXT_PROFILE_MASK,
+    SDL_GL_CONTEXT_RESET_NOTIFICATION,
   },
-  SDL_GLcontextFlag::SDL_GL_CONTEXT_FORWARD_COMPATIBLE_FLAG,
+  SDL_GLcontextFlag::{SDL_GL_CONTEXT_FORWARD_COMPATIBLE_FLAG, SDL_GL_CONTEXT_ROBUST_ACCESS_FLAG},
   SDL_GLprofile::SDL_GL_CONTEXT_PROFILE_CORE,
 };
 
@@ -26,8 +28,12 @@ pub enum WindowError {
 pub struct Window {
   window: *mut sdl2_sys::SDL_Window,
   gl_context: sdl2_sys::SDL_GLContext,
+  vsync_enabled: bool,
   keyboard_device: input::SdlKeyboardDevice,
   mouse_device: input::SdlMouseDevice,
+  resource_tracker: Option<graphics::ResourceTracker>,
+  audio_thread: std::rc::Rc<std::cell::RefCell<audio::AudioThread>>,
+  audio_device: Option<audio::SdlMixerDevice>,
 }
 
 /// Settings for a window.
@@ -38,6 +44,7 @@ pub struct WindowSettings {
   pub vsync_enabled: bool,
   pub initial_color: common::Color,
   pub icon: Option<graphics::Image>,
+  pub audio_settings: audio::AudioThreadSettings,
 }
 
 impl Default for WindowSettings {
@@ -49,6 +56,7 @@ impl Default for WindowSettings {
       vsync_enabled: true,
       initial_color: common::Color::BLACK,
       icon: None,
+      audio_settings: audio::AudioThreadSettings::default(),
     }
   }
 }
@@ -83,33 +91,34 @@ impl Window {
         return Err(WindowError::FailedToCreateWindow);
       }
 
-      SDL_GL_SetAttribute(SDL_GL_CONTEXT_MAJOR_VERSION, 4);
-      SDL_GL_SetAttribute(SDL_GL_CONTEXT_MINOR_VERSION, 1);
-      SDL_GL_SetAttribute(SDL_GL_CONTEXT_FLAGS, SDL_GL_CONTEXT_FORWARD_COMPATIBLE_FLAG as c_int);
-      SDL_GL_SetAttribute(SDL_GL_CONTEXT_PROFILE_MASK, SDL_GL_CONTEXT_PROFILE_CORE as c_int);
-
       // create the OpenGL context
-      let gl_context = SDL_GL_CreateContext(window);
-      if gl_context.is_null() {
-        return Err(WindowError::FailedToCreateRenderer);
-      }
-
-      if settings.vsync_enabled {
-        // try adaptive vsync first
-        if SDL_GL_SetSwapInterval(-1) == -1 {
-          // if that fails, try normal vsync
-          SDL_GL_SetSwapInterval(1);
-        }
-      }
+      let gl_context = create_gl_context(window, settings.vsync_enabled)?;
 
-      SDL_GL_MakeCurrent(window, gl_context);
       SDL_GL_LoadLibrary(std::ptr::null());
 
+      // validate and track graphics resources in debug builds, to catch leaks
+      let resource_tracker = cfg!(debug_assertions).then(graphics::ResourceTracker::new);
+
+      // mix on a dedicated thread, and let the device pull from it directly
+      let audio_settings = settings.audio_settings;
+      let (audio_thread, audio_stream) = audio::AudioThread::spawn(audio_settings);
+      let audio_thread = std::rc::Rc::new(std::cell::RefCell::new(audio_thread));
+      let audio_device =
+        audio::SdlMixerDevice::open(audio_settings.sample_rate, audio_settings.block_frames, audio_stream);
+
+      if audio_device.is_none() {
+        common::warn!("Failed to open an audio device, the mix won't be heard");
+      }
+
       let window = Self {
         window,
         gl_context,
+        vsync_enabled: settings.vsync_enabled,
         keyboard_device: input::SdlKeyboardDevice::default(),
         mouse_device: input::SdlMouseDevice::default(),
+        resource_tracker: resource_tracker.clone(),
+        audio_thread: audio_thread.clone(),
+        audio_device,
       };
 
       // set the window icon
@@ -117,8 +126,17 @@ impl Window {
         window.set_window_icon(icon);
       }
 
-      audio::AudioServer::install(audio::SdlAudioBackend::new());
-      graphics::GraphicsServer::install(graphics::SdlGraphicsBackend::new());
+      audio::AudioServer::install(audio::ThreadedAudioBackend::new(audio_thread));
+
+      // `None` is shadowed by sdl2_sys here, so avoid matching on it
+      if let Some(tracker) = resource_tracker {
+        graphics::GraphicsServer::install(graphics::TrackingGraphicsBackend::new(
+          graphics::ValidatingBackend::new(graphics::SdlGraphicsBackend::new()),
+          tracker,
+        ));
+      } else {
+        graphics::GraphicsServer::install(graphics::SdlGraphicsBackend::new());
+      }
 
       graphics::graphics().clear_color_buffer(settings.initial_color);
       window.present();
@@ -169,6 +187,10 @@ impl Window {
       self.keyboard_device.clear_events();
       self.mouse_device.clear_events();
 
+      // run commands deferred from other threads
+      graphics::GraphicsServer::flush_commands();
+      audio::AudioServer::flush_commands();
+
       while SDL_PollEvent(&mut event) != 0 {
         if event.type_ == SDL_EventType::SDL_QUIT as u32 {
           running = false;
@@ -189,17 +211,60 @@ impl Window {
         if event.type_ == SDL_EventType::SDL_MOUSEBUTTONUP as u32 {
           self.mouse_device.on_mouse_up(event.button.button);
         }
+
+        if event.type_ == SDL_EventType::SDL_MOUSEMOTION as u32 {
+          let motion = event.motion;
+
+          self
+            .mouse_device
+            .on_mouse_move(motion.x, motion.y, motion.xrel, motion.yrel);
+        }
+
+        if event.type_ == SDL_EventType::SDL_MOUSEWHEEL as u32 {
+          self.mouse_device.on_mouse_wheel(event.wheel.y);
+        }
+      }
+
+      // the driver may have reset the context out from under us
+      if graphics::graphics().device_status() == graphics::DeviceStatus::Lost {
+        self.recover_graphics_device();
       }
 
       running
     }
   }
 
+  /// Replaces a lost OpenGL context and restores graphics resources into it.
+  fn recover_graphics_device(&mut self) {
+    use sdl2_sys::*;
+
+    common::warn!("The graphics device was lost, recreating it");
+
+    unsafe {
+      SDL_GL_DeleteContext(self.gl_context);
+
+      match create_gl_context(self.window, self.vsync_enabled) {
+        Ok(gl_context) => self.gl_context = gl_context,
+        Err(error) => panic!("Failed to recreate the graphics device: {error:?}"),
+      }
+    }
+
+    graphics::recover_device();
+  }
+
   /// Gets the keyboard device.
   pub fn keyboard(&self) -> &dyn input::KeyboardDevice {
     &self.keyboard_device
   }
 
+  /// Gets the audio thread, to play voices on the mix the device pulls from.
+  ///
+  /// The audio server plays through the same thread, and borrows it for each
+  /// call, so don't hold on to it across calls to the server.
+  pub fn audio_thread(&self) -> std::cell::RefMut<'_, audio::AudioThread> {
+    self.audio_thread.borrow_mut()
+  }
+
   /// Gets the mouse device.
   pub fn mouse(&self) -> &dyn input::MouseDevice {
     &self.mouse_device
@@ -236,11 +301,68 @@ impl common::Clipboard for Window {
   }
 }
 
+/// Creates an OpenGL context for the window and makes it current.
+///
+/// Asks for a robust context first, so the driver reports resets instead of
+/// leaving us with a dead context, and falls back to a regular one.
+unsafe fn create_gl_context(
+  window: *mut sdl2_sys::SDL_Window,
+  vsync_enabled: bool,
+) -> Result<sdl2_sys::SDL_GLContext, WindowError> {
+  use sdl2_sys::*;
+
+  unsafe {
+    let forward_compatible = SDL_GL_CONTEXT_FORWARD_COMPATIBLE_FLAG as c_int;
+    let robust_access = SDL_GL_CONTEXT_ROBUST_ACCESS_FLAG as c_int;
+
+    SDL_GL_SetAttribute(SDL_GL_CONTEXT_MAJOR_VERSION, 4);
+    SDL_GL_SetAttribute(SDL_GL_CONTEXT_MINOR_VERSION, 1);
+    SDL_GL_SetAttribute(SDL_GL_CONTEXT_PROFILE_MASK, SDL_GL_CONTEXT_PROFILE_CORE as c_int);
+    SDL_GL_SetAttribute(SDL_GL_CONTEXT_FLAGS, forward_compatible | robust_access);
+    SDL_GL_SetAttribute(
+      SDL_GL_CONTEXT_RESET_NOTIFICATION,
+      SDL_GL_CONTEXT_RESET_LOSE_CONTEXT as c_int,
+    );
+
+    let mut gl_context = SDL_GL_CreateContext(window);
+
+    if gl_context.is_null() {
+      SDL_GL_SetAttribute(SDL_GL_CONTEXT_FLAGS, forward_compatible);
+      SDL_GL_SetAttribute(SDL_GL_CONTEXT_RESET_NOTIFICATION, 0);
+
+      gl_context = SDL_GL_CreateContext(window);
+    }
+
+    if gl_context.is_null() {
+      return Err(WindowError::FailedToCreateRenderer);
+    }
+
+    if vsync_enabled {
+      // try adaptive vsync first
+      if SDL_GL_SetSwapInterval(-1) == -1 {
+        // if that fails, try normal vsync
+        SDL_GL_SetSwapInterval(1);
+      }
+    }
+
+    SDL_GL_MakeCurrent(window, gl_context);
+
+    Ok(gl_context)
+  }
+}
+
 impl Drop for Window {
   /// Destroys the window.
   fn drop(&mut self) {
     use sdl2_sys::*;
 
+    if let Some(tracker) = &self.resource_tracker {
+      tracker.report_leaks();
+    }
+
+    // the device has to close before SDL shuts down
+    self.audio_device.take();
+
     unsafe {
       SDL_GL_DeleteContext(self.gl_context);
       SDL_DestroyWindow(self.window);
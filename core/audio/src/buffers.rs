@@ -12,10 +12,22 @@ impl AudioBuffer {
       buffer_id: audio().buffer_create().unwrap(),
     }
   }
+
+  /// Returns the ID of this buffer.
+  pub fn id(&self) -> BufferId {
+    self.buffer_id
+  }
+
+  /// Replaces the buffer's contents with interleaved PCM data.
+  pub fn write_data(&self, sample_rate: AudioSampleRate, data: &[u8]) -> Result<(), BufferError> {
+    audio().buffer_write_data(self.buffer_id, sample_rate, data)
+  }
 }
 
 impl Drop for AudioBuffer {
   fn drop(&mut self) {
-    audio().buffer_delete(self.buffer_id).unwrap();
+    let id = self.buffer_id;
+
+    AudioServer::defer(move |audio| audio.buffer_delete(id).expect("Failed to delete buffer"));
   }
 }
@@ -0,0 +1,188 @@
+//! Lock-free channels between the game and audio threads.
+//!
+//! The audio thread can't wait on a lock held by a game thread that's in the
+//! middle of a hitch, so everything crossing between them goes through a
+//! bounded single-producer, single-consumer ring that never blocks either side.
+
+use std::{
+  cell::UnsafeCell,
+  mem::MaybeUninit,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  },
+};
+
+/// Creates a channel that holds up to `capacity` values.
+pub fn spsc_channel<T: Send>(capacity: usize) -> (SpscSender<T>, SpscReceiver<T>) {
+  let ring = Arc::new(Ring {
+    slots: (0..capacity.max(1))
+      .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+      .collect(),
+    head: AtomicUsize::new(0),
+    tail: AtomicUsize::new(0),
+  });
+
+  (SpscSender { ring: ring.clone() }, SpscReceiver { ring })
+}
+
+/// The ring shared by both ends of a channel.
+struct Ring<T> {
+  slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+  /// The number of values ever received.
+  head: AtomicUsize,
+  /// The number of values ever sent.
+  tail: AtomicUsize,
+}
+
+// only the sender writes a slot before publishing it, and only the receiver
+// reads it afterwards
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+  fn len(&self) -> usize {
+    self
+      .tail
+      .load(Ordering::Acquire)
+      .wrapping_sub(self.head.load(Ordering::Acquire))
+  }
+
+  #[inline]
+  fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+    self.slots[index % self.slots.len()].get()
+  }
+}
+
+impl<T> Drop for Ring<T> {
+  fn drop(&mut self) {
+    let head = *self.head.get_mut();
+    let tail = *self.tail.get_mut();
+
+    for index in head..tail {
+      unsafe { (*self.slot(index)).assume_init_drop() };
+    }
+  }
+}
+
+/// The sending end of a [`spsc_channel`].
+pub struct SpscSender<T> {
+  ring: Arc<Ring<T>>,
+}
+
+impl<T: Send> SpscSender<T> {
+  /// Sends a value, handing it back if the channel is full.
+  pub fn send(&mut self, value: T) -> Result<(), T> {
+    let ring = &self.ring;
+    let tail = ring.tail.load(Ordering::Relaxed);
+
+    if tail.wrapping_sub(ring.head.load(Ordering::Acquire)) == ring.slots.len() {
+      return Err(value);
+    }
+
+    unsafe { (*ring.slot(tail)).write(value) };
+
+    ring.tail.store(tail.wrapping_add(1), Ordering::Release);
+
+    Ok(())
+  }
+
+  /// The number of values waiting to be received.
+  pub fn len(&self) -> usize {
+    self.ring.len()
+  }
+
+  /// Determines if every value sent has been received.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+/// The receiving end of a [`spsc_channel`].
+pub struct SpscReceiver<T> {
+  ring: Arc<Ring<T>>,
+}
+
+impl<T: Send> SpscReceiver<T> {
+  /// Receives the oldest value, if there is one.
+  pub fn receive(&mut self) -> Option<T> {
+    let ring = &self.ring;
+    let head = ring.head.load(Ordering::Relaxed);
+
+    if head == ring.tail.load(Ordering::Acquire) {
+      return None;
+    }
+
+    let value = unsafe { (*ring.slot(head)).assume_init_read() };
+
+    ring.head.store(head.wrapping_add(1), Ordering::Release);
+
+    Some(value)
+  }
+
+  /// The number of values waiting to be received.
+  pub fn len(&self) -> usize {
+    self.ring.len()
+  }
+
+  /// Determines if there's nothing to receive.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_send_values_in_order_around_the_ring() {
+    let (mut sender, mut receiver) = spsc_channel(3);
+
+    for round in 0..4 {
+      assert!(sender.send(round * 10).is_ok());
+      assert!(sender.send(round * 10 + 1).is_ok());
+      assert_eq!(receiver.receive(), Some(round * 10));
+      assert_eq!(receiver.receive(), Some(round * 10 + 1));
+    }
+
+    assert_eq!(receiver.receive(), None);
+
+    sender.send(1).unwrap();
+    sender.send(2).unwrap();
+    sender.send(3).unwrap();
+
+    assert_eq!(sender.send(4), Err(4));
+    assert_eq!(receiver.len(), 3);
+  }
+
+  #[test]
+  fn it_should_hand_values_between_threads() {
+    let (mut sender, mut receiver) = spsc_channel(16);
+
+    let producer = std::thread::spawn(move || {
+      for value in 0..10_000 {
+        let mut value = Box::new(value);
+
+        while let Err(rejected) = sender.send(value) {
+          value = rejected;
+          std::thread::yield_now();
+        }
+      }
+    });
+
+    let mut expected = 0;
+
+    while expected < 10_000 {
+      match receiver.receive() {
+        Some(value) => {
+          assert_eq!(*value, expected);
+          expected += 1;
+        }
+        None => std::thread::yield_now(),
+      }
+    }
+
+    producer.join().unwrap();
+  }
+}
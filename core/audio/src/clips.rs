@@ -32,6 +32,11 @@ impl AudioClip {
     todo!()
   }
 
+  /// Plays the given buffer's samples from this clip.
+  pub fn set_buffer(&self, buffer: &AudioBuffer) -> Result<(), ClipError> {
+    audio().clip_set_buffer(self.clip_id, buffer.id())
+  }
+
   /// Returns the ID of this clip.
   pub fn id(&self) -> ClipId {
     self.clip_id
@@ -40,6 +45,8 @@ impl AudioClip {
 
 impl Drop for AudioClip {
   fn drop(&mut self) {
-    audio().clip_delete(self.clip_id).unwrap();
+    let id = self.clip_id;
+
+    AudioServer::defer(move |audio| audio.clip_delete(id).expect("Failed to delete clip"));
   }
 }
@@ -0,0 +1,214 @@
+//! Dynamics processing for mix buses.
+//!
+//! Software-mixing backends run each [`AudioBus`] over its block of
+//! interleaved samples before it's summed into the master, and the master bus
+//! last of all, so content from different sources sits at a similar level and
+//! never clips.
+
+use super::*;
+
+/// Processes blocks of interleaved samples in place.
+pub trait AudioEffect: Send {
+  fn process(&mut self, samples: &mut [f32], channels: usize);
+}
+
+/// A mix bus, with a chain of effects applied to everything routed through it.
+pub struct AudioBus {
+  pub name: String,
+  /// Linear gain applied after the effects.
+  pub gain: f32,
+  effects: Vec<Box<dyn AudioEffect>>,
+}
+
+impl AudioBus {
+  /// Creates a new bus with no effects.
+  pub fn new(name: impl Into<String>) -> Self {
+    Self {
+      name: name.into(),
+      gain: 1.,
+      effects: Vec::new(),
+    }
+  }
+
+  /// Creates the master bus, which limits its output to just under full scale.
+  pub fn master(sample_rate: AudioSampleRate) -> Self {
+    Self::new("master").with_effect(PeakLimiter::new(sample_rate, -1.))
+  }
+
+  /// Appends an effect to the end of the chain.
+  pub fn with_effect(mut self, effect: impl AudioEffect + 'static) -> Self {
+    self.add_effect(effect);
+    self
+  }
+
+  /// Appends an effect to the end of the chain.
+  pub fn add_effect(&mut self, effect: impl AudioEffect + 'static) {
+    self.effects.push(Box::new(effect));
+  }
+
+  /// Runs a block of interleaved samples through the bus.
+  pub fn process(&mut self, samples: &mut [f32], channels: usize) {
+    for effect in &mut self.effects {
+      effect.process(samples, channels);
+    }
+
+    if self.gain != 1. {
+      for sample in samples.iter_mut() {
+        *sample *= self.gain;
+      }
+    }
+  }
+}
+
+/// A limiter that keeps every sample under a ceiling.
+///
+/// Gain drops instantly to catch a peak, then recovers over the release time.
+/// All channels share one gain, so the stereo image doesn't shift.
+pub struct PeakLimiter {
+  ceiling: f32,
+  release: f32,
+  gain: f32,
+}
+
+impl PeakLimiter {
+  /// Creates a limiter with the given ceiling, in dBFS.
+  pub fn new(sample_rate: AudioSampleRate, ceiling_db: f32) -> Self {
+    Self {
+      ceiling: db_to_gain(ceiling_db),
+      release: smoothing_coefficient(sample_rate, 0.1),
+      gain: 1.,
+    }
+  }
+}
+
+impl AudioEffect for PeakLimiter {
+  fn process(&mut self, samples: &mut [f32], channels: usize) {
+    for frame in samples.chunks_mut(channels.max(1)) {
+      let peak = frame.iter().fold(0f32, |peak, sample| peak.max(sample.abs()));
+
+      self.gain = 1. - (1. - self.gain) * self.release;
+
+      if peak * self.gain > self.ceiling {
+        self.gain = self.ceiling / peak;
+      }
+
+      for sample in frame {
+        *sample *= self.gain;
+      }
+    }
+  }
+}
+
+/// A feed-forward compressor, for evening out the level of a bus.
+pub struct Compressor {
+  /// The level above which gain is reduced, in dBFS.
+  pub threshold_db: f32,
+  /// How many decibels over the threshold in give one decibel out.
+  pub ratio: f32,
+  /// Gain applied after compression, in decibels.
+  pub makeup_db: f32,
+  attack: f32,
+  release: f32,
+  envelope_db: f32,
+}
+
+impl Compressor {
+  /// Creates a compressor with the given threshold and ratio, and typical
+  /// attack (10ms) and release (100ms) times.
+  pub fn new(sample_rate: AudioSampleRate, threshold_db: f32, ratio: f32) -> Self {
+    Self {
+      threshold_db,
+      ratio: ratio.max(1.),
+      makeup_db: 0.,
+      attack: smoothing_coefficient(sample_rate, 0.01),
+      release: smoothing_coefficient(sample_rate, 0.1),
+      envelope_db: SILENCE_DB,
+    }
+  }
+
+  /// Sets the gain applied after compression, in decibels.
+  pub fn with_makeup(mut self, makeup_db: f32) -> Self {
+    self.makeup_db = makeup_db;
+    self
+  }
+}
+
+impl AudioEffect for Compressor {
+  fn process(&mut self, samples: &mut [f32], channels: usize) {
+    for frame in samples.chunks_mut(channels.max(1)) {
+      let peak = frame.iter().fold(0f32, |peak, sample| peak.max(sample.abs()));
+      let level_db = gain_to_db(peak);
+
+      let coefficient = match level_db > self.envelope_db {
+        true => self.attack,
+        false => self.release,
+      };
+
+      self.envelope_db = level_db + (self.envelope_db - level_db) * coefficient;
+
+      let overshoot = (self.envelope_db - self.threshold_db).max(0.);
+      let reduction_db = overshoot - overshoot / self.ratio;
+      let gain = db_to_gain(self.makeup_db - reduction_db);
+
+      for sample in frame {
+        *sample *= gain;
+      }
+    }
+  }
+}
+
+/// The level treated as silence, in dBFS.
+const SILENCE_DB: f32 = -120.;
+
+/// Converts decibels to a linear gain.
+pub fn db_to_gain(db: f32) -> f32 {
+  10f32.powf(db / 20.)
+}
+
+/// Converts a linear gain to decibels.
+pub fn gain_to_db(gain: f32) -> f32 {
+  match gain > 0. {
+    true => (20. * gain.log10()).max(SILENCE_DB),
+    false => SILENCE_DB,
+  }
+}
+
+/// The per-sample coefficient of a one-pole smoother with the given time
+/// constant, in seconds.
+pub(crate) fn smoothing_coefficient(sample_rate: AudioSampleRate, seconds: f32) -> f32 {
+  (-1. / (seconds * sample_rate.frequency as f32)).exp()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_keep_peaks_under_the_ceiling() {
+    let mut bus = AudioBus::master(AudioSampleRate::STANDARD);
+    let mut samples = (0..4_400)
+      .map(|index| (index as f32 * 0.05).sin() * 2.)
+      .collect::<Vec<_>>();
+
+    bus.process(&mut samples, 2);
+
+    let ceiling = db_to_gain(-1.);
+
+    assert!(samples.iter().all(|sample| sample.abs() <= ceiling + f32::EPSILON));
+    assert!(samples.iter().any(|sample| sample.abs() > ceiling * 0.9));
+  }
+
+  #[test]
+  fn it_should_compress_loud_signals() {
+    let mut compressor = Compressor::new(AudioSampleRate::STANDARD, -20., 4.);
+    let mut loud = vec![0.5; 8_800];
+    let mut quiet = vec![0.01; 8_800];
+
+    compressor.process(&mut loud, 2);
+    compressor.process(&mut quiet, 2);
+
+    // -6dB is 14dB over the threshold, which comes out 3.5dB over
+    assert!((gain_to_db(loud[8_799]) - -16.5).abs() < 0.1);
+    assert!((quiet[8_799] - 0.01).abs() < 0.0001);
+  }
+}
@@ -1,4 +1,7 @@
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{
+  atomic::{AtomicU64, Ordering},
+  Mutex,
+};
 
 use super::*;
 
@@ -10,6 +13,7 @@ pub struct HeadlessAudioBackend {
   next_buffer_id: AtomicU64,
   next_clip_id: AtomicU64,
   next_source_id: AtomicU64,
+  master_tap: Mutex<Option<AudioTap>>,
 }
 
 #[allow(unused_variables)]
@@ -30,6 +34,10 @@ impl AudioBackend for HeadlessAudioBackend {
     Ok(ClipId(self.next_clip_id.fetch_add(1, Ordering::Relaxed)))
   }
 
+  fn clip_set_buffer(&self, clip: ClipId, buffer: BufferId) -> Result<(), ClipError> {
+    Ok(())
+  }
+
   fn clip_delete(&self, clip: ClipId) -> Result<(), ClipError> {
     Ok(())
   }
@@ -97,4 +105,11 @@ impl AudioBackend for HeadlessAudioBackend {
   fn source_delete(&self, source: SourceId) -> Result<(), SourceError> {
     Ok(())
   }
+
+  fn master_set_tap(&self, tap: Option<AudioTap>) -> Result<(), RecordingError> {
+    // nothing is mixed, so the tap never receives any samples
+    *self.master_tap.lock().unwrap() = tap;
+
+    Ok(())
+  }
 }
@@ -3,15 +3,30 @@
 #![allow(clippy::new_without_default)]
 
 pub use buffers::*;
+pub use channels::*;
 pub use clips::*;
+pub use dynamics::*;
+pub use loudness::*;
+pub use mixing::*;
+pub use recording::*;
+pub use reverb::*;
 pub use sampling::*;
 pub use sources::*;
+pub use threaded::*;
 
 mod buffers;
+mod channels;
 mod clips;
+mod dynamics;
 mod headless;
+mod loudness;
+mod mixing;
+mod recording;
+mod reverb;
 mod sampling;
 mod sources;
+mod threaded;
+mod wav;
 
 use common::Vec3;
 
@@ -19,7 +34,7 @@ common::impl_arena_index!(pub BufferId, "Identifies an Audio Buffer.");
 common::impl_arena_index!(pub ClipId, "Identifies an Audio Clip.");
 common::impl_arena_index!(pub SourceId, "Identifies an Audio Source.");
 
-common::impl_server!(AudioServer by AudioBackend default headless::HeadlessAudioBackend);
+common::impl_server!(AudioServer by AudioBackend default headless::HeadlessAudioBackend, thread bound);
 
 /// Gets the audio server instance.
 #[inline(always)]
@@ -34,6 +49,7 @@ pub enum AudioError {
   BufferError(BufferError),
   ClipError(ClipError),
   SourceError(SourceError),
+  RecordingError(RecordingError),
 }
 
 /// A possible error when interacting with buffers.
@@ -61,6 +77,7 @@ pub enum SourceError {
 common::impl_error_coercion!(BufferError into AudioError);
 common::impl_error_coercion!(ClipError into AudioError);
 common::impl_error_coercion!(SourceError into AudioError);
+common::impl_error_coercion!(RecordingError into AudioError);
 
 /// Represents a backend implementation for the underlying audio API.
 ///
@@ -76,6 +93,7 @@ pub trait AudioBackend {
 
   // clips
   fn clip_create(&self) -> Result<ClipId, ClipError>;
+  fn clip_set_buffer(&self, clip: ClipId, buffer: BufferId) -> Result<(), ClipError>;
   fn clip_delete(&self, clip: ClipId) -> Result<(), ClipError>;
 
   // sources
@@ -95,4 +113,7 @@ pub trait AudioBackend {
   fn source_set_clip(&self, source: SourceId, clip: ClipId) -> Result<(), SourceError>;
   fn source_play(&self, source: SourceId) -> Result<(), SourceError>;
   fn source_delete(&self, source: SourceId) -> Result<(), SourceError>;
+
+  // recording
+  fn master_set_tap(&self, tap: Option<AudioTap>) -> Result<(), RecordingError>;
 }
@@ -0,0 +1,269 @@
+//! Loudness measurement and normalization.
+//!
+//! Loudness is measured in LUFS, following ITU-R BS.1770: samples are
+//! K-weighted to approximate how loud they sound, then averaged over gated
+//! 400ms blocks so silence doesn't drag the result down. Clips normalized to
+//! the same target sound equally loud, whatever their peaks.
+//!
+//! A [`LoudnessImporter`] normalizes WAV clips when they're imported, so the
+//! work is done once, offline.
+
+use common::{AssetError, AssetImporter, ImportedAsset, VirtualPath};
+
+use super::*;
+
+/// A common loudness target for game audio, in LUFS.
+pub const DEFAULT_LOUDNESS_TARGET: f32 = -23.;
+
+/// Measures the integrated loudness of interleaved samples, in LUFS.
+///
+/// Returns `None` if the samples are silent.
+pub fn measure_loudness(samples: &[f32], sample_rate: AudioSampleRate) -> Option<f32> {
+  let channels = sample_rate.channels.max(1) as usize;
+  let frequency = sample_rate.frequency as f32;
+
+  // mean square of the K-weighted signal, summed over channels, per 100ms step
+  let step = ((frequency * 0.1) as usize).max(1);
+  let frames = samples.len() / channels;
+  let mut steps = vec![0f64; frames.div_ceil(step)];
+
+  for channel in 0..channels {
+    let mut shelf = Biquad::k_shelf(frequency);
+    let mut high_pass = Biquad::k_high_pass(frequency);
+
+    for frame in 0..frames {
+      let sample = high_pass.process(shelf.process(samples[frame * channels + channel]));
+
+      steps[frame / step] += (sample * sample) as f64;
+    }
+  }
+
+  // 400ms blocks overlapping by 75%; a short clip is one block
+  let blocks = match steps.len() {
+    0 => return None,
+    1..=3 => vec![steps.iter().sum::<f64>() / frames as f64],
+    _ => steps
+      .windows(4)
+      .map(|window| window.iter().sum::<f64>() / (step * 4) as f64)
+      .collect::<Vec<_>>(),
+  };
+
+  let loudness = |power: f64| -0.691 + 10. * power.log10();
+  let gated_mean = |threshold: f64| {
+    let gated = blocks
+      .iter()
+      .filter(|power| loudness(**power) > threshold)
+      .collect::<Vec<_>>();
+
+    match gated.is_empty() {
+      true => None,
+      false => Some(gated.iter().copied().sum::<f64>() / gated.len() as f64),
+    }
+  };
+
+  let absolute = gated_mean(-70.)?;
+  let relative = gated_mean(loudness(absolute) - 10.)?;
+
+  Some(loudness(relative) as f32)
+}
+
+/// Scales interleaved samples to the target loudness, in LUFS.
+///
+/// The gain is capped so peaks stay under -1 dBFS; quiet clips with loud
+/// transients may land below the target. Returns the gain applied, in
+/// decibels.
+pub fn normalize_loudness(samples: &mut [f32], sample_rate: AudioSampleRate, target: f32) -> f32 {
+  let Some(loudness) = measure_loudness(samples, sample_rate) else {
+    return 0.;
+  };
+
+  let peak = samples.iter().fold(0f32, |peak, sample| peak.max(sample.abs()));
+  let gain_db = (target - loudness).min(-1. - gain_to_db(peak));
+  let gain = db_to_gain(gain_db);
+
+  for sample in samples.iter_mut() {
+    *sample *= gain;
+  }
+
+  gain_db
+}
+
+/// Normalizes the loudness of WAV clips as they're imported.
+pub struct LoudnessImporter {
+  /// The loudness to normalize to, in LUFS.
+  pub target: f32,
+}
+
+impl Default for LoudnessImporter {
+  fn default() -> Self {
+    Self {
+      target: DEFAULT_LOUDNESS_TARGET,
+    }
+  }
+}
+
+impl AssetImporter for LoudnessImporter {
+  fn name(&self) -> &str {
+    "loudness"
+  }
+
+  fn extensions(&self) -> &[&str] {
+    &["wav"]
+  }
+
+  fn import(&self, path: &VirtualPath, data: &[u8]) -> Result<ImportedAsset, AssetError> {
+    let failed = |error| AssetError::ImportFailed(format!("{path:?}: {error}"));
+    let (sample_rate, mut samples) = wav::read_wav(data).map_err(failed)?;
+
+    normalize_loudness(&mut samples, sample_rate, self.target);
+
+    let mut stream = std::io::Cursor::new(Vec::new());
+    let samples = samples.iter().map(|sample| wav::to_pcm16(*sample));
+
+    wav::write_wav(
+      &mut stream,
+      sample_rate.frequency as u32,
+      sample_rate.channels as u16,
+      samples,
+    )
+    .map_err(failed)?;
+
+    Ok(ImportedAsset {
+      data: stream.into_inner(),
+      references: Vec::new(),
+    })
+  }
+}
+
+/// A second-order IIR filter.
+struct Biquad {
+  b: [f32; 3],
+  a: [f32; 2],
+  state: [f32; 2],
+}
+
+impl Biquad {
+  /// The first stage of K-weighting, modelling the acoustic effect of the
+  /// head; these reproduce the coefficients in BS.1770 at 48kHz.
+  fn k_shelf(frequency: f32) -> Self {
+    const GAIN_DB: f64 = 3.999843853973347;
+    const Q: f64 = 0.7071752369554196;
+    const CUTOFF: f64 = 1681.974450955533;
+
+    let k = (std::f64::consts::PI * CUTOFF / frequency as f64).tan();
+    let high_gain = 10f64.powf(GAIN_DB / 20.);
+    let band_gain = high_gain.powf(0.4996667741545416);
+
+    Self::normalized(
+      [
+        high_gain + band_gain * k / Q + k * k,
+        2. * (k * k - high_gain),
+        high_gain - band_gain * k / Q + k * k,
+      ],
+      [1. + k / Q + k * k, 2. * (k * k - 1.), 1. - k / Q + k * k],
+    )
+  }
+
+  /// The second stage of K-weighting, a high-pass filter.
+  fn k_high_pass(frequency: f32) -> Self {
+    const Q: f64 = 0.5003270373238773;
+    const CUTOFF: f64 = 38.13547087602444;
+
+    let k = (std::f64::consts::PI * CUTOFF / frequency as f64).tan();
+
+    Self {
+      b: [1., -2., 1.],
+      ..Self::normalized([0.; 3], [1. + k / Q + k * k, 2. * (k * k - 1.), 1. - k / Q + k * k])
+    }
+  }
+
+  fn normalized(b: [f64; 3], a: [f64; 3]) -> Self {
+    Self {
+      b: b.map(|b| (b / a[0]) as f32),
+      a: [(a[1] / a[0]) as f32, (a[2] / a[0]) as f32],
+      state: [0.; 2],
+    }
+  }
+
+  /// Filters one sample (transposed direct form II).
+  fn process(&mut self, input: f32) -> f32 {
+    let output = self.b[0] * input + self.state[0];
+
+    self.state[0] = self.b[1] * input - self.a[0] * output + self.state[1];
+    self.state[1] = self.b[2] * input - self.a[1] * output;
+
+    output
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const MONO: AudioSampleRate = AudioSampleRate {
+    frequency: 48_000,
+    channels: 1,
+    bits_per_sample: 16,
+  };
+
+  fn sine(amplitude: f32, seconds: f32) -> Vec<f32> {
+    (0..(48_000. * seconds) as usize)
+      .map(|index| (std::f32::consts::TAU * 997. * index as f32 / 48_000.).sin() * amplitude)
+      .collect()
+  }
+
+  #[test]
+  fn it_should_measure_loudness() {
+    // a full-scale sine on one channel is -3.01 LUFS
+    let loudness = measure_loudness(&sine(1., 2.), MONO).unwrap();
+
+    assert!((loudness - -3.01).abs() < 0.1, "{loudness}");
+    assert_eq!(measure_loudness(&[0.; 4_800], MONO), None);
+  }
+
+  #[test]
+  fn it_should_normalize_clips_to_the_target() {
+    let mut samples = sine(0.01, 1.);
+
+    normalize_loudness(&mut samples, MONO, -23.);
+
+    let loudness = measure_loudness(&samples, MONO).unwrap();
+
+    assert!((loudness - -23.).abs() < 0.1, "{loudness}");
+  }
+
+  #[test]
+  fn it_should_not_push_peaks_over_full_scale() {
+    let mut samples = sine(0.01, 1.);
+
+    samples[100] = 1.;
+
+    let gain_db = normalize_loudness(&mut samples, MONO, -3.);
+
+    assert!((gain_db - -1.).abs() < 0.001);
+    assert!(samples
+      .iter()
+      .all(|sample| sample.abs() <= db_to_gain(-1.) + f32::EPSILON));
+  }
+
+  #[test]
+  fn it_should_normalize_wav_clips_on_import() {
+    let mut stream = std::io::Cursor::new(Vec::new());
+    let samples = sine(0.05, 1.);
+
+    wav::write_wav(
+      &mut stream,
+      48_000,
+      1,
+      samples.iter().map(|sample| wav::to_pcm16(*sample)),
+    )
+    .unwrap();
+
+    let path = VirtualPath::new("local://hit.wav");
+    let imported = LoudnessImporter::default().import(&path, &stream.into_inner()).unwrap();
+    let (sample_rate, samples) = wav::read_wav(&imported.data).unwrap();
+    let loudness = measure_loudness(&samples, sample_rate).unwrap();
+
+    assert!((loudness - DEFAULT_LOUDNESS_TARGET).abs() < 0.1, "{loudness}");
+  }
+}
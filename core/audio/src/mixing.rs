@@ -0,0 +1,679 @@
+//! Software mixing on a dedicated audio thread.
+//!
+//! The game thread never touches the mix directly. It sends [`AudioCommand`]s
+//! (play, stop, parameter changes) through a lock-free queue, and the audio
+//! thread applies them between blocks. The thread keeps a few blocks mixed
+//! ahead of the device, which pulls them through an [`AudioStream`], so a
+//! hitch on the game thread only delays commands and never starves the
+//! output:
+//!
+//! ```rust,ignore
+//! let (mut audio, mut stream) = AudioThread::spawn(AudioThreadSettings::default());
+//!
+//! // in the device callback
+//! stream.read(output);
+//!
+//! // on the game thread
+//! let voice = audio.play(&footstep, VoiceSettings::default());
+//! audio.set_gain(voice, 0.5);
+//! ```
+
+use std::{
+  sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+  },
+  thread::JoinHandle,
+  time::Duration,
+};
+
+use super::*;
+
+common::impl_arena_index!(pub VoiceId, "Identifies a voice playing on the audio thread.");
+
+/// The lowest pitch a voice plays at; lower pitches would never advance.
+pub(crate) const MIN_PITCH: f32 = 0.01;
+
+/// Decoded samples that voices play from.
+#[derive(Clone, Debug)]
+pub struct AudioSamples {
+  pub frequency: u32,
+  pub channels: usize,
+  /// Interleaved samples, in `[-1, 1]`.
+  pub samples: Vec<f32>,
+}
+
+impl AudioSamples {
+  /// The number of frames (one sample per channel).
+  pub fn frames(&self) -> usize {
+    self.samples.len() / self.channels.max(1)
+  }
+
+  /// The sample for the given frame and output channel, mapping mono to
+  /// every channel.
+  #[inline]
+  fn sample(&self, frame: usize, channel: usize) -> f32 {
+    let channels = self.channels.max(1);
+
+    self.samples[frame * channels + channel % channels]
+  }
+}
+
+/// How a voice plays.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VoiceSettings {
+  pub gain: f32,
+  pub pitch: f32,
+  pub looping: bool,
+}
+
+impl Default for VoiceSettings {
+  fn default() -> Self {
+    Self {
+      gain: 1.,
+      pitch: 1.,
+      looping: false,
+    }
+  }
+}
+
+/// A change to the mix, sent from the game thread.
+#[derive(Clone, Debug)]
+pub enum AudioCommand {
+  Play {
+    voice: VoiceId,
+    samples: Arc<AudioSamples>,
+    settings: VoiceSettings,
+  },
+  Stop {
+    voice: VoiceId,
+  },
+  SetGain {
+    voice: VoiceId,
+    gain: f32,
+  },
+  SetPitch {
+    voice: VoiceId,
+    pitch: f32,
+  },
+  SetLooping {
+    voice: VoiceId,
+    looping: bool,
+  },
+  SetMasterGain {
+    gain: f32,
+  },
+  SetReverb {
+    preset: ReverbPreset,
+  },
+  StopAll,
+}
+
+/// A voice being mixed.
+struct Voice {
+  id: VoiceId,
+  samples: Arc<AudioSamples>,
+  settings: VoiceSettings,
+  /// The position in source frames, between samples when resampling.
+  position: f64,
+}
+
+/// Mixes voices into blocks of interleaved samples.
+///
+/// Voice storage is allocated up front, so mixing never allocates; voices
+/// played beyond [`AudioMixer::with_max_voices`] are refused, and reported as
+/// finished straight away.
+pub struct AudioMixer {
+  sample_rate: AudioSampleRate,
+  voices: Vec<Voice>,
+  reverb: Reverb,
+  master: AudioBus,
+  finished: Vec<VoiceId>,
+}
+
+impl AudioMixer {
+  /// Creates a mixer with the standard master bus.
+  pub fn new(sample_rate: AudioSampleRate) -> Self {
+    Self {
+      sample_rate,
+      voices: Vec::new(),
+      reverb: Reverb::new(sample_rate, ReverbPreset::NONE),
+      master: AudioBus::master(sample_rate),
+      finished: Vec::new(),
+    }
+    .with_max_voices(AudioThreadSettings::default().max_voices)
+  }
+
+  /// Sets the most voices that can play at once.
+  pub fn with_max_voices(mut self, max_voices: usize) -> Self {
+    self.voices = Vec::with_capacity(max_voices);
+    self.finished = Vec::with_capacity(max_voices);
+    self
+  }
+
+  /// The number of voices playing.
+  pub fn voices(&self) -> usize {
+    self.voices.len()
+  }
+
+  /// The master bus, which every voice is mixed through.
+  pub fn master_mut(&mut self) -> &mut AudioBus {
+    &mut self.master
+  }
+
+  /// The reverb every voice is mixed through, ahead of the master bus.
+  pub fn reverb_mut(&mut self) -> &mut Reverb {
+    &mut self.reverb
+  }
+
+  /// Takes the voices that finished playing since the last call.
+  pub fn drain_finished(&mut self) -> std::vec::Drain<'_, VoiceId> {
+    self.finished.drain(..)
+  }
+
+  /// Applies a command to the mix.
+  pub fn apply(&mut self, command: AudioCommand) {
+    match command {
+      AudioCommand::Play {
+        voice,
+        samples,
+        mut settings,
+      } => {
+        self.voices.retain(|it| it.id != voice);
+
+        if self.voices.len() == self.voices.capacity() {
+          finish(&mut self.finished, voice);
+          return;
+        }
+
+        settings.pitch = settings.pitch.max(MIN_PITCH);
+
+        self.voices.push(Voice {
+          id: voice,
+          samples,
+          settings,
+          position: 0.,
+        });
+      }
+      AudioCommand::Stop { voice } => self.voices.retain(|it| it.id != voice),
+      AudioCommand::SetGain { voice: id, gain } => {
+        if let Some(voice) = self.voice_mut(id) {
+          voice.settings.gain = gain;
+        }
+      }
+      AudioCommand::SetPitch { voice: id, pitch } => {
+        if let Some(voice) = self.voice_mut(id) {
+          voice.settings.pitch = pitch.max(MIN_PITCH);
+        }
+      }
+      AudioCommand::SetLooping { voice: id, looping } => {
+        if let Some(voice) = self.voice_mut(id) {
+          voice.settings.looping = looping;
+        }
+      }
+      AudioCommand::SetMasterGain { gain } => self.master.gain = gain,
+      AudioCommand::SetReverb { preset } => self.reverb.set_preset(preset),
+      AudioCommand::StopAll => self.voices.clear(),
+    }
+  }
+
+  fn voice_mut(&mut self, id: VoiceId) -> Option<&mut Voice> {
+    self.voices.iter_mut().find(|voice| voice.id == id)
+  }
+
+  /// Mixes the next block of interleaved samples.
+  pub fn render(&mut self, output: &mut [f32]) {
+    let channels = self.sample_rate.channels.max(1) as usize;
+    let frames = output.len() / channels;
+
+    output.fill(0.);
+
+    for voice in &mut self.voices {
+      let length = voice.samples.frames();
+
+      if length == 0 {
+        finish(&mut self.finished, voice.id);
+        continue;
+      }
+
+      let step = voice.samples.frequency as f64 / self.sample_rate.frequency as f64 * voice.settings.pitch as f64;
+
+      for frame in 0..frames {
+        if voice.position >= length as f64 {
+          if !voice.settings.looping {
+            finish(&mut self.finished, voice.id);
+            break;
+          }
+
+          voice.position %= length as f64;
+        }
+
+        // linear interpolation between neighbouring frames
+        let index = voice.position as usize;
+        let next = match voice.settings.looping {
+          true => (index + 1) % length,
+          false => (index + 1).min(length - 1),
+        };
+        let blend = (voice.position - index as f64) as f32;
+
+        for channel in 0..channels {
+          let a = voice.samples.sample(index, channel);
+          let b = voice.samples.sample(next, channel);
+
+          output[frame * channels + channel] += (a + (b - a) * blend) * voice.settings.gain;
+        }
+
+        voice.position += step;
+      }
+    }
+
+    let finished = &self.finished;
+
+    self.voices.retain(|voice| !finished.contains(&voice.id));
+    self.reverb.process(output, channels);
+    self.master.process(output, channels);
+  }
+}
+
+/// Reports a voice as finished, without growing past the preallocated list.
+#[inline]
+fn finish(finished: &mut Vec<VoiceId>, voice: VoiceId) {
+  if finished.len() < finished.capacity() {
+    finished.push(voice);
+  }
+}
+
+/// Settings for the audio thread.
+#[derive(Copy, Clone, Debug)]
+pub struct AudioThreadSettings {
+  pub sample_rate: AudioSampleRate,
+  /// The number of frames in each mixed block.
+  pub block_frames: usize,
+  /// The number of blocks mixed ahead of the device. More blocks ride out
+  /// longer stalls of the audio thread, at the cost of latency.
+  pub buffered_blocks: usize,
+  /// The most commands that can wait for the audio thread at once.
+  pub command_capacity: usize,
+  /// The most voices that can play at once.
+  pub max_voices: usize,
+}
+
+impl Default for AudioThreadSettings {
+  fn default() -> Self {
+    Self {
+      sample_rate: AudioSampleRate::STANDARD,
+      block_frames: 256,
+      buffered_blocks: 4,
+      command_capacity: 1024,
+      max_voices: 64,
+    }
+  }
+}
+
+/// Counters shared with the audio thread.
+#[derive(Default)]
+struct AudioThreadCounters {
+  underruns: AtomicUsize,
+  voices: AtomicUsize,
+}
+
+/// The game thread's handle to the audio thread.
+///
+/// Dropping the handle stops the thread.
+pub struct AudioThread {
+  commands: SpscSender<AudioCommand>,
+  finished: SpscReceiver<VoiceId>,
+  counters: Arc<AudioThreadCounters>,
+  is_running: Arc<AtomicBool>,
+  handle: Option<JoinHandle<()>>,
+  next_voice: u32,
+  dropped_commands: usize,
+}
+
+impl AudioThread {
+  /// Starts the audio thread, returning the handle for the game thread and
+  /// the stream for the device to pull mixed samples from.
+  pub fn spawn(settings: AudioThreadSettings) -> (Self, AudioStream) {
+    let (commands, mut command_receiver) = spsc_channel(settings.command_capacity);
+    let (mut finished_sender, finished) = spsc_channel(settings.command_capacity);
+    let (mut blocks, block_receiver) = spsc_channel(settings.buffered_blocks);
+    let (recycled_sender, mut recycled) = spsc_channel(settings.buffered_blocks + 1);
+
+    let counters = Arc::new(AudioThreadCounters::default());
+    let is_running = Arc::new(AtomicBool::new(true));
+
+    let thread_counters = counters.clone();
+    let thread_is_running = is_running.clone();
+
+    let channels = settings.sample_rate.channels.max(1) as usize;
+    let block_length = settings.block_frames * channels;
+    let block_duration = settings.block_frames as f64 / settings.sample_rate.frequency as f64;
+
+    let handle = std::thread::Builder::new()
+      .name("audio".to_string())
+      .spawn(move || {
+        let mut mixer = AudioMixer::new(settings.sample_rate).with_max_voices(settings.max_voices);
+
+        while thread_is_running.load(Ordering::Acquire) {
+          // top up the blocks ahead of the device
+          while blocks.len() < settings.buffered_blocks {
+            while let Some(command) = command_receiver.receive() {
+              mixer.apply(command);
+
+              for voice in mixer.drain_finished() {
+                let _ = finished_sender.send(voice);
+              }
+            }
+
+            let mut block = recycled
+              .receive()
+              .unwrap_or_else(|| vec![0.; block_length].into_boxed_slice());
+
+            mixer.render(&mut block);
+
+            for voice in mixer.drain_finished() {
+              let _ = finished_sender.send(voice);
+            }
+
+            thread_counters.voices.store(mixer.voices(), Ordering::Relaxed);
+
+            if blocks.send(block).is_err() {
+              break;
+            }
+          }
+
+          std::thread::sleep(Duration::from_secs_f64(block_duration / 2.));
+        }
+      })
+      .expect("Failed to spawn the audio thread");
+
+    let stream = AudioStream {
+      blocks: block_receiver,
+      recycled: recycled_sender,
+      current: None,
+      counters: counters.clone(),
+    };
+
+    let thread = Self {
+      commands,
+      finished,
+      counters,
+      is_running,
+      handle: Some(handle),
+      next_voice: 0,
+      dropped_commands: 0,
+    };
+
+    (thread, stream)
+  }
+
+  /// Sends a command to the audio thread.
+  ///
+  /// Returns false if the queue is full and the command was dropped.
+  pub fn send(&mut self, command: AudioCommand) -> bool {
+    match self.commands.send(command) {
+      Ok(()) => true,
+      Err(_) => {
+        self.dropped_commands += 1;
+        false
+      }
+    }
+  }
+
+  /// Starts playing the samples on a new voice.
+  pub fn play(&mut self, samples: &Arc<AudioSamples>, settings: VoiceSettings) -> VoiceId {
+    let voice = VoiceId::from(self.next_voice);
+
+    self.next_voice += 1;
+    self.send(AudioCommand::Play {
+      voice,
+      samples: samples.clone(),
+      settings,
+    });
+
+    voice
+  }
+
+  /// Stops a voice.
+  pub fn stop(&mut self, voice: VoiceId) {
+    self.send(AudioCommand::Stop { voice });
+  }
+
+  /// Changes the gain of a voice.
+  pub fn set_gain(&mut self, voice: VoiceId, gain: f32) {
+    self.send(AudioCommand::SetGain { voice, gain });
+  }
+
+  /// Changes the pitch of a voice.
+  pub fn set_pitch(&mut self, voice: VoiceId, pitch: f32) {
+    self.send(AudioCommand::SetPitch {
+      voice,
+      pitch: pitch.max(MIN_PITCH),
+    });
+  }
+
+  /// Changes whether a voice loops.
+  pub fn set_looping(&mut self, voice: VoiceId, looping: bool) {
+    self.send(AudioCommand::SetLooping { voice, looping });
+  }
+
+  /// Changes the gain of the whole mix.
+  pub fn set_master_gain(&mut self, gain: f32) {
+    self.send(AudioCommand::SetMasterGain { gain });
+  }
+
+  /// Changes the reverb of the whole mix; see [`ReverbZones`].
+  pub fn set_reverb(&mut self, preset: ReverbPreset) {
+    self.send(AudioCommand::SetReverb { preset });
+  }
+
+  /// Takes the voices that finished playing on their own.
+  pub fn take_finished(&mut self) -> Vec<VoiceId> {
+    std::iter::from_fn(|| self.finished.receive()).collect()
+  }
+
+  /// The number of voices playing, as of the last mixed block.
+  pub fn voices(&self) -> usize {
+    self.counters.voices.load(Ordering::Relaxed)
+  }
+
+  /// The number of times the device asked for samples that weren't mixed yet.
+  pub fn underruns(&self) -> usize {
+    self.counters.underruns.load(Ordering::Relaxed)
+  }
+
+  /// The number of commands dropped because the queue was full.
+  pub fn dropped_commands(&self) -> usize {
+    self.dropped_commands
+  }
+}
+
+impl Drop for AudioThread {
+  fn drop(&mut self) {
+    self.is_running.store(false, Ordering::Release);
+
+    if let Some(handle) = self.handle.take() {
+      let _ = handle.join();
+    }
+  }
+}
+
+/// The device's end of the audio thread, pulling mixed samples.
+pub struct AudioStream {
+  blocks: SpscReceiver<Box<[f32]>>,
+  recycled: SpscSender<Box<[f32]>>,
+  current: Option<(Box<[f32]>, usize)>,
+  counters: Arc<AudioThreadCounters>,
+}
+
+impl AudioStream {
+  /// Fills the output with mixed interleaved samples.
+  ///
+  /// If the audio thread has fallen behind, the rest is filled with silence
+  /// and counted as an underrun.
+  pub fn read(&mut self, output: &mut [f32]) {
+    let mut written = 0;
+
+    while written < output.len() {
+      let (block, offset) = match &mut self.current {
+        Some(current) => current,
+        None => match self.blocks.receive() {
+          Some(block) => self.current.insert((block, 0)),
+          None => {
+            output[written..].fill(0.);
+            self.counters.underruns.fetch_add(1, Ordering::Relaxed);
+            return;
+          }
+        },
+      };
+
+      let count = (block.len() - *offset).min(output.len() - written);
+
+      output[written..written + count].copy_from_slice(&block[*offset..*offset + count]);
+
+      written += count;
+      *offset += count;
+
+      if *offset == block.len() {
+        let (block, _) = self.current.take().unwrap();
+
+        // the mixer reuses blocks, so it never allocates while running
+        let _ = self.recycled.send(block);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLE_RATE: AudioSampleRate = AudioSampleRate {
+    frequency: 1000,
+    channels: 2,
+    bits_per_sample: 16,
+  };
+
+  fn create_samples(frames: usize) -> Arc<AudioSamples> {
+    Arc::new(AudioSamples {
+      frequency: 1000,
+      channels: 1,
+      samples: vec![0.5; frames],
+    })
+  }
+
+  #[test]
+  fn it_should_mix_voices_until_they_finish() {
+    let mut mixer = AudioMixer::new(SAMPLE_RATE);
+    let mut block = vec![0.; 16];
+    let voice = VoiceId::from(1u32);
+
+    mixer.apply(AudioCommand::Play {
+      voice,
+      samples: create_samples(10),
+      settings: VoiceSettings::default(),
+    });
+
+    mixer.render(&mut block);
+
+    assert_eq!(block[0], block[1]); // mono reaches both channels
+    assert!(block[0] > 0.4);
+    assert_eq!(mixer.voices(), 1);
+
+    mixer.render(&mut block);
+
+    assert_eq!(block[4], 0.);
+    assert_eq!(mixer.voices(), 0);
+    assert_eq!(mixer.drain_finished().collect::<Vec<_>>(), vec![voice]);
+  }
+
+  #[test]
+  fn it_should_refuse_voices_past_the_limit() {
+    let mut mixer = AudioMixer::new(SAMPLE_RATE).with_max_voices(1);
+    let first = VoiceId::from(1u32);
+    let second = VoiceId::from(2u32);
+
+    for voice in [first, second] {
+      mixer.apply(AudioCommand::Play {
+        voice,
+        samples: create_samples(100),
+        settings: VoiceSettings::default(),
+      });
+    }
+
+    assert_eq!(mixer.voices(), 1);
+    assert_eq!(mixer.drain_finished().collect::<Vec<_>>(), vec![second]);
+  }
+
+  #[test]
+  fn it_should_keep_pitch_positive() {
+    let mut mixer = AudioMixer::new(SAMPLE_RATE);
+    let voice = VoiceId::from(1u32);
+
+    mixer.apply(AudioCommand::Play {
+      voice,
+      samples: create_samples(4),
+      settings: VoiceSettings {
+        pitch: 0.,
+        ..Default::default()
+      },
+    });
+
+    assert_eq!(mixer.voice_mut(voice).unwrap().settings.pitch, MIN_PITCH);
+
+    mixer.apply(AudioCommand::SetPitch { voice, pitch: -1. });
+
+    assert_eq!(mixer.voice_mut(voice).unwrap().settings.pitch, MIN_PITCH);
+  }
+
+  #[test]
+  fn it_should_apply_parameter_changes() {
+    let mut mixer = AudioMixer::new(SAMPLE_RATE);
+    let mut block = vec![0.; 16];
+    let voice = VoiceId::from(1u32);
+
+    mixer.apply(AudioCommand::Play {
+      voice,
+      samples: create_samples(4),
+      settings: VoiceSettings {
+        looping: true,
+        ..Default::default()
+      },
+    });
+
+    mixer.apply(AudioCommand::SetGain { voice, gain: 0.5 });
+    mixer.render(&mut block);
+
+    assert!((block[15] - 0.25).abs() < 1e-3);
+
+    mixer.apply(AudioCommand::Stop { voice });
+    mixer.render(&mut block);
+
+    assert_eq!(mixer.voices(), 0);
+    assert!(block.iter().all(|sample| *sample == 0.));
+  }
+
+  #[test]
+  fn it_should_stream_mixed_blocks_from_the_audio_thread() {
+    let (mut audio, mut stream) = AudioThread::spawn(AudioThreadSettings {
+      sample_rate: SAMPLE_RATE,
+      block_frames: 8,
+      ..Default::default()
+    });
+
+    audio.play(&create_samples(1000), VoiceSettings::default());
+
+    let mut output = vec![0.; 16];
+    let started = std::time::Instant::now();
+
+    // the blocks mixed before the command arrived are silent
+    while output.iter().all(|sample| *sample == 0.) {
+      assert!(started.elapsed() < Duration::from_secs(5), "no audio was mixed");
+
+      std::thread::sleep(Duration::from_millis(1));
+      stream.read(&mut output);
+    }
+
+    assert_eq!(audio.dropped_commands(), 0);
+  }
+}
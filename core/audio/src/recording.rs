@@ -0,0 +1,250 @@
+//! Recording of the master mix.
+//!
+//! Backends that mix in software hand every block of the master output to
+//! the [`AudioTap`] installed with [`AudioBackend::master_set_tap`], as
+//! interleaved samples. An [`AudioRecorder`] keeps those samples either for
+//! the whole session, or only for the last few seconds, for "share the last
+//! 30 seconds" features:
+//!
+//! ```rust,ignore
+//! let tap = AudioRecorder::with_ring_buffer(AudioSampleRate::STANDARD, TimeSpan::from_seconds(30.)).attach()?;
+//!
+//! // later, when the player asks for a clip
+//! tap.lock().unwrap().save_wav("local://clip.wav")?;
+//! ```
+//!
+//! Samples are stamped with the [`TimeStamp`] they were mixed at, so a range
+//! of the recording can be exported to line up with frames captured elsewhere.
+//! Only WAV is written; there's no OGG encoder in the engine yet.
+
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex},
+};
+
+use common::{OutputStream, StreamError, TimeSpan, TimeStamp, ToVirtualPath};
+
+use super::*;
+
+/// A shared [`AudioRecorder`] that a backend writes the master mix into.
+pub type AudioTap = Arc<Mutex<AudioRecorder>>;
+
+/// A possible error when recording audio.
+#[derive(Debug)]
+pub enum RecordingError {
+  /// The backend can't expose its master mix.
+  Unsupported,
+  /// Nothing has been recorded in the requested range.
+  NothingRecorded,
+  StreamError(StreamError),
+}
+
+common::impl_error_coercion!(StreamError into RecordingError);
+
+/// Records interleaved samples of the master mix as 16-bit PCM.
+pub struct AudioRecorder {
+  frequency: u32,
+  channels: u16,
+  /// The most frames to keep, if the recording is a ring buffer.
+  capacity: Option<usize>,
+  samples: VecDeque<i16>,
+  /// When the first frame was written.
+  started_at: Option<TimeStamp>,
+  /// How many frames have fallen out of the ring buffer.
+  discarded_frames: usize,
+}
+
+impl AudioRecorder {
+  /// Creates a recorder that keeps everything it's given.
+  pub fn new(sample_rate: AudioSampleRate) -> Self {
+    Self {
+      frequency: sample_rate.frequency as u32,
+      channels: sample_rate.channels.max(1) as u16,
+      capacity: None,
+      samples: VecDeque::new(),
+      started_at: None,
+      discarded_frames: 0,
+    }
+  }
+
+  /// Creates a recorder that only keeps the most recent audio.
+  pub fn with_ring_buffer(sample_rate: AudioSampleRate, duration: TimeSpan) -> Self {
+    let mut recorder = Self::new(sample_rate);
+    let capacity = (duration.as_seconds() as f64 * recorder.frequency as f64).ceil() as usize;
+
+    recorder.capacity = Some(capacity);
+    recorder.samples.reserve(capacity * recorder.channels as usize);
+    recorder
+  }
+
+  /// Installs the recorder as the master tap of the audio server.
+  pub fn attach(self) -> Result<AudioTap, RecordingError> {
+    let tap = Arc::new(Mutex::new(self));
+
+    audio().master_set_tap(Some(tap.clone()))?;
+
+    Ok(tap)
+  }
+
+  /// Writes a block of interleaved samples in the range [-1, 1].
+  ///
+  /// The timestamp is when the block was mixed. Only the first block's is
+  /// kept; after that the sample clock decides when each frame was heard, so
+  /// scheduling jitter doesn't stretch the recording.
+  pub fn write(&mut self, timestamp: TimeStamp, samples: &[f32]) {
+    self.started_at.get_or_insert(timestamp);
+
+    for sample in samples {
+      self.samples.push_back(wav::to_pcm16(*sample));
+    }
+
+    if let Some(capacity) = self.capacity {
+      let excess = self.frame_count().saturating_sub(capacity);
+
+      self.samples.drain(..excess * self.channels as usize);
+      self.discarded_frames += excess;
+    }
+  }
+
+  /// The number of whole frames held by the recorder.
+  pub fn frame_count(&self) -> usize {
+    self.samples.len() / self.channels as usize
+  }
+
+  /// The length of the audio held by the recorder.
+  pub fn duration(&self) -> TimeSpan {
+    self.frames_to_time(self.frame_count())
+  }
+
+  /// Discards everything recorded so far.
+  pub fn clear(&mut self) {
+    self.samples.clear();
+    self.started_at = None;
+    self.discarded_frames = 0;
+  }
+
+  /// Writes everything held by the recorder as a WAV file.
+  pub fn to_wav(&self, stream: &mut dyn OutputStream) -> Result<(), RecordingError> {
+    self.write_wav(stream, 0..self.frame_count())
+  }
+
+  /// Writes the audio heard between the given times as a WAV file.
+  ///
+  /// The range is clamped to what the recorder still holds.
+  pub fn to_wav_range(
+    &self,
+    stream: &mut dyn OutputStream,
+    from: TimeStamp,
+    to: TimeStamp,
+  ) -> Result<(), RecordingError> {
+    let started_at = self.started_at.ok_or(RecordingError::NothingRecorded)?;
+    let frame_at = |timestamp: TimeStamp| {
+      let frame = self.time_to_frames(timestamp - started_at);
+
+      frame.saturating_sub(self.discarded_frames).min(self.frame_count())
+    };
+
+    let frames = frame_at(from)..frame_at(to);
+
+    if frames.is_empty() {
+      return Err(RecordingError::NothingRecorded);
+    }
+
+    self.write_wav(stream, frames)
+  }
+
+  /// Saves everything held by the recorder as a WAV file at the given path.
+  pub fn save_wav(&self, path: impl ToVirtualPath) -> Result<(), RecordingError> {
+    let mut stream = path
+      .to_virtual_path()
+      .open_output_stream()
+      .map_err(|_| RecordingError::StreamError(StreamError::GeneralFailure))?;
+
+    self.to_wav(stream.as_mut())
+  }
+
+  fn write_wav(&self, stream: &mut dyn OutputStream, frames: std::ops::Range<usize>) -> Result<(), RecordingError> {
+    let channels = self.channels as usize;
+    let samples = self.samples.range(frames.start * channels..frames.end * channels);
+
+    Ok(wav::write_wav(stream, self.frequency, self.channels, samples.copied())?)
+  }
+
+  fn frames_to_time(&self, frames: usize) -> TimeSpan {
+    TimeSpan::from_seconds((frames as f64 / self.frequency as f64) as f32)
+  }
+
+  fn time_to_frames(&self, time: TimeSpan) -> usize {
+    (time.as_seconds() as f64 * self.frequency as f64).round() as usize
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use super::*;
+
+  const MONO: AudioSampleRate = AudioSampleRate {
+    frequency: 100,
+    channels: 1,
+    bits_per_sample: 16,
+  };
+
+  #[test]
+  fn it_should_write_recordings_as_wav() {
+    let mut recorder = AudioRecorder::new(AudioSampleRate::STANDARD);
+
+    recorder.write(TimeStamp::now(), &[0., 0., 1., -1., 2., 0.5]);
+
+    let mut stream = Cursor::new(Vec::new());
+
+    recorder.to_wav(&mut stream).unwrap();
+
+    let bytes = stream.into_inner();
+
+    assert_eq!(recorder.frame_count(), 3);
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(&bytes[8..12], b"WAVE");
+    assert_eq!(bytes.len(), 44 + 12);
+    assert_eq!(i16::from_le_bytes([bytes[48], bytes[49]]), i16::MAX);
+    assert_eq!(i16::from_le_bytes([bytes[52], bytes[53]]), i16::MAX);
+  }
+
+  #[test]
+  fn it_should_keep_only_the_most_recent_audio_in_a_ring_buffer() {
+    let mut recorder = AudioRecorder::with_ring_buffer(MONO, TimeSpan::from_seconds(0.5));
+
+    for _ in 0..8 {
+      recorder.write(TimeStamp::now(), &[0.25; 10]);
+    }
+
+    assert_eq!(recorder.frame_count(), 50);
+    assert_eq!(recorder.duration(), TimeSpan::from_seconds(0.5));
+  }
+
+  #[test]
+  fn it_should_export_ranges_by_timestamp() {
+    let mut recorder = AudioRecorder::with_ring_buffer(MONO, TimeSpan::from_seconds(1.));
+    let started_at = TimeStamp::now();
+
+    recorder.write(started_at, &[0.; 150]);
+
+    let mut stream = Cursor::new(Vec::new());
+
+    // the first half second has fallen out of the buffer
+    recorder
+      .to_wav_range(
+        &mut stream,
+        started_at + TimeSpan::from_seconds(0.25),
+        started_at + TimeSpan::from_seconds(1.),
+      )
+      .unwrap();
+
+    assert_eq!(stream.into_inner().len(), 44 + 50 * 2);
+    assert!(matches!(
+      recorder.to_wav_range(&mut Cursor::new(Vec::new()), started_at, started_at),
+      Err(RecordingError::NothingRecorded)
+    ));
+  }
+}
@@ -0,0 +1,484 @@
+//! Reverb, and zones that set it from where the listener is.
+//!
+//! A [`Reverb`] effect runs in the mixer ahead of the master bus. Scenes place
+//! [`ReverbZone`]s around caves, halls and rooms, and each frame the game
+//! works out which zones the listener is in and sends the blend to the audio
+//! thread:
+//!
+//! ```rust,ignore
+//! let mut zones = ReverbZones::new();
+//!
+//! zones.add(ReverbZone::sphere(cave_center, 20., ReverbPreset::CAVE));
+//!
+//! // each frame
+//! audio.set_reverb(zones.update(listener_position, delta_time));
+//! ```
+//!
+//! Zones fade in over their blend distance, overlapping zones are averaged by
+//! how far inside them the listener is, and moving between zones crossfades
+//! over [`ReverbZones::crossfade_time`], so the space never changes abruptly.
+
+use common::{Arena, Lerp, Sphere, AABB};
+
+use super::*;
+
+common::impl_arena_index!(pub ReverbZoneId, "Identifies a zone in [`ReverbZones`].");
+
+/// The character of a reverberant space.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ReverbPreset {
+  /// How much reverb is mixed in with the dry signal, from 0 to 1.
+  pub wet: f32,
+  /// How long the tail rings out, from 0 to 1.
+  pub decay: f32,
+  /// How quickly high frequencies die away in the tail, from 0 to 1.
+  pub damping: f32,
+  /// How large the space sounds, from 0 to 1.
+  pub size: f32,
+}
+
+impl ReverbPreset {
+  /// No reverb at all.
+  pub const NONE: Self = Self {
+    wet: 0.,
+    decay: 0.5,
+    damping: 0.5,
+    size: 0.5,
+  };
+
+  /// A small, soft-furnished room.
+  pub const SMALL_ROOM: Self = Self {
+    wet: 0.2,
+    decay: 0.4,
+    damping: 0.6,
+    size: 0.2,
+  };
+
+  /// A large hall with a long, smooth tail.
+  pub const HALL: Self = Self {
+    wet: 0.35,
+    decay: 0.85,
+    damping: 0.3,
+    size: 0.8,
+  };
+
+  /// A cave, with a long, bright tail off bare rock.
+  pub const CAVE: Self = Self {
+    wet: 0.5,
+    decay: 0.9,
+    damping: 0.1,
+    size: 1.,
+  };
+
+  /// Averages presets by weight, filling whatever weight is left under 1
+  /// with the fallback preset.
+  pub fn blend(presets: impl IntoIterator<Item = (Self, f32)>, fallback: Self) -> Self {
+    let presets = presets
+      .into_iter()
+      .filter(|(_, weight)| *weight > 0.)
+      .collect::<Vec<_>>();
+
+    let total = presets.iter().map(|(_, weight)| weight).sum::<f32>();
+    let remainder = (1. - total).max(0.);
+    let normalizer = total.max(1.);
+
+    let mut result = Self {
+      wet: fallback.wet * remainder,
+      decay: fallback.decay * remainder,
+      damping: fallback.damping * remainder,
+      size: fallback.size * remainder,
+    };
+
+    for (preset, weight) in presets {
+      let weight = weight / normalizer;
+
+      result.wet += preset.wet * weight;
+      result.decay += preset.decay * weight;
+      result.damping += preset.damping * weight;
+      result.size += preset.size * weight;
+    }
+
+    result
+  }
+}
+
+impl Default for ReverbPreset {
+  fn default() -> Self {
+    Self::NONE
+  }
+}
+
+impl Lerp for ReverbPreset {
+  #[inline]
+  fn lerp(a: Self, b: Self, t: f32) -> Self {
+    Self {
+      wet: f32::lerp(a.wet, b.wet, t),
+      decay: f32::lerp(a.decay, b.decay, t),
+      damping: f32::lerp(a.damping, b.damping, t),
+      size: f32::lerp(a.size, b.size, t),
+    }
+  }
+}
+
+/// The volume a [`ReverbZone`] covers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReverbZoneShape {
+  Sphere(Sphere),
+  Box(AABB),
+}
+
+impl ReverbZoneShape {
+  /// How far the point is outside the shape, or zero if it's inside.
+  pub fn distance_outside(&self, point: Vec3) -> f32 {
+    match self {
+      Self::Sphere(sphere) => (point.distance(sphere.center) - sphere.radius).max(0.),
+      Self::Box(aabb) => point.distance(point.clamp(aabb.min, aabb.max)),
+    }
+  }
+}
+
+/// A volume in a scene with its own reverb.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReverbZone {
+  pub shape: ReverbZoneShape,
+  pub preset: ReverbPreset,
+  /// How far outside the shape the zone fades out over.
+  pub blend_distance: f32,
+}
+
+impl ReverbZone {
+  /// Creates a spherical zone.
+  pub fn sphere(center: Vec3, radius: f32, preset: ReverbPreset) -> Self {
+    Self {
+      shape: ReverbZoneShape::Sphere(Sphere { radius, center }),
+      preset,
+      blend_distance: 2.,
+    }
+  }
+
+  /// Creates a box-shaped zone.
+  pub fn cuboid(min: Vec3, max: Vec3, preset: ReverbPreset) -> Self {
+    Self {
+      shape: ReverbZoneShape::Box(AABB::from_min_max(min, max)),
+      preset,
+      blend_distance: 2.,
+    }
+  }
+
+  /// Sets the distance the zone fades out over.
+  pub fn with_blend_distance(mut self, blend_distance: f32) -> Self {
+    self.blend_distance = blend_distance;
+    self
+  }
+
+  /// How much the zone applies at the point, from 0 outside its blend
+  /// distance to 1 inside its shape.
+  pub fn weight(&self, point: Vec3) -> f32 {
+    let distance = self.shape.distance_outside(point);
+
+    match self.blend_distance > 0. {
+      true => 1. - (distance / self.blend_distance).min(1.),
+      false if distance > 0. => 0.,
+      false => 1.,
+    }
+  }
+}
+
+/// The reverb zones in a scene, and the blend heard by the listener.
+pub struct ReverbZones {
+  zones: Arena<ReverbZoneId, ReverbZone>,
+  /// The preset heard outside every zone.
+  pub outside: ReverbPreset,
+  /// Roughly how long moving between zones takes to crossfade, in seconds.
+  pub crossfade_time: f32,
+  current: ReverbPreset,
+}
+
+impl ReverbZones {
+  /// Creates an empty set of zones, with no reverb outside them.
+  pub fn new() -> Self {
+    Self {
+      zones: Arena::new(),
+      outside: ReverbPreset::NONE,
+      crossfade_time: 0.5,
+      current: ReverbPreset::NONE,
+    }
+  }
+
+  /// Adds a zone.
+  pub fn add(&mut self, zone: ReverbZone) -> ReverbZoneId {
+    self.zones.insert(zone)
+  }
+
+  /// Gets a zone, to move or change it.
+  pub fn get_mut(&mut self, zone: ReverbZoneId) -> Option<&mut ReverbZone> {
+    self.zones.get_mut(zone)
+  }
+
+  /// Removes a zone.
+  pub fn remove(&mut self, zone: ReverbZoneId) -> Option<ReverbZone> {
+    self.zones.remove(zone)
+  }
+
+  /// The blend of every zone at the point, without crossfading.
+  pub fn preset_at(&self, point: Vec3) -> ReverbPreset {
+    let presets = self.zones.iter().map(|zone| (zone.preset, zone.weight(point)));
+
+    ReverbPreset::blend(presets, self.outside)
+  }
+
+  /// The preset the listener hears, as of the last update.
+  pub fn current(&self) -> ReverbPreset {
+    self.current
+  }
+
+  /// Crossfades towards the blend at the listener's position, returning the
+  /// preset to send to the mixer.
+  pub fn update(&mut self, listener: Vec3, delta_time: f32) -> ReverbPreset {
+    let target = self.preset_at(listener);
+    let t = match self.crossfade_time > 0. {
+      true => (delta_time / self.crossfade_time).min(1.),
+      false => 1.,
+    };
+
+    self.current = ReverbPreset::lerp(self.current, target, t);
+    self.current
+  }
+}
+
+/// A Schroeder-style reverb: parallel damped combs, then allpasses to
+/// diffuse them, per channel.
+///
+/// Changes to the preset are smoothed over a few milliseconds so they don't
+/// click.
+pub struct Reverb {
+  target: ReverbPreset,
+  current: ReverbPreset,
+  smoothing: f32,
+  scale: f32,
+  channels: Vec<ReverbChannel>,
+}
+
+/// Comb delays at 44.1kHz, from Freeverb.
+const COMB_DELAYS: [usize; 4] = [1116, 1188, 1277, 1356];
+
+/// Allpass delays at 44.1kHz, from Freeverb.
+const ALLPASS_DELAYS: [usize; 2] = [556, 441];
+
+/// How much longer the delays are for odd channels, to widen the image.
+const STEREO_SPREAD: usize = 23;
+
+/// The gain on the input to the combs, so their sum doesn't clip.
+const INPUT_GAIN: f32 = 0.015;
+
+impl Reverb {
+  /// Creates a reverb with the given preset.
+  pub fn new(sample_rate: AudioSampleRate, preset: ReverbPreset) -> Self {
+    Self {
+      target: preset,
+      current: preset,
+      smoothing: smoothing_coefficient(sample_rate, 0.05),
+      scale: sample_rate.frequency as f32 / 44_100.,
+      channels: Vec::new(),
+    }
+  }
+
+  /// The preset the reverb is moving towards.
+  pub fn preset(&self) -> ReverbPreset {
+    self.target
+  }
+
+  /// Changes the preset, smoothly.
+  pub fn set_preset(&mut self, preset: ReverbPreset) {
+    self.target = preset;
+  }
+}
+
+impl AudioEffect for Reverb {
+  fn process(&mut self, samples: &mut [f32], channels: usize) {
+    let channels = channels.max(1);
+
+    while self.channels.len() < channels {
+      let spread = (self.channels.len() % 2) * STEREO_SPREAD;
+
+      self.channels.push(ReverbChannel::new(self.scale, spread));
+    }
+
+    for frame in samples.chunks_mut(channels) {
+      self.current = ReverbPreset::lerp(self.target, self.current, self.smoothing);
+
+      let feedback = 0.7 + self.current.decay.clamp(0., 1.) * 0.28;
+      let damping = self.current.damping.clamp(0., 1.) * 0.4;
+      let size = 0.5 + self.current.size.clamp(0., 1.) * 0.5;
+
+      for (sample, channel) in frame.iter_mut().zip(&mut self.channels) {
+        *sample += channel.process(*sample, feedback, damping, size) * self.current.wet;
+      }
+    }
+  }
+}
+
+/// The delay lines for one channel of a [`Reverb`].
+struct ReverbChannel {
+  combs: [Comb; 4],
+  allpasses: [Allpass; 2],
+}
+
+impl ReverbChannel {
+  fn new(scale: f32, spread: usize) -> Self {
+    let length = |delay: usize| (((delay + spread) as f32 * scale) as usize).max(1);
+
+    Self {
+      combs: COMB_DELAYS.map(|delay| Comb::new(length(delay))),
+      allpasses: ALLPASS_DELAYS.map(|delay| Allpass::new(length(delay))),
+    }
+  }
+
+  fn process(&mut self, input: f32, feedback: f32, damping: f32, size: f32) -> f32 {
+    let input = input * INPUT_GAIN;
+    let mut output = 0.;
+
+    for comb in &mut self.combs {
+      output += comb.process(input, feedback, damping, size);
+    }
+
+    for allpass in &mut self.allpasses {
+      output = allpass.process(output);
+    }
+
+    output
+  }
+}
+
+/// A feedback comb filter with a low-pass in the loop.
+struct Comb {
+  buffer: Vec<f32>,
+  index: usize,
+  filtered: f32,
+}
+
+impl Comb {
+  fn new(length: usize) -> Self {
+    Self {
+      buffer: vec![0.; length],
+      index: 0,
+      filtered: 0.,
+    }
+  }
+
+  /// Delays the input by the fraction of the full line given by the size.
+  fn process(&mut self, input: f32, feedback: f32, damping: f32, size: f32) -> f32 {
+    let length = ((self.buffer.len() as f32 * size) as usize).clamp(1, self.buffer.len());
+
+    if self.index >= length {
+      self.index = 0;
+    }
+
+    let output = self.buffer[self.index];
+
+    self.filtered = output * (1. - damping) + self.filtered * damping;
+    self.buffer[self.index] = input + self.filtered * feedback;
+    self.index += 1;
+
+    output
+  }
+}
+
+/// An allpass filter, which smears the signal in time without colouring it.
+struct Allpass {
+  buffer: Vec<f32>,
+  index: usize,
+}
+
+impl Allpass {
+  fn new(length: usize) -> Self {
+    Self {
+      buffer: vec![0.; length],
+      index: 0,
+    }
+  }
+
+  fn process(&mut self, input: f32) -> f32 {
+    let delayed = self.buffer[self.index];
+
+    self.buffer[self.index] = input + delayed * 0.5;
+    self.index = (self.index + 1) % self.buffer.len();
+
+    delayed - input
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_blend_overlapping_zones_by_weight() {
+    let mut zones = ReverbZones::new();
+
+    zones.add(ReverbZone::sphere(Vec3::ZERO, 10., ReverbPreset::CAVE).with_blend_distance(10.));
+    zones.add(ReverbZone::cuboid(
+      Vec3::new(5., -5., -5.),
+      Vec3::new(15., 5., 5.),
+      ReverbPreset::HALL,
+    ));
+
+    assert_eq!(zones.preset_at(Vec3::new(100., 0., 0.)), ReverbPreset::NONE);
+    assert_eq!(zones.preset_at(Vec3::new(-5., 0., 0.)), ReverbPreset::CAVE);
+
+    // halfway through the cave's blend distance, fading to nothing outside
+    let fading = zones.preset_at(Vec3::new(-15., 0., 0.));
+
+    assert!((fading.wet - ReverbPreset::CAVE.wet * 0.5).abs() < 1e-5);
+
+    // fully inside both, so they're averaged evenly
+    let overlap = zones.preset_at(Vec3::new(7., 0., 0.));
+
+    assert!((overlap.wet - (ReverbPreset::CAVE.wet + ReverbPreset::HALL.wet) / 2.).abs() < 1e-5);
+  }
+
+  #[test]
+  fn it_should_crossfade_when_the_listener_moves_between_zones() {
+    let mut zones = ReverbZones::new();
+
+    zones.add(ReverbZone::sphere(Vec3::ZERO, 5., ReverbPreset::SMALL_ROOM));
+
+    let first = zones.update(Vec3::ZERO, 0.1);
+
+    assert!(first.wet > 0. && first.wet < ReverbPreset::SMALL_ROOM.wet);
+
+    for _ in 0..100 {
+      zones.update(Vec3::ZERO, 0.1);
+    }
+
+    assert!((zones.current().wet - ReverbPreset::SMALL_ROOM.wet).abs() < 1e-4);
+  }
+
+  #[test]
+  fn it_should_add_a_decaying_tail_to_the_mix() {
+    let sample_rate = AudioSampleRate::STANDARD;
+    let mut dry = Reverb::new(sample_rate, ReverbPreset::NONE);
+    let mut wet = Reverb::new(sample_rate, ReverbPreset::HALL);
+
+    let mut impulse = vec![0.; 44_100 * 2];
+
+    impulse[0] = 1.;
+    impulse[1] = 1.;
+
+    let mut dry_output = impulse.clone();
+    let mut wet_output = impulse.clone();
+
+    dry.process(&mut dry_output, 2);
+    wet.process(&mut wet_output, 2);
+
+    assert_eq!(dry_output, impulse);
+
+    let energy = |samples: &[f32]| samples.iter().map(|sample| sample * sample).sum::<f32>();
+    let early = energy(&wet_output[2..22_050]);
+    let late = energy(&wet_output[66_150..]);
+
+    assert!(early > 0.);
+    assert!(late < early);
+    assert!(wet_output.iter().all(|sample| sample.is_finite() && sample.abs() < 2.));
+  }
+}
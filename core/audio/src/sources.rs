@@ -106,6 +106,8 @@ impl AudioSource {
 
 impl Drop for AudioSource {
   fn drop(&mut self) {
-    audio().source_delete(self.id).unwrap();
+    let id = self.id;
+
+    AudioServer::defer(move |audio| audio.source_delete(id).expect("Failed to delete source"));
   }
 }
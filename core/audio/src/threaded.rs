@@ -0,0 +1,396 @@
+//! An [`AudioBackend`] that plays through an [`AudioThread`].
+//!
+//! Buffers are decoded to samples on the game thread as they're written, and
+//! sources become voices on the audio thread when played, so clips played
+//! through the [`AudioServer`] share the mix with everything else:
+//!
+//! ```rust,ignore
+//! let (thread, stream) = AudioThread::spawn(AudioThreadSettings::default());
+//! let thread = Rc::new(RefCell::new(thread));
+//!
+//! AudioServer::install(ThreadedAudioBackend::new(thread.clone()));
+//! ```
+//!
+//! The mixer has no spatialization yet, so source positions and velocities
+//! are kept, but don't change how sources sound.
+
+use std::{
+  cell::{RefCell, RefMut},
+  rc::Rc,
+  sync::Arc,
+};
+
+use common::FastHashMap;
+
+use super::*;
+
+/// An [`AudioBackend`] that mixes on an [`AudioThread`].
+pub struct ThreadedAudioBackend {
+  thread: Rc<RefCell<AudioThread>>,
+  state: RefCell<ThreadedState>,
+}
+
+#[derive(Default)]
+struct ThreadedState {
+  next_id: u32,
+  buffers: FastHashMap<BufferId, Option<Arc<AudioSamples>>>,
+  clips: FastHashMap<ClipId, Option<BufferId>>,
+  sources: FastHashMap<SourceId, ThreadedSource>,
+}
+
+#[derive(Default)]
+struct ThreadedSource {
+  clip: Option<ClipId>,
+  settings: VoiceSettings,
+  position: Vec3,
+  velocity: Vec3,
+  voice: Option<VoiceId>,
+}
+
+impl ThreadedAudioBackend {
+  /// Creates a backend that plays through the given thread.
+  ///
+  /// The backend collects the thread's finished voices, to know when sources
+  /// stop playing.
+  pub fn new(thread: Rc<RefCell<AudioThread>>) -> Self {
+    Self {
+      thread,
+      state: RefCell::new(ThreadedState::default()),
+    }
+  }
+
+  fn thread(&self) -> RefMut<'_, AudioThread> {
+    self.thread.borrow_mut()
+  }
+
+  /// Borrows the state, after forgetting the voices that finished playing.
+  fn state(&self) -> RefMut<'_, ThreadedState> {
+    let mut state = self.state.borrow_mut();
+
+    for finished in self.thread().take_finished() {
+      for source in state.sources.values_mut() {
+        if source.voice == Some(finished) {
+          source.voice = None;
+        }
+      }
+    }
+
+    state
+  }
+
+  fn next_id(&self) -> u32 {
+    let mut state = self.state.borrow_mut();
+
+    // zero is reserved for `NONE`
+    state.next_id += 1;
+    state.next_id
+  }
+
+  /// Applies a change to a source's settings, and to its voice if playing.
+  fn update_source(
+    &self,
+    source: SourceId,
+    update: impl FnOnce(&mut VoiceSettings),
+    command: impl FnOnce(&mut AudioThread, VoiceId, &VoiceSettings),
+  ) -> Result<(), SourceError> {
+    let mut state = self.state();
+    let source_state = state.sources.get_mut(&source).ok_or(SourceError::InvalidId(source))?;
+
+    update(&mut source_state.settings);
+
+    if let Some(voice) = source_state.voice {
+      command(&mut self.thread(), voice, &source_state.settings);
+    }
+
+    Ok(())
+  }
+}
+
+/// Decodes interleaved PCM data, as described by the sample rate.
+fn decode_samples(sample_rate: AudioSampleRate, data: &[u8]) -> Option<AudioSamples> {
+  let samples = match sample_rate.bits_per_sample {
+    8 => data.iter().map(|it| (*it as f32 - 128.) / 128.).collect(),
+    16 => data
+      .as_chunks::<2>()
+      .0
+      .iter()
+      .map(|it| i16::from_le_bytes(*it) as f32 / 32768.)
+      .collect(),
+    32 => data
+      .as_chunks::<4>()
+      .0
+      .iter()
+      .map(|it| f32::from_le_bytes(*it))
+      .collect(),
+    _ => return None,
+  };
+
+  Some(AudioSamples {
+    frequency: sample_rate.frequency as u32,
+    channels: sample_rate.channels.max(1) as usize,
+    samples,
+  })
+}
+
+impl AudioBackend for ThreadedAudioBackend {
+  fn buffer_create(&self) -> Result<BufferId, BufferError> {
+    let buffer = BufferId::from(self.next_id());
+
+    self.state.borrow_mut().buffers.insert(buffer, None);
+
+    Ok(buffer)
+  }
+
+  fn buffer_write_data(&self, buffer: BufferId, sample_rate: AudioSampleRate, data: &[u8]) -> Result<(), BufferError> {
+    let samples = decode_samples(sample_rate, data).ok_or(BufferError::FailedToCreate)?;
+    let mut state = self.state.borrow_mut();
+    let entry = state.buffers.get_mut(&buffer).ok_or(BufferError::InvalidId(buffer))?;
+
+    *entry = Some(Arc::new(samples));
+
+    Ok(())
+  }
+
+  fn buffer_delete(&self, buffer: BufferId) -> Result<(), BufferError> {
+    // voices keep their own reference to the samples, and play on
+    match self.state.borrow_mut().buffers.remove(&buffer) {
+      Some(_) => Ok(()),
+      None => Err(BufferError::InvalidId(buffer)),
+    }
+  }
+
+  fn clip_create(&self) -> Result<ClipId, ClipError> {
+    let clip = ClipId::from(self.next_id());
+
+    self.state.borrow_mut().clips.insert(clip, None);
+
+    Ok(clip)
+  }
+
+  fn clip_set_buffer(&self, clip: ClipId, buffer: BufferId) -> Result<(), ClipError> {
+    let mut state = self.state.borrow_mut();
+    let entry = state.clips.get_mut(&clip).ok_or(ClipError::InvalidId(clip))?;
+
+    *entry = Some(buffer);
+
+    Ok(())
+  }
+
+  fn clip_delete(&self, clip: ClipId) -> Result<(), ClipError> {
+    match self.state.borrow_mut().clips.remove(&clip) {
+      Some(_) => Ok(()),
+      None => Err(ClipError::InvalidId(clip)),
+    }
+  }
+
+  fn source_create(&self) -> Result<SourceId, SourceError> {
+    let source = SourceId::from(self.next_id());
+
+    self
+      .state
+      .borrow_mut()
+      .sources
+      .insert(source, ThreadedSource::default());
+
+    Ok(source)
+  }
+
+  fn source_is_playing(&self, source: SourceId) -> Option<bool> {
+    self.state().sources.get(&source).map(|it| it.voice.is_some())
+  }
+
+  fn source_get_gain(&self, source: SourceId) -> Option<f32> {
+    self.state().sources.get(&source).map(|it| it.settings.gain)
+  }
+
+  fn source_set_gain(&self, source: SourceId, gain: f32) -> Result<(), SourceError> {
+    self.update_source(
+      source,
+      |settings| settings.gain = gain,
+      |thread, voice, settings| thread.set_gain(voice, settings.gain),
+    )
+  }
+
+  fn source_get_pitch(&self, source: SourceId) -> Option<f32> {
+    self.state().sources.get(&source).map(|it| it.settings.pitch)
+  }
+
+  fn source_set_pitch(&self, source: SourceId, pitch: f32) -> Result<(), SourceError> {
+    self.update_source(
+      source,
+      |settings| settings.pitch = pitch.max(MIN_PITCH),
+      |thread, voice, settings| thread.set_pitch(voice, settings.pitch),
+    )
+  }
+
+  fn source_get_position(&self, source: SourceId) -> Option<Vec3> {
+    self.state().sources.get(&source).map(|it| it.position)
+  }
+
+  fn source_set_position(&self, source: SourceId, position: Vec3) -> Result<(), SourceError> {
+    let mut state = self.state();
+    let source_state = state.sources.get_mut(&source).ok_or(SourceError::InvalidId(source))?;
+
+    source_state.position = position;
+
+    Ok(())
+  }
+
+  fn source_set_velocity(&self, source: SourceId, velocity: Vec3) -> Result<(), SourceError> {
+    let mut state = self.state();
+    let source_state = state.sources.get_mut(&source).ok_or(SourceError::InvalidId(source))?;
+
+    source_state.velocity = velocity;
+
+    Ok(())
+  }
+
+  fn source_get_velocity(&self, source: SourceId) -> Option<Vec3> {
+    self.state().sources.get(&source).map(|it| it.velocity)
+  }
+
+  fn source_is_looping(&self, source: SourceId) -> Option<bool> {
+    self.state().sources.get(&source).map(|it| it.settings.looping)
+  }
+
+  fn source_set_looping(&self, source: SourceId, looping: bool) -> Result<(), SourceError> {
+    self.update_source(
+      source,
+      |settings| settings.looping = looping,
+      |thread, voice, settings| thread.set_looping(voice, settings.looping),
+    )
+  }
+
+  fn source_get_clip(&self, source: SourceId) -> Option<ClipId> {
+    self.state().sources.get(&source).and_then(|it| it.clip)
+  }
+
+  fn source_set_clip(&self, source: SourceId, clip: ClipId) -> Result<(), SourceError> {
+    let mut state = self.state();
+    let source_state = state.sources.get_mut(&source).ok_or(SourceError::InvalidId(source))?;
+
+    source_state.clip = Some(clip);
+
+    Ok(())
+  }
+
+  fn source_play(&self, source: SourceId) -> Result<(), SourceError> {
+    let mut state = self.state();
+    let samples = state
+      .sources
+      .get(&source)
+      .ok_or(SourceError::InvalidId(source))?
+      .clip
+      .and_then(|clip| state.clips.get(&clip).copied().flatten())
+      .and_then(|buffer| state.buffers.get(&buffer).cloned().flatten());
+
+    let source_state = state.sources.get_mut(&source).ok_or(SourceError::InvalidId(source))?;
+    let mut thread = self.thread();
+
+    // restart from the beginning, like OpenAL
+    if let Some(voice) = source_state.voice.take() {
+      thread.stop(voice);
+    }
+
+    // sources without samples play silence
+    if let Some(samples) = samples {
+      source_state.voice = Some(thread.play(&samples, source_state.settings));
+    }
+
+    Ok(())
+  }
+
+  fn source_delete(&self, source: SourceId) -> Result<(), SourceError> {
+    let source_state = self
+      .state()
+      .sources
+      .remove(&source)
+      .ok_or(SourceError::InvalidId(source))?;
+
+    if let Some(voice) = source_state.voice {
+      self.thread().stop(voice);
+    }
+
+    Ok(())
+  }
+
+  fn master_set_tap(&self, _tap: Option<AudioTap>) -> Result<(), RecordingError> {
+    // the mix stays on the audio thread, which doesn't feed taps yet
+    Err(RecordingError::Unsupported)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, Instant};
+
+  use super::*;
+
+  fn create_backend() -> (ThreadedAudioBackend, AudioStream) {
+    let (thread, stream) = AudioThread::spawn(AudioThreadSettings {
+      block_frames: 8,
+      ..Default::default()
+    });
+
+    (ThreadedAudioBackend::new(Rc::new(RefCell::new(thread))), stream)
+  }
+
+  fn create_clip(backend: &ThreadedAudioBackend, frames: usize) -> ClipId {
+    let buffer = backend.buffer_create().unwrap();
+    let clip = backend.clip_create().unwrap();
+    let sample_rate = AudioSampleRate {
+      frequency: 1000,
+      channels: 1,
+      bits_per_sample: 16,
+    };
+
+    backend
+      .buffer_write_data(buffer, sample_rate, &vec![0x40; frames * 2])
+      .unwrap();
+    backend.clip_set_buffer(clip, buffer).unwrap();
+
+    clip
+  }
+
+  #[test]
+  fn it_should_play_sources_until_their_voice_finishes() {
+    let (backend, mut stream) = create_backend();
+    let clip = create_clip(&backend, 16);
+    let source = backend.source_create().unwrap();
+
+    backend.source_set_clip(source, clip).unwrap();
+    backend.source_play(source).unwrap();
+
+    assert_eq!(backend.source_is_playing(source), Some(true));
+
+    let started = Instant::now();
+
+    while backend.source_is_playing(source) == Some(true) {
+      assert!(started.elapsed() < Duration::from_secs(5), "the voice never finished");
+
+      std::thread::sleep(Duration::from_millis(1));
+      stream.read(&mut [0.; 16]);
+    }
+  }
+
+  #[test]
+  fn it_should_clamp_source_pitch() {
+    let (backend, _stream) = create_backend();
+    let source = backend.source_create().unwrap();
+
+    backend.source_set_pitch(source, 0.).unwrap();
+
+    assert_eq!(backend.source_get_pitch(source), Some(MIN_PITCH));
+  }
+
+  #[test]
+  fn it_should_play_silence_without_samples() {
+    let (backend, _stream) = create_backend();
+    let source = backend.source_create().unwrap();
+
+    backend.source_play(source).unwrap();
+
+    assert_eq!(backend.source_is_playing(source), Some(false));
+    assert!(backend.source_play(SourceId::from(999u32)).is_err());
+  }
+}
@@ -0,0 +1,121 @@
+//! Reading and writing of 16-bit PCM WAV data.
+
+use common::{OutputStream, StreamError};
+
+use super::*;
+
+/// Writes interleaved 16-bit samples as a WAV file.
+pub(crate) fn write_wav(
+  stream: &mut dyn OutputStream,
+  frequency: u32,
+  channels: u16,
+  samples: impl ExactSizeIterator<Item = i16>,
+) -> Result<(), StreamError> {
+  const BYTES_PER_SAMPLE: u16 = 2;
+
+  let block_align = channels * BYTES_PER_SAMPLE;
+  let data_size = (samples.len() * BYTES_PER_SAMPLE as usize) as u32;
+
+  stream.write_bytes(b"RIFF")?;
+  stream.write_u32(36 + data_size)?;
+  stream.write_bytes(b"WAVE")?;
+
+  stream.write_bytes(b"fmt ")?;
+  stream.write_u32(16)?;
+  stream.write_u16(1)?; // PCM
+  stream.write_u16(channels)?;
+  stream.write_u32(frequency)?;
+  stream.write_u32(frequency * block_align as u32)?;
+  stream.write_u16(block_align)?;
+  stream.write_u16(BYTES_PER_SAMPLE * 8)?;
+
+  stream.write_bytes(b"data")?;
+  stream.write_u32(data_size)?;
+
+  for sample in samples {
+    stream.write_i16(sample)?;
+  }
+
+  Ok(())
+}
+
+/// Reads a 16-bit PCM WAV file as interleaved samples in the range [-1, 1].
+pub(crate) fn read_wav(data: &[u8]) -> Result<(AudioSampleRate, Vec<f32>), StreamError> {
+  if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+    return Err(StreamError::InvalidData);
+  }
+
+  let mut sample_rate = None;
+  let mut chunks = &data[12..];
+
+  while chunks.len() >= 8 {
+    let id = &chunks[0..4];
+    let size = u32::from_le_bytes([chunks[4], chunks[5], chunks[6], chunks[7]]) as usize;
+    let body = chunks.get(8..8 + size).ok_or(StreamError::EndOfStream)?;
+
+    match id {
+      b"fmt " if body.len() >= 16 => {
+        let read_u16 = |offset: usize| u16::from_le_bytes([body[offset], body[offset + 1]]);
+        let frequency = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+
+        if read_u16(0) != 1 || read_u16(14) != 16 {
+          return Err(StreamError::InvalidData);
+        }
+
+        sample_rate = Some(AudioSampleRate {
+          frequency: u16::try_from(frequency).map_err(|_| StreamError::InvalidData)?,
+          channels: read_u16(2) as u8,
+          bits_per_sample: 16,
+        });
+      }
+      b"data" => {
+        let sample_rate = sample_rate.ok_or(StreamError::InvalidData)?;
+        let samples = body
+          .as_chunks::<2>()
+          .0
+          .iter()
+          .map(|&sample| i16::from_le_bytes(sample) as f32 / i16::MAX as f32)
+          .collect();
+
+        return Ok((sample_rate, samples));
+      }
+      _ => {}
+    }
+
+    // chunks are padded to an even length
+    chunks = chunks.get(8 + size + size % 2..).unwrap_or_default();
+  }
+
+  Err(StreamError::EndOfStream)
+}
+
+/// Converts a sample in the range [-1, 1] to 16-bit PCM.
+pub(crate) fn to_pcm16(sample: f32) -> i16 {
+  (sample.clamp(-1., 1.) * i16::MAX as f32) as i16
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use super::*;
+
+  #[test]
+  fn it_should_round_trip_wav_data() {
+    let samples = [0., 0.5, -0.5, 1.];
+    let mut stream = Cursor::new(Vec::new());
+
+    write_wav(&mut stream, 22_050, 2, samples.iter().map(|sample| to_pcm16(*sample))).unwrap();
+
+    let (sample_rate, decoded) = read_wav(&stream.into_inner()).unwrap();
+
+    assert_eq!(sample_rate.frequency, 22_050);
+    assert_eq!(sample_rate.channels, 2);
+
+    for (decoded, sample) in decoded.iter().zip(samples) {
+      assert!((decoded - sample).abs() < 0.001);
+    }
+
+    assert!(read_wav(b"RIFF\0\0\0\0AVI ").is_err());
+  }
+}
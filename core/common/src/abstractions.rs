@@ -2,14 +2,24 @@
 
 pub use assets::*;
 pub use callbacks::*;
+pub use datatables::*;
 pub use platform::*;
+pub use profiles::*;
+pub use projects::*;
 pub use serialized::*;
 pub use services::*;
+pub use timelines::*;
+pub use transitions::*;
 pub use variant::*;
 
 mod assets;
 mod callbacks;
+mod datatables;
 mod platform;
+mod profiles;
+mod projects;
 mod serialized;
 mod services;
+mod timelines;
+mod transitions;
 mod variant;
@@ -1,11 +1,29 @@
 use std::{
-  fmt::{Debug, Formatter},
-  sync::Arc,
+  collections::hash_map::Entry,
+  fmt::{Debug, Display, Formatter},
+  hash::{Hash, Hasher},
+  str::FromStr,
+  sync::{Arc, OnceLock},
 };
 
+pub use bundles::*;
+pub use importers::*;
 use macros::Singleton;
+pub use manifests::*;
+pub use targets::*;
+pub use updater::*;
+
+use crate::{
+  BlockableFuture, Chunk, ContentHash, ContentHasher, FastHashMap, FromStream, FromVariant, Guid, HashAlgorithm,
+  InputStream, ProjectDefinition, StreamError, ToVariant, ToVirtualPath, Variant, VariantError, VirtualPath,
+  RON_TYPE_KEY,
+};
 
-use crate::{BlockableFuture, FastHashMap, FromStream, Guid, InputStream, ToVirtualPath, VirtualPath};
+mod bundles;
+mod importers;
+mod manifests;
+mod targets;
+mod updater;
 
 /// An error that can occur when loading an asset
 #[derive(Debug)]
@@ -14,20 +32,33 @@ pub enum AssetError {
   NotFound,
   LoadFailed,
   TypeMismatch,
+  ImportFailed(String),
 }
 
 /// Represents a database that can load and save assets.
+///
+/// Source assets with a registered [`AssetImporter`] are imported as they're
+/// read, and the result is cached against the content hash of the source; an
+/// edited file is imported again, but loading an unchanged one is free.
+///
+/// Relative paths are looked up in each asset root in turn; by default
+/// there's a single root, `local://assets`, and a project's roots are used
+/// once it's [configured](AssetDatabase::configure).
 #[derive(Singleton)]
 pub struct AssetDatabase {
-  base_path: VirtualPath,
+  asset_roots: Vec<VirtualPath>,
   asset_map: AssetMetadataMap,
+  importers: AssetImporterRegistry,
+  imports: FastHashMap<ContentHash, Vec<u8>>,
 }
 
 impl Default for AssetDatabase {
   fn default() -> Self {
     Self {
-      base_path: VirtualPath::new("local://assets"),
+      asset_roots: vec![VirtualPath::new("local://assets")],
       asset_map: AssetMetadataMap::default(),
+      importers: AssetImporterRegistry::default(),
+      imports: FastHashMap::default(),
     }
   }
 }
@@ -70,12 +101,85 @@ struct AssetMetadata {
 }
 
 impl AssetDatabase {
-  /// Returns the instance of the asset database.
-  pub fn read_asset(&self, id: &AssetId) -> Result<Box<dyn InputStream>, AssetError> {
-    if let Some(metadata) = self.asset_map.resolve(id) {
-      metadata.path.open_input_stream().map_err(|_| AssetError::LoadFailed)
-    } else {
-      Err(AssetError::NotFound)
+  /// Loads an asset by path, e.g.
+  /// `AssetDatabase::load::<ScriptModule>("enemy.bsc")`.
+  ///
+  /// Paths without a scheme are relative to the database's asset roots.
+  pub fn load<A: Asset>(path: &str) -> Result<A, AssetError> {
+    let path = match path.contains("://") {
+      true => VirtualPath::new(path),
+      false => Self::instance().resolve_path(path),
+    };
+
+    A::from_id(&AssetId::Path(path))
+  }
+
+  /// Uses the asset roots of the given project.
+  pub fn configure(&mut self, project: &ProjectDefinition) {
+    self.asset_roots = project.asset_roots.iter().map(|root| root.path.clone()).collect();
+  }
+
+  /// The roots relative paths are looked up in, in order.
+  pub fn asset_roots(&self) -> &[VirtualPath] {
+    &self.asset_roots
+  }
+
+  /// Finds a relative path in the first asset root that has it, or the first
+  /// root if none do.
+  pub fn resolve_path(&self, relative: &str) -> VirtualPath {
+    let mut candidates = self.asset_roots.iter().map(|root| root.join(relative));
+    let first = candidates.next().unwrap_or_else(|| VirtualPath::new(relative));
+
+    match first.exists() {
+      true => first,
+      false => candidates.find(|path| path.exists()).unwrap_or(first),
+    }
+  }
+
+  /// Registers an importer to run over matching source assets as they're
+  /// read.
+  pub fn register_importer(&mut self, importer: impl AssetImporter + 'static) {
+    self.importers.register(importer);
+  }
+
+  /// Opens a stream over the asset with the given ID, importing it first if
+  /// there's an importer for it.
+  pub fn read_asset(&mut self, id: &AssetId) -> Result<Box<dyn InputStream>, AssetError> {
+    let path = match (self.asset_map.resolve(id), id) {
+      (Some(metadata), _) => metadata.path.clone(),
+      // paths can be read even if the database hasn't seen them before
+      (None, AssetId::Path(path)) => path.clone(),
+      (None, _) => return Err(AssetError::NotFound),
+    };
+
+    let Some(importer) = self.importers.find(&path) else {
+      return path.open_input_stream().map_err(|_| AssetError::LoadFailed);
+    };
+
+    let data = path.read_all_bytes().map_err(|_| AssetError::LoadFailed)?;
+    let mut hasher = ContentHasher::new(HashAlgorithm::XxHash3);
+
+    hasher.update(importer.name().as_bytes());
+    hasher.update(&importer.version().to_le_bytes());
+    hasher.update(&data);
+
+    let imported = match self.imports.entry(hasher.finish()) {
+      Entry::Occupied(entry) => entry.get().clone(),
+      Entry::Vacant(entry) => entry.insert(importer.import(&path, &data)?.data).clone(),
+    };
+
+    Ok(Box::new(std::io::Cursor::new(imported)))
+  }
+
+  /// Verifies the assets under the database's first asset root against a
+  /// manifest.
+  ///
+  /// This is intended to be run at startup to detect corrupt or tampered
+  /// content before any of it is loaded.
+  pub fn verify_manifest(&self, manifest: &AssetManifest) -> Result<(), Vec<ManifestViolation>> {
+    match self.asset_roots.first() {
+      Some(root) => manifest.verify(root),
+      None => Ok(()),
     }
   }
 }
@@ -128,23 +232,63 @@ pub trait Asset: Sized {
   }
 }
 
-/// Represents an asset on the virtual file system.
-#[derive(Clone, Eq, PartialEq, Hash)]
+/// A typed reference to an asset, as stored in components and scenes.
+///
+/// References serialize as their [`AssetId`], and load the asset lazily the
+/// first time it's asked for; clones share the loaded asset:
+///
+/// ```rust,ignore
+/// struct Sprite {
+///   texture: AssetRef<Texture>,
+/// }
+///
+/// let texture = sprite.texture.get_or_placeholder();
+/// ```
 pub struct AssetRef<T> {
   id: AssetId,
-  _marker: std::marker::PhantomData<T>,
+  loaded: Arc<OnceLock<Arc<T>>>,
+}
+
+/// An asset that can stand in for one that's missing or failed to load, so a
+/// broken reference shows up clearly rather than stopping the game.
+pub trait AssetPlaceholder {
+  /// Creates the placeholder, e.g. a magenta checkerboard texture.
+  fn placeholder() -> Self;
 }
 
 impl<T> Default for AssetRef<T> {
   fn default() -> Self {
+    Self::new(AssetId::None)
+  }
+}
+
+impl<T> Clone for AssetRef<T> {
+  fn clone(&self) -> Self {
     Self {
-      id: AssetId::None,
-      _marker: std::marker::PhantomData,
+      id: self.id.clone(),
+      loaded: self.loaded.clone(),
     }
   }
 }
 
+impl<T> PartialEq for AssetRef<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.id == other.id
+  }
+}
+
+impl<T> Eq for AssetRef<T> {}
+
+impl<T> Hash for AssetRef<T> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.id.hash(state);
+  }
+}
+
 /// Possible means of identifying an asset.
+///
+/// IDs are written as text like `guid:<guid>`, `key:<key>` or a path with a
+/// scheme like `local://sprites/player.png`; an empty string is no asset.
 #[derive(Default, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum AssetId {
   #[default]
@@ -154,38 +298,134 @@ pub enum AssetId {
   Path(VirtualPath),
 }
 
-impl<A: Asset> AssetRef<A> {
-  /// Creates an asset from a GUID.
-  #[inline]
-  pub fn from_id(id: Guid) -> Self {
+impl Display for AssetId {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      AssetId::None => Ok(()),
+      AssetId::Guid(guid) => write!(formatter, "guid:{guid}"),
+      AssetId::Key(key) => write!(formatter, "key:{key}"),
+      AssetId::Path(path) => write!(formatter, "{path}"),
+    }
+  }
+}
+
+impl FromStr for AssetId {
+  type Err = AssetError;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    if text.is_empty() {
+      return Ok(AssetId::None);
+    }
+
+    if let Some(guid) = text.strip_prefix("guid:") {
+      return Guid::parse_str(guid)
+        .map(AssetId::Guid)
+        .map_err(|_| AssetError::InvalidId);
+    }
+
+    if let Some(key) = text.strip_prefix("key:") {
+      return Ok(AssetId::Key(key.to_string()));
+    }
+
+    match text.contains("://") {
+      true => Ok(AssetId::Path(VirtualPath::new(text))),
+      false => Err(AssetError::InvalidId),
+    }
+  }
+}
+
+impl<T> AssetRef<T> {
+  /// Creates a reference to the asset with the given ID.
+  pub fn new(id: AssetId) -> Self {
     Self {
-      id: AssetId::Guid(id),
-      _marker: std::marker::PhantomData,
+      id,
+      loaded: Arc::new(OnceLock::new()),
     }
   }
 
-  /// Creates an asset from a key.
+  /// Creates a reference from a GUID.
+  #[inline]
+  pub fn from_id(id: Guid) -> Self {
+    Self::new(AssetId::Guid(id))
+  }
+
+  /// Creates a reference from a key.
   #[inline]
   pub fn from_key(key: impl AsRef<str>) -> Self {
-    Self {
-      id: AssetId::Key(key.as_ref().to_string()),
-      _marker: std::marker::PhantomData,
-    }
+    Self::new(AssetId::Key(key.as_ref().to_string()))
   }
 
-  /// Creates an asset from a virtual path.
+  /// Creates a reference from a virtual path.
   #[inline]
   pub fn from_path(path: impl ToVirtualPath) -> Self {
-    Self {
-      id: AssetId::Path(path.to_virtual_path()),
-      _marker: std::marker::PhantomData,
+    Self::new(AssetId::Path(path.to_virtual_path()))
+  }
+
+  /// The ID of the referenced asset.
+  pub fn id(&self) -> &AssetId {
+    &self.id
+  }
+
+  /// Determines if the reference points at nothing.
+  pub fn is_none(&self) -> bool {
+    self.id == AssetId::None
+  }
+
+  /// Determines if the asset has been loaded.
+  pub fn is_loaded(&self) -> bool {
+    self.loaded.get().is_some()
+  }
+
+  /// Converts the reference into its serialized form, tagged with the asset
+  /// type so tools can offer a picker for it; see [`AssetRefField`].
+  pub fn to_chunk(&self) -> Chunk {
+    AssetRefField {
+      asset_type: short_type_name::<T>().to_string(),
+      id: self.id.clone(),
     }
+    .to_chunk()
   }
 
-  /// Resolves the asset from the asset manager.
+  /// Reads a reference from its serialized form, either the tagged form
+  /// written by [`AssetRef::to_chunk`] or just the ID as text.
+  pub fn from_chunk(chunk: &Chunk) -> Result<Self, StreamError> {
+    let field = AssetRefField::from_chunk(chunk).ok_or(StreamError::InvalidData)?;
+
+    Ok(Self::new(field.id))
+  }
+}
+
+impl<A: Asset> AssetRef<A> {
+  /// Resolves a fresh copy of the asset from the asset database.
   pub fn resolve(&self) -> Result<A, AssetError> {
     A::from_id(&self.id)
   }
+
+  /// Gets the asset, loading it the first time it's asked for.
+  ///
+  /// Failures aren't remembered, so a missing asset is looked for again the
+  /// next time, e.g. after it's been imported.
+  pub fn get(&self) -> Result<Arc<A>, AssetError> {
+    if let Some(asset) = self.loaded.get() {
+      return Ok(asset.clone());
+    }
+
+    let asset = Arc::new(self.resolve()?);
+
+    Ok(self.loaded.get_or_init(|| asset).clone())
+  }
+
+  /// Gets the asset, or its placeholder if it's missing or fails to load.
+  pub fn get_or_placeholder(&self) -> Arc<A>
+  where
+    A: AssetPlaceholder,
+  {
+    self.get().unwrap_or_else(|error| {
+      crate::warn!("Using a placeholder for asset {}: {:?}", self.id, error);
+
+      Arc::new(A::placeholder())
+    })
+  }
 }
 
 impl<T> Debug for AssetRef<T> {
@@ -194,6 +434,82 @@ impl<T> Debug for AssetRef<T> {
   }
 }
 
+impl<T> ToVariant for AssetRef<T> {
+  fn to_variant(&self) -> Variant {
+    Variant::String(self.id.to_string())
+  }
+}
+
+impl<T> FromVariant for AssetRef<T> {
+  fn from_variant(variant: Variant) -> Result<Self, VariantError> {
+    match variant {
+      Variant::Null => Ok(Self::default()),
+      Variant::String(text) => text.parse().map(Self::new).map_err(|_| VariantError::InvalidConversion),
+      _ => Err(VariantError::InvalidConversion),
+    }
+  }
+}
+
+/// The type-erased form of an [`AssetRef`] in serialized data.
+///
+/// Inspectors and editors use this to recognise asset fields in a
+/// component's fields and offer a picker of assets of the right type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssetRefField {
+  /// The short name of the asset type, like `Texture`.
+  pub asset_type: String,
+  pub id: AssetId,
+}
+
+impl AssetRefField {
+  /// The type name asset references are tagged with.
+  pub const TYPE_NAME: &'static str = "AssetRef";
+
+  /// Reads an asset field from a chunk, either the tagged form or just the ID
+  /// as text, whose asset type is unknown.
+  pub fn from_chunk(chunk: &Chunk) -> Option<Self> {
+    match chunk {
+      Chunk::Variant(Variant::String(text)) => Some(Self {
+        asset_type: String::new(),
+        id: text.parse().ok()?,
+      }),
+      Chunk::Variant(Variant::Null) => Some(Self {
+        asset_type: String::new(),
+        id: AssetId::None,
+      }),
+      Chunk::Map(_) if chunk.type_name() == Some(Self::TYPE_NAME) => Some(Self {
+        asset_type: chunk.read_field_or("asset", String::new()).ok()?,
+        id: chunk.read_field_or("id", String::new()).ok()?.parse().ok()?,
+      }),
+      _ => None,
+    }
+  }
+
+  /// Converts the field into its tagged form.
+  pub fn to_chunk(&self) -> Chunk {
+    let mut fields = FastHashMap::default();
+
+    fields.insert(
+      RON_TYPE_KEY.to_string(),
+      Chunk::Variant(Variant::String(Self::TYPE_NAME.to_string())),
+    );
+    fields.insert(
+      "asset".to_string(),
+      Chunk::Variant(Variant::String(self.asset_type.clone())),
+    );
+    fields.insert("id".to_string(), Chunk::Variant(Variant::String(self.id.to_string())));
+
+    Chunk::Map(fields)
+  }
+}
+
+/// The name of a type without its module path.
+fn short_type_name<T: ?Sized>() -> &'static str {
+  let name = std::any::type_name::<T>();
+
+  name.rsplit("::").next().unwrap_or(name)
+}
+
 impl<A: FromStream> Asset for A {
   type Decoder = Self;
 }
@@ -203,3 +519,140 @@ impl<A: FromStream> AssetDecoder<A> for A {
     A::from_stream_async(stream).await.map_err(|_| AssetError::LoadFailed)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io::Read,
+    sync::atomic::{AtomicUsize, Ordering},
+  };
+
+  use super::*;
+
+  static IMPORT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+  struct CountingImporter;
+
+  impl AssetImporter for CountingImporter {
+    fn name(&self) -> &str {
+      "counting"
+    }
+
+    fn extensions(&self) -> &[&str] {
+      &["txt"]
+    }
+
+    fn import(&self, _path: &VirtualPath, data: &[u8]) -> Result<ImportedAsset, AssetError> {
+      IMPORT_COUNT.fetch_add(1, Ordering::SeqCst);
+
+      Ok(ImportedAsset {
+        data: data.to_ascii_uppercase(),
+        references: Vec::new(),
+      })
+    }
+  }
+
+  #[test]
+  fn it_should_import_assets_as_they_are_read_and_cache_by_content() {
+    let path = std::env::temp_dir().join(format!("surreal-assets-{}.txt", std::process::id()));
+    let id = AssetId::Path(VirtualPath::new(&path.to_string_lossy()));
+
+    let mut database = AssetDatabase::default();
+    let read = |database: &mut AssetDatabase| {
+      let mut data = Vec::new();
+
+      database.read_asset(&id).unwrap().read_to_end(&mut data).unwrap();
+      data
+    };
+
+    database.register_importer(CountingImporter);
+
+    std::fs::write(&path, b"hello").unwrap();
+
+    assert_eq!(read(&mut database), b"HELLO");
+    assert_eq!(read(&mut database), b"HELLO");
+    assert_eq!(IMPORT_COUNT.load(Ordering::SeqCst), 1);
+
+    std::fs::write(&path, b"world").unwrap();
+
+    assert_eq!(read(&mut database), b"WORLD");
+    assert_eq!(IMPORT_COUNT.load(Ordering::SeqCst), 2);
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(matches!(
+      database.read_asset(&AssetId::Key("missing".to_string())),
+      Err(AssetError::NotFound)
+    ));
+  }
+
+  #[derive(Debug, PartialEq)]
+  struct TextAsset(String);
+
+  impl FromStream for TextAsset {
+    async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+      let mut text = String::new();
+
+      stream.read_to_string(&mut text)?;
+
+      Ok(Self(text))
+    }
+  }
+
+  impl AssetPlaceholder for TextAsset {
+    fn placeholder() -> Self {
+      Self("missing".to_string())
+    }
+  }
+
+  #[test]
+  fn it_should_round_trip_asset_references_through_chunks() {
+    let reference = AssetRef::<TextAsset>::from_id(Guid::from_u128(0x5eed));
+    let chunk = reference.to_chunk();
+    let field = AssetRefField::from_chunk(&chunk).unwrap();
+
+    assert_eq!(field.asset_type, "TextAsset");
+    assert_eq!(&field.id, reference.id());
+    assert_eq!(AssetRef::<TextAsset>::from_chunk(&chunk).unwrap(), reference);
+
+    for id in [
+      AssetId::None,
+      AssetId::Key("player".to_string()),
+      AssetId::Path(VirtualPath::new("local://sprites/player.png")),
+    ] {
+      assert_eq!(id.to_string().parse::<AssetId>().unwrap(), id);
+    }
+
+    let from_text = Chunk::Variant(Variant::String("key:player".to_string()));
+
+    assert_eq!(
+      from_text.read::<AssetRef<TextAsset>>().unwrap(),
+      AssetRef::from_key("player")
+    );
+    assert!(AssetRef::<TextAsset>::from_chunk(&Chunk::Variant(Variant::String("nonsense".to_string()))).is_err());
+  }
+
+  #[test]
+  fn it_should_resolve_lazily_and_fall_back_to_placeholders() {
+    let path = std::env::temp_dir().join(format!("surreal-asset-ref-{}.text", std::process::id()));
+
+    std::fs::write(&path, "hello").unwrap();
+
+    let reference = AssetRef::<TextAsset>::from_path(path.to_string_lossy().as_ref());
+    let shared = reference.clone();
+
+    assert!(!reference.is_loaded());
+    assert_eq!(reference.get().unwrap().0, "hello");
+
+    std::fs::remove_file(&path).unwrap();
+
+    // clones share the loaded asset, so it's not read again
+    assert!(shared.is_loaded());
+    assert_eq!(shared.get_or_placeholder().0, "hello");
+
+    let missing = AssetRef::<TextAsset>::from_key("missing");
+
+    assert_eq!(missing.get_or_placeholder().0, "missing");
+    assert!(!missing.is_loaded());
+  }
+}
@@ -0,0 +1,104 @@
+//! Asset bundles, packed offline so shipping builds don't import at runtime.
+
+use std::collections::BTreeMap;
+
+use crate::{FromStream, InputStream, OutputStream, StreamError, ToStream};
+
+/// The magic number at the start of a serialized [`AssetBundle`].
+const BUNDLE_MAGIC: u32 = 0x444E4253; // 'SBND'
+
+/// The current version of the serialized [`AssetBundle`] format.
+const BUNDLE_VERSION: u16 = 1;
+
+/// A set of imported assets packed into a single file, keyed by path.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AssetBundle {
+  entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl AssetBundle {
+  /// Creates a new, empty bundle.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The number of assets in the bundle.
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  /// Determines if the bundle is empty.
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Adds an imported asset to the bundle under the given path.
+  pub fn insert(&mut self, path: impl Into<String>, data: Vec<u8>) {
+    self.entries.insert(path.into(), data);
+  }
+
+  /// Gets the contents of the asset at the given path, if it's present.
+  pub fn get(&self, path: &str) -> Option<&[u8]> {
+    self.entries.get(path).map(Vec::as_slice)
+  }
+
+  /// Iterates over all assets in the bundle, ordered by path.
+  pub fn iter(&self) -> impl Iterator<Item = (&str, &[u8])> {
+    self.entries.iter().map(|(path, data)| (path.as_str(), data.as_slice()))
+  }
+}
+
+impl FromStream for AssetBundle {
+  async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+    if stream.read_u32()? != BUNDLE_MAGIC || stream.read_u16()? != BUNDLE_VERSION {
+      return Err(StreamError::InvalidData);
+    }
+
+    let count = stream.read_u32()?;
+    let mut entries = BTreeMap::new();
+
+    for _ in 0..count {
+      let path = stream.read_string()?;
+      let length = stream.read_u64()?;
+
+      entries.insert(path, stream.read_bytes(length as usize)?);
+    }
+
+    Ok(Self { entries })
+  }
+}
+
+impl ToStream for AssetBundle {
+  fn to_stream(&self, stream: &mut dyn OutputStream) -> Result<(), Self::Error> {
+    stream.write_u32(BUNDLE_MAGIC)?;
+    stream.write_u16(BUNDLE_VERSION)?;
+    stream.write_u32(self.entries.len() as u32)?;
+
+    for (path, data) in &self.entries {
+      stream.write_string(path)?;
+      stream.write_u64(data.len() as u64)?;
+      stream.write_bytes(data)?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_round_trip_through_streams() {
+    let mut bundle = AssetBundle::new();
+
+    bundle.insert("sprites/bunny.png", b"bunny".to_vec());
+    bundle.insert("fonts/font.otf", b"font".to_vec());
+
+    let bytes = bundle.to_bytes().unwrap();
+    let result = AssetBundle::from_bytes(&bytes).unwrap();
+
+    assert_eq!(result, bundle);
+    assert_eq!(result.get("sprites/bunny.png"), Some(&b"bunny"[..]));
+  }
+}
@@ -0,0 +1,127 @@
+//! Importers that convert source assets into the form the engine loads.
+//!
+//! Importers are registered in an [`AssetImporterRegistry`], which picks one
+//! for each source file by its extension. Tools run the registry offline over
+//! a whole assets folder, so shipping builds only ever load imported data.
+
+use super::*;
+
+/// Converts source assets of some kind into their runtime form.
+pub trait AssetImporter: Send + Sync {
+  /// A unique name for the importer, used to key cached imports.
+  fn name(&self) -> &str;
+
+  /// The version of the importer's output; bump it to invalidate cached
+  /// imports.
+  fn version(&self) -> u32 {
+    1
+  }
+
+  /// The file extensions this importer handles, without the leading dot.
+  fn extensions(&self) -> &[&str];
+
+  /// Imports the contents of the source asset at the given path.
+  fn import(&self, path: &VirtualPath, data: &[u8]) -> Result<ImportedAsset, AssetError>;
+
+  /// Imports the source asset for a specific build target.
+  ///
+  /// Importers that emit different artifacts per target override this; by
+  /// default every target gets the same output.
+  fn import_for_target(
+    &self,
+    path: &VirtualPath,
+    data: &[u8],
+    _target: &TargetProfile,
+  ) -> Result<ImportedAsset, AssetError> {
+    self.import(path, data)
+  }
+}
+
+/// The result of importing a single asset.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportedAsset {
+  /// The imported data, as the engine will load it.
+  pub data: Vec<u8>,
+  /// Paths of other assets this one refers to, relative to the assets root.
+  pub references: Vec<String>,
+}
+
+/// The set of importers known to the asset pipeline.
+#[derive(Default)]
+pub struct AssetImporterRegistry {
+  importers: Vec<Box<dyn AssetImporter>>,
+}
+
+impl AssetImporterRegistry {
+  /// Creates a new, empty registry.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Registers an importer.
+  ///
+  /// Importers registered later take precedence for the same extension, so
+  /// games can override the defaults.
+  pub fn register(&mut self, importer: impl AssetImporter + 'static) {
+    self.importers.push(Box::new(importer));
+  }
+
+  /// Finds the importer for the given source asset, if there is one.
+  pub fn find(&self, path: &VirtualPath) -> Option<&dyn AssetImporter> {
+    self
+      .importers
+      .iter()
+      .rev()
+      .find(|importer| importer.extensions().iter().any(|it| path.has_extension(it)))
+      .map(|importer| importer.as_ref())
+  }
+
+  /// Iterates over all registered importers.
+  pub fn iter(&self) -> impl Iterator<Item = &dyn AssetImporter> {
+    self.importers.iter().map(|importer| importer.as_ref())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct UppercaseImporter(&'static str);
+
+  impl AssetImporter for UppercaseImporter {
+    fn name(&self) -> &str {
+      self.0
+    }
+
+    fn extensions(&self) -> &[&str] {
+      &["txt"]
+    }
+
+    fn import(&self, _path: &VirtualPath, data: &[u8]) -> Result<ImportedAsset, AssetError> {
+      Ok(ImportedAsset {
+        data: data.to_ascii_uppercase(),
+        references: Vec::new(),
+      })
+    }
+  }
+
+  #[test]
+  fn it_should_find_importers_by_extension() {
+    let mut registry = AssetImporterRegistry::new();
+
+    registry.register(UppercaseImporter("default"));
+    registry.register(UppercaseImporter("override"));
+
+    let importer = registry.find(&VirtualPath::new("local://notes.txt")).unwrap();
+
+    assert_eq!(importer.name(), "override");
+    assert!(registry.find(&VirtualPath::new("local://image.png")).is_none());
+    assert_eq!(
+      importer
+        .import(&VirtualPath::new("local://notes.txt"), b"hello")
+        .unwrap()
+        .data,
+      b"HELLO"
+    );
+  }
+}
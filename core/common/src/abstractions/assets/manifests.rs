@@ -0,0 +1,312 @@
+//! Asset manifests for integrity verification and change detection.
+
+use std::collections::BTreeMap;
+
+use crate::{
+  ContentHash, FileSystemError, FromStream, HashAlgorithm, InputStream, OutputStream, StreamError, ToStream,
+  ToVirtualPath, VirtualPath,
+};
+
+/// The magic number at the start of a serialized [`AssetManifest`].
+const MANIFEST_MAGIC: u32 = 0x4E414D53; // 'SMAN'
+
+/// The current version of the serialized [`AssetManifest`] format.
+const MANIFEST_VERSION: u16 = 1;
+
+/// A manifest of content hashes for a set of assets.
+///
+/// Manifests are built over a root directory (or a bundle), shipped alongside
+/// the content, and verified at startup to detect corrupt or tampered files.
+/// Two manifests can also be diffed to work out which assets have changed
+/// between builds, which is the basis for computing patches.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AssetManifest {
+  algorithm: HashAlgorithm,
+  entries: BTreeMap<String, AssetManifestEntry>,
+}
+
+/// A single entry in an [`AssetManifest`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AssetManifestEntry {
+  pub size: u64,
+  pub hash: ContentHash,
+}
+
+/// A problem detected while verifying an [`AssetManifest`].
+#[derive(Debug, Eq, PartialEq)]
+pub enum ManifestViolation {
+  /// The asset is listed in the manifest, but is missing on disk.
+  Missing(String),
+  /// The asset couldn't be read.
+  Unreadable(String),
+  /// The asset has a different size to the one in the manifest.
+  SizeMismatch { path: String, expected: u64, actual: u64 },
+  /// The asset has a different hash to the one in the manifest.
+  HashMismatch {
+    path: String,
+    expected: ContentHash,
+    actual: ContentHash,
+  },
+}
+
+/// The difference between two [`AssetManifest`]s.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct ManifestDiff {
+  pub added: Vec<String>,
+  pub removed: Vec<String>,
+  pub changed: Vec<String>,
+}
+
+impl ManifestDiff {
+  /// Determines if there are no differences between the manifests.
+  pub fn is_empty(&self) -> bool {
+    self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+  }
+}
+
+impl AssetManifest {
+  /// Creates a new empty manifest using the given hashing algorithm.
+  pub fn new(algorithm: HashAlgorithm) -> Self {
+    Self {
+      algorithm,
+      entries: BTreeMap::new(),
+    }
+  }
+
+  /// Builds a manifest from all files under the given root directory.
+  pub fn from_directory(root: impl ToVirtualPath, algorithm: HashAlgorithm) -> Result<Self, FileSystemError> {
+    let root = root.to_virtual_path();
+    let mut manifest = Self::new(algorithm);
+    let mut pending = vec![root.clone()];
+
+    while let Some(directory) = pending.pop() {
+      for path in directory.files() {
+        let bytes = path.read_all_bytes()?;
+
+        manifest.insert(relative_path(&root, &path), &bytes);
+      }
+
+      pending.extend(directory.directories());
+    }
+
+    Ok(manifest)
+  }
+
+  /// The hashing algorithm used by this manifest.
+  pub fn algorithm(&self) -> HashAlgorithm {
+    self.algorithm
+  }
+
+  /// The number of entries in the manifest.
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  /// Determines if the manifest is empty.
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Adds the given asset contents to the manifest under the given path.
+  pub fn insert(&mut self, path: impl Into<String>, data: &[u8]) {
+    self.entries.insert(path.into(), AssetManifestEntry {
+      size: data.len() as u64,
+      hash: ContentHash::of(self.algorithm, data),
+    });
+  }
+
+  /// Gets the entry for the given path, if it's present.
+  pub fn get(&self, path: &str) -> Option<&AssetManifestEntry> {
+    self.entries.get(path)
+  }
+
+  /// Iterates over all entries in the manifest, ordered by path.
+  pub fn iter(&self) -> impl Iterator<Item = (&str, &AssetManifestEntry)> {
+    self.entries.iter().map(|(path, entry)| (path.as_str(), entry))
+  }
+
+  /// Verifies the contents of the given root directory against the manifest.
+  ///
+  /// Files on disk that are not listed in the manifest are ignored.
+  pub fn verify(&self, root: impl ToVirtualPath) -> Result<(), Vec<ManifestViolation>> {
+    let root = root.to_virtual_path();
+    let mut violations = Vec::new();
+
+    for (path, entry) in &self.entries {
+      let full_path = root.join(path);
+
+      if !full_path.exists() {
+        violations.push(ManifestViolation::Missing(path.clone()));
+        continue;
+      }
+
+      let Ok(bytes) = full_path.read_all_bytes() else {
+        violations.push(ManifestViolation::Unreadable(path.clone()));
+        continue;
+      };
+
+      if bytes.len() as u64 != entry.size {
+        violations.push(ManifestViolation::SizeMismatch {
+          path: path.clone(),
+          expected: entry.size,
+          actual: bytes.len() as u64,
+        });
+        continue;
+      }
+
+      let actual = ContentHash::of(self.algorithm, &bytes);
+
+      if actual != entry.hash {
+        violations.push(ManifestViolation::HashMismatch {
+          path: path.clone(),
+          expected: entry.hash,
+          actual,
+        });
+      }
+    }
+
+    if violations.is_empty() {
+      Ok(())
+    } else {
+      Err(violations)
+    }
+  }
+
+  /// Computes which assets differ between this manifest and a newer one.
+  pub fn diff(&self, newer: &AssetManifest) -> ManifestDiff {
+    let mut diff = ManifestDiff::default();
+
+    for (path, entry) in &newer.entries {
+      match self.entries.get(path) {
+        None => diff.added.push(path.clone()),
+        Some(existing) if existing != entry => diff.changed.push(path.clone()),
+        Some(_) => {}
+      }
+    }
+
+    for path in self.entries.keys() {
+      if !newer.entries.contains_key(path) {
+        diff.removed.push(path.clone());
+      }
+    }
+
+    diff
+  }
+}
+
+impl FromStream for AssetManifest {
+  async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+    if stream.read_u32()? != MANIFEST_MAGIC || stream.read_u16()? != MANIFEST_VERSION {
+      return Err(StreamError::InvalidData);
+    }
+
+    let algorithm = HashAlgorithm::from_byte(stream.read_u8()?).ok_or(StreamError::InvalidData)?;
+
+    let count = stream.read_u32()?;
+    let mut entries = BTreeMap::new();
+
+    for _ in 0..count {
+      let path = stream.read_string()?;
+      let size = stream.read_u64()?;
+      let hash = ContentHash::read_from(stream)?;
+
+      entries.insert(path, AssetManifestEntry { size, hash });
+    }
+
+    Ok(Self { algorithm, entries })
+  }
+}
+
+impl ToStream for AssetManifest {
+  fn to_stream(&self, stream: &mut dyn OutputStream) -> Result<(), Self::Error> {
+    stream.write_u32(MANIFEST_MAGIC)?;
+    stream.write_u16(MANIFEST_VERSION)?;
+    stream.write_u8(self.algorithm as u8)?;
+    stream.write_u32(self.entries.len() as u32)?;
+
+    for (path, entry) in &self.entries {
+      stream.write_string(path)?;
+      stream.write_u64(entry.size)?;
+      entry.hash.write_to(stream)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Computes the path of the given file relative to the given root directory.
+pub(super) fn relative_path(root: &VirtualPath, path: &VirtualPath) -> String {
+  let root = root.location().replace('\\', "/");
+  let path = path.location().replace('\\', "/");
+
+  path
+    .strip_prefix(&root)
+    .map(|relative| relative.trim_start_matches('/'))
+    .unwrap_or(&path)
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_diff_manifests() {
+    let mut old = AssetManifest::new(HashAlgorithm::XxHash3);
+    let mut new = AssetManifest::new(HashAlgorithm::XxHash3);
+
+    old.insert("a.png", b"aaaa");
+    old.insert("b.png", b"bbbb");
+    old.insert("c.png", b"cccc");
+
+    new.insert("a.png", b"aaaa");
+    new.insert("b.png", b"BBBB");
+    new.insert("d.png", b"dddd");
+
+    let diff = old.diff(&new);
+
+    assert_eq!(diff.added, vec!["d.png"]);
+    assert_eq!(diff.removed, vec!["c.png"]);
+    assert_eq!(diff.changed, vec!["b.png"]);
+  }
+
+  #[test]
+  fn it_should_round_trip_through_streams() {
+    let mut manifest = AssetManifest::new(HashAlgorithm::Blake3);
+
+    manifest.insert("sprites/bunny.png", b"bunny");
+    manifest.insert("fonts/font.otf", b"font");
+
+    let bytes = manifest.to_bytes().unwrap();
+    let result = AssetManifest::from_bytes(&bytes).unwrap();
+
+    assert_eq!(manifest, result);
+  }
+
+  #[test]
+  fn it_should_verify_files_on_disk() {
+    let root = std::env::temp_dir().join(format!("surreal-manifest-{}", std::process::id()));
+
+    std::fs::create_dir_all(root.join("nested")).unwrap();
+    std::fs::write(root.join("a.txt"), b"Hello").unwrap();
+    std::fs::write(root.join("nested/b.txt"), b"World").unwrap();
+
+    let root_path = VirtualPath::new(&root.to_string_lossy());
+    let manifest = AssetManifest::from_directory(&root_path, HashAlgorithm::Blake3).unwrap();
+
+    assert_eq!(manifest.len(), 2);
+    assert!(manifest.get("nested/b.txt").is_some());
+    assert!(manifest.verify(&root_path).is_ok());
+
+    std::fs::write(root.join("nested/b.txt"), b"Earth").unwrap();
+    std::fs::remove_file(root.join("a.txt")).unwrap();
+
+    let violations = manifest.verify(&root_path).unwrap_err();
+
+    std::fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(violations.len(), 2);
+    assert_eq!(violations[0], ManifestViolation::Missing("a.txt".to_string()));
+    assert!(matches!(violations[1], ManifestViolation::HashMismatch { .. }));
+  }
+}
@@ -0,0 +1,117 @@
+//! Build targets that assets are imported for.
+//!
+//! One assets folder serves every platform: importers emit different
+//! artifacts per [`TargetProfile`] (e.g. indexed sprites for GBA, RGBA
+//! elsewhere), and source files can be swapped out entirely with variants
+//! named for a target, like `hero.gba.png` in place of `hero.png`.
+
+/// How images are stored for a build target.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ImageTarget {
+  /// 32-bit RGBA.
+  #[default]
+  Rgba32,
+  /// Indexed color, with a palette of at most the given number of colors.
+  Indexed { colors: usize },
+}
+
+/// A build target that assets are imported for.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TargetProfile {
+  /// The name of the target, also used to pick out its asset variants.
+  pub name: String,
+  /// How images are stored on the target.
+  pub images: ImageTarget,
+}
+
+impl Default for TargetProfile {
+  fn default() -> Self {
+    Self::desktop()
+  }
+}
+
+impl TargetProfile {
+  /// The names of the built-in targets.
+  pub const BUILT_IN: [&'static str; 3] = ["desktop", "web", "gba"];
+
+  /// The desktop target.
+  pub fn desktop() -> Self {
+    Self {
+      name: "desktop".to_string(),
+      images: ImageTarget::Rgba32,
+    }
+  }
+
+  /// The web target.
+  pub fn web() -> Self {
+    Self {
+      name: "web".to_string(),
+      images: ImageTarget::Rgba32,
+    }
+  }
+
+  /// The Game Boy Advance target, with 16 color (4bpp) sprites.
+  pub fn gba() -> Self {
+    Self {
+      name: "gba".to_string(),
+      images: ImageTarget::Indexed { colors: 16 },
+    }
+  }
+
+  /// Finds a built-in target by name.
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "desktop" => Some(Self::desktop()),
+      "web" => Some(Self::web()),
+      "gba" => Some(Self::gba()),
+      _ => None,
+    }
+  }
+
+  /// Splits a variant path like `sprites/hero.gba.png` into its target and
+  /// the path it stands in for, `sprites/hero.png`.
+  ///
+  /// Only built-in target names, and this target's own name, are recognized.
+  pub fn split_variant<'a>(&self, path: &'a str) -> Option<(&'a str, String)> {
+    let (folder, name) = match path.rsplit_once('/') {
+      Some((folder, name)) => (Some(folder), name),
+      None => (None, path),
+    };
+
+    let (stem, extension) = name.rsplit_once('.')?;
+    let (base, target) = stem.rsplit_once('.')?;
+
+    if base.is_empty() || !(Self::BUILT_IN.contains(&target) || target == self.name) {
+      return None;
+    }
+
+    let file = format!("{base}.{extension}");
+
+    Some((target, match folder {
+      Some(folder) => format!("{folder}/{file}"),
+      None => file,
+    }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_split_variants_by_target() {
+    let target = TargetProfile::gba();
+
+    assert_eq!(
+      target.split_variant("sprites/hero.gba.png"),
+      Some(("gba", "sprites/hero.png".to_string()))
+    );
+    assert_eq!(
+      target.split_variant("music.web.ogg"),
+      Some(("web", "music.ogg".to_string()))
+    );
+    assert_eq!(target.split_variant("sprites/hero.png"), None);
+    assert_eq!(target.split_variant("level.v2.ron"), None);
+    assert_eq!(target.split_variant(".gba.png"), None);
+  }
+}
@@ -0,0 +1,757 @@
+//! Incremental updates for installed asset bundles.
+//!
+//! An [`Updater`] downloads a [`PatchManifest`] from some [`PatchSource`],
+//! works out which chunks of the locally installed bundles differ from the
+//! published ones by comparing content hashes, fetches only those chunks (as
+//! binary patches where the source offers them), and then swaps the rebuilt
+//! bundles into place once every one of them has been staged and verified.
+
+use std::collections::BTreeMap;
+
+use super::manifests::relative_path;
+use crate::{
+  BinaryPatch, ContentHash, FileSystemError, FromStream, HashAlgorithm, InputStream, OutputStream, PatchError,
+  StreamError, ToStream, ToVirtualPath, VirtualPath,
+};
+
+/// The magic number at the start of a serialized [`PatchManifest`].
+const PATCH_MANIFEST_MAGIC: u32 = 0x44505553; // 'SUPD'
+
+/// The current version of the serialized [`PatchManifest`] format.
+const PATCH_MANIFEST_VERSION: u16 = 1;
+
+/// The default size of the chunks that bundles are split into.
+const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
+
+/// The most chunk hashes reserved up front when reading a manifest, so a
+/// malformed count can't allocate unbounded memory before the stream runs out.
+const MAX_PREALLOCATED_CHUNKS: usize = 1024;
+
+/// The extension given to bundles while they're being staged.
+const STAGING_EXTENSION: &str = "staging";
+
+/// The extension given to installed bundles while they're being replaced.
+const BACKUP_EXTENSION: &str = "backup";
+
+/// An error that can occur while updating.
+#[derive(Debug)]
+pub enum UpdateError {
+  /// The patch manifest couldn't be fetched from the source.
+  ManifestUnavailable,
+  /// A chunk couldn't be fetched from the source.
+  ChunkUnavailable {
+    bundle: String,
+    index: usize,
+  },
+  /// Fetched or rebuilt content didn't match the manifest.
+  VerificationFailed {
+    bundle: String,
+  },
+  FileSystemError(FileSystemError),
+  PatchError(PatchError),
+}
+
+crate::impl_error_coercion!(FileSystemError into UpdateError);
+crate::impl_error_coercion!(PatchError into UpdateError);
+
+/// Describes the published state of a set of bundles, chunk by chunk.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PatchManifest {
+  chunk_size: u32,
+  algorithm: HashAlgorithm,
+  bundles: BTreeMap<String, BundleDescriptor>,
+}
+
+/// Describes a single published bundle in a [`PatchManifest`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct BundleDescriptor {
+  pub size: u64,
+  pub hash: ContentHash,
+  pub chunks: Vec<ContentHash>,
+}
+
+impl BundleDescriptor {
+  /// Describes the given bundle contents.
+  pub fn describe(data: &[u8], chunk_size: u32, algorithm: HashAlgorithm) -> Self {
+    Self {
+      size: data.len() as u64,
+      hash: ContentHash::of(algorithm, data),
+      chunks: data
+        .chunks(chunk_size as usize)
+        .map(|chunk| ContentHash::of(algorithm, chunk))
+        .collect(),
+    }
+  }
+}
+
+impl PatchManifest {
+  /// Creates a new empty manifest.
+  pub fn new(chunk_size: u32, algorithm: HashAlgorithm) -> Self {
+    Self {
+      chunk_size: chunk_size.max(1),
+      algorithm,
+      bundles: BTreeMap::new(),
+    }
+  }
+
+  /// Builds a manifest describing every file in the given directory.
+  ///
+  /// Bundles are installed side by side, so subdirectories aren't included.
+  pub fn from_directory(root: impl ToVirtualPath, algorithm: HashAlgorithm) -> Result<Self, FileSystemError> {
+    let root = root.to_virtual_path();
+    let mut manifest = Self::new(DEFAULT_CHUNK_SIZE, algorithm);
+
+    for path in root.files() {
+      let bytes = path.read_all_bytes()?;
+
+      manifest.insert(relative_path(&root, &path), &bytes);
+    }
+
+    Ok(manifest)
+  }
+
+  /// The size of the chunks that bundles are split into.
+  pub fn chunk_size(&self) -> u32 {
+    self.chunk_size
+  }
+
+  /// The hashing algorithm used by this manifest.
+  pub fn algorithm(&self) -> HashAlgorithm {
+    self.algorithm
+  }
+
+  /// Adds the given bundle contents to the manifest.
+  pub fn insert(&mut self, name: impl Into<String>, data: &[u8]) {
+    let descriptor = BundleDescriptor::describe(data, self.chunk_size, self.algorithm);
+
+    self.bundles.insert(name.into(), descriptor);
+  }
+
+  /// Gets the descriptor for the given bundle.
+  pub fn get(&self, name: &str) -> Option<&BundleDescriptor> {
+    self.bundles.get(name)
+  }
+
+  /// Iterates over all bundles in the manifest, ordered by name.
+  pub fn iter(&self) -> impl Iterator<Item = (&str, &BundleDescriptor)> {
+    self.bundles.iter().map(|(name, bundle)| (name.as_str(), bundle))
+  }
+}
+
+impl FromStream for PatchManifest {
+  async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+    if stream.read_u32()? != PATCH_MANIFEST_MAGIC || stream.read_u16()? != PATCH_MANIFEST_VERSION {
+      return Err(StreamError::InvalidData);
+    }
+
+    let chunk_size = stream.read_u32()?;
+
+    if chunk_size == 0 {
+      return Err(StreamError::InvalidData);
+    }
+
+    let algorithm = HashAlgorithm::from_byte(stream.read_u8()?).ok_or(StreamError::InvalidData)?;
+    let bundle_count = stream.read_u32()?;
+    let mut bundles = BTreeMap::new();
+
+    for _ in 0..bundle_count {
+      let name = stream.read_string()?;
+
+      if !is_valid_bundle_name(&name) {
+        return Err(StreamError::InvalidData);
+      }
+
+      let size = stream.read_u64()?;
+      let hash = ContentHash::read_from(stream)?;
+      let chunk_count = stream.read_u32()?;
+      let mut chunks = Vec::with_capacity((chunk_count as usize).min(MAX_PREALLOCATED_CHUNKS));
+
+      for _ in 0..chunk_count {
+        chunks.push(ContentHash::read_from(stream)?);
+      }
+
+      bundles.insert(name, BundleDescriptor { size, hash, chunks });
+    }
+
+    Ok(Self {
+      chunk_size,
+      algorithm,
+      bundles,
+    })
+  }
+}
+
+/// Determines if a bundle name from a manifest is safe to install, i.e. it
+/// names a file directly in the install root rather than a path elsewhere.
+fn is_valid_bundle_name(name: &str) -> bool {
+  !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', ':'])
+}
+
+impl ToStream for PatchManifest {
+  fn to_stream(&self, stream: &mut dyn OutputStream) -> Result<(), Self::Error> {
+    stream.write_u32(PATCH_MANIFEST_MAGIC)?;
+    stream.write_u16(PATCH_MANIFEST_VERSION)?;
+    stream.write_u32(self.chunk_size)?;
+    stream.write_u8(self.algorithm as u8)?;
+    stream.write_u32(self.bundles.len() as u32)?;
+
+    for (name, bundle) in &self.bundles {
+      stream.write_string(name)?;
+      stream.write_u64(bundle.size)?;
+      bundle.hash.write_to(stream)?;
+      stream.write_u32(bundle.chunks.len() as u32)?;
+
+      for chunk in &bundle.chunks {
+        chunk.write_to(stream)?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// A source of published bundles and patches.
+///
+/// Implement this over whatever transport a game ships with (HTTP, a CDN, a
+/// platform content service); [`DirectoryPatchSource`] serves a mirror on the
+/// virtual file system.
+pub trait PatchSource {
+  /// Fetches the latest [`PatchManifest`].
+  fn fetch_manifest(&self) -> Result<PatchManifest, UpdateError>;
+
+  /// Fetches the full contents of a single chunk of a bundle.
+  fn fetch_chunk(&self, manifest: &PatchManifest, bundle: &str, index: usize) -> Result<Vec<u8>, UpdateError>;
+
+  /// Fetches a patch from a chunk with the given hash to the published chunk.
+  ///
+  /// Returns `None` if the source has no such patch, in which case the whole
+  /// chunk is fetched instead.
+  #[allow(unused_variables)]
+  fn fetch_patch(&self, bundle: &str, index: usize, from: &ContentHash) -> Result<Option<BinaryPatch>, UpdateError> {
+    Ok(None)
+  }
+}
+
+/// A [`PatchSource`] that serves a mirror directory.
+///
+/// The directory contains a `patch.manifest`, the published bundles side by
+/// side, and optionally a `patches` folder of chunk patches named
+/// `<bundle>.<chunk index>.<source chunk hash>.patch`.
+pub struct DirectoryPatchSource {
+  root: VirtualPath,
+}
+
+impl DirectoryPatchSource {
+  /// The name of the manifest file in the mirror directory.
+  pub const MANIFEST_NAME: &'static str = "patch.manifest";
+
+  /// Creates a new source over the given mirror directory.
+  pub fn new(root: impl ToVirtualPath) -> Self {
+    Self {
+      root: root.to_virtual_path(),
+    }
+  }
+
+  /// The path of the patch for the given chunk of a bundle.
+  pub fn patch_path(&self, bundle: &str, index: usize, from: &ContentHash) -> VirtualPath {
+    let bundle = bundle.replace(['/', '\\'], "_");
+
+    self.root.join(&format!("patches/{}.{}.{}.patch", bundle, index, from))
+  }
+}
+
+impl PatchSource for DirectoryPatchSource {
+  fn fetch_manifest(&self) -> Result<PatchManifest, UpdateError> {
+    PatchManifest::from_path(self.root.join(Self::MANIFEST_NAME)).map_err(|_| UpdateError::ManifestUnavailable)
+  }
+
+  fn fetch_chunk(&self, manifest: &PatchManifest, bundle: &str, index: usize) -> Result<Vec<u8>, UpdateError> {
+    let unavailable = || UpdateError::ChunkUnavailable {
+      bundle: bundle.to_string(),
+      index,
+    };
+
+    let descriptor = manifest.get(bundle).ok_or_else(unavailable)?;
+    let offset = index as u64 * manifest.chunk_size() as u64;
+    let length = (descriptor.size.saturating_sub(offset)).min(manifest.chunk_size() as u64);
+
+    let mut stream = self.root.join(bundle).open_input_stream().map_err(|_| unavailable())?;
+
+    stream
+      .seek(std::io::SeekFrom::Start(offset))
+      .map_err(|_| unavailable())?;
+
+    stream.read_bytes(length as usize).map_err(|_| unavailable())
+  }
+
+  fn fetch_patch(&self, bundle: &str, index: usize, from: &ContentHash) -> Result<Option<BinaryPatch>, UpdateError> {
+    let path = self.patch_path(bundle, index, from);
+
+    if !path.exists() {
+      return Ok(None);
+    }
+
+    Ok(BinaryPatch::from_path(path).ok())
+  }
+}
+
+/// The set of changes needed to bring the installed bundles up to date.
+pub struct UpdatePlan {
+  manifest: PatchManifest,
+  bundles: Vec<BundleUpdate>,
+}
+
+/// The changes needed to bring a single installed bundle up to date.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BundleUpdate {
+  pub name: String,
+  pub changed_chunks: Vec<usize>,
+}
+
+impl UpdatePlan {
+  /// Determines if the installed bundles are already up to date.
+  pub fn is_up_to_date(&self) -> bool {
+    self.bundles.is_empty()
+  }
+
+  /// The bundles that need updating.
+  pub fn bundles(&self) -> &[BundleUpdate] {
+    &self.bundles
+  }
+
+  /// The manifest the plan was computed against.
+  pub fn manifest(&self) -> &PatchManifest {
+    &self.manifest
+  }
+}
+
+/// A summary of an applied update.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct UpdateReport {
+  pub bundles_updated: usize,
+  pub chunks_patched: usize,
+  pub chunks_downloaded: usize,
+  pub bytes_downloaded: u64,
+}
+
+/// Updates locally installed bundles from a [`PatchSource`].
+pub struct Updater<S> {
+  source: S,
+  install_root: VirtualPath,
+}
+
+impl<S: PatchSource> Updater<S> {
+  /// Creates a new updater for bundles installed under the given directory.
+  pub fn new(source: S, install_root: impl ToVirtualPath) -> Self {
+    Self {
+      source,
+      install_root: install_root.to_virtual_path(),
+    }
+  }
+
+  /// Checks for updates and applies them, if there are any.
+  pub fn update(&self) -> Result<UpdateReport, UpdateError> {
+    let plan = self.check()?;
+
+    if plan.is_up_to_date() {
+      return Ok(UpdateReport::default());
+    }
+
+    self.apply(&plan)
+  }
+
+  /// Fetches the latest manifest and works out which chunks have changed.
+  pub fn check(&self) -> Result<UpdatePlan, UpdateError> {
+    let manifest = self.source.fetch_manifest()?;
+    let mut bundles = Vec::new();
+
+    for (name, descriptor) in manifest.iter() {
+      let local = self.read_installed(name)?;
+
+      if local.len() as u64 == descriptor.size && ContentHash::of(manifest.algorithm(), &local) == descriptor.hash {
+        continue;
+      }
+
+      let local_chunks = local.chunks(manifest.chunk_size() as usize).collect::<Vec<_>>();
+      let changed_chunks = descriptor
+        .chunks
+        .iter()
+        .enumerate()
+        .filter(|(index, expected)| match local_chunks.get(*index) {
+          Some(chunk) => ContentHash::of(manifest.algorithm(), chunk) != **expected,
+          None => true,
+        })
+        .map(|(index, _)| index)
+        .collect();
+
+      bundles.push(BundleUpdate {
+        name: name.to_string(),
+        changed_chunks,
+      });
+    }
+
+    Ok(UpdatePlan { manifest, bundles })
+  }
+
+  /// Applies the given plan.
+  ///
+  /// Every bundle is rebuilt into a staging file and verified before any of
+  /// them are swapped into place. Installed bundles are moved aside while
+  /// they're replaced, and moved back if any swap fails, so a failed update
+  /// leaves the previous installation in place (short of the file system
+  /// failing again while rolling back).
+  pub fn apply(&self, plan: &UpdatePlan) -> Result<UpdateReport, UpdateError> {
+    let mut report = UpdateReport::default();
+    let mut staged = Vec::with_capacity(plan.bundles.len());
+
+    for update in &plan.bundles {
+      match self.stage_bundle(&plan.manifest, update, &mut report) {
+        Ok(path) => staged.push((path, self.install_root.join(&update.name))),
+        Err(error) => {
+          for (staging_path, _) in &staged {
+            let _ = staging_path.delete();
+          }
+
+          return Err(error);
+        }
+      }
+    }
+
+    let mut swapped = Vec::with_capacity(staged.len());
+
+    for (staging_path, target_path) in &staged {
+      match swap_bundle(staging_path, target_path) {
+        Ok(backup_path) => swapped.push((target_path, backup_path)),
+        Err(error) => {
+          // put back the bundles that were already swapped, newest first
+          for (target_path, backup_path) in swapped.iter().rev() {
+            let _ = match backup_path {
+              Some(backup_path) => backup_path.rename_to(target_path),
+              None => target_path.delete(),
+            };
+          }
+
+          for (staging_path, _) in &staged {
+            let _ = staging_path.delete();
+          }
+
+          return Err(error);
+        }
+      }
+    }
+
+    for (_, backup_path) in swapped {
+      if let Some(backup_path) = backup_path {
+        let _ = backup_path.delete();
+      }
+
+      report.bundles_updated += 1;
+    }
+
+    Ok(report)
+  }
+
+  /// Rebuilds a single bundle into its staging file, returning its path.
+  fn stage_bundle(
+    &self,
+    manifest: &PatchManifest,
+    update: &BundleUpdate,
+    report: &mut UpdateReport,
+  ) -> Result<VirtualPath, UpdateError> {
+    let descriptor = manifest
+      .get(&update.name)
+      .ok_or_else(|| UpdateError::VerificationFailed {
+        bundle: update.name.clone(),
+      })?;
+
+    let local = self.read_installed(&update.name)?;
+    let local_chunks = local.chunks(manifest.chunk_size() as usize).collect::<Vec<_>>();
+    // the published size is untrusted, so size the output after what's
+    // installed
+    let mut output = Vec::with_capacity(local.len());
+
+    for (index, expected) in descriptor.chunks.iter().enumerate() {
+      let current = local_chunks.get(index).copied();
+
+      if update.changed_chunks.contains(&index) {
+        let chunk = self.fetch_chunk(manifest, &update.name, index, current, expected, report)?;
+
+        output.extend_from_slice(&chunk);
+      } else {
+        output.extend_from_slice(current.unwrap_or_default());
+      }
+    }
+
+    if output.len() as u64 != descriptor.size || ContentHash::of(manifest.algorithm(), &output) != descriptor.hash {
+      return Err(UpdateError::VerificationFailed {
+        bundle: update.name.clone(),
+      });
+    }
+
+    let staging_path = self.install_root.join(&update.name).append_extension(STAGING_EXTENSION);
+    let mut stream = staging_path.open_output_stream()?;
+
+    stream.write_bytes(&output).map_err(FileSystemError::from)?;
+    stream.flush().map_err(FileSystemError::from)?;
+
+    Ok(staging_path)
+  }
+
+  /// Fetches a changed chunk, preferring a patch against the installed chunk.
+  fn fetch_chunk(
+    &self,
+    manifest: &PatchManifest,
+    bundle: &str,
+    index: usize,
+    current: Option<&[u8]>,
+    expected: &ContentHash,
+    report: &mut UpdateReport,
+  ) -> Result<Vec<u8>, UpdateError> {
+    let algorithm = manifest.algorithm();
+
+    if let Some(current) = current {
+      let current_hash = ContentHash::of(algorithm, current);
+
+      if let Some(patch) = self.source.fetch_patch(bundle, index, &current_hash)? {
+        if let Ok(chunk) = patch.apply(current) {
+          if ContentHash::of(algorithm, &chunk) == *expected {
+            report.chunks_patched += 1;
+            report.bytes_downloaded += patch.literal_size() as u64;
+
+            return Ok(chunk);
+          }
+        }
+      }
+    }
+
+    let chunk = self.source.fetch_chunk(manifest, bundle, index)?;
+
+    if ContentHash::of(algorithm, &chunk) != *expected {
+      return Err(UpdateError::VerificationFailed {
+        bundle: bundle.to_string(),
+      });
+    }
+
+    report.chunks_downloaded += 1;
+    report.bytes_downloaded += chunk.len() as u64;
+
+    Ok(chunk)
+  }
+
+  /// Reads the installed copy of the given bundle, or nothing if it's missing.
+  fn read_installed(&self, name: &str) -> Result<Vec<u8>, UpdateError> {
+    let path = self.install_root.join(name);
+
+    if !path.exists() {
+      return Ok(Vec::new());
+    }
+
+    Ok(path.read_all_bytes()?)
+  }
+}
+
+/// Swaps a staged bundle into place, moving any installed bundle aside first.
+///
+/// Returns where the installed bundle was moved to, if there was one.
+fn swap_bundle(staging_path: &VirtualPath, target_path: &VirtualPath) -> Result<Option<VirtualPath>, UpdateError> {
+  let backup_path = if target_path.exists() {
+    let backup_path = target_path.append_extension(BACKUP_EXTENSION);
+
+    target_path.rename_to(&backup_path)?;
+
+    Some(backup_path)
+  } else {
+    None
+  };
+
+  if let Err(error) = staging_path.rename_to(target_path) {
+    if let Some(backup_path) = &backup_path {
+      let _ = backup_path.rename_to(target_path);
+    }
+
+    return Err(error.into());
+  }
+
+  Ok(backup_path)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Creates a fresh temporary directory for a test.
+  fn temp_directory(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("surreal-updater-{}-{}", name, std::process::id()));
+
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+
+    path
+  }
+
+  /// Builds some bundle content of the given size.
+  fn bundle_content(size: usize, seed: u8) -> Vec<u8> {
+    (0..size)
+      .map(|index| (index as u8).wrapping_mul(31).wrapping_add(seed))
+      .collect()
+  }
+
+  #[test]
+  fn it_should_round_trip_patch_manifests() {
+    let mut manifest = PatchManifest::new(16, HashAlgorithm::XxHash3);
+
+    manifest.insert("core.bundle", &bundle_content(100, 0));
+
+    let bytes = manifest.to_bytes().unwrap();
+
+    assert_eq!(PatchManifest::from_bytes(&bytes).unwrap(), manifest);
+  }
+
+  #[test]
+  fn it_should_reject_malformed_patch_manifests() {
+    let mut manifest = PatchManifest::new(16, HashAlgorithm::XxHash3);
+
+    manifest.insert("core.bundle", &bundle_content(100, 0));
+
+    // the chunk size follows the magic number and version
+    let mut bytes = manifest.to_bytes().unwrap();
+
+    bytes[6..10].fill(0);
+
+    assert!(PatchManifest::from_bytes(&bytes).is_err());
+
+    for name in ["../escape.bundle", "nested/core.bundle", "..", ""] {
+      let mut manifest = PatchManifest::new(16, HashAlgorithm::XxHash3);
+
+      manifest.insert(name, &bundle_content(100, 0));
+
+      assert!(PatchManifest::from_bytes(&manifest.to_bytes().unwrap()).is_err());
+    }
+  }
+
+  #[test]
+  fn it_should_update_only_changed_chunks() {
+    let mirror = temp_directory("mirror");
+    let install = temp_directory("install");
+
+    let old_bundle = bundle_content(200_000, 0);
+    let mut new_bundle = old_bundle.clone();
+
+    new_bundle[70_000] ^= 0xFF;
+    new_bundle.extend_from_slice(b"appended content");
+
+    std::fs::write(install.join("core.bundle"), &old_bundle).unwrap();
+    std::fs::write(mirror.join("core.bundle"), &new_bundle).unwrap();
+    std::fs::write(mirror.join("extra.bundle"), b"brand new bundle").unwrap();
+
+    let mirror_path = VirtualPath::new(&mirror.to_string_lossy());
+    let install_path = VirtualPath::new(&install.to_string_lossy());
+
+    let manifest = PatchManifest::from_directory(&mirror_path, HashAlgorithm::Blake3).unwrap();
+    manifest
+      .to_path(mirror_path.join(DirectoryPatchSource::MANIFEST_NAME))
+      .unwrap();
+
+    let updater = Updater::new(DirectoryPatchSource::new(&mirror_path), &install_path);
+    let plan = updater.check().unwrap();
+
+    assert_eq!(plan.bundles(), &[
+      BundleUpdate {
+        name: "core.bundle".to_string(),
+        changed_chunks: vec![1, 3],
+      },
+      BundleUpdate {
+        name: "extra.bundle".to_string(),
+        changed_chunks: vec![0],
+      },
+    ]);
+
+    let report = updater.apply(&plan).unwrap();
+
+    assert_eq!(report.bundles_updated, 2);
+    assert_eq!(report.chunks_downloaded, 3);
+    assert_eq!(std::fs::read(install.join("core.bundle")).unwrap(), new_bundle);
+    assert_eq!(
+      std::fs::read(install.join("extra.bundle")).unwrap(),
+      b"brand new bundle"
+    );
+    assert!(updater.check().unwrap().is_up_to_date());
+
+    std::fs::remove_dir_all(&mirror).unwrap();
+    std::fs::remove_dir_all(&install).unwrap();
+  }
+
+  #[test]
+  fn it_should_prefer_patches_over_full_chunks() {
+    let mirror = temp_directory("patches");
+    let install = temp_directory("patched");
+
+    let old_bundle = bundle_content(4096, 0);
+    let mut new_bundle = old_bundle.clone();
+
+    new_bundle[2000] ^= 0xFF;
+
+    std::fs::write(install.join("core.bundle"), &old_bundle).unwrap();
+    std::fs::write(mirror.join("core.bundle"), &new_bundle).unwrap();
+    std::fs::create_dir_all(mirror.join("patches")).unwrap();
+
+    let mirror_path = VirtualPath::new(&mirror.to_string_lossy());
+    let install_path = VirtualPath::new(&install.to_string_lossy());
+    let source = DirectoryPatchSource::new(&mirror_path);
+
+    let mut manifest = PatchManifest::new(DEFAULT_CHUNK_SIZE, HashAlgorithm::Blake3);
+    manifest.insert("core.bundle", &new_bundle);
+    manifest
+      .to_path(mirror_path.join(DirectoryPatchSource::MANIFEST_NAME))
+      .unwrap();
+
+    let old_hash = ContentHash::of(HashAlgorithm::Blake3, &old_bundle);
+    BinaryPatch::compute(&old_bundle, &new_bundle)
+      .to_path(source.patch_path("core.bundle", 0, &old_hash))
+      .unwrap();
+
+    let report = Updater::new(source, &install_path).update().unwrap();
+
+    assert_eq!(report.chunks_patched, 1);
+    assert_eq!(report.chunks_downloaded, 0);
+    assert!(report.bytes_downloaded < 1024);
+    assert_eq!(std::fs::read(install.join("core.bundle")).unwrap(), new_bundle);
+
+    std::fs::remove_dir_all(&mirror).unwrap();
+    std::fs::remove_dir_all(&install).unwrap();
+  }
+
+  #[test]
+  fn it_should_roll_back_failed_installs() {
+    let mirror = temp_directory("rollback-mirror");
+    let install = temp_directory("rollback-install");
+
+    std::fs::write(install.join("core.bundle"), b"old core").unwrap();
+    std::fs::write(install.join("extra.bundle"), b"old extra").unwrap();
+    std::fs::write(mirror.join("core.bundle"), b"new core").unwrap();
+    std::fs::write(mirror.join("extra.bundle"), b"new extra").unwrap();
+
+    // a directory in the way of the backup makes the second swap fail
+    std::fs::create_dir_all(install.join("extra.bundle.backup").join("blocked")).unwrap();
+
+    let mirror_path = VirtualPath::new(&mirror.to_string_lossy());
+    let install_path = VirtualPath::new(&install.to_string_lossy());
+
+    PatchManifest::from_directory(&mirror_path, HashAlgorithm::Blake3)
+      .unwrap()
+      .to_path(mirror_path.join(DirectoryPatchSource::MANIFEST_NAME))
+      .unwrap();
+
+    let updater = Updater::new(DirectoryPatchSource::new(&mirror_path), &install_path);
+
+    assert!(updater.update().is_err());
+    assert_eq!(std::fs::read(install.join("core.bundle")).unwrap(), b"old core");
+    assert_eq!(std::fs::read(install.join("extra.bundle")).unwrap(), b"old extra");
+    assert!(!install.join("core.bundle.backup").exists());
+    assert!(!install.join("core.bundle.staging").exists());
+    assert!(!install.join("extra.bundle.staging").exists());
+
+    std::fs::remove_dir_all(&mirror).unwrap();
+    std::fs::remove_dir_all(&install).unwrap();
+  }
+}
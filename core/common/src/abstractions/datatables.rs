@@ -0,0 +1,318 @@
+//! Tables of gameplay data, like enemy stats and item prices.
+//!
+//! Balance data lives in CSV or RON files rather than Rust constants, so it
+//! can be tuned without a rebuild. Each row is read into a type implementing
+//! [`DataRow`], and looked up by its key:
+//!
+//! ```rust,ignore
+//! struct EnemyStats {
+//!   id: String,
+//!   health: u32,
+//!   speed: f32,
+//! }
+//!
+//! impl DataRow for EnemyStats {
+//!   type Key = String;
+//!
+//!   fn key(&self) -> String {
+//!     self.id.clone()
+//!   }
+//!
+//!   fn from_chunk(chunk: &Chunk) -> Result<Self, StreamError> {
+//!     Ok(Self {
+//!       id: chunk.read_field("id")?,
+//!       health: chunk.read_field("health")?,
+//!       speed: chunk.read_field_or("speed", 1.)?,
+//!     })
+//!   }
+//! }
+//!
+//! let mut enemies = DataTable::<EnemyStats>::from_path("assets/enemies.csv")?;
+//!
+//! // each frame, or when the file watcher says so
+//! enemies.reload()?;
+//!
+//! let goblin = enemies.get(&"goblin".to_string());
+//! ```
+//!
+//! In RON, a table is a list of rows, `[(id: "goblin", health: 10), ...]`.
+
+use std::hash::Hash;
+
+use crate::{
+  Chunk, ContentHash, CsvFormat, FastHashMap, Format, FromStream, HashAlgorithm, InputStream, RonFormat, StreamError,
+  ToVirtualPath, VirtualPath,
+};
+
+/// An error that occurs while loading a data table.
+#[derive(Debug)]
+pub enum DataTableError {
+  StreamError(StreamError),
+  /// The file's extension isn't a known table format.
+  UnsupportedFormat(String),
+  /// A row couldn't be read; the index is zero-based, excluding any header.
+  InvalidRow(usize, StreamError),
+  /// Two rows share a key; the second row's index is recorded.
+  DuplicateKey(usize),
+}
+
+crate::impl_error_coercion!(StreamError into DataTableError);
+
+/// A row of a [`DataTable`].
+pub trait DataRow: Sized {
+  /// The type rows are looked up by.
+  type Key: Clone + Eq + Hash;
+
+  /// The key of this row, unique within its table.
+  fn key(&self) -> Self::Key;
+
+  /// Reads a row from a [`Chunk`] mapping column names to values.
+  fn from_chunk(chunk: &Chunk) -> Result<Self, StreamError>;
+}
+
+/// A table of strongly-typed rows, loaded from a CSV or RON file.
+pub struct DataTable<R: DataRow> {
+  rows: Vec<R>,
+  index: FastHashMap<R::Key, usize>,
+  source: Option<DataSource>,
+}
+
+/// Where a [`DataTable`] was loaded from, for reloading it.
+struct DataSource {
+  path: VirtualPath,
+  hash: ContentHash,
+}
+
+impl<R: DataRow> Default for DataTable<R> {
+  fn default() -> Self {
+    Self {
+      rows: Vec::new(),
+      index: FastHashMap::default(),
+      source: None,
+    }
+  }
+}
+
+impl<R: DataRow> DataTable<R> {
+  /// Builds a table from a sequence of rows.
+  pub fn from_chunk(chunk: &Chunk) -> Result<Self, DataTableError> {
+    let rows = chunk.as_sequence().ok_or(StreamError::InvalidData)?;
+    let mut table = Self::default();
+
+    for (index, row) in rows.iter().enumerate() {
+      let row = R::from_chunk(row).map_err(|error| DataTableError::InvalidRow(index, error))?;
+
+      if table.index.insert(row.key(), index).is_some() {
+        return Err(DataTableError::DuplicateKey(index));
+      }
+
+      table.rows.push(row);
+    }
+
+    Ok(table)
+  }
+
+  /// Reads a table from CSV text.
+  pub fn from_csv_str(text: &str) -> Result<Self, DataTableError> {
+    Self::from_format::<CsvFormat>(text.as_bytes())
+  }
+
+  /// Reads a table from RON text.
+  pub fn from_ron_str(text: &str) -> Result<Self, DataTableError> {
+    Self::from_format::<RonFormat>(text.as_bytes())
+  }
+
+  /// Loads a table from a `.csv` or `.ron` file, remembering the path so the
+  /// table can be reloaded.
+  pub fn from_path(path: impl ToVirtualPath) -> Result<Self, DataTableError> {
+    let path = path.to_virtual_path();
+    let data = path.read_all_bytes().map_err(StreamError::from)?;
+
+    let mut table = Self::from_data(&path, &data)?;
+
+    table.source = Some(DataSource {
+      hash: ContentHash::of(HashAlgorithm::XxHash3, &data),
+      path,
+    });
+
+    Ok(table)
+  }
+
+  /// Reloads the table from the file it was loaded from, if the file has
+  /// changed, returning whether it did.
+  ///
+  /// If the edited file can't be read the error is returned and the table
+  /// keeps its existing rows, so a typo mid-edit doesn't take the game down.
+  pub fn reload(&mut self) -> Result<bool, DataTableError> {
+    let Some(source) = &self.source else {
+      return Ok(false);
+    };
+
+    let data = source.path.read_all_bytes().map_err(StreamError::from)?;
+    let hash = ContentHash::of(HashAlgorithm::XxHash3, &data);
+
+    if hash == source.hash {
+      return Ok(false);
+    }
+
+    let table = Self::from_data(&source.path, &data)?;
+
+    self.rows = table.rows;
+    self.index = table.index;
+    self.source = Some(DataSource {
+      path: source.path.clone(),
+      hash,
+    });
+
+    Ok(true)
+  }
+
+  /// The file the table was loaded from, if any.
+  pub fn path(&self) -> Option<&VirtualPath> {
+    self.source.as_ref().map(|source| &source.path)
+  }
+
+  /// Looks up the row with the given key.
+  pub fn get(&self, key: &R::Key) -> Option<&R> {
+    self.index.get(key).map(|index| &self.rows[*index])
+  }
+
+  /// Determines if there's a row with the given key.
+  pub fn contains_key(&self, key: &R::Key) -> bool {
+    self.index.contains_key(key)
+  }
+
+  /// The rows of the table, in file order.
+  pub fn rows(&self) -> &[R] {
+    &self.rows
+  }
+
+  /// Iterates over the rows of the table, in file order.
+  pub fn iter(&self) -> impl Iterator<Item = &R> {
+    self.rows.iter()
+  }
+
+  /// The number of rows in the table.
+  pub fn len(&self) -> usize {
+    self.rows.len()
+  }
+
+  /// Determines if the table has no rows.
+  pub fn is_empty(&self) -> bool {
+    self.rows.is_empty()
+  }
+
+  fn from_format<F: Format + Default>(data: &[u8]) -> Result<Self, DataTableError> {
+    let chunk = F::default().read_chunk(&mut std::io::Cursor::new(data))?;
+
+    Self::from_chunk(&chunk)
+  }
+
+  fn from_data(path: &VirtualPath, data: &[u8]) -> Result<Self, DataTableError> {
+    match path.extension() {
+      "csv" => Self::from_format::<CsvFormat>(data),
+      "ron" => Self::from_format::<RonFormat>(data),
+      extension => Err(DataTableError::UnsupportedFormat(extension.to_string())),
+    }
+  }
+}
+
+impl<R: DataRow> FromStream for DataTable<R> {
+  type Error = DataTableError;
+
+  /// Reads a table from a stream, which holds RON if it starts with a list
+  /// and CSV otherwise.
+  async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+    let mut text = String::new();
+
+    stream.read_to_string(&mut text).map_err(StreamError::from)?;
+
+    match text.trim_start().starts_with('[') {
+      true => Self::from_ron_str(&text),
+      false => Self::from_csv_str(&text),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Debug, PartialEq)]
+  struct ItemPrice {
+    id: String,
+    price: u32,
+    tradeable: bool,
+  }
+
+  impl DataRow for ItemPrice {
+    type Key = String;
+
+    fn key(&self) -> String {
+      self.id.clone()
+    }
+
+    fn from_chunk(chunk: &Chunk) -> Result<Self, StreamError> {
+      Ok(Self {
+        id: chunk.read_field("id")?,
+        price: chunk.read_field("price")?,
+        tradeable: chunk.read_field_or("tradeable", true)?,
+      })
+    }
+  }
+
+  #[test]
+  fn it_should_read_rows_from_csv_and_ron() {
+    let csv = DataTable::<ItemPrice>::from_csv_str("id,price,tradeable\nsword,100,true\nrelic,5000,false\n").unwrap();
+    let ron = DataTable::<ItemPrice>::from_ron_str(
+      r#"[(id: "sword", price: 100), (id: "relic", price: 5000, tradeable: false)]"#,
+    )
+    .unwrap();
+
+    for table in [&csv, &ron] {
+      assert_eq!(table.len(), 2);
+      assert_eq!(table.get(&"sword".to_string()).unwrap().price, 100);
+      assert!(!table.get(&"relic".to_string()).unwrap().tradeable);
+      assert!(!table.contains_key(&"shield".to_string()));
+    }
+
+    assert_eq!(csv.rows(), ron.rows());
+  }
+
+  #[test]
+  fn it_should_reject_bad_rows_and_duplicate_keys() {
+    assert!(matches!(
+      DataTable::<ItemPrice>::from_csv_str("id,price\nsword,100\nshield,cheap\n"),
+      Err(DataTableError::InvalidRow(1, _))
+    ));
+    assert!(matches!(
+      DataTable::<ItemPrice>::from_csv_str("id,price\nsword,100\nsword,200\n"),
+      Err(DataTableError::DuplicateKey(1))
+    ));
+  }
+
+  #[test]
+  fn it_should_reload_when_the_file_changes() {
+    let path = std::env::temp_dir().join(format!("surreal-datatable-{}.csv", std::process::id()));
+    let path = VirtualPath::new(&path.to_string_lossy());
+
+    std::fs::write(path.location(), "id,price\nsword,100\n").unwrap();
+
+    let mut table = DataTable::<ItemPrice>::from_path(path.clone()).unwrap();
+
+    assert!(!table.reload().unwrap());
+
+    std::fs::write(path.location(), "id,price\nsword,150\nshield,80\n").unwrap();
+
+    assert!(table.reload().unwrap());
+    assert_eq!(table.get(&"sword".to_string()).unwrap().price, 150);
+    assert_eq!(table.len(), 2);
+
+    std::fs::write(path.location(), "id,price\nsword,oops\n").unwrap();
+
+    assert!(table.reload().is_err());
+    assert_eq!(table.get(&"sword".to_string()).unwrap().price, 150);
+
+    std::fs::remove_file(path.location()).unwrap();
+  }
+}
@@ -0,0 +1,703 @@
+//! Persistent player profiles.
+//!
+//! A [`PlayerProfile`] holds what belongs to the player rather than to a
+//! playthrough: their settings, what they've unlocked and their lifetime
+//! statistics. It's stored apart from save slots, so deleting a save never
+//! loses an unlock.
+//!
+//! The [`ProfileService`] keeps profiles in a local [`ProfileStore`] and can
+//! sync them through a [`ProfileSyncBackend`], like a synced folder, the
+//! platform's cloud storage or a custom web service:
+//!
+//! ```rust,ignore
+//! let mut profiles = ProfileService::new(ProfileStore::new("local://profiles"))
+//!   .with_sync(FolderSyncBackend::new("local://cloud/profiles"))
+//!   .with_policy(ConflictPolicy::Merge);
+//!
+//! let mut profile = profiles.load("player-1")?;
+//!
+//! profile.unlock("hard-mode");
+//! profile.add_statistic("enemies-defeated", 1.);
+//!
+//! profiles.save(&mut profile)?;
+//! profiles.sync(&mut profile)?;
+//! ```
+
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  io::Write,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+  BinaryFormat, Chunk, ContentHash, FastHashMap, FileSystemError, Format, HashAlgorithm, StreamError, ToVariant,
+  ToVirtualPath, Variant, VirtualPath,
+};
+
+/// The bytes every encoded profile starts with.
+const MAGIC: &[u8; 4] = b"SPRF";
+
+/// The length of the header before the payload, with the content hash.
+const HEADER_SIZE: usize = MAGIC.len() + 16;
+
+/// An error when loading, saving or syncing a profile.
+#[derive(Debug)]
+pub enum ProfileError {
+  /// The profile's data failed its checksum, or couldn't be read.
+  Corrupt,
+  FileSystemError(FileSystemError),
+  StreamError(StreamError),
+  /// The sync backend failed, e.g. it's offline.
+  SyncFailed(String),
+}
+
+crate::impl_error_coercion!(FileSystemError into ProfileError);
+crate::impl_error_coercion!(StreamError into ProfileError);
+
+impl From<std::io::Error> for ProfileError {
+  #[inline]
+  fn from(error: std::io::Error) -> Self {
+    Self::FileSystemError(FileSystemError::IoError(error))
+  }
+}
+
+/// The settings, unlocks and statistics of a single player.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerProfile {
+  pub id: String,
+  /// Incremented every time the profile is saved.
+  pub revision: u64,
+  /// When the profile was last saved, in seconds since the Unix epoch.
+  pub modified: u64,
+  /// The revision last pushed to or pulled from the sync backend.
+  synced_revision: u64,
+  settings: FastHashMap<String, Variant>,
+  unlocks: BTreeSet<String>,
+  statistics: BTreeMap<String, f64>,
+}
+
+impl PlayerProfile {
+  /// Creates an empty profile.
+  pub fn new(id: impl Into<String>) -> Self {
+    Self {
+      id: id.into(),
+      ..Default::default()
+    }
+  }
+
+  /// Gets a setting, if it's been set.
+  pub fn setting(&self, name: &str) -> Option<&Variant> {
+    self.settings.get(name)
+  }
+
+  /// Sets a setting.
+  ///
+  /// Only plain values persist; callables and pointers fail to save.
+  pub fn set_setting(&mut self, name: impl Into<String>, value: impl ToVariant) {
+    self.settings.insert(name.into(), value.to_variant());
+  }
+
+  /// Unlocks something, returning whether it was newly unlocked.
+  pub fn unlock(&mut self, name: impl Into<String>) -> bool {
+    self.unlocks.insert(name.into())
+  }
+
+  /// Determines if something has been unlocked.
+  pub fn is_unlocked(&self, name: &str) -> bool {
+    self.unlocks.contains(name)
+  }
+
+  /// Iterates over everything unlocked, in name order.
+  pub fn unlocks(&self) -> impl Iterator<Item = &str> {
+    self.unlocks.iter().map(String::as_str)
+  }
+
+  /// Gets a statistic, which starts at zero.
+  pub fn statistic(&self, name: &str) -> f64 {
+    self.statistics.get(name).copied().unwrap_or_default()
+  }
+
+  /// Sets a statistic.
+  pub fn set_statistic(&mut self, name: impl Into<String>, value: f64) {
+    self.statistics.insert(name.into(), value);
+  }
+
+  /// Adds to a statistic, returning the new value.
+  pub fn add_statistic(&mut self, name: impl Into<String>, amount: f64) -> f64 {
+    let value = self.statistics.entry(name.into()).or_default();
+
+    *value += amount;
+    *value
+  }
+
+  /// Determines if the profile has changed since it was last synced.
+  pub fn has_unsynced_changes(&self) -> bool {
+    self.revision != self.synced_revision
+  }
+
+  /// Combines two copies of a profile that changed independently.
+  ///
+  /// Unlocks are kept from both, statistics take the larger value, and
+  /// settings come from whichever copy was saved last.
+  pub fn merge(&self, other: &PlayerProfile) -> PlayerProfile {
+    let (older, newer) = match (self.modified, self.revision) >= (other.modified, other.revision) {
+      true => (other, self),
+      false => (self, other),
+    };
+
+    let mut merged = newer.clone();
+
+    merged.unlocks.extend(older.unlocks.iter().cloned());
+
+    for (name, &value) in &older.statistics {
+      let statistic = merged.statistics.entry(name.clone()).or_insert(value);
+
+      *statistic = statistic.max(value);
+    }
+
+    merged.revision = self.revision.max(other.revision);
+    merged
+  }
+
+  /// Encodes the profile with a checksum, for storage or syncing.
+  pub fn encode(&self) -> Result<Vec<u8>, ProfileError> {
+    let mut payload = std::io::Cursor::new(Vec::new());
+
+    BinaryFormat::default().write_chunk(&mut payload, &self.to_chunk())?;
+
+    let payload = payload.into_inner();
+    let hash = ContentHash::of(HashAlgorithm::XxHash3, &payload);
+
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + payload.len());
+
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(hash.as_bytes());
+    bytes.extend_from_slice(&payload);
+
+    Ok(bytes)
+  }
+
+  /// Decodes a profile encoded with [`PlayerProfile::encode`], checking it
+  /// wasn't corrupted along the way.
+  pub fn decode(bytes: &[u8]) -> Result<Self, ProfileError> {
+    if bytes.len() < HEADER_SIZE || &bytes[..MAGIC.len()] != MAGIC {
+      return Err(ProfileError::Corrupt);
+    }
+
+    let (header, payload) = bytes.split_at(HEADER_SIZE);
+
+    if ContentHash::of(HashAlgorithm::XxHash3, payload).as_bytes() != &header[MAGIC.len()..] {
+      return Err(ProfileError::Corrupt);
+    }
+
+    let chunk = BinaryFormat::default()
+      .read_chunk(&mut std::io::Cursor::new(payload))
+      .map_err(|_| ProfileError::Corrupt)?;
+
+    Self::from_chunk(&chunk).map_err(|_| ProfileError::Corrupt)
+  }
+
+  fn to_chunk(&self) -> Chunk {
+    let mut fields = FastHashMap::default();
+
+    let settings = self
+      .settings
+      .iter()
+      .map(|(name, value)| (name.clone(), Chunk::Variant(value.clone())))
+      .collect();
+
+    let unlocks = self
+      .unlocks
+      .iter()
+      .map(|name| Chunk::Variant(Variant::String(name.clone())))
+      .collect();
+
+    let statistics = self
+      .statistics
+      .iter()
+      .map(|(name, &value)| (name.clone(), Chunk::Variant(Variant::F64(value))))
+      .collect();
+
+    fields.insert("id".to_string(), Chunk::Variant(Variant::String(self.id.clone())));
+    fields.insert("revision".to_string(), Chunk::Variant(Variant::U64(self.revision)));
+    fields.insert("modified".to_string(), Chunk::Variant(Variant::U64(self.modified)));
+    fields.insert(
+      "synced_revision".to_string(),
+      Chunk::Variant(Variant::U64(self.synced_revision)),
+    );
+    fields.insert("settings".to_string(), Chunk::Map(settings));
+    fields.insert("unlocks".to_string(), Chunk::Sequence(unlocks));
+    fields.insert("statistics".to_string(), Chunk::Map(statistics));
+
+    Chunk::Map(fields)
+  }
+
+  fn from_chunk(chunk: &Chunk) -> Result<Self, StreamError> {
+    let map = |key: &str| match chunk.get(key) {
+      Some(Chunk::Map(map)) => Ok(map),
+      _ => Err(StreamError::InvalidData),
+    };
+
+    let settings = map("settings")?
+      .iter()
+      .map(|(name, value)| match value {
+        Chunk::Variant(value) => Ok((name.clone(), value.clone())),
+        _ => Err(StreamError::InvalidData),
+      })
+      .collect::<Result<_, _>>()?;
+
+    let unlocks = chunk
+      .get("unlocks")
+      .and_then(Chunk::as_sequence)
+      .ok_or(StreamError::InvalidData)?
+      .iter()
+      .map(Chunk::read::<String>)
+      .collect::<Result<_, _>>()?;
+
+    let statistics = map("statistics")?
+      .iter()
+      .map(|(name, value)| Ok((name.clone(), value.read::<f64>()?)))
+      .collect::<Result<_, StreamError>>()?;
+
+    Ok(Self {
+      id: chunk.read_field("id")?,
+      revision: chunk.read_field("revision")?,
+      modified: chunk.read_field("modified")?,
+      synced_revision: chunk.read_field("synced_revision")?,
+      settings,
+      unlocks,
+      statistics,
+    })
+  }
+}
+
+/// How to resolve a profile that changed both locally and remotely since it
+/// was last synced.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ConflictPolicy {
+  /// Keep the local copy, overwriting the remote one.
+  PreferLocal,
+  /// Keep the remote copy, discarding local changes.
+  PreferRemote,
+  /// Keep whichever copy was saved last.
+  PreferNewest,
+  /// Combine both copies; see [`PlayerProfile::merge`].
+  #[default]
+  Merge,
+}
+
+impl ConflictPolicy {
+  /// Resolves two conflicting copies of a profile into one.
+  pub fn resolve(&self, local: &PlayerProfile, remote: &PlayerProfile) -> PlayerProfile {
+    let mut resolved = match self {
+      Self::PreferLocal => local.clone(),
+      Self::PreferRemote => remote.clone(),
+      Self::PreferNewest if (remote.modified, remote.revision) > (local.modified, local.revision) => remote.clone(),
+      Self::PreferNewest => local.clone(),
+      Self::Merge => local.merge(remote),
+    };
+
+    // the result is newer than both copies, so it wins on every device
+    resolved.revision = local.revision.max(remote.revision) + 1;
+    resolved
+  }
+}
+
+/// Syncs encoded profiles with remote storage, like the platform's cloud
+/// storage or a custom web service.
+///
+/// Backends only move bytes; the [`ProfileService`] decides what to push and
+/// pull, and resolves conflicts.
+pub trait ProfileSyncBackend: Send {
+  /// Downloads the encoded profile, if the backend has a copy.
+  fn pull(&mut self, id: &str) -> Result<Option<Vec<u8>>, ProfileError>;
+
+  /// Uploads the encoded profile, replacing the backend's copy.
+  fn push(&mut self, id: &str, data: &[u8]) -> Result<(), ProfileError>;
+}
+
+/// A [`ProfileSyncBackend`] backed by a folder, like one kept in sync by a
+/// desktop cloud client or shared between builds on the same machine.
+pub struct FolderSyncBackend {
+  root: VirtualPath,
+}
+
+impl FolderSyncBackend {
+  /// Creates a backend that syncs into the given folder.
+  pub fn new(root: impl ToVirtualPath) -> Self {
+    Self {
+      root: root.to_virtual_path(),
+    }
+  }
+}
+
+impl ProfileSyncBackend for FolderSyncBackend {
+  fn pull(&mut self, id: &str) -> Result<Option<Vec<u8>>, ProfileError> {
+    let path = self.root.join(&format!("{id}.profile"));
+
+    match path.exists() {
+      true => Ok(Some(path.read_all_bytes()?)),
+      false => Ok(None),
+    }
+  }
+
+  fn push(&mut self, id: &str, data: &[u8]) -> Result<(), ProfileError> {
+    write_bytes(&self.root.join(&format!("{id}.profile")), data)
+  }
+}
+
+/// Local storage for profiles, resilient to interrupted writes.
+///
+/// Each profile is double-buffered across two slots. A save always writes the
+/// slot not holding the latest good copy, and loading takes the newest slot
+/// that passes its checksum, so a crash or power loss mid-save falls back to
+/// the previous save rather than losing the profile.
+pub struct ProfileStore {
+  root: VirtualPath,
+}
+
+/// The size of the generation counter before each slot's profile.
+const GENERATION_SIZE: usize = 8;
+
+impl ProfileStore {
+  /// Creates a store that keeps profiles in the given folder.
+  pub fn new(root: impl ToVirtualPath) -> Self {
+    Self {
+      root: root.to_virtual_path(),
+    }
+  }
+
+  /// Loads the newest good copy of a profile, if there is one.
+  ///
+  /// Returns [`ProfileError::Corrupt`] if there are copies but none of them
+  /// are good.
+  pub fn load(&self, id: &str) -> Result<Option<PlayerProfile>, ProfileError> {
+    let slots = [self.read_slot(id, 0)?, self.read_slot(id, 1)?];
+
+    let newest = slots
+      .iter()
+      .filter_map(|slot| match slot {
+        Slot::Good(generation, profile) => Some((generation, profile)),
+        _ => None,
+      })
+      .max_by_key(|(generation, _)| **generation);
+
+    match newest {
+      Some((_, profile)) => Ok(Some(profile.clone())),
+      None if slots.iter().any(|slot| matches!(slot, Slot::Corrupt)) => Err(ProfileError::Corrupt),
+      None => Ok(None),
+    }
+  }
+
+  /// Writes a profile to the slot not holding its newest good copy.
+  pub fn write(&self, profile: &PlayerProfile) -> Result<(), ProfileError> {
+    let generations = [0, 1].map(|slot| match self.read_slot(&profile.id, slot) {
+      Ok(Slot::Good(generation, _)) => Some(generation),
+      _ => None,
+    });
+
+    let (slot, generation) = match generations {
+      [Some(a), Some(b)] if a >= b => (1, a + 1),
+      [Some(_), Some(b)] => (0, b + 1),
+      [Some(a), None] => (1, a + 1),
+      [None, Some(b)] => (0, b + 1),
+      [None, None] => (0, 1),
+    };
+
+    let mut bytes = u64::to_le_bytes(generation).to_vec();
+
+    bytes.extend(profile.encode()?);
+
+    write_bytes(&self.slot_path(&profile.id, slot), &bytes)
+  }
+
+  fn slot_path(&self, id: &str, slot: usize) -> VirtualPath {
+    self.root.join(&format!("{id}.profile.{slot}"))
+  }
+
+  fn read_slot(&self, id: &str, slot: usize) -> Result<Slot, ProfileError> {
+    let path = self.slot_path(id, slot);
+
+    if !path.exists() {
+      return Ok(Slot::Missing);
+    }
+
+    let bytes = path.read_all_bytes()?;
+
+    if bytes.len() < GENERATION_SIZE {
+      return Ok(Slot::Corrupt);
+    }
+
+    let (generation, profile) = bytes.split_at(GENERATION_SIZE);
+    let generation = u64::from_le_bytes(generation.try_into().unwrap());
+
+    Ok(match PlayerProfile::decode(profile) {
+      Ok(profile) => Slot::Good(generation, profile),
+      Err(_) => Slot::Corrupt,
+    })
+  }
+}
+
+/// The contents of one of a profile's slots in a [`ProfileStore`].
+enum Slot {
+  Missing,
+  Corrupt,
+  /// A profile that passed its checksum, and the generation it was written in.
+  Good(u64, PlayerProfile),
+}
+
+/// Writes all the bytes to a path, flushing before returning.
+fn write_bytes(path: &VirtualPath, bytes: &[u8]) -> Result<(), ProfileError> {
+  let mut stream = path.open_output_stream()?;
+
+  stream.write_all(bytes)?;
+  stream.flush()?;
+
+  Ok(())
+}
+
+/// The result of syncing a profile.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SyncOutcome {
+  /// Neither copy had changed.
+  UpToDate,
+  /// The local copy was uploaded.
+  Pushed,
+  /// The remote copy was downloaded.
+  Pulled,
+  /// Both copies had changed, and were resolved by the [`ConflictPolicy`].
+  Resolved,
+}
+
+/// Loads, saves and syncs player profiles.
+pub struct ProfileService {
+  store: ProfileStore,
+  sync: Option<Box<dyn ProfileSyncBackend>>,
+  policy: ConflictPolicy,
+}
+
+impl ProfileService {
+  /// Creates a service over the given local store, with no sync backend.
+  pub fn new(store: ProfileStore) -> Self {
+    Self {
+      store,
+      sync: None,
+      policy: ConflictPolicy::default(),
+    }
+  }
+
+  /// Syncs profiles through the given backend.
+  pub fn with_sync(mut self, backend: impl ProfileSyncBackend + 'static) -> Self {
+    self.sync = Some(Box::new(backend));
+    self
+  }
+
+  /// Resolves sync conflicts with the given policy.
+  pub fn with_policy(mut self, policy: ConflictPolicy) -> Self {
+    self.policy = policy;
+    self
+  }
+
+  /// Loads a profile, or creates a new one if there isn't one yet.
+  ///
+  /// If every local copy is corrupt, the profile is restored from the sync
+  /// backend where there is one.
+  pub fn load(&mut self, id: &str) -> Result<PlayerProfile, ProfileError> {
+    match self.store.load(id) {
+      Ok(Some(profile)) => Ok(profile),
+      Ok(None) => Ok(PlayerProfile::new(id)),
+      Err(ProfileError::Corrupt) => {
+        let backend = self.sync.as_mut().ok_or(ProfileError::Corrupt)?;
+        let data = backend.pull(id)?.ok_or(ProfileError::Corrupt)?;
+        let profile = PlayerProfile::decode(&data)?;
+
+        self.store.write(&profile)?;
+
+        Ok(profile)
+      }
+      Err(error) => Err(error),
+    }
+  }
+
+  /// Saves a profile locally, as a new revision.
+  pub fn save(&mut self, profile: &mut PlayerProfile) -> Result<(), ProfileError> {
+    profile.revision += 1;
+    profile.modified = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_secs())
+      .unwrap_or_default();
+
+    self.store.write(profile)
+  }
+
+  /// Syncs a profile with the backend, if there is one.
+  ///
+  /// Whichever copy changed since the last sync wins; if both did, they're
+  /// resolved by the [`ConflictPolicy`]. A corrupt remote copy is replaced by
+  /// the local one.
+  pub fn sync(&mut self, profile: &mut PlayerProfile) -> Result<SyncOutcome, ProfileError> {
+    let Some(backend) = self.sync.as_mut() else {
+      return Ok(SyncOutcome::UpToDate);
+    };
+
+    let remote = backend
+      .pull(&profile.id)?
+      .and_then(|data| PlayerProfile::decode(&data).ok());
+    let base = profile.synced_revision;
+
+    let outcome = match remote {
+      Some(remote) if remote.revision == base && !profile.has_unsynced_changes() => return Ok(SyncOutcome::UpToDate),
+      Some(remote) if remote.revision == base => SyncOutcome::Pushed,
+      Some(remote) if !profile.has_unsynced_changes() => {
+        *profile = remote;
+        SyncOutcome::Pulled
+      }
+      Some(remote) => {
+        *profile = self.policy.resolve(profile, &remote);
+        SyncOutcome::Resolved
+      }
+      None => SyncOutcome::Pushed,
+    };
+
+    profile.synced_revision = profile.revision;
+
+    if outcome != SyncOutcome::Pulled {
+      backend.push(&profile.id, &profile.encode()?)?;
+    }
+
+    self.store.write(profile)?;
+
+    Ok(outcome)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  use super::*;
+
+  /// A sync backend shared between two services, like two devices.
+  #[derive(Clone, Default)]
+  struct SharedBackend(Arc<Mutex<FastHashMap<String, Vec<u8>>>>);
+
+  impl ProfileSyncBackend for SharedBackend {
+    fn pull(&mut self, id: &str) -> Result<Option<Vec<u8>>, ProfileError> {
+      Ok(self.0.lock().unwrap().get(id).cloned())
+    }
+
+    fn push(&mut self, id: &str, data: &[u8]) -> Result<(), ProfileError> {
+      self.0.lock().unwrap().insert(id.to_string(), data.to_vec());
+
+      Ok(())
+    }
+  }
+
+  fn temp_folder(name: &str) -> VirtualPath {
+    let path = std::env::temp_dir().join(format!("surreal-profiles-{name}-{}", std::process::id()));
+
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+
+    VirtualPath::new(&path.to_string_lossy())
+  }
+
+  #[test]
+  fn it_should_round_trip_profiles() {
+    let mut profile = PlayerProfile::new("player");
+
+    profile.set_setting("volume", 0.5f32);
+    profile.unlock("hard-mode");
+    profile.add_statistic("jumps", 3.);
+
+    let mut bytes = profile.encode().unwrap();
+
+    assert_eq!(PlayerProfile::decode(&bytes).unwrap(), profile);
+
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+
+    assert!(matches!(PlayerProfile::decode(&bytes), Err(ProfileError::Corrupt)));
+  }
+
+  #[test]
+  fn it_should_fall_back_to_the_previous_slot_when_corrupt() {
+    let folder = temp_folder("slots");
+    let mut service = ProfileService::new(ProfileStore::new(folder.clone()));
+    let mut profile = service.load("player").unwrap();
+
+    profile.unlock("level-2");
+    service.save(&mut profile).unwrap();
+
+    profile.unlock("level-3");
+    service.save(&mut profile).unwrap();
+
+    // tear the newest write, which went to the second slot
+    write_bytes(&folder.join("player.profile.1"), b"torn").unwrap();
+
+    let recovered = service.load("player").unwrap();
+
+    assert!(recovered.is_unlocked("level-2"));
+    assert!(!recovered.is_unlocked("level-3"));
+
+    write_bytes(&folder.join("player.profile.0"), b"torn").unwrap();
+
+    assert!(matches!(service.load("player"), Err(ProfileError::Corrupt)));
+  }
+
+  #[test]
+  fn it_should_sync_and_merge_conflicts_between_devices() {
+    let cloud = SharedBackend::default();
+    let mut desktop = ProfileService::new(ProfileStore::new(temp_folder("desktop"))).with_sync(cloud.clone());
+    let mut handheld = ProfileService::new(ProfileStore::new(temp_folder("handheld"))).with_sync(cloud.clone());
+
+    let mut first = desktop.load("player").unwrap();
+
+    first.unlock("castle");
+    first.set_statistic("jumps", 10.);
+    desktop.save(&mut first).unwrap();
+
+    assert_eq!(desktop.sync(&mut first).unwrap(), SyncOutcome::Pushed);
+    assert_eq!(desktop.sync(&mut first).unwrap(), SyncOutcome::UpToDate);
+
+    let mut second = handheld.load("player").unwrap();
+
+    assert_eq!(handheld.sync(&mut second).unwrap(), SyncOutcome::Pulled);
+    assert!(second.is_unlocked("castle"));
+
+    // both devices play offline, then sync
+    first.unlock("forest");
+    desktop.save(&mut first).unwrap();
+
+    second.unlock("desert");
+    second.set_statistic("jumps", 25.);
+    handheld.save(&mut second).unwrap();
+
+    assert_eq!(desktop.sync(&mut first).unwrap(), SyncOutcome::Pushed);
+    assert_eq!(handheld.sync(&mut second).unwrap(), SyncOutcome::Resolved);
+    assert_eq!(second.unlocks().collect::<Vec<_>>(), vec!["castle", "desert", "forest"]);
+    assert_eq!(second.statistic("jumps"), 25.);
+
+    assert_eq!(desktop.sync(&mut first).unwrap(), SyncOutcome::Pulled);
+    assert_eq!(first, second);
+  }
+
+  #[test]
+  fn it_should_restore_corrupt_profiles_from_the_backend() {
+    let cloud = SharedBackend::default();
+    let folder = temp_folder("restore");
+    let mut service = ProfileService::new(ProfileStore::new(folder.clone())).with_sync(cloud);
+    let mut profile = service.load("player").unwrap();
+
+    profile.unlock("secret");
+    service.save(&mut profile).unwrap();
+    service.sync(&mut profile).unwrap();
+
+    for slot in 0..2 {
+      write_bytes(&folder.join(&format!("player.profile.{slot}")), b"torn").unwrap();
+    }
+
+    assert!(service.load("player").unwrap().is_unlocked("secret"));
+    assert!(ProfileStore::new(folder).load("player").unwrap().is_some());
+  }
+}
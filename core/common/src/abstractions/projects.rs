@@ -0,0 +1,437 @@
+//! Project definitions, shared by the runtime, the editor and the tools.
+//!
+//! A project is described by a `Surreal.toml` file at its root, so every
+//! entry point agrees on where its assets live and how it's built:
+//!
+//! ```toml
+//! [project]
+//! name = "Demo"
+//! version = "0.1.0"
+//! startup_scene = "scenes/main.ron"
+//! targets = ["desktop", "web"]
+//! plugins = ["physics"]
+//!
+//! [[assets]]
+//! name = "game"
+//! path = "assets"
+//!
+//! [[assets]]
+//! name = "shared"
+//! path = "../shared/assets"
+//!
+//! [build]
+//! output = "target/content"
+//! ```
+//!
+//! Everything but the project name is optional; relative paths are relative
+//! to the project's root.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+  Chunk, FastHashMap, Format, Serialize, StreamError, TargetProfile, ToVirtualPath, TomlFormat, Variant, Version,
+  VirtualPath,
+};
+
+/// The name of the file that defines a project.
+pub const PROJECT_FILE_NAME: &str = "Surreal.toml";
+
+/// An error that occurs while reading a project definition.
+#[derive(Debug)]
+pub enum ProjectDefinitionError {
+  StreamError(StreamError),
+  /// A required field is missing, like `project.name`.
+  MissingField(&'static str),
+  /// A field has the wrong type or an invalid value.
+  InvalidField(&'static str),
+  UnknownTarget(String),
+  /// Two asset roots have the same name.
+  DuplicateAssetRoot(String),
+}
+
+crate::impl_error_coercion!(StreamError into ProjectDefinitionError);
+
+/// The definition of a project, as read from its `Surreal.toml`.
+#[derive(Clone, Debug)]
+pub struct ProjectDefinition {
+  /// The folder containing the project file.
+  pub root: VirtualPath,
+  pub name: String,
+  pub version: Version,
+  /// The scene to open when the game starts, relative to the asset roots.
+  pub startup_scene: Option<String>,
+  /// The platforms the project is built for; the first is the default.
+  pub targets: Vec<TargetProfile>,
+  /// The names of the plugins the project uses.
+  pub plugins: Vec<String>,
+  /// Where the project's assets live, in the order they're searched.
+  pub asset_roots: Vec<AssetRoot>,
+  pub build: BuildSettings,
+}
+
+/// A named folder of assets within a project.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssetRoot {
+  pub name: String,
+  pub path: VirtualPath,
+}
+
+/// Settings for building a project's content.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildSettings {
+  /// Where packed content is written.
+  pub output: VirtualPath,
+  /// Re-use imports from previous builds when their source hasn't changed.
+  pub use_cache: bool,
+  /// Pack small sprites into atlases automatically.
+  pub auto_atlas: bool,
+}
+
+impl ProjectDefinition {
+  /// Reads the project file at the given path.
+  pub fn from_path(path: impl ToVirtualPath) -> Result<Self, ProjectDefinitionError> {
+    let path = path.to_virtual_path();
+    let mut stream = path.open_input_stream().map_err(|_| StreamError::GeneralFailure)?;
+    let chunk = TomlFormat.read_chunk(&mut stream)?;
+
+    let root = match path.location().rsplit_once('/') {
+      Some((folder, _)) => VirtualPath::new(&format!("{}://{}", path.scheme(), folder)),
+      None => VirtualPath::new(&format!("{}://.", path.scheme())),
+    };
+
+    Self::from_chunk(&chunk, root)
+  }
+
+  /// Reads a project from the text of its project file.
+  pub fn parse(text: &str, root: impl ToVirtualPath) -> Result<Self, ProjectDefinitionError> {
+    let chunk = TomlFormat.read_chunk(&mut std::io::Cursor::new(text.as_bytes()))?;
+
+    Self::from_chunk(&chunk, root.to_virtual_path())
+  }
+
+  /// Writes the project file into the project's root.
+  pub fn save(&self) -> Result<(), ProjectDefinitionError> {
+    Ok(self.to_format_path::<TomlFormat>(self.root.join(PROJECT_FILE_NAME))?)
+  }
+
+  /// Finds the project file in the given folder or the nearest folder above
+  /// it, like cargo does with `Cargo.toml`.
+  pub fn discover(start: impl AsRef<Path>) -> Option<PathBuf> {
+    start
+      .as_ref()
+      .ancestors()
+      .map(|folder| folder.join(PROJECT_FILE_NAME))
+      .find(|path| path.is_file())
+  }
+
+  /// Creates a project with the default settings: a single asset root,
+  /// `assets`, built for desktop into `target/content`.
+  pub fn new(name: impl Into<String>, root: impl ToVirtualPath) -> Self {
+    let root = root.to_virtual_path();
+
+    Self {
+      name: name.into(),
+      version: Version::new(0, 1, 0),
+      startup_scene: None,
+      targets: vec![TargetProfile::default()],
+      plugins: Vec::new(),
+      asset_roots: vec![AssetRoot {
+        name: "assets".to_string(),
+        path: root.join("assets"),
+      }],
+      build: BuildSettings {
+        output: root.join("target/content"),
+        use_cache: true,
+        auto_atlas: true,
+      },
+      root,
+    }
+  }
+
+  /// Reads a project from its parsed project file.
+  pub fn from_chunk(chunk: &Chunk, root: VirtualPath) -> Result<Self, ProjectDefinitionError> {
+    let details = chunk
+      .get("project")
+      .ok_or(ProjectDefinitionError::MissingField("project"))?;
+
+    let name = match details.get("name") {
+      Some(name) => name.read::<String>().map_err(|_| invalid("project.name"))?,
+      None => return Err(ProjectDefinitionError::MissingField("project.name")),
+    };
+
+    let mut project = Self::new(name, root);
+
+    if let Some(version) = details.get("version") {
+      let version = version.read::<String>().map_err(|_| invalid("project.version"))?;
+
+      project.version = Version::parse(&version).map_err(|_| invalid("project.version"))?;
+    }
+
+    if let Some(scene) = details.get("startup_scene") {
+      project.startup_scene = Some(scene.read().map_err(|_| invalid("project.startup_scene"))?);
+    }
+
+    if let Some(targets) = details.get("targets") {
+      project.targets = read_strings(targets, "project.targets")?
+        .into_iter()
+        .map(|name| TargetProfile::from_name(&name).ok_or(ProjectDefinitionError::UnknownTarget(name)))
+        .collect::<Result<Vec<_>, _>>()?;
+    }
+
+    if let Some(plugins) = details.get("plugins") {
+      project.plugins = read_strings(plugins, "project.plugins")?;
+    }
+
+    if let Some(roots) = chunk.get("assets") {
+      let roots = roots.as_sequence().ok_or(invalid("assets"))?;
+
+      project.asset_roots.clear();
+
+      for root in roots {
+        let name = root.read_field::<String>("name").map_err(|_| invalid("assets.name"))?;
+        let path = root.read_field::<String>("path").map_err(|_| invalid("assets.path"))?;
+
+        if project.asset_root(&name).is_some() {
+          return Err(ProjectDefinitionError::DuplicateAssetRoot(name));
+        }
+
+        project.asset_roots.push(AssetRoot {
+          path: project.resolve(&path),
+          name,
+        });
+      }
+    }
+
+    if let Some(build) = chunk.get("build") {
+      if let Some(output) = build.get("output") {
+        project.build.output = project.resolve(&output.read::<String>().map_err(|_| invalid("build.output"))?);
+      }
+
+      project.build.use_cache = build
+        .read_field_or("use_cache", project.build.use_cache)
+        .map_err(|_| invalid("build.use_cache"))?;
+      project.build.auto_atlas = build
+        .read_field_or("auto_atlas", project.build.auto_atlas)
+        .map_err(|_| invalid("build.auto_atlas"))?;
+    }
+
+    Ok(project)
+  }
+
+  /// Converts the project into the contents of its project file, with paths
+  /// relative to the project's root where possible.
+  pub fn to_chunk(&self) -> Chunk {
+    let string = |value: &str| Chunk::Variant(Variant::String(value.to_string()));
+    let strings = |values: Vec<&str>| Chunk::Sequence(values.into_iter().map(string).collect());
+
+    let mut project = FastHashMap::default();
+
+    project.insert("name".to_string(), string(&self.name));
+    project.insert("version".to_string(), string(&self.version.to_string()));
+    project.insert(
+      "targets".to_string(),
+      strings(self.targets.iter().map(|it| it.name.as_str()).collect()),
+    );
+    project.insert(
+      "plugins".to_string(),
+      strings(self.plugins.iter().map(String::as_str).collect()),
+    );
+
+    if let Some(scene) = &self.startup_scene {
+      project.insert("startup_scene".to_string(), string(scene));
+    }
+
+    let asset_roots = self
+      .asset_roots
+      .iter()
+      .map(|asset_root| {
+        let mut fields = FastHashMap::default();
+
+        fields.insert("name".to_string(), string(&asset_root.name));
+        fields.insert("path".to_string(), string(&self.relative(&asset_root.path)));
+
+        Chunk::Map(fields)
+      })
+      .collect();
+
+    let mut build = FastHashMap::default();
+
+    build.insert("output".to_string(), string(&self.relative(&self.build.output)));
+    build.insert(
+      "use_cache".to_string(),
+      Chunk::Variant(Variant::Bool(self.build.use_cache)),
+    );
+    build.insert(
+      "auto_atlas".to_string(),
+      Chunk::Variant(Variant::Bool(self.build.auto_atlas)),
+    );
+
+    let mut fields = FastHashMap::default();
+
+    fields.insert("project".to_string(), Chunk::Map(project));
+    fields.insert("assets".to_string(), Chunk::Sequence(asset_roots));
+    fields.insert("build".to_string(), Chunk::Map(build));
+
+    Chunk::Map(fields)
+  }
+
+  /// The default target, used when none is asked for.
+  pub fn default_target(&self) -> TargetProfile {
+    self.targets.first().cloned().unwrap_or_default()
+  }
+
+  /// Finds one of the project's targets by name.
+  pub fn target(&self, name: &str) -> Option<&TargetProfile> {
+    self.targets.iter().find(|target| target.name == name)
+  }
+
+  /// Finds an asset root by name.
+  pub fn asset_root(&self, name: &str) -> Option<&AssetRoot> {
+    self.asset_roots.iter().find(|root| root.name == name)
+  }
+
+  /// Determines if the project uses the given plugin.
+  pub fn has_plugin(&self, name: &str) -> bool {
+    self.plugins.iter().any(|plugin| plugin == name)
+  }
+
+  /// Resolves a path from the project file against the project's root;
+  /// paths with a scheme or that are absolute are left as they are.
+  fn resolve(&self, path: &str) -> VirtualPath {
+    match path.contains("://") || path.starts_with('/') {
+      true => VirtualPath::new(path),
+      false => self.root.join(path),
+    }
+  }
+
+  /// A path relative to the project's root, if it's beneath it.
+  fn relative(&self, path: &VirtualPath) -> String {
+    let prefix = format!("{}/", self.root.location().trim_end_matches('/'));
+
+    match path.scheme() == self.root.scheme() {
+      true => path
+        .location()
+        .strip_prefix(&prefix)
+        .unwrap_or(path.location())
+        .to_string(),
+      false => path.to_string(),
+    }
+  }
+}
+
+impl Serialize for ProjectDefinition {
+  fn serialize(&self) -> Chunk {
+    self.to_chunk()
+  }
+}
+
+fn invalid(field: &'static str) -> ProjectDefinitionError {
+  ProjectDefinitionError::InvalidField(field)
+}
+
+/// Reads a list of strings.
+fn read_strings(chunk: &Chunk, field: &'static str) -> Result<Vec<String>, ProjectDefinitionError> {
+  chunk
+    .as_sequence()
+    .ok_or(invalid(field))?
+    .iter()
+    .map(|value| value.read::<String>().map_err(|_| invalid(field)))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PROJECT: &str = r#"
+    [project]
+    name = "Demo"
+    version = "1.2.3"
+    startup_scene = "scenes/main.ron"
+    targets = ["web", "desktop"]
+    plugins = ["physics"]
+
+    [[assets]]
+    name = "game"
+    path = "assets"
+
+    [[assets]]
+    name = "shared"
+    path = "../shared"
+
+    [build]
+    output = "dist"
+    auto_atlas = false
+  "#;
+
+  #[test]
+  fn it_should_read_project_definitions() {
+    let project = ProjectDefinition::parse(PROJECT, "local://projects/demo").unwrap();
+
+    assert_eq!(project.name, "Demo");
+    assert_eq!(project.version, Version::new(1, 2, 3));
+    assert_eq!(project.startup_scene.as_deref(), Some("scenes/main.ron"));
+    assert_eq!(project.default_target(), TargetProfile::web());
+    assert!(project.target("desktop").is_some());
+    assert!(project.has_plugin("physics"));
+    assert_eq!(project.asset_roots.len(), 2);
+    assert_eq!(
+      project.asset_root("shared").unwrap().path,
+      VirtualPath::new("local://projects/demo/../shared")
+    );
+    assert_eq!(project.build.output, VirtualPath::new("local://projects/demo/dist"));
+    assert!(project.build.use_cache);
+    assert!(!project.build.auto_atlas);
+  }
+
+  #[test]
+  fn it_should_default_everything_but_the_name() {
+    let project = ProjectDefinition::parse("[project]\nname = \"Minimal\"", "local://minimal").unwrap();
+
+    assert_eq!(project.targets, vec![TargetProfile::desktop()]);
+    assert_eq!(project.asset_roots, vec![AssetRoot {
+      name: "assets".to_string(),
+      path: VirtualPath::new("local://minimal/assets"),
+    }]);
+
+    assert!(matches!(
+      ProjectDefinition::parse("[project]", "local://broken"),
+      Err(ProjectDefinitionError::MissingField("project.name"))
+    ));
+    assert!(matches!(
+      ProjectDefinition::parse("[project]\nname = \"A\"\ntargets = [\"n64\"]", "local://broken"),
+      Err(ProjectDefinitionError::UnknownTarget(name)) if name == "n64"
+    ));
+  }
+
+  #[test]
+  fn it_should_round_trip_through_project_files() {
+    let project = ProjectDefinition::parse(PROJECT, "local://projects/demo").unwrap();
+    let text = project.to_format_string::<TomlFormat>().unwrap();
+    let reread = ProjectDefinition::parse(&text, "local://projects/demo").unwrap();
+
+    assert!(text.contains("path = \"assets\""));
+    assert_eq!(reread.asset_roots, project.asset_roots);
+    assert_eq!(reread.build, project.build);
+    assert_eq!(reread.targets, project.targets);
+    assert_eq!(reread.startup_scene, project.startup_scene);
+  }
+
+  #[test]
+  fn it_should_discover_project_files_in_parent_folders() {
+    let root = std::env::temp_dir().join(format!("surreal-project-{}", std::process::id()));
+    let nested = root.join("assets/sprites");
+
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::write(root.join(PROJECT_FILE_NAME), "[project]\nname = \"Found\"").unwrap();
+
+    let path = ProjectDefinition::discover(&nested).unwrap();
+    let project = ProjectDefinition::from_path(path.to_string_lossy().as_ref()).unwrap();
+
+    assert_eq!(path, root.join(PROJECT_FILE_NAME));
+    assert_eq!(project.name, "Found");
+    assert_eq!(project.root, VirtualPath::new(&root.to_string_lossy()));
+
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+}
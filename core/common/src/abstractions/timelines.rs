@@ -0,0 +1,515 @@
+//! Timelines for cutscenes and other scripted sequences.
+//!
+//! A [`Timeline`] places animation clips, camera moves, audio cues and script
+//! callbacks on a shared time axis. A [`TimelinePlayer`] plays it back,
+//! dispatching everything to a [`TimelineDirector`] implemented by the game,
+//! and reports a blend weight so the sequence can ease into and out of
+//! gameplay rather than cutting.
+//!
+//! Timelines are assets, authored in RON:
+//!
+//! ```ron
+//! Timeline(
+//!   duration: 4.0,
+//!   blend_in: 0.5,
+//!   blend_out: 0.5,
+//!   tracks: [
+//!     Animation(target: "hero", clips: [(clip: "wave", start: 0.0, duration: 2.0)]),
+//!     Camera(keys: [(time: 0.0, position: (0.0, 1.0, 5.0), look_at: (0.0, 1.0, 0.0), fov: 60.0)]),
+//!     Audio(cues: [(time: 1.0, clip: "fanfare")]),
+//!     Script(cues: [(time: 3.5, callback: "open_door", argument: "north")]),
+//!   ],
+//! )
+//! ```
+
+use crate::{Chunk, Format, FromStream, InputStream, Lerp, PerspectiveCamera, RonFormat, StreamError, Variant, Vec3};
+
+/// A sequence of tracks placed on a time axis.
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+  /// The length of the timeline, in seconds.
+  pub duration: f32,
+  /// How long the timeline takes to blend in from gameplay, in seconds.
+  pub blend_in: f32,
+  /// How long the timeline takes to blend back out to gameplay, in seconds.
+  pub blend_out: f32,
+  pub tracks: Vec<TimelineTrack>,
+}
+
+/// A single track in a [`Timeline`].
+#[derive(Clone, Debug)]
+pub enum TimelineTrack {
+  /// Plays animation clips on a named target.
+  Animation { target: String, clips: Vec<TimelineClip> },
+  /// Moves the camera between keyed poses.
+  Camera { keys: Vec<CameraKey> },
+  /// Plays audio clips at points in time.
+  Audio { cues: Vec<AudioCue> },
+  /// Invokes script callbacks at points in time.
+  Script { cues: Vec<ScriptCue> },
+}
+
+/// An animation clip placed on an animation track.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimelineClip {
+  pub clip: String,
+  pub start: f32,
+  pub duration: f32,
+  pub speed: f32,
+}
+
+/// A keyed camera pose on a camera track.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraKey {
+  pub time: f32,
+  pub pose: CameraPose,
+}
+
+/// An audio cue on an audio track.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioCue {
+  pub time: f32,
+  pub clip: String,
+  pub volume: f32,
+}
+
+/// A script callback on a script track.
+#[derive(Clone, Debug)]
+pub struct ScriptCue {
+  pub time: f32,
+  pub callback: String,
+  pub argument: Variant,
+}
+
+/// The pose of a camera; where it is, what it's looking at and its field of
+/// view.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CameraPose {
+  pub position: Vec3,
+  pub look_at: Vec3,
+  pub fov: f32,
+}
+
+impl CameraPose {
+  /// Captures the pose of the given camera.
+  pub fn from_camera(camera: &PerspectiveCamera) -> Self {
+    Self {
+      position: camera.position,
+      look_at: camera.look_at,
+      fov: camera.fov,
+    }
+  }
+
+  /// Applies the pose to the given camera.
+  pub fn apply_to(&self, camera: &mut PerspectiveCamera) {
+    camera.position = self.position;
+    camera.look_at = self.look_at;
+    camera.fov = self.fov;
+  }
+}
+
+impl Lerp for CameraPose {
+  fn lerp(a: Self, b: Self, t: f32) -> Self {
+    Self {
+      position: Vec3::lerp(a.position, b.position, t),
+      look_at: Vec3::lerp(a.look_at, b.look_at, t),
+      fov: f32::lerp(a.fov, b.fov, t),
+    }
+  }
+}
+
+/// Receives the output of a [`TimelinePlayer`].
+///
+/// Every method is optional; implement the ones your game cares about.
+#[allow(unused_variables)]
+pub trait TimelineDirector {
+  /// Poses the target at the given local time within an animation clip.
+  fn animate(&mut self, target: &str, clip: &str, time: f32, weight: f32) {}
+
+  /// Moves the camera to the given pose, blended with gameplay by weight.
+  fn move_camera(&mut self, pose: &CameraPose, weight: f32) {}
+
+  /// Plays an audio clip.
+  fn play_audio(&mut self, clip: &str, volume: f32) {}
+
+  /// Invokes a script callback.
+  fn invoke(&mut self, callback: &str, argument: &Variant) {}
+}
+
+impl Timeline {
+  /// Reads a timeline from a [`Chunk`], as produced by [`RonFormat`].
+  pub fn from_chunk(chunk: &Chunk) -> Result<Self, StreamError> {
+    let tracks = chunk
+      .get("tracks")
+      .and_then(Chunk::as_sequence)
+      .unwrap_or_default()
+      .iter()
+      .map(TimelineTrack::from_chunk)
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let duration = match chunk.get("duration") {
+      Some(duration) => duration.read()?,
+      None => tracks.iter().map(TimelineTrack::end_time).fold(0., f32::max),
+    };
+
+    Ok(Self {
+      duration,
+      blend_in: chunk.read_field_or("blend_in", 0.)?,
+      blend_out: chunk.read_field_or("blend_out", 0.)?,
+      tracks,
+    })
+  }
+
+  /// The blend weight between gameplay (0) and the timeline (1) at the given
+  /// time.
+  pub fn blend_weight(&self, time: f32) -> f32 {
+    let blend_in = if self.blend_in > 0. { time / self.blend_in } else { 1. };
+    let blend_out = if self.blend_out > 0. {
+      (self.duration - time) / self.blend_out
+    } else {
+      1.
+    };
+
+    blend_in.min(blend_out).clamp(0., 1.)
+  }
+}
+
+impl FromStream for Timeline {
+  async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+    Self::from_chunk(&RonFormat::default().read_chunk(stream)?)
+  }
+}
+
+impl TimelineTrack {
+  fn from_chunk(chunk: &Chunk) -> Result<Self, StreamError> {
+    let items = |key: &str| chunk.get(key).and_then(Chunk::as_sequence).unwrap_or_default();
+
+    match chunk.type_name() {
+      Some("Animation") => Ok(Self::Animation {
+        target: chunk.read_field("target")?,
+        clips: items("clips")
+          .iter()
+          .map(|clip| {
+            Ok(TimelineClip {
+              clip: clip.read_field("clip")?,
+              start: clip.read_field_or("start", 0.)?,
+              duration: clip.read_field("duration")?,
+              speed: clip.read_field_or("speed", 1.)?,
+            })
+          })
+          .collect::<Result<_, StreamError>>()?,
+      }),
+      Some("Camera") => {
+        let mut keys = items("keys")
+          .iter()
+          .map(|key| {
+            Ok(CameraKey {
+              time: key.read_field("time")?,
+              pose: CameraPose {
+                position: key.get("position").ok_or(StreamError::InvalidData)?.read_vec3()?,
+                look_at: key.get("look_at").ok_or(StreamError::InvalidData)?.read_vec3()?,
+                fov: key.read_field_or("fov", 60.)?,
+              },
+            })
+          })
+          .collect::<Result<Vec<_>, StreamError>>()?;
+
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Ok(Self::Camera { keys })
+      }
+      Some("Audio") => Ok(Self::Audio {
+        cues: items("cues")
+          .iter()
+          .map(|cue| {
+            Ok(AudioCue {
+              time: cue.read_field("time")?,
+              clip: cue.read_field("clip")?,
+              volume: cue.read_field_or("volume", 1.)?,
+            })
+          })
+          .collect::<Result<_, StreamError>>()?,
+      }),
+      Some("Script") => Ok(Self::Script {
+        cues: items("cues")
+          .iter()
+          .map(|cue| {
+            Ok(ScriptCue {
+              time: cue.read_field("time")?,
+              callback: cue.read_field("callback")?,
+              argument: cue.read_field_or("argument", Variant::Null)?,
+            })
+          })
+          .collect::<Result<_, StreamError>>()?,
+      }),
+      _ => Err(StreamError::InvalidData),
+    }
+  }
+
+  /// The time at which the last item on this track finishes.
+  fn end_time(&self) -> f32 {
+    match self {
+      Self::Animation { clips, .. } => clips.iter().map(|clip| clip.start + clip.duration).fold(0., f32::max),
+      Self::Camera { keys } => keys.iter().map(|key| key.time).fold(0., f32::max),
+      Self::Audio { cues } => cues.iter().map(|cue| cue.time).fold(0., f32::max),
+      Self::Script { cues } => cues.iter().map(|cue| cue.time).fold(0., f32::max),
+    }
+  }
+}
+
+/// The playback state of a [`TimelinePlayer`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PlaybackState {
+  #[default]
+  Stopped,
+  Playing,
+  Paused,
+  Finished,
+}
+
+/// Plays back a [`Timeline`].
+#[derive(Clone, Debug)]
+pub struct TimelinePlayer {
+  timeline: Timeline,
+  time: f32,
+  // cues after this time have not fired yet; `None` means none have
+  fired_until: Option<f32>,
+  state: PlaybackState,
+  pub speed: f32,
+}
+
+impl TimelinePlayer {
+  /// Creates a new player for the given timeline.
+  pub fn new(timeline: Timeline) -> Self {
+    Self {
+      timeline,
+      time: 0.,
+      fired_until: None,
+      state: PlaybackState::Stopped,
+      speed: 1.,
+    }
+  }
+
+  /// The timeline being played.
+  pub fn timeline(&self) -> &Timeline {
+    &self.timeline
+  }
+
+  /// The current playback position, in seconds.
+  pub fn time(&self) -> f32 {
+    self.time
+  }
+
+  /// The current playback state.
+  pub fn state(&self) -> PlaybackState {
+    self.state
+  }
+
+  /// The current blend weight between gameplay (0) and the timeline (1).
+  pub fn weight(&self) -> f32 {
+    match self.state {
+      PlaybackState::Playing | PlaybackState::Paused => self.timeline.blend_weight(self.time),
+      PlaybackState::Stopped | PlaybackState::Finished => 0.,
+    }
+  }
+
+  /// Starts playback, from the beginning if the timeline has finished.
+  pub fn play(&mut self) {
+    if self.state == PlaybackState::Finished {
+      self.seek(0.);
+    }
+
+    self.state = PlaybackState::Playing;
+  }
+
+  /// Pauses playback.
+  pub fn pause(&mut self) {
+    if self.state == PlaybackState::Playing {
+      self.state = PlaybackState::Paused;
+    }
+  }
+
+  /// Stops playback and rewinds to the beginning.
+  pub fn stop(&mut self) {
+    self.state = PlaybackState::Stopped;
+    self.seek(0.);
+  }
+
+  /// Jumps to the given time.
+  ///
+  /// Audio and script cues between the old and new positions are skipped.
+  pub fn seek(&mut self, time: f32) {
+    self.time = time.clamp(0., self.timeline.duration);
+    self.fired_until = if self.time > 0. { Some(self.time) } else { None };
+  }
+
+  /// Advances playback and dispatches the timeline to the given director.
+  pub fn update(&mut self, delta_time: f32, director: &mut dyn TimelineDirector) {
+    if self.state != PlaybackState::Playing {
+      return;
+    }
+
+    self.time = (self.time + delta_time * self.speed).min(self.timeline.duration);
+
+    let weight = self.timeline.blend_weight(self.time);
+    let is_due = |time: f32| self.fired_until.is_none_or(|fired| time > fired) && time <= self.time;
+
+    for track in &self.timeline.tracks {
+      match track {
+        TimelineTrack::Animation { target, clips } => {
+          for clip in clips {
+            if self.time >= clip.start && self.time < clip.start + clip.duration {
+              director.animate(target, &clip.clip, (self.time - clip.start) * clip.speed, weight);
+            }
+          }
+        }
+        TimelineTrack::Camera { keys } => {
+          if let Some(pose) = evaluate_camera(keys, self.time) {
+            director.move_camera(&pose, weight);
+          }
+        }
+        TimelineTrack::Audio { cues } => {
+          for cue in cues.iter().filter(|cue| is_due(cue.time)) {
+            director.play_audio(&cue.clip, cue.volume);
+          }
+        }
+        TimelineTrack::Script { cues } => {
+          for cue in cues.iter().filter(|cue| is_due(cue.time)) {
+            director.invoke(&cue.callback, &cue.argument);
+          }
+        }
+      }
+    }
+
+    self.fired_until = Some(self.time);
+
+    if self.time >= self.timeline.duration {
+      self.state = PlaybackState::Finished;
+    }
+  }
+}
+
+/// Interpolates the camera pose at the given time.
+fn evaluate_camera(keys: &[CameraKey], time: f32) -> Option<CameraPose> {
+  let first = keys.first()?;
+  let last = keys.last()?;
+
+  if time <= first.time {
+    return Some(first.pose);
+  }
+
+  if time >= last.time {
+    return Some(last.pose);
+  }
+
+  let index = keys.iter().position(|key| key.time > time)?;
+  let (from, to) = (&keys[index - 1], &keys[index]);
+  let t = (time - from.time) / (to.time - from.time);
+
+  Some(CameraPose::lerp(from.pose, to.pose, t))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TIMELINE: &str = r#"
+    Timeline(
+      duration: 4.0,
+      blend_in: 1.0,
+      blend_out: 1.0,
+      tracks: [
+        Animation(target: "hero", clips: [(clip: "wave", start: 1.0, duration: 2.0, speed: 2.0)]),
+        Camera(keys: [
+          (time: 0.0, position: (0.0, 0.0, 0.0), look_at: (0.0, 0.0, -1.0), fov: 60.0),
+          (time: 2.0, position: (2.0, 0.0, 0.0), look_at: (0.0, 0.0, -1.0), fov: 40.0),
+        ]),
+        Audio(cues: [(time: 0.0, clip: "intro"), (time: 1.5, clip: "fanfare", volume: 0.5)]),
+        Script(cues: [(time: 3.5, callback: "open_door", argument: "north")]),
+      ],
+    )
+  "#;
+
+  #[derive(Default)]
+  struct RecordingDirector {
+    events: Vec<String>,
+    camera: Option<CameraPose>,
+  }
+
+  impl TimelineDirector for RecordingDirector {
+    fn animate(&mut self, target: &str, clip: &str, time: f32, _weight: f32) {
+      self.events.push(format!("animate {} {} {}", target, clip, time));
+    }
+
+    fn move_camera(&mut self, pose: &CameraPose, _weight: f32) {
+      self.camera = Some(*pose);
+    }
+
+    fn play_audio(&mut self, clip: &str, volume: f32) {
+      self.events.push(format!("audio {} {}", clip, volume));
+    }
+
+    fn invoke(&mut self, callback: &str, argument: &Variant) {
+      self.events.push(format!("invoke {} {:?}", callback, argument));
+    }
+  }
+
+  #[test]
+  fn it_should_load_timelines_from_ron() {
+    let timeline = Timeline::from_bytes(TIMELINE.as_bytes()).unwrap();
+
+    assert_eq!(timeline.duration, 4.);
+    assert_eq!(timeline.tracks.len(), 4);
+    assert!(matches!(&timeline.tracks[0], TimelineTrack::Animation { target, .. } if target == "hero"));
+  }
+
+  #[test]
+  fn it_should_dispatch_tracks_in_order() {
+    let mut player = TimelinePlayer::new(Timeline::from_bytes(TIMELINE.as_bytes()).unwrap());
+    let mut director = RecordingDirector::default();
+
+    player.play();
+    player.update(0., &mut director);
+    assert_eq!(director.events, vec!["audio intro 1"]);
+
+    director.events.clear();
+    player.update(1.5, &mut director);
+    assert_eq!(director.events, vec!["animate hero wave 1", "audio fanfare 0.5"]);
+    assert_eq!(director.camera.unwrap().fov, 45.);
+
+    director.events.clear();
+    player.update(10., &mut director);
+    assert_eq!(director.events, vec!["invoke open_door String(\"north\")"]);
+    assert_eq!(player.state(), PlaybackState::Finished);
+  }
+
+  #[test]
+  fn it_should_pause_and_seek() {
+    let mut player = TimelinePlayer::new(Timeline::from_bytes(TIMELINE.as_bytes()).unwrap());
+    let mut director = RecordingDirector::default();
+
+    player.play();
+    player.pause();
+    player.update(1., &mut director);
+
+    assert_eq!(player.time(), 0.);
+    assert!(director.events.is_empty());
+
+    player.seek(2.);
+    player.play();
+    player.update(0.1, &mut director);
+
+    // cues before the seek position are skipped
+    assert!(director.events.iter().all(|event| !event.starts_with("audio")));
+  }
+
+  #[test]
+  fn it_should_blend_into_and_out_of_gameplay() {
+    let timeline = Timeline::from_bytes(TIMELINE.as_bytes()).unwrap();
+
+    assert_eq!(timeline.blend_weight(0.), 0.);
+    assert_eq!(timeline.blend_weight(0.5), 0.5);
+    assert_eq!(timeline.blend_weight(2.), 1.);
+    assert_eq!(timeline.blend_weight(3.75), 0.25);
+  }
+}
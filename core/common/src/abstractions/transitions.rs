@@ -0,0 +1,239 @@
+//! Fullscreen transitions, like fades and wipes between scenes.
+//!
+//! A [`Transition`] first covers the screen, then reveals it again; whatever
+//! switches scenes does so while the screen is covered, and the renderer
+//! draws the [`TransitionStyle`] at the current [`Transition::coverage`]:
+//!
+//! ```rust,ignore
+//! let transition = Transition::circle(player.position, 1.2)
+//!   .with_easing(easing_cubic_in)
+//!   .on_finished(|| music.play("level2"));
+//!
+//! scenes.switch_to(level2, Some(transition));
+//! ```
+
+use crate::{easing_linear, Color, Easing, StringName, Vec2};
+
+/// How a [`Transition`] covers the screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TransitionStyle {
+  /// Fades the whole screen to the transition's color.
+  Fade,
+  /// Shrinks a circle around a point in the view, from 0 to 1 on each axis.
+  Circle { center: Vec2 },
+  /// Pixelates the screen into blocks up to the given size, in pixels, then
+  /// fades to the transition's color.
+  Pixelate { block_size: f32 },
+  /// Covers the screen with a shader registered with the renderer under the
+  /// given name, which decides where the screen is covered first.
+  Mask { shader: StringName },
+}
+
+/// The phases of a [`Transition`], in order.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TransitionPhase {
+  /// The screen is being covered.
+  Covering,
+  /// The screen is covered and being revealed again.
+  Revealing,
+  Finished,
+}
+
+/// A moment in a [`Transition`], returned from [`Transition::update`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TransitionEvent {
+  /// The screen was fully covered; switch scenes now.
+  Covered,
+  /// The screen was fully revealed.
+  Finished,
+}
+
+/// A callback run at a moment in a [`Transition`].
+type TransitionCallback = Box<dyn FnOnce()>;
+
+/// A fullscreen transition that covers the screen then reveals it again.
+pub struct Transition {
+  pub style: TransitionStyle,
+  /// The color the screen is covered with.
+  pub color: Color,
+  /// How long covering takes, in seconds.
+  pub cover_duration: f32,
+  /// How long revealing takes, in seconds.
+  pub reveal_duration: f32,
+  /// Eases coverage from 0 to 1 while covering, and back while revealing.
+  pub easing: Easing<f32>,
+  phase: TransitionPhase,
+  elapsed: f32,
+  on_covered: Vec<TransitionCallback>,
+  on_finished: Vec<TransitionCallback>,
+}
+
+impl Transition {
+  /// Creates a transition of the given style, spending half its duration
+  /// covering the screen and half revealing it.
+  pub fn new(style: TransitionStyle, duration: f32) -> Self {
+    Self {
+      style,
+      color: Color::BLACK,
+      cover_duration: duration / 2.,
+      reveal_duration: duration / 2.,
+      easing: easing_linear,
+      phase: TransitionPhase::Covering,
+      elapsed: 0.,
+      on_covered: Vec::new(),
+      on_finished: Vec::new(),
+    }
+  }
+
+  /// Fades out to a color and back in.
+  pub fn fade(duration: f32) -> Self {
+    Self::new(TransitionStyle::Fade, duration)
+  }
+
+  /// Closes a circle around a point, then opens it again.
+  pub fn circle(center: Vec2, duration: f32) -> Self {
+    Self::new(TransitionStyle::Circle { center }, duration)
+  }
+
+  /// Pixelates into blocks, then back to sharp.
+  pub fn pixelate(block_size: f32, duration: f32) -> Self {
+    Self::new(TransitionStyle::Pixelate { block_size }, duration)
+  }
+
+  /// Wipes with a registered mask shader.
+  pub fn mask(shader: impl Into<StringName>, duration: f32) -> Self {
+    Self::new(TransitionStyle::Mask { shader: shader.into() }, duration)
+  }
+
+  /// Covers the screen with the given color.
+  pub fn with_color(mut self, color: Color) -> Self {
+    self.color = color;
+    self
+  }
+
+  /// Eases the coverage with the given function.
+  pub fn with_easing(mut self, easing: Easing<f32>) -> Self {
+    self.easing = easing;
+    self
+  }
+
+  /// Covers and reveals over different lengths of time.
+  pub fn with_durations(mut self, cover_duration: f32, reveal_duration: f32) -> Self {
+    self.cover_duration = cover_duration;
+    self.reveal_duration = reveal_duration;
+    self
+  }
+
+  /// Runs a callback once the screen is covered.
+  pub fn on_covered(mut self, callback: impl FnOnce() + 'static) -> Self {
+    self.on_covered.push(Box::new(callback));
+    self
+  }
+
+  /// Runs a callback once the transition finishes.
+  pub fn on_finished(mut self, callback: impl FnOnce() + 'static) -> Self {
+    self.on_finished.push(Box::new(callback));
+    self
+  }
+
+  /// The current phase of the transition.
+  pub fn phase(&self) -> TransitionPhase {
+    self.phase
+  }
+
+  /// Determines if the transition has finished.
+  pub fn is_finished(&self) -> bool {
+    self.phase == TransitionPhase::Finished
+  }
+
+  /// How much of the screen is covered, eased, from 0 to 1.
+  pub fn coverage(&self) -> f32 {
+    let progress = |elapsed: f32, duration: f32| match duration > 0. {
+      true => (elapsed / duration).clamp(0., 1.),
+      false => 1.,
+    };
+
+    match self.phase {
+      TransitionPhase::Covering => (self.easing)(0., 1., progress(self.elapsed, self.cover_duration)),
+      TransitionPhase::Revealing => (self.easing)(1., 0., progress(self.elapsed, self.reveal_duration)),
+      TransitionPhase::Finished => 0.,
+    }
+  }
+
+  /// Advances the transition, running callbacks for the moment it reaches,
+  /// if any.
+  ///
+  /// Reaches at most one moment per update, so the frame the screen is
+  /// covered is always drawn fully covered.
+  pub fn update(&mut self, delta_time: f32) -> Option<TransitionEvent> {
+    self.elapsed += delta_time;
+
+    match self.phase {
+      TransitionPhase::Covering if self.elapsed >= self.cover_duration => {
+        self.phase = TransitionPhase::Revealing;
+        self.elapsed = 0.;
+
+        for callback in self.on_covered.drain(..) {
+          callback();
+        }
+
+        Some(TransitionEvent::Covered)
+      }
+      TransitionPhase::Revealing if self.elapsed >= self.reveal_duration => {
+        self.phase = TransitionPhase::Finished;
+
+        for callback in self.on_finished.drain(..) {
+          callback();
+        }
+
+        Some(TransitionEvent::Finished)
+      }
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::Cell, rc::Rc};
+
+  use super::*;
+
+  #[test]
+  fn it_should_cover_then_reveal() {
+    let finished = Rc::new(Cell::new(false));
+    let mut transition = Transition::fade(1.).on_finished({
+      let finished = finished.clone();
+      move || finished.set(true)
+    });
+
+    assert_eq!(transition.coverage(), 0.);
+    assert_eq!(transition.update(0.25), None);
+    assert_eq!(transition.coverage(), 0.5);
+
+    // the covered frame is drawn fully covered, even when overshooting
+    assert_eq!(transition.update(0.5), Some(TransitionEvent::Covered));
+    assert_eq!(transition.phase(), TransitionPhase::Revealing);
+    assert_eq!(transition.coverage(), 1.);
+
+    assert_eq!(transition.update(0.25), None);
+    assert_eq!(transition.coverage(), 0.5);
+    assert!(!finished.get());
+
+    assert_eq!(transition.update(0.25), Some(TransitionEvent::Finished));
+    assert!(transition.is_finished());
+    assert!(finished.get());
+    assert_eq!(transition.coverage(), 0.);
+    assert_eq!(transition.update(1.), None);
+  }
+
+  #[test]
+  fn it_should_support_uneven_and_instant_durations() {
+    let mut transition = Transition::circle(Vec2::splat(0.5), 0.).with_durations(0., 2.);
+
+    assert_eq!(transition.coverage(), 1.);
+    assert_eq!(transition.update(0.), Some(TransitionEvent::Covered));
+    assert_eq!(transition.update(1.), None);
+    assert_eq!(transition.coverage(), 0.5);
+  }
+}
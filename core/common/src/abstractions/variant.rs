@@ -163,6 +163,8 @@ impl std::ops::Neg for Variant {
       Variant::I16(value) => Ok(Variant::I16(value.neg())),
       Variant::I32(value) => Ok(Variant::I32(value.neg())),
       Variant::I64(value) => Ok(Variant::I64(value.neg())),
+      Variant::F32(value) => Ok(Variant::F32(value.neg())),
+      Variant::F64(value) => Ok(Variant::F64(value.neg())),
       Variant::Vec2(value) => Ok(Variant::Vec2(value.neg())),
       Variant::Vec3(value) => Ok(Variant::Vec3(value.neg())),
       Variant::Vec4(value) => Ok(Variant::Vec4(value.neg())),
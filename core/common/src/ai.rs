@@ -0,0 +1,14 @@
+//! Artificial intelligence helpers; steering, flocking, perception and the
+//! like.
+
+pub use avoidance::*;
+pub use blackboard::*;
+pub use flocking::*;
+pub use perception::*;
+
+use crate::{FastHashMap, FromVariant, StringName, ToStringName, ToVariant, Variant, Vec2};
+
+mod avoidance;
+mod blackboard;
+mod flocking;
+mod perception;
@@ -0,0 +1,498 @@
+//! Local collision avoidance for crowds, via ORCA.
+
+use crate::{vec2, Vec2};
+
+/// Tolerance for parallel lines in the linear programs.
+const EPSILON: f32 = 0.00001;
+
+/// The shortest time horizon and time step, in seconds; the velocity solve
+/// divides by both.
+const MIN_TIME: f32 = 0.001;
+
+/// An agent taking part in a [`Crowd`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CrowdAgent {
+  pub position: Vec2,
+  pub velocity: Vec2,
+  /// The velocity the agent would like to travel at, e.g. towards the next
+  /// point on its path.
+  pub preferred_velocity: Vec2,
+  pub radius: f32,
+  pub max_speed: f32,
+  /// How much right-of-way the agent has; agents yield to higher priorities.
+  pub priority: f32,
+}
+
+impl Default for CrowdAgent {
+  fn default() -> Self {
+    Self {
+      position: Vec2::ZERO,
+      velocity: Vec2::ZERO,
+      preferred_velocity: Vec2::ZERO,
+      radius: 0.5,
+      max_speed: 2.,
+      priority: 1.,
+    }
+  }
+}
+
+impl CrowdAgent {
+  /// Points the preferred velocity at the given target, slowing on arrival.
+  pub fn steer_towards(&mut self, target: Vec2) {
+    let offset = target - self.position;
+
+    self.preferred_velocity = offset.clamp_length_max(self.max_speed);
+  }
+}
+
+/// Settings for a [`Crowd`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CrowdSettings {
+  /// How far ahead, in seconds, agents look for collisions with each other.
+  pub time_horizon: f32,
+  /// How far away other agents are considered, in world units.
+  pub neighbour_distance: f32,
+  /// The most neighbours considered per agent.
+  pub max_neighbours: usize,
+}
+
+impl Default for CrowdSettings {
+  fn default() -> Self {
+    Self {
+      time_horizon: 2.,
+      neighbour_distance: 10.,
+      max_neighbours: 10,
+    }
+  }
+}
+
+/// A group of agents that steer around each other.
+///
+/// Uses Optimal Reciprocal Collision Avoidance (ORCA): each agent picks the
+/// velocity closest to its preferred one that is guaranteed collision-free
+/// for the time horizon, assuming its neighbours share the effort. The effort
+/// is split by priority, so a high-priority agent barely deviates while
+/// others make way. The result is smooth flow through chokepoints without
+/// overlap or the oscillation of naive separation forces.
+#[derive(Clone, Debug, Default)]
+pub struct Crowd {
+  pub settings: CrowdSettings,
+  agents: Vec<CrowdAgent>,
+  lines: Vec<Line>,
+  neighbours: Vec<(f32, usize)>,
+}
+
+/// A directed line; the permitted half-plane is to its left.
+#[derive(Copy, Clone, Debug, Default)]
+struct Line {
+  point: Vec2,
+  direction: Vec2,
+}
+
+impl Crowd {
+  /// Creates a new crowd with the given settings.
+  ///
+  /// The time horizon is clamped to a small positive value.
+  pub fn new(mut settings: CrowdSettings) -> Self {
+    settings.time_horizon = settings.time_horizon.max(MIN_TIME);
+
+    Self {
+      settings,
+      ..Default::default()
+    }
+  }
+
+  /// Adds an agent, returning its index.
+  pub fn add_agent(&mut self, agent: CrowdAgent) -> usize {
+    self.agents.push(agent);
+    self.agents.len() - 1
+  }
+
+  /// The agents in the crowd.
+  pub fn agents(&self) -> &[CrowdAgent] {
+    &self.agents
+  }
+
+  /// The agents in the crowd, mutably.
+  pub fn agents_mut(&mut self) -> &mut [CrowdAgent] {
+    &mut self.agents
+  }
+
+  /// Computes new velocities and moves every agent by the given time step.
+  pub fn step(&mut self, delta_time: f32) {
+    let velocities = (0..self.agents.len())
+      .map(|index| self.compute_velocity(index, delta_time))
+      .collect::<Vec<_>>();
+
+    for (agent, velocity) in self.agents.iter_mut().zip(velocities) {
+      agent.velocity = velocity;
+      agent.position += velocity * delta_time;
+    }
+  }
+
+  /// Computes a collision-free velocity for a single agent.
+  ///
+  /// The time step and horizon are clamped to a small positive value, since
+  /// the settings can be changed after the crowd is created.
+  pub fn compute_velocity(&mut self, index: usize, delta_time: f32) -> Vec2 {
+    let agent = self.agents[index];
+    let delta_time = delta_time.max(MIN_TIME);
+    let inverse_horizon = 1. / self.settings.time_horizon.max(MIN_TIME);
+
+    self.find_neighbours(index);
+    self.lines.clear();
+
+    for &(_, other_index) in &self.neighbours {
+      let other = &self.agents[other_index];
+
+      let relative_position = other.position - agent.position;
+      let relative_velocity = agent.velocity - other.velocity;
+      let distance_sq = relative_position.length_squared();
+      let combined_radius = agent.radius + other.radius;
+      let combined_radius_sq = combined_radius * combined_radius;
+
+      let mut line = Line::default();
+      let u;
+
+      if distance_sq > combined_radius_sq {
+        // no collision yet; project onto the truncated velocity obstacle
+        let w = relative_velocity - relative_position * inverse_horizon;
+        let w_length_sq = w.length_squared();
+        let dot = w.dot(relative_position);
+
+        if dot < 0. && dot * dot > combined_radius_sq * w_length_sq {
+          // project onto the cut-off circle
+          let w_length = w_length_sq.sqrt();
+          let unit_w = w / w_length;
+
+          line.direction = vec2(unit_w.y, -unit_w.x);
+          u = unit_w * (combined_radius * inverse_horizon - w_length);
+        } else {
+          // project onto the nearest leg of the cone
+          let leg = (distance_sq - combined_radius_sq).sqrt();
+
+          line.direction = if relative_position.perp_dot(w) > 0. {
+            vec2(
+              relative_position.x * leg - relative_position.y * combined_radius,
+              relative_position.x * combined_radius + relative_position.y * leg,
+            ) / distance_sq
+          } else {
+            -vec2(
+              relative_position.x * leg + relative_position.y * combined_radius,
+              -relative_position.x * combined_radius + relative_position.y * leg,
+            ) / distance_sq
+          };
+
+          u = line.direction * relative_velocity.dot(line.direction) - relative_velocity;
+        }
+      } else {
+        // already overlapping; separate within this time step
+        let w = relative_velocity - relative_position / delta_time;
+        let w_length = w.length().max(EPSILON);
+        let unit_w = w / w_length;
+
+        line.direction = vec2(unit_w.y, -unit_w.x);
+        u = unit_w * (combined_radius / delta_time - w_length);
+      }
+
+      let total_priority = agent.priority + other.priority;
+      let responsibility = match total_priority > 0. {
+        true => other.priority / total_priority,
+        false => 0.5,
+      };
+
+      line.point = agent.velocity + u * responsibility;
+
+      self.lines.push(line);
+    }
+
+    let mut result = Vec2::ZERO;
+    let failed_line = linear_program_2(
+      &self.lines,
+      agent.max_speed,
+      agent.preferred_velocity,
+      false,
+      &mut result,
+    );
+
+    if failed_line < self.lines.len() {
+      linear_program_3(&self.lines, failed_line, agent.max_speed, &mut result);
+    }
+
+    result
+  }
+
+  /// Collects the nearest agents within range, closest first.
+  fn find_neighbours(&mut self, index: usize) {
+    let position = self.agents[index].position;
+    let range_sq = self.settings.neighbour_distance * self.settings.neighbour_distance;
+
+    self.neighbours.clear();
+
+    for (other_index, other) in self.agents.iter().enumerate() {
+      let distance_sq = other.position.distance_squared(position);
+
+      if other_index != index && distance_sq < range_sq {
+        self.neighbours.push((distance_sq, other_index));
+      }
+    }
+
+    self.neighbours.sort_by(|a, b| a.0.total_cmp(&b.0));
+    self.neighbours.truncate(self.settings.max_neighbours);
+  }
+}
+
+/// Solves a 1-dimensional linear program along the given line.
+fn linear_program_1(
+  lines: &[Line],
+  line_index: usize,
+  radius: f32,
+  optimal: Vec2,
+  optimize_direction: bool,
+  result: &mut Vec2,
+) -> bool {
+  let line = lines[line_index];
+  let dot = line.point.dot(line.direction);
+  let discriminant = dot * dot + radius * radius - line.point.length_squared();
+
+  if discriminant < 0. {
+    // the max speed circle fully invalidates this line
+    return false;
+  }
+
+  let discriminant = discriminant.sqrt();
+  let mut t_left = -dot - discriminant;
+  let mut t_right = -dot + discriminant;
+
+  for other in &lines[..line_index] {
+    let denominator = line.direction.perp_dot(other.direction);
+    let numerator = other.direction.perp_dot(line.point - other.point);
+
+    if denominator.abs() <= EPSILON {
+      // the lines are parallel
+      if numerator < 0. {
+        return false;
+      }
+
+      continue;
+    }
+
+    let t = numerator / denominator;
+
+    if denominator >= 0. {
+      t_right = t_right.min(t);
+    } else {
+      t_left = t_left.max(t);
+    }
+
+    if t_left > t_right {
+      return false;
+    }
+  }
+
+  let t = if optimize_direction {
+    match optimal.dot(line.direction) > 0. {
+      true => t_right,
+      false => t_left,
+    }
+  } else {
+    line.direction.dot(optimal - line.point).clamp(t_left, t_right)
+  };
+
+  *result = line.point + line.direction * t;
+
+  true
+}
+
+/// Solves a 2-dimensional linear program subject to the given lines.
+///
+/// Returns the index of the line it failed on, or the line count on success.
+fn linear_program_2(lines: &[Line], radius: f32, optimal: Vec2, optimize_direction: bool, result: &mut Vec2) -> usize {
+  *result = if optimize_direction {
+    optimal * radius
+  } else {
+    optimal.clamp_length_max(radius)
+  };
+
+  for index in 0..lines.len() {
+    let line = lines[index];
+
+    if line.direction.perp_dot(line.point - *result) > 0. {
+      // the result doesn't satisfy this constraint
+      let previous = *result;
+
+      if !linear_program_1(lines, index, radius, optimal, optimize_direction, result) {
+        *result = previous;
+        return index;
+      }
+    }
+  }
+
+  lines.len()
+}
+
+/// Finds the velocity that least violates the constraints, when there's no
+/// velocity that satisfies them all (e.g. in dense crowds).
+fn linear_program_3(lines: &[Line], begin_line: usize, radius: f32, result: &mut Vec2) {
+  let mut distance = 0.;
+  let mut projected = Vec::with_capacity(lines.len());
+
+  for index in begin_line..lines.len() {
+    let line = lines[index];
+
+    if line.direction.perp_dot(line.point - *result) <= distance {
+      continue;
+    }
+
+    projected.clear();
+
+    for other in &lines[..index] {
+      let determinant = line.direction.perp_dot(other.direction);
+
+      let point = if determinant.abs() <= EPSILON {
+        if line.direction.dot(other.direction) > 0. {
+          continue; // same direction
+        }
+
+        (line.point + other.point) * 0.5
+      } else {
+        line.point + line.direction * (other.direction.perp_dot(line.point - other.point) / determinant)
+      };
+
+      projected.push(Line {
+        point,
+        direction: (other.direction - line.direction).normalize_or_zero(),
+      });
+    }
+
+    let previous = *result;
+    let optimal = vec2(-line.direction.y, line.direction.x);
+
+    if linear_program_2(&projected, radius, optimal, true, result) < projected.len() {
+      // can only fail due to floating point error; keep the last result
+      *result = previous;
+    }
+
+    distance = line.direction.perp_dot(line.point - *result);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn simulate(crowd: &mut Crowd, goals: &[Vec2], steps: usize) -> f32 {
+    let mut closest = f32::MAX;
+
+    for _ in 0..steps {
+      for (agent, goal) in crowd.agents_mut().iter_mut().zip(goals) {
+        agent.steer_towards(*goal);
+      }
+
+      crowd.step(0.1);
+
+      let agents = crowd.agents();
+      for i in 0..agents.len() {
+        for j in i + 1..agents.len() {
+          let gap = agents[i].position.distance(agents[j].position) - agents[i].radius - agents[j].radius;
+
+          closest = closest.min(gap);
+        }
+      }
+    }
+
+    closest
+  }
+
+  #[test]
+  fn it_should_pass_head_on_agents_without_overlap() {
+    let mut crowd = Crowd::default();
+    let goals = [vec2(10., 0.), vec2(-10., 0.)];
+
+    crowd.add_agent(CrowdAgent {
+      position: vec2(-10., 0.),
+      ..Default::default()
+    });
+    crowd.add_agent(CrowdAgent {
+      position: vec2(10., 0.01),
+      ..Default::default()
+    });
+
+    let closest = simulate(&mut crowd, &goals, 200);
+
+    assert!(closest > -0.05, "agents overlapped by {}", -closest);
+    assert!(crowd.agents()[0].position.distance(goals[0]) < 0.5);
+    assert!(crowd.agents()[1].position.distance(goals[1]) < 0.5);
+  }
+
+  #[test]
+  fn it_should_respect_max_speed() {
+    let mut crowd = Crowd::default();
+
+    crowd.add_agent(CrowdAgent {
+      max_speed: 1.5,
+      ..Default::default()
+    });
+
+    crowd.agents_mut()[0].preferred_velocity = vec2(10., 0.);
+    crowd.step(0.1);
+
+    assert!((crowd.agents()[0].velocity.length() - 1.5).abs() < 0.001);
+  }
+
+  #[test]
+  fn it_should_clamp_zero_time_steps_and_horizons() {
+    let mut crowd = Crowd::new(CrowdSettings {
+      time_horizon: 0.,
+      ..Default::default()
+    });
+
+    assert_eq!(crowd.settings.time_horizon, MIN_TIME);
+
+    // overlapping agents take the path that divides by the time step
+    for x in [0., 0.5] {
+      crowd.add_agent(CrowdAgent {
+        position: vec2(x, 0.),
+        ..Default::default()
+      });
+    }
+
+    crowd.settings.time_horizon = 0.;
+    crowd.step(0.);
+
+    assert!(crowd.agents().iter().all(|agent| agent.velocity.is_finite()));
+  }
+
+  #[test]
+  fn it_should_make_way_for_higher_priority_agents() {
+    let mut crowd = Crowd::default();
+    let goals = [vec2(10., 0.), vec2(-10., 0.)];
+
+    crowd.add_agent(CrowdAgent {
+      position: vec2(-10., 0.),
+      priority: 10.,
+      ..Default::default()
+    });
+    crowd.add_agent(CrowdAgent {
+      position: vec2(10., 0.01),
+      priority: 1.,
+      ..Default::default()
+    });
+
+    let mut deviation = [0f32; 2];
+
+    for _ in 0..200 {
+      for (agent, goal) in crowd.agents_mut().iter_mut().zip(&goals) {
+        agent.steer_towards(*goal);
+      }
+
+      crowd.step(0.1);
+
+      for (index, agent) in crowd.agents().iter().enumerate() {
+        deviation[index] = deviation[index].max(agent.position.y.abs());
+      }
+    }
+
+    assert!(deviation[0] < deviation[1] * 0.5, "{:?}", deviation);
+  }
+}
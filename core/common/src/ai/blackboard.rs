@@ -0,0 +1,69 @@
+use super::*;
+
+/// A shared key/value store that AI systems read and write decisions from.
+///
+/// Sensors write what they know (e.g. the last known position of a target),
+/// and behaviours read it back without knowing where it came from.
+#[derive(Clone, Debug, Default)]
+pub struct Blackboard {
+  entries: FastHashMap<StringName, Variant>,
+}
+
+impl Blackboard {
+  /// Creates a new, empty blackboard.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Determines if the blackboard has a value for the given key.
+  pub fn contains(&self, key: impl ToStringName) -> bool {
+    self.entries.contains_key(&key.to_string_name())
+  }
+
+  /// Gets the value for the given key.
+  pub fn get(&self, key: impl ToStringName) -> Option<&Variant> {
+    self.entries.get(&key.to_string_name())
+  }
+
+  /// Gets the value for the given key, converted to the given type.
+  pub fn get_as<T: FromVariant>(&self, key: impl ToStringName) -> Option<T> {
+    T::from_variant(self.get(key)?.clone()).ok()
+  }
+
+  /// Sets the value for the given key.
+  pub fn set(&mut self, key: impl ToStringName, value: impl ToVariant) {
+    self.entries.insert(key.to_string_name(), value.to_variant());
+  }
+
+  /// Removes the value for the given key.
+  pub fn remove(&mut self, key: impl ToStringName) -> Option<Variant> {
+    self.entries.remove(&key.to_string_name())
+  }
+
+  /// Removes all values.
+  pub fn clear(&mut self) {
+    self.entries.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::vec2;
+
+  #[test]
+  fn it_should_store_and_convert_values() {
+    let mut blackboard = Blackboard::new();
+
+    blackboard.set("target_position", vec2(1., 2.));
+    blackboard.set("alert", true);
+
+    assert_eq!(blackboard.get_as::<Vec2>("target_position"), Some(vec2(1., 2.)));
+    assert_eq!(blackboard.get_as::<bool>("alert"), Some(true));
+    assert_eq!(blackboard.get_as::<bool>("target_position"), None);
+
+    blackboard.remove("alert");
+
+    assert!(!blackboard.contains("alert"));
+  }
+}
@@ -0,0 +1,288 @@
+//! Flocking behaviour for birds, fish and swarms.
+
+use super::*;
+use crate::{SpatialHashMap, SpatialShape};
+
+/// A single member of a [`Flock`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Boid {
+  pub position: Vec2,
+  pub velocity: Vec2,
+}
+
+/// A circular obstacle that boids steer around.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FlockObstacle {
+  pub center: Vec2,
+  pub radius: f32,
+}
+
+/// Parameters for a [`Flock`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FlockSettings {
+  /// How far away other boids are considered neighbours, in world units.
+  pub neighbour_radius: f32,
+  /// How close neighbours can get before the boid moves away from them.
+  pub separation_radius: f32,
+  /// How far ahead of an obstacle's surface boids start to turn away.
+  pub avoidance_distance: f32,
+  pub separation_weight: f32,
+  pub alignment_weight: f32,
+  pub cohesion_weight: f32,
+  pub avoidance_weight: f32,
+  pub max_speed: f32,
+  /// The largest change in velocity per second from any single behaviour.
+  pub max_force: f32,
+}
+
+impl Default for FlockSettings {
+  fn default() -> Self {
+    Self {
+      neighbour_radius: 5.,
+      separation_radius: 1.5,
+      avoidance_distance: 3.,
+      separation_weight: 1.5,
+      alignment_weight: 1.,
+      cohesion_weight: 1.,
+      avoidance_weight: 3.,
+      max_speed: 4.,
+      max_force: 8.,
+    }
+  }
+}
+
+/// A group of boids that flock together.
+///
+/// Each boid steers by separation (don't crowd neighbours), alignment (head
+/// the same way as neighbours) and cohesion (stay near neighbours), plus
+/// avoidance of obstacles. Neighbours are found via a spatial hash, so large
+/// flocks stay cheap. Each flock has its own settings, so birds and fish can
+/// behave differently side by side.
+pub struct Flock {
+  pub settings: FlockSettings,
+  boids: Vec<Boid>,
+  obstacles: Vec<FlockObstacle>,
+  spatial: SpatialHashMap<usize>,
+}
+
+impl Default for Flock {
+  fn default() -> Self {
+    Self::new(FlockSettings::default())
+  }
+}
+
+impl Flock {
+  /// Creates a new flock with the given settings.
+  pub fn new(settings: FlockSettings) -> Self {
+    Self {
+      settings,
+      boids: Vec::new(),
+      obstacles: Vec::new(),
+      spatial: SpatialHashMap::with_resolution(settings.neighbour_radius),
+    }
+  }
+
+  /// Adds a boid, returning its index.
+  pub fn add_boid(&mut self, boid: Boid) -> usize {
+    self.boids.push(boid);
+    self.boids.len() - 1
+  }
+
+  /// Adds an obstacle to steer around.
+  pub fn add_obstacle(&mut self, obstacle: FlockObstacle) {
+    self.obstacles.push(obstacle);
+  }
+
+  /// The boids in the flock.
+  pub fn boids(&self) -> &[Boid] {
+    &self.boids
+  }
+
+  /// The boids in the flock, mutably.
+  pub fn boids_mut(&mut self) -> &mut [Boid] {
+    &mut self.boids
+  }
+
+  /// The obstacles the flock steers around.
+  pub fn obstacles(&self) -> &[FlockObstacle] {
+    &self.obstacles
+  }
+
+  /// Steers and moves every boid by the given time step.
+  pub fn step(&mut self, delta_time: f32) {
+    self.spatial = SpatialHashMap::with_resolution(self.settings.neighbour_radius);
+
+    for (index, boid) in self.boids.iter().enumerate() {
+      self.spatial.add(
+        SpatialShape::Circle {
+          center: boid.position,
+          radius: 0.,
+        },
+        index,
+      );
+    }
+
+    let forces = (0..self.boids.len())
+      .map(|index| self.steering_force(index))
+      .collect::<Vec<_>>();
+
+    let max_speed = self.settings.max_speed;
+
+    for (boid, force) in self.boids.iter_mut().zip(forces) {
+      boid.velocity = (boid.velocity + force * delta_time).clamp_length_max(max_speed);
+      boid.position += boid.velocity * delta_time;
+    }
+  }
+
+  /// Computes the combined steering force for a single boid.
+  fn steering_force(&self, index: usize) -> Vec2 {
+    let settings = &self.settings;
+    let boid = self.boids[index];
+
+    let mut separation = Vec2::ZERO;
+    let mut alignment = Vec2::ZERO;
+    let mut center = Vec2::ZERO;
+    let mut count = 0;
+
+    let neighbours = self.spatial.query(SpatialShape::Circle {
+      center: boid.position,
+      radius: settings.neighbour_radius,
+    });
+
+    for &other_index in neighbours {
+      if other_index == index {
+        continue;
+      }
+
+      let other = self.boids[other_index];
+      let offset = boid.position - other.position;
+      let distance = offset.length();
+
+      // push away harder the closer we are
+      if distance < settings.separation_radius && distance > f32::EPSILON {
+        separation += offset / (distance * distance);
+      }
+
+      alignment += other.velocity;
+      center += other.position;
+      count += 1;
+    }
+
+    let mut force = Vec2::ZERO;
+
+    if count > 0 {
+      force += self.steer(boid, separation) * settings.separation_weight;
+      force += self.steer(boid, alignment / count as f32) * settings.alignment_weight;
+      force += self.steer(boid, center / count as f32 - boid.position) * settings.cohesion_weight;
+    }
+
+    for obstacle in &self.obstacles {
+      let offset = boid.position - obstacle.center;
+      let gap = offset.length() - obstacle.radius;
+
+      if gap < settings.avoidance_distance {
+        let urgency = 1. - (gap / settings.avoidance_distance).max(0.);
+
+        force += self.steer(boid, offset) * settings.avoidance_weight * urgency;
+      }
+    }
+
+    force
+  }
+
+  /// Reynolds-style steering; the change in velocity to head in a direction.
+  fn steer(&self, boid: Boid, direction: Vec2) -> Vec2 {
+    let Some(direction) = direction.try_normalize() else {
+      return Vec2::ZERO;
+    };
+
+    let desired = direction * self.settings.max_speed;
+
+    (desired - boid.velocity).clamp_length_max(self.settings.max_force)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::vec2;
+
+  #[test]
+  fn it_should_separate_crowded_boids() {
+    let mut flock = Flock::new(FlockSettings {
+      cohesion_weight: 0.,
+      ..Default::default()
+    });
+
+    flock.add_boid(Boid {
+      position: vec2(0., 0.),
+      ..Default::default()
+    });
+    flock.add_boid(Boid {
+      position: vec2(0.2, 0.),
+      ..Default::default()
+    });
+
+    for _ in 0..20 {
+      flock.step(0.05);
+    }
+
+    let boids = flock.boids();
+
+    assert!(boids[0].position.distance(boids[1].position) > 1.);
+  }
+
+  #[test]
+  fn it_should_align_headings() {
+    let mut flock = Flock::new(FlockSettings {
+      separation_weight: 0.,
+      cohesion_weight: 0.,
+      ..Default::default()
+    });
+
+    flock.add_boid(Boid {
+      position: vec2(0., 0.),
+      velocity: vec2(2., 0.),
+    });
+    flock.add_boid(Boid {
+      position: vec2(0., 2.),
+      velocity: vec2(0., 2.),
+    });
+
+    for _ in 0..40 {
+      flock.step(0.05);
+    }
+
+    let boids = flock.boids();
+    let a = boids[0].velocity.normalize();
+    let b = boids[1].velocity.normalize();
+
+    assert!(a.dot(b) > 0.99, "{a} vs {b}");
+  }
+
+  #[test]
+  fn it_should_steer_around_obstacles() {
+    let mut flock = Flock::default();
+
+    flock.add_obstacle(FlockObstacle {
+      center: vec2(5., 0.),
+      radius: 1.,
+    });
+    flock.add_boid(Boid {
+      position: vec2(0., 0.1),
+      velocity: vec2(4., 0.),
+    });
+
+    for _ in 0..60 {
+      flock.step(0.05);
+
+      let boid = flock.boids()[0];
+
+      assert!(
+        boid.position.distance(vec2(5., 0.)) > 1.,
+        "hit the obstacle at {}",
+        boid.position
+      );
+    }
+  }
+}
@@ -0,0 +1,356 @@
+//! Perception for AI agents; sight, hearing and memory.
+
+use std::hash::Hash;
+
+use super::*;
+
+/// Determines whether one point can see another.
+///
+/// Typically implemented by a physics world via raycasts, so that walls and
+/// other colliders block vision.
+pub trait LineOfSight {
+  /// Determines if there's nothing blocking the view between two points.
+  fn has_line_of_sight(&self, from: Vec2, to: Vec2) -> bool;
+}
+
+/// A [`LineOfSight`] where nothing is ever occluded.
+impl LineOfSight for () {
+  fn has_line_of_sight(&self, _from: Vec2, _to: Vec2) -> bool {
+    true
+  }
+}
+
+/// A cone of vision projecting out from an agent.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VisionCone {
+  /// How far the agent can see, in world units.
+  pub range: f32,
+  /// The angle either side of the facing direction that can be seen, in
+  /// radians.
+  pub half_angle: f32,
+}
+
+impl Default for VisionCone {
+  fn default() -> Self {
+    Self {
+      range: 10.,
+      half_angle: std::f32::consts::FRAC_PI_4,
+    }
+  }
+}
+
+impl VisionCone {
+  /// Determines if the point lies inside the cone, ignoring occlusion.
+  pub fn contains(&self, origin: Vec2, facing: Vec2, point: Vec2) -> bool {
+    let offset = point - origin;
+    let distance = offset.length();
+
+    if distance > self.range {
+      return false;
+    }
+
+    if distance <= f32::EPSILON {
+      return true;
+    }
+
+    facing.normalize_or_zero().dot(offset / distance) >= self.half_angle.cos()
+  }
+}
+
+/// A noise emitted by gameplay, like footsteps or gunfire.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HearingEvent<T> {
+  /// Who made the noise, if anyone in particular.
+  pub source: Option<T>,
+  pub position: Vec2,
+  /// How loud the noise is at its source, from 0 to 1.
+  pub loudness: f32,
+  /// How far the noise carries before fading out entirely, in world units.
+  pub radius: f32,
+}
+
+impl<T> HearingEvent<T> {
+  /// How loud the noise is at the given position, with linear falloff.
+  pub fn loudness_at(&self, position: Vec2) -> f32 {
+    if self.radius <= 0. {
+      return 0.;
+    }
+
+    let falloff = 1. - position.distance(self.position) / self.radius;
+
+    self.loudness * falloff.clamp(0., 1.)
+  }
+}
+
+/// The sense a memory was last refreshed by.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Sense {
+  Sight,
+  Hearing,
+}
+
+/// What an agent remembers about something it perceived.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PerceptionMemory {
+  /// Where it was last seen or heard.
+  pub last_known_position: Vec2,
+  /// How long ago it was last perceived, in seconds.
+  pub age: f32,
+  pub sense: Sense,
+  /// Whether it's visible right now.
+  pub is_visible: bool,
+}
+
+/// The senses and memory of a single AI agent.
+#[derive(Clone, Debug)]
+pub struct Perceiver<T> {
+  pub position: Vec2,
+  /// The direction the agent is looking.
+  pub facing: Vec2,
+  pub vision: VisionCone,
+  /// The quietest noise the agent notices, from 0 to 1.
+  pub hearing_threshold: f32,
+  /// How long the agent remembers things it can no longer perceive.
+  pub memory_duration: f32,
+  memories: FastHashMap<T, PerceptionMemory>,
+}
+
+impl<T> Default for Perceiver<T> {
+  fn default() -> Self {
+    Self {
+      position: Vec2::ZERO,
+      facing: Vec2::X,
+      vision: VisionCone::default(),
+      hearing_threshold: 0.1,
+      memory_duration: 10.,
+      memories: FastHashMap::default(),
+    }
+  }
+}
+
+impl<T: Copy + Eq + Hash> Perceiver<T> {
+  /// Determines if the given point is within the vision cone and unoccluded.
+  pub fn can_see(&self, point: Vec2, sight: &dyn LineOfSight) -> bool {
+    self.vision.contains(self.position, self.facing, point) && sight.has_line_of_sight(self.position, point)
+  }
+
+  /// Determines if the given noise is loud enough to be heard.
+  pub fn can_hear(&self, event: &HearingEvent<T>) -> bool {
+    let loudness = event.loudness_at(self.position);
+
+    loudness > 0. && loudness >= self.hearing_threshold
+  }
+
+  /// Senses the given targets and noises, updating memory.
+  ///
+  /// Anything not perceived for longer than the memory duration is forgotten.
+  pub fn perceive(
+    &mut self,
+    delta_time: f32,
+    targets: &[(T, Vec2)],
+    noises: &[HearingEvent<T>],
+    sight: &dyn LineOfSight,
+  ) {
+    for memory in self.memories.values_mut() {
+      memory.age += delta_time;
+      memory.is_visible = false;
+    }
+
+    for &(target, position) in targets {
+      if self.can_see(position, sight) {
+        self.memories.insert(target, PerceptionMemory {
+          last_known_position: position,
+          age: 0.,
+          sense: Sense::Sight,
+          is_visible: true,
+        });
+      }
+    }
+
+    for noise in noises {
+      let Some(source) = noise.source else {
+        continue;
+      };
+
+      if !self.can_hear(noise) {
+        continue;
+      }
+
+      // sight is more reliable than hearing; don't overwrite what we can see
+      let memory = self.memories.entry(source).or_insert(PerceptionMemory {
+        last_known_position: noise.position,
+        age: 0.,
+        sense: Sense::Hearing,
+        is_visible: false,
+      });
+
+      if !memory.is_visible {
+        memory.last_known_position = noise.position;
+        memory.age = 0.;
+        memory.sense = Sense::Hearing;
+      }
+    }
+
+    let memory_duration = self.memory_duration;
+
+    self.memories.retain(|_, memory| memory.age <= memory_duration);
+  }
+
+  /// What the agent remembers about the given target.
+  pub fn memory_of(&self, target: T) -> Option<&PerceptionMemory> {
+    self.memories.get(&target)
+  }
+
+  /// Everything the agent currently remembers.
+  pub fn memories(&self) -> impl Iterator<Item = (&T, &PerceptionMemory)> {
+    self.memories.iter()
+  }
+
+  /// The most recently perceived target, preferring visible ones.
+  pub fn best_target(&self) -> Option<(T, &PerceptionMemory)> {
+    self
+      .memories
+      .iter()
+      .min_by(|(_, a), (_, b)| (!a.is_visible, a.age).partial_cmp(&(!b.is_visible, b.age)).unwrap())
+      .map(|(target, memory)| (*target, memory))
+  }
+
+  /// Writes the best target into the blackboard, or clears it if there's none.
+  ///
+  /// Sets `has_target`, and when there is one, `target_position`,
+  /// `target_visible` and `target_age`.
+  pub fn write_to(&self, blackboard: &mut Blackboard) {
+    match self.best_target() {
+      Some((_, memory)) => {
+        blackboard.set("has_target", true);
+        blackboard.set("target_position", memory.last_known_position);
+        blackboard.set("target_visible", memory.is_visible);
+        blackboard.set("target_age", memory.age);
+      }
+      None => {
+        blackboard.set("has_target", false);
+        blackboard.remove("target_position");
+        blackboard.remove("target_visible");
+        blackboard.remove("target_age");
+      }
+    }
+  }
+}
+
+/// Collects noises emitted by gameplay and delivers them to perceivers.
+#[derive(Clone, Debug)]
+pub struct PerceptionSystem<T> {
+  noises: Vec<HearingEvent<T>>,
+}
+
+impl<T> Default for PerceptionSystem<T> {
+  fn default() -> Self {
+    Self { noises: Vec::new() }
+  }
+}
+
+impl<T: Copy + Eq + Hash> PerceptionSystem<T> {
+  /// Creates a new, empty perception system.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Emits a noise, to be heard on the next update.
+  pub fn emit_noise(&mut self, event: HearingEvent<T>) {
+    self.noises.push(event);
+  }
+
+  /// Updates all perceivers with the given targets and pending noises.
+  pub fn update(
+    &mut self,
+    delta_time: f32,
+    perceivers: &mut [Perceiver<T>],
+    targets: &[(T, Vec2)],
+    sight: &dyn LineOfSight,
+  ) {
+    for perceiver in perceivers {
+      perceiver.perceive(delta_time, targets, &self.noises, sight);
+    }
+
+    self.noises.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::vec2;
+
+  /// A wall along x = 5 that blocks sight.
+  struct Wall;
+
+  impl LineOfSight for Wall {
+    fn has_line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+      (from.x < 5.) == (to.x < 5.)
+    }
+  }
+
+  #[test]
+  fn it_should_see_targets_inside_the_cone() {
+    let perceiver = Perceiver::<u32>::default();
+
+    assert!(perceiver.can_see(vec2(5., 1.), &()));
+    assert!(!perceiver.can_see(vec2(-5., 0.), &()));
+    assert!(!perceiver.can_see(vec2(20., 0.), &()));
+    assert!(!perceiver.can_see(vec2(6., 0.), &Wall));
+  }
+
+  #[test]
+  fn it_should_attenuate_noises_with_distance() {
+    let noise = HearingEvent::<u32> {
+      source: Some(1),
+      position: Vec2::ZERO,
+      loudness: 1.,
+      radius: 10.,
+    };
+
+    assert_eq!(noise.loudness_at(Vec2::ZERO), 1.);
+    assert_eq!(noise.loudness_at(vec2(5., 0.)), 0.5);
+    assert_eq!(noise.loudness_at(vec2(20., 0.)), 0.);
+  }
+
+  #[test]
+  fn it_should_remember_last_known_positions() {
+    let mut system = PerceptionSystem::new();
+    let mut perceivers = [Perceiver {
+      memory_duration: 2.,
+      ..Default::default()
+    }];
+
+    system.update(0.1, &mut perceivers, &[(1, vec2(3., 0.))], &Wall);
+    assert!(perceivers[0].memory_of(1).unwrap().is_visible);
+
+    // the target ducks behind the wall, but makes some noise
+    system.emit_noise(HearingEvent {
+      source: Some(1),
+      position: vec2(7., 1.),
+      loudness: 1.,
+      radius: 20.,
+    });
+    system.update(0.1, &mut perceivers, &[(1, vec2(7., 1.))], &Wall);
+
+    let memory = perceivers[0].memory_of(1).unwrap();
+    assert!(!memory.is_visible);
+    assert_eq!(memory.sense, Sense::Hearing);
+    assert_eq!(memory.last_known_position, vec2(7., 1.));
+
+    let mut blackboard = Blackboard::new();
+    perceivers[0].write_to(&mut blackboard);
+
+    assert_eq!(blackboard.get_as::<Vec2>("target_position"), Some(vec2(7., 1.)));
+    assert_eq!(blackboard.get_as::<bool>("target_visible"), Some(false));
+
+    // and eventually we forget about it
+    system.update(3., &mut perceivers, &[(1, vec2(7., 1.))], &Wall);
+    perceivers[0].write_to(&mut blackboard);
+
+    assert!(perceivers[0].memory_of(1).is_none());
+    assert_eq!(blackboard.get_as::<bool>("has_target"), Some(false));
+    assert!(!blackboard.contains("target_position"));
+  }
+}
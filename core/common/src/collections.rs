@@ -17,6 +17,7 @@ pub use ringbuffer::*;
 pub use smallvec::{smallvec, SmallVec};
 pub use spatialhash::*;
 pub use swapvec::*;
+pub use tilegrid::*;
 
 mod anymap;
 mod arena;
@@ -29,6 +30,7 @@ mod quadtree;
 mod ringbuffer;
 mod spatialhash;
 mod swapvec;
+mod tilegrid;
 
 /// A faster hasher that is not resilient to DoS attacks.
 type FastHasher = BuildHasherDefault<rustc_hash::FxHasher>;
@@ -31,7 +31,7 @@ pub trait ArenaIndex {
 /// occupied remains empty until the next insert. This means that the order of
 /// elements in the arena is not guaranteed to be the same as the order in which
 /// they were inserted.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Arena<K, V> {
   entries: Vec<ArenaEntry<V>>,
   current_generation: u32,
@@ -40,7 +40,7 @@ pub struct Arena<K, V> {
 }
 
 /// A single entry in an `Arena`.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 enum ArenaEntry<T> {
   Vacant,
   Occupied { value: T, generation: u32 },
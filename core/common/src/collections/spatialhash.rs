@@ -1,9 +1,12 @@
-use std::{collections::HashMap, hash::RandomState};
+use std::{
+  collections::HashMap,
+  hash::{BuildHasher, RandomState},
+};
 
 use crate::{
   impl_arena_index,
   maths::{ivec2, IVec2, Vec2},
-  unsafe_mutable_alias, Arena,
+  unsafe_mutable_alias, Arena, ArenaIndex,
 };
 
 // Internal index for a spatial hash map entry.
@@ -16,9 +19,28 @@ pub enum SpatialShape {
 }
 
 impl SpatialShape {
+  /// The axis-aligned bounds of this shape, as (min, max).
+  pub fn bounds(&self) -> (Vec2, Vec2) {
+    match self {
+      SpatialShape::Circle { center, radius } => (*center - Vec2::splat(*radius), *center + Vec2::splat(*radius)),
+      SpatialShape::AABB { min, max } => (*min, *max),
+    }
+  }
+
   /// Determines if this shape intersects the given other shape
-  pub fn intersects(&self, _other: &SpatialShape) -> bool {
-    todo!()
+  pub fn intersects(&self, other: &SpatialShape) -> bool {
+    match (self, other) {
+      (SpatialShape::Circle { center: a, radius: ra }, SpatialShape::Circle { center: b, radius: rb }) => {
+        a.distance_squared(*b) <= (ra + rb) * (ra + rb)
+      }
+      (SpatialShape::Circle { center, radius }, SpatialShape::AABB { min, max })
+      | (SpatialShape::AABB { min, max }, SpatialShape::Circle { center, radius }) => {
+        center.clamp(*min, *max).distance_squared(*center) <= radius * radius
+      }
+      (SpatialShape::AABB { min: a_min, max: a_max }, SpatialShape::AABB { min: b_min, max: b_max }) => {
+        a_min.cmple(*b_max).all() && b_min.cmple(*a_max).all()
+      }
+    }
   }
 }
 
@@ -81,57 +103,52 @@ impl<T> SpatialHashMap<T> {
 
   /// Adds an item to the spatial hash map.
   pub fn add(&mut self, shape: SpatialShape, value: T) {
-    match &shape {
-      SpatialShape::Circle { center, radius } => {
-        let min = (*center - Vec2::splat(*radius)).floor();
-        let max = (*center + Vec2::splat(*radius)).ceil();
+    let (min, max) = self.cell_range(&shape);
+    let capacity = (max.x - min.x) as usize;
 
-        self.add(SpatialShape::AABB { min, max }, value);
-      }
-      SpatialShape::AABB { min, max } => {
-        let min = (*min / self.resolution).floor();
-        let max = (*max / self.resolution).ceil();
-        let capacity = max.x as usize - min.x as usize;
-
-        let entry = Entry { value, shape };
-        let index = self.values.insert(entry);
-
-        for x in min.x as i32..max.x as i32 {
-          for y in min.y as i32..max.y as i32 {
-            let point = ivec2(x, y);
-
-            self
-              .lookup
-              .entry(point)
-              .or_insert_with(|| Vec::with_capacity(capacity))
-              .push(index);
-          }
-        }
+    let entry = Entry { value, shape };
+    let index = self.values.insert(entry);
+
+    for x in min.x..max.x {
+      for y in min.y..max.y {
+        let point = ivec2(x, y);
+
+        self
+          .lookup
+          .entry(point)
+          .or_insert_with(|| Vec::with_capacity(capacity))
+          .push(index);
       }
-    };
+    }
   }
 
   /// Queries for all entries in the given shape
   pub fn query(&self, shape: SpatialShape) -> impl Iterator<Item = &T> {
     self
-      .values
-      .iter()
+      .candidates(&shape)
+      .into_iter()
+      .flat_map(|index| self.values.get(index))
       .filter(move |it| it.shape.intersects(&shape))
       .map(|it| &it.value)
   }
 
   /// Mutably queries for all entries in the given shape
-  pub fn query_mut(&mut self, shape: SpatialShape) -> impl Iterator<Item = &T> {
+  pub fn query_mut(&mut self, shape: SpatialShape) -> impl Iterator<Item = &mut T> {
+    let candidates = self.candidates(&shape);
+
     self
       .values
-      .iter_mut()
-      .filter(move |it| it.shape.intersects(&shape))
-      .map(|it| &it.value)
+      .enumerate_mut()
+      .filter(move |(index, it)| {
+        candidates.binary_search_by_key(&sort_key(index), sort_key).is_ok() && it.shape.intersects(&shape)
+      })
+      .map(|(_, it)| &mut it.value)
   }
 
   /// Clears the spatial hash map of all entries.
   pub fn clear(&mut self) {
     self.lookup.clear();
+    self.values.clear();
   }
 
   /// Returns an iterator over the items in the spatial hash map.
@@ -182,6 +199,42 @@ impl<T> SpatialHashMap<T> {
   }
 }
 
+impl<T, S: BuildHasher> SpatialHashMap<T, S> {
+  /// The range of cells covered by the given shape, as [min, max).
+  fn cell_range(&self, shape: &SpatialShape) -> (IVec2, IVec2) {
+    let (min, max) = shape.bounds();
+
+    let min = (min / self.resolution).floor().as_ivec2();
+    let max = (max / self.resolution).ceil().as_ivec2().max(min + IVec2::ONE);
+
+    (min, max)
+  }
+
+  /// The entries in the cells covered by the given shape, sorted and
+  /// deduplicated.
+  fn candidates(&self, shape: &SpatialShape) -> Vec<EntryIndex> {
+    let (min, max) = self.cell_range(shape);
+    let mut candidates = Vec::new();
+
+    for x in min.x..max.x {
+      for y in min.y..max.y {
+        if let Some(contents) = self.lookup.get(&ivec2(x, y)) {
+          candidates.extend_from_slice(contents);
+        }
+      }
+    }
+
+    candidates.sort_by_key(sort_key);
+    candidates.dedup();
+    candidates
+  }
+}
+
+/// Orders entry indices so that duplicates are adjacent.
+fn sort_key(index: &EntryIndex) -> (u32, u32) {
+  (index.ordinal(), index.generation())
+}
+
 impl<'a, T> IntoIterator for &'a SpatialHashMap<T> {
   type Item = &'a T;
   type IntoIter = impl Iterator<Item = Self::Item>;
@@ -218,4 +271,52 @@ mod tests {
 
     assert_eq!(map.len(), 4);
   }
+
+  #[test]
+  fn spatial_hash_map_should_query_intersecting_shapes() {
+    let mut map = SpatialHashMap::with_resolution(10.0);
+
+    for (index, x) in [0.0, 5.0, 25.0, 100.0].into_iter().enumerate() {
+      map.add(
+        SpatialShape::Circle {
+          center: Vec2::new(x, 0.0),
+          radius: 1.0,
+        },
+        index,
+      );
+    }
+
+    let mut results: Vec<_> = map
+      .query(SpatialShape::Circle {
+        center: Vec2::new(3.0, 0.0),
+        radius: 4.0,
+      })
+      .copied()
+      .collect();
+
+    results.sort();
+
+    assert_eq!(results, vec![0, 1]);
+
+    let results: Vec<_> = map
+      .query(SpatialShape::AABB {
+        min: Vec2::new(20.0, -5.0),
+        max: Vec2::new(120.0, 5.0),
+      })
+      .collect();
+
+    assert_eq!(results.len(), 2);
+
+    map.clear();
+
+    assert_eq!(
+      map
+        .query(SpatialShape::AABB {
+          min: Vec2::ZERO,
+          max: Vec2::ONE
+        })
+        .count(),
+      0
+    );
+  }
 }
@@ -0,0 +1,355 @@
+use std::{collections::VecDeque, hash::Hash};
+
+pub use commands::*;
+
+use super::{DenseGrid, FastHashMap, FastHashSet};
+use crate::maths::{heuristics, ivec2, IVec2, NeighbourList, Neighbourhood, PathFindingGrid};
+
+/// A single tile in a [`TileGrid`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tile {
+  /// Can actors stand on this tile?
+  pub walkable: bool,
+  /// Does this tile block line of sight?
+  pub opaque: bool,
+}
+
+impl Tile {
+  /// An open floor tile.
+  pub const FLOOR: Self = Self {
+    walkable: true,
+    opaque: false,
+  };
+
+  /// A solid wall tile.
+  pub const WALL: Self = Self {
+    walkable: false,
+    opaque: true,
+  };
+}
+
+impl Default for Tile {
+  fn default() -> Self {
+    Self::FLOOR
+  }
+}
+
+/// An error when placing or moving actors on a [`TileGrid`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum TileGridError {
+  OutOfBounds(IVec2),
+  NotWalkable(IVec2),
+  Occupied(IVec2),
+  UnknownActor,
+}
+
+/// A grid-based world model of tiles and the actors standing on them.
+///
+/// Each tile holds at most one actor; `A` is whatever identifies an actor in
+/// the game (an entity id, an arena index, etc).
+#[derive(Clone, Debug)]
+pub struct TileGrid<A> {
+  tiles: DenseGrid<Tile>,
+  occupants: FastHashMap<IVec2, A>,
+  positions: FastHashMap<A, IVec2>,
+}
+
+impl<A: Copy + Eq + Hash> TileGrid<A> {
+  /// Creates a new grid of floor tiles with the given dimensions.
+  pub fn new(width: usize, height: usize) -> Self {
+    Self {
+      tiles: DenseGrid::new(width, height),
+      occupants: FastHashMap::default(),
+      positions: FastHashMap::default(),
+    }
+  }
+
+  /// The width of the grid, in tiles.
+  pub fn width(&self) -> usize {
+    self.tiles.width()
+  }
+
+  /// The height of the grid, in tiles.
+  pub fn height(&self) -> usize {
+    self.tiles.height()
+  }
+
+  /// Is the given position inside the grid?
+  pub fn is_in_bounds(&self, position: IVec2) -> bool {
+    self.tiles.is_valid(position.x, position.y)
+  }
+
+  /// Gets the tile at the given position.
+  pub fn tile(&self, position: IVec2) -> Option<&Tile> {
+    self.tiles.get(position.x, position.y)
+  }
+
+  /// Sets the tile at the given position.
+  pub fn set_tile(&mut self, position: IVec2, tile: Tile) {
+    self.tiles.set(position.x, position.y, tile);
+  }
+
+  /// Can an actor stand on the given position, ignoring occupancy?
+  pub fn is_walkable(&self, position: IVec2) -> bool {
+    self.tile(position).is_some_and(|tile| tile.walkable)
+  }
+
+  /// Is the given position walkable and unoccupied?
+  pub fn is_passable(&self, position: IVec2) -> bool {
+    self.is_walkable(position) && !self.occupants.contains_key(&position)
+  }
+
+  /// The actor standing on the given position, if any.
+  pub fn occupant(&self, position: IVec2) -> Option<A> {
+    self.occupants.get(&position).copied()
+  }
+
+  /// The position of the given actor, if it's on the grid.
+  pub fn position_of(&self, actor: A) -> Option<IVec2> {
+    self.positions.get(&actor).copied()
+  }
+
+  /// Iterates the actors on the grid and their positions.
+  pub fn actors(&self) -> impl Iterator<Item = (A, IVec2)> + '_ {
+    self.positions.iter().map(|(actor, position)| (*actor, *position))
+  }
+
+  /// Places an actor on the grid, moving it if it's already present.
+  ///
+  /// Placing an actor on the tile it already stands on does nothing.
+  pub fn place(&mut self, actor: A, position: IVec2) -> Result<(), TileGridError> {
+    if self.positions.get(&actor) == Some(&position) {
+      return Ok(());
+    }
+
+    self.check_destination(position)?;
+    self.remove(actor);
+
+    self.occupants.insert(position, actor);
+    self.positions.insert(actor, position);
+
+    Ok(())
+  }
+
+  /// Removes an actor from the grid, returning where it stood.
+  pub fn remove(&mut self, actor: A) -> Option<IVec2> {
+    let position = self.positions.remove(&actor)?;
+
+    self.occupants.remove(&position);
+
+    Some(position)
+  }
+
+  /// Moves an actor to the given position.
+  pub fn move_actor(&mut self, actor: A, position: IVec2) -> Result<(), TileGridError> {
+    if !self.positions.contains_key(&actor) {
+      return Err(TileGridError::UnknownActor);
+    }
+
+    self.place(actor, position)
+  }
+
+  /// Finds a path for an actor to the given goal.
+  ///
+  /// Other actors are not treated as obstacles, so crowds don't block routes;
+  /// they are resolved when moving, via [`MoveCommand`].
+  pub fn find_path_for(&self, actor: A, goal: IVec2) -> Option<VecDeque<IVec2>> {
+    let start = self.position_of(actor)?;
+
+    self.find_path(start, goal, heuristics::manhattan_distance)
+  }
+
+  /// The next step an actor should take towards the given goal.
+  pub fn step_towards(&self, actor: A, goal: IVec2) -> Option<MoveCommand<A>> {
+    let start = self.position_of(actor)?;
+    let path = self.find_path_for(actor, goal)?;
+    let next = *path.get(1)?;
+
+    Some(MoveCommand::new(actor, next - start))
+  }
+
+  /// Computes the tiles visible from the given position.
+  ///
+  /// Rays are cast to the edge of a square of the given radius; opaque tiles
+  /// are visible themselves but hide whatever is behind them.
+  pub fn field_of_view(&self, origin: IVec2, radius: i32) -> FastHashSet<IVec2> {
+    let mut visible = FastHashSet::default();
+
+    if !self.is_in_bounds(origin) {
+      return visible;
+    }
+
+    visible.insert(origin);
+
+    for i in -radius..=radius {
+      for target in [
+        origin + ivec2(i, -radius),
+        origin + ivec2(i, radius),
+        origin + ivec2(-radius, i),
+        origin + ivec2(radius, i),
+      ] {
+        self.cast_ray(origin, target, radius, &mut visible);
+      }
+    }
+
+    visible
+  }
+
+  /// Walks a Bresenham line from origin to target, marking visible tiles.
+  fn cast_ray(&self, origin: IVec2, target: IVec2, radius: i32, visible: &mut FastHashSet<IVec2>) {
+    let delta = (target - origin).abs();
+    let step = (target - origin).signum();
+    let mut error = delta.x - delta.y;
+    let mut current = origin;
+
+    while current != target {
+      let doubled = error * 2;
+
+      if doubled > -delta.y {
+        error -= delta.y;
+        current.x += step.x;
+      }
+
+      if doubled < delta.x {
+        error += delta.x;
+        current.y += step.y;
+      }
+
+      let offset = current - origin;
+      if offset.length_squared() > radius * radius {
+        break;
+      }
+
+      let Some(tile) = self.tile(current) else {
+        break;
+      };
+
+      visible.insert(current);
+
+      if tile.opaque {
+        break;
+      }
+    }
+  }
+
+  fn check_destination(&self, position: IVec2) -> Result<(), TileGridError> {
+    if !self.is_in_bounds(position) {
+      return Err(TileGridError::OutOfBounds(position));
+    }
+
+    if !self.is_walkable(position) {
+      return Err(TileGridError::NotWalkable(position));
+    }
+
+    if self.occupants.contains_key(&position) {
+      return Err(TileGridError::Occupied(position));
+    }
+
+    Ok(())
+  }
+}
+
+mod commands;
+
+impl<A: Copy + Eq + Hash> PathFindingGrid for TileGrid<A> {
+  fn get_neighbours(&self, center: IVec2, results: &mut NeighbourList<IVec2>) {
+    for neighbour in center.adjacent_neighbours() {
+      if self.is_walkable(neighbour) {
+        results.push(neighbour);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn create_grid() -> TileGrid<u32> {
+    let mut grid = TileGrid::new(5, 5);
+
+    // a wall down the middle, with a gap at the top
+    for y in 0..4 {
+      grid.set_tile(ivec2(2, y), Tile::WALL);
+    }
+
+    grid
+  }
+
+  #[test]
+  fn it_should_track_occupancy() {
+    let mut grid = create_grid();
+
+    grid.place(1, ivec2(0, 0)).unwrap();
+
+    assert_eq!(grid.occupant(ivec2(0, 0)), Some(1));
+    assert_eq!(grid.place(1, ivec2(0, 0)), Ok(()));
+    assert_eq!(grid.place(2, ivec2(0, 0)), Err(TileGridError::Occupied(ivec2(0, 0))));
+    assert_eq!(grid.place(2, ivec2(2, 0)), Err(TileGridError::NotWalkable(ivec2(2, 0))));
+    assert_eq!(grid.place(2, ivec2(9, 0)), Err(TileGridError::OutOfBounds(ivec2(9, 0))));
+
+    grid.move_actor(1, ivec2(1, 0)).unwrap();
+
+    assert_eq!(grid.occupant(ivec2(0, 0)), None);
+    assert_eq!(grid.position_of(1), Some(ivec2(1, 0)));
+    assert_eq!(grid.remove(1), Some(ivec2(1, 0)));
+    assert!(grid.is_passable(ivec2(1, 0)));
+  }
+
+  #[test]
+  fn it_should_resolve_moves_and_bumps() {
+    let mut grid = create_grid();
+
+    grid.place(1, ivec2(1, 0)).unwrap();
+    grid.place(2, ivec2(1, 1)).unwrap();
+
+    let outcome = MoveCommand::new(1, ivec2(1, 0)).execute(&mut grid).unwrap();
+    assert_eq!(outcome, MoveOutcome::Blocked { position: ivec2(2, 0) });
+
+    let outcome = MoveCommand::new(1, ivec2(0, 1)).execute(&mut grid).unwrap();
+    assert_eq!(outcome, MoveOutcome::Attack {
+      target: 2,
+      position: ivec2(1, 1)
+    });
+
+    let outcome = MoveCommand::new(1, ivec2(-1, 0)).execute(&mut grid).unwrap();
+    assert_eq!(outcome, MoveOutcome::Moved {
+      from: ivec2(1, 0),
+      to: ivec2(0, 0)
+    });
+  }
+
+  #[test]
+  fn it_should_path_actors_around_walls() {
+    let mut grid = create_grid();
+
+    grid.place(1, ivec2(0, 0)).unwrap();
+
+    let goal = ivec2(4, 0);
+    let path = grid.find_path_for(1, goal).unwrap();
+
+    assert!(path.contains(&ivec2(2, 4)));
+
+    for _ in 0..32 {
+      let Some(command) = grid.step_towards(1, goal) else {
+        break;
+      };
+
+      command.execute(&mut grid).unwrap();
+    }
+
+    assert_eq!(grid.position_of(1), Some(goal));
+  }
+
+  #[test]
+  fn it_should_compute_field_of_view() {
+    let grid = create_grid();
+    let visible = grid.field_of_view(ivec2(0, 0), 8);
+
+    assert!(visible.contains(&ivec2(1, 0)));
+    assert!(visible.contains(&ivec2(2, 0)));
+    assert!(!visible.contains(&ivec2(3, 0)));
+    assert!(visible.contains(&ivec2(1, 4)));
+    assert!(!visible.contains(&ivec2(4, 4)));
+  }
+}
@@ -0,0 +1,527 @@
+//! Commands for actors on a [`TileGrid`].
+//!
+//! Commands follow the 'alternative' pattern: rather than failing outright, a
+//! command may hand back another command to perform in its place. A bump into
+//! an enemy becomes an attack, and long-running commands (auto-explore,
+//! travel, rest) perform one step per turn by returning that step as their
+//! alternative, staying active in a [`CommandRunner`] until they finish.
+
+use std::collections::VecDeque;
+
+use super::*;
+
+/// The most alternatives followed for a single command, so commands that hand
+/// back each other in a cycle fail rather than hang.
+const MAX_ALTERNATIVES: usize = 64;
+
+/// The world that commands act upon; implemented by the game.
+#[allow(unused_variables)]
+pub trait CommandWorld<A> {
+  /// The grid the actors stand on.
+  fn grid(&self) -> &TileGrid<A>;
+
+  /// The grid the actors stand on, mutably.
+  fn grid_mut(&mut self) -> &mut TileGrid<A>;
+
+  /// How far the given actor can see, in tiles.
+  fn sight_radius(&self, actor: A) -> i32 {
+    8
+  }
+
+  /// Would the given actor consider the other hostile?
+  fn is_hostile(&self, actor: A, other: A) -> bool {
+    false
+  }
+
+  /// Has the given actor explored this tile?
+  fn is_explored(&self, actor: A, position: IVec2) -> bool {
+    true
+  }
+
+  /// Records that the given actor has seen these tiles.
+  fn explore(&mut self, actor: A, visible: &FastHashSet<IVec2>) {}
+
+  /// The actor's current and maximum health.
+  fn health(&self, actor: A) -> (f32, f32) {
+    (1., 1.)
+  }
+
+  /// Resolves an attack from one actor against another.
+  fn attack(&mut self, actor: A, target: A) {}
+
+  /// Spends a turn resting.
+  fn rest(&mut self, actor: A) {}
+}
+
+/// The result of performing a [`Command`].
+pub enum CommandResult<A> {
+  /// The command has finished.
+  Success,
+  /// The command could not be performed; no turn was spent.
+  Failure,
+  /// Perform this command instead.
+  Alternative(Box<dyn Command<A>>),
+}
+
+/// Something an actor can do.
+pub trait Command<A> {
+  /// Performs the command for the given actor.
+  fn perform(&mut self, world: &mut dyn CommandWorld<A>, actor: A) -> CommandResult<A>;
+}
+
+/// Performs a command, following any alternatives until one succeeds or
+/// fails.
+///
+/// A command that's still handing back alternatives after
+/// [`MAX_ALTERNATIVES`] of them fails.
+pub fn perform_command<A: Copy>(command: &mut dyn Command<A>, world: &mut dyn CommandWorld<A>, actor: A) -> bool {
+  let mut result = command.perform(world, actor);
+
+  for _ in 0..MAX_ALTERNATIVES {
+    match result {
+      CommandResult::Success => return true,
+      CommandResult::Failure => return false,
+      CommandResult::Alternative(mut alternative) => result = alternative.perform(world, actor),
+    }
+  }
+
+  matches!(result, CommandResult::Success)
+}
+
+/// The outcome of a turn in a [`CommandRunner`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TurnOutcome {
+  /// Nothing to do.
+  Idle,
+  /// A step was performed and the command is still running.
+  Performed,
+  /// The command finished.
+  Completed,
+  /// The command failed or was interrupted.
+  Failed,
+}
+
+/// Runs a single, possibly long-running, command for an actor.
+pub struct CommandRunner<A> {
+  active: Option<Box<dyn Command<A>>>,
+}
+
+impl<A> Default for CommandRunner<A> {
+  fn default() -> Self {
+    Self { active: None }
+  }
+}
+
+impl<A: Copy> CommandRunner<A> {
+  /// Starts a new command, replacing any that is running.
+  pub fn start(&mut self, command: impl Command<A> + 'static) {
+    self.active = Some(Box::new(command));
+  }
+
+  /// Is a command running?
+  pub fn is_busy(&self) -> bool {
+    self.active.is_some()
+  }
+
+  /// Cancels the running command.
+  pub fn cancel(&mut self) {
+    self.active = None;
+  }
+
+  /// Performs one turn of the running command.
+  pub fn take_turn(&mut self, world: &mut dyn CommandWorld<A>, actor: A) -> TurnOutcome {
+    let Some(command) = &mut self.active else {
+      return TurnOutcome::Idle;
+    };
+
+    let outcome = match command.perform(world, actor) {
+      CommandResult::Success => TurnOutcome::Completed,
+      CommandResult::Failure => TurnOutcome::Failed,
+      CommandResult::Alternative(mut step) => match perform_command(step.as_mut(), world, actor) {
+        true => return TurnOutcome::Performed,
+        false => TurnOutcome::Failed,
+      },
+    };
+
+    self.active = None;
+
+    outcome
+  }
+}
+
+/// Moves an actor one step in a direction, bumping into whatever is there.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MoveCommand<A> {
+  pub actor: A,
+  pub direction: IVec2,
+}
+
+/// The result of executing a [`MoveCommand`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MoveOutcome<A> {
+  /// The actor moved to a new tile.
+  Moved { from: IVec2, to: IVec2 },
+  /// The actor bumped into another actor, and should attack it.
+  Attack { target: A, position: IVec2 },
+  /// The actor bumped into a wall or the edge of the grid.
+  Blocked { position: IVec2 },
+}
+
+impl<A: Copy + Eq + Hash> MoveCommand<A> {
+  /// Creates a new move command; the direction is clamped to a single step.
+  pub fn new(actor: A, direction: IVec2) -> Self {
+    Self {
+      actor,
+      direction: direction.clamp(ivec2(-1, -1), ivec2(1, 1)),
+    }
+  }
+
+  /// Executes the command against the given grid.
+  pub fn execute(&self, grid: &mut TileGrid<A>) -> Result<MoveOutcome<A>, TileGridError> {
+    let from = grid.position_of(self.actor).ok_or(TileGridError::UnknownActor)?;
+    let to = from + self.direction;
+
+    if let Some(target) = grid.occupant(to).filter(|target| *target != self.actor) {
+      return Ok(MoveOutcome::Attack { target, position: to });
+    }
+
+    if !grid.is_walkable(to) {
+      return Ok(MoveOutcome::Blocked { position: to });
+    }
+
+    grid.move_actor(self.actor, to)?;
+
+    Ok(MoveOutcome::Moved { from, to })
+  }
+}
+
+impl<A: Copy + Eq + Hash + 'static> Command<A> for MoveCommand<A> {
+  fn perform(&mut self, world: &mut dyn CommandWorld<A>, _actor: A) -> CommandResult<A> {
+    match self.execute(world.grid_mut()) {
+      Ok(MoveOutcome::Moved { .. }) => CommandResult::Success,
+      Ok(MoveOutcome::Attack { target, .. }) => CommandResult::Alternative(Box::new(AttackCommand { target })),
+      Ok(MoveOutcome::Blocked { .. }) | Err(_) => CommandResult::Failure,
+    }
+  }
+}
+
+/// Attacks another actor.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AttackCommand<A> {
+  pub target: A,
+}
+
+impl<A: Copy> Command<A> for AttackCommand<A> {
+  fn perform(&mut self, world: &mut dyn CommandWorld<A>, actor: A) -> CommandResult<A> {
+    world.attack(actor, self.target);
+
+    CommandResult::Success
+  }
+}
+
+/// Rests for a single turn.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RestCommand;
+
+impl<A: Copy> Command<A> for RestCommand {
+  fn perform(&mut self, world: &mut dyn CommandWorld<A>, actor: A) -> CommandResult<A> {
+    world.rest(actor);
+
+    CommandResult::Success
+  }
+}
+
+/// Walks towards unexplored tiles until the map is explored or an enemy is
+/// sighted.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AutoExploreCommand;
+
+impl<A: Copy + Eq + Hash + 'static> Command<A> for AutoExploreCommand {
+  fn perform(&mut self, world: &mut dyn CommandWorld<A>, actor: A) -> CommandResult<A> {
+    if look_around(world, actor) {
+      return CommandResult::Failure;
+    }
+
+    let grid = world.grid();
+    let Some(start) = grid.position_of(actor) else {
+      return CommandResult::Failure;
+    };
+
+    // breadth-first search for the nearest reachable unexplored tile
+    let mut frontier = VecDeque::from([start]);
+    let mut visited = FastHashSet::from_iter([start]);
+    let mut neighbours = NeighbourList::new();
+
+    while let Some(current) = frontier.pop_front() {
+      if !world.is_explored(actor, current) {
+        return step_towards(world, actor, current);
+      }
+
+      neighbours.clear();
+      grid.get_neighbours(current, &mut neighbours);
+
+      for neighbour in &neighbours {
+        if visited.insert(*neighbour) {
+          frontier.push_back(*neighbour);
+        }
+      }
+    }
+
+    CommandResult::Success
+  }
+}
+
+/// Walks to a point, stopping if an enemy comes into view.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TravelToCommand {
+  pub goal: IVec2,
+}
+
+impl<A: Copy + Eq + Hash + 'static> Command<A> for TravelToCommand {
+  fn perform(&mut self, world: &mut dyn CommandWorld<A>, actor: A) -> CommandResult<A> {
+    if look_around(world, actor) {
+      return CommandResult::Failure;
+    }
+
+    if world.grid().position_of(actor) == Some(self.goal) {
+      return CommandResult::Success;
+    }
+
+    step_towards(world, actor, self.goal)
+  }
+}
+
+/// Rests until fully healed, stopping if an enemy comes into view.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RestUntilHealedCommand;
+
+impl<A: Copy + Eq + Hash + 'static> Command<A> for RestUntilHealedCommand {
+  fn perform(&mut self, world: &mut dyn CommandWorld<A>, actor: A) -> CommandResult<A> {
+    if look_around(world, actor) {
+      return CommandResult::Failure;
+    }
+
+    let (health, max_health) = world.health(actor);
+
+    if health >= max_health {
+      return CommandResult::Success;
+    }
+
+    CommandResult::Alternative(Box::new(RestCommand))
+  }
+}
+
+/// Takes the next step towards the goal.
+///
+/// Paths run through other actors, and stepping into one attacks it, so the
+/// walk stops rather than bumping into an actor that isn't hostile.
+fn step_towards<A: Copy + Eq + Hash + 'static>(world: &dyn CommandWorld<A>, actor: A, goal: IVec2) -> CommandResult<A> {
+  let grid = world.grid();
+  let (Some(position), Some(step)) = (grid.position_of(actor), grid.step_towards(actor, goal)) else {
+    return CommandResult::Failure;
+  };
+
+  match grid.occupant(position + step.direction) {
+    Some(other) if other != actor && !world.is_hostile(actor, other) => CommandResult::Failure,
+    _ => CommandResult::Alternative(Box::new(step)),
+  }
+}
+
+/// Updates the actor's exploration from its field of view, and reports
+/// whether any hostile actors are in sight.
+fn look_around<A: Copy + Eq + Hash>(world: &mut dyn CommandWorld<A>, actor: A) -> bool {
+  let Some(position) = world.grid().position_of(actor) else {
+    return false;
+  };
+
+  let visible = world.grid().field_of_view(position, world.sight_radius(actor));
+
+  world.explore(actor, &visible);
+
+  let grid = world.grid();
+
+  grid
+    .actors()
+    .any(|(other, position)| other != actor && visible.contains(&position) && world.is_hostile(actor, other))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HERO: u32 = 1;
+  const GOBLIN: u32 = 2;
+  const DOG: u32 = 3;
+
+  struct TestWorld {
+    grid: TileGrid<u32>,
+    explored: FastHashSet<IVec2>,
+    health: f32,
+    attacks: Vec<(u32, u32)>,
+  }
+
+  impl TestWorld {
+    fn new() -> Self {
+      let mut grid = TileGrid::new(8, 3);
+
+      // a corridor, with a door-less wall splitting it in two
+      for y in 0..2 {
+        grid.set_tile(ivec2(4, y), Tile::WALL);
+      }
+
+      grid.place(HERO, ivec2(0, 0)).unwrap();
+
+      Self {
+        grid,
+        explored: FastHashSet::default(),
+        health: 5.,
+        attacks: Vec::new(),
+      }
+    }
+  }
+
+  impl CommandWorld<u32> for TestWorld {
+    fn grid(&self) -> &TileGrid<u32> {
+      &self.grid
+    }
+
+    fn grid_mut(&mut self) -> &mut TileGrid<u32> {
+      &mut self.grid
+    }
+
+    fn sight_radius(&self, _actor: u32) -> i32 {
+      3
+    }
+
+    fn is_hostile(&self, actor: u32, other: u32) -> bool {
+      actor != other && other != DOG
+    }
+
+    fn is_explored(&self, _actor: u32, position: IVec2) -> bool {
+      self.explored.contains(&position)
+    }
+
+    fn explore(&mut self, _actor: u32, visible: &FastHashSet<IVec2>) {
+      self.explored.extend(visible);
+    }
+
+    fn health(&self, _actor: u32) -> (f32, f32) {
+      (self.health, 10.)
+    }
+
+    fn attack(&mut self, actor: u32, target: u32) {
+      self.attacks.push((actor, target));
+    }
+
+    fn rest(&mut self, _actor: u32) {
+      self.health += 1.;
+    }
+  }
+
+  fn run_until_done(runner: &mut CommandRunner<u32>, world: &mut TestWorld) -> (TurnOutcome, usize) {
+    for turn in 0..100 {
+      match runner.take_turn(world, HERO) {
+        TurnOutcome::Performed => continue,
+        outcome => return (outcome, turn),
+      }
+    }
+
+    panic!("command did not finish");
+  }
+
+  #[test]
+  fn it_should_turn_bumps_into_attacks() {
+    let mut world = TestWorld::new();
+
+    world.grid.place(GOBLIN, ivec2(1, 0)).unwrap();
+
+    assert!(perform_command(
+      &mut MoveCommand::new(HERO, ivec2(1, 0)),
+      &mut world,
+      HERO
+    ));
+    assert_eq!(world.attacks, vec![(HERO, GOBLIN)]);
+    assert!(!perform_command(
+      &mut MoveCommand::new(HERO, ivec2(0, -1)),
+      &mut world,
+      HERO
+    ));
+  }
+
+  #[test]
+  fn it_should_auto_explore_the_whole_map() {
+    let mut world = TestWorld::new();
+    let mut runner = CommandRunner::default();
+
+    runner.start(AutoExploreCommand);
+
+    let (outcome, _) = run_until_done(&mut runner, &mut world);
+
+    assert_eq!(outcome, TurnOutcome::Completed);
+    assert!(!runner.is_busy());
+
+    for y in 0..3 {
+      for x in 0..8 {
+        if world.grid.is_walkable(ivec2(x, y)) {
+          assert!(world.explored.contains(&ivec2(x, y)), "{x}, {y} unexplored");
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn it_should_interrupt_travel_when_an_enemy_is_sighted() {
+    let mut world = TestWorld::new();
+    let mut runner = CommandRunner::default();
+
+    world.grid.place(GOBLIN, ivec2(7, 0)).unwrap();
+    runner.start(TravelToCommand { goal: ivec2(7, 2) });
+
+    let (outcome, _) = run_until_done(&mut runner, &mut world);
+    let position = world.grid.position_of(HERO).unwrap();
+
+    assert_eq!(outcome, TurnOutcome::Failed);
+    assert!(position.x >= 3 && position != ivec2(7, 2));
+  }
+
+  #[test]
+  fn it_should_not_walk_into_allies() {
+    let mut world = TestWorld::new();
+    let mut runner = CommandRunner::default();
+
+    world.grid.place(DOG, ivec2(1, 0)).unwrap();
+    runner.start(TravelToCommand { goal: ivec2(2, 0) });
+
+    let (outcome, _) = run_until_done(&mut runner, &mut world);
+
+    assert_eq!(outcome, TurnOutcome::Failed);
+    assert!(world.attacks.is_empty());
+  }
+
+  #[test]
+  fn it_should_fail_alternatives_that_go_in_circles() {
+    struct Dither;
+
+    impl Command<u32> for Dither {
+      fn perform(&mut self, _world: &mut dyn CommandWorld<u32>, _actor: u32) -> CommandResult<u32> {
+        CommandResult::Alternative(Box::new(Dither))
+      }
+    }
+
+    assert!(!perform_command(&mut Dither, &mut TestWorld::new(), HERO));
+  }
+
+  #[test]
+  fn it_should_rest_until_healed() {
+    let mut world = TestWorld::new();
+    let mut runner = CommandRunner::default();
+
+    runner.start(RestUntilHealedCommand);
+
+    let (outcome, turns) = run_until_done(&mut runner, &mut world);
+
+    assert_eq!(outcome, TurnOutcome::Completed);
+    assert_eq!(turns, 5);
+    assert_eq!(world.health, 10.);
+  }
+}
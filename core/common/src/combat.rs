@@ -0,0 +1,584 @@
+//! Damage, health and hit detection for action games.
+//!
+//! Combatants take damage through their [`Health`], which can grant a window
+//! of invulnerability after each hit. Attacks are [`HitboxTrack`]s that switch
+//! hitboxes on and off over the frames of an animation. Each frame, a
+//! [`CombatWorld`] overlaps the active hitboxes of attackers against the
+//! [`HitShape`]s that make up other combatants' hurtboxes, applies damage and
+//! raises [`CombatEvent`]s for hits and deaths.
+
+use crate::{impl_arena_index, vec2, Arena, Checksum, FastHashSet, Rectangle, StateHasher, Vec2};
+
+impl_arena_index!(pub CombatantId, "Identifies a combatant in a combat world.");
+
+/// The health of something that can take damage.
+#[derive(Clone, Debug)]
+pub struct Health {
+  current: f32,
+  max: f32,
+  invulnerability: f32,
+  invulnerable_for: f32,
+}
+
+impl Checksum for Health {
+  fn checksum(&self, hasher: &mut StateHasher) {
+    hasher.write_f32(self.current);
+    hasher.write_f32(self.max);
+    hasher.write_f32(self.invulnerability);
+    hasher.write_f32(self.invulnerable_for);
+  }
+}
+
+/// The result of applying damage to [`Health`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DamageOutcome {
+  /// The damage was ignored, as the target was invulnerable or already dead.
+  Ignored,
+  /// The target took the given amount of damage.
+  Damaged(f32),
+  /// The target took the given amount of damage, and died.
+  Killed(f32),
+}
+
+impl Health {
+  /// Creates health at the given maximum.
+  pub fn new(max: f32) -> Self {
+    Self {
+      current: max,
+      max,
+      invulnerability: 0.,
+      invulnerable_for: 0.,
+    }
+  }
+
+  /// Grants invulnerability for the given number of seconds after each hit.
+  pub fn with_invulnerability(mut self, seconds: f32) -> Self {
+    self.invulnerability = seconds;
+    self
+  }
+
+  /// The current health.
+  pub fn current(&self) -> f32 {
+    self.current
+  }
+
+  /// The maximum health.
+  pub fn max(&self) -> f32 {
+    self.max
+  }
+
+  /// The seconds of invulnerability granted after each hit.
+  pub fn invulnerability(&self) -> f32 {
+    self.invulnerability
+  }
+
+  /// Determines if health has run out.
+  pub fn is_dead(&self) -> bool {
+    self.current <= 0.
+  }
+
+  /// Determines if damage is currently being ignored.
+  pub fn is_invulnerable(&self) -> bool {
+    self.invulnerable_for > 0.
+  }
+
+  /// Makes damage be ignored for the given number of seconds, e.g. while
+  /// dodging.
+  pub fn make_invulnerable(&mut self, seconds: f32) {
+    self.invulnerable_for = self.invulnerable_for.max(seconds);
+  }
+
+  /// Applies damage, unless invulnerable or dead.
+  pub fn damage(&mut self, amount: f32) -> DamageOutcome {
+    if self.is_dead() || self.is_invulnerable() || amount <= 0. {
+      return DamageOutcome::Ignored;
+    }
+
+    let amount = amount.min(self.current);
+
+    self.current -= amount;
+    self.invulnerable_for = self.invulnerability;
+
+    if self.is_dead() {
+      DamageOutcome::Killed(amount)
+    } else {
+      DamageOutcome::Damaged(amount)
+    }
+  }
+
+  /// Sets the current health directly, clamped to the maximum; for debugging
+  /// tools and save games rather than gameplay.
+  pub fn set_current(&mut self, current: f32) {
+    self.current = current.clamp(0., self.max);
+  }
+
+  /// Sets the maximum health, clamping the current health to it.
+  pub fn set_max(&mut self, max: f32) {
+    self.max = max;
+    self.current = self.current.min(max);
+  }
+
+  /// Restores health, up to the maximum. The dead stay dead.
+  pub fn heal(&mut self, amount: f32) {
+    if !self.is_dead() {
+      self.current = (self.current + amount).min(self.max);
+    }
+  }
+
+  /// Brings health back to the maximum, even if dead.
+  pub fn revive(&mut self) {
+    self.current = self.max;
+    self.invulnerable_for = 0.;
+  }
+
+  /// Counts down any invulnerability.
+  pub fn update(&mut self, delta_time: f32) {
+    self.invulnerable_for = (self.invulnerable_for - delta_time).max(0.);
+  }
+}
+
+/// A shape for hitboxes and hurtboxes, relative to its combatant.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HitShape {
+  Circle { center: Vec2, radius: f32 },
+  Rectangle(Rectangle),
+}
+
+impl HitShape {
+  /// Places the shape at a combatant's position, mirrored if they're facing
+  /// left.
+  pub fn to_world(&self, position: Vec2, flipped: bool) -> Self {
+    let mirror = |point: Vec2| if flipped { vec2(-point.x, point.y) } else { point };
+
+    match *self {
+      HitShape::Circle { center, radius } => HitShape::Circle {
+        center: position + mirror(center),
+        radius,
+      },
+      HitShape::Rectangle(rectangle) => {
+        let (a, b) = (mirror(rectangle.min), mirror(rectangle.max));
+
+        HitShape::Rectangle(Rectangle::new(position + a.min(b), position + a.max(b)))
+      }
+    }
+  }
+
+  /// Determines if two shapes overlap.
+  pub fn intersects(&self, other: &Self) -> bool {
+    match (self, other) {
+      (HitShape::Circle { center: a, radius: ra }, HitShape::Circle { center: b, radius: rb }) => {
+        a.distance_squared(*b) <= (ra + rb) * (ra + rb)
+      }
+      (HitShape::Rectangle(a), HitShape::Rectangle(b)) => a.intersects(b),
+      (HitShape::Circle { center, radius }, HitShape::Rectangle(rectangle))
+      | (HitShape::Rectangle(rectangle), HitShape::Circle { center, radius }) => {
+        center.distance_squared(center.clamp(rectangle.min, rectangle.max)) <= radius * radius
+      }
+    }
+  }
+}
+
+/// A shape that deals damage while its attack is active.
+#[derive(Clone, Debug)]
+pub struct Hitbox {
+  pub shape: HitShape,
+  pub damage: f32,
+}
+
+/// Hitboxes that are active between two times in an attack animation.
+#[derive(Clone, Debug)]
+pub struct HitboxFrame {
+  pub start: f32,
+  pub end: f32,
+  pub hitboxes: Vec<Hitbox>,
+}
+
+/// The hitboxes of an attack over the course of its animation.
+#[derive(Clone, Debug, Default)]
+pub struct HitboxTrack {
+  pub duration: f32,
+  pub frames: Vec<HitboxFrame>,
+}
+
+impl HitboxTrack {
+  /// Creates an empty track for an animation of the given length in seconds.
+  pub fn new(duration: f32) -> Self {
+    Self {
+      duration,
+      frames: Vec::new(),
+    }
+  }
+
+  /// Adds a hitbox that's active between the given times.
+  pub fn with_hitbox(mut self, start: f32, end: f32, hitbox: Hitbox) -> Self {
+    self.frames.push(HitboxFrame {
+      start,
+      end,
+      hitboxes: vec![hitbox],
+    });
+    self
+  }
+
+  /// The hitboxes active at the given time.
+  pub fn active_at(&self, time: f32) -> impl Iterator<Item = &Hitbox> {
+    self
+      .frames
+      .iter()
+      .filter(move |frame| time >= frame.start && time < frame.end)
+      .flat_map(|frame| &frame.hitboxes)
+  }
+}
+
+/// Something that takes part in combat.
+#[derive(Clone, Debug)]
+pub struct Combatant {
+  pub position: Vec2,
+  /// Mirrors hitboxes and hurtboxes horizontally, for facing left.
+  pub flipped: bool,
+  /// Combatants on the same team don't hit each other.
+  pub team: u32,
+  pub health: Health,
+  /// The shapes that can be hit.
+  pub hurtboxes: Vec<HitShape>,
+}
+
+impl Combatant {
+  /// Creates a combatant with the given health and no hurtboxes.
+  pub fn new(health: Health) -> Self {
+    Self {
+      position: Vec2::ZERO,
+      flipped: false,
+      team: 0,
+      health,
+      hurtboxes: Vec::new(),
+    }
+  }
+}
+
+/// Something that happened in combat.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CombatEvent {
+  Damaged {
+    target: CombatantId,
+    attacker: Option<CombatantId>,
+    amount: f32,
+  },
+  Died {
+    target: CombatantId,
+    killer: Option<CombatantId>,
+  },
+}
+
+/// An attack in progress.
+struct Attack {
+  track: HitboxTrack,
+  time: f32,
+  swing: u32,
+}
+
+/// A combatant and their current attack.
+struct CombatantState {
+  combatant: Combatant,
+  attack: Option<Attack>,
+}
+
+/// Resolves hits between combatants.
+///
+/// Each swing of an attack hits each target at most once, however long its
+/// hitboxes stay active. A hit on an invulnerable target doesn't count, so
+/// the swing can still land if the invulnerability runs out in time.
+#[derive(Default)]
+pub struct CombatWorld {
+  combatants: Arena<CombatantId, CombatantState>,
+  struck: FastHashSet<(CombatantId, u32, CombatantId)>,
+  events: Vec<CombatEvent>,
+  next_swing: u32,
+}
+
+impl CombatWorld {
+  /// Creates a new, empty world.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a combatant to the world.
+  pub fn add(&mut self, combatant: Combatant) -> CombatantId {
+    self.combatants.insert(CombatantState {
+      combatant,
+      attack: None,
+    })
+  }
+
+  /// Removes a combatant from the world.
+  pub fn remove(&mut self, id: CombatantId) -> Option<Combatant> {
+    self
+      .struck
+      .retain(|(attacker, _, target)| *attacker != id && *target != id);
+    self.combatants.remove(id).map(|state| state.combatant)
+  }
+
+  /// Gets a combatant.
+  pub fn get(&self, id: CombatantId) -> Option<&Combatant> {
+    self.combatants.get(id).map(|state| &state.combatant)
+  }
+
+  /// Gets a combatant to move or turn them.
+  pub fn get_mut(&mut self, id: CombatantId) -> Option<&mut Combatant> {
+    self.combatants.get_mut(id).map(|state| &mut state.combatant)
+  }
+
+  /// Starts an attack, replacing any in progress.
+  pub fn start_attack(&mut self, id: CombatantId, track: HitboxTrack) {
+    let swing = self.next_swing;
+
+    if let Some(state) = self.combatants.get_mut(id) {
+      self.next_swing = self.next_swing.wrapping_add(1);
+      self.struck.retain(|(attacker, _, _)| *attacker != id);
+
+      state.attack = Some(Attack { track, time: 0., swing });
+    }
+  }
+
+  /// Stops an attack in progress, e.g. when the attacker is staggered.
+  pub fn cancel_attack(&mut self, id: CombatantId) {
+    if let Some(state) = self.combatants.get_mut(id) {
+      state.attack = None;
+    }
+  }
+
+  /// Determines if a combatant is attacking.
+  pub fn is_attacking(&self, id: CombatantId) -> bool {
+    self.combatants.get(id).is_some_and(|state| state.attack.is_some())
+  }
+
+  /// Moves an attack to the given time, to keep it in step with the
+  /// animation that's playing it.
+  pub fn sync_attack(&mut self, id: CombatantId, time: f32) {
+    if let Some(attack) = self.combatants.get_mut(id).and_then(|state| state.attack.as_mut()) {
+      attack.time = time;
+    }
+  }
+
+  /// Applies damage from outside an attack, like a trap or a projectile.
+  pub fn apply_damage(&mut self, target: CombatantId, amount: f32, attacker: Option<CombatantId>) -> DamageOutcome {
+    let Some(state) = self.combatants.get_mut(target) else {
+      return DamageOutcome::Ignored;
+    };
+
+    let outcome = state.combatant.health.damage(amount);
+
+    match outcome {
+      DamageOutcome::Ignored => {}
+      DamageOutcome::Damaged(amount) => {
+        self.events.push(CombatEvent::Damaged {
+          target,
+          attacker,
+          amount,
+        });
+      }
+      DamageOutcome::Killed(amount) => {
+        self.events.push(CombatEvent::Damaged {
+          target,
+          attacker,
+          amount,
+        });
+        self.events.push(CombatEvent::Died {
+          target,
+          killer: attacker,
+        });
+
+        // the dead don't finish their attacks
+        state.attack = None;
+      }
+    }
+
+    outcome
+  }
+
+  /// Advances attacks and invulnerability, and resolves hits.
+  pub fn update(&mut self, delta_time: f32) {
+    let mut hits = Vec::new();
+
+    for (attacker_id, attacker) in self.combatants.enumerate() {
+      let Some(attack) = &attacker.attack else {
+        continue;
+      };
+
+      let hitboxes = attack
+        .track
+        .active_at(attack.time)
+        .map(|hitbox| {
+          let shape = hitbox
+            .shape
+            .to_world(attacker.combatant.position, attacker.combatant.flipped);
+
+          (shape, hitbox.damage)
+        })
+        .collect::<Vec<_>>();
+
+      if hitboxes.is_empty() {
+        continue;
+      }
+
+      for (target_id, target) in self.combatants.enumerate() {
+        let target = &target.combatant;
+
+        if target_id == attacker_id
+          || target.team == attacker.combatant.team
+          || target.health.is_dead()
+          || self.struck.contains(&(attacker_id, attack.swing, target_id))
+        {
+          continue;
+        }
+
+        let hurtboxes = target
+          .hurtboxes
+          .iter()
+          .map(|shape| shape.to_world(target.position, target.flipped))
+          .collect::<Vec<_>>();
+
+        // the strongest overlapping hitbox lands
+        let damage = hitboxes
+          .iter()
+          .filter(|(shape, _)| hurtboxes.iter().any(|hurtbox| shape.intersects(hurtbox)))
+          .map(|(_, damage)| *damage)
+          .reduce(f32::max);
+
+        if let Some(damage) = damage {
+          hits.push((attacker_id, attack.swing, target_id, damage));
+        }
+      }
+    }
+
+    for (attacker, swing, target, damage) in hits {
+      if self.apply_damage(target, damage, Some(attacker)) != DamageOutcome::Ignored {
+        self.struck.insert((attacker, swing, target));
+      }
+    }
+
+    for (_, state) in self.combatants.enumerate_mut() {
+      state.combatant.health.update(delta_time);
+
+      if let Some(attack) = &mut state.attack {
+        attack.time += delta_time;
+
+        if attack.time >= attack.track.duration {
+          state.attack = None;
+        }
+      }
+    }
+  }
+
+  /// Takes the events raised since the last call.
+  pub fn drain_events(&mut self) -> Vec<CombatEvent> {
+    std::mem::take(&mut self.events)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn create_slash() -> HitboxTrack {
+    let hitbox = Hitbox {
+      shape: HitShape::Rectangle(Rectangle::new(vec2(0., -0.5), vec2(2., 0.5))),
+      damage: 10.,
+    };
+
+    HitboxTrack::new(0.5).with_hitbox(0.1, 0.3, hitbox)
+  }
+
+  fn create_combatant(position: Vec2, team: u32, health: Health) -> Combatant {
+    Combatant {
+      position,
+      team,
+      hurtboxes: vec![HitShape::Circle {
+        center: Vec2::ZERO,
+        radius: 0.5,
+      }],
+      ..Combatant::new(health)
+    }
+  }
+
+  #[test]
+  fn it_should_ignore_damage_while_invulnerable() {
+    let mut health = Health::new(20.).with_invulnerability(1.);
+
+    assert_eq!(health.damage(5.), DamageOutcome::Damaged(5.));
+    assert_eq!(health.damage(5.), DamageOutcome::Ignored);
+
+    health.update(1.);
+
+    assert_eq!(health.damage(50.), DamageOutcome::Killed(15.));
+    assert!(health.is_dead());
+
+    health.heal(10.);
+
+    assert_eq!(health.current(), 0.);
+  }
+
+  #[test]
+  fn it_should_hit_each_target_once_per_swing() {
+    let mut world = CombatWorld::new();
+
+    let player = world.add(create_combatant(Vec2::ZERO, 0, Health::new(100.)));
+    let enemy = world.add(create_combatant(vec2(2., 0.), 1, Health::new(15.)));
+    let behind = world.add(create_combatant(vec2(-2., 0.), 1, Health::new(15.)));
+
+    world.start_attack(player, create_slash());
+
+    for _ in 0..5 {
+      world.update(0.1);
+    }
+
+    assert!(!world.is_attacking(player));
+    assert_eq!(world.get(enemy).unwrap().health.current(), 5.);
+    assert_eq!(world.get(behind).unwrap().health.current(), 15.);
+    assert_eq!(world.drain_events(), vec![CombatEvent::Damaged {
+      target: enemy,
+      attacker: Some(player),
+      amount: 10.,
+    }]);
+
+    // turning around hits what's behind, and a second swing finishes the enemy
+    world.get_mut(player).unwrap().flipped = true;
+    world.start_attack(player, create_slash());
+    world.sync_attack(player, 0.2);
+    world.update(0.1);
+
+    world.get_mut(player).unwrap().flipped = false;
+    world.start_attack(player, create_slash());
+    world.sync_attack(player, 0.2);
+    world.update(0.1);
+
+    let events = world.drain_events();
+
+    assert_eq!(world.get(behind).unwrap().health.current(), 5.);
+    assert!(events.contains(&CombatEvent::Died {
+      target: enemy,
+      killer: Some(player),
+    }));
+  }
+
+  #[test]
+  fn it_should_not_hit_teammates_or_through_invulnerability() {
+    let mut world = CombatWorld::new();
+
+    let player = world.add(create_combatant(Vec2::ZERO, 0, Health::new(100.)));
+    let ally = world.add(create_combatant(vec2(1., 0.), 0, Health::new(100.)));
+    let enemy = world.add(create_combatant(vec2(1., 0.), 1, Health::new(100.)));
+
+    world.get_mut(enemy).unwrap().health.make_invulnerable(0.05);
+    world.start_attack(player, create_slash());
+    world.sync_attack(player, 0.1);
+    world.update(0.1);
+
+    assert_eq!(world.get(enemy).unwrap().health.current(), 100.);
+
+    // the swing is still active when the invulnerability runs out
+    world.update(0.1);
+
+    assert_eq!(world.get(ally).unwrap().health.current(), 100.);
+    assert_eq!(world.get(enemy).unwrap().health.current(), 90.);
+  }
+}
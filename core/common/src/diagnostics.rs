@@ -1,9 +1,17 @@
 //! Diagnostic utilities for the engine.
 
+pub use console::*;
+pub use determinism::*;
 pub use logging::*;
 pub use profiling::*;
+pub use remote::*;
 pub use server::*;
+pub use statistics::*;
 
+mod console;
+mod determinism;
 mod logging;
 mod profiling;
+mod remote;
 mod server;
+mod statistics;
@@ -0,0 +1,194 @@
+//! A developer console for poking at a running game.
+//!
+//! Systems register named commands, and the console parses lines typed by
+//! the developer into a command and its arguments:
+//!
+//! ```rust,ignore
+//! let mut console = DeveloperConsole::new();
+//!
+//! console.register("god", "toggles invulnerability", move |_| {
+//!   player.toggle_god_mode();
+//!   Ok(String::new())
+//! });
+//!
+//! println!("{}", console.execute("debug_draw physics off")?);
+//! ```
+//!
+//! Arguments are separated by whitespace; wrap an argument in double quotes
+//! to include spaces in it.
+
+use std::{
+  collections::BTreeMap,
+  fmt::{Display, Formatter},
+};
+
+/// A potential error when executing a console command.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConsoleError {
+  UnknownCommand(String),
+  InvalidArguments(String),
+}
+
+impl Display for ConsoleError {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::UnknownCommand(name) => write!(formatter, "unknown command '{name}'; try 'help'"),
+      Self::InvalidArguments(message) => write!(formatter, "{message}"),
+    }
+  }
+}
+
+/// The handler of a console command, given its arguments and returning the
+/// text to show the developer.
+pub type ConsoleHandler = Box<dyn FnMut(&[&str]) -> Result<String, ConsoleError>>;
+
+/// A registered console command.
+struct ConsoleCommand {
+  help: String,
+  handler: ConsoleHandler,
+}
+
+/// A set of named commands the developer can run by typing them.
+///
+/// `help` is built in, and lists every command with its help text.
+#[derive(Default)]
+pub struct DeveloperConsole {
+  commands: BTreeMap<String, ConsoleCommand>,
+  history: Vec<String>,
+}
+
+impl DeveloperConsole {
+  /// Creates a console with no commands.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Registers a command, replacing any command with the same name.
+  pub fn register(
+    &mut self,
+    name: impl Into<String>,
+    help: impl Into<String>,
+    handler: impl FnMut(&[&str]) -> Result<String, ConsoleError> + 'static,
+  ) {
+    self.commands.insert(name.into(), ConsoleCommand {
+      help: help.into(),
+      handler: Box::new(handler),
+    });
+  }
+
+  /// Determines if a command is registered.
+  pub fn contains(&self, name: &str) -> bool {
+    self.commands.contains_key(name)
+  }
+
+  /// The names of the registered commands, in order.
+  pub fn commands(&self) -> impl Iterator<Item = &str> {
+    self.commands.keys().map(String::as_str)
+  }
+
+  /// The lines executed so far, oldest first.
+  pub fn history(&self) -> &[String] {
+    &self.history
+  }
+
+  /// Parses and runs a line of input, returning the command's output.
+  pub fn execute(&mut self, line: &str) -> Result<String, ConsoleError> {
+    let tokens = tokenize(line)?;
+    let Some((name, arguments)) = tokens.split_first() else {
+      return Ok(String::new());
+    };
+
+    self.history.push(line.trim().to_string());
+
+    if name == "help" {
+      return Ok(self.help());
+    }
+
+    let arguments = arguments.iter().map(String::as_str).collect::<Vec<_>>();
+    let command = self
+      .commands
+      .get_mut(name)
+      .ok_or_else(|| ConsoleError::UnknownCommand(name.clone()))?;
+
+    (command.handler)(&arguments)
+  }
+
+  /// Lists every command with its help text.
+  fn help(&self) -> String {
+    self
+      .commands
+      .iter()
+      .map(|(name, command)| format!("{name}: {}", command.help))
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+}
+
+/// Splits a line into whitespace-separated tokens, keeping quoted tokens
+/// whole.
+fn tokenize(line: &str) -> Result<Vec<String>, ConsoleError> {
+  let mut tokens = Vec::new();
+  let mut chars = line.chars().peekable();
+
+  while let Some(&next) = chars.peek() {
+    if next.is_whitespace() {
+      chars.next();
+      continue;
+    }
+
+    let mut token = String::new();
+
+    if next == '"' {
+      chars.next();
+
+      loop {
+        match chars.next() {
+          Some('"') => break,
+          Some(character) => token.push(character),
+          None => return Err(ConsoleError::InvalidArguments("unterminated quote".to_string())),
+        }
+      }
+    } else {
+      while let Some(character) = chars.next_if(|character| !character.is_whitespace()) {
+        token.push(character);
+      }
+    }
+
+    tokens.push(token);
+  }
+
+  Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, rc::Rc};
+
+  use super::*;
+
+  #[test]
+  fn it_should_run_registered_commands() {
+    let mut console = DeveloperConsole::new();
+    let said = Rc::new(RefCell::new(Vec::new()));
+
+    console.register("say", "repeats its arguments", {
+      let said = said.clone();
+
+      move |arguments| {
+        said.borrow_mut().extend(arguments.iter().map(|it| it.to_string()));
+        Ok(arguments.join(" "))
+      }
+    });
+
+    assert_eq!(console.execute(r#"say hello "big world""#).unwrap(), "hello big world");
+    assert_eq!(*said.borrow(), vec!["hello", "big world"]);
+    assert_eq!(console.execute("help").unwrap(), "say: repeats its arguments");
+    assert_eq!(console.execute("   ").unwrap(), "");
+    assert_eq!(
+      console.execute("jump"),
+      Err(ConsoleError::UnknownCommand("jump".to_string()))
+    );
+    assert!(console.execute(r#"say "oops"#).is_err());
+    assert_eq!(console.history(), &["say hello \"big world\"", "help", "jump"]);
+  }
+}
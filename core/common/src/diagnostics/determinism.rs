@@ -0,0 +1,564 @@
+//! Checksums of simulation state, for finding where two runs diverge.
+//!
+//! Lockstep networking and replays rely on every peer simulating exactly the
+//! same thing; when they don't, the symptoms usually show up long after the
+//! cause. A [`DeterminismRecorder`] hashes the state of each system or
+//! component type at the end of every frame, so two runs (or a client and its
+//! server) can be compared to find the first frame and section that differ:
+//!
+//! ```rust,ignore
+//! let mut recorder = DeterminismRecorder::new();
+//!
+//! recorder.begin_frame(frame);
+//! recorder.record("physics", |hasher| world.checksum(hasher));
+//! scene.record_checksums(&mut recorder);
+//! recorder.end_frame();
+//!
+//! // compare against the server's checksum for the same frame
+//! if let Err(divergence) = recorder.verify(&server_checksum) {
+//!   panic!("{divergence}");
+//! }
+//! ```
+//!
+//! Hashes are taken over the exact bits of each value, so `0.1 + 0.2` and
+//! `0.3` hash differently, as they would simulate differently.
+
+use std::{
+  collections::VecDeque,
+  fmt::{Display, Formatter},
+};
+
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::{InputStream, OutputStream, Quat, StreamError, StringName, Vec2, Vec3};
+
+/// Hashes simulation state, bit for bit.
+pub struct StateHasher(Xxh3);
+
+impl Default for StateHasher {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl StateHasher {
+  /// Creates an empty hasher.
+  pub fn new() -> Self {
+    Self(Xxh3::new())
+  }
+
+  /// Hashes raw bytes.
+  pub fn write_bytes(&mut self, bytes: &[u8]) {
+    self.0.update(bytes);
+  }
+
+  /// Hashes a `u32`.
+  pub fn write_u32(&mut self, value: u32) {
+    self.write_bytes(&value.to_le_bytes());
+  }
+
+  /// Hashes a `u64`.
+  pub fn write_u64(&mut self, value: u64) {
+    self.write_bytes(&value.to_le_bytes());
+  }
+
+  /// Hashes an `f32` by its bits.
+  pub fn write_f32(&mut self, value: f32) {
+    self.write_u32(value.to_bits());
+  }
+
+  /// Hashes an `f64` by its bits.
+  pub fn write_f64(&mut self, value: f64) {
+    self.write_u64(value.to_bits());
+  }
+
+  /// Hashes a string, prefixed by its length so adjacent strings can't run
+  /// together.
+  pub fn write_str(&mut self, value: &str) {
+    self.write_u64(value.len() as u64);
+    self.write_bytes(value.as_bytes());
+  }
+
+  /// Hashes any value with a [`Checksum`].
+  pub fn write<T: Checksum + ?Sized>(&mut self, value: &T) {
+    value.checksum(self);
+  }
+
+  /// The hash of everything written so far.
+  pub fn finish(&self) -> u64 {
+    self.0.digest()
+  }
+}
+
+/// A value whose state can be hashed into a [`StateHasher`].
+pub trait Checksum {
+  fn checksum(&self, hasher: &mut StateHasher);
+}
+
+macro_rules! impl_checksum {
+  ($type:ty, $method:ident as $cast:ty) => {
+    impl Checksum for $type {
+      #[inline]
+      fn checksum(&self, hasher: &mut StateHasher) {
+        hasher.$method(*self as $cast);
+      }
+    }
+  };
+}
+
+impl_checksum!(bool, write_u32 as u32);
+impl_checksum!(u8, write_u32 as u32);
+impl_checksum!(u16, write_u32 as u32);
+impl_checksum!(u32, write_u32 as u32);
+impl_checksum!(u64, write_u64 as u64);
+impl_checksum!(usize, write_u64 as u64);
+impl_checksum!(i8, write_u32 as u32);
+impl_checksum!(i16, write_u32 as u32);
+impl_checksum!(i32, write_u32 as u32);
+impl_checksum!(i64, write_u64 as u64);
+impl_checksum!(f32, write_f32 as f32);
+impl_checksum!(f64, write_f64 as f64);
+
+impl Checksum for str {
+  fn checksum(&self, hasher: &mut StateHasher) {
+    hasher.write_str(self);
+  }
+}
+
+impl Checksum for String {
+  fn checksum(&self, hasher: &mut StateHasher) {
+    hasher.write_str(self);
+  }
+}
+
+impl Checksum for Vec2 {
+  fn checksum(&self, hasher: &mut StateHasher) {
+    hasher.write_f32(self.x);
+    hasher.write_f32(self.y);
+  }
+}
+
+impl Checksum for Vec3 {
+  fn checksum(&self, hasher: &mut StateHasher) {
+    hasher.write_f32(self.x);
+    hasher.write_f32(self.y);
+    hasher.write_f32(self.z);
+  }
+}
+
+impl Checksum for Quat {
+  fn checksum(&self, hasher: &mut StateHasher) {
+    hasher.write_f32(self.x);
+    hasher.write_f32(self.y);
+    hasher.write_f32(self.z);
+    hasher.write_f32(self.w);
+  }
+}
+
+impl<T: Checksum> Checksum for [T] {
+  fn checksum(&self, hasher: &mut StateHasher) {
+    hasher.write_u64(self.len() as u64);
+
+    for value in self {
+      value.checksum(hasher);
+    }
+  }
+}
+
+impl<T: Checksum> Checksum for Vec<T> {
+  fn checksum(&self, hasher: &mut StateHasher) {
+    self.as_slice().checksum(hasher);
+  }
+}
+
+impl<T: Checksum> Checksum for Option<T> {
+  fn checksum(&self, hasher: &mut StateHasher) {
+    match self {
+      Some(value) => {
+        hasher.write_u32(1);
+        value.checksum(hasher);
+      }
+      None => hasher.write_u32(0),
+    }
+  }
+}
+
+/// The checksums of each section of state at the end of a frame.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameChecksum {
+  pub frame: u64,
+  /// The checksum of each section, like `physics` or a component type, in
+  /// the order they were recorded.
+  pub sections: Vec<(StringName, u64)>,
+}
+
+impl FrameChecksum {
+  /// The checksum of the given section, if it was recorded.
+  pub fn section(&self, name: &str) -> Option<u64> {
+    self
+      .sections
+      .iter()
+      .find(|(section, _)| *section == name)
+      .map(|(_, checksum)| *checksum)
+  }
+
+  /// A single checksum over every section, for sending over the network
+  /// cheaply; compare sections once this differs.
+  pub fn combined(&self) -> u64 {
+    let mut hasher = StateHasher::new();
+
+    for (name, checksum) in &self.sections {
+      hasher.write_str(name.as_ref());
+      hasher.write_u64(*checksum);
+    }
+
+    hasher.finish()
+  }
+
+  /// Finds the first section that differs from another checksum of the same
+  /// frame, treating this one as expected.
+  pub fn compare(&self, actual: &FrameChecksum) -> Option<Divergence> {
+    let divergence = |section: StringName, expected, actual| Divergence {
+      frame: self.frame,
+      section,
+      expected,
+      actual,
+    };
+
+    for (name, expected) in &self.sections {
+      match actual.section(name.as_ref()) {
+        Some(checksum) if checksum == *expected => continue,
+        checksum => return Some(divergence(*name, Some(*expected), checksum)),
+      }
+    }
+
+    actual
+      .sections
+      .iter()
+      .find(|(name, _)| self.section(name.as_ref()).is_none())
+      .map(|(name, checksum)| divergence(*name, None, Some(*checksum)))
+  }
+
+  /// Reads a frame checksum from the given stream.
+  pub fn read_from(stream: &mut dyn InputStream) -> Result<Self, StreamError> {
+    let frame = stream.read_u64()?;
+    let count = stream.read_u32()? as usize;
+    let mut sections = Vec::with_capacity(count);
+
+    for _ in 0..count {
+      let name = stream.read_string()?;
+      let checksum = stream.read_u64()?;
+
+      sections.push((StringName::from(name), checksum));
+    }
+
+    Ok(Self { frame, sections })
+  }
+
+  /// Writes the frame checksum to the given stream.
+  pub fn write_to(&self, stream: &mut dyn OutputStream) -> Result<(), StreamError> {
+    stream.write_u64(self.frame)?;
+    stream.write_u32(self.sections.len() as u32)?;
+
+    for (name, checksum) in &self.sections {
+      stream.write_string(name.as_ref())?;
+      stream.write_u64(*checksum)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// The first place two runs of a simulation differ.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+  pub frame: u64,
+  pub section: StringName,
+  /// The expected checksum, or `None` if the section wasn't expected.
+  pub expected: Option<u64>,
+  /// The actual checksum, or `None` if the section is missing.
+  pub actual: Option<u64>,
+}
+
+impl Display for Divergence {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    match (self.expected, self.actual) {
+      (Some(expected), Some(actual)) => write!(
+        formatter,
+        "simulation diverged on frame {} in {}: expected {expected:016x}, got {actual:016x}",
+        self.frame, self.section
+      ),
+      (Some(_), None) => write!(
+        formatter,
+        "simulation diverged on frame {}: {} is missing",
+        self.frame, self.section
+      ),
+      _ => write!(
+        formatter,
+        "simulation diverged on frame {}: {} is unexpected",
+        self.frame, self.section
+      ),
+    }
+  }
+}
+
+/// Records per-frame checksums of simulation state.
+///
+/// A disabled recorder skips hashing entirely, so it can stay wired in and
+/// be switched on when chasing a desync.
+pub struct DeterminismRecorder {
+  enabled: bool,
+  capacity: usize,
+  frames: VecDeque<FrameChecksum>,
+  current: Option<FrameChecksum>,
+}
+
+impl Default for DeterminismRecorder {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl DeterminismRecorder {
+  /// Creates an enabled recorder that keeps every frame.
+  pub fn new() -> Self {
+    Self::with_capacity(usize::MAX)
+  }
+
+  /// Creates an enabled recorder that keeps only the most recent frames;
+  /// enough to cover the latency between client and server.
+  pub fn with_capacity(capacity: usize) -> Self {
+    Self {
+      enabled: true,
+      capacity: capacity.max(1),
+      frames: VecDeque::new(),
+      current: None,
+    }
+  }
+
+  /// Determines if checksums are being recorded.
+  pub fn is_enabled(&self) -> bool {
+    self.enabled
+  }
+
+  /// Starts or stops recording checksums.
+  pub fn set_enabled(&mut self, enabled: bool) {
+    self.enabled = enabled;
+
+    if !enabled {
+      self.current = None;
+    }
+  }
+
+  /// Starts recording the given frame.
+  pub fn begin_frame(&mut self, frame: u64) {
+    if self.enabled {
+      self.current = Some(FrameChecksum {
+        frame,
+        sections: Vec::new(),
+      });
+    }
+  }
+
+  /// Records a section of state, hashed by the given function.
+  ///
+  /// Recording the same section twice in a frame hashes both into it.
+  pub fn record(&mut self, section: impl Into<StringName>, body: impl FnOnce(&mut StateHasher)) {
+    let Some(current) = &mut self.current else {
+      return;
+    };
+
+    let section = section.into();
+    let mut hasher = StateHasher::new();
+
+    match current.sections.iter_mut().find(|(name, _)| *name == section) {
+      Some((_, checksum)) => {
+        hasher.write_u64(*checksum);
+        body(&mut hasher);
+        *checksum = hasher.finish();
+      }
+      None => {
+        body(&mut hasher);
+        current.sections.push((section, hasher.finish()));
+      }
+    }
+  }
+
+  /// Records a section of state from a value with a [`Checksum`].
+  pub fn record_value<T: Checksum + ?Sized>(&mut self, section: impl Into<StringName>, value: &T) {
+    self.record(section, |hasher| hasher.write(value));
+  }
+
+  /// Finishes the current frame, returning its checksum.
+  pub fn end_frame(&mut self) -> Option<&FrameChecksum> {
+    let current = self.current.take()?;
+
+    if self.frames.len() >= self.capacity {
+      self.frames.pop_front();
+    }
+
+    self.frames.push_back(current);
+    self.frames.back()
+  }
+
+  /// The recorded frames, oldest first.
+  pub fn frames(&self) -> impl Iterator<Item = &FrameChecksum> {
+    self.frames.iter()
+  }
+
+  /// The checksum of the given frame, if it's still recorded.
+  pub fn frame(&self, frame: u64) -> Option<&FrameChecksum> {
+    self.frames.iter().find(|checksum| checksum.frame == frame)
+  }
+
+  /// Checks a checksum from another peer against the same frame here,
+  /// logging the first divergent section.
+  ///
+  /// Frames that haven't been recorded here, or have been forgotten, pass.
+  pub fn verify(&self, remote: &FrameChecksum) -> Result<(), Divergence> {
+    let Some(local) = self.frame(remote.frame) else {
+      return Ok(());
+    };
+
+    match local.compare(remote) {
+      Some(divergence) => {
+        crate::warn!("{divergence}");
+        Err(divergence)
+      }
+      None => Ok(()),
+    }
+  }
+
+  /// Finds the first divergence from an expected run, like a recording of
+  /// the same replay on another machine, logging it.
+  ///
+  /// Frames are matched by number; frames missing from either run are
+  /// skipped.
+  pub fn compare<'a>(&self, expected: impl IntoIterator<Item = &'a FrameChecksum>) -> Option<Divergence> {
+    let divergence = expected.into_iter().find_map(|expected| {
+      let actual = self.frame(expected.frame)?;
+
+      expected.compare(actual)
+    })?;
+
+    crate::warn!("{divergence}");
+
+    Some(divergence)
+  }
+
+  /// Reads a recorded run from the given stream.
+  pub fn read_from(stream: &mut dyn InputStream) -> Result<Self, StreamError> {
+    let count = stream.read_u32()? as usize;
+    let mut recorder = Self::new();
+
+    for _ in 0..count {
+      recorder.frames.push_back(FrameChecksum::read_from(stream)?);
+    }
+
+    Ok(recorder)
+  }
+
+  /// Writes the recorded run to the given stream, to compare against later.
+  pub fn write_to(&self, stream: &mut dyn OutputStream) -> Result<(), StreamError> {
+    stream.write_u32(self.frames.len() as u32)?;
+
+    for frame in &self.frames {
+      frame.write_to(stream)?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn simulate(recorder: &mut DeterminismRecorder, frames: u64, glitch_on: Option<u64>) {
+    let mut position = Vec2::ZERO;
+    let mut health = 100.;
+
+    for frame in 0..frames {
+      position += Vec2::new(0.1, 0.2);
+      health -= 1.;
+
+      if glitch_on == Some(frame) {
+        health += f32::EPSILON * 64.;
+      }
+
+      recorder.begin_frame(frame);
+      recorder.record_value("physics", &position);
+      recorder.record_value("Health", &health);
+      recorder.end_frame();
+    }
+  }
+
+  #[test]
+  fn it_should_find_the_first_divergent_section() {
+    let mut server = DeterminismRecorder::new();
+    let mut client = DeterminismRecorder::new();
+
+    simulate(&mut server, 10, None);
+    simulate(&mut client, 10, Some(4));
+
+    assert_eq!(client.compare(server.frames()).map(|it| it.frame), Some(4));
+    assert!(client.verify(server.frame(3).unwrap()).is_ok());
+
+    let divergence = client.verify(server.frame(4).unwrap()).unwrap_err();
+
+    assert_eq!(divergence.section, "Health");
+    assert_ne!(divergence.expected, divergence.actual);
+    assert_ne!(server.frame(4).unwrap().combined(), client.frame(4).unwrap().combined());
+  }
+
+  #[test]
+  fn it_should_report_missing_sections() {
+    let expected = FrameChecksum {
+      frame: 1,
+      sections: vec![(StringName::from("physics"), 1), (StringName::from("Health"), 2)],
+    };
+    let actual = FrameChecksum {
+      frame: 1,
+      sections: vec![(StringName::from("physics"), 1)],
+    };
+
+    let divergence = expected.compare(&actual).unwrap();
+
+    assert_eq!(divergence.section, "Health");
+    assert_eq!(divergence.actual, None);
+    assert_eq!(
+      divergence.to_string(),
+      "simulation diverged on frame 1: Health is missing"
+    );
+  }
+
+  #[test]
+  fn it_should_round_trip_recorded_runs() {
+    let mut recorder = DeterminismRecorder::with_capacity(4);
+
+    simulate(&mut recorder, 10, None);
+
+    assert_eq!(recorder.frames().count(), 4);
+
+    let mut buffer = std::io::Cursor::new(Vec::new());
+
+    recorder.write_to(&mut buffer).unwrap();
+    buffer.set_position(0);
+
+    let loaded = DeterminismRecorder::read_from(&mut buffer).unwrap();
+
+    assert!(loaded.frames().eq(recorder.frames()));
+    assert_eq!(loaded.compare(recorder.frames()), None);
+  }
+
+  #[test]
+  fn it_should_skip_hashing_when_disabled() {
+    let mut recorder = DeterminismRecorder::new();
+
+    recorder.set_enabled(false);
+    recorder.begin_frame(0);
+    recorder.record("physics", |_| panic!("should not hash"));
+
+    assert!(recorder.end_frame().is_none());
+  }
+}
@@ -12,6 +12,20 @@ pub enum LogLevel {
   Error,
 }
 
+impl LogLevel {
+  /// Gets the level with the given ordinal, as produced by `level as u8`.
+  pub fn from_ordinal(ordinal: u8) -> Option<Self> {
+    match ordinal {
+      0 => Some(LogLevel::Trace),
+      1 => Some(LogLevel::Debug),
+      2 => Some(LogLevel::Info),
+      3 => Some(LogLevel::Warn),
+      4 => Some(LogLevel::Error),
+      _ => None,
+    }
+  }
+}
+
 /// An event that is logged.
 #[derive(Debug, Clone)]
 pub struct LogEvent {
@@ -1,5 +1,11 @@
+use crate::FrameHitch;
+
 /// A sink for profiling output.
-pub trait Profiler {}
+pub trait Profiler {
+  /// Notifies the profiler that a frame took much longer than expected.
+  #[allow(unused_variables)]
+  fn frame_hitch(&mut self, hitch: &FrameHitch) {}
+}
 
 /// Notifies the profiler that a frame has started.
 #[macro_export]
@@ -0,0 +1,461 @@
+//! Streaming of diagnostics to a remote viewer.
+//!
+//! A device build binds a [`RemoteDiagnostics`] sink, and a desktop viewer
+//! tool, or the editor, attaches to it with a [`RemoteViewer`] to watch frame
+//! timings, profile scopes and logs as they happen:
+//!
+//! ```rust,ignore
+//! // on the device
+//! let remote = RemoteDiagnostics::bind("0.0.0.0:7878")?;
+//!
+//! remote.record_frame(&timing);
+//! remote.warn("texture streaming is behind");
+//!
+//! // in the viewer
+//! let mut viewer = RemoteViewer::connect("192.168.1.20:7878")?;
+//!
+//! for event in viewer.poll()? {
+//!   timeline.push(event);
+//! }
+//! ```
+//!
+//! Each event is sent as a little-endian `u32` length followed by the event
+//! in [`BinaryFormat`]. Viewers can attach and detach at any time; nothing is
+//! buffered for viewers that aren't attached, so an idle sink costs almost
+//! nothing.
+//!
+//! Sending never blocks the game. Each viewer has a buffer of data its socket
+//! hasn't taken yet, written out as the socket allows, and a viewer that falls
+//! too far behind is dropped.
+
+use std::{
+  io::{ErrorKind, Read, Write},
+  net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+  sync::Mutex,
+};
+
+use super::*;
+use crate::{BinaryFormat, Channel, ChannelError, Format, FrameHitch, FrameTiming, Serialize, StreamError, TimeSpan};
+
+/// How much unsent data a viewer may fall behind by before it's dropped.
+const DEFAULT_MAX_PENDING: usize = 4 * 1024 * 1024;
+
+/// The largest event a viewer will accept, to reject garbage early.
+const MAX_EVENT_SIZE: usize = 1024 * 1024;
+
+/// Streams diagnostic events to any attached [`RemoteViewer`]s.
+///
+/// The sink is a [`Log`] and a [`Profiler`], so it can be installed wherever
+/// those are, and a [`Channel`] for a [`DiagnosticServer`].
+pub struct RemoteDiagnostics {
+  listener: TcpListener,
+  viewers: Mutex<Vec<AttachedViewer>>,
+  min_level: LogLevel,
+  max_pending: usize,
+}
+
+/// A viewer's socket, and the data it hasn't taken yet.
+struct AttachedViewer {
+  stream: TcpStream,
+  pending: Vec<u8>,
+}
+
+impl AttachedViewer {
+  /// Writes as much pending data as the socket takes without blocking.
+  ///
+  /// Returns false if the viewer has gone away.
+  fn flush(&mut self) -> bool {
+    let mut written = 0;
+
+    let is_connected = loop {
+      if written == self.pending.len() {
+        break true;
+      }
+
+      match self.stream.write(&self.pending[written..]) {
+        Ok(0) => break false,
+        Ok(count) => written += count,
+        Err(error) if error.kind() == ErrorKind::WouldBlock => break true,
+        Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+        Err(_) => break false,
+      }
+    };
+
+    self.pending.drain(..written);
+
+    is_connected
+  }
+}
+
+impl RemoteDiagnostics {
+  /// Listens for viewers on the given address.
+  pub fn bind(address: impl ToSocketAddrs) -> Result<Self, DiagnosticError> {
+    let listener = TcpListener::bind(address).map_err(|_| DiagnosticError::FailedToStart)?;
+
+    listener
+      .set_nonblocking(true)
+      .map_err(|_| DiagnosticError::FailedToStart)?;
+
+    Ok(Self {
+      listener,
+      viewers: Mutex::new(Vec::new()),
+      min_level: LogLevel::Trace,
+      max_pending: DEFAULT_MAX_PENDING,
+    })
+  }
+
+  /// Only streams logs at or above the given level.
+  pub fn with_min_level(mut self, level: LogLevel) -> Self {
+    self.min_level = level;
+    self
+  }
+
+  /// Drops viewers that fall behind by more than the given number of bytes.
+  pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+    self.max_pending = max_pending;
+    self
+  }
+
+  /// The address the sink is listening on.
+  pub fn local_addr(&self) -> Option<SocketAddr> {
+    self.listener.local_addr().ok()
+  }
+
+  /// The number of attached viewers, as of the last event sent.
+  pub fn viewer_count(&self) -> usize {
+    self.viewers.lock().unwrap().len()
+  }
+
+  /// Queues an event for every attached viewer, first attaching any that are
+  /// waiting, and writes as much as each viewer takes without blocking.
+  ///
+  /// Viewers that can't keep up are dropped.
+  pub fn publish(&self, event: &DiagnosticEvent) -> Result<(), DiagnosticError> {
+    let mut viewers = self.viewers.lock().unwrap();
+
+    while let Ok((stream, _)) = self.listener.accept() {
+      if configure_viewer(&stream).is_ok() {
+        viewers.push(AttachedViewer {
+          stream,
+          pending: Vec::new(),
+        });
+      }
+    }
+
+    if viewers.is_empty() {
+      return Ok(());
+    }
+
+    let frame = encode_event(event).map_err(|_| DiagnosticError::FailedToSend)?;
+
+    viewers.retain_mut(|viewer| {
+      if viewer.pending.len() + frame.len() > self.max_pending {
+        return false;
+      }
+
+      viewer.pending.extend_from_slice(&frame);
+      viewer.flush()
+    });
+
+    Ok(())
+  }
+
+  /// Writes out data still waiting for slow viewers, e.g. while nothing is
+  /// being published; publishing does this too.
+  pub fn flush(&self) {
+    self.viewers.lock().unwrap().retain_mut(|viewer| viewer.flush());
+  }
+
+  /// Sends the timing of a frame, and the hitch if it had one.
+  pub fn record_frame(&self, timing: &FrameTiming) {
+    let _ = self.publish(&DiagnosticEvent::Frame {
+      frame: timing.frame,
+      duration: TimeSpan::from_seconds(timing.raw_delta_time),
+    });
+
+    if let Some(hitch) = &timing.hitch {
+      self.publish_hitch(hitch);
+    }
+  }
+
+  /// Sends how long a named scope took.
+  pub fn record_scope(&self, name: impl Into<String>, duration: TimeSpan) {
+    let _ = self.publish(&DiagnosticEvent::Profile {
+      name: name.into(),
+      duration,
+    });
+  }
+
+  fn publish_hitch(&self, hitch: &FrameHitch) {
+    let _ = self.publish(&DiagnosticEvent::Profile {
+      name: format!("hitch (frame {})", hitch.frame),
+      duration: hitch.duration,
+    });
+  }
+}
+
+impl Log for RemoteDiagnostics {
+  fn is_level_enabled(&self, level: LogLevel) -> bool {
+    level >= self.min_level
+  }
+
+  fn log(&self, level: LogLevel, message: &str) {
+    if self.is_level_enabled(level) {
+      let _ = self.publish(&DiagnosticEvent::Log {
+        level,
+        message: message.to_string(),
+      });
+    }
+  }
+}
+
+impl Profiler for RemoteDiagnostics {
+  fn frame_hitch(&mut self, hitch: &FrameHitch) {
+    self.publish_hitch(hitch);
+  }
+}
+
+impl Channel<DiagnosticProtocol> for RemoteDiagnostics {
+  fn send(&self, command: DiagnosticEvent) -> Result<(), ChannelError> {
+    self.publish(&command).map_err(|_| ChannelError::FailedToSend)
+  }
+
+  fn receive(&self) -> Result<Option<()>, ChannelError> {
+    Ok(None)
+  }
+}
+
+/// A viewer attached to a [`RemoteDiagnostics`] sink.
+pub struct RemoteViewer {
+  stream: TcpStream,
+  buffer: Vec<u8>,
+  connected: bool,
+}
+
+impl RemoteViewer {
+  /// Attaches to a sink at the given address.
+  pub fn connect(address: impl ToSocketAddrs) -> Result<Self, DiagnosticError> {
+    let stream = TcpStream::connect(address).map_err(|_| DiagnosticError::FailedToConnect)?;
+
+    stream
+      .set_nonblocking(true)
+      .map_err(|_| DiagnosticError::FailedToConnect)?;
+
+    Ok(Self {
+      stream,
+      buffer: Vec::new(),
+      connected: true,
+    })
+  }
+
+  /// Determines if the sink is still there.
+  pub fn is_connected(&self) -> bool {
+    self.connected
+  }
+
+  /// Reads every event that has arrived since the last poll, without
+  /// blocking.
+  ///
+  /// Events that arrived before the sink went away are still returned; the
+  /// next poll reports the disconnection.
+  pub fn poll(&mut self) -> Result<Vec<DiagnosticEvent>, DiagnosticError> {
+    if !self.connected {
+      return Err(DiagnosticError::Disconnected);
+    }
+
+    let mut chunk = [0; 4096];
+
+    loop {
+      match self.stream.read(&mut chunk) {
+        Ok(0) => {
+          self.connected = false;
+          break;
+        }
+        Ok(count) => self.buffer.extend_from_slice(&chunk[..count]),
+        Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+        Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+        Err(_) => {
+          self.connected = false;
+          break;
+        }
+      }
+    }
+
+    let mut events = Vec::new();
+
+    while let Some(event) = self.next_event()? {
+      events.push(event);
+    }
+
+    Ok(events)
+  }
+
+  /// Polls for events and hands each to the given listener.
+  pub fn dispatch(&mut self, listener: &mut dyn DiagnosticListener) -> Result<usize, DiagnosticError> {
+    let events = self.poll()?;
+
+    for event in &events {
+      listener.on_debug_event(event);
+    }
+
+    Ok(events.len())
+  }
+
+  /// Takes the next complete event off the buffer, if there is one.
+  fn next_event(&mut self) -> Result<Option<DiagnosticEvent>, DiagnosticError> {
+    let Some(header) = self.buffer.first_chunk::<4>() else {
+      return Ok(None);
+    };
+
+    let length = u32::from_le_bytes(*header) as usize;
+
+    if length > MAX_EVENT_SIZE {
+      self.connected = false;
+      return Err(DiagnosticError::FailedToReceive);
+    }
+
+    if self.buffer.len() < 4 + length {
+      return Ok(None);
+    }
+
+    let frame = self.buffer.drain(..4 + length).skip(4).collect::<Vec<_>>();
+
+    decode_event(&frame)
+      .map(Some)
+      .map_err(|_| DiagnosticError::FailedToReceive)
+  }
+}
+
+/// Sets up a newly attached viewer's stream for sending.
+fn configure_viewer(stream: &TcpStream) -> std::io::Result<()> {
+  stream.set_nonblocking(true)?;
+  stream.set_nodelay(true)
+}
+
+/// Encodes an event as a length-prefixed frame.
+fn encode_event(event: &DiagnosticEvent) -> Result<Vec<u8>, StreamError> {
+  let body = event.to_binary_bytes()?;
+  let mut frame = Vec::with_capacity(4 + body.len());
+
+  frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+  frame.extend_from_slice(&body);
+
+  Ok(frame)
+}
+
+/// Decodes an event from the body of a frame.
+fn decode_event(body: &[u8]) -> Result<DiagnosticEvent, StreamError> {
+  let chunk = BinaryFormat::default().read_chunk(&mut std::io::Cursor::new(body))?;
+
+  DiagnosticEvent::from_chunk(&chunk)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, Instant};
+
+  use super::*;
+
+  /// Polls until the given number of events arrive, or a second passes.
+  fn poll_for(viewer: &mut RemoteViewer, count: usize) -> Vec<DiagnosticEvent> {
+    let deadline = Instant::now() + Duration::from_secs(1);
+    let mut events = Vec::new();
+
+    while events.len() < count && Instant::now() < deadline {
+      events.extend(viewer.poll().unwrap());
+      std::thread::sleep(Duration::from_millis(5));
+    }
+
+    events
+  }
+
+  #[test]
+  fn it_should_round_trip_events() {
+    let events = [
+      DiagnosticEvent::Log {
+        level: LogLevel::Warn,
+        message: "low on memory".to_string(),
+      },
+      DiagnosticEvent::Frame {
+        frame: 42,
+        duration: TimeSpan::from_millis(16.),
+      },
+      DiagnosticEvent::Counter {
+        name: "entities".to_string(),
+        value: 1200,
+      },
+    ];
+
+    for event in events {
+      let frame = encode_event(&event).unwrap();
+
+      assert_eq!(decode_event(&frame[4..]).unwrap(), event);
+    }
+  }
+
+  #[test]
+  fn it_should_stream_events_to_attached_viewers() {
+    let remote = RemoteDiagnostics::bind("127.0.0.1:0")
+      .unwrap()
+      .with_min_level(LogLevel::Info);
+
+    // nobody is listening yet, so this goes nowhere
+    remote.info("before");
+
+    let mut viewer = RemoteViewer::connect(remote.local_addr().unwrap()).unwrap();
+
+    remote.debug("filtered");
+    remote.warn("after");
+    remote.record_scope("physics", TimeSpan::from_millis(2.));
+
+    assert_eq!(remote.viewer_count(), 1);
+
+    let events = poll_for(&mut viewer, 2);
+
+    assert_eq!(events, vec![
+      DiagnosticEvent::Log {
+        level: LogLevel::Warn,
+        message: "after".to_string(),
+      },
+      DiagnosticEvent::Profile {
+        name: "physics".to_string(),
+        duration: TimeSpan::from_millis(2.),
+      },
+    ]);
+
+    drop(remote);
+
+    let deadline = Instant::now() + Duration::from_secs(1);
+
+    while viewer.poll().is_ok() && Instant::now() < deadline {
+      std::thread::sleep(Duration::from_millis(5));
+    }
+
+    assert!(!viewer.is_connected());
+  }
+
+  #[test]
+  fn it_should_drop_viewers_that_fall_behind() {
+    let remote = RemoteDiagnostics::bind("127.0.0.1:0")
+      .unwrap()
+      .with_max_pending(64 * 1024);
+
+    // this viewer never reads, so the socket fills and data backs up
+    let _viewer = RemoteViewer::connect(remote.local_addr().unwrap()).unwrap();
+    let message = "x".repeat(16 * 1024);
+
+    remote.info("attach");
+
+    assert_eq!(remote.viewer_count(), 1);
+
+    for _ in 0..10_000 {
+      remote.info(&message);
+
+      if remote.viewer_count() == 0 {
+        break;
+      }
+    }
+
+    assert_eq!(remote.viewer_count(), 0);
+  }
+}
@@ -3,28 +3,89 @@
 //! This is primarily used for debugging and profiling, and is not intended to
 //! be used in production.
 
-use crate::{Channel, Protocol, TimeStamp};
+use crate::{Channel, Chunk, FastHashMap, Protocol, Serialize, StreamError, TimeSpan, Variant};
 
 /// An event that can be sent to a diagnostic listener.
-#[derive(Debug)]
+///
+/// Events may cross process boundaries, so they carry durations rather than
+/// timestamps.
+#[derive(Clone, Debug, PartialEq)]
 pub enum DiagnosticEvent {
-  Log {
-    level: super::LogLevel,
-    message: String,
-  },
-  Profile {
-    name: String,
-    start: TimeStamp,
-    end: TimeStamp,
-  },
-  Counter {
-    name: String,
-    value: i64,
-  },
-  Telemetry {
-    name: &'static str,
-    value: f64,
-  },
+  Log { level: super::LogLevel, message: String },
+  Profile { name: String, duration: TimeSpan },
+  Frame { frame: u64, duration: TimeSpan },
+  Counter { name: String, value: i64 },
+  Telemetry { name: String, value: f64 },
+}
+
+impl DiagnosticEvent {
+  /// Reads an event from a [`Chunk`], as produced by [`Serialize`].
+  pub fn from_chunk(chunk: &Chunk) -> Result<Self, StreamError> {
+    let kind: String = chunk.read_field("type")?;
+
+    Ok(match kind.as_str() {
+      "log" => Self::Log {
+        level: super::LogLevel::from_ordinal(chunk.read_field("level")?).ok_or(StreamError::InvalidData)?,
+        message: chunk.read_field("message")?,
+      },
+      "profile" => Self::Profile {
+        name: chunk.read_field("name")?,
+        duration: TimeSpan::from_seconds(chunk.read_field("duration")?),
+      },
+      "frame" => Self::Frame {
+        frame: chunk.read_field("frame")?,
+        duration: TimeSpan::from_seconds(chunk.read_field("duration")?),
+      },
+      "counter" => Self::Counter {
+        name: chunk.read_field("name")?,
+        value: chunk.read_field("value")?,
+      },
+      "telemetry" => Self::Telemetry {
+        name: chunk.read_field("name")?,
+        value: chunk.read_field("value")?,
+      },
+      _ => return Err(StreamError::InvalidData),
+    })
+  }
+}
+
+impl Serialize for DiagnosticEvent {
+  fn serialize(&self) -> Chunk {
+    let mut map = FastHashMap::default();
+    let mut field = |key: &str, value: Variant| {
+      map.insert(key.to_string(), Chunk::Variant(value));
+    };
+
+    match self {
+      Self::Log { level, message } => {
+        field("type", Variant::String("log".to_string()));
+        field("level", Variant::U8(*level as u8));
+        field("message", Variant::String(message.clone()));
+      }
+      Self::Profile { name, duration } => {
+        field("type", Variant::String("profile".to_string()));
+        field("name", Variant::String(name.clone()));
+        field("duration", Variant::F32(duration.as_seconds()));
+      }
+      Self::Frame { frame, duration } => {
+        field("type", Variant::String("frame".to_string()));
+        field("frame", Variant::U64(*frame));
+        field("duration", Variant::F32(duration.as_seconds()));
+      }
+      Self::Counter { name, value } => {
+        field("type", Variant::String("counter".to_string()));
+        field("name", Variant::String(name.clone()));
+        field("value", Variant::I64(*value));
+      }
+      Self::Telemetry { name, value } => {
+        field("type", Variant::String("telemetry".to_string()));
+        field("name", Variant::String(name.clone()));
+        field("value", Variant::F64(*value));
+      }
+    }
+
+    Chunk::Map(map)
+  }
 }
 
 /// A listener that can receive diagnostic events.
@@ -48,6 +109,8 @@ pub enum DiagnosticError {
   FailedToStart,
   FailedToConnect,
   FailedToSend,
+  FailedToReceive,
+  Disconnected,
 }
 
 /// A [`Protocol`] for diagnostic information.
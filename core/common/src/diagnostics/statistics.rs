@@ -0,0 +1,165 @@
+use crate::{FastHashMap, RingBuffer, StringName, TimeSpan};
+
+/// How quickly smoothed timings follow new samples, from 0 to 1.
+const TIMING_SMOOTHING: f32 = 0.1;
+
+/// Statistics over recent frames, for performance overlays.
+///
+/// Frame times are kept for a window of recent frames. Named timings, like
+/// the time spent in each system, are smoothed so they can be read while
+/// they change every frame.
+pub struct FrameStatistics {
+  frame_times: RingBuffer<f32>,
+  timings: FastHashMap<StringName, f32>,
+}
+
+impl Default for FrameStatistics {
+  fn default() -> Self {
+    Self::new(240)
+  }
+}
+
+impl FrameStatistics {
+  /// Creates statistics over the given number of recent frames.
+  pub fn new(window: usize) -> Self {
+    Self {
+      frame_times: RingBuffer::new(window.max(1)),
+      timings: FastHashMap::default(),
+    }
+  }
+
+  /// Records how long a frame took, in seconds.
+  pub fn record_frame(&mut self, frame_time: f32) {
+    self.frame_times.push(frame_time);
+  }
+
+  /// Records how long something took this frame, like a system's update.
+  pub fn record_timing(&mut self, name: impl Into<StringName>, duration: TimeSpan) {
+    let sample = duration.as_seconds();
+
+    self
+      .timings
+      .entry(name.into())
+      .and_modify(|timing| *timing += (sample - *timing) * TIMING_SMOOTHING)
+      .or_insert(sample);
+  }
+
+  /// The smoothed timings, in seconds, slowest first.
+  pub fn timings(&self) -> Vec<(StringName, f32)> {
+    let mut timings = self
+      .timings
+      .iter()
+      .map(|(name, timing)| (*name, *timing))
+      .collect::<Vec<_>>();
+
+    timings.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    timings
+  }
+
+  /// Iterates over the recorded frame times, newest first.
+  pub fn frame_times(&self) -> impl Iterator<Item = f32> + '_ {
+    self.frame_times.iter().copied()
+  }
+
+  /// The number of frames recorded, up to the window size.
+  pub fn frame_count(&self) -> usize {
+    self.frame_times.iter().count()
+  }
+
+  /// The mean frame time, in seconds.
+  pub fn average_frame_time(&self) -> f32 {
+    match self.frame_count() {
+      0 => 0.,
+      count => self.frame_times().sum::<f32>() / count as f32,
+    }
+  }
+
+  /// The shortest frame time, in seconds.
+  pub fn min_frame_time(&self) -> f32 {
+    self.frame_times().reduce(f32::min).unwrap_or_default()
+  }
+
+  /// The longest frame time, in seconds.
+  pub fn max_frame_time(&self) -> f32 {
+    self.frame_times().reduce(f32::max).unwrap_or_default()
+  }
+
+  /// The frame time that the given fraction of frames come in under, e.g.
+  /// `0.99` for the 99th percentile.
+  pub fn percentile_frame_time(&self, percentile: f32) -> f32 {
+    let mut frame_times = self.frame_times().collect::<Vec<_>>();
+
+    if frame_times.is_empty() {
+      return 0.;
+    }
+
+    frame_times.sort_by(f32::total_cmp);
+
+    let index = (percentile.clamp(0., 1.) * (frame_times.len() - 1) as f32).round() as usize;
+
+    frame_times[index]
+  }
+
+  /// The average frames per second.
+  pub fn fps(&self) -> f32 {
+    match self.average_frame_time() {
+      time if time > 0. => 1. / time,
+      _ => 0.,
+    }
+  }
+
+  /// Counts frame times into evenly sized buckets from zero to the given
+  /// maximum, in seconds; longer frames land in the last bucket.
+  pub fn histogram(&self, bucket_count: usize, max_frame_time: f32) -> Vec<usize> {
+    let mut buckets = vec![0; bucket_count];
+
+    if bucket_count == 0 || max_frame_time <= 0. {
+      return buckets;
+    }
+
+    for frame_time in self.frame_times() {
+      let bucket = (frame_time / max_frame_time * bucket_count as f32) as usize;
+
+      buckets[bucket.min(bucket_count - 1)] += 1;
+    }
+
+    buckets
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_summarize_frame_times() {
+    let mut statistics = FrameStatistics::new(4);
+
+    for frame_time in [0.010, 0.020, 0.020, 0.030, 0.050] {
+      statistics.record_frame(frame_time);
+    }
+
+    assert_eq!(statistics.frame_count(), 4);
+    assert!((statistics.average_frame_time() - 0.030).abs() < 1e-6);
+    assert!((statistics.fps() - 33.333).abs() < 0.01);
+    assert_eq!(statistics.min_frame_time(), 0.020);
+    assert_eq!(statistics.max_frame_time(), 0.050);
+    assert_eq!(statistics.percentile_frame_time(1.), 0.050);
+    assert_eq!(statistics.histogram(4, 0.040), vec![0, 0, 2, 2]);
+  }
+
+  #[test]
+  fn it_should_smooth_timings() {
+    let mut statistics = FrameStatistics::default();
+
+    statistics.record_timing("physics", TimeSpan::from_millis(2.));
+    statistics.record_timing("render", TimeSpan::from_millis(4.));
+    statistics.record_timing("render", TimeSpan::from_millis(14.));
+
+    let timings = statistics.timings();
+
+    assert_eq!(timings[0].0, "render");
+    assert!((timings[0].1 - 0.005).abs() < 1e-6);
+    assert!((timings[1].1 - 0.002).abs() < 1e-6);
+  }
+}
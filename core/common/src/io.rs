@@ -3,11 +3,15 @@
 pub use buffers::*;
 pub use compression::*;
 pub use formats::*;
+pub use hashing::*;
+pub use patching::*;
 pub use streams::*;
 pub use virtualfs::*;
 
 mod buffers;
 mod compression;
 mod formats;
+mod hashing;
+mod patching;
 mod streams;
 mod virtualfs;
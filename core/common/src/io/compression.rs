@@ -1,4 +1,9 @@
 //! Common compression/Decompression support.
+//!
+//! All codecs support both one-shot compression of byte slices as well as
+//! streaming compression between arbitrary readers and writers. Subsystems
+//! that need to persist the choice of codec (bundles, save games, snapshots)
+//! should prefer [`CompressionFormat`], which can be stored as a single byte.
 
 use std::io::{Read, Write};
 
@@ -6,12 +11,18 @@ use std::io::{Read, Write};
 pub trait Compressor {
   /// Compresses the given data.
   fn compress(&self, data: &[u8]) -> Result<Vec<u8>, std::io::Error>;
+
+  /// Compresses all data from the given reader into the given writer.
+  fn compress_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), std::io::Error>;
 }
 
 /// A trait for decompressing data.
 pub trait Decompressor {
   /// Decompresses the given data.
   fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, std::io::Error>;
+
+  /// Decompresses all data from the given reader into the given writer.
+  fn decompress_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), std::io::Error>;
 }
 
 /// The DEFLATE compression algorithm.
@@ -25,6 +36,15 @@ impl Compressor for Deflate {
 
     encoder.finish()
   }
+
+  fn compress_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), std::io::Error> {
+    let mut encoder = flate2::write::DeflateEncoder::new(output, flate2::Compression::default());
+
+    std::io::copy(input, &mut encoder)?;
+    encoder.finish()?;
+
+    Ok(())
+  }
 }
 
 impl Decompressor for Deflate {
@@ -36,6 +56,14 @@ impl Decompressor for Deflate {
 
     Ok(decompressed)
   }
+
+  fn decompress_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), std::io::Error> {
+    let mut decoder = flate2::read::DeflateDecoder::new(input);
+
+    std::io::copy(&mut decoder, output)?;
+
+    Ok(())
+  }
 }
 
 /// The Zlib compression algorithm.
@@ -49,6 +77,15 @@ impl Compressor for Zlib {
 
     encoder.finish()
   }
+
+  fn compress_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), std::io::Error> {
+    let mut encoder = flate2::write::ZlibEncoder::new(output, flate2::Compression::default());
+
+    std::io::copy(input, &mut encoder)?;
+    encoder.finish()?;
+
+    Ok(())
+  }
 }
 
 impl Decompressor for Zlib {
@@ -60,6 +97,171 @@ impl Decompressor for Zlib {
 
     Ok(decompressed)
   }
+
+  fn decompress_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), std::io::Error> {
+    let mut decoder = flate2::read::ZlibDecoder::new(input);
+
+    std::io::copy(&mut decoder, output)?;
+
+    Ok(())
+  }
+}
+
+/// The LZ4 compression algorithm, using the LZ4 frame format.
+///
+/// LZ4 trades compression ratio for very fast compression and decompression,
+/// which makes it a good fit for data that is written often, such as network
+/// snapshots and streamed world regions.
+pub struct Lz4;
+
+impl Compressor for Lz4 {
+  fn compress(&self, data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+
+    encoder.write_all(data)?;
+
+    encoder.finish().map_err(std::io::Error::other)
+  }
+
+  fn compress_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), std::io::Error> {
+    let mut encoder = lz4_flex::frame::FrameEncoder::new(output);
+
+    std::io::copy(input, &mut encoder)?;
+    encoder.finish().map_err(std::io::Error::other)?;
+
+    Ok(())
+  }
+}
+
+impl Decompressor for Lz4 {
+  fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    let mut decoder = lz4_flex::frame::FrameDecoder::new(data);
+    let mut decompressed = Vec::new();
+
+    decoder.read_to_end(&mut decompressed)?;
+
+    Ok(decompressed)
+  }
+
+  fn decompress_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), std::io::Error> {
+    let mut decoder = lz4_flex::frame::FrameDecoder::new(input);
+
+    std::io::copy(&mut decoder, output)?;
+
+    Ok(())
+  }
+}
+
+/// The Zstandard compression algorithm.
+///
+/// Zstandard offers a much better compression ratio than LZ4 at a small cost
+/// in speed, which makes it a good fit for data that is written once and read
+/// many times, such as asset bundles and save games.
+pub struct Zstd;
+
+impl Zstd {
+  /// The compression level used by the [`Zstd`] codec.
+  const LEVEL: i32 = zstd::DEFAULT_COMPRESSION_LEVEL;
+}
+
+impl Compressor for Zstd {
+  fn compress(&self, data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    zstd::stream::encode_all(data, Self::LEVEL)
+  }
+
+  fn compress_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), std::io::Error> {
+    zstd::stream::copy_encode(input, output, Self::LEVEL)
+  }
+}
+
+impl Decompressor for Zstd {
+  fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    zstd::stream::decode_all(data)
+  }
+
+  fn decompress_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), std::io::Error> {
+    zstd::stream::copy_decode(input, output)
+  }
+}
+
+/// A compression format that can be selected at runtime.
+///
+/// The format can be persisted alongside compressed data as a single byte
+/// (see [`CompressionFormat::to_byte`]), so that readers know how to
+/// decompress it without each subsystem choosing its own scheme.
+#[repr(u8)]
+#[derive(Default, Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum CompressionFormat {
+  #[default]
+  None = 0,
+  Deflate = 1,
+  Zlib = 2,
+  Lz4 = 3,
+  Zstd = 4,
+}
+
+impl CompressionFormat {
+  /// Converts the format to its persisted byte representation.
+  #[inline]
+  pub fn to_byte(self) -> u8 {
+    self as u8
+  }
+
+  /// Converts a persisted byte back into a format, if it's recognised.
+  pub fn from_byte(value: u8) -> Option<Self> {
+    match value {
+      0 => Some(Self::None),
+      1 => Some(Self::Deflate),
+      2 => Some(Self::Zlib),
+      3 => Some(Self::Lz4),
+      4 => Some(Self::Zstd),
+      _ => None,
+    }
+  }
+}
+
+impl Compressor for CompressionFormat {
+  fn compress(&self, data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    match self {
+      Self::None => Ok(data.to_vec()),
+      Self::Deflate => Deflate.compress(data),
+      Self::Zlib => Zlib.compress(data),
+      Self::Lz4 => Lz4.compress(data),
+      Self::Zstd => Zstd.compress(data),
+    }
+  }
+
+  fn compress_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), std::io::Error> {
+    match self {
+      Self::None => std::io::copy(input, output).map(|_| ()),
+      Self::Deflate => Deflate.compress_stream(input, output),
+      Self::Zlib => Zlib.compress_stream(input, output),
+      Self::Lz4 => Lz4.compress_stream(input, output),
+      Self::Zstd => Zstd.compress_stream(input, output),
+    }
+  }
+}
+
+impl Decompressor for CompressionFormat {
+  fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    match self {
+      Self::None => Ok(data.to_vec()),
+      Self::Deflate => Deflate.decompress(data),
+      Self::Zlib => Zlib.decompress(data),
+      Self::Lz4 => Lz4.decompress(data),
+      Self::Zstd => Zstd.decompress(data),
+    }
+  }
+
+  fn decompress_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), std::io::Error> {
+    match self {
+      Self::None => std::io::copy(input, output).map(|_| ()),
+      Self::Deflate => Deflate.decompress_stream(input, output),
+      Self::Zlib => Zlib.decompress_stream(input, output),
+      Self::Lz4 => Lz4.decompress_stream(input, output),
+      Self::Zstd => Zstd.decompress_stream(input, output),
+    }
+  }
 }
 
 #[cfg(test)]
@@ -85,4 +287,69 @@ mod tests {
 
     assert_eq!(bytes, decompressed.as_slice());
   }
+
+  #[test]
+  fn it_should_compress_and_decompress_using_lz4() {
+    let bytes = b"AAAAAABBBBBBCCCCCDDDDDEEEEE";
+
+    let compressed = Lz4.compress(bytes).unwrap();
+    let decompressed = Lz4.decompress(&compressed).unwrap();
+
+    assert_eq!(bytes, decompressed.as_slice());
+  }
+
+  #[test]
+  fn it_should_compress_and_decompress_using_zstd() {
+    let bytes = b"AAAAAABBBBBBCCCCCDDDDDEEEEE";
+
+    let compressed = Zstd.compress(bytes).unwrap();
+    let decompressed = Zstd.decompress(&compressed).unwrap();
+
+    assert_eq!(bytes, decompressed.as_slice());
+  }
+
+  #[test]
+  fn it_should_stream_compress_and_decompress_all_formats() {
+    let bytes = b"AAAAAABBBBBBCCCCCDDDDDEEEEE".repeat(64);
+
+    for format in [
+      CompressionFormat::None,
+      CompressionFormat::Deflate,
+      CompressionFormat::Zlib,
+      CompressionFormat::Lz4,
+      CompressionFormat::Zstd,
+    ] {
+      let mut compressed = Vec::new();
+      let mut decompressed = Vec::new();
+
+      format.compress_stream(&mut bytes.as_slice(), &mut compressed).unwrap();
+      format
+        .decompress_stream(&mut compressed.as_slice(), &mut decompressed)
+        .unwrap();
+
+      assert_eq!(bytes, decompressed, "round-trip failed for {:?}", format);
+    }
+  }
+
+  #[test]
+  fn it_should_interoperate_between_one_shot_and_streaming() {
+    let bytes = b"AAAAAABBBBBBCCCCCDDDDDEEEEE";
+
+    let compressed = Lz4.compress(bytes).unwrap();
+    let mut decompressed = Vec::new();
+
+    Lz4
+      .decompress_stream(&mut compressed.as_slice(), &mut decompressed)
+      .unwrap();
+
+    assert_eq!(bytes, decompressed.as_slice());
+  }
+
+  #[test]
+  fn it_should_round_trip_compression_format_bytes() {
+    let format = CompressionFormat::Zstd;
+
+    assert_eq!(CompressionFormat::from_byte(format.to_byte()), Some(format));
+    assert_eq!(CompressionFormat::from_byte(255), None);
+  }
 }
@@ -1,19 +1,97 @@
-use crate::{FastHashMap, FromVariant, InputStream, OutputStream, StreamError, ToVariant, ToVirtualPath, Variant};
+use crate::{
+  FastHashMap, FromVariant, InputStream, OutputStream, StreamError, ToVariant, ToVirtualPath, Variant, Vec2, Vec3,
+};
 
 mod binary;
+mod csv;
 mod json;
+mod ron;
+mod toml;
 
 pub use binary::*;
+pub use csv::*;
 pub use json::*;
+pub use ron::*;
+pub use toml::*;
 
 /// A chunk of serialized data
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Chunk {
   Variant(Variant),
   Sequence(Vec<Chunk>),
   Map(FastHashMap<String, Chunk>),
 }
 
+impl Chunk {
+  /// Gets the value of the given key, if this chunk is a map.
+  pub fn get(&self, key: &str) -> Option<&Chunk> {
+    match self {
+      Chunk::Map(map) => map.get(key),
+      _ => None,
+    }
+  }
+
+  /// The type name recorded against this chunk, if it has one.
+  ///
+  /// Only formats that name their structures (such as [`RonFormat`]) record
+  /// these; see [`RON_TYPE_KEY`].
+  pub fn type_name(&self) -> Option<&str> {
+    match self.get(RON_TYPE_KEY) {
+      Some(Chunk::Variant(Variant::String(name))) => Some(name),
+      _ => None,
+    }
+  }
+
+  /// The elements of this chunk, if it's a sequence.
+  pub fn as_sequence(&self) -> Option<&[Chunk]> {
+    match self {
+      Chunk::Sequence(values) => Some(values),
+      _ => None,
+    }
+  }
+
+  /// Reads this chunk as a single value.
+  pub fn read<T: FromVariant>(&self) -> Result<T, StreamError> {
+    match self {
+      Chunk::Variant(variant) => T::from_variant(variant.clone()).map_err(|_| StreamError::InvalidData),
+      _ => Err(StreamError::InvalidData),
+    }
+  }
+
+  /// Reads the value of the given key as a single value.
+  pub fn read_field<T: FromVariant>(&self, key: &str) -> Result<T, StreamError> {
+    self.get(key).ok_or(StreamError::InvalidData)?.read()
+  }
+
+  /// Reads the value of the given key, or a default if it's missing.
+  pub fn read_field_or<T: FromVariant>(&self, key: &str, default: T) -> Result<T, StreamError> {
+    match self.get(key) {
+      Some(chunk) => chunk.read(),
+      None => Ok(default),
+    }
+  }
+
+  /// Reads this chunk as a [`Vec2`], either directly or from a sequence.
+  pub fn read_vec2(&self) -> Result<Vec2, StreamError> {
+    match self {
+      Chunk::Variant(Variant::Vec2(value)) => Ok(*value),
+      Chunk::Sequence(values) if values.len() == 2 => Ok(Vec2::new(values[0].read()?, values[1].read()?)),
+      _ => Err(StreamError::InvalidData),
+    }
+  }
+
+  /// Reads this chunk as a [`Vec3`], either directly or from a sequence.
+  pub fn read_vec3(&self) -> Result<Vec3, StreamError> {
+    match self {
+      Chunk::Variant(Variant::Vec3(value)) => Ok(*value),
+      Chunk::Sequence(values) if values.len() == 3 => {
+        Ok(Vec3::new(values[0].read()?, values[1].read()?, values[2].read()?))
+      }
+      _ => Err(StreamError::InvalidData),
+    }
+  }
+}
+
 /// Represents a type that can be serialized.
 pub trait Serialize: Sized {
   // TODO: make this fallible
@@ -45,6 +123,16 @@ pub trait Serialize: Sized {
     Self::to_format_path::<JsonFormat>(self, path)
   }
 
+  /// Serializes the type to a RON string.
+  fn to_ron_string(&self) -> Result<String, StreamError> {
+    Self::to_format_string::<RonFormat>(self)
+  }
+
+  /// Serializes the type to a RON file.
+  fn to_ron_path(&self, path: impl ToVirtualPath) -> Result<(), StreamError> {
+    Self::to_format_path::<RonFormat>(self, path)
+  }
+
   /// Serializes the type to a byte array with a specific format.
   fn to_format_bytes<F: Format + Default>(&self) -> Result<Vec<u8>, StreamError> {
     let mut format = F::default();
@@ -105,6 +193,16 @@ pub trait Deserialize: Sized {
     Self::from_format_path::<JsonFormat>(path)
   }
 
+  /// Deserializes the type from a RON string.
+  fn from_ron_string(data: &str) -> Result<Self, StreamError> {
+    Self::from_format_string::<RonFormat>(data)
+  }
+
+  /// Deserializes the type from a RON path.
+  fn from_ron_path(path: impl ToVirtualPath) -> Result<Self, StreamError> {
+    Self::from_format_path::<RonFormat>(path)
+  }
+
   /// Deserializes the type from a byte slice with a specific format.
   fn from_format_bytes<F: Format + Default>(data: &[u8]) -> Result<Self, StreamError> {
     let mut format = F::default();
@@ -0,0 +1,276 @@
+use std::fmt::Write;
+
+use super::*;
+
+/// A file format for working with CSV (comma-separated values).
+///
+/// The first line is a header naming each column. Every following line is
+/// read into a [`Chunk::Map`] from column name to value, and the whole file
+/// into a [`Chunk::Sequence`] of those rows.
+///
+/// Unquoted values are read as booleans, integers or floats where they parse
+/// as one, empty values as null, and anything else as a string. Quoted
+/// values, `"like, this"`, are always strings, with `""` standing for a quote.
+#[derive(Default)]
+pub struct CsvFormat;
+
+impl Format for CsvFormat {
+  fn read_chunk(&mut self, stream: &mut dyn InputStream) -> Result<Chunk, StreamError> {
+    let mut text = String::new();
+
+    stream.read_to_string(&mut text)?;
+
+    let mut records = parse_records(&text)?.into_iter();
+    let Some(header) = records.next() else {
+      return Ok(Chunk::Sequence(Vec::new()));
+    };
+
+    let columns = header
+      .into_iter()
+      .map(|field| match field {
+        Field::Bare(name) => name.trim().to_string(),
+        Field::Quoted(name) => name,
+      })
+      .collect::<Vec<_>>();
+
+    let rows = records
+      .map(|record| {
+        if record.len() != columns.len() {
+          return Err(StreamError::InvalidData);
+        }
+
+        let row = columns
+          .iter()
+          .zip(record)
+          .map(|(column, field)| (column.clone(), Chunk::Variant(field.into_variant())))
+          .collect();
+
+        Ok(Chunk::Map(row))
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Chunk::Sequence(rows))
+  }
+
+  fn write_chunk(&mut self, stream: &mut dyn OutputStream, chunk: &Chunk) -> Result<(), StreamError> {
+    let Chunk::Sequence(rows) = chunk else {
+      return Err(StreamError::InvalidData);
+    };
+
+    // maps don't keep their order, so columns are written alphabetically
+    let mut columns = rows
+      .iter()
+      .flat_map(|row| match row {
+        Chunk::Map(map) => map.keys().cloned().collect(),
+        _ => Vec::new(),
+      })
+      .collect::<Vec<_>>();
+
+    columns.sort();
+    columns.dedup();
+
+    let mut output = String::new();
+
+    write_record(&mut output, columns.iter().map(|column| quote(column)));
+
+    for row in rows {
+      let Chunk::Map(map) = row else {
+        return Err(StreamError::InvalidData);
+      };
+
+      let fields = columns
+        .iter()
+        .map(|column| match map.get(column) {
+          None => Ok(String::new()),
+          Some(Chunk::Variant(variant)) => write_variant(variant),
+          Some(_) => Err(StreamError::InvalidData),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+      write_record(&mut output, fields.into_iter());
+    }
+
+    stream.write_bytes(output.as_bytes())
+  }
+}
+
+/// A single field of a CSV record.
+enum Field {
+  Bare(String),
+  Quoted(String),
+}
+
+impl Field {
+  /// Converts the field into the value it most likely holds.
+  fn into_variant(self) -> Variant {
+    let text = match self {
+      Field::Quoted(text) => return Variant::String(text),
+      Field::Bare(text) => text.trim().to_string(),
+    };
+
+    if text.is_empty() {
+      Variant::Null
+    } else if let Ok(value) = text.parse::<bool>() {
+      Variant::Bool(value)
+    } else if let Ok(value) = text.parse::<i64>() {
+      Variant::I64(value)
+    } else if let Ok(value) = text.parse::<f64>() {
+      Variant::F64(value)
+    } else {
+      Variant::String(text)
+    }
+  }
+}
+
+/// Splits CSV text into records of fields, skipping blank lines.
+fn parse_records(text: &str) -> Result<Vec<Vec<Field>>, StreamError> {
+  let mut records = Vec::new();
+  let mut record = Vec::new();
+  let mut chars = text.chars().peekable();
+
+  loop {
+    let field = match chars.peek() {
+      Some('"') => {
+        chars.next();
+
+        let mut value = String::new();
+
+        loop {
+          match chars.next() {
+            Some('"') if chars.peek() == Some(&'"') => {
+              chars.next();
+              value.push('"');
+            }
+            Some('"') => break,
+            Some(other) => value.push(other),
+            None => return Err(StreamError::InvalidData),
+          }
+        }
+
+        Field::Quoted(value)
+      }
+      _ => {
+        let mut value = String::new();
+
+        while let Some(next) = chars.next_if(|next| !matches!(next, ',' | '\n' | '\r')) {
+          value.push(next);
+        }
+
+        Field::Bare(value)
+      }
+    };
+
+    record.push(field);
+
+    match chars.next() {
+      Some(',') => continue,
+      Some('\r') if chars.peek() == Some(&'\n') => {
+        chars.next();
+      }
+      Some('\r' | '\n') | None => {}
+      Some(_) => return Err(StreamError::InvalidData),
+    }
+
+    let is_blank = matches!(record.as_slice(), [Field::Bare(value)] if value.trim().is_empty());
+
+    if !is_blank {
+      records.push(std::mem::take(&mut record));
+    }
+
+    record.clear();
+
+    if chars.peek().is_none() {
+      return Ok(records);
+    }
+  }
+}
+
+fn write_record(output: &mut String, fields: impl Iterator<Item = String>) {
+  for (index, field) in fields.enumerate() {
+    if index > 0 {
+      output.push(',');
+    }
+
+    output.push_str(&field);
+  }
+
+  output.push('\n');
+}
+
+fn write_variant(variant: &Variant) -> Result<String, StreamError> {
+  let mut output = String::new();
+
+  match variant {
+    Variant::Null => {}
+    Variant::Bool(value) => write!(output, "{}", value).unwrap(),
+    Variant::U8(value) => write!(output, "{}", value).unwrap(),
+    Variant::U16(value) => write!(output, "{}", value).unwrap(),
+    Variant::U32(value) => write!(output, "{}", value).unwrap(),
+    Variant::U64(value) => write!(output, "{}", value).unwrap(),
+    Variant::I8(value) => write!(output, "{}", value).unwrap(),
+    Variant::I16(value) => write!(output, "{}", value).unwrap(),
+    Variant::I32(value) => write!(output, "{}", value).unwrap(),
+    Variant::I64(value) => write!(output, "{}", value).unwrap(),
+    Variant::F32(value) => write!(output, "{:?}", value).unwrap(),
+    Variant::F64(value) => write!(output, "{:?}", value).unwrap(),
+    Variant::Char(value) => output = quote(&value.to_string()),
+    Variant::String(value) => output = quote(value),
+    Variant::StringName(value) => output = quote(value.as_ref()),
+    _ => return Err(StreamError::InvalidData),
+  }
+
+  Ok(output)
+}
+
+/// Quotes a string, so it's read back as a string whatever it holds.
+fn quote(value: &str) -> String {
+  format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(text: &str) -> Result<Chunk, StreamError> {
+    CsvFormat.read_chunk(&mut std::io::Cursor::new(text.as_bytes()))
+  }
+
+  #[test]
+  fn it_should_read_rows_keyed_by_column() {
+    let chunk =
+      parse("id,health,speed,boss,title\ngoblin,10,1.5,false,\n\"ogre, big\",200,0.5,true,\"\"\"Smasher\"\"\"\r\n")
+        .unwrap();
+
+    let Chunk::Sequence(rows) = chunk else {
+      panic!("expected a sequence");
+    };
+
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].read_field::<String>("id").unwrap(), "goblin");
+    assert_eq!(rows[0].read_field::<u32>("health").unwrap(), 10);
+    assert_eq!(rows[0].read_field::<f32>("speed").unwrap(), 1.5);
+    assert!(matches!(rows[0].get("title"), Some(Chunk::Variant(Variant::Null))));
+    assert_eq!(rows[1].read_field::<String>("id").unwrap(), "ogre, big");
+    assert!(rows[1].read_field::<bool>("boss").unwrap());
+    assert_eq!(rows[1].read_field::<String>("title").unwrap(), "\"Smasher\"");
+  }
+
+  #[test]
+  fn it_should_reject_ragged_or_unterminated_rows() {
+    assert!(parse("a,b\n1,2,3\n").is_err());
+    assert!(parse("a,b\n\"1,2\n").is_err());
+  }
+
+  #[test]
+  fn it_should_round_trip_chunks() {
+    let chunk = parse("name,price\n\"42\",3\n\"sword\",1.25\n").unwrap();
+    let mut output = std::io::Cursor::new(Vec::new());
+
+    CsvFormat.write_chunk(&mut output, &chunk).unwrap();
+
+    let text = String::from_utf8(output.into_inner()).unwrap();
+
+    assert_eq!(text, "\"name\",\"price\"\n\"42\",3\n\"sword\",1.25\n");
+    assert_eq!(parse(&text).unwrap(), chunk);
+  }
+}
@@ -10,22 +10,9 @@ pub struct JsonFormat {
 impl Format for JsonFormat {
   fn read_chunk(&mut self, stream: &mut dyn InputStream) -> Result<Chunk, StreamError> {
     let mut reader = parser::JsonStreamReader::new(stream);
+    let token = reader.next_token()?;
 
-    while let Ok(token) = reader.next_token() {
-      // TODO: do something with the token
-      match token {
-        JsonToken::ObjectStart => {}
-        JsonToken::ObjectEnd => {}
-        JsonToken::ArrayStart => {}
-        JsonToken::ArrayEnd => {}
-        JsonToken::String(_) => {}
-        JsonToken::Number(_) => {}
-        JsonToken::Boolean(_) => {}
-        JsonToken::Null => {}
-      }
-    }
-
-    todo!()
+    read_value(&mut reader, token)
   }
 
   fn write_chunk(&mut self, stream: &mut dyn OutputStream, chunk: &Chunk) -> Result<(), StreamError> {
@@ -106,6 +93,46 @@ impl Format for JsonFormat {
   }
 }
 
+/// Reads the value that starts with the given token.
+fn read_value(reader: &mut parser::JsonStreamReader, token: JsonToken) -> Result<Chunk, StreamError> {
+  match token {
+    JsonToken::ObjectStart => {
+      let mut map = FastHashMap::default();
+
+      loop {
+        match reader.next_token()? {
+          JsonToken::ObjectEnd => break,
+          JsonToken::String(key) => {
+            let token = reader.next_token()?;
+
+            map.insert(key, read_value(reader, token)?);
+          }
+          _ => return Err(StreamError::InvalidData),
+        }
+      }
+
+      Ok(Chunk::Map(map))
+    }
+    JsonToken::ArrayStart => {
+      let mut sequence = Vec::new();
+
+      loop {
+        match reader.next_token()? {
+          JsonToken::ArrayEnd => break,
+          token => sequence.push(read_value(reader, token)?),
+        }
+      }
+
+      Ok(Chunk::Sequence(sequence))
+    }
+    JsonToken::String(value) => Ok(Chunk::Variant(Variant::String(value))),
+    JsonToken::Number(value) => Ok(Chunk::Variant(Variant::F64(value))),
+    JsonToken::Boolean(value) => Ok(Chunk::Variant(Variant::Bool(value))),
+    JsonToken::Null => Ok(Chunk::Variant(Variant::Null)),
+    JsonToken::ObjectEnd | JsonToken::ArrayEnd => Err(StreamError::InvalidData),
+  }
+}
+
 #[allow(dead_code)]
 mod parser {
   use super::*;
@@ -202,8 +229,15 @@ mod parser {
 
             return Ok(JsonToken::Boolean(false));
           }
+          'n' => {
+            self.stream.read_char()?; // read 'u'
+            self.stream.read_char()?; // read 'l'
+            self.stream.read_char()?; // read 'l'
+
+            return Ok(JsonToken::Null);
+          }
           // numbers
-          '0'..='9' => {
+          '0'..='9' | '-' => {
             let mut number = next.to_string();
 
             while let Ok(next) = self.stream.read_char() {
@@ -276,5 +310,22 @@ mod parser {
 
       assert_eq!(parser.next_token().unwrap(), JsonToken::Number(42.0));
     }
+
+    #[test]
+    fn format_should_read_nested_values() {
+      let code = r#"{"name": "test", "values": [1, -2.5, true], "none": null, "empty": {}}"#;
+      let chunk = JsonFormat::default()
+        .read_chunk(&mut std::io::Cursor::new(code.as_bytes()))
+        .unwrap();
+
+      assert_eq!(chunk.read_field::<String>("name").unwrap(), "test");
+      assert_eq!(chunk.get("values").unwrap().as_sequence().unwrap(), &[
+        Chunk::Variant(Variant::F64(1.)),
+        Chunk::Variant(Variant::F64(-2.5)),
+        Chunk::Variant(Variant::Bool(true)),
+      ]);
+      assert!(matches!(chunk.get("none"), Some(Chunk::Variant(Variant::Null))));
+      assert_eq!(chunk.get("empty"), Some(&Chunk::Map(FastHashMap::default())));
+    }
   }
 }
@@ -0,0 +1,679 @@
+use std::{fmt::Write, iter::Peekable, str::Chars};
+
+use super::*;
+
+/// The key used to record the name of a named struct or enum variant when it's
+/// read into a [`Chunk::Map`].
+pub const RON_TYPE_KEY: &str = "@type";
+
+/// A file format for working with RON (Rusty Object Notation).
+///
+/// RON maps onto [`Chunk`]s as follows:
+///
+/// * Named structs and enum variants, `Name(field: value)`, become maps with
+///   the name recorded under [`RON_TYPE_KEY`]; anonymous structs, `(field:
+///   value)`, and `{ "key": value }` maps become plain maps.
+/// * Tuples and lists, `(a, b)` and `[a, b]`, become sequences; a tuple
+///   variant's values are recorded under keys `"0"`, `"1"`, etc.
+/// * `Some(value)` is unwrapped, `None` becomes null, and other bare
+///   identifiers (unit variants) become strings.
+/// * Non-finite floats are written, and read, as `NaN`, `inf` and `-inf`.
+#[derive(Default)]
+pub struct RonFormat {
+  indent: usize,
+}
+
+impl Format for RonFormat {
+  fn read_chunk(&mut self, stream: &mut dyn InputStream) -> Result<Chunk, StreamError> {
+    let mut text = String::new();
+
+    stream.read_to_string(&mut text)?;
+
+    let mut parser = RonParser::new(&text);
+    let chunk = parser.parse_value()?;
+
+    parser.skip_trivia();
+
+    match parser.chars.peek() {
+      None => Ok(chunk),
+      Some(_) => Err(StreamError::InvalidData),
+    }
+  }
+
+  fn write_chunk(&mut self, stream: &mut dyn OutputStream, chunk: &Chunk) -> Result<(), StreamError> {
+    let mut output = String::new();
+
+    self
+      .write_value(&mut output, chunk)
+      .map_err(|_| StreamError::GeneralFailure)?;
+
+    stream.write_bytes(output.as_bytes())
+  }
+}
+
+impl RonFormat {
+  fn write_value(&mut self, output: &mut String, chunk: &Chunk) -> std::fmt::Result {
+    match chunk {
+      Chunk::Variant(variant) => write_variant(output, variant),
+      Chunk::Sequence(values) => self.write_values(output, values.iter(), '[', ']'),
+      Chunk::Map(map) => {
+        let mut entries = map.iter().filter(|(key, _)| *key != RON_TYPE_KEY).collect::<Vec<_>>();
+        let name = match map.get(RON_TYPE_KEY) {
+          Some(Chunk::Variant(Variant::String(name))) => Some(name),
+          _ => None,
+        };
+
+        if let Some(name) = name {
+          output.push_str(name);
+
+          // tuple variants are read with their values under "0", "1", etc.
+          if !entries.is_empty() && (0..entries.len()).all(|index| map.contains_key(&index.to_string())) {
+            let values = (0..entries.len()).map(|index| &map[&index.to_string()]);
+
+            return self.write_values(output, values, '(', ')');
+          }
+        }
+
+        entries.sort_by_key(|(key, _)| *key);
+
+        // named structs always need parentheses, and empty maps are written
+        // as `{}` so they aren't read back as empty tuples
+        let is_struct = name.is_some() || (!entries.is_empty() && entries.iter().all(|(key, _)| is_identifier(key)));
+
+        output.push(if is_struct { '(' } else { '{' });
+
+        for (key, value) in &entries {
+          self.write_newline(output, 1);
+
+          if is_struct {
+            write!(output, "{}: ", key)?;
+          } else {
+            write!(output, "\"{}\": ", escape(key))?;
+          }
+
+          self.indent += 1;
+          self.write_value(output, value)?;
+          self.indent -= 1;
+
+          output.push(',');
+        }
+
+        if !entries.is_empty() {
+          self.write_newline(output, 0);
+        }
+
+        output.push(if is_struct { ')' } else { '}' });
+        Ok(())
+      }
+    }
+  }
+
+  /// Writes comma-separated values between the given brackets.
+  fn write_values<'a>(
+    &mut self,
+    output: &mut String,
+    values: impl ExactSizeIterator<Item = &'a Chunk> + Clone,
+    open: char,
+    close: char,
+  ) -> std::fmt::Result {
+    let count = values.len();
+    let nested = values.clone().any(|value| !matches!(value, Chunk::Variant(_)));
+
+    output.push(open);
+
+    for (index, value) in values.enumerate() {
+      if nested {
+        self.write_newline(output, 1);
+      } else if index > 0 {
+        output.push(' ');
+      }
+
+      self.indent += 1;
+      self.write_value(output, value)?;
+      self.indent -= 1;
+
+      if nested || index + 1 < count {
+        output.push(',');
+      }
+    }
+
+    if nested {
+      self.write_newline(output, 0);
+    }
+
+    output.push(close);
+    Ok(())
+  }
+
+  fn write_newline(&self, output: &mut String, extra: usize) {
+    output.push('\n');
+    output.push_str(&"  ".repeat(self.indent + extra));
+  }
+}
+
+fn write_variant(output: &mut String, variant: &Variant) -> std::fmt::Result {
+  match variant {
+    Variant::Null => output.push_str("None"),
+    Variant::Bool(value) => write!(output, "{}", value)?,
+    Variant::Char(value) => write!(output, "\"{}\"", escape(&value.to_string()))?,
+    Variant::U8(value) => write!(output, "{}", value)?,
+    Variant::U16(value) => write!(output, "{}", value)?,
+    Variant::U32(value) => write!(output, "{}", value)?,
+    Variant::U64(value) => write!(output, "{}", value)?,
+    Variant::I8(value) => write!(output, "{}", value)?,
+    Variant::I16(value) => write!(output, "{}", value)?,
+    Variant::I32(value) => write!(output, "{}", value)?,
+    Variant::I64(value) => write!(output, "{}", value)?,
+    Variant::F32(value) => write_float(output, *value)?,
+    Variant::F64(value) => write_float(output, *value)?,
+    Variant::String(value) => write!(output, "\"{}\"", escape(value))?,
+    Variant::StringName(value) => write!(output, "\"{}\"", escape(value.as_ref()))?,
+    Variant::Vec2(value) => write!(output, "({:?}, {:?})", value.x, value.y)?,
+    Variant::Vec3(value) => write!(output, "({:?}, {:?}, {:?})", value.x, value.y, value.z)?,
+    Variant::Vec4(value) => write!(output, "({:?}, {:?}, {:?}, {:?})", value.x, value.y, value.z, value.w)?,
+    Variant::Quat(value) => write!(output, "({:?}, {:?}, {:?}, {:?})", value.x, value.y, value.z, value.w)?,
+    Variant::Color(value) => write!(output, "({:?}, {:?}, {:?}, {:?})", value.r, value.g, value.b, value.a)?,
+    Variant::Color32(value) => write!(output, "({}, {}, {}, {})", value.r, value.g, value.b, value.a)?,
+    Variant::Callable(_) | Variant::Pointer(_) | Variant::Any(_) => output.push_str("None"),
+  }
+
+  Ok(())
+}
+
+/// Writes a float, spelling out values that don't have a numeric literal the
+/// way RON does.
+fn write_float<F: Copy + Into<f64> + std::fmt::Debug>(output: &mut String, value: F) -> std::fmt::Result {
+  let float: f64 = value.into();
+
+  match float {
+    _ if float.is_nan() => output.push_str("NaN"),
+    f64::INFINITY => output.push_str("inf"),
+    f64::NEG_INFINITY => output.push_str("-inf"),
+    _ => write!(output, "{:?}", value)?,
+  }
+
+  Ok(())
+}
+
+fn is_identifier(value: &str) -> bool {
+  let mut chars = value.chars();
+
+  matches!(chars.next(), Some(first) if first.is_alphabetic() || first == '_')
+    && chars.all(|next| next.is_alphanumeric() || next == '_')
+}
+
+fn escape(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+    .replace('\t', "\\t")
+}
+
+/// How deeply values may nest before the text is rejected, so malicious
+/// input can't overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// A recursive-descent parser for RON text.
+struct RonParser<'a> {
+  chars: Peekable<Chars<'a>>,
+  depth: usize,
+}
+
+impl<'a> RonParser<'a> {
+  fn new(text: &'a str) -> Self {
+    Self {
+      chars: text.chars().peekable(),
+      depth: 0,
+    }
+  }
+
+  /// Skips whitespace and comments.
+  fn skip_trivia(&mut self) {
+    loop {
+      match self.chars.peek() {
+        Some(next) if next.is_whitespace() => {
+          self.chars.next();
+        }
+        Some('/') => {
+          let mut lookahead = self.chars.clone();
+          lookahead.next();
+
+          match lookahead.next() {
+            Some('/') => {
+              for next in self.chars.by_ref() {
+                if next == '\n' {
+                  break;
+                }
+              }
+            }
+            Some('*') => {
+              self.chars.next();
+              self.chars.next();
+
+              let mut previous = '\0';
+              for next in self.chars.by_ref() {
+                if previous == '*' && next == '/' {
+                  break;
+                }
+                previous = next;
+              }
+            }
+            _ => return,
+          }
+        }
+        _ => return,
+      }
+    }
+  }
+
+  fn expect(&mut self, expected: char) -> Result<(), StreamError> {
+    self.skip_trivia();
+
+    match self.chars.next() {
+      Some(next) if next == expected => Ok(()),
+      _ => Err(StreamError::InvalidData),
+    }
+  }
+
+  /// Consumes the given character if it's next, returning whether it was.
+  fn accept(&mut self, expected: char) -> bool {
+    self.skip_trivia();
+
+    if self.chars.peek() == Some(&expected) {
+      self.chars.next();
+      true
+    } else {
+      false
+    }
+  }
+
+  fn parse_value(&mut self) -> Result<Chunk, StreamError> {
+    if self.depth >= MAX_DEPTH {
+      return Err(StreamError::InvalidData);
+    }
+
+    self.depth += 1;
+    let value = self.parse_nested_value();
+    self.depth -= 1;
+
+    value
+  }
+
+  fn parse_nested_value(&mut self) -> Result<Chunk, StreamError> {
+    self.skip_trivia();
+
+    match self.chars.peek().copied() {
+      Some('"') => Ok(Chunk::Variant(Variant::String(self.parse_string()?))),
+      Some('[') => {
+        self.chars.next();
+        Ok(Chunk::Sequence(self.parse_values(']')?))
+      }
+      Some('{') => {
+        self.chars.next();
+        self.parse_map()
+      }
+      Some('(') => {
+        self.chars.next();
+        self.parse_parenthesized(None)
+      }
+      Some(next) if next.is_ascii_digit() || next == '-' || next == '+' || next == '.' => self.parse_number(),
+      Some(next) if next.is_alphabetic() || next == '_' => {
+        let identifier = self.parse_identifier();
+
+        match identifier.as_str() {
+          "true" => return Ok(Chunk::Variant(Variant::Bool(true))),
+          "false" => return Ok(Chunk::Variant(Variant::Bool(false))),
+          "None" => return Ok(Chunk::Variant(Variant::Null)),
+          "NaN" => return Ok(Chunk::Variant(Variant::F64(f64::NAN))),
+          "inf" => return Ok(Chunk::Variant(Variant::F64(f64::INFINITY))),
+          _ => {}
+        }
+
+        if self.accept('(') {
+          if identifier == "Some" {
+            let value = self.parse_value()?;
+
+            self.accept(',');
+            self.expect(')')?;
+
+            return Ok(value);
+          }
+
+          self.parse_parenthesized(Some(identifier))
+        } else {
+          Ok(Chunk::Variant(Variant::String(identifier)))
+        }
+      }
+      _ => Err(StreamError::InvalidData),
+    }
+  }
+
+  /// Parses the contents of a struct or tuple, after the opening parenthesis.
+  fn parse_parenthesized(&mut self, name: Option<String>) -> Result<Chunk, StreamError> {
+    self.skip_trivia();
+
+    // look ahead for `identifier:` to distinguish structs from tuples
+    let mut lookahead = self.chars.clone();
+    let mut has_identifier = false;
+
+    while let Some(next) = lookahead.peek() {
+      if next.is_alphanumeric() || *next == '_' {
+        has_identifier = true;
+        lookahead.next();
+      } else {
+        break;
+      }
+    }
+
+    while lookahead.peek().is_some_and(|next| next.is_whitespace()) {
+      lookahead.next();
+    }
+
+    let is_struct = has_identifier && lookahead.peek() == Some(&':');
+
+    if is_struct {
+      let mut map = FastHashMap::default();
+
+      if let Some(name) = name {
+        map.insert(RON_TYPE_KEY.to_string(), Chunk::Variant(Variant::String(name)));
+      }
+
+      while !self.accept(')') {
+        self.skip_trivia();
+
+        let key = self.parse_identifier();
+        if key.is_empty() {
+          return Err(StreamError::InvalidData);
+        }
+
+        self.expect(':')?;
+        map.insert(key, self.parse_value()?);
+
+        if !self.accept(',') {
+          self.expect(')')?;
+          break;
+        }
+      }
+
+      return Ok(Chunk::Map(map));
+    }
+
+    let values = self.parse_values(')')?;
+
+    match name {
+      None => Ok(Chunk::Sequence(values)),
+      Some(name) => {
+        let mut map = FastHashMap::default();
+
+        map.insert(RON_TYPE_KEY.to_string(), Chunk::Variant(Variant::String(name)));
+
+        for (index, value) in values.into_iter().enumerate() {
+          map.insert(index.to_string(), value);
+        }
+
+        Ok(Chunk::Map(map))
+      }
+    }
+  }
+
+  /// Parses comma-separated values up to the given closing character.
+  fn parse_values(&mut self, close: char) -> Result<Vec<Chunk>, StreamError> {
+    let mut values = Vec::new();
+
+    while !self.accept(close) {
+      values.push(self.parse_value()?);
+
+      if !self.accept(',') {
+        self.expect(close)?;
+        break;
+      }
+    }
+
+    Ok(values)
+  }
+
+  fn parse_map(&mut self) -> Result<Chunk, StreamError> {
+    let mut map = FastHashMap::default();
+
+    while !self.accept('}') {
+      let key = match self.parse_value()? {
+        Chunk::Variant(Variant::String(key)) => key,
+        Chunk::Variant(variant) => {
+          let mut key = String::new();
+          write_variant(&mut key, &variant).map_err(|_| StreamError::InvalidData)?;
+          key
+        }
+        _ => return Err(StreamError::InvalidData),
+      };
+
+      self.expect(':')?;
+      map.insert(key, self.parse_value()?);
+
+      if !self.accept(',') {
+        self.expect('}')?;
+        break;
+      }
+    }
+
+    Ok(Chunk::Map(map))
+  }
+
+  fn parse_identifier(&mut self) -> String {
+    let mut identifier = String::new();
+
+    while let Some(next) = self.chars.peek() {
+      if next.is_alphanumeric() || *next == '_' {
+        identifier.push(*next);
+        self.chars.next();
+      } else {
+        break;
+      }
+    }
+
+    identifier
+  }
+
+  fn parse_string(&mut self) -> Result<String, StreamError> {
+    self.expect('"')?;
+
+    let mut string = String::new();
+
+    loop {
+      match self.chars.next().ok_or(StreamError::InvalidData)? {
+        '"' => return Ok(string),
+        '\\' => match self.chars.next().ok_or(StreamError::InvalidData)? {
+          'n' => string.push('\n'),
+          't' => string.push('\t'),
+          'r' => string.push('\r'),
+          '0' => string.push('\0'),
+          other => string.push(other),
+        },
+        other => string.push(other),
+      }
+    }
+  }
+
+  fn parse_number(&mut self) -> Result<Chunk, StreamError> {
+    let mut number = String::new();
+
+    while let Some(next) = self.chars.peek() {
+      if next.is_ascii_digit() || matches!(next, '.' | 'e' | 'E' | '+' | '-' | '_') {
+        if *next != '_' {
+          number.push(*next);
+        }
+        self.chars.next();
+      } else {
+        break;
+      }
+    }
+
+    if matches!(number.as_str(), "-" | "+") && self.parse_identifier() == "inf" {
+      return Ok(Chunk::Variant(Variant::F64(match number.as_str() {
+        "-" => f64::NEG_INFINITY,
+        _ => f64::INFINITY,
+      })));
+    }
+
+    if let Ok(integer) = number.parse::<i64>() {
+      return Ok(Chunk::Variant(Variant::I64(integer)));
+    }
+
+    number
+      .parse::<f64>()
+      .map(|float| Chunk::Variant(Variant::F64(float)))
+      .map_err(|_| StreamError::InvalidData)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(text: &str) -> Chunk {
+    RonFormat::default()
+      .read_chunk(&mut std::io::Cursor::new(text.as_bytes()))
+      .unwrap()
+  }
+
+  #[test]
+  fn it_should_read_scalars() {
+    assert_eq!(parse("42"), Chunk::Variant(Variant::I64(42)));
+    assert_eq!(parse("-1.5e2"), Chunk::Variant(Variant::F64(-150.)));
+    assert_eq!(parse("true"), Chunk::Variant(Variant::Bool(true)));
+    assert!(matches!(parse("None"), Chunk::Variant(Variant::Null)));
+    assert_eq!(parse("Some(3)"), Chunk::Variant(Variant::I64(3)));
+    assert_eq!(
+      parse(r#""a \"quoted\" string""#),
+      Chunk::Variant(Variant::String("a \"quoted\" string".to_string()))
+    );
+  }
+
+  #[test]
+  fn it_should_read_named_structs() {
+    let chunk = parse(
+      r#"
+      // a comment
+      Player(
+        name: "Bob",
+        position: (1.0, 2.0),
+        tags: ["a", "b"], /* trailing */
+      )
+      "#,
+    );
+
+    let Chunk::Map(map) = chunk else {
+      panic!("expected a map");
+    };
+
+    assert_eq!(map[RON_TYPE_KEY], Chunk::Variant(Variant::String("Player".to_string())));
+    assert_eq!(map["name"], Chunk::Variant(Variant::String("Bob".to_string())));
+    assert_eq!(
+      map["position"],
+      Chunk::Sequence(vec![Chunk::Variant(Variant::F64(1.)), Chunk::Variant(Variant::F64(2.))])
+    );
+  }
+
+  #[test]
+  fn it_should_read_maps_and_tuple_variants() {
+    let Chunk::Map(map) = parse(r#"{ "first": Circle(2.0), "second": Empty }"#) else {
+      panic!("expected a map");
+    };
+
+    let Chunk::Map(circle) = &map["first"] else {
+      panic!("expected a map");
+    };
+
+    assert_eq!(circle["0"], Chunk::Variant(Variant::F64(2.)));
+    assert_eq!(map["second"], Chunk::Variant(Variant::String("Empty".to_string())));
+  }
+
+  #[test]
+  fn it_should_reject_malformed_input() {
+    let mut format = RonFormat::default();
+
+    assert!(format
+      .read_chunk(&mut std::io::Cursor::new(b"(a: 1".as_slice()))
+      .is_err());
+    assert!(format
+      .read_chunk(&mut std::io::Cursor::new(b"[1, 2] 3".as_slice()))
+      .is_err());
+  }
+
+  #[test]
+  fn it_should_round_trip_chunks() {
+    let mut map = FastHashMap::default();
+
+    map.insert(
+      RON_TYPE_KEY.to_string(),
+      Chunk::Variant(Variant::String("Thing".to_string())),
+    );
+    map.insert("count".to_string(), Chunk::Variant(Variant::I64(3)));
+    map.insert("scale".to_string(), Chunk::Variant(Variant::F64(1.)));
+    map.insert(
+      "items".to_string(),
+      Chunk::Sequence(vec![Chunk::Variant(Variant::String("x".to_string()))]),
+    );
+
+    let chunk = Chunk::Map(map);
+    let mut output = std::io::Cursor::new(Vec::new());
+
+    RonFormat::default().write_chunk(&mut output, &chunk).unwrap();
+
+    let text = String::from_utf8(output.into_inner()).unwrap();
+
+    assert!(text.starts_with("Thing("));
+    assert_eq!(parse(&text), chunk);
+  }
+
+  fn round_trip(chunk: &Chunk) -> Chunk {
+    let mut output = std::io::Cursor::new(Vec::new());
+
+    RonFormat::default().write_chunk(&mut output, chunk).unwrap();
+
+    parse(&String::from_utf8(output.into_inner()).unwrap())
+  }
+
+  #[test]
+  fn it_should_round_trip_empty_maps() {
+    let empty = Chunk::Map(FastHashMap::default());
+    let mut map = FastHashMap::default();
+
+    map.insert("empty".to_string(), empty.clone());
+
+    assert_eq!(round_trip(&empty), empty);
+    assert_eq!(round_trip(&Chunk::Map(map.clone())), Chunk::Map(map));
+  }
+
+  #[test]
+  fn it_should_round_trip_tuple_variants() {
+    let chunk = parse(r#"{ "first": Circle(2.0), "second": Line((0, 0), (1, 1)), "third": Empty() }"#);
+
+    assert_eq!(round_trip(&chunk), chunk);
+  }
+
+  #[test]
+  fn it_should_round_trip_non_finite_floats() {
+    let chunk = Chunk::Sequence(vec![
+      Chunk::Variant(Variant::F64(f64::INFINITY)),
+      Chunk::Variant(Variant::F64(f64::NEG_INFINITY)),
+    ]);
+
+    assert_eq!(round_trip(&chunk), chunk);
+    assert!(matches!(
+      round_trip(&Chunk::Variant(Variant::F32(f32::NAN))),
+      Chunk::Variant(Variant::F64(value)) if value.is_nan()
+    ));
+  }
+
+  #[test]
+  fn it_should_reject_deeply_nested_input() {
+    let mut format = RonFormat::default();
+    let nested = "[".repeat(MAX_DEPTH + 1) + &"]".repeat(MAX_DEPTH + 1);
+
+    assert!(format.read_chunk(&mut std::io::Cursor::new(nested.as_bytes())).is_err());
+    assert!(format
+      .read_chunk(&mut std::io::Cursor::new(&nested.as_bytes()[1..nested.len() - 1]))
+      .is_ok());
+  }
+}
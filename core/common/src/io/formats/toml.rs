@@ -0,0 +1,605 @@
+use std::{fmt::Write, iter::Peekable, str::Chars};
+
+use super::*;
+
+/// A file format for working with TOML, as used by project files.
+///
+/// TOML maps onto [`Chunk`]s as follows:
+///
+/// * The document, tables and inline tables become maps; dotted keys and
+///   `[a.b]` headers become nested maps.
+/// * Arrays become sequences, and `[[a]]` arrays of tables become sequences of
+///   maps.
+/// * Strings, integers, floats and booleans become their variants; dates and
+///   times aren't supported.
+///
+/// TOML has no null, so null fields are left out when writing.
+#[derive(Default)]
+pub struct TomlFormat;
+
+impl Format for TomlFormat {
+  fn read_chunk(&mut self, stream: &mut dyn InputStream) -> Result<Chunk, StreamError> {
+    let mut text = String::new();
+
+    stream.read_to_string(&mut text)?;
+
+    TomlParser::new(&text).parse_document()
+  }
+
+  fn write_chunk(&mut self, stream: &mut dyn OutputStream, chunk: &Chunk) -> Result<(), StreamError> {
+    let Chunk::Map(fields) = chunk else {
+      return Err(StreamError::InvalidData);
+    };
+
+    let mut output = String::new();
+
+    write_table(&mut output, &mut Vec::new(), fields).map_err(|_| StreamError::InvalidData)?;
+
+    stream.write_bytes(output.trim_start().as_bytes())
+  }
+}
+
+/// Writes the fields of a table, followed by its sub-tables under their own
+/// headers.
+fn write_table(output: &mut String, path: &mut Vec<String>, fields: &FastHashMap<String, Chunk>) -> std::fmt::Result {
+  let mut fields = fields.iter().collect::<Vec<_>>();
+
+  fields.sort_by_key(|(name, _)| name.as_str());
+
+  for (name, value) in &fields {
+    match value {
+      Chunk::Variant(Variant::Null) | Chunk::Map(_) => continue,
+      Chunk::Sequence(items) if is_table_array(items) => continue,
+      _ => {}
+    }
+
+    write!(output, "{} = ", format_key(name))?;
+    write_value(output, value)?;
+    output.push('\n');
+  }
+
+  for (name, value) in &fields {
+    path.push(format_key(name));
+
+    match value {
+      Chunk::Map(table) => {
+        write!(output, "\n[{}]\n", path.join("."))?;
+        write_table(output, path, table)?;
+      }
+      Chunk::Sequence(items) if is_table_array(items) => {
+        for item in items.iter() {
+          let Chunk::Map(table) = item else { unreachable!() };
+
+          write!(output, "\n[[{}]]\n", path.join("."))?;
+          write_table(output, path, table)?;
+        }
+      }
+      _ => {}
+    }
+
+    path.pop();
+  }
+
+  Ok(())
+}
+
+/// Writes a value inline, using inline tables for any maps.
+fn write_value(output: &mut String, chunk: &Chunk) -> std::fmt::Result {
+  match chunk {
+    Chunk::Variant(variant) => write_variant(output, variant),
+    Chunk::Sequence(items) => {
+      output.push('[');
+
+      for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+          output.push_str(", ");
+        }
+
+        write_value(output, item)?;
+      }
+
+      output.push(']');
+      Ok(())
+    }
+    Chunk::Map(fields) => {
+      let mut fields = fields
+        .iter()
+        .filter(|(_, value)| !matches!(value, Chunk::Variant(Variant::Null)))
+        .collect::<Vec<_>>();
+
+      fields.sort_by_key(|(name, _)| name.as_str());
+
+      output.push('{');
+
+      for (index, (name, value)) in fields.into_iter().enumerate() {
+        output.push_str(if index > 0 { ", " } else { " " });
+        write!(output, "{} = ", format_key(name))?;
+        write_value(output, value)?;
+      }
+
+      output.push_str(" }");
+      Ok(())
+    }
+  }
+}
+
+fn write_variant(output: &mut String, variant: &Variant) -> std::fmt::Result {
+  match variant {
+    Variant::Bool(value) => write!(output, "{}", value),
+    Variant::Char(value) => write!(output, "\"{}\"", escape(&value.to_string())),
+    Variant::U8(value) => write!(output, "{}", value),
+    Variant::U16(value) => write!(output, "{}", value),
+    Variant::U32(value) => write!(output, "{}", value),
+    Variant::U64(value) => write!(output, "{}", value),
+    Variant::I8(value) => write!(output, "{}", value),
+    Variant::I16(value) => write!(output, "{}", value),
+    Variant::I32(value) => write!(output, "{}", value),
+    Variant::I64(value) => write!(output, "{}", value),
+    Variant::F32(value) => write!(output, "{:?}", value),
+    Variant::F64(value) => write!(output, "{:?}", value),
+    Variant::String(value) => write!(output, "\"{}\"", escape(value)),
+    Variant::StringName(value) => write!(output, "\"{}\"", escape(value.as_ref())),
+    Variant::Vec2(value) => write!(output, "[{:?}, {:?}]", value.x, value.y),
+    Variant::Vec3(value) => write!(output, "[{:?}, {:?}, {:?}]", value.x, value.y, value.z),
+    Variant::Vec4(value) => write!(output, "[{:?}, {:?}, {:?}, {:?}]", value.x, value.y, value.z, value.w),
+    Variant::Quat(value) => write!(output, "[{:?}, {:?}, {:?}, {:?}]", value.x, value.y, value.z, value.w),
+    Variant::Color(value) => write!(output, "[{:?}, {:?}, {:?}, {:?}]", value.r, value.g, value.b, value.a),
+    Variant::Color32(value) => write!(output, "[{}, {}, {}, {}]", value.r, value.g, value.b, value.a),
+    // there's no way to write these in TOML
+    Variant::Null | Variant::Callable(_) | Variant::Pointer(_) | Variant::Any(_) => Err(std::fmt::Error),
+  }
+}
+
+/// Determines if a sequence is written as an array of tables.
+fn is_table_array(items: &[Chunk]) -> bool {
+  !items.is_empty() && items.iter().all(|item| matches!(item, Chunk::Map(_)))
+}
+
+fn is_bare_key_char(next: char) -> bool {
+  next.is_ascii_alphanumeric() || next == '_' || next == '-'
+}
+
+fn format_key(key: &str) -> String {
+  match !key.is_empty() && key.chars().all(is_bare_key_char) {
+    true => key.to_string(),
+    false => format!("\"{}\"", escape(key)),
+  }
+}
+
+fn escape(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+    .replace('\t', "\\t")
+}
+
+/// A recursive-descent parser for TOML text.
+struct TomlParser<'a> {
+  chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> TomlParser<'a> {
+  fn new(text: &'a str) -> Self {
+    Self {
+      chars: text.chars().peekable(),
+    }
+  }
+
+  /// Skips spaces, tabs and comments, but not line breaks.
+  fn skip_spaces(&mut self) {
+    while let Some(next) = self.chars.peek() {
+      match next {
+        ' ' | '\t' => {
+          self.chars.next();
+        }
+        '#' => while self.chars.next_if(|next| *next != '\n').is_some() {},
+        _ => return,
+      }
+    }
+  }
+
+  /// Skips whitespace, line breaks and comments.
+  fn skip_trivia(&mut self) {
+    loop {
+      self.skip_spaces();
+
+      if self.chars.next_if(|next| matches!(next, '\n' | '\r')).is_none() {
+        return;
+      }
+    }
+  }
+
+  fn expect(&mut self, expected: char) -> Result<(), StreamError> {
+    match self.chars.next() {
+      Some(next) if next == expected => Ok(()),
+      _ => Err(StreamError::InvalidData),
+    }
+  }
+
+  /// Expects the end of a line, after a key/value pair or a table header.
+  fn expect_line_end(&mut self) -> Result<(), StreamError> {
+    self.skip_spaces();
+
+    match self.chars.next() {
+      None | Some('\n') => Ok(()),
+      Some('\r') => self.expect('\n'),
+      _ => Err(StreamError::InvalidData),
+    }
+  }
+
+  fn parse_document(&mut self) -> Result<Chunk, StreamError> {
+    let mut root = FastHashMap::default();
+    let mut table = Vec::new();
+
+    loop {
+      self.skip_trivia();
+
+      match self.chars.peek() {
+        None => return Ok(Chunk::Map(root)),
+        Some('[') => {
+          self.chars.next();
+
+          let is_array = self.chars.next_if_eq(&'[').is_some();
+
+          table = self.parse_key()?;
+
+          self.expect(']')?;
+
+          if is_array {
+            self.expect(']')?;
+
+            let (last, parent) = table.split_last().unwrap();
+            let parent = table_at(&mut root, parent)?;
+            let items = parent
+              .entry(last.clone())
+              .or_insert_with(|| Chunk::Sequence(Vec::new()));
+
+            match items {
+              Chunk::Sequence(items) => items.push(Chunk::Map(FastHashMap::default())),
+              _ => return Err(StreamError::InvalidData),
+            }
+          } else {
+            table_at(&mut root, &table)?;
+          }
+        }
+        Some(_) => {
+          let (key, value) = self.parse_key_value()?;
+
+          insert(table_at(&mut root, &table)?, &key, value)?;
+        }
+      }
+
+      self.expect_line_end()?;
+    }
+  }
+
+  fn parse_key_value(&mut self) -> Result<(Vec<String>, Chunk), StreamError> {
+    let key = self.parse_key()?;
+
+    self.skip_spaces();
+    self.expect('=')?;
+
+    Ok((key, self.parse_value()?))
+  }
+
+  /// Parses a possibly dotted key, like `a."b c".d`.
+  fn parse_key(&mut self) -> Result<Vec<String>, StreamError> {
+    let mut key = Vec::new();
+
+    loop {
+      self.skip_spaces();
+
+      let segment = match self.chars.peek() {
+        Some('"') => self.parse_basic_string()?,
+        Some('\'') => self.parse_literal_string()?,
+        _ => {
+          let mut segment = String::new();
+
+          while let Some(next) = self.chars.next_if(|next| is_bare_key_char(*next)) {
+            segment.push(next);
+          }
+
+          if segment.is_empty() {
+            return Err(StreamError::InvalidData);
+          }
+
+          segment
+        }
+      };
+
+      key.push(segment);
+
+      self.skip_spaces();
+
+      if self.chars.next_if_eq(&'.').is_none() {
+        return Ok(key);
+      }
+    }
+  }
+
+  fn parse_value(&mut self) -> Result<Chunk, StreamError> {
+    self.skip_spaces();
+
+    match self.chars.peek().copied() {
+      Some('"') => Ok(Chunk::Variant(Variant::String(self.parse_basic_string()?))),
+      Some('\'') => Ok(Chunk::Variant(Variant::String(self.parse_literal_string()?))),
+      Some('[') => {
+        self.chars.next();
+
+        let mut items = Vec::new();
+
+        loop {
+          self.skip_trivia();
+
+          if self.chars.next_if_eq(&']').is_some() {
+            return Ok(Chunk::Sequence(items));
+          }
+
+          items.push(self.parse_value()?);
+
+          self.skip_trivia();
+
+          if self.chars.next_if_eq(&',').is_none() {
+            self.expect(']')?;
+            return Ok(Chunk::Sequence(items));
+          }
+        }
+      }
+      Some('{') => {
+        self.chars.next();
+
+        let mut fields = FastHashMap::default();
+
+        self.skip_spaces();
+
+        if self.chars.next_if_eq(&'}').is_some() {
+          return Ok(Chunk::Map(fields));
+        }
+
+        loop {
+          let (key, value) = self.parse_key_value()?;
+
+          insert(&mut fields, &key, value)?;
+
+          self.skip_spaces();
+
+          if self.chars.next_if_eq(&',').is_none() {
+            self.expect('}')?;
+            return Ok(Chunk::Map(fields));
+          }
+        }
+      }
+      Some('t' | 'f') => {
+        let mut word = String::new();
+
+        while let Some(next) = self.chars.next_if(|next| next.is_ascii_alphabetic()) {
+          word.push(next);
+        }
+
+        match word.as_str() {
+          "true" => Ok(Chunk::Variant(Variant::Bool(true))),
+          "false" => Ok(Chunk::Variant(Variant::Bool(false))),
+          _ => Err(StreamError::InvalidData),
+        }
+      }
+      Some(next) if next.is_ascii_digit() || matches!(next, '-' | '+' | 'i' | 'n') => self.parse_number(),
+      _ => Err(StreamError::InvalidData),
+    }
+  }
+
+  fn parse_basic_string(&mut self) -> Result<String, StreamError> {
+    self.expect('"')?;
+
+    let mut string = String::new();
+
+    loop {
+      match self.chars.next().ok_or(StreamError::InvalidData)? {
+        '"' => return Ok(string),
+        '\n' => return Err(StreamError::InvalidData),
+        '\\' => match self.chars.next().ok_or(StreamError::InvalidData)? {
+          'n' => string.push('\n'),
+          't' => string.push('\t'),
+          'r' => string.push('\r'),
+          'b' => string.push('\u{8}'),
+          'f' => string.push('\u{c}'),
+          'u' => string.push(self.parse_unicode_escape(4)?),
+          'U' => string.push(self.parse_unicode_escape(8)?),
+          '"' => string.push('"'),
+          '\\' => string.push('\\'),
+          _ => return Err(StreamError::InvalidData),
+        },
+        other => string.push(other),
+      }
+    }
+  }
+
+  fn parse_unicode_escape(&mut self, length: usize) -> Result<char, StreamError> {
+    let digits = (0..length).filter_map(|_| self.chars.next()).collect::<String>();
+
+    u32::from_str_radix(&digits, 16)
+      .ok()
+      .and_then(char::from_u32)
+      .ok_or(StreamError::InvalidData)
+  }
+
+  fn parse_literal_string(&mut self) -> Result<String, StreamError> {
+    self.expect('\'')?;
+
+    let mut string = String::new();
+
+    loop {
+      match self.chars.next().ok_or(StreamError::InvalidData)? {
+        '\'' => return Ok(string),
+        '\n' => return Err(StreamError::InvalidData),
+        other => string.push(other),
+      }
+    }
+  }
+
+  fn parse_number(&mut self) -> Result<Chunk, StreamError> {
+    let mut number = String::new();
+
+    while let Some(next) = self
+      .chars
+      .next_if(|next| next.is_ascii_alphanumeric() || matches!(next, '.' | '+' | '-' | '_'))
+    {
+      if next != '_' {
+        number.push(next);
+      }
+    }
+
+    if let Ok(integer) = number.parse::<i64>() {
+      return Ok(Chunk::Variant(Variant::I64(integer)));
+    }
+
+    let unsigned = number.trim_start_matches(['+', '-']);
+    let float = match unsigned {
+      "inf" => f64::INFINITY,
+      "nan" => f64::NAN,
+      _ if unsigned.starts_with(|next: char| next.is_ascii_digit()) => {
+        number.parse::<f64>().map_err(|_| StreamError::InvalidData)?
+      }
+      _ => return Err(StreamError::InvalidData),
+    };
+
+    Ok(Chunk::Variant(Variant::F64(match number.starts_with('-') {
+      true => -float.abs(),
+      false => float,
+    })))
+  }
+}
+
+/// Finds the table at the given path, creating any that are missing; arrays
+/// of tables lead into their last table.
+fn table_at<'a>(
+  root: &'a mut FastHashMap<String, Chunk>,
+  path: &[String],
+) -> Result<&'a mut FastHashMap<String, Chunk>, StreamError> {
+  let Some((first, rest)) = path.split_first() else {
+    return Ok(root);
+  };
+
+  let table = match root
+    .entry(first.clone())
+    .or_insert_with(|| Chunk::Map(FastHashMap::default()))
+  {
+    Chunk::Map(table) => table,
+    Chunk::Sequence(items) => match items.last_mut() {
+      Some(Chunk::Map(table)) => table,
+      _ => return Err(StreamError::InvalidData),
+    },
+    Chunk::Variant(_) => return Err(StreamError::InvalidData),
+  };
+
+  table_at(table, rest)
+}
+
+/// Inserts a value at a dotted key, rejecting duplicates.
+fn insert(table: &mut FastHashMap<String, Chunk>, key: &[String], value: Chunk) -> Result<(), StreamError> {
+  let (last, parent) = key.split_last().ok_or(StreamError::InvalidData)?;
+  let table = table_at(table, parent)?;
+
+  match table.contains_key(last) {
+    true => Err(StreamError::InvalidData),
+    false => {
+      table.insert(last.clone(), value);
+      Ok(())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(text: &str) -> Result<Chunk, StreamError> {
+    TomlFormat.read_chunk(&mut std::io::Cursor::new(text.as_bytes()))
+  }
+
+  #[test]
+  fn it_should_read_tables_and_values() {
+    let chunk = parse(
+      r#"
+        # a project
+        title = "Demo" # trailing comment
+        physics.gravity = -9.8
+
+        [window]
+        size = [1280, 720]
+        "full screen" = false
+        options = { vsync = true, scale = 2 }
+
+        [[layers]]
+        name = 'background'
+
+        [[layers]]
+        name = "foreground\tlayer"
+        order = 1_000
+      "#,
+    )
+    .unwrap();
+
+    assert_eq!(chunk.read_field::<String>("title").unwrap(), "Demo");
+    assert_eq!(
+      chunk.get("physics").unwrap().read_field::<f64>("gravity").unwrap(),
+      -9.8
+    );
+
+    let window = chunk.get("window").unwrap();
+
+    assert_eq!(window.get("size").unwrap().read_vec2().unwrap(), Vec2::new(1280., 720.));
+    assert!(!window.read_field::<bool>("full screen").unwrap());
+    assert_eq!(window.get("options").unwrap().read_field::<i64>("scale").unwrap(), 2);
+
+    let layers = chunk.get("layers").unwrap().as_sequence().unwrap();
+
+    assert_eq!(layers.len(), 2);
+    assert_eq!(layers[0].read_field::<String>("name").unwrap(), "background");
+    assert_eq!(layers[1].read_field::<String>("name").unwrap(), "foreground\tlayer");
+    assert_eq!(layers[1].read_field::<i64>("order").unwrap(), 1000);
+  }
+
+  #[test]
+  fn it_should_reject_malformed_input() {
+    assert!(parse("title = ").is_err());
+    assert!(parse("title = \"unterminated").is_err());
+    assert!(parse("a = 1\na = 2").is_err());
+    assert!(parse("a = 1 b = 2").is_err());
+    assert!(parse("[table").is_err());
+    assert!(parse("created = 1979-05-27T07:32:00Z").is_err());
+  }
+
+  #[test]
+  fn it_should_round_trip_chunks() {
+    let mut window = FastHashMap::default();
+    let mut layer = FastHashMap::default();
+    let mut root = FastHashMap::default();
+
+    window.insert(
+      "title".to_string(),
+      Chunk::Variant(Variant::String("My \"Game\"".to_string())),
+    );
+    window.insert("scale".to_string(), Chunk::Variant(Variant::F64(1.)));
+    layer.insert("name".to_string(), Chunk::Variant(Variant::String("ui".to_string())));
+
+    root.insert("version".to_string(), Chunk::Variant(Variant::I64(3)));
+    root.insert("window".to_string(), Chunk::Map(window));
+    root.insert("layers".to_string(), Chunk::Sequence(vec![Chunk::Map(layer)]));
+    root.insert(
+      "tags".to_string(),
+      Chunk::Sequence(vec![Chunk::Variant(Variant::Bool(true))]),
+    );
+
+    let chunk = Chunk::Map(root);
+    let mut output = std::io::Cursor::new(Vec::new());
+
+    TomlFormat.write_chunk(&mut output, &chunk).unwrap();
+
+    let text = String::from_utf8(output.into_inner()).unwrap();
+
+    assert!(text.starts_with("tags = [true]\nversion = 3\n"));
+    assert!(text.contains("\n[[layers]]\n"));
+    assert_eq!(parse(&text).unwrap(), chunk);
+  }
+}
@@ -0,0 +1,218 @@
+//! Content hashing for content-addressable data.
+//!
+//! Two algorithms are provided: [`HashAlgorithm::XxHash3`] is a very fast
+//! non-cryptographic hash suitable for change detection and caching, while
+//! [`HashAlgorithm::Blake3`] is a cryptographic hash suitable for verifying the
+//! integrity of shipped content.
+
+use std::{
+  fmt::{Display, Formatter},
+  io::{Read, Write},
+};
+
+use crate::{FileSystemError, InputStream, OutputStream, StreamError, ToVirtualPath};
+
+/// A hashing algorithm for content-addressable data.
+#[repr(u8)]
+#[derive(Default, Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum HashAlgorithm {
+  /// The 128-bit variant of XXH3; fast, but not cryptographically secure.
+  #[default]
+  XxHash3 = 0,
+  /// BLAKE3; slower, but cryptographically secure.
+  Blake3 = 1,
+}
+
+impl HashAlgorithm {
+  /// Converts a persisted byte back into an algorithm, if it's recognised.
+  pub fn from_byte(value: u8) -> Option<Self> {
+    match value {
+      0 => Some(Self::XxHash3),
+      1 => Some(Self::Blake3),
+      _ => None,
+    }
+  }
+}
+
+/// A hash of some content, tagged with the algorithm that produced it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ContentHash {
+  XxHash3([u8; 16]),
+  Blake3([u8; 32]),
+}
+
+impl ContentHash {
+  /// Computes the hash of the given data.
+  pub fn of(algorithm: HashAlgorithm, data: &[u8]) -> Self {
+    let mut hasher = ContentHasher::new(algorithm);
+
+    hasher.update(data);
+    hasher.finish()
+  }
+
+  /// Computes the hash of all data in the given reader.
+  pub fn of_reader(algorithm: HashAlgorithm, reader: &mut dyn Read) -> Result<Self, std::io::Error> {
+    let mut hasher = ContentHasher::new(algorithm);
+
+    std::io::copy(reader, &mut hasher)?;
+
+    Ok(hasher.finish())
+  }
+
+  /// Computes the hash of the file at the given path.
+  pub fn of_path(algorithm: HashAlgorithm, path: impl ToVirtualPath) -> Result<Self, FileSystemError> {
+    let path = path.to_virtual_path();
+    let mut stream = path.open_input_stream()?;
+
+    Ok(Self::of_reader(algorithm, &mut stream)?)
+  }
+
+  /// The algorithm that produced this hash.
+  pub fn algorithm(&self) -> HashAlgorithm {
+    match self {
+      Self::XxHash3(_) => HashAlgorithm::XxHash3,
+      Self::Blake3(_) => HashAlgorithm::Blake3,
+    }
+  }
+
+  /// The raw bytes of this hash.
+  pub fn as_bytes(&self) -> &[u8] {
+    match self {
+      Self::XxHash3(bytes) => bytes,
+      Self::Blake3(bytes) => bytes,
+    }
+  }
+
+  /// Converts the hash into a lower-case hexadecimal string.
+  pub fn to_hex(&self) -> String {
+    self.as_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+  }
+
+  /// Reads a hash from the given stream.
+  pub fn read_from(stream: &mut dyn InputStream) -> Result<Self, StreamError> {
+    match HashAlgorithm::from_byte(stream.read_u8()?) {
+      Some(HashAlgorithm::XxHash3) => {
+        let mut bytes = [0; 16];
+        stream.read_exact(&mut bytes)?;
+        Ok(Self::XxHash3(bytes))
+      }
+      Some(HashAlgorithm::Blake3) => {
+        let mut bytes = [0; 32];
+        stream.read_exact(&mut bytes)?;
+        Ok(Self::Blake3(bytes))
+      }
+      None => Err(StreamError::InvalidData),
+    }
+  }
+
+  /// Writes the hash to the given stream.
+  pub fn write_to(&self, stream: &mut dyn OutputStream) -> Result<(), StreamError> {
+    stream.write_u8(self.algorithm() as u8)?;
+    stream.write_bytes(self.as_bytes())
+  }
+}
+
+impl Display for ContentHash {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(formatter, "{}", self.to_hex())
+  }
+}
+
+/// An incremental hasher for [`ContentHash`]es.
+///
+/// The hasher implements [`Write`], so content can be streamed into it without
+/// buffering the whole thing in memory.
+pub enum ContentHasher {
+  XxHash3(Box<xxhash_rust::xxh3::Xxh3>),
+  Blake3(Box<blake3::Hasher>),
+}
+
+impl ContentHasher {
+  /// Creates a new hasher for the given algorithm.
+  pub fn new(algorithm: HashAlgorithm) -> Self {
+    match algorithm {
+      HashAlgorithm::XxHash3 => Self::XxHash3(Box::default()),
+      HashAlgorithm::Blake3 => Self::Blake3(Box::default()),
+    }
+  }
+
+  /// Feeds the given data into the hasher.
+  pub fn update(&mut self, data: &[u8]) {
+    match self {
+      Self::XxHash3(hasher) => hasher.update(data),
+      Self::Blake3(hasher) => {
+        hasher.update(data);
+      }
+    }
+  }
+
+  /// Finalizes the hasher, producing a [`ContentHash`].
+  pub fn finish(&self) -> ContentHash {
+    match self {
+      Self::XxHash3(hasher) => ContentHash::XxHash3(hasher.digest128().to_le_bytes()),
+      Self::Blake3(hasher) => ContentHash::Blake3(*hasher.finalize().as_bytes()),
+    }
+  }
+}
+
+impl Write for ContentHasher {
+  #[inline]
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.update(buf);
+
+    Ok(buf.len())
+  }
+
+  #[inline]
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_produce_stable_hashes() {
+    let hash1 = ContentHash::of(HashAlgorithm::XxHash3, b"Hello, world!");
+    let hash2 = ContentHash::of(HashAlgorithm::XxHash3, b"Hello, world!");
+    let hash3 = ContentHash::of(HashAlgorithm::XxHash3, b"Hello, world?");
+
+    assert_eq!(hash1, hash2);
+    assert_ne!(hash1, hash3);
+  }
+
+  #[test]
+  fn it_should_hash_incrementally() {
+    for algorithm in [HashAlgorithm::XxHash3, HashAlgorithm::Blake3] {
+      let mut hasher = ContentHasher::new(algorithm);
+
+      hasher.update(b"Hello, ");
+      hasher.update(b"world!");
+
+      assert_eq!(hasher.finish(), ContentHash::of(algorithm, b"Hello, world!"));
+    }
+  }
+
+  #[test]
+  fn it_should_match_known_blake3_digest() {
+    let hash = ContentHash::of(HashAlgorithm::Blake3, b"");
+
+    assert_eq!(
+      hash.to_hex(),
+      "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+    );
+  }
+
+  #[test]
+  fn it_should_read_and_write_hashes_to_streams() {
+    let hash = ContentHash::of(HashAlgorithm::Blake3, b"Hello, world!");
+    let mut cursor = std::io::Cursor::new(Vec::new());
+
+    hash.write_to(&mut cursor).unwrap();
+    cursor.set_position(0);
+
+    assert_eq!(ContentHash::read_from(&mut cursor).unwrap(), hash);
+  }
+}
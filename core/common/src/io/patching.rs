@@ -0,0 +1,385 @@
+//! Binary patching (delta encoding) between two versions of some content.
+//!
+//! Patches are computed rsync-style: the source is split into fixed-size
+//! blocks which are indexed by a cheap rolling checksum, and the target is then
+//! scanned for runs of those blocks at any offset. Matching runs become copy
+//! operations and everything else is inserted literally, so small edits to
+//! large files result in small patches.
+
+use crate::{ContentHash, FastHashMap, FromStream, HashAlgorithm, InputStream, OutputStream, StreamError, ToStream};
+
+/// The magic number at the start of a serialized [`BinaryPatch`].
+const PATCH_MAGIC: u32 = 0x54415053; // 'SPAT'
+
+/// The current version of the serialized [`BinaryPatch`] format.
+const PATCH_VERSION: u16 = 1;
+
+/// The default block size used when computing patches.
+const DEFAULT_BLOCK_SIZE: usize = 512;
+
+/// The most operations reserved up front when reading a patch, so a malformed
+/// count can't allocate unbounded memory before the stream runs out.
+const MAX_PREALLOCATED_OPERATIONS: usize = 1024;
+
+/// The most bytes reserved up front when applying a patch; the target grows
+/// past this as operations are applied, and is verified against its hash.
+const MAX_PREALLOCATED_TARGET_SIZE: usize = 16 * 1024 * 1024;
+
+/// An error that can occur when applying a [`BinaryPatch`].
+#[derive(Debug, Eq, PartialEq)]
+pub enum PatchError {
+  /// The data the patch was applied to isn't what the patch was computed from.
+  SourceMismatch,
+  /// The result of applying the patch doesn't match the expected target.
+  TargetMismatch,
+  /// The patch refers to data outside the bounds of the source.
+  OutOfBounds,
+}
+
+/// A single operation in a [`BinaryPatch`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PatchOp {
+  /// Copies a range of bytes from the source.
+  Copy { offset: u64, length: u64 },
+  /// Inserts the given bytes literally.
+  Insert(Vec<u8>),
+}
+
+/// A binary patch that transforms some source data into some target data.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BinaryPatch {
+  source_hash: ContentHash,
+  target_hash: ContentHash,
+  target_size: u64,
+  operations: Vec<PatchOp>,
+}
+
+impl BinaryPatch {
+  /// Computes a patch from the given source data to the given target data.
+  pub fn compute(source: &[u8], target: &[u8]) -> Self {
+    Self::compute_with_block_size(source, target, DEFAULT_BLOCK_SIZE)
+  }
+
+  /// Computes a patch using the given block size.
+  ///
+  /// Smaller blocks find more matches at the cost of a slower diff.
+  pub fn compute_with_block_size(source: &[u8], target: &[u8], block_size: usize) -> Self {
+    let block_size = block_size.max(1);
+    let mut builder = PatchBuilder::default();
+
+    // index all full blocks in the source by their weak checksum
+    let mut blocks = FastHashMap::<u32, Vec<usize>>::default();
+
+    for (index, block) in source.chunks_exact(block_size).enumerate() {
+      blocks
+        .entry(RollingChecksum::new(block).value())
+        .or_default()
+        .push(index * block_size);
+    }
+
+    // scan the target for matching blocks at any offset
+    let mut position = 0;
+    let mut checksum = None;
+
+    while position + block_size <= target.len() {
+      let window = &target[position..position + block_size];
+      let weak = checksum.get_or_insert_with(|| RollingChecksum::new(window));
+
+      let matched = blocks.get(&weak.value()).and_then(|offsets| {
+        offsets
+          .iter()
+          .find(|&&offset| &source[offset..offset + block_size] == window)
+      });
+
+      if let Some(&offset) = matched {
+        builder.copy(offset as u64, block_size as u64);
+        position += block_size;
+        checksum = None;
+      } else {
+        builder.insert(target[position]);
+
+        if position + block_size < target.len() {
+          weak.roll(target[position], target[position + block_size]);
+        }
+
+        position += 1;
+      }
+    }
+
+    for &byte in &target[position..] {
+      builder.insert(byte);
+    }
+
+    Self {
+      source_hash: ContentHash::of(HashAlgorithm::XxHash3, source),
+      target_hash: ContentHash::of(HashAlgorithm::XxHash3, target),
+      target_size: target.len() as u64,
+      operations: builder.finish(),
+    }
+  }
+
+  /// The operations in this patch.
+  pub fn operations(&self) -> &[PatchOp] {
+    &self.operations
+  }
+
+  /// The number of literal bytes carried by this patch.
+  pub fn literal_size(&self) -> usize {
+    self
+      .operations
+      .iter()
+      .map(|operation| match operation {
+        PatchOp::Insert(bytes) => bytes.len(),
+        PatchOp::Copy { .. } => 0,
+      })
+      .sum()
+  }
+
+  /// Determines if this patch was computed from the given source data.
+  pub fn can_apply_to(&self, source: &[u8]) -> bool {
+    ContentHash::of(self.source_hash.algorithm(), source) == self.source_hash
+  }
+
+  /// Applies the patch to the given source data, producing the target data.
+  pub fn apply(&self, source: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if !self.can_apply_to(source) {
+      return Err(PatchError::SourceMismatch);
+    }
+
+    let mut target = Vec::with_capacity((self.target_size as usize).min(MAX_PREALLOCATED_TARGET_SIZE));
+
+    for operation in &self.operations {
+      match operation {
+        PatchOp::Copy { offset, length } => {
+          let start = *offset as usize;
+          let end = start.checked_add(*length as usize).ok_or(PatchError::OutOfBounds)?;
+
+          target.extend_from_slice(source.get(start..end).ok_or(PatchError::OutOfBounds)?);
+        }
+        PatchOp::Insert(bytes) => target.extend_from_slice(bytes),
+      }
+    }
+
+    if ContentHash::of(self.target_hash.algorithm(), &target) != self.target_hash {
+      return Err(PatchError::TargetMismatch);
+    }
+
+    Ok(target)
+  }
+}
+
+impl FromStream for BinaryPatch {
+  async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+    if stream.read_u32()? != PATCH_MAGIC || stream.read_u16()? != PATCH_VERSION {
+      return Err(StreamError::InvalidData);
+    }
+
+    let source_hash = ContentHash::read_from(stream)?;
+    let target_hash = ContentHash::read_from(stream)?;
+    let target_size = stream.read_u64()?;
+    let count = stream.read_u32()?;
+    let mut operations = Vec::with_capacity((count as usize).min(MAX_PREALLOCATED_OPERATIONS));
+
+    for _ in 0..count {
+      operations.push(match stream.read_u8()? {
+        0 => PatchOp::Copy {
+          offset: stream.read_u64()?,
+          length: stream.read_u64()?,
+        },
+        1 => {
+          let length = stream.read_u32()? as usize;
+
+          PatchOp::Insert(stream.read_bytes(length)?)
+        }
+        _ => return Err(StreamError::InvalidData),
+      });
+    }
+
+    Ok(Self {
+      source_hash,
+      target_hash,
+      target_size,
+      operations,
+    })
+  }
+}
+
+impl ToStream for BinaryPatch {
+  fn to_stream(&self, stream: &mut dyn OutputStream) -> Result<(), Self::Error> {
+    stream.write_u32(PATCH_MAGIC)?;
+    stream.write_u16(PATCH_VERSION)?;
+
+    self.source_hash.write_to(stream)?;
+    self.target_hash.write_to(stream)?;
+
+    stream.write_u64(self.target_size)?;
+    stream.write_u32(self.operations.len() as u32)?;
+
+    for operation in &self.operations {
+      match operation {
+        PatchOp::Copy { offset, length } => {
+          stream.write_u8(0)?;
+          stream.write_u64(*offset)?;
+          stream.write_u64(*length)?;
+        }
+        PatchOp::Insert(bytes) => {
+          stream.write_u8(1)?;
+          stream.write_u32(bytes.len() as u32)?;
+          stream.write_bytes(bytes)?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// Accumulates [`PatchOp`]s, merging adjacent operations where possible.
+#[derive(Default)]
+struct PatchBuilder {
+  operations: Vec<PatchOp>,
+}
+
+impl PatchBuilder {
+  /// Adds a copy operation, extending the previous copy if it's contiguous.
+  fn copy(&mut self, offset: u64, length: u64) {
+    if let Some(PatchOp::Copy {
+      offset: last_offset,
+      length: last_length,
+    }) = self.operations.last_mut()
+    {
+      if *last_offset + *last_length == offset {
+        *last_length += length;
+        return;
+      }
+    }
+
+    self.operations.push(PatchOp::Copy { offset, length });
+  }
+
+  /// Adds a single literal byte, extending the previous insert if possible.
+  fn insert(&mut self, byte: u8) {
+    if let Some(PatchOp::Insert(bytes)) = self.operations.last_mut() {
+      bytes.push(byte);
+    } else {
+      self.operations.push(PatchOp::Insert(vec![byte]));
+    }
+  }
+
+  fn finish(self) -> Vec<PatchOp> {
+    self.operations
+  }
+}
+
+/// An rsync-style rolling checksum over a fixed-size window.
+struct RollingChecksum {
+  a: u32,
+  b: u32,
+  length: u32,
+}
+
+impl RollingChecksum {
+  /// Computes the checksum of the given window.
+  fn new(window: &[u8]) -> Self {
+    let length = window.len() as u32;
+    let mut a = 0u32;
+    let mut b = 0u32;
+
+    for (index, &byte) in window.iter().enumerate() {
+      a = a.wrapping_add(byte as u32);
+      b = b.wrapping_add((length - index as u32).wrapping_mul(byte as u32));
+    }
+
+    Self {
+      a: a & 0xFFFF,
+      b: b & 0xFFFF,
+      length,
+    }
+  }
+
+  /// Rolls the window forward by one byte.
+  fn roll(&mut self, removed: u8, added: u8) {
+    self.a = self.a.wrapping_sub(removed as u32).wrapping_add(added as u32) & 0xFFFF;
+    self.b = self
+      .b
+      .wrapping_sub(self.length.wrapping_mul(removed as u32))
+      .wrapping_add(self.a)
+      & 0xFFFF;
+  }
+
+  /// The current value of the checksum.
+  fn value(&self) -> u32 {
+    self.a | (self.b << 16)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rolling_checksum_should_match_fresh_checksum() {
+    let data = b"The quick brown fox jumps over the lazy dog";
+    let mut rolling = RollingChecksum::new(&data[0..8]);
+
+    for start in 1..data.len() - 8 {
+      rolling.roll(data[start - 1], data[start + 7]);
+
+      assert_eq!(rolling.value(), RollingChecksum::new(&data[start..start + 8]).value());
+    }
+  }
+
+  #[test]
+  fn it_should_patch_small_edits_efficiently() {
+    let source = (0..16_384).map(|index| (index * 7 % 251) as u8).collect::<Vec<_>>();
+    let mut target = source.clone();
+
+    target.splice(1000..1000, b"inserted".iter().copied());
+    target[9000] ^= 0xFF;
+
+    let patch = BinaryPatch::compute(&source, &target);
+
+    assert!(patch.literal_size() < 2048);
+    assert_eq!(patch.apply(&source).unwrap(), target);
+  }
+
+  #[test]
+  fn it_should_patch_unrelated_content() {
+    let patch = BinaryPatch::compute_with_block_size(b"Hello, world!", b"Goodbye, world!", 4);
+
+    assert_eq!(patch.apply(b"Hello, world!").unwrap(), b"Goodbye, world!");
+  }
+
+  #[test]
+  fn it_should_reject_the_wrong_source() {
+    let patch = BinaryPatch::compute(b"Hello, world!", b"Goodbye, world!");
+
+    assert_eq!(patch.apply(b"Hello, earth!"), Err(PatchError::SourceMismatch));
+  }
+
+  #[test]
+  fn it_should_round_trip_through_streams() {
+    let patch = BinaryPatch::compute_with_block_size(b"AAAABBBBCCCCDDDD", b"AAAAXXXXCCCCDDDDEE", 4);
+
+    let bytes = patch.to_bytes().unwrap();
+    let result = BinaryPatch::from_bytes(&bytes).unwrap();
+
+    assert_eq!(patch, result);
+  }
+
+  #[test]
+  fn it_should_not_trust_sizes_in_malformed_patches() {
+    let mut patch = BinaryPatch::compute(b"", b"");
+    let mut bytes = patch.to_bytes().unwrap();
+
+    // claim far more operations than the stream holds
+    let length = bytes.len();
+    bytes[length - 4..].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    assert!(BinaryPatch::from_bytes(&bytes).is_err());
+
+    patch.target_size = u64::MAX;
+
+    assert_eq!(patch.apply(b"").unwrap(), b"");
+  }
+}
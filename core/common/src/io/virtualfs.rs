@@ -24,6 +24,8 @@ pub trait FileSystem: Send + Sync {
   fn is_directory(&self, path: &VirtualPath) -> bool;
   fn files(&self, path: &VirtualPath) -> Vec<VirtualPath>;
   fn directories(&self, path: &VirtualPath) -> Vec<VirtualPath>;
+  fn rename(&self, from: &VirtualPath, to: &VirtualPath) -> Result<(), FileSystemError>;
+  fn delete(&self, path: &VirtualPath) -> Result<(), FileSystemError>;
 
   // read and write
   fn open_read(&self, path: &VirtualPath) -> Result<Box<dyn InputStream>, FileSystemError>;
@@ -199,6 +201,24 @@ impl VirtualPath {
   pub fn directories(&self) -> Vec<VirtualPath> {
     FileSystemManager::with_filesystem(self, |file_system| file_system.directories(self))
   }
+
+  /// Renames (moves) the file at this path to the given path.
+  ///
+  /// Where the underlying file system supports it, the rename is atomic, which
+  /// makes it suitable for swapping staged files into place. Both paths must
+  /// belong to the same file system.
+  pub fn rename_to(&self, target: &VirtualPath) -> Result<(), FileSystemError> {
+    if self.scheme != target.scheme {
+      return Err(FileSystemError::NotSupported);
+    }
+
+    FileSystemManager::with_filesystem(self, |file_system| file_system.rename(self, target))
+  }
+
+  /// Deletes the file at this path.
+  pub fn delete(&self) -> Result<(), FileSystemError> {
+    FileSystemManager::with_filesystem(self, |file_system| file_system.delete(self))
+  }
 }
 
 impl std::fmt::Debug for VirtualPath {
@@ -244,6 +264,7 @@ impl ToVirtualPath for &VirtualPath {
 #[derive(Debug)]
 pub enum FileSystemError {
   NotFound,
+  NotSupported,
   IoError(std::io::Error),
   StreamError(super::StreamError),
 }
@@ -55,6 +55,18 @@ impl FileSystem for LocalFileSystem {
     results
   }
 
+  fn rename(&self, from: &VirtualPath, to: &VirtualPath) -> Result<(), FileSystemError> {
+    std::fs::rename(to_path(from), to_path(to))?;
+
+    Ok(())
+  }
+
+  fn delete(&self, path: &VirtualPath) -> Result<(), FileSystemError> {
+    std::fs::remove_file(to_path(path))?;
+
+    Ok(())
+  }
+
   fn open_read(&self, path: &VirtualPath) -> Result<Box<dyn InputStream>, FileSystemError> {
     let file = OpenOptions::new()
       .read(true)
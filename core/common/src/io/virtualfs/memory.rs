@@ -38,6 +38,23 @@ impl FileSystem for MemoryFileSystem {
     todo!()
   }
 
+  fn rename(&self, from: &VirtualPath, to: &VirtualPath) -> Result<(), FileSystemError> {
+    let mut files = self.files.write().unwrap();
+    let file = files.remove(&from.location).ok_or(FileSystemError::NotFound)?;
+
+    files.insert(to.location.clone(), file);
+
+    Ok(())
+  }
+
+  fn delete(&self, path: &VirtualPath) -> Result<(), FileSystemError> {
+    let mut files = self.files.write().unwrap();
+
+    files.remove(&path.location).ok_or(FileSystemError::NotFound)?;
+
+    Ok(())
+  }
+
   fn open_read(&self, _path: &VirtualPath) -> Result<Box<dyn InputStream>, FileSystemError> {
     todo!()
   }
@@ -11,18 +11,25 @@
 #![feature(async_closure)]
 
 pub use abstractions::*;
+pub use ai::*;
 pub use collections::*;
+pub use combat::*;
 pub use concurrency::*;
 pub use diagnostics::*;
 pub use io::*;
 pub use maths::*;
 pub use memory::*;
 pub use network::*;
+pub use procgen::*;
+pub use spawning::*;
 pub use strings::*;
 pub use utilities::*;
+pub use wind::*;
 
 mod abstractions;
+mod ai;
 mod collections;
+mod combat;
 mod concurrency;
 mod diagnostics;
 mod io;
@@ -30,8 +37,11 @@ pub mod lua;
 mod maths;
 mod memory;
 mod network;
+mod procgen;
+mod spawning;
 mod strings;
 mod utilities;
+mod wind;
 
 pub use macros::{profiling, Singleton};
 
@@ -10,13 +10,17 @@ pub use hex::*;
 pub use lerp::*;
 pub use linear::*;
 pub use neighbours::*;
+pub use noise::*;
+pub use palettes::*;
 pub use paths::*;
 pub use random::*;
 pub use ranges::*;
 pub use rectangles::*;
 pub use shapes::*;
 pub use size::*;
+pub use snapping::*;
 pub use splines::*;
+pub use springs::*;
 pub use time::*;
 pub use weights::*;
 
@@ -30,13 +34,17 @@ mod hex;
 mod lerp;
 mod linear;
 mod neighbours;
+mod noise;
+mod palettes;
 mod paths;
 mod random;
 mod ranges;
 mod rectangles;
 mod shapes;
 mod size;
+mod snapping;
 mod splines;
+mod springs;
 mod time;
 mod weights;
 
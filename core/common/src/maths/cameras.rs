@@ -1,6 +1,15 @@
 //! Camera types and utilities.
 
+pub use debug::*;
+pub use effects::*;
+pub use photo::*;
+
 use super::*;
+use crate::LayerMask;
+
+mod debug;
+mod effects;
+mod photo;
 
 /// Represents a camera.
 pub trait Camera {
@@ -24,6 +33,57 @@ pub trait Camera {
   fn frustum(&self) -> Frustum {
     Frustum::from_projection_view(self.projection() * self.view())
   }
+
+  /// The layers this camera draws; see [`LayerMask`].
+  #[inline]
+  fn culling_mask(&self) -> LayerMask {
+    LayerMask::ALL
+  }
+
+  /// Projects a point in the world onto a viewport of the given size, in
+  /// pixels from its top-left corner.
+  ///
+  /// Returns `None` for points behind the camera.
+  fn world_to_screen(&self, point: Vec3, viewport: Vec2) -> Option<Vec2> {
+    let clip = self.projection_view() * point.extend(1.);
+
+    if clip.w <= 0. {
+      return None;
+    }
+
+    let ndc = clip.truncate() / clip.w;
+
+    Some(vec2((ndc.x + 1.) / 2. * viewport.x, (1. - ndc.y) / 2. * viewport.y))
+  }
+
+  /// Casts a ray from the camera through a point on a viewport of the given
+  /// size, in pixels from its top-left corner.
+  ///
+  /// The ray starts on the near plane and its direction spans the depth of
+  /// the view, so it reaches the far plane at a distance of one.
+  fn screen_to_ray(&self, screen: Vec2, viewport: Vec2) -> Ray3 {
+    let inverse = self.projection_view().inverse();
+    let ndc = vec2(screen.x / viewport.x * 2. - 1., 1. - screen.y / viewport.y * 2.);
+
+    let near = inverse.project_point3(ndc.extend(-1.));
+    let far = inverse.project_point3(ndc.extend(1.));
+
+    Ray3::new(near, far - near)
+  }
+
+  /// Finds the point on the `z = 0` plane under a point on a viewport of the
+  /// given size, for picking in 2D scenes.
+  ///
+  /// Falls back to the near plane if the camera looks along the plane.
+  fn screen_to_world_2d(&self, screen: Vec2, viewport: Vec2) -> Vec2 {
+    let ray = self.screen_to_ray(screen, viewport);
+
+    if ray.direction.z.abs() <= f32::EPSILON {
+      return ray.origin.truncate();
+    }
+
+    ray.point_at(-ray.origin.z / ray.direction.z).truncate()
+  }
 }
 
 /// An orthographic camera.
@@ -35,6 +95,7 @@ pub struct OrthographicCamera {
   pub near_plane: f32,
   pub far_plane: f32,
   pub ortho_size: f32,
+  pub culling_mask: LayerMask,
 }
 
 impl Default for OrthographicCamera {
@@ -46,6 +107,7 @@ impl Default for OrthographicCamera {
       near_plane: 0.1,
       far_plane: 100.0,
       ortho_size: 4.5,
+      culling_mask: LayerMask::ALL,
     }
   }
 }
@@ -69,6 +131,10 @@ impl Camera for OrthographicCamera {
   fn view(&self) -> Mat4 {
     Mat4::look_at_rh(self.position, self.look_at, self.up)
   }
+
+  fn culling_mask(&self) -> LayerMask {
+    self.culling_mask
+  }
 }
 
 /// A perspective camera.
@@ -81,6 +147,7 @@ pub struct PerspectiveCamera {
   pub far_plane: f32,
   pub fov: f32,
   pub aspect_ratio: f32,
+  pub culling_mask: LayerMask,
 }
 
 impl Default for PerspectiveCamera {
@@ -93,6 +160,7 @@ impl Default for PerspectiveCamera {
       far_plane: 100.0,
       fov: 60.0,
       aspect_ratio: 1.0,
+      culling_mask: LayerMask::ALL,
     }
   }
 }
@@ -101,7 +169,7 @@ impl Camera for PerspectiveCamera {
   fn position(&self) -> Vec3 {
     self.position
   }
-  
+
   fn projection(&self) -> Mat4 {
     Mat4::perspective_lh(self.fov, self.aspect_ratio, self.near_plane, self.far_plane)
   }
@@ -109,4 +177,52 @@ impl Camera for PerspectiveCamera {
   fn view(&self) -> Mat4 {
     Mat4::look_at_lh(self.position, self.look_at, self.up)
   }
+
+  fn culling_mask(&self) -> LayerMask {
+    self.culling_mask
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const VIEWPORT: Vec2 = Vec2::new(800., 600.);
+
+  #[test]
+  fn it_should_round_trip_points_through_the_screen() {
+    let camera = PerspectiveCamera {
+      position: vec3(0., 2., 10.),
+      look_at: Vec3::ZERO,
+      fov: std::f32::consts::FRAC_PI_3,
+      aspect_ratio: VIEWPORT.x / VIEWPORT.y,
+      ..Default::default()
+    };
+
+    let point = vec3(1., 0.5, -2.);
+    let screen = camera.world_to_screen(point, VIEWPORT).unwrap();
+    let ray = camera.screen_to_ray(screen, VIEWPORT);
+
+    assert!(ray.direction.normalize().dot((point - ray.origin).normalize()) > 0.9999);
+    assert!(camera.world_to_screen(vec3(0., 2., 20.), VIEWPORT).is_none());
+
+    let center = camera.world_to_screen(Vec3::ZERO, VIEWPORT).unwrap();
+
+    assert!(center.distance(VIEWPORT / 2.) < 0.01);
+  }
+
+  #[test]
+  fn it_should_find_points_on_the_2d_plane() {
+    let camera = OrthographicCamera {
+      position: vec3(0., 0., -1.),
+      ortho_size: 10.,
+      ..Default::default()
+    };
+
+    for point in [vec2(0., 0.), vec2(2., -3.), vec2(-4., 4.5)] {
+      let screen = camera.world_to_screen(point.extend(0.), VIEWPORT).unwrap();
+
+      assert!(camera.screen_to_world_2d(screen, VIEWPORT).distance(point) < 0.001);
+    }
+  }
 }
@@ -0,0 +1,446 @@
+use super::*;
+
+/// How a [`DebugCamera`] moves.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum DebugCameraMode {
+  /// Orbits around a focus point; look to rotate, pan to move the focus and
+  /// zoom to move closer or further away.
+  #[default]
+  Orbit,
+  /// Flies freely; move with WASD, look with the mouse, zoom to dolly.
+  Fly,
+  /// Looks down on the `z = 0` plane; pan to move and zoom to scale.
+  Pan2D,
+}
+
+impl DebugCameraMode {
+  /// The mode after this one, for cycling with a single key.
+  pub fn next(&self) -> Self {
+    match self {
+      Self::Orbit => Self::Fly,
+      Self::Fly => Self::Pan2D,
+      Self::Pan2D => Self::Orbit,
+    }
+  }
+}
+
+/// Input for a [`DebugCamera`] over a frame, however it was gathered.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DebugCameraInput {
+  /// Movement along the camera's right, up and forward axes, each from -1
+  /// to 1.
+  pub movement: Vec3,
+  /// How far the mouse moved while looking, in pixels.
+  pub look: Vec2,
+  /// How far the mouse moved while panning, in pixels.
+  pub pan: Vec2,
+  /// Steps to zoom in by; negative steps zoom out.
+  pub zoom: f32,
+  /// Move faster while held.
+  pub fast: bool,
+}
+
+/// Settings for a [`DebugCamera`].
+#[derive(Clone, Debug)]
+pub struct DebugCameraSettings {
+  /// How fast the camera flies, in units per second.
+  pub move_speed: f32,
+  /// How much faster the camera moves while fast is held.
+  pub fast_multiplier: f32,
+  /// How far the camera turns per pixel of mouse movement, in radians.
+  pub look_sensitivity: f32,
+  /// How far the camera pans per pixel of mouse movement, relative to the
+  /// distance or size of the view.
+  pub pan_sensitivity: f32,
+  /// How much each step of zoom scales the view.
+  pub zoom_factor: f32,
+  pub fov: f32,
+  pub aspect_ratio: f32,
+  pub near_plane: f32,
+  pub far_plane: f32,
+}
+
+impl Default for DebugCameraSettings {
+  fn default() -> Self {
+    Self {
+      move_speed: 5.,
+      fast_multiplier: 4.,
+      look_sensitivity: 0.005,
+      pan_sensitivity: 0.002,
+      zoom_factor: 1.1,
+      fov: std::f32::consts::FRAC_PI_3,
+      aspect_ratio: 16. / 9.,
+      near_plane: 0.1,
+      far_plane: 1000.,
+    }
+  }
+}
+
+/// The limit on pitch, short of straight up or down so the view stays
+/// upright.
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+/// A camera for inspecting any scene, independent of the game's own cameras.
+///
+/// Toggle it on and draw through [`DebugCamera::active`] to look around
+/// without disturbing the game's cameras, then toggle it off to go back:
+///
+/// ```rust,ignore
+/// let mut debug_camera = DebugCamera::new(DebugCameraSettings::default());
+///
+/// debug_camera.update(&controls.take_input(), time.delta_time);
+///
+/// renderer.render(debug_camera.active(&game_camera));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DebugCamera {
+  pub settings: DebugCameraSettings,
+  mode: DebugCameraMode,
+  enabled: bool,
+  focus: Vec3,
+  distance: f32,
+  position: Vec3,
+  yaw: f32,
+  pitch: f32,
+  center: Vec2,
+  ortho_size: f32,
+}
+
+impl DebugCamera {
+  /// Creates a disabled camera, orbiting the origin.
+  pub fn new(settings: DebugCameraSettings) -> Self {
+    let mut camera = Self {
+      settings,
+      mode: DebugCameraMode::Orbit,
+      enabled: false,
+      focus: Vec3::ZERO,
+      distance: 10.,
+      position: Vec3::ZERO,
+      yaw: 0.,
+      pitch: 0.3,
+      center: Vec2::ZERO,
+      ortho_size: 10.,
+    };
+
+    camera.position = camera.orbit_position();
+    camera
+  }
+
+  /// Determines if the camera is in use.
+  pub fn is_enabled(&self) -> bool {
+    self.enabled
+  }
+
+  /// Starts or stops using the camera.
+  pub fn set_enabled(&mut self, enabled: bool) {
+    self.enabled = enabled;
+  }
+
+  /// Starts using the camera if it's not in use, and stops if it is.
+  pub fn toggle(&mut self) {
+    self.enabled = !self.enabled;
+  }
+
+  /// How the camera moves.
+  pub fn mode(&self) -> DebugCameraMode {
+    self.mode
+  }
+
+  /// Changes how the camera moves, keeping the view where it is when
+  /// switching between orbiting and flying.
+  pub fn set_mode(&mut self, mode: DebugCameraMode) {
+    match (self.mode, mode) {
+      (DebugCameraMode::Orbit, DebugCameraMode::Fly) => self.position = self.orbit_position(),
+      (DebugCameraMode::Fly, DebugCameraMode::Orbit) => self.focus = self.position + self.forward() * self.distance,
+      _ => {}
+    }
+
+    self.mode = mode;
+  }
+
+  /// Moves on to the next mode.
+  pub fn cycle_mode(&mut self) {
+    self.set_mode(self.mode.next());
+  }
+
+  /// Centers the view on a point, from the given distance.
+  pub fn focus_on(&mut self, point: Vec3, distance: f32) {
+    self.focus = point;
+    self.distance = distance.max(self.settings.near_plane);
+    self.position = self.orbit_position();
+    self.center = point.truncate();
+  }
+
+  /// Places the camera at a position, looking at a point; handy for starting
+  /// from where the game's camera is.
+  pub fn look_from(&mut self, position: Vec3, look_at: Vec3) {
+    let direction = (look_at - position).normalize_or(Vec3::Z);
+
+    self.position = position;
+    self.distance = position.distance(look_at).max(self.settings.near_plane);
+    self.focus = look_at;
+    self.yaw = direction.x.atan2(direction.z);
+    self.pitch = (-direction.y).asin().clamp(-MAX_PITCH, MAX_PITCH);
+    self.center = look_at.truncate();
+  }
+
+  /// Moves the camera by a frame of input; does nothing while disabled.
+  pub fn update(&mut self, input: &DebugCameraInput, delta_time: f32) {
+    if !self.enabled {
+      return;
+    }
+
+    let settings = &self.settings;
+    let speed = match input.fast {
+      true => settings.move_speed * settings.fast_multiplier,
+      false => settings.move_speed,
+    };
+
+    let zoom = settings.zoom_factor.powf(input.zoom);
+
+    match self.mode {
+      DebugCameraMode::Orbit => {
+        self.turn(input.look);
+
+        let (right, up, forward) = self.axes();
+        let pan = (right * -input.pan.x + up * input.pan.y) * self.distance * self.settings.pan_sensitivity;
+        let movement = right * input.movement.x + up * input.movement.y + forward * input.movement.z;
+
+        self.focus += pan + movement * speed * delta_time;
+        self.distance = (self.distance / zoom).max(self.settings.near_plane);
+        self.position = self.orbit_position();
+      }
+      DebugCameraMode::Fly => {
+        self.turn(input.look);
+
+        let (right, up, forward) = self.axes();
+        let movement = right * input.movement.x + up * input.movement.y + forward * input.movement.z;
+        let dolly = forward * input.zoom * self.settings.zoom_factor;
+
+        self.position += movement * speed * delta_time + dolly;
+      }
+      DebugCameraMode::Pan2D => {
+        // screen y runs down, but world y runs up
+        let drag = input.pan + input.look;
+        let pan = vec2(-drag.x, drag.y) * self.ortho_size * self.settings.pan_sensitivity;
+        let movement = input.movement.truncate() * self.ortho_size * 0.5;
+
+        self.center += pan + movement * speed * 0.1 * delta_time;
+        self.ortho_size = (self.ortho_size / zoom).max(0.01);
+      }
+    }
+  }
+
+  /// The camera to render with; this one while it's in use, otherwise the
+  /// game's own.
+  pub fn active<'a>(&'a self, game: &'a dyn Camera) -> &'a dyn Camera {
+    match self.enabled {
+      true => self,
+      false => game,
+    }
+  }
+
+  /// The size of the view in 2D mode, in world units.
+  pub fn ortho_size(&self) -> f32 {
+    self.ortho_size
+  }
+
+  /// The direction the camera looks in.
+  pub fn forward(&self) -> Vec3 {
+    let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+    let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+
+    vec3(cos_pitch * sin_yaw, -sin_pitch, cos_pitch * cos_yaw)
+  }
+
+  fn axes(&self) -> (Vec3, Vec3, Vec3) {
+    let forward = self.forward();
+    let right = Vec3::Y.cross(forward).normalize_or(Vec3::X);
+    let up = forward.cross(right);
+
+    (right, up, forward)
+  }
+
+  fn turn(&mut self, look: Vec2) {
+    self.yaw += look.x * self.settings.look_sensitivity;
+    self.pitch = (self.pitch + look.y * self.settings.look_sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+  }
+
+  fn orbit_position(&self) -> Vec3 {
+    self.focus - self.forward() * self.distance
+  }
+
+  fn perspective(&self) -> PerspectiveCamera {
+    PerspectiveCamera {
+      position: self.position,
+      look_at: self.position + self.forward(),
+      up: Vec3::Y,
+      near_plane: self.settings.near_plane,
+      far_plane: self.settings.far_plane,
+      fov: self.settings.fov,
+      aspect_ratio: self.settings.aspect_ratio,
+      culling_mask: LayerMask::ALL,
+    }
+  }
+}
+
+impl Camera for DebugCamera {
+  fn position(&self) -> Vec3 {
+    match self.mode {
+      DebugCameraMode::Pan2D => self.center.extend(-1.),
+      _ => self.position,
+    }
+  }
+
+  fn projection(&self) -> Mat4 {
+    match self.mode {
+      DebugCameraMode::Pan2D => {
+        let half_height = self.ortho_size / 2.;
+        let half_width = half_height * self.settings.aspect_ratio;
+
+        Mat4::orthographic_rh_gl(
+          -half_width,
+          half_width,
+          -half_height,
+          half_height,
+          self.settings.near_plane,
+          self.settings.far_plane,
+        )
+      }
+      _ => self.perspective().projection(),
+    }
+  }
+
+  fn view(&self) -> Mat4 {
+    match self.mode {
+      DebugCameraMode::Pan2D => Mat4::from_translation(-self.center.extend(0.)),
+      _ => self.perspective().view(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const VIEWPORT: Vec2 = Vec2::new(1600., 900.);
+
+  fn enabled_camera() -> DebugCamera {
+    let mut camera = DebugCamera::new(DebugCameraSettings::default());
+
+    camera.toggle();
+    camera
+  }
+
+  #[test]
+  fn it_should_orbit_around_the_focus() {
+    let mut camera = enabled_camera();
+
+    camera.focus_on(vec3(1., 2., 3.), 5.);
+
+    for look in [vec2(200., 0.), vec2(0., 100.), vec2(-50., -300.)] {
+      camera.update(
+        &DebugCameraInput {
+          look,
+          ..Default::default()
+        },
+        0.016,
+      );
+
+      assert!((camera.position().distance(vec3(1., 2., 3.)) - 5.).abs() < 1e-4);
+
+      let focus = camera.world_to_screen(vec3(1., 2., 3.), VIEWPORT).unwrap();
+
+      assert!(focus.distance(VIEWPORT / 2.) < 0.5);
+    }
+
+    camera.update(
+      &DebugCameraInput {
+        zoom: 2.,
+        ..Default::default()
+      },
+      0.016,
+    );
+
+    assert!((camera.position().distance(vec3(1., 2., 3.)) - 5. / 1.21).abs() < 1e-4);
+  }
+
+  #[test]
+  fn it_should_fly_where_it_looks() {
+    let mut camera = enabled_camera();
+
+    camera.set_mode(DebugCameraMode::Fly);
+    camera.look_from(Vec3::ZERO, vec3(0., 0., 10.));
+
+    let input = DebugCameraInput {
+      movement: vec3(0., 0., 1.),
+      ..Default::default()
+    };
+
+    camera.update(&input, 1.);
+
+    assert!(camera.position().distance(vec3(0., 0., 5.)) < 1e-4);
+
+    camera.update(&DebugCameraInput { fast: true, ..input }, 1.);
+
+    assert!(camera.position().distance(vec3(0., 0., 25.)) < 1e-4);
+
+    // switching to orbit keeps the view in place
+    let forward = camera.forward();
+
+    camera.cycle_mode();
+    camera.set_mode(DebugCameraMode::Orbit);
+
+    assert!(camera.forward().distance(forward) < 1e-6);
+  }
+
+  #[test]
+  fn it_should_pan_and_zoom_in_2d() {
+    let mut camera = enabled_camera();
+
+    camera.set_mode(DebugCameraMode::Pan2D);
+    camera.update(
+      &DebugCameraInput {
+        zoom: -1.,
+        ..Default::default()
+      },
+      0.016,
+    );
+
+    assert!((camera.ortho_size() - 11.).abs() < 1e-4);
+
+    // dragging right moves the view left
+    camera.update(
+      &DebugCameraInput {
+        pan: vec2(100., 0.),
+        ..Default::default()
+      },
+      0.016,
+    );
+
+    let center = camera.screen_to_world_2d(VIEWPORT / 2., VIEWPORT);
+
+    assert!(center.x < 0.);
+    assert!(center.y.abs() < 1e-4);
+  }
+
+  #[test]
+  fn it_should_stand_in_for_the_game_camera_while_enabled() {
+    let game = OrthographicCamera::default();
+    let mut camera = DebugCamera::new(DebugCameraSettings::default());
+
+    camera.update(
+      &DebugCameraInput {
+        zoom: 5.,
+        ..Default::default()
+      },
+      0.016,
+    );
+
+    assert_eq!(camera.active(&game).position(), game.position);
+
+    camera.toggle();
+
+    assert_eq!(camera.active(&game).position(), camera.position());
+  }
+}
@@ -0,0 +1,308 @@
+//! Camera effects; screen shake, punches, hit-stop and letterboxing.
+
+use super::*;
+
+/// Settings for [`CameraEffects`].
+#[derive(Clone, Debug)]
+pub struct CameraEffectSettings {
+  /// The largest translation a full-trauma shake can apply, in world units.
+  pub max_shake_offset: Vec2,
+  /// The largest roll a full-trauma shake can apply, in radians.
+  pub max_shake_roll: f32,
+  /// How quickly the shake noise is sampled, in cycles per second.
+  pub shake_frequency: f32,
+  /// How much trauma is lost per second.
+  pub trauma_decay: f32,
+  /// How quickly punches return to rest; higher is snappier.
+  pub punch_decay: f32,
+  /// How quickly letterbox bars slide in and out, in screen fractions/second.
+  pub letterbox_speed: f32,
+}
+
+impl Default for CameraEffectSettings {
+  fn default() -> Self {
+    Self {
+      max_shake_offset: vec2(0.5, 0.5),
+      max_shake_roll: 0.1,
+      shake_frequency: 15.,
+      trauma_decay: 1.,
+      punch_decay: 12.,
+      letterbox_speed: 1.,
+    }
+  }
+}
+
+/// A toolkit of effects layered on top of a [`Camera`].
+///
+/// Shake is trauma-based: gameplay adds trauma in the range 0 to 1, and the
+/// shake magnitude is the square of the trauma so small hits are subtle and
+/// big ones are violent. Trauma decays over time, and the motion itself comes
+/// from Perlin noise so it stays smooth rather than jittering.
+///
+/// Effects are advanced with unscaled time, so they keep playing during
+/// hit-stop or while the game is paused.
+#[derive(Clone, Debug)]
+pub struct CameraEffects {
+  pub settings: CameraEffectSettings,
+  trauma: f32,
+  time: f32,
+  punch: Vec2,
+  letterbox: f32,
+  letterbox_target: f32,
+  hit_stop: Option<HitStop>,
+  noise: [PerlinNoise; 3],
+}
+
+/// An in-flight hit-stop.
+#[derive(Copy, Clone, Debug)]
+struct HitStop {
+  remaining: f32,
+  time_scale: f32,
+  previous_time_scale: Option<f32>,
+}
+
+impl Default for CameraEffects {
+  fn default() -> Self {
+    Self::new(CameraEffectSettings::default())
+  }
+}
+
+impl CameraEffects {
+  /// Creates a new set of camera effects with the given settings.
+  pub fn new(settings: CameraEffectSettings) -> Self {
+    Self {
+      settings,
+      trauma: 0.,
+      time: 0.,
+      punch: Vec2::ZERO,
+      letterbox: 0.,
+      letterbox_target: 0.,
+      hit_stop: None,
+      noise: [PerlinNoise::new(0), PerlinNoise::new(1), PerlinNoise::new(2)],
+    }
+  }
+
+  /// Adds trauma to the camera, clamped to the range 0 to 1.
+  pub fn add_trauma(&mut self, amount: f32) {
+    self.trauma = (self.trauma + amount).clamp(0., 1.);
+  }
+
+  /// The current trauma of the camera.
+  pub fn trauma(&self) -> f32 {
+    self.trauma
+  }
+
+  /// Kicks the camera in the given direction; it springs back over time.
+  pub fn punch(&mut self, offset: Vec2) {
+    self.punch += offset;
+  }
+
+  /// Freezes the game clock (or slows it to the given scale) for a moment.
+  ///
+  /// The previous time scale is restored once the hit-stop has elapsed.
+  pub fn hit_stop(&mut self, duration: TimeSpan, time_scale: f32) {
+    let previous_time_scale = self.hit_stop.and_then(|it| it.previous_time_scale);
+
+    self.hit_stop = Some(HitStop {
+      remaining: duration.as_seconds(),
+      time_scale,
+      previous_time_scale,
+    });
+  }
+
+  /// Is a hit-stop currently in effect?
+  pub fn is_hit_stopped(&self) -> bool {
+    self.hit_stop.is_some()
+  }
+
+  /// Slides the letterbox bars in, each covering the given fraction of the
+  /// screen height.
+  pub fn show_letterbox(&mut self, amount: f32) {
+    self.letterbox_target = amount.clamp(0., 0.5);
+  }
+
+  /// Slides the letterbox bars out.
+  pub fn hide_letterbox(&mut self) {
+    self.letterbox_target = 0.;
+  }
+
+  /// Advances all effects, applying any hit-stop to the given [`Time`].
+  ///
+  /// Call this after [`Time::update`] each frame.
+  pub fn update(&mut self, time: &mut Time) {
+    let delta_time = time.unscaled_delta_time();
+
+    self.time += delta_time;
+    self.trauma = (self.trauma - self.settings.trauma_decay * delta_time).max(0.);
+    self.punch *= (-self.settings.punch_decay * delta_time).exp();
+
+    let step = self.settings.letterbox_speed * delta_time;
+    let difference = self.letterbox_target - self.letterbox;
+
+    self.letterbox += difference.clamp(-step, step);
+
+    if let Some(hit_stop) = &mut self.hit_stop {
+      let previous_time_scale = *hit_stop.previous_time_scale.get_or_insert(time.time_scale());
+
+      hit_stop.remaining -= delta_time;
+
+      if hit_stop.remaining > 0. {
+        time.set_time_scale(hit_stop.time_scale);
+      } else {
+        time.set_time_scale(previous_time_scale);
+        self.hit_stop = None;
+      }
+    }
+  }
+
+  /// The current translation applied to the camera.
+  pub fn offset(&self) -> Vec2 {
+    let shake = self.trauma * self.trauma;
+    let t = self.time * self.settings.shake_frequency;
+
+    let noise = vec2(self.noise[0].sample_1d(t), self.noise[1].sample_1d(t));
+
+    noise * self.settings.max_shake_offset * shake + self.punch
+  }
+
+  /// The current roll applied to the camera, in radians.
+  pub fn roll(&self) -> f32 {
+    let shake = self.trauma * self.trauma;
+    let t = self.time * self.settings.shake_frequency;
+
+    self.noise[2].sample_1d(t) * self.settings.max_shake_roll * shake
+  }
+
+  /// The height of each letterbox bar, as a fraction of the screen height.
+  pub fn letterbox(&self) -> f32 {
+    self.letterbox
+  }
+
+  /// The letterbox bars to draw over a viewport of the given size.
+  ///
+  /// Returns the top and bottom bars, or `None` if the letterbox is hidden.
+  pub fn letterbox_bars(&self, viewport: Vec2) -> Option<[Rectangle; 2]> {
+    if self.letterbox <= 0. {
+      return None;
+    }
+
+    let height = viewport.y * self.letterbox;
+
+    Some([
+      Rectangle::new(vec2(0., 0.), vec2(viewport.x, height)),
+      Rectangle::new(vec2(0., viewport.y - height), viewport),
+    ])
+  }
+
+  /// Applies the current shake and punch to the given view matrix.
+  pub fn apply_to_view(&self, view: Mat4) -> Mat4 {
+    let offset = self.offset();
+
+    Mat4::from_rotation_z(self.roll()) * Mat4::from_translation(vec3(-offset.x, -offset.y, 0.)) * view
+  }
+
+  /// Wraps the given camera so its view includes these effects.
+  pub fn wrap<'a, C: Camera>(&'a self, camera: &'a C) -> AffectedCamera<'a, C> {
+    AffectedCamera { camera, effects: self }
+  }
+}
+
+/// A [`Camera`] with [`CameraEffects`] applied to its view.
+pub struct AffectedCamera<'a, C> {
+  camera: &'a C,
+  effects: &'a CameraEffects,
+}
+
+impl<'a, C: Camera> Camera for AffectedCamera<'a, C> {
+  fn position(&self) -> Vec3 {
+    self.camera.position() + self.effects.offset().extend(0.)
+  }
+
+  fn projection(&self) -> Mat4 {
+    self.camera.projection()
+  }
+
+  fn view(&self) -> Mat4 {
+    self.effects.apply_to_view(self.camera.view())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tick(effects: &mut CameraEffects, time: &mut Time, delta_time: f32) {
+    time.update(delta_time);
+    effects.update(time);
+  }
+
+  #[test]
+  fn it_should_decay_trauma_over_time() {
+    let mut effects = CameraEffects::default();
+    let mut time = Time::new();
+
+    effects.add_trauma(2.);
+    assert_eq!(effects.trauma(), 1.);
+
+    tick(&mut effects, &mut time, 0.5);
+    assert_eq!(effects.trauma(), 0.5);
+
+    tick(&mut effects, &mut time, 1.);
+    assert_eq!(effects.trauma(), 0.);
+    assert_eq!(effects.offset(), Vec2::ZERO);
+    assert_eq!(effects.roll(), 0.);
+  }
+
+  #[test]
+  fn it_should_return_punches_to_rest() {
+    let mut effects = CameraEffects::default();
+    let mut time = Time::new();
+
+    effects.punch(vec2(1., 0.));
+    tick(&mut effects, &mut time, 0.1);
+
+    assert!(effects.offset().x > 0. && effects.offset().x < 1.);
+
+    for _ in 0..60 {
+      tick(&mut effects, &mut time, 0.1);
+    }
+
+    assert!(effects.offset().length() < 0.001);
+  }
+
+  #[test]
+  fn it_should_apply_and_restore_hit_stop() {
+    let mut effects = CameraEffects::default();
+    let mut time = Time::new();
+
+    time.set_time_scale(0.75);
+    effects.hit_stop(TimeSpan::from_seconds(0.2), 0.);
+
+    tick(&mut effects, &mut time, 0.1);
+    assert!(effects.is_hit_stopped());
+    assert_eq!(time.time_scale(), 0.);
+
+    tick(&mut effects, &mut time, 0.1);
+    tick(&mut effects, &mut time, 0.1);
+    assert!(!effects.is_hit_stopped());
+    assert_eq!(time.time_scale(), 0.75);
+  }
+
+  #[test]
+  fn it_should_slide_letterbox_bars() {
+    let mut effects = CameraEffects::default();
+    let mut time = Time::new();
+
+    assert!(effects.letterbox_bars(vec2(100., 100.)).is_none());
+
+    effects.show_letterbox(0.1);
+    tick(&mut effects, &mut time, 0.05);
+    tick(&mut effects, &mut time, 0.05);
+    tick(&mut effects, &mut time, 0.05);
+
+    let [top, bottom] = effects.letterbox_bars(vec2(100., 100.)).unwrap();
+
+    assert!((top.height() - 10.).abs() < 0.001);
+    assert!((bottom.max.y - 100.).abs() < 0.001);
+  }
+}
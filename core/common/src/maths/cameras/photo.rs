@@ -0,0 +1,364 @@
+use super::*;
+
+/// How far the camera can look up or down, short of straight up so it stays
+/// upright.
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+/// A frame of input for the [`PhotoMode`] camera, in whatever units the
+/// controls produce; the settings scale it into motion.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PhotoCameraInput {
+  /// Movement along the camera's right, up and forward axes.
+  pub movement: Vec3,
+  /// Turning, as yaw and pitch.
+  pub look: Vec2,
+  /// Rolling clockwise or counter-clockwise.
+  pub roll: f32,
+  /// Zooming in (positive) or out (negative) by narrowing the field of view.
+  pub zoom: f32,
+  /// Moves faster while held.
+  pub fast: bool,
+}
+
+/// Settings for [`PhotoMode`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhotoModeSettings {
+  /// How fast the camera moves, in units per second.
+  pub move_speed: f32,
+  /// How much faster the camera moves while fast is held.
+  pub fast_multiplier: f32,
+  /// How far the camera turns per unit of look input, in radians.
+  pub look_sensitivity: f32,
+  /// How fast the camera rolls, in radians per second.
+  pub roll_speed: f32,
+  /// How much the field of view changes per unit of zoom input, in radians.
+  pub zoom_speed: f32,
+  /// The narrowest field of view, in radians.
+  pub min_fov: f32,
+  /// The widest field of view, in radians.
+  pub max_fov: f32,
+  /// How far the camera may stray from where photo mode started, if limited.
+  pub max_distance: Option<f32>,
+  pub near_plane: f32,
+  pub far_plane: f32,
+  pub aspect_ratio: f32,
+}
+
+impl Default for PhotoModeSettings {
+  fn default() -> Self {
+    Self {
+      move_speed: 5.,
+      fast_multiplier: 4.,
+      look_sensitivity: 0.005,
+      roll_speed: 1.,
+      zoom_speed: 0.05,
+      min_fov: 10f32.to_radians(),
+      max_fov: 120f32.to_radians(),
+      max_distance: Some(20.),
+      near_plane: 0.1,
+      far_plane: 1000.,
+      aspect_ratio: 16. / 9.,
+    }
+  }
+}
+
+/// A photo mode: a free camera with roll and field of view control, that
+/// freezes the game clock and can hide the UI while framing a shot.
+///
+/// ```rust,ignore
+/// let mut photo_mode = PhotoMode::new(PhotoModeSettings::default());
+///
+/// photo_mode.enter(&game_camera, &mut time);
+/// photo_mode.update(&input, &time);
+///
+/// renderer.render(photo_mode.active(&game_camera));
+///
+/// if !photo_mode.is_ui_hidden() {
+///   hud.render();
+/// }
+/// ```
+///
+/// The camera moves in real time, so it keeps working while the game is
+/// frozen.
+#[derive(Clone, Debug)]
+pub struct PhotoMode {
+  pub settings: PhotoModeSettings,
+  active: bool,
+  hide_ui: bool,
+  was_paused: bool,
+  origin: Vec3,
+  position: Vec3,
+  yaw: f32,
+  pitch: f32,
+  roll: f32,
+  fov: f32,
+}
+
+impl PhotoMode {
+  /// Creates an inactive photo mode.
+  pub fn new(settings: PhotoModeSettings) -> Self {
+    Self {
+      settings,
+      active: false,
+      hide_ui: false,
+      was_paused: false,
+      origin: Vec3::ZERO,
+      position: Vec3::ZERO,
+      yaw: 0.,
+      pitch: 0.,
+      roll: 0.,
+      fov: std::f32::consts::FRAC_PI_3,
+    }
+  }
+
+  /// Determines if photo mode is in use.
+  pub fn is_active(&self) -> bool {
+    self.active
+  }
+
+  /// Starts photo mode from where the game's camera is, freezing the game
+  /// clock.
+  pub fn enter(&mut self, camera: &dyn Camera, time: &mut Time) {
+    if self.active {
+      return;
+    }
+
+    // works for cameras of either handedness
+    let forward = camera
+      .screen_to_ray(Vec2::splat(0.5), Vec2::ONE)
+      .direction
+      .normalize_or(Vec3::Z);
+
+    self.active = true;
+    self.was_paused = time.is_paused();
+    self.origin = camera.position();
+    self.position = self.origin;
+    self.yaw = forward.x.atan2(forward.z);
+    self.pitch = (-forward.y).asin().clamp(-MAX_PITCH, MAX_PITCH);
+    self.roll = 0.;
+
+    time.pause();
+  }
+
+  /// Stops photo mode, unfreezing the game clock unless it was already
+  /// paused, and showing the UI again.
+  pub fn exit(&mut self, time: &mut Time) {
+    if !self.active {
+      return;
+    }
+
+    self.active = false;
+    self.hide_ui = false;
+
+    if !self.was_paused {
+      time.resume();
+    }
+  }
+
+  /// Enters photo mode if it's not in use, and exits if it is.
+  pub fn toggle(&mut self, camera: &dyn Camera, time: &mut Time) {
+    match self.active {
+      true => self.exit(time),
+      false => self.enter(camera, time),
+    }
+  }
+
+  /// Determines if the UI should be hidden.
+  pub fn is_ui_hidden(&self) -> bool {
+    self.active && self.hide_ui
+  }
+
+  /// Hides or shows the UI while in photo mode.
+  pub fn set_ui_hidden(&mut self, hidden: bool) {
+    self.hide_ui = hidden;
+  }
+
+  /// Hides the UI if it's shown, and shows it if it's hidden.
+  pub fn toggle_ui(&mut self) {
+    self.hide_ui = !self.hide_ui;
+  }
+
+  /// The camera's roll, in radians.
+  pub fn roll(&self) -> f32 {
+    self.roll
+  }
+
+  /// Rolls the camera to an angle, in radians.
+  pub fn set_roll(&mut self, roll: f32) {
+    self.roll = roll;
+  }
+
+  /// The camera's vertical field of view, in radians.
+  pub fn fov(&self) -> f32 {
+    self.fov
+  }
+
+  /// Changes the field of view, within the settings' limits.
+  pub fn set_fov(&mut self, fov: f32) {
+    self.fov = fov.clamp(self.settings.min_fov, self.settings.max_fov);
+  }
+
+  /// Moves the camera by a frame of input; does nothing while inactive.
+  pub fn update(&mut self, input: &PhotoCameraInput, time: &Time) {
+    if !self.active {
+      return;
+    }
+
+    let delta_time = time.unscaled_delta_time();
+    let settings = &self.settings;
+    let speed = match input.fast {
+      true => settings.move_speed * settings.fast_multiplier,
+      false => settings.move_speed,
+    };
+
+    self.yaw += input.look.x * settings.look_sensitivity;
+    self.pitch = (self.pitch + input.look.y * settings.look_sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+    self.roll += input.roll * settings.roll_speed * delta_time;
+    self.set_fov(self.fov - input.zoom * self.settings.zoom_speed);
+
+    let (right, up, forward) = self.axes();
+    let movement = right * input.movement.x + up * input.movement.y + forward * input.movement.z;
+
+    self.position += movement * speed * delta_time;
+
+    if let Some(max_distance) = self.settings.max_distance {
+      let offset = self.position - self.origin;
+
+      self.position = self.origin + offset.clamp_length_max(max_distance);
+    }
+  }
+
+  /// The camera to render with; this one while in photo mode, otherwise the
+  /// game's own.
+  pub fn active<'a>(&'a self, game: &'a dyn Camera) -> &'a dyn Camera {
+    match self.active {
+      true => self,
+      false => game,
+    }
+  }
+
+  /// The direction the camera looks in.
+  pub fn forward(&self) -> Vec3 {
+    let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+    let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+
+    vec3(cos_pitch * sin_yaw, -sin_pitch, cos_pitch * cos_yaw)
+  }
+
+  /// The camera's right, up and forward axes, before rolling.
+  fn axes(&self) -> (Vec3, Vec3, Vec3) {
+    let forward = self.forward();
+    let right = Vec3::Y.cross(forward).normalize_or(Vec3::X);
+    let up = forward.cross(right);
+
+    (right, up, forward)
+  }
+}
+
+impl Camera for PhotoMode {
+  fn position(&self) -> Vec3 {
+    self.position
+  }
+
+  fn projection(&self) -> Mat4 {
+    Mat4::perspective_rh_gl(
+      self.fov,
+      self.settings.aspect_ratio,
+      self.settings.near_plane,
+      self.settings.far_plane,
+    )
+  }
+
+  fn view(&self) -> Mat4 {
+    let (_, up, forward) = self.axes();
+    let up = Quat::from_axis_angle(forward, self.roll) * up;
+
+    Mat4::look_to_rh(self.position, forward, up)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn game_camera() -> PerspectiveCamera {
+    PerspectiveCamera {
+      position: vec3(0., 2., -10.),
+      look_at: vec3(0., 2., 0.),
+      fov: 1.,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn it_should_freeze_time_while_active() {
+    let mut photo_mode = PhotoMode::new(PhotoModeSettings::default());
+    let mut time = Time::new();
+
+    photo_mode.enter(&game_camera(), &mut time);
+
+    assert!(time.is_paused());
+    assert_eq!(photo_mode.position(), vec3(0., 2., -10.));
+    assert!(photo_mode.forward().abs_diff_eq(Vec3::Z, 0.0001));
+
+    photo_mode.toggle_ui();
+
+    assert!(photo_mode.is_ui_hidden());
+
+    photo_mode.exit(&mut time);
+
+    assert!(!time.is_paused());
+    assert!(!photo_mode.is_ui_hidden());
+  }
+
+  #[test]
+  fn it_should_keep_an_already_paused_clock_paused() {
+    let mut photo_mode = PhotoMode::new(PhotoModeSettings::default());
+    let mut time = Time::new();
+
+    time.pause();
+    photo_mode.enter(&game_camera(), &mut time);
+    photo_mode.exit(&mut time);
+
+    assert!(time.is_paused());
+  }
+
+  #[test]
+  fn it_should_move_roll_and_zoom_in_real_time() {
+    let mut photo_mode = PhotoMode::new(PhotoModeSettings {
+      max_distance: Some(5.),
+      ..Default::default()
+    });
+    let mut time = Time::new();
+
+    photo_mode.enter(&game_camera(), &mut time);
+    time.update(1.);
+
+    let fov = photo_mode.fov();
+
+    photo_mode.update(
+      &PhotoCameraInput {
+        movement: vec3(0., 0., 1.),
+        roll: 0.5,
+        zoom: 1.,
+        ..Default::default()
+      },
+      &time,
+    );
+
+    assert!(photo_mode.position().abs_diff_eq(vec3(0., 2., -5.), 0.0001));
+    assert_eq!(photo_mode.roll(), 0.5);
+    assert!(photo_mode.fov() < fov);
+
+    // stays within reach of where photo mode started
+    photo_mode.update(
+      &PhotoCameraInput {
+        movement: vec3(0., 0., 1.),
+        ..Default::default()
+      },
+      &time,
+    );
+
+    assert!(photo_mode.position().abs_diff_eq(vec3(0., 2., -5.), 0.0001));
+  }
+}
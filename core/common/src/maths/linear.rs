@@ -3,6 +3,7 @@
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign};
 
 pub use aabb::*;
+pub use bounds::*;
 pub use frustum::*;
 pub use glam::*;
 pub use planes::*;
@@ -13,6 +14,7 @@ pub use vectors::*;
 use super::*;
 
 mod aabb;
+mod bounds;
 mod frustum;
 mod planes;
 mod rays;
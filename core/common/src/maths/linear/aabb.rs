@@ -27,6 +27,16 @@ impl AABB {
     Self::from_min_max(min, max)
   }
 
+  /// The center point of the AABB.
+  pub fn center(&self) -> Vec3 {
+    (self.min + self.max) / 2.
+  }
+
+  /// The size of the AABB along each axis.
+  pub fn size(&self) -> Vec3 {
+    self.max - self.min
+  }
+
   /// Retrieves the nth corner of the AABB.
   ///
   /// This method will panic if the index is out of bounds.
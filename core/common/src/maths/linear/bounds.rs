@@ -0,0 +1,192 @@
+use super::*;
+
+/// Bounding volumes around an object, for culling, picking and debug
+/// drawing.
+///
+/// Both a box and a sphere are kept; the sphere is cheap to test and stays
+/// tight under rotation, while the box fits long or flat objects better.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bounds {
+  pub aabb: AABB,
+  pub sphere: Sphere,
+}
+
+impl Bounds {
+  /// Creates bounds that fit the given box.
+  pub fn from_aabb(aabb: AABB) -> Self {
+    let sphere = Sphere {
+      center: aabb.center(),
+      radius: aabb.size().length() / 2.,
+    };
+
+    Self { aabb, sphere }
+  }
+
+  /// Creates bounds that fit the given 2D rectangle, on the `z = 0` plane.
+  pub fn from_rectangle(rectangle: &Rectangle) -> Self {
+    Self::from_aabb(AABB::from_min_max(rectangle.min.extend(0.), rectangle.max.extend(0.)))
+  }
+
+  /// Creates bounds that fit the given points, or `None` if there aren't
+  /// any.
+  pub fn from_points(points: impl IntoIterator<Item = Vec3> + Clone) -> Option<Self> {
+    let mut points_iter = points.clone().into_iter().peekable();
+
+    points_iter.peek()?;
+
+    let mut min = Vec3::splat(f32::MAX);
+    let mut max = Vec3::splat(f32::MIN);
+
+    for point in points_iter {
+      min = min.min(point);
+      max = max.max(point);
+    }
+
+    let aabb = AABB::from_min_max(min, max);
+    let center = aabb.center();
+
+    // the farthest point from the center is usually tighter than the corner
+    let radius = points
+      .into_iter()
+      .map(|point| point.distance(center))
+      .fold(0., f32::max);
+
+    Some(Self {
+      aabb,
+      sphere: Sphere { center, radius },
+    })
+  }
+
+  /// The center of the bounds.
+  pub fn center(&self) -> Vec3 {
+    self.aabb.center()
+  }
+
+  /// Transforms the bounds into another space, like from local to world.
+  pub fn transform(&self, transform: &Mat4) -> Self {
+    let scale = transform
+      .x_axis
+      .truncate()
+      .length()
+      .max(transform.y_axis.truncate().length())
+      .max(transform.z_axis.truncate().length());
+
+    Self {
+      aabb: self.aabb.transform(transform),
+      sphere: Sphere {
+        center: transform.transform_point3(self.sphere.center),
+        radius: self.sphere.radius * scale,
+      },
+    }
+  }
+
+  /// Builds bounds around both these bounds and the other.
+  pub fn union(&self, other: &Self) -> Self {
+    let (a, b) = (&self.sphere, &other.sphere);
+    let offset = b.center - a.center;
+    let distance = offset.length();
+
+    let sphere = if distance + b.radius <= a.radius {
+      a.clone()
+    } else if distance + a.radius <= b.radius {
+      b.clone()
+    } else {
+      let radius = (distance + a.radius + b.radius) / 2.;
+
+      Sphere {
+        center: a.center + offset * ((radius - a.radius) / distance),
+        radius,
+      }
+    };
+
+    Self {
+      aabb: self.aabb.union(&other.aabb),
+      sphere,
+    }
+  }
+
+  /// Determines if the point is inside the bounds.
+  pub fn contains_point(&self, point: Vec3) -> bool {
+    point.distance_squared(self.sphere.center) <= self.sphere.radius * self.sphere.radius && self.aabb.contains(point)
+  }
+
+  /// The distance along the ray to where it enters the bounds, or `None` if
+  /// it misses them; rays starting inside hit at zero.
+  pub fn intersects_ray(&self, ray: &Ray3) -> Option<f32> {
+    let mut near = 0f32;
+    let mut far = f32::MAX;
+
+    for axis in 0..3 {
+      let origin = ray.origin[axis];
+      let direction = ray.direction[axis];
+      let (min, max) = (self.aabb.min[axis], self.aabb.max[axis]);
+
+      if direction.abs() < f32::EPSILON {
+        if origin < min || origin > max {
+          return None;
+        }
+
+        continue;
+      }
+
+      let (t0, t1) = ((min - origin) / direction, (max - origin) / direction);
+
+      near = near.max(t0.min(t1));
+      far = far.min(t0.max(t1));
+
+      if near > far {
+        return None;
+      }
+    }
+
+    Some(near)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_fit_points() {
+    let bounds = Bounds::from_points([vec3(-1., 0., 0.), vec3(1., 0., 0.), vec3(0., 2., 0.)]).unwrap();
+
+    assert_eq!(bounds.aabb, AABB::from_min_max(vec3(-1., 0., 0.), vec3(1., 2., 0.)));
+    assert_eq!(bounds.center(), vec3(0., 1., 0.));
+    assert!((bounds.sphere.radius - 2f32.sqrt()).abs() < 1e-6);
+    assert!(Bounds::from_points(Vec::<Vec3>::new()).is_none());
+  }
+
+  #[test]
+  fn it_should_transform_and_union() {
+    let bounds = Bounds::from_aabb(AABB::from_min_max(Vec3::splat(-1.), Vec3::ONE));
+    let moved = bounds.transform(&Mat4::from_scale_rotation_translation(
+      Vec3::splat(2.),
+      Quat::IDENTITY,
+      vec3(10., 0., 0.),
+    ));
+
+    assert_eq!(moved.aabb, AABB::from_min_max(vec3(8., -2., -2.), vec3(12., 2., 2.)));
+    assert!((moved.sphere.radius - bounds.sphere.radius * 2.).abs() < 1e-5);
+
+    let union = bounds.union(&moved);
+
+    assert_eq!(union.aabb, AABB::from_min_max(vec3(-1., -2., -2.), vec3(12., 2., 2.)));
+
+    for point in [vec3(-1., -1., -1.), vec3(12., 2., 2.), vec3(0., 1., 1.)] {
+      assert!(union.sphere.center.distance(point) <= union.sphere.radius + 1e-4);
+    }
+  }
+
+  #[test]
+  fn it_should_pick_with_rays() {
+    let bounds = Bounds::from_aabb(AABB::from_min_max(Vec3::splat(-1.), Vec3::ONE));
+
+    assert_eq!(bounds.intersects_ray(&ray3(vec3(-5., 0., 0.), Vec3::X)), Some(4.));
+    assert_eq!(bounds.intersects_ray(&ray3(Vec3::ZERO, Vec3::Y)), Some(0.));
+    assert_eq!(bounds.intersects_ray(&ray3(vec3(-5., 3., 0.), Vec3::X)), None);
+    assert_eq!(bounds.intersects_ray(&ray3(vec3(-5., 0., 0.), -Vec3::X)), None);
+    assert!(bounds.contains_point(Vec3::splat(0.5)));
+    assert!(!bounds.contains_point(vec3(1.5, 0., 0.)));
+  }
+}
@@ -0,0 +1,140 @@
+//! Gradient noise functions.
+
+use super::*;
+
+/// Seeded Perlin gradient noise.
+///
+/// Samples are smooth and continuous, and lie roughly in the range -1 to 1,
+/// which makes them useful for organic motion (camera shake, wind gusts) as
+/// well as procedural generation.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PerlinNoise {
+  seed: u32,
+}
+
+impl PerlinNoise {
+  /// Creates a new noise function with the given seed.
+  pub const fn new(seed: u32) -> Self {
+    Self { seed }
+  }
+
+  /// Samples 1-dimensional noise at the given position.
+  pub fn sample_1d(&self, x: f32) -> f32 {
+    let x0 = x.floor();
+    let t = x - x0;
+
+    let g0 = self.gradient_1d(x0 as i32);
+    let g1 = self.gradient_1d(x0 as i32 + 1);
+
+    // scaled so the output spans roughly -1 to 1
+    lerp_f32(g0 * t, g1 * (t - 1.), fade(t)) * 2.
+  }
+
+  /// Samples 2-dimensional noise at the given position.
+  pub fn sample_2d(&self, position: Vec2) -> f32 {
+    let cell = position.floor();
+    let local = position - cell;
+    let (x, y) = (cell.x as i32, cell.y as i32);
+
+    let n00 = self.gradient_2d(x, y).dot(local);
+    let n10 = self.gradient_2d(x + 1, y).dot(local - vec2(1., 0.));
+    let n01 = self.gradient_2d(x, y + 1).dot(local - vec2(0., 1.));
+    let n11 = self.gradient_2d(x + 1, y + 1).dot(local - vec2(1., 1.));
+
+    let u = fade(local.x);
+    let v = fade(local.y);
+
+    lerp_f32(lerp_f32(n00, n10, u), lerp_f32(n01, n11, u), v) * std::f32::consts::SQRT_2
+  }
+
+  /// Samples layered (fractal) 2-dimensional noise at the given position.
+  pub fn fractal_2d(&self, position: Vec2, octaves: u32, lacunarity: f32, persistence: f32) -> f32 {
+    let mut total = 0.;
+    let mut amplitude = 1.;
+    let mut frequency = 1.;
+    let mut max_amplitude = 0.;
+
+    for octave in 0..octaves.max(1) {
+      let layer = PerlinNoise::new(self.seed.wrapping_add(octave));
+
+      total += layer.sample_2d(position * frequency) * amplitude;
+      max_amplitude += amplitude;
+      amplitude *= persistence;
+      frequency *= lacunarity;
+    }
+
+    total / max_amplitude
+  }
+
+  fn gradient_1d(&self, x: i32) -> f32 {
+    (hash(self.seed, x, 0) as f32 / u32::MAX as f32) * 2. - 1.
+  }
+
+  fn gradient_2d(&self, x: i32, y: i32) -> Vec2 {
+    let angle = hash(self.seed, x, y) as f32 / u32::MAX as f32 * std::f32::consts::TAU;
+
+    vec2(angle.cos(), angle.sin())
+  }
+}
+
+/// Perlin's quintic fade curve.
+#[inline]
+fn fade(t: f32) -> f32 {
+  t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+#[inline]
+fn lerp_f32(a: f32, b: f32, t: f32) -> f32 {
+  a + (b - a) * t
+}
+
+/// Hashes a lattice point into a pseudo-random value.
+fn hash(seed: u32, x: i32, y: i32) -> u32 {
+  let mut hash = seed
+    .wrapping_mul(0x27D4_EB2D)
+    .wrapping_add((x as u32).wrapping_mul(0x85EB_CA6B))
+    .wrapping_add((y as u32).wrapping_mul(0xC2B2_AE35));
+
+  hash ^= hash >> 15;
+  hash = hash.wrapping_mul(0x2C1B_3C6D);
+  hash ^= hash >> 12;
+  hash = hash.wrapping_mul(0x297A_2D39);
+  hash ^= hash >> 15;
+
+  hash
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_be_zero_at_lattice_points() {
+    let noise = PerlinNoise::new(42);
+
+    assert_eq!(noise.sample_1d(3.), 0.);
+    assert_eq!(noise.sample_2d(vec2(2., 5.)), 0.);
+  }
+
+  #[test]
+  fn it_should_be_deterministic_and_bounded() {
+    let a = PerlinNoise::new(7);
+    let b = PerlinNoise::new(7);
+
+    for index in 0..1000 {
+      let x = index as f32 * 0.173;
+
+      assert_eq!(a.sample_1d(x), b.sample_1d(x));
+      assert!(a.sample_1d(x).abs() <= 1.);
+      assert!(a.fractal_2d(vec2(x, -x), 4, 2., 0.5).abs() <= 1.);
+    }
+  }
+
+  #[test]
+  fn it_should_vary_with_seed() {
+    let a = PerlinNoise::new(1);
+    let b = PerlinNoise::new(2);
+
+    assert_ne!(a.sample_1d(0.5), b.sample_1d(0.5));
+  }
+}
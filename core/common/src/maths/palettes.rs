@@ -0,0 +1,363 @@
+//! Color palettes, and the common formats for sharing them.
+//!
+//! Palettes read from and write to JASC (`.pal`), GIMP (`.gpl`) and Lospec
+//! JSON files, so they round-trip through most pixel art tools.
+
+use std::fmt::Write;
+
+use super::*;
+use crate::{Chunk, Format, JsonFormat, Variant};
+
+/// An error that occurs when reading a [`ColorPalette`].
+#[derive(Debug, PartialEq)]
+pub enum PaletteError {
+  InvalidHeader,
+  InvalidColor(String),
+  InvalidData,
+}
+
+/// An ordered list of colors, e.g. for pixel art.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColorPalette {
+  pub name: String,
+  colors: Vec<Color32>,
+}
+
+impl ColorPalette {
+  /// Creates a new, empty palette with the given name.
+  pub fn new(name: impl Into<String>) -> Self {
+    Self {
+      name: name.into(),
+      colors: Vec::new(),
+    }
+  }
+
+  /// Creates an unnamed palette from the given colors.
+  pub fn from_colors(colors: impl IntoIterator<Item = Color32>) -> Self {
+    Self {
+      name: String::new(),
+      colors: colors.into_iter().collect(),
+    }
+  }
+
+  /// The number of colors in the palette.
+  pub fn len(&self) -> usize {
+    self.colors.len()
+  }
+
+  /// Determines if the palette has no colors.
+  pub fn is_empty(&self) -> bool {
+    self.colors.is_empty()
+  }
+
+  /// The colors in the palette, in order.
+  pub fn colors(&self) -> &[Color32] {
+    &self.colors
+  }
+
+  /// Gets the color at the given index.
+  pub fn get(&self, index: usize) -> Option<Color32> {
+    self.colors.get(index).copied()
+  }
+
+  /// Replaces the color at the given index.
+  pub fn set(&mut self, index: usize, color: Color32) {
+    if let Some(existing) = self.colors.get_mut(index) {
+      *existing = color;
+    }
+  }
+
+  /// Adds a color to the end of the palette.
+  pub fn push(&mut self, color: Color32) {
+    self.colors.push(color);
+  }
+
+  /// Inserts a color at the given index, shifting later colors along.
+  pub fn insert(&mut self, index: usize, color: Color32) {
+    self.colors.insert(index.min(self.colors.len()), color);
+  }
+
+  /// Removes the color at the given index.
+  pub fn remove(&mut self, index: usize) -> Option<Color32> {
+    if index < self.colors.len() {
+      Some(self.colors.remove(index))
+    } else {
+      None
+    }
+  }
+
+  /// Moves a color to a new index, shifting the colors in between.
+  pub fn reorder(&mut self, from: usize, to: usize) {
+    if let Some(color) = self.remove(from) {
+      self.insert(to, color);
+    }
+  }
+
+  /// Swaps the colors at two indices.
+  pub fn swap(&mut self, a: usize, b: usize) {
+    if a < self.colors.len() && b < self.colors.len() {
+      self.colors.swap(a, b);
+    }
+  }
+
+  /// Inserts a ramp of colors blending from one color to another at the given
+  /// index.
+  ///
+  /// The ramp includes both ends, so `steps` must be at least 2 to reach the
+  /// second color.
+  pub fn insert_ramp(&mut self, index: usize, from: Color32, to: Color32, steps: usize) {
+    let index = index.min(self.colors.len());
+    let ramp = (0..steps).map(|step| {
+      let t = if steps > 1 {
+        step as f32 / (steps - 1) as f32
+      } else {
+        0.
+      };
+
+      Color32::lerp(from, to, t)
+    });
+
+    self.colors.splice(index..index, ramp);
+  }
+
+  /// Adds a ramp of colors blending from one color to another to the end of
+  /// the palette.
+  pub fn push_ramp(&mut self, from: Color32, to: Color32, steps: usize) {
+    self.insert_ramp(self.colors.len(), from, to, steps);
+  }
+
+  /// Reads a palette in any of the supported formats, detected by its
+  /// contents.
+  pub fn parse(text: &str) -> Result<Self, PaletteError> {
+    let text = text.trim_start();
+
+    if text.starts_with("JASC-PAL") {
+      Self::from_jasc_pal(text)
+    } else if text.starts_with("GIMP Palette") {
+      Self::from_gimp_gpl(text)
+    } else if text.starts_with('{') {
+      Self::from_lospec_json(text)
+    } else {
+      Err(PaletteError::InvalidHeader)
+    }
+  }
+
+  /// Reads a palette from a JASC (Paint Shop Pro) `.pal` file.
+  pub fn from_jasc_pal(text: &str) -> Result<Self, PaletteError> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+
+    if lines.next() != Some("JASC-PAL") {
+      return Err(PaletteError::InvalidHeader);
+    }
+
+    // the version is always 0100
+    lines.next().ok_or(PaletteError::InvalidHeader)?;
+
+    let count: usize = lines
+      .next()
+      .and_then(|line| line.parse().ok())
+      .ok_or(PaletteError::InvalidHeader)?;
+
+    let colors = lines.take(count).map(parse_rgb).collect::<Result<Vec<_>, _>>()?;
+
+    if colors.len() != count {
+      return Err(PaletteError::InvalidData);
+    }
+
+    Ok(Self::from_colors(colors))
+  }
+
+  /// Writes the palette as a JASC (Paint Shop Pro) `.pal` file.
+  pub fn to_jasc_pal(&self) -> String {
+    let mut text = format!("JASC-PAL\r\n0100\r\n{}\r\n", self.colors.len());
+
+    for color in &self.colors {
+      let _ = write!(text, "{} {} {}\r\n", color.r, color.g, color.b);
+    }
+
+    text
+  }
+
+  /// Reads a palette from a GIMP `.gpl` file.
+  pub fn from_gimp_gpl(text: &str) -> Result<Self, PaletteError> {
+    let mut lines = text.lines().map(str::trim);
+
+    if lines.next() != Some("GIMP Palette") {
+      return Err(PaletteError::InvalidHeader);
+    }
+
+    let mut palette = Self::default();
+
+    for line in lines {
+      if line.is_empty() || line.starts_with('#') || line.starts_with("Columns:") {
+        continue;
+      }
+
+      match line.strip_prefix("Name:") {
+        Some(name) => palette.name = name.trim().to_string(),
+        None => palette.push(parse_rgb(line)?),
+      }
+    }
+
+    Ok(palette)
+  }
+
+  /// Writes the palette as a GIMP `.gpl` file.
+  pub fn to_gimp_gpl(&self) -> String {
+    let mut text = format!("GIMP Palette\nName: {}\nColumns: 0\n#\n", self.name);
+
+    for color in &self.colors {
+      let _ = writeln!(text, "{:>3} {:>3} {:>3}\t{}", color.r, color.g, color.b, to_hex(*color));
+    }
+
+    text
+  }
+
+  /// Reads a palette from a Lospec JSON file.
+  pub fn from_lospec_json(text: &str) -> Result<Self, PaletteError> {
+    let chunk = JsonFormat::default()
+      .read_chunk(&mut std::io::Cursor::new(text.as_bytes()))
+      .map_err(|_| PaletteError::InvalidData)?;
+
+    let colors = chunk
+      .get("colors")
+      .and_then(Chunk::as_sequence)
+      .ok_or(PaletteError::InvalidData)?
+      .iter()
+      .map(|color| match color {
+        Chunk::Variant(Variant::String(hex)) => parse_hex(hex),
+        _ => Err(PaletteError::InvalidData),
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Self {
+      name: chunk.read_field_or("name", String::new()).unwrap_or_default(),
+      colors,
+    })
+  }
+
+  /// Writes the palette as a Lospec JSON file.
+  pub fn to_lospec_json(&self) -> String {
+    let colors = self
+      .colors
+      .iter()
+      .map(|color| format!("\"{}\"", to_hex(*color)))
+      .collect::<Vec<_>>();
+
+    format!(
+      "{{\n  \"name\": \"{}\",\n  \"author\": \"\",\n  \"colors\": [{}]\n}}\n",
+      self.name.replace('\\', "\\\\").replace('"', "\\\""),
+      colors.join(", ")
+    )
+  }
+}
+
+/// Parses a line of whitespace-separated red, green and blue components.
+fn parse_rgb(line: &str) -> Result<Color32, PaletteError> {
+  let mut components = line.split_whitespace().map(str::parse::<u8>);
+  let mut next = || match components.next() {
+    Some(Ok(component)) => Ok(component),
+    _ => Err(PaletteError::InvalidColor(line.to_string())),
+  };
+
+  Ok(Color32::rgb(next()?, next()?, next()?))
+}
+
+/// Parses a color from hex digits, like `ff8800`, with an optional `#`.
+fn parse_hex(hex: &str) -> Result<Color32, PaletteError> {
+  let digits = hex.trim_start_matches('#');
+  let invalid = || PaletteError::InvalidColor(hex.to_string());
+
+  if digits.len() != 6 {
+    return Err(invalid());
+  }
+
+  let value = u32::from_str_radix(digits, 16).map_err(|_| invalid())?;
+
+  Ok(Color32::from_packed(value << 8 | 0xFF))
+}
+
+/// Formats a color as hex digits, like `ff8800`.
+fn to_hex(color: Color32) -> String {
+  format!("{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn create_palette() -> ColorPalette {
+    let mut palette = ColorPalette::from_colors([Color32::BLACK, Color32::rgb(255, 136, 0), Color32::WHITE]);
+
+    palette.name = "Test".to_string();
+    palette
+  }
+
+  #[test]
+  fn it_should_edit_colors() {
+    let mut palette = create_palette();
+
+    palette.insert(1, Color32::RED);
+    palette.reorder(0, 3);
+    palette.swap(0, 1);
+
+    assert_eq!(palette.colors(), &[
+      Color32::rgb(255, 136, 0),
+      Color32::RED,
+      Color32::WHITE,
+      Color32::BLACK
+    ]);
+    assert_eq!(palette.remove(3), Some(Color32::BLACK));
+    assert_eq!(palette.remove(3), None);
+  }
+
+  #[test]
+  fn it_should_generate_ramps_between_colors() {
+    let mut palette = ColorPalette::new("Ramp");
+
+    palette.push(Color32::RED);
+    palette.insert_ramp(0, Color32::rgb(0, 0, 0), Color32::rgb(200, 100, 40), 3);
+
+    assert_eq!(palette.colors(), &[
+      Color32::rgb(0, 0, 0),
+      Color32::rgb(100, 50, 20),
+      Color32::rgb(200, 100, 40),
+      Color32::RED
+    ]);
+  }
+
+  #[test]
+  fn it_should_round_trip_jasc_palettes() {
+    let palette = create_palette();
+    let text = palette.to_jasc_pal();
+
+    assert!(text.starts_with("JASC-PAL\r\n0100\r\n3\r\n0 0 0\r\n"));
+    assert_eq!(ColorPalette::parse(&text).unwrap().colors(), palette.colors());
+    assert_eq!(
+      ColorPalette::from_jasc_pal("JASC-PAL\n0100\n2\n0 0 0\n"),
+      Err(PaletteError::InvalidData)
+    );
+  }
+
+  #[test]
+  fn it_should_round_trip_gimp_palettes() {
+    let palette = create_palette();
+    let text = palette.to_gimp_gpl();
+
+    assert!(text.contains("255 136   0\tff8800"));
+    assert_eq!(ColorPalette::parse(&text).unwrap(), palette);
+    assert_eq!(
+      ColorPalette::from_gimp_gpl("GIMP Palette\n12 x 3\n"),
+      Err(PaletteError::InvalidColor("12 x 3".to_string()))
+    );
+  }
+
+  #[test]
+  fn it_should_round_trip_lospec_palettes() {
+    let palette = create_palette();
+    let text = palette.to_lospec_json();
+
+    assert!(text.contains(r#""colors": ["000000", "ff8800", "ffffff"]"#));
+    assert_eq!(ColorPalette::parse(&text).unwrap(), palette);
+  }
+}
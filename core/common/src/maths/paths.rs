@@ -1,8 +1,12 @@
-use std::{collections::VecDeque, hash::Hash};
+use std::{cmp::Reverse, collections::VecDeque, hash::Hash};
+
+pub use hierarchical::*;
 
 use super::*;
 use crate::collections::{FastHashMap, PriorityQueue};
 
+mod hierarchical;
+
 /// Arbitrary upper limit on the number of steps to use in the find_path
 /// function.
 const MAXIMUM_STEPS: usize = 128;
@@ -35,7 +39,7 @@ pub trait PathFindingGrid<T: Copy + Hash + Eq = IVec2> {
     came_from.insert(start, start);
     cost_so_far.insert(start, 0.);
 
-    frontier.push(start, 0);
+    frontier.push(start, Reverse(0));
 
     let mut neighbours = NeighbourList::new();
 
@@ -80,7 +84,8 @@ pub trait PathFindingGrid<T: Copy + Hash + Eq = IVec2> {
 
           let priority = new_cost + heuristic(neighbour, &goal);
 
-          frontier.push(*neighbour, priority.ceil() as usize);
+          // the queue pops the highest weight first, so reverse it for A*
+          frontier.push(*neighbour, Reverse(priority.ceil() as usize));
         }
       }
     }
@@ -106,6 +111,11 @@ pub mod heuristics {
 
     (dx * dx + dy * dy) as f32
   }
+
+  /// The number of orthogonal steps between two points.
+  pub fn manhattan_distance(from: &IVec2, to: &IVec2) -> Cost {
+    ((to.x - from.x).abs() + (to.y - from.y).abs()) as f32
+  }
 }
 
 #[cfg(test)]
@@ -0,0 +1,482 @@
+//! Hierarchical path-finding (HPA*) for large grids.
+
+use std::{cmp::Ordering, sync::Arc};
+
+use super::*;
+
+/// A pre-computed abstraction of a grid for fast path queries over large maps.
+///
+/// The grid is divided into square clusters. Walkable openings between
+/// neighbouring clusters become 'entrances', and the costs (and paths) between
+/// entrances inside each cluster are cached up-front. A query then only has to
+/// search the start and goal clusters locally, and A* over the much smaller
+/// graph of entrances in between, before stitching the cached paths together.
+///
+/// Paths are near-optimal rather than optimal, and the grid is assumed to have
+/// symmetric costs. Rebuild the abstraction whenever the grid changes.
+pub struct HierarchicalPathFinder {
+  size: IVec2,
+  cluster_size: i32,
+  nodes: Vec<IVec2>,
+  node_lookup: FastHashMap<IVec2, usize>,
+  clusters: FastHashMap<IVec2, Vec<usize>>,
+  edges: Vec<Vec<AbstractEdge>>,
+}
+
+/// A connection between two entrances.
+struct AbstractEdge {
+  to: usize,
+  cost: Cost,
+  /// The steps taken along this edge, excluding the starting point.
+  path: Arc<[IVec2]>,
+}
+
+/// Entrances longer than this get a transition at each end, rather than one
+/// in the middle.
+const LONG_ENTRANCE: usize = 6;
+
+impl HierarchicalPathFinder {
+  /// Builds the abstraction over a grid of the given size.
+  pub fn build(grid: &impl PathFindingGrid, size: UVec2, cluster_size: u32) -> Self {
+    let mut result = Self {
+      size: size.as_ivec2(),
+      cluster_size: cluster_size.max(2) as i32,
+      nodes: Vec::new(),
+      node_lookup: FastHashMap::default(),
+      clusters: FastHashMap::default(),
+      edges: Vec::new(),
+    };
+
+    result.build_entrances(grid);
+    result.build_intra_edges(grid);
+
+    result
+  }
+
+  /// The number of entrance nodes in the abstract graph.
+  pub fn node_count(&self) -> usize {
+    self.nodes.len()
+  }
+
+  /// The cluster containing the given point.
+  pub fn cluster_of(&self, point: IVec2) -> IVec2 {
+    point.div_euclid(IVec2::splat(self.cluster_size))
+  }
+
+  /// Finds a path between two points; the result includes both ends.
+  pub fn find_path(&self, grid: &impl PathFindingGrid, start: IVec2, goal: IVec2) -> Option<VecDeque<IVec2>> {
+    if start == goal {
+      return Some(VecDeque::from([start]));
+    }
+
+    if !self.is_in_bounds(start) || !self.is_in_bounds(goal) {
+      return None;
+    }
+
+    let from_start = self.search_cluster(grid, start);
+    let from_goal = self.search_cluster(grid, goal);
+
+    // links from the start into the graph, and from the graph to the goal
+    let start_links = self.links(&from_start, self.cluster_of(start));
+    let goal_links = self.links(&from_goal, self.cluster_of(goal));
+
+    let direct = match self.cluster_of(start) == self.cluster_of(goal) {
+      true => from_start.cost_to(goal),
+      false => None,
+    };
+
+    let abstract_path = self.search_graph(&start_links, &goal_links);
+
+    let use_direct = match (direct, &abstract_path) {
+      (Some(direct), Some((cost, _))) => direct <= *cost,
+      (Some(_), None) => true,
+      (None, _) => false,
+    };
+
+    if use_direct {
+      return from_start.path_to(goal);
+    }
+
+    let (_, route) = abstract_path?;
+    let mut path = from_start.path_to(self.nodes[route[0]])?;
+
+    for window in route.windows(2) {
+      let edge = self.edges[window[0]].iter().find(|edge| edge.to == window[1])?;
+
+      path.extend(edge.path.iter().copied());
+    }
+
+    // the goal search ran backwards, so walk its path in reverse
+    let mut tail = from_goal.path_to(self.nodes[*route.last()?])?;
+
+    tail.pop_back();
+    path.extend(tail.into_iter().rev());
+
+    Some(path)
+  }
+
+  fn is_in_bounds(&self, point: IVec2) -> bool {
+    point.cmpge(IVec2::ZERO).all() && point.cmplt(self.size).all()
+  }
+
+  fn cluster_bounds(&self, cluster: IVec2) -> (IVec2, IVec2) {
+    let min = cluster * self.cluster_size;
+    let max = (min + IVec2::splat(self.cluster_size - 1)).min(self.size - IVec2::ONE);
+
+    (min, max)
+  }
+
+  fn add_node(&mut self, point: IVec2) -> usize {
+    if let Some(index) = self.node_lookup.get(&point) {
+      return *index;
+    }
+
+    let index = self.nodes.len();
+
+    self.nodes.push(point);
+    self.edges.push(Vec::new());
+    self.node_lookup.insert(point, index);
+    self.clusters.entry(self.cluster_of(point)).or_default().push(index);
+
+    index
+  }
+
+  fn add_edge(&mut self, from: usize, to: usize, cost: Cost, path: Vec<IVec2>) {
+    self.edges[from].push(AbstractEdge {
+      to,
+      cost,
+      path: path.into(),
+    });
+  }
+
+  /// Finds openings along the right and bottom border of every cluster.
+  fn build_entrances(&mut self, grid: &impl PathFindingGrid) {
+    let clusters = (self.size + IVec2::splat(self.cluster_size - 1)) / self.cluster_size;
+
+    for cy in 0..clusters.y {
+      for cx in 0..clusters.x {
+        let (min, max) = self.cluster_bounds(ivec2(cx, cy));
+
+        if max.x + 1 < self.size.x {
+          let border = (min.y..=max.y).map(|y| (ivec2(max.x, y), ivec2(max.x + 1, y)));
+          self.add_entrances(grid, border.collect());
+        }
+
+        if max.y + 1 < self.size.y {
+          let border = (min.x..=max.x).map(|x| (ivec2(x, max.y), ivec2(x, max.y + 1)));
+          self.add_entrances(grid, border.collect());
+        }
+      }
+    }
+  }
+
+  /// Splits a cluster border into runs of open crossings, adding transitions.
+  fn add_entrances(&mut self, grid: &impl PathFindingGrid, border: Vec<(IVec2, IVec2)>) {
+    let mut neighbours = NeighbourList::new();
+    let mut is_connected = |a: IVec2, b: IVec2| {
+      neighbours.clear();
+      grid.get_neighbours(a, &mut neighbours);
+      let forward = neighbours.contains(&b);
+
+      neighbours.clear();
+      grid.get_neighbours(b, &mut neighbours);
+      forward && neighbours.contains(&a)
+    };
+
+    let open = border.iter().map(|(a, b)| is_connected(*a, *b)).collect::<Vec<_>>();
+    let mut index = 0;
+
+    while index < border.len() {
+      if !open[index] {
+        index += 1;
+        continue;
+      }
+
+      let start = index;
+      while index < border.len() && open[index] {
+        index += 1;
+      }
+
+      let run = &border[start..index];
+      let crossings = match run.len() >= LONG_ENTRANCE {
+        true => vec![run[0], run[run.len() - 1]],
+        false => vec![run[run.len() / 2]],
+      };
+
+      for (a, b) in crossings {
+        let node_a = self.add_node(a);
+        let node_b = self.add_node(b);
+
+        self.add_edge(node_a, node_b, grid.get_cost(a, b), vec![b]);
+        self.add_edge(node_b, node_a, grid.get_cost(b, a), vec![a]);
+      }
+    }
+  }
+
+  /// Caches paths between every pair of entrances within each cluster.
+  fn build_intra_edges(&mut self, grid: &impl PathFindingGrid) {
+    let clusters = self.clusters.clone();
+
+    for nodes in clusters.values() {
+      for from in nodes {
+        let search = self.search_cluster(grid, self.nodes[*from]);
+
+        for to in nodes {
+          if from == to {
+            continue;
+          }
+
+          let target = self.nodes[*to];
+
+          if let (Some(cost), Some(mut path)) = (search.cost_to(target), search.path_to(target)) {
+            path.pop_front();
+            self.add_edge(*from, *to, cost, path.into());
+          }
+        }
+      }
+    }
+  }
+
+  /// Runs Dijkstra from a point, without leaving its cluster.
+  fn search_cluster(&self, grid: &impl PathFindingGrid, start: IVec2) -> ClusterSearch {
+    let (min, max) = self.cluster_bounds(self.cluster_of(start));
+
+    let mut frontier = PriorityQueue::new();
+    let mut search = ClusterSearch::default();
+    let mut neighbours = NeighbourList::new();
+
+    search.costs.insert(start, 0.);
+    frontier.push(start, OrderedCost(0.));
+
+    while let Some(current) = frontier.pop() {
+      let cost = search.costs[&current];
+
+      neighbours.clear();
+      grid.get_neighbours(current, &mut neighbours);
+
+      for neighbour in &neighbours {
+        if neighbour.cmplt(min).any() || neighbour.cmpgt(max).any() {
+          continue;
+        }
+
+        let new_cost = cost + grid.get_cost(current, *neighbour);
+
+        if search.costs.get(neighbour).is_none_or(|existing| new_cost < *existing) {
+          search.costs.insert(*neighbour, new_cost);
+          search.came_from.insert(*neighbour, current);
+          frontier.push(*neighbour, OrderedCost(new_cost));
+        }
+      }
+    }
+
+    search.start = start;
+    search
+  }
+
+  /// Converts a local search into links to the entrances of a cluster.
+  fn links(&self, search: &ClusterSearch, cluster: IVec2) -> Vec<(usize, Cost)> {
+    let Some(nodes) = self.clusters.get(&cluster) else {
+      return Vec::new();
+    };
+
+    nodes
+      .iter()
+      .filter_map(|node| Some((*node, search.cost_to(self.nodes[*node])?)))
+      .collect()
+  }
+
+  /// A* over the abstract graph, from the start links to the goal links.
+  fn search_graph(&self, start_links: &[(usize, Cost)], goal_links: &[(usize, Cost)]) -> Option<(Cost, Vec<usize>)> {
+    let goal_costs: FastHashMap<usize, Cost> = goal_links.iter().copied().collect();
+    let goal_points: Vec<IVec2> = goal_links.iter().map(|(node, _)| self.nodes[*node]).collect();
+
+    if goal_points.is_empty() {
+      return None;
+    }
+
+    let heuristic = |node: usize| {
+      let point = self.nodes[node].as_vec2();
+
+      goal_points
+        .iter()
+        .map(|goal| point.distance(goal.as_vec2()))
+        .fold(Cost::MAX, Cost::min)
+    };
+
+    let mut frontier = PriorityQueue::new();
+    let mut costs = FastHashMap::default();
+    let mut came_from = FastHashMap::default();
+
+    for (node, cost) in start_links {
+      costs.insert(*node, *cost);
+      frontier.push(*node, OrderedCost(cost + heuristic(*node)));
+    }
+
+    let mut best: Option<(Cost, usize)> = None;
+
+    while let Some(current) = frontier.pop() {
+      let cost = costs[&current];
+
+      if best.is_some_and(|(best, _)| cost >= best) {
+        continue;
+      }
+
+      if let Some(exit) = goal_costs.get(&current) {
+        if best.is_none_or(|(best, _)| cost + exit < best) {
+          best = Some((cost + exit, current));
+        }
+      }
+
+      for edge in &self.edges[current] {
+        let new_cost = cost + edge.cost;
+
+        if costs.get(&edge.to).is_none_or(|existing| new_cost < *existing) {
+          costs.insert(edge.to, new_cost);
+          came_from.insert(edge.to, current);
+          frontier.push(edge.to, OrderedCost(new_cost + heuristic(edge.to)));
+        }
+      }
+    }
+
+    let (cost, mut current) = best?;
+    let mut route = vec![current];
+
+    while let Some(previous) = came_from.get(&current) {
+      current = *previous;
+      route.push(current);
+    }
+
+    route.reverse();
+
+    Some((cost, route))
+  }
+}
+
+/// The result of a search bounded to a single cluster.
+#[derive(Default)]
+struct ClusterSearch {
+  start: IVec2,
+  costs: FastHashMap<IVec2, Cost>,
+  came_from: FastHashMap<IVec2, IVec2>,
+}
+
+impl ClusterSearch {
+  fn cost_to(&self, point: IVec2) -> Option<Cost> {
+    self.costs.get(&point).copied()
+  }
+
+  fn path_to(&self, point: IVec2) -> Option<VecDeque<IVec2>> {
+    self.costs.get(&point)?;
+
+    let mut path = VecDeque::from([point]);
+    let mut current = point;
+
+    while current != self.start {
+      current = self.came_from[&current];
+      path.push_front(current);
+    }
+
+    Some(path)
+  }
+}
+
+/// A cost ordered so the cheapest pops first from a [`PriorityQueue`].
+#[derive(Copy, Clone, PartialEq)]
+struct OrderedCost(Cost);
+
+impl Eq for OrderedCost {}
+
+impl PartialOrd for OrderedCost {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for OrderedCost {
+  fn cmp(&self, other: &Self) -> Ordering {
+    other.0.total_cmp(&self.0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::collections::DenseGrid;
+
+  struct Walls(DenseGrid<bool>);
+
+  impl PathFindingGrid for Walls {
+    fn get_neighbours(&self, center: IVec2, results: &mut NeighbourList<IVec2>) {
+      for neighbour in center.adjacent_neighbours() {
+        if self.0.get(neighbour.x, neighbour.y).is_some_and(|open| *open) {
+          results.push(neighbour);
+        }
+      }
+    }
+  }
+
+  /// A 64x64 map with a wall down the middle and a single gap near the bottom.
+  fn create_map() -> Walls {
+    let mut grid = DenseGrid::new(64, 64);
+
+    grid.fill(true);
+
+    for y in 0..60 {
+      grid.set(32, y, false);
+    }
+
+    Walls(grid)
+  }
+
+  fn assert_contiguous(grid: &Walls, path: &VecDeque<IVec2>) {
+    for window in path.iter().collect::<Vec<_>>().windows(2) {
+      let mut neighbours = NeighbourList::new();
+      grid.get_neighbours(*window[0], &mut neighbours);
+
+      assert!(neighbours.contains(window[1]), "{} -> {}", window[0], window[1]);
+    }
+  }
+
+  #[test]
+  fn it_should_find_paths_across_clusters() {
+    let grid = create_map();
+    let finder = HierarchicalPathFinder::build(&grid, uvec2(64, 64), 8);
+
+    let start = ivec2(2, 2);
+    let goal = ivec2(60, 2);
+    let path = finder.find_path(&grid, start, goal).unwrap();
+
+    assert_eq!(path.front(), Some(&start));
+    assert_eq!(path.back(), Some(&goal));
+    assert!(path.contains(&ivec2(32, 61)) || path.contains(&ivec2(32, 62)) || path.contains(&ivec2(32, 63)));
+    assert_contiguous(&grid, &path);
+
+    // the optimal route is 58 across plus 59 down and back up
+    assert!(path.len() - 1 <= 58 + 59 * 2 + 8);
+  }
+
+  #[test]
+  fn it_should_find_paths_within_a_cluster() {
+    let grid = create_map();
+    let finder = HierarchicalPathFinder::build(&grid, uvec2(64, 64), 8);
+
+    let path = finder.find_path(&grid, ivec2(1, 1), ivec2(4, 3)).unwrap();
+
+    assert_eq!(path.len(), 6);
+    assert_contiguous(&grid, &path);
+  }
+
+  #[test]
+  fn it_should_fail_for_unreachable_goals() {
+    let mut grid = create_map();
+
+    for y in 60..64 {
+      grid.0.set(32, y, false);
+    }
+
+    let finder = HierarchicalPathFinder::build(&grid, uvec2(64, 64), 8);
+
+    assert!(finder.node_count() > 0);
+    assert!(finder.find_path(&grid, ivec2(2, 2), ivec2(60, 2)).is_none());
+  }
+}
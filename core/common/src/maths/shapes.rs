@@ -32,7 +32,7 @@ impl Lerp for Circle {
 }
 
 /// A sphere in 3-space.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Sphere {
   pub radius: f32,
   pub center: Vec3,
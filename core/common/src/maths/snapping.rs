@@ -0,0 +1,179 @@
+//! Snapping of positions and rotations, for editor tools and gameplay.
+//!
+//! Grid snapping rounds to the nearest cell corner, angle snapping rounds to
+//! the nearest increment, and vertex snapping pulls a point onto nearby
+//! geometry. [`SnapSettings`] combines the three the way placement tools use
+//! them: a nearby vertex wins, otherwise the grid applies.
+
+use super::*;
+
+/// Snaps a point to the nearest corner of a grid with the given cell size.
+///
+/// Axes with a cell size of zero are left alone.
+#[inline]
+pub fn snap_to_grid(point: Vec3, cell_size: Vec3) -> Vec3 {
+  Vec3::select(
+    cell_size.cmpgt(Vec3::ZERO),
+    (point / cell_size).round() * cell_size,
+    point,
+  )
+}
+
+/// Snaps a 2D point to the nearest corner of a grid with the given cell size.
+///
+/// Axes with a cell size of zero are left alone.
+#[inline]
+pub fn snap_to_grid_2d(point: Vec2, cell_size: Vec2) -> Vec2 {
+  snap_to_grid(point.extend(0.), cell_size.extend(0.)).truncate()
+}
+
+/// Finds the cell of a grid with the given cell size that contains a point,
+/// like the tile under the cursor.
+#[inline]
+pub fn grid_cell(point: Vec2, cell_size: Vec2) -> IVec2 {
+  (point / cell_size).floor().as_ivec2()
+}
+
+/// Snaps an angle, in radians, to the nearest multiple of the increment.
+///
+/// An increment of zero leaves the angle alone.
+#[inline]
+pub fn snap_angle(angle: f32, increment: f32) -> f32 {
+  match increment > 0. {
+    true => (angle / increment).round() * increment,
+    false => angle,
+  }
+}
+
+/// Finds the vertex nearest to a point, if any is within the given radius.
+pub fn snap_to_vertex(point: Vec3, vertices: impl IntoIterator<Item = Vec3>, radius: f32) -> Option<Vec3> {
+  vertices
+    .into_iter()
+    .map(|vertex| (vertex, vertex.distance_squared(point)))
+    .filter(|(_, distance)| *distance <= radius * radius)
+    .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    .map(|(vertex, _)| vertex)
+}
+
+/// Settings for snapping placed and transformed objects.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapSettings {
+  /// Whether snapping is applied at all.
+  pub enabled: bool,
+  /// The size of a grid cell on each axis; zero disables an axis.
+  pub grid_size: Vec3,
+  /// The increment rotations snap to, in radians; zero disables it.
+  pub angle_increment: f32,
+  /// How close a vertex must be to snap to it; zero disables it.
+  pub vertex_radius: f32,
+}
+
+impl Default for SnapSettings {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      grid_size: Vec3::ONE,
+      angle_increment: 15f32.to_radians(),
+      vertex_radius: 0.25,
+    }
+  }
+}
+
+impl SnapSettings {
+  /// Snaps a position to a nearby vertex if there is one, otherwise to the
+  /// grid.
+  pub fn snap_position(&self, point: Vec3, vertices: impl IntoIterator<Item = Vec3>) -> Vec3 {
+    if !self.enabled {
+      return point;
+    }
+
+    if self.vertex_radius > 0. {
+      if let Some(vertex) = snap_to_vertex(point, vertices, self.vertex_radius) {
+        return vertex;
+      }
+    }
+
+    snap_to_grid(point, self.grid_size)
+  }
+
+  /// Snaps a 2D position to the grid.
+  pub fn snap_position_2d(&self, point: Vec2) -> Vec2 {
+    match self.enabled {
+      true => snap_to_grid_2d(point, self.grid_size.truncate()),
+      false => point,
+    }
+  }
+
+  /// Snaps an angle, in radians.
+  pub fn snap_angle(&self, angle: f32) -> f32 {
+    match self.enabled {
+      true => snap_angle(angle, self.angle_increment),
+      false => angle,
+    }
+  }
+
+  /// Snaps a rotation to the angle increment about each axis.
+  pub fn snap_rotation(&self, rotation: Quat) -> Quat {
+    if !self.enabled || self.angle_increment <= 0. {
+      return rotation;
+    }
+
+    let (x, y, z) = rotation.to_euler(EulerRot::XYZ);
+
+    Quat::from_euler(
+      EulerRot::XYZ,
+      self.snap_angle(x),
+      self.snap_angle(y),
+      self.snap_angle(z),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::f32::consts::FRAC_PI_2;
+
+  use super::*;
+
+  #[test]
+  fn it_should_snap_to_grids() {
+    assert_eq!(
+      snap_to_grid(vec3(1.2, -0.7, 3.3), Vec3::splat(0.5)),
+      vec3(1., -0.5, 3.5)
+    );
+    assert_eq!(snap_to_grid(vec3(1.2, 1.2, 1.2), vec3(1., 0., 2.)), vec3(1., 1.2, 2.));
+    assert_eq!(snap_to_grid_2d(vec2(15., 17.), vec2(16., 16.)), vec2(16., 16.));
+    assert_eq!(grid_cell(vec2(-0.5, 33.), vec2(16., 16.)), ivec2(-1, 2));
+  }
+
+  #[test]
+  fn it_should_snap_angles_and_rotations() {
+    assert!((snap_angle(0.8, FRAC_PI_2 / 2.) - FRAC_PI_2 / 2.).abs() < 1e-6);
+    assert_eq!(snap_angle(0.8, 0.), 0.8);
+
+    let settings = SnapSettings {
+      angle_increment: FRAC_PI_2,
+      ..Default::default()
+    };
+
+    let snapped = settings.snap_rotation(Quat::from_rotation_z(1.4));
+
+    assert!(snapped.angle_between(Quat::from_rotation_z(FRAC_PI_2)) < 1e-4);
+  }
+
+  #[test]
+  fn it_should_prefer_nearby_vertices_over_the_grid() {
+    let settings = SnapSettings::default();
+    let vertices = [vec3(0.4, 0.1, 0.), vec3(3., 3., 3.)];
+
+    assert_eq!(settings.snap_position(vec3(0.3, 0.2, 0.), vertices), vertices[0]);
+    assert_eq!(settings.snap_position(vec3(1.8, 0.2, 0.), vertices), vec3(2., 0., 0.));
+
+    let disabled = SnapSettings {
+      enabled: false,
+      ..settings
+    };
+
+    assert_eq!(disabled.snap_position(vec3(1.8, 0.2, 0.), vertices), vec3(1.8, 0.2, 0.));
+  }
+}
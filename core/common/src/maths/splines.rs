@@ -1,3 +1,5 @@
+use super::*;
+
 /// A spline is a curve defined by control points.
 pub trait Spline {
   /// Returns the value of the spline at the given time.
@@ -62,3 +64,172 @@ impl Spline for CatmulRomSpline {
     (value_plus_epsilon - value_minus_epsilon) / (2.0 * epsilon)
   }
 }
+
+/// A Catmull-Rom spline through points in 3-space, measured by distance
+/// along it so things can move along it at an even speed.
+#[derive(Default, Clone, Debug)]
+pub struct SplinePath {
+  points: Vec<Vec3>,
+  closed: bool,
+  /// The distance along the path at evenly spaced spline parameters.
+  distances: Vec<f32>,
+}
+
+/// The number of samples per segment used to measure distance.
+const SAMPLES_PER_SEGMENT: usize = 16;
+
+impl SplinePath {
+  /// Creates a path through the given points, joining the last back to the
+  /// first if it's closed.
+  pub fn new(points: Vec<Vec3>, closed: bool) -> Self {
+    let mut path = Self {
+      points,
+      closed,
+      distances: vec![0.],
+    };
+
+    let samples = path.segments() * SAMPLES_PER_SEGMENT;
+    let mut previous = path.evaluate(0.);
+
+    for sample in 1..=samples {
+      let point = path.evaluate(sample as f32 / SAMPLES_PER_SEGMENT as f32);
+      let distance = path.distances[sample - 1] + point.distance(previous);
+
+      path.distances.push(distance);
+      previous = point;
+    }
+
+    path
+  }
+
+  /// The points the path passes through.
+  pub fn points(&self) -> &[Vec3] {
+    &self.points
+  }
+
+  /// Determines if the path joins its last point back to its first.
+  pub fn is_closed(&self) -> bool {
+    self.closed
+  }
+
+  /// The number of curved segments between points.
+  pub fn segments(&self) -> usize {
+    match (self.points.len(), self.closed) {
+      (0 | 1, _) => 0,
+      (count, true) => count,
+      (count, false) => count - 1,
+    }
+  }
+
+  /// The length of the path.
+  pub fn length(&self) -> f32 {
+    self.distances.last().copied().unwrap_or_default()
+  }
+
+  /// The distance along the path to the given point.
+  pub fn distance_to_point(&self, index: usize) -> f32 {
+    self
+      .distances
+      .get(index * SAMPLES_PER_SEGMENT)
+      .copied()
+      .unwrap_or_else(|| self.length())
+  }
+
+  /// The position at a distance along the path.
+  pub fn point_at(&self, distance: f32) -> Vec3 {
+    self.evaluate(self.parameter_at(distance))
+  }
+
+  /// The direction of the path at a distance along it.
+  pub fn tangent_at(&self, distance: f32) -> Vec3 {
+    let parameter = self.parameter_at(distance);
+    let epsilon = 0.001;
+    let before = self.evaluate((parameter - epsilon).max(0.));
+    let after = self.evaluate((parameter + epsilon).min(self.segments() as f32));
+
+    (after - before).normalize_or_zero()
+  }
+
+  /// Finds the spline parameter, from 0 to the number of segments, at a
+  /// distance along the path.
+  fn parameter_at(&self, distance: f32) -> f32 {
+    if self.distances.len() < 2 {
+      return 0.;
+    }
+
+    let distance = distance.clamp(0., self.length());
+    let index = self
+      .distances
+      .partition_point(|it| *it < distance)
+      .max(1)
+      .min(self.distances.len() - 1);
+
+    let start = self.distances[index - 1];
+    let span = self.distances[index] - start;
+    let blend = if span > 0. { (distance - start) / span } else { 0. };
+
+    (index - 1) as f32 / SAMPLES_PER_SEGMENT as f32 + blend / SAMPLES_PER_SEGMENT as f32
+  }
+
+  /// The position at a spline parameter, from 0 to the number of segments.
+  fn evaluate(&self, t: f32) -> Vec3 {
+    let count = self.points.len();
+
+    match count {
+      0 => return Vec3::ZERO,
+      1 => return self.points[0],
+      _ => {}
+    }
+
+    let segment = (t.max(0.) as usize).min(self.segments() - 1);
+    let t = t - segment as f32;
+
+    let point = |index: isize| match self.closed {
+      true => self.points[index.rem_euclid(count as isize) as usize],
+      false => self.points[index.max(0).min(count as isize - 1) as usize],
+    };
+
+    let p0 = point(segment as isize - 1);
+    let p1 = point(segment as isize);
+    let p2 = point(segment as isize + 1);
+    let p3 = point(segment as isize + 2);
+
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    let m0 = -0.5 * t3 + t2 - 0.5 * t;
+    let m1 = 1.5 * t3 - 2.5 * t2 + 1.0;
+    let m2 = -1.5 * t3 + 2.0 * t2 + 0.5 * t;
+    let m3 = 0.5 * t3 - 0.5 * t2;
+
+    p0 * m0 + p1 * m1 + p2 * m2 + p3 * m3
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_measure_paths_by_distance() {
+    let path = SplinePath::new(vec![vec3(0., 0., 0.), vec3(10., 0., 0.), vec3(20., 0., 0.)], false);
+
+    assert!((path.length() - 20.).abs() < 1e-3);
+    assert!((path.distance_to_point(1) - 10.).abs() < 1e-3);
+    assert!(path.point_at(5.).distance(vec3(5., 0., 0.)) < 1e-2);
+    assert!(path.tangent_at(15.).distance(Vec3::X) < 1e-3);
+
+    let square = SplinePath::new(
+      vec![
+        vec3(0., 0., 0.),
+        vec3(10., 0., 0.),
+        vec3(10., 10., 0.),
+        vec3(0., 10., 0.),
+      ],
+      true,
+    );
+
+    assert_eq!(square.segments(), 4);
+    assert!(square.point_at(square.length()).distance(Vec3::ZERO) < 1e-3);
+  }
+}
@@ -0,0 +1,204 @@
+//! Frame-rate independent smoothing with springs.
+//!
+//! Springs here are critically damped: they close in on their target as fast
+//! as possible without overshooting. They're integrated exactly rather than
+//! stepped, so a spring updated at 30 FPS follows the same path as one updated
+//! at 240 FPS. That makes them a good fit for camera follow, UI animation and
+//! procedural animation alike.
+//!
+//! Stiffness is given as a half-life: roughly how long, in seconds, it takes
+//! to close half the distance to the target.
+
+use std::ops::{Add, Mul, Sub};
+
+use super::*;
+
+/// Converts a half-life to the decay rate of a critically damped spring.
+#[inline]
+fn half_life_to_decay(half_life: f32) -> f32 {
+  2. * std::f32::consts::LN_2 / half_life.max(f32::EPSILON)
+}
+
+/// Moves a value towards a target, closing half the distance every
+/// `half_life` seconds.
+///
+/// This is the frame-rate independent way to write `lerp(a, b, 0.1)` each
+/// frame.
+#[inline]
+pub fn damp<T: Lerp>(current: T, target: T, half_life: f32, delta_time: f32) -> T {
+  let t = 1. - (-std::f32::consts::LN_2 * delta_time / half_life.max(f32::EPSILON)).exp();
+
+  T::lerp(current, target, t)
+}
+
+/// A value that can be smoothed by a [`Spring`].
+pub trait SpringValue: Identity + Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self> {}
+
+impl<T: Identity + Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>> SpringValue for T {}
+
+/// A critically damped spring that smooths a value towards a target.
+#[derive(Copy, Clone, Debug)]
+pub struct Spring<T> {
+  pub value: T,
+  pub velocity: T,
+  pub half_life: f32,
+}
+
+impl<T: SpringValue> Spring<T> {
+  /// Creates a spring at rest at the given value.
+  pub fn new(value: T, half_life: f32) -> Self {
+    Self {
+      value,
+      velocity: T::ZERO,
+      half_life,
+    }
+  }
+
+  /// Moves the spring towards the target, returning the new value.
+  pub fn update(&mut self, target: T, delta_time: f32) -> T {
+    let decay = half_life_to_decay(self.half_life);
+
+    let offset = self.value - target;
+    let impulse = self.velocity + offset * decay;
+    let falloff = (-decay * delta_time).exp();
+
+    self.value = target + (offset + impulse * delta_time) * falloff;
+    self.velocity = (self.velocity - impulse * (decay * delta_time)) * falloff;
+
+    self.value
+  }
+
+  /// Snaps the spring to a value, at rest.
+  pub fn reset(&mut self, value: T) {
+    self.value = value;
+    self.velocity = T::ZERO;
+  }
+}
+
+/// A critically damped spring that smooths a rotation towards a target.
+///
+/// Rotations take the shortest path, and velocity is angular, in radians per
+/// second about each axis.
+#[derive(Copy, Clone, Debug)]
+pub struct QuatSpring {
+  pub value: Quat,
+  pub velocity: Vec3,
+  pub half_life: f32,
+}
+
+impl QuatSpring {
+  /// Creates a spring at rest at the given rotation.
+  pub fn new(value: Quat, half_life: f32) -> Self {
+    Self {
+      value,
+      velocity: Vec3::ZERO,
+      half_life,
+    }
+  }
+
+  /// Moves the spring towards the target, returning the new rotation.
+  pub fn update(&mut self, target: Quat, delta_time: f32) -> Quat {
+    let decay = half_life_to_decay(self.half_life);
+
+    // the same as a vector spring, on the rotation from the target
+    let mut difference = self.value * target.inverse();
+
+    if difference.w < 0. {
+      difference = -difference;
+    }
+
+    let offset = difference.to_scaled_axis();
+    let impulse = self.velocity + offset * decay;
+    let falloff = (-decay * delta_time).exp();
+
+    self.value = (Quat::from_scaled_axis((offset + impulse * delta_time) * falloff) * target).normalize();
+    self.velocity = (self.velocity - impulse * (decay * delta_time)) * falloff;
+
+    self.value
+  }
+
+  /// Snaps the spring to a rotation, at rest.
+  pub fn reset(&mut self, value: Quat) {
+    self.value = value;
+    self.velocity = Vec3::ZERO;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Runs a spring for a second at the given frame rate.
+  fn simulate<T: SpringValue>(spring: &mut Spring<T>, target: T, frames_per_second: u32) -> T {
+    for _ in 0..frames_per_second {
+      spring.update(target, 1. / frames_per_second as f32);
+    }
+
+    spring.value
+  }
+
+  #[test]
+  fn it_should_damp_independently_of_frame_rate() {
+    let mut slow = 0.;
+    let mut fast = 0.;
+
+    for _ in 0..30 {
+      slow = damp(slow, 10., 0.25, 1. / 30.);
+    }
+
+    for _ in 0..240 {
+      fast = damp(fast, 10., 0.25, 1. / 240.);
+    }
+
+    // four half-lives leave a sixteenth of the distance
+    assert!((slow - 9.375f32).abs() < 1e-3);
+    assert!((slow - fast).abs() < 1e-3);
+  }
+
+  #[test]
+  fn it_should_follow_the_same_path_at_any_frame_rate() {
+    let mut slow = Spring::new(vec3(0., 0., 0.), 0.1);
+    let mut fast = slow;
+
+    slow.velocity = vec3(5., 0., 0.);
+    fast.velocity = slow.velocity;
+
+    let slow = simulate(&mut slow, vec3(1., 2., 3.), 30);
+    let fast = simulate(&mut fast, vec3(1., 2., 3.), 240);
+
+    assert!(slow.distance(fast) < 1e-4);
+    assert!(slow.distance(vec3(1., 2., 3.)) < 0.01);
+  }
+
+  #[test]
+  fn it_should_not_overshoot_from_rest() {
+    let mut spring = Spring::new(0., 0.1);
+
+    for _ in 0..100 {
+      let value = spring.update(1., 1. / 60.);
+
+      assert!(value <= 1.);
+    }
+
+    assert!((spring.value - 1.).abs() < 1e-3);
+  }
+
+  #[test]
+  fn it_should_smooth_rotations() {
+    let target = Quat::from_rotation_y(2.);
+
+    let mut slow = QuatSpring::new(Quat::from_rotation_y(-2.), 0.2);
+    let mut fast = slow;
+
+    for _ in 0..30 {
+      slow.update(target, 1. / 30.);
+    }
+
+    for _ in 0..240 {
+      fast.update(target, 1. / 240.);
+    }
+
+    assert!(slow.value.angle_between(fast.value) < 1e-3);
+    assert!(slow.value.angle_between(target) < 0.02);
+  }
+}
@@ -2,10 +2,14 @@
 
 pub use clocks::*;
 pub use counters::*;
+pub use pacing::*;
+pub use service::*;
 pub use spans::*;
 pub use stamps::*;
 
 mod clocks;
 mod counters;
+mod pacing;
+mod service;
 mod spans;
 mod stamps;
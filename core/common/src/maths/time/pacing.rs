@@ -0,0 +1,337 @@
+use std::time::{Duration, Instant};
+
+use super::TimeSpan;
+use crate::{Profiler, RingBuffer};
+
+/// Refresh rates that are commonly reported by displays, in Hz.
+const COMMON_REFRESH_RATES: [f32; 10] = [30., 48., 50., 60., 75., 90., 120., 144., 165., 240.];
+
+/// Settings for a [`FramePacer`].
+#[derive(Clone, Debug)]
+pub struct FramePacerSettings {
+  /// The maximum number of frames per second, or `None` for no cap.
+  pub target_fps: Option<f32>,
+  /// How far ahead of the deadline to stop sleeping and start spinning.
+  ///
+  /// OS sleeps are coarse; spinning for the last stretch keeps frame times
+  /// consistent at the cost of a little CPU.
+  pub spin_threshold: TimeSpan,
+  /// The largest delta time that will be reported, in seconds.
+  pub max_delta_time: f32,
+  /// The number of frames to average delta times over.
+  pub smoothing_samples: usize,
+  /// How many times longer than expected a frame must take to be a hitch.
+  pub hitch_threshold: f32,
+}
+
+impl Default for FramePacerSettings {
+  fn default() -> Self {
+    Self {
+      target_fps: None,
+      spin_threshold: TimeSpan::from_millis(2.),
+      max_delta_time: 0.25,
+      smoothing_samples: 4,
+      hitch_threshold: 2.,
+    }
+  }
+}
+
+/// Timing information for a single frame, produced by a [`FramePacer`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameTiming {
+  /// The index of the frame, starting from 1.
+  pub frame: u64,
+  /// The smoothed and clamped delta time, in seconds.
+  pub delta_time: f32,
+  /// The actual time since the last frame, in seconds.
+  pub raw_delta_time: f32,
+  /// The hitch observed on this frame, if any.
+  pub hitch: Option<FrameHitch>,
+}
+
+/// A frame that took much longer than expected.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameHitch {
+  /// The index of the frame that hitched.
+  pub frame: u64,
+  /// How long the frame actually took.
+  pub duration: TimeSpan,
+  /// How long the frame was expected to take.
+  pub expected: TimeSpan,
+}
+
+/// Paces the main loop.
+///
+/// The pacer caps the frame rate (sleeping, then spinning, until the next
+/// frame is due), smooths and clamps delta times so a single slow frame
+/// doesn't launch objects across the world, detects the display's vsync
+/// interval and reports hitches to an optional [`Profiler`].
+pub struct FramePacer {
+  settings: FramePacerSettings,
+  frame: u64,
+  last_frame: Instant,
+  deltas: RingBuffer<f32>,
+  intervals: RingBuffer<f32>,
+  profiler: Option<Box<dyn Profiler>>,
+}
+
+impl Default for FramePacer {
+  fn default() -> Self {
+    Self::new(FramePacerSettings::default())
+  }
+}
+
+impl FramePacer {
+  /// Creates a new frame pacer with the given settings.
+  ///
+  /// A target frame rate that isn't positive removes the cap, as with
+  /// [`FramePacer::set_target_fps`].
+  pub fn new(mut settings: FramePacerSettings) -> Self {
+    settings.target_fps = settings.target_fps.filter(|fps| *fps > 0.);
+
+    Self {
+      deltas: RingBuffer::new(settings.smoothing_samples.max(1)),
+      intervals: RingBuffer::new(60),
+      settings,
+      frame: 0,
+      last_frame: Instant::now(),
+      profiler: None,
+    }
+  }
+
+  /// The settings of the pacer.
+  pub fn settings(&self) -> &FramePacerSettings {
+    &self.settings
+  }
+
+  /// Changes the frame rate cap, or removes it with `None`.
+  pub fn set_target_fps(&mut self, target_fps: Option<f32>) {
+    self.settings.target_fps = target_fps.filter(|fps| *fps > 0.);
+  }
+
+  /// Sets the profiler that hitches are reported to.
+  pub fn set_profiler(&mut self, profiler: impl Profiler + 'static) {
+    self.profiler = Some(Box::new(profiler));
+  }
+
+  /// Waits until the next frame is due, then measures it.
+  ///
+  /// Call this once at the top of the main loop.
+  pub fn tick(&mut self) -> FrameTiming {
+    // measure the work done before waiting, so we can see the vsync interval
+    let work_time = self.last_frame.elapsed().as_secs_f32();
+
+    if let Some(target_fps) = self.settings.target_fps {
+      let deadline = self.last_frame + Duration::from_secs_f32(1. / target_fps);
+
+      wait_until(deadline, self.settings.spin_threshold.into());
+    }
+
+    let now = Instant::now();
+    let raw_delta_time = (now - self.last_frame).as_secs_f32();
+
+    self.last_frame = now;
+    self.intervals.push(work_time);
+
+    self.advance(raw_delta_time)
+  }
+
+  /// Advances the pacer by a frame of the given length, without waiting.
+  ///
+  /// This is useful when the frame time comes from elsewhere, such as a
+  /// platform layer that already paces presentation.
+  pub fn advance(&mut self, raw_delta_time: f32) -> FrameTiming {
+    let expected = self.expected_frame_time();
+
+    self.frame += 1;
+    self.deltas.push(raw_delta_time.clamp(0., self.settings.max_delta_time));
+
+    let hitch = expected
+      .filter(|expected| raw_delta_time > expected * self.settings.hitch_threshold)
+      .map(|expected| FrameHitch {
+        frame: self.frame,
+        duration: TimeSpan::from_seconds(raw_delta_time),
+        expected: TimeSpan::from_seconds(expected),
+      });
+
+    if let (Some(hitch), Some(profiler)) = (&hitch, &mut self.profiler) {
+      profiler.frame_hitch(hitch);
+    }
+
+    FrameTiming {
+      frame: self.frame,
+      delta_time: self.smoothed_delta_time(),
+      raw_delta_time,
+      hitch,
+    }
+  }
+
+  /// The smoothed delta time over recent frames, in seconds.
+  pub fn smoothed_delta_time(&self) -> f32 {
+    average(&self.deltas).unwrap_or(0.)
+  }
+
+  /// The refresh rate of the display, in Hz, if vsync appears to be active.
+  ///
+  /// Detection relies on the display being the bottleneck, so it's only
+  /// reliable when the frame rate cap (if any) is above the refresh rate.
+  pub fn detected_refresh_rate(&self) -> Option<f32> {
+    let mut samples = self.intervals.iter().copied().collect::<Vec<_>>();
+
+    if samples.len() < self.intervals.len() {
+      return None;
+    }
+
+    samples.sort_by(f32::total_cmp);
+
+    let median = samples[samples.len() / 2];
+    if median <= 0. {
+      return None;
+    }
+
+    // vsync'd frames are consistent; anything too noisy isn't being paced
+    let consistent = samples
+      .iter()
+      .filter(|sample| (*sample - median).abs() <= median * 0.1)
+      .count();
+
+    if consistent * 10 < samples.len() * 8 {
+      return None;
+    }
+
+    let rate = 1. / median;
+
+    COMMON_REFRESH_RATES
+      .iter()
+      .copied()
+      .find(|common| (rate - common).abs() <= common * 0.05)
+  }
+
+  /// How long the next frame is expected to take, in seconds.
+  fn expected_frame_time(&self) -> Option<f32> {
+    match self.settings.target_fps {
+      Some(target_fps) => Some(1. / target_fps),
+      None => average(&self.deltas),
+    }
+  }
+}
+
+/// Averages the samples in the given buffer.
+fn average(samples: &RingBuffer<f32>) -> Option<f32> {
+  let (total, count) = samples
+    .iter()
+    .fold((0., 0), |(total, count), sample| (total + sample, count + 1));
+
+  if count > 0 {
+    Some(total / count as f32)
+  } else {
+    None
+  }
+}
+
+/// Sleeps until shortly before the deadline, then spins until it's reached.
+fn wait_until(deadline: Instant, spin_threshold: Duration) {
+  loop {
+    let now = Instant::now();
+    if now >= deadline {
+      break;
+    }
+
+    let remaining = deadline - now;
+
+    if remaining > spin_threshold {
+      std::thread::sleep(remaining - spin_threshold);
+    } else {
+      std::hint::spin_loop();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, rc::Rc};
+
+  use super::*;
+  use crate::ApproxEq;
+
+  #[test]
+  fn it_should_clamp_and_smooth_delta_times() {
+    let mut pacer = FramePacer::new(FramePacerSettings {
+      max_delta_time: 0.1,
+      smoothing_samples: 2,
+      ..Default::default()
+    });
+
+    assert!(pacer.advance(0.02).delta_time.approx_eq(0.02));
+    assert!(pacer.advance(0.04).delta_time.approx_eq(0.03));
+    assert!(pacer.advance(5.0).delta_time.approx_eq(0.07));
+  }
+
+  #[test]
+  fn it_should_report_hitches_to_the_profiler() {
+    struct TestProfiler(Rc<RefCell<Vec<FrameHitch>>>);
+
+    impl Profiler for TestProfiler {
+      fn frame_hitch(&mut self, hitch: &FrameHitch) {
+        self.0.borrow_mut().push(*hitch);
+      }
+    }
+
+    let hitches = Rc::new(RefCell::new(Vec::new()));
+    let mut pacer = FramePacer::default();
+
+    pacer.set_target_fps(Some(100.));
+    pacer.set_profiler(TestProfiler(hitches.clone()));
+
+    assert!(pacer.advance(0.01).hitch.is_none());
+    assert!(pacer.advance(0.05).hitch.is_some());
+
+    assert_eq!(hitches.borrow().len(), 1);
+    assert_eq!(hitches.borrow()[0].frame, 2);
+  }
+
+  #[test]
+  fn it_should_cap_the_frame_rate() {
+    let mut pacer = FramePacer::new(FramePacerSettings {
+      target_fps: Some(200.),
+      ..Default::default()
+    });
+
+    pacer.tick();
+
+    let start = Instant::now();
+
+    for _ in 0..4 {
+      pacer.tick();
+    }
+
+    assert!(start.elapsed() >= Duration::from_millis(19));
+  }
+
+  #[test]
+  fn it_should_ignore_frame_rates_that_are_not_positive() {
+    for target_fps in [0., -60., f32::NAN] {
+      let mut pacer = FramePacer::new(FramePacerSettings {
+        target_fps: Some(target_fps),
+        ..Default::default()
+      });
+
+      assert_eq!(pacer.settings().target_fps, None);
+
+      pacer.tick();
+    }
+  }
+
+  #[test]
+  fn it_should_detect_common_refresh_rates() {
+    let mut pacer = FramePacer::default();
+
+    assert_eq!(pacer.detected_refresh_rate(), None);
+
+    for _ in 0..60 {
+      pacer.intervals.push(1. / 59.9);
+    }
+
+    assert_eq!(pacer.detected_refresh_rate(), Some(60.));
+  }
+}
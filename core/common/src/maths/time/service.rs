@@ -0,0 +1,488 @@
+use crate::{FastHashMap, FrameCounter, StringName, TimeSpan};
+
+/// The slowest audio will play when following the game clock.
+const MIN_PITCH_SCALE: f32 = 0.1;
+
+/// The engine's view of time.
+///
+/// [`Time`] keeps two clocks side by side: an unscaled clock that follows real
+/// time, and a game clock that can be slowed down, sped up or paused. It also
+/// exposes the fixed-update accumulator so physics and other simulation can
+/// step at a constant rate, and lets individual systems (such as UI) opt out of
+/// the global time scale or pause via named [`TimeDomain`]s.
+///
+/// On top of the time scale, short [`TimeDilation`]s (slow-motion, hit-stops)
+/// can be layered; these elapse in real time and scale everything that reads
+/// the game clock, including fixed steps and [`Time::pitch_scale`].
+pub struct Time {
+  frame: u64,
+  fixed_frame: u64,
+  paused: bool,
+  time_scale: f32,
+  dilation: f32,
+  dilations: Vec<ActiveDilation>,
+  unscaled_delta_time: f32,
+  unscaled_total_time: f64,
+  delta_time: f32,
+  total_time: f64,
+  fixed_delta_time: f32,
+  fixed_accumulator: f32,
+  max_fixed_steps: u32,
+  domains: FastHashMap<StringName, TimeDomain>,
+  frame_counter: FrameCounter,
+}
+
+/// Overrides how time flows for a particular system.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeDomain {
+  /// An additional scale applied on top of the clock.
+  pub scale: f32,
+  /// Keeps the domain running while the game clock is paused.
+  pub ignore_pause: bool,
+  /// Uses the unscaled clock rather than the game clock.
+  pub ignore_time_scale: bool,
+}
+
+impl Default for TimeDomain {
+  fn default() -> Self {
+    Self {
+      scale: 1.,
+      ignore_pause: false,
+      ignore_time_scale: false,
+    }
+  }
+}
+
+impl TimeDomain {
+  /// A domain that runs in real time, regardless of pause or time scale.
+  pub const REAL_TIME: Self = Self {
+    scale: 1.,
+    ignore_pause: true,
+    ignore_time_scale: true,
+  };
+}
+
+/// A temporary change to the speed of the game clock.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeDilation {
+  /// The scale applied to the game clock while the dilation is in effect.
+  pub scale: f32,
+  /// How long the dilation lasts, in real time.
+  pub duration: TimeSpan,
+  /// How long the end of the dilation takes to ease back to normal speed, in
+  /// real time; this is part of the duration.
+  pub blend_out: TimeSpan,
+}
+
+impl TimeDilation {
+  /// Freezes the game clock for a moment, e.g. to sell the weight of a hit.
+  pub fn hit_stop(duration: TimeSpan) -> Self {
+    Self {
+      scale: 0.,
+      duration,
+      blend_out: TimeSpan::ZERO,
+    }
+  }
+
+  /// Slows the game clock to the given scale, easing back over the last
+  /// quarter of the duration.
+  pub fn slow_motion(scale: f32, duration: TimeSpan) -> Self {
+    Self {
+      scale,
+      duration,
+      blend_out: duration * 0.25,
+    }
+  }
+
+  /// The scale of the dilation after the given time in effect, in seconds.
+  fn scale_at(&self, elapsed: f32) -> f32 {
+    let blend_out = self.blend_out.as_seconds();
+    let blend_start = self.duration.as_seconds() - blend_out;
+
+    if blend_out <= 0. || elapsed < blend_start {
+      return self.scale;
+    }
+
+    let t = ((elapsed - blend_start) / blend_out).min(1.);
+
+    self.scale + (1. - self.scale) * t
+  }
+}
+
+/// A dilation in effect, and how long it has been so.
+#[derive(Copy, Clone, Debug)]
+struct ActiveDilation {
+  dilation: TimeDilation,
+  elapsed: f32,
+}
+
+impl Default for Time {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Time {
+  /// Creates a new time service with a 60Hz fixed step.
+  pub fn new() -> Self {
+    Self {
+      frame: 0,
+      fixed_frame: 0,
+      paused: false,
+      time_scale: 1.,
+      dilation: 1.,
+      dilations: Vec::new(),
+      unscaled_delta_time: 0.,
+      unscaled_total_time: 0.,
+      delta_time: 0.,
+      total_time: 0.,
+      fixed_delta_time: 1. / 60.,
+      fixed_accumulator: 0.,
+      max_fixed_steps: 8,
+      domains: FastHashMap::default(),
+      frame_counter: FrameCounter::default(),
+    }
+  }
+
+  /// Advances both clocks by the given real delta time, in seconds.
+  ///
+  /// Call this once per frame, before any fixed steps are consumed.
+  pub fn update(&mut self, unscaled_delta_time: f32) {
+    self.frame += 1;
+    self.frame_counter.tick(unscaled_delta_time);
+
+    self.unscaled_delta_time = unscaled_delta_time;
+    self.unscaled_total_time += unscaled_delta_time as f64;
+
+    // the strongest dilation wins, rather than stacking into a standstill
+    self.dilation = self
+      .dilations
+      .iter()
+      .map(|active| active.dilation.scale_at(active.elapsed))
+      .min_by(f32::total_cmp)
+      .unwrap_or(1.);
+
+    self.dilations.retain_mut(|active| {
+      active.elapsed += unscaled_delta_time;
+      active.elapsed < active.dilation.duration.as_seconds()
+    });
+
+    self.delta_time = if self.paused {
+      0.
+    } else {
+      unscaled_delta_time * self.scaled_time_scale()
+    };
+    self.total_time += self.delta_time as f64;
+
+    // cap the accumulator so a long stall doesn't spiral into endless steps
+    let max_accumulated = self.fixed_delta_time * self.max_fixed_steps as f32;
+
+    self.fixed_accumulator = (self.fixed_accumulator + self.delta_time).min(max_accumulated);
+  }
+
+  /// Consumes a single fixed step from the accumulator, if one is due.
+  ///
+  /// Typically called in a loop: `while time.consume_fixed_step() { ... }`.
+  pub fn consume_fixed_step(&mut self) -> bool {
+    if self.fixed_accumulator < self.fixed_delta_time {
+      return false;
+    }
+
+    self.fixed_accumulator -= self.fixed_delta_time;
+    self.fixed_frame += 1;
+
+    true
+  }
+
+  /// How far between the last and next fixed step we are, from 0 to 1.
+  ///
+  /// Use this to interpolate rendered state between simulation steps.
+  pub fn fixed_alpha(&self) -> f32 {
+    self.fixed_accumulator / self.fixed_delta_time
+  }
+
+  /// The time left over in the fixed-step accumulator, in seconds.
+  pub fn fixed_accumulator(&self) -> f32 {
+    self.fixed_accumulator
+  }
+
+  /// The length of a fixed step, in seconds.
+  pub fn fixed_delta_time(&self) -> f32 {
+    self.fixed_delta_time
+  }
+
+  /// Changes the length of a fixed step, in seconds.
+  pub fn set_fixed_delta_time(&mut self, fixed_delta_time: f32) {
+    self.fixed_delta_time = fixed_delta_time.max(f32::EPSILON);
+  }
+
+  /// Changes the maximum number of fixed steps that can be owed at once.
+  pub fn set_max_fixed_steps(&mut self, max_fixed_steps: u32) {
+    self.max_fixed_steps = max_fixed_steps.max(1);
+  }
+
+  /// The number of frames since the clock started.
+  pub fn frame_count(&self) -> u64 {
+    self.frame
+  }
+
+  /// The number of fixed steps consumed since the clock started.
+  pub fn fixed_frame_count(&self) -> u64 {
+    self.fixed_frame
+  }
+
+  /// The average frames per second over recent frames.
+  pub fn average_fps(&self) -> f32 {
+    self.frame_counter.average_fps()
+  }
+
+  /// The game delta time for this frame, in seconds.
+  pub fn delta_time(&self) -> f32 {
+    self.delta_time
+  }
+
+  /// The total game time elapsed, in seconds.
+  pub fn total_time(&self) -> f64 {
+    self.total_time
+  }
+
+  /// The real delta time for this frame, in seconds.
+  pub fn unscaled_delta_time(&self) -> f32 {
+    self.unscaled_delta_time
+  }
+
+  /// The total real time elapsed, in seconds.
+  pub fn unscaled_total_time(&self) -> f64 {
+    self.unscaled_total_time
+  }
+
+  /// The scale applied to the game clock.
+  pub fn time_scale(&self) -> f32 {
+    self.time_scale
+  }
+
+  /// Changes the scale applied to the game clock (e.g. 0.5 for slow-motion).
+  pub fn set_time_scale(&mut self, time_scale: f32) {
+    self.time_scale = time_scale.max(0.);
+  }
+
+  /// Applies a temporary dilation to the game clock, from the next update.
+  pub fn dilate(&mut self, dilation: TimeDilation) {
+    self.dilations.push(ActiveDilation { dilation, elapsed: 0. });
+  }
+
+  /// Freezes the game clock for the given real duration.
+  pub fn hit_stop(&mut self, duration: TimeSpan) {
+    self.dilate(TimeDilation::hit_stop(duration));
+  }
+
+  /// Slows the game clock to the given scale for the given real duration.
+  pub fn slow_motion(&mut self, scale: f32, duration: TimeSpan) {
+    self.dilate(TimeDilation::slow_motion(scale.max(0.), duration));
+  }
+
+  /// Ends all dilations immediately.
+  pub fn clear_dilations(&mut self) {
+    self.dilations.clear();
+    self.dilation = 1.;
+  }
+
+  /// Is a dilation currently in effect?
+  pub fn is_dilated(&self) -> bool {
+    !self.dilations.is_empty()
+  }
+
+  /// The scale applied to the game clock by dilations this frame.
+  pub fn dilation(&self) -> f32 {
+    self.dilation
+  }
+
+  /// The pitch multiplier for audio that should follow the game clock.
+  ///
+  /// This follows the time scale and dilations, but never drops low enough to
+  /// silence a source; pause sources that should stop during a hit-stop.
+  pub fn pitch_scale(&self) -> f32 {
+    self.scaled_time_scale().max(MIN_PITCH_SCALE)
+  }
+
+  /// Is the game clock paused?
+  pub fn is_paused(&self) -> bool {
+    self.paused
+  }
+
+  /// Pauses the game clock.
+  pub fn pause(&mut self) {
+    self.paused = true;
+  }
+
+  /// Resumes the game clock.
+  pub fn resume(&mut self) {
+    self.paused = false;
+  }
+
+  /// Sets the time overrides for the given domain.
+  pub fn set_domain(&mut self, name: impl Into<StringName>, domain: TimeDomain) {
+    self.domains.insert(name.into(), domain);
+  }
+
+  /// Removes the time overrides for the given domain.
+  pub fn remove_domain(&mut self, name: impl Into<StringName>) {
+    self.domains.remove(&name.into());
+  }
+
+  /// The delta time for this frame as seen by the given domain, in seconds.
+  ///
+  /// Domains without overrides see the game clock.
+  pub fn domain_delta_time(&self, name: impl Into<StringName>) -> f32 {
+    let Some(domain) = self.domains.get(&name.into()) else {
+      return self.delta_time;
+    };
+
+    if self.paused && !domain.ignore_pause {
+      return 0.;
+    }
+
+    let delta_time = if domain.ignore_time_scale {
+      self.unscaled_delta_time
+    } else {
+      self.unscaled_delta_time * self.scaled_time_scale()
+    };
+
+    delta_time * domain.scale
+  }
+
+  /// The time scale with dilations applied.
+  fn scaled_time_scale(&self) -> f32 {
+    self.time_scale * self.dilation
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_scale_and_pause_the_game_clock() {
+    let mut time = Time::new();
+
+    time.set_time_scale(0.5);
+    time.update(0.1);
+
+    assert_eq!(time.delta_time(), 0.05);
+    assert_eq!(time.unscaled_delta_time(), 0.1);
+
+    time.pause();
+    time.update(0.1);
+
+    assert_eq!(time.delta_time(), 0.);
+    assert_eq!(time.unscaled_delta_time(), 0.1);
+    assert_eq!(time.frame_count(), 2);
+  }
+
+  #[test]
+  fn it_should_accumulate_fixed_steps() {
+    let mut time = Time::new();
+
+    time.set_fixed_delta_time(0.1);
+    time.update(0.25);
+
+    let mut steps = 0;
+    while time.consume_fixed_step() {
+      steps += 1;
+    }
+
+    assert_eq!(steps, 2);
+    assert_eq!(time.fixed_frame_count(), 2);
+    assert!((time.fixed_alpha() - 0.5).abs() < 0.001);
+  }
+
+  #[test]
+  fn it_should_cap_the_fixed_step_accumulator() {
+    let mut time = Time::new();
+
+    time.set_fixed_delta_time(0.1);
+    time.set_max_fixed_steps(3);
+    time.update(10.);
+
+    let mut steps = 0;
+    while time.consume_fixed_step() {
+      steps += 1;
+    }
+
+    assert_eq!(steps, 3);
+  }
+
+  #[test]
+  fn it_should_freeze_the_game_clock_during_hit_stop() {
+    let mut time = Time::new();
+
+    time.set_domain("ui", TimeDomain::REAL_TIME);
+    time.set_fixed_delta_time(0.1);
+    time.hit_stop(TimeSpan::from_seconds(0.2));
+
+    for _ in 0..2 {
+      time.update(0.1);
+
+      assert_eq!(time.delta_time(), 0.);
+      assert_eq!(time.domain_delta_time("ui"), 0.1);
+      assert_eq!(time.pitch_scale(), MIN_PITCH_SCALE);
+      assert!(!time.consume_fixed_step());
+    }
+
+    time.update(0.1);
+
+    assert!(!time.is_dilated());
+    assert_eq!(time.delta_time(), 0.1);
+    assert!(time.consume_fixed_step());
+  }
+
+  #[test]
+  fn it_should_blend_out_of_slow_motion() {
+    let mut time = Time::new();
+
+    time.set_time_scale(0.5);
+    time.slow_motion(0.2, TimeSpan::from_seconds(0.4));
+    time.hit_stop(TimeSpan::from_seconds(0.1));
+
+    let mut scales = Vec::new();
+
+    for _ in 0..5 {
+      time.update(0.1);
+      scales.push(time.dilation());
+    }
+
+    // the hit-stop wins while it lasts, then slow motion eases back
+    assert_eq!(scales, vec![0., 0.2, 0.2, 0.2, 1.]);
+    assert!((time.delta_time() - 0.05).abs() < 0.0001);
+
+    time.slow_motion(0.5, TimeSpan::from_seconds(0.4));
+    time.update(0.35);
+    time.update(0.);
+
+    assert!((time.dilation() - 0.75).abs() < 0.0001);
+  }
+
+  #[test]
+  fn it_should_apply_domain_overrides() {
+    let mut time = Time::new();
+
+    time.set_domain("ui", TimeDomain::REAL_TIME);
+    time.set_domain("effects", TimeDomain {
+      scale: 2.,
+      ..Default::default()
+    });
+
+    time.set_time_scale(0.5);
+    time.update(0.1);
+
+    assert_eq!(time.domain_delta_time("ui"), 0.1);
+    assert_eq!(time.domain_delta_time("effects"), 0.1);
+    assert_eq!(time.domain_delta_time("gameplay"), 0.05);
+
+    time.pause();
+    time.update(0.1);
+
+    assert_eq!(time.domain_delta_time("ui"), 0.1);
+    assert_eq!(time.domain_delta_time("effects"), 0.);
+  }
+}
@@ -1,4 +1,7 @@
-use std::{ops::Sub, time::Instant};
+use std::{
+  ops::{Add, Sub},
+  time::Instant,
+};
 
 use super::TimeSpan;
 
@@ -15,6 +18,14 @@ impl TimeStamp {
   }
 }
 
+impl Add<TimeSpan> for TimeStamp {
+  type Output = TimeStamp;
+
+  fn add(self, rhs: TimeSpan) -> Self::Output {
+    TimeStamp(self.0 + rhs.into())
+  }
+}
+
 impl Sub for TimeStamp {
   type Output = TimeSpan;
 
@@ -1,7 +1,11 @@
 //! Memory management tools
 
 pub use nan::*;
+pub use pool::*;
 pub use stack::*;
+pub use tracking::*;
 
 mod nan;
+mod pool;
 mod stack;
+mod tracking;
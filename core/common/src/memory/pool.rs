@@ -0,0 +1,185 @@
+use crate::{impl_arena_index, ArenaIndex};
+
+impl_arena_index!(pub PoolId, "Identifies an object taken from an object pool.");
+
+/// A pool of reusable objects.
+///
+/// Objects are created up front (or on demand) and recycled when released,
+/// so that games spawning many short-lived things, like bullets, don't
+/// allocate in the middle of a frame.
+pub struct ObjectPool<T> {
+  slots: Vec<PoolSlot<T>>,
+  free: Vec<u32>,
+  factory: Box<dyn Fn() -> T>,
+  reset: Option<PoolReset<T>>,
+}
+
+/// Restores a released object to its initial state.
+type PoolReset<T> = Box<dyn Fn(&mut T)>;
+
+/// A slot in an [`ObjectPool`].
+struct PoolSlot<T> {
+  value: T,
+  generation: u32,
+  is_active: bool,
+}
+
+impl<T> ObjectPool<T> {
+  /// Creates a new, empty pool that creates objects with the given factory.
+  pub fn new(factory: impl Fn() -> T + 'static) -> Self {
+    Self {
+      slots: Vec::new(),
+      free: Vec::new(),
+      factory: Box::new(factory),
+      reset: None,
+    }
+  }
+
+  /// Resets objects with the given function when they're released.
+  pub fn with_reset(mut self, reset: impl Fn(&mut T) + 'static) -> Self {
+    self.reset = Some(Box::new(reset));
+    self
+  }
+
+  /// Creates objects until the pool holds at least the given number.
+  pub fn warm_up(&mut self, count: usize) {
+    while self.slots.len() < count {
+      self.free.push(self.slots.len() as u32);
+      self.slots.push(PoolSlot {
+        value: (self.factory)(),
+        generation: 0,
+        is_active: false,
+      });
+    }
+  }
+
+  /// The number of objects the pool holds, active or not.
+  pub fn capacity(&self) -> usize {
+    self.slots.len()
+  }
+
+  /// The number of objects currently in use.
+  pub fn active_count(&self) -> usize {
+    self.slots.len() - self.free.len()
+  }
+
+  /// Takes an object from the pool, creating a new one if it's empty.
+  pub fn acquire(&mut self) -> PoolId {
+    if self.free.is_empty() {
+      self.warm_up(self.slots.len() + 1);
+    }
+
+    let index = self.free.pop().expect("pool should have a free slot");
+    let slot = &mut self.slots[index as usize];
+
+    slot.is_active = true;
+
+    PoolId::from_parts(index, slot.generation)
+  }
+
+  /// Returns an object to the pool; returns false if it wasn't in use.
+  pub fn release(&mut self, id: PoolId) -> bool {
+    let Some(slot) = self.slots.get_mut(id.ordinal() as usize) else {
+      return false;
+    };
+
+    if !slot.is_active || slot.generation != id.generation() {
+      return false;
+    }
+
+    slot.is_active = false;
+    slot.generation = slot.generation.wrapping_add(1);
+
+    if let Some(reset) = &self.reset {
+      reset(&mut slot.value);
+    }
+
+    self.free.push(id.ordinal());
+
+    true
+  }
+
+  /// Determines if the given object is still in use.
+  pub fn is_active(&self, id: PoolId) -> bool {
+    self.get(id).is_some()
+  }
+
+  /// Gets an object that's in use.
+  pub fn get(&self, id: PoolId) -> Option<&T> {
+    let slot = self.slots.get(id.ordinal() as usize)?;
+
+    (slot.is_active && slot.generation == id.generation()).then_some(&slot.value)
+  }
+
+  /// Mutably gets an object that's in use.
+  pub fn get_mut(&mut self, id: PoolId) -> Option<&mut T> {
+    let slot = self.slots.get_mut(id.ordinal() as usize)?;
+
+    (slot.is_active && slot.generation == id.generation()).then_some(&mut slot.value)
+  }
+
+  /// Iterates the objects in use.
+  pub fn iter(&self) -> impl Iterator<Item = (PoolId, &T)> {
+    self
+      .slots
+      .iter()
+      .enumerate()
+      .filter(|(_, slot)| slot.is_active)
+      .map(|(index, slot)| (PoolId::from_parts(index as u32, slot.generation), &slot.value))
+  }
+
+  /// Mutably iterates the objects in use.
+  pub fn iter_mut(&mut self) -> impl Iterator<Item = (PoolId, &mut T)> {
+    self
+      .slots
+      .iter_mut()
+      .enumerate()
+      .filter(|(_, slot)| slot.is_active)
+      .map(|(index, slot)| (PoolId::from_parts(index as u32, slot.generation), &mut slot.value))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::Cell, rc::Rc};
+
+  use super::*;
+
+  #[test]
+  fn it_should_recycle_released_objects() {
+    let created = Rc::new(Cell::new(0));
+    let counter = created.clone();
+
+    let mut pool = ObjectPool::new(move || {
+      counter.set(counter.get() + 1);
+      Vec::<u32>::with_capacity(16)
+    })
+    .with_reset(|bullet| bullet.clear());
+
+    pool.warm_up(4);
+    assert_eq!(created.get(), 4);
+
+    let ids = (0..4).map(|_| pool.acquire()).collect::<Vec<_>>();
+    pool.get_mut(ids[0]).unwrap().push(7);
+
+    assert_eq!(created.get(), 4);
+    assert_eq!(pool.active_count(), 4);
+
+    assert!(pool.release(ids[0]));
+    assert!(!pool.release(ids[0]));
+    assert!(pool.get(ids[0]).is_none());
+
+    // the recycled object was reset, and the stale id doesn't reach it
+    let id = pool.acquire();
+
+    assert!(pool.get(id).unwrap().is_empty());
+    assert!(!pool.is_active(ids[0]));
+    assert_eq!(created.get(), 4);
+
+    // an empty pool grows on demand
+    pool.acquire();
+
+    assert_eq!(created.get(), 5);
+    assert_eq!(pool.iter().count(), 5);
+  }
+}
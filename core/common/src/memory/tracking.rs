@@ -0,0 +1,156 @@
+use std::{
+  alloc::{GlobalAlloc, Layout, System},
+  sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A global allocator that counts the memory allocated through it.
+///
+/// Install it in the game's binary to see memory usage in diagnostics:
+///
+/// ```rust,ignore
+/// #[global_allocator]
+/// static ALLOCATOR: TrackingAllocator = TrackingAllocator::new();
+///
+/// let usage = ALLOCATOR.statistics();
+/// ```
+pub struct TrackingAllocator<A = System> {
+  inner: A,
+  allocated: AtomicUsize,
+  peak: AtomicUsize,
+  allocations: AtomicUsize,
+  total_allocations: AtomicUsize,
+}
+
+/// A snapshot of the memory counted by a [`TrackingAllocator`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemoryStatistics {
+  /// The bytes allocated right now.
+  pub allocated: usize,
+  /// The most bytes ever allocated at once.
+  pub peak: usize,
+  /// The number of allocations alive right now.
+  pub allocations: usize,
+  /// The number of allocations ever made.
+  pub total_allocations: usize,
+}
+
+impl TrackingAllocator<System> {
+  /// Creates an allocator that tracks the system allocator.
+  pub const fn new() -> Self {
+    Self::wrap(System)
+  }
+}
+
+impl Default for TrackingAllocator<System> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<A> TrackingAllocator<A> {
+  /// Creates an allocator that tracks another allocator.
+  pub const fn wrap(inner: A) -> Self {
+    Self {
+      inner,
+      allocated: AtomicUsize::new(0),
+      peak: AtomicUsize::new(0),
+      allocations: AtomicUsize::new(0),
+      total_allocations: AtomicUsize::new(0),
+    }
+  }
+
+  /// A snapshot of the memory allocated so far.
+  pub fn statistics(&self) -> MemoryStatistics {
+    MemoryStatistics {
+      allocated: self.allocated.load(Ordering::Relaxed),
+      peak: self.peak.load(Ordering::Relaxed),
+      allocations: self.allocations.load(Ordering::Relaxed),
+      total_allocations: self.total_allocations.load(Ordering::Relaxed),
+    }
+  }
+
+  fn record_alloc(&self, size: usize) {
+    let allocated = self.allocated.fetch_add(size, Ordering::Relaxed) + size;
+
+    self.peak.fetch_max(allocated, Ordering::Relaxed);
+    self.allocations.fetch_add(1, Ordering::Relaxed);
+    self.total_allocations.fetch_add(1, Ordering::Relaxed);
+  }
+
+  fn record_dealloc(&self, size: usize) {
+    self.allocated.fetch_sub(size, Ordering::Relaxed);
+    self.allocations.fetch_sub(1, Ordering::Relaxed);
+  }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for TrackingAllocator<A> {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    let pointer = self.inner.alloc(layout);
+
+    if !pointer.is_null() {
+      self.record_alloc(layout.size());
+    }
+
+    pointer
+  }
+
+  unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+    self.inner.dealloc(pointer, layout);
+    self.record_dealloc(layout.size());
+  }
+
+  unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+    let pointer = self.inner.alloc_zeroed(layout);
+
+    if !pointer.is_null() {
+      self.record_alloc(layout.size());
+    }
+
+    pointer
+  }
+
+  unsafe fn realloc(&self, pointer: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    let new_pointer = self.inner.realloc(pointer, layout, new_size);
+
+    if !new_pointer.is_null() {
+      self.record_dealloc(layout.size());
+      self.record_alloc(new_size);
+      self.total_allocations.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    new_pointer
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_count_allocations() {
+    let allocator = TrackingAllocator::new();
+    let layout = Layout::from_size_align(256, 8).unwrap();
+
+    unsafe {
+      let first = allocator.alloc(layout);
+      let second = allocator.alloc_zeroed(layout);
+
+      assert_eq!(allocator.statistics().allocated, 512);
+      assert_eq!(allocator.statistics().allocations, 2);
+
+      let second = allocator.realloc(second, layout, 1024);
+
+      assert_eq!(allocator.statistics().allocated, 1280);
+
+      allocator.dealloc(first, layout);
+      allocator.dealloc(second, Layout::from_size_align(1024, 8).unwrap());
+    }
+
+    let statistics = allocator.statistics();
+
+    assert_eq!(statistics.allocated, 0);
+    assert_eq!(statistics.allocations, 0);
+    assert_eq!(statistics.peak, 1280);
+    assert_eq!(statistics.total_allocations, 2);
+  }
+}
@@ -0,0 +1,270 @@
+//! Procedural generation from composable generator nodes.
+//!
+//! Generators are small nodes (noise, masks, scatter, constraint solvers) that
+//! are composed into a graph and evaluated over rectangular regions on demand.
+//! All randomness is derived from a seed and the position being generated, so
+//! the same seed always gives the same world, no matter which regions are
+//! generated first.
+
+use std::hash::Hash;
+
+pub use layouts::*;
+pub use nodes::*;
+pub use wfc::*;
+
+use crate::{uvec2, DenseGrid, FastHashMap, IVec2, Random, Tile, TileGrid, UVec2};
+
+mod layouts;
+mod nodes;
+mod wfc;
+
+/// An error that can occur during generation.
+#[derive(Debug)]
+pub enum GenerationError {
+  /// A constraint solver couldn't find a valid solution for the region.
+  Contradiction,
+}
+
+/// A rectangular region of cells to generate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Region {
+  pub origin: IVec2,
+  pub size: UVec2,
+}
+
+impl Region {
+  /// Creates a new region from its origin and size.
+  pub const fn new(origin: IVec2, size: UVec2) -> Self {
+    Self { origin, size }
+  }
+
+  /// Determines if the given world position lies in the region.
+  pub fn contains(&self, position: IVec2) -> bool {
+    let local = position - self.origin;
+
+    local.x >= 0 && local.y >= 0 && (local.x as u32) < self.size.x && (local.y as u32) < self.size.y
+  }
+
+  /// Iterates every world position in the region, row by row.
+  pub fn positions(&self) -> impl Iterator<Item = IVec2> + '_ {
+    (0..self.size.y as i32).flat_map(move |y| (0..self.size.x as i32).map(move |x| self.origin + IVec2::new(x, y)))
+  }
+
+  /// Finds the world positions whose generated values match the predicate.
+  ///
+  /// Useful for turning scatter output into entity spawn points.
+  pub fn positions_where<T>(&self, output: &DenseGrid<T>, predicate: impl Fn(&T) -> bool) -> Vec<IVec2> {
+    self
+      .positions()
+      .filter(|position| {
+        let local = *position - self.origin;
+
+        output.get(local.x, local.y).is_some_and(&predicate)
+      })
+      .collect()
+  }
+}
+
+/// The shared state for a generation pass.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GenerationContext {
+  pub seed: u64,
+}
+
+impl GenerationContext {
+  /// Creates a new context with the given seed.
+  pub const fn new(seed: u64) -> Self {
+    Self { seed }
+  }
+
+  /// A random generator unique to the given salt and position.
+  ///
+  /// Nodes use a distinct salt each, so they don't correlate with each other.
+  pub fn random_at(&self, salt: u64, position: IVec2) -> Random {
+    let mut hash = self.seed ^ salt.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+
+    hash = mix(hash ^ (position.x as u32 as u64));
+    hash = mix(hash ^ ((position.y as u32 as u64) << 32));
+
+    Random::with_seed(hash)
+  }
+}
+
+/// SplitMix64 finalizer.
+fn mix(mut value: u64) -> u64 {
+  value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+  value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+  value ^ (value >> 31)
+}
+
+/// A node in a procedural generation graph.
+pub trait GeneratorNode {
+  type Output: Clone + Default;
+
+  /// Generates the values for every cell in the region.
+  fn generate(&self, context: &GenerationContext, region: Region) -> Result<DenseGrid<Self::Output>, GenerationError>;
+
+  /// Transforms each generated value.
+  fn map<U, F>(self, body: F) -> MapNode<Self, F>
+  where
+    Self: Sized,
+    F: Fn(Self::Output) -> U,
+  {
+    MapNode { source: self, body }
+  }
+
+  /// Keeps values only where the mask is set.
+  fn masked_by<M>(self, mask: M) -> MaskNode<Self, M>
+  where
+    Self: Sized,
+    M: GeneratorNode<Output = bool>,
+  {
+    MaskNode { source: self, mask }
+  }
+
+  /// Evaluates the node lazily in chunks of the given size, as they're needed.
+  fn chunked(self, context: GenerationContext, chunk_size: u32) -> ChunkedGenerator<Self>
+  where
+    Self: Sized,
+  {
+    ChunkedGenerator::new(self, context, chunk_size)
+  }
+}
+
+/// Lazily generates a node in fixed-size chunks, caching the results.
+///
+/// Position-based nodes (noise, scatter) line up across chunk boundaries;
+/// constraint solvers only see one chunk at a time, so they may not.
+pub struct ChunkedGenerator<N: GeneratorNode> {
+  node: N,
+  context: GenerationContext,
+  chunk_size: u32,
+  chunks: FastHashMap<IVec2, DenseGrid<N::Output>>,
+}
+
+impl<N: GeneratorNode> ChunkedGenerator<N> {
+  /// Creates a new chunked generator.
+  pub fn new(node: N, context: GenerationContext, chunk_size: u32) -> Self {
+    Self {
+      node,
+      context,
+      chunk_size: chunk_size.max(1),
+      chunks: FastHashMap::default(),
+    }
+  }
+
+  /// The chunk containing the given world position.
+  pub fn chunk_of(&self, position: IVec2) -> IVec2 {
+    position.div_euclid(IVec2::splat(self.chunk_size as i32))
+  }
+
+  /// The region covered by the given chunk.
+  pub fn chunk_region(&self, chunk: IVec2) -> Region {
+    Region::new(chunk * self.chunk_size as i32, uvec2(self.chunk_size, self.chunk_size))
+  }
+
+  /// Determines if the given chunk has been generated yet.
+  pub fn is_generated(&self, chunk: IVec2) -> bool {
+    self.chunks.contains_key(&chunk)
+  }
+
+  /// The number of chunks generated so far.
+  pub fn generated_chunks(&self) -> usize {
+    self.chunks.len()
+  }
+
+  /// Gets the value at the given world position, generating its chunk if
+  /// needed.
+  pub fn get(&mut self, position: IVec2) -> Result<&N::Output, GenerationError> {
+    let chunk = self.chunk_of(position);
+    let region = self.chunk_region(chunk);
+
+    if !self.chunks.contains_key(&chunk) {
+      let output = self.node.generate(&self.context, region)?;
+
+      self.chunks.insert(chunk, output);
+    }
+
+    let local = position - region.origin;
+
+    Ok(
+      self.chunks[&chunk]
+        .get(local.x, local.y)
+        .expect("position should be in chunk"),
+    )
+  }
+
+  /// Discards a generated chunk, e.g. once it's out of range.
+  pub fn evict(&mut self, chunk: IVec2) {
+    self.chunks.remove(&chunk);
+  }
+}
+
+/// Writes generated output into a tile grid, converting values with the given
+/// function.
+///
+/// Cells outside the tile grid are ignored.
+pub fn paint_tiles<A: Copy + Eq + Hash, T>(
+  tiles: &mut TileGrid<A>,
+  region: Region,
+  output: &DenseGrid<T>,
+  to_tile: impl Fn(&T) -> Tile,
+) {
+  for position in region.positions() {
+    let local = position - region.origin;
+
+    if let Some(value) = output.get(local.x, local.y) {
+      tiles.set_tile(position, to_tile(value));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ivec2;
+
+  #[test]
+  fn it_should_generate_the_same_values_regardless_of_region() {
+    let context = GenerationContext::new(42);
+    let node = NoiseNode::new(0.1);
+
+    let whole = node.generate(&context, Region::new(ivec2(0, 0), uvec2(8, 8))).unwrap();
+    let part = node.generate(&context, Region::new(ivec2(4, 4), uvec2(4, 4))).unwrap();
+
+    assert_eq!(whole.get(5, 6), part.get(1, 2));
+  }
+
+  #[test]
+  fn it_should_generate_chunks_lazily() {
+    let mut generator = NoiseNode::new(0.1)
+      .map(|value| value > 0.)
+      .chunked(GenerationContext::new(7), 16);
+
+    assert_eq!(generator.generated_chunks(), 0);
+
+    generator.get(ivec2(3, 3)).unwrap();
+    generator.get(ivec2(10, 12)).unwrap();
+    assert_eq!(generator.generated_chunks(), 1);
+
+    generator.get(ivec2(-1, 3)).unwrap();
+    assert!(generator.is_generated(ivec2(-1, 0)));
+    assert_eq!(generator.generated_chunks(), 2);
+  }
+
+  #[test]
+  fn it_should_paint_generated_tiles() {
+    let region = Region::new(ivec2(0, 0), uvec2(4, 4));
+    let walls = ConstantNode(false)
+      .generate(&GenerationContext::new(0), region)
+      .unwrap();
+    let mut tiles = TileGrid::<u32>::new(2, 2);
+
+    paint_tiles(&mut tiles, region, &walls, |walkable| match walkable {
+      true => Tile::FLOOR,
+      false => Tile::WALL,
+    });
+
+    assert!(!tiles.is_walkable(ivec2(1, 1)));
+  }
+}
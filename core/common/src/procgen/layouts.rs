@@ -0,0 +1,510 @@
+//! Room-and-connection layouts with lock-and-key progression.
+
+use super::*;
+use crate::{impl_arena_index, ivec2, Arena, FastHashSet, StringName};
+
+impl_arena_index!(pub RoomId, "Identifies a room in a layout.");
+
+/// An error in a [`LayoutGraph`].
+#[derive(Debug)]
+pub enum LayoutError {
+  /// A room referenced by a connection or item doesn't exist.
+  InvalidRoom(RoomId),
+  /// The rooms can't be reached from the start, even with every obtainable
+  /// item.
+  Unsolvable(Vec<RoomId>),
+}
+
+/// A room in a layout.
+#[derive(Clone, Debug)]
+pub struct Room {
+  /// The name of the geometry template to build the room from.
+  pub template: StringName,
+  /// Where the room sits on the layout grid, in rooms.
+  pub cell: IVec2,
+  /// Items that can be picked up in this room.
+  pub items: Vec<StringName>,
+}
+
+/// A two-way connection between rooms, optionally gated by an item.
+#[derive(Clone, Debug)]
+pub struct RoomConnection {
+  pub from: RoomId,
+  pub to: RoomId,
+  /// The item needed to pass through, if any.
+  pub lock: Option<StringName>,
+}
+
+/// The order a player can progress through a layout.
+#[derive(Clone, Debug, Default)]
+pub struct Progression {
+  /// Items in the order they become obtainable.
+  pub items: Vec<StringName>,
+  /// Every room the player can eventually reach.
+  pub reachable: FastHashSet<RoomId>,
+}
+
+/// An abstract graph of rooms, connections and the items that gate them.
+#[derive(Default)]
+pub struct LayoutGraph {
+  rooms: Arena<RoomId, Room>,
+  connections: Vec<RoomConnection>,
+  start: Option<RoomId>,
+}
+
+impl LayoutGraph {
+  /// Creates a new, empty layout.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a room; the first room added is the start.
+  pub fn add_room(&mut self, template: impl Into<StringName>, cell: IVec2) -> RoomId {
+    let id = self.rooms.insert(Room {
+      template: template.into(),
+      cell,
+      items: Vec::new(),
+    });
+
+    self.start.get_or_insert(id);
+
+    id
+  }
+
+  /// Gets a room.
+  pub fn room(&self, id: RoomId) -> Option<&Room> {
+    self.rooms.get(id)
+  }
+
+  /// Iterates the rooms in the layout.
+  pub fn rooms(&self) -> impl Iterator<Item = (RoomId, &Room)> {
+    self.rooms.enumerate()
+  }
+
+  /// The connections between rooms.
+  pub fn connections(&self) -> &[RoomConnection] {
+    &self.connections
+  }
+
+  /// The room the player starts in.
+  pub fn start(&self) -> Option<RoomId> {
+    self.start
+  }
+
+  /// Changes the room the player starts in.
+  pub fn set_start(&mut self, room: RoomId) {
+    self.start = Some(room);
+  }
+
+  /// Connects two rooms, optionally requiring an item to pass.
+  pub fn connect(&mut self, from: RoomId, to: RoomId, lock: Option<StringName>) -> Result<(), LayoutError> {
+    for room in [from, to] {
+      if self.rooms.get(room).is_none() {
+        return Err(LayoutError::InvalidRoom(room));
+      }
+    }
+
+    self.connections.push(RoomConnection { from, to, lock });
+
+    Ok(())
+  }
+
+  /// Places an item to be picked up in the given room.
+  pub fn place_item(&mut self, room: RoomId, item: impl Into<StringName>) -> Result<(), LayoutError> {
+    let room_data = self.rooms.get_mut(room).ok_or(LayoutError::InvalidRoom(room))?;
+
+    room_data.items.push(item.into());
+
+    Ok(())
+  }
+
+  /// Works out what the player can reach, collecting items as they go.
+  pub fn progression(&self) -> Progression {
+    let mut progression = Progression::default();
+    let mut inventory = FastHashSet::default();
+
+    let Some(start) = self.start else {
+      return progression;
+    };
+
+    loop {
+      // flood out from the start through every door we can open
+      let mut reached = FastHashSet::default();
+      let mut frontier = vec![start];
+
+      reached.insert(start);
+
+      while let Some(room) = frontier.pop() {
+        for connection in &self.connections {
+          let other = match room {
+            _ if connection.from == room => connection.to,
+            _ if connection.to == room => connection.from,
+            _ => continue,
+          };
+
+          let unlocked = connection.lock.is_none_or(|lock| inventory.contains(&lock));
+
+          if unlocked && reached.insert(other) {
+            frontier.push(other);
+          }
+        }
+      }
+
+      // pick up everything we can reach; stop once there's nothing new
+      let mut found_new = false;
+
+      for room in reached.iter().flat_map(|room| self.rooms.get(*room)) {
+        for &item in &room.items {
+          if inventory.insert(item) {
+            progression.items.push(item);
+            found_new = true;
+          }
+        }
+      }
+
+      progression.reachable = reached;
+
+      if !found_new {
+        return progression;
+      }
+    }
+  }
+
+  /// Checks that every room can be reached from the start.
+  pub fn validate(&self) -> Result<Progression, LayoutError> {
+    let progression = self.progression();
+
+    let unreachable = self
+      .rooms
+      .enumerate()
+      .map(|(id, _)| id)
+      .filter(|id| !progression.reachable.contains(id))
+      .collect::<Vec<_>>();
+
+    match unreachable.is_empty() {
+      true => Ok(progression),
+      false => Err(LayoutError::Unsolvable(unreachable)),
+    }
+  }
+
+  /// Builds tile geometry for the layout from per-room templates.
+  ///
+  /// Each room occupies `room_size` tiles; rooms without a template become
+  /// walled boxes. Connected rooms that share a wall get a door punched
+  /// through it, and the doors are returned so that locks can be spawned.
+  pub fn build_tiles<A: Copy + Eq + Hash>(
+    &self,
+    room_size: UVec2,
+    template_for: impl Fn(&Room) -> Option<DenseGrid<Tile>>,
+  ) -> (TileGrid<A>, Vec<Door>) {
+    let Some(min) = self.rooms.iter().map(|room| room.cell).reduce(IVec2::min) else {
+      return (TileGrid::new(0, 0), Vec::new());
+    };
+
+    let max = self.rooms.iter().map(|room| room.cell).fold(min, IVec2::max);
+    let size = room_size.as_ivec2();
+    let extent = (max - min + IVec2::ONE) * size;
+
+    let mut tiles = TileGrid::new(extent.x as usize, extent.y as usize);
+    let origin_of = |room: &Room| (room.cell - min) * size;
+
+    for position in Region::new(IVec2::ZERO, extent.as_uvec2()).positions() {
+      tiles.set_tile(position, Tile::WALL);
+    }
+
+    for room in self.rooms.iter() {
+      let origin = origin_of(room);
+      let template = template_for(room);
+
+      for position in Region::new(IVec2::ZERO, room_size).positions() {
+        let tile = match &template {
+          Some(template) => template.get(position.x, position.y).copied().unwrap_or(Tile::WALL),
+          None if position.x == 0 || position.y == 0 => Tile::WALL,
+          None if position.x == size.x - 1 || position.y == size.y - 1 => Tile::WALL,
+          None => Tile::FLOOR,
+        };
+
+        tiles.set_tile(origin + position, tile);
+      }
+    }
+
+    let mut doors = Vec::new();
+
+    for connection in &self.connections {
+      let (Some(from), Some(to)) = (self.rooms.get(connection.from), self.rooms.get(connection.to)) else {
+        continue;
+      };
+
+      let step = to.cell - from.cell;
+
+      // only rooms that are side by side share a wall
+      if step.abs().element_sum() != 1 {
+        continue;
+      }
+
+      // carve a corridor between the room centers, through both walls
+      let to_region = Region::new(origin_of(to), room_size);
+      let mut position = origin_of(from) + size / 2;
+      let mut door = None;
+
+      while position != origin_of(to) + size / 2 {
+        tiles.set_tile(position, Tile::FLOOR);
+
+        if door.is_none() && to_region.contains(position) {
+          door = Some(position);
+        }
+
+        position += step;
+      }
+
+      doors.push(Door {
+        position: door.unwrap_or(position),
+        lock: connection.lock,
+        rooms: (connection.from, connection.to),
+      });
+    }
+
+    (tiles, doors)
+  }
+}
+
+/// A door between rooms in generated geometry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Door {
+  /// The tile in the wall of the second room that was opened up.
+  pub position: IVec2,
+  pub lock: Option<StringName>,
+  pub rooms: (RoomId, RoomId),
+}
+
+/// Generates metroidvania-style layouts on a grid of rooms.
+///
+/// Rooms grow outward from the start in zones; the way into each zone after
+/// the first is locked, and its key is hidden somewhere in an earlier zone.
+/// Layouts are solvable by construction, which [`LayoutGraph::validate`]
+/// confirms.
+#[derive(Clone, Debug)]
+pub struct LayoutGenerator {
+  pub room_count: usize,
+  /// The names of the items that gate each zone, in order.
+  pub keys: Vec<StringName>,
+  /// The templates to choose from for each room.
+  pub templates: Vec<StringName>,
+  /// The chance of adding extra connections to form loops, from 0 to 1.
+  pub loop_chance: f32,
+}
+
+impl Default for LayoutGenerator {
+  fn default() -> Self {
+    Self {
+      room_count: 12,
+      keys: Vec::new(),
+      templates: vec![StringName::from("room")],
+      loop_chance: 0.1,
+    }
+  }
+}
+
+impl LayoutGenerator {
+  /// Generates a layout from the given context.
+  pub fn generate(&self, context: &GenerationContext) -> LayoutGraph {
+    let mut random = context.random_at(3, IVec2::ZERO);
+    let mut layout = LayoutGraph::new();
+    let mut occupied = FastHashMap::default();
+
+    let zone_count = self.keys.len() + 1;
+    let rooms_per_zone = (self.room_count / zone_count).max(1);
+    let mut zones: Vec<Vec<RoomId>> = vec![Vec::new(); zone_count];
+
+    let start = layout.add_room(self.pick_template(&mut random), IVec2::ZERO);
+
+    occupied.insert(IVec2::ZERO, start);
+    zones[0].push(start);
+
+    for zone in 0..zone_count {
+      let target = match zone {
+        _ if zone == zone_count - 1 => self.room_count.saturating_sub(occupied.len()),
+        _ => rooms_per_zone - zones[zone].len(),
+      };
+
+      for index in 0..target {
+        // grow the first room of a zone out of any earlier room
+        let candidates = match index == 0 && zone > 0 {
+          true => zones[..zone].concat(),
+          false => zones[zone].clone(),
+        };
+
+        let Some((parent, cell)) = self.find_free_cell(&layout, &occupied, &candidates, &mut random) else {
+          break;
+        };
+
+        let room = layout.add_room(self.pick_template(&mut random), cell);
+        let lock = match index == 0 && zone > 0 {
+          true => Some(self.keys[zone - 1]),
+          false => None,
+        };
+
+        layout.connect(parent, room, lock).expect("rooms should exist");
+        occupied.insert(cell, room);
+        zones[zone].push(room);
+
+        // the key for this zone hides somewhere before it
+        if lock.is_some() {
+          let earlier = zones[..zone].concat();
+          let key_room = earlier[random.next_range(0..earlier.len())];
+
+          layout
+            .place_item(key_room, self.keys[zone - 1])
+            .expect("room should exist");
+        }
+      }
+    }
+
+    self.add_loops(&mut layout, &occupied, &zones, &mut random);
+
+    layout
+  }
+
+  fn pick_template(&self, random: &mut Random) -> StringName {
+    match self.templates.is_empty() {
+      true => StringName::from("room"),
+      false => self.templates[random.next_range(0..self.templates.len())],
+    }
+  }
+
+  fn find_free_cell(
+    &self,
+    layout: &LayoutGraph,
+    occupied: &FastHashMap<IVec2, RoomId>,
+    candidates: &[RoomId],
+    random: &mut Random,
+  ) -> Option<(RoomId, IVec2)> {
+    let mut options = Vec::new();
+
+    for &room in candidates {
+      let cell = layout.rooms.get(room)?.cell;
+
+      for step in [ivec2(1, 0), ivec2(-1, 0), ivec2(0, 1), ivec2(0, -1)] {
+        if !occupied.contains_key(&(cell + step)) {
+          options.push((room, cell + step));
+        }
+      }
+    }
+
+    match options.is_empty() {
+      true => None,
+      false => Some(options[random.next_range(0..options.len())]),
+    }
+  }
+
+  /// Adds unlocked shortcuts between neighbouring rooms in the same zone.
+  fn add_loops(
+    &self,
+    layout: &mut LayoutGraph,
+    occupied: &FastHashMap<IVec2, RoomId>,
+    zones: &[Vec<RoomId>],
+    random: &mut Random,
+  ) {
+    for zone in zones {
+      for &room in zone {
+        let Some(cell) = layout.room(room).map(|room| room.cell) else {
+          continue;
+        };
+
+        for step in [ivec2(1, 0), ivec2(0, 1)] {
+          let Some(&other) = occupied.get(&(cell + step)) else {
+            continue;
+          };
+
+          let already_connected = layout.connections.iter().any(|connection| {
+            (connection.from == room && connection.to == other) || (connection.from == other && connection.to == room)
+          });
+
+          if zone.contains(&other) && !already_connected && random.next::<f32>() < self.loop_chance {
+            layout.connect(room, other, None).expect("rooms should exist");
+          }
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{heuristics, PathFindingGrid};
+
+  #[test]
+  fn it_should_detect_unsolvable_layouts() {
+    let mut layout = LayoutGraph::new();
+
+    let start = layout.add_room("hall", ivec2(0, 0));
+    let vault = layout.add_room("vault", ivec2(1, 0));
+    let closet = layout.add_room("closet", ivec2(0, 1));
+
+    layout.connect(start, vault, Some("key".into())).unwrap();
+    layout.connect(start, closet, None).unwrap();
+
+    // the key is locked behind its own door
+    layout.place_item(vault, "key").unwrap();
+
+    assert!(matches!(layout.validate(), Err(LayoutError::Unsolvable(rooms)) if rooms == vec![vault]));
+
+    // moving it somewhere reachable fixes things
+    let mut layout = LayoutGraph::new();
+
+    let start = layout.add_room("hall", ivec2(0, 0));
+    let vault = layout.add_room("vault", ivec2(1, 0));
+    let closet = layout.add_room("closet", ivec2(0, 1));
+
+    layout.connect(start, vault, Some("key".into())).unwrap();
+    layout.connect(start, closet, None).unwrap();
+    layout.place_item(closet, "key").unwrap();
+
+    let progression = layout.validate().unwrap();
+
+    assert_eq!(progression.items, vec![StringName::from("key")]);
+  }
+
+  #[test]
+  fn it_should_generate_solvable_layouts() {
+    let generator = LayoutGenerator {
+      room_count: 20,
+      keys: vec!["dash".into(), "double_jump".into(), "red_key".into()],
+      ..Default::default()
+    };
+
+    for seed in 0..20 {
+      let layout = generator.generate(&GenerationContext::new(seed));
+      let progression = layout.validate().unwrap();
+
+      assert_eq!(layout.rooms().count(), 20);
+      assert_eq!(progression.items.len(), 3);
+      assert_eq!(layout.connections().iter().filter(|it| it.lock.is_some()).count(), 3);
+    }
+  }
+
+  #[test]
+  fn it_should_build_tiles_with_doors() {
+    let mut layout = LayoutGraph::new();
+
+    let start = layout.add_room("hall", ivec2(0, 0));
+    let vault = layout.add_room("vault", ivec2(1, 0));
+
+    layout.connect(start, vault, Some("key".into())).unwrap();
+
+    let (tiles, doors) = layout.build_tiles::<u32>(uvec2(7, 5), |_| None);
+
+    assert_eq!((tiles.width(), tiles.height()), (14, 5));
+    assert_eq!(doors.len(), 1);
+    assert_eq!(doors[0].lock, Some("key".into()));
+
+    // the rooms are walled off from each other, except through the door
+    let path = tiles
+      .find_path(ivec2(3, 2), ivec2(10, 2), heuristics::manhattan_distance)
+      .expect("rooms should connect");
+
+    assert!(path.contains(&doors[0].position));
+    assert!(!tiles.is_walkable(ivec2(6, 1)));
+  }
+}
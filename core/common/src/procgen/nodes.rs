@@ -0,0 +1,239 @@
+use super::*;
+use crate::{PerlinNoise, Vec2};
+
+/// Generates the same value everywhere.
+#[derive(Clone, Debug, Default)]
+pub struct ConstantNode<T>(pub T);
+
+impl<T: Clone + Default> GeneratorNode for ConstantNode<T> {
+  type Output = T;
+
+  fn generate(&self, _context: &GenerationContext, region: Region) -> Result<DenseGrid<T>, GenerationError> {
+    let mut output = DenseGrid::new(region.size.x as usize, region.size.y as usize);
+
+    output.fill(self.0.clone());
+
+    Ok(output)
+  }
+}
+
+/// Generates fractal Perlin noise, roughly in the range -1 to 1.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NoiseNode {
+  /// How many noise features per cell; smaller values are smoother.
+  pub frequency: f32,
+  pub octaves: u32,
+  pub lacunarity: f32,
+  pub persistence: f32,
+  /// Distinguishes this node's noise from other nodes with the same seed.
+  pub salt: u64,
+}
+
+impl NoiseNode {
+  /// Creates a noise node with the given frequency.
+  pub fn new(frequency: f32) -> Self {
+    Self {
+      frequency,
+      octaves: 4,
+      lacunarity: 2.,
+      persistence: 0.5,
+      salt: 0,
+    }
+  }
+}
+
+impl GeneratorNode for NoiseNode {
+  type Output = f32;
+
+  fn generate(&self, context: &GenerationContext, region: Region) -> Result<DenseGrid<f32>, GenerationError> {
+    let seed = mix(context.seed ^ self.salt) as u32;
+    let noise = PerlinNoise::new(seed);
+    let mut output = DenseGrid::new(region.size.x as usize, region.size.y as usize);
+
+    for position in region.positions() {
+      let local = position - region.origin;
+      let sample = noise.fractal_2d(
+        position.as_vec2() * self.frequency,
+        self.octaves,
+        self.lacunarity,
+        self.persistence,
+      );
+
+      output.set(local.x, local.y, sample);
+    }
+
+    Ok(output)
+  }
+}
+
+/// Transforms the values of another node; see [`GeneratorNode::map`].
+#[derive(Clone, Debug)]
+pub struct MapNode<N, F> {
+  pub(super) source: N,
+  pub(super) body: F,
+}
+
+impl<N, F, U> GeneratorNode for MapNode<N, F>
+where
+  N: GeneratorNode,
+  F: Fn(N::Output) -> U,
+  U: Clone + Default,
+{
+  type Output = U;
+
+  fn generate(&self, context: &GenerationContext, region: Region) -> Result<DenseGrid<U>, GenerationError> {
+    let source = self.source.generate(context, region)?;
+    let values = source.as_slice().iter().cloned().map(&self.body).collect::<Vec<_>>();
+
+    Ok(DenseGrid::from_slice(source.stride(), &values))
+  }
+}
+
+/// Keeps values of another node only where a mask is set; see
+/// [`GeneratorNode::masked_by`].
+#[derive(Clone, Debug)]
+pub struct MaskNode<N, M> {
+  pub(super) source: N,
+  pub(super) mask: M,
+}
+
+impl<N, M> GeneratorNode for MaskNode<N, M>
+where
+  N: GeneratorNode,
+  M: GeneratorNode<Output = bool>,
+{
+  type Output = Option<N::Output>;
+
+  fn generate(&self, context: &GenerationContext, region: Region) -> Result<DenseGrid<Self::Output>, GenerationError> {
+    let source = self.source.generate(context, region)?;
+    let mask = self.mask.generate(context, region)?;
+
+    let values = source
+      .as_slice()
+      .iter()
+      .zip(mask.as_slice())
+      .map(|(value, &keep)| keep.then(|| value.clone()))
+      .collect::<Vec<_>>();
+
+    Ok(DenseGrid::from_slice(source.stride(), &values))
+  }
+}
+
+/// Randomly scatters points over the cells of a mask.
+///
+/// Each cell is chosen independently with the given density, and no chosen
+/// cell is within `min_spacing` of another chosen cell that outranks it, so
+/// scattered props and spawns don't clump.
+#[derive(Clone, Debug)]
+pub struct ScatterNode<M> {
+  pub mask: M,
+  /// The chance of each masked cell being chosen, from 0 to 1.
+  pub density: f32,
+  /// The minimum distance between chosen cells, in cells.
+  pub min_spacing: f32,
+  /// Distinguishes this node's choices from other nodes with the same seed.
+  pub salt: u64,
+}
+
+impl<M: GeneratorNode<Output = bool>> ScatterNode<M> {
+  /// Creates a scatter node over the given mask.
+  pub fn new(mask: M, density: f32) -> Self {
+    Self {
+      mask,
+      density,
+      min_spacing: 0.,
+      salt: 1,
+    }
+  }
+
+  /// A stable random rank for the cell, or `None` if it's not a candidate.
+  fn rank(&self, context: &GenerationContext, position: IVec2) -> Option<f32> {
+    let mut random = context.random_at(self.salt, position);
+
+    (random.next::<f32>() < self.density).then(|| random.next::<f32>())
+  }
+}
+
+impl<M: GeneratorNode<Output = bool>> GeneratorNode for ScatterNode<M> {
+  type Output = bool;
+
+  fn generate(&self, context: &GenerationContext, region: Region) -> Result<DenseGrid<bool>, GenerationError> {
+    let mask = self.mask.generate(context, region)?;
+    let mut output = DenseGrid::new(region.size.x as usize, region.size.y as usize);
+    let spacing = self.min_spacing.ceil() as i32;
+
+    for position in region.positions() {
+      let local = position - region.origin;
+
+      if !mask.get(local.x, local.y).copied().unwrap_or(false) {
+        continue;
+      }
+
+      let Some(rank) = self.rank(context, position) else {
+        continue;
+      };
+
+      // candidates only depend on position, so this agrees across regions
+      let outranked = (-spacing..=spacing).any(|y| {
+        (-spacing..=spacing).any(|x| {
+          let offset = IVec2::new(x, y);
+          let other = position + offset;
+
+          offset != IVec2::ZERO
+            && Vec2::new(x as f32, y as f32).length() < self.min_spacing
+            && self.rank(context, other).is_some_and(|other_rank| other_rank > rank)
+        })
+      });
+
+      output.set(local.x, local.y, !outranked);
+    }
+
+    Ok(output)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ivec2;
+
+  #[test]
+  fn it_should_mask_values() {
+    let context = GenerationContext::new(3);
+    let region = Region::new(ivec2(0, 0), uvec2(16, 16));
+
+    let noise = NoiseNode::new(0.2);
+    let land = noise.map(|height| height > 0.);
+    let masked = noise.masked_by(land).generate(&context, region).unwrap();
+
+    assert!(masked.as_slice().iter().flatten().all(|height| *height > 0.));
+    assert!(masked.as_slice().iter().any(|height| height.is_none()));
+  }
+
+  #[test]
+  fn it_should_scatter_with_spacing() {
+    let context = GenerationContext::new(11);
+    let region = Region::new(ivec2(0, 0), uvec2(32, 32));
+
+    let scatter = ScatterNode {
+      min_spacing: 3.,
+      ..ScatterNode::new(ConstantNode(true), 0.5)
+    };
+
+    let output = scatter.generate(&context, region).unwrap();
+    let points = region.positions_where(&output, |chosen| *chosen);
+
+    assert!(!points.is_empty());
+
+    for a in &points {
+      for b in &points {
+        assert!(a == b || a.as_vec2().distance(b.as_vec2()) >= 3.);
+      }
+    }
+
+    // the same seed gives the same points
+    let again = scatter.generate(&context, region).unwrap();
+
+    assert_eq!(region.positions_where(&again, |chosen| *chosen), points);
+  }
+}
@@ -0,0 +1,285 @@
+use super::*;
+
+/// The directions between adjacent cells, for adjacency rules.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Adjacency {
+  Left,
+  Right,
+  Up,
+  Down,
+}
+
+impl Adjacency {
+  const ALL: [Adjacency; 4] = [Adjacency::Left, Adjacency::Right, Adjacency::Up, Adjacency::Down];
+
+  /// The offset to the neighbouring cell in this direction.
+  pub fn offset(self) -> IVec2 {
+    match self {
+      Adjacency::Left => IVec2::new(-1, 0),
+      Adjacency::Right => IVec2::new(1, 0),
+      Adjacency::Up => IVec2::new(0, -1),
+      Adjacency::Down => IVec2::new(0, 1),
+    }
+  }
+
+  /// The opposite direction.
+  pub fn opposite(self) -> Self {
+    match self {
+      Adjacency::Left => Adjacency::Right,
+      Adjacency::Right => Adjacency::Left,
+      Adjacency::Up => Adjacency::Down,
+      Adjacency::Down => Adjacency::Up,
+    }
+  }
+}
+
+/// The set of tiles still possible in a cell, one bit per tile.
+type Domain = u64;
+
+/// A constraint solver that fills a region with tiles via Wave Function
+/// Collapse.
+///
+/// Tiles are identified by index, and rules list which tiles may sit next to
+/// each other in each direction. The solver repeatedly collapses the most
+/// constrained cell to a weighted random tile and propagates the consequences
+/// to its neighbours, restarting with a new seed on contradictions.
+#[derive(Clone, Debug)]
+pub struct WaveFunctionCollapse {
+  weights: Vec<f32>,
+  rules: Vec<[Domain; 4]>,
+  /// How many times to restart on contradiction before giving up.
+  pub max_attempts: u32,
+  /// Distinguishes this node's choices from other nodes with the same seed.
+  pub salt: u64,
+}
+
+impl Default for WaveFunctionCollapse {
+  fn default() -> Self {
+    Self {
+      weights: Vec::new(),
+      rules: Vec::new(),
+      max_attempts: 10,
+      salt: 2,
+    }
+  }
+}
+
+impl WaveFunctionCollapse {
+  /// The most tiles a single solver supports.
+  pub const MAX_TILES: usize = Domain::BITS as usize;
+
+  /// Creates a new, empty solver.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a tile with the given relative frequency, returning its index.
+  pub fn add_tile(&mut self, weight: f32) -> usize {
+    assert!(
+      self.weights.len() < Self::MAX_TILES,
+      "too many tiles for a single solver"
+    );
+
+    self.weights.push(weight.max(0.));
+    self.rules.push([0; 4]);
+    self.weights.len() - 1
+  }
+
+  /// Allows tile `b` to sit in the given direction of tile `a`, and vice versa.
+  pub fn allow(&mut self, a: usize, direction: Adjacency, b: usize) {
+    self.rules[a][direction as usize] |= 1 << b;
+    self.rules[b][direction.opposite() as usize] |= 1 << a;
+  }
+
+  /// Allows the tiles to sit next to each other in every direction.
+  pub fn allow_all(&mut self, a: usize, b: usize) {
+    for direction in Adjacency::ALL {
+      self.allow(a, direction, b);
+    }
+  }
+
+  /// The number of tiles.
+  pub fn tile_count(&self) -> usize {
+    self.weights.len()
+  }
+
+  /// Attempts a single solve, returning `None` on contradiction.
+  fn solve(&self, region: Region, random: &mut Random) -> Option<Vec<Domain>> {
+    let width = region.size.x as i32;
+    let height = region.size.y as i32;
+    let all = match self.weights.len() {
+      Self::MAX_TILES => Domain::MAX,
+      count => (1 << count) - 1,
+    };
+
+    let mut cells = vec![all; (width * height) as usize];
+
+    // propagate everything once up front, in case the rules alone
+    // over-constrain
+    let mut pending = (0..cells.len()).collect::<Vec<_>>();
+
+    loop {
+      self.propagate(&mut cells, &mut pending, width, height)?;
+
+      // find the most constrained cell that's still undecided
+      let mut best = None;
+      let mut best_entropy = f32::MAX;
+
+      for (index, domain) in cells.iter().enumerate() {
+        let count = domain.count_ones();
+
+        if count > 1 {
+          let entropy = count as f32 + random.next::<f32>() * 0.1;
+
+          if entropy < best_entropy {
+            best_entropy = entropy;
+            best = Some(index);
+          }
+        }
+      }
+
+      let Some(index) = best else {
+        return Some(cells);
+      };
+
+      cells[index] = 1 << self.choose(cells[index], random)?;
+      pending.push(index);
+    }
+  }
+
+  /// Narrows the neighbours of the pending cells until nothing changes.
+  fn propagate(&self, cells: &mut [Domain], pending: &mut Vec<usize>, width: i32, height: i32) -> Option<()> {
+    while let Some(index) = pending.pop() {
+      let position = IVec2::new(index as i32 % width, index as i32 / width);
+
+      for direction in Adjacency::ALL {
+        let neighbour = position + direction.offset();
+
+        if neighbour.x < 0 || neighbour.y < 0 || neighbour.x >= width || neighbour.y >= height {
+          continue;
+        }
+
+        let allowed = self.allowed(cells[index], direction);
+        let neighbour_index = (neighbour.y * width + neighbour.x) as usize;
+        let constrained = cells[neighbour_index] & allowed;
+
+        if constrained == 0 {
+          return None;
+        }
+
+        if constrained != cells[neighbour_index] {
+          cells[neighbour_index] = constrained;
+          pending.push(neighbour_index);
+        }
+      }
+    }
+
+    Some(())
+  }
+
+  /// The tiles allowed next to any tile in the domain, in the given direction.
+  fn allowed(&self, domain: Domain, direction: Adjacency) -> Domain {
+    (0..self.weights.len())
+      .filter(|tile| domain & (1 << tile) != 0)
+      .fold(0, |allowed, tile| allowed | self.rules[tile][direction as usize])
+  }
+
+  /// Picks a weighted random tile from the domain.
+  fn choose(&self, domain: Domain, random: &mut Random) -> Option<usize> {
+    let mut tiles = (0..self.weights.len()).filter(|tile| domain & (1 << tile) != 0);
+    let total: f32 = tiles.clone().map(|tile| self.weights[tile]).sum();
+
+    if total <= 0. {
+      return tiles.clone().next();
+    }
+
+    let mut target = random.next::<f32>() * total;
+
+    for tile in tiles.clone() {
+      target -= self.weights[tile];
+
+      if target <= 0. {
+        return Some(tile);
+      }
+    }
+
+    tiles.next_back()
+  }
+}
+
+impl GeneratorNode for WaveFunctionCollapse {
+  type Output = usize;
+
+  fn generate(&self, context: &GenerationContext, region: Region) -> Result<DenseGrid<usize>, GenerationError> {
+    if self.weights.is_empty() {
+      return Err(GenerationError::Contradiction);
+    }
+
+    for attempt in 0..self.max_attempts.max(1) {
+      let mut random = context.random_at(self.salt.wrapping_add(attempt as u64), region.origin);
+
+      if let Some(cells) = self.solve(region, &mut random) {
+        let tiles = cells
+          .iter()
+          .map(|domain| domain.trailing_zeros() as usize)
+          .collect::<Vec<_>>();
+
+        return Ok(DenseGrid::from_slice(region.size.x as usize, &tiles));
+      }
+    }
+
+    Err(GenerationError::Contradiction)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ivec2;
+
+  #[test]
+  fn it_should_respect_adjacency_rules() {
+    // water may touch sand, sand may touch grass, but water may not touch grass
+    let mut solver = WaveFunctionCollapse::new();
+
+    let water = solver.add_tile(1.);
+    let sand = solver.add_tile(1.);
+    let grass = solver.add_tile(1.);
+
+    solver.allow_all(water, water);
+    solver.allow_all(sand, sand);
+    solver.allow_all(grass, grass);
+    solver.allow_all(water, sand);
+    solver.allow_all(sand, grass);
+
+    let region = Region::new(ivec2(0, 0), uvec2(16, 16));
+    let output = solver.generate(&GenerationContext::new(5), region).unwrap();
+
+    for position in region.positions() {
+      let tile = *output.get(position.x, position.y).unwrap();
+
+      for direction in Adjacency::ALL {
+        let neighbour = position + direction.offset();
+
+        if let Some(&other) = output.get(neighbour.x, neighbour.y) {
+          assert!(solver.rules[tile][direction as usize] & (1 << other) != 0);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn it_should_fail_on_impossible_rules() {
+    let mut solver = WaveFunctionCollapse::new();
+
+    // a lone tile that can't be next to anything, not even itself
+    solver.add_tile(1.);
+
+    let region = Region::new(ivec2(0, 0), uvec2(2, 2));
+
+    assert!(matches!(
+      solver.generate(&GenerationContext::new(0), region),
+      Err(GenerationError::Contradiction)
+    ));
+  }
+}
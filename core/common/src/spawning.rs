@@ -0,0 +1,346 @@
+//! Spawning and despawning of pooled entities.
+//!
+//! A [`Spawner`] picks what to spawn from a weighted [`SpawnTable`], places it
+//! within a [`SpawnArea`], and despawns it again according to a
+//! [`DespawnPolicy`]. Spawned instances live in an [`ObjectPool`], so waves of
+//! enemies or bullets don't allocate once the pool has warmed up.
+
+use crate::{vec2, ObjectPool, PoolId, Random, Rectangle, Vec2};
+
+/// What a spawn condition can see when deciding whether to allow a spawn.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SpawnContext {
+  /// Seconds since the spawner started.
+  pub elapsed: f32,
+  /// How many instances are currently alive.
+  pub alive: usize,
+  /// How many instances have been spawned in total.
+  pub spawned: usize,
+}
+
+/// An entry in a [`SpawnTable`].
+struct SpawnEntry<K> {
+  kind: K,
+  weight: f32,
+  condition: Option<SpawnCondition>,
+}
+
+/// Decides whether an entry in a [`SpawnTable`] may spawn.
+type SpawnCondition = Box<dyn Fn(&SpawnContext) -> bool>;
+
+/// A weighted table of things to spawn, with optional conditions.
+pub struct SpawnTable<K> {
+  entries: Vec<SpawnEntry<K>>,
+}
+
+impl<K> Default for SpawnTable<K> {
+  fn default() -> Self {
+    Self { entries: Vec::new() }
+  }
+}
+
+impl<K> SpawnTable<K> {
+  /// Creates a new, empty table.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds something that can always spawn.
+  pub fn add(&mut self, kind: K, weight: f32) {
+    self.entries.push(SpawnEntry {
+      kind,
+      weight,
+      condition: None,
+    });
+  }
+
+  /// Adds something that can only spawn when the condition holds.
+  pub fn add_when(&mut self, kind: K, weight: f32, condition: impl Fn(&SpawnContext) -> bool + 'static) {
+    self.entries.push(SpawnEntry {
+      kind,
+      weight,
+      condition: Some(Box::new(condition)),
+    });
+  }
+
+  /// Picks something to spawn from the entries allowed in the given context.
+  pub fn select(&self, context: &SpawnContext, random: &mut Random) -> Option<&K> {
+    let eligible = || {
+      self
+        .entries
+        .iter()
+        .filter(|entry| entry.weight > 0. && entry.condition.as_ref().is_none_or(|condition| condition(context)))
+    };
+
+    // two passes rather than collecting, so spawning doesn't allocate
+    let total_weight: f32 = eligible().map(|entry| entry.weight).sum();
+    let mut weight = random.next::<f32>() * total_weight;
+
+    eligible()
+      .find(|entry| {
+        weight -= entry.weight;
+        weight <= 0.
+      })
+      .or_else(|| eligible().last())
+      .map(|entry| &entry.kind)
+  }
+}
+
+/// Where a [`Spawner`] places what it spawns.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpawnArea {
+  /// Always the same point.
+  Point(Vec2),
+  /// Anywhere in a circle.
+  Circle { center: Vec2, radius: f32 },
+  /// Anywhere in a rectangle.
+  Rectangle(Rectangle),
+  /// One of a set of points, e.g. doors or portals.
+  Points(Vec<Vec2>),
+}
+
+impl SpawnArea {
+  /// Picks a random position in the area.
+  pub fn sample(&self, random: &mut Random) -> Vec2 {
+    match self {
+      SpawnArea::Point(point) => *point,
+      SpawnArea::Circle { center, radius } => {
+        let angle = random.next::<f32>() * std::f32::consts::TAU;
+        let distance = random.next::<f32>().sqrt() * radius;
+
+        *center + vec2(angle.cos(), angle.sin()) * distance
+      }
+      SpawnArea::Rectangle(rectangle) => {
+        rectangle.min + (rectangle.max - rectangle.min) * vec2(random.next(), random.next())
+      }
+      SpawnArea::Points(points) => random.choose(points.iter().copied()).unwrap_or_default(),
+    }
+  }
+}
+
+/// When spawned instances are removed again.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DespawnPolicy {
+  /// Despawn after this many seconds alive.
+  pub max_lifetime: Option<f32>,
+  /// Despawn once this far from the focus point (e.g. the player or camera).
+  pub max_distance: Option<f32>,
+}
+
+impl DespawnPolicy {
+  /// Determines if an instance should be despawned.
+  pub fn should_despawn(&self, age: f32, position: Vec2, focus: Vec2) -> bool {
+    let too_old = self.max_lifetime.is_some_and(|lifetime| age >= lifetime);
+    let too_far = self
+      .max_distance
+      .is_some_and(|distance| position.distance(focus) > distance);
+
+    too_old || too_far
+  }
+}
+
+/// An instance created by a [`Spawner`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpawnedInstance<K> {
+  pub kind: K,
+  pub position: Vec2,
+  /// Seconds since it was spawned.
+  pub age: f32,
+}
+
+/// Something that happened during a [`Spawner`] update.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpawnEvent<K> {
+  Spawned { id: PoolId, kind: K, position: Vec2 },
+  Despawned { id: PoolId, kind: K },
+}
+
+/// Periodically spawns instances from a table, and despawns them by policy.
+pub struct Spawner<K> {
+  pub table: SpawnTable<K>,
+  pub area: SpawnArea,
+  /// Seconds between spawns.
+  pub interval: f32,
+  /// The most instances alive at once.
+  pub max_alive: usize,
+  pub despawn: DespawnPolicy,
+  instances: ObjectPool<SpawnedInstance<K>>,
+  context: SpawnContext,
+  timer: f32,
+}
+
+impl<K: Clone + Default + 'static> Spawner<K> {
+  /// Creates a new spawner.
+  pub fn new(table: SpawnTable<K>, area: SpawnArea, interval: f32) -> Self {
+    Self {
+      table,
+      area,
+      interval,
+      max_alive: usize::MAX,
+      despawn: DespawnPolicy::default(),
+      instances: ObjectPool::new(SpawnedInstance::default),
+      context: SpawnContext::default(),
+      timer: 0.,
+    }
+  }
+
+  /// Pre-allocates room for the given number of live instances.
+  pub fn warm_up(&mut self, count: usize) {
+    self.instances.warm_up(count);
+  }
+
+  /// The instances currently alive.
+  pub fn instances(&self) -> impl Iterator<Item = (PoolId, &SpawnedInstance<K>)> {
+    self.instances.iter()
+  }
+
+  /// Moves an instance, e.g. to follow its entity.
+  pub fn set_position(&mut self, id: PoolId, position: Vec2) {
+    if let Some(instance) = self.instances.get_mut(id) {
+      instance.position = position;
+    }
+  }
+
+  /// Despawns an instance early, e.g. when it's destroyed.
+  pub fn despawn(&mut self, id: PoolId) -> bool {
+    self.instances.release(id)
+  }
+
+  /// Advances time, spawning and despawning as needed.
+  pub fn update(&mut self, delta_time: f32, focus: Vec2, random: &mut Random) -> Vec<SpawnEvent<K>> {
+    let mut events = Vec::new();
+
+    self.context.elapsed += delta_time;
+    self.timer += delta_time;
+
+    // age everything and collect what's expired
+    let mut expired = Vec::new();
+
+    for (id, instance) in self.instances.iter_mut() {
+      instance.age += delta_time;
+
+      if self.despawn.should_despawn(instance.age, instance.position, focus) {
+        expired.push((id, instance.kind.clone()));
+      }
+    }
+
+    for (id, kind) in expired {
+      self.instances.release(id);
+      events.push(SpawnEvent::Despawned { id, kind });
+    }
+
+    while self.interval > 0. && self.timer >= self.interval {
+      self.timer -= self.interval;
+      self.context.alive = self.instances.active_count();
+
+      if self.context.alive >= self.max_alive {
+        continue;
+      }
+
+      let Some(kind) = self.table.select(&self.context, random).cloned() else {
+        continue;
+      };
+
+      let position = self.area.sample(random);
+      let id = self.instances.acquire();
+
+      if let Some(instance) = self.instances.get_mut(id) {
+        instance.kind = kind.clone();
+        instance.position = position;
+        instance.age = 0.;
+      }
+
+      self.context.spawned += 1;
+      events.push(SpawnEvent::Spawned { id, kind, position });
+    }
+
+    self.context.alive = self.instances.active_count();
+
+    events
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_respect_spawn_conditions() {
+    let mut table = SpawnTable::new();
+    let mut random = Random::with_seed(1);
+
+    table.add("grunt", 1.);
+    table.add_when("boss", 100., |context| context.elapsed >= 60.);
+
+    let early = SpawnContext::default();
+    let late = SpawnContext {
+      elapsed: 60.,
+      ..Default::default()
+    };
+
+    for _ in 0..20 {
+      assert_eq!(table.select(&early, &mut random), Some(&"grunt"));
+    }
+
+    let bosses = (0..100)
+      .filter(|_| table.select(&late, &mut random) == Some(&"boss"))
+      .count();
+
+    assert!(bosses > 90);
+  }
+
+  #[test]
+  fn it_should_sample_inside_areas() {
+    let mut random = Random::with_seed(2);
+    let area = SpawnArea::Circle {
+      center: vec2(5., 5.),
+      radius: 2.,
+    };
+
+    for _ in 0..100 {
+      assert!(area.sample(&mut random).distance(vec2(5., 5.)) <= 2.);
+    }
+  }
+
+  #[test]
+  fn it_should_spawn_on_interval_and_despawn_by_policy() {
+    let mut table = SpawnTable::new();
+    let mut random = Random::with_seed(3);
+
+    table.add(1u32, 1.);
+
+    let mut spawner = Spawner::new(table, SpawnArea::Point(Vec2::ZERO), 1.);
+
+    spawner.max_alive = 3;
+    spawner.despawn.max_lifetime = Some(2.5);
+    spawner.warm_up(3);
+
+    let mut spawned = 0;
+    let mut despawned = 0;
+
+    for _ in 0..10 {
+      for event in spawner.update(1., Vec2::ZERO, &mut random) {
+        match event {
+          SpawnEvent::Spawned { .. } => spawned += 1,
+          SpawnEvent::Despawned { .. } => despawned += 1,
+        }
+      }
+
+      assert!(spawner.instances().count() <= 3);
+    }
+
+    assert_eq!(spawned, 10);
+    assert_eq!(despawned, 7);
+
+    // distance policies remove things the focus has left behind
+    spawner.despawn = DespawnPolicy {
+      max_distance: Some(10.),
+      ..Default::default()
+    };
+
+    let events = spawner.update(0.5, vec2(100., 0.), &mut random);
+
+    assert_eq!(events.len(), 3);
+    assert_eq!(spawner.instances().count(), 0);
+  }
+}
@@ -5,7 +5,9 @@ use std::{any::Any, sync::Arc};
 pub use crashes::*;
 pub use errors::*;
 pub use events::*;
+pub use layers::*;
 pub use owned::*;
+pub use servers::*;
 pub use settings::*;
 pub use singleton::*;
 pub use version::*;
@@ -13,7 +15,9 @@ pub use version::*;
 mod crashes;
 mod errors;
 mod events;
+mod layers;
 mod owned;
+mod servers;
 mod settings;
 mod singleton;
 mod version;
@@ -66,31 +70,122 @@ pub fn downcast_arc<T: Any + 'static>(arc: Arc<dyn Any>) -> Result<Arc<T>, Arc<d
 }
 
 /// Implements a new server type for the given backend.
+///
+/// By default the backend must be `Send + Sync`, so the server can be used
+/// from any thread. Servers declared `thread bound` instead accept any backend,
+/// but bind to the thread that installs it; using the server from any other
+/// thread panics, and such threads should `defer` their commands to it
+/// instead, through a [`CommandQueue`].
+///
+/// A backend can only be installed once. Until then the server uses its
+/// default backend (e.g. in tests); thread bound servers give each thread a
+/// default of its own.
 #[macro_export]
 macro_rules! impl_server {
-  ($type:ident by $backend:ident default $default:ty) => {
+  ($type:ident by $backend:ident default $default:ty, thread bound) => {
     pub struct $type {
-      backend: core::cell::UnsafeCell<Box<dyn $backend>>,
+      backend: std::sync::OnceLock<Box<dyn $backend>>,
     }
 
-    static SINGLETON: $crate::UnsafeSingleton<$type> = $crate::UnsafeSingleton::new(|| $type {
-      backend: core::cell::UnsafeCell::new(Box::new(<$default>::default())),
-    });
+    static SINGLETON: $type = $type {
+      backend: std::sync::OnceLock::new(),
+    };
+
+    static SERVER_THREAD: $crate::ServerThread = $crate::ServerThread::new();
+    static SERVER_COMMANDS: $crate::CommandQueue<dyn $backend> = $crate::CommandQueue::new();
+
+    std::thread_local! {
+      static DEFAULT_BACKEND: &'static dyn $backend = Box::leak(Box::new(<$default>::default()));
+    }
 
+    // the installed backend is only handed out on the thread SERVER_THREAD is bound
+    // to
     unsafe impl Send for $type {}
     unsafe impl Sync for $type {}
 
     impl $type {
       /// Gets the singleton instance of the [`$type`].
+      ///
+      /// Panics if a backend is installed, and this isn't the thread it was
+      /// installed on.
       pub fn instance() -> &'static dyn $backend {
-        use std::ops::Deref;
-
-        unsafe { SINGLETON.backend.get().as_ref().unwrap().deref() }
+        match SINGLETON.backend.get() {
+          Some(backend) => {
+            SERVER_THREAD.check(stringify!($type));
+            backend.as_ref()
+          }
+          None => DEFAULT_BACKEND.with(|backend| *backend),
+        }
       }
 
-      /// Creates a new [`$type`] for the given [`$backend`].
+      /// Installs the [`$backend`] for the [`$type`], bound to the current
+      /// thread.
+      ///
+      /// Panics if a backend is already installed.
       pub fn install(backend: impl $backend + 'static) {
-        unsafe { SINGLETON.backend.get().replace(Box::new(backend)) };
+        SERVER_THREAD.bind(stringify!($type));
+
+        if SINGLETON.backend.set(Box::new(backend)).is_err() {
+          panic!("{} already has a backend installed", stringify!($type));
+        }
+      }
+
+      /// Determines if the current thread is the one the backend was
+      /// installed on.
+      pub fn is_server_thread() -> bool {
+        SERVER_THREAD.is_current()
+      }
+
+      /// Runs a command against the backend, from any thread.
+      ///
+      /// On the server's thread, or before a backend is installed, the command
+      /// runs immediately; elsewhere it's queued until the next
+      /// [`Self::flush_commands`]. Resources release themselves through this,
+      /// since they may be dropped off the server's thread.
+      pub fn defer(command: impl FnOnce(&(dyn $backend + 'static)) + Send + 'static) {
+        if SINGLETON.backend.get().is_none() || SERVER_THREAD.is_current() {
+          command(Self::instance());
+        } else {
+          SERVER_COMMANDS.enqueue(Box::new(command));
+        }
+      }
+
+      /// Runs the commands deferred from other threads, returning how many ran.
+      ///
+      /// Call this once per frame from the server's thread.
+      pub fn flush_commands() -> usize {
+        SERVER_COMMANDS.flush(Self::instance())
+      }
+    }
+  };
+  ($type:ident by $backend:ident default $default:ty) => {
+    pub struct $type {
+      backend: std::sync::OnceLock<Box<dyn $backend + Send + Sync>>,
+    }
+
+    static SINGLETON: $type = $type {
+      backend: std::sync::OnceLock::new(),
+    };
+
+    impl $type {
+      /// Gets the singleton instance of the [`$type`].
+      ///
+      /// If no backend is installed yet, the default is installed.
+      pub fn instance() -> &'static (dyn $backend + Send + Sync) {
+        SINGLETON
+          .backend
+          .get_or_init(|| Box::new(<$default>::default()))
+          .as_ref()
+      }
+
+      /// Installs the [`$backend`] for the [`$type`].
+      ///
+      /// Install backends during startup; panics if a backend is already
+      /// installed, including the default one installed by using the server.
+      pub fn install(backend: impl $backend + Send + Sync + 'static) {
+        if SINGLETON.backend.set(Box::new(backend)).is_err() {
+          panic!("{} already has a backend installed", stringify!($type));
+        }
       }
     }
   };
@@ -113,6 +208,54 @@ macro_rules! impl_error_coercion {
 mod tests {
   use super::*;
 
+  pub trait TestBackend {
+    fn name(&self) -> &'static str;
+  }
+
+  #[derive(Default)]
+  struct DefaultBackend;
+
+  impl TestBackend for DefaultBackend {
+    fn name(&self) -> &'static str {
+      "default"
+    }
+  }
+
+  struct InstalledBackend;
+
+  impl TestBackend for InstalledBackend {
+    fn name(&self) -> &'static str {
+      "installed"
+    }
+  }
+
+  crate::impl_server!(TestServer by TestBackend default DefaultBackend, thread bound);
+
+  #[test]
+  fn it_should_install_thread_bound_servers_once() {
+    std::thread::spawn(|| {
+      assert_eq!(TestServer::instance().name(), "default");
+      assert!(!TestServer::is_server_thread());
+
+      TestServer::install(InstalledBackend);
+
+      assert_eq!(TestServer::instance().name(), "installed");
+      assert!(TestServer::is_server_thread());
+      assert!(std::panic::catch_unwind(|| TestServer::install(InstalledBackend)).is_err());
+
+      std::thread::spawn(|| TestServer::defer(|backend| assert_eq!(backend.name(), "installed")))
+        .join()
+        .unwrap();
+
+      assert_eq!(TestServer::flush_commands(), 1);
+    })
+    .join()
+    .unwrap();
+
+    assert!(!TestServer::is_server_thread());
+    assert!(std::panic::catch_unwind(|| TestServer::instance().name()).is_err());
+  }
+
   #[test]
   fn test_downcast_arc_between_types() {
     let initial = Arc::new("Hello, World!");
@@ -0,0 +1,343 @@
+//! Layers and tags shared across the engine.
+//!
+//! A [`LayerMask`] is a set of up to 32 layers, and the same mask decides
+//! which entities a camera draws and which colliders touch; layers are named
+//! once in the [`LayerRegistry`], so "Enemies" is the same bit everywhere.
+//! [`Tags`] are free-form names for finding entities, with no limit.
+
+use std::{
+  fmt::{Debug, Formatter},
+  ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not},
+  sync::RwLock,
+};
+
+use crate::{Singleton, StringName};
+
+/// The number of layers in a [`LayerMask`].
+pub const MAX_LAYERS: usize = 32;
+
+/// A set of layers, as a bit mask.
+#[repr(transparent)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LayerMask(pub u32);
+
+impl LayerMask {
+  /// No layers.
+  pub const NONE: Self = Self(0);
+  /// Every layer.
+  pub const ALL: Self = Self(u32::MAX);
+  /// The layer everything is on unless told otherwise.
+  pub const DEFAULT: Self = Self::layer(0);
+
+  /// A mask of the single layer at the given index.
+  pub const fn layer(index: usize) -> Self {
+    assert!(index < MAX_LAYERS, "layer index out of range");
+
+    Self(1 << index)
+  }
+
+  /// The mask of the named layer in the global [`LayerRegistry`], registering
+  /// it if it's new.
+  ///
+  /// Panics if every layer is already taken.
+  pub fn named(name: &str) -> Self {
+    LayerRegistry::instance()
+      .register(name)
+      .unwrap_or_else(|_| panic!("no layers left to register '{name}'"))
+  }
+
+  /// Determines if no layers are set.
+  pub const fn is_empty(self) -> bool {
+    self.0 == 0
+  }
+
+  /// Determines if every layer of the other mask is set.
+  pub const fn contains(self, other: Self) -> bool {
+    self.0 & other.0 == other.0
+  }
+
+  /// Determines if any layer is set in both masks.
+  pub const fn intersects(self, other: Self) -> bool {
+    self.0 & other.0 != 0
+  }
+
+  /// Adds the layers of the other mask.
+  pub const fn with(self, other: Self) -> Self {
+    Self(self.0 | other.0)
+  }
+
+  /// Removes the layers of the other mask.
+  pub const fn without(self, other: Self) -> Self {
+    Self(self.0 & !other.0)
+  }
+
+  /// The indices of the layers that are set, lowest first.
+  pub fn indices(self) -> impl Iterator<Item = usize> {
+    (0..MAX_LAYERS).filter(move |&index| self.0 & (1 << index) != 0)
+  }
+}
+
+impl BitOr for LayerMask {
+  type Output = Self;
+
+  #[inline]
+  fn bitor(self, rhs: Self) -> Self::Output {
+    self.with(rhs)
+  }
+}
+
+impl BitOrAssign for LayerMask {
+  #[inline]
+  fn bitor_assign(&mut self, rhs: Self) {
+    *self = self.with(rhs);
+  }
+}
+
+impl BitAnd for LayerMask {
+  type Output = Self;
+
+  #[inline]
+  fn bitand(self, rhs: Self) -> Self::Output {
+    Self(self.0 & rhs.0)
+  }
+}
+
+impl BitAndAssign for LayerMask {
+  #[inline]
+  fn bitand_assign(&mut self, rhs: Self) {
+    self.0 &= rhs.0;
+  }
+}
+
+impl Not for LayerMask {
+  type Output = Self;
+
+  #[inline]
+  fn not(self) -> Self::Output {
+    Self(!self.0)
+  }
+}
+
+/// Lists the layers by name where they have one, e.g. `LayerMask(Default |
+/// Enemies | 7)`.
+impl Debug for LayerMask {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    let registry = LayerRegistry::instance();
+    let names = self
+      .indices()
+      .map(|index| registry.name_of(index).unwrap_or_else(|| index.to_string()))
+      .collect::<Vec<_>>();
+
+    write!(formatter, "LayerMask({})", names.join(" | "))
+  }
+}
+
+/// An error when registering a layer.
+#[derive(Debug, PartialEq)]
+pub enum LayerError {
+  /// Every layer already has a name.
+  NoLayersLeft,
+  /// The layer index already has a different name.
+  AlreadyNamed(usize, String),
+}
+
+/// The names of the layers.
+///
+/// Games register their layers once at startup, and every system looks masks
+/// up by name from then on. Layer 0 is always `Default`.
+#[derive(Singleton)]
+pub struct LayerRegistry {
+  names: RwLock<[Option<String>; MAX_LAYERS]>,
+}
+
+impl Default for LayerRegistry {
+  fn default() -> Self {
+    let mut names = [const { None }; MAX_LAYERS];
+
+    names[0] = Some("Default".to_string());
+
+    Self {
+      names: RwLock::new(names),
+    }
+  }
+}
+
+impl LayerRegistry {
+  /// Names the next free layer, returning its mask.
+  ///
+  /// Registering a name twice returns the same layer.
+  pub fn register(&self, name: &str) -> Result<LayerMask, LayerError> {
+    let mut names = self.names.write().unwrap();
+
+    if let Some(index) = names.iter().position(|it| it.as_deref() == Some(name)) {
+      return Ok(LayerMask::layer(index));
+    }
+
+    let index = names.iter().position(Option::is_none).ok_or(LayerError::NoLayersLeft)?;
+
+    names[index] = Some(name.to_string());
+
+    Ok(LayerMask::layer(index))
+  }
+
+  /// Names the layer at the given index, so it matches indices baked into
+  /// content or shared with other tools.
+  pub fn register_at(&self, index: usize, name: &str) -> Result<LayerMask, LayerError> {
+    let mut names = self.names.write().unwrap();
+
+    match &names[index] {
+      Some(existing) if existing != name => Err(LayerError::AlreadyNamed(index, existing.clone())),
+      _ => {
+        names[index] = Some(name.to_string());
+
+        Ok(LayerMask::layer(index))
+      }
+    }
+  }
+
+  /// The mask of the named layer, if it's been registered.
+  pub fn get(&self, name: &str) -> Option<LayerMask> {
+    let names = self.names.read().unwrap();
+
+    names
+      .iter()
+      .position(|it| it.as_deref() == Some(name))
+      .map(LayerMask::layer)
+  }
+
+  /// The mask of all the named layers; unregistered names are skipped.
+  pub fn mask(&self, names: &[&str]) -> LayerMask {
+    names
+      .iter()
+      .filter_map(|name| self.get(name))
+      .fold(LayerMask::NONE, LayerMask::with)
+  }
+
+  /// The name of the layer at the given index, if it has one.
+  pub fn name_of(&self, index: usize) -> Option<String> {
+    self.names.read().unwrap().get(index)?.clone()
+  }
+}
+
+/// A set of tags on an entity, for finding it by name.
+///
+/// Unlike layers, tags aren't limited in number, but they take no part in
+/// culling or collision.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Tags {
+  names: Vec<StringName>,
+}
+
+impl Tags {
+  /// Creates an empty set of tags.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a tag.
+  pub fn with(mut self, tag: impl Into<StringName>) -> Self {
+    self.insert(tag);
+    self
+  }
+
+  /// Adds a tag, if it's not already present.
+  pub fn insert(&mut self, tag: impl Into<StringName>) {
+    let tag = tag.into();
+
+    if !self.names.contains(&tag) {
+      self.names.push(tag);
+    }
+  }
+
+  /// Removes a tag, returning whether it was present.
+  pub fn remove(&mut self, tag: impl Into<StringName>) -> bool {
+    let tag = tag.into();
+    let count = self.names.len();
+
+    self.names.retain(|it| *it != tag);
+    self.names.len() != count
+  }
+
+  /// Determines if the tag is present.
+  pub fn contains(&self, tag: impl Into<StringName>) -> bool {
+    self.names.contains(&tag.into())
+  }
+
+  /// Iterates over the tags, in the order they were added.
+  pub fn iter(&self) -> impl Iterator<Item = &StringName> {
+    self.names.iter()
+  }
+
+  pub fn len(&self) -> usize {
+    self.names.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.names.is_empty()
+  }
+}
+
+impl Debug for Tags {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    formatter.debug_set().entries(self.names.iter()).finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_combine_and_test_masks() {
+    let players = LayerMask::layer(1);
+    let enemies = LayerMask::layer(2);
+    let mask = players | enemies;
+
+    assert!(mask.contains(players));
+    assert!(!players.contains(mask));
+    assert!(mask.intersects(enemies));
+    assert!(!mask.intersects(LayerMask::DEFAULT));
+    assert_eq!(mask.without(players), enemies);
+    assert_eq!(!LayerMask::ALL, LayerMask::NONE);
+    assert_eq!(mask.indices().collect::<Vec<_>>(), vec![1, 2]);
+  }
+
+  #[test]
+  fn it_should_register_layers_by_name() {
+    let registry = LayerRegistry::default();
+
+    let enemies = registry.register("Enemies").unwrap();
+
+    assert_eq!(enemies, LayerMask::layer(1));
+    assert_eq!(registry.register("Enemies").unwrap(), enemies);
+    assert_eq!(registry.get("Default"), Some(LayerMask::DEFAULT));
+    assert_eq!(registry.get("Missing"), None);
+
+    let water = registry.register_at(8, "Water").unwrap();
+
+    assert_eq!(registry.mask(&["Enemies", "Water", "Missing"]), enemies | water);
+    assert_eq!(
+      registry.register_at(8, "Lava"),
+      Err(LayerError::AlreadyNamed(8, "Water".to_string()))
+    );
+
+    for index in 0..MAX_LAYERS {
+      let _ = registry.register(&format!("Layer {index}"));
+    }
+
+    assert_eq!(registry.register("Overflow"), Err(LayerError::NoLayersLeft));
+  }
+
+  #[test]
+  fn it_should_tag_by_name() {
+    let mut tags = Tags::new().with("boss").with("flying");
+
+    tags.insert("boss");
+
+    assert_eq!(tags.len(), 2);
+    assert!(tags.contains("flying"));
+    assert!(tags.remove("flying"));
+    assert!(!tags.remove("flying"));
+    assert!(!tags.contains("flying"));
+  }
+}
@@ -0,0 +1,169 @@
+//! Thread safety for server singletons.
+//!
+//! Servers come in two flavours (see [`crate::impl_server`]): those whose
+//! backends are `Send + Sync` and can be used from any thread, and those bound
+//! to the thread their backend was installed on (e.g. graphics, where the
+//! context belongs to one thread). Other threads can't touch a bound backend
+//! directly; instead they push commands into a [`CommandQueue`] that the bound
+//! thread flushes once per frame.
+
+use std::{
+  sync::{Mutex, OnceLock},
+  thread::ThreadId,
+};
+
+/// A command deferred to run against a backend of type `B`.
+pub type DeferredCommand<B> = Box<dyn FnOnce(&B) + Send>;
+
+/// A queue of commands to run against a backend on its own thread.
+pub struct CommandQueue<B: ?Sized> {
+  commands: Mutex<Vec<DeferredCommand<B>>>,
+}
+
+impl<B: ?Sized> Default for CommandQueue<B> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<B: ?Sized> CommandQueue<B> {
+  /// Creates a new, empty queue.
+  pub const fn new() -> Self {
+    Self {
+      commands: Mutex::new(Vec::new()),
+    }
+  }
+
+  /// The number of commands waiting to run.
+  pub fn len(&self) -> usize {
+    self.commands.lock().unwrap().len()
+  }
+
+  /// Determines if there are no commands waiting to run.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Adds a command to the end of the queue.
+  pub fn enqueue(&self, command: DeferredCommand<B>) {
+    self.commands.lock().unwrap().push(command);
+  }
+
+  /// Runs every queued command against the backend, in order, and returns how
+  /// many ran.
+  ///
+  /// Commands queued while flushing run on the next flush.
+  pub fn flush(&self, backend: &B) -> usize {
+    let commands = std::mem::take(&mut *self.commands.lock().unwrap());
+    let count = commands.len();
+
+    for command in commands {
+      command(backend);
+    }
+
+    count
+  }
+}
+
+/// Guards a server so that it's only used from the thread it's bound to.
+///
+/// No thread is current until [`ServerThread::bind`] is called.
+pub struct ServerThread {
+  owner: OnceLock<ThreadId>,
+}
+
+impl Default for ServerThread {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl ServerThread {
+  /// Creates a new, unbound guard.
+  pub const fn new() -> Self {
+    Self { owner: OnceLock::new() }
+  }
+
+  /// Binds the guard to the current thread.
+  ///
+  /// Panics if it's already bound to another thread.
+  pub fn bind(&self, server: &str) {
+    let current = std::thread::current().id();
+    let owner = *self.owner.get_or_init(|| current);
+
+    if owner != current {
+      panic!("{server} is already bound to thread {owner:?}, and can't be rebound to {current:?}");
+    }
+  }
+
+  /// Determines if the guard is bound to the current thread.
+  pub fn is_current(&self) -> bool {
+    self.owner.get() == Some(&std::thread::current().id())
+  }
+
+  /// Panics if the current thread may not use the server.
+  #[inline]
+  pub fn check(&self, server: &str) {
+    if !self.is_current() {
+      panic!("{server} can only be used from the thread it was installed on; defer commands to it instead");
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  };
+
+  use super::*;
+
+  #[test]
+  fn it_should_run_deferred_commands_in_order() {
+    let queue = Arc::new(CommandQueue::<Vec<usize>>::new());
+    let total = Arc::new(AtomicUsize::new(0));
+
+    let worker = {
+      let queue = queue.clone();
+      let total = total.clone();
+
+      std::thread::spawn(move || {
+        for index in 0..4 {
+          let total = total.clone();
+
+          queue.enqueue(Box::new(move |values: &Vec<usize>| {
+            total.fetch_add(values[index], Ordering::Relaxed);
+          }));
+        }
+      })
+    };
+
+    worker.join().unwrap();
+
+    assert_eq!(queue.len(), 4);
+    assert_eq!(queue.flush(&vec![1, 2, 3, 4]), 4);
+    assert_eq!(total.load(Ordering::Relaxed), 10);
+    assert!(queue.is_empty());
+  }
+
+  #[test]
+  fn it_should_guard_servers_to_their_bound_thread() {
+    let guard = Arc::new(ServerThread::new());
+
+    assert!(!guard.is_current());
+
+    let worker = {
+      let guard = guard.clone();
+
+      std::thread::spawn(move || {
+        guard.bind("TestServer");
+        guard.is_current()
+      })
+    };
+
+    assert!(worker.join().unwrap());
+    assert!(!guard.is_current());
+    assert!(std::panic::catch_unwind(|| guard.check("TestServer")).is_err());
+  }
+}
@@ -0,0 +1,137 @@
+//! Wind that blows across the world.
+//!
+//! A [`WindManager`] combines a global wind, which gusts over space and time,
+//! with local [`WindZone`]s like fans and updrafts. Anything that wants to be
+//! blown around samples it at its position.
+
+use crate::{impl_arena_index, vec2, Arena, PerlinNoise, Vec3};
+
+impl_arena_index!(pub WindZoneId, "Identifies a zone of local wind.");
+
+/// A sphere of local wind, fading out towards its edge.
+#[derive(Clone, Debug)]
+pub struct WindZone {
+  pub center: Vec3,
+  pub radius: f32,
+  pub velocity: Vec3,
+}
+
+/// The wind across the world.
+pub struct WindManager {
+  /// The steady wind everywhere, before gusts.
+  pub velocity: Vec3,
+  /// How much gusts add to or take from the wind, as a fraction of it.
+  pub gust_strength: f32,
+  /// How many gusts pass a point each second.
+  pub gust_frequency: f32,
+  /// The size of a gust, in world units.
+  pub gust_size: f32,
+  zones: Arena<WindZoneId, WindZone>,
+  noise: PerlinNoise,
+  time: f32,
+}
+
+impl Default for WindManager {
+  fn default() -> Self {
+    Self {
+      velocity: Vec3::ZERO,
+      gust_strength: 0.5,
+      gust_frequency: 0.25,
+      gust_size: 20.,
+      zones: Arena::new(),
+      noise: PerlinNoise::default(),
+      time: 0.,
+    }
+  }
+}
+
+impl WindManager {
+  /// Creates a new manager with the given steady wind.
+  pub fn new(velocity: Vec3) -> Self {
+    Self {
+      velocity,
+      ..Default::default()
+    }
+  }
+
+  /// Adds a zone of local wind.
+  pub fn add_zone(&mut self, zone: WindZone) -> WindZoneId {
+    self.zones.insert(zone)
+  }
+
+  /// Gets a zone of local wind, e.g. to move it.
+  pub fn zone_mut(&mut self, id: WindZoneId) -> Option<&mut WindZone> {
+    self.zones.get_mut(id)
+  }
+
+  /// Removes a zone of local wind.
+  pub fn remove_zone(&mut self, id: WindZoneId) -> Option<WindZone> {
+    self.zones.remove(id)
+  }
+
+  /// Advances the gusts.
+  pub fn update(&mut self, delta_time: f32) {
+    self.time += delta_time;
+  }
+
+  /// The velocity of the wind at a position.
+  pub fn sample(&self, position: Vec3) -> Vec3 {
+    // gusts roll across the ground, so sample noise over it and time
+    let point = vec2(position.x, position.z) / self.gust_size.max(f32::EPSILON);
+    let gust = self.noise.sample_2d(point + vec2(self.time * self.gust_frequency, 0.));
+
+    let mut velocity = self.velocity * (1. + gust * self.gust_strength);
+
+    for (_, zone) in self.zones.enumerate() {
+      let distance = zone.center.distance(position);
+
+      if distance < zone.radius {
+        velocity += zone.velocity * (1. - distance / zone.radius);
+      }
+    }
+
+    velocity
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::vec3;
+
+  #[test]
+  fn it_should_gust_around_the_steady_wind() {
+    let mut wind = WindManager::new(vec3(10., 0., 0.));
+    let mut samples = Vec::new();
+
+    for _ in 0..100 {
+      wind.update(0.1);
+      samples.push(wind.sample(vec3(3., 0., 7.)).x);
+    }
+
+    let min = samples.iter().copied().fold(f32::MAX, f32::min);
+    let max = samples.iter().copied().fold(f32::MIN, f32::max);
+
+    assert!(min >= 5. && max <= 15.);
+    assert!(max - min > 1.);
+  }
+
+  #[test]
+  fn it_should_add_local_zones() {
+    let mut wind = WindManager::default();
+
+    let updraft = wind.add_zone(WindZone {
+      center: Vec3::ZERO,
+      radius: 4.,
+      velocity: vec3(0., 8., 0.),
+    });
+
+    assert_eq!(wind.sample(Vec3::ZERO), vec3(0., 8., 0.));
+    assert_eq!(wind.sample(vec3(2., 0., 0.)), vec3(0., 4., 0.));
+    assert_eq!(wind.sample(vec3(5., 0., 0.)), Vec3::ZERO);
+
+    wind.remove_zone(updraft);
+
+    assert_eq!(wind.sample(Vec3::ZERO), Vec3::ZERO);
+  }
+}
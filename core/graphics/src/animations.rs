@@ -1,6 +1,30 @@
 //! Animation support.
-
-use common::{Color, Color32, FastHashMap, Lerp, Quat, StringName, TimeSpan, Vec2, Vec3};
+//!
+//! Clips can carry [`AnimationMarker`]s, which the tree sends through an
+//! [`EventBus`] as it plays past them, so audio, particles and combat can
+//! react to footsteps and attack windows without polling animation time:
+//!
+//! ```rust,ignore
+//! let events = EventBus::new();
+//!
+//! tree.update_with_events(delta_time, &events);
+//!
+//! for event in events.iter() {
+//!   match (event.name.as_ref(), event.kind) {
+//!     ("footstep", AnimationEventKind::Fired) => play_footstep(&event.payload),
+//!     ("attack", AnimationEventKind::Began) => combat.start_attack(attacker, swing.clone()),
+//!     ("attack", AnimationEventKind::Ended) => combat.cancel_attack(attacker),
+//!     _ => {}
+//!   }
+//! }
+//! ```
+
+use common::{Color, Color32, EventBus, FastHashMap, Lerp, Quat, StringName, TimeSpan, Variant, Vec2, Vec3};
+pub use ik::*;
+pub use retargeting::*;
+
+mod ik;
+mod retargeting;
 
 /// Represents a type that can be animated by an animation tree.
 pub trait Animatable<V> {
@@ -44,6 +68,75 @@ pub struct AnimationTransition<T> {
 pub struct AnimationClip {
   pub duration: TimeSpan,
   pub tracks: Vec<AnimationTrack>,
+  pub markers: Vec<AnimationMarker>,
+}
+
+/// A named point or window in a clip, sent as [`AnimationEvent`]s when the
+/// clip plays past it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationMarker {
+  pub name: StringName,
+  /// When the marker fires, in seconds from the start of the clip.
+  pub time: f32,
+  /// How long the marker's window stays open, in seconds, or zero for a
+  /// marker that just fires once.
+  pub duration: f32,
+  /// Data for whoever reacts to the marker, like the surface of a footstep.
+  pub payload: Variant,
+}
+
+impl AnimationMarker {
+  /// Creates a marker that fires once at the given time.
+  pub fn new(name: StringName, time: f32) -> Self {
+    Self {
+      name,
+      time,
+      duration: 0.,
+      payload: Variant::Null,
+    }
+  }
+
+  /// Creates a marker that opens a window, like when an attack is active.
+  pub fn window(name: StringName, start: f32, end: f32) -> Self {
+    Self {
+      duration: (end - start).max(0.),
+      ..Self::new(name, start)
+    }
+  }
+
+  /// Attaches a payload to the marker.
+  pub fn with_payload(mut self, payload: Variant) -> Self {
+    self.payload = payload;
+    self
+  }
+
+  /// When the marker's window closes.
+  pub fn end(&self) -> f32 {
+    self.time + self.duration
+  }
+}
+
+/// How an [`AnimationEvent`] relates to its marker.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AnimationEventKind {
+  /// The clip played past a marker with no window.
+  Fired,
+  /// The clip played into a marker's window.
+  Began,
+  /// The clip played out of a marker's window, or left the state while it
+  /// was open.
+  Ended,
+}
+
+/// A marker that an animation tree played past.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationEvent {
+  /// The state whose clip the marker is on.
+  pub state: StringName,
+  /// The name of the marker.
+  pub name: StringName,
+  pub kind: AnimationEventKind,
+  pub payload: Variant,
 }
 
 /// Data for a single animation track.
@@ -114,12 +207,27 @@ impl<T> AnimationTree<T> {
 
   /// Updates the animation tree.
   pub fn update(&mut self, delta_time: f32) {
+    self.advance(delta_time, &mut |_| {});
+  }
+
+  /// Updates the animation tree, sending the markers it plays past to the
+  /// given event bus.
+  pub fn update_with_events(&mut self, delta_time: f32, events: &EventBus<AnimationEvent>) {
+    self.advance(delta_time, &mut |event| events.send(event));
+  }
+
+  fn advance(&mut self, delta_time: f32, emit: &mut dyn FnMut(AnimationEvent)) {
     if let Some(state) = self.current.and_then(|it| self.nodes.get_mut(&it)) {
+      let start = state.time_elapsed.as_seconds();
+
       state.time_elapsed += TimeSpan::from_seconds(state.speed * delta_time);
 
       // loop the animation if it's finished
       if state.time_elapsed > state.clip.duration {
+        emit_markers(state, start, state.clip.duration.as_seconds(), true, emit);
         state.time_elapsed = TimeSpan::ZERO;
+      } else {
+        emit_markers(state, start, state.time_elapsed.as_seconds(), false, emit);
       }
 
       // evaluate all transitions each tick
@@ -127,6 +235,8 @@ impl<T> AnimationTree<T> {
         let AnimationTransition { condition, target } = transition;
 
         if condition(state, &self.state) {
+          close_windows(state, emit);
+
           self.current = Some(*target);
           break;
         }
@@ -135,6 +245,60 @@ impl<T> AnimationTree<T> {
   }
 }
 
+/// Sends events for the markers between the two times, from `start`
+/// inclusive to `end` exclusive, or inclusive at the end of a loop.
+fn emit_markers<T>(
+  state: &AnimationState<T>,
+  start: f32,
+  end: f32,
+  inclusive: bool,
+  emit: &mut dyn FnMut(AnimationEvent),
+) {
+  let crossed = |time: f32| time >= start && (time < end || (inclusive && time <= end));
+
+  for marker in &state.clip.markers {
+    let event = |kind| AnimationEvent {
+      state: state.name,
+      name: marker.name,
+      kind,
+      payload: marker.payload.clone(),
+    };
+
+    if marker.duration <= 0. {
+      if crossed(marker.time) {
+        emit(event(AnimationEventKind::Fired));
+      }
+
+      continue;
+    }
+
+    if crossed(marker.time) {
+      emit(event(AnimationEventKind::Began));
+    }
+
+    // windows still open at the end of a loop close with it
+    if crossed(marker.end()) || (inclusive && marker.time <= end && marker.end() > end) {
+      emit(event(AnimationEventKind::Ended));
+    }
+  }
+}
+
+/// Sends events to close the windows open in a state that's being left.
+fn close_windows<T>(state: &AnimationState<T>, emit: &mut dyn FnMut(AnimationEvent)) {
+  let time = state.time_elapsed.as_seconds();
+
+  for marker in &state.clip.markers {
+    if marker.duration > 0. && marker.time < time && time <= marker.end() {
+      emit(AnimationEvent {
+        state: state.name,
+        name: marker.name,
+        kind: AnimationEventKind::Ended,
+        payload: marker.payload.clone(),
+      });
+    }
+  }
+}
+
 /// Evaluates the final value of the given keyframes by interpolation.
 pub fn evaluate_keyframes<T: Default + Lerp + Copy>(time: f32, keyframes: &[AnimationKeyFrame<T>]) -> T {
   // Handle empty keyframes case
@@ -234,6 +398,7 @@ mod tests {
             },
           ]),
         ],
+        markers: vec![],
       },
       transitions: vec![
         AnimationTransition {
@@ -276,4 +441,62 @@ mod tests {
     assert_eq!(evaluate_keyframes(-1., &keyframes), 0.0);
     assert_eq!(evaluate_keyframes(3.0, &keyframes), 0.0);
   }
+
+  #[test]
+  fn it_should_send_markers_through_the_event_bus() {
+    let mut tree = AnimationTree::new(AnimationParams::default());
+    let events = EventBus::new();
+
+    tree.add_state(AnimationState {
+      name: "attack".to_string_name(),
+      clip: AnimationClip {
+        duration: TimeSpan::from_seconds(1.0),
+        tracks: vec![],
+        markers: vec![
+          AnimationMarker::new("footstep".to_string_name(), 0.3).with_payload(Variant::String("gravel".to_string())),
+          AnimationMarker::window("swing".to_string_name(), 0.5, 0.7),
+        ],
+      },
+      transitions: vec![AnimationTransition {
+        target: "idle".to_string_name(),
+        condition: Box::new(|_, p| p.is_jumping),
+      }],
+      time_elapsed: TimeSpan::ZERO,
+      speed: 1.0,
+    });
+
+    tree.add_state(AnimationState {
+      name: "idle".to_string_name(),
+      clip: AnimationClip::default(),
+      transitions: vec![],
+      time_elapsed: TimeSpan::ZERO,
+      speed: 1.0,
+    });
+
+    tree.set_current("attack".to_string_name());
+
+    let step = |tree: &mut AnimationTree<AnimationParams>| {
+      tree.update_with_events(0.2, &events);
+
+      events
+        .iter()
+        .map(|event| (event.name.to_string(), event.kind))
+        .collect::<Vec<_>>()
+    };
+
+    assert!(step(&mut tree).is_empty());
+    assert_eq!(step(&mut tree), vec![(
+      "footstep".to_string(),
+      AnimationEventKind::Fired
+    )]);
+
+    // leaving the state mid-swing closes the window
+    tree.modify_state(|params| params.is_jumping = true);
+
+    assert_eq!(step(&mut tree), vec![
+      ("swing".to_string(), AnimationEventKind::Began),
+      ("swing".to_string(), AnimationEventKind::Ended),
+    ]);
+    assert_eq!(tree.current().unwrap().name, "idle".to_string_name());
+  }
 }
@@ -0,0 +1,401 @@
+//! Inverse kinematics.
+//!
+//! Solvers work on joint positions in model space, so the same solver drives
+//! a 2D bone rig ([`Vec2`]) or a 3D skeleton ([`Vec3`]). They're applied to a
+//! pose after it's been sampled from clips, pulling chains like arms and legs
+//! towards targets such as door handles or uneven ground.
+
+use std::ops::{Add, Mul, Sub};
+
+use super::*;
+
+/// A vector type that inverse kinematics can be solved in.
+pub trait IkVector: Copy + Default + Lerp + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self> {
+  fn dot(self, other: Self) -> f32;
+  fn length(self) -> f32;
+  fn normalize_or_zero(self) -> Self;
+
+  /// The distance between two points.
+  fn distance(self, other: Self) -> f32 {
+    (other - self).length()
+  }
+
+  /// The part of this vector perpendicular to the given unit axis.
+  fn reject_from(self, axis: Self) -> Self {
+    self - axis * self.dot(axis)
+  }
+}
+
+macro_rules! impl_ik_vector {
+  ($type:ty) => {
+    impl IkVector for $type {
+      #[inline]
+      fn dot(self, other: Self) -> f32 {
+        <$type>::dot(self, other)
+      }
+
+      #[inline]
+      fn length(self) -> f32 {
+        <$type>::length(self)
+      }
+
+      #[inline]
+      fn normalize_or_zero(self) -> Self {
+        <$type>::normalize_or_zero(self)
+      }
+    }
+  };
+}
+
+impl_ik_vector!(Vec2);
+impl_ik_vector!(Vec3);
+
+/// A solver that moves joints in a pose.
+pub trait IkSolver<V> {
+  /// Solves the pose in place, returning how far the end joint is left from
+  /// its target.
+  fn solve(&self, pose: &mut [V]) -> f32;
+}
+
+/// An analytic solver for chains of exactly two bones, e.g. arms and legs.
+///
+/// The middle joint bends towards the pole, if there is one; otherwise it
+/// keeps bending the way it already does.
+#[derive(Clone, Debug)]
+pub struct TwoBoneIk<V> {
+  /// The root, middle and end joints, as indices into the pose.
+  pub joints: [usize; 3],
+  pub target: V,
+  /// A point the middle joint bends towards, e.g. in front of the knee.
+  pub pole: Option<V>,
+  /// How much of the solution to blend over the sampled pose, from 0 to 1.
+  pub weight: f32,
+}
+
+impl<V: IkVector> TwoBoneIk<V> {
+  /// Creates a new solver for the given joints.
+  pub fn new(joints: [usize; 3], target: V) -> Self {
+    Self {
+      joints,
+      target,
+      pole: None,
+      weight: 1.,
+    }
+  }
+}
+
+impl<V: IkVector> IkSolver<V> for TwoBoneIk<V> {
+  fn solve(&self, pose: &mut [V]) -> f32 {
+    let [root, middle, end] = self.joints.map(|joint| pose[joint]);
+
+    let upper_length = root.distance(middle);
+    let lower_length = middle.distance(end);
+
+    // keep the triangle from degenerating when the target is out of reach
+    let min_reach = (upper_length - lower_length).abs() + 1e-4;
+    let max_reach = (upper_length + lower_length - 1e-4).max(min_reach);
+
+    let mut axis = (self.target - root).normalize_or_zero();
+
+    if axis.length() == 0. {
+      axis = (end - root).normalize_or_zero();
+    }
+
+    let reach = root.distance(self.target).clamp(min_reach, max_reach);
+
+    // the law of cosines gives the angle at the root
+    let cos_angle = ((upper_length * upper_length + reach * reach - lower_length * lower_length)
+      / (2. * upper_length * reach).max(f32::EPSILON))
+    .clamp(-1., 1.);
+    let sin_angle = (1. - cos_angle * cos_angle).sqrt();
+
+    let bend = self
+      .pole
+      .map(|pole| (pole - root).reject_from(axis))
+      .unwrap_or_else(|| (middle - root).reject_from(axis))
+      .normalize_or_zero();
+
+    let solved_middle = match bend.length() > 0. {
+      true => root + (axis * cos_angle + bend * sin_angle) * upper_length,
+      false => root + axis * upper_length,
+    };
+    let solved_end = solved_middle + (root + axis * reach - solved_middle).normalize_or_zero() * lower_length;
+
+    pose[self.joints[1]] = V::lerp(middle, solved_middle, self.weight);
+    pose[self.joints[2]] = V::lerp(end, solved_end, self.weight);
+
+    pose[self.joints[2]].distance(self.target)
+  }
+}
+
+/// Limits how far a joint may bend away from its parent bone.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct IkConstraint {
+  /// The largest angle, in radians, between the bones either side of the
+  /// joint.
+  pub max_bend: Option<f32>,
+}
+
+/// An iterative FABRIK solver for chains of any length, e.g. tails and
+/// tentacles.
+#[derive(Clone, Debug)]
+pub struct FabrikIk<V> {
+  /// The joints from root to end, as indices into the pose.
+  pub joints: Vec<usize>,
+  pub target: V,
+  /// A point the middle joints bend towards.
+  ///
+  /// The pole is applied after the constraints, so it may bend joints past
+  /// them.
+  pub pole: Option<V>,
+  /// Constraints for each joint, in the same order as the joints.
+  pub constraints: Vec<IkConstraint>,
+  pub max_iterations: u32,
+  /// Stop once the end is this close to the target.
+  pub tolerance: f32,
+  /// How much of the solution to blend over the sampled pose, from 0 to 1.
+  pub weight: f32,
+}
+
+impl<V: IkVector> FabrikIk<V> {
+  /// Creates a new solver for the given joints.
+  pub fn new(joints: Vec<usize>, target: V) -> Self {
+    Self {
+      joints,
+      target,
+      pole: None,
+      constraints: Vec::new(),
+      max_iterations: 10,
+      tolerance: 1e-3,
+      weight: 1.,
+    }
+  }
+
+  /// Constrains the joint at the given position in the chain.
+  pub fn with_constraint(mut self, index: usize, constraint: IkConstraint) -> Self {
+    if self.constraints.len() <= index {
+      self.constraints.resize(index + 1, IkConstraint::default());
+    }
+
+    self.constraints[index] = constraint;
+    self
+  }
+
+  /// Places each joint from the root outwards, honouring constraints.
+  fn forward(&self, positions: &mut [V], lengths: &[f32]) {
+    for index in 1..positions.len() {
+      let mut direction = (positions[index] - positions[index - 1]).normalize_or_zero();
+
+      if index >= 2 {
+        let max_bend = self.constraints.get(index - 1).and_then(|it| it.max_bend);
+
+        if let Some(max_bend) = max_bend {
+          let parent = (positions[index - 1] - positions[index - 2]).normalize_or_zero();
+
+          direction = constrain_bend(parent, direction, max_bend);
+        }
+      }
+
+      positions[index] = positions[index - 1] + direction * lengths[index - 1];
+    }
+  }
+
+  /// Places each joint from the end inwards, starting at the target.
+  fn backward(&self, positions: &mut [V], lengths: &[f32]) {
+    let last = positions.len() - 1;
+
+    positions[last] = self.target;
+
+    for index in (0..last).rev() {
+      let direction = (positions[index] - positions[index + 1]).normalize_or_zero();
+
+      positions[index] = positions[index + 1] + direction * lengths[index];
+    }
+  }
+}
+
+impl<V: IkVector> IkSolver<V> for FabrikIk<V> {
+  fn solve(&self, pose: &mut [V]) -> f32 {
+    if self.joints.len() < 2 {
+      return 0.;
+    }
+
+    let mut positions = self.joints.iter().map(|&joint| pose[joint]).collect::<Vec<_>>();
+    let lengths = positions
+      .windows(2)
+      .map(|bone| bone[0].distance(bone[1]))
+      .collect::<Vec<_>>();
+
+    let root = positions[0];
+    let last = positions.len() - 1;
+
+    if root.distance(self.target) >= lengths.iter().sum::<f32>() {
+      // out of reach, so point the whole chain at the target
+      let direction = (self.target - root).normalize_or_zero();
+
+      for index in 1..positions.len() {
+        positions[index] = positions[index - 1] + direction * lengths[index - 1];
+      }
+    } else {
+      for _ in 0..self.max_iterations {
+        self.backward(&mut positions, &lengths);
+
+        positions[0] = root;
+        self.forward(&mut positions, &lengths);
+
+        if positions[last].distance(self.target) <= self.tolerance {
+          break;
+        }
+      }
+    }
+
+    if let Some(pole) = self.pole {
+      // swing each middle joint around the line between its neighbours, which
+      // keeps both bone lengths the same
+      for index in 1..last {
+        let start = positions[index - 1];
+        let axis = (positions[index + 1] - start).normalize_or_zero();
+        let offset = (positions[index] - start).reject_from(axis);
+        let towards = (pole - start).reject_from(axis).normalize_or_zero();
+
+        if towards.length() > 0. {
+          positions[index] = positions[index] - offset + towards * offset.length();
+        }
+      }
+    }
+
+    for (index, &joint) in self.joints.iter().enumerate() {
+      pose[joint] = V::lerp(pose[joint], positions[index], self.weight);
+    }
+
+    pose[self.joints[last]].distance(self.target)
+  }
+}
+
+/// Rotates a direction towards its parent until it bends no further than the
+/// given angle.
+fn constrain_bend<V: IkVector>(parent: V, direction: V, max_bend: f32) -> V {
+  let cos_bend = parent.dot(direction).clamp(-1., 1.);
+
+  if cos_bend.acos() <= max_bend {
+    return direction;
+  }
+
+  let perpendicular = direction.reject_from(parent).normalize_or_zero();
+
+  parent * max_bend.cos() + perpendicular * max_bend.sin()
+}
+
+/// Samples joint positions from clip tracks, then applies IK on top.
+pub struct IkRig<V> {
+  solvers: Vec<Box<dyn IkSolver<V>>>,
+}
+
+impl<V> Default for IkRig<V> {
+  fn default() -> Self {
+    Self { solvers: Vec::new() }
+  }
+}
+
+impl<V: IkVector> IkRig<V> {
+  /// Creates a new, empty rig.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a solver; solvers run in the order they're added.
+  pub fn add_solver(&mut self, solver: impl IkSolver<V> + 'static) {
+    self.solvers.push(Box::new(solver));
+  }
+
+  /// Runs every solver over an already-sampled pose.
+  pub fn apply(&self, pose: &mut [V]) {
+    for solver in &self.solvers {
+      solver.solve(pose);
+    }
+  }
+
+  /// Samples one joint position per track at the given time, then applies IK.
+  pub fn evaluate(&self, time: f32, tracks: &[AnimationTrackData<V>]) -> Vec<V> {
+    let mut pose = tracks
+      .iter()
+      .map(|keyframes| evaluate_keyframes(time, keyframes))
+      .collect::<Vec<_>>();
+
+    self.apply(&mut pose);
+
+    pose
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{vec2, vec3};
+
+  use super::*;
+
+  #[test]
+  fn it_should_solve_two_bones_towards_the_pole() {
+    let mut pose = vec![vec2(0., 0.), vec2(1., 0.), vec2(2., 0.)];
+    let mut solver = TwoBoneIk::new([0, 1, 2], vec2(1., 1.));
+
+    solver.pole = Some(vec2(-1., 1.));
+
+    let error = solver.solve(&mut pose);
+
+    assert!(error < 1e-3);
+    assert!((pose[0].distance(pose[1]) - 1.).abs() < 1e-3);
+    assert!((pose[1].distance(pose[2]) - 1.).abs() < 1e-3);
+
+    // the elbow bends to the pole's side of the line to the target
+    assert!(pose[1].x < pose[1].y);
+  }
+
+  #[test]
+  fn it_should_solve_long_chains_with_fabrik() {
+    let mut pose = (0..5).map(|i| vec3(0., i as f32, 0.)).collect::<Vec<_>>();
+    let solver = FabrikIk::new(vec![0, 1, 2, 3, 4], vec3(2., 2., 1.));
+
+    assert!(solver.solve(&mut pose) < 1e-2);
+
+    for bone in pose.windows(2) {
+      assert!((bone[0].distance(bone[1]) - 1.).abs() < 1e-3);
+    }
+
+    // targets out of reach straighten the chain towards them
+    let solver = FabrikIk::new(vec![0, 1, 2, 3, 4], vec3(10., 0., 0.));
+
+    solver.solve(&mut pose);
+
+    assert!(pose[4].distance(vec3(4., 0., 0.)) < 1e-3);
+  }
+
+  #[test]
+  fn it_should_apply_constraints_after_sampling() {
+    let track = |x: f32| {
+      vec![
+        AnimationKeyFrame {
+          time: 0.,
+          value: vec2(x, 0.),
+        },
+        AnimationKeyFrame {
+          time: 1.,
+          value: vec2(x, 1.),
+        },
+      ]
+    };
+
+    let mut rig = IkRig::new();
+
+    rig
+      .add_solver(FabrikIk::new(vec![0, 1, 2], vec2(0., 1.5)).with_constraint(1, IkConstraint { max_bend: Some(0.5) }));
+
+    let pose = rig.evaluate(0.5, &[track(0.), track(1.), track(2.)]);
+
+    let upper = (pose[1] - pose[0]).normalize();
+    let lower = (pose[2] - pose[1]).normalize();
+
+    assert_eq!(pose[0], vec2(0., 0.5));
+    assert!(upper.dot(lower).acos() <= 0.5 + 1e-3);
+  }
+}
@@ -0,0 +1,392 @@
+//! Skeletons, and retargeting clips between them.
+//!
+//! Clips authored against one skeleton can play on another if a
+//! [`BoneMapping`] says which bones correspond. Rotations are carried over
+//! relative to each skeleton's rest pose, and translations are scaled by how
+//! long the target's bones are compared to the source's, so a walk authored on
+//! a tall character doesn't skate on a short one.
+
+use common::{Chunk, Format, FromStream, InputStream, RonFormat, StreamError, ToStringName};
+
+use super::*;
+
+/// A single bone in a [`Skeleton`].
+#[derive(Clone, Debug)]
+pub struct Bone {
+  pub name: StringName,
+  /// The index of the parent bone, if this isn't a root.
+  pub parent: Option<usize>,
+  /// The translation relative to the parent in the rest pose.
+  pub rest_translation: Vec3,
+  /// The rotation relative to the parent in the rest pose.
+  pub rest_rotation: Quat,
+}
+
+/// A hierarchy of bones; parents always come before their children.
+#[derive(Clone, Debug, Default)]
+pub struct Skeleton {
+  bones: Vec<Bone>,
+}
+
+impl Skeleton {
+  /// Creates a new, empty skeleton.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a bone, returning its index.
+  pub fn add_bone(
+    &mut self,
+    name: impl ToStringName,
+    parent: Option<usize>,
+    translation: Vec3,
+    rotation: Quat,
+  ) -> usize {
+    debug_assert!(
+      parent.is_none_or(|parent| parent < self.bones.len()),
+      "parents must be added before their children"
+    );
+
+    self.bones.push(Bone {
+      name: name.to_string_name(),
+      parent,
+      rest_translation: translation,
+      rest_rotation: rotation,
+    });
+
+    self.bones.len() - 1
+  }
+
+  /// The bones of the skeleton.
+  pub fn bones(&self) -> &[Bone] {
+    &self.bones
+  }
+
+  /// Finds a bone by name.
+  pub fn find_bone(&self, name: StringName) -> Option<usize> {
+    self.bones.iter().position(|bone| bone.name == name)
+  }
+
+  /// The rest pose of the skeleton.
+  pub fn rest_pose(&self) -> Vec<BonePose> {
+    self
+      .bones
+      .iter()
+      .map(|bone| BonePose {
+        translation: bone.rest_translation,
+        rotation: bone.rest_rotation,
+      })
+      .collect()
+  }
+}
+
+/// The transform of a bone relative to its parent.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BonePose {
+  pub translation: Vec3,
+  pub rotation: Quat,
+}
+
+/// The animation of a single bone, by name.
+#[derive(Clone, Debug)]
+pub struct BoneTrack {
+  pub bone: StringName,
+  pub translation: AnimationTrackData<Vec3>,
+  pub rotation: AnimationTrackData<Quat>,
+}
+
+/// A clip of animation for a skeleton.
+#[derive(Clone, Debug, Default)]
+pub struct SkeletalClip {
+  pub duration: TimeSpan,
+  pub tracks: Vec<BoneTrack>,
+}
+
+impl SkeletalClip {
+  /// Samples the clip at the given time.
+  ///
+  /// Bones without a track, or without keys for a channel, keep their rest
+  /// pose.
+  pub fn sample(&self, skeleton: &Skeleton, time: f32) -> Vec<BonePose> {
+    let mut pose = skeleton.rest_pose();
+
+    for track in &self.tracks {
+      let Some(index) = skeleton.find_bone(track.bone) else {
+        continue;
+      };
+
+      if !track.translation.is_empty() {
+        pose[index].translation = evaluate_keyframes(time, &track.translation);
+      }
+
+      if !track.rotation.is_empty() {
+        pose[index].rotation = evaluate_keyframes(time, &track.rotation);
+      }
+    }
+
+    pose
+  }
+}
+
+/// Says which bones of a source skeleton correspond to a target skeleton.
+///
+/// Mappings are usually authored once per rig and shared by every clip in an
+/// animation pack, e.g.:
+///
+/// ```ron
+/// BoneMapping(
+///   bones: { "mixamorig:Hips": "pelvis", "mixamorig:Spine": "spine_01" },
+///   scale_translations: true,
+/// )
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BoneMapping {
+  /// Source bone names to target bone names.
+  pub bones: FastHashMap<StringName, StringName>,
+  /// Scales translations by the ratio of the target bone's length to the
+  /// source's.
+  pub scale_translations: bool,
+}
+
+impl BoneMapping {
+  /// Creates a new, empty mapping.
+  pub fn new() -> Self {
+    Self {
+      bones: FastHashMap::default(),
+      scale_translations: true,
+    }
+  }
+
+  /// Maps a source bone onto a target bone.
+  pub fn map(&mut self, source: impl ToStringName, target: impl ToStringName) {
+    self.bones.insert(source.to_string_name(), target.to_string_name());
+  }
+
+  /// Reads a mapping from a [`Chunk`], as produced by [`RonFormat`].
+  pub fn from_chunk(chunk: &Chunk) -> Result<Self, StreamError> {
+    let Some(Chunk::Map(bones)) = chunk.get("bones") else {
+      return Err(StreamError::InvalidData);
+    };
+
+    let mut mapping = Self::new();
+
+    for (source, target) in bones {
+      mapping.map(source, target.read::<String>()?);
+    }
+
+    mapping.scale_translations = chunk.read_field_or("scale_translations", true)?;
+
+    Ok(mapping)
+  }
+}
+
+impl FromStream for BoneMapping {
+  async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+    Self::from_chunk(&RonFormat::default().read_chunk(stream)?)
+  }
+}
+
+/// A source bone paired with the target bone it drives.
+#[derive(Copy, Clone, Debug)]
+struct RetargetBone {
+  target: StringName,
+  /// Undoes the source's rest rotation and applies the target's.
+  rest_delta: (Quat, Quat),
+  source_rest_translation: Vec3,
+  target_rest_translation: Vec3,
+  scale: f32,
+}
+
+/// Converts clips from one skeleton to another.
+pub struct Retargeter {
+  bones: FastHashMap<StringName, RetargetBone>,
+}
+
+impl Retargeter {
+  /// Creates a retargeter between the given skeletons.
+  ///
+  /// Mapped bones that are missing from either skeleton are ignored.
+  pub fn new(source: &Skeleton, target: &Skeleton, mapping: &BoneMapping) -> Self {
+    let mut bones = FastHashMap::default();
+
+    for (&source_name, &target_name) in &mapping.bones {
+      let (Some(source_index), Some(target_index)) = (source.find_bone(source_name), target.find_bone(target_name))
+      else {
+        continue;
+      };
+
+      let source_bone = &source.bones[source_index];
+      let target_bone = &target.bones[target_index];
+
+      let source_length = source_bone.rest_translation.length();
+      let target_length = target_bone.rest_translation.length();
+
+      let scale = match mapping.scale_translations && source_length > f32::EPSILON {
+        true => target_length / source_length,
+        false => 1.,
+      };
+
+      bones.insert(source_name, RetargetBone {
+        target: target_name,
+        rest_delta: (source_bone.rest_rotation.inverse(), target_bone.rest_rotation),
+        source_rest_translation: source_bone.rest_translation,
+        target_rest_translation: target_bone.rest_translation,
+        scale,
+      });
+    }
+
+    Self { bones }
+  }
+
+  /// Converts a clip authored on the source skeleton to the target skeleton.
+  ///
+  /// Tracks for unmapped bones are dropped.
+  pub fn retarget(&self, clip: &SkeletalClip) -> SkeletalClip {
+    let tracks = clip
+      .tracks
+      .iter()
+      .filter_map(|track| {
+        let bone = self.bones.get(&track.bone)?;
+        let (inverse_source_rest, target_rest) = bone.rest_delta;
+
+        let translation = track
+          .translation
+          .iter()
+          .map(|key| AnimationKeyFrame {
+            time: key.time,
+            value: bone.target_rest_translation + (key.value - bone.source_rest_translation) * bone.scale,
+          })
+          .collect();
+
+        let rotation = track
+          .rotation
+          .iter()
+          .map(|key| AnimationKeyFrame {
+            time: key.time,
+            value: (target_rest * (inverse_source_rest * key.value)).normalize(),
+          })
+          .collect();
+
+        Some(BoneTrack {
+          bone: bone.target,
+          translation,
+          rotation,
+        })
+      })
+      .collect();
+
+    SkeletalClip {
+      duration: clip.duration,
+      tracks,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::vec3;
+
+  use super::*;
+
+  fn biped(hip_height: f32, shin_length: f32) -> Skeleton {
+    let mut skeleton = Skeleton::new();
+
+    let hips = skeleton.add_bone("hips", None, vec3(0., hip_height, 0.), Quat::IDENTITY);
+    let knee = skeleton.add_bone("knee", Some(hips), vec3(0., -shin_length, 0.), Quat::IDENTITY);
+
+    skeleton.add_bone("foot", Some(knee), vec3(0., -shin_length, 0.), Quat::IDENTITY);
+    skeleton
+  }
+
+  #[test]
+  fn it_should_scale_translations_by_proportion() {
+    let source = biped(1., 0.5);
+    let target = biped(2., 1.);
+
+    let mut mapping = BoneMapping::new();
+
+    mapping.map("hips", "hips");
+
+    let clip = SkeletalClip {
+      duration: TimeSpan::from_seconds(1.),
+      tracks: vec![BoneTrack {
+        bone: "hips".to_string_name(),
+        translation: vec![AnimationKeyFrame {
+          time: 0.,
+          value: vec3(0., 0.9, 0.5),
+        }],
+        rotation: vec![],
+      }],
+    };
+
+    let retargeted = Retargeter::new(&source, &target, &mapping).retarget(&clip);
+    let pose = retargeted.sample(&target, 0.);
+
+    // the hips dip and stride twice as far on a skeleton twice as tall
+    assert!(pose[0].translation.distance(vec3(0., 1.8, 1.)) < 1e-5);
+  }
+
+  #[test]
+  fn it_should_carry_rotations_relative_to_rest_poses() {
+    let source = biped(1., 0.5);
+    let mut target = Skeleton::new();
+
+    // the target's knee rests a quarter-turn around, as exported by another
+    // tool
+    let hips = target.add_bone("pelvis", None, vec3(0., 1., 0.), Quat::IDENTITY);
+    let target_rest = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+
+    target.add_bone("shin", Some(hips), vec3(0., -0.5, 0.), target_rest);
+
+    let mut mapping = BoneMapping::new();
+
+    mapping.map("hips", "pelvis");
+    mapping.map("knee", "shin");
+
+    let bend = Quat::from_rotation_x(0.5);
+    let clip = SkeletalClip {
+      duration: TimeSpan::from_seconds(1.),
+      tracks: vec![
+        BoneTrack {
+          bone: "knee".to_string_name(),
+          translation: vec![],
+          rotation: vec![AnimationKeyFrame { time: 0., value: bend }],
+        },
+        BoneTrack {
+          bone: "foot".to_string_name(),
+          translation: vec![],
+          rotation: vec![AnimationKeyFrame { time: 0., value: bend }],
+        },
+      ],
+    };
+
+    let retargeted = Retargeter::new(&source, &target, &mapping).retarget(&clip);
+
+    // the unmapped foot is dropped
+    assert_eq!(retargeted.tracks.len(), 1);
+    assert_eq!(retargeted.tracks[0].bone, "shin".to_string_name());
+
+    let pose = retargeted.sample(&target, 0.);
+
+    assert!(pose[1].rotation.angle_between(target_rest * bend) < 1e-4);
+  }
+
+  #[test]
+  fn it_should_load_bone_mappings_from_ron() {
+    let mapping = BoneMapping::from_bytes(
+      br#"
+        BoneMapping(
+          bones: { "mixamorig:Hips": "pelvis", "mixamorig:Spine": "spine_01" },
+          scale_translations: false,
+        )
+      "#,
+    )
+    .unwrap();
+
+    assert!(!mapping.scale_translations);
+    assert_eq!(
+      mapping.bones.get(&"mixamorig:Hips".to_string_name()),
+      Some(&"pelvis".to_string_name())
+    );
+  }
+}
@@ -39,7 +39,7 @@ impl<T> Buffer<T> {
   /// Constructs a new empty buffer on the GPU.
   pub fn new(kind: BufferKind, usage: BufferUsage) -> Result<Self, BufferError> {
     Ok(Self {
-      state: internal::GraphicsCell::new(BufferState {
+      state: internal::GraphicsCell::restorable(BufferState {
         id: graphics().buffer_create()?,
         kind,
         usage,
@@ -106,8 +106,22 @@ impl<T> Buffer<T> {
   }
 }
 
+impl Restore for BufferState {
+  const ORDER: RestoreOrder = RestoreOrder::Buffer;
+
+  fn restore(&mut self) -> Result<(), GraphicsError> {
+    // the contents are lost; owners re-upload them
+    self.id = graphics().buffer_create()?;
+    self.length = 0;
+
+    Ok(())
+  }
+}
+
 impl Drop for BufferState {
   fn drop(&mut self) {
-    graphics().buffer_delete(self.id).expect("Failed to delete buffer")
+    let id = self.id;
+
+    GraphicsServer::defer(move |graphics| graphics.buffer_delete(id).expect("Failed to delete buffer"));
   }
 }
@@ -0,0 +1,625 @@
+//! Capturing graphics calls for debugging.
+//!
+//! A [`CapturingGraphicsBackend`] wraps another backend and, when asked,
+//! records every call made to it with its arguments and result. Captures are
+//! plain [`CaptureLog`]s that can be dumped as text or diffed against each
+//! other, e.g. to find where two backends disagree on the same frame.
+//!
+//! Raw pointers are never recorded, so captures of the same frame are
+//! identical from run to run.
+
+use std::{
+  fmt::{Debug, Display, Formatter},
+  sync::{Arc, Mutex},
+};
+
+use common::{Color, Rectangle, UVec2};
+
+use super::*;
+
+/// A single call to a [`GraphicsBackend`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CapturedCall {
+  pub name: &'static str,
+  pub arguments: Vec<(&'static str, String)>,
+  pub result: String,
+}
+
+impl Display for CapturedCall {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(formatter, "{}(", self.name)?;
+
+    for (index, (name, value)) in self.arguments.iter().enumerate() {
+      if index > 0 {
+        write!(formatter, ", ")?;
+      }
+
+      write!(formatter, "{name}: {value}")?;
+    }
+
+    write!(formatter, ")")?;
+
+    if self.result != "()" {
+      write!(formatter, " -> {}", self.result)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// The calls captured from a [`GraphicsBackend`], in order.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CaptureLog {
+  pub calls: Vec<CapturedCall>,
+}
+
+/// A difference between two [`CaptureLog`]s.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CaptureDifference {
+  /// The call at this index differs between the logs.
+  Changed {
+    index: usize,
+    left: CapturedCall,
+    right: CapturedCall,
+  },
+  /// Only the left log has a call at this index.
+  Removed { index: usize, call: CapturedCall },
+  /// Only the right log has a call at this index.
+  Added { index: usize, call: CapturedCall },
+}
+
+impl CaptureLog {
+  /// Compares this log against another, call by call.
+  pub fn diff(&self, other: &CaptureLog) -> Vec<CaptureDifference> {
+    let mut differences = Vec::new();
+
+    for index in 0..self.calls.len().max(other.calls.len()) {
+      match (self.calls.get(index), other.calls.get(index)) {
+        (Some(left), Some(right)) if left != right => differences.push(CaptureDifference::Changed {
+          index,
+          left: left.clone(),
+          right: right.clone(),
+        }),
+        (Some(call), None) => differences.push(CaptureDifference::Removed {
+          index,
+          call: call.clone(),
+        }),
+        (None, Some(call)) => differences.push(CaptureDifference::Added {
+          index,
+          call: call.clone(),
+        }),
+        _ => {}
+      }
+    }
+
+    differences
+  }
+}
+
+impl Display for CaptureLog {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    for (index, call) in self.calls.iter().enumerate() {
+      writeln!(formatter, "{index:>6}: {call}")?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Controls capturing on a [`CapturingGraphicsBackend`].
+///
+/// This is a cheap handle; keep a clone of it after installing the backend to
+/// start and collect captures.
+#[derive(Clone, Default)]
+pub struct GraphicsCapture {
+  state: Arc<Mutex<CaptureState>>,
+}
+
+#[derive(Default)]
+struct CaptureState {
+  mode: CaptureMode,
+  current: CaptureLog,
+  finished: Option<CaptureLog>,
+}
+
+#[derive(Copy, Clone, Default, Eq, PartialEq)]
+enum CaptureMode {
+  #[default]
+  Idle,
+  NextFrame,
+  Frame,
+  Manual,
+}
+
+impl GraphicsCapture {
+  /// Creates a new capture handle.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Captures every call from the start of the next frame to its end.
+  pub fn capture_next_frame(&self) {
+    self.state.lock().unwrap().mode = CaptureMode::NextFrame;
+  }
+
+  /// Starts capturing immediately, until [`GraphicsCapture::stop`].
+  pub fn start(&self) {
+    let mut state = self.state.lock().unwrap();
+
+    state.mode = CaptureMode::Manual;
+    state.current = CaptureLog::default();
+  }
+
+  /// Stops capturing, returning what was captured.
+  pub fn stop(&self) -> Option<CaptureLog> {
+    let mut state = self.state.lock().unwrap();
+
+    match state.mode {
+      CaptureMode::Frame | CaptureMode::Manual => {
+        state.mode = CaptureMode::Idle;
+        Some(std::mem::take(&mut state.current))
+      }
+      _ => None,
+    }
+  }
+
+  /// Determines if calls are being captured.
+  pub fn is_capturing(&self) -> bool {
+    matches!(
+      self.state.lock().unwrap().mode,
+      CaptureMode::Frame | CaptureMode::Manual
+    )
+  }
+
+  /// Takes the last completed frame capture, if there is one.
+  pub fn take_frame(&self) -> Option<CaptureLog> {
+    self.state.lock().unwrap().finished.take()
+  }
+
+  /// Starts a frame capture, if one was requested.
+  fn begin_frame(&self) {
+    let mut state = self.state.lock().unwrap();
+
+    if state.mode == CaptureMode::NextFrame {
+      state.mode = CaptureMode::Frame;
+      state.current = CaptureLog::default();
+    }
+  }
+
+  /// Finishes a frame capture, if one is running.
+  fn end_frame(&self) {
+    let mut state = self.state.lock().unwrap();
+
+    if state.mode == CaptureMode::Frame {
+      state.mode = CaptureMode::Idle;
+      state.finished = Some(std::mem::take(&mut state.current));
+    }
+  }
+
+  /// Records a call, if capturing.
+  fn record(&self, call: impl FnOnce() -> CapturedCall) {
+    let mut state = self.state.lock().unwrap();
+
+    if matches!(state.mode, CaptureMode::Frame | CaptureMode::Manual) {
+      state.current.calls.push(call());
+    }
+  }
+}
+
+/// A [`GraphicsBackend`] that can capture the calls made to another backend.
+pub struct CapturingGraphicsBackend<B> {
+  inner: B,
+  capture: GraphicsCapture,
+}
+
+impl<B: GraphicsBackend> CapturingGraphicsBackend<B> {
+  /// Wraps the given backend, capturing through the given handle.
+  pub fn new(inner: B, capture: GraphicsCapture) -> Self {
+    Self { inner, capture }
+  }
+
+  /// Calls the inner backend, recording the call if capturing.
+  fn record<R: Debug>(
+    &self,
+    name: &'static str,
+    arguments: impl FnOnce() -> Vec<(&'static str, String)>,
+    call: impl FnOnce(&B) -> R,
+  ) -> R {
+    let result = call(&self.inner);
+
+    self.capture.record(|| CapturedCall {
+      name,
+      arguments: arguments(),
+      result: format!("{result:?}"),
+    });
+
+    result
+  }
+}
+
+/// Records a call to the inner backend, with the named arguments.
+///
+/// Arguments are recorded by their [`Debug`] form, or by the given expression
+/// for those that shouldn't be recorded directly, like pointers.
+macro_rules! capture {
+  ($self:ident.$name:ident($($argument:expr),*) [$($key:ident $(= $value:expr)?),*]) => {
+    $self.record(
+      stringify!($name),
+      || vec![$((stringify!($key), capture!(@value $key $($value)?))),*],
+      |inner| inner.$name($($argument),*),
+    )
+  };
+  (@value $key:ident) => {
+    format!("{:?}", $key)
+  };
+  (@value $key:ident $value:expr) => {
+    format!("{:?}", $value)
+  };
+}
+
+#[allow(clippy::too_many_arguments)]
+impl<B: GraphicsBackend> GraphicsBackend for CapturingGraphicsBackend<B> {
+  fn begin_frame(&self) {
+    self.capture.begin_frame();
+
+    capture!(self.begin_frame()[])
+  }
+
+  fn end_frame(&self) {
+    capture!(self.end_frame()[]);
+
+    self.capture.end_frame();
+  }
+
+  fn device_status(&self) -> DeviceStatus {
+    capture!(self.device_status()[])
+  }
+
+  fn device_reset(&self) {
+    capture!(self.device_reset()[])
+  }
+
+  fn clear_color_buffer(&self, color: Color) {
+    capture!(self.clear_color_buffer(color)[color])
+  }
+
+  fn clear_depth_buffer(&self, depth: f32) {
+    capture!(self.clear_depth_buffer(depth)[depth])
+  }
+
+  fn viewport_size(&self) -> (usize, usize) {
+    capture!(self.viewport_size()[])
+  }
+
+  fn set_viewport_size(&self, size: UVec2) {
+    capture!(self.set_viewport_size(size)[size])
+  }
+
+  fn set_blend_state(&self, blend_state: BlendState) {
+    capture!(self.set_blend_state(blend_state)[blend_state])
+  }
+
+  fn set_culling_mode(&self, culling_mode: CullingMode) {
+    capture!(self.set_culling_mode(culling_mode)[culling_mode])
+  }
+
+  fn set_depth_state(&self, depth_state: DepthState) {
+    capture!(self.set_depth_state(depth_state)[depth_state])
+  }
+
+  fn set_scissor_mode(&self, scissor_mode: ScissorMode) {
+    capture!(self.set_scissor_mode(scissor_mode)[scissor_mode])
+  }
+
+  fn buffer_create(&self) -> Result<BufferId, BufferError> {
+    capture!(self.buffer_create()[])
+  }
+
+  fn buffer_read_data(
+    &self,
+    buffer: BufferId,
+    offset: usize,
+    length: usize,
+    pointer: *mut u8,
+  ) -> Result<(), BufferError> {
+    capture!(self.buffer_read_data(buffer, offset, length, pointer)[buffer, offset, length])
+  }
+
+  fn buffer_write_data(
+    &self,
+    buffer: BufferId,
+    usage: BufferUsage,
+    kind: BufferKind,
+    length: usize,
+    pointer: *const u8,
+  ) -> Result<(), BufferError> {
+    capture!(self.buffer_write_data(buffer, usage, kind, length, pointer)[buffer, usage, kind, length])
+  }
+
+  fn buffer_bind_storage(&self, buffer: BufferId, binding: u32) -> Result<(), BufferError> {
+    capture!(self.buffer_bind_storage(buffer, binding)[buffer, binding])
+  }
+
+  fn buffer_delete(&self, buffer: BufferId) -> Result<(), BufferError> {
+    capture!(self.buffer_delete(buffer)[buffer])
+  }
+
+  fn texture_create(&self, sampler: &TextureSampler) -> Result<TextureId, TextureError> {
+    capture!(self.texture_create(sampler)[sampler])
+  }
+
+  fn texture_set_options(&self, texture: TextureId, sampler: &TextureSampler) -> Result<(), TextureError> {
+    capture!(self.texture_set_options(texture, sampler)[texture, sampler])
+  }
+
+  fn texture_initialize(
+    &self,
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    capture!(self.texture_initialize(texture, width, height, format)[texture, width, height, format])
+  }
+
+  fn texture_read_data(
+    &self,
+    texture: TextureId,
+    length: usize,
+    pixel_format: TextureFormat,
+    pixels: *mut u8,
+    mip_level: usize,
+  ) -> Result<(), TextureError> {
+    capture!(self.texture_read_data(texture, length, pixel_format, pixels, mip_level)[texture, length, pixel_format, mip_level])
+  }
+
+  fn texture_write_data(
+    &self,
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    pixels: *const u8,
+    internal_format: TextureFormat,
+    pixel_format: TextureFormat,
+    mip_level: usize,
+  ) -> Result<(), TextureError> {
+    capture!(self.texture_write_data(texture, width, height, pixels, internal_format, pixel_format, mip_level)[
+      texture,
+      width,
+      height,
+      pixels = !pixels.is_null(),
+      internal_format,
+      pixel_format,
+      mip_level
+    ])
+  }
+
+  fn texture_write_sub_data(
+    &self,
+    texture: TextureId,
+    region: &Rectangle,
+    pixels: *const u8,
+    pixel_format: TextureFormat,
+    mip_level: usize,
+  ) -> Result<(), TextureError> {
+    capture!(self.texture_write_sub_data(texture, region, pixels, pixel_format, mip_level)[texture, region, pixel_format, mip_level])
+  }
+
+  fn texture_initialize_layers(
+    &self,
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    layers: u32,
+    format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    capture!(self.texture_initialize_layers(texture, width, height, layers, format)[texture, width, height, layers, format])
+  }
+
+  fn texture_write_layer_data(
+    &self,
+    texture: TextureId,
+    region: &Rectangle,
+    layer: u32,
+    pixels: *const u8,
+    pixel_format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    capture!(self.texture_write_layer_data(texture, region, layer, pixels, pixel_format)[texture, region, layer, pixel_format])
+  }
+
+  fn texture_set_mip_range(&self, texture: TextureId, base_level: usize, max_level: usize) -> Result<(), TextureError> {
+    capture!(self.texture_set_mip_range(texture, base_level, max_level)[texture, base_level, max_level])
+  }
+
+  fn texture_delete(&self, texture: TextureId) -> Result<(), TextureError> {
+    capture!(self.texture_delete(texture)[texture])
+  }
+
+  fn shader_create(&self) -> Result<ShaderId, ShaderError> {
+    capture!(self.shader_create()[])
+  }
+
+  fn shader_link(&self, shader: ShaderId, kernels: &[ShaderKernel]) -> Result<(), ShaderError> {
+    // the kinds of kernel are enough to tell programs apart without their code
+    capture!(self.shader_link(shader, kernels)[shader, kernels = kernels.iter().map(|it| it.kind).collect::<Vec<_>>()])
+  }
+
+  fn shader_link_async(&self, shader: ShaderId, kernels: &[ShaderKernel]) -> Result<(), ShaderError> {
+    capture!(self.shader_link_async(shader, kernels)[shader, kernels = kernels.iter().map(|it| it.kind).collect::<Vec<_>>()])
+  }
+
+  fn shader_link_status(&self, shader: ShaderId) -> ShaderLinkStatus {
+    capture!(self.shader_link_status(shader)[shader])
+  }
+
+  fn shader_binary(&self, shader: ShaderId) -> Option<ShaderBinary> {
+    capture!(self.shader_binary(shader)[shader])
+  }
+
+  fn shader_link_binary(&self, shader: ShaderId, binary: &ShaderBinary) -> Result<(), ShaderError> {
+    capture!(self.shader_link_binary(shader, binary)[shader, format = binary.format])
+  }
+
+  fn shader_uniform_location(&self, shader: ShaderId, name: &str) -> Option<usize> {
+    capture!(self.shader_uniform_location(shader, name)[shader, name])
+  }
+
+  fn shader_set_uniform(&self, shader: ShaderId, location: usize, value: &ShaderUniform) -> Result<(), ShaderError> {
+    capture!(self.shader_set_uniform(shader, location, value)[shader, location, value])
+  }
+
+  fn shader_activate(&self, shader: ShaderId) -> Result<(), ShaderError> {
+    capture!(self.shader_activate(shader)[shader])
+  }
+
+  fn shader_dispatch_compute(&self, shader: ShaderId, x: u32, y: u32, z: u32) -> Result<(), ShaderError> {
+    capture!(self.shader_dispatch_compute(shader, x, y, z)[shader, x, y, z])
+  }
+
+  fn shader_memory_barrier(&self, barrier: MemoryBarrier) -> Result<(), ShaderError> {
+    capture!(self.shader_memory_barrier(barrier)[barrier])
+  }
+
+  fn shader_delete(&self, shader: ShaderId) -> Result<(), ShaderError> {
+    capture!(self.shader_delete(shader)[shader])
+  }
+
+  fn mesh_create(
+    &self,
+    vertices: BufferId,
+    indices: BufferId,
+    descriptors: &[VertexDescriptor],
+  ) -> Result<MeshId, MeshError> {
+    capture!(self.mesh_create(vertices, indices, descriptors)[vertices, indices, descriptors])
+  }
+
+  fn mesh_draw(
+    &self,
+    mesh: MeshId,
+    topology: PrimitiveTopology,
+    vertex_count: usize,
+    index_count: usize,
+  ) -> Result<(), MeshError> {
+    capture!(self.mesh_draw(mesh, topology, vertex_count, index_count)[mesh, topology, vertex_count, index_count])
+  }
+
+  fn mesh_delete(&self, mesh: MeshId) -> Result<(), MeshError> {
+    capture!(self.mesh_delete(mesh)[mesh])
+  }
+
+  fn target_create(
+    &self,
+    color_attachment: TextureId,
+    depth_attachment: Option<TextureId>,
+    stencil_attachment: Option<TextureId>,
+  ) -> Result<TargetId, TargetError> {
+    capture!(self.target_create(color_attachment, depth_attachment, stencil_attachment)[
+      color_attachment,
+      depth_attachment,
+      stencil_attachment
+    ])
+  }
+
+  fn target_activate(&self, target: TargetId) -> Result<(), TargetError> {
+    capture!(self.target_activate(target)[target])
+  }
+
+  fn target_set_default(&self) -> Result<(), TargetError> {
+    capture!(self.target_set_default()[])
+  }
+
+  fn target_blit_to_active(
+    &self,
+    target: TargetId,
+    source_rect: Option<Rectangle>,
+    dest_rect: Option<Rectangle>,
+    filter: TextureFilter,
+  ) -> Result<(), TargetError> {
+    capture!(self.target_blit_to_active(target, source_rect, dest_rect, filter)[target, source_rect, dest_rect, filter])
+  }
+
+  fn target_delete(&self, target: TargetId) -> Result<(), TargetError> {
+    capture!(self.target_delete(target)[target])
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::headless::HeadlessGraphicsBackend;
+
+  fn draw_frame(backend: &dyn GraphicsBackend, color: Color) {
+    backend.begin_frame();
+    backend.clear_color_buffer(color);
+
+    let buffer = backend.buffer_create().unwrap();
+    let data = [0u8; 16];
+
+    backend
+      .buffer_write_data(
+        buffer,
+        BufferUsage::Static,
+        BufferKind::Element,
+        data.len(),
+        data.as_ptr(),
+      )
+      .unwrap();
+    backend.buffer_delete(buffer).unwrap();
+    backend.end_frame();
+  }
+
+  #[test]
+  fn it_should_capture_a_single_frame() {
+    let capture = GraphicsCapture::new();
+    let backend = CapturingGraphicsBackend::new(HeadlessGraphicsBackend::default(), capture.clone());
+
+    draw_frame(&backend, Color::BLACK);
+
+    assert!(capture.take_frame().is_none());
+
+    capture.capture_next_frame();
+    draw_frame(&backend, Color::BLACK);
+    draw_frame(&backend, Color::BLACK);
+
+    let log = capture.take_frame().unwrap();
+    let names = log.calls.iter().map(|call| call.name).collect::<Vec<_>>();
+
+    assert_eq!(names, vec![
+      "begin_frame",
+      "clear_color_buffer",
+      "buffer_create",
+      "buffer_write_data",
+      "buffer_delete",
+      "end_frame"
+    ]);
+    assert!(!capture.is_capturing());
+    assert!(log
+      .to_string()
+      .contains("buffer_write_data(buffer: BufferId(2), usage: Static"));
+  }
+
+  #[test]
+  fn it_should_diff_captures() {
+    let capture = GraphicsCapture::new();
+    let backend = CapturingGraphicsBackend::new(HeadlessGraphicsBackend::default(), capture.clone());
+
+    capture.start();
+    draw_frame(&backend, Color::BLACK);
+    let before = capture.stop().unwrap();
+
+    capture.start();
+    draw_frame(&backend, Color::WHITE);
+    backend.clear_depth_buffer(1.);
+    let after = capture.stop().unwrap();
+
+    let differences = before.diff(&after);
+
+    // the clear color and the next buffer's ID changed, and a clear was added
+    assert_eq!(differences.len(), 5);
+    assert!(matches!(differences[0], CaptureDifference::Changed { index: 1, .. }));
+    assert!(matches!(
+      differences.last(),
+      Some(CaptureDifference::Added { index: 6, .. })
+    ));
+  }
+}
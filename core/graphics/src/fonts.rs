@@ -1,5 +1,7 @@
 //! Font support for Surreal.
 
 mod otf;
+mod rich;
 
 pub use otf::*;
+pub use rich::*;
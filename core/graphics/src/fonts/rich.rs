@@ -0,0 +1,592 @@
+//! Rich text markup, with inline icons and animated effects.
+//!
+//! Markup uses square-bracketed tags, like
+//! `Press [icon=button_a] to [color=#ff0000][b]jump[/b][/color]!`:
+//!
+//! - `[color=#rrggbb]` or `[color=#rrggbbaa]` tints text.
+//! - `[size=2]` scales text relative to the base size.
+//! - `[b]` makes text bold.
+//! - `[wave]` and `[shake]` animate each character.
+//! - `[icon=name]` places an inline sprite, such as a button prompt.
+//! - `[event=name]` fires an event when a [`Typewriter`] reaches it.
+//!
+//! A literal bracket is written as `[[`.
+
+use common::{vec2, Color32, Random, Vec2};
+
+/// How fast waving characters bob, in radians per second.
+const WAVE_SPEED: f32 = 6.;
+/// The phase difference between neighbouring waving characters.
+const WAVE_SPACING: f32 = 0.6;
+/// How far waving characters bob, relative to their height.
+const WAVE_AMPLITUDE: f32 = 0.15;
+/// How many times per second shaking characters jump.
+const SHAKE_RATE: f32 = 20.;
+/// How far shaking characters jump, relative to their height.
+const SHAKE_AMPLITUDE: f32 = 0.05;
+
+/// An error when parsing rich text markup.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RichTextError {
+  UnknownTag(String),
+  InvalidValue(String),
+  UnclosedTag(String),
+  UnexpectedClosingTag(String),
+}
+
+/// Animated effects applied to each character.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct RichTextEffects {
+  pub wave: bool,
+  pub shake: bool,
+}
+
+/// The style of a run of rich text.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RichTextStyle {
+  pub color: Color32,
+  /// The size of the text, relative to the base size it's laid out with.
+  pub scale: f32,
+  pub bold: bool,
+  pub effects: RichTextEffects,
+}
+
+impl Default for RichTextStyle {
+  fn default() -> Self {
+    Self {
+      color: Color32::WHITE,
+      scale: 1.,
+      bold: false,
+      effects: RichTextEffects::default(),
+    }
+  }
+}
+
+/// A piece of parsed rich text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RichTextElement {
+  Text { text: String, style: RichTextStyle },
+  Icon { name: String, style: RichTextStyle },
+  Event(String),
+}
+
+/// Parsed rich text markup.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RichText {
+  elements: Vec<RichTextElement>,
+}
+
+impl RichText {
+  /// Parses rich text from the given markup.
+  pub fn parse(markup: &str) -> Result<Self, RichTextError> {
+    let mut elements = Vec::new();
+    let mut style = RichTextStyle::default();
+    let mut open_tags = Vec::<(String, RichTextStyle)>::new();
+    let mut text = String::new();
+    let mut rest = markup;
+
+    while let Some(start) = rest.find('[') {
+      text.push_str(&rest[..start]);
+      rest = &rest[start + 1..];
+
+      if let Some(escaped) = rest.strip_prefix('[') {
+        text.push('[');
+        rest = escaped;
+        continue;
+      }
+
+      let end = rest
+        .find(']')
+        .ok_or_else(|| RichTextError::UnclosedTag(rest.to_string()))?;
+      let tag = &rest[..end];
+
+      rest = &rest[end + 1..];
+
+      if !text.is_empty() {
+        elements.push(RichTextElement::Text {
+          text: std::mem::take(&mut text),
+          style,
+        });
+      }
+
+      if let Some(name) = tag.strip_prefix('/') {
+        match open_tags.pop() {
+          Some((open, previous)) if open == name => style = previous,
+          _ => return Err(RichTextError::UnexpectedClosingTag(name.to_string())),
+        }
+
+        continue;
+      }
+
+      let (name, value) = match tag.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (tag, None),
+      };
+
+      let invalid_value = || RichTextError::InvalidValue(tag.to_string());
+      let previous = style;
+
+      match (name, value) {
+        ("icon", Some(icon)) => {
+          elements.push(RichTextElement::Icon {
+            name: icon.to_string(),
+            style,
+          });
+          continue;
+        }
+        ("event", Some(event)) => {
+          elements.push(RichTextElement::Event(event.to_string()));
+          continue;
+        }
+        ("b", None) => style.bold = true,
+        ("wave", None) => style.effects.wave = true,
+        ("shake", None) => style.effects.shake = true,
+        ("color", Some(color)) => style.color = parse_color(color).ok_or_else(invalid_value)?,
+        ("size", Some(size)) => style.scale = size.parse().map_err(|_| invalid_value())?,
+        ("icon" | "event" | "b" | "wave" | "shake" | "color" | "size", _) => return Err(invalid_value()),
+        _ => return Err(RichTextError::UnknownTag(name.to_string())),
+      }
+
+      open_tags.push((name.to_string(), previous));
+    }
+
+    text.push_str(rest);
+
+    if let Some((name, _)) = open_tags.pop() {
+      return Err(RichTextError::UnclosedTag(name));
+    }
+
+    if !text.is_empty() {
+      elements.push(RichTextElement::Text { text, style });
+    }
+
+    Ok(Self { elements })
+  }
+
+  /// The elements of the text, in order.
+  pub fn elements(&self) -> &[RichTextElement] {
+    &self.elements
+  }
+
+  /// The text without any markup, icons or events.
+  pub fn plain_text(&self) -> String {
+    self
+      .elements
+      .iter()
+      .filter_map(|element| match element {
+        RichTextElement::Text { text, .. } => Some(text.as_str()),
+        _ => None,
+      })
+      .collect()
+  }
+}
+
+/// Parses a `#rrggbb` or `#rrggbbaa` color.
+fn parse_color(color: &str) -> Option<Color32> {
+  let hex = color.strip_prefix('#')?;
+  let value = u32::from_str_radix(hex, 16).ok()?;
+
+  match hex.len() {
+    6 => Some(Color32::from_packed(value << 8 | 0xFF)),
+    8 => Some(Color32::from_packed(value)),
+    _ => None,
+  }
+}
+
+/// Measures characters and icons for laying out rich text.
+pub trait RichTextMetrics {
+  /// The horizontal advance of a character at the given size.
+  fn advance(&self, character: char, size: f32, bold: bool) -> f32;
+
+  /// The distance between lines of text at the given size.
+  fn line_height(&self, size: f32) -> f32;
+
+  /// The size of an icon drawn in text of the given size.
+  fn icon_size(&self, _name: &str, size: f32) -> Vec2 {
+    vec2(size, size)
+  }
+}
+
+/// Metrics for fixed-width fonts, such as bitmap fonts.
+#[derive(Copy, Clone, Debug)]
+pub struct MonospaceMetrics {
+  /// The advance of every character, relative to the text size.
+  pub advance: f32,
+  /// The line height, relative to the text size.
+  pub line_height: f32,
+}
+
+impl RichTextMetrics for MonospaceMetrics {
+  fn advance(&self, _character: char, size: f32, _bold: bool) -> f32 {
+    self.advance * size
+  }
+
+  fn line_height(&self, size: f32) -> f32 {
+    self.line_height * size
+  }
+}
+
+/// What a laid out glyph draws.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RichGlyphKind {
+  Character(char),
+  Icon(String),
+}
+
+/// A character or icon positioned by a [`RichTextLayout`].
+#[derive(Clone, Debug)]
+pub struct RichGlyph {
+  pub kind: RichGlyphKind,
+  /// The top-left of the glyph, relative to the top-left of the text.
+  pub position: Vec2,
+  pub size: Vec2,
+  pub style: RichTextStyle,
+  /// The order in which the glyph is revealed by a [`Typewriter`].
+  pub index: usize,
+}
+
+/// A glyph as it should be drawn at a moment in time.
+#[derive(Clone, Debug)]
+pub struct AnimatedGlyph<'a> {
+  pub glyph: &'a RichGlyph,
+  pub position: Vec2,
+}
+
+/// Rich text laid out into lines, ready to draw.
+#[derive(Clone, Debug, Default)]
+pub struct RichTextLayout {
+  glyphs: Vec<RichGlyph>,
+  events: Vec<(usize, String)>,
+  size: Vec2,
+}
+
+impl RichTextLayout {
+  /// Lays out rich text at the given base size.
+  ///
+  /// Lines wrap between words to fit the maximum width, if there is one.
+  /// Glyphs of different sizes share a line by aligning their bottoms.
+  pub fn new(text: &RichText, metrics: &dyn RichTextMetrics, size: f32, max_width: Option<f32>) -> Self {
+    let mut builder = LayoutBuilder {
+      glyphs: Vec::new(),
+      max_width: max_width.unwrap_or(f32::INFINITY),
+      default_line_height: metrics.line_height(size),
+      line_start: 0,
+      break_at: None,
+      cursor: 0.,
+      top: 0.,
+      width: 0.,
+    };
+
+    let mut events = Vec::new();
+
+    for element in text.elements() {
+      match element {
+        RichTextElement::Text { text, style } => {
+          let size = size * style.scale;
+
+          for character in text.chars() {
+            if character == '\n' {
+              builder.finish_line(builder.glyphs.len());
+              continue;
+            }
+
+            let advance = metrics.advance(character, size, style.bold);
+            let glyph_size = vec2(advance, metrics.line_height(size));
+
+            builder.push(RichGlyphKind::Character(character), glyph_size, style);
+          }
+        }
+        RichTextElement::Icon { name, style } => {
+          let glyph_size = metrics.icon_size(name, size * style.scale);
+
+          builder.push(RichGlyphKind::Icon(name.clone()), glyph_size, style);
+        }
+        RichTextElement::Event(name) => {
+          events.push((builder.glyphs.len(), name.clone()));
+        }
+      }
+    }
+
+    builder.finish_line(builder.glyphs.len());
+
+    Self {
+      glyphs: builder.glyphs,
+      events,
+      size: vec2(builder.width, builder.top),
+    }
+  }
+
+  /// The laid out glyphs, in reveal order.
+  pub fn glyphs(&self) -> &[RichGlyph] {
+    &self.glyphs
+  }
+
+  /// The size of the laid out text.
+  pub fn size(&self) -> Vec2 {
+    self.size
+  }
+
+  /// Positions the first `revealed` glyphs at the given time, in seconds,
+  /// applying their effects.
+  pub fn animate(&self, time: f32, revealed: usize) -> impl Iterator<Item = AnimatedGlyph<'_>> {
+    self.glyphs.iter().take(revealed).map(move |glyph| {
+      let height = glyph.size.y;
+      let mut offset = Vec2::ZERO;
+
+      if glyph.style.effects.wave {
+        let phase = time * WAVE_SPEED + glyph.index as f32 * WAVE_SPACING;
+
+        offset.y += phase.sin() * height * WAVE_AMPLITUDE;
+      }
+
+      if glyph.style.effects.shake {
+        let step = (time * SHAKE_RATE) as u64;
+        let mut random = Random::with_seed(step.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ glyph.index as u64);
+
+        offset += vec2(random.next_range(-1.0..1.0), random.next_range(-1.0..1.0)) * height * SHAKE_AMPLITUDE;
+      }
+
+      AnimatedGlyph {
+        glyph,
+        position: glyph.position + offset,
+      }
+    })
+  }
+}
+
+/// Builds up lines of glyphs, wrapping them to a maximum width.
+struct LayoutBuilder {
+  glyphs: Vec<RichGlyph>,
+  max_width: f32,
+  default_line_height: f32,
+  line_start: usize,
+  break_at: Option<usize>,
+  cursor: f32,
+  top: f32,
+  width: f32,
+}
+
+impl LayoutBuilder {
+  /// Adds a glyph to the current line, wrapping first if it doesn't fit.
+  fn push(&mut self, kind: RichGlyphKind, size: Vec2, style: &RichTextStyle) {
+    let is_space = matches!(kind, RichGlyphKind::Character(character) if character.is_whitespace());
+
+    if self.cursor + size.x > self.max_width && self.cursor > 0. {
+      if is_space {
+        // spaces at the end of a line are dropped, rather than wrapped
+        self.finish_line(self.glyphs.len());
+        return;
+      }
+
+      let split = self
+        .break_at
+        .filter(|&split| split > self.line_start)
+        .unwrap_or(self.glyphs.len());
+
+      self.finish_line(split);
+    }
+
+    self.glyphs.push(RichGlyph {
+      kind,
+      position: vec2(self.cursor, 0.),
+      size,
+      style: *style,
+      index: self.glyphs.len(),
+    });
+
+    self.cursor += size.x;
+
+    if is_space {
+      self.break_at = Some(self.glyphs.len());
+    }
+  }
+
+  /// Ends the current line before the glyph at `end`; glyphs from there on
+  /// move down to start the next line.
+  fn finish_line(&mut self, end: usize) {
+    let line = &mut self.glyphs[self.line_start..end];
+    let height = line
+      .iter()
+      .map(|glyph| glyph.size.y)
+      .reduce(f32::max)
+      .unwrap_or(self.default_line_height);
+
+    let width = line
+      .iter()
+      .filter(|glyph| !matches!(glyph.kind, RichGlyphKind::Character(character) if character.is_whitespace()))
+      .map(|glyph| glyph.position.x + glyph.size.x)
+      .fold(0., f32::max);
+
+    for glyph in line {
+      glyph.position.y = self.top + height - glyph.size.y;
+    }
+
+    let carried = self.glyphs.get(end).map_or(self.cursor, |glyph| glyph.position.x);
+
+    for glyph in &mut self.glyphs[end..] {
+      glyph.position.x -= carried;
+    }
+
+    self.width = self.width.max(width);
+    self.top += height;
+    self.cursor -= carried;
+    self.line_start = end;
+    self.break_at = None;
+  }
+}
+
+/// Reveals laid out rich text a glyph at a time, firing its events as it
+/// reaches them.
+#[derive(Clone, Debug)]
+pub struct Typewriter {
+  glyphs_per_second: f32,
+  revealed: f32,
+  next_event: usize,
+}
+
+impl Typewriter {
+  /// Creates a typewriter that reveals the given number of glyphs per second.
+  pub fn new(glyphs_per_second: f32) -> Self {
+    Self {
+      glyphs_per_second,
+      revealed: 0.,
+      next_event: 0,
+    }
+  }
+
+  /// The number of glyphs revealed so far.
+  pub fn revealed(&self) -> usize {
+    self.revealed as usize
+  }
+
+  /// Has all of the given text been revealed?
+  pub fn is_finished(&self, layout: &RichTextLayout) -> bool {
+    self.revealed() >= layout.glyphs.len() && self.next_event >= layout.events.len()
+  }
+
+  /// Reveals more of the text, returning the events that were reached.
+  pub fn update<'a>(&mut self, layout: &'a RichTextLayout, delta_time: f32) -> Vec<&'a str> {
+    self.revealed = (self.revealed + delta_time * self.glyphs_per_second).min(layout.glyphs.len() as f32);
+    self.take_events(layout)
+  }
+
+  /// Reveals the rest of the text at once, returning the skipped events.
+  pub fn skip<'a>(&mut self, layout: &'a RichTextLayout) -> Vec<&'a str> {
+    self.revealed = layout.glyphs.len() as f32;
+    self.take_events(layout)
+  }
+
+  /// Starts revealing from the beginning again.
+  pub fn reset(&mut self) {
+    self.revealed = 0.;
+    self.next_event = 0;
+  }
+
+  fn take_events<'a>(&mut self, layout: &'a RichTextLayout) -> Vec<&'a str> {
+    let revealed = self.revealed();
+    let events = layout.events[self.next_event.min(layout.events.len())..]
+      .iter()
+      .take_while(|(index, _)| *index <= revealed)
+      .map(|(_, name)| name.as_str())
+      .collect::<Vec<_>>();
+
+    self.next_event += events.len();
+
+    events
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const METRICS: MonospaceMetrics = MonospaceMetrics {
+    advance: 0.5,
+    line_height: 1.,
+  };
+
+  #[test]
+  fn it_should_parse_nested_tags() {
+    let text = RichText::parse("Press [icon=a] to [color=#ff0000][b]jump[/b] now[/color] [[ok]").unwrap();
+    let red = Color32::rgb(255, 0, 0);
+
+    assert_eq!(text.plain_text(), "Press  to jump now [ok]");
+    assert_eq!(text.elements()[1], RichTextElement::Icon {
+      name: "a".to_string(),
+      style: RichTextStyle::default(),
+    });
+
+    let RichTextElement::Text { style, .. } = &text.elements()[3] else {
+      panic!("expected text");
+    };
+
+    assert_eq!(style.color, red);
+    assert!(style.bold);
+
+    let RichTextElement::Text { style, .. } = &text.elements()[4] else {
+      panic!("expected text");
+    };
+
+    assert_eq!(style.color, red);
+    assert!(!style.bold);
+  }
+
+  #[test]
+  fn it_should_reject_malformed_markup() {
+    let error = |markup| RichText::parse(markup).unwrap_err();
+
+    assert_eq!(error("[b]bold"), RichTextError::UnclosedTag("b".to_string()));
+    assert_eq!(
+      error("[b]bold[/wave]"),
+      RichTextError::UnexpectedClosingTag("wave".to_string())
+    );
+    assert_eq!(error("[blink]"), RichTextError::UnknownTag("blink".to_string()));
+    assert_eq!(
+      error("[color=red]"),
+      RichTextError::InvalidValue("color=red".to_string())
+    );
+    assert_eq!(error("[size=1"), RichTextError::UnclosedTag("size=1".to_string()));
+  }
+
+  #[test]
+  fn it_should_wrap_words_and_align_mixed_sizes() {
+    let text = RichText::parse("aa bb [size=2]c[/size]\ndd").unwrap();
+    let layout = RichTextLayout::new(&text, &METRICS, 10., Some(30.));
+    let glyphs = layout.glyphs();
+
+    // "aa bb" is 25 wide, so the large "c" wraps onto its own line
+    assert_eq!(glyphs[3].position, vec2(15., 0.));
+    assert_eq!(glyphs[6].kind, RichGlyphKind::Character('c'));
+    assert_eq!(glyphs[6].position, vec2(0., 10.));
+    assert_eq!(glyphs[7].position, vec2(0., 30.));
+    assert_eq!(layout.size(), vec2(25., 40.));
+  }
+
+  #[test]
+  fn it_should_animate_only_effected_glyphs() {
+    let text = RichText::parse("a[wave]b[/wave][shake]c[/shake]").unwrap();
+    let layout = RichTextLayout::new(&text, &METRICS, 10., None);
+
+    let at = |time| layout.animate(time, 3).map(|it| it.position).collect::<Vec<_>>();
+    let (first, second) = (at(0.1), at(0.2));
+
+    assert_eq!(first[0], second[0]);
+    assert_ne!(first[1], second[1]);
+    assert_ne!(first[2], second[2]);
+    assert_eq!(layout.animate(0., 2).count(), 2);
+  }
+
+  #[test]
+  fn it_should_reveal_text_and_fire_events() {
+    let text = RichText::parse("[event=start]ab[event=middle]cd[event=end]").unwrap();
+    let layout = RichTextLayout::new(&text, &METRICS, 10., None);
+    let mut typewriter = Typewriter::new(10.);
+
+    assert_eq!(typewriter.update(&layout, 0.1), vec!["start"]);
+    assert_eq!(typewriter.update(&layout, 0.1), vec!["middle"]);
+    assert_eq!(typewriter.revealed(), 2);
+    assert!(!typewriter.is_finished(&layout));
+    assert_eq!(typewriter.skip(&layout), vec!["end"]);
+    assert!(typewriter.is_finished(&layout));
+  }
+}
@@ -0,0 +1,419 @@
+//! Debug drawing, grouped into categories that can be toggled at runtime.
+//!
+//! Systems submit lines and shapes to [`Gizmos`] under a [`DebugCategory`],
+//! like physics colliders or AI sight cones, and every category has its own
+//! color from the color-blind-safe [`DebugPalette`], so the same kind of
+//! thing always looks the same:
+//!
+//! ```rust,ignore
+//! let categories = DebugCategories::default();
+//! let mut gizmos = Gizmos::new(categories.clone());
+//!
+//! categories.register_commands(&mut console);
+//!
+//! gizmos.circle(DebugCategory::Physics, body.position, body.radius);
+//! gizmos.arrow(DebugCategory::Ai, agent.position, agent.target);
+//!
+//! gizmos.draw(&mut geometry);
+//! ```
+//!
+//! Submissions to a disabled category are dropped straight away, so debug
+//! drawing can stay in place and be switched on from the developer console
+//! with `debug_draw physics on`.
+
+use std::sync::{
+  atomic::{AtomicU32, Ordering},
+  Arc,
+};
+
+use common::{vec2, Bounds, Color32, ConsoleError, DeveloperConsole, Rectangle, Vec2};
+
+use super::*;
+
+/// A color-blind-safe palette for debug drawing.
+///
+/// These are the Okabe-Ito colors, which stay distinguishable under the
+/// common forms of color blindness.
+pub struct DebugPalette;
+
+impl DebugPalette {
+  pub const ORANGE: Color32 = Color32::rgb(230, 159, 0);
+  pub const SKY_BLUE: Color32 = Color32::rgb(86, 180, 233);
+  pub const BLUISH_GREEN: Color32 = Color32::rgb(0, 158, 115);
+  pub const YELLOW: Color32 = Color32::rgb(240, 228, 66);
+  pub const BLUE: Color32 = Color32::rgb(0, 114, 178);
+  pub const VERMILLION: Color32 = Color32::rgb(213, 94, 0);
+  pub const REDDISH_PURPLE: Color32 = Color32::rgb(204, 121, 167);
+  pub const GREY: Color32 = Color32::rgb(153, 153, 153);
+}
+
+/// A category of debug drawing, toggled as a whole.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum DebugCategory {
+  Physics,
+  Ai,
+  Navigation,
+  Audio,
+  Gameplay,
+}
+
+impl DebugCategory {
+  /// Every category, in display order.
+  pub const ALL: [Self; 5] = [Self::Physics, Self::Ai, Self::Navigation, Self::Audio, Self::Gameplay];
+
+  /// The name of the category, as typed in the console.
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Physics => "physics",
+      Self::Ai => "ai",
+      Self::Navigation => "navigation",
+      Self::Audio => "audio",
+      Self::Gameplay => "gameplay",
+    }
+  }
+
+  /// Finds a category by its name.
+  pub fn from_name(name: &str) -> Option<Self> {
+    Self::ALL.into_iter().find(|category| category.name() == name)
+  }
+
+  /// The color everything in the category is drawn in.
+  pub fn color(&self) -> Color32 {
+    match self {
+      Self::Physics => DebugPalette::BLUISH_GREEN,
+      Self::Ai => DebugPalette::VERMILLION,
+      Self::Navigation => DebugPalette::SKY_BLUE,
+      Self::Audio => DebugPalette::YELLOW,
+      Self::Gameplay => DebugPalette::REDDISH_PURPLE,
+    }
+  }
+
+  fn bit(&self) -> u32 {
+    1 << *self as u8
+  }
+}
+
+/// Which debug categories are drawn, shared between [`Gizmos`] and whatever
+/// toggles them, like the developer console.
+///
+/// Every category is enabled by default.
+#[derive(Clone)]
+pub struct DebugCategories(Arc<AtomicU32>);
+
+impl Default for DebugCategories {
+  fn default() -> Self {
+    let all = DebugCategory::ALL
+      .iter()
+      .fold(0, |bits, category| bits | category.bit());
+
+    Self(Arc::new(AtomicU32::new(all)))
+  }
+}
+
+impl DebugCategories {
+  /// Determines if the given category is drawn.
+  pub fn is_enabled(&self, category: DebugCategory) -> bool {
+    self.0.load(Ordering::Relaxed) & category.bit() != 0
+  }
+
+  /// Shows or hides the given category.
+  pub fn set_enabled(&self, category: DebugCategory, enabled: bool) {
+    match enabled {
+      true => self.0.fetch_or(category.bit(), Ordering::Relaxed),
+      false => self.0.fetch_and(!category.bit(), Ordering::Relaxed),
+    };
+  }
+
+  /// Shows the given category if it's hidden, and hides it if it's shown.
+  pub fn toggle(&self, category: DebugCategory) {
+    self.0.fetch_xor(category.bit(), Ordering::Relaxed);
+  }
+
+  /// Registers the `debug_draw` command with the given console.
+  ///
+  /// `debug_draw` lists the categories and whether they're shown;
+  /// `debug_draw <category|all> [on|off]` shows, hides or toggles them.
+  pub fn register_commands(&self, console: &mut DeveloperConsole) {
+    let categories = self.clone();
+
+    console.register(
+      "debug_draw",
+      "shows or hides debug drawing: debug_draw [<category>|all] [on|off]",
+      move |arguments| categories.execute(arguments),
+    );
+  }
+
+  /// Runs the `debug_draw` command.
+  fn execute(&self, arguments: &[&str]) -> Result<String, ConsoleError> {
+    let selected = match arguments.first() {
+      None => Vec::new(),
+      Some(&"all") => DebugCategory::ALL.to_vec(),
+      Some(name) => vec![DebugCategory::from_name(name).ok_or_else(|| {
+        let names = DebugCategory::ALL.map(|category| category.name()).join(", ");
+
+        ConsoleError::InvalidArguments(format!("unknown category '{name}'; expected all, {names}"))
+      })?],
+    };
+
+    for category in &selected {
+      match arguments.get(1) {
+        None => self.toggle(*category),
+        Some(&"on") => self.set_enabled(*category, true),
+        Some(&"off") => self.set_enabled(*category, false),
+        Some(state) => {
+          return Err(ConsoleError::InvalidArguments(format!(
+            "expected on or off, but got '{state}'"
+          )))
+        }
+      }
+    }
+
+    let states = DebugCategory::ALL
+      .iter()
+      .map(|category| {
+        let state = if self.is_enabled(*category) { "on" } else { "off" };
+
+        format!("{}: {state}", category.name())
+      })
+      .collect::<Vec<_>>();
+
+    Ok(states.join("\n"))
+  }
+}
+
+/// A shape submitted to [`Gizmos`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum GizmoShape {
+  Line { from: Vec2, to: Vec2 },
+  Circle { center: Vec2, radius: f32 },
+  Rectangle { rectangle: Rectangle },
+}
+
+/// A shape to draw this frame, in the color of its category.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gizmo {
+  pub category: DebugCategory,
+  pub shape: GizmoShape,
+  pub color: Color32,
+}
+
+/// The number of segments in a gizmo circle.
+const CIRCLE_SEGMENTS: usize = 24;
+
+/// The length of the head of an arrow, relative to the arrow.
+const ARROW_HEAD: f32 = 0.2;
+
+/// Immediate-mode debug drawing, filtered by category.
+///
+/// Gizmos are submitted every frame and cleared when drawn.
+pub struct Gizmos {
+  categories: DebugCategories,
+  gizmos: Vec<Gizmo>,
+  /// The width of lines and outlines, in world units.
+  pub line_width: f32,
+}
+
+impl Default for Gizmos {
+  fn default() -> Self {
+    Self::new(DebugCategories::default())
+  }
+}
+
+impl Gizmos {
+  /// Creates gizmos that draw the given categories.
+  pub fn new(categories: DebugCategories) -> Self {
+    Self {
+      categories,
+      gizmos: Vec::new(),
+      line_width: 1.,
+    }
+  }
+
+  /// The categories that are drawn.
+  pub fn categories(&self) -> &DebugCategories {
+    &self.categories
+  }
+
+  /// Draws a line.
+  pub fn line(&mut self, category: DebugCategory, from: Vec2, to: Vec2) {
+    self.submit(category, GizmoShape::Line { from, to });
+  }
+
+  /// Draws a line with a head at its end, for directions and targets.
+  pub fn arrow(&mut self, category: DebugCategory, from: Vec2, to: Vec2) {
+    let head = (from - to) * ARROW_HEAD;
+
+    self.line(category, from, to);
+    self.line(category, to, to + head + head.perp() * 0.5);
+    self.line(category, to, to + head - head.perp() * 0.5);
+  }
+
+  /// Draws the outline of a circle, like a collider or an audio range.
+  pub fn circle(&mut self, category: DebugCategory, center: Vec2, radius: f32) {
+    self.submit(category, GizmoShape::Circle { center, radius });
+  }
+
+  /// Draws the outline of a rectangle.
+  pub fn rectangle(&mut self, category: DebugCategory, rectangle: Rectangle) {
+    self.submit(category, GizmoShape::Rectangle { rectangle });
+  }
+
+  /// Draws bounds as seen from above the `z = 0` plane; the box as a
+  /// rectangle and the sphere as a circle.
+  pub fn bounds(&mut self, category: DebugCategory, bounds: &Bounds) {
+    self.rectangle(
+      category,
+      Rectangle::from_corner_points(
+        bounds.aabb.min.x,
+        bounds.aabb.min.y,
+        bounds.aabb.max.x,
+        bounds.aabb.max.y,
+      ),
+    );
+    self.circle(category, bounds.sphere.center.truncate(), bounds.sphere.radius);
+  }
+
+  /// Draws a small cross, marking a point.
+  pub fn point(&mut self, category: DebugCategory, position: Vec2, size: f32) {
+    let half = size * 0.5;
+
+    self.line(category, position - vec2(half, half), position + vec2(half, half));
+    self.line(category, position - vec2(half, -half), position + vec2(half, -half));
+  }
+
+  /// The gizmos submitted this frame.
+  pub fn iter(&self) -> impl Iterator<Item = &Gizmo> {
+    self.gizmos.iter()
+  }
+
+  /// The number of gizmos submitted this frame.
+  pub fn len(&self) -> usize {
+    self.gizmos.len()
+  }
+
+  /// Determines if nothing was submitted this frame.
+  pub fn is_empty(&self) -> bool {
+    self.gizmos.is_empty()
+  }
+
+  /// Forgets everything submitted this frame.
+  pub fn clear(&mut self) {
+    self.gizmos.clear();
+  }
+
+  /// Draws everything submitted this frame into a geometry batch, and
+  /// clears it for the next.
+  ///
+  /// Categories hidden since submission are skipped.
+  pub fn draw(&mut self, batch: &mut GeometryBatch) {
+    for gizmo in self.gizmos.drain(..) {
+      if !self.categories.is_enabled(gizmo.category) {
+        continue;
+      }
+
+      match gizmo.shape {
+        GizmoShape::Line { from, to } => draw_line(batch, from, to, self.line_width, gizmo.color),
+        GizmoShape::Circle { center, radius } => {
+          for index in 0..CIRCLE_SEGMENTS {
+            let point = |index: usize| {
+              let angle = index as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+
+              center + Vec2::from_angle(angle) * radius
+            };
+
+            draw_line(batch, point(index), point(index + 1), self.line_width, gizmo.color);
+          }
+        }
+        GizmoShape::Rectangle { rectangle } => {
+          let corners = [
+            rectangle.top_left(),
+            rectangle.top_right(),
+            rectangle.bottom_right(),
+            rectangle.bottom_left(),
+          ];
+
+          for index in 0..corners.len() {
+            let next = corners[(index + 1) % corners.len()];
+
+            draw_line(batch, corners[index], next, self.line_width, gizmo.color);
+          }
+        }
+      }
+    }
+  }
+
+  fn submit(&mut self, category: DebugCategory, shape: GizmoShape) {
+    if self.categories.is_enabled(category) {
+      self.gizmos.push(Gizmo {
+        category,
+        shape,
+        color: category.color(),
+      });
+    }
+  }
+}
+
+/// Draws a line as a quad of the given width.
+fn draw_line(batch: &mut GeometryBatch, from: Vec2, to: Vec2, width: f32, color: Color32) {
+  let Some(direction) = (to - from).try_normalize() else {
+    return;
+  };
+
+  let offset = direction.perp() * width * 0.5;
+
+  batch.draw_triangle(from - offset, from + offset, to + offset, color);
+  batch.draw_triangle(from - offset, to + offset, to - offset, color);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_skip_disabled_categories() {
+    let categories = DebugCategories::default();
+    let mut gizmos = Gizmos::new(categories.clone());
+
+    categories.set_enabled(DebugCategory::Audio, false);
+
+    gizmos.circle(DebugCategory::Audio, Vec2::ZERO, 10.);
+    gizmos.circle(DebugCategory::Physics, Vec2::ZERO, 1.);
+    gizmos.arrow(DebugCategory::Ai, Vec2::ZERO, Vec2::X);
+
+    assert_eq!(gizmos.len(), 4);
+    assert_eq!(gizmos.iter().next().unwrap().color, DebugPalette::BLUISH_GREEN);
+    assert!(gizmos.iter().all(|gizmo| gizmo.category != DebugCategory::Audio));
+  }
+
+  #[test]
+  fn it_should_toggle_categories_from_the_console() {
+    let categories = DebugCategories::default();
+    let mut console = DeveloperConsole::new();
+
+    categories.register_commands(&mut console);
+
+    console.execute("debug_draw physics off").unwrap();
+    assert!(!categories.is_enabled(DebugCategory::Physics));
+
+    console.execute("debug_draw physics").unwrap();
+    assert!(categories.is_enabled(DebugCategory::Physics));
+
+    let states = console.execute("debug_draw all off").unwrap();
+
+    assert!(DebugCategory::ALL.iter().all(|it| !categories.is_enabled(*it)));
+    assert!(states.starts_with("physics: off\nai: off"));
+
+    assert!(console.execute("debug_draw lighting").is_err());
+    assert!(console.execute("debug_draw ai maybe").is_err());
+  }
+
+  #[test]
+  fn it_should_use_distinct_colors_per_category() {
+    for (index, a) in DebugCategory::ALL.iter().enumerate() {
+      for b in &DebugCategory::ALL[index + 1..] {
+        assert_ne!(a.color(), b.color());
+      }
+    }
+  }
+}
@@ -39,6 +39,14 @@ impl GraphicsBackend for HeadlessGraphicsBackend {
     // no-op
   }
 
+  fn device_status(&self) -> DeviceStatus {
+    DeviceStatus::Ready
+  }
+
+  fn device_reset(&self) {
+    // no-op
+  }
+
   fn clear_color_buffer(&self, color: Color) {
     // no-op
   }
@@ -63,6 +71,10 @@ impl GraphicsBackend for HeadlessGraphicsBackend {
     // no-op
   }
 
+  fn set_depth_state(&self, depth_state: DepthState) {
+    // no-op
+  }
+
   fn set_scissor_mode(&self, scissor_mode: ScissorMode) {
     // no-op
   }
@@ -92,6 +104,10 @@ impl GraphicsBackend for HeadlessGraphicsBackend {
     Ok(())
   }
 
+  fn buffer_bind_storage(&self, buffer: BufferId, binding: u32) -> Result<(), BufferError> {
+    Ok(())
+  }
+
   fn buffer_delete(&self, buffer: BufferId) -> Result<(), BufferError> {
     Ok(())
   }
@@ -149,6 +165,32 @@ impl GraphicsBackend for HeadlessGraphicsBackend {
     Ok(())
   }
 
+  fn texture_initialize_layers(
+    &self,
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    layers: u32,
+    format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    Ok(())
+  }
+
+  fn texture_write_layer_data(
+    &self,
+    texture: TextureId,
+    region: &Rectangle,
+    layer: u32,
+    pixels: *const u8,
+    pixel_format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    Ok(())
+  }
+
+  fn texture_set_mip_range(&self, texture: TextureId, base_level: usize, max_level: usize) -> Result<(), TextureError> {
+    Ok(())
+  }
+
   fn texture_delete(&self, texture: TextureId) -> Result<(), TextureError> {
     Ok(())
   }
@@ -161,6 +203,22 @@ impl GraphicsBackend for HeadlessGraphicsBackend {
     Ok(())
   }
 
+  fn shader_link_async(&self, shader: ShaderId, kernels: &[ShaderKernel]) -> Result<(), ShaderError> {
+    Ok(())
+  }
+
+  fn shader_link_status(&self, shader: ShaderId) -> ShaderLinkStatus {
+    ShaderLinkStatus::Linked
+  }
+
+  fn shader_binary(&self, shader: ShaderId) -> Option<ShaderBinary> {
+    None
+  }
+
+  fn shader_link_binary(&self, shader: ShaderId, binary: &ShaderBinary) -> Result<(), ShaderError> {
+    Err(ShaderError::UnsupportedBinary)
+  }
+
   fn shader_uniform_location(&self, shader: ShaderId, name: &str) -> Option<usize> {
     None
   }
@@ -13,14 +13,18 @@ impl<T> Clone for GraphicsCell<T> {
   }
 }
 
-impl<T> GraphicsCell<T> {
-  /// Creates a new graphics state with the given value.
-  pub fn new(value: T) -> Self {
-    Self {
-      state: Arc::new(RwLock::new(value)),
-    }
+impl<T: crate::Restore> GraphicsCell<T> {
+  /// Creates a new graphics state that's restored if the device is lost.
+  pub fn restorable(value: T) -> Self {
+    let state = Arc::new(RwLock::new(value));
+
+    crate::register_resource(&state);
+
+    Self { state }
   }
+}
 
+impl<T> GraphicsCell<T> {
   /// Locks the state for reading.
   #[inline]
   pub fn read(&self) -> RwLockReadGuard<T> {
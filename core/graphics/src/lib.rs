@@ -7,31 +7,57 @@
 
 pub use animations::*;
 pub use buffers::*;
+pub use capture::*;
 pub use fonts::*;
 pub use geometry::*;
+pub use gizmos::*;
 pub use images::*;
+pub use lighting::*;
 pub use materials::*;
 pub use meshes::*;
+pub use metaballs::*;
+pub use minimaps::*;
+pub use performance::*;
+pub use photos::*;
+pub use recovery::*;
 pub use rendering::*;
 pub use shaders::*;
+pub use shapes::*;
 pub use sprites::*;
 pub use targets::*;
 pub use textures::*;
+pub use tracking::*;
+pub use transitions::*;
+pub use validation::*;
+pub use weather::*;
 
 mod animations;
 mod buffers;
+mod capture;
 mod fonts;
 mod geometry;
+mod gizmos;
 mod headless;
 mod images;
 mod internal;
+mod lighting;
 mod materials;
 mod meshes;
+mod metaballs;
+mod minimaps;
+mod performance;
+mod photos;
+mod recovery;
 mod rendering;
 mod shaders;
+mod shapes;
 mod sprites;
 mod targets;
 mod textures;
+mod tracking;
+mod transitions;
+mod validation;
+mod weather;
 
 pub use macros::Vertex;
 
@@ -41,7 +67,7 @@ common::impl_arena_index!(pub ShaderId, "Identifies a shader program.");
 common::impl_arena_index!(pub MeshId, "Identifies a mesh.");
 common::impl_arena_index!(pub TargetId, "Identifies a render target.");
 
-common::impl_server!(GraphicsServer by GraphicsBackend default headless::HeadlessGraphicsBackend);
+common::impl_server!(GraphicsServer by GraphicsBackend default headless::HeadlessGraphicsBackend, thread bound);
 
 /// Gets the graphics server instance.
 #[inline(always)]
@@ -58,6 +84,7 @@ pub enum GraphicsError {
   ShaderError(ShaderError),
   MeshError(MeshError),
   TargetError(TargetError),
+  MaterialError(MaterialError),
 }
 
 /// A possible error when interacting with buffers.
@@ -65,6 +92,7 @@ pub enum GraphicsError {
 pub enum BufferError {
   CreationFailed,
   InvalidId(BufferId),
+  Deleted(BufferId),
   BufferTooSmall,
   NullPointer,
 }
@@ -73,6 +101,16 @@ pub enum BufferError {
 #[derive(Debug)]
 pub enum TextureError {
   InvalidId(TextureId),
+  Deleted(TextureId),
+  NotInitialized(TextureId),
+  InvalidSize(u32, u32),
+  InvalidRegion(common::Rectangle),
+  InvalidLayer(u32),
+  InvalidMipRange(usize, usize),
+  FormatMismatch {
+    expected: TextureFormat,
+    actual: TextureFormat,
+  },
   InvalidImage(ImageError),
 }
 
@@ -80,16 +118,23 @@ pub enum TextureError {
 #[derive(Debug)]
 pub enum ShaderError {
   InvalidId(ShaderId),
+  Deleted(ShaderId),
+  NotLinked(ShaderId),
   CompileError(String),
   FailedToLoad,
   InvalidInclude,
   InvalidUniform,
+  /// The driver can't link a program from the binary, e.g. it was cached by
+  /// a different driver version.
+  UnsupportedBinary,
 }
 
 /// A possible error when interacting with meshes.
 #[derive(Debug)]
 pub enum MeshError {
   InvalidId(MeshId),
+  Deleted(MeshId),
+  InvalidBuffer(BufferId),
   FailedToCreate,
 }
 
@@ -97,13 +142,26 @@ pub enum MeshError {
 #[derive(Debug)]
 pub enum TargetError {
   InvalidId(TargetId),
+  Deleted(TargetId),
+  InvalidAttachment(TextureId),
   FailedToBuildAttachments,
 }
 
+/// A possible error when loading materials.
+#[derive(Debug)]
+pub enum MaterialError {
+  InvalidData(common::StreamError),
+  InvalidField(String),
+  ShaderError(ShaderError),
+  TextureError(TextureError),
+}
+
 /// A memory barrier for synchronising memory access in a shader.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum MemoryBarrier {
   ImageAccess,
+  /// Compute writes must land before they're read as vertex attributes.
+  VertexAttributes,
 }
 
 common::impl_error_coercion!(BufferError into GraphicsError);
@@ -111,6 +169,9 @@ common::impl_error_coercion!(TextureError into GraphicsError);
 common::impl_error_coercion!(ShaderError into GraphicsError);
 common::impl_error_coercion!(MeshError into GraphicsError);
 common::impl_error_coercion!(TargetError into GraphicsError);
+common::impl_error_coercion!(MaterialError into GraphicsError);
+common::impl_error_coercion!(ShaderError into MaterialError);
+common::impl_error_coercion!(TextureError into MaterialError);
 
 /// An abstraction on top of the underlying graphics API.
 ///
@@ -125,6 +186,10 @@ pub trait GraphicsBackend {
   fn begin_frame(&self);
   fn end_frame(&self);
 
+  // device
+  fn device_status(&self) -> DeviceStatus;
+  fn device_reset(&self);
+
   // clear targets
   fn clear_color_buffer(&self, color: common::Color);
   fn clear_depth_buffer(&self, depth: f32);
@@ -134,12 +199,14 @@ pub trait GraphicsBackend {
   fn set_viewport_size(&self, size: common::UVec2);
   fn set_blend_state(&self, blend_state: BlendState);
   fn set_culling_mode(&self, culling_mode: CullingMode);
+  fn set_depth_state(&self, depth_state: DepthState);
   fn set_scissor_mode(&self, scissor_mode: ScissorMode);
 
   // buffers
   fn buffer_create(&self) -> Result<BufferId, BufferError>;
   fn buffer_read_data(&self, buffer: BufferId, offset: usize, length: usize, pointer: *mut u8) -> Result<(), BufferError>;
   fn buffer_write_data(&self, buffer: BufferId, usage: BufferUsage, kind: BufferKind, length: usize, pointer: *const u8) -> Result<(), BufferError>;
+  fn buffer_bind_storage(&self, buffer: BufferId, binding: u32) -> Result<(), BufferError>;
   fn buffer_delete(&self, buffer: BufferId) -> Result<(), BufferError>;
 
   // textures
@@ -149,11 +216,18 @@ pub trait GraphicsBackend {
   fn texture_read_data(&self, texture: TextureId, length: usize, pixel_format: TextureFormat, pixels: *mut u8, mip_level: usize) -> Result<(), TextureError>;
   fn texture_write_data(&self, texture: TextureId, width: u32, height: u32, pixels: *const u8, internal_format: TextureFormat, pixel_format: TextureFormat, mip_level: usize) -> Result<(), TextureError>;
   fn texture_write_sub_data(&self, texture: TextureId, region: &common::Rectangle, pixels: *const u8, pixel_format: TextureFormat, mip_level: usize) -> Result<(), TextureError>;
+  fn texture_initialize_layers(&self, texture: TextureId, width: u32, height: u32, layers: u32, format: TextureFormat) -> Result<(), TextureError>;
+  fn texture_write_layer_data(&self, texture: TextureId, region: &common::Rectangle, layer: u32, pixels: *const u8, pixel_format: TextureFormat) -> Result<(), TextureError>;
+  fn texture_set_mip_range(&self, texture: TextureId, base_level: usize, max_level: usize) -> Result<(), TextureError>;
   fn texture_delete(&self, texture: TextureId) -> Result<(), TextureError>;
 
   // shaders
   fn shader_create(&self) -> Result<ShaderId, ShaderError>;
   fn shader_link(&self, shader: ShaderId, kernels: &[ShaderKernel]) -> Result<(), ShaderError>;
+  fn shader_link_async(&self, shader: ShaderId, kernels: &[ShaderKernel]) -> Result<(), ShaderError>;
+  fn shader_link_status(&self, shader: ShaderId) -> ShaderLinkStatus;
+  fn shader_binary(&self, shader: ShaderId) -> Option<ShaderBinary>;
+  fn shader_link_binary(&self, shader: ShaderId, binary: &ShaderBinary) -> Result<(), ShaderError>;
   fn shader_uniform_location(&self, shader: ShaderId, name: &str) -> Option<usize>;
   fn shader_set_uniform(&self, shader: ShaderId, location: usize, value: &ShaderUniform) -> Result<(), ShaderError>;
   fn shader_activate(&self, shader: ShaderId) -> Result<(), ShaderError>;
@@ -0,0 +1,410 @@
+//! Soft shadows for 2D lights.
+//!
+//! Each [`Light2D`] renders the [`Occluder2D`]s around it into a 1D
+//! [`ShadowMap`]: one texel per direction out from the light, holding how far
+//! away each occluder is and how much light it lets through. Shading a point
+//! then looks up its direction and distance in the map.
+//!
+//! Hard shadows look harsh in most art styles, so lookups approximate the
+//! penumbra of a light with a size. Texels around the point's direction are
+//! searched for blockers, and the shadow is filtered over a wedge that widens
+//! the further the point is behind them and the bigger the light's
+//! [`Light2D::source_radius`], so contact shadows stay crisp and long shadows
+//! go soft:
+//!
+//! ```rust,ignore
+//! let shadows = light.shadow_map(&occluders, 256);
+//!
+//! let color = light.illuminate(point, &shadows);
+//! ```
+//!
+//! Occluders can be partly transparent, like foliage or frosted glass, and
+//! the light left after passing through several of them is multiplied
+//! together.
+
+use std::f32::consts::{PI, TAU};
+
+use common::{Color, Color32, Vec2};
+
+use super::*;
+
+/// The most occluders a single texel of a [`ShadowMap`] remembers; any
+/// further away are dropped.
+const MAX_LAYERS: usize = 4;
+
+/// The number of texels sampled when filtering a penumbra.
+const PENUMBRA_TAPS: usize = 9;
+
+/// The widest a penumbra can get, either side of the point, in radians.
+const MAX_PENUMBRA: f32 = PI / 8.;
+
+/// A point light in 2D.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Light2D {
+  pub position: Vec2,
+  /// How far the light reaches.
+  pub range: f32,
+  pub color: Color,
+  pub intensity: f32,
+  /// The radius of the light's source; bigger sources cast softer shadows,
+  /// and zero casts hard ones.
+  pub source_radius: f32,
+}
+
+impl Default for Light2D {
+  fn default() -> Self {
+    Self {
+      position: Vec2::ZERO,
+      range: 10.,
+      color: Color::WHITE,
+      intensity: 1.,
+      source_radius: 0.5,
+    }
+  }
+}
+
+impl Light2D {
+  /// A shader uniform key for the light's position.
+  pub const POSITION: ShaderUniformKey<Vec2> = ShaderUniformKey::new("u_light_position");
+  /// A shader uniform key for how far the light reaches.
+  pub const RANGE: ShaderUniformKey<f32> = ShaderUniformKey::new("u_light_range");
+  /// A shader uniform key for the light's color, premultiplied by its
+  /// intensity.
+  pub const COLOR: ShaderUniformKey<Color> = ShaderUniformKey::new("u_light_color");
+  /// A shader uniform key for the radius of the light's source.
+  pub const SOURCE_RADIUS: ShaderUniformKey<f32> = ShaderUniformKey::new("u_light_source_radius");
+
+  /// Sets the light's parameters on a lighting material.
+  pub fn apply(&self, material: &mut Material) {
+    material.set_uniform(Self::POSITION, self.position);
+    material.set_uniform(Self::RANGE, self.range);
+    material.set_uniform(Self::COLOR, scale_color(self.color, self.intensity));
+    material.set_uniform(Self::SOURCE_RADIUS, self.source_radius);
+  }
+
+  /// Renders the occluders around the light into a shadow map with the
+  /// given number of directions.
+  pub fn shadow_map(&self, occluders: &[Occluder2D], resolution: usize) -> ShadowMap {
+    let resolution = resolution.max(1);
+    let mut texels = vec![ShadowTexel::default(); resolution];
+
+    for (index, texel) in texels.iter_mut().enumerate() {
+      let angle = (index as f32 + 0.5) / resolution as f32 * TAU;
+      let direction = Vec2::from_angle(angle);
+
+      for occluder in occluders {
+        if occluder.opacity <= 0. {
+          continue;
+        }
+
+        // only the nearest edge counts, so an occluder isn't counted again
+        // where the ray leaves it
+        let nearest = occluder
+          .edges()
+          .filter_map(|(a, b)| intersect_ray(self.position, direction, a, b))
+          .filter(|distance| *distance <= self.range)
+          .min_by(f32::total_cmp);
+
+        if let Some(distance) = nearest {
+          texel.insert(distance, occluder.opacity.min(1.));
+        }
+      }
+    }
+
+    ShadowMap {
+      origin: self.position,
+      range: self.range,
+      source_radius: self.source_radius,
+      texels,
+    }
+  }
+
+  /// The light reaching a point, fading out over the light's range and
+  /// through the shadows in the map.
+  pub fn illuminate(&self, point: Vec2, shadows: &ShadowMap) -> Color {
+    let distance = point.distance(self.position);
+
+    if distance >= self.range {
+      return Color::rgba(0., 0., 0., 0.);
+    }
+
+    let falloff = 1. - distance / self.range;
+    let light = falloff * falloff * self.intensity * shadows.visibility(point);
+
+    scale_color(self.color, light)
+  }
+}
+
+/// Something that blocks light in 2D, as an outline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Occluder2D {
+  pub points: Vec<Vec2>,
+  /// Whether the last point joins back to the first.
+  pub closed: bool,
+  /// How much light the occluder blocks, from 0 for none to 1 for all.
+  pub opacity: f32,
+}
+
+impl Occluder2D {
+  /// Creates an opaque, closed occluder from an outline.
+  pub fn polygon(points: Vec<Vec2>) -> Self {
+    Self {
+      points,
+      closed: true,
+      opacity: 1.,
+    }
+  }
+
+  /// Creates an opaque occluder from a line, like a thin wall.
+  pub fn line(a: Vec2, b: Vec2) -> Self {
+    Self {
+      points: vec![a, b],
+      closed: false,
+      opacity: 1.,
+    }
+  }
+
+  /// Sets how much light the occluder blocks.
+  pub fn with_opacity(mut self, opacity: f32) -> Self {
+    self.opacity = opacity;
+    self
+  }
+
+  /// The edges of the occluder's outline.
+  pub fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    let count = self.points.len();
+    let edges = match (count, self.closed) {
+      (0 | 1, _) => 0,
+      (2, _) | (_, false) => count - 1,
+      (_, true) => count,
+    };
+
+    (0..edges).map(move |index| (self.points[index], self.points[(index + 1) % count]))
+  }
+}
+
+/// An occluder hit by one direction of a [`ShadowMap`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct ShadowLayer {
+  distance: f32,
+  opacity: f32,
+}
+
+/// The occluders hit in one direction out from a light, nearest first.
+#[derive(Copy, Clone, Debug, Default)]
+struct ShadowTexel {
+  layers: [ShadowLayer; MAX_LAYERS],
+  count: usize,
+}
+
+impl ShadowTexel {
+  /// Adds an occluder, keeping the nearest if there are too many.
+  fn insert(&mut self, distance: f32, opacity: f32) {
+    let index = self.layers[..self.count].partition_point(|layer| layer.distance < distance);
+
+    if index >= MAX_LAYERS {
+      return;
+    }
+
+    self.count = (self.count + 1).min(MAX_LAYERS);
+    self.layers.copy_within(index..self.count - 1, index + 1);
+    self.layers[index] = ShadowLayer { distance, opacity };
+  }
+
+  /// The layers hit, nearest first.
+  fn layers(&self) -> &[ShadowLayer] {
+    &self.layers[..self.count]
+  }
+
+  /// The fraction of light that reaches the given distance.
+  fn transmittance(&self, distance: f32) -> f32 {
+    self
+      .layers()
+      .iter()
+      .take_while(|layer| layer.distance < distance)
+      .fold(1., |light, layer| light * (1. - layer.opacity))
+  }
+}
+
+/// How far occluders are from a light in each direction around it.
+#[derive(Clone, Debug)]
+pub struct ShadowMap {
+  origin: Vec2,
+  range: f32,
+  source_radius: f32,
+  texels: Vec<ShadowTexel>,
+}
+
+impl ShadowMap {
+  /// The number of directions in the map.
+  pub fn resolution(&self) -> usize {
+    self.texels.len()
+  }
+
+  /// The fraction of the light that reaches a point, from 0 in full shadow
+  /// to 1 in full light, with soft edges for lights with a size.
+  pub fn visibility(&self, point: Vec2) -> f32 {
+    let offset = point - self.origin;
+    let distance = offset.length();
+    let angle = offset.y.atan2(offset.x).rem_euclid(TAU);
+
+    if self.source_radius <= 0. || distance <= 0. {
+      return self.texel_at(angle).transmittance(distance);
+    }
+
+    // find the blockers that could cast a penumbra over the point
+    let search = (self.source_radius / distance).atan().min(MAX_PENUMBRA);
+    let (blocker_sum, blocker_count) = self
+      .taps(angle, search)
+      .filter_map(|texel| texel.layers().first().filter(|layer| layer.distance < distance))
+      .fold((0., 0), |(sum, count), layer| (sum + layer.distance, count + 1));
+
+    if blocker_count == 0 {
+      return 1.;
+    }
+
+    // the penumbra widens the further the point is behind its blockers
+    let blocker = (blocker_sum / blocker_count as f32).max(f32::EPSILON);
+    let width = (self.source_radius * (distance - blocker) / (blocker * distance)).min(MAX_PENUMBRA);
+
+    self
+      .taps(angle, width)
+      .map(|texel| texel.transmittance(distance))
+      .sum::<f32>()
+      / PENUMBRA_TAPS as f32
+  }
+
+  /// Packs the nearest occluder in each direction into a row of pixels, for
+  /// lighting shaders: red holds its distance over the light's range, and
+  /// alpha its opacity.
+  pub fn to_image(&self) -> Image<Color32> {
+    let mut image = Image::new(self.texels.len() as u32, 1);
+
+    for (index, texel) in self.texels.iter().enumerate() {
+      let pixel = match texel.layers().first() {
+        Some(layer) => Color32::rgba(
+          ((layer.distance / self.range).clamp(0., 1.) * 255.) as u8,
+          0,
+          0,
+          (layer.opacity * 255.) as u8,
+        ),
+        None => Color32::rgba(255, 0, 0, 0),
+      };
+
+      image.set_pixel(index as u32, 0, pixel);
+    }
+
+    image
+  }
+
+  /// The texel covering a direction, in radians.
+  fn texel_at(&self, angle: f32) -> &ShadowTexel {
+    let resolution = self.texels.len();
+    let index = (angle / TAU * resolution as f32) as usize;
+
+    &self.texels[index.min(resolution - 1)]
+  }
+
+  /// The texels sampled across a wedge either side of a direction.
+  fn taps(&self, angle: f32, half_width: f32) -> impl Iterator<Item = &ShadowTexel> + '_ {
+    (0..PENUMBRA_TAPS).map(move |tap| {
+      let offset = (tap as f32 / (PENUMBRA_TAPS - 1) as f32) * 2. - 1.;
+
+      self.texel_at((angle + offset * half_width).rem_euclid(TAU))
+    })
+  }
+}
+
+/// The distance along a ray to where it crosses a segment, if it does.
+fn intersect_ray(origin: Vec2, direction: Vec2, a: Vec2, b: Vec2) -> Option<f32> {
+  let edge = b - a;
+  let denominator = direction.perp_dot(edge);
+
+  if denominator.abs() < f32::EPSILON {
+    return None;
+  }
+
+  let to_start = a - origin;
+  let distance = to_start.perp_dot(edge) / denominator;
+  let along = to_start.perp_dot(direction) / denominator;
+
+  (distance >= 0. && (0. ..=1.).contains(&along)).then_some(distance)
+}
+
+/// Scales the color of a light, leaving its alpha alone.
+fn scale_color(color: Color, scale: f32) -> Color {
+  Color::rgba(color.r * scale, color.g * scale, color.b * scale, color.a)
+}
+
+#[cfg(test)]
+mod tests {
+  use common::vec2;
+
+  use super::*;
+
+  fn create_wall(opacity: f32) -> Occluder2D {
+    Occluder2D::line(vec2(2., -1.), vec2(2., 1.)).with_opacity(opacity)
+  }
+
+  #[test]
+  fn it_should_cast_hard_shadows_from_point_lights() {
+    let light = Light2D {
+      source_radius: 0.,
+      ..Default::default()
+    };
+
+    let shadows = light.shadow_map(&[create_wall(1.)], 512);
+
+    assert_eq!(shadows.visibility(vec2(1., 0.)), 1.);
+    assert_eq!(shadows.visibility(vec2(4., 0.)), 0.);
+    assert_eq!(shadows.visibility(vec2(4., 3.)), 1.);
+    assert_eq!(light.illuminate(vec2(4., 0.), &shadows).r, 0.);
+    assert!(light.illuminate(vec2(-4., 0.), &shadows).r > 0.);
+  }
+
+  #[test]
+  fn it_should_soften_shadow_edges_with_the_size_of_the_light() {
+    let light = Light2D {
+      source_radius: 1.,
+      ..Default::default()
+    };
+
+    let shadows = light.shadow_map(&[create_wall(1.)], 512);
+
+    // the wall's edge falls at y = 4 at x = 8; with a hard light this is
+    // a sharp step
+    let samples = (0..=8).map(|step| shadows.visibility(vec2(8., 2. + step as f32 * 0.5)));
+    let partial = samples
+      .filter(|visibility| *visibility > 0.05 && *visibility < 0.95)
+      .count();
+
+    assert!(
+      partial >= 2,
+      "expected a soft edge, got {partial} partially lit samples"
+    );
+    assert_eq!(shadows.visibility(vec2(8., 0.)), 0.);
+
+    // contact shadows right behind the wall stay crisp
+    assert!(shadows.visibility(vec2(2.2, 0.5)) < 0.05);
+  }
+
+  #[test]
+  fn it_should_let_light_through_translucent_occluders() {
+    let light = Light2D {
+      source_radius: 0.,
+      ..Default::default()
+    };
+
+    let glass = create_wall(0.5);
+    let curtain = Occluder2D::polygon(vec![vec2(4., -1.), vec2(5., -1.), vec2(5., 1.), vec2(4., 1.)]).with_opacity(0.5);
+    let shadows = light.shadow_map(&[glass, curtain], 256);
+
+    assert!((shadows.visibility(vec2(3., 0.)) - 0.5).abs() < 1e-5);
+    assert!((shadows.visibility(vec2(8., 0.)) - 0.25).abs() < 1e-5);
+
+    let image = shadows.to_image();
+
+    assert_eq!(image.get_pixel(0, 0).r, (2. / 10. * 255.) as u8);
+    assert_eq!(image.get_pixel(0, 0).a, 127);
+  }
+}
@@ -4,9 +4,12 @@
 //! pipeline state changes through to shader programs and uniforms.
 
 use common::ToVirtualPath;
+pub use descriptors::*;
 
 use super::*;
 
+mod descriptors;
+
 /// Blending states for materials.
 #[derive(Default, Copy, Clone, Debug, Eq, PartialEq)]
 pub enum BlendState {
@@ -43,6 +46,31 @@ pub enum CullingMode {
   Both,
 }
 
+/// Depth testing states for materials.
+#[derive(Default, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DepthState {
+  #[default]
+  Disabled,
+  Enabled {
+    comparison: DepthComparison,
+    write: bool,
+  },
+}
+
+/// Comparisons between a fragment's depth and the depth buffer.
+#[derive(Default, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DepthComparison {
+  Never,
+  #[default]
+  Less,
+  Equal,
+  LessOrEqual,
+  Greater,
+  NotEqual,
+  GreaterOrEqual,
+  Always,
+}
+
 /// Scissor modes for materials.
 #[derive(Default, Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ScissorMode {
@@ -64,7 +92,23 @@ pub struct Material {
   uniforms: ShaderUniformSet,
   blend_state: BlendState,
   culling_mode: CullingMode,
+  depth_state: DepthState,
   scissor_mode: ScissorMode,
+  // textures the material loaded itself, kept alive for as long as it is
+  textures: Vec<Texture>,
+}
+
+impl PartialEq for Material {
+  /// Materials are equal if they'd render identically, i.e. share a shader,
+  /// uniforms and render state.
+  fn eq(&self, other: &Self) -> bool {
+    self.shader.id() == other.shader.id()
+      && self.uniforms == other.uniforms
+      && self.blend_state == other.blend_state
+      && self.culling_mode == other.culling_mode
+      && self.depth_state == other.depth_state
+      && self.scissor_mode == other.scissor_mode
+  }
 }
 
 impl Material {
@@ -80,7 +124,9 @@ impl Material {
       uniforms: ShaderUniformSet::default(),
       blend_state: BlendState::Disabled,
       culling_mode: CullingMode::Disabled,
+      depth_state: DepthState::Disabled,
       scissor_mode: ScissorMode::Disabled,
+      textures: Vec::new(),
     }
   }
 
@@ -114,6 +160,16 @@ impl Material {
     self.culling_mode = mode;
   }
 
+  /// Gets the depth state of the material.
+  pub fn depth_state(&self) -> DepthState {
+    self.depth_state
+  }
+
+  /// Sets the depth state of the material.
+  pub fn set_depth_state(&mut self, state: DepthState) {
+    self.depth_state = state;
+  }
+
   /// Gets the scissor mode of the material.
   pub fn scissor_mode(&self) -> ScissorMode {
     self.scissor_mode
@@ -141,6 +197,14 @@ impl Material {
     self.uniforms.set_texture(key, texture, sampler);
   }
 
+  /// Sets the given [`UniformKey`] with all layers of a texture array.
+  pub fn set_texture_array<'a, K>(&'a mut self, key: K, textures: &'a TextureArray, sampler: Option<TextureSampler>)
+  where
+    K: Into<ShaderUniformKey<&'a TextureArray>>,
+  {
+    self.uniforms.set_texture_array(key, textures, sampler);
+  }
+
   /// Removes all uniforms from the material.
   pub fn clear_uniforms(&mut self) {
     self.uniforms.clear();
@@ -152,6 +216,7 @@ impl Material {
 
     graphics.set_blend_state(self.blend_state);
     graphics.set_culling_mode(self.culling_mode);
+    graphics.set_depth_state(self.depth_state);
     graphics.set_scissor_mode(self.scissor_mode);
 
     self.uniforms.apply_to_shader(&self.shader);
@@ -167,6 +232,7 @@ impl Material {
 
     graphics.set_blend_state(BlendState::Disabled);
     graphics.set_culling_mode(CullingMode::Disabled);
+    graphics.set_depth_state(DepthState::Disabled);
     graphics.set_scissor_mode(ScissorMode::Disabled);
   }
 }
@@ -0,0 +1,321 @@
+//! Data-driven materials, loaded from `.material` assets.
+//!
+//! A `.material` file is a RON document naming a shader along with the
+//! uniforms, textures and pipeline state to render it with:
+//!
+//! ```ron
+//! Material(
+//!   shader: "local://assets/shaders/water.glsl",
+//!   blend: Enabled(source: SourceAlpha, destination: OneMinusSourceAlpha),
+//!   cull: Back,
+//!   depth: Enabled(comparison: LessOrEqual, write: false),
+//!   uniforms: {
+//!     "u_speed": 0.5,
+//!     "u_tint": (0.2, 0.4, 1.0, 1.0),
+//!   },
+//!   textures: {
+//!     "u_texture": "local://assets/textures/water.png",
+//!   },
+//! )
+//! ```
+//!
+//! Every field but `shader` is optional. Uniforms may be booleans, numbers or
+//! vectors of 2 to 4 numbers.
+
+use common::{Chunk, Format, FromStream, FromVariant, InputStream, RonFormat, StreamError, Variant, VirtualPath};
+
+use super::*;
+
+/// Describes a [`Material`] as it's stored in a `.material` asset.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MaterialDescriptor {
+  pub shader: String,
+  pub blend_state: BlendState,
+  pub culling_mode: CullingMode,
+  pub depth_state: DepthState,
+  pub uniforms: Vec<(String, ShaderUniform)>,
+  pub textures: Vec<(String, String)>,
+}
+
+impl MaterialDescriptor {
+  /// Parses a descriptor from the given RON text.
+  pub fn parse(text: &str) -> Result<Self, MaterialError> {
+    let chunk = RonFormat::default().read_chunk(&mut std::io::Cursor::new(text.as_bytes()))?;
+
+    Self::from_chunk(&chunk)
+  }
+
+  /// Reads a descriptor from a [`Chunk`], as produced by [`RonFormat`].
+  pub fn from_chunk(chunk: &Chunk) -> Result<Self, MaterialError> {
+    let invalid = |field: &str| MaterialError::InvalidField(field.to_string());
+
+    let mut descriptor = Self {
+      shader: chunk.read_field("shader").map_err(|_| invalid("shader"))?,
+      ..Self::default()
+    };
+
+    if let Some(blend) = chunk.get("blend") {
+      descriptor.blend_state = read_blend_state(blend).ok_or_else(|| invalid("blend"))?;
+    }
+
+    if let Some(cull) = chunk.get("cull") {
+      descriptor.culling_mode = read_culling_mode(cull).ok_or_else(|| invalid("cull"))?;
+    }
+
+    if let Some(depth) = chunk.get("depth") {
+      descriptor.depth_state = read_depth_state(depth).ok_or_else(|| invalid("depth"))?;
+    }
+
+    for (name, value) in read_entries(chunk, "uniforms")? {
+      let uniform = read_uniform(value).ok_or_else(|| invalid(name))?;
+
+      descriptor.uniforms.push((name.to_string(), uniform));
+    }
+
+    for (name, value) in read_entries(chunk, "textures")? {
+      let path = value.read::<String>().map_err(|_| invalid(name))?;
+
+      descriptor.textures.push((name.to_string(), path));
+    }
+
+    Ok(descriptor)
+  }
+
+  /// The paths of the shader and textures this material refers to.
+  pub fn references(&self) -> impl Iterator<Item = &str> {
+    std::iter::once(self.shader.as_str()).chain(self.textures.iter().map(|(_, path)| path.as_str()))
+  }
+
+  /// Loads the shader and textures, and builds a [`Material`].
+  pub fn create(&self) -> Result<Material, MaterialError> {
+    let path = VirtualPath::new(&self.shader);
+    let mut material = if path.has_extension("shady") {
+      Material::from_shader_path::<Shady>(path)?
+    } else {
+      Material::from_shader_path::<GLSL>(path)?
+    };
+
+    self.apply(&mut material)?;
+
+    Ok(material)
+  }
+
+  /// Applies the uniforms, textures and pipeline state to an existing
+  /// [`Material`], loading any textures it refers to.
+  pub fn apply(&self, material: &mut Material) -> Result<(), MaterialError> {
+    material.set_blend_state(self.blend_state);
+    material.set_culling_mode(self.culling_mode);
+    material.set_depth_state(self.depth_state);
+
+    for (name, uniform) in &self.uniforms {
+      material.uniforms.set_uniform_by_name(name, uniform.clone());
+    }
+
+    for (name, path) in &self.textures {
+      let texture = Texture::from_path(path)?;
+
+      material.uniforms.set_texture_by_name(name, &texture, None);
+      material.textures.push(texture);
+    }
+
+    Ok(())
+  }
+}
+
+impl From<StreamError> for MaterialError {
+  fn from(error: StreamError) -> Self {
+    Self::InvalidData(error)
+  }
+}
+
+impl FromStream for Material {
+  type Error = MaterialError;
+
+  async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+    let chunk = RonFormat::default().read_chunk(stream)?;
+
+    MaterialDescriptor::from_chunk(&chunk)?.create()
+  }
+}
+
+/// Reads the entries of an optional `{ "name": value }` map field.
+fn read_entries<'a>(chunk: &'a Chunk, field: &str) -> Result<Vec<(&'a str, &'a Chunk)>, MaterialError> {
+  match chunk.get(field) {
+    None => Ok(Vec::new()),
+    Some(Chunk::Map(entries)) => {
+      let mut entries = entries
+        .iter()
+        .map(|(name, value)| (name.as_str(), value))
+        .collect::<Vec<_>>();
+
+      // keep texture slots stable between loads
+      entries.sort_by_key(|(name, _)| *name);
+
+      Ok(entries)
+    }
+    Some(_) => Err(MaterialError::InvalidField(field.to_string())),
+  }
+}
+
+/// Reads the name of a unit variant, or a struct variant's type name.
+fn read_variant_name(chunk: &Chunk) -> Option<&str> {
+  match chunk {
+    Chunk::Variant(Variant::String(name)) => Some(name),
+    _ => chunk.type_name(),
+  }
+}
+
+fn read_blend_state(chunk: &Chunk) -> Option<BlendState> {
+  match read_variant_name(chunk)? {
+    "Disabled" => Some(BlendState::Disabled),
+    "Enabled" => Some(BlendState::Enabled {
+      source: read_blend_factor(chunk.get("source")?)?,
+      destination: read_blend_factor(chunk.get("destination")?)?,
+    }),
+    _ => None,
+  }
+}
+
+fn read_blend_factor(chunk: &Chunk) -> Option<BlendFactor> {
+  match read_variant_name(chunk)? {
+    "One" => Some(BlendFactor::One),
+    "SourceAlpha" => Some(BlendFactor::SourceAlpha),
+    "SourceColor" => Some(BlendFactor::SourceColor),
+    "DestinationAlpha" => Some(BlendFactor::DestinationAlpha),
+    "DestinationColor" => Some(BlendFactor::DestinationColor),
+    "OneMinusSourceAlpha" => Some(BlendFactor::OneMinusSourceAlpha),
+    "OneMinusSourceColor" => Some(BlendFactor::OneMinusSourceColor),
+    "OneMinusDestinationAlpha" => Some(BlendFactor::OneMinusDestinationAlpha),
+    "OneMinusDestinationColor" => Some(BlendFactor::OneMinusDestinationColor),
+    _ => None,
+  }
+}
+
+fn read_culling_mode(chunk: &Chunk) -> Option<CullingMode> {
+  match read_variant_name(chunk)? {
+    "Disabled" => Some(CullingMode::Disabled),
+    "Front" => Some(CullingMode::Front),
+    "Back" => Some(CullingMode::Back),
+    "Both" => Some(CullingMode::Both),
+    _ => None,
+  }
+}
+
+fn read_depth_state(chunk: &Chunk) -> Option<DepthState> {
+  match read_variant_name(chunk)? {
+    "Disabled" => Some(DepthState::Disabled),
+    "Enabled" => Some(DepthState::Enabled {
+      comparison: match chunk.get("comparison") {
+        Some(comparison) => read_depth_comparison(comparison)?,
+        None => DepthComparison::default(),
+      },
+      write: chunk.read_field_or("write", true).ok()?,
+    }),
+    _ => None,
+  }
+}
+
+fn read_depth_comparison(chunk: &Chunk) -> Option<DepthComparison> {
+  match read_variant_name(chunk)? {
+    "Never" => Some(DepthComparison::Never),
+    "Less" => Some(DepthComparison::Less),
+    "Equal" => Some(DepthComparison::Equal),
+    "LessOrEqual" => Some(DepthComparison::LessOrEqual),
+    "Greater" => Some(DepthComparison::Greater),
+    "NotEqual" => Some(DepthComparison::NotEqual),
+    "GreaterOrEqual" => Some(DepthComparison::GreaterOrEqual),
+    "Always" => Some(DepthComparison::Always),
+    _ => None,
+  }
+}
+
+fn read_uniform(chunk: &Chunk) -> Option<ShaderUniform> {
+  match chunk {
+    Chunk::Variant(Variant::Bool(value)) => Some(ShaderUniform::Bool(*value)),
+    Chunk::Variant(Variant::I64(value)) => Some(ShaderUniform::I32(i32::try_from(*value).ok()?)),
+    Chunk::Variant(variant) => f32::from_variant(variant.clone()).ok().map(ShaderUniform::F32),
+    Chunk::Sequence(values) => {
+      let values = values
+        .iter()
+        .map(|value| value.read::<f32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+      match values[..] {
+        [x, y] => Some(ShaderUniform::Vec2(common::vec2(x, y))),
+        [x, y, z] => Some(ShaderUniform::Vec3(common::vec3(x, y, z))),
+        [x, y, z, w] => Some(ShaderUniform::Vec4(common::vec4(x, y, z, w))),
+        _ => None,
+      }
+    }
+    Chunk::Map(_) => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const WATER: &str = r#"
+    Material(
+      shader: "local://assets/shaders/water.glsl",
+      blend: Enabled(source: SourceAlpha, destination: OneMinusSourceAlpha),
+      cull: Back,
+      depth: Enabled(comparison: LessOrEqual, write: false),
+      uniforms: {
+        "u_speed": 0.5,
+        "u_octaves": 3,
+        "u_tint": (0.2, 0.4, 1.0, 1.0),
+      },
+      textures: {
+        "u_texture": "local://assets/textures/water.png",
+      },
+    )
+  "#;
+
+  #[test]
+  fn it_should_parse_material_descriptors() {
+    let descriptor = MaterialDescriptor::parse(WATER).unwrap();
+
+    assert_eq!(descriptor.shader, "local://assets/shaders/water.glsl");
+    assert_eq!(descriptor.culling_mode, CullingMode::Back);
+    assert_eq!(descriptor.depth_state, DepthState::Enabled {
+      comparison: DepthComparison::LessOrEqual,
+      write: false,
+    });
+    assert_eq!(descriptor.uniforms, vec![
+      ("u_octaves".to_string(), ShaderUniform::I32(3)),
+      ("u_speed".to_string(), ShaderUniform::F32(0.5)),
+      (
+        "u_tint".to_string(),
+        ShaderUniform::Vec4(common::vec4(0.2, 0.4, 1.0, 1.0))
+      ),
+    ]);
+    assert_eq!(descriptor.references().count(), 2);
+  }
+
+  #[test]
+  fn it_should_reject_invalid_fields() {
+    let error = MaterialDescriptor::parse(r#"Material(shader: "a.glsl", cull: Sideways)"#).unwrap_err();
+
+    assert!(matches!(error, MaterialError::InvalidField(field) if field == "cull"));
+    assert!(MaterialDescriptor::parse("Material(cull: Back)").is_err());
+  }
+
+  #[test]
+  fn it_should_apply_descriptors_to_materials() {
+    let mut material = SHADER_SPRITE_STANDARD.to_material().unwrap();
+    let descriptor = MaterialDescriptor {
+      blend_state: BlendState::Enabled {
+        source: BlendFactor::One,
+        destination: BlendFactor::One,
+      },
+      uniforms: vec![("u_intensity".to_string(), ShaderUniform::F32(2.))],
+      ..MaterialDescriptor::default()
+    };
+
+    descriptor.apply(&mut material).unwrap();
+
+    assert_eq!(material.blend_state(), descriptor.blend_state);
+    assert_eq!(material.depth_state(), DepthState::Disabled);
+  }
+}
@@ -3,10 +3,15 @@
 //! Meshes abstract over vertex and index data on the GPU as well, and
 //! provide utilities for constructing data from pieces.
 
-use common::{vec2, Color32, Size, Vec2, Vec3};
+pub use batching::*;
+use common::{vec2, Bounds, Color32, Size, Vec2, Vec3};
+pub use skinning::*;
 
 use super::*;
 
+mod batching;
+mod skinning;
+
 /// Represents the different topologies supported for a mesh.
 #[derive(Default, Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum PrimitiveTopology {
@@ -20,8 +25,34 @@ pub enum PrimitiveTopology {
 ///
 /// Vertices provide a set of [`VertexDescriptor`]s which are used for binding
 /// vertex data to a mesh.
-pub trait Vertex: Clone {
+pub trait Vertex: Clone + Send + Sync + 'static {
   const DESCRIPTORS: &'static [VertexDescriptor];
+
+  /// The position of the vertex, for computing [`Bounds`].
+  ///
+  /// Derived for vertices with a `position` field.
+  fn position(&self) -> Option<Vec3> {
+    None
+  }
+}
+
+/// A vertex position that can be widened into 3-space.
+pub trait VertexPosition {
+  fn to_position(&self) -> Vec3;
+}
+
+impl VertexPosition for Vec2 {
+  #[inline]
+  fn to_position(&self) -> Vec3 {
+    self.extend(0.)
+  }
+}
+
+impl VertexPosition for Vec3 {
+  #[inline]
+  fn to_position(&self) -> Vec3 {
+    *self
+  }
 }
 
 /// An index into a mesh buffer.
@@ -129,6 +160,7 @@ struct MeshState<V> {
   id: MeshId,
   vertices: Buffer<V>,
   indices: Buffer<MeshIndex>,
+  bounds: Option<Bounds>,
 }
 
 impl<V> MeshState<V> {
@@ -145,10 +177,11 @@ impl<V: Vertex> Mesh<V> {
     let indices = Buffer::new(BufferKind::Index, usage).map_err(|_| MeshError::FailedToCreate)?;
 
     Ok(Self {
-      state: internal::GraphicsCell::new(MeshState {
+      state: internal::GraphicsCell::restorable(MeshState {
         id: graphics().mesh_create(vertices.id(), indices.id(), V::DESCRIPTORS)?,
         vertices,
         indices,
+        bounds: None,
       }),
     })
   }
@@ -191,6 +224,19 @@ impl<V: Vertex> Mesh<V> {
     self.state.read().indices.len()
   }
 
+  /// The bounds of the mesh's vertices, in model space.
+  ///
+  /// Computed when uploaded from a [`MeshBuilder`]; meshes written through
+  /// [`Mesh::with_buffers`] should call [`Mesh::set_bounds`] themselves.
+  pub fn bounds(&self) -> Option<Bounds> {
+    self.state.read().bounds.clone()
+  }
+
+  /// Replaces the cached bounds of the mesh.
+  pub fn set_bounds(&mut self, bounds: Option<Bounds>) {
+    self.state.write().bounds = bounds;
+  }
+
   /// Draws this mesh with the given material and topology.
   pub fn draw(&self, material: &Material, topology: PrimitiveTopology) {
     let state = self.state.read();
@@ -220,9 +266,21 @@ impl<V: Vertex> Mesh<V> {
   }
 }
 
+impl<V: Vertex> Restore for MeshState<V> {
+  const ORDER: RestoreOrder = RestoreOrder::Mesh;
+
+  fn restore(&mut self) -> Result<(), GraphicsError> {
+    self.id = graphics().mesh_create(self.vertices.id(), self.indices.id(), V::DESCRIPTORS)?;
+
+    Ok(())
+  }
+}
+
 impl<V> Drop for MeshState<V> {
   fn drop(&mut self) {
-    graphics().mesh_delete(self.id).expect("Failed to delete mesh");
+    let id = self.id;
+
+    GraphicsServer::defer(move |graphics| graphics.mesh_delete(id).expect("Failed to delete mesh"));
   }
 }
 
@@ -252,6 +310,16 @@ impl<V: Vertex> MeshBuilder<V> {
     self.indices.len()
   }
 
+  /// Returns the vertices added so far.
+  pub fn vertices(&self) -> &[V] {
+    &self.vertices
+  }
+
+  /// Returns the indices added so far.
+  pub fn indices(&self) -> &[MeshIndex] {
+    &self.indices
+  }
+
   /// Adds a single vertex to the mesh.
   pub fn add_vertex(&mut self, vertex: V) {
     self.vertices.push(vertex);
@@ -323,12 +391,19 @@ impl<V: Vertex> MeshBuilder<V> {
     self.add_index(offset + 3);
   }
 
+  /// The bounds of the vertices added so far, if they have positions.
+  pub fn bounds(&self) -> Option<Bounds> {
+    Bounds::from_points(self.vertices.iter().filter_map(Vertex::position))
+  }
+
   /// Uploads the contents of the [`MeshBuilder`] to the given [`Mesh`].
   pub fn upload_to(&self, mesh: &mut Mesh<V>) {
     mesh.with_buffers(|vertices, indices| {
       vertices.write_data(&self.vertices);
       indices.write_data(&self.indices);
     });
+
+    mesh.set_bounds(self.bounds());
   }
 
   /// Builds a new [`Mesh`] and returns it.
@@ -538,4 +613,22 @@ mod tests {
 
     assert_eq!(descriptors.len(), 3);
   }
+
+  #[test]
+  fn mesh_builder_should_compute_bounds_from_positions() {
+    let mut builder = MeshBuilder::<Vertex2>::new();
+
+    assert!(builder.bounds().is_none());
+
+    builder.add_triangle(&[
+      Vertex2::new([-1., 0.], [0., 0.], Color32::WHITE),
+      Vertex2::new([1., 0.], [1., 0.], Color32::WHITE),
+      Vertex2::new([0., 2.], [0.5, 1.], Color32::WHITE),
+    ]);
+
+    let bounds = builder.bounds().unwrap();
+
+    assert_eq!(bounds.aabb.min, Vec3::new(-1., 0., 0.));
+    assert_eq!(bounds.aabb.max, Vec3::new(1., 2., 0.));
+  }
 }
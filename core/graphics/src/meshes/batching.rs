@@ -0,0 +1,205 @@
+//! Static batching of meshes that share a material.
+//!
+//! Levels built from CSG brushes or voxel chunks are made of many small meshes
+//! that never move. Drawing each one separately costs a draw call apiece; a
+//! [`StaticBatcher`] instead bakes their world transforms into the vertices
+//! and merges everything that shares a [`Material`] into a single mesh, once,
+//! at load time.
+
+use common::Mat4;
+
+use super::*;
+
+/// The default maximum number of vertices in a single batch.
+const DEFAULT_MAX_VERTICES: usize = 65536;
+
+/// A vertex that can have a world transform baked into it.
+pub trait TransformableVertex: Vertex {
+  /// Returns this vertex transformed by the given matrix.
+  fn transform(&self, matrix: &Mat4) -> Self;
+}
+
+impl TransformableVertex for Vertex2 {
+  fn transform(&self, matrix: &Mat4) -> Self {
+    Self {
+      position: matrix.transform_point3(self.position.extend(0.)).truncate(),
+      ..self.clone()
+    }
+  }
+}
+
+impl TransformableVertex for Vertex3 {
+  fn transform(&self, matrix: &Mat4) -> Self {
+    Self {
+      position: matrix.transform_point3(self.position),
+      ..self.clone()
+    }
+  }
+}
+
+/// Merges static meshes that share a material into combined meshes.
+pub struct StaticBatcher<V> {
+  batches: Vec<(Material, MeshBuilder<V>)>,
+  max_vertices: usize,
+}
+
+impl<V: TransformableVertex> Default for StaticBatcher<V> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<V: TransformableVertex> StaticBatcher<V> {
+  /// Creates a new, empty batcher.
+  pub fn new() -> Self {
+    Self {
+      batches: Vec::new(),
+      max_vertices: DEFAULT_MAX_VERTICES,
+    }
+  }
+
+  /// Limits the number of vertices in each batch; meshes that would overflow
+  /// a batch start a new one.
+  pub fn with_max_vertices(mut self, max_vertices: usize) -> Self {
+    self.max_vertices = max_vertices;
+    self
+  }
+
+  /// The batches built so far, as a material and the geometry drawn with it.
+  pub fn batches(&self) -> &[(Material, MeshBuilder<V>)] {
+    &self.batches
+  }
+
+  /// Adds a mesh to the batch for its material, baking in its transform.
+  pub fn add(&mut self, material: &Material, mesh: &MeshBuilder<V>, transform: Mat4) {
+    let vertex_count = mesh.vertices().len();
+    let max_vertices = self.max_vertices;
+
+    let batch = self
+      .batches
+      .iter_mut()
+      .rev()
+      .find(|(it, builder)| it == material && builder.vertices().len() + vertex_count <= max_vertices);
+
+    let builder = match batch {
+      Some((_, builder)) => builder,
+      None => {
+        self.batches.push((material.clone(), MeshBuilder::new()));
+        &mut self.batches.last_mut().unwrap().1
+      }
+    };
+
+    let offset = builder.vertex_count();
+
+    for vertex in mesh.vertices() {
+      builder.add_vertex(vertex.transform(&transform));
+    }
+
+    for index in mesh.indices() {
+      builder.add_index(offset + index);
+    }
+  }
+
+  /// Uploads each batch to the GPU.
+  pub fn build(&self) -> Vec<StaticBatch<V>> {
+    self
+      .batches
+      .iter()
+      .map(|(material, builder)| StaticBatch {
+        material: material.clone(),
+        mesh: builder.to_mesh(),
+      })
+      .collect()
+  }
+}
+
+/// A combined mesh, drawn with a single material.
+pub struct StaticBatch<V> {
+  pub material: Material,
+  pub mesh: Mesh<V>,
+}
+
+impl<V: Vertex> StaticBatch<V> {
+  /// Draws the batch in a single draw call.
+  pub fn draw(&self, queue: &mut RenderQueue, topology: PrimitiveTopology) {
+    queue.set_material(&self.material);
+    queue.draw_mesh(&self.mesh, topology);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{vec3, Color32};
+
+  use super::*;
+
+  fn create_triangle() -> MeshBuilder<Vertex3> {
+    let mut builder = MeshBuilder::new();
+
+    builder.add_triangle(&[
+      Vertex3::new(vec3(0., 0., 0.), Vec2::ZERO, Color32::WHITE),
+      Vertex3::new(vec3(1., 0., 0.), Vec2::ZERO, Color32::WHITE),
+      Vertex3::new(vec3(0., 1., 0.), Vec2::ZERO, Color32::WHITE),
+    ]);
+
+    builder
+  }
+
+  fn create_material() -> Material {
+    Material::from_shader_program(&ShaderProgram::new().unwrap())
+  }
+
+  #[test]
+  fn it_should_merge_meshes_sharing_a_material() {
+    let mut batcher = StaticBatcher::new();
+    let material = create_material();
+    let triangle = create_triangle();
+
+    batcher.add(&material, &triangle, Mat4::IDENTITY);
+    batcher.add(&material, &triangle, Mat4::from_translation(vec3(10., 0., 0.)));
+
+    let batches = batcher.batches();
+
+    assert_eq!(batches.len(), 1);
+
+    let builder = &batches[0].1;
+
+    assert_eq!(builder.indices(), &[0, 1, 2, 3, 4, 5]);
+    assert_eq!(builder.vertices()[4].position, vec3(11., 0., 0.));
+  }
+
+  #[test]
+  fn it_should_split_batches_by_material() {
+    let mut batcher = StaticBatcher::new();
+    let opaque = create_material();
+    let mut transparent = opaque.clone();
+    let triangle = create_triangle();
+
+    transparent.set_blend_state(BlendState::Enabled {
+      source: BlendFactor::SourceAlpha,
+      destination: BlendFactor::OneMinusSourceAlpha,
+    });
+
+    batcher.add(&opaque, &triangle, Mat4::IDENTITY);
+    batcher.add(&transparent, &triangle, Mat4::IDENTITY);
+    batcher.add(&opaque, &triangle, Mat4::IDENTITY);
+
+    assert_eq!(batcher.batches().len(), 2);
+    assert_eq!(batcher.build().len(), 2);
+  }
+
+  #[test]
+  fn it_should_start_a_new_batch_past_the_vertex_limit() {
+    let mut batcher = StaticBatcher::new().with_max_vertices(4);
+    let material = create_material();
+    let triangle = create_triangle();
+
+    batcher.add(&material, &triangle, Mat4::IDENTITY);
+    batcher.add(&material, &triangle, Mat4::IDENTITY);
+
+    let batches = batcher.batches();
+
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[1].1.indices(), &[0, 1, 2]);
+  }
+}
@@ -0,0 +1,263 @@
+//! Skinned meshes, deformed by the bones of a skeleton.
+//!
+//! Skinning normally happens in the vertex shader, which means every pass that
+//! draws the mesh (shadows, depth pre-pass, the main pass) pays for it again.
+//! Where compute shaders are available, a [`SkinnedMesh`] can instead skin its
+//! vertices once per frame into a regular vertex buffer that every pass draws.
+
+use common::{Mat4, UVec4, Vec4};
+
+use super::*;
+
+/// The number of threads in each compute skinning work group.
+const WORK_GROUP_SIZE: u32 = 64;
+
+/// A vertex influenced by up to four bones.
+///
+/// The layout is shared with the compute shader, so it must stay tightly
+/// packed.
+#[repr(C)]
+#[derive(Clone, Debug, Vertex)]
+pub struct SkinnedVertex {
+  #[vertex(3, F32)]
+  pub position: Vec3,
+  #[vertex(3, F32)]
+  pub normal: Vec3,
+  #[vertex(2, F32)]
+  pub uv: Vec2,
+  #[vertex(4, U32)]
+  pub bone_indices: UVec4,
+  #[vertex(4, F32)]
+  pub bone_weights: Vec4,
+}
+
+/// A vertex after skinning, as written by the compute pre-pass.
+#[repr(C)]
+#[derive(Clone, Debug, Vertex)]
+pub struct DeformedVertex {
+  #[vertex(3, F32)]
+  pub position: Vec3,
+  #[vertex(3, F32)]
+  pub normal: Vec3,
+  #[vertex(2, F32)]
+  pub uv: Vec2,
+}
+
+/// How a [`SkinnedMesh`] is deformed by its bones.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SkinningMode {
+  /// Skins in the vertex shader, on every pass that draws the mesh.
+  #[default]
+  VertexShader,
+  /// Skins once per frame in a compute pre-pass; passes draw the result as a
+  /// regular mesh of [`DeformedVertex`]s.
+  ComputePrePass,
+}
+
+/// A mesh deformed by a set of bone matrices.
+pub struct SkinnedMesh {
+  bind_pose: Mesh<SkinnedVertex>,
+  compute: Option<ComputeSkinning>,
+  bone_matrices: Vec<Mat4>,
+  is_dirty: bool,
+}
+
+/// The resources for the compute skinning pre-pass.
+struct ComputeSkinning {
+  shader: ShaderProgram,
+  bones: Buffer<Mat4>,
+  skinned: Mesh<DeformedVertex>,
+}
+
+impl ComputeSkinning {
+  /// Creates the output mesh, starting in the bind pose.
+  fn new(vertices: &[SkinnedVertex], indices: &[MeshIndex]) -> Result<Self, GraphicsError> {
+    let shader = SHADER_MESH_SKINNED_COMPUTE.to_program()?;
+    let bones = Buffer::new(BufferKind::Element, BufferUsage::Dynamic)?;
+    let mut skinned = Mesh::new(BufferUsage::Dynamic)?;
+
+    let deformed = vertices
+      .iter()
+      .map(|vertex| DeformedVertex {
+        position: vertex.position,
+        normal: vertex.normal,
+        uv: vertex.uv,
+      })
+      .collect::<Vec<_>>();
+
+    skinned.with_buffers(|vertices, buffer_indices| {
+      vertices.write_data(&deformed);
+      buffer_indices.write_data(indices);
+    });
+
+    Ok(Self { shader, bones, skinned })
+  }
+}
+
+impl SkinnedMesh {
+  /// Creates a new skinned mesh in its bind pose.
+  ///
+  /// If the compute pre-pass is requested but can't be created (e.g. the
+  /// driver doesn't support compute shaders), the mesh falls back to skinning
+  /// in the vertex shader; check [`SkinnedMesh::mode`] to pick a material.
+  pub fn new(vertices: &[SkinnedVertex], indices: &[MeshIndex], mode: SkinningMode) -> Result<Self, MeshError> {
+    let mut bind_pose = Mesh::new(BufferUsage::Static)?;
+
+    bind_pose.with_buffers(|buffer_vertices, buffer_indices| {
+      buffer_vertices.write_data(vertices);
+      buffer_indices.write_data(indices);
+    });
+
+    let compute = match mode {
+      SkinningMode::ComputePrePass => ComputeSkinning::new(vertices, indices).ok(),
+      SkinningMode::VertexShader => None,
+    };
+
+    Ok(Self {
+      bind_pose,
+      compute,
+      bone_matrices: Vec::new(),
+      is_dirty: false,
+    })
+  }
+
+  /// The way this mesh is actually being skinned.
+  pub fn mode(&self) -> SkinningMode {
+    match self.compute {
+      Some(_) => SkinningMode::ComputePrePass,
+      None => SkinningMode::VertexShader,
+    }
+  }
+
+  /// The current bone matrices.
+  pub fn bone_matrices(&self) -> &[Mat4] {
+    &self.bone_matrices
+  }
+
+  /// Poses the mesh with the given bone matrices, e.g. from a sampled clip.
+  pub fn set_pose(&mut self, bone_matrices: &[Mat4]) {
+    self.bone_matrices.clear();
+    self.bone_matrices.extend_from_slice(bone_matrices);
+    self.is_dirty = true;
+  }
+
+  /// Runs the compute pre-pass, if the pose has changed since the last one.
+  ///
+  /// Call this once per frame, before any pass draws the mesh. Returns true
+  /// if a pre-pass was enqueued.
+  pub fn prepare(&mut self, queue: &mut RenderQueue) -> bool {
+    let Some(compute) = &mut self.compute else {
+      return false;
+    };
+
+    if !self.is_dirty || self.bone_matrices.is_empty() {
+      return false;
+    }
+
+    compute.bones.write_data(&self.bone_matrices);
+
+    self
+      .bind_pose
+      .with_buffers(|vertices, _| queue.bind_storage_buffer(vertices, 0));
+    queue.bind_storage_buffer(&compute.bones, 1);
+    compute
+      .skinned
+      .with_buffers(|vertices, _| queue.bind_storage_buffer(vertices, 2));
+
+    let vertex_count = self.bind_pose.vertices() as u32;
+
+    queue.dispatch_compute(&compute.shader, (vertex_count.div_ceil(WORK_GROUP_SIZE), 1, 1));
+    queue.memory_barrier(MemoryBarrier::VertexAttributes);
+
+    self.is_dirty = false;
+
+    true
+  }
+
+  /// Draws the mesh in its current pose.
+  ///
+  /// With the compute pre-pass, the material draws [`DeformedVertex`]s;
+  /// otherwise it skins [`SkinnedVertex`]s itself (e.g.
+  /// [`SHADER_MESH_SKINNED`]), and the bone matrices are uploaded for it.
+  pub fn draw(&self, queue: &mut RenderQueue, material: &Material, topology: PrimitiveTopology) {
+    queue.set_material(material);
+
+    match &self.compute {
+      Some(compute) => queue.draw_mesh(&compute.skinned, topology),
+      None => {
+        for (index, matrix) in self.bone_matrices.iter().enumerate() {
+          queue.set_uniform(material.shader(), &format!("u_bone_matrices[{index}]"), matrix);
+        }
+
+        queue.draw_mesh(&self.bind_pose, topology);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{uvec4, vec4, Color};
+
+  use super::*;
+
+  fn create_vertices() -> Vec<SkinnedVertex> {
+    (0..3)
+      .map(|index| SkinnedVertex {
+        position: Vec3::new(index as f32, 0., 0.),
+        normal: Vec3::Z,
+        uv: Vec2::ZERO,
+        bone_indices: uvec4(0, 1, 0, 0),
+        bone_weights: vec4(0.5, 0.5, 0., 0.),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn it_should_keep_vertex_layouts_packed_for_compute() {
+    let size = |descriptors: &[VertexDescriptor]| descriptors.iter().map(|it| it.size().as_bytes()).sum::<usize>();
+
+    assert_eq!(size_of::<SkinnedVertex>(), 64);
+    assert_eq!(size_of::<DeformedVertex>(), 32);
+    assert_eq!(size(SkinnedVertex::DESCRIPTORS), size_of::<SkinnedVertex>());
+    assert_eq!(size(DeformedVertex::DESCRIPTORS), size_of::<DeformedVertex>());
+  }
+
+  #[test]
+  fn it_should_skin_once_per_pose_for_many_passes() {
+    let mut queue = RenderQueue::new();
+    let mut mesh = SkinnedMesh::new(&create_vertices(), &[0, 1, 2], SkinningMode::ComputePrePass).unwrap();
+    let material = Material::from_shader_program(&ShaderProgram::new().unwrap());
+
+    assert_eq!(mesh.mode(), SkinningMode::ComputePrePass);
+
+    mesh.set_pose(&[Mat4::IDENTITY, Mat4::from_translation(Vec3::Y)]);
+
+    assert!(mesh.prepare(&mut queue));
+
+    // shadow and main passes share the skinned vertices
+    for _ in 0..2 {
+      assert!(!mesh.prepare(&mut queue));
+
+      queue.clear_color_buffer(Color::BLACK);
+      mesh.draw(&mut queue, &material, PrimitiveTopology::Triangles);
+    }
+
+    queue.flush().unwrap();
+  }
+
+  #[test]
+  fn it_should_skin_in_the_vertex_shader_without_compute() {
+    let mut queue = RenderQueue::new();
+    let mut mesh = SkinnedMesh::new(&create_vertices(), &[0, 1, 2], SkinningMode::VertexShader).unwrap();
+    let material = SHADER_MESH_SKINNED.to_material().unwrap();
+
+    mesh.set_pose(&[Mat4::IDENTITY, Mat4::IDENTITY]);
+
+    assert_eq!(mesh.mode(), SkinningMode::VertexShader);
+    assert!(!mesh.prepare(&mut queue));
+
+    mesh.draw(&mut queue, &material, PrimitiveTopology::Triangles);
+    queue.flush().unwrap();
+  }
+}
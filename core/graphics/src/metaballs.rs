@@ -0,0 +1,291 @@
+//! Screen-space metaballs, for liquid-looking 2D fluids.
+//!
+//! Each [`Metaball`] is drawn as a soft blob into an offscreen target, where
+//! overlapping blobs add up into a smooth field. The field is then drawn over
+//! the view with a threshold shader, so blobs that are close together merge
+//! into a single body of fluid with a rim of light around its edge.
+
+use common::{vec2, Color, Color32, Mat4, Rectangle, Vec2};
+
+use super::*;
+
+/// The size, in pixels, of the blob texture accumulated for each metaball.
+const BLOB_SIZE: u32 = 64;
+
+/// A single blob of fluid, e.g. one per particle.
+#[derive(Copy, Clone, Debug)]
+pub struct Metaball {
+  pub position: Vec2,
+  pub radius: f32,
+}
+
+/// The look of a [`MetaballRenderer`]'s fluid.
+#[derive(Clone, Debug)]
+pub struct MetaballSettings {
+  /// How strong the field must be to count as fluid, from 0 to 1.
+  pub threshold: f32,
+  /// The color of the body of the fluid.
+  pub color: Color,
+  /// The color of the rim of light around the edge of the fluid.
+  pub rim_color: Color,
+  /// How far into the field the rim extends past the threshold.
+  pub rim_width: f32,
+  /// The resolution of the offscreen target, relative to the view.
+  pub resolution_scale: f32,
+}
+
+impl Default for MetaballSettings {
+  fn default() -> Self {
+    Self {
+      threshold: 0.5,
+      color: Color::rgba(0.2, 0.45, 0.9, 0.85),
+      rim_color: Color::rgb(0.75, 0.9, 1.),
+      rim_width: 0.1,
+      resolution_scale: 0.5,
+    }
+  }
+}
+
+impl MetaballSettings {
+  /// A shader uniform key for the field strength that counts as fluid.
+  pub const THRESHOLD: ShaderUniformKey<f32> = ShaderUniformKey::new("u_threshold");
+  /// A shader uniform key for the width of the rim.
+  pub const RIM_WIDTH: ShaderUniformKey<f32> = ShaderUniformKey::new("u_rim_width");
+  /// A shader uniform key for the color of the fluid.
+  pub const FLUID_COLOR: ShaderUniformKey<Color> = ShaderUniformKey::new("u_fluid_color");
+  /// A shader uniform key for the color of the rim.
+  pub const RIM_COLOR: ShaderUniformKey<Color> = ShaderUniformKey::new("u_rim_color");
+
+  /// Sets the fluid parameters on a threshold material.
+  pub fn apply(&self, material: &mut Material) {
+    material.set_uniform(Self::THRESHOLD, self.threshold);
+    material.set_uniform(Self::RIM_WIDTH, self.rim_width);
+    material.set_uniform(Self::FLUID_COLOR, self.color);
+    material.set_uniform(Self::RIM_COLOR, self.rim_color);
+  }
+}
+
+/// Renders metaballs as a 2D fluid.
+pub struct MetaballRenderer {
+  settings: MetaballSettings,
+  target: RenderTarget,
+  blob: Texture,
+  accumulate: Material,
+  threshold: Material,
+}
+
+impl MetaballRenderer {
+  /// Creates a renderer for a view of the given size, in pixels.
+  pub fn new(width: u32, height: u32, settings: MetaballSettings) -> Result<Self, GraphicsError> {
+    let target = create_target(width, height, settings.resolution_scale)?;
+    let blob = Texture::from_image(&create_blob_image(BLOB_SIZE))?;
+
+    // blobs add up into the field
+    let mut accumulate = SHADER_SPRITE_STANDARD.to_material()?;
+
+    accumulate.set_blend_state(BlendState::Enabled {
+      source: BlendFactor::One,
+      destination: BlendFactor::One,
+    });
+
+    let mut threshold = SHADER_METABALL_THRESHOLD.to_material()?;
+
+    threshold.set_blend_state(BlendState::Enabled {
+      source: BlendFactor::SourceAlpha,
+      destination: BlendFactor::OneMinusSourceAlpha,
+    });
+
+    settings.apply(&mut threshold);
+
+    Ok(Self {
+      settings,
+      target,
+      blob,
+      accumulate,
+      threshold,
+    })
+  }
+
+  /// The settings for the look of the fluid.
+  pub fn settings(&self) -> &MetaballSettings {
+    &self.settings
+  }
+
+  /// Changes the look of the fluid.
+  pub fn set_settings(&mut self, settings: MetaballSettings) {
+    settings.apply(&mut self.threshold);
+
+    self.settings = settings;
+  }
+
+  /// The offscreen target the field is accumulated into.
+  pub fn target(&self) -> &RenderTarget {
+    &self.target
+  }
+
+  /// Resizes the offscreen target, e.g. when the window changes size.
+  pub fn resize(&mut self, width: u32, height: u32) -> Result<(), GraphicsError> {
+    self.target = create_target(width, height, self.settings.resolution_scale)?;
+
+    Ok(())
+  }
+
+  /// Renders the metaballs within the view to the active target.
+  pub fn render(
+    &mut self,
+    batch: &mut SpriteBatch,
+    view: Rectangle,
+    projection_view: &Mat4,
+    metaballs: impl IntoIterator<Item = Metaball>,
+  ) {
+    // accumulate the field offscreen
+    self.accumulate.set_uniform(PROJECTION_VIEW, projection_view);
+    self.target.activate();
+
+    graphics().clear_color_buffer(Color::CLEAR);
+
+    batch.begin(&self.accumulate);
+
+    for metaball in metaballs {
+      batch.draw_sprite(&self.blob, &SpriteOptions {
+        position: metaball.position,
+        scale: Vec2::splat(metaball.radius * 2. / BLOB_SIZE as f32),
+        ..Default::default()
+      });
+    }
+
+    batch.flush();
+
+    self.target.deactivate();
+
+    // then threshold it over the view
+    let field = self.target.color_attachment();
+    let size = vec2(field.width() as f32, field.height() as f32);
+
+    self.threshold.set_uniform(PROJECTION_VIEW, projection_view);
+
+    batch.begin(&self.threshold);
+    batch.draw_sprite(&field, &SpriteOptions {
+      position: view.center(),
+      scale: view.size() / size,
+      ..Default::default()
+    });
+    batch.flush();
+  }
+}
+
+/// Samples the field of the given metaballs at a point, as the renderer would
+/// accumulate it.
+///
+/// Useful for gameplay that needs to know where the fluid is, e.g. for
+/// splashes or buoyancy.
+pub fn metaball_field(metaballs: &[Metaball], point: Vec2) -> f32 {
+  metaballs
+    .iter()
+    .map(|metaball| blob_falloff(point.distance(metaball.position) / metaball.radius))
+    .sum::<f32>()
+    .min(1.)
+}
+
+/// The strength of a blob at a distance from its center, relative to its
+/// radius.
+fn blob_falloff(distance: f32) -> f32 {
+  if distance >= 1. {
+    return 0.;
+  }
+
+  let falloff = 1. - distance * distance;
+
+  falloff * falloff
+}
+
+/// Creates the offscreen target for the field.
+fn create_target(width: u32, height: u32, scale: f32) -> Result<RenderTarget, TargetError> {
+  let width = ((width as f32 * scale) as u32).max(1);
+  let height = ((height as f32 * scale) as u32).max(1);
+
+  RenderTarget::new(&RenderTargetDescriptor {
+    color_attachment: RenderTextureDescriptor {
+      width,
+      height,
+      options: TextureOptions {
+        format: TextureFormat::RGBA8,
+        sampler: TextureSampler {
+          wrap_mode: TextureWrap::Clamp,
+          minify_filter: TextureFilter::Linear,
+          magnify_filter: TextureFilter::Linear,
+        },
+      },
+    },
+    depth_attachment: None,
+    stencil_attachment: None,
+  })
+}
+
+/// Creates the soft blob drawn for each metaball.
+fn create_blob_image(size: u32) -> Image<Color32> {
+  let mut image = Image::new(size, size);
+  let center = size as f32 / 2.;
+
+  for y in 0..size {
+    for x in 0..size {
+      let distance = vec2(x as f32 + 0.5 - center, y as f32 + 0.5 - center).length() / center;
+      let strength = (blob_falloff(distance) * 255.) as u8;
+
+      image.set_pixel(x, y, Color32::rgba(strength, strength, strength, strength));
+    }
+  }
+
+  image
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_merge_nearby_metaballs() {
+    let metaballs = [
+      Metaball {
+        position: vec2(-1.2, 0.),
+        radius: 2.,
+      },
+      Metaball {
+        position: vec2(1.2, 0.),
+        radius: 2.,
+      },
+    ];
+
+    let threshold = MetaballSettings::default().threshold;
+
+    // each blob alone is too weak halfway between them, but together they
+    // bridge the gap
+    assert!(blob_falloff(0.6) < threshold);
+    assert!(metaball_field(&metaballs, Vec2::ZERO) >= threshold);
+    assert_eq!(metaball_field(&metaballs, vec2(4., 0.)), 0.);
+  }
+
+  #[test]
+  fn it_should_create_soft_blobs() {
+    let blob = create_blob_image(BLOB_SIZE);
+
+    assert!(blob.get_pixel(BLOB_SIZE / 2, BLOB_SIZE / 2).a > 250);
+    assert_eq!(blob.get_pixel(0, 0).a, 0);
+  }
+
+  #[test]
+  fn it_should_render_metaballs() {
+    let mut renderer = MetaballRenderer::new(256, 128, MetaballSettings::default()).unwrap();
+    let mut batch = SpriteBatch::new().unwrap();
+
+    let view = Rectangle::from_size(Vec2::ZERO, vec2(32., 16.));
+    let metaballs = (0..8).map(|index| Metaball {
+      position: vec2(index as f32, 0.),
+      radius: 1.5,
+    });
+
+    renderer.render(&mut batch, view, &Mat4::IDENTITY, metaballs);
+
+    assert_eq!(renderer.target().color_attachment().width(), 128);
+  }
+}
@@ -0,0 +1,523 @@
+//! Minimaps and world maps.
+//!
+//! A [`Minimap`] composes a simplified view of the world into a texture: a
+//! background of tile colors or a top-down capture, hidden by [`FogOfWar`]
+//! where the player hasn't been, with registered [`MapMarker`]s on top. The
+//! view can be zoomed and rotated to follow the player, and a
+//! [`MinimapWidget`] draws the result on the HUD.
+
+use common::{vec2, Angle, Arena, Color32, DenseGrid, Lerp, LineOfSight, Rectangle, UVec2, Vec2};
+
+use super::*;
+
+common::impl_arena_index!(pub MarkerId, "Identifies a marker on a minimap.");
+
+/// How much of the underlying map shows through in explored areas that
+/// aren't currently visible.
+const EXPLORED_VISIBILITY: f32 = 0.5;
+
+/// What the fog of war knows about a part of the world.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum FogState {
+  #[default]
+  Unexplored,
+  Explored,
+  Visible,
+}
+
+/// Tracks which parts of the world have been seen, on a grid of cells with
+/// cell (0, 0) at the world origin.
+#[derive(Clone, Debug)]
+pub struct FogOfWar {
+  cells: DenseGrid<FogState>,
+  cell_size: f32,
+}
+
+impl FogOfWar {
+  /// Creates a fog of war, with everything unexplored.
+  pub fn new(width: usize, height: usize, cell_size: f32) -> Self {
+    Self {
+      cells: DenseGrid::new(width, height),
+      cell_size,
+    }
+  }
+
+  /// The fog state at the given world position.
+  pub fn state_at(&self, position: Vec2) -> FogState {
+    let cell = (position / self.cell_size).floor();
+
+    self
+      .cells
+      .get(cell.x as i32, cell.y as i32)
+      .copied()
+      .unwrap_or_default()
+  }
+
+  /// Hides everything that was visible, leaving it explored.
+  ///
+  /// Call this each frame before revealing what can currently be seen.
+  pub fn begin_frame(&mut self) {
+    for state in self.cells.as_mut_slice() {
+      if *state == FogState::Visible {
+        *state = FogState::Explored;
+      }
+    }
+  }
+
+  /// Makes cells within the radius of the origin visible, if there's a line
+  /// of sight to their centres.
+  pub fn reveal(&mut self, origin: Vec2, radius: f32, sight: &dyn LineOfSight) {
+    let min = ((origin - radius) / self.cell_size).floor();
+    let max = ((origin + radius) / self.cell_size).floor();
+
+    for y in min.y as i32..=max.y as i32 {
+      for x in min.x as i32..=max.x as i32 {
+        let center = (vec2(x as f32, y as f32) + 0.5) * self.cell_size;
+
+        if self.cells.is_valid(x, y) && center.distance(origin) <= radius && sight.has_line_of_sight(origin, center) {
+          self.cells.set(x, y, FogState::Visible);
+        }
+      }
+    }
+  }
+}
+
+/// The shape of a marker on a minimap.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum MarkerShape {
+  #[default]
+  Circle,
+  Square,
+  Diamond,
+}
+
+/// When a marker shows through the fog of war.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum MarkerVisibility {
+  /// Always shown, like the player or an objective.
+  #[default]
+  Always,
+  /// Shown once its area has been explored, like a shop.
+  Explored,
+  /// Shown only while its area is visible, like an enemy.
+  Visible,
+}
+
+/// A point of interest shown on a minimap.
+#[derive(Clone, Debug)]
+pub struct MapMarker {
+  pub position: Vec2,
+  pub color: Color32,
+  pub shape: MarkerShape,
+  /// The radius of the marker, in pixels on the map.
+  pub radius: f32,
+  pub visibility: MarkerVisibility,
+  /// Pins the marker to the edge of the map while it's out of view.
+  pub clamp_to_edge: bool,
+}
+
+impl Default for MapMarker {
+  fn default() -> Self {
+    Self {
+      position: Vec2::ZERO,
+      color: Color32::WHITE,
+      shape: MarkerShape::default(),
+      radius: 2.,
+      visibility: MarkerVisibility::default(),
+      clamp_to_edge: false,
+    }
+  }
+}
+
+/// The simplified view of the world drawn beneath the markers.
+pub enum MinimapBackground {
+  Color(Color32),
+  /// A color per tile, with tile (0, 0) at the world origin.
+  Tiles {
+    colors: DenseGrid<Color32>,
+    tile_size: f32,
+  },
+  /// A top-down capture of the world, covering the given bounds.
+  Capture {
+    image: Image<Color32>,
+    bounds: Rectangle,
+  },
+}
+
+impl MinimapBackground {
+  /// Captures the contents of a texture, like a top-down render target, as
+  /// covering the given bounds of the world.
+  pub fn capture(texture: &Texture, bounds: Rectangle) -> Self {
+    let mut image = Image::new(texture.width(), texture.height());
+
+    for (pixel, color) in image.as_slice_mut().iter_mut().zip(texture.read_pixels()) {
+      *pixel = color;
+    }
+
+    Self::Capture { image, bounds }
+  }
+
+  /// The color of the background at the given world position.
+  fn sample(&self, position: Vec2) -> Color32 {
+    match self {
+      MinimapBackground::Color(color) => *color,
+      MinimapBackground::Tiles { colors, tile_size } => {
+        let tile = (position / *tile_size).floor();
+
+        colors
+          .get(tile.x as i32, tile.y as i32)
+          .copied()
+          .unwrap_or(Color32::CLEAR)
+      }
+      MinimapBackground::Capture { image, bounds } => {
+        if !bounds.contains_point(position) {
+          return Color32::CLEAR;
+        }
+
+        // the top row of a capture is the top of the world
+        let uv = (position - bounds.min()) / bounds.size();
+        let x = (uv.x * image.width() as f32) as u32;
+        let y = ((1. - uv.y) * image.height() as f32) as u32;
+
+        image.get_pixel(x.min(image.width() - 1), y.min(image.height() - 1))
+      }
+    }
+  }
+}
+
+/// Settings for a [`Minimap`].
+#[derive(Clone, Debug)]
+pub struct MinimapSettings {
+  /// The size of the minimap texture, in pixels.
+  pub size: UVec2,
+  /// The color of unexplored areas.
+  pub fog_color: Color32,
+  /// Clips the map to a circle, rather than a square.
+  pub circular: bool,
+}
+
+impl Default for MinimapSettings {
+  fn default() -> Self {
+    Self {
+      size: UVec2::new(128, 128),
+      fog_color: Color32::BLACK,
+      circular: false,
+    }
+  }
+}
+
+/// Renders a top-down view of the world and its markers into a texture.
+pub struct Minimap {
+  settings: MinimapSettings,
+  texture: Texture,
+  image: Image<Color32>,
+  background: MinimapBackground,
+  markers: Arena<MarkerId, MapMarker>,
+  center: Vec2,
+  zoom: f32,
+  rotation: f32,
+}
+
+impl Minimap {
+  /// Creates a new minimap, with an empty background.
+  pub fn new(settings: MinimapSettings) -> Result<Self, TextureError> {
+    let texture = Texture::new(settings.size.x, settings.size.y, &TextureOptions::default())?;
+
+    Ok(Self {
+      image: Image::new(settings.size.x, settings.size.y),
+      settings,
+      texture,
+      background: MinimapBackground::Color(Color32::CLEAR),
+      markers: Arena::new(),
+      center: Vec2::ZERO,
+      zoom: 1.,
+      rotation: 0.,
+    })
+  }
+
+  /// The texture the map is rendered into.
+  pub fn texture(&self) -> &Texture {
+    &self.texture
+  }
+
+  /// The pixels of the last render.
+  pub fn image(&self) -> &Image<Color32> {
+    &self.image
+  }
+
+  /// Changes what's drawn beneath the markers.
+  pub fn set_background(&mut self, background: MinimapBackground) {
+    self.background = background;
+  }
+
+  /// Centres the map on the given world position.
+  pub fn set_center(&mut self, center: Vec2) {
+    self.center = center;
+  }
+
+  /// Changes the zoom of the map, in pixels per world unit.
+  pub fn set_zoom(&mut self, zoom: f32) {
+    self.zoom = zoom.max(f32::EPSILON);
+  }
+
+  /// Rotates the map, e.g. so the player's heading is always up.
+  pub fn set_rotation(&mut self, rotation: Angle) {
+    self.rotation = rotation.into();
+  }
+
+  /// Adds a marker to the map.
+  pub fn add_marker(&mut self, marker: MapMarker) -> MarkerId {
+    self.markers.insert(marker)
+  }
+
+  /// Gets a marker to move or restyle it.
+  pub fn marker_mut(&mut self, id: MarkerId) -> Option<&mut MapMarker> {
+    self.markers.get_mut(id)
+  }
+
+  /// Removes a marker from the map.
+  pub fn remove_marker(&mut self, id: MarkerId) -> Option<MapMarker> {
+    self.markers.remove(id)
+  }
+
+  /// Converts a world position to pixel coordinates on the map.
+  pub fn world_to_map(&self, position: Vec2) -> Vec2 {
+    let offset = Vec2::from_angle(-self.rotation).rotate(position - self.center) * self.zoom;
+
+    self.half_size() + vec2(offset.x, -offset.y)
+  }
+
+  /// Converts pixel coordinates on the map to a world position.
+  pub fn map_to_world(&self, point: Vec2) -> Vec2 {
+    let offset = point - self.half_size();
+
+    self.center + Vec2::from_angle(self.rotation).rotate(vec2(offset.x, -offset.y) / self.zoom)
+  }
+
+  /// Renders the map into its texture, hiding what the fog of war hasn't
+  /// revealed.
+  pub fn render(&mut self, fog: Option<&FogOfWar>) {
+    let fog_state = |position| fog.map_or(FogState::Visible, |fog| fog.state_at(position));
+    let (width, height) = (self.settings.size.x, self.settings.size.y);
+
+    for y in 0..height {
+      for x in 0..width {
+        let point = vec2(x as f32, y as f32) + 0.5;
+
+        let color = if !self.is_on_map(point, 0.) {
+          Color32::CLEAR
+        } else {
+          let position = self.map_to_world(point);
+          let color = self.background.sample(position);
+
+          match fog_state(position) {
+            FogState::Visible => color,
+            FogState::Explored => Color32::lerp(self.settings.fog_color, color, EXPLORED_VISIBILITY),
+            FogState::Unexplored => self.settings.fog_color,
+          }
+        };
+
+        self.image.set_pixel(x, y, color);
+      }
+    }
+
+    for (_, marker) in self.markers.enumerate() {
+      let shown = match marker.visibility {
+        MarkerVisibility::Always => true,
+        MarkerVisibility::Explored => fog_state(marker.position) != FogState::Unexplored,
+        MarkerVisibility::Visible => fog_state(marker.position) == FogState::Visible,
+      };
+
+      let mut point = self.world_to_map(marker.position);
+
+      if !self.is_on_map(point, marker.radius) {
+        if !marker.clamp_to_edge {
+          continue;
+        }
+
+        point = self.clamp_to_edge(point, marker.radius);
+      }
+
+      if shown {
+        draw_marker(&mut self.image, point, marker);
+      }
+    }
+
+    self.texture.write_pixels(width, height, self.image.as_slice());
+  }
+
+  /// Determines if a point is within the map, inset by a margin.
+  fn is_on_map(&self, point: Vec2, margin: f32) -> bool {
+    let half_size = self.half_size();
+    let offset = point - half_size;
+
+    if self.settings.circular {
+      offset.length() <= half_size.min_element() - margin
+    } else {
+      offset.abs().cmple(half_size - margin).all()
+    }
+  }
+
+  /// Moves a point onto the edge of the map, inset by a margin.
+  fn clamp_to_edge(&self, point: Vec2, margin: f32) -> Vec2 {
+    let half_size = self.half_size();
+    let offset = point - half_size;
+
+    if self.settings.circular {
+      half_size + offset.clamp_length_max(half_size.min_element() - margin)
+    } else {
+      half_size + offset.clamp(-(half_size - margin), half_size - margin)
+    }
+  }
+
+  fn half_size(&self) -> Vec2 {
+    self.settings.size.as_vec2() / 2.
+  }
+}
+
+/// Rasterizes a marker centred on the given point.
+fn draw_marker(image: &mut Image<Color32>, point: Vec2, marker: &MapMarker) {
+  let radius = marker.radius;
+  let min = (point - radius).floor().max(Vec2::ZERO);
+  let max = (point + radius)
+    .ceil()
+    .min(vec2(image.width() as f32, image.height() as f32));
+
+  for y in min.y as u32..max.y as u32 {
+    for x in min.x as u32..max.x as u32 {
+      let offset = (vec2(x as f32, y as f32) + 0.5 - point).abs();
+
+      let inside = match marker.shape {
+        MarkerShape::Circle => offset.length() <= radius,
+        MarkerShape::Square => offset.max_element() <= radius,
+        MarkerShape::Diamond => offset.x + offset.y <= radius,
+      };
+
+      if inside {
+        image.set_pixel(x, y, marker.color);
+      }
+    }
+  }
+}
+
+/// Draws a [`Minimap`] on the HUD.
+#[derive(Clone, Debug)]
+pub struct MinimapWidget {
+  /// The centre of the widget on screen.
+  pub position: Vec2,
+  /// The size of the widget on screen.
+  pub size: Vec2,
+  pub color: Color32,
+}
+
+impl MinimapWidget {
+  /// Draws the minimap's texture into the sprite batch.
+  pub fn draw(&self, minimap: &Minimap, batch: &mut SpriteBatch) {
+    let texture = minimap.texture();
+
+    batch.draw_sprite(texture, &SpriteOptions {
+      position: self.position,
+      scale: self.size / vec2(texture.width() as f32, texture.height() as f32),
+      color: self.color,
+      ..Default::default()
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A wall along x = 2 that blocks sight.
+  struct Wall;
+
+  impl LineOfSight for Wall {
+    fn has_line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+      (from.x < 2.) == (to.x < 2.)
+    }
+  }
+
+  fn create_minimap(circular: bool) -> Minimap {
+    Minimap::new(MinimapSettings {
+      size: UVec2::new(16, 16),
+      circular,
+      ..Default::default()
+    })
+    .unwrap()
+  }
+
+  #[test]
+  fn it_should_reveal_fog_with_line_of_sight() {
+    let mut fog = FogOfWar::new(8, 8, 1.);
+
+    fog.reveal(vec2(0.5, 0.5), 3., &Wall);
+
+    assert_eq!(fog.state_at(vec2(1.5, 1.5)), FogState::Visible);
+    assert_eq!(fog.state_at(vec2(2.5, 0.5)), FogState::Unexplored);
+    assert_eq!(fog.state_at(vec2(0.5, 4.5)), FogState::Unexplored);
+
+    fog.begin_frame();
+
+    assert_eq!(fog.state_at(vec2(1.5, 1.5)), FogState::Explored);
+  }
+
+  #[test]
+  fn it_should_convert_between_world_and_map_space() {
+    let mut minimap = create_minimap(false);
+
+    minimap.set_center(vec2(10., 10.));
+    minimap.set_zoom(2.);
+
+    assert_eq!(minimap.world_to_map(vec2(10., 10.)), vec2(8., 8.));
+    assert_eq!(minimap.world_to_map(vec2(11., 11.)), vec2(10., 6.));
+
+    minimap.set_rotation(Angle::Degrees(90.));
+
+    let point = minimap.world_to_map(vec2(10., 11.));
+
+    assert!(point.distance(vec2(10., 8.)) < 0.001);
+    assert!(minimap.map_to_world(point).distance(vec2(10., 11.)) < 0.001);
+  }
+
+  #[test]
+  fn it_should_render_tiles_fog_and_markers() {
+    let mut minimap = create_minimap(true);
+    let mut colors = DenseGrid::new(4, 4);
+    let mut fog = FogOfWar::new(4, 4, 4.);
+
+    colors.fill(Color32::GREEN);
+    fog.reveal(vec2(6., 6.), 2., &());
+
+    minimap.set_background(MinimapBackground::Tiles { colors, tile_size: 4. });
+    minimap.set_center(vec2(8., 8.));
+
+    let enemy = minimap.add_marker(MapMarker {
+      position: vec2(6., 6.),
+      color: Color32::RED,
+      visibility: MarkerVisibility::Visible,
+      ..Default::default()
+    });
+
+    minimap.add_marker(MapMarker {
+      position: vec2(100., 8.),
+      color: Color32::BLUE,
+      clamp_to_edge: true,
+      ..Default::default()
+    });
+
+    minimap.render(Some(&fog));
+
+    let image = minimap.image();
+
+    assert_eq!(image.get_pixel(6, 9), Color32::RED);
+    assert_eq!(image.get_pixel(7, 8), Color32::GREEN);
+    assert_eq!(image.get_pixel(12, 4), Color32::BLACK);
+    assert_eq!(image.get_pixel(0, 0), Color32::CLEAR);
+    assert_eq!(image.get_pixel(13, 8), Color32::BLUE);
+
+    minimap.marker_mut(enemy).unwrap().position = vec2(14., 14.);
+    minimap.render(Some(&fog));
+
+    assert_ne!(minimap.image().get_pixel(13, 2), Color32::RED);
+  }
+}
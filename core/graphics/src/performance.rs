@@ -0,0 +1,380 @@
+//! An on-screen performance overlay.
+//!
+//! The [`PerformanceHud`] graphs recent frame times and their distribution,
+//! and summarizes frame rate, draw calls and triangles from a
+//! [`ResourceTracker`], memory from a [`TrackingAllocator`], and any timings
+//! and counters the game records, like per-system update times and the number
+//! of entities:
+//!
+//! ```rust,ignore
+//! let mut hud = PerformanceHud::new(PerformanceHudSettings::default())
+//!   .with_tracker(tracker.clone())
+//!   .with_memory(|| ALLOCATOR.statistics());
+//!
+//! if keyboard.was_key_pressed(VirtualKey::F3) {
+//!   hud.toggle();
+//! }
+//!
+//! hud.update(time.delta_time);
+//! hud.set_counter("entities", scene.entities().count());
+//! hud.statistics.record_timing("physics", physics_time);
+//!
+//! hud.draw(&mut geometry);
+//!
+//! for (index, line) in hud.lines().iter().enumerate() {
+//!   text.draw(line, hud.line_position(index));
+//! }
+//! ```
+//!
+//! [`TrackingAllocator`]: common::TrackingAllocator
+
+use common::{vec2, Color32, FastHashMap, FrameStatistics, MemoryStatistics, Rectangle, StringName, Vec2};
+
+use super::*;
+
+/// Settings for a [`PerformanceHud`].
+#[derive(Clone, Debug)]
+pub struct PerformanceHudSettings {
+  /// The top-left corner of the overlay, in screen pixels.
+  pub position: Vec2,
+  /// The size of each graph, in screen pixels.
+  pub graph_size: Vec2,
+  /// The frame time the game aims for, in seconds; frames over it are drawn
+  /// as slow, and frames over twice it as hitches.
+  pub target_frame_time: f32,
+  /// The number of buckets in the frame time histogram.
+  pub histogram_buckets: usize,
+  /// The height of a line of text, for laying out [`PerformanceHud::lines`].
+  pub line_height: f32,
+  /// The number of frames to keep statistics over.
+  pub window: usize,
+}
+
+impl Default for PerformanceHudSettings {
+  fn default() -> Self {
+    Self {
+      position: vec2(8., 8.),
+      graph_size: vec2(240., 60.),
+      target_frame_time: 1. / 60.,
+      histogram_buckets: 24,
+      line_height: 16.,
+      window: 240,
+    }
+  }
+}
+
+const BACKGROUND_COLOR: Color32 = Color32::rgba(0, 0, 0, 160);
+const TARGET_COLOR: Color32 = Color32::rgba(255, 255, 255, 96);
+const FAST_COLOR: Color32 = Color32::rgb(96, 208, 96);
+const SLOW_COLOR: Color32 = Color32::rgb(240, 200, 64);
+const HITCH_COLOR: Color32 = Color32::rgb(232, 72, 72);
+const HISTOGRAM_COLOR: Color32 = Color32::rgb(96, 160, 232);
+
+/// Spacing between the parts of the overlay, in screen pixels.
+const PADDING: f32 = 4.;
+
+/// An on-screen overlay of performance statistics, toggled on demand.
+pub struct PerformanceHud {
+  settings: PerformanceHudSettings,
+  visible: bool,
+  /// Frame times and named timings; record per-system timings here.
+  pub statistics: FrameStatistics,
+  tracker: Option<ResourceTracker>,
+  memory: Option<Box<dyn Fn() -> MemoryStatistics>>,
+  counters: FastHashMap<StringName, usize>,
+}
+
+impl PerformanceHud {
+  /// Creates a hidden overlay.
+  pub fn new(settings: PerformanceHudSettings) -> Self {
+    Self {
+      statistics: FrameStatistics::new(settings.window),
+      settings,
+      visible: false,
+      tracker: None,
+      memory: None,
+      counters: FastHashMap::default(),
+    }
+  }
+
+  /// Shows draw calls and triangles counted by the given tracker.
+  pub fn with_tracker(mut self, tracker: ResourceTracker) -> Self {
+    self.tracker = Some(tracker);
+    self
+  }
+
+  /// Shows memory usage, read through the given function; typically from a
+  /// [`common::TrackingAllocator`] installed as the global allocator.
+  pub fn with_memory(mut self, memory: impl Fn() -> MemoryStatistics + 'static) -> Self {
+    self.memory = Some(Box::new(memory));
+    self
+  }
+
+  /// Determines if the overlay is shown.
+  pub fn is_visible(&self) -> bool {
+    self.visible
+  }
+
+  /// Shows or hides the overlay.
+  pub fn set_visible(&mut self, visible: bool) {
+    self.visible = visible;
+  }
+
+  /// Shows the overlay if it's hidden, and hides it if it's shown; bind this
+  /// to a key.
+  pub fn toggle(&mut self) {
+    self.visible = !self.visible;
+  }
+
+  /// Records the time the last frame took, in seconds.
+  ///
+  /// Statistics are recorded while the overlay is hidden, so they're ready
+  /// the moment it's shown.
+  pub fn update(&mut self, delta_time: f32) {
+    self.statistics.record_frame(delta_time);
+  }
+
+  /// Sets a counter to show, like the number of live entities.
+  pub fn set_counter(&mut self, name: impl Into<StringName>, value: usize) {
+    self.counters.insert(name.into(), value);
+  }
+
+  /// The text of the overlay, one entry per line, to draw with the game's
+  /// font from [`PerformanceHud::line_position`].
+  pub fn lines(&self) -> Vec<String> {
+    let statistics = &self.statistics;
+    let mut lines = vec![format!(
+      "{:.0} fps  {:.2} ms avg  {:.2} ms p99  {:.2} ms max",
+      statistics.fps(),
+      statistics.average_frame_time() * 1000.,
+      statistics.percentile_frame_time(0.99) * 1000.,
+      statistics.max_frame_time() * 1000.,
+    )];
+
+    if let Some(tracker) = &self.tracker {
+      let resources = tracker.statistics();
+
+      lines.push(format!(
+        "{} draw calls  {} triangles  {} resources",
+        resources.draw_calls,
+        resources.triangles,
+        resources.live.total()
+      ));
+    }
+
+    if let Some(memory) = &self.memory {
+      let memory = memory();
+
+      lines.push(format!(
+        "{} allocated  {} peak  {} allocations",
+        format_bytes(memory.allocated),
+        format_bytes(memory.peak),
+        memory.allocations
+      ));
+    }
+
+    let mut counters = self.counters.iter().collect::<Vec<_>>();
+
+    counters.sort_by_key(|(name, _)| name.to_string());
+
+    for (name, value) in counters {
+      lines.push(format!("{name}: {value}"));
+    }
+
+    for (name, timing) in statistics.timings() {
+      lines.push(format!("{name}: {:.2} ms", timing * 1000.));
+    }
+
+    lines
+  }
+
+  /// Where the given line of text goes, below the graphs.
+  pub fn line_position(&self, index: usize) -> Vec2 {
+    let graphs = self.graph_bounds(1);
+
+    vec2(
+      graphs.left(),
+      graphs.bottom() + PADDING + index as f32 * self.settings.line_height,
+    )
+  }
+
+  /// The rectangles that make up the graphs, with their colors.
+  ///
+  /// The frame time graph shows the newest frame on the right, scaled so the
+  /// target frame time is halfway up; the histogram below it buckets frame
+  /// times from zero to twice the target.
+  pub fn shapes(&self) -> Vec<(Rectangle, Color32)> {
+    let target = self.settings.target_frame_time;
+    let frames = self.graph_bounds(0);
+    let histogram = self.graph_bounds(1);
+
+    let mut shapes = vec![
+      (
+        Rectangle::new(frames.min() - PADDING, histogram.max() + PADDING),
+        BACKGROUND_COLOR,
+      ),
+      (line_at(frames, 0.5), TARGET_COLOR),
+    ];
+
+    let window = self.settings.window.max(1);
+    let bar_width = frames.width() / window as f32;
+
+    for (index, frame_time) in self.statistics.frame_times().enumerate() {
+      let height = (frame_time / (target * 2.)).min(1.) * frames.height();
+      let right = frames.right() - index as f32 * bar_width;
+      let color = match frame_time {
+        time if time > target * 2. => HITCH_COLOR,
+        time if time > target => SLOW_COLOR,
+        _ => FAST_COLOR,
+      };
+
+      shapes.push((
+        Rectangle::from_corner_points(right - bar_width, frames.bottom() - height, right, frames.bottom()),
+        color,
+      ));
+    }
+
+    let buckets = self.statistics.histogram(self.settings.histogram_buckets, target * 2.);
+
+    let tallest = buckets.iter().copied().max().unwrap_or_default().max(1);
+    let bucket_width = histogram.width() / buckets.len().max(1) as f32;
+
+    for (index, count) in buckets.into_iter().enumerate().filter(|(_, count)| *count > 0) {
+      let height = count as f32 / tallest as f32 * histogram.height();
+      let left = histogram.left() + index as f32 * bucket_width;
+
+      shapes.push((
+        Rectangle::from_corner_points(
+          left,
+          histogram.bottom() - height,
+          left + bucket_width,
+          histogram.bottom(),
+        ),
+        HISTOGRAM_COLOR,
+      ));
+    }
+
+    shapes
+  }
+
+  /// Draws the graphs into a geometry batch, if the overlay is shown.
+  pub fn draw(&self, batch: &mut GeometryBatch) {
+    if !self.visible {
+      return;
+    }
+
+    for (rectangle, color) in self.shapes() {
+      batch.draw_rectangle(rectangle, color);
+    }
+  }
+
+  /// The bounds of the given graph, from the top.
+  fn graph_bounds(&self, index: usize) -> Rectangle {
+    let size = self.settings.graph_size;
+    let top_left = self.settings.position + vec2(0., index as f32 * (size.y + PADDING));
+
+    Rectangle::new(top_left, top_left + size)
+  }
+}
+
+/// A thin horizontal line across a rectangle, at a fraction of its height
+/// from the bottom.
+fn line_at(bounds: Rectangle, fraction: f32) -> Rectangle {
+  let y = bounds.bottom() - bounds.height() * fraction;
+
+  Rectangle::from_corner_points(bounds.left(), y, bounds.right(), y + 1.)
+}
+
+/// Formats a number of bytes with a binary unit, like `1.5 MiB`.
+fn format_bytes(bytes: usize) -> String {
+  const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+  let mut value = bytes as f64;
+  let mut unit = 0;
+
+  while value >= 1024. && unit < UNITS.len() - 1 {
+    value /= 1024.;
+    unit += 1;
+  }
+
+  match unit {
+    0 => format!("{bytes} B"),
+    _ => format!("{value:.1} {}", UNITS[unit]),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_summarize_statistics_as_text() {
+    let tracker = ResourceTracker::new();
+    let mut hud = PerformanceHud::new(PerformanceHudSettings::default())
+      .with_tracker(tracker)
+      .with_memory(|| MemoryStatistics {
+        allocated: 3 * 1024 * 1024 / 2,
+        peak: 2048,
+        allocations: 12,
+        total_allocations: 40,
+      });
+
+    hud.update(0.020);
+    hud.update(0.020);
+    hud.set_counter("entities", 42);
+    hud
+      .statistics
+      .record_timing("physics", common::TimeSpan::from_millis(1.5));
+
+    let lines = hud.lines();
+
+    assert_eq!(lines[0], "50 fps  20.00 ms avg  20.00 ms p99  20.00 ms max");
+    assert_eq!(lines[1], "0 draw calls  0 triangles  0 resources");
+    assert_eq!(lines[2], "1.5 MiB allocated  2.0 KiB peak  12 allocations");
+    assert_eq!(lines[3], "entities: 42");
+    assert_eq!(lines[4], "physics: 1.50 ms");
+  }
+
+  #[test]
+  fn it_should_graph_frame_times_by_speed() {
+    let mut hud = PerformanceHud::new(PerformanceHudSettings {
+      position: Vec2::ZERO,
+      graph_size: vec2(100., 50.),
+      target_frame_time: 0.010,
+      histogram_buckets: 4,
+      window: 10,
+      ..Default::default()
+    });
+
+    assert!(!hud.is_visible());
+
+    hud.toggle();
+
+    assert!(hud.is_visible());
+
+    for frame_time in [0.005, 0.015, 0.030] {
+      hud.update(frame_time);
+    }
+
+    let shapes = hud.shapes();
+    let bars = &shapes[2..5];
+
+    // newest first, from the right
+    assert_eq!(
+      bars[0],
+      (Rectangle::from_corner_points(90., 0., 100., 50.), HITCH_COLOR)
+    );
+    assert_eq!(
+      bars[1],
+      (Rectangle::from_corner_points(80., 12.5, 90., 50.), SLOW_COLOR)
+    );
+    assert_eq!(
+      bars[2],
+      (Rectangle::from_corner_points(70., 37.5, 80., 50.), FAST_COLOR)
+    );
+
+    // one frame in the second bucket, and two in the last
+    assert_eq!(shapes.len(), 7);
+    assert_eq!(hud.line_position(1), vec2(0., 108. + 16.));
+  }
+}
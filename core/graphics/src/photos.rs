@@ -0,0 +1,290 @@
+//! Photo mode captures and filters.
+//!
+//! A [`PhotoCapture`] renders a shot at a multiple of the screen's resolution
+//! by splitting the view into tiles, rendering each into an offscreen target
+//! and stitching them together in memory, so photos can be far larger than
+//! the GPU's largest texture. A [`PhotoFilterStack`] then grades the result
+//! before it's saved:
+//!
+//! ```rust,ignore
+//! let capture = PhotoCapture::new(1920, 1080, 4)?;
+//! let camera = photo_mode.active(&game_camera);
+//!
+//! let mut photo = capture.capture(&camera.projection(), &camera.view(), |projection_view| {
+//!   renderer.render_scene(projection_view);
+//! });
+//!
+//! PhotoFilterStack::default()
+//!   .with(PhotoFilter::Exposure(0.5))
+//!   .with(PhotoFilter::Vignette { strength: 0.4, radius: 0.75 })
+//!   .apply(&mut photo);
+//!
+//! photo.to_path("local://photos/0001.png")?;
+//! ```
+//!
+//! Screen-space effects only see the tile they're rendered in, so effects
+//! that reach across the screen, like bloom, may show seams between tiles.
+
+use common::{Color, Color32, Mat4, UVec2, Vec2, Vec3};
+
+use super::*;
+
+/// A filter applied to a photo before it's saved.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PhotoFilter {
+  /// Brightens or darkens by the given number of stops.
+  Exposure(f32),
+  /// Scales the distance from mid-gray; 1 leaves the photo as is.
+  Contrast(f32),
+  /// Scales the distance from gray; 0 is black and white, 1 leaves the photo
+  /// as is.
+  Saturation(f32),
+  /// Blends towards sepia tones by the given amount, from 0 to 1.
+  Sepia(f32),
+  /// Multiplies by a color.
+  Tint(Color),
+  /// Darkens towards the corners, beyond a radius from the center where the
+  /// corners are at 1.
+  Vignette { strength: f32, radius: f32 },
+}
+
+impl PhotoFilter {
+  /// Filters a single color, at a position in the photo from 0 to 1 on each
+  /// axis.
+  pub fn apply(&self, color: Color, position: Vec2) -> Color {
+    let rgb = Vec3::new(color.r, color.g, color.b);
+
+    let rgb = match *self {
+      PhotoFilter::Exposure(stops) => rgb * 2f32.powf(stops),
+      PhotoFilter::Contrast(amount) => (rgb - 0.5) * amount + 0.5,
+      PhotoFilter::Saturation(amount) => {
+        let gray = Vec3::splat(luminance(rgb));
+
+        gray + (rgb - gray) * amount
+      }
+      PhotoFilter::Sepia(amount) => {
+        let sepia = Vec3::new(
+          rgb.dot(Vec3::new(0.393, 0.769, 0.189)),
+          rgb.dot(Vec3::new(0.349, 0.686, 0.168)),
+          rgb.dot(Vec3::new(0.272, 0.534, 0.131)),
+        );
+
+        rgb.lerp(sepia, amount)
+      }
+      PhotoFilter::Tint(tint) => rgb * Vec3::new(tint.r, tint.g, tint.b),
+      PhotoFilter::Vignette { strength, radius } => {
+        // the corners are a distance of 1 from the center
+        let distance = (position - Vec2::splat(0.5)).length() * std::f32::consts::SQRT_2;
+        let falloff = ((distance - radius) / (1. - radius).max(f32::EPSILON)).clamp(0., 1.);
+
+        rgb * (1. - falloff * falloff * strength)
+      }
+    };
+
+    Color::rgba(rgb.x, rgb.y, rgb.z, color.a)
+  }
+}
+
+/// The perceived brightness of a color.
+fn luminance(rgb: Vec3) -> f32 {
+  rgb.dot(Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+/// Filters applied to a photo in order.
+#[derive(Clone, Debug, Default)]
+pub struct PhotoFilterStack {
+  pub filters: Vec<PhotoFilter>,
+}
+
+impl PhotoFilterStack {
+  /// Adds a filter to the end of the stack.
+  pub fn with(mut self, filter: PhotoFilter) -> Self {
+    self.filters.push(filter);
+    self
+  }
+
+  /// Adds a filter to the end of the stack.
+  pub fn push(&mut self, filter: PhotoFilter) {
+    self.filters.push(filter);
+  }
+
+  /// Applies every filter to the photo, in order.
+  pub fn apply(&self, image: &mut Image<Color32>) {
+    if self.filters.is_empty() {
+      return;
+    }
+
+    let size = Vec2::new(image.width() as f32, image.height() as f32);
+
+    for y in 0..image.height() {
+      for x in 0..image.width() {
+        let position = (Vec2::new(x as f32, y as f32) + 0.5) / size;
+        let color = self
+          .filters
+          .iter()
+          .fold(Color::from(image.get_pixel(x, y)), |color, filter| {
+            filter.apply(color, position)
+          });
+
+        image.set_pixel(x, y, Color32::from(color));
+      }
+    }
+  }
+}
+
+/// Renders photos larger than the screen, a tile at a time.
+pub struct PhotoCapture {
+  target: RenderTarget,
+  tile_size: UVec2,
+  scale: u32,
+}
+
+impl PhotoCapture {
+  /// Creates a capture of tiles of the given size, in pixels, making photos
+  /// `scale` times as wide and tall.
+  pub fn new(tile_width: u32, tile_height: u32, scale: u32) -> Result<Self, TargetError> {
+    let target = RenderTarget::new(&RenderTargetDescriptor {
+      color_attachment: RenderTextureDescriptor {
+        width: tile_width,
+        height: tile_height,
+        options: TextureOptions::default(),
+      },
+      depth_attachment: None,
+      stencil_attachment: None,
+    })?;
+
+    Ok(Self {
+      target,
+      tile_size: UVec2::new(tile_width, tile_height),
+      scale: scale.max(1),
+    })
+  }
+
+  /// The size of the photos this captures, in pixels.
+  pub fn size(&self) -> UVec2 {
+    self.tile_size * self.scale
+  }
+
+  /// Captures a photo through the given projection and view, calling
+  /// `render` once per tile with the projection-view to draw the scene with.
+  pub fn capture(&self, projection: &Mat4, view: &Mat4, mut render: impl FnMut(&Mat4)) -> Image<Color32> {
+    let size = self.size();
+    let mut image = Image::new(size.x, size.y);
+
+    for row in 0..self.scale {
+      for column in 0..self.scale {
+        let projection_view = tile_projection(projection, self.scale, column, row) * *view;
+
+        self.target.activate();
+
+        graphics().clear_color_buffer(Color::CLEAR);
+
+        render(&projection_view);
+
+        self.target.deactivate();
+
+        let pixels = self.target.color_attachment().read_pixels::<Color32>();
+
+        stitch_tile(&mut image, &pixels, self.tile_size, column, row);
+      }
+    }
+
+    image
+  }
+}
+
+/// Narrows a projection to one tile of a `scale` by `scale` grid over the
+/// view, counting columns from the left and rows from the top.
+pub fn tile_projection(projection: &Mat4, scale: u32, column: u32, row: u32) -> Mat4 {
+  let scale = scale as f32;
+  let center = Vec2::new(
+    -1. + (2. * column as f32 + 1.) / scale,
+    1. - (2. * row as f32 + 1.) / scale,
+  );
+
+  Mat4::from_translation((-center * scale).extend(0.)) * Mat4::from_scale(Vec3::new(scale, scale, 1.)) * *projection
+}
+
+/// Copies a tile's pixels, read bottom row first, into its place in the photo.
+fn stitch_tile(image: &mut Image<Color32>, pixels: &[Color32], tile_size: UVec2, column: u32, row: u32) {
+  for y in 0..tile_size.y {
+    for x in 0..tile_size.x {
+      let Some(pixel) = pixels.get((x + y * tile_size.x) as usize) else {
+        return;
+      };
+
+      image.set_pixel(
+        column * tile_size.x + x,
+        row * tile_size.y + (tile_size.y - 1 - y),
+        *pixel,
+      );
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{vec2, vec3};
+
+  use super::*;
+
+  #[test]
+  fn it_should_narrow_the_projection_to_each_tile() {
+    let projection = Mat4::IDENTITY;
+
+    let top_left = tile_projection(&projection, 2, 0, 0);
+
+    assert!(top_left
+      .project_point3(vec3(-1., 1., 0.))
+      .abs_diff_eq(vec3(-1., 1., 0.), 0.0001));
+    assert!(top_left
+      .project_point3(vec3(0., 0., 0.))
+      .abs_diff_eq(vec3(1., -1., 0.), 0.0001));
+
+    let bottom_right = tile_projection(&projection, 2, 1, 1);
+
+    assert!(bottom_right
+      .project_point3(vec3(0.5, -0.5, 0.))
+      .abs_diff_eq(Vec3::ZERO, 0.0001));
+  }
+
+  #[test]
+  fn it_should_stitch_tiles_top_down() {
+    let mut image = Image::new(4, 4);
+    let tile = [Color32::BLACK, Color32::BLACK, Color32::WHITE, Color32::WHITE];
+
+    stitch_tile(&mut image, &tile, UVec2::new(2, 2), 1, 0);
+
+    // the last row read is the top of the tile
+    assert_eq!(image.get_pixel(2, 0), Color32::WHITE);
+    assert_eq!(image.get_pixel(3, 1), Color32::BLACK);
+    assert_eq!(image.get_pixel(0, 0), Color32::default());
+  }
+
+  #[test]
+  fn it_should_apply_filters_in_order() {
+    let mut image = Image::new(2, 1);
+
+    image.set_pixel(0, 0, Color32::rgb(255, 0, 0));
+    image.set_pixel(1, 0, Color32::rgb(64, 64, 64));
+
+    PhotoFilterStack::default()
+      .with(PhotoFilter::Saturation(0.))
+      .with(PhotoFilter::Exposure(1.))
+      .apply(&mut image);
+
+    let red = image.get_pixel(0, 0);
+
+    assert_eq!(red.r, red.g);
+    assert_eq!(red.g, red.b);
+    assert!(image.get_pixel(1, 0).r.abs_diff(128) <= 1);
+
+    let vignette = PhotoFilter::Vignette {
+      strength: 1.,
+      radius: 0.5,
+    };
+
+    assert_eq!(vignette.apply(Color::WHITE, vec2(0.5, 0.5)), Color::WHITE);
+    assert!(vignette.apply(Color::WHITE, vec2(0., 0.)).r < 0.001);
+  }
+}
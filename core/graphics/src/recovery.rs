@@ -0,0 +1,185 @@
+//! Recovery from a lost graphics device.
+//!
+//! The GPU can lose every resource out from under us, e.g. when the driver
+//! resets after a timeout or is updated while the game is running. Backends
+//! report this through [`GraphicsBackend::device_status`]; the platform then
+//! creates a fresh context and calls [`recover_device`], which re-creates
+//! every live resource from the descriptors it retained: textures are
+//! re-allocated (and reloaded, if they came from an image on disk), shaders
+//! are re-linked from their kernels and targets are rebuilt from their
+//! attachments.
+//!
+//! Anything uploaded at runtime, like buffer contents or painted pixels, is
+//! gone. Higher layers re-upload it when notified via [`on_device_restored`],
+//! or by checking [`device_generation`].
+
+use std::sync::{
+  atomic::{AtomicU64, Ordering},
+  Arc, Mutex, RwLock, Weak,
+};
+
+use super::*;
+
+/// The state of the graphics device.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum DeviceStatus {
+  /// The device is working normally.
+  #[default]
+  Ready,
+  /// The device was lost, along with every resource on it.
+  Lost,
+}
+
+/// The result of [`recover_device`].
+#[derive(Debug, Default)]
+pub struct DeviceRecovery {
+  /// The number of resources that were re-created.
+  pub restored: usize,
+  /// Errors from resources that couldn't be re-created.
+  pub errors: Vec<GraphicsError>,
+}
+
+/// The order resources are restored in, so dependencies come first.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) enum RestoreOrder {
+  Buffer,
+  Texture,
+  Shader,
+  Mesh,
+  Target,
+}
+
+/// Internal state for a resource that can be re-created on a new device.
+pub(crate) trait Restore: Send + Sync + 'static {
+  const ORDER: RestoreOrder;
+
+  /// Re-creates the resource on the device, replacing its old ID.
+  fn restore(&mut self) -> Result<(), GraphicsError>;
+}
+
+/// A live resource, as seen by the registry.
+trait DeviceResource: Send + Sync {
+  fn order(&self) -> RestoreOrder;
+  fn restore(&self) -> Result<(), GraphicsError>;
+}
+
+impl<T: Restore> DeviceResource for RwLock<T> {
+  fn order(&self) -> RestoreOrder {
+    T::ORDER
+  }
+
+  fn restore(&self) -> Result<(), GraphicsError> {
+    self.write().unwrap().restore()
+  }
+}
+
+static RESOURCES: Mutex<Vec<Weak<dyn DeviceResource>>> = Mutex::new(Vec::new());
+static LISTENERS: Mutex<Vec<Box<dyn Fn() + Send>>> = Mutex::new(Vec::new());
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Registers a resource to be restored if the device is lost.
+pub(crate) fn register_resource<T: Restore>(resource: &Arc<RwLock<T>>) {
+  let mut resources = RESOURCES.lock().unwrap();
+
+  // drop dead resources instead of growing
+  if resources.len() == resources.capacity() {
+    resources.retain(|resource| resource.strong_count() > 0);
+  }
+
+  resources.push(Arc::downgrade(resource) as Weak<dyn DeviceResource>);
+}
+
+/// The number of times the device has been restored.
+///
+/// Cache this alongside data uploaded at runtime; when it changes, the data
+/// needs uploading again.
+pub fn device_generation() -> u64 {
+  GENERATION.load(Ordering::Acquire)
+}
+
+/// Adds a listener to be called after the device is restored.
+pub fn on_device_restored(listener: impl Fn() + Send + 'static) {
+  LISTENERS.lock().unwrap().push(Box::new(listener));
+}
+
+/// Re-creates every live resource after the device was lost.
+///
+/// The platform should call this once a new device is ready, on the graphics
+/// thread.
+pub fn recover_device() -> DeviceRecovery {
+  graphics().device_reset();
+
+  let mut resources = RESOURCES
+    .lock()
+    .unwrap()
+    .iter()
+    .filter_map(Weak::upgrade)
+    .collect::<Vec<_>>();
+
+  resources.sort_by_key(|resource| resource.order());
+
+  let mut recovery = DeviceRecovery::default();
+
+  for resource in resources {
+    match resource.restore() {
+      Ok(()) => recovery.restored += 1,
+      Err(error) => recovery.errors.push(error),
+    }
+  }
+
+  GENERATION.fetch_add(1, Ordering::AcqRel);
+
+  for listener in LISTENERS.lock().unwrap().iter() {
+    listener();
+  }
+
+  if !recovery.errors.is_empty() {
+    common::warn!(
+      "Restored {} graphics resources, {} failed: {:?}",
+      recovery.restored,
+      recovery.errors.len(),
+      recovery.errors
+    );
+  }
+
+  recovery
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_recreate_resources_with_new_ids() {
+    let texture = Texture::new(16, 8, &TextureOptions::default()).unwrap();
+    let target = RenderTarget::new(&RenderTargetDescriptor {
+      color_attachment: RenderTextureDescriptor {
+        width: 32,
+        height: 32,
+        options: TextureOptions::default(),
+      },
+      depth_attachment: None,
+      stencil_attachment: None,
+    })
+    .unwrap();
+
+    let shader = ShaderProgram::from_kernels(&[ShaderKernel {
+      kind: ShaderKind::Vertex,
+      code: String::new(),
+    }])
+    .unwrap();
+
+    let (texture_id, target_id, shader_id) = (texture.id(), target.id(), shader.id());
+    let generation = device_generation();
+
+    let recovery = recover_device();
+
+    assert!(recovery.errors.is_empty());
+    assert!(recovery.restored >= 4);
+    assert!(device_generation() > generation);
+    assert_ne!(texture.id(), texture_id);
+    assert_ne!(target.id(), target_id);
+    assert_ne!(shader.id(), shader_id);
+    assert_eq!(texture.width(), 16);
+  }
+}
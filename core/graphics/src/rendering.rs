@@ -4,10 +4,14 @@
 //! complex render pipelines than using the 'material', 'mesh', 'render targets'
 //! etc. do alone.
 
+pub use culling::*;
+pub use occlusion::*;
 pub use pipelines::*;
 pub use queue::*;
 
 use super::*;
 
+mod culling;
+mod occlusion;
 mod pipelines;
 mod queue;
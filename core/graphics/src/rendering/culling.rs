@@ -0,0 +1,444 @@
+//! Visibility culling.
+//!
+//! Objects are culled against the camera frustum by default. Indoor scenes can
+//! additionally describe their layout as a [`RoomGraph`]: rooms built from
+//! brush volumes, connected by portal polygons such as doorways and windows.
+//! When the camera is inside a room, only rooms seen through a chain of
+//! portals are visible, and only through the part of each portal in view.
+//!
+//! Objects on layers outside the camera's [`Camera::culling_mask`] are never
+//! visible.
+
+use common::{Bounds, Camera, Frustum, LayerMask, Plane, Vec3, AABB};
+
+/// An object with bounds that can be culled.
+pub trait Cullable {
+  fn bounds(&self) -> AABB;
+
+  /// The layers the object is on.
+  fn layers(&self) -> LayerMask {
+    LayerMask::DEFAULT
+  }
+}
+
+/// An object with its layers, for culling objects that don't carry their own.
+impl<T: Cullable> Cullable for (T, LayerMask) {
+  #[inline]
+  fn bounds(&self) -> AABB {
+    self.0.bounds()
+  }
+
+  #[inline]
+  fn layers(&self) -> LayerMask {
+    self.1
+  }
+}
+
+impl Cullable for AABB {
+  #[inline]
+  fn bounds(&self) -> AABB {
+    self.clone()
+  }
+}
+
+impl Cullable for Bounds {
+  #[inline]
+  fn bounds(&self) -> AABB {
+    self.aabb.clone()
+  }
+}
+
+/// The signed distance of a point from a plane, positive on the inside.
+#[inline]
+fn signed_distance(plane: &Plane, point: Vec3) -> f32 {
+  plane.normal.dot(point) + plane.distance
+}
+
+/// A convex volume bounded by inward-facing planes.
+#[derive(Clone, Debug, Default)]
+pub struct ClipVolume {
+  planes: Vec<Plane>,
+}
+
+impl ClipVolume {
+  /// Creates a volume from inward-facing planes.
+  pub fn from_planes(planes: Vec<Plane>) -> Self {
+    Self { planes }
+  }
+
+  /// Creates a volume from a camera frustum.
+  pub fn from_frustum(frustum: &Frustum) -> Self {
+    Self::from_planes(frustum.to_array().to_vec())
+  }
+
+  /// Creates the volume seen from the eye through a convex portal polygon.
+  ///
+  /// The volume starts at the portal, so nothing between the eye and the
+  /// portal is included.
+  pub fn through_portal(eye: Vec3, polygon: &[Vec3]) -> Self {
+    let centroid = polygon.iter().copied().sum::<Vec3>() / polygon.len().max(1) as f32;
+    let mut planes = Vec::with_capacity(polygon.len() + 1);
+
+    let facing = |plane: Plane, point: Vec3, inside: bool| match (signed_distance(&plane, point) >= 0.) == inside {
+      true => plane,
+      false => Plane::new(-plane.normal, -plane.distance),
+    };
+
+    for (index, &a) in polygon.iter().enumerate() {
+      let b = polygon[(index + 1) % polygon.len()];
+      let normal = (a - eye).cross(b - eye).normalize_or_zero();
+
+      if normal != Vec3::ZERO {
+        planes.push(facing(Plane::from_point(normal, eye), centroid, true));
+      }
+    }
+
+    if polygon.len() >= 3 {
+      let normal = (polygon[1] - polygon[0])
+        .cross(polygon[2] - polygon[0])
+        .normalize_or_zero();
+      let portal = Plane::from_point(normal, polygon[0]);
+
+      if normal != Vec3::ZERO && signed_distance(&portal, eye).abs() > f32::EPSILON {
+        planes.push(facing(portal, eye, false));
+      }
+    }
+
+    Self::from_planes(planes)
+  }
+
+  /// Determines if the point is inside the volume.
+  pub fn contains_point(&self, point: Vec3) -> bool {
+    self.planes.iter().all(|plane| signed_distance(plane, point) >= 0.)
+  }
+
+  /// Determines if any part of the AABB may be inside the volume.
+  pub fn intersects_aabb(&self, aabb: &AABB) -> bool {
+    self.planes.iter().all(|plane| {
+      // test the corner furthest along the plane's normal
+      let corner = Vec3::select(plane.normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+
+      signed_distance(plane, corner) >= 0.
+    })
+  }
+
+  /// Clips a convex polygon to the volume, returning what's left.
+  pub fn clip_polygon(&self, polygon: &[Vec3]) -> Vec<Vec3> {
+    let mut output = polygon.to_vec();
+
+    for plane in &self.planes {
+      let input = std::mem::take(&mut output);
+
+      for (index, &current) in input.iter().enumerate() {
+        let next = input[(index + 1) % input.len()];
+        let current_distance = signed_distance(plane, current);
+        let next_distance = signed_distance(plane, next);
+
+        if current_distance >= 0. {
+          output.push(current);
+        }
+
+        if (current_distance >= 0.) != (next_distance >= 0.) {
+          let t = current_distance / (current_distance - next_distance);
+
+          output.push(current + (next - current) * t);
+        }
+      }
+
+      if output.len() < 3 {
+        return Vec::new();
+      }
+    }
+
+    output
+  }
+}
+
+/// A room in a [`RoomGraph`], built from convex brush volumes.
+#[derive(Clone, Debug, Default)]
+pub struct Room {
+  pub brushes: Vec<AABB>,
+}
+
+impl Room {
+  /// Determines if the point lies in the room.
+  pub fn contains(&self, point: Vec3) -> bool {
+    self.brushes.iter().any(|brush| brush.contains(point))
+  }
+
+  /// Determines if the AABB overlaps the room.
+  pub fn intersects(&self, aabb: &AABB) -> bool {
+    self.brushes.iter().any(|brush| brush.intersects(aabb))
+  }
+}
+
+/// An opening between two rooms, e.g. a doorway.
+#[derive(Clone, Debug)]
+pub struct Portal {
+  pub rooms: [usize; 2],
+  /// The corners of the opening, as a convex polygon.
+  pub polygon: Vec<Vec3>,
+}
+
+/// Rooms connected by portals, for visibility culling in indoor scenes.
+#[derive(Clone, Debug)]
+pub struct RoomGraph {
+  rooms: Vec<Room>,
+  portals: Vec<Portal>,
+  /// The most portals visibility may pass through in a row.
+  pub max_depth: usize,
+}
+
+impl Default for RoomGraph {
+  fn default() -> Self {
+    Self {
+      rooms: Vec::new(),
+      portals: Vec::new(),
+      max_depth: 8,
+    }
+  }
+}
+
+/// The rooms visible from a camera, and the volumes they're seen through.
+#[derive(Clone, Debug, Default)]
+pub struct RoomVisibility {
+  volumes: Vec<Vec<ClipVolume>>,
+}
+
+impl RoomVisibility {
+  /// Determines if any of the room can be seen.
+  pub fn is_room_visible(&self, room: usize) -> bool {
+    self.volumes.get(room).is_some_and(|volumes| !volumes.is_empty())
+  }
+
+  /// Determines if the AABB can be seen in the given room.
+  pub fn is_visible_in(&self, room: usize, aabb: &AABB) -> bool {
+    self
+      .volumes
+      .get(room)
+      .is_some_and(|volumes| volumes.iter().any(|volume| volume.intersects_aabb(aabb)))
+  }
+}
+
+impl RoomGraph {
+  /// Creates a new, empty room graph.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a room from its brush volumes, returning its index.
+  pub fn add_room(&mut self, brushes: Vec<AABB>) -> usize {
+    self.rooms.push(Room { brushes });
+    self.rooms.len() - 1
+  }
+
+  /// Connects two rooms with a portal.
+  pub fn add_portal(&mut self, from: usize, to: usize, polygon: Vec<Vec3>) {
+    self.portals.push(Portal {
+      rooms: [from, to],
+      polygon,
+    });
+  }
+
+  /// The rooms in the graph.
+  pub fn rooms(&self) -> &[Room] {
+    &self.rooms
+  }
+
+  /// The portals in the graph.
+  pub fn portals(&self) -> &[Portal] {
+    &self.portals
+  }
+
+  /// Finds the room containing the point.
+  pub fn room_at(&self, point: Vec3) -> Option<usize> {
+    self.rooms.iter().position(|room| room.contains(point))
+  }
+
+  /// Determines what's visible from the eye, starting with the given view
+  /// volume; returns `None` if the eye isn't in any room.
+  pub fn visibility(&self, eye: Vec3, view: &ClipVolume) -> Option<RoomVisibility> {
+    let start = self.room_at(eye)?;
+    let mut visibility = RoomVisibility {
+      volumes: vec![Vec::new(); self.rooms.len()],
+    };
+
+    self.visit(start, eye, view.clone(), &mut visibility, &mut vec![start]);
+
+    Some(visibility)
+  }
+
+  /// Marks a room visible, then looks through its portals.
+  fn visit(&self, room: usize, eye: Vec3, volume: ClipVolume, visibility: &mut RoomVisibility, path: &mut Vec<usize>) {
+    for portal in &self.portals {
+      let next = match portal.rooms {
+        [from, to] if from == room => to,
+        [from, to] if to == room => from,
+        _ => continue,
+      };
+
+      // don't look back into rooms we've come through
+      if path.contains(&next) || path.len() > self.max_depth {
+        continue;
+      }
+
+      let clipped = volume.clip_polygon(&portal.polygon);
+
+      if clipped.is_empty() {
+        continue;
+      }
+
+      path.push(next);
+      self.visit(next, eye, ClipVolume::through_portal(eye, &clipped), visibility, path);
+      path.pop();
+    }
+
+    visibility.volumes[room].push(volume);
+  }
+}
+
+/// Finds the indices of the objects the camera can see.
+///
+/// Objects are culled against the camera frustum. If rooms are given and the
+/// camera is inside one, objects in rooms are also culled through portals;
+/// objects outside every room are still only frustum culled.
+pub fn cull_visible_objects<T: Cullable>(camera: &dyn Camera, objects: &[T], rooms: Option<&RoomGraph>) -> Vec<usize> {
+  let culling_mask = camera.culling_mask();
+  let frustum = ClipVolume::from_frustum(&camera.frustum());
+  let visibility = rooms.and_then(|rooms| Some((rooms, rooms.visibility(camera.position(), &frustum)?)));
+
+  let is_visible = |bounds: &AABB| {
+    let Some((rooms, visibility)) = &visibility else {
+      return frustum.intersects_aabb(bounds);
+    };
+
+    let mut overlapping = (0..rooms.rooms.len())
+      .filter(|&room| rooms.rooms[room].intersects(bounds))
+      .peekable();
+
+    match overlapping.peek() {
+      Some(_) => overlapping.any(|room| visibility.is_visible_in(room, bounds)),
+      None => frustum.intersects_aabb(bounds),
+    }
+  };
+
+  objects
+    .iter()
+    .enumerate()
+    .filter(|(_, object)| culling_mask.intersects(object.layers()) && is_visible(&object.bounds()))
+    .map(|(index, _)| index)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{vec3, PerspectiveCamera};
+
+  use super::*;
+
+  fn camera_at(position: Vec3, look_at: Vec3) -> PerspectiveCamera {
+    PerspectiveCamera {
+      position,
+      look_at,
+      fov: std::f32::consts::FRAC_PI_2,
+      ..Default::default()
+    }
+  }
+
+  fn object_at(center: Vec3) -> AABB {
+    AABB::from_min_max(center - Vec3::splat(0.25), center + Vec3::splat(0.25))
+  }
+
+  /// A hall (A) with a doorway into a long room (B), which has a doorway on
+  /// its far side into another room (C).
+  fn create_rooms() -> RoomGraph {
+    let mut rooms = RoomGraph::new();
+
+    let a = rooms.add_room(vec![AABB::from_min_max(vec3(-5., 0., -5.), vec3(5., 3., 5.))]);
+    let b = rooms.add_room(vec![AABB::from_min_max(vec3(-5., 0., 5.), vec3(5., 3., 15.))]);
+    let c = rooms.add_room(vec![AABB::from_min_max(vec3(5., 0., 5.), vec3(15., 3., 15.))]);
+
+    rooms.add_portal(a, b, vec![
+      vec3(-1., 0., 5.),
+      vec3(1., 0., 5.),
+      vec3(1., 2., 5.),
+      vec3(-1., 2., 5.),
+    ]);
+
+    rooms.add_portal(b, c, vec![
+      vec3(5., 0., 13.),
+      vec3(5., 0., 14.),
+      vec3(5., 2., 14.),
+      vec3(5., 2., 13.),
+    ]);
+
+    rooms
+  }
+
+  #[test]
+  fn it_should_clip_polygons_to_volumes() {
+    let volume = ClipVolume::from_planes(vec![Plane::from_point(Vec3::X, Vec3::ZERO)]);
+    let clipped = volume.clip_polygon(&[vec3(-1., 0., 0.), vec3(1., 0., 0.), vec3(1., 1., 0.), vec3(-1., 1., 0.)]);
+
+    assert_eq!(clipped.len(), 4);
+    assert!(clipped.iter().all(|point| point.x >= 0.));
+    assert!(volume
+      .clip_polygon(&[vec3(-2., 0., 0.), vec3(-1., 0., 0.), vec3(-1., 1., 0.)])
+      .is_empty());
+  }
+
+  #[test]
+  fn it_should_only_see_rooms_through_portals() {
+    let rooms = create_rooms();
+    let camera = camera_at(vec3(0., 1.5, 0.), vec3(0., 1.5, 10.));
+
+    let objects = [
+      object_at(vec3(2., 1., 2.)),   // in the hall, in view
+      object_at(vec3(0., 1., -3.)),  // in the hall, behind the camera
+      object_at(vec3(0., 1., 10.)),  // through the doorway
+      object_at(vec3(4., 1., 7.)),   // beside the doorway, hidden by the wall
+      object_at(vec3(10., 1., 10.)), // in the far room, which can't be seen
+    ];
+
+    let frustum_only = cull_visible_objects(&camera, &objects, None);
+    let with_portals = cull_visible_objects(&camera, &objects, Some(&rooms));
+
+    assert_eq!(frustum_only, vec![0, 2, 3, 4]);
+    assert_eq!(with_portals, vec![0, 2]);
+
+    let visibility = rooms
+      .visibility(camera.position, &ClipVolume::from_frustum(&camera.frustum()))
+      .unwrap();
+
+    assert!(visibility.is_room_visible(1));
+    assert!(!visibility.is_room_visible(2));
+  }
+
+  #[test]
+  fn it_should_fall_back_to_frustum_culling_outside_rooms() {
+    let rooms = create_rooms();
+    let camera = camera_at(vec3(0., 1.5, -20.), vec3(0., 1.5, 0.));
+
+    let objects = [object_at(vec3(0., 1., 0.)), object_at(vec3(0., 1., -30.))];
+
+    assert!(rooms.room_at(camera.position).is_none());
+    assert_eq!(cull_visible_objects(&camera, &objects, Some(&rooms)), vec![0]);
+  }
+
+  #[test]
+  fn it_should_skip_layers_outside_the_culling_mask() {
+    let enemies = LayerMask::layer(2);
+    let mut camera = camera_at(Vec3::ZERO, vec3(0., 0., 10.));
+
+    let objects = [
+      (object_at(vec3(0., 0., 5.)), LayerMask::DEFAULT),
+      (object_at(vec3(0., 0., 5.)), enemies),
+    ];
+
+    assert_eq!(cull_visible_objects(&camera, &objects, None), vec![0, 1]);
+
+    camera.culling_mask = enemies;
+
+    assert_eq!(cull_visible_objects(&camera, &objects, None), vec![1]);
+  }
+}
@@ -0,0 +1,236 @@
+//! Hierarchical Z (Hi-Z) occlusion culling.
+//!
+//! The depth buffer of a frame (usually the previous one, or a cheap pass of
+//! large occluders) is reduced into a [`DepthPyramid`]: a mip chain where each
+//! texel holds the farthest depth of the four beneath it. Testing an object
+//! then only needs a handful of texels from the level where its screen bounds
+//! span about two texels; if its nearest point is behind all of them, it's
+//! hidden and its draw can be skipped.
+//!
+//! Dense CSG and voxel scenes, where most geometry is behind walls, benefit
+//! the most. Frustum culling should still run first; objects outside the
+//! screen are never reported as occluded.
+
+use common::{Mat4, Vec3, AABB};
+
+use super::*;
+
+/// A single level of a [`DepthPyramid`].
+#[derive(Clone, Debug)]
+struct DepthLevel {
+  width: usize,
+  height: usize,
+  depths: Vec<f32>,
+}
+
+impl DepthLevel {
+  #[inline]
+  fn get(&self, x: usize, y: usize) -> f32 {
+    self.depths[x + y * self.width]
+  }
+
+  /// Halves the level, keeping the farthest depth of each block.
+  fn downsample(&self) -> Self {
+    let width = self.width.div_ceil(2);
+    let height = self.height.div_ceil(2);
+    let mut depths = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+      for x in 0..width {
+        // odd edges fold into the last block
+        let x0 = (x * 2).min(self.width - 1);
+        let y0 = (y * 2).min(self.height - 1);
+        let x1 = (x * 2 + 1).min(self.width - 1);
+        let y1 = (y * 2 + 1).min(self.height - 1);
+
+        depths.push(
+          self
+            .get(x0, y0)
+            .max(self.get(x1, y0))
+            .max(self.get(x0, y1))
+            .max(self.get(x1, y1)),
+        );
+      }
+    }
+
+    Self { width, height, depths }
+  }
+}
+
+/// A depth buffer reduced into a mip chain of farthest depths.
+///
+/// Depths are in window space with the OpenGL conventions: `[0, 1]` from the
+/// near to the far plane, with the first row at the bottom of the screen.
+#[derive(Clone, Debug)]
+pub struct DepthPyramid {
+  levels: Vec<DepthLevel>,
+}
+
+impl DepthPyramid {
+  /// Builds a pyramid from the depths of a `width` by `height` buffer.
+  ///
+  /// # Panics
+  /// Panics if the number of depths doesn't match the size.
+  pub fn from_depths(width: usize, height: usize, depths: &[f32]) -> Self {
+    assert_eq!(depths.len(), width * height, "Depth buffer size mismatch");
+
+    let mut levels = vec![DepthLevel {
+      width,
+      height,
+      depths: depths.to_vec(),
+    }];
+
+    while let Some(last) = levels.last().filter(|level| level.width > 1 || level.height > 1) {
+      levels.push(last.downsample());
+    }
+
+    Self { levels }
+  }
+
+  /// Builds a pyramid from a depth texture, e.g. the depth attachment of a
+  /// [`RenderTarget`].
+  ///
+  /// The backend must be able to read back the texture; otherwise nothing is
+  /// ever reported as occluded.
+  pub fn from_texture(texture: &Texture) -> Self {
+    Self::from_depths(
+      texture.width() as usize,
+      texture.height() as usize,
+      &texture.read_pixels::<f32>(),
+    )
+  }
+
+  /// The width of the full resolution level.
+  pub fn width(&self) -> usize {
+    self.levels[0].width
+  }
+
+  /// The height of the full resolution level.
+  pub fn height(&self) -> usize {
+    self.levels[0].height
+  }
+
+  /// The number of levels, down to a single texel.
+  pub fn levels(&self) -> usize {
+    self.levels.len()
+  }
+
+  /// Determines if the bounds are hidden behind the depths in the pyramid.
+  ///
+  /// Bounds that cross the near plane or lie outside the screen are never
+  /// occluded.
+  pub fn is_occluded(&self, bounds: &AABB, projection_view: &Mat4) -> bool {
+    let mut min = Vec3::splat(f32::MAX);
+    let mut max = Vec3::splat(f32::MIN);
+
+    for index in 0..8 {
+      let clip = *projection_view * bounds.corner(index).extend(1.);
+
+      if clip.w <= f32::EPSILON {
+        return false;
+      }
+
+      let ndc = clip.truncate() / clip.w;
+
+      min = min.min(ndc);
+      max = max.max(ndc);
+    }
+
+    if max.x < -1. || min.x > 1. || max.y < -1. || min.y > 1. || min.z > 1. {
+      return false;
+    }
+
+    let nearest = min.z.max(-1.) * 0.5 + 0.5;
+
+    // screen bounds in texels of the full resolution level
+    let to_texels = |ndc: f32, size: usize| ((ndc.clamp(-1., 1.) * 0.5 + 0.5) * size as f32) as usize;
+
+    let x0 = to_texels(min.x, self.width()).min(self.width() - 1);
+    let x1 = to_texels(max.x, self.width()).min(self.width() - 1);
+    let y0 = to_texels(min.y, self.height()).min(self.height() - 1);
+    let y1 = to_texels(max.y, self.height()).min(self.height() - 1);
+
+    // pick the level where the bounds span about two texels
+    let extent = (x1 - x0).max(y1 - y0).max(1);
+    let level = (usize::BITS - extent.leading_zeros()) as usize;
+    let level = level.saturating_sub(1).min(self.levels.len() - 1);
+    let depths = &self.levels[level];
+
+    for y in (y0 >> level)..=(y1 >> level).min(depths.height - 1) {
+      for x in (x0 >> level)..=(x1 >> level).min(depths.width - 1) {
+        if nearest <= depths.get(x, y) {
+          return false;
+        }
+      }
+    }
+
+    true
+  }
+
+  /// Filters the given objects down to those not hidden behind the depths in
+  /// the pyramid, e.g. the results of [`cull_visible_objects`].
+  pub fn cull<T: Cullable>(&self, projection_view: &Mat4, objects: &[T], candidates: &[usize]) -> Vec<usize> {
+    candidates
+      .iter()
+      .copied()
+      .filter(|&index| !self.is_occluded(&objects[index].bounds(), projection_view))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::vec3;
+
+  use super::*;
+
+  const SIZE: usize = 64;
+
+  fn projection_view() -> Mat4 {
+    let projection = Mat4::perspective_rh_gl(std::f32::consts::FRAC_PI_2, 1., 0.1, 100.);
+    let view = Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+
+    projection * view
+  }
+
+  /// A wall 5 units away, covering the left half of the screen.
+  fn create_pyramid() -> DepthPyramid {
+    let clip = projection_view() * vec3(0., 0., -5.).extend(1.);
+    let wall = clip.z / clip.w * 0.5 + 0.5;
+
+    let depths = (0..SIZE * SIZE)
+      .map(|index| if index % SIZE < SIZE / 2 { wall } else { 1. })
+      .collect::<Vec<_>>();
+
+    DepthPyramid::from_depths(SIZE, SIZE, &depths)
+  }
+
+  fn create_box(center: Vec3, half_size: f32) -> AABB {
+    AABB::from_min_max(center - half_size, center + half_size)
+  }
+
+  #[test]
+  fn it_should_reduce_to_farthest_depths() {
+    let pyramid = DepthPyramid::from_depths(3, 2, &[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+
+    assert_eq!(pyramid.levels(), 3);
+    assert_eq!(pyramid.levels[1].depths, vec![0.5, 0.6]);
+    assert_eq!(pyramid.levels[2].depths, vec![0.6]);
+  }
+
+  #[test]
+  fn it_should_cull_objects_behind_occluders() {
+    let pyramid = create_pyramid();
+    let projection_view = projection_view();
+
+    let objects = [
+      create_box(vec3(-3., 0., -10.), 1.),   // behind the wall
+      create_box(vec3(-0.6, 0., -2.), 0.25), // in front of the wall
+      create_box(vec3(3., 0., -10.), 1.),    // beside the wall
+      create_box(vec3(0., 0., 0.), 1.),      // around the camera
+    ];
+
+    assert_eq!(pyramid.levels(), 7);
+    assert_eq!(pyramid.cull(&projection_view, &objects, &[0, 1, 2, 3]), vec![1, 2, 3]);
+  }
+}
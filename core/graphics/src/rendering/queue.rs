@@ -32,6 +32,7 @@ enum RenderCommand {
     uniforms: Box<ShaderUniformSet>,
     blend_state: BlendState,
     culling_mode: CullingMode,
+    depth_state: DepthState,
     scissor_mode: ScissorMode,
   },
   /// Sets the given uniform on the given shader by its name.
@@ -53,6 +54,8 @@ enum RenderCommand {
     vertex_count: usize,
     index_count: usize,
   },
+  /// Binds a buffer to a shader storage binding point.
+  BindStorageBuffer { buffer_id: BufferId, binding: u32 },
   /// Dispatches a compute shader for execution.
   DispatchCompute {
     shader_id: ShaderId,
@@ -142,10 +145,25 @@ impl RenderQueue {
       uniforms: Box::new(material.uniforms().clone()),
       blend_state: material.blend_state(),
       culling_mode: material.culling_mode(),
+      depth_state: material.depth_state(),
       scissor_mode: material.scissor_mode(),
     });
   }
 
+  /// Sets a uniform on the given shader by name.
+  ///
+  /// Like [`ShaderProgram::set_uniform`], uniforms the shader doesn't have are
+  /// ignored.
+  pub fn set_uniform(&mut self, shader: &ShaderProgram, name: &str, uniform: impl Into<ShaderUniform>) {
+    if let Some(location) = shader.get_uniform_location(name) {
+      self.enqueue(RenderCommand::SetUniformByLocation {
+        shader_id: shader.id(),
+        location,
+        uniform: uniform.into(),
+      });
+    }
+  }
+
   /// Draws the given [`Mesh`].
   pub fn draw_mesh<V: Vertex>(&mut self, mesh: &Mesh<V>, topology: PrimitiveTopology) {
     self.enqueue(RenderCommand::DrawMesh {
@@ -156,6 +174,22 @@ impl RenderQueue {
     })
   }
 
+  /// Draws the first vertices and indices of the given [`Mesh`].
+  pub fn draw_mesh_sub<V: Vertex>(
+    &mut self,
+    mesh: &Mesh<V>,
+    topology: PrimitiveTopology,
+    vertex_count: usize,
+    index_count: usize,
+  ) {
+    self.enqueue(RenderCommand::DrawMesh {
+      mesh_id: mesh.id(),
+      topology,
+      vertex_count,
+      index_count,
+    })
+  }
+
   /// Blits the given [`RenderTarget`] to the display.
   pub fn blit_render_target_to_display(&mut self, target: &RenderTarget, clear_color: Option<Color>) {
     self.enqueue(RenderCommand::SetRenderTargetToDisplay);
@@ -170,6 +204,14 @@ impl RenderQueue {
     });
   }
 
+  /// Binds the given [`Buffer`] to a shader storage binding point.
+  pub fn bind_storage_buffer<T>(&mut self, buffer: &Buffer<T>, binding: u32) {
+    self.enqueue(RenderCommand::BindStorageBuffer {
+      buffer_id: buffer.id(),
+      binding,
+    });
+  }
+
   /// Dispatches a compute shader for execution.
   pub fn dispatch_compute(&mut self, shader: &ShaderProgram, group_count: (u32, u32, u32)) {
     self.enqueue(RenderCommand::DispatchCompute {
@@ -214,10 +256,12 @@ impl RenderQueue {
           uniforms,
           blend_state,
           culling_mode,
+          depth_state,
           scissor_mode,
         } => {
           graphics.set_blend_state(blend_state);
           graphics.set_culling_mode(culling_mode);
+          graphics.set_depth_state(depth_state);
           graphics.set_scissor_mode(scissor_mode);
 
           for (key, uniform) in uniforms.iter() {
@@ -248,6 +292,9 @@ impl RenderQueue {
         } => {
           graphics.shader_set_uniform(shader_id, location, &uniform)?;
         }
+        RenderCommand::BindStorageBuffer { buffer_id, binding } => {
+          graphics.buffer_bind_storage(buffer_id, binding)?;
+        }
         RenderCommand::DispatchCompute {
           shader_id,
           group_count: (x, y, z),
@@ -10,9 +10,11 @@ use common::*;
 
 use super::*;
 
+mod compilation;
 mod lang;
 mod templates;
 
+pub use compilation::*;
 pub use lang::*;
 pub use templates::*;
 
@@ -41,7 +43,7 @@ pub enum ShaderKind {
 }
 
 /// Defines a single kernel function in a shader program.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ShaderKernel {
   pub kind: ShaderKind,
   pub code: String,
@@ -57,15 +59,17 @@ pub struct ShaderProgram {
 struct ShaderProgramState {
   id: ShaderId,
   location_cache: FastHashMap<String, Option<usize>>,
+  kernels: Vec<ShaderKernel>,
 }
 
 impl ShaderProgram {
   /// Creates a new blank [`ShaderProgram`] on the GPU.
   pub fn new() -> Result<Self, ShaderError> {
     Ok(Self {
-      state: internal::GraphicsCell::new(ShaderProgramState {
+      state: internal::GraphicsCell::restorable(ShaderProgramState {
         id: graphics().shader_create()?,
         location_cache: FastHashMap::default(),
+        kernels: Vec::new(),
       }),
     })
   }
@@ -170,7 +174,28 @@ impl ShaderProgram {
 
   /// Reloads the [`ShaderProgram`] from the given shader code.
   pub fn load_kernels(&self, kernels: &[ShaderKernel]) -> Result<(), ShaderError> {
-    graphics().shader_link(self.id(), kernels)?;
+    let mut state = self.state.write();
+
+    graphics().shader_link(state.id, kernels)?;
+
+    // keep the kernels to re-link if the device is lost
+    state.kernels = kernels.to_vec();
+    state.location_cache.clear();
+
+    Ok(())
+  }
+}
+
+impl Restore for ShaderProgramState {
+  const ORDER: RestoreOrder = RestoreOrder::Shader;
+
+  fn restore(&mut self) -> Result<(), GraphicsError> {
+    self.id = graphics().shader_create()?;
+    self.location_cache.clear();
+
+    if !self.kernels.is_empty() {
+      graphics().shader_link(self.id, &self.kernels)?;
+    }
 
     Ok(())
   }
@@ -178,14 +203,14 @@ impl ShaderProgram {
 
 impl Drop for ShaderProgramState {
   fn drop(&mut self) {
-    graphics()
-      .shader_delete(self.id)
-      .expect("Failed to delete shader program");
+    let id = self.id;
+
+    GraphicsServer::defer(move |graphics| graphics.shader_delete(id).expect("Failed to delete shader program"));
   }
 }
 
 /// Representation of a single value that can be used in a shader.
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ShaderUniform {
   Bool(bool),
   I32(i32),
@@ -208,6 +233,7 @@ pub enum ShaderUniform {
   Color(Color),
   Color32(Color32),
   Texture(TextureId, u8, Option<TextureSampler>),
+  LayeredTexture(TextureId, u8, Option<TextureSampler>),
   TextureArray(Vec<TextureId>),
 }
 
@@ -273,7 +299,7 @@ impl<U> From<&'static str> for ShaderUniformKey<U> {
 }
 
 /// A set of [`ShaderUniform`]s that can be passed around the application.
-#[derive(Default, Clone)]
+#[derive(Default, Clone, PartialEq)]
 pub struct ShaderUniformSet {
   uniforms: FastHashMap<String, ShaderUniform>,
   textures: TextureBindingSet,
@@ -297,10 +323,32 @@ impl ShaderUniformSet {
   where
     K: Into<ShaderUniformKey<&'a Texture>>,
   {
-    let key = key.into().name.to_string();
+    self.set_texture_by_name(key.into().name, texture, sampler);
+  }
+
+  /// Sets a uniform by a name only known at runtime, e.g. one read from an
+  /// asset.
+  pub fn set_uniform_by_name(&mut self, name: &str, value: impl Into<ShaderUniform>) {
+    self.uniforms.insert(name.to_string(), value.into());
+  }
+
+  /// Sets a texture by a name only known at runtime.
+  pub fn set_texture_by_name(&mut self, name: &str, texture: &Texture, sampler: Option<TextureSampler>) {
     let slot = self.allocate_texture_slot(texture);
     let uniform = ShaderUniform::Texture(texture.id(), slot, sampler);
 
+    self.uniforms.insert(name.to_string(), uniform);
+  }
+
+  /// Sets the given key as a uniform with all layers of a texture array.
+  pub fn set_texture_array<'a, K>(&mut self, key: K, textures: &'a TextureArray, sampler: Option<TextureSampler>)
+  where
+    K: Into<ShaderUniformKey<&'a TextureArray>>,
+  {
+    let key = key.into().name.to_string();
+    let slot = self.allocate_texture_slot(textures.texture());
+    let uniform = ShaderUniform::LayeredTexture(textures.id(), slot, sampler);
+
     self.uniforms.insert(key, uniform);
   }
 
@@ -347,7 +395,7 @@ impl<'a> IntoIterator for &'a ShaderUniformSet {
 ///
 /// This is useful for tracking unique texture assignments across multiple
 /// materials, invocations, vertices, etc.
-#[derive(Default, Clone)]
+#[derive(Default, Clone, PartialEq)]
 pub struct TextureBindingSet {
   slots: [Option<TextureId>; MAX_TEXTURE_UNITS],
 }
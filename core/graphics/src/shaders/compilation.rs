@@ -0,0 +1,623 @@
+//! Asynchronous shader compilation and a persistent program binary cache.
+//!
+//! Compiling and linking shaders when they're first needed causes hitches at
+//! startup and level loads. A [`ShaderCompiler`] loads and parses shader code
+//! on a worker thread, handing out programs straight away that draw with a
+//! pending placeholder until their real kernels are linked.
+//!
+//! Compiling and linking goes through [`GraphicsBackend::shader_link_async`],
+//! which hands the work to the driver's own threads where it supports
+//! `KHR_parallel_shader_compile`; [`ShaderCompiler::update`] only polls for
+//! completion, and swaps finished programs in. Linked programs are kept in a
+//! [`ShaderCache`] as driver binaries, so later runs skip compiling entirely:
+//!
+//! ```rust,ignore
+//! let cache = ShaderCache::from_path("local://cache/shaders.bin").unwrap_or_default();
+//! let mut compiler = ShaderCompiler::new(cache)?;
+//!
+//! let program = compiler.compile_path::<GLSL>("assets/shaders/water.glsl")?;
+//!
+//! // each frame
+//! compiler.update();
+//!
+//! // on shutdown
+//! compiler.cache_mut().save_if_dirty("local://cache/shaders.bin")?;
+//! ```
+
+use std::{
+  panic::{catch_unwind, AssertUnwindSafe},
+  sync::mpsc,
+  thread::JoinHandle,
+};
+
+use common::{ContentHash, ContentHasher, HashAlgorithm};
+
+use super::*;
+
+/// Identifies a shader cache file.
+const CACHE_MAGIC: u32 = u32::from_le_bytes(*b"SSHC");
+
+/// The version of the shader cache format.
+const CACHE_VERSION: u16 = 1;
+
+/// The most programs a cache file can hold, to reject corrupt counts.
+const MAX_CACHED_PROGRAMS: u32 = 65_536;
+
+/// The largest program binary a cache file can hold, to reject corrupt
+/// lengths before allocating for them.
+const MAX_BINARY_LENGTH: u32 = 64 * 1024 * 1024;
+
+/// The progress of a program linked with
+/// [`GraphicsBackend::shader_link_async`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShaderLinkStatus {
+  /// The driver is still compiling or linking the program.
+  Compiling,
+  /// The program is linked and ready to draw with.
+  Linked,
+  /// The program failed to compile or link, with the driver's log.
+  Failed(String),
+}
+
+/// A linked program, in the driver's own binary format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderBinary {
+  /// The driver-specific format of the binary.
+  pub format: u32,
+  pub data: Vec<u8>,
+}
+
+/// A cache of program binaries, keyed by the kernels they were linked from.
+///
+/// Binaries are only valid for the driver that produced them; one that's
+/// rejected after a driver update is simply compiled again and replaced.
+#[derive(Default)]
+pub struct ShaderCache {
+  binaries: FastHashMap<ContentHash, ShaderBinary>,
+  is_dirty: bool,
+}
+
+impl ShaderCache {
+  /// Creates an empty cache.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The number of cached binaries.
+  pub fn len(&self) -> usize {
+    self.binaries.len()
+  }
+
+  /// Determines if the cache is empty.
+  pub fn is_empty(&self) -> bool {
+    self.binaries.is_empty()
+  }
+
+  /// Determines if binaries were added since the cache was loaded or saved.
+  pub fn is_dirty(&self) -> bool {
+    self.is_dirty
+  }
+
+  /// Finds the binary linked from the given kernels.
+  pub fn get(&self, kernels: &[ShaderKernel]) -> Option<&ShaderBinary> {
+    self.binaries.get(&Self::key(kernels))
+  }
+
+  /// Caches the binary linked from the given kernels.
+  pub fn insert(&mut self, kernels: &[ShaderKernel], binary: ShaderBinary) {
+    self.binaries.insert(Self::key(kernels), binary);
+    self.is_dirty = true;
+  }
+
+  /// Saves the cache to the given path, if anything was added to it.
+  pub fn save_if_dirty(&mut self, path: impl ToVirtualPath) -> Result<(), StreamError> {
+    if self.is_dirty {
+      self.to_path(path)?;
+      self.is_dirty = false;
+    }
+
+    Ok(())
+  }
+
+  /// Hashes the kernels' code into a cache key.
+  fn key(kernels: &[ShaderKernel]) -> ContentHash {
+    let mut hasher = ContentHasher::new(HashAlgorithm::XxHash3);
+
+    for kernel in kernels {
+      hasher.update(&[kind_to_byte(kernel.kind)]);
+      hasher.update(&(kernel.code.len() as u64).to_le_bytes());
+      hasher.update(kernel.code.as_bytes());
+    }
+
+    hasher.finish()
+  }
+}
+
+fn kind_to_byte(kind: ShaderKind) -> u8 {
+  match kind {
+    ShaderKind::Vertex => 0,
+    ShaderKind::Fragment => 1,
+    ShaderKind::Compute => 2,
+  }
+}
+
+impl FromStream for ShaderCache {
+  async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+    if stream.read_u32()? != CACHE_MAGIC || stream.read_u16()? != CACHE_VERSION {
+      return Err(StreamError::InvalidData);
+    }
+
+    let count = stream.read_u32()?;
+
+    if count > MAX_CACHED_PROGRAMS {
+      return Err(StreamError::InvalidData);
+    }
+
+    let mut binaries = FastHashMap::default();
+
+    for _ in 0..count {
+      let key = ContentHash::read_from(stream)?;
+      let format = stream.read_u32()?;
+      let length = stream.read_u32()?;
+
+      if length > MAX_BINARY_LENGTH {
+        return Err(StreamError::InvalidData);
+      }
+
+      let data = stream.read_bytes(length as usize)?;
+
+      binaries.insert(key, ShaderBinary { format, data });
+    }
+
+    Ok(Self {
+      binaries,
+      is_dirty: false,
+    })
+  }
+}
+
+impl ToStream for ShaderCache {
+  fn to_stream(&self, stream: &mut dyn OutputStream) -> Result<(), Self::Error> {
+    stream.write_u32(CACHE_MAGIC)?;
+    stream.write_u16(CACHE_VERSION)?;
+    stream.write_u32(self.binaries.len() as u32)?;
+
+    for (key, binary) in &self.binaries {
+      key.write_to(stream)?;
+      stream.write_u32(binary.format)?;
+      stream.write_u32(binary.data.len() as u32)?;
+      stream.write_bytes(&binary.data)?;
+    }
+
+    Ok(())
+  }
+}
+
+impl ShaderProgram {
+  /// Reloads the [`ShaderProgram`] from the given kernels, linking from a
+  /// cached binary where possible and caching the result otherwise.
+  pub fn load_kernels_cached(&self, kernels: &[ShaderKernel], cache: &mut ShaderCache) -> Result<(), ShaderError> {
+    if let Some(binary) = cache.get(kernels) {
+      let mut state = self.state.write();
+
+      if graphics().shader_link_binary(state.id, binary).is_ok() {
+        state.kernels = kernels.to_vec();
+        state.location_cache.clear();
+
+        return Ok(());
+      }
+    }
+
+    self.load_kernels(kernels)?;
+
+    if let Some(binary) = graphics().shader_binary(self.id()) {
+      cache.insert(kernels, binary);
+    }
+
+    Ok(())
+  }
+
+  /// Swaps in a program linked separately, deleting the one it replaces.
+  fn replace_linked(&self, id: ShaderId, kernels: Vec<ShaderKernel>) {
+    let mut state = self.state.write();
+    let previous = std::mem::replace(&mut state.id, id);
+
+    state.kernels = kernels;
+    state.location_cache.clear();
+
+    if let Err(error) = graphics().shader_delete(previous) {
+      common::warn!("Failed to delete replaced shader {:?}: {:?}", previous, error);
+    }
+  }
+}
+
+/// Loads and parses the kernels of a shader, on the worker thread.
+type ParseJob = Box<dyn FnOnce() -> Result<Vec<ShaderKernel>, ShaderError> + Send>;
+
+/// Kernels parsed on the worker thread.
+struct ParsedShader {
+  ticket: u64,
+  kernels: Result<Vec<ShaderKernel>, ShaderError>,
+}
+
+/// A program still drawing with the placeholder.
+enum PendingShader {
+  /// Waiting on the worker thread for its kernels.
+  Parsing(ShaderProgram),
+  /// Waiting on the driver to link its kernels into a separate program,
+  /// which replaces the placeholder once it's done.
+  Linking {
+    program: ShaderProgram,
+    linking: ShaderId,
+    kernels: Vec<ShaderKernel>,
+  },
+}
+
+impl PendingShader {
+  fn program(&self) -> &ShaderProgram {
+    match self {
+      Self::Parsing(program) => program,
+      Self::Linking { program, .. } => program,
+    }
+  }
+}
+
+/// Compiles shaders without blocking the main thread.
+///
+/// Loading and parsing happens on a single worker thread, in the order the
+/// shaders were requested, and the driver compiles and links on its own
+/// threads. Drivers without `KHR_parallel_shader_compile` finish compiling
+/// when the program is first polled, on the main thread.
+pub struct ShaderCompiler {
+  cache: ShaderCache,
+  placeholder: Vec<ShaderKernel>,
+  pending: FastHashMap<u64, PendingShader>,
+  next_ticket: u64,
+  jobs: Option<mpsc::Sender<(u64, ParseJob)>>,
+  receiver: mpsc::Receiver<ParsedShader>,
+  worker: Option<JoinHandle<()>>,
+}
+
+impl ShaderCompiler {
+  /// Creates a compiler that links through the given cache.
+  pub fn new(cache: ShaderCache) -> Result<Self, ShaderError> {
+    let (jobs, job_receiver) = mpsc::channel::<(u64, ParseJob)>();
+    let (sender, receiver) = mpsc::channel();
+
+    let worker = std::thread::Builder::new()
+      .name("shader-compiler".to_string())
+      .spawn(move || {
+        for (ticket, parse) in job_receiver {
+          // a panicking job fails its own shader, not the ones queued after it
+          let kernels = catch_unwind(AssertUnwindSafe(parse)).unwrap_or_else(|panic| {
+            let message = panic
+              .downcast_ref::<&str>()
+              .map(|it| it.to_string())
+              .or_else(|| panic.downcast_ref::<String>().cloned())
+              .unwrap_or_default();
+
+            Err(ShaderError::CompileError(format!(
+              "Parsing the shader panicked: {message}"
+            )))
+          });
+
+          if sender.send(ParsedShader { ticket, kernels }).is_err() {
+            break;
+          }
+        }
+      })
+      .expect("Failed to spawn the shader compiler thread");
+
+    Ok(Self {
+      cache,
+      placeholder: SHADER_PENDING.to_kernels()?,
+      pending: FastHashMap::default(),
+      next_ticket: 0,
+      jobs: Some(jobs),
+      receiver,
+      worker: Some(worker),
+    })
+  }
+
+  /// The cache programs are linked through.
+  pub fn cache(&self) -> &ShaderCache {
+    &self.cache
+  }
+
+  /// The cache programs are linked through, e.g. to save it.
+  pub fn cache_mut(&mut self) -> &mut ShaderCache {
+    &mut self.cache
+  }
+
+  /// The number of programs still waiting for their kernels.
+  pub fn pending(&self) -> usize {
+    self.pending.len()
+  }
+
+  /// Determines if the program is still drawing with the placeholder.
+  pub fn is_pending(&self, program: &ShaderProgram) -> bool {
+    self.pending.values().any(|it| it.program().id() == program.id())
+  }
+
+  /// Starts compiling the shader at the given path.
+  ///
+  /// The program draws with the pending placeholder until it's linked.
+  pub fn compile_path<S: ShaderLanguage + 'static>(
+    &mut self,
+    path: impl ToVirtualPath,
+  ) -> Result<ShaderProgram, ShaderError> {
+    let path = path.to_virtual_path();
+
+    self.spawn(move || {
+      let code = path.read_all_text().map_err(|_| ShaderError::FailedToLoad)?;
+
+      S::parse_kernels(&code)
+    })
+  }
+
+  /// Starts compiling the given shader code.
+  ///
+  /// The program draws with the pending placeholder until it's linked.
+  pub fn compile_code<S: ShaderLanguage + 'static>(
+    &mut self,
+    code: impl Into<String>,
+  ) -> Result<ShaderProgram, ShaderError> {
+    let code = code.into();
+
+    self.spawn(move || S::parse_kernels(&code))
+  }
+
+  /// Starts linking the programs whose kernels are ready, and swaps in the
+  /// ones the driver has finished; call once per frame.
+  ///
+  /// Returns the number of programs swapped in. Programs that fail to load or
+  /// link keep drawing with the placeholder.
+  pub fn update(&mut self) -> usize {
+    let mut linked = 0;
+
+    loop {
+      match self.receiver.try_recv() {
+        Ok(parsed) => linked += self.start_link(parsed) as usize,
+        Err(mpsc::TryRecvError::Empty) => break,
+        Err(mpsc::TryRecvError::Disconnected) => {
+          self.abandon_parsing();
+          break;
+        }
+      }
+    }
+
+    linked + self.poll_links()
+  }
+
+  /// Blocks until every pending program is linked, e.g. behind a loading
+  /// screen, so nothing draws with the placeholder once play starts.
+  pub fn warm_up(&mut self) -> usize {
+    let mut linked = 0;
+
+    while !self.pending.is_empty() {
+      linked += self.update();
+
+      let is_parsing = self.pending.values().any(|it| matches!(it, PendingShader::Parsing(_)));
+
+      if !is_parsing {
+        std::thread::yield_now();
+        continue;
+      }
+
+      // wait on the worker rather than spinning
+      match self.receiver.recv() {
+        Ok(parsed) => linked += self.start_link(parsed) as usize,
+        Err(_) => self.abandon_parsing(),
+      }
+    }
+
+    linked
+  }
+
+  /// Creates a placeholder program, and queues its real kernels to be parsed
+  /// on the worker thread.
+  fn spawn(
+    &mut self,
+    parse: impl FnOnce() -> Result<Vec<ShaderKernel>, ShaderError> + Send + 'static,
+  ) -> Result<ShaderProgram, ShaderError> {
+    let program = ShaderProgram::new()?;
+
+    program.load_kernels_cached(&self.placeholder, &mut self.cache)?;
+
+    let ticket = self.next_ticket;
+
+    self.next_ticket += 1;
+    self.pending.insert(ticket, PendingShader::Parsing(program.clone()));
+
+    if let Some(jobs) = &self.jobs {
+      // a dead worker leaves the program on the placeholder
+      let _ = jobs.send((ticket, Box::new(parse)));
+    }
+
+    Ok(program)
+  }
+
+  /// Starts linking parsed kernels into a new program for the driver to
+  /// compile, or links them straight from the cache.
+  ///
+  /// Returns true if the program was swapped in already.
+  fn start_link(&mut self, parsed: ParsedShader) -> bool {
+    let Some(PendingShader::Parsing(program)) = self.pending.remove(&parsed.ticket) else {
+      return false;
+    };
+
+    let kernels = match parsed.kernels {
+      Ok(kernels) => kernels,
+      Err(error) => {
+        common::warn!("Failed to load shader {:?}: {:?}", program.id(), error);
+        return false;
+      }
+    };
+
+    let linking = match graphics().shader_create() {
+      Ok(linking) => linking,
+      Err(error) => {
+        common::warn!("Failed to create shader {:?}: {:?}", program.id(), error);
+        return false;
+      }
+    };
+
+    if let Some(binary) = self.cache.get(&kernels) {
+      if graphics().shader_link_binary(linking, binary).is_ok() {
+        program.replace_linked(linking, kernels);
+        return true;
+      }
+    }
+
+    if let Err(error) = graphics().shader_link_async(linking, &kernels) {
+      common::warn!("Failed to compile shader {:?}: {:?}", program.id(), error);
+      let _ = graphics().shader_delete(linking);
+      return false;
+    }
+
+    self.pending.insert(parsed.ticket, PendingShader::Linking {
+      program,
+      linking,
+      kernels,
+    });
+
+    false
+  }
+
+  /// Swaps in the programs the driver has finished linking.
+  fn poll_links(&mut self) -> usize {
+    let cache = &mut self.cache;
+    let mut linked = 0;
+
+    self.pending.retain(|_, pending| {
+      let PendingShader::Linking {
+        program,
+        linking,
+        kernels,
+      } = pending
+      else {
+        return true;
+      };
+
+      match graphics().shader_link_status(*linking) {
+        ShaderLinkStatus::Compiling => true,
+        ShaderLinkStatus::Linked => {
+          if let Some(binary) = graphics().shader_binary(*linking) {
+            cache.insert(kernels, binary);
+          }
+
+          program.replace_linked(*linking, std::mem::take(kernels));
+          linked += 1;
+          false
+        }
+        ShaderLinkStatus::Failed(log) => {
+          common::warn!("Failed to compile shader {:?}: {}", program.id(), log);
+          let _ = graphics().shader_delete(*linking);
+          false
+        }
+      }
+    });
+
+    linked
+  }
+
+  /// Gives up on the programs waiting for the worker, if it's stopped.
+  fn abandon_parsing(&mut self) {
+    common::warn!("The shader compiler thread stopped, leaving shaders on the placeholder");
+
+    self
+      .pending
+      .retain(|_, pending| !matches!(pending, PendingShader::Parsing(_)));
+  }
+}
+
+impl Drop for ShaderCompiler {
+  fn drop(&mut self) {
+    // closing the queue lets the worker finish its current shader and stop
+    self.jobs.take();
+
+    if let Some(worker) = self.worker.take() {
+      let _ = worker.join();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_round_trip_the_cache() {
+    let kernels = SHADER_SPRITE_STANDARD.to_kernels().unwrap();
+    let mut cache = ShaderCache::new();
+
+    cache.insert(&kernels, ShaderBinary {
+      format: 42,
+      data: vec![1, 2, 3],
+    });
+
+    let loaded = ShaderCache::from_bytes(&cache.to_bytes().unwrap()).unwrap();
+
+    assert!(cache.is_dirty());
+    assert!(!loaded.is_dirty());
+    assert_eq!(loaded.get(&kernels), cache.get(&kernels));
+    assert!(loaded.get(&SHADER_PENDING.to_kernels().unwrap()).is_none());
+  }
+
+  #[test]
+  fn it_should_link_programs_once_parsed() {
+    let mut compiler = ShaderCompiler::new(ShaderCache::new()).unwrap();
+
+    let sprite = compiler
+      .compile_code::<GLSL>(include_str!("./embedded/sprite-standard.glsl"))
+      .unwrap();
+    let broken = compiler.compile_path::<GLSL>("local://does/not/exist.glsl").unwrap();
+
+    assert_eq!(compiler.pending(), 2);
+    assert!(compiler.is_pending(&sprite));
+
+    assert_eq!(compiler.warm_up(), 1);
+    assert_eq!(compiler.pending(), 0);
+    assert!(!compiler.is_pending(&sprite));
+    assert!(!compiler.is_pending(&broken));
+  }
+
+  #[test]
+  fn it_should_fail_shaders_whose_parsing_panics() {
+    let mut compiler = ShaderCompiler::new(ShaderCache::new()).unwrap();
+
+    let panicking = compiler.spawn(|| panic!("bad shader")).unwrap();
+    let sprite = compiler
+      .compile_code::<GLSL>(include_str!("./embedded/sprite-standard.glsl"))
+      .unwrap();
+
+    assert_eq!(compiler.warm_up(), 1);
+    assert!(!compiler.is_pending(&panicking));
+    assert!(!compiler.is_pending(&sprite));
+  }
+
+  #[test]
+  fn it_should_reject_corrupt_cache_sizes() {
+    let kernels = SHADER_SPRITE_STANDARD.to_kernels().unwrap();
+    let mut cache = ShaderCache::new();
+
+    cache.insert(&kernels, ShaderBinary {
+      format: 42,
+      data: vec![1, 2, 3],
+    });
+
+    let bytes = cache.to_bytes().unwrap();
+
+    // the count follows the magic and version
+    let mut huge_count = bytes.clone();
+    huge_count[6..10].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    // the length follows the key and format, at the end of the entry header
+    let mut huge_length = bytes.clone();
+    let length_offset = bytes.len() - 3 - 4;
+    huge_length[length_offset..length_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    assert!(ShaderCache::from_bytes(&huge_count).is_err());
+    assert!(ShaderCache::from_bytes(&huge_length).is_err());
+  }
+}
@@ -0,0 +1,56 @@
+// Skins vertices once per frame into a vertex buffer that every pass can draw.
+
+#shader_type compute
+
+#extension GL_ARB_compute_shader : require
+#extension GL_ARB_shader_storage_buffer_object : require
+
+layout(local_size_x = 64) in;
+
+// SkinnedVertex: position (3), normal (3), uv (2), bone indices (4), bone weights (4)
+layout(std430, binding = 0) readonly buffer BindPose {
+  float bind_pose[];
+};
+
+layout(std430, binding = 1) readonly buffer Bones {
+  mat4 bone_matrices[];
+};
+
+// DeformedVertex: position (3), normal (3), uv (2)
+layout(std430, binding = 2) writeonly buffer Skinned {
+  float skinned[];
+};
+
+void main() {
+  uint index = gl_GlobalInvocationID.x;
+
+  if (index >= uint(skinned.length()) / 8u) {
+    return;
+  }
+
+  uint i = index * 16u;
+  uint o = index * 8u;
+
+  vec4 position = vec4(bind_pose[i + 0u], bind_pose[i + 1u], bind_pose[i + 2u], 1.0);
+  vec4 normal = vec4(bind_pose[i + 3u], bind_pose[i + 4u], bind_pose[i + 5u], 0.0);
+  uvec4 bones = floatBitsToUint(vec4(bind_pose[i + 8u], bind_pose[i + 9u], bind_pose[i + 10u], bind_pose[i + 11u]));
+  vec4 weights = vec4(bind_pose[i + 12u], bind_pose[i + 13u], bind_pose[i + 14u], bind_pose[i + 15u]);
+
+  // blend the bone matrices
+  mat4 boneMatrix = bone_matrices[bones.x] * weights.x +
+  bone_matrices[bones.y] * weights.y +
+  bone_matrices[bones.z] * weights.z +
+  bone_matrices[bones.w] * weights.w;
+
+  position = boneMatrix * position;
+  normal = boneMatrix * normal;
+
+  skinned[o + 0u] = position.x;
+  skinned[o + 1u] = position.y;
+  skinned[o + 2u] = position.z;
+  skinned[o + 3u] = normal.x;
+  skinned[o + 4u] = normal.y;
+  skinned[o + 5u] = normal.z;
+  skinned[o + 6u] = bind_pose[i + 6u];
+  skinned[o + 7u] = bind_pose[i + 7u];
+}
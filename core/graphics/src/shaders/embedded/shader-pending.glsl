@@ -0,0 +1,20 @@
+// Stands in for shaders that are still compiling, so there's no hitch waiting for them.
+
+#shader_type vertex
+
+uniform mat4 u_projection_view;
+
+// missing components default to (0, 0, 0, 1), so this works for 2D and 3D positions
+layout(location = 0) in vec4 a_position;
+
+void main() {
+  gl_Position = u_projection_view * vec4(a_position.xyz, 1.0);
+}
+
+#shader_type fragment
+
+out vec4 frag_color;
+
+void main() {
+  frag_color = vec4(1.0, 0.0, 1.0, 0.25);
+}
@@ -0,0 +1,66 @@
+// Implements signed distance field shapes, for crisp primitives at any scale.
+//
+// Every shape is a rounded box in its own space: circles and capsules are
+// boxes rounded all the way, so a single distance function covers them all.
+
+#shader_type vertex
+
+uniform mat4 u_projection_view;
+
+layout(location = 0) in vec2 a_position;
+layout(location = 1) in vec2 a_local;
+layout(location = 2) in vec2 a_half_size;
+layout(location = 3) in vec3 a_params;
+layout(location = 4) in vec4 a_fill;
+layout(location = 5) in vec4 a_border;
+
+out vec2 v_local;
+out vec2 v_half_size;
+out vec3 v_params;
+out vec4 v_fill;
+out vec4 v_border;
+
+void main() {
+  v_local = a_local;
+  v_half_size = a_half_size;
+  v_params = a_params;
+  v_fill = a_fill;
+  v_border = a_border;
+
+  gl_Position = u_projection_view * vec4(a_position, 0.0, 1.0);
+}
+
+#shader_type fragment
+
+in vec2 v_local;
+in vec2 v_half_size;
+in vec3 v_params;
+in vec4 v_fill;
+in vec4 v_border;
+
+out vec4 frag_color;
+
+float rounded_box_distance(vec2 point, vec2 half_size, float radius) {
+  vec2 q = abs(point) - half_size + radius;
+
+  return length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+void main() {
+  float radius = v_params.x;
+  float border_width = v_params.y;
+  float softness = v_params.z;
+
+  float distance = rounded_box_distance(v_local, v_half_size, radius);
+  float edge = max(fwidth(distance), 0.0001);
+
+  // soft shapes (shadows) fade across the edge, hard ones antialias inside it
+  float coverage = softness > 0.0
+    ? 1.0 - smoothstep(-softness, softness, distance)
+    : clamp(-distance / edge, 0.0, 1.0);
+
+  float fill = border_width > 0.0 ? clamp((-distance - border_width) / edge, 0.0, 1.0) : 1.0;
+  vec4 color = mix(v_border, v_fill, fill);
+
+  frag_color = vec4(color.rgb, color.a * coverage);
+}
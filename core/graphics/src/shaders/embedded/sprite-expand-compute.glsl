@@ -0,0 +1,52 @@
+// Expands sprite instances into quads for the standard sprite shaders.
+
+#shader_type compute
+
+#extension GL_ARB_compute_shader : require
+#extension GL_ARB_shader_storage_buffer_object : require
+
+layout(local_size_x = 64) in;
+
+// GpuSprite: position (2), size (2), uv min (2), uv max (2), rotation (1), color (1), layer (1)
+layout(std430, binding = 0) readonly buffer Sprites {
+  float sprites[];
+};
+
+// SpriteVertex: position (2), uv (2), color (1), layer (1)
+layout(std430, binding = 1) writeonly buffer Vertices {
+  float vertices[];
+};
+
+const vec2 CORNERS[4] = vec2[](vec2(-0.5, -0.5), vec2(-0.5, 0.5), vec2(0.5, 0.5), vec2(0.5, -0.5));
+
+void main() {
+  uint index = gl_GlobalInvocationID.x;
+
+  if (index >= uint(sprites.length()) / 11u) {
+    return;
+  }
+
+  uint i = index * 11u;
+
+  vec2 position = vec2(sprites[i + 0u], sprites[i + 1u]);
+  vec2 size = vec2(sprites[i + 2u], sprites[i + 3u]);
+  vec2 uv_min = vec2(sprites[i + 4u], sprites[i + 5u]);
+  vec2 uv_max = vec2(sprites[i + 6u], sprites[i + 7u]);
+  float rotation = sprites[i + 8u];
+
+  mat2 transform = mat2(cos(rotation), sin(rotation), -sin(rotation), cos(rotation)) * mat2(size.x, 0.0, 0.0, size.y);
+
+  for (uint corner = 0u; corner < 4u; corner++) {
+    uint o = (index * 4u + corner) * 6u;
+
+    vec2 point = position + transform * CORNERS[corner];
+    vec2 uv = mix(uv_min, uv_max, CORNERS[corner] + 0.5);
+
+    vertices[o + 0u] = point.x;
+    vertices[o + 1u] = point.y;
+    vertices[o + 2u] = uv.x;
+    vertices[o + 3u] = uv.y;
+    vertices[o + 4u] = sprites[i + 9u]; // packed color, copied bit for bit
+    vertices[o + 5u] = sprites[i + 10u];
+  }
+}
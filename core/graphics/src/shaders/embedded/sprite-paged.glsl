@@ -0,0 +1,36 @@
+// Implements a sprite shader that samples sprites from the pages of a texture array.
+
+#shader_type vertex
+
+uniform mat4 u_projection_view;
+
+layout(location = 0) in vec2 a_position;
+layout(location = 1) in vec2 a_texcoord_0;
+layout(location = 2) in vec4 a_color;
+layout(location = 3) in float a_layer;
+
+out vec2 v_texcoord_0;
+out vec4 v_color;
+out float v_layer;
+
+void main() {
+  v_texcoord_0 = a_texcoord_0;
+  v_color = a_color;
+  v_layer = a_layer;
+
+  gl_Position = u_projection_view * vec4(a_position, 0.0, 1.0);
+}
+
+#shader_type fragment
+
+uniform sampler2DArray u_texture;
+
+in vec2 v_texcoord_0;
+in vec4 v_color;
+in float v_layer;
+
+out vec4 frag_color;
+
+void main() {
+  frag_color = texture(u_texture, vec3(v_texcoord_0, v_layer)) * v_color;
+}
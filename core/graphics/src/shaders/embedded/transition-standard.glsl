@@ -0,0 +1,61 @@
+// Draws the scene through a fullscreen transition.
+//
+// Modes are: 0 fade, 1 circle, 2 pixelate.
+
+#shader_type vertex
+
+uniform mat4 u_projection_view;
+
+layout(location = 0) in vec2 a_position;
+layout(location = 1) in vec2 a_texcoord_0;
+layout(location = 2) in vec4 a_color;
+
+out vec2 v_texcoord_0;
+out vec4 v_color;
+
+void main() {
+  v_texcoord_0 = a_texcoord_0;
+  v_color = a_color;
+
+  gl_Position = u_projection_view * vec4(a_position, 0.0, 1.0);
+}
+
+#shader_type fragment
+
+uniform sampler2D u_texture;
+uniform uint u_mode;
+uniform float u_coverage;
+uniform vec4 u_color;
+uniform vec2 u_center;
+uniform float u_block_size;
+uniform vec2 u_resolution;
+
+in vec2 v_texcoord_0;
+in vec4 v_color;
+
+out vec4 frag_color;
+
+void main() {
+  vec2 uv = v_texcoord_0;
+  float covered = u_coverage;
+
+  if (u_mode == 1u) {
+    // the circle shrinks from past the furthest corner down to nothing
+    vec2 aspect = vec2(u_resolution.x / u_resolution.y, 1.0);
+    float radius = (1.0 - u_coverage) * length(aspect) * 1.5;
+    float distance = length((uv - u_center) * aspect);
+
+    covered = smoothstep(radius - 0.005, radius + 0.005, distance);
+  } else if (u_mode == 2u) {
+    // pixelate most of the way, then fade out the blocks
+    float block = max(1.0, u_block_size * smoothstep(0.0, 0.8, u_coverage));
+    vec2 blocks = u_resolution / block;
+
+    uv = (floor(uv * blocks) + 0.5) / blocks;
+    covered = smoothstep(0.8, 1.0, u_coverage);
+  }
+
+  vec4 scene = texture(u_texture, uv) * v_color;
+
+  frag_color = mix(scene, u_color, covered);
+}
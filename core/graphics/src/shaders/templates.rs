@@ -23,6 +23,11 @@ impl<S: ShaderLanguage> ShaderTemplate<S> {
     ShaderProgram::from_code::<S>(self.code)
   }
 
+  /// Parses the template into its kernels.
+  pub fn to_kernels(&self) -> Result<Vec<ShaderKernel>, ShaderError> {
+    S::parse_kernels(self.code)
+  }
+
   /// Converts the template into a material.
   pub fn to_material(&self) -> Result<Material, ShaderError> {
     Material::from_template(self)
@@ -53,7 +58,14 @@ mod embedded {
   pub const PROJECTION_VIEW: ShaderUniformKey<&Mat4> = ShaderUniformKey::new("u_projection_view");
 
   pub const SHADER_CANVAS_STANDARD: ShaderTemplate<GLSL> = include_shader!("./embedded/canvas-standard.glsl");
+  pub const SHADER_METABALL_THRESHOLD: ShaderTemplate<GLSL> = include_shader!("./embedded/metaball-threshold.glsl");
   pub const SHADER_MESH_SKINNED: ShaderTemplate<GLSL> = include_shader!("./embedded/mesh-skinned.glsl");
+  pub const SHADER_MESH_SKINNED_COMPUTE: ShaderTemplate<GLSL> = include_shader!("./embedded/mesh-skinned-compute.glsl");
   pub const SHADER_SPRITE_STANDARD: ShaderTemplate<GLSL> = include_shader!("./embedded/sprite-standard.glsl");
   pub const SHADER_SPRITE_STANDARD_PALETTE: ShaderTemplate<GLSL> = include_shader!("./embedded/sprite-standard-palette.glsl");
+  pub const SHADER_SPRITE_EXPAND_COMPUTE: ShaderTemplate<GLSL> = include_shader!("./embedded/sprite-expand-compute.glsl");
+  pub const SHADER_SPRITE_PAGED: ShaderTemplate<GLSL> = include_shader!("./embedded/sprite-paged.glsl");
+  pub const SHADER_PENDING: ShaderTemplate<GLSL> = include_shader!("./embedded/shader-pending.glsl");
+  pub const SHADER_SHAPE_STANDARD: ShaderTemplate<GLSL> = include_shader!("./embedded/shape-standard.glsl");
+  pub const SHADER_TRANSITION_STANDARD: ShaderTemplate<GLSL> = include_shader!("./embedded/transition-standard.glsl");
 }
@@ -0,0 +1,342 @@
+//! Signed distance field shape rendering.
+//!
+//! Rather than tessellating primitives into triangles, each shape is drawn as
+//! a single quad and its edge is evaluated per-pixel from a signed distance
+//! field. Shapes stay crisp at any zoom or resolution, and borders, rounded
+//! corners and soft shadows come almost for free.
+
+use common::{vec2, vec3, Color32, Rectangle, Vec2, Vec3};
+
+use super::*;
+
+/// The drop shadow beneath a shape.
+#[derive(Copy, Clone, Debug)]
+pub struct ShapeShadow {
+  /// The offset of the shadow from the shape, in world units.
+  pub offset: Vec2,
+  /// How far the shadow fades out past the shape's edge, in world units.
+  pub blur: f32,
+  /// The color of the shadow.
+  pub color: Color32,
+}
+
+impl Default for ShapeShadow {
+  fn default() -> Self {
+    Self {
+      offset: vec2(2., -2.),
+      blur: 4.,
+      color: Color32::rgba(0, 0, 0, 128),
+    }
+  }
+}
+
+/// The style of a shape drawn in a [`ShapeBatch`].
+#[derive(Copy, Clone, Debug)]
+pub struct ShapeStyle {
+  /// The color inside the shape.
+  pub fill: Color32,
+  /// The color of the border around the inside of the shape's edge.
+  pub border_color: Color32,
+  /// The width of the border, in world units; zero for no border.
+  pub border_width: f32,
+  /// An optional drop shadow.
+  pub shadow: Option<ShapeShadow>,
+}
+
+impl Default for ShapeStyle {
+  fn default() -> Self {
+    Self {
+      fill: Color32::WHITE,
+      border_color: Color32::CLEAR,
+      border_width: 0.,
+      shadow: None,
+    }
+  }
+}
+
+impl ShapeStyle {
+  /// A style that fills the shape with a single color.
+  pub fn filled(fill: Color32) -> Self {
+    Self {
+      fill,
+      ..Self::default()
+    }
+  }
+
+  /// Adds a border to the style.
+  pub fn with_border(self, color: Color32, width: f32) -> Self {
+    Self {
+      border_color: color,
+      border_width: width,
+      ..self
+    }
+  }
+
+  /// Adds a drop shadow to the style.
+  pub fn with_shadow(self, shadow: ShapeShadow) -> Self {
+    Self {
+      shadow: Some(shadow),
+      ..self
+    }
+  }
+}
+
+/// The signed distance from a point to a rounded box centered on the origin.
+///
+/// Negative inside the box, positive outside. A radius equal to the smallest
+/// half extent gives a circle or capsule. This matches the distance evaluated
+/// in the shape shader.
+pub fn rounded_box_distance(point: Vec2, half_size: Vec2, radius: f32) -> f32 {
+  let q = point.abs() - half_size + Vec2::splat(radius);
+
+  q.max(Vec2::ZERO).length() + q.x.max(q.y).min(0.) - radius
+}
+
+/// A batch renderer for signed distance field shapes.
+///
+/// Rounded rectangles, circles and capsules, with borders and shadows, all
+/// draw in a single call per flush.
+pub struct ShapeBatch {
+  mesh: Mesh<ShapeVertex>,
+  vertices: Vec<ShapeVertex>,
+  indices: Vec<MeshIndex>,
+  material: Option<Material>,
+}
+
+/// A specialized vertex for use in our shape batch.
+#[repr(C)]
+#[derive(Clone, Debug, Vertex)]
+struct ShapeVertex {
+  #[vertex(2, F32)]
+  pub position: Vec2,
+  /// The position relative to the shape's center, in the shape's own space.
+  #[vertex(2, F32)]
+  pub local: Vec2,
+  #[vertex(2, F32)]
+  pub half_size: Vec2,
+  /// The corner radius, border width and edge softness.
+  #[vertex(3, F32)]
+  pub params: Vec3,
+  #[vertex(4, U8, normalize)]
+  pub fill: Color32,
+  #[vertex(4, U8, normalize)]
+  pub border: Color32,
+}
+
+/// A single quad in the batch, before it's expanded into vertices.
+struct ShapeQuad {
+  center: Vec2,
+  axis: Vec2,
+  half_size: Vec2,
+  radius: f32,
+  border_width: f32,
+  softness: f32,
+  fill: Color32,
+  border: Color32,
+}
+
+impl ShapeBatch {
+  /// Creates a new shape batch.
+  pub fn new() -> Result<Self, MeshError> {
+    Ok(Self {
+      mesh: Mesh::new(BufferUsage::Dynamic)?,
+      vertices: Vec::new(),
+      indices: Vec::new(),
+      material: None,
+    })
+  }
+
+  /// Creates a material for drawing shapes, with alpha blending enabled.
+  pub fn create_material() -> Result<Material, ShaderError> {
+    let mut material = SHADER_SHAPE_STANDARD.to_material()?;
+
+    material.set_blend_state(BlendState::Enabled {
+      source: BlendFactor::SourceAlpha,
+      destination: BlendFactor::OneMinusSourceAlpha,
+    });
+
+    Ok(material)
+  }
+
+  /// Restarts the batch with the given material.
+  pub fn begin(&mut self, material: &Material) {
+    self.material = Some(material.clone());
+    self.vertices.clear();
+    self.indices.clear();
+  }
+
+  /// Draws a rectangle in the batch.
+  pub fn draw_rectangle(&mut self, rectangle: Rectangle, style: &ShapeStyle) {
+    self.draw_rounded_rectangle(rectangle, 0., style);
+  }
+
+  /// Draws a rectangle with rounded corners in the batch.
+  pub fn draw_rounded_rectangle(&mut self, rectangle: Rectangle, radius: f32, style: &ShapeStyle) {
+    let half_size = rectangle.size() / 2.;
+
+    self.draw_shape(rectangle.center(), Vec2::X, half_size, radius, style);
+  }
+
+  /// Draws a circle in the batch.
+  pub fn draw_circle(&mut self, center: Vec2, radius: f32, style: &ShapeStyle) {
+    self.draw_shape(center, Vec2::X, Vec2::splat(radius), radius, style);
+  }
+
+  /// Draws a capsule between two points in the batch.
+  pub fn draw_capsule(&mut self, from: Vec2, to: Vec2, radius: f32, style: &ShapeStyle) {
+    let delta = to - from;
+    let length = delta.length();
+    let axis = if length > 0. { delta / length } else { Vec2::X };
+
+    let center = (from + to) / 2.;
+    let half_size = vec2(length / 2. + radius, radius);
+
+    self.draw_shape(center, axis, half_size, radius, style);
+  }
+
+  /// Draws a shape, and its shadow, oriented along the given axis.
+  fn draw_shape(&mut self, center: Vec2, axis: Vec2, half_size: Vec2, radius: f32, style: &ShapeStyle) {
+    let half_size = half_size.abs();
+    let radius = radius.clamp(0., half_size.min_element());
+
+    if let Some(shadow) = &style.shadow {
+      self.push_quad(ShapeQuad {
+        center: center + shadow.offset,
+        axis,
+        half_size,
+        radius,
+        border_width: 0.,
+        softness: shadow.blur.max(0.),
+        fill: shadow.color,
+        border: shadow.color,
+      });
+    }
+
+    self.push_quad(ShapeQuad {
+      center,
+      axis,
+      half_size,
+      radius,
+      border_width: style.border_width.max(0.),
+      softness: 0.,
+      fill: style.fill,
+      border: style.border_color,
+    });
+  }
+
+  /// Expands a shape into a quad, padded to fit any softened edge.
+  fn push_quad(&mut self, quad: ShapeQuad) {
+    let base_offset = self.vertices.len() as MeshIndex;
+    let extent = quad.half_size + Vec2::splat(quad.softness);
+    let normal = quad.axis.perp();
+
+    for corner in [vec2(-1., -1.), vec2(-1., 1.), vec2(1., 1.), vec2(1., -1.)] {
+      let local = corner * extent;
+
+      self.vertices.push(ShapeVertex {
+        position: quad.center + quad.axis * local.x + normal * local.y,
+        local,
+        half_size: quad.half_size,
+        params: vec3(quad.radius, quad.border_width, quad.softness),
+        fill: quad.fill,
+        border: quad.border,
+      });
+    }
+
+    self.indices.push(base_offset);
+    self.indices.push(base_offset + 1);
+    self.indices.push(base_offset + 2);
+
+    self.indices.push(base_offset);
+    self.indices.push(base_offset + 2);
+    self.indices.push(base_offset + 3);
+  }
+
+  /// Flushes the batch content to the GPU.
+  pub fn flush(&mut self) {
+    // ensure we're in a valid state to render something
+    if self.vertices.is_empty() || self.indices.is_empty() {
+      return;
+    }
+
+    let Some(material) = self.material.as_mut() else {
+      return;
+    };
+
+    // upload and draw the mesh
+    self.mesh.with_buffers(|vertices, indices| {
+      vertices.write_data(&self.vertices);
+      indices.write_data(&self.indices);
+    });
+
+    self.mesh.draw_sub(
+      material,
+      PrimitiveTopology::Triangles,
+      self.vertices.len(),
+      self.indices.len(),
+    );
+
+    self.vertices.clear();
+    self.indices.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_measure_distance_to_rounded_boxes() {
+    let half_size = vec2(4., 2.);
+
+    assert_eq!(rounded_box_distance(Vec2::ZERO, half_size, 0.), -2.);
+    assert_eq!(rounded_box_distance(vec2(6., 0.), half_size, 0.), 2.);
+    assert_eq!(rounded_box_distance(vec2(4., 2.), half_size, 0.), 0.);
+
+    // rounding pulls the corner in, but leaves the sides alone
+    assert!(rounded_box_distance(vec2(4., 2.), half_size, 1.) > 0.);
+    assert_eq!(rounded_box_distance(vec2(4., 0.), half_size, 1.), 0.);
+
+    // fully rounded squares are circles
+    let circle = rounded_box_distance(vec2(3., 4.), Vec2::splat(2.), 2.);
+
+    assert!((circle - 3.).abs() < 1e-5);
+  }
+
+  #[test]
+  fn it_should_batch_shapes_as_quads() {
+    let mut batch = ShapeBatch::new().unwrap();
+    let material = ShapeBatch::create_material().unwrap();
+
+    let style = ShapeStyle::filled(Color32::RED).with_border(Color32::BLACK, 1.);
+    let shadowed = style.with_shadow(ShapeShadow::default());
+
+    batch.begin(&material);
+    batch.draw_rounded_rectangle(Rectangle::from_size(Vec2::ZERO, vec2(8., 4.)), 1., &style);
+    batch.draw_circle(vec2(4., 4.), 2., &shadowed);
+
+    assert_eq!(batch.vertices.len(), 12);
+    assert_eq!(batch.indices.len(), 18);
+
+    // the shadow is padded out by its blur
+    assert_eq!(batch.vertices[4].local, vec2(-6., -6.));
+    assert_eq!(batch.vertices[4].params.z, 4.);
+
+    batch.flush();
+
+    assert!(batch.vertices.is_empty());
+  }
+
+  #[test]
+  fn it_should_orient_capsules_between_points() {
+    let mut batch = ShapeBatch::new().unwrap();
+
+    batch.draw_capsule(vec2(0., 0.), vec2(0., 10.), 1., &ShapeStyle::default());
+
+    let vertex = &batch.vertices[0];
+
+    assert_eq!(vertex.half_size, vec2(6., 1.));
+    assert_eq!(vertex.params.x, 1.);
+    assert!((vertex.position - vec2(1., -1.)).length() < 1e-5);
+  }
+}
@@ -3,17 +3,28 @@
 pub use aseprite::*;
 pub use atlas::*;
 pub use batch::*;
+pub use gpu::*;
+pub use paging::*;
+pub use tilemap::*;
 
 use super::*;
 
 mod aseprite;
 mod atlas;
 mod batch;
+mod gpu;
+mod paging;
+mod tilemap;
 
 /// Represents something that can be drawn as a sprite.
 pub trait Sprite {
   /// Returns the texture region for this sprite.
   fn to_region(&self) -> TextureRegion;
+
+  /// The bounds of this sprite when drawn with the given options.
+  fn bounds(&self, options: &SpriteOptions) -> common::Bounds {
+    options.bounds(self.to_region().size)
+  }
 }
 
 impl Sprite for Texture {
@@ -1,6 +1,6 @@
 //! A sprite atlas utility
 
-use common::{Color32, UVec2};
+use common::{uvec2, Chunk, Color32, FastHashMap, Format, RonFormat, StreamError, UVec2};
 
 use super::*;
 
@@ -66,6 +66,91 @@ impl SpriteAtlasBuilder {
   }
 }
 
+/// Where a texture packed into an atlas by the asset pipeline ended up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AtlasRedirect {
+  /// The path of the atlas texture.
+  pub atlas: String,
+  pub offset: UVec2,
+  pub size: UVec2,
+}
+
+impl AtlasRedirect {
+  /// The region of the given atlas texture that holds the original texture.
+  pub fn to_region(&self, atlas: &Texture) -> TextureRegion {
+    atlas
+      .to_region()
+      .slice(self.offset.x, self.offset.y, self.size.x, self.size.y)
+  }
+}
+
+/// The textures the asset pipeline packed into shared atlases, by their
+/// original path.
+///
+/// The pipeline packs small textures automatically and writes these
+/// redirects to [`AtlasRedirects::PATH`]; resolve texture paths through them
+/// so sprites drawn from the same atlas batch together, while content keeps
+/// referring to the original textures.
+#[derive(Default)]
+pub struct AtlasRedirects {
+  redirects: FastHashMap<String, AtlasRedirect>,
+}
+
+impl AtlasRedirects {
+  /// Where the asset pipeline writes redirects, relative to the assets root.
+  pub const PATH: &'static str = "atlases.ron";
+
+  /// Reads redirects from a map of original paths to atlas regions.
+  pub fn from_chunk(chunk: &Chunk) -> Result<Self, StreamError> {
+    let Chunk::Map(entries) = chunk else {
+      return Err(StreamError::InvalidData);
+    };
+
+    let mut redirects = FastHashMap::default();
+
+    for (path, entry) in entries {
+      redirects.insert(path.clone(), AtlasRedirect {
+        atlas: entry.read_field("atlas")?,
+        offset: uvec2(entry.read_field("x")?, entry.read_field("y")?),
+        size: uvec2(entry.read_field("width")?, entry.read_field("height")?),
+      });
+    }
+
+    Ok(Self { redirects })
+  }
+
+  /// Reads redirects from the RON written by the asset pipeline.
+  pub fn from_ron_str(text: &str) -> Result<Self, StreamError> {
+    let chunk = RonFormat::default().read_chunk(&mut std::io::Cursor::new(text.as_bytes()))?;
+
+    Self::from_chunk(&chunk)
+  }
+
+  /// The redirect for the texture at the given path, if it was atlased.
+  pub fn get(&self, path: &str) -> Option<&AtlasRedirect> {
+    self.redirects.get(path)
+  }
+
+  /// The number of atlased textures.
+  pub fn len(&self) -> usize {
+    self.redirects.len()
+  }
+
+  /// Determines if no textures were atlased.
+  pub fn is_empty(&self) -> bool {
+    self.redirects.is_empty()
+  }
+
+  /// Resolves the texture at the given path to a region, loading its atlas
+  /// instead if it was atlased.
+  pub fn resolve(&self, path: &str, mut load: impl FnMut(&str) -> Texture) -> TextureRegion {
+    match self.get(path) {
+      Some(redirect) => redirect.to_region(&load(&redirect.atlas)),
+      None => load(path).to_region(),
+    }
+  }
+}
+
 /// Calculates the nearest power of 2 for the given value.
 fn nearest_power_of_2(value: u32) -> u32 {
   let mut result = 1;
@@ -76,3 +161,31 @@ fn nearest_power_of_2(value: u32) -> u32 {
 
   result
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_read_redirects_from_the_pipeline() {
+    let redirects = AtlasRedirects::from_ron_str(
+      r#"{
+        "icons/coin.png": (atlas: "icons/auto-atlas.png", x: 0, y: 0, width: 16, height: 16),
+        "icons/gem.png": (atlas: "icons/auto-atlas.png", x: 16, y: 0, width: 16, height: 8),
+      }"#,
+    )
+    .unwrap();
+
+    assert_eq!(redirects.len(), 2);
+    assert_eq!(
+      redirects.get("icons/gem.png"),
+      Some(&AtlasRedirect {
+        atlas: "icons/auto-atlas.png".to_string(),
+        offset: uvec2(16, 0),
+        size: uvec2(16, 8),
+      })
+    );
+    assert!(redirects.get("icons/banner.png").is_none());
+    assert!(AtlasRedirects::from_ron_str("[]").is_err());
+  }
+}
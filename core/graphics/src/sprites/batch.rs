@@ -1,4 +1,4 @@
-use common::{vec2, Angle, Color32, Mat2, Vec2};
+use common::{vec2, Angle, Bounds, Color32, Mat2, Rectangle, UVec2, Vec2};
 
 use super::*;
 
@@ -11,23 +11,45 @@ const DEFAULT_SPRITE_COUNT: usize = 1024;
 /// for as many sprites as possible.
 ///
 /// Batching is possible over 1 material and for sprites of the same texture.
+/// Sprites drawn from a [`SpritePager`] share its texture array, so only break
+/// the batch when the pager runs out of pages.
 pub struct SpriteBatch {
   mesh: Mesh<SpriteVertex>,
   material: Option<Material>,
   vertices: Vec<SpriteVertex>,
-  last_texture: Option<Texture>,
+  last_texture: Option<BatchTexture>,
+}
+
+/// The texture bound for the current batch run.
+enum BatchTexture {
+  Texture(Texture),
+  Array(TextureArray),
+}
+
+impl BatchTexture {
+  fn id(&self) -> TextureId {
+    match self {
+      BatchTexture::Texture(texture) => texture.id(),
+      BatchTexture::Array(textures) => textures.id(),
+    }
+  }
 }
 
 /// A specialized vertex for use in our sprite batch.
+///
+/// The layout is shared with the sprite expansion compute shader, so it must
+/// stay tightly packed.
 #[repr(C)]
-#[derive(Clone, Debug, Vertex)]
-struct SpriteVertex {
+#[derive(Clone, Debug, Default, Vertex)]
+pub(crate) struct SpriteVertex {
   #[vertex(2, F32)]
   pub position: Vec2,
   #[vertex(2, F32)]
   pub uv: Vec2,
   #[vertex(4, U8, normalize)]
   pub color: Color32,
+  #[vertex(1, F32)]
+  pub layer: f32,
 }
 
 /// Options for drawing a sprite.
@@ -49,6 +71,26 @@ impl Default for SpriteOptions {
   }
 }
 
+impl SpriteOptions {
+  /// The bounds of a sprite of the given size drawn with these options, on
+  /// the `z = 0` plane.
+  pub fn bounds(&self, size: UVec2) -> Bounds {
+    let corners = self.corners(size);
+
+    Bounds::from_points(corners.map(|corner| corner.extend(0.))).expect("a quad always has corners")
+  }
+
+  /// The corners of a sprite of the given size drawn with these options, in
+  /// the order they're pushed to the batch.
+  pub(crate) fn corners(&self, size: UVec2) -> [Vec2; 4] {
+    let scale = vec2(size.x as f32 * self.scale.x, size.y as f32 * self.scale.y);
+    let transform = Mat2::from_scale_angle(scale, self.rotation.into());
+
+    [vec2(-0.5, -0.5), vec2(-0.5, 0.5), vec2(0.5, 0.5), vec2(0.5, -0.5)]
+      .map(|corner| self.position + transform * corner)
+  }
+}
+
 impl SpriteBatch {
   /// Constructs a new [`SpriteBatch`] with a default capacity.
   pub fn new() -> Result<Self, MeshError> {
@@ -92,48 +134,86 @@ impl SpriteBatch {
 
     // flush if the texture has changed
     let region = sprite.to_region();
-    if let Some(texture) = &self.last_texture {
-      if texture.id() != region.texture.id() {
+
+    self.bind_texture(region.texture.id(), || BatchTexture::Texture(region.texture.clone()));
+    self.push_quad(region.size, region.calculate_uv(), 0., options);
+  }
+
+  /// Draws a sprite from a [`SpritePager`], paging it in if necessary.
+  ///
+  /// Paged sprites share the pager's texture array, so they batch together
+  /// regardless of which page they're on; draw them with a material that
+  /// samples layers, like [`SHADER_SPRITE_PAGED`].
+  pub fn draw_paged(&mut self, pager: &mut SpritePager, sprite: PagedSprite, options: &SpriteOptions) {
+    if self.vertices.len() + 4 >= self.vertices.capacity() {
+      self.flush();
+    }
+
+    let region = match pager.request(sprite) {
+      Some(region) => region,
+      None => {
+        // every page is in use by this batch; draw it so its pages can be
+        // reused
         self.flush();
-        self.last_texture = Some(region.texture.clone());
+        pager.begin_frame();
+
+        let Some(region) = pager.request(sprite) else {
+          return; // the sprite doesn't fit on a page at all
+        };
+
+        region
       }
-    } else if self.last_texture.is_none() {
-      self.last_texture = Some(region.texture.clone());
-    }
+    };
 
-    let scale = vec2(
-      region.size.x as f32 * options.scale.x,
-      region.size.y as f32 * options.scale.y,
-    );
+    let textures = pager.texture_array();
+
+    self.bind_texture(textures.id(), || BatchTexture::Array(textures.clone()));
+    self.push_quad(region.size, region.uv, region.layer as f32, options);
+  }
+
+  /// Flushes the batch if the given texture differs from the current one.
+  fn bind_texture(&mut self, id: TextureId, texture: impl FnOnce() -> BatchTexture) {
+    match &self.last_texture {
+      Some(last) if last.id() == id => {}
+      Some(_) => {
+        self.flush();
+        self.last_texture = Some(texture());
+      }
+      None => self.last_texture = Some(texture()),
+    }
+  }
 
-    let angle = options.rotation;
-    let translation = options.position;
-    let transform = Mat2::from_scale_angle(scale, angle.into());
-    let uv = region.calculate_uv();
+  /// Adds the vertices for a single sprite quad.
+  fn push_quad(&mut self, size: UVec2, uv: Rectangle, layer: f32, options: &SpriteOptions) {
+    let [top_left, bottom_left, bottom_right, top_right] = options.corners(size);
 
     // add vertices
     self.vertices.push(SpriteVertex {
-      position: translation + transform * vec2(-0.5, -0.5),
+      position: top_left,
       color: options.color,
       uv: uv.top_left(),
+      layer,
     });
 
     self.vertices.push(SpriteVertex {
-      position: translation + transform * vec2(-0.5, 0.5),
+      position: bottom_left,
       color: options.color,
       uv: uv.bottom_left(),
+      layer,
     });
 
     self.vertices.push(SpriteVertex {
-      position: translation + transform * vec2(0.5, 0.5),
+      position: bottom_right,
       color: options.color,
       uv: uv.bottom_right(),
+      layer,
     });
 
     self.vertices.push(SpriteVertex {
-      position: translation + transform * vec2(0.5, -0.5),
+      position: top_right,
       color: options.color,
       uv: uv.top_right(),
+      layer,
     });
   }
 
@@ -156,8 +236,10 @@ impl SpriteBatch {
     let index_count = sprite_count * 6;
     let mesh = &mut self.mesh;
 
-    if let Some(texture) = &self.last_texture {
-      material.set_texture("u_texture", texture, None);
+    match &self.last_texture {
+      Some(BatchTexture::Texture(texture)) => material.set_texture("u_texture", texture, None),
+      Some(BatchTexture::Array(textures)) => material.set_texture_array("u_texture", textures, None),
+      None => {}
     }
 
     // write vertices to mesh
@@ -172,7 +254,7 @@ impl SpriteBatch {
 }
 
 /// Fills a new buffer with standard quad indices.
-fn build_quad_indices(sprite_count: usize) -> Vec<u32> {
+pub(crate) fn build_quad_indices(sprite_count: usize) -> Vec<u32> {
   let mut indices = Vec::with_capacity(sprite_count * 6);
   let mut index = 0;
 
@@ -0,0 +1,293 @@
+//! GPU-driven sprite rendering for very large numbers of sprites.
+//!
+//! A [`SpriteBatch`] assembles four vertices per sprite on the CPU, which
+//! becomes the bottleneck long before the GPU does. A [`GpuSpriteBatch`]
+//! instead uploads one small [`GpuSprite`] per sprite into a storage buffer,
+//! and a compute pre-pass expands them into quads on the GPU, drawn in a
+//! single call with the standard sprite shaders.
+//!
+//! Every sprite in the batch shares one texture, so pack them into an atlas.
+
+use common::{vec2, Color32, Mat2, Rectangle, UVec2, Vec2};
+
+use super::*;
+
+/// The number of threads in each sprite expansion work group.
+const WORK_GROUP_SIZE: u32 = 64;
+
+/// A single sprite, as uploaded to the GPU.
+///
+/// The layout is shared with the compute shader, so it must stay tightly
+/// packed.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GpuSprite {
+  pub position: Vec2,
+  /// The size of the sprite in world units, after scaling.
+  pub size: Vec2,
+  pub uv_min: Vec2,
+  pub uv_max: Vec2,
+  /// The rotation of the sprite, in radians.
+  pub rotation: f32,
+  pub color: Color32,
+  /// The texture array layer to sample, for paged sprites.
+  pub layer: f32,
+}
+
+impl GpuSprite {
+  /// Creates a sprite of the given size and texture coordinates, drawn with
+  /// the given options.
+  pub fn new(size: UVec2, uv: Rectangle, options: &SpriteOptions) -> Self {
+    Self {
+      position: options.position,
+      size: vec2(size.x as f32 * options.scale.x, size.y as f32 * options.scale.y),
+      uv_min: uv.min,
+      uv_max: uv.max,
+      rotation: options.rotation.into(),
+      color: options.color,
+      layer: 0.,
+    }
+  }
+
+  /// Expands the sprite into the same quad as the compute shader.
+  pub(crate) fn expand(&self) -> [SpriteVertex; 4] {
+    let transform = Mat2::from_scale_angle(self.size, self.rotation);
+
+    [vec2(-0.5, -0.5), vec2(-0.5, 0.5), vec2(0.5, 0.5), vec2(0.5, -0.5)].map(|corner| SpriteVertex {
+      position: self.position + transform * corner,
+      uv: self.uv_min + (self.uv_max - self.uv_min) * (corner + 0.5),
+      color: self.color,
+      layer: self.layer,
+    })
+  }
+}
+
+/// A sprite batch that expands its quads on the GPU.
+///
+/// Where compute shaders aren't available, the batch falls back to expanding
+/// quads on the CPU; it's still drawn in a single call, but check
+/// [`GpuSpriteBatch::is_gpu_driven`] before relying on the throughput.
+pub struct GpuSpriteBatch {
+  expand: Option<ShaderProgram>,
+  instances: Buffer<GpuSprite>,
+  mesh: Mesh<SpriteVertex>,
+  capacity: usize,
+  sprites: Vec<GpuSprite>,
+  material: Option<Material>,
+  texture: Option<Texture>,
+}
+
+impl GpuSpriteBatch {
+  /// Creates a new batch with room for the given number of sprites; it grows
+  /// as needed.
+  pub fn with_capacity(sprite_count: usize) -> Result<Self, GraphicsError> {
+    let mut batch = Self {
+      expand: SHADER_SPRITE_EXPAND_COMPUTE.to_program().ok(),
+      instances: Buffer::new(BufferKind::Element, BufferUsage::Dynamic)?,
+      mesh: Mesh::new(BufferUsage::Dynamic)?,
+      capacity: 0,
+      sprites: Vec::with_capacity(sprite_count),
+      material: None,
+      texture: None,
+    };
+
+    batch.reserve(sprite_count);
+
+    Ok(batch)
+  }
+
+  /// Determines if quads are expanded on the GPU.
+  pub fn is_gpu_driven(&self) -> bool {
+    self.expand.is_some()
+  }
+
+  /// The number of sprites in the batch.
+  pub fn len(&self) -> usize {
+    self.sprites.len()
+  }
+
+  /// Determines if the batch has no sprites.
+  pub fn is_empty(&self) -> bool {
+    self.sprites.is_empty()
+  }
+
+  /// Starts a new batch run with the given [`Material`].
+  pub fn begin(&mut self, material: &Material) {
+    self.material = Some(material.clone());
+    self.sprites.clear();
+    self.texture = None;
+  }
+
+  /// Adds a single [`Sprite`] to the batch with the given [`SpriteOptions`].
+  ///
+  /// Returns false if the sprite's texture differs from the rest of the
+  /// batch; it's not drawn.
+  pub fn draw_sprite(&mut self, sprite: &impl Sprite, options: &SpriteOptions) -> bool {
+    let region = sprite.to_region();
+
+    match &self.texture {
+      Some(texture) if texture.id() != region.texture.id() => return false,
+      Some(_) => {}
+      None => self.texture = Some(region.texture.clone()),
+    }
+
+    self
+      .sprites
+      .push(GpuSprite::new(region.size, region.calculate_uv(), options));
+
+    true
+  }
+
+  /// Adds a sprite that's already in its GPU form, e.g. one simulated in
+  /// bulk, sampling the batch's texture.
+  pub fn push(&mut self, sprite: GpuSprite) {
+    self.sprites.push(sprite);
+  }
+
+  /// Sets the texture sprites pushed with [`GpuSpriteBatch::push`] sample.
+  pub fn set_texture(&mut self, texture: &Texture) {
+    self.texture = Some(texture.clone());
+  }
+
+  /// Expands and draws the batch, then empties it.
+  pub fn flush(&mut self, queue: &mut RenderQueue) {
+    if self.sprites.is_empty() {
+      return; // no sprites? no problem
+    }
+
+    if self.material.is_none() {
+      return;
+    }
+
+    let sprite_count = self.sprites.len();
+
+    if sprite_count > self.capacity {
+      self.reserve(sprite_count.next_power_of_two());
+    }
+
+    match &self.expand {
+      Some(expand) => {
+        self.instances.write_data(&self.sprites);
+
+        queue.bind_storage_buffer(&self.instances, 0);
+        self
+          .mesh
+          .with_buffers(|vertices, _| queue.bind_storage_buffer(vertices, 1));
+        queue.dispatch_compute(expand, ((sprite_count as u32).div_ceil(WORK_GROUP_SIZE), 1, 1));
+        queue.memory_barrier(MemoryBarrier::VertexAttributes);
+      }
+      None => {
+        let vertices = self.sprites.iter().flat_map(GpuSprite::expand).collect::<Vec<_>>();
+
+        self.mesh.with_buffers(|buffer, _| buffer.write_data(&vertices));
+      }
+    }
+
+    let material = self.material.as_mut().unwrap();
+
+    if let Some(texture) = &self.texture {
+      material.set_texture("u_texture", texture, None);
+    }
+
+    queue.set_material(material);
+    queue.draw_mesh_sub(
+      &self.mesh,
+      PrimitiveTopology::Triangles,
+      sprite_count * 4,
+      sprite_count * 6,
+    );
+
+    self.sprites.clear();
+  }
+
+  /// Grows the mesh to hold the given number of sprites.
+  fn reserve(&mut self, sprite_count: usize) {
+    if sprite_count <= self.capacity {
+      return;
+    }
+
+    let indices = build_quad_indices(sprite_count);
+    let is_gpu_driven = self.is_gpu_driven();
+
+    self.mesh.with_buffers(|vertices, buffer_indices| {
+      // the compute shader writes into the existing vertex buffer
+      if is_gpu_driven {
+        vertices.write_data(&vec![SpriteVertex::default(); sprite_count * 4]);
+      }
+
+      buffer_indices.write_data(&indices);
+    });
+
+    self.capacity = sprite_count;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{Angle, Color};
+
+  use super::*;
+
+  #[test]
+  fn it_should_keep_sprite_layouts_packed_for_compute() {
+    let size = |descriptors: &[VertexDescriptor]| descriptors.iter().map(|it| it.size().as_bytes()).sum::<usize>();
+
+    assert_eq!(size_of::<GpuSprite>(), 44);
+    assert_eq!(size_of::<SpriteVertex>(), 24);
+    assert_eq!(size(SpriteVertex::DESCRIPTORS), size_of::<SpriteVertex>());
+  }
+
+  #[test]
+  fn it_should_expand_sprites_like_the_sprite_batch() {
+    let options = SpriteOptions {
+      position: vec2(10., 20.),
+      rotation: Angle::Degrees(30.),
+      scale: vec2(2., 1.),
+      ..Default::default()
+    };
+
+    let size = UVec2::new(16, 8);
+    let uv = Rectangle::from_corner_points(0., 0., 0.5, 0.25);
+    let vertices = GpuSprite::new(size, uv, &options).expand();
+
+    for (vertex, corner) in vertices.iter().zip(options.corners(size)) {
+      assert!(vertex.position.abs_diff_eq(corner, 1e-4));
+    }
+
+    assert_eq!(vertices[0].uv, uv.top_left());
+    assert_eq!(vertices[1].uv, uv.bottom_left());
+    assert_eq!(vertices[2].uv, uv.bottom_right());
+    assert_eq!(vertices[3].uv, uv.top_right());
+  }
+
+  #[test]
+  fn it_should_draw_many_sprites_in_one_call() {
+    let mut queue = RenderQueue::new();
+    let mut batch = GpuSpriteBatch::with_capacity(4).unwrap();
+    let texture = Texture::new(16, 16, &TextureOptions::default()).unwrap();
+    let other = Texture::new(16, 16, &TextureOptions::default()).unwrap();
+    let material = SHADER_SPRITE_STANDARD.to_material().unwrap();
+
+    assert!(batch.is_gpu_driven());
+
+    batch.begin(&material);
+
+    for index in 0..100 {
+      let options = SpriteOptions {
+        position: vec2(index as f32, 0.),
+        color: Color::WHITE.into(),
+        ..Default::default()
+      };
+
+      assert!(batch.draw_sprite(&texture, &options));
+    }
+
+    assert!(!batch.draw_sprite(&other, &SpriteOptions::default()));
+    assert_eq!(batch.len(), 100);
+
+    batch.flush(&mut queue);
+
+    assert!(batch.is_empty());
+    assert_eq!(batch.capacity, 128);
+  }
+}
@@ -0,0 +1,266 @@
+//! Automatic paging of sprites into texture arrays.
+//!
+//! Games with lots of loose sprite textures break the [`SpriteBatch`] on every
+//! texture switch. A [`SpritePager`] packs those sprites onto the layers
+//! ('pages') of a single [`TextureArray`] as they're drawn, so they can all be
+//! drawn with one binding. When every page is full, the least recently used
+//! page is evicted and re-packed.
+
+use common::{uvec2, Color32, Rectangle, UVec2};
+
+use super::*;
+
+/// A handle to a sprite managed by a [`SpritePager`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct PagedSprite(usize);
+
+/// Where a paged sprite currently lives in the texture array.
+#[derive(Clone, Debug)]
+pub struct PagedRegion {
+  pub layer: u32,
+  pub uv: Rectangle,
+  pub size: UVec2,
+}
+
+/// A sprite's pixels, kept so it can be re-paged after eviction.
+struct SpriteEntry {
+  size: UVec2,
+  pixels: Vec<Color32>,
+  placement: Option<(u32, UVec2)>,
+}
+
+/// A single layer of the texture array, packed shelf by shelf.
+#[derive(Default)]
+struct Page {
+  sprites: Vec<usize>,
+  cursor: UVec2,
+  shelf_height: u32,
+  last_used: u64,
+}
+
+impl Page {
+  /// Finds room for a sprite of the given size, if there is any.
+  fn try_pack(&mut self, size: UVec2, page_size: UVec2) -> Option<UVec2> {
+    let (mut cursor, mut shelf_height) = (self.cursor, self.shelf_height);
+
+    if cursor.x + size.x > page_size.x {
+      cursor = uvec2(0, cursor.y + shelf_height);
+      shelf_height = 0;
+    }
+
+    if cursor.x + size.x > page_size.x || cursor.y + size.y > page_size.y {
+      return None;
+    }
+
+    self.cursor = uvec2(cursor.x + size.x, cursor.y);
+    self.shelf_height = shelf_height.max(size.y);
+
+    Some(cursor)
+  }
+}
+
+/// Packs sprites into the pages of a texture array on demand.
+pub struct SpritePager {
+  textures: TextureArray,
+  sprites: Vec<SpriteEntry>,
+  pages: Vec<Page>,
+  frame: u64,
+}
+
+impl SpritePager {
+  /// Creates a pager with the given number of pages of the given size.
+  ///
+  /// The page count bounds the pager's memory use; sprites are evicted rather
+  /// than growing it.
+  pub fn new(page_size: UVec2, page_count: u32) -> Result<Self, TextureError> {
+    let textures = TextureArray::new(page_size.x, page_size.y, page_count, &TextureOptions::default())?;
+
+    Ok(Self {
+      textures,
+      sprites: Vec::new(),
+      pages: (0..page_count).map(|_| Page::default()).collect(),
+      frame: 0,
+    })
+  }
+
+  /// The texture array holding the pages.
+  pub fn texture_array(&self) -> &TextureArray {
+    &self.textures
+  }
+
+  /// Adds a sprite; it's paged in the first time it's requested.
+  pub fn add(&mut self, image: &Image<Color32>) -> PagedSprite {
+    self.sprites.push(SpriteEntry {
+      size: uvec2(image.width(), image.height()),
+      pixels: image.as_slice().to_vec(),
+      placement: None,
+    });
+
+    PagedSprite(self.sprites.len() - 1)
+  }
+
+  /// Is the sprite currently on a page?
+  pub fn is_resident(&self, sprite: PagedSprite) -> bool {
+    self.sprites[sprite.0].placement.is_some()
+  }
+
+  /// The number of pages with at least one sprite on them.
+  pub fn resident_pages(&self) -> usize {
+    self.pages.iter().filter(|page| !page.sprites.is_empty()).count()
+  }
+
+  /// Starts a new frame.
+  ///
+  /// Pages used since the last call are protected from eviction, as draws
+  /// still in flight may be sampling them.
+  pub fn begin_frame(&mut self) {
+    self.frame += 1;
+  }
+
+  /// Finds the sprite's region, paging it in if it isn't resident.
+  ///
+  /// Returns `None` if the sprite is larger than a page, or every page has
+  /// been used this frame.
+  pub fn request(&mut self, sprite: PagedSprite) -> Option<PagedRegion> {
+    let page_size = uvec2(self.textures.width(), self.textures.height());
+
+    if self.sprites[sprite.0].placement.is_none() {
+      let size = self.sprites[sprite.0].size;
+
+      if size.x > page_size.x || size.y > page_size.y {
+        return None;
+      }
+
+      let (layer, offset) = match self.pack(size, page_size) {
+        Some(placement) => placement,
+        None => {
+          let layer = self.evict_least_recently_used()?;
+          let offset = self.pages[layer as usize].try_pack(size, page_size)?;
+
+          (layer, offset)
+        }
+      };
+
+      let entry = &mut self.sprites[sprite.0];
+      let region = Rectangle::from_corner_points(
+        offset.x as f32,
+        offset.y as f32,
+        (offset.x + size.x) as f32,
+        (offset.y + size.y) as f32,
+      );
+
+      self.textures.write_layer_pixels(layer, &region, &entry.pixels);
+      self.pages[layer as usize].sprites.push(sprite.0);
+
+      entry.placement = Some((layer, offset));
+    }
+
+    let entry = &self.sprites[sprite.0];
+    let (layer, offset) = entry.placement?;
+
+    self.pages[layer as usize].last_used = self.frame;
+
+    Some(PagedRegion {
+      layer,
+      uv: Rectangle::from_corner_points(
+        offset.x as f32 / page_size.x as f32,
+        offset.y as f32 / page_size.y as f32,
+        (offset.x + entry.size.x) as f32 / page_size.x as f32,
+        (offset.y + entry.size.y) as f32 / page_size.y as f32,
+      ),
+      size: entry.size,
+    })
+  }
+
+  /// Packs into the first page with room.
+  fn pack(&mut self, size: UVec2, page_size: UVec2) -> Option<(u32, UVec2)> {
+    self
+      .pages
+      .iter_mut()
+      .enumerate()
+      .find_map(|(layer, page)| Some((layer as u32, page.try_pack(size, page_size)?)))
+  }
+
+  /// Empties the least recently used page that wasn't used this frame.
+  fn evict_least_recently_used(&mut self) -> Option<u32> {
+    let frame = self.frame;
+    let (layer, page) = self
+      .pages
+      .iter_mut()
+      .enumerate()
+      .filter(|(_, page)| page.last_used < frame)
+      .min_by_key(|(_, page)| page.last_used)?;
+
+    for sprite in page.sprites.drain(..) {
+      self.sprites[sprite].placement = None;
+    }
+
+    *page = Page::default();
+
+    Some(layer as u32)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_pack_sprites_onto_shared_pages() {
+    let mut pager = SpritePager::new(uvec2(64, 64), 2).unwrap();
+    let sprites = (0..4).map(|_| pager.add(&Image::new(32, 32))).collect::<Vec<_>>();
+
+    let regions = sprites
+      .iter()
+      .map(|&sprite| pager.request(sprite).unwrap())
+      .collect::<Vec<_>>();
+
+    assert!(regions.iter().all(|region| region.layer == 0));
+    assert_eq!(pager.resident_pages(), 1);
+    assert_eq!(regions[3].uv.left(), 0.5);
+    assert_eq!(regions[3].uv.top(), 0.5);
+  }
+
+  #[test]
+  fn it_should_evict_the_least_recently_used_page() {
+    let mut pager = SpritePager::new(uvec2(32, 32), 2).unwrap();
+
+    let a = pager.add(&Image::new(32, 32));
+    let b = pager.add(&Image::new(32, 32));
+    let c = pager.add(&Image::new(32, 32));
+
+    pager.request(a).unwrap();
+    pager.begin_frame();
+    pager.request(b).unwrap();
+    pager.begin_frame();
+
+    // both pages are full; a was used longest ago
+    assert_eq!(pager.request(c).unwrap().layer, 0);
+    assert!(!pager.is_resident(a));
+    assert!(pager.is_resident(b));
+
+    // everything is in use this frame, so nothing can be evicted
+    pager.request(b).unwrap();
+
+    assert!(pager.request(a).is_none());
+  }
+
+  #[test]
+  fn it_should_batch_paged_sprites_across_pages() {
+    let mut pager = SpritePager::new(uvec2(16, 16), 4).unwrap();
+    let mut batch = SpriteBatch::new().unwrap();
+    let material = SHADER_SPRITE_PAGED.to_material().unwrap();
+
+    let sprites = (0..8).map(|_| pager.add(&Image::new(16, 16))).collect::<Vec<_>>();
+
+    batch.begin(&material);
+
+    for sprite in sprites {
+      batch.draw_paged(&mut pager, sprite, &SpriteOptions::default());
+    }
+
+    batch.flush();
+
+    assert_eq!(pager.resident_pages(), 4);
+  }
+}
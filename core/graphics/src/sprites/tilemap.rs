@@ -0,0 +1,324 @@
+//! Dual-mode tile map rendering; ASCII glyphs or graphical tiles.
+
+use std::{fmt::Write, hash::Hash};
+
+use common::{ivec2, uvec2, vec2, Color32, IVec2, Tile, TileGrid, UVec2, Vec2};
+
+use super::*;
+
+/// How a [`TileMapRenderer`] presents tiles.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TileRenderMode {
+  /// Colored glyphs from a code page 437 font sheet.
+  #[default]
+  Glyphs,
+  /// Cells from a graphical tileset.
+  Tiles,
+}
+
+/// How a single tile looks, in both render modes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TileVisual {
+  pub glyph: char,
+  pub foreground: Color32,
+  /// The color behind the glyph; fully transparent to draw nothing.
+  pub background: Color32,
+  /// The cell in the tileset to use in [`TileRenderMode::Tiles`].
+  pub tile: UVec2,
+}
+
+impl Default for TileVisual {
+  fn default() -> Self {
+    Self {
+      glyph: ' ',
+      foreground: Color32::WHITE,
+      background: Color32::CLEAR,
+      tile: UVec2::ZERO,
+    }
+  }
+}
+
+/// A source of tiles to render.
+pub trait TileView {
+  /// The size of the view, in tiles.
+  fn size(&self) -> UVec2;
+
+  /// How the tile at the given position looks, or `None` to draw nothing.
+  fn visual_at(&self, position: IVec2) -> Option<TileVisual>;
+}
+
+/// Adapts a [`TileGrid`] into a [`TileView`] with a styling function.
+///
+/// The style is given each tile and the actor standing on it, if any.
+pub struct TileGridView<'a, A, F> {
+  grid: &'a TileGrid<A>,
+  style: F,
+}
+
+impl<'a, A, F> TileGridView<'a, A, F>
+where
+  A: Copy + Eq + Hash,
+  F: Fn(IVec2, &Tile, Option<A>) -> Option<TileVisual>,
+{
+  /// Creates a view over the given grid.
+  pub fn new(grid: &'a TileGrid<A>, style: F) -> Self {
+    Self { grid, style }
+  }
+}
+
+impl<'a, A, F> TileView for TileGridView<'a, A, F>
+where
+  A: Copy + Eq + Hash,
+  F: Fn(IVec2, &Tile, Option<A>) -> Option<TileVisual>,
+{
+  fn size(&self) -> UVec2 {
+    uvec2(self.grid.width() as u32, self.grid.height() as u32)
+  }
+
+  fn visual_at(&self, position: IVec2) -> Option<TileVisual> {
+    let tile = self.grid.tile(position)?;
+
+    (self.style)(position, tile, self.grid.occupant(position))
+  }
+}
+
+/// The glyph cell used to fill tile backgrounds (a full block).
+const BACKGROUND_GLYPH: char = '█';
+
+/// Renders a [`TileView`] as either glyphs or tiles, switchable at runtime.
+///
+/// Both modes draw from the same [`TileVisual`]s, so a game can offer a
+/// classic ASCII presentation and a graphical one over the same map.
+pub struct TileMapRenderer {
+  mode: TileRenderMode,
+  glyphs: TextureAtlas,
+  tiles: Option<TextureAtlas>,
+  /// The size of each tile on screen, in world units.
+  pub cell_size: Vec2,
+}
+
+impl TileMapRenderer {
+  /// Creates a renderer from a 16x16 code page 437 glyph sheet.
+  pub fn new(glyph_sheet: &Texture, cell_size: Vec2) -> Self {
+    let glyph_size = uvec2(glyph_sheet.width() / 16, glyph_sheet.height() / 16);
+
+    Self {
+      mode: TileRenderMode::Glyphs,
+      glyphs: TextureAtlas::new(glyph_sheet.to_region(), glyph_size),
+      tiles: None,
+      cell_size,
+    }
+  }
+
+  /// Adds a graphical tileset, with cells of the given size in pixels.
+  pub fn with_tileset(mut self, tileset: &Texture, tile_size: UVec2) -> Self {
+    self.tiles = Some(TextureAtlas::new(tileset.to_region(), tile_size));
+    self
+  }
+
+  /// The current render mode.
+  pub fn mode(&self) -> TileRenderMode {
+    self.mode
+  }
+
+  /// Changes the render mode.
+  ///
+  /// Falls back to glyphs if tiles are requested without a tileset.
+  pub fn set_mode(&mut self, mode: TileRenderMode) {
+    self.mode = match mode {
+      TileRenderMode::Tiles if self.tiles.is_none() => TileRenderMode::Glyphs,
+      mode => mode,
+    };
+  }
+
+  /// Switches between glyphs and tiles.
+  pub fn toggle_mode(&mut self) {
+    self.set_mode(match self.mode {
+      TileRenderMode::Glyphs => TileRenderMode::Tiles,
+      TileRenderMode::Tiles => TileRenderMode::Glyphs,
+    });
+  }
+
+  /// Draws the view into the given batch, with its first tile at the origin.
+  pub fn draw(&self, batch: &mut SpriteBatch, view: &dyn TileView, origin: Vec2) {
+    let size = view.size();
+
+    for y in 0..size.y as i32 {
+      for x in 0..size.x as i32 {
+        let Some(visual) = view.visual_at(ivec2(x, y)) else {
+          continue;
+        };
+
+        let position = origin + vec2(x as f32 + 0.5, y as f32 + 0.5) * self.cell_size;
+
+        for (region, color) in self.layers(&visual) {
+          batch.draw_sprite(&region, &SpriteOptions {
+            position,
+            scale: self.cell_size / region.size.as_vec2(),
+            color,
+            ..Default::default()
+          });
+        }
+      }
+    }
+  }
+
+  /// The regions and tints to draw for a single tile, back to front.
+  fn layers(&self, visual: &TileVisual) -> Vec<(TextureRegion, Color32)> {
+    match (self.mode, &self.tiles) {
+      (TileRenderMode::Tiles, Some(tiles)) => vec![(tiles.slice(visual.tile.x, visual.tile.y), Color32::WHITE)],
+      _ => {
+        let mut layers = Vec::with_capacity(2);
+
+        if visual.background.a > 0 {
+          layers.push((self.glyph(BACKGROUND_GLYPH), visual.background));
+        }
+
+        if visual.glyph != ' ' {
+          layers.push((self.glyph(visual.glyph), visual.foreground));
+        }
+
+        layers
+      }
+    }
+  }
+
+  fn glyph(&self, glyph: char) -> TextureRegion {
+    let cell = glyph_cell(glyph);
+
+    self.glyphs.slice(cell.x, cell.y)
+  }
+}
+
+/// Finds the cell for a character in a 16x16 code page 437 glyph sheet.
+///
+/// Characters outside the code page are drawn as `?`.
+pub fn glyph_cell(glyph: char) -> UVec2 {
+  let index = match glyph {
+    '\u{0}'..='\u{7f}' => glyph as u32,
+    '░' => 176,
+    '▒' => 177,
+    '▓' => 178,
+    '│' => 179,
+    '┐' => 191,
+    '└' => 192,
+    '─' => 196,
+    '┘' => 217,
+    '┌' => 218,
+    '█' => 219,
+    '·' => 250,
+    _ => '?' as u32,
+  };
+
+  uvec2(index % 16, index / 16)
+}
+
+/// Renders the view as text for a terminal, using 24-bit ANSI colors.
+pub fn render_ansi(view: &dyn TileView) -> String {
+  let size = view.size();
+  let mut output = String::new();
+
+  for y in 0..size.y as i32 {
+    for x in 0..size.x as i32 {
+      let visual = view.visual_at(ivec2(x, y)).unwrap_or_default();
+      let Color32 { r, g, b, .. } = visual.foreground;
+
+      write!(output, "\x1b[38;2;{r};{g};{b}m").unwrap();
+
+      if visual.background.a > 0 {
+        let Color32 { r, g, b, .. } = visual.background;
+
+        write!(output, "\x1b[48;2;{r};{g};{b}m").unwrap();
+      } else {
+        output.push_str("\x1b[49m");
+      }
+
+      output.push(visual.glyph);
+    }
+
+    output.push_str("\x1b[0m\n");
+  }
+
+  output
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn create_view(
+    grid: &TileGrid<u32>,
+  ) -> TileGridView<'_, u32, impl Fn(IVec2, &Tile, Option<u32>) -> Option<TileVisual>> {
+    TileGridView::new(grid, |_, tile, actor| {
+      Some(match (tile.walkable, actor) {
+        (_, Some(_)) => TileVisual {
+          glyph: '@',
+          tile: uvec2(1, 0),
+          ..Default::default()
+        },
+        (true, None) => TileVisual {
+          glyph: '.',
+          ..Default::default()
+        },
+        (false, None) => TileVisual {
+          glyph: '#',
+          background: Color32::rgb(64, 64, 64),
+          tile: uvec2(2, 0),
+          ..Default::default()
+        },
+      })
+    })
+  }
+
+  #[test]
+  fn it_should_map_glyphs_to_code_page_cells() {
+    assert_eq!(glyph_cell('@'), uvec2(0, 4));
+    assert_eq!(glyph_cell('█'), uvec2(11, 13));
+    assert_eq!(glyph_cell('λ'), glyph_cell('?'));
+  }
+
+  #[test]
+  fn it_should_render_tile_grids_as_ansi_text() {
+    let mut grid = TileGrid::new(3, 1);
+
+    grid.set_tile(ivec2(2, 0), Tile::WALL);
+    grid.place(7, ivec2(0, 0)).unwrap();
+
+    let output = render_ansi(&create_view(&grid));
+    let glyphs: String = output.chars().filter(|c| matches!(c, '@' | '.' | '#')).collect();
+
+    assert_eq!(glyphs, "@.#");
+    assert!(output.contains("\x1b[48;2;64;64;64m#"));
+    assert!(output.ends_with("\x1b[0m\n"));
+  }
+
+  #[test]
+  fn it_should_switch_between_glyphs_and_tiles() {
+    let glyphs = Texture::new(128, 128, &TextureOptions::default()).unwrap();
+    let tiles = Texture::new(64, 16, &TextureOptions::default()).unwrap();
+
+    let mut renderer = TileMapRenderer::new(&glyphs, vec2(1., 1.));
+    let wall = TileVisual {
+      glyph: '#',
+      background: Color32::rgb(64, 64, 64),
+      tile: uvec2(2, 0),
+      ..Default::default()
+    };
+
+    // no tileset yet, so we stay on glyphs
+    renderer.toggle_mode();
+    assert_eq!(renderer.mode(), TileRenderMode::Glyphs);
+
+    let layers = renderer.layers(&wall);
+    assert_eq!(layers.len(), 2);
+    assert_eq!(layers[1].0.offset, uvec2(3 * 8, 2 * 8));
+
+    renderer = renderer.with_tileset(&tiles, uvec2(16, 16));
+    renderer.toggle_mode();
+    assert_eq!(renderer.mode(), TileRenderMode::Tiles);
+
+    let layers = renderer.layers(&wall);
+    assert_eq!(layers.len(), 1);
+    assert_eq!(layers[0].0.offset, uvec2(32, 0));
+  }
+}
@@ -84,7 +84,7 @@ impl RenderTarget {
       .and_then(|it| it.to_texture().ok());
 
     Ok(Self {
-      state: internal::GraphicsCell::new(RenderTargetState {
+      state: internal::GraphicsCell::restorable(RenderTargetState {
         id: graphics().target_create(
           color_attachment.id(),
           depth_attachment.as_ref().map(|it| it.id()),
@@ -139,10 +139,25 @@ impl RenderTarget {
   }
 }
 
+impl Restore for RenderTargetState {
+  const ORDER: RestoreOrder = RestoreOrder::Target;
+
+  fn restore(&mut self) -> Result<(), GraphicsError> {
+    // the attachments are restored first
+    self.id = graphics().target_create(
+      self.color_attachment.id(),
+      self.depth_attachment.as_ref().map(|it| it.id()),
+      self.stencil_attachment.as_ref().map(|it| it.id()),
+    )?;
+
+    Ok(())
+  }
+}
+
 impl Drop for RenderTargetState {
   fn drop(&mut self) {
-    graphics()
-      .target_delete(self.id)
-      .expect("Failed to delete render target");
+    let id = self.id;
+
+    GraphicsServer::defer(move |graphics| graphics.target_delete(id).expect("Failed to delete render target"));
   }
 }
@@ -1,9 +1,16 @@
 //! Texture management and loading.
 
-use common::{uvec2, Color, Color32, Pixel, Rectangle, ToVirtualPath, UVec2};
+pub use arrays::*;
+use common::{uvec2, Color, Color32, Pixel, Rectangle, ToVirtualPath, UVec2, VirtualPath};
+pub use painting::*;
+pub use streaming::*;
 
 use super::*;
 
+mod arrays;
+mod painting;
+mod streaming;
+
 /// Different supported texture formats.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum TextureFormat {
@@ -19,6 +26,21 @@ pub enum TextureFormat {
   A32,
 }
 
+impl TextureFormat {
+  /// The size of a single pixel in this format, in bytes.
+  pub const fn bytes_per_pixel(&self) -> usize {
+    match self {
+      Self::R8 | Self::A8 => 1,
+      Self::RG8 => 2,
+      Self::RGB8 => 3,
+      Self::RGBA8 | Self::R32 | Self::A32 => 4,
+      Self::RG32 => 8,
+      Self::RGB32 => 12,
+      Self::RGBA32 => 16,
+    }
+  }
+}
+
 /// Texture wrapping modes modes.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum TextureWrap {
@@ -74,30 +96,45 @@ struct TextureState {
   options: TextureOptions,
   width: u32,
   height: u32,
+  /// The number of layers, for texture arrays.
+  layers: Option<u32>,
+  /// The image the texture was loaded from, to reload if the device is lost.
+  source: Option<VirtualPath>,
 }
 
 impl Texture {
   /// Creates a new blank texture on the GPU with default options.
   pub fn new(width: u32, height: u32, options: &TextureOptions) -> Result<Self, TextureError> {
-    let texture = Self {
-      state: internal::GraphicsCell::new(TextureState {
+    let texture = Self::allocate(width, height, options)?;
+
+    texture.initialize(width, height, options.format);
+
+    Ok(texture)
+  }
+
+  /// Creates a new texture on the GPU without any storage for its mips.
+  fn allocate(width: u32, height: u32, options: &TextureOptions) -> Result<Self, TextureError> {
+    Ok(Self {
+      state: internal::GraphicsCell::restorable(TextureState {
         id: graphics().texture_create(&options.sampler)?,
         options: options.clone(),
         width,
         height,
+        layers: None,
+        source: None,
       }),
-    };
-
-    texture.initialize(width, height, options.format);
-
-    Ok(texture)
+    })
   }
 
   /// Loads a texture from the given path.
   pub fn from_path(path: impl ToVirtualPath) -> Result<Self, TextureError> {
-    let image = Image::<Color32>::from_path(path).map_err(TextureError::InvalidImage)?;
+    let path = path.to_virtual_path();
+    let image = Image::<Color32>::from_path(path.clone()).map_err(TextureError::InvalidImage)?;
+    let texture = Self::from_image(&image)?;
 
-    Self::from_image(&image)
+    texture.state.write().source = Some(path);
+
+    Ok(texture)
   }
 
   /// Loads a texture from the given image.
@@ -235,6 +272,35 @@ impl Texture {
       .expect("Failed to write texture data");
   }
 
+  /// Uploads raw pixel data, in the texture's format, to a single mip level.
+  ///
+  /// Writing an empty slice releases the storage for that level.
+  pub fn write_mip_bytes(&self, mip_level: usize, width: u32, height: u32, pixels: &[u8]) {
+    let state = self.state.read();
+
+    graphics()
+      .texture_write_data(
+        state.id,
+        width,
+        height,
+        match pixels.len() {
+          0 => std::ptr::null(),
+          _ => pixels.as_ptr(),
+        },
+        state.options.format,
+        state.options.format,
+        mip_level,
+      )
+      .expect("Failed to write texture data");
+  }
+
+  /// Limits sampling to the given (inclusive) range of mip levels.
+  pub fn set_mip_range(&self, base_level: usize, max_level: usize) {
+    graphics()
+      .texture_set_mip_range(self.id(), base_level, max_level)
+      .expect("Failed to set texture mip range");
+  }
+
   /// Uploads a subsection of pixel data to the texture.
   pub fn write_sub_pixels<T: Texel>(&self, region: &Rectangle, pixels: &[T]) {
     let state = self.state.read();
@@ -251,9 +317,47 @@ impl Texture {
   }
 }
 
+impl Restore for TextureState {
+  const ORDER: RestoreOrder = RestoreOrder::Texture;
+
+  fn restore(&mut self) -> Result<(), GraphicsError> {
+    let graphics = graphics();
+
+    self.id = graphics.texture_create(&self.options.sampler)?;
+
+    if let Some(layers) = self.layers {
+      graphics.texture_initialize_layers(self.id, self.width, self.height, layers, self.options.format)?;
+    } else if self.width > 0 && self.height > 0 {
+      graphics.texture_initialize(self.id, self.width, self.height, self.options.format)?;
+    }
+
+    // other pixels were uploaded at runtime; owners re-upload them
+    if let Some(path) = &self.source {
+      let image = Image::<Color32>::from_path(path.clone()).map_err(TextureError::InvalidImage)?;
+
+      self.width = image.width();
+      self.height = image.height();
+
+      graphics.texture_write_data(
+        self.id,
+        self.width,
+        self.height,
+        image.as_slice().as_ptr() as *const u8,
+        self.options.format,
+        Color32::FORMAT,
+        0, // mip level
+      )?;
+    }
+
+    Ok(())
+  }
+}
+
 impl Drop for TextureState {
   fn drop(&mut self) {
-    graphics().texture_delete(self.id).expect("Failed to delete texture");
+    let id = self.id;
+
+    GraphicsServer::defer(move |graphics| graphics.texture_delete(id).expect("Failed to delete texture"));
   }
 }
 
@@ -0,0 +1,65 @@
+//! Texture arrays, for drawing from many same-sized layers with one binding.
+
+use super::*;
+
+/// A set of same-sized texture layers that are bound together as one texture.
+///
+/// Shaders sample these with a `sampler2DArray` and a layer index, so draws
+/// that use different layers don't need to switch textures.
+#[derive(Clone)]
+pub struct TextureArray {
+  texture: Texture,
+  layers: u32,
+}
+
+impl TextureArray {
+  /// Creates a new texture array with the given size and number of layers.
+  pub fn new(width: u32, height: u32, layers: u32, options: &TextureOptions) -> Result<Self, TextureError> {
+    let texture = Texture::allocate(width, height, options)?;
+
+    graphics().texture_initialize_layers(texture.id(), width, height, layers, options.format)?;
+
+    texture.state.write().layers = Some(layers);
+
+    Ok(Self { texture, layers })
+  }
+
+  /// Returns the [`TextureId`] of the underlying texture.
+  pub fn id(&self) -> TextureId {
+    self.texture.id()
+  }
+
+  /// Returns the width of each layer.
+  pub fn width(&self) -> u32 {
+    self.texture.width()
+  }
+
+  /// Returns the height of each layer.
+  pub fn height(&self) -> u32 {
+    self.texture.height()
+  }
+
+  /// Returns the number of layers.
+  pub fn layers(&self) -> u32 {
+    self.layers
+  }
+
+  /// Returns the format of the layers.
+  pub fn format(&self) -> TextureFormat {
+    self.texture.format()
+  }
+
+  /// The underlying texture, for binding.
+  pub(crate) fn texture(&self) -> &Texture {
+    &self.texture
+  }
+
+  /// Uploads pixel data to a region of a single layer.
+  pub fn write_layer_pixels<T: Texel>(&self, layer: u32, region: &Rectangle, pixels: &[T]) {
+    debug_assert!(layer < self.layers, "layer {layer} is out of range");
+
+    graphics()
+      .texture_write_layer_data(self.id(), region, layer, pixels.as_ptr() as *const u8, T::FORMAT)
+      .expect("Failed to write texture layer data");
+  }
+}
@@ -0,0 +1,333 @@
+//! Textures that can be painted on at runtime.
+//!
+//! A [`PaintableTexture`] keeps a copy of its pixels on the CPU, so brushes
+//! can blend into it cheaply. Only the region touched since the last
+//! [`PaintableTexture::flush`] is uploaded, which keeps things like fog of war
+//! reveal maps, decal layers and drawing minigames cheap to update every frame.
+
+use common::{vec2, Color, Lerp, Vec2};
+
+use super::*;
+
+/// How a brush combines with the pixels beneath it.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PaintBlend {
+  /// Paints over the existing pixels, respecting the brush's alpha.
+  #[default]
+  Normal,
+  /// Replaces the existing pixels, alpha included.
+  Replace,
+  /// Adds the brush color to the existing pixels.
+  Add,
+  /// Erases the existing pixels towards transparent.
+  Erase,
+}
+
+/// The shape of a brush.
+#[derive(Copy, Clone)]
+pub enum BrushShape<'a> {
+  /// A circle that fades out from its hard core to its radius.
+  ///
+  /// A hardness of 1 gives a hard edge, and 0 fades all the way from the
+  /// center.
+  Circle { radius: f32, hardness: f32 },
+  /// An image stamped at its own size, tinted by the brush color.
+  Stamp(&'a Image<Color32>),
+}
+
+/// A brush to paint with.
+#[derive(Copy, Clone)]
+pub struct Brush<'a> {
+  pub shape: BrushShape<'a>,
+  pub color: Color32,
+  pub opacity: f32,
+  pub blend: PaintBlend,
+  /// The distance between dabs along a stroke, as a fraction of the brush
+  /// size.
+  pub spacing: f32,
+}
+
+impl<'a> Brush<'a> {
+  /// Creates a hard round brush of the given radius and color.
+  pub fn circle(radius: f32, color: Color32) -> Self {
+    Self {
+      shape: BrushShape::Circle { radius, hardness: 1. },
+      color,
+      opacity: 1.,
+      blend: PaintBlend::Normal,
+      spacing: 0.25,
+    }
+  }
+
+  /// Creates a brush that stamps the given image.
+  pub fn stamp(image: &'a Image<Color32>) -> Self {
+    Self {
+      shape: BrushShape::Stamp(image),
+      color: Color32::WHITE,
+      opacity: 1.,
+      blend: PaintBlend::Normal,
+      spacing: 0.25,
+    }
+  }
+
+  /// The half-size of the area the brush covers, in pixels.
+  fn extents(&self) -> Vec2 {
+    match self.shape {
+      BrushShape::Circle { radius, .. } => vec2(radius, radius),
+      BrushShape::Stamp(image) => vec2(image.width() as f32, image.height() as f32) / 2.,
+    }
+  }
+
+  /// The brush color and how strongly it covers the pixel at the given offset
+  /// from its center.
+  fn sample(&self, offset: Vec2) -> (Color, f32) {
+    let color = Color::from(self.color);
+
+    match self.shape {
+      BrushShape::Circle { radius, hardness } => {
+        let distance = offset.length() / radius.max(f32::EPSILON);
+        let hardness = hardness.clamp(0., 1.);
+
+        let coverage = if distance <= hardness {
+          1.
+        } else {
+          ((1. - distance) / (1. - hardness).max(f32::EPSILON)).clamp(0., 1.)
+        };
+
+        (color, coverage * self.opacity)
+      }
+      BrushShape::Stamp(image) => {
+        let point = offset + self.extents();
+
+        if point.x < 0. || point.y < 0. {
+          return (color, 0.);
+        }
+
+        let texel = Color::from(image.get_pixel(point.x as u32, point.y as u32));
+
+        (color * texel, texel.a * self.opacity)
+      }
+    }
+  }
+}
+
+/// A texture that can be painted on with brushes at runtime.
+pub struct PaintableTexture {
+  texture: Texture,
+  image: Image<Color32>,
+  dirty: Option<(UVec2, UVec2)>,
+  generation: u64,
+}
+
+impl PaintableTexture {
+  /// Creates a new, transparent paintable texture.
+  pub fn new(width: u32, height: u32) -> Result<Self, TextureError> {
+    Self::from_image(Image::new(width, height))
+  }
+
+  /// Creates a paintable texture starting from the given image.
+  pub fn from_image(image: Image<Color32>) -> Result<Self, TextureError> {
+    let texture = Texture::from_image(&image)?;
+
+    Ok(Self {
+      texture,
+      image,
+      dirty: None,
+      generation: device_generation(),
+    })
+  }
+
+  /// The texture being painted on.
+  pub fn texture(&self) -> &Texture {
+    &self.texture
+  }
+
+  /// The painted pixels, including any that haven't been flushed yet.
+  pub fn image(&self) -> &Image<Color32> {
+    &self.image
+  }
+
+  /// The region painted since the last flush, in pixels.
+  pub fn dirty_region(&self) -> Option<Rectangle> {
+    self
+      .dirty
+      .map(|(min, max)| Rectangle::new(min.as_vec2(), max.as_vec2()))
+  }
+
+  /// Fills the whole texture with a color.
+  pub fn fill(&mut self, color: Color32) {
+    self.image.as_slice_mut().fill(color);
+    self.mark_dirty(UVec2::ZERO, uvec2(self.image.width(), self.image.height()));
+  }
+
+  /// Stamps the brush once, centered on the given pixel position.
+  pub fn dab(&mut self, position: Vec2, brush: &Brush) {
+    let extents = brush.extents();
+    let size = vec2(self.image.width() as f32, self.image.height() as f32);
+
+    let min = (position - extents).floor().clamp(Vec2::ZERO, size);
+    let max = (position + extents).ceil().clamp(Vec2::ZERO, size);
+
+    if min.x >= max.x || min.y >= max.y {
+      return;
+    }
+
+    let (min, max) = (min.as_uvec2(), max.as_uvec2());
+
+    for y in min.y..max.y {
+      for x in min.x..max.x {
+        let offset = vec2(x as f32, y as f32) + 0.5 - position;
+        let (color, coverage) = brush.sample(offset);
+
+        if coverage <= 0. {
+          continue;
+        }
+
+        let pixel = Color::from(self.image.get_pixel(x, y));
+        let pixel = blend(pixel, color, coverage.min(1.), brush.blend);
+
+        self.image.set_pixel(x, y, Color32::from(pixel));
+      }
+    }
+
+    self.mark_dirty(min, max);
+  }
+
+  /// Paints a line of dabs from one pixel position to another.
+  pub fn stroke(&mut self, from: Vec2, to: Vec2, brush: &Brush) {
+    let spacing = (brush.extents().max_element() * 2. * brush.spacing).max(1.);
+    let steps = (from.distance(to) / spacing).ceil().max(1.) as usize;
+
+    for step in 0..=steps {
+      self.dab(from.lerp(to, step as f32 / steps as f32), brush);
+    }
+  }
+
+  /// Uploads the painted region to the texture.
+  ///
+  /// If the device was restored since the last flush, the whole image is
+  /// uploaded again.
+  pub fn flush(&mut self) {
+    if self.generation != device_generation() {
+      self.generation = device_generation();
+      self.mark_dirty(UVec2::ZERO, uvec2(self.image.width(), self.image.height()));
+    }
+
+    let Some((min, max)) = self.dirty.take() else {
+      return;
+    };
+
+    let width = self.image.width() as usize;
+    let mut pixels = Vec::with_capacity(((max.x - min.x) * (max.y - min.y)) as usize);
+
+    for y in min.y..max.y {
+      let row = y as usize * width;
+
+      pixels.extend_from_slice(&self.image.as_slice()[row + min.x as usize..row + max.x as usize]);
+    }
+
+    let region = Rectangle::new(min.as_vec2(), max.as_vec2());
+
+    self.texture.write_sub_pixels(&region, &pixels);
+  }
+
+  /// Grows the dirty region to include the given pixels.
+  fn mark_dirty(&mut self, min: UVec2, max: UVec2) {
+    self.dirty = Some(match self.dirty {
+      Some((dirty_min, dirty_max)) => (dirty_min.min(min), dirty_max.max(max)),
+      None => (min, max),
+    });
+  }
+}
+
+/// Blends a brush color into a pixel with the given coverage.
+fn blend(pixel: Color, color: Color, coverage: f32, mode: PaintBlend) -> Color {
+  match mode {
+    PaintBlend::Normal => {
+      let alpha = color.a * coverage;
+      let mut result = Color::lerp(pixel, color, alpha);
+
+      result.a = pixel.a + alpha * (1. - pixel.a);
+      result
+    }
+    PaintBlend::Replace => Color::lerp(pixel, color, coverage),
+    PaintBlend::Add => {
+      let added = pixel + color * (color.a * coverage);
+
+      Color::rgba(added.r.min(1.), added.g.min(1.), added.b.min(1.), pixel.a)
+    }
+    PaintBlend::Erase => Color::rgba(pixel.r, pixel.g, pixel.b, pixel.a * (1. - coverage)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_paint_circles_and_track_dirty_regions() {
+    let mut canvas = PaintableTexture::new(32, 32).unwrap();
+
+    canvas.dab(vec2(8., 8.), &Brush::circle(3., Color32::RED));
+
+    assert_eq!(canvas.image().get_pixel(8, 8), Color32::RED);
+    assert_eq!(canvas.image().get_pixel(8, 12), Color32::CLEAR);
+    assert_eq!(
+      canvas.dirty_region(),
+      Some(Rectangle::new(vec2(5., 5.), vec2(11., 11.)))
+    );
+
+    canvas.stroke(vec2(8., 8.), vec2(24., 8.), &Brush::circle(2., Color32::BLUE));
+
+    assert_eq!(canvas.image().get_pixel(16, 8), Color32::BLUE);
+    assert_eq!(
+      canvas.dirty_region(),
+      Some(Rectangle::new(vec2(5., 5.), vec2(26., 11.)))
+    );
+
+    canvas.flush();
+
+    assert_eq!(canvas.dirty_region(), None);
+  }
+
+  #[test]
+  fn it_should_blend_brushes_with_existing_pixels() {
+    let mut canvas = PaintableTexture::new(8, 8).unwrap();
+
+    canvas.fill(Color32::BLACK);
+
+    let mut brush = Brush::circle(8., Color32::WHITE);
+
+    brush.opacity = 0.5;
+    canvas.dab(vec2(4., 4.), &brush);
+
+    assert_eq!(canvas.image().get_pixel(4, 4), Color32::rgb(127, 127, 127));
+
+    brush.opacity = 1.;
+    brush.blend = PaintBlend::Erase;
+    canvas.dab(vec2(4., 4.), &brush);
+
+    assert_eq!(canvas.image().get_pixel(4, 4).a, 0);
+  }
+
+  #[test]
+  fn it_should_stamp_images_clipped_to_the_texture() {
+    let mut canvas = PaintableTexture::new(8, 8).unwrap();
+    let mut stamp = Image::new(4, 4);
+
+    stamp.as_slice_mut().fill(Color32::WHITE);
+    stamp.set_pixel(1, 1, Color32::CLEAR);
+
+    let mut brush = Brush::stamp(&stamp);
+
+    brush.color = Color32::GREEN;
+    canvas.dab(vec2(1., 1.), &brush);
+
+    // the stamp hangs off the top left corner, so its clear pixel lands on (0,
+    // 0)
+    assert_eq!(canvas.image().get_pixel(0, 0), Color32::CLEAR);
+    assert_eq!(canvas.image().get_pixel(2, 2), Color32::GREEN);
+    assert_eq!(canvas.image().get_pixel(3, 3), Color32::CLEAR);
+    assert_eq!(canvas.dirty_region(), Some(Rectangle::new(vec2(0., 0.), vec2(3., 3.))));
+  }
+}
@@ -0,0 +1,454 @@
+//! Texture streaming, with per-mip residency.
+//!
+//! Large textures only need their finest mips when they cover a lot of the
+//! screen. A [`TextureStreamer`] uploads the coarsest few mips of each texture
+//! up front, then streams finer mips in on worker threads as they're needed,
+//! and back out again when they aren't, keeping everything within a memory
+//! budget.
+//!
+//! Mips are always made resident from coarse to fine, and the texture's mip
+//! range is only widened once a level is on the GPU, so sampling never sees a
+//! missing level.
+
+use std::sync::{mpsc, Arc};
+
+use super::*;
+
+/// The default number of coarse mips that are always resident.
+const DEFAULT_RESIDENT_MIPS: usize = 4;
+
+/// A source of mip levels for a streamed texture, e.g. a file on disk.
+///
+/// Level 0 is the full-size image; each level after it is half the size of
+/// the one before. Mips are loaded on worker threads.
+pub trait MipSource: Send + Sync {
+  /// The format of the pixel data.
+  fn format(&self) -> TextureFormat;
+
+  /// The number of mip levels.
+  fn mip_count(&self) -> usize;
+
+  /// The size, in pixels, of the given mip level.
+  fn mip_size(&self, level: usize) -> UVec2;
+
+  /// The size, in bytes, of the given mip level.
+  fn mip_bytes(&self, level: usize) -> usize;
+
+  /// Loads the pixel data for the given mip level.
+  fn load_mip(&self, level: usize) -> Result<Vec<u8>, TextureError>;
+}
+
+/// A full chain of mips held in memory.
+#[derive(Clone, Debug)]
+pub struct MipChain {
+  format: TextureFormat,
+  levels: Vec<(UVec2, Vec<u8>)>,
+}
+
+impl MipChain {
+  /// Creates a chain from pre-built levels, finest first.
+  pub fn new(format: TextureFormat, levels: Vec<(UVec2, Vec<u8>)>) -> Self {
+    Self { format, levels }
+  }
+
+  /// Builds a chain from an image by repeatedly box-filtering it down to 1x1.
+  pub fn from_image(image: &Image<Color32>) -> Self {
+    let mut size = uvec2(image.width(), image.height());
+    let mut pixels = image.as_slice().to_vec();
+    let mut levels = Vec::new();
+
+    loop {
+      levels.push((size, pixels.iter().flat_map(|it| [it.r, it.g, it.b, it.a]).collect()));
+
+      if size.x <= 1 && size.y <= 1 {
+        break;
+      }
+
+      let next = uvec2((size.x / 2).max(1), (size.y / 2).max(1));
+      let mut next_pixels = Vec::with_capacity((next.x * next.y) as usize);
+
+      for y in 0..next.y {
+        for x in 0..next.x {
+          let mut sum = [0u32; 4];
+
+          for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let sx = (x * 2 + dx).min(size.x - 1);
+            let sy = (y * 2 + dy).min(size.y - 1);
+            let pixel = pixels[(sx + sy * size.x) as usize];
+
+            sum[0] += pixel.r as u32;
+            sum[1] += pixel.g as u32;
+            sum[2] += pixel.b as u32;
+            sum[3] += pixel.a as u32;
+          }
+
+          next_pixels.push(Color32::rgba(
+            (sum[0] / 4) as u8,
+            (sum[1] / 4) as u8,
+            (sum[2] / 4) as u8,
+            (sum[3] / 4) as u8,
+          ));
+        }
+      }
+
+      size = next;
+      pixels = next_pixels;
+    }
+
+    Self::new(TextureFormat::RGBA8, levels)
+  }
+}
+
+impl MipSource for MipChain {
+  fn format(&self) -> TextureFormat {
+    self.format
+  }
+
+  fn mip_count(&self) -> usize {
+    self.levels.len()
+  }
+
+  fn mip_size(&self, level: usize) -> UVec2 {
+    self.levels[level].0
+  }
+
+  fn mip_bytes(&self, level: usize) -> usize {
+    self.levels[level].1.len()
+  }
+
+  fn load_mip(&self, level: usize) -> Result<Vec<u8>, TextureError> {
+    Ok(self.levels[level].1.clone())
+  }
+}
+
+/// A handle to a texture managed by a [`TextureStreamer`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct StreamingHandle(usize);
+
+/// A streamed texture and its residency.
+struct StreamingSlot {
+  texture: Texture,
+  source: Arc<dyn MipSource>,
+  /// The finest mip currently on the GPU.
+  resident_level: usize,
+  /// Mips from this level down are never evicted.
+  floor_level: usize,
+  /// The finest mip worth having at the current screen size.
+  desired_level: usize,
+  screen_size: f32,
+  priority: f32,
+  in_flight: Option<usize>,
+}
+
+impl StreamingSlot {
+  /// The bytes of all resident mips.
+  fn resident_bytes(&self) -> usize {
+    (self.resident_level..self.source.mip_count())
+      .map(|level| self.source.mip_bytes(level))
+      .sum()
+  }
+
+  /// How much this texture deserves its next mip.
+  fn score(&self) -> f32 {
+    self.priority * self.screen_size
+  }
+
+  /// Picks the finest mip that still has at least one texel per pixel.
+  fn calculate_desired_level(&self) -> usize {
+    if self.screen_size <= 0. {
+      return self.floor_level;
+    }
+
+    let size = self.source.mip_size(0);
+    let texels_per_pixel = size.x.max(size.y) as f32 / self.screen_size;
+
+    (texels_per_pixel.log2().floor().max(0.) as usize).min(self.floor_level)
+  }
+
+  /// Uploads a freshly loaded mip and makes it visible.
+  fn make_resident(&mut self, level: usize, pixels: &[u8]) {
+    let size = self.source.mip_size(level);
+
+    self.texture.write_mip_bytes(level, size.x, size.y, pixels);
+    self.texture.set_mip_range(level, self.source.mip_count() - 1);
+    self.resident_level = level;
+  }
+
+  /// Hides the finest resident mip and releases its storage.
+  fn evict(&mut self) -> usize {
+    let level = self.resident_level;
+
+    self.resident_level += 1;
+    self
+      .texture
+      .set_mip_range(self.resident_level, self.source.mip_count() - 1);
+    self.texture.write_mip_bytes(level, 0, 0, &[]);
+
+    self.source.mip_bytes(level)
+  }
+}
+
+/// A mip that finished loading on a worker thread.
+struct LoadedMip {
+  slot: usize,
+  level: usize,
+  result: Result<Vec<u8>, TextureError>,
+}
+
+/// Streams texture mips in and out based on their size on screen.
+///
+/// Each frame, report how large each texture appears via
+/// [`TextureStreamer::set_screen_size`], then call [`TextureStreamer::update`].
+pub struct TextureStreamer {
+  slots: Vec<StreamingSlot>,
+  budget: usize,
+  resident_mips: usize,
+  sender: mpsc::Sender<LoadedMip>,
+  receiver: mpsc::Receiver<LoadedMip>,
+}
+
+impl TextureStreamer {
+  /// Creates a streamer that keeps resident mips within the given budget, in
+  /// bytes.
+  ///
+  /// The always-resident coarse mips count towards the budget, but are never
+  /// evicted to satisfy it.
+  pub fn new(budget: usize) -> Self {
+    let (sender, receiver) = mpsc::channel();
+
+    Self {
+      slots: Vec::new(),
+      budget,
+      resident_mips: DEFAULT_RESIDENT_MIPS,
+      sender,
+      receiver,
+    }
+  }
+
+  /// Sets how many of the coarsest mips are loaded up front and kept resident.
+  pub fn with_resident_mips(mut self, resident_mips: usize) -> Self {
+    self.resident_mips = resident_mips.max(1);
+    self
+  }
+
+  /// Adds a texture, uploading its coarsest mips immediately.
+  pub fn add(&mut self, source: impl MipSource + 'static) -> Result<StreamingHandle, TextureError> {
+    let source: Arc<dyn MipSource> = Arc::new(source);
+    let mip_count = source.mip_count();
+    let floor_level = mip_count.saturating_sub(self.resident_mips);
+    let size = source.mip_size(0);
+
+    let texture = Texture::allocate(size.x, size.y, &TextureOptions {
+      format: source.format(),
+      ..TextureOptions::default()
+    })?;
+
+    let mut slot = StreamingSlot {
+      texture,
+      source: source.clone(),
+      resident_level: mip_count,
+      floor_level,
+      desired_level: floor_level,
+      screen_size: 0.,
+      priority: 1.,
+      in_flight: None,
+    };
+
+    for level in (floor_level..mip_count).rev() {
+      slot.make_resident(level, &source.load_mip(level)?);
+    }
+
+    self.slots.push(slot);
+
+    Ok(StreamingHandle(self.slots.len() - 1))
+  }
+
+  /// The texture for the given handle.
+  pub fn texture(&self, handle: StreamingHandle) -> &Texture {
+    &self.slots[handle.0].texture
+  }
+
+  /// The finest mip of the given texture that's currently on the GPU.
+  pub fn resident_level(&self, handle: StreamingHandle) -> usize {
+    self.slots[handle.0].resident_level
+  }
+
+  /// Sets how large, in pixels, the texture currently appears on screen.
+  ///
+  /// Use the largest size of any instance drawn this frame, or zero if it's
+  /// not visible at all.
+  pub fn set_screen_size(&mut self, handle: StreamingHandle, pixels: f32) {
+    self.slots[handle.0].screen_size = pixels;
+  }
+
+  /// Sets a priority hint for the texture, e.g. to favour the player's
+  /// character over background props. The default is 1.
+  pub fn set_priority(&mut self, handle: StreamingHandle, priority: f32) {
+    self.slots[handle.0].priority = priority;
+  }
+
+  /// The bytes of all resident mips.
+  pub fn resident_bytes(&self) -> usize {
+    self.slots.iter().map(StreamingSlot::resident_bytes).sum()
+  }
+
+  /// Is any mip still loading?
+  pub fn is_streaming(&self) -> bool {
+    self.slots.iter().any(|slot| slot.in_flight.is_some())
+  }
+
+  /// Uploads finished mips, evicts unneeded ones and requests new ones.
+  pub fn update(&mut self) {
+    while let Ok(loaded) = self.receiver.try_recv() {
+      let slot = &mut self.slots[loaded.slot];
+
+      slot.in_flight = None;
+
+      // a failed load leaves the texture on its coarser mips
+      if let Ok(pixels) = loaded.result {
+        if loaded.level + 1 == slot.resident_level && loaded.level >= slot.desired_level {
+          slot.make_resident(loaded.level, &pixels);
+        }
+      }
+    }
+
+    for slot in &mut self.slots {
+      slot.desired_level = slot.calculate_desired_level();
+
+      while slot.resident_level < slot.desired_level {
+        slot.evict();
+      }
+    }
+
+    let mut candidates = (0..self.slots.len())
+      .filter(|&index| {
+        let slot = &self.slots[index];
+        slot.in_flight.is_none() && slot.resident_level > slot.desired_level
+      })
+      .collect::<Vec<_>>();
+
+    candidates.sort_by(|&a, &b| self.slots[b].score().total_cmp(&self.slots[a].score()));
+
+    let mut used = self.resident_bytes()
+      + self
+        .slots
+        .iter()
+        .filter_map(|slot| slot.in_flight.map(|level| slot.source.mip_bytes(level)))
+        .sum::<usize>();
+
+    for index in candidates {
+      let level = self.slots[index].resident_level - 1;
+      let cost = self.slots[index].source.mip_bytes(level);
+
+      while used + cost > self.budget {
+        let Some(victim) = self.find_eviction_victim(self.slots[index].score()) else {
+          break;
+        };
+
+        used -= self.slots[victim].evict();
+      }
+
+      if used + cost > self.budget {
+        continue;
+      }
+
+      let slot = &mut self.slots[index];
+      let source = slot.source.clone();
+      let sender = self.sender.clone();
+
+      slot.in_flight = Some(level);
+      used += cost;
+
+      std::thread::spawn(move || {
+        let result = source.load_mip(level);
+
+        sender
+          .send(LoadedMip {
+            slot: index,
+            level,
+            result,
+          })
+          .ok();
+      });
+    }
+  }
+
+  /// Finds the least deserving texture with a streamed mip to give up.
+  fn find_eviction_victim(&self, score: f32) -> Option<usize> {
+    (0..self.slots.len())
+      .filter(|&index| {
+        let slot = &self.slots[index];
+        slot.in_flight.is_none() && slot.resident_level < slot.floor_level && slot.score() < score
+      })
+      .min_by(|&a, &b| self.slots[a].score().total_cmp(&self.slots[b].score()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn update_until_settled(streamer: &mut TextureStreamer) {
+    for _ in 0..1000 {
+      streamer.update();
+
+      if !streamer.is_streaming() {
+        streamer.update();
+
+        if !streamer.is_streaming() {
+          return;
+        }
+      }
+
+      std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+
+    panic!("streaming never settled");
+  }
+
+  #[test]
+  fn it_should_load_only_coarse_mips_up_front() {
+    let mut streamer = TextureStreamer::new(usize::MAX);
+    let chain = MipChain::from_image(&Image::new(256, 256));
+
+    assert_eq!(chain.mip_count(), 9);
+
+    let handle = streamer.add(chain).unwrap();
+
+    assert_eq!(streamer.resident_level(handle), 5);
+    assert_eq!(streamer.resident_bytes(), (8 * 8 + 4 * 4 + 2 * 2 + 1) * 4);
+  }
+
+  #[test]
+  fn it_should_stream_mips_by_screen_size() {
+    let mut streamer = TextureStreamer::new(usize::MAX);
+    let handle = streamer.add(MipChain::from_image(&Image::new(256, 256))).unwrap();
+
+    streamer.set_screen_size(handle, 64.);
+    update_until_settled(&mut streamer);
+
+    assert_eq!(streamer.resident_level(handle), 2);
+
+    streamer.set_screen_size(handle, 0.);
+    streamer.update();
+
+    assert_eq!(streamer.resident_level(handle), 5);
+  }
+
+  #[test]
+  fn it_should_favour_higher_priority_textures_within_budget() {
+    let mut streamer = TextureStreamer::new(24000).with_resident_mips(1);
+    let hero = streamer.add(MipChain::from_image(&Image::new(64, 64))).unwrap();
+    let prop = streamer.add(MipChain::from_image(&Image::new(64, 64))).unwrap();
+
+    streamer.set_screen_size(hero, 64.);
+    streamer.set_screen_size(prop, 64.);
+    streamer.set_priority(hero, 2.);
+
+    update_until_settled(&mut streamer);
+
+    assert_eq!(streamer.resident_level(hero), 0);
+    assert!(streamer.resident_level(prop) > 0);
+    assert!(streamer.resident_bytes() <= 24000);
+  }
+}
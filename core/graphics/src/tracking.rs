@@ -0,0 +1,650 @@
+//! Tracking of live graphics resources, to find leaks.
+//!
+//! A [`TrackingGraphicsBackend`] wraps another backend and keeps a record of
+//! every buffer, texture, shader, mesh and target it creates until it's
+//! deleted, along with where it was created in debug builds. It also counts
+//! resources created and deleted each frame, and warns about deletes of
+//! resources it doesn't know about, which backends otherwise ignore, and
+//! the draw calls and triangles submitted each frame.
+//!
+//! Call [`ResourceTracker::report_leaks`] on shutdown to list whatever is
+//! still alive, and [`ResourceTracker::memory_report`] to see how much GPU
+//! memory they take.
+
+use std::{
+  backtrace::Backtrace,
+  fmt::{Display, Formatter},
+  sync::{Arc, Mutex},
+};
+
+use common::{Color, FastHashMap, Rectangle, UVec2};
+pub use memory::*;
+
+use super::*;
+
+mod memory;
+
+/// A resource owned by a [`GraphicsBackend`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum GraphicsResource {
+  Buffer(BufferId),
+  Texture(TextureId),
+  Shader(ShaderId),
+  Mesh(MeshId),
+  Target(TargetId),
+}
+
+/// A count of each kind of [`GraphicsResource`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ResourceCounts {
+  pub buffers: usize,
+  pub textures: usize,
+  pub shaders: usize,
+  pub meshes: usize,
+  pub targets: usize,
+}
+
+impl ResourceCounts {
+  /// The count across all kinds of resource.
+  pub fn total(&self) -> usize {
+    self.buffers + self.textures + self.shaders + self.meshes + self.targets
+  }
+
+  /// The count for the given resource's kind.
+  fn count_mut(&mut self, resource: GraphicsResource) -> &mut usize {
+    match resource {
+      GraphicsResource::Buffer(_) => &mut self.buffers,
+      GraphicsResource::Texture(_) => &mut self.textures,
+      GraphicsResource::Shader(_) => &mut self.shaders,
+      GraphicsResource::Mesh(_) => &mut self.meshes,
+      GraphicsResource::Target(_) => &mut self.targets,
+    }
+  }
+}
+
+/// Statistics about the resources of a [`TrackingGraphicsBackend`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ResourceStatistics {
+  /// The resources alive right now.
+  pub live: ResourceCounts,
+  /// The resources created in the last complete frame.
+  pub created: ResourceCounts,
+  /// The resources deleted in the last complete frame.
+  pub deleted: ResourceCounts,
+  /// Deletes of unknown or already deleted resources in the last complete
+  /// frame.
+  pub invalid_deletes: usize,
+  /// The meshes drawn in the last complete frame.
+  pub draw_calls: usize,
+  /// The triangles drawn in the last complete frame.
+  pub triangles: usize,
+}
+
+/// A resource that was still alive when leaks were reported.
+#[derive(Clone, Debug)]
+pub struct LeakedResource {
+  pub resource: GraphicsResource,
+  /// The frame the resource was created in.
+  pub frame: u64,
+  /// Where the resource was created; only captured in debug builds.
+  pub backtrace: Option<Arc<Backtrace>>,
+}
+
+impl Display for LeakedResource {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(formatter, "{:?} created in frame {}", self.resource, self.frame)?;
+
+    if let Some(backtrace) = &self.backtrace {
+      write!(formatter, " at:\n{backtrace}")?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Tracks the resources of a [`TrackingGraphicsBackend`].
+///
+/// This is a cheap handle; keep a clone of it after installing the backend to
+/// read statistics and report leaks.
+#[derive(Clone, Default)]
+pub struct ResourceTracker {
+  state: Arc<Mutex<TrackerState>>,
+}
+
+#[derive(Default)]
+struct TrackerState {
+  frame: u64,
+  live: FastHashMap<GraphicsResource, (u64, Option<Arc<Backtrace>>)>,
+  counts: ResourceCounts,
+  current_frame: ResourceStatistics,
+  last_frame: ResourceStatistics,
+  memory: MemoryState,
+}
+
+impl ResourceTracker {
+  /// Creates a new tracker.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Statistics for the live resources and the last complete frame.
+  pub fn statistics(&self) -> ResourceStatistics {
+    let state = self.state.lock().unwrap();
+
+    ResourceStatistics {
+      live: state.counts,
+      ..state.last_frame
+    }
+  }
+
+  /// Determines if the given resource is alive.
+  pub fn is_live(&self, resource: GraphicsResource) -> bool {
+    self.state.lock().unwrap().live.contains_key(&resource)
+  }
+
+  /// Logs and returns every resource that's still alive, oldest first.
+  pub fn report_leaks(&self) -> Vec<LeakedResource> {
+    let state = self.state.lock().unwrap();
+
+    let mut leaks = state
+      .live
+      .iter()
+      .map(|(resource, (frame, backtrace))| LeakedResource {
+        resource: *resource,
+        frame: *frame,
+        backtrace: backtrace.clone(),
+      })
+      .collect::<Vec<_>>();
+
+    leaks.sort_by_key(|leak| leak.frame);
+
+    if !leaks.is_empty() {
+      common::warn!("{} graphics resources were never deleted", leaks.len());
+
+      for leak in &leaks {
+        common::warn!("  {leak}");
+      }
+    }
+
+    leaks
+  }
+
+  /// Forgets every live resource, e.g. when they're lost with the device.
+  fn reset(&self) {
+    let mut state = self.state.lock().unwrap();
+
+    state.live.clear();
+    state.counts = ResourceCounts::default();
+    state.memory.reset();
+  }
+
+  /// Starts counting a new frame.
+  fn begin_frame(&self) {
+    let mut state = self.state.lock().unwrap();
+
+    state.frame += 1;
+    state.current_frame = ResourceStatistics::default();
+  }
+
+  /// Finishes counting the current frame.
+  fn end_frame(&self) {
+    let mut state = self.state.lock().unwrap();
+
+    state.last_frame = state.current_frame;
+    state.memory.warn_exceeded();
+  }
+
+  /// Records the creation of a resource.
+  fn create(&self, resource: GraphicsResource) {
+    let backtrace = cfg!(debug_assertions).then(|| Arc::new(Backtrace::force_capture()));
+    let mut state = self.state.lock().unwrap();
+    let frame = state.frame;
+
+    state.live.insert(resource, (frame, backtrace));
+    state.memory.create(resource);
+
+    *state.counts.count_mut(resource) += 1;
+    *state.current_frame.created.count_mut(resource) += 1;
+  }
+
+  /// Records the deletion of a resource.
+  fn delete(&self, resource: GraphicsResource) {
+    let mut state = self.state.lock().unwrap();
+
+    if state.live.remove(&resource).is_none() {
+      common::warn!("Deleted {resource:?}, which is not alive");
+
+      state.current_frame.invalid_deletes += 1;
+      return;
+    }
+
+    state.memory.delete(resource);
+
+    *state.counts.count_mut(resource) -= 1;
+    *state.current_frame.deleted.count_mut(resource) += 1;
+  }
+
+  /// Records a draw call.
+  fn draw(&self, topology: PrimitiveTopology, vertex_count: usize, index_count: usize) {
+    let mut state = self.state.lock().unwrap();
+
+    state.current_frame.draw_calls += 1;
+
+    if topology == PrimitiveTopology::Triangles {
+      // indexed meshes draw their indices; others draw their vertices in order
+      let count = if index_count > 0 { index_count } else { vertex_count };
+
+      state.current_frame.triangles += count / 3;
+    }
+  }
+}
+
+/// A [`GraphicsBackend`] that tracks the resources of another backend.
+pub struct TrackingGraphicsBackend<B> {
+  inner: B,
+  tracker: ResourceTracker,
+}
+
+impl<B: GraphicsBackend> TrackingGraphicsBackend<B> {
+  /// Wraps the given backend, tracking through the given handle.
+  pub fn new(inner: B, tracker: ResourceTracker) -> Self {
+    Self { inner, tracker }
+  }
+
+  /// Tracks a newly created resource, if it was created.
+  fn create<T: Copy, E>(&self, result: Result<T, E>, resource: impl FnOnce(T) -> GraphicsResource) -> Result<T, E> {
+    if let Ok(id) = &result {
+      self.tracker.create(resource(*id));
+    }
+
+    result
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+impl<B: GraphicsBackend> GraphicsBackend for TrackingGraphicsBackend<B> {
+  fn begin_frame(&self) {
+    self.tracker.begin_frame();
+    self.inner.begin_frame();
+  }
+
+  fn end_frame(&self) {
+    self.inner.end_frame();
+    self.tracker.end_frame();
+  }
+
+  fn device_status(&self) -> DeviceStatus {
+    self.inner.device_status()
+  }
+
+  fn device_reset(&self) {
+    self.tracker.reset();
+    self.inner.device_reset()
+  }
+
+  fn clear_color_buffer(&self, color: Color) {
+    self.inner.clear_color_buffer(color)
+  }
+
+  fn clear_depth_buffer(&self, depth: f32) {
+    self.inner.clear_depth_buffer(depth)
+  }
+
+  fn viewport_size(&self) -> (usize, usize) {
+    self.inner.viewport_size()
+  }
+
+  fn set_viewport_size(&self, size: UVec2) {
+    self.inner.set_viewport_size(size)
+  }
+
+  fn set_blend_state(&self, blend_state: BlendState) {
+    self.inner.set_blend_state(blend_state)
+  }
+
+  fn set_culling_mode(&self, culling_mode: CullingMode) {
+    self.inner.set_culling_mode(culling_mode)
+  }
+
+  fn set_depth_state(&self, depth_state: DepthState) {
+    self.inner.set_depth_state(depth_state)
+  }
+
+  fn set_scissor_mode(&self, scissor_mode: ScissorMode) {
+    self.inner.set_scissor_mode(scissor_mode)
+  }
+
+  fn buffer_create(&self) -> Result<BufferId, BufferError> {
+    self.create(self.inner.buffer_create(), GraphicsResource::Buffer)
+  }
+
+  fn buffer_read_data(
+    &self,
+    buffer: BufferId,
+    offset: usize,
+    length: usize,
+    pointer: *mut u8,
+  ) -> Result<(), BufferError> {
+    self.inner.buffer_read_data(buffer, offset, length, pointer)
+  }
+
+  fn buffer_write_data(
+    &self,
+    buffer: BufferId,
+    usage: BufferUsage,
+    kind: BufferKind,
+    length: usize,
+    pointer: *const u8,
+  ) -> Result<(), BufferError> {
+    self.buffer_written(buffer, length);
+    self.inner.buffer_write_data(buffer, usage, kind, length, pointer)
+  }
+
+  fn buffer_bind_storage(&self, buffer: BufferId, binding: u32) -> Result<(), BufferError> {
+    self.inner.buffer_bind_storage(buffer, binding)
+  }
+
+  fn buffer_delete(&self, buffer: BufferId) -> Result<(), BufferError> {
+    self.tracker.delete(GraphicsResource::Buffer(buffer));
+    self.inner.buffer_delete(buffer)
+  }
+
+  fn texture_create(&self, sampler: &TextureSampler) -> Result<TextureId, TextureError> {
+    self.create(self.inner.texture_create(sampler), GraphicsResource::Texture)
+  }
+
+  fn texture_set_options(&self, texture: TextureId, sampler: &TextureSampler) -> Result<(), TextureError> {
+    self.inner.texture_set_options(texture, sampler)
+  }
+
+  fn texture_initialize(
+    &self,
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    self.texture_allocated(texture, width, height, 1, format);
+    self.inner.texture_initialize(texture, width, height, format)
+  }
+
+  fn texture_read_data(
+    &self,
+    texture: TextureId,
+    length: usize,
+    pixel_format: TextureFormat,
+    pixels: *mut u8,
+    mip_level: usize,
+  ) -> Result<(), TextureError> {
+    self
+      .inner
+      .texture_read_data(texture, length, pixel_format, pixels, mip_level)
+  }
+
+  fn texture_write_data(
+    &self,
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    pixels: *const u8,
+    internal_format: TextureFormat,
+    pixel_format: TextureFormat,
+    mip_level: usize,
+  ) -> Result<(), TextureError> {
+    match mip_level {
+      0 => self.texture_allocated(texture, width, height, 1, internal_format),
+      _ => self.texture_mipmapped(texture),
+    }
+
+    self
+      .inner
+      .texture_write_data(texture, width, height, pixels, internal_format, pixel_format, mip_level)
+  }
+
+  fn texture_write_sub_data(
+    &self,
+    texture: TextureId,
+    region: &Rectangle,
+    pixels: *const u8,
+    pixel_format: TextureFormat,
+    mip_level: usize,
+  ) -> Result<(), TextureError> {
+    self
+      .inner
+      .texture_write_sub_data(texture, region, pixels, pixel_format, mip_level)
+  }
+
+  fn texture_initialize_layers(
+    &self,
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    layers: u32,
+    format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    self.texture_allocated(texture, width, height, layers, format);
+    self
+      .inner
+      .texture_initialize_layers(texture, width, height, layers, format)
+  }
+
+  fn texture_write_layer_data(
+    &self,
+    texture: TextureId,
+    region: &Rectangle,
+    layer: u32,
+    pixels: *const u8,
+    pixel_format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    self
+      .inner
+      .texture_write_layer_data(texture, region, layer, pixels, pixel_format)
+  }
+
+  fn texture_set_mip_range(&self, texture: TextureId, base_level: usize, max_level: usize) -> Result<(), TextureError> {
+    if max_level > base_level {
+      self.texture_mipmapped(texture);
+    }
+
+    self.inner.texture_set_mip_range(texture, base_level, max_level)
+  }
+
+  fn texture_delete(&self, texture: TextureId) -> Result<(), TextureError> {
+    self.tracker.delete(GraphicsResource::Texture(texture));
+    self.inner.texture_delete(texture)
+  }
+
+  fn shader_create(&self) -> Result<ShaderId, ShaderError> {
+    self.create(self.inner.shader_create(), GraphicsResource::Shader)
+  }
+
+  fn shader_link(&self, shader: ShaderId, kernels: &[ShaderKernel]) -> Result<(), ShaderError> {
+    self.inner.shader_link(shader, kernels)
+  }
+
+  fn shader_link_async(&self, shader: ShaderId, kernels: &[ShaderKernel]) -> Result<(), ShaderError> {
+    self.inner.shader_link_async(shader, kernels)
+  }
+
+  fn shader_link_status(&self, shader: ShaderId) -> ShaderLinkStatus {
+    self.inner.shader_link_status(shader)
+  }
+
+  fn shader_binary(&self, shader: ShaderId) -> Option<ShaderBinary> {
+    self.inner.shader_binary(shader)
+  }
+
+  fn shader_link_binary(&self, shader: ShaderId, binary: &ShaderBinary) -> Result<(), ShaderError> {
+    self.inner.shader_link_binary(shader, binary)
+  }
+
+  fn shader_uniform_location(&self, shader: ShaderId, name: &str) -> Option<usize> {
+    self.inner.shader_uniform_location(shader, name)
+  }
+
+  fn shader_set_uniform(&self, shader: ShaderId, location: usize, value: &ShaderUniform) -> Result<(), ShaderError> {
+    self.inner.shader_set_uniform(shader, location, value)
+  }
+
+  fn shader_activate(&self, shader: ShaderId) -> Result<(), ShaderError> {
+    self.inner.shader_activate(shader)
+  }
+
+  fn shader_dispatch_compute(&self, shader: ShaderId, x: u32, y: u32, z: u32) -> Result<(), ShaderError> {
+    self.inner.shader_dispatch_compute(shader, x, y, z)
+  }
+
+  fn shader_memory_barrier(&self, barrier: MemoryBarrier) -> Result<(), ShaderError> {
+    self.inner.shader_memory_barrier(barrier)
+  }
+
+  fn shader_delete(&self, shader: ShaderId) -> Result<(), ShaderError> {
+    self.tracker.delete(GraphicsResource::Shader(shader));
+    self.inner.shader_delete(shader)
+  }
+
+  fn mesh_create(
+    &self,
+    vertices: BufferId,
+    indices: BufferId,
+    descriptors: &[VertexDescriptor],
+  ) -> Result<MeshId, MeshError> {
+    self.create(
+      self.inner.mesh_create(vertices, indices, descriptors),
+      GraphicsResource::Mesh,
+    )
+  }
+
+  fn mesh_draw(
+    &self,
+    mesh: MeshId,
+    topology: PrimitiveTopology,
+    vertex_count: usize,
+    index_count: usize,
+  ) -> Result<(), MeshError> {
+    self.tracker.draw(topology, vertex_count, index_count);
+    self.inner.mesh_draw(mesh, topology, vertex_count, index_count)
+  }
+
+  fn mesh_delete(&self, mesh: MeshId) -> Result<(), MeshError> {
+    self.tracker.delete(GraphicsResource::Mesh(mesh));
+    self.inner.mesh_delete(mesh)
+  }
+
+  fn target_create(
+    &self,
+    color_attachment: TextureId,
+    depth_attachment: Option<TextureId>,
+    stencil_attachment: Option<TextureId>,
+  ) -> Result<TargetId, TargetError> {
+    self.target_created(&[Some(color_attachment), depth_attachment, stencil_attachment]);
+    self.create(
+      self
+        .inner
+        .target_create(color_attachment, depth_attachment, stencil_attachment),
+      GraphicsResource::Target,
+    )
+  }
+
+  fn target_activate(&self, target: TargetId) -> Result<(), TargetError> {
+    self.inner.target_activate(target)
+  }
+
+  fn target_set_default(&self) -> Result<(), TargetError> {
+    self.inner.target_set_default()
+  }
+
+  fn target_blit_to_active(
+    &self,
+    target: TargetId,
+    source_rect: Option<Rectangle>,
+    dest_rect: Option<Rectangle>,
+    filter: TextureFilter,
+  ) -> Result<(), TargetError> {
+    self.inner.target_blit_to_active(target, source_rect, dest_rect, filter)
+  }
+
+  fn target_delete(&self, target: TargetId) -> Result<(), TargetError> {
+    self.tracker.delete(GraphicsResource::Target(target));
+    self.inner.target_delete(target)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::headless::HeadlessGraphicsBackend;
+
+  fn create_backend() -> (TrackingGraphicsBackend<HeadlessGraphicsBackend>, ResourceTracker) {
+    let tracker = ResourceTracker::new();
+    let backend = TrackingGraphicsBackend::new(HeadlessGraphicsBackend::default(), tracker.clone());
+
+    (backend, tracker)
+  }
+
+  #[test]
+  fn it_should_count_resources_each_frame() {
+    let (backend, tracker) = create_backend();
+
+    backend.begin_frame();
+
+    let buffer = backend.buffer_create().unwrap();
+    let texture = backend.texture_create(&TextureOptions::default().sampler).unwrap();
+
+    backend.buffer_delete(buffer).unwrap();
+    backend.end_frame();
+
+    let statistics = tracker.statistics();
+
+    assert_eq!(statistics.live.textures, 1);
+    assert_eq!(statistics.live.total(), 1);
+    assert_eq!(statistics.created.total(), 2);
+    assert_eq!(statistics.deleted.buffers, 1);
+    assert!(tracker.is_live(GraphicsResource::Texture(texture)));
+
+    backend.begin_frame();
+    backend.end_frame();
+
+    assert_eq!(tracker.statistics().created.total(), 0);
+  }
+
+  #[test]
+  fn it_should_count_draw_calls_and_triangles() {
+    let (backend, tracker) = create_backend();
+    let mesh = backend.mesh_create(BufferId::NONE, BufferId::NONE, &[]).unwrap();
+
+    backend.begin_frame();
+    backend.mesh_draw(mesh, PrimitiveTopology::Triangles, 4, 6).unwrap();
+    backend.mesh_draw(mesh, PrimitiveTopology::Triangles, 9, 0).unwrap();
+    backend.mesh_draw(mesh, PrimitiveTopology::Lines, 2, 0).unwrap();
+    backend.end_frame();
+
+    let statistics = tracker.statistics();
+
+    assert_eq!(statistics.draw_calls, 3);
+    assert_eq!(statistics.triangles, 5);
+  }
+
+  #[test]
+  fn it_should_report_leaks_and_invalid_deletes() {
+    let (backend, tracker) = create_backend();
+
+    backend.begin_frame();
+
+    let shader = backend.shader_create().unwrap();
+    let mesh = backend.mesh_create(BufferId::NONE, BufferId::NONE, &[]).unwrap();
+
+    backend.shader_delete(shader).unwrap();
+    backend.shader_delete(shader).unwrap();
+    backend.end_frame();
+
+    assert_eq!(tracker.statistics().invalid_deletes, 1);
+
+    let leaks = tracker.report_leaks();
+
+    assert_eq!(leaks.len(), 1);
+    assert_eq!(leaks[0].resource, GraphicsResource::Mesh(mesh));
+    assert_eq!(leaks[0].frame, 1);
+    assert_eq!(leaks[0].backtrace.is_some(), cfg!(debug_assertions));
+  }
+}
@@ -0,0 +1,512 @@
+//! Estimates of GPU memory, for staying within a budget.
+//!
+//! The [`ResourceTracker`] estimates the memory of every texture, render
+//! target and buffer from its size and format, and attributes it to the
+//! scope, like a scene or asset bundle, that was active when the resource
+//! was created:
+//!
+//! ```rust,ignore
+//! tracker.set_memory_budget(MemoryBudget::new()
+//!   .with_total(Size::from_megabytes(96.))
+//!   .with_scope("forest", Size::from_megabytes(32.)));
+//!
+//! tracker.set_memory_scope(Some("forest".into()));
+//! let level = load_level("forest")?;
+//! tracker.set_memory_scope(None);
+//!
+//! println!("{}", tracker.memory_report());
+//! ```
+//!
+//! Estimates ignore driver padding and compression, so treat them as a
+//! guide; they're most useful on small targets where every texture counts.
+//! Exceeding a budget logs a warning at the end of the frame it happens in.
+
+use common::{Size, StringName};
+
+use super::*;
+
+/// Estimated GPU memory, by kind of resource.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemoryUsage {
+  pub textures: Size,
+  pub render_targets: Size,
+  pub buffers: Size,
+}
+
+impl MemoryUsage {
+  /// The memory across all kinds of resource.
+  pub fn total(&self) -> Size {
+    self.textures + self.render_targets + self.buffers
+  }
+}
+
+/// Estimated GPU memory of a single scope.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScopeMemory {
+  /// The scope; resources created outside of any scope have none.
+  pub scope: Option<StringName>,
+  pub usage: MemoryUsage,
+}
+
+/// A breakdown of estimated GPU memory.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryReport {
+  pub total: MemoryUsage,
+  /// Memory by scope, largest first.
+  pub scopes: Vec<ScopeMemory>,
+  /// Texture and render target memory by format, largest first.
+  pub formats: Vec<(TextureFormat, Size)>,
+}
+
+impl MemoryReport {
+  /// The memory of the given scope.
+  pub fn scope(&self, scope: impl Into<StringName>) -> MemoryUsage {
+    let scope = Some(scope.into());
+
+    self
+      .scopes
+      .iter()
+      .find(|it| it.scope == scope)
+      .map(|it| it.usage)
+      .unwrap_or_default()
+  }
+}
+
+impl Display for MemoryReport {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    let write_usage = |formatter: &mut Formatter<'_>, name: &str, usage: &MemoryUsage| {
+      writeln!(
+        formatter,
+        "{name}: {:?} (textures {:?}, targets {:?}, buffers {:?})",
+        usage.total(),
+        usage.textures,
+        usage.render_targets,
+        usage.buffers
+      )
+    };
+
+    write_usage(formatter, "total", &self.total)?;
+
+    for scope in &self.scopes {
+      let name = scope.scope.as_ref().map_or("unscoped", |it| it.as_ref());
+
+      write_usage(formatter, &format!("  {name}"), &scope.usage)?;
+    }
+
+    for (format, size) in &self.formats {
+      writeln!(formatter, "  {format:?}: {size:?}")?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Limits on estimated GPU memory.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryBudget {
+  /// The limit across every scope.
+  pub total: Option<Size>,
+  /// Limits on individual scopes.
+  pub scopes: FastHashMap<StringName, Size>,
+}
+
+impl MemoryBudget {
+  /// Creates a budget without any limits.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Limits memory across every scope.
+  pub fn with_total(mut self, limit: Size) -> Self {
+    self.total = Some(limit);
+    self
+  }
+
+  /// Limits the memory of a single scope.
+  pub fn with_scope(mut self, scope: impl Into<StringName>, limit: Size) -> Self {
+    self.scopes.insert(scope.into(), limit);
+    self
+  }
+}
+
+/// A memory budget that was exceeded.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BudgetExceeded {
+  /// The scope over its budget, or `None` for the total budget.
+  pub scope: Option<StringName>,
+  pub used: Size,
+  pub budget: Size,
+}
+
+impl Display for BudgetExceeded {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    match &self.scope {
+      Some(scope) => write!(formatter, "GPU memory for '{scope}'")?,
+      None => write!(formatter, "Total GPU memory")?,
+    }
+
+    write!(formatter, " is {:?}, over its budget of {:?}", self.used, self.budget)
+  }
+}
+
+/// The estimated memory of a texture.
+#[derive(Default)]
+struct TextureMemory {
+  scope: Option<StringName>,
+  format: Option<TextureFormat>,
+  width: u32,
+  height: u32,
+  layers: u32,
+  mipmapped: bool,
+  render_target: bool,
+}
+
+impl TextureMemory {
+  fn size(&self) -> Size {
+    let Some(format) = self.format else {
+      return Size::default();
+    };
+
+    let base = self.width as usize * self.height as usize * self.layers.max(1) as usize * format.bytes_per_pixel();
+
+    // a full mip chain adds roughly a third
+    match self.mipmapped {
+      true => Size::from_bytes(base + base / 3),
+      false => Size::from_bytes(base),
+    }
+  }
+}
+
+/// The estimated memory of a buffer.
+struct BufferMemory {
+  scope: Option<StringName>,
+  size: usize,
+}
+
+/// Memory estimates kept by a [`ResourceTracker`].
+#[derive(Default)]
+pub(super) struct MemoryState {
+  scope: Option<StringName>,
+  textures: FastHashMap<TextureId, TextureMemory>,
+  buffers: FastHashMap<BufferId, BufferMemory>,
+  budget: MemoryBudget,
+  /// Budgets already warned about, so each is only logged once.
+  exceeded: Vec<Option<StringName>>,
+}
+
+impl MemoryState {
+  /// Starts estimating a newly created resource, in the current scope.
+  pub(super) fn create(&mut self, resource: GraphicsResource) {
+    let scope = self.scope;
+
+    match resource {
+      GraphicsResource::Texture(texture) => {
+        self.textures.insert(texture, TextureMemory {
+          scope,
+          ..Default::default()
+        });
+      }
+      GraphicsResource::Buffer(buffer) => {
+        self.buffers.insert(buffer, BufferMemory { scope, size: 0 });
+      }
+      _ => {}
+    }
+  }
+
+  /// Stops estimating a deleted resource.
+  pub(super) fn delete(&mut self, resource: GraphicsResource) {
+    match resource {
+      GraphicsResource::Texture(texture) => {
+        self.textures.remove(&texture);
+      }
+      GraphicsResource::Buffer(buffer) => {
+        self.buffers.remove(&buffer);
+      }
+      _ => {}
+    }
+  }
+
+  /// Forgets every resource, keeping the scope and budget.
+  pub(super) fn reset(&mut self) {
+    self.textures.clear();
+    self.buffers.clear();
+    self.exceeded.clear();
+  }
+
+  fn texture_allocated(&mut self, texture: TextureId, width: u32, height: u32, layers: u32, format: TextureFormat) {
+    if let Some(memory) = self.textures.get_mut(&texture) {
+      memory.width = width;
+      memory.height = height;
+      memory.layers = layers;
+      memory.format = Some(format);
+    }
+  }
+
+  fn texture_mipmapped(&mut self, texture: TextureId) {
+    if let Some(memory) = self.textures.get_mut(&texture) {
+      memory.mipmapped = true;
+    }
+  }
+
+  fn buffer_written(&mut self, buffer: BufferId, length: usize) {
+    if let Some(memory) = self.buffers.get_mut(&buffer) {
+      memory.size = length;
+    }
+  }
+
+  fn target_created(&mut self, attachments: &[Option<TextureId>]) {
+    for texture in attachments.iter().flatten() {
+      if let Some(memory) = self.textures.get_mut(texture) {
+        memory.render_target = true;
+      }
+    }
+  }
+
+  fn report(&self) -> MemoryReport {
+    let mut total = MemoryUsage::default();
+    let mut scopes = FastHashMap::<Option<StringName>, MemoryUsage>::default();
+    let mut formats = FastHashMap::<TextureFormat, Size>::default();
+
+    for texture in self.textures.values() {
+      let size = texture.size();
+      let usage = scopes.entry(texture.scope).or_default();
+
+      if texture.render_target {
+        total.render_targets += size;
+        usage.render_targets += size;
+      } else {
+        total.textures += size;
+        usage.textures += size;
+      }
+
+      if let Some(format) = texture.format {
+        *formats.entry(format).or_default() += size;
+      }
+    }
+
+    for buffer in self.buffers.values() {
+      let size = Size::from_bytes(buffer.size);
+
+      total.buffers += size;
+      scopes.entry(buffer.scope).or_default().buffers += size;
+    }
+
+    let mut scopes = scopes
+      .into_iter()
+      .map(|(scope, usage)| ScopeMemory { scope, usage })
+      .collect::<Vec<_>>();
+
+    let mut formats = formats.into_iter().collect::<Vec<_>>();
+
+    scopes.sort_by_key(|it| std::cmp::Reverse(it.usage.total()));
+    formats.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+
+    MemoryReport { total, scopes, formats }
+  }
+
+  fn exceeded_budgets(&self) -> Vec<BudgetExceeded> {
+    let report = self.report();
+    let mut exceeded = Vec::new();
+
+    if let Some(budget) = self.budget.total {
+      if report.total.total() > budget {
+        exceeded.push(BudgetExceeded {
+          scope: None,
+          used: report.total.total(),
+          budget,
+        });
+      }
+    }
+
+    for (scope, budget) in &self.budget.scopes {
+      let used = report.scope(*scope).total();
+
+      if used > *budget {
+        exceeded.push(BudgetExceeded {
+          scope: Some(*scope),
+          used,
+          budget: *budget,
+        });
+      }
+    }
+
+    exceeded
+  }
+
+  /// Warns about budgets that have been exceeded since they were last within
+  /// their limits.
+  pub(super) fn warn_exceeded(&mut self) {
+    if self.budget.total.is_none() && self.budget.scopes.is_empty() {
+      return;
+    }
+
+    let exceeded = self.exceeded_budgets();
+
+    for budget in &exceeded {
+      if !self.exceeded.contains(&budget.scope) {
+        common::warn!("{budget}");
+      }
+    }
+
+    self.exceeded = exceeded.into_iter().map(|it| it.scope).collect();
+  }
+}
+
+impl ResourceTracker {
+  /// Attributes resources created from now on to the given scope, like the
+  /// scene or bundle being loaded.
+  pub fn set_memory_scope(&self, scope: Option<StringName>) {
+    self.state.lock().unwrap().memory.scope = scope;
+  }
+
+  /// Limits the estimated GPU memory; see
+  /// [`ResourceTracker::check_memory_budget`].
+  pub fn set_memory_budget(&self, budget: MemoryBudget) {
+    let mut state = self.state.lock().unwrap();
+
+    state.memory.budget = budget;
+    state.memory.exceeded.clear();
+  }
+
+  /// Estimates the GPU memory of the live resources.
+  pub fn memory_report(&self) -> MemoryReport {
+    self.state.lock().unwrap().memory.report()
+  }
+
+  /// The budgets that the live resources exceed.
+  pub fn check_memory_budget(&self) -> Vec<BudgetExceeded> {
+    self.state.lock().unwrap().memory.exceeded_budgets()
+  }
+
+  fn with_memory(&self, body: impl FnOnce(&mut MemoryState)) {
+    body(&mut self.state.lock().unwrap().memory);
+  }
+}
+
+impl<B: GraphicsBackend> TrackingGraphicsBackend<B> {
+  /// Records the size of a texture's storage.
+  pub(super) fn texture_allocated(
+    &self,
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    layers: u32,
+    format: TextureFormat,
+  ) {
+    self
+      .tracker
+      .with_memory(|memory| memory.texture_allocated(texture, width, height, layers, format));
+  }
+
+  /// Records that a texture has more than one mip level.
+  pub(super) fn texture_mipmapped(&self, texture: TextureId) {
+    self.tracker.with_memory(|memory| memory.texture_mipmapped(texture));
+  }
+
+  /// Records the size of a buffer's storage.
+  pub(super) fn buffer_written(&self, buffer: BufferId, length: usize) {
+    self.tracker.with_memory(|memory| memory.buffer_written(buffer, length));
+  }
+
+  /// Records the textures attached to a render target.
+  pub(super) fn target_created(&self, attachments: &[Option<TextureId>]) {
+    self.tracker.with_memory(|memory| memory.target_created(attachments));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::headless::HeadlessGraphicsBackend;
+
+  fn create_backend() -> (TrackingGraphicsBackend<HeadlessGraphicsBackend>, ResourceTracker) {
+    let tracker = ResourceTracker::new();
+    let backend = TrackingGraphicsBackend::new(HeadlessGraphicsBackend::default(), tracker.clone());
+
+    (backend, tracker)
+  }
+
+  fn create_texture(
+    backend: &TrackingGraphicsBackend<HeadlessGraphicsBackend>,
+    size: u32,
+    format: TextureFormat,
+  ) -> TextureId {
+    let texture = backend.texture_create(&TextureOptions::default().sampler).unwrap();
+
+    backend.texture_initialize(texture, size, size, format).unwrap();
+    texture
+  }
+
+  #[test]
+  fn it_should_estimate_memory_by_scope_and_format() {
+    let (backend, tracker) = create_backend();
+
+    tracker.set_memory_scope(Some("forest".into()));
+
+    create_texture(&backend, 256, TextureFormat::RGBA8);
+    create_texture(&backend, 128, TextureFormat::R8);
+
+    let buffer = backend.buffer_create().unwrap();
+    let data = [0u8; 1024];
+
+    backend
+      .buffer_write_data(
+        buffer,
+        BufferUsage::Static,
+        BufferKind::Element,
+        data.len(),
+        data.as_ptr(),
+      )
+      .unwrap();
+
+    tracker.set_memory_scope(None);
+
+    let color = create_texture(&backend, 64, TextureFormat::RGBA32);
+    backend.target_create(color, None, None).unwrap();
+
+    let report = tracker.memory_report();
+    let forest = report.scope("forest");
+
+    assert_eq!(forest.textures, Size::from_bytes(256 * 256 * 4 + 128 * 128));
+    assert_eq!(forest.buffers, Size::from_bytes(1024));
+    assert_eq!(report.total.render_targets, Size::from_bytes(64 * 64 * 16));
+    assert_eq!(
+      report.formats[0],
+      (TextureFormat::RGBA8, Size::from_bytes(256 * 256 * 4))
+    );
+    assert_eq!(report.scopes[0].scope, Some("forest".into()));
+
+    backend.buffer_delete(buffer).unwrap();
+
+    assert_eq!(tracker.memory_report().scope("forest").buffers, Size::default());
+  }
+
+  #[test]
+  fn it_should_report_exceeded_budgets() {
+    let (backend, tracker) = create_backend();
+
+    tracker.set_memory_budget(
+      MemoryBudget::new()
+        .with_total(Size::from_kilobytes(256.))
+        .with_scope("menu", Size::from_kilobytes(16.)),
+    );
+
+    tracker.set_memory_scope(Some("menu".into()));
+    let texture = create_texture(&backend, 64, TextureFormat::RGBA8);
+    tracker.set_memory_scope(None);
+
+    assert!(tracker.check_memory_budget().is_empty());
+
+    backend.texture_set_mip_range(texture, 0, 6).unwrap();
+
+    let exceeded = tracker.check_memory_budget();
+
+    assert_eq!(exceeded.len(), 1);
+    assert_eq!(exceeded[0].scope, Some("menu".into()));
+    assert!(exceeded[0].used > exceeded[0].budget);
+
+    create_texture(&backend, 256, TextureFormat::RGBA8);
+
+    assert_eq!(tracker.check_memory_budget().len(), 2);
+  }
+}
@@ -0,0 +1,177 @@
+//! Fullscreen transition effects.
+//!
+//! A [`TransitionRenderer`] draws the scene through the current
+//! [`Transition`], e.g. as the last step of a frame rendered offscreen:
+//!
+//! ```rust,ignore
+//! let mut transitions = TransitionRenderer::new()?;
+//!
+//! transitions.register_mask("diamonds", SHADER_DIAMONDS.to_material()?);
+//!
+//! match scenes.transition() {
+//!   Some(transition) => transitions.render(&mut batch, target.color_attachment(), view, &projection_view, transition),
+//!   None => batch.draw_sprite(&target.color_attachment(), &options),
+//! }
+//! ```
+//!
+//! Mask shaders decide where the screen is covered first. They're drawn like
+//! sprites of the scene's texture, `u_texture`, and given the same uniforms as
+//! the built-in styles; `u_coverage` goes from 0 to 1 as the screen is
+//! covered, and should be fully covered with `u_color` at 1.
+
+use common::{Color, FastHashMap, Mat4, Rectangle, StringName, Transition, TransitionStyle, Vec2};
+
+use super::*;
+
+/// The built-in styles, by their index in the transition shader.
+const MODE_FADE: u32 = 0;
+const MODE_CIRCLE: u32 = 1;
+const MODE_PIXELATE: u32 = 2;
+
+/// A shader uniform key for the style of the built-in transition shader.
+pub const TRANSITION_MODE: ShaderUniformKey<u32> = ShaderUniformKey::new("u_mode");
+/// A shader uniform key for how much of the screen is covered, from 0 to 1.
+pub const TRANSITION_COVERAGE: ShaderUniformKey<f32> = ShaderUniformKey::new("u_coverage");
+/// A shader uniform key for the color the screen is covered with.
+pub const TRANSITION_COLOR: ShaderUniformKey<Color> = ShaderUniformKey::new("u_color");
+/// A shader uniform key for the center of a circle, from 0 to 1 on each axis.
+pub const TRANSITION_CENTER: ShaderUniformKey<Vec2> = ShaderUniformKey::new("u_center");
+/// A shader uniform key for the largest size of a pixelated block, in pixels.
+pub const TRANSITION_BLOCK_SIZE: ShaderUniformKey<f32> = ShaderUniformKey::new("u_block_size");
+/// A shader uniform key for the size of the scene's texture, in pixels.
+pub const TRANSITION_RESOLUTION: ShaderUniformKey<Vec2> = ShaderUniformKey::new("u_resolution");
+
+/// The uniforms for drawing a [`Transition`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TransitionUniforms {
+  pub mode: u32,
+  pub coverage: f32,
+  pub color: Color,
+  pub center: Vec2,
+  pub block_size: f32,
+  pub resolution: Vec2,
+}
+
+impl TransitionUniforms {
+  /// Computes the uniforms for a transition over a scene of the given size,
+  /// in pixels.
+  pub fn new(transition: &Transition, resolution: Vec2) -> Self {
+    let mut uniforms = Self {
+      mode: MODE_FADE,
+      coverage: transition.coverage(),
+      color: transition.color,
+      center: Vec2::splat(0.5),
+      block_size: 1.,
+      resolution,
+    };
+
+    match transition.style {
+      TransitionStyle::Fade | TransitionStyle::Mask { .. } => {}
+      TransitionStyle::Circle { center } => {
+        uniforms.mode = MODE_CIRCLE;
+        uniforms.center = center;
+      }
+      TransitionStyle::Pixelate { block_size } => {
+        uniforms.mode = MODE_PIXELATE;
+        uniforms.block_size = block_size.max(1.);
+      }
+    }
+
+    uniforms
+  }
+
+  /// Sets the uniforms on a material.
+  pub fn apply(&self, material: &mut Material) {
+    material.set_uniform(TRANSITION_MODE, self.mode);
+    material.set_uniform(TRANSITION_COVERAGE, self.coverage);
+    material.set_uniform(TRANSITION_COLOR, self.color);
+    material.set_uniform(TRANSITION_CENTER, self.center);
+    material.set_uniform(TRANSITION_BLOCK_SIZE, self.block_size);
+    material.set_uniform(TRANSITION_RESOLUTION, self.resolution);
+  }
+}
+
+/// Draws scenes through fullscreen transitions.
+pub struct TransitionRenderer {
+  standard: Material,
+  masks: FastHashMap<StringName, Material>,
+}
+
+impl TransitionRenderer {
+  /// Creates a renderer with the built-in styles.
+  pub fn new() -> Result<Self, GraphicsError> {
+    Ok(Self {
+      standard: SHADER_TRANSITION_STANDARD.to_material()?,
+      masks: FastHashMap::default(),
+    })
+  }
+
+  /// Registers a mask shader for [`TransitionStyle::Mask`].
+  pub fn register_mask(&mut self, name: impl Into<StringName>, material: Material) {
+    self.masks.insert(name.into(), material);
+  }
+
+  /// Renders the scene's texture over the view, through the transition.
+  ///
+  /// Masks that aren't registered fall back to a fade.
+  pub fn render(
+    &mut self,
+    batch: &mut SpriteBatch,
+    scene: &Texture,
+    view: Rectangle,
+    projection_view: &Mat4,
+    transition: &Transition,
+  ) {
+    let size = Vec2::new(scene.width() as f32, scene.height() as f32);
+    let uniforms = TransitionUniforms::new(transition, size);
+
+    let material = match transition.style {
+      TransitionStyle::Mask { shader } => self.masks.get_mut(&shader).unwrap_or(&mut self.standard),
+      _ => &mut self.standard,
+    };
+
+    uniforms.apply(material);
+    material.set_uniform(PROJECTION_VIEW, projection_view);
+
+    batch.begin(material);
+    batch.draw_sprite(scene, &SpriteOptions {
+      position: view.center(),
+      scale: view.size() / size,
+      ..Default::default()
+    });
+    batch.flush();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::vec2;
+
+  use super::*;
+
+  #[test]
+  fn it_should_compute_uniforms_for_each_style() {
+    let resolution = vec2(320., 240.);
+
+    let fade = TransitionUniforms::new(&Transition::fade(1.).with_color(Color::WHITE), resolution);
+
+    assert_eq!(fade.mode, MODE_FADE);
+    assert_eq!(fade.color, Color::WHITE);
+    assert_eq!(fade.resolution, resolution);
+
+    let circle = TransitionUniforms::new(&Transition::circle(vec2(0.25, 0.75), 1.), resolution);
+
+    assert_eq!(circle.mode, MODE_CIRCLE);
+    assert_eq!(circle.center, vec2(0.25, 0.75));
+
+    let mut transition = Transition::pixelate(16., 1.);
+
+    transition.update(0.25);
+
+    let pixelate = TransitionUniforms::new(&transition, resolution);
+
+    assert_eq!(pixelate.mode, MODE_PIXELATE);
+    assert_eq!(pixelate.block_size, 16.);
+    assert_eq!(pixelate.coverage, 0.5);
+  }
+}
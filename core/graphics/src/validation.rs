@@ -0,0 +1,825 @@
+//! Validation of calls into a graphics backend.
+//!
+//! A [`ValidatingBackend`] wraps another backend and checks the preconditions
+//! of each call before passing it on, so mistakes like using a deleted
+//! texture or uploading pixels outside of it come back as errors instead of
+//! silently corrupting GPU state. It's meant for debug builds; the checks
+//! aren't free.
+
+use std::sync::Mutex;
+
+use common::{Color, FastHashMap, FastHashSet, Rectangle, UVec2};
+
+use super::*;
+
+/// A [`GraphicsBackend`] that validates calls before passing them on to
+/// another backend.
+pub struct ValidatingBackend<B> {
+  inner: B,
+  state: Mutex<ValidationState>,
+}
+
+#[derive(Default)]
+struct ValidationState {
+  /// Live buffers and the length of the data written to them.
+  buffers: FastHashMap<BufferId, usize>,
+  textures: FastHashMap<TextureId, TextureInfo>,
+  /// Live shaders and whether they've been linked.
+  shaders: FastHashMap<ShaderId, bool>,
+  meshes: FastHashSet<MeshId>,
+  targets: FastHashSet<TargetId>,
+  deleted: FastHashSet<GraphicsResource>,
+}
+
+/// What's known about a live texture.
+#[derive(Default)]
+struct TextureInfo {
+  width: u32,
+  height: u32,
+  layers: u32,
+  format: Option<TextureFormat>,
+}
+
+impl<B: GraphicsBackend> ValidatingBackend<B> {
+  /// Wraps the given backend.
+  pub fn new(inner: B) -> Self {
+    Self {
+      inner,
+      state: Mutex::new(ValidationState::default()),
+    }
+  }
+
+  /// Validates a call against the current state.
+  fn validate<R, E>(&self, body: impl FnOnce(&mut ValidationState) -> Result<R, E>) -> Result<R, E> {
+    body(&mut self.state.lock().unwrap())
+  }
+}
+
+impl ValidationState {
+  fn buffer(&mut self, buffer: BufferId) -> Result<&mut usize, BufferError> {
+    match self.buffers.get_mut(&buffer) {
+      Some(length) => Ok(length),
+      None if self.deleted.contains(&GraphicsResource::Buffer(buffer)) => Err(BufferError::Deleted(buffer)),
+      None => Err(BufferError::InvalidId(buffer)),
+    }
+  }
+
+  fn texture(&mut self, texture: TextureId) -> Result<&mut TextureInfo, TextureError> {
+    match self.textures.get_mut(&texture) {
+      Some(info) => Ok(info),
+      None if self.deleted.contains(&GraphicsResource::Texture(texture)) => Err(TextureError::Deleted(texture)),
+      None => Err(TextureError::InvalidId(texture)),
+    }
+  }
+
+  /// A texture that has storage in the given format.
+  fn initialized_texture(&mut self, texture: TextureId) -> Result<(&TextureInfo, TextureFormat), TextureError> {
+    let info = self.texture(texture)?;
+
+    match info.format {
+      Some(format) => Ok((info, format)),
+      None => Err(TextureError::NotInitialized(texture)),
+    }
+  }
+
+  fn shader(&mut self, shader: ShaderId) -> Result<&mut bool, ShaderError> {
+    match self.shaders.get_mut(&shader) {
+      Some(linked) => Ok(linked),
+      None if self.deleted.contains(&GraphicsResource::Shader(shader)) => Err(ShaderError::Deleted(shader)),
+      None => Err(ShaderError::InvalidId(shader)),
+    }
+  }
+
+  fn linked_shader(&mut self, shader: ShaderId) -> Result<(), ShaderError> {
+    match self.shader(shader)? {
+      true => Ok(()),
+      false => Err(ShaderError::NotLinked(shader)),
+    }
+  }
+
+  fn mesh(&self, mesh: MeshId) -> Result<(), MeshError> {
+    match self.meshes.contains(&mesh) {
+      true => Ok(()),
+      false if self.deleted.contains(&GraphicsResource::Mesh(mesh)) => Err(MeshError::Deleted(mesh)),
+      false => Err(MeshError::InvalidId(mesh)),
+    }
+  }
+
+  fn target(&self, target: TargetId) -> Result<(), TargetError> {
+    match self.targets.contains(&target) {
+      true => Ok(()),
+      false if self.deleted.contains(&GraphicsResource::Target(target)) => Err(TargetError::Deleted(target)),
+      false => Err(TargetError::InvalidId(target)),
+    }
+  }
+
+  /// Records a newly created resource; backends may reuse deleted IDs.
+  fn create(&mut self, resource: GraphicsResource) {
+    self.deleted.remove(&resource);
+
+    match resource {
+      GraphicsResource::Buffer(buffer) => {
+        self.buffers.insert(buffer, 0);
+      }
+      GraphicsResource::Texture(texture) => {
+        self.textures.insert(texture, TextureInfo::default());
+      }
+      GraphicsResource::Shader(shader) => {
+        self.shaders.insert(shader, false);
+      }
+      GraphicsResource::Mesh(mesh) => {
+        self.meshes.insert(mesh);
+      }
+      GraphicsResource::Target(target) => {
+        self.targets.insert(target);
+      }
+    }
+  }
+
+  /// Records a deleted resource, once it's been validated.
+  fn delete(&mut self, resource: GraphicsResource) {
+    match resource {
+      GraphicsResource::Buffer(buffer) => {
+        self.buffers.remove(&buffer);
+      }
+      GraphicsResource::Texture(texture) => {
+        self.textures.remove(&texture);
+      }
+      GraphicsResource::Shader(shader) => {
+        self.shaders.remove(&shader);
+      }
+      GraphicsResource::Mesh(mesh) => {
+        self.meshes.remove(&mesh);
+      }
+      GraphicsResource::Target(target) => {
+        self.targets.remove(&target);
+      }
+    }
+
+    self.deleted.insert(resource);
+  }
+}
+
+/// Checks that pixels in one format can be written to storage in another.
+fn validate_format(expected: TextureFormat, actual: TextureFormat) -> Result<(), TextureError> {
+  match channel_count(expected) == channel_count(actual) {
+    true => Ok(()),
+    false => Err(TextureError::FormatMismatch { expected, actual }),
+  }
+}
+
+/// Checks that a region lies within a texture of the given size.
+fn validate_region(region: &Rectangle, width: u32, height: u32) -> Result<(), TextureError> {
+  let inside = region.min.x >= 0.
+    && region.min.y >= 0.
+    && region.min.x <= region.max.x
+    && region.min.y <= region.max.y
+    && region.max.x <= width as f32
+    && region.max.y <= height as f32;
+
+  match inside {
+    true => Ok(()),
+    false => Err(TextureError::InvalidRegion(*region)),
+  }
+}
+
+/// The number of channels in a texture format.
+fn channel_count(format: TextureFormat) -> usize {
+  match format {
+    TextureFormat::R8 | TextureFormat::R32 | TextureFormat::A8 | TextureFormat::A32 => 1,
+    TextureFormat::RG8 | TextureFormat::RG32 => 2,
+    TextureFormat::RGB8 | TextureFormat::RGB32 => 3,
+    TextureFormat::RGBA8 | TextureFormat::RGBA32 => 4,
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+impl<B: GraphicsBackend> GraphicsBackend for ValidatingBackend<B> {
+  fn begin_frame(&self) {
+    self.inner.begin_frame()
+  }
+
+  fn end_frame(&self) {
+    self.inner.end_frame()
+  }
+
+  fn device_status(&self) -> DeviceStatus {
+    self.inner.device_status()
+  }
+
+  fn device_reset(&self) {
+    // every resource was lost with the device
+    *self.state.lock().unwrap() = ValidationState::default();
+
+    self.inner.device_reset()
+  }
+
+  fn clear_color_buffer(&self, color: Color) {
+    self.inner.clear_color_buffer(color)
+  }
+
+  fn clear_depth_buffer(&self, depth: f32) {
+    self.inner.clear_depth_buffer(depth)
+  }
+
+  fn viewport_size(&self) -> (usize, usize) {
+    self.inner.viewport_size()
+  }
+
+  fn set_viewport_size(&self, size: UVec2) {
+    self.inner.set_viewport_size(size)
+  }
+
+  fn set_blend_state(&self, blend_state: BlendState) {
+    self.inner.set_blend_state(blend_state)
+  }
+
+  fn set_culling_mode(&self, culling_mode: CullingMode) {
+    self.inner.set_culling_mode(culling_mode)
+  }
+
+  fn set_depth_state(&self, depth_state: DepthState) {
+    self.inner.set_depth_state(depth_state)
+  }
+
+  fn set_scissor_mode(&self, scissor_mode: ScissorMode) {
+    self.inner.set_scissor_mode(scissor_mode)
+  }
+
+  fn buffer_create(&self) -> Result<BufferId, BufferError> {
+    let buffer = self.inner.buffer_create()?;
+
+    self.validate(|state| {
+      state.create(GraphicsResource::Buffer(buffer));
+      Ok(())
+    })?;
+
+    Ok(buffer)
+  }
+
+  fn buffer_read_data(
+    &self,
+    buffer: BufferId,
+    offset: usize,
+    length: usize,
+    pointer: *mut u8,
+  ) -> Result<(), BufferError> {
+    self.validate(|state| {
+      if pointer.is_null() {
+        return Err(BufferError::NullPointer);
+      }
+
+      match offset + length <= *state.buffer(buffer)? {
+        true => Ok(()),
+        false => Err(BufferError::BufferTooSmall),
+      }
+    })?;
+
+    self.inner.buffer_read_data(buffer, offset, length, pointer)
+  }
+
+  fn buffer_write_data(
+    &self,
+    buffer: BufferId,
+    usage: BufferUsage,
+    kind: BufferKind,
+    length: usize,
+    pointer: *const u8,
+  ) -> Result<(), BufferError> {
+    self.validate(|state| {
+      if pointer.is_null() && length > 0 {
+        return Err(BufferError::NullPointer);
+      }
+
+      *state.buffer(buffer)? = length;
+
+      Ok(())
+    })?;
+
+    self.inner.buffer_write_data(buffer, usage, kind, length, pointer)
+  }
+
+  fn buffer_bind_storage(&self, buffer: BufferId, binding: u32) -> Result<(), BufferError> {
+    self.validate(|state| state.buffer(buffer).map(|_| ()))?;
+    self.inner.buffer_bind_storage(buffer, binding)
+  }
+
+  fn buffer_delete(&self, buffer: BufferId) -> Result<(), BufferError> {
+    self.validate(|state| {
+      state.buffer(buffer)?;
+      state.delete(GraphicsResource::Buffer(buffer));
+
+      Ok(())
+    })?;
+
+    self.inner.buffer_delete(buffer)
+  }
+
+  fn texture_create(&self, sampler: &TextureSampler) -> Result<TextureId, TextureError> {
+    let texture = self.inner.texture_create(sampler)?;
+
+    self.validate(|state| {
+      state.create(GraphicsResource::Texture(texture));
+      Ok(())
+    })?;
+
+    Ok(texture)
+  }
+
+  fn texture_set_options(&self, texture: TextureId, sampler: &TextureSampler) -> Result<(), TextureError> {
+    self.validate(|state| state.texture(texture).map(|_| ()))?;
+    self.inner.texture_set_options(texture, sampler)
+  }
+
+  fn texture_initialize(
+    &self,
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    self.validate(|state| {
+      let info = state.texture(texture)?;
+
+      if width == 0 || height == 0 {
+        return Err(TextureError::InvalidSize(width, height));
+      }
+
+      *info = TextureInfo {
+        width,
+        height,
+        layers: 1,
+        format: Some(format),
+      };
+
+      Ok(())
+    })?;
+
+    self.inner.texture_initialize(texture, width, height, format)
+  }
+
+  fn texture_read_data(
+    &self,
+    texture: TextureId,
+    length: usize,
+    pixel_format: TextureFormat,
+    pixels: *mut u8,
+    mip_level: usize,
+  ) -> Result<(), TextureError> {
+    self.validate(|state| {
+      let (_, format) = state.initialized_texture(texture)?;
+
+      validate_format(format, pixel_format)
+    })?;
+
+    self
+      .inner
+      .texture_read_data(texture, length, pixel_format, pixels, mip_level)
+  }
+
+  fn texture_write_data(
+    &self,
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    pixels: *const u8,
+    internal_format: TextureFormat,
+    pixel_format: TextureFormat,
+    mip_level: usize,
+  ) -> Result<(), TextureError> {
+    self.validate(|state| {
+      let info = state.texture(texture)?;
+
+      // writing no pixels just allocates (or releases) storage
+      if !pixels.is_null() {
+        if width == 0 || height == 0 {
+          return Err(TextureError::InvalidSize(width, height));
+        }
+
+        validate_format(internal_format, pixel_format)?;
+      }
+
+      if mip_level == 0 {
+        *info = TextureInfo {
+          width,
+          height,
+          layers: 1,
+          format: Some(internal_format),
+        };
+      }
+
+      Ok(())
+    })?;
+
+    self
+      .inner
+      .texture_write_data(texture, width, height, pixels, internal_format, pixel_format, mip_level)
+  }
+
+  fn texture_write_sub_data(
+    &self,
+    texture: TextureId,
+    region: &Rectangle,
+    pixels: *const u8,
+    pixel_format: TextureFormat,
+    mip_level: usize,
+  ) -> Result<(), TextureError> {
+    self.validate(|state| {
+      let (info, format) = state.initialized_texture(texture)?;
+
+      validate_format(format, pixel_format)?;
+      validate_region(region, info.width >> mip_level, info.height >> mip_level)
+    })?;
+
+    self
+      .inner
+      .texture_write_sub_data(texture, region, pixels, pixel_format, mip_level)
+  }
+
+  fn texture_initialize_layers(
+    &self,
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    layers: u32,
+    format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    self.validate(|state| {
+      let info = state.texture(texture)?;
+
+      if width == 0 || height == 0 {
+        return Err(TextureError::InvalidSize(width, height));
+      }
+
+      if layers == 0 {
+        return Err(TextureError::InvalidLayer(layers));
+      }
+
+      *info = TextureInfo {
+        width,
+        height,
+        layers,
+        format: Some(format),
+      };
+
+      Ok(())
+    })?;
+
+    self
+      .inner
+      .texture_initialize_layers(texture, width, height, layers, format)
+  }
+
+  fn texture_write_layer_data(
+    &self,
+    texture: TextureId,
+    region: &Rectangle,
+    layer: u32,
+    pixels: *const u8,
+    pixel_format: TextureFormat,
+  ) -> Result<(), TextureError> {
+    self.validate(|state| {
+      let (info, format) = state.initialized_texture(texture)?;
+
+      if layer >= info.layers {
+        return Err(TextureError::InvalidLayer(layer));
+      }
+
+      validate_format(format, pixel_format)?;
+      validate_region(region, info.width, info.height)
+    })?;
+
+    self
+      .inner
+      .texture_write_layer_data(texture, region, layer, pixels, pixel_format)
+  }
+
+  fn texture_set_mip_range(&self, texture: TextureId, base_level: usize, max_level: usize) -> Result<(), TextureError> {
+    self.validate(|state| {
+      state.texture(texture)?;
+
+      match base_level <= max_level {
+        true => Ok(()),
+        false => Err(TextureError::InvalidMipRange(base_level, max_level)),
+      }
+    })?;
+
+    self.inner.texture_set_mip_range(texture, base_level, max_level)
+  }
+
+  fn texture_delete(&self, texture: TextureId) -> Result<(), TextureError> {
+    self.validate(|state| {
+      state.texture(texture)?;
+      state.delete(GraphicsResource::Texture(texture));
+
+      Ok(())
+    })?;
+
+    self.inner.texture_delete(texture)
+  }
+
+  fn shader_create(&self) -> Result<ShaderId, ShaderError> {
+    let shader = self.inner.shader_create()?;
+
+    self.validate(|state| {
+      state.create(GraphicsResource::Shader(shader));
+      Ok(())
+    })?;
+
+    Ok(shader)
+  }
+
+  fn shader_link(&self, shader: ShaderId, kernels: &[ShaderKernel]) -> Result<(), ShaderError> {
+    self.validate(|state| {
+      state.shader(shader)?;
+
+      match kernels.is_empty() {
+        true => Err(ShaderError::CompileError("no kernels to link".to_string())),
+        false => Ok(()),
+      }
+    })?;
+
+    self.inner.shader_link(shader, kernels)?;
+    self.validate(|state| {
+      *state.shader(shader)? = true;
+      Ok(())
+    })
+  }
+
+  fn shader_link_async(&self, shader: ShaderId, kernels: &[ShaderKernel]) -> Result<(), ShaderError> {
+    self.validate(|state| {
+      state.shader(shader)?;
+
+      match kernels.is_empty() {
+        true => Err(ShaderError::CompileError("no kernels to link".to_string())),
+        false => Ok(()),
+      }
+    })?;
+
+    // the program isn't usable until its status says it's linked
+    self.inner.shader_link_async(shader, kernels)
+  }
+
+  fn shader_link_status(&self, shader: ShaderId) -> ShaderLinkStatus {
+    if let Err(error) = self.validate(|state| state.shader(shader).map(|_| ())) {
+      return ShaderLinkStatus::Failed(format!("{error:?}"));
+    }
+
+    let status = self.inner.shader_link_status(shader);
+
+    if status == ShaderLinkStatus::Linked {
+      let _ = self.validate(|state| {
+        *state.shader(shader)? = true;
+        Ok::<_, ShaderError>(())
+      });
+    }
+
+    status
+  }
+
+  fn shader_binary(&self, shader: ShaderId) -> Option<ShaderBinary> {
+    self.validate(|state| state.linked_shader(shader)).ok()?;
+    self.inner.shader_binary(shader)
+  }
+
+  fn shader_link_binary(&self, shader: ShaderId, binary: &ShaderBinary) -> Result<(), ShaderError> {
+    self.validate(|state| state.shader(shader).map(|_| ()))?;
+    self.inner.shader_link_binary(shader, binary)?;
+    self.validate(|state| {
+      *state.shader(shader)? = true;
+      Ok(())
+    })
+  }
+
+  fn shader_uniform_location(&self, shader: ShaderId, name: &str) -> Option<usize> {
+    self.validate(|state| state.linked_shader(shader)).ok()?;
+    self.inner.shader_uniform_location(shader, name)
+  }
+
+  fn shader_set_uniform(&self, shader: ShaderId, location: usize, value: &ShaderUniform) -> Result<(), ShaderError> {
+    self.validate(|state| state.linked_shader(shader))?;
+    self.inner.shader_set_uniform(shader, location, value)
+  }
+
+  fn shader_activate(&self, shader: ShaderId) -> Result<(), ShaderError> {
+    self.validate(|state| state.linked_shader(shader))?;
+    self.inner.shader_activate(shader)
+  }
+
+  fn shader_dispatch_compute(&self, shader: ShaderId, x: u32, y: u32, z: u32) -> Result<(), ShaderError> {
+    self.validate(|state| state.linked_shader(shader))?;
+    self.inner.shader_dispatch_compute(shader, x, y, z)
+  }
+
+  fn shader_memory_barrier(&self, barrier: MemoryBarrier) -> Result<(), ShaderError> {
+    self.inner.shader_memory_barrier(barrier)
+  }
+
+  fn shader_delete(&self, shader: ShaderId) -> Result<(), ShaderError> {
+    self.validate(|state| {
+      state.shader(shader)?;
+      state.delete(GraphicsResource::Shader(shader));
+
+      Ok(())
+    })?;
+
+    self.inner.shader_delete(shader)
+  }
+
+  fn mesh_create(
+    &self,
+    vertices: BufferId,
+    indices: BufferId,
+    descriptors: &[VertexDescriptor],
+  ) -> Result<MeshId, MeshError> {
+    self.validate(|state| {
+      for buffer in [vertices, indices] {
+        if state.buffer(buffer).is_err() {
+          return Err(MeshError::InvalidBuffer(buffer));
+        }
+      }
+
+      Ok(())
+    })?;
+
+    let mesh = self.inner.mesh_create(vertices, indices, descriptors)?;
+
+    self.validate(|state| {
+      state.create(GraphicsResource::Mesh(mesh));
+      Ok(())
+    })?;
+
+    Ok(mesh)
+  }
+
+  fn mesh_draw(
+    &self,
+    mesh: MeshId,
+    topology: PrimitiveTopology,
+    vertex_count: usize,
+    index_count: usize,
+  ) -> Result<(), MeshError> {
+    self.validate(|state| state.mesh(mesh))?;
+    self.inner.mesh_draw(mesh, topology, vertex_count, index_count)
+  }
+
+  fn mesh_delete(&self, mesh: MeshId) -> Result<(), MeshError> {
+    self.validate(|state| {
+      state.mesh(mesh)?;
+      state.delete(GraphicsResource::Mesh(mesh));
+
+      Ok(())
+    })?;
+
+    self.inner.mesh_delete(mesh)
+  }
+
+  fn target_create(
+    &self,
+    color_attachment: TextureId,
+    depth_attachment: Option<TextureId>,
+    stencil_attachment: Option<TextureId>,
+  ) -> Result<TargetId, TargetError> {
+    self.validate(|state| {
+      let attachments = [Some(color_attachment), depth_attachment, stencil_attachment];
+
+      for texture in attachments.into_iter().flatten() {
+        if state.initialized_texture(texture).is_err() {
+          return Err(TargetError::InvalidAttachment(texture));
+        }
+      }
+
+      Ok(())
+    })?;
+
+    let target = self
+      .inner
+      .target_create(color_attachment, depth_attachment, stencil_attachment)?;
+
+    self.validate(|state| {
+      state.create(GraphicsResource::Target(target));
+      Ok(())
+    })?;
+
+    Ok(target)
+  }
+
+  fn target_activate(&self, target: TargetId) -> Result<(), TargetError> {
+    self.validate(|state| state.target(target))?;
+    self.inner.target_activate(target)
+  }
+
+  fn target_set_default(&self) -> Result<(), TargetError> {
+    self.inner.target_set_default()
+  }
+
+  fn target_blit_to_active(
+    &self,
+    target: TargetId,
+    source_rect: Option<Rectangle>,
+    dest_rect: Option<Rectangle>,
+    filter: TextureFilter,
+  ) -> Result<(), TargetError> {
+    self.validate(|state| state.target(target))?;
+    self.inner.target_blit_to_active(target, source_rect, dest_rect, filter)
+  }
+
+  fn target_delete(&self, target: TargetId) -> Result<(), TargetError> {
+    self.validate(|state| {
+      state.target(target)?;
+      state.delete(GraphicsResource::Target(target));
+
+      Ok(())
+    })?;
+
+    self.inner.target_delete(target)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::headless::HeadlessGraphicsBackend;
+
+  fn create_backend() -> ValidatingBackend<HeadlessGraphicsBackend> {
+    ValidatingBackend::new(HeadlessGraphicsBackend::default())
+  }
+
+  #[test]
+  fn it_should_reject_use_after_delete() {
+    let backend = create_backend();
+    let buffer = backend.buffer_create().unwrap();
+
+    backend.buffer_delete(buffer).unwrap();
+
+    assert!(matches!(backend.buffer_delete(buffer), Err(BufferError::Deleted(_))));
+    assert!(matches!(
+      backend.buffer_bind_storage(BufferId::from(99u32), 0),
+      Err(BufferError::InvalidId(_))
+    ));
+    assert!(matches!(
+      backend.mesh_create(buffer, buffer, &[]),
+      Err(MeshError::InvalidBuffer(_))
+    ));
+  }
+
+  #[test]
+  fn it_should_reject_invalid_texture_uploads() {
+    let backend = create_backend();
+    let texture = backend.texture_create(&TextureOptions::default().sampler).unwrap();
+    let pixels = [0u8; 4];
+
+    assert!(matches!(
+      backend.texture_write_sub_data(texture, &Rectangle::default(), pixels.as_ptr(), TextureFormat::RGBA8, 0),
+      Err(TextureError::NotInitialized(_))
+    ));
+    assert!(matches!(
+      backend.texture_initialize(texture, 0, 16, TextureFormat::RGBA8),
+      Err(TextureError::InvalidSize(0, 16))
+    ));
+
+    backend
+      .texture_initialize(texture, 16, 16, TextureFormat::RGBA8)
+      .unwrap();
+
+    let region = Rectangle::new(common::vec2(8., 8.), common::vec2(24., 24.));
+
+    assert!(matches!(
+      backend.texture_write_sub_data(texture, &region, pixels.as_ptr(), TextureFormat::RGBA8, 0),
+      Err(TextureError::InvalidRegion(_))
+    ));
+    assert!(matches!(
+      backend.texture_write_sub_data(texture, &Rectangle::default(), pixels.as_ptr(), TextureFormat::R8, 0),
+      Err(TextureError::FormatMismatch { .. })
+    ));
+    assert!(backend
+      .texture_write_sub_data(
+        texture,
+        &Rectangle::default(),
+        pixels.as_ptr(),
+        TextureFormat::RGBA32,
+        0
+      )
+      .is_ok());
+  }
+
+  #[test]
+  fn it_should_reject_unlinked_shaders() {
+    let backend = create_backend();
+    let shader = backend.shader_create().unwrap();
+
+    assert!(matches!(
+      backend.shader_activate(shader),
+      Err(ShaderError::NotLinked(_))
+    ));
+
+    backend
+      .shader_link(shader, &[ShaderKernel {
+        kind: ShaderKind::Vertex,
+        code: String::new(),
+      }])
+      .unwrap();
+
+    assert!(backend.shader_activate(shader).is_ok());
+  }
+}
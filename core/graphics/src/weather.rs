@@ -0,0 +1,500 @@
+//! Weather effects.
+//!
+//! [`Weather`] is a small state machine over [`WeatherKind`]s that blends the
+//! [`WeatherProfile`] of each as it changes. Each frame it simulates rain or
+//! snow around the camera, and provides the parameters for wetness and fog
+//! post effects, and volumes to crossfade the looping audio of each kind.
+
+use std::{f32::consts::FRAC_PI_2, ops::Range};
+
+use common::{vec2, Angle, Color32, FastHashMap, Lerp, Random, Rectangle, Vec2, WeightedSet};
+
+use super::*;
+
+/// How far past the edges of the view precipitation is simulated, so that it
+/// doesn't pop in when the camera moves.
+const VIEW_MARGIN: f32 = 2.;
+
+/// The kinds of weather.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum WeatherKind {
+  #[default]
+  Clear,
+  Rain,
+  Snow,
+  Fog,
+}
+
+/// Particles falling from the sky, like rain or snow.
+#[derive(Clone, Debug)]
+pub struct Precipitation {
+  /// The number of particles per square world unit.
+  pub density: f32,
+  /// The velocity particles fall at in still air.
+  pub velocity: Vec2,
+  /// How far particles drift side to side as they fall.
+  pub sway: f32,
+  /// How much of the wind's velocity particles pick up.
+  pub wind_influence: f32,
+  pub size: Vec2,
+  pub color: Color32,
+}
+
+/// Describes how a kind of weather looks.
+#[derive(Clone, Debug)]
+pub struct WeatherProfile {
+  pub precipitation: Option<Precipitation>,
+  /// How wet surfaces look, from 0 to 1.
+  pub wetness: f32,
+  /// How thick the fog is, from 0 to 1.
+  pub fog_density: f32,
+  pub fog_color: Color32,
+}
+
+impl WeatherKind {
+  /// The default profile for this kind of weather.
+  pub fn default_profile(self) -> WeatherProfile {
+    match self {
+      WeatherKind::Clear => WeatherProfile {
+        precipitation: None,
+        wetness: 0.,
+        fog_density: 0.,
+        fog_color: Color32::rgb(200, 200, 210),
+      },
+      WeatherKind::Rain => WeatherProfile {
+        precipitation: Some(Precipitation {
+          density: 0.5,
+          velocity: vec2(0., -20.),
+          sway: 0.,
+          wind_influence: 1.,
+          size: vec2(0.05, 0.5),
+          color: Color32::rgba(170, 190, 220, 160),
+        }),
+        wetness: 1.,
+        fog_density: 0.2,
+        fog_color: Color32::rgb(120, 130, 145),
+      },
+      WeatherKind::Snow => WeatherProfile {
+        precipitation: Some(Precipitation {
+          density: 0.3,
+          velocity: vec2(0., -2.),
+          sway: 0.5,
+          wind_influence: 0.5,
+          size: vec2(0.15, 0.15),
+          color: Color32::WHITE,
+        }),
+        wetness: 0.2,
+        fog_density: 0.3,
+        fog_color: Color32::rgb(220, 225, 235),
+      },
+      WeatherKind::Fog => WeatherProfile {
+        precipitation: None,
+        wetness: 0.3,
+        fog_density: 0.8,
+        fog_color: Color32::rgb(180, 185, 190),
+      },
+    }
+  }
+}
+
+/// The parameters for the weather's post effects, blended across the current
+/// weather.
+#[derive(Clone, Debug)]
+pub struct WeatherEffects {
+  pub wetness: f32,
+  pub fog_density: f32,
+  pub fog_color: Color32,
+}
+
+impl WeatherEffects {
+  /// A shader uniform key for the wetness of surfaces.
+  pub const WETNESS: ShaderUniformKey<f32> = ShaderUniformKey::new("u_wetness");
+  /// A shader uniform key for the density of the fog.
+  pub const FOG_DENSITY: ShaderUniformKey<f32> = ShaderUniformKey::new("u_fog_density");
+  /// A shader uniform key for the color of the fog.
+  pub const FOG_COLOR: ShaderUniformKey<Color32> = ShaderUniformKey::new("u_fog_color");
+
+  /// Sets the effect parameters on a post effect material.
+  pub fn apply(&self, material: &mut Material) {
+    material.set_uniform(Self::WETNESS, self.wetness);
+    material.set_uniform(Self::FOG_DENSITY, self.fog_density);
+    material.set_uniform(Self::FOG_COLOR, self.fog_color);
+  }
+}
+
+/// A single particle of precipitation.
+#[derive(Clone, Debug)]
+struct WeatherParticle {
+  kind: WeatherKind,
+  position: Vec2,
+  velocity: Vec2,
+  phase: f32,
+}
+
+/// A weather state machine, and the effects of the current weather.
+///
+/// Changing the weather blends from the old weather to the new over time. If
+/// transitions are added, the weather also changes by itself: it holds for a
+/// random time, then picks one of the transitions out of the current weather.
+pub struct Weather {
+  profiles: FastHashMap<WeatherKind, WeatherProfile>,
+  transitions: FastHashMap<WeatherKind, WeightedSet<WeatherKind>>,
+  current: WeatherKind,
+  weights: FastHashMap<WeatherKind, f32>,
+  blend_duration: f32,
+  hold_time: Range<f32>,
+  hold_remaining: Option<f32>,
+  wind: Vec2,
+  time: f32,
+  view: Rectangle,
+  particles: Vec<WeatherParticle>,
+  random: Random,
+}
+
+impl Default for Weather {
+  fn default() -> Self {
+    Self::new(WeatherKind::Clear)
+  }
+}
+
+impl Weather {
+  /// Creates a new weather, starting in the given kind.
+  pub fn new(initial: WeatherKind) -> Self {
+    let profiles = [
+      WeatherKind::Clear,
+      WeatherKind::Rain,
+      WeatherKind::Snow,
+      WeatherKind::Fog,
+    ]
+    .map(|kind| (kind, kind.default_profile()));
+
+    let mut weights = FastHashMap::default();
+
+    weights.insert(initial, 1.);
+
+    Self {
+      profiles: profiles.into_iter().collect(),
+      transitions: FastHashMap::default(),
+      current: initial,
+      weights,
+      blend_duration: 5.,
+      hold_time: 60.0..180.0,
+      hold_remaining: None,
+      wind: Vec2::ZERO,
+      time: 0.,
+      view: Rectangle::default(),
+      particles: Vec::new(),
+      random: Random::with_thread_local_seed(),
+    }
+  }
+
+  /// Replaces the profile for a kind of weather.
+  pub fn with_profile(mut self, kind: WeatherKind, profile: WeatherProfile) -> Self {
+    self.profiles.insert(kind, profile);
+    self
+  }
+
+  /// Allows the weather to change from one kind to another by itself, with
+  /// the given relative likelihood.
+  pub fn with_transition(mut self, from: WeatherKind, to: WeatherKind, weight: f32) -> Self {
+    self.transitions.entry(from).or_default().add(to, weight);
+    self
+  }
+
+  /// Sets how long, in seconds, weather holds before changing by itself.
+  pub fn with_hold_time(mut self, hold_time: Range<f32>) -> Self {
+    self.hold_time = hold_time;
+    self
+  }
+
+  /// Sets how long, in seconds, changes in the weather take to blend in.
+  pub fn with_blend_duration(mut self, duration: f32) -> Self {
+    self.blend_duration = duration;
+    self
+  }
+
+  /// Seeds the random choice of transitions and particle placement.
+  pub fn with_seed(mut self, seed: u64) -> Self {
+    self.random = Random::with_seed(seed);
+    self
+  }
+
+  /// The weather being changed to, or the current weather if settled.
+  pub fn current(&self) -> WeatherKind {
+    self.current
+  }
+
+  /// Determines if the weather is still blending in from a previous kind.
+  pub fn is_blending(&self) -> bool {
+    self.weight(self.current) < 1.
+  }
+
+  /// How much of a kind of weather is in the current blend, from 0 to 1.
+  pub fn weight(&self, kind: WeatherKind) -> f32 {
+    self.weights.get(&kind).copied().unwrap_or(0.)
+  }
+
+  /// Changes the weather, blending from whatever it is now.
+  pub fn set_weather(&mut self, kind: WeatherKind) {
+    self.current = kind;
+    self.hold_remaining = None;
+  }
+
+  /// Sets the wind that blows precipitation around.
+  pub fn set_wind(&mut self, wind: Vec2) {
+    self.wind = wind;
+  }
+
+  /// Advances the weather, simulating precipitation around the given view
+  /// rectangle of the camera.
+  pub fn update(&mut self, delta_time: f32, view: Rectangle) {
+    self.time += delta_time;
+    self.view = Rectangle::from_size(view.center(), view.size() + VIEW_MARGIN * 2.);
+
+    self.update_state(delta_time);
+    self.update_particles(delta_time);
+  }
+
+  /// Transitions by itself, and blends towards the current weather.
+  fn update_state(&mut self, delta_time: f32) {
+    if let Some(transitions) = self.transitions.get(&self.current) {
+      let hold_time = self.hold_time.clone();
+      let remaining = self
+        .hold_remaining
+        .get_or_insert_with(|| self.random.next_range(hold_time));
+
+      *remaining -= delta_time;
+
+      if *remaining <= 0. {
+        if let Some(&next) = transitions.select(&mut self.random) {
+          self.set_weather(next);
+        }
+      }
+    }
+
+    let step = if self.blend_duration > 0. {
+      delta_time / self.blend_duration
+    } else {
+      1.
+    };
+
+    // fade out everything else, and give the current weather what's left
+    let current = self.current;
+
+    self.weights.retain(|kind, weight| {
+      *weight = (*weight - step).max(0.);
+      *kind != current && *weight > 0.
+    });
+
+    let others = self.weights.values().sum::<f32>();
+
+    self.weights.insert(current, 1. - others);
+  }
+
+  /// Moves precipitation, keeping as much around the view as the blend calls
+  /// for.
+  fn update_particles(&mut self, delta_time: f32) {
+    let (min, size) = (self.view.min(), self.view.size());
+    let area = self.view.area();
+
+    for (&kind, profile) in &self.profiles {
+      let Some(precipitation) = &profile.precipitation else {
+        continue;
+      };
+
+      let target = (precipitation.density * area * self.weight(kind)) as usize;
+      let mut count = self.particles.iter().filter(|particle| particle.kind == kind).count();
+
+      // particles that leave the view wrap around to its other side, unless
+      // there are too many, which is how precipitation thins out
+      self.particles.retain_mut(|particle| {
+        if particle.kind != kind {
+          return true;
+        }
+
+        let sway = (self.time * 2. + particle.phase).sin() * precipitation.sway;
+
+        particle.velocity = precipitation.velocity + self.wind * precipitation.wind_influence + vec2(sway, 0.);
+        particle.position += particle.velocity * delta_time;
+
+        let offset = particle.position - min;
+
+        if offset.cmpge(Vec2::ZERO).all() && offset.cmplt(size).all() {
+          return true;
+        }
+
+        if count > target {
+          count -= 1;
+          return false;
+        }
+
+        particle.position = min + offset.rem_euclid(size);
+        true
+      });
+
+      for _ in count..target {
+        let position = min + size * vec2(self.random.next::<f32>(), self.random.next::<f32>());
+
+        self.particles.push(WeatherParticle {
+          kind,
+          position,
+          velocity: precipitation.velocity,
+          phase: self.random.next_range(0.0..std::f32::consts::TAU),
+        });
+      }
+    }
+  }
+
+  /// The number of particles of precipitation being simulated.
+  pub fn particle_count(&self) -> usize {
+    self.particles.len()
+  }
+
+  /// The post effect parameters of the current blend of weather.
+  pub fn effects(&self) -> WeatherEffects {
+    let mut effects = WeatherEffects {
+      wetness: 0.,
+      fog_density: 0.,
+      fog_color: Color32::CLEAR,
+    };
+
+    let mut total_weight = 0.;
+
+    for (kind, &weight) in &self.weights {
+      let Some(profile) = self.profiles.get(kind) else {
+        continue;
+      };
+
+      total_weight += weight;
+
+      effects.wetness += profile.wetness * weight;
+      effects.fog_density += profile.fog_density * weight;
+      effects.fog_color = Color32::lerp(effects.fog_color, profile.fog_color, weight / total_weight);
+    }
+
+    effects
+  }
+
+  /// The volume for the looping ambience of a kind of weather, e.g. the gain
+  /// for an audio source playing rain.
+  ///
+  /// Volumes crossfade at equal power as the weather blends.
+  pub fn audio_volume(&self, kind: WeatherKind) -> f32 {
+    (self.weight(kind) * FRAC_PI_2).sin()
+  }
+
+  /// Draws the precipitation with the given particle texture.
+  ///
+  /// Particles are stretched by their profile's size and rotated to face the
+  /// way they're falling, so a round texture works for both rain and snow.
+  pub fn draw(&self, batch: &mut SpriteBatch, texture: &Texture) {
+    for particle in &self.particles {
+      let Some(precipitation) = &self.profiles[&particle.kind].precipitation else {
+        continue;
+      };
+
+      batch.draw_sprite(texture, &SpriteOptions {
+        position: particle.position,
+        rotation: Angle::Radians((particle.velocity.to_angle() - FRAC_PI_2) as f64),
+        scale: precipitation.size,
+        color: precipitation.color,
+      });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn create_view() -> Rectangle {
+    Rectangle::from_size(Vec2::ZERO, vec2(16., 16.))
+  }
+
+  #[test]
+  fn it_should_blend_between_weather() {
+    let mut weather = Weather::new(WeatherKind::Clear).with_blend_duration(2.).with_seed(42);
+
+    weather.set_weather(WeatherKind::Rain);
+    weather.update(1., create_view());
+
+    assert!(weather.is_blending());
+    assert_eq!(weather.weight(WeatherKind::Clear), 0.5);
+    assert_eq!(weather.weight(WeatherKind::Rain), 0.5);
+    assert_eq!(weather.effects().wetness, 0.5);
+    assert!((weather.audio_volume(WeatherKind::Rain) - 0.5f32.sqrt()).abs() < 1e-5);
+
+    // changing again mid-blend fades out both rain and clear
+    weather.set_weather(WeatherKind::Snow);
+    weather.update(0.5, create_view());
+
+    assert_eq!(weather.weight(WeatherKind::Clear), 0.25);
+    assert_eq!(weather.weight(WeatherKind::Rain), 0.25);
+    assert_eq!(weather.weight(WeatherKind::Snow), 0.5);
+
+    weather.update(2., create_view());
+
+    assert!(!weather.is_blending());
+    assert_eq!(weather.weight(WeatherKind::Rain), 0.);
+    assert_eq!(
+      weather.effects().fog_color,
+      WeatherKind::Snow.default_profile().fog_color
+    );
+  }
+
+  #[test]
+  fn it_should_keep_precipitation_around_the_camera() {
+    let mut weather = Weather::new(WeatherKind::Rain).with_seed(42);
+    let view = create_view();
+
+    weather.update(0.1, view);
+
+    let count = weather.particle_count();
+
+    assert!(count > 0);
+
+    let view = Rectangle::from_size(vec2(100., 50.), view.size());
+
+    for _ in 0..10 {
+      weather.update(0.1, view);
+    }
+
+    assert_eq!(weather.particle_count(), count);
+    assert!(weather.particles.iter().all(|particle| {
+      let offset = (particle.position - view.center()).abs();
+
+      offset.cmple(view.size() / 2. + VIEW_MARGIN).all()
+    }));
+
+    weather.set_weather(WeatherKind::Clear);
+
+    for _ in 0..100 {
+      weather.update(0.1, view);
+    }
+
+    assert_eq!(weather.particle_count(), 0);
+  }
+
+  #[test]
+  fn it_should_transition_by_itself() {
+    let mut weather = Weather::new(WeatherKind::Clear)
+      .with_transition(WeatherKind::Clear, WeatherKind::Fog, 1.)
+      .with_hold_time(1.0..2.0)
+      .with_seed(42);
+
+    weather.update(0.5, create_view());
+
+    assert_eq!(weather.current(), WeatherKind::Clear);
+
+    weather.update(2., create_view());
+
+    assert_eq!(weather.current(), WeatherKind::Fog);
+
+    // fog has no transitions out, so it holds
+    for _ in 0..100 {
+      weather.update(1., create_view());
+    }
+
+    assert_eq!(weather.current(), WeatherKind::Fog);
+  }
+}
@@ -0,0 +1,120 @@
+use common::{FastHashMap, FastHashSet, StringName};
+
+use super::*;
+
+/// A physical input that can be bound to an action.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum InputBinding {
+  Key(VirtualKey),
+  Mouse(MouseButton),
+  Gamepad(GamepadButton),
+}
+
+/// Whether an action was pressed or released.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ActionState {
+  Pressed,
+  Released,
+}
+
+/// A change in the state of an action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionEvent {
+  pub action: StringName,
+  pub state: ActionState,
+}
+
+/// Maps physical inputs to named actions, like `jump` or `punch`.
+///
+/// Gameplay code reads actions rather than keys, so controls can be rebound
+/// without touching it.
+#[derive(Clone, Default)]
+pub struct ActionMap {
+  bindings: FastHashMap<InputBinding, StringName>,
+  held: FastHashSet<StringName>,
+}
+
+impl ActionMap {
+  /// Creates an empty action map.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Binds an input to an action, replacing any existing binding for it.
+  pub fn bind(&mut self, binding: InputBinding, action: impl Into<StringName>) {
+    self.bindings.insert(binding, action.into());
+  }
+
+  /// Removes the binding for an input.
+  pub fn unbind(&mut self, binding: InputBinding) {
+    self.bindings.remove(&binding);
+  }
+
+  /// Determines if the given action is held down.
+  pub fn is_held(&self, action: impl Into<StringName>) -> bool {
+    self.held.contains(&action.into())
+  }
+
+  /// Releases every held action, without raising events; for when the
+  /// device behind them goes away.
+  pub fn release_all(&mut self) {
+    self.held.clear();
+  }
+
+  /// Translates an input event into an action event, if it's bound.
+  ///
+  /// Repeated presses from held keys are ignored.
+  pub fn translate(&mut self, event: &InputEvent) -> Option<ActionEvent> {
+    let (binding, state) = match event {
+      InputEvent::KeyboardEvent(KeyboardEvent::KeyDown(key)) => (InputBinding::Key(*key), ActionState::Pressed),
+      InputEvent::KeyboardEvent(KeyboardEvent::KeyUp(key)) => (InputBinding::Key(*key), ActionState::Released),
+      InputEvent::MouseEvent(MouseEvent::MouseDown(button)) => (InputBinding::Mouse(*button), ActionState::Pressed),
+      InputEvent::MouseEvent(MouseEvent::MouseUp(button)) => (InputBinding::Mouse(*button), ActionState::Released),
+      InputEvent::MouseEvent(MouseEvent::MouseMove { .. } | MouseEvent::MouseWheel { .. }) => return None,
+      InputEvent::GamepadEvent(GamepadEvent::ButtonDown(button)) => {
+        (InputBinding::Gamepad(*button), ActionState::Pressed)
+      }
+      InputEvent::GamepadEvent(GamepadEvent::ButtonUp(button)) => {
+        (InputBinding::Gamepad(*button), ActionState::Released)
+      }
+    };
+
+    let action = *self.bindings.get(&binding)?;
+    let changed = match state {
+      ActionState::Pressed => self.held.insert(action),
+      ActionState::Released => self.held.remove(&action),
+    };
+
+    changed.then_some(ActionEvent { action, state })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_translate_bound_inputs_to_actions() {
+    let mut actions = ActionMap::new();
+
+    actions.bind(InputBinding::Key(VirtualKey::Space), "jump");
+
+    let press = InputEvent::KeyboardEvent(KeyboardEvent::KeyDown(VirtualKey::Space));
+    let release = InputEvent::KeyboardEvent(KeyboardEvent::KeyUp(VirtualKey::Space));
+
+    assert_eq!(
+      actions.translate(&press),
+      Some(ActionEvent {
+        action: "jump".into(),
+        state: ActionState::Pressed,
+      })
+    );
+    assert!(actions.is_held("jump"));
+    assert_eq!(actions.translate(&press), None);
+    assert_eq!(actions.translate(&release).unwrap().state, ActionState::Released);
+    assert_eq!(
+      actions.translate(&InputEvent::KeyboardEvent(KeyboardEvent::KeyDown(VirtualKey::Tab))),
+      None
+    );
+  }
+}
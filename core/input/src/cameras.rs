@@ -0,0 +1,189 @@
+//! Keyboard and mouse controls for the debug camera.
+//!
+//! [`DebugCameraControls`] listens to raw input and turns it into a
+//! [`DebugCameraInput`] each frame, independent of the game's own bindings:
+//!
+//! ```rust,ignore
+//! let mut controls = DebugCameraControls::default();
+//!
+//! for event in events {
+//!   controls.on_event(&event);
+//! }
+//!
+//! controls.apply(&mut debug_camera, time.delta_time);
+//! ```
+//!
+//! WASD moves, Q and E move down and up, and Shift moves faster. Hold the
+//! right mouse button to look or orbit, the middle button to pan, and scroll
+//! to zoom. The toggle key turns the camera on and off, and the mode key
+//! cycles between orbit, fly and 2D pan.
+
+use common::{vec3, DebugCamera, DebugCameraInput, FastHashSet, Vec2};
+
+use super::*;
+
+/// Gathers debug camera input from the keyboard and mouse.
+pub struct DebugCameraControls {
+  pub toggle_key: VirtualKey,
+  pub mode_key: VirtualKey,
+  held_keys: FastHashSet<VirtualKey>,
+  held_buttons: FastHashSet<MouseButton>,
+  look: Vec2,
+  pan: Vec2,
+  zoom: f32,
+  toggles: u32,
+  mode_changes: u32,
+}
+
+impl Default for DebugCameraControls {
+  fn default() -> Self {
+    Self {
+      toggle_key: VirtualKey::F8,
+      mode_key: VirtualKey::F9,
+      held_keys: FastHashSet::default(),
+      held_buttons: FastHashSet::default(),
+      look: Vec2::ZERO,
+      pan: Vec2::ZERO,
+      zoom: 0.,
+      toggles: 0,
+      mode_changes: 0,
+    }
+  }
+}
+
+impl DebugCameraControls {
+  /// Takes the input gathered since the last call.
+  pub fn take_input(&mut self) -> DebugCameraInput {
+    let axis = |negative, positive| match (self.held_keys.contains(&negative), self.held_keys.contains(&positive)) {
+      (true, false) => -1.,
+      (false, true) => 1.,
+      _ => 0.,
+    };
+
+    DebugCameraInput {
+      movement: vec3(
+        axis(VirtualKey::A, VirtualKey::D),
+        axis(VirtualKey::Q, VirtualKey::E),
+        axis(VirtualKey::S, VirtualKey::W),
+      ),
+      look: std::mem::take(&mut self.look),
+      pan: std::mem::take(&mut self.pan),
+      zoom: std::mem::take(&mut self.zoom),
+      fast: self.held_keys.contains(&VirtualKey::Shift),
+    }
+  }
+
+  /// Applies the input gathered since the last call to the camera, toggling
+  /// it and cycling its mode as requested.
+  pub fn apply(&mut self, camera: &mut DebugCamera, delta_time: f32) {
+    for _ in 0..std::mem::take(&mut self.toggles) {
+      camera.toggle();
+    }
+
+    for _ in 0..std::mem::take(&mut self.mode_changes) {
+      camera.cycle_mode();
+    }
+
+    camera.update(&self.take_input(), delta_time);
+  }
+}
+
+impl InputListener for DebugCameraControls {
+  fn on_event(&mut self, event: &InputEvent) {
+    match event {
+      InputEvent::KeyboardEvent(KeyboardEvent::KeyDown(key)) => {
+        // ignore repeats from held keys
+        if self.held_keys.insert(*key) {
+          if *key == self.toggle_key {
+            self.toggles += 1;
+          } else if *key == self.mode_key {
+            self.mode_changes += 1;
+          }
+        }
+      }
+      InputEvent::KeyboardEvent(KeyboardEvent::KeyUp(key)) => {
+        self.held_keys.remove(key);
+      }
+      InputEvent::MouseEvent(MouseEvent::MouseDown(button)) => {
+        self.held_buttons.insert(*button);
+      }
+      InputEvent::MouseEvent(MouseEvent::MouseUp(button)) => {
+        self.held_buttons.remove(button);
+      }
+      InputEvent::MouseEvent(MouseEvent::MouseMove { delta, .. }) => {
+        if self.held_buttons.contains(&MouseButton::Right) {
+          self.look += *delta;
+        }
+
+        if self.held_buttons.contains(&MouseButton::Middle) {
+          self.pan += *delta;
+        }
+      }
+      InputEvent::MouseEvent(MouseEvent::MouseWheel { delta }) => {
+        self.zoom += *delta;
+      }
+      InputEvent::GamepadEvent(_) => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{vec2, Camera, DebugCameraMode, DebugCameraSettings};
+
+  use super::*;
+
+  #[test]
+  fn it_should_gather_movement_and_mouse_input() {
+    let mut controls = DebugCameraControls::default();
+
+    for event in [
+      InputEvent::KeyboardEvent(KeyboardEvent::KeyDown(VirtualKey::W)),
+      InputEvent::KeyboardEvent(KeyboardEvent::KeyDown(VirtualKey::A)),
+      InputEvent::KeyboardEvent(KeyboardEvent::KeyDown(VirtualKey::Shift)),
+      InputEvent::MouseEvent(MouseEvent::MouseMove {
+        position: vec2(10., 10.),
+        delta: vec2(10., 10.),
+      }),
+      InputEvent::MouseEvent(MouseEvent::MouseDown(MouseButton::Right)),
+      InputEvent::MouseEvent(MouseEvent::MouseMove {
+        position: vec2(15., 12.),
+        delta: vec2(5., 2.),
+      }),
+      InputEvent::MouseEvent(MouseEvent::MouseWheel { delta: 2. }),
+    ] {
+      controls.on_event(&event);
+    }
+
+    let input = controls.take_input();
+
+    assert_eq!(input.movement, vec3(-1., 0., 1.));
+    assert_eq!(input.look, vec2(5., 2.));
+    assert_eq!(input.pan, Vec2::ZERO);
+    assert_eq!(input.zoom, 2.);
+    assert!(input.fast);
+
+    // mouse input is used up, held keys are not
+    let input = controls.take_input();
+
+    assert_eq!(input.movement, vec3(-1., 0., 1.));
+    assert_eq!(input.look, Vec2::ZERO);
+  }
+
+  #[test]
+  fn it_should_toggle_and_cycle_the_camera() {
+    let mut controls = DebugCameraControls::default();
+    let mut camera = DebugCamera::new(DebugCameraSettings::default());
+    let start = camera.position();
+
+    controls.on_event(&InputEvent::KeyboardEvent(KeyboardEvent::KeyDown(VirtualKey::F8)));
+    controls.on_event(&InputEvent::KeyboardEvent(KeyboardEvent::KeyDown(VirtualKey::F8)));
+    controls.on_event(&InputEvent::KeyboardEvent(KeyboardEvent::KeyDown(VirtualKey::F9)));
+    controls.on_event(&InputEvent::KeyboardEvent(KeyboardEvent::KeyDown(VirtualKey::D)));
+    controls.apply(&mut camera, 1.);
+
+    assert!(camera.is_enabled());
+    assert_eq!(camera.mode(), DebugCameraMode::Fly);
+    assert_ne!(camera.position(), start);
+  }
+}
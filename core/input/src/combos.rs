@@ -0,0 +1,322 @@
+//! Input buffering and combo detection, for action and fighting games.
+//!
+//! An [`InputBuffer`] remembers the actions pressed over the last moments of
+//! play, so a jump pressed just before landing still counts, and
+//! [`ComboPattern`]s look for sequences and chords in it:
+//!
+//! ```rust,ignore
+//! let hadouken = ComboPattern::new()
+//!   .then("down")
+//!   .then("down_forward")
+//!   .then("forward")
+//!   .then("punch");
+//!
+//! if let Some(event) = actions.translate(&event) {
+//!   buffer.push(&event, time.total_time);
+//! }
+//!
+//! if buffer.take(&hadouken, time.total_time) {
+//!   player.throw_fireball();
+//! }
+//! ```
+//!
+//! Times are whatever clock the game runs on, so replays and rollback see the
+//! same combos as the original play.
+
+use common::{RingBuffer, StringName, TimeSpan};
+
+use super::*;
+
+/// An action pressed at some point in time.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BufferedAction {
+  pub action: StringName,
+  pub time: TimeSpan,
+}
+
+/// A ring buffer of recently pressed actions.
+pub struct InputBuffer {
+  presses: RingBuffer<BufferedAction>,
+  /// Presses at or before this time have already been used up.
+  consumed_at: Option<TimeSpan>,
+}
+
+impl InputBuffer {
+  /// Creates a buffer that remembers up to the given number of presses.
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      presses: RingBuffer::new(capacity.max(1)),
+      consumed_at: None,
+    }
+  }
+
+  /// Records an action event; only presses are buffered.
+  pub fn push(&mut self, event: &ActionEvent, time: TimeSpan) {
+    if event.state == ActionState::Pressed {
+      self.press(event.action, time);
+    }
+  }
+
+  /// Records a press of the given action.
+  pub fn press(&mut self, action: impl Into<StringName>, time: TimeSpan) {
+    self.presses.push(BufferedAction {
+      action: action.into(),
+      time,
+    });
+  }
+
+  /// Iterates over the presses that haven't been consumed, newest first.
+  pub fn recent(&self) -> impl Iterator<Item = &BufferedAction> {
+    let consumed_at = self.consumed_at;
+
+    self
+      .presses
+      .iter()
+      .take_while(move |press| consumed_at.is_none_or(|consumed_at| press.time > consumed_at))
+  }
+
+  /// Determines if the action was pressed within the window before `now`.
+  pub fn was_pressed_within(&self, action: impl Into<StringName>, window: TimeSpan, now: TimeSpan) -> bool {
+    let action = action.into();
+
+    self
+      .recent()
+      .take_while(|press| now - press.time <= window)
+      .any(|press| press.action == action)
+  }
+
+  /// Determines if the pattern was just performed.
+  pub fn matches(&self, pattern: &ComboPattern, now: TimeSpan) -> bool {
+    pattern.find(&self.recent().collect::<Vec<_>>(), now).is_some()
+  }
+
+  /// Determines if the pattern was just performed, and if so consumes every
+  /// buffered press, so it doesn't fire again or feed another combo.
+  pub fn take(&mut self, pattern: &ComboPattern, now: TimeSpan) -> bool {
+    let is_match = self.matches(pattern, now);
+
+    if is_match {
+      self.consume(now);
+    }
+
+    is_match
+  }
+
+  /// Consumes every press up to the given time.
+  pub fn consume(&mut self, now: TimeSpan) {
+    self.consumed_at = Some(now);
+  }
+}
+
+/// A single step in a [`ComboPattern`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComboStep {
+  /// One action is pressed.
+  Press(StringName),
+  /// Several actions are pressed together, within the chord window.
+  Chord(Vec<StringName>),
+}
+
+/// A sequence of presses and chords, with some leniency in timing.
+///
+/// Unrelated presses between steps are ignored, so a sloppy motion still
+/// counts as long as each step follows the last within the step window.
+#[derive(Debug, Clone)]
+pub struct ComboPattern {
+  steps: Vec<ComboStep>,
+  /// The longest gap allowed between one step and the next.
+  pub step_window: TimeSpan,
+  /// The longest spread allowed between the presses of a chord.
+  pub chord_window: TimeSpan,
+  /// How long after the last step the combo still counts.
+  pub input_window: TimeSpan,
+}
+
+impl Default for ComboPattern {
+  fn default() -> Self {
+    Self {
+      steps: Vec::new(),
+      step_window: TimeSpan::from_millis(250.),
+      chord_window: TimeSpan::from_millis(50.),
+      input_window: TimeSpan::from_millis(100.),
+    }
+  }
+}
+
+impl ComboPattern {
+  /// Creates an empty pattern with typical timing windows.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a press of the given action as the next step.
+  pub fn then(mut self, action: impl Into<StringName>) -> Self {
+    self.steps.push(ComboStep::Press(action.into()));
+    self
+  }
+
+  /// Adds a chord of the given actions as the next step.
+  pub fn then_chord<S: Into<StringName>>(mut self, actions: impl IntoIterator<Item = S>) -> Self {
+    self
+      .steps
+      .push(ComboStep::Chord(actions.into_iter().map(Into::into).collect()));
+    self
+  }
+
+  /// Sets the longest gap allowed between steps.
+  pub fn with_step_window(mut self, window: TimeSpan) -> Self {
+    self.step_window = window;
+    self
+  }
+
+  /// Sets the longest spread allowed between the presses of a chord.
+  pub fn with_chord_window(mut self, window: TimeSpan) -> Self {
+    self.chord_window = window;
+    self
+  }
+
+  /// The steps of the pattern.
+  pub fn steps(&self) -> &[ComboStep] {
+    &self.steps
+  }
+
+  /// Finds the pattern in presses ordered newest first, returning when it
+  /// started.
+  ///
+  /// Steps are matched from the last backwards, each taking the newest press
+  /// that fits, which leaves the most room for the steps before it.
+  fn find(&self, presses: &[&BufferedAction], now: TimeSpan) -> Option<TimeSpan> {
+    let mut cursor = 0;
+    let mut next_time: Option<TimeSpan> = None;
+
+    for step in self.steps.iter().rev() {
+      let is_in_time = |time: TimeSpan| match next_time {
+        Some(next_time) => time <= next_time && next_time - time <= self.step_window,
+        None => now - time <= self.input_window,
+      };
+
+      let (index, time) = match step {
+        ComboStep::Press(action) => presses[cursor..]
+          .iter()
+          .position(|press| press.action == *action)
+          .map(|index| (cursor + index, presses[cursor + index].time))?,
+        ComboStep::Chord(actions) => {
+          let found = actions
+            .iter()
+            .map(|action| {
+              presses[cursor..]
+                .iter()
+                .position(|press| press.action == *action)
+                .map(|index| cursor + index)
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+          let first = *found.iter().max()?;
+          let last = *found.iter().min()?;
+
+          if presses[last].time - presses[first].time > self.chord_window {
+            return None;
+          }
+
+          (first, presses[first].time)
+        }
+      };
+
+      if !is_in_time(time) {
+        return None;
+      }
+
+      cursor = index + 1;
+      next_time = Some(time);
+    }
+
+    next_time
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn millis(millis: f32) -> TimeSpan {
+    TimeSpan::from_millis(millis)
+  }
+
+  fn quarter_circle_punch() -> ComboPattern {
+    ComboPattern::new()
+      .then("down")
+      .then("down_forward")
+      .then("forward")
+      .then("punch")
+  }
+
+  #[test]
+  fn it_should_detect_sequences_with_leniency() {
+    let mut buffer = InputBuffer::new(16);
+
+    buffer.press("down", millis(0.));
+    buffer.press("down_forward", millis(50.));
+    buffer.press("kick", millis(60.));
+    buffer.press("forward", millis(100.));
+    buffer.press("punch", millis(150.));
+
+    assert!(buffer.matches(&quarter_circle_punch(), millis(160.)));
+    assert!(!buffer.matches(&quarter_circle_punch(), millis(400.)));
+
+    assert!(buffer.take(&quarter_circle_punch(), millis(160.)));
+    assert!(!buffer.matches(&quarter_circle_punch(), millis(160.)));
+  }
+
+  #[test]
+  fn it_should_reject_sequences_that_are_too_slow_or_out_of_order() {
+    let mut buffer = InputBuffer::new(16);
+
+    buffer.press("down", millis(0.));
+    buffer.press("down_forward", millis(400.));
+    buffer.press("forward", millis(450.));
+    buffer.press("punch", millis(500.));
+
+    assert!(!buffer.matches(&quarter_circle_punch(), millis(500.)));
+
+    buffer.press("forward", millis(600.));
+    buffer.press("down_forward", millis(650.));
+    buffer.press("down", millis(700.));
+    buffer.press("punch", millis(750.));
+
+    assert!(!buffer.matches(&quarter_circle_punch(), millis(750.)));
+  }
+
+  #[test]
+  fn it_should_detect_chords() {
+    let throw = ComboPattern::new().then_chord(["punch", "kick"]);
+    let mut buffer = InputBuffer::new(16);
+
+    buffer.press("punch", millis(0.));
+    buffer.press("kick", millis(30.));
+
+    assert!(buffer.matches(&throw, millis(40.)));
+
+    buffer.consume(millis(40.));
+    buffer.press("punch", millis(100.));
+    buffer.press("kick", millis(200.));
+
+    assert!(!buffer.matches(&throw, millis(200.)));
+  }
+
+  #[test]
+  fn it_should_buffer_presses_for_a_window() {
+    let mut buffer = InputBuffer::new(4);
+
+    buffer.push(
+      &ActionEvent {
+        action: "jump".into(),
+        state: ActionState::Pressed,
+      },
+      millis(100.),
+    );
+
+    assert!(buffer.was_pressed_within("jump", millis(100.), millis(150.)));
+    assert!(!buffer.was_pressed_within("jump", millis(100.), millis(300.)));
+    assert!(!buffer.was_pressed_within("dash", millis(100.), millis(150.)));
+  }
+}
@@ -0,0 +1,35 @@
+use common::impl_variant_enum;
+
+/// A gamepad input device.
+pub trait GamepadDevice {
+  /// All pending gamepad events.
+  fn events(&self) -> &[GamepadEvent];
+}
+
+/// A gamepad event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadEvent {
+  ButtonDown(GamepadButton),
+  ButtonUp(GamepadButton),
+}
+
+/// Possible buttons on a gamepad, named by position rather than label.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+  /// A on Xbox pads, cross on PlayStation pads.
+  South,
+  East,
+  West,
+  North,
+  DPadUp,
+  DPadDown,
+  DPadLeft,
+  DPadRight,
+  LeftShoulder,
+  RightShoulder,
+  Start,
+  Select,
+}
+
+impl_variant_enum!(GamepadButton as u8);
@@ -39,6 +39,33 @@ pub enum VirtualKey {
   Backspace,
   Tab,
   Enter,
+  A,
+  B,
+  C,
+  D,
+  E,
+  F,
+  G,
+  H,
+  I,
+  J,
+  K,
+  L,
+  M,
+  N,
+  O,
+  P,
+  Q,
+  R,
+  S,
+  T,
+  U,
+  V,
+  W,
+  X,
+  Y,
+  Z,
+  Shift,
 }
 
 impl_variant_enum!(VirtualKey as u32);
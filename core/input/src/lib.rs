@@ -1,10 +1,20 @@
 //! Input engine for Surreal.
 
+pub use actions::*;
+pub use cameras::*;
+pub use combos::*;
+pub use gamepads::*;
 pub use keyboards::*;
 pub use mouse::*;
+pub use players::*;
 
+mod actions;
+mod cameras;
+mod combos;
+mod gamepads;
 mod keyboards;
 mod mouse;
+mod players;
 
 /// An input event.
 ///
@@ -15,6 +25,7 @@ mod mouse;
 pub enum InputEvent {
   KeyboardEvent(KeyboardEvent),
   MouseEvent(MouseEvent),
+  GamepadEvent(GamepadEvent),
 }
 
 /// A listener for input events.
@@ -12,6 +12,7 @@ pub enum MouseEvent {
   MouseMove { position: Vec2, delta: Vec2 },
   MouseDown(MouseButton),
   MouseUp(MouseButton),
+  MouseWheel { delta: f32 },
 }
 
 /// Possible mouse buttons.
@@ -0,0 +1,322 @@
+//! Routing of input from many devices to local players.
+//!
+//! Each input event arrives tagged with the device that raised it. A
+//! [`PlayerRouter`] lets unclaimed devices join a free player slot by
+//! pressing a join button, gives every player their own [`ActionMap`], and
+//! turns device events into per-player [`PlayerEvent`]s:
+//!
+//! ```rust,ignore
+//! let mut router = PlayerRouter::new(4, actions)
+//!   .with_join_binding(InputBinding::Gamepad(GamepadButton::South))
+//!   .with_join_binding(InputBinding::Key(VirtualKey::Enter));
+//!
+//! for event in device_events {
+//!   match router.handle(&event) {
+//!     Some(PlayerEvent::Joined { player, .. }) => spawn_player(player),
+//!     Some(PlayerEvent::Action { player, event }) => players[player].on_action(event),
+//!     _ => {}
+//!   }
+//! }
+//! ```
+//!
+//! When a player's device is unplugged their slot is held for them, and the
+//! same device, or any unclaimed one pressing join, picks it back up.
+
+use common::FastHashMap;
+
+use super::*;
+
+/// Identifies an input device; assigned by the platform, and stable for as
+/// long as the device stays connected.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct InputDeviceId(pub u32);
+
+/// The kinds of input device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InputDeviceKind {
+  /// A keyboard, along with any mouse the platform pairs with it.
+  Keyboard,
+  Gamepad,
+}
+
+/// An event from a specific input device.
+#[derive(Debug, Clone)]
+pub enum DeviceEvent {
+  Connected(InputDeviceId, InputDeviceKind),
+  Disconnected(InputDeviceId),
+  Input(InputDeviceId, InputEvent),
+}
+
+/// Something that happened to a local player, by slot index.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerEvent {
+  /// A device claimed a free slot.
+  Joined { player: usize, device: InputDeviceId },
+  /// A player left, freeing their slot.
+  Left { player: usize },
+  /// A player's device was disconnected; the slot is held for them.
+  DeviceLost { player: usize },
+  /// A device picked up a player whose device was lost.
+  DeviceRestored { player: usize, device: InputDeviceId },
+  /// A player's action changed state.
+  Action { player: usize, event: ActionEvent },
+}
+
+/// A local player's slot.
+#[derive(Clone)]
+pub struct PlayerSlot {
+  /// The player's own action map.
+  pub actions: ActionMap,
+  state: SlotState,
+}
+
+/// Who holds a [`PlayerSlot`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum SlotState {
+  Free,
+  Claimed(InputDeviceId),
+  /// The device was lost; it's remembered so it can reclaim the slot.
+  Lost(InputDeviceId),
+}
+
+impl PlayerSlot {
+  /// The device driving this player, if it's connected.
+  pub fn device(&self) -> Option<InputDeviceId> {
+    match self.state {
+      SlotState::Claimed(device) => Some(device),
+      _ => None,
+    }
+  }
+
+  /// Determines if a player holds this slot, even if their device is lost.
+  pub fn is_occupied(&self) -> bool {
+    self.state != SlotState::Free
+  }
+}
+
+/// Assigns input devices to local player slots, and routes their input.
+pub struct PlayerRouter {
+  slots: Vec<PlayerSlot>,
+  template: ActionMap,
+  join_bindings: Vec<InputBinding>,
+  devices: FastHashMap<InputDeviceId, InputDeviceKind>,
+}
+
+impl PlayerRouter {
+  /// Creates a router with the given number of slots; each player starts
+  /// with a copy of the given action map.
+  pub fn new(slot_count: usize, actions: ActionMap) -> Self {
+    let slot = PlayerSlot {
+      actions: actions.clone(),
+      state: SlotState::Free,
+    };
+
+    Self {
+      slots: vec![slot; slot_count],
+      template: actions,
+      join_bindings: Vec::new(),
+      devices: FastHashMap::default(),
+    }
+  }
+
+  /// Adds an input that makes an unclaimed device join.
+  pub fn with_join_binding(mut self, binding: InputBinding) -> Self {
+    self.join_bindings.push(binding);
+    self
+  }
+
+  /// The player slots.
+  pub fn slots(&self) -> &[PlayerSlot] {
+    &self.slots
+  }
+
+  /// Mutably accesses a player's slot, to rebind their actions.
+  pub fn slot_mut(&mut self, player: usize) -> Option<&mut PlayerSlot> {
+    self.slots.get_mut(player)
+  }
+
+  /// The player the given device drives, if any.
+  pub fn player_for(&self, device: InputDeviceId) -> Option<usize> {
+    self.slots.iter().position(|slot| slot.device() == Some(device))
+  }
+
+  /// The kind of a connected device.
+  pub fn device_kind(&self, device: InputDeviceId) -> Option<InputDeviceKind> {
+    self.devices.get(&device).copied()
+  }
+
+  /// Assigns a device to a free slot directly, bypassing the join button.
+  pub fn join(&mut self, device: InputDeviceId) -> Option<PlayerEvent> {
+    if self.player_for(device).is_some() {
+      return None;
+    }
+
+    // players waiting on a lost device are picked up before new ones join
+    let player = self
+      .slots
+      .iter()
+      .position(|slot| matches!(slot.state, SlotState::Lost(_)))
+      .or_else(|| self.slots.iter().position(|slot| slot.state == SlotState::Free))?;
+
+    let was_lost = self.slots[player].is_occupied();
+
+    self.slots[player].state = SlotState::Claimed(device);
+
+    Some(match was_lost {
+      true => PlayerEvent::DeviceRestored { player, device },
+      false => PlayerEvent::Joined { player, device },
+    })
+  }
+
+  /// Removes a player, freeing their slot and resetting their actions.
+  pub fn leave(&mut self, player: usize) -> Option<PlayerEvent> {
+    let slot = self.slots.get_mut(player).filter(|slot| slot.is_occupied())?;
+
+    slot.state = SlotState::Free;
+    slot.actions = self.template.clone();
+
+    Some(PlayerEvent::Left { player })
+  }
+
+  /// Handles an event from a device.
+  pub fn handle(&mut self, event: &DeviceEvent) -> Option<PlayerEvent> {
+    match event {
+      DeviceEvent::Connected(device, kind) => {
+        self.devices.insert(*device, *kind);
+
+        // a device that comes back reclaims its own slot straight away
+        let player = self
+          .slots
+          .iter()
+          .position(|slot| slot.state == SlotState::Lost(*device))?;
+
+        self.slots[player].state = SlotState::Claimed(*device);
+
+        Some(PlayerEvent::DeviceRestored {
+          player,
+          device: *device,
+        })
+      }
+      DeviceEvent::Disconnected(device) => {
+        self.devices.remove(device);
+
+        let player = self.player_for(*device)?;
+        let slot = &mut self.slots[player];
+
+        slot.state = SlotState::Lost(*device);
+        slot.actions.release_all();
+
+        Some(PlayerEvent::DeviceLost { player })
+      }
+      DeviceEvent::Input(device, input) => match self.player_for(*device) {
+        Some(player) => {
+          let event = self.slots[player].actions.translate(input)?;
+
+          Some(PlayerEvent::Action { player, event })
+        }
+        None if self.is_join(input) => self.join(*device),
+        None => None,
+      },
+    }
+  }
+
+  /// Determines if the input presses one of the join bindings.
+  fn is_join(&self, input: &InputEvent) -> bool {
+    let binding = match input {
+      InputEvent::KeyboardEvent(KeyboardEvent::KeyDown(key)) => InputBinding::Key(*key),
+      InputEvent::MouseEvent(MouseEvent::MouseDown(button)) => InputBinding::Mouse(*button),
+      InputEvent::GamepadEvent(GamepadEvent::ButtonDown(button)) => InputBinding::Gamepad(*button),
+      _ => return false,
+    };
+
+    self.join_bindings.contains(&binding)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PAD_1: InputDeviceId = InputDeviceId(1);
+  const PAD_2: InputDeviceId = InputDeviceId(2);
+  const PAD_3: InputDeviceId = InputDeviceId(3);
+
+  fn press(device: InputDeviceId, button: GamepadButton) -> DeviceEvent {
+    DeviceEvent::Input(device, InputEvent::GamepadEvent(GamepadEvent::ButtonDown(button)))
+  }
+
+  fn router() -> PlayerRouter {
+    let mut actions = ActionMap::new();
+
+    actions.bind(InputBinding::Gamepad(GamepadButton::South), "jump");
+
+    PlayerRouter::new(2, actions).with_join_binding(InputBinding::Gamepad(GamepadButton::Start))
+  }
+
+  #[test]
+  fn it_should_let_devices_join_free_slots() {
+    let mut router = router();
+
+    assert_eq!(router.handle(&press(PAD_1, GamepadButton::South)), None);
+    assert_eq!(
+      router.handle(&press(PAD_1, GamepadButton::Start)),
+      Some(PlayerEvent::Joined {
+        player: 0,
+        device: PAD_1
+      })
+    );
+    assert_eq!(
+      router.handle(&press(PAD_2, GamepadButton::Start)),
+      Some(PlayerEvent::Joined {
+        player: 1,
+        device: PAD_2
+      })
+    );
+    assert_eq!(router.handle(&press(PAD_3, GamepadButton::Start)), None);
+
+    let Some(PlayerEvent::Action { player, event }) = router.handle(&press(PAD_2, GamepadButton::South)) else {
+      panic!("expected an action");
+    };
+
+    assert_eq!(player, 1);
+    assert_eq!(event.action, "jump");
+    assert!(router.slots()[1].actions.is_held("jump"));
+    assert!(!router.slots()[0].actions.is_held("jump"));
+  }
+
+  #[test]
+  fn it_should_hold_slots_for_disconnected_devices() {
+    let mut router = router();
+
+    router.handle(&DeviceEvent::Connected(PAD_1, InputDeviceKind::Gamepad));
+    router.handle(&press(PAD_1, GamepadButton::Start));
+    router.handle(&press(PAD_1, GamepadButton::South));
+
+    assert_eq!(
+      router.handle(&DeviceEvent::Disconnected(PAD_1)),
+      Some(PlayerEvent::DeviceLost { player: 0 })
+    );
+    assert!(router.slots()[0].is_occupied());
+    assert!(!router.slots()[0].actions.is_held("jump"));
+
+    assert_eq!(
+      router.handle(&DeviceEvent::Connected(PAD_1, InputDeviceKind::Gamepad)),
+      Some(PlayerEvent::DeviceRestored {
+        player: 0,
+        device: PAD_1
+      })
+    );
+
+    router.handle(&DeviceEvent::Disconnected(PAD_1));
+
+    assert_eq!(
+      router.handle(&press(PAD_2, GamepadButton::Start)),
+      Some(PlayerEvent::DeviceRestored {
+        player: 0,
+        device: PAD_2
+      })
+    );
+    assert_eq!(router.leave(0), Some(PlayerEvent::Left { player: 0 }));
+    assert_eq!(router.player_for(PAD_2), None);
+  }
+}
@@ -0,0 +1,55 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Attribute, DeriveInput, Meta, NestedMeta, Path};
+
+pub fn impl_component_trait(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  let ident = &input.ident;
+  let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+  let requires = match parse_requires(&input.attrs) {
+    Ok(requires) => requires,
+    Err(error) => return error.to_compile_error().into(),
+  };
+
+  let expanded = quote! {
+    impl #impl_generics Component for #ident #type_generics #where_clause {
+      fn requires(&self) -> &'static [ComponentRequirement] {
+        const REQUIRES: &[ComponentRequirement] = &[
+          #(ComponentRequirement::of::<#requires>()),*
+        ];
+
+        REQUIRES
+      }
+    }
+  };
+
+  expanded.into()
+}
+
+/// Parses the types in `#[component(requires(A, B))]` attributes.
+fn parse_requires(attrs: &[Attribute]) -> syn::Result<Vec<Path>> {
+  let mut requires = Vec::new();
+
+  for attr in attrs.iter().filter(|attr| attr.path.is_ident("component")) {
+    let Meta::List(list) = attr.parse_meta()? else {
+      return Err(syn::Error::new(attr.span(), "expected #[component(requires(...))]"));
+    };
+
+    for nested in list.nested {
+      match nested {
+        NestedMeta::Meta(Meta::List(inner)) if inner.path.is_ident("requires") => {
+          for item in inner.nested {
+            match item {
+              NestedMeta::Meta(Meta::Path(path)) => requires.push(path),
+              other => return Err(syn::Error::new(other.span(), "expected a component type")),
+            }
+          }
+        }
+        other => return Err(syn::Error::new(other.span(), "unknown component attribute")),
+      }
+    }
+  }
+
+  Ok(requires)
+}
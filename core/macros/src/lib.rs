@@ -2,6 +2,7 @@
 
 use proc_macro::TokenStream;
 
+mod component;
 mod profiling;
 mod singleton;
 mod vertex;
@@ -18,6 +19,13 @@ pub fn derive_singleton(input: TokenStream) -> TokenStream {
   singleton::impl_singleton(input)
 }
 
+/// Derives the `Component` trait for a type, with the components it requires
+/// declared as `#[component(requires(Transform))]`.
+#[proc_macro_derive(Component, attributes(component))]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+  component::impl_component_trait(input)
+}
+
 /// Derives the `Vertex` trait for a type.
 #[proc_macro_derive(Vertex, attributes(vertex))]
 pub fn derive_vertex(input: TokenStream) -> TokenStream {
@@ -5,6 +5,7 @@ use syn::{parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Fie
 pub fn impl_vertex_trait(input: TokenStream) -> TokenStream {
   let input = parse_macro_input!(input as DeriveInput);
   let descriptors = parse_struct(&input.data);
+  let position = parse_position(&input.data);
   let ident = &input.ident;
 
   let expanded = quote! {
@@ -12,6 +13,8 @@ pub fn impl_vertex_trait(input: TokenStream) -> TokenStream {
       const DESCRIPTORS: &'static [VertexDescriptor] = &[
         #(#descriptors),*
       ];
+
+      #position
     }
   };
 
@@ -45,6 +48,26 @@ fn parse_struct(data: &Data) -> Vec<proc_macro2::TokenStream> {
   }
 }
 
+/// Implements `Vertex::position` if the struct has a `position` field.
+fn parse_position(data: &Data) -> proc_macro2::TokenStream {
+  let has_position = match data {
+    Data::Struct(ref data) => data
+      .fields
+      .iter()
+      .any(|field| field.ident.as_ref().is_some_and(|ident| ident == "position")),
+    _ => false,
+  };
+
+  match has_position {
+    true => quote! {
+      fn position(&self) -> Option<common::Vec3> {
+        Some(VertexPosition::to_position(&self.position))
+      }
+    },
+    false => quote! {},
+  }
+}
+
 /// Parses the `#[vertex]` attributes on a field.
 fn parse_fields(attributes: &Vec<Attribute>) -> (usize, proc_macro2::TokenStream, bool) {
   let mut count = None;
@@ -1,5 +1,7 @@
 use super::*;
 
+mod broadphase;
+mod islands;
 mod world2d;
 mod world3d;
 
@@ -34,6 +36,171 @@ mod tests {
     world.collider_delete(collider_id).unwrap();
   }
 
+  #[test]
+  fn test_raycast_and_line_of_sight_2d() {
+    let world = physics().create_world_2d().unwrap();
+    let collider_id = world.collider_create().unwrap();
+
+    world.collider_set_position(collider_id, Vec2::new(5., 0.)).unwrap();
+
+    let hit = world.raycast(Vec2::ZERO, Vec2::X, 10.).unwrap();
+
+    assert_eq!(hit.collider, collider_id);
+    assert_eq!(hit.distance, 4.);
+    assert!(world.raycast(Vec2::ZERO, Vec2::Y, 10.).is_none());
+    assert!(world.raycast(Vec2::ZERO, Vec2::X, 3.).is_none());
+
+    assert!(!world.has_line_of_sight(Vec2::ZERO, Vec2::new(10., 0.)));
+    assert!(world.has_line_of_sight(Vec2::ZERO, Vec2::new(0., 10.)));
+  }
+
+  #[test]
+  fn test_line_of_sight_between_colliders_2d() {
+    let world = physics().create_world_2d().unwrap();
+    let viewer = world.collider_create().unwrap();
+    let target = world.collider_create().unwrap();
+
+    world.collider_set_position(viewer, Vec2::ZERO).unwrap();
+    world.collider_set_position(target, Vec2::new(5., 0.)).unwrap();
+
+    // neither the viewer's nor the target's own collider blocks the view
+    assert!(world.has_line_of_sight(Vec2::ZERO, Vec2::new(5., 0.)));
+
+    let wall = world.collider_create().unwrap();
+
+    world.collider_set_position(wall, Vec2::new(2.5, 0.)).unwrap();
+
+    assert!(!world.has_line_of_sight(Vec2::ZERO, Vec2::new(5., 0.)));
+  }
+
+  #[test]
+  fn test_convex_colliders_2d() {
+    let world = physics().create_world_2d().unwrap();
+
+    // a diamond, wound clockwise, and a square split into two triangles
+    let diamond = [
+      Vec2::new(0., 1.),
+      Vec2::new(1., 0.),
+      Vec2::new(0., -1.),
+      Vec2::new(-1., 0.),
+    ];
+    let collider_id = world.collider_create_convex(&diamond).unwrap();
+
+    world.collider_set_position(collider_id, Vec2::new(5., 0.)).unwrap();
+
+    let hit = world.raycast(Vec2::ZERO, Vec2::X, 10.).unwrap();
+
+    assert_eq!(hit.collider, collider_id);
+    assert_eq!(hit.distance, 4.);
+    assert!(world.raycast(Vec2::new(0., 2.), Vec2::X, 10.).is_none());
+    assert!(world.collider_create_convex(&diamond[..2]).is_err());
+
+    let lower = vec![Vec2::new(0., 10.), Vec2::new(1., 10.), Vec2::new(1., 11.)];
+    let upper = vec![Vec2::new(0., 10.), Vec2::new(1., 11.), Vec2::new(0., 11.)];
+    let pieces = world.collider_create_compound(&[lower, upper]).unwrap();
+
+    assert_eq!(pieces.len(), 2);
+    assert_eq!(world.raycast(Vec2::new(0.5, 0.), Vec2::Y, 20.).unwrap().distance, 10.);
+  }
+
+  #[test]
+  fn test_collider_pairs_2d() {
+    let world = physics().create_world_2d().unwrap();
+    let a = world.collider_create().unwrap();
+    let b = world.collider_create().unwrap();
+    let c = world.collider_create().unwrap();
+
+    world.collider_set_position(b, Vec2::new(1.5, 0.)).unwrap();
+    world.collider_set_position(c, Vec2::new(20., 0.)).unwrap();
+
+    assert_eq!(world.query_collider_pairs(), vec![(a, b)]);
+
+    world.collider_set_position(c, Vec2::new(3., 0.)).unwrap();
+    world.collider_delete(a).unwrap();
+
+    assert_eq!(world.query_collider_pairs(), vec![(b, c)]);
+    assert_eq!(world.query_point(Vec2::new(20., 0.)), vec![]);
+    assert_eq!(world.query_point(Vec2::new(3., 0.)), vec![c]);
+  }
+
+  #[test]
+  fn test_collision_filters_2d() {
+    let world = physics().create_world_2d().unwrap();
+    let players = LayerMask::layer(1);
+    let enemies = LayerMask::layer(2);
+
+    let player = world.collider_create().unwrap();
+    let ally = world.collider_create().unwrap();
+    let enemy = world.collider_create().unwrap();
+
+    world
+      .collider_set_filter(player, CollisionFilter::new(players, enemies))
+      .unwrap();
+    world
+      .collider_set_filter(ally, CollisionFilter::new(players, enemies))
+      .unwrap();
+    world
+      .collider_set_filter(enemy, CollisionFilter::new(enemies, LayerMask::ALL))
+      .unwrap();
+
+    assert_eq!(world.collider_get_filter(enemy).unwrap().layers, enemies);
+    assert_eq!(world.query_collider_pairs(), vec![(player, enemy), (ally, enemy)]);
+
+    world.collider_set_position(enemy, Vec2::new(5., 0.)).unwrap();
+
+    assert_eq!(world.raycast(Vec2::ZERO, Vec2::X, 10.).unwrap().collider, player);
+    assert_eq!(
+      world
+        .raycast_masked(Vec2::ZERO, Vec2::X, 10., enemies)
+        .unwrap()
+        .collider,
+      enemy
+    );
+    assert_eq!(world.query_point_masked(Vec2::ZERO, enemies), vec![]);
+  }
+
+  #[test]
+  fn test_bodies_separate_and_sleep_2d() {
+    let world = physics().create_world_2d().unwrap();
+    let a = world.body_create().unwrap();
+    let b = world.body_create().unwrap();
+
+    for (body, x) in [(a, 0.), (b, 1.5)] {
+      let collider = world.collider_create().unwrap();
+
+      world.collider_set_body(collider, Some(body)).unwrap();
+      world.body_set_position(body, Vec2::new(x, 0.)).unwrap();
+    }
+
+    for _ in 0..60 {
+      world.tick(1. / 60.);
+    }
+
+    let distance = world.body_get_position(b).unwrap().x - world.body_get_position(a).unwrap().x;
+
+    assert!(distance > 1.9);
+    assert!(world.body_is_sleeping(a).unwrap());
+    assert!(world.body_is_sleeping(b).unwrap());
+
+    world.body_set_velocity(a, Vec2::X).unwrap();
+
+    assert!(!world.body_is_sleeping(a).unwrap());
+  }
+
+  #[test]
+  fn test_colliders_keep_their_offset_from_bodies_2d() {
+    let world = physics().create_world_2d().unwrap();
+    let body = world.body_create().unwrap();
+    let collider = world.collider_create().unwrap();
+
+    world.collider_set_position(collider, Vec2::new(5., 0.)).unwrap();
+    world.collider_set_body(collider, Some(body)).unwrap();
+    world.body_set_velocity(body, Vec2::Y).unwrap();
+    world.tick(1.);
+
+    assert_eq!(world.collider_get_position(collider).unwrap(), Vec2::new(5., 1.));
+  }
+
   #[test]
   fn test_basic_physics_world_3d() {
     let world = physics().create_world_3d().unwrap();
@@ -44,4 +211,22 @@ mod tests {
 
     world.collider_delete(collider_id).unwrap();
   }
+
+  #[test]
+  fn test_checksum_physics_world_2d() {
+    let checksum = |velocity: Vec2| {
+      let world = physics().create_world_2d().unwrap();
+      let body_id = world.body_create().unwrap();
+
+      world.collider_create().unwrap();
+      world.body_set_velocity(body_id, velocity).unwrap();
+
+      let mut hasher = common::StateHasher::new();
+      world.checksum(&mut hasher);
+      hasher.finish()
+    };
+
+    assert_eq!(checksum(Vec2::X), checksum(Vec2::X));
+    assert_ne!(checksum(Vec2::X), checksum(Vec2::new(1. + f32::EPSILON, 0.)));
+  }
 }
@@ -0,0 +1,305 @@
+//! Sweep-and-prune broadphase for the home-baked backend.
+//!
+//! Colliders are kept as fattened bounds sorted along the x axis. Bodies
+//! rarely move far between ticks, so most updates fit inside their fattened
+//! bounds and cost nothing. The rest only move a few places in the nearly
+//! sorted list, which insertion sort puts right in linear time. Finding
+//! overlapping pairs is then a single sweep over the list.
+
+use common::{ArenaIndex, FastHashMap};
+
+use super::*;
+
+/// How far bounds are fattened on every side, so small movements don't need
+/// the list re-sorted.
+const MARGIN: Real = 0.1;
+
+/// A vector the broadphase can sort and compare bounds with.
+pub(crate) trait BroadphaseVector: Copy {
+  /// The component the bounds are sorted along.
+  fn axis(self) -> Real;
+
+  /// Determines if every component is less than or equal to the other's.
+  fn all_le(self, other: Self) -> bool;
+
+  /// Offsets every component by the given amount.
+  fn offset(self, amount: Real) -> Self;
+}
+
+impl BroadphaseVector for Real2 {
+  #[inline(always)]
+  fn axis(self) -> Real {
+    self.x
+  }
+
+  #[inline(always)]
+  fn all_le(self, other: Self) -> bool {
+    self.cmple(other).all()
+  }
+
+  #[inline(always)]
+  fn offset(self, amount: Real) -> Self {
+    self + amount
+  }
+}
+
+impl BroadphaseVector for Real3 {
+  #[inline(always)]
+  fn axis(self) -> Real {
+    self.x
+  }
+
+  #[inline(always)]
+  fn all_le(self, other: Self) -> bool {
+    self.cmple(other).all()
+  }
+
+  #[inline(always)]
+  fn offset(self, amount: Real) -> Self {
+    self + amount
+  }
+}
+
+/// The fattened bounds of a single collider.
+#[derive(Copy, Clone)]
+struct Proxy<V> {
+  collider: ColliderId,
+  min: V,
+  max: V,
+}
+
+impl<V: BroadphaseVector> Proxy<V> {
+  #[inline]
+  fn overlaps(&self, min: V, max: V) -> bool {
+    self.min.all_le(max) && min.all_le(self.max)
+  }
+}
+
+/// A sweep-and-prune broadphase over collider bounds.
+pub(crate) struct Broadphase<V> {
+  /// Proxies, sorted by the minimum of their bounds along the axis.
+  proxies: Vec<Proxy<V>>,
+  /// The position of each collider's proxy in the sorted list.
+  lookup: FastHashMap<ColliderId, usize>,
+  /// The widest proxy along the axis, to bound how far back queries look.
+  max_width: Real,
+}
+
+impl<V> Default for Broadphase<V> {
+  fn default() -> Self {
+    Self {
+      proxies: Vec::new(),
+      lookup: FastHashMap::default(),
+      max_width: 0.,
+    }
+  }
+}
+
+impl<V: BroadphaseVector> Broadphase<V> {
+  /// The number of colliders in the broadphase.
+  pub fn len(&self) -> usize {
+    self.proxies.len()
+  }
+
+  /// Adds a collider with the given bounds.
+  pub fn insert(&mut self, collider: ColliderId, min: V, max: V) {
+    let index = self.proxies.len();
+
+    self.proxies.push(Proxy {
+      collider,
+      min: min.offset(-MARGIN),
+      max: max.offset(MARGIN),
+    });
+    self.lookup.insert(collider, index);
+    self.resort(index);
+  }
+
+  /// Moves a collider to the given bounds.
+  pub fn update(&mut self, collider: ColliderId, min: V, max: V) {
+    let Some(&index) = self.lookup.get(&collider) else {
+      return self.insert(collider, min, max);
+    };
+
+    let proxy = &mut self.proxies[index];
+
+    // still inside the fattened bounds? nothing to do
+    if proxy.min.all_le(min) && max.all_le(proxy.max) {
+      return;
+    }
+
+    proxy.min = min.offset(-MARGIN);
+    proxy.max = max.offset(MARGIN);
+
+    self.resort(index);
+  }
+
+  /// Removes a collider.
+  pub fn remove(&mut self, collider: ColliderId) {
+    let Some(index) = self.lookup.remove(&collider) else {
+      return;
+    };
+
+    self.proxies.remove(index);
+
+    for proxy in &self.proxies[index..] {
+      *self.lookup.get_mut(&proxy.collider).unwrap() -= 1;
+    }
+  }
+
+  /// Finds the colliders whose bounds overlap the given bounds.
+  ///
+  /// Candidates may not actually touch the bounds, since proxies are
+  /// fattened; test their shapes before relying on them.
+  pub fn query(&self, min: V, max: V) -> impl Iterator<Item = ColliderId> + '_ {
+    let start = self
+      .proxies
+      .partition_point(|proxy| proxy.min.axis() < min.axis() - self.max_width);
+
+    self.proxies[start..]
+      .iter()
+      .take_while(move |proxy| proxy.min.axis() <= max.axis())
+      .filter(move |proxy| proxy.overlaps(min, max))
+      .map(|proxy| proxy.collider)
+  }
+
+  /// Finds every pair of colliders whose bounds overlap.
+  ///
+  /// Pairs are ordered by collider, so the result doesn't depend on the
+  /// order colliders were moved in.
+  pub fn pairs(&self) -> Vec<(ColliderId, ColliderId)> {
+    let mut pairs = Vec::new();
+
+    for (index, a) in self.proxies.iter().enumerate() {
+      for b in &self.proxies[index + 1..] {
+        if b.min.axis() > a.max.axis() {
+          break;
+        }
+
+        if a.overlaps(b.min, b.max) {
+          pairs.push(match sort_key(&a.collider) <= sort_key(&b.collider) {
+            true => (a.collider, b.collider),
+            false => (b.collider, a.collider),
+          });
+        }
+      }
+    }
+
+    pairs.sort_unstable_by_key(|(a, b)| (sort_key(a), sort_key(b)));
+    pairs
+  }
+
+  /// Moves the proxy at the given index to its sorted position.
+  fn resort(&mut self, mut index: usize) {
+    let proxy = self.proxies[index];
+
+    self.max_width = self.max_width.max(proxy.max.axis() - proxy.min.axis());
+
+    while index > 0 && self.proxies[index - 1].min.axis() > proxy.min.axis() {
+      self.swap(index, index - 1);
+      index -= 1;
+    }
+
+    while index + 1 < self.proxies.len() && self.proxies[index + 1].min.axis() < proxy.min.axis() {
+      self.swap(index, index + 1);
+      index += 1;
+    }
+  }
+
+  fn swap(&mut self, a: usize, b: usize) {
+    self.proxies.swap(a, b);
+
+    self.lookup.insert(self.proxies[a].collider, a);
+    self.lookup.insert(self.proxies[b].collider, b);
+  }
+}
+
+/// Orders colliders by their position in the arena.
+fn sort_key(collider: &ColliderId) -> (u32, u32) {
+  (collider.ordinal(), collider.generation())
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{Random, Vec2};
+
+  use super::*;
+
+  /// The pairs whose exact bounds overlap.
+  fn brute_force_pairs(bounds: &[(ColliderId, Vec2, Vec2)]) -> Vec<(ColliderId, ColliderId)> {
+    let mut pairs = Vec::new();
+
+    for (index, &(a, a_min, a_max)) in bounds.iter().enumerate() {
+      for &(b, b_min, b_max) in &bounds[index + 1..] {
+        if a_min.cmple(b_max).all() && b_min.cmple(a_max).all() {
+          pairs.push((a, b));
+        }
+      }
+    }
+
+    pairs
+  }
+
+  #[test]
+  fn it_should_find_overlapping_pairs_and_queries() {
+    let mut broadphase = Broadphase::default();
+    let [a, b, c] = [1u32, 2, 3].map(ColliderId::from);
+
+    broadphase.insert(a, Vec2::ZERO, Vec2::ONE);
+    broadphase.insert(b, Vec2::splat(0.5), Vec2::splat(1.5));
+    broadphase.insert(c, Vec2::splat(10.), Vec2::splat(11.));
+
+    assert_eq!(broadphase.pairs(), vec![(a, b)]);
+    assert_eq!(
+      broadphase
+        .query(Vec2::splat(10.5), Vec2::splat(10.5))
+        .collect::<Vec<_>>(),
+      vec![c]
+    );
+
+    broadphase.update(c, Vec2::splat(1.), Vec2::splat(2.));
+    broadphase.remove(a);
+
+    assert_eq!(broadphase.len(), 2);
+    assert_eq!(broadphase.pairs(), vec![(b, c)]);
+    assert_eq!(broadphase.query(Vec2::ZERO, Vec2::splat(0.2)).count(), 0);
+  }
+
+  #[test]
+  fn it_should_match_brute_force_as_bodies_move() {
+    let mut random = Random::with_seed(42);
+    let mut broadphase = Broadphase::default();
+    let mut bounds = Vec::new();
+
+    for index in 0..500u32 {
+      let id = ColliderId::from(index + 1);
+      let min = Vec2::new(random.next::<f32>() * 100., random.next::<f32>() * 100.);
+      let max = min + Vec2::splat(1. + random.next::<f32>());
+
+      broadphase.insert(id, min, max);
+      bounds.push((id, min, max));
+    }
+
+    for _ in 0..10 {
+      for (id, min, max) in &mut bounds {
+        let offset = Vec2::new(random.next::<f32>() - 0.5, random.next::<f32>() - 0.5) * 4.;
+
+        *min += offset;
+        *max += offset;
+
+        broadphase.update(*id, *min, *max);
+      }
+
+      // fattened proxies report near misses too, but never miss an overlap
+      let pairs = broadphase.pairs();
+
+      for pair in brute_force_pairs(&bounds) {
+        assert!(pairs.contains(&pair), "missing pair {pair:?}");
+      }
+
+      assert!(broadphase
+        .proxies
+        .windows(2)
+        .all(|it| it[0].min.axis() <= it[1].min.axis()));
+    }
+  }
+}
@@ -0,0 +1,301 @@
+//! Contact islands, sleeping and the contact solver for the home-baked backend.
+//!
+//! Bodies joined by contacts form an island, which is solved on its own.
+//! Islands never share bodies, so they can be solved in parallel, and an
+//! island that has come to rest is put to sleep as a whole; it costs nothing
+//! until an awake body touches it, or one of its bodies is moved.
+//!
+//! There's no job system to hand the islands to, so big worlds spread them
+//! across scoped threads spawned for each tick. That costs a thread spawn per
+//! core every tick, which only pays off past [`PARALLEL_THRESHOLD`] bodies.
+
+use super::*;
+
+/// Bodies slower than this are considered at rest.
+const SLEEP_VELOCITY: Real = 0.05;
+
+/// How long every body in an island must be at rest before it sleeps.
+const SLEEP_TIME: Real = 0.5;
+
+/// The number of velocity iterations per island.
+const ITERATIONS: usize = 4;
+
+/// The fewest bodies worth spreading the islands across threads for.
+const PARALLEL_THRESHOLD: usize = 512;
+
+/// How much penetration is left for the next tick, to avoid jitter.
+const SLOP: Real = 0.01;
+
+/// How much of the remaining penetration is corrected each tick.
+const CORRECTION: Real = 0.8;
+
+/// A dynamic body, as seen by the solver.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct SolverBody {
+  pub position: Real2,
+  pub velocity: Real2,
+  pub inverse_mass: Real,
+  /// How long the body has been at rest.
+  pub sleep_time: Real,
+  pub is_sleeping: bool,
+}
+
+/// A contact between two bodies, or a body and the static world.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Contact {
+  pub a: usize,
+  /// The other body, or none for static colliders.
+  pub b: Option<usize>,
+  /// The direction from `a` to `b`.
+  pub normal: Real2,
+  pub depth: Real,
+}
+
+/// A set of bodies joined by contacts, solved together.
+#[derive(Debug, Default)]
+pub(crate) struct Island {
+  /// The bodies in the island, in ascending order.
+  pub bodies: Vec<usize>,
+  pub contacts: Vec<Contact>,
+}
+
+/// Groups the bodies into islands by their contacts.
+///
+/// Every body is in exactly one island, even if it touches nothing, and
+/// islands are ordered by their first body so the result is deterministic.
+pub(crate) fn build_islands(body_count: usize, contacts: &[Contact]) -> Vec<Island> {
+  fn find(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+      parents[index] = parents[parents[index]];
+      index = parents[index];
+    }
+
+    index
+  }
+
+  let mut parents = (0..body_count).collect::<Vec<_>>();
+
+  for contact in contacts {
+    if let Some(b) = contact.b {
+      let a = find(&mut parents, contact.a);
+      let b = find(&mut parents, b);
+
+      parents[a.max(b)] = a.min(b);
+    }
+  }
+
+  let mut islands = Vec::<Island>::new();
+  let mut lookup = vec![usize::MAX; body_count];
+
+  for body in 0..body_count {
+    let root = find(&mut parents, body);
+
+    if lookup[root] == usize::MAX {
+      lookup[root] = islands.len();
+      islands.push(Island::default());
+    }
+
+    islands[lookup[root]].bodies.push(body);
+  }
+
+  for contact in contacts {
+    let root = find(&mut parents, contact.a);
+
+    islands[lookup[root]].contacts.push(*contact);
+  }
+
+  islands
+}
+
+/// Solves the contacts of every awake island, and puts islands that have
+/// come to rest to sleep.
+pub(crate) fn solve_islands(bodies: &mut [SolverBody], islands: &[Island], delta: Real) {
+  let shared = &*bodies;
+
+  let results = if shared.len() >= PARALLEL_THRESHOLD && islands.len() > 1 {
+    let workers = std::thread::available_parallelism().map_or(1, |it| it.get());
+    let chunk_size = islands.len().div_ceil(workers);
+
+    std::thread::scope(|scope| {
+      let workers = islands
+        .chunks(chunk_size)
+        .map(|chunk| scope.spawn(move || solve_chunk(shared, chunk, delta)))
+        .collect::<Vec<_>>();
+
+      workers
+        .into_iter()
+        .map(|worker| worker.join().expect("Failed to solve islands"))
+        .collect::<Vec<_>>()
+    })
+  } else {
+    vec![solve_chunk(shared, islands, delta)]
+  };
+
+  for (index, body) in results.into_iter().flatten() {
+    bodies[index] = body;
+  }
+}
+
+/// Solves a run of islands, returning the bodies that changed.
+fn solve_chunk(bodies: &[SolverBody], islands: &[Island], delta: Real) -> Vec<(usize, SolverBody)> {
+  islands
+    .iter()
+    .flat_map(|island| solve_island(bodies, island, delta))
+    .collect()
+}
+
+/// Solves a single island, returning the bodies that changed.
+fn solve_island(bodies: &[SolverBody], island: &Island, delta: Real) -> Vec<(usize, SolverBody)> {
+  let mut local = island.bodies.iter().map(|&index| bodies[index]).collect::<Vec<_>>();
+
+  if local.iter().all(|body| body.is_sleeping) {
+    return Vec::new();
+  }
+
+  // anything touching an awake body wakes up with it
+  for body in local.iter_mut().filter(|body| body.is_sleeping) {
+    body.is_sleeping = false;
+    body.sleep_time = 0.;
+  }
+
+  let local_index = |index: usize| island.bodies.binary_search(&index).unwrap();
+  let contacts = island
+    .contacts
+    .iter()
+    .map(|contact| (local_index(contact.a), contact.b.map(local_index), contact))
+    .collect::<Vec<_>>();
+
+  for _ in 0..ITERATIONS {
+    for &(a, b, contact) in &contacts {
+      let inverse_mass_a = local[a].inverse_mass;
+      let inverse_mass_b = b.map_or(0., |b| local[b].inverse_mass);
+      let total = inverse_mass_a + inverse_mass_b;
+
+      let velocity_b = b.map_or(Real2::ZERO, |b| local[b].velocity);
+      let closing = (velocity_b - local[a].velocity).dot(contact.normal);
+
+      if closing >= 0. || total <= 0. {
+        continue;
+      }
+
+      let impulse = contact.normal * (-closing / total);
+
+      local[a].velocity -= impulse * inverse_mass_a;
+
+      if let Some(b) = b {
+        local[b].velocity += impulse * inverse_mass_b;
+      }
+    }
+  }
+
+  for &(a, b, contact) in &contacts {
+    let inverse_mass_a = local[a].inverse_mass;
+    let inverse_mass_b = b.map_or(0., |b| local[b].inverse_mass);
+    let total = inverse_mass_a + inverse_mass_b;
+
+    if total <= 0. {
+      continue;
+    }
+
+    let correction = contact.normal * ((contact.depth - SLOP).max(0.) * CORRECTION / total);
+
+    local[a].position -= correction * inverse_mass_a;
+
+    if let Some(b) = b {
+      local[b].position += correction * inverse_mass_b;
+    }
+  }
+
+  let mut rest_time = Real::MAX;
+
+  for body in &mut local {
+    if body.velocity.length_squared() < SLEEP_VELOCITY * SLEEP_VELOCITY {
+      body.sleep_time += delta;
+    } else {
+      body.sleep_time = 0.;
+    }
+
+    rest_time = rest_time.min(body.sleep_time);
+  }
+
+  if rest_time >= SLEEP_TIME {
+    for body in &mut local {
+      body.is_sleeping = true;
+      body.velocity = Real2::ZERO;
+    }
+  }
+
+  island.bodies.iter().copied().zip(local).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn create_body(position: Real2, velocity: Real2) -> SolverBody {
+    SolverBody {
+      position,
+      velocity,
+      inverse_mass: 1.,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn it_should_group_bodies_by_contacts() {
+    let contact = |a, b| Contact {
+      a,
+      b,
+      normal: Real2::X,
+      depth: 0.1,
+    };
+
+    let islands = build_islands(5, &[contact(3, Some(1)), contact(4, None), contact(0, Some(3))]);
+
+    assert_eq!(islands.len(), 3);
+    assert_eq!(islands[0].bodies, vec![0, 1, 3]);
+    assert_eq!(islands[0].contacts.len(), 2);
+    assert_eq!(islands[1].bodies, vec![2]);
+    assert_eq!(islands[2].bodies, vec![4]);
+    assert_eq!(islands[2].contacts.len(), 1);
+  }
+
+  #[test]
+  fn it_should_separate_bodies_and_sleep_at_rest() {
+    let mut bodies = vec![
+      create_body(Real2::ZERO, Real2::X),
+      create_body(Real2::new(1.5, 0.), -Real2::X),
+      create_body(Real2::new(10., 0.), Real2::ZERO),
+    ];
+
+    let contacts = [Contact {
+      a: 0,
+      b: Some(1),
+      normal: Real2::X,
+      depth: 0.5,
+    }];
+
+    let islands = build_islands(bodies.len(), &contacts);
+
+    solve_islands(&mut bodies, &islands, 0.1);
+
+    assert!(bodies[0].velocity.length() < 1e-5);
+    assert!(bodies[1].velocity.length() < 1e-5);
+    assert!(bodies[1].position.x - bodies[0].position.x > 1.8);
+
+    for _ in 0..5 {
+      solve_islands(&mut bodies, &build_islands(3, &[]), 0.1);
+    }
+
+    assert!(bodies.iter().all(|body| body.is_sleeping));
+
+    // touching a sleeping body wakes it up
+    bodies[0].is_sleeping = false;
+    bodies[0].velocity = Real2::X;
+
+    solve_islands(&mut bodies, &build_islands(3, &contacts), 0.1);
+
+    assert!(!bodies[1].is_sleeping);
+    assert!(bodies[2].is_sleeping);
+  }
+}
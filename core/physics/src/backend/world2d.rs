@@ -1,26 +1,213 @@
 use std::sync::RwLock;
 
-use common::Arena;
+use common::{Arena, ArenaIndex, FastHashMap, StateHasher};
 
-use super::*;
+use super::{
+  broadphase::Broadphase,
+  islands::{build_islands, solve_islands, Contact, SolverBody},
+  *,
+};
 
 /// A 2D physics world.
 #[derive(Default)]
 pub struct PhysicsWorld2D {
   colliders: RwLock<Arena<ColliderId, Collider>>,
   bodies: RwLock<Arena<BodyId, Body>>,
+  broadphase: RwLock<Broadphase<Real2>>,
 }
 
 /// A 2D collider.
 struct Collider {
   position: Real2,
   shape: ColliderShape,
+  /// The body the collider moves with, if any.
+  body: Option<BodyId>,
+  /// Where the collider sits relative to its body.
+  offset: Real2,
+  filter: CollisionFilter,
 }
 
 /// A 2D collider shape.
 enum ColliderShape {
   Circle { radius: f32 },
   Rectangle { width: f32, height: f32 },
+  Convex { points: Vec<Real2> },
+}
+
+impl Collider {
+  /// Finds the distance along a normalized ray to the collider, if it hits.
+  ///
+  /// Rays starting inside the collider hit it immediately.
+  fn intersect_ray(&self, origin: Real2, direction: Real2) -> Option<Real> {
+    let offset = origin - self.position;
+
+    match self.shape {
+      ColliderShape::Circle { radius } => {
+        let b = offset.dot(direction);
+        let c = offset.length_squared() - radius * radius;
+
+        if c <= 0. {
+          return Some(0.);
+        }
+
+        let discriminant = b * b - c;
+
+        if b > 0. || discriminant < 0. {
+          return None;
+        }
+
+        Some(-b - discriminant.sqrt())
+      }
+      ColliderShape::Rectangle { width, height } => {
+        let half_size = Real2::new(width, height) / 2.;
+        let inverse = direction.recip();
+
+        let t1 = (-half_size - offset) * inverse;
+        let t2 = (half_size - offset) * inverse;
+
+        let near = t1.min(t2).max_element();
+        let far = t1.max(t2).min_element();
+
+        if far < 0. || near > far {
+          return None;
+        }
+
+        Some(near.max(0.))
+      }
+      ColliderShape::Convex { ref points } => {
+        let mut near = Real::MIN;
+        let mut far = Real::MAX;
+
+        // clip the ray against the inside of each edge in turn
+        for (i, &a) in points.iter().enumerate() {
+          let edge = points[(i + 1) % points.len()] - a;
+          let normal = Real2::new(edge.y, -edge.x);
+          let denominator = normal.dot(direction);
+          let numerator = normal.dot(a - offset);
+
+          if denominator == 0. {
+            if numerator < 0. {
+              return None;
+            }
+          } else if denominator < 0. {
+            near = near.max(numerator / denominator);
+          } else {
+            far = far.min(numerator / denominator);
+          }
+        }
+
+        if far < 0. || near > far {
+          return None;
+        }
+
+        Some(near.max(0.))
+      }
+    }
+  }
+}
+
+impl Collider {
+  /// The world-space bounds of the collider, as (min, max).
+  fn bounds(&self) -> (Real2, Real2) {
+    let (min, max) = match self.shape {
+      ColliderShape::Circle { radius } => (Real2::splat(-radius), Real2::splat(radius)),
+      ColliderShape::Rectangle { width, height } => {
+        let half_size = Real2::new(width, height) / 2.;
+
+        (-half_size, half_size)
+      }
+      ColliderShape::Convex { ref points } => points.iter().fold((Real2::MAX, Real2::MIN), |(min, max), &point| {
+        (min.min(point), max.max(point))
+      }),
+    };
+
+    (self.position + min, self.position + max)
+  }
+
+  /// Finds the direction from this collider to the other and how deeply
+  /// they overlap, if they do.
+  ///
+  /// Only circles are tested exactly; other shapes are approximated by their
+  /// bounds.
+  fn contact(&self, other: &Collider) -> Option<(Real2, Real)> {
+    if let (ColliderShape::Circle { radius: a }, ColliderShape::Circle { radius: b }) = (&self.shape, &other.shape) {
+      let offset = other.position - self.position;
+      let depth = a + b - offset.length();
+
+      return (depth > 0.).then(|| (offset.try_normalize().unwrap_or(Real2::X), depth));
+    }
+
+    let (a_min, a_max) = self.bounds();
+    let (b_min, b_max) = other.bounds();
+
+    let overlap = a_max.min(b_max) - a_min.max(b_min);
+    let offset = (b_min + b_max) - (a_min + a_max);
+
+    if overlap.x <= 0. || overlap.y <= 0. {
+      return None;
+    }
+
+    // push apart along the axis of least overlap
+    match overlap.x < overlap.y {
+      true => Some((Real2::new(offset.x.signum(), 0.), overlap.x)),
+      false => Some((Real2::new(0., offset.y.signum()), overlap.y)),
+    }
+  }
+
+  /// Determines if the point is inside the collider.
+  fn contains_point(&self, point: Real2) -> bool {
+    let offset = point - self.position;
+
+    match self.shape {
+      ColliderShape::Circle { radius } => offset.length_squared() <= radius * radius,
+      ColliderShape::Rectangle { width, height } => offset.x.abs() <= width / 2. && offset.y.abs() <= height / 2.,
+      ColliderShape::Convex { ref points } => points.iter().enumerate().all(|(i, &a)| {
+        let edge = points[(i + 1) % points.len()] - a;
+
+        edge.perp_dot(offset - a) >= 0.
+      }),
+    }
+  }
+}
+
+/// Moves colliders to the positions of the bodies they're attached to,
+/// keeping their offsets from them.
+fn sync_colliders(
+  colliders: &mut Arena<ColliderId, Collider>,
+  broadphase: &mut Broadphase<Real2>,
+  lookup: &FastHashMap<BodyId, usize>,
+  solver: &[SolverBody],
+) {
+  for (id, collider) in colliders.enumerate_mut() {
+    let Some(&index) = collider.body.and_then(|body| lookup.get(&body)) else {
+      continue;
+    };
+
+    if solver[index].is_sleeping {
+      continue;
+    }
+
+    collider.position = solver[index].position + collider.offset;
+
+    let (min, max) = collider.bounds();
+
+    broadphase.update(id, min, max);
+  }
+}
+
+impl PhysicsWorld2D {
+  /// Adds a collider to the arena and the broadphase.
+  fn collider_insert(&self, collider: Collider) -> Result<ColliderId, ColliderError> {
+    let mut colliders = self.colliders.write().expect("Failed to lock colliders");
+    let mut broadphase = self.broadphase.write().expect("Failed to lock broadphase");
+
+    let (min, max) = collider.bounds();
+    let id = colliders.insert(collider);
+
+    broadphase.insert(id, min, max);
+
+    Ok(id)
+  }
 }
 
 /// A 2D physics body.
@@ -28,6 +215,17 @@ struct Body {
   position: Real2,
   velocity: Real2,
   kind: BodyKind,
+  /// How long the body has been at rest.
+  sleep_time: Real,
+  is_sleeping: bool,
+}
+
+impl Body {
+  /// Wakes the body, so it's simulated again.
+  fn wake(&mut self) {
+    self.sleep_time = 0.;
+    self.is_sleeping = false;
+  }
 }
 
 /// A 2D physics body kind.
@@ -39,17 +237,224 @@ enum BodyKind {
 impl PhysicsWorld for PhysicsWorld2D {
   type Vector = Real2;
 
-  fn tick(&self, _delta: f32) {
-    // TODO: Implement physics simulation.
+  fn tick(&self, delta: f32) {
+    let mut colliders = self.colliders.write().expect("Failed to lock colliders");
+    let mut bodies = self.bodies.write().expect("Failed to lock bodies");
+    let mut broadphase = self.broadphase.write().expect("Failed to lock broadphase");
+
+    // static bodies never move, so only dynamic ones take part in solving
+    let mut ids = Vec::new();
+    let mut solver = Vec::new();
+
+    for (id, body) in bodies.enumerate() {
+      if let BodyKind::Dynamic = body.kind {
+        ids.push(id);
+        solver.push(SolverBody {
+          position: body.position,
+          velocity: body.velocity,
+          inverse_mass: 1.,
+          sleep_time: body.sleep_time,
+          is_sleeping: body.is_sleeping,
+        });
+      }
+    }
+
+    let lookup = ids
+      .iter()
+      .enumerate()
+      .map(|(index, &id)| (id, index))
+      .collect::<FastHashMap<_, _>>();
+
+    for body in solver.iter_mut().filter(|body| !body.is_sleeping) {
+      body.position += body.velocity * delta;
+    }
+
+    sync_colliders(&mut colliders, &mut broadphase, &lookup, &solver);
+
+    let contacts = broadphase
+      .pairs()
+      .into_iter()
+      .filter_map(|(a, b)| {
+        let collider_a = colliders.get(a)?;
+        let collider_b = colliders.get(b)?;
+
+        let body_a = collider_a.body.and_then(|body| lookup.get(&body).copied());
+        let body_b = collider_b.body.and_then(|body| lookup.get(&body).copied());
+
+        // nothing to solve between static colliders, parts of the same body,
+        // bodies that are both asleep, or colliders filtered from each other
+        let is_asleep = |body: Option<usize>| body.is_none_or(|body| solver[body].is_sleeping);
+
+        if body_a == body_b
+          || (is_asleep(body_a) && is_asleep(body_b))
+          || !collider_a.filter.collides_with(&collider_b.filter)
+        {
+          return None;
+        }
+
+        let (normal, depth) = collider_a.contact(collider_b)?;
+
+        Some(match body_a {
+          Some(a) => Contact {
+            a,
+            b: body_b,
+            normal,
+            depth,
+          },
+          None => Contact {
+            a: body_b?,
+            b: None,
+            normal: -normal,
+            depth,
+          },
+        })
+      })
+      .collect::<Vec<_>>();
+
+    let islands = build_islands(solver.len(), &contacts);
+
+    solve_islands(&mut solver, &islands, delta);
+    sync_colliders(&mut colliders, &mut broadphase, &lookup, &solver);
+
+    for (id, state) in ids.into_iter().zip(solver) {
+      let body = bodies.get_mut(id).unwrap();
+
+      body.position = state.position;
+      body.velocity = state.velocity;
+      body.sleep_time = state.sleep_time;
+      body.is_sleeping = state.is_sleeping;
+    }
   }
 
-  fn collider_create(&self) -> Result<ColliderId, ColliderError> {
-    let mut colliders = self.colliders.write().expect("Failed to lock colliders");
+  fn checksum(&self, hasher: &mut StateHasher) {
+    let colliders = self.colliders.read().expect("Failed to lock colliders");
+    let bodies = self.bodies.read().expect("Failed to lock bodies");
+
+    for (id, collider) in colliders.enumerate() {
+      hasher.write_u32(id.ordinal());
+      hasher.write(&collider.position);
+      hasher.write(&collider.offset);
+      hasher.write_u32(collider.filter.layers.0);
+      hasher.write_u32(collider.filter.mask.0);
+
+      match &collider.shape {
+        ColliderShape::Circle { radius } => {
+          hasher.write_u32(0);
+          hasher.write_f32(*radius);
+        }
+        ColliderShape::Rectangle { width, height } => {
+          hasher.write_u32(1);
+          hasher.write_f32(*width);
+          hasher.write_f32(*height);
+        }
+        ColliderShape::Convex { points } => {
+          hasher.write_u32(2);
+          hasher.write(points);
+        }
+      }
+    }
+
+    for (id, body) in bodies.enumerate() {
+      hasher.write_u32(id.ordinal());
+      hasher.write(&body.position);
+      hasher.write(&body.velocity);
+      hasher.write_u32(match body.kind {
+        BodyKind::Static => 0,
+        BodyKind::Dynamic => 1,
+      });
+      hasher.write_f32(body.sleep_time);
+      hasher.write_u32(body.is_sleeping as u32);
+    }
+  }
 
-    Ok(colliders.insert(Collider {
+  fn raycast_excluding(
+    &self,
+    origin: Self::Vector,
+    direction: Self::Vector,
+    max_distance: Real,
+    mask: LayerMask,
+    excluded: &[ColliderId],
+  ) -> Option<RayHit<Self::Vector>> {
+    let direction = direction.try_normalize()?;
+    let colliders = self.colliders.read().expect("Failed to lock colliders");
+    let broadphase = self.broadphase.read().expect("Failed to lock broadphase");
+
+    let end = origin + direction * max_distance.min(Real::MAX / 2.);
+
+    broadphase
+      .query(origin.min(end), origin.max(end))
+      .filter(|id| !excluded.contains(id))
+      .filter_map(|id| {
+        let collider = colliders
+          .get(id)
+          .filter(|collider| mask.intersects(collider.filter.layers))?;
+        let distance = collider.intersect_ray(origin, direction)?;
+
+        (distance <= max_distance).then_some(RayHit {
+          collider: id,
+          point: origin + direction * distance,
+          distance,
+        })
+      })
+      .min_by(|a, b| a.distance.total_cmp(&b.distance))
+  }
+
+  fn query_point_masked(&self, point: Self::Vector, mask: LayerMask) -> Vec<ColliderId> {
+    let colliders = self.colliders.read().expect("Failed to lock colliders");
+    let broadphase = self.broadphase.read().expect("Failed to lock broadphase");
+
+    broadphase
+      .query(point, point)
+      .filter(|&id| {
+        colliders
+          .get(id)
+          .is_some_and(|collider| mask.intersects(collider.filter.layers) && collider.contains_point(point))
+      })
+      .collect()
+  }
+
+  fn query_collider_pairs(&self) -> Vec<(ColliderId, ColliderId)> {
+    let colliders = self.colliders.read().expect("Failed to lock colliders");
+    let broadphase = self.broadphase.read().expect("Failed to lock broadphase");
+
+    broadphase
+      .pairs()
+      .into_iter()
+      .filter(|&(a, b)| match (colliders.get(a), colliders.get(b)) {
+        (Some(a), Some(b)) => a.filter.collides_with(&b.filter),
+        _ => false,
+      })
+      .collect()
+  }
+
+  fn collider_create(&self) -> Result<ColliderId, ColliderError> {
+    self.collider_insert(Collider {
       shape: ColliderShape::Circle { radius: 1.0 },
       position: Real2::ZERO,
-    }))
+      body: None,
+      offset: Real2::ZERO,
+      filter: CollisionFilter::default(),
+    })
+  }
+
+  fn collider_create_convex(&self, points: &[Self::Vector]) -> Result<ColliderId, ColliderError> {
+    if points.len() < 3 {
+      return Err(ColliderError::InvalidShape);
+    }
+
+    let mut points = points.to_vec();
+
+    if signed_area(&points) < 0. {
+      points.reverse();
+    }
+
+    self.collider_insert(Collider {
+      shape: ColliderShape::Convex { points },
+      position: Real2::ZERO,
+      body: None,
+      offset: Real2::ZERO,
+      filter: CollisionFilter::default(),
+    })
   }
 
   fn collider_get_position(&self, id: ColliderId) -> Result<Self::Vector, ColliderError> {
@@ -63,8 +468,45 @@ impl PhysicsWorld for PhysicsWorld2D {
     let mut colliders = self.colliders.write().expect("Failed to lock colliders");
     let collider = colliders.get_mut(id).ok_or(ColliderError::InvalidId(id))?;
 
+    // attached colliders move relative to their body
+    collider.offset += position - collider.position;
     collider.position = position;
 
+    let (min, max) = collider.bounds();
+    let mut broadphase = self.broadphase.write().expect("Failed to lock broadphase");
+
+    broadphase.update(id, min, max);
+
+    Ok(())
+  }
+
+  fn collider_set_body(&self, id: ColliderId, body: Option<BodyId>) -> Result<(), ColliderError> {
+    let mut colliders = self.colliders.write().expect("Failed to lock colliders");
+    let collider = colliders.get_mut(id).ok_or(ColliderError::InvalidId(id))?;
+    let bodies = self.bodies.read().expect("Failed to lock bodies");
+
+    // the collider stays where it is, and moves with the body from here on
+    collider.body = body;
+    collider.offset = body
+      .and_then(|body| bodies.get(body))
+      .map_or(Real2::ZERO, |body| collider.position - body.position);
+
+    Ok(())
+  }
+
+  fn collider_get_filter(&self, id: ColliderId) -> Result<CollisionFilter, ColliderError> {
+    let colliders = self.colliders.read().expect("Failed to lock colliders");
+    let collider = colliders.get(id).ok_or(ColliderError::InvalidId(id))?;
+
+    Ok(collider.filter)
+  }
+
+  fn collider_set_filter(&self, id: ColliderId, filter: CollisionFilter) -> Result<(), ColliderError> {
+    let mut colliders = self.colliders.write().expect("Failed to lock colliders");
+    let collider = colliders.get_mut(id).ok_or(ColliderError::InvalidId(id))?;
+
+    collider.filter = filter;
+
     Ok(())
   }
 
@@ -73,6 +515,10 @@ impl PhysicsWorld for PhysicsWorld2D {
 
     colliders.remove(id).ok_or(ColliderError::InvalidId(id))?;
 
+    let mut broadphase = self.broadphase.write().expect("Failed to lock broadphase");
+
+    broadphase.remove(id);
+
     Ok(())
   }
 
@@ -83,6 +529,8 @@ impl PhysicsWorld for PhysicsWorld2D {
       position: Real2::ZERO,
       velocity: Real2::ZERO,
       kind: BodyKind::Dynamic,
+      sleep_time: 0.,
+      is_sleeping: false,
     }))
   }
 
@@ -98,6 +546,7 @@ impl PhysicsWorld for PhysicsWorld2D {
     let body = bodies.get_mut(id).ok_or(BodyError::InvalidId(id))?;
 
     body.position = position;
+    body.wake();
 
     Ok(())
   }
@@ -114,10 +563,18 @@ impl PhysicsWorld for PhysicsWorld2D {
     let body = bodies.get_mut(id).ok_or(BodyError::InvalidId(id))?;
 
     body.velocity = velocity;
+    body.wake();
 
     Ok(())
   }
 
+  fn body_is_sleeping(&self, id: BodyId) -> Result<bool, BodyError> {
+    let bodies = self.bodies.read().expect("Failed to lock bodies");
+    let body = bodies.get(id).ok_or(BodyError::InvalidId(id))?;
+
+    Ok(body.is_sleeping)
+  }
+
   fn body_delete(&self, id: BodyId) -> Result<(), BodyError> {
     let mut bodies = self.bodies.write().expect("Failed to lock bodies");
 
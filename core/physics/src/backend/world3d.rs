@@ -1,18 +1,129 @@
 use std::sync::RwLock;
 
-use common::Arena;
+use common::{Arena, ArenaIndex, StateHasher};
 
-use super::*;
+use super::{broadphase::Broadphase, *};
 
 /// A 3D physics world.
 #[derive(Default)]
 pub struct PhysicsWorld3D {
   colliders: RwLock<Arena<ColliderId, Collider>>,
   bodies: RwLock<Arena<BodyId, Body>>,
+  broadphase: RwLock<Broadphase<Real3>>,
 }
 
 /// A 3D collider.
-struct Collider {}
+struct Collider {
+  position: Real3,
+  shape: ColliderShape,
+  /// The body the collider moves with, if any.
+  body: Option<BodyId>,
+  filter: CollisionFilter,
+}
+
+/// A 3D collider shape.
+enum ColliderShape {
+  Sphere {
+    radius: f32,
+  },
+  /// A convex hull, as the outward-facing planes of its faces.
+  Convex {
+    planes: Vec<(Real3, Real)>,
+    /// The bounds of the hull's vertices, as (min, max).
+    extents: (Real3, Real3),
+  },
+}
+
+impl Collider {
+  /// Finds the distance along a normalized ray to the collider, if it hits.
+  ///
+  /// Rays starting inside the collider hit it immediately.
+  fn intersect_ray(&self, origin: Real3, direction: Real3) -> Option<Real> {
+    let offset = origin - self.position;
+
+    match self.shape {
+      ColliderShape::Sphere { radius } => {
+        let b = offset.dot(direction);
+        let c = offset.length_squared() - radius * radius;
+
+        if c <= 0. {
+          return Some(0.);
+        }
+
+        let discriminant = b * b - c;
+
+        if b > 0. || discriminant < 0. {
+          return None;
+        }
+
+        Some(-b - discriminant.sqrt())
+      }
+      ColliderShape::Convex { ref planes, .. } => {
+        let mut near = Real::MIN;
+        let mut far = Real::MAX;
+
+        // clip the ray against the inside of each face in turn
+        for &(normal, distance) in planes {
+          let denominator = normal.dot(direction);
+          let numerator = distance - normal.dot(offset);
+
+          if denominator == 0. {
+            if numerator < 0. {
+              return None;
+            }
+          } else if denominator > 0. {
+            far = far.min(numerator / denominator);
+          } else {
+            near = near.max(numerator / denominator);
+          }
+        }
+
+        if far < 0. || near > far {
+          return None;
+        }
+
+        Some(near.max(0.))
+      }
+    }
+  }
+
+  /// The world-space bounds of the collider, as (min, max).
+  fn bounds(&self) -> (Real3, Real3) {
+    let (min, max) = match self.shape {
+      ColliderShape::Sphere { radius } => (Real3::splat(-radius), Real3::splat(radius)),
+      ColliderShape::Convex { extents, .. } => extents,
+    };
+
+    (self.position + min, self.position + max)
+  }
+
+  /// Determines if the point is inside the collider.
+  fn contains_point(&self, point: Real3) -> bool {
+    let offset = point - self.position;
+
+    match self.shape {
+      ColliderShape::Sphere { radius } => offset.length_squared() <= radius * radius,
+      ColliderShape::Convex { ref planes, .. } => {
+        planes.iter().all(|&(normal, distance)| normal.dot(offset) <= distance)
+      }
+    }
+  }
+}
+
+impl PhysicsWorld3D {
+  /// Adds a collider to the arena and the broadphase.
+  fn collider_insert(&self, collider: Collider) -> Result<ColliderId, ColliderError> {
+    let mut colliders = self.colliders.write().expect("Failed to lock colliders");
+    let mut broadphase = self.broadphase.write().expect("Failed to lock broadphase");
+
+    let (min, max) = collider.bounds();
+    let id = colliders.insert(collider);
+
+    broadphase.insert(id, min, max);
+
+    Ok(id)
+  }
+}
 
 /// A 3D physics body.
 struct Body {}
@@ -25,18 +136,184 @@ impl PhysicsWorld for PhysicsWorld3D {
     // no-op
   }
 
+  fn checksum(&self, hasher: &mut StateHasher) {
+    let colliders = self.colliders.read().expect("Failed to lock colliders");
+    let bodies = self.bodies.read().expect("Failed to lock bodies");
+
+    for (id, collider) in colliders.enumerate() {
+      hasher.write_u32(id.ordinal());
+      hasher.write(&collider.position);
+      hasher.write_u32(collider.filter.layers.0);
+      hasher.write_u32(collider.filter.mask.0);
+
+      match &collider.shape {
+        ColliderShape::Sphere { radius } => {
+          hasher.write_u32(0);
+          hasher.write_f32(*radius);
+        }
+        ColliderShape::Convex { planes, .. } => {
+          hasher.write_u32(1);
+          hasher.write_u64(planes.len() as u64);
+
+          for (normal, distance) in planes {
+            hasher.write(normal);
+            hasher.write_f32(*distance);
+          }
+        }
+      }
+    }
+
+    // bodies carry no state yet, so only their identity matters
+    for (id, _) in bodies.enumerate() {
+      hasher.write_u32(id.ordinal());
+    }
+  }
+
+  fn raycast_excluding(
+    &self,
+    origin: Self::Vector,
+    direction: Self::Vector,
+    max_distance: Real,
+    mask: LayerMask,
+    excluded: &[ColliderId],
+  ) -> Option<RayHit<Self::Vector>> {
+    let direction = direction.try_normalize()?;
+    let colliders = self.colliders.read().expect("Failed to lock colliders");
+    let broadphase = self.broadphase.read().expect("Failed to lock broadphase");
+
+    let end = origin + direction * max_distance.min(Real::MAX / 2.);
+
+    broadphase
+      .query(origin.min(end), origin.max(end))
+      .filter(|id| !excluded.contains(id))
+      .filter_map(|id| {
+        let collider = colliders
+          .get(id)
+          .filter(|collider| mask.intersects(collider.filter.layers))?;
+        let distance = collider.intersect_ray(origin, direction)?;
+
+        (distance <= max_distance).then_some(RayHit {
+          collider: id,
+          point: origin + direction * distance,
+          distance,
+        })
+      })
+      .min_by(|a, b| a.distance.total_cmp(&b.distance))
+  }
+
+  fn query_point_masked(&self, point: Self::Vector, mask: LayerMask) -> Vec<ColliderId> {
+    let colliders = self.colliders.read().expect("Failed to lock colliders");
+    let broadphase = self.broadphase.read().expect("Failed to lock broadphase");
+
+    broadphase
+      .query(point, point)
+      .filter(|&id| {
+        colliders
+          .get(id)
+          .is_some_and(|collider| mask.intersects(collider.filter.layers) && collider.contains_point(point))
+      })
+      .collect()
+  }
+
+  fn query_collider_pairs(&self) -> Vec<(ColliderId, ColliderId)> {
+    let colliders = self.colliders.read().expect("Failed to lock colliders");
+    let broadphase = self.broadphase.read().expect("Failed to lock broadphase");
+
+    broadphase
+      .pairs()
+      .into_iter()
+      .filter(|&(a, b)| match (colliders.get(a), colliders.get(b)) {
+        (Some(a), Some(b)) => a.filter.collides_with(&b.filter),
+        _ => false,
+      })
+      .collect()
+  }
+
   fn collider_create(&self) -> Result<ColliderId, ColliderError> {
-    let mut colliders = self.colliders.write().expect("Failed to lock colliders");
+    self.collider_insert(Collider {
+      shape: ColliderShape::Sphere { radius: 1.0 },
+      position: Real3::ZERO,
+      body: None,
+      filter: CollisionFilter::default(),
+    })
+  }
+
+  fn collider_create_convex(&self, points: &[Self::Vector]) -> Result<ColliderId, ColliderError> {
+    if points.len() < 4 {
+      return Err(ColliderError::InvalidShape);
+    }
+
+    let hull = ConvexHull::from_points(points).ok_or(ColliderError::InvalidShape)?;
+    let planes = hull
+      .faces
+      .iter()
+      .map(|&[a, b, c]| {
+        let [a, b, c] = [hull.vertices[a], hull.vertices[b], hull.vertices[c]];
+        let normal = (b - a).cross(c - a).normalize();
+
+        (normal, normal.dot(a))
+      })
+      .collect();
 
-    Ok(colliders.insert(Collider {}))
+    let extents = hull
+      .vertices
+      .iter()
+      .fold((Real3::MAX, Real3::MIN), |(min, max), &vertex| {
+        (min.min(vertex), max.max(vertex))
+      });
+
+    self.collider_insert(Collider {
+      shape: ColliderShape::Convex { planes, extents },
+      position: Real3::ZERO,
+      body: None,
+      filter: CollisionFilter::default(),
+    })
   }
 
   fn collider_get_position(&self, id: ColliderId) -> Result<Self::Vector, ColliderError> {
-    todo!()
+    let colliders = self.colliders.read().expect("Failed to lock colliders");
+    let collider = colliders.get(id).ok_or(ColliderError::InvalidId(id))?;
+
+    Ok(collider.position)
   }
 
   fn collider_set_position(&self, id: ColliderId, position: Self::Vector) -> Result<(), ColliderError> {
-    todo!()
+    let mut colliders = self.colliders.write().expect("Failed to lock colliders");
+    let collider = colliders.get_mut(id).ok_or(ColliderError::InvalidId(id))?;
+
+    collider.position = position;
+
+    let (min, max) = collider.bounds();
+    let mut broadphase = self.broadphase.write().expect("Failed to lock broadphase");
+
+    broadphase.update(id, min, max);
+
+    Ok(())
+  }
+
+  fn collider_set_body(&self, id: ColliderId, body: Option<BodyId>) -> Result<(), ColliderError> {
+    let mut colliders = self.colliders.write().expect("Failed to lock colliders");
+    let collider = colliders.get_mut(id).ok_or(ColliderError::InvalidId(id))?;
+
+    collider.body = body;
+
+    Ok(())
+  }
+
+  fn collider_get_filter(&self, id: ColliderId) -> Result<CollisionFilter, ColliderError> {
+    let colliders = self.colliders.read().expect("Failed to lock colliders");
+    let collider = colliders.get(id).ok_or(ColliderError::InvalidId(id))?;
+
+    Ok(collider.filter)
+  }
+
+  fn collider_set_filter(&self, id: ColliderId, filter: CollisionFilter) -> Result<(), ColliderError> {
+    let mut colliders = self.colliders.write().expect("Failed to lock colliders");
+    let collider = colliders.get_mut(id).ok_or(ColliderError::InvalidId(id))?;
+
+    collider.filter = filter;
+
+    Ok(())
   }
 
   fn collider_delete(&self, id: ColliderId) -> Result<(), ColliderError> {
@@ -44,6 +321,10 @@ impl PhysicsWorld for PhysicsWorld3D {
 
     colliders.remove(id).ok_or(ColliderError::InvalidId(id))?;
 
+    let mut broadphase = self.broadphase.write().expect("Failed to lock broadphase");
+
+    broadphase.remove(id);
+
     Ok(())
   }
 
@@ -69,6 +350,13 @@ impl PhysicsWorld for PhysicsWorld3D {
     todo!()
   }
 
+  fn body_is_sleeping(&self, id: BodyId) -> Result<bool, BodyError> {
+    let bodies = self.bodies.read().expect("Failed to lock bodies");
+
+    // bodies aren't simulated in 3D yet, so they never fall asleep
+    bodies.get(id).map(|_| false).ok_or(BodyError::InvalidId(id))
+  }
+
   fn body_delete(&self, id: BodyId) -> Result<(), BodyError> {
     let mut bodies = self.bodies.write().expect("Failed to lock bodies");
 
@@ -0,0 +1,391 @@
+//! Effectors that push bodies around inside a volume.
+//!
+//! An [`Effector`] applies forces to any [`EffectorBody`] overlapping its
+//! volume. Bodies are sampled at each of their float points, or at their
+//! centre if they have none, so uneven forces turn them; a boat with float
+//! points at the corners of its hull rolls back upright in water.
+//!
+//! Wind effectors blow what's inside them along with a [`WindManager`], and
+//! effectors can also push [`EffectorParticle`]s, the point masses that make
+//! up cloth and rope.
+
+use std::f32::consts::PI;
+
+use common::{Arena, Quat, WindManager, AABB};
+
+use super::*;
+
+common::impl_arena_index!(pub EffectorId, "Identifies an effector.");
+
+/// The acceleration due to gravity, for the weight of displaced fluid.
+const GRAVITY: Real = 9.81;
+
+/// What an effector does to the bodies inside it.
+#[derive(Clone, Debug)]
+pub enum EffectorKind {
+  /// Fluid filling the volume, with its surface at the top.
+  ///
+  /// Bodies are pushed up by the weight of the fluid they displace, and
+  /// slowed by drag in proportion to how deep they are.
+  Buoyancy {
+    density: Real,
+    linear_drag: Real,
+    angular_drag: Real,
+  },
+  /// Blows bodies along with the wind.
+  ///
+  /// Bodies are pushed towards the wind's velocity, closing the difference at
+  /// the given rate per second.
+  Wind { drag: Real },
+}
+
+impl EffectorKind {
+  /// Buoyancy for water.
+  pub fn water() -> Self {
+    Self::Buoyancy {
+      density: 1000.,
+      linear_drag: 1.,
+      angular_drag: 1.,
+    }
+  }
+
+  /// Wind that catches light objects, like leaves and flags.
+  pub fn wind() -> Self {
+    Self::Wind { drag: 0.5 }
+  }
+}
+
+/// Applies forces to bodies inside its volume.
+#[derive(Clone, Debug)]
+pub struct Effector {
+  pub volume: AABB,
+  pub kind: EffectorKind,
+}
+
+/// The state of a body that effectors can push on.
+///
+/// Effectors only change velocities; moving the body is left to its owner.
+#[derive(Clone, Debug)]
+pub struct EffectorBody {
+  pub position: Real3,
+  pub rotation: Quat,
+  pub linear_velocity: Real3,
+  pub angular_velocity: Real3,
+  pub mass: Real,
+  /// The radius of the sphere sampled around each float point.
+  pub radius: Real,
+  /// Points relative to the body where effectors sample it, e.g. the corners
+  /// of a hull. The body is sampled at its centre if there are none.
+  pub float_points: Vec<Real3>,
+}
+
+impl EffectorBody {
+  /// Creates a body at rest at the origin.
+  pub fn new(mass: Real, radius: Real) -> Self {
+    Self {
+      position: Real3::ZERO,
+      rotation: Quat::IDENTITY,
+      linear_velocity: Real3::ZERO,
+      angular_velocity: Real3::ZERO,
+      mass,
+      radius,
+      float_points: Vec::new(),
+    }
+  }
+
+  /// The points to sample, in world space.
+  fn sample_points(&self) -> Vec<Real3> {
+    if self.float_points.is_empty() {
+      return vec![self.position];
+    }
+
+    self
+      .float_points
+      .iter()
+      .map(|point| self.position + self.rotation * *point)
+      .collect()
+  }
+
+  /// Pushes the body with a force at a point for a step.
+  fn apply_force_at(&mut self, force: Real3, point: Real3, delta_time: Real) {
+    // treat the body as a solid sphere when turning it
+    let inertia = 0.4 * self.mass * self.radius * self.radius;
+
+    self.linear_velocity += force / self.mass * delta_time;
+    self.angular_velocity += (point - self.position).cross(force) / inertia * delta_time;
+  }
+}
+
+/// A point mass that effectors can push on, like a particle of a cloth or
+/// rope.
+#[derive(Clone, Debug)]
+pub struct EffectorParticle {
+  pub position: Real3,
+  pub velocity: Real3,
+  pub mass: Real,
+  /// The radius of the sphere sampled around the particle.
+  pub radius: Real,
+}
+
+impl Effector {
+  /// Applies the effector to a body for a step.
+  pub fn apply(&self, body: &mut EffectorBody, wind: &WindManager, delta_time: Real) {
+    if body.mass <= 0. || body.radius <= 0. {
+      return;
+    }
+
+    let points = body.sample_points();
+    let share = 1. / points.len() as Real;
+    let mut angular_drag = 0.;
+
+    for point in points {
+      let velocity = body.linear_velocity + body.angular_velocity.cross(point - body.position);
+      let (force, drag) = self.force_at(point, velocity, body.radius, body.mass * share, share, wind);
+
+      if force != Real3::ZERO {
+        body.apply_force_at(force, point, delta_time);
+      }
+
+      angular_drag += drag * share;
+    }
+
+    body.angular_velocity *= (1. - angular_drag * delta_time).max(0.);
+  }
+
+  /// Applies the effector to a particle for a step.
+  pub fn apply_to_particle(&self, particle: &mut EffectorParticle, wind: &WindManager, delta_time: Real) {
+    if particle.mass <= 0. {
+      return;
+    }
+
+    let (force, _) = self.force_at(
+      particle.position,
+      particle.velocity,
+      particle.radius,
+      particle.mass,
+      1.,
+      wind,
+    );
+
+    particle.velocity += force / particle.mass * delta_time;
+  }
+
+  /// The force on a sample of a body, and the angular drag on the body it
+  /// contributes.
+  ///
+  /// Each sample carries the given mass, and the given share of the volume of
+  /// a sphere of the given radius.
+  fn force_at(
+    &self,
+    point: Real3,
+    velocity: Real3,
+    radius: Real,
+    mass: Real,
+    share: Real,
+    wind: &WindManager,
+  ) -> (Real3, Real) {
+    match self.kind {
+      EffectorKind::Buoyancy {
+        density,
+        linear_drag,
+        angular_drag,
+      } => {
+        let submersion = self.submersion(point, radius);
+
+        if submersion <= 0. {
+          return (Real3::ZERO, 0.);
+        }
+
+        let volume = 4. / 3. * PI * radius.powi(3) * share;
+
+        let lift = Real3::Y * density * GRAVITY * volume * submersion;
+        let drag = -velocity * linear_drag * mass * submersion;
+
+        (lift + drag, angular_drag * submersion)
+      }
+      EffectorKind::Wind { drag } => {
+        if !self.volume.contains(point) {
+          return (Real3::ZERO, 0.);
+        }
+
+        ((wind.sample(point) - velocity) * drag * mass, 0.)
+      }
+    }
+  }
+
+  /// How much of a sphere around a point is below the surface at the top of
+  /// the volume, from 0 to 1.
+  fn submersion(&self, point: Real3, radius: Real) -> Real {
+    let AABB { min, max } = self.volume;
+
+    let inside = point.x >= min.x && point.x <= max.x && point.z >= min.z && point.z <= max.z;
+
+    if !inside || point.y + radius < min.y {
+      return 0.;
+    }
+
+    ((max.y - (point.y - radius)) / (radius * 2.)).clamp(0., 1.)
+  }
+}
+
+/// A collection of effectors.
+#[derive(Default)]
+pub struct EffectorSet {
+  effectors: Arena<EffectorId, Effector>,
+}
+
+impl EffectorSet {
+  /// Creates a new, empty set.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds an effector to the set.
+  pub fn add(&mut self, effector: Effector) -> EffectorId {
+    self.effectors.insert(effector)
+  }
+
+  /// Gets an effector, e.g. to move its volume.
+  pub fn get_mut(&mut self, id: EffectorId) -> Option<&mut Effector> {
+    self.effectors.get_mut(id)
+  }
+
+  /// Removes an effector from the set.
+  pub fn remove(&mut self, id: EffectorId) -> Option<Effector> {
+    self.effectors.remove(id)
+  }
+
+  /// Applies every effector to a body for a step.
+  pub fn apply(&self, body: &mut EffectorBody, wind: &WindManager, delta_time: Real) {
+    for (_, effector) in self.effectors.enumerate() {
+      effector.apply(body, wind, delta_time);
+    }
+  }
+
+  /// Applies every effector to particles for a step, like the points of a
+  /// cloth or rope.
+  pub fn apply_to_particles(&self, particles: &mut [EffectorParticle], wind: &WindManager, delta_time: Real) {
+    for (_, effector) in self.effectors.enumerate() {
+      for particle in particles.iter_mut() {
+        effector.apply_to_particle(particle, wind, delta_time);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::vec3;
+
+  use super::*;
+
+  const DELTA_TIME: Real = 1. / 60.;
+
+  fn create_water() -> EffectorSet {
+    let mut effectors = EffectorSet::new();
+
+    effectors.add(Effector {
+      volume: AABB::from_min_max(vec3(-50., -100., -50.), vec3(50., 0., 50.)),
+      kind: EffectorKind::water(),
+    });
+
+    effectors
+  }
+
+  fn simulate(effectors: &EffectorSet, body: &mut EffectorBody, seconds: Real) {
+    for _ in 0..(seconds / DELTA_TIME) as usize {
+      body.linear_velocity.y -= GRAVITY * DELTA_TIME;
+
+      effectors.apply(body, &WindManager::default(), DELTA_TIME);
+
+      body.position += body.linear_velocity * DELTA_TIME;
+      body.rotation = (Quat::from_scaled_axis(body.angular_velocity * DELTA_TIME) * body.rotation).normalize();
+    }
+  }
+
+  /// A body of the given density, a metre across.
+  fn create_body(density: Real) -> EffectorBody {
+    let radius = 0.5;
+
+    EffectorBody::new(density * 4. / 3. * PI * radius * radius * radius, radius)
+  }
+
+  #[test]
+  fn it_should_float_light_bodies_at_the_surface() {
+    let water = create_water();
+    let mut body = create_body(500.);
+
+    body.position = vec3(0., 2., 0.);
+
+    simulate(&water, &mut body, 20.);
+
+    // half as dense as water, so it floats half under
+    assert!(body.position.y.abs() < 0.05);
+    assert!(body.linear_velocity.length() < 0.05);
+  }
+
+  #[test]
+  fn it_should_sink_heavy_bodies_slowly() {
+    let water = create_water();
+    let mut body = create_body(2000.);
+
+    simulate(&water, &mut body, 6.);
+
+    // drag holds it to a terminal velocity of roughly half its weight over
+    // its drag
+    assert!(body.position.y < -1.);
+    assert!((body.linear_velocity.y + GRAVITY / 2.).abs() < 0.25);
+  }
+
+  #[test]
+  fn it_should_roll_boats_upright() {
+    let water = create_water();
+    let mut boat = create_body(400.);
+
+    boat.float_points = vec![
+      vec3(-1., 0., -2.),
+      vec3(1., 0., -2.),
+      vec3(-1., 0., 2.),
+      vec3(1., 0., 2.),
+    ];
+    boat.rotation = Quat::from_rotation_z(0.5);
+
+    simulate(&water, &mut boat, 20.);
+
+    assert!((boat.rotation * Real3::Y).angle_between(Real3::Y) < 0.05);
+    assert!(boat.position.y.abs() < 0.5);
+  }
+
+  #[test]
+  fn it_should_blow_bodies_and_particles_inside_wind_volumes() {
+    let mut effectors = EffectorSet::new();
+    let mut wind = WindManager::new(vec3(5., 0., 0.));
+
+    wind.gust_strength = 0.;
+
+    effectors.add(Effector {
+      volume: AABB::from_min_max(vec3(-10., -10., -10.), vec3(10., 10., 10.)),
+      kind: EffectorKind::Wind { drag: 2. },
+    });
+
+    let mut body = create_body(100.);
+
+    for _ in 0..300 {
+      effectors.apply(&mut body, &wind, DELTA_TIME);
+    }
+
+    assert!((body.linear_velocity - wind.velocity).length() < 0.01);
+
+    let mut particles = [vec3(0., 0., 0.), vec3(20., 0., 0.)].map(|position| EffectorParticle {
+      position,
+      velocity: Real3::ZERO,
+      mass: 0.1,
+      radius: 0.1,
+    });
+
+    effectors.apply_to_particles(&mut particles, &wind, DELTA_TIME);
+
+    assert!(particles[0].velocity.x > 0.);
+    assert_eq!(particles[1].velocity, Real3::ZERO);
+  }
+}
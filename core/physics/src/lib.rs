@@ -1,8 +1,16 @@
 //! Physics engine for Surreal.
 
-use common::{Vec2, Vec3, Vector};
+use common::{LayerMask, LineOfSight, StateHasher, Vec2, Vec3, Vector};
+pub use effectors::*;
+pub use picking::*;
+pub use shapes::*;
+pub use vehicles::*;
 
 mod backend;
+mod effectors;
+mod picking;
+mod shapes;
+mod vehicles;
 
 common::impl_arena_index!(pub ColliderId, "Identifies a collider.");
 common::impl_arena_index!(pub BodyId, "Identifies a physics body.");
@@ -40,6 +48,7 @@ pub enum WorldError {
 pub enum ColliderError {
   CreationFailed,
   InvalidId(ColliderId),
+  InvalidShape,
   NullPointer,
 }
 
@@ -55,6 +64,49 @@ common::impl_error_coercion!(WorldError into PhysicsError);
 common::impl_error_coercion!(ColliderError into PhysicsError);
 common::impl_error_coercion!(BodyError into PhysicsError);
 
+/// The result of a successful raycast.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit<V> {
+  pub collider: ColliderId,
+  pub point: V,
+  pub distance: Real,
+}
+
+/// Which colliders a collider touches, by layer.
+///
+/// Two colliders touch only if each one's mask includes a layer of the other,
+/// so either side can opt out of the pair.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CollisionFilter {
+  /// The layers the collider is on.
+  pub layers: LayerMask,
+  /// The layers the collider touches.
+  pub mask: LayerMask,
+}
+
+impl Default for CollisionFilter {
+  fn default() -> Self {
+    Self {
+      layers: LayerMask::DEFAULT,
+      mask: LayerMask::ALL,
+    }
+  }
+}
+
+impl CollisionFilter {
+  /// Creates a filter for a collider on the given layers, touching the
+  /// layers of the mask.
+  pub const fn new(layers: LayerMask, mask: LayerMask) -> Self {
+    Self { layers, mask }
+  }
+
+  /// Determines if colliders with the two filters touch.
+  #[inline]
+  pub const fn collides_with(&self, other: &Self) -> bool {
+    self.mask.intersects(other.layers) && other.mask.intersects(self.layers)
+  }
+}
+
 /// An abstraction on top of the underlying physics API.
 ///
 /// This is a mid-level abstraction that makes use of 'opaque' resource IDs to
@@ -78,11 +130,70 @@ pub trait PhysicsWorld {
   /// Steps the physics simulation by the given delta time.
   fn tick(&self, delta: f32);
 
+  /// Hashes the state of every collider and body, in a stable order, for
+  /// checking the simulation is deterministic; see
+  /// [`common::DeterminismRecorder`].
+  fn checksum(&self, hasher: &mut StateHasher);
+
+  // queries
+  fn raycast(&self, origin: Self::Vector, direction: Self::Vector, max_distance: Real) -> Option<RayHit<Self::Vector>> {
+    self.raycast_masked(origin, direction, max_distance, LayerMask::ALL)
+  }
+
+  fn query_point(&self, point: Self::Vector) -> Vec<ColliderId> {
+    self.query_point_masked(point, LayerMask::ALL)
+  }
+
+  /// Casts a ray against only the colliders on the layers of the mask.
+  fn raycast_masked(
+    &self,
+    origin: Self::Vector,
+    direction: Self::Vector,
+    max_distance: Real,
+    mask: LayerMask,
+  ) -> Option<RayHit<Self::Vector>> {
+    self.raycast_excluding(origin, direction, max_distance, mask, &[])
+  }
+
+  /// Casts a ray against the colliders on the layers of the mask, ignoring
+  /// the excluded ones, like the collider of whoever casts it.
+  fn raycast_excluding(
+    &self,
+    origin: Self::Vector,
+    direction: Self::Vector,
+    max_distance: Real,
+    mask: LayerMask,
+    excluded: &[ColliderId],
+  ) -> Option<RayHit<Self::Vector>>;
+
+  /// Finds the colliders under a point, on the layers of the mask.
+  fn query_point_masked(&self, point: Self::Vector, mask: LayerMask) -> Vec<ColliderId>;
+
+  /// Finds the pairs of colliders whose bounds overlap, as candidates for
+  /// narrowphase contact tests.
+  ///
+  /// Pairs may not actually touch, but every pair that does is included;
+  /// pairs whose [`CollisionFilter`]s don't collide are left out.
+  fn query_collider_pairs(&self) -> Vec<(ColliderId, ColliderId)>;
+
   // colliders
   fn collider_create(&self) -> Result<ColliderId, ColliderError>;
+  fn collider_create_convex(&self, points: &[Self::Vector]) -> Result<ColliderId, ColliderError>;
   fn collider_get_position(&self, id: ColliderId) -> Result<Self::Vector, ColliderError>;
   fn collider_set_position(&self, id: ColliderId, position: Self::Vector) -> Result<(), ColliderError>;
   fn collider_delete(&self, id: ColliderId) -> Result<(), ColliderError>;
+  fn collider_get_filter(&self, id: ColliderId) -> Result<CollisionFilter, ColliderError>;
+  fn collider_set_filter(&self, id: ColliderId, filter: CollisionFilter) -> Result<(), ColliderError>;
+
+  /// Attaches the collider to a body, so it moves with it and takes part in
+  /// its contacts, or detaches it with `None`.
+  fn collider_set_body(&self, id: ColliderId, body: Option<BodyId>) -> Result<(), ColliderError>;
+
+  /// Creates a convex collider for each piece of a decomposed shape, like
+  /// those from [`convex_shapes_from_mask`] or [`decompose_mesh`].
+  fn collider_create_compound(&self, pieces: &[Vec<Self::Vector>]) -> Result<Vec<ColliderId>, ColliderError> {
+    pieces.iter().map(|piece| self.collider_create_convex(piece)).collect()
+  }
 
   // bodies
   fn body_create(&self) -> Result<BodyId, BodyError>;
@@ -91,4 +202,29 @@ pub trait PhysicsWorld {
   fn body_get_velocity(&self, id: BodyId) -> Result<Self::Vector, BodyError>;
   fn body_set_velocity(&self, id: BodyId, velocity: Self::Vector) -> Result<(), BodyError>;
   fn body_delete(&self, id: BodyId) -> Result<(), BodyError>;
+
+  /// Determines if the body has come to rest and stopped being simulated.
+  ///
+  /// Moving the body, or touching it with an awake one, wakes it up.
+  fn body_is_sleeping(&self, id: BodyId) -> Result<bool, BodyError>;
+}
+
+/// Occludes sight with the colliders of a 2D world.
+///
+/// Colliders at either end, like those of the viewer and the target, don't
+/// block sight.
+impl LineOfSight for PhysicsWorld2D {
+  fn has_line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+    let distance = from.distance(to);
+
+    let mut excluded = self.query_point(from);
+
+    excluded.extend(self.query_point(to));
+
+    // hits just short of the target are touching it
+    match self.raycast_excluding(from, to - from, distance, LayerMask::ALL, &excluded) {
+      Some(hit) => hit.distance >= distance - 0.001,
+      None => true,
+    }
+  }
 }
@@ -0,0 +1,206 @@
+//! Picking of objects under the cursor.
+//!
+//! Mouse-driven gameplay and editor tools both need to know what's under the
+//! cursor. The cursor is converted to the world with the [`Camera`] it's seen
+//! through, then tested against a physics world: a point test in 2D, and a
+//! ray cast in 3D. An [`EntityPicker`] maps the colliders hit back to the
+//! entities that own them:
+//!
+//! ```rust,ignore
+//! let mut picker = EntityPicker::new();
+//!
+//! picker.register(collider, entity);
+//!
+//! if let Some(entity) = picker.pick_entity_2d(&world, &camera, mouse.position, viewport) {
+//!   selection.select(entity);
+//! }
+//! ```
+
+use common::{ArenaIndex, Camera, FastHashMap, Vec2};
+
+use super::*;
+
+/// Finds the collider under a point on the screen of a 2D world.
+///
+/// Where colliders overlap, the most recently created wins.
+pub fn pick_collider_2d(
+  world: &PhysicsWorld2D,
+  camera: &dyn Camera,
+  screen: Vec2,
+  viewport: Vec2,
+) -> Option<ColliderId> {
+  let point = camera.screen_to_world_2d(screen, viewport);
+
+  world.query_point(point).into_iter().max_by_key(|id| id.ordinal())
+}
+
+/// Finds the nearest collider under a point on the screen of a 3D world.
+pub fn pick_collider_3d(
+  world: &PhysicsWorld3D,
+  camera: &dyn Camera,
+  screen: Vec2,
+  viewport: Vec2,
+) -> Option<RayHit<Vec3>> {
+  let ray = camera.screen_to_ray(screen, viewport);
+
+  // the ray spans the view from the near plane to the far plane
+  world.raycast(ray.origin, ray.direction, ray.direction.length())
+}
+
+/// Maps colliders to the entities that own them, for picking entities.
+pub struct EntityPicker<E> {
+  owners: FastHashMap<ColliderId, E>,
+}
+
+impl<E> Default for EntityPicker<E> {
+  fn default() -> Self {
+    Self {
+      owners: FastHashMap::default(),
+    }
+  }
+}
+
+impl<E: Copy> EntityPicker<E> {
+  /// Creates an empty picker.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Makes the entity pickable by the given collider.
+  pub fn register(&mut self, collider: ColliderId, entity: E) {
+    self.owners.insert(collider, entity);
+  }
+
+  /// Stops picking by the given collider.
+  pub fn unregister(&mut self, collider: ColliderId) {
+    self.owners.remove(&collider);
+  }
+
+  /// The entity that owns the given collider.
+  pub fn owner(&self, collider: ColliderId) -> Option<E> {
+    self.owners.get(&collider).copied()
+  }
+
+  /// Finds the entity under a point on the screen of a 2D world.
+  ///
+  /// Colliders without an owner are skipped, so triggers and scenery don't
+  /// block the entities behind them.
+  pub fn pick_entity_2d(&self, world: &PhysicsWorld2D, camera: &dyn Camera, screen: Vec2, viewport: Vec2) -> Option<E> {
+    let point = camera.screen_to_world_2d(screen, viewport);
+
+    world
+      .query_point(point)
+      .into_iter()
+      .filter(|id| self.owners.contains_key(id))
+      .max_by_key(|id| id.ordinal())
+      .and_then(|id| self.owner(id))
+  }
+
+  /// Finds the nearest entity under a point on the screen of a 3D world.
+  pub fn pick_entity_3d(&self, world: &PhysicsWorld3D, camera: &dyn Camera, screen: Vec2, viewport: Vec2) -> Option<E> {
+    pick_collider_3d(world, camera, screen, viewport).and_then(|hit| self.owner(hit.collider))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{vec2, vec3, OrthographicCamera, PerspectiveCamera};
+
+  use super::*;
+
+  const VIEWPORT: Vec2 = Vec2::new(800., 600.);
+
+  #[test]
+  fn it_should_pick_entities_in_2d() {
+    let world = physics().create_world_2d().unwrap();
+    let camera = OrthographicCamera {
+      ortho_size: 10.,
+      ..Default::default()
+    };
+
+    let circle = world.collider_create().unwrap();
+    let square = world
+      .collider_create_convex(&[vec2(-1., -1.), vec2(1., -1.), vec2(1., 1.), vec2(-1., 1.)])
+      .unwrap();
+
+    world.collider_set_position(circle, vec2(3., 2.)).unwrap();
+    world.collider_set_position(square, vec2(-3., -2.)).unwrap();
+
+    let mut picker = EntityPicker::new();
+
+    picker.register(circle, "circle");
+    picker.register(square, "square");
+
+    let over = |point: Vec2| camera.world_to_screen(point.extend(0.), VIEWPORT).unwrap();
+
+    assert_eq!(
+      picker.pick_entity_2d(&*world, &camera, over(vec2(3.5, 2.5)), VIEWPORT),
+      Some("circle")
+    );
+    assert_eq!(
+      picker.pick_entity_2d(&*world, &camera, over(vec2(-2.2, -1.2)), VIEWPORT),
+      Some("square")
+    );
+    assert_eq!(
+      picker.pick_entity_2d(&*world, &camera, over(vec2(0., 0.)), VIEWPORT),
+      None
+    );
+
+    picker.unregister(circle);
+
+    assert_eq!(
+      picker.pick_entity_2d(&*world, &camera, over(vec2(3.5, 2.5)), VIEWPORT),
+      None
+    );
+    assert_eq!(
+      pick_collider_2d(&*world, &camera, over(vec2(3.5, 2.5)), VIEWPORT),
+      Some(circle)
+    );
+  }
+
+  #[test]
+  fn it_should_pick_the_nearest_entity_in_3d() {
+    let world = physics().create_world_3d().unwrap();
+    let camera = PerspectiveCamera {
+      position: vec3(0., 0., 10.),
+      look_at: Vec3::ZERO,
+      fov: std::f32::consts::FRAC_PI_3,
+      aspect_ratio: VIEWPORT.x / VIEWPORT.y,
+      ..Default::default()
+    };
+
+    let near = world.collider_create().unwrap();
+    let far = world
+      .collider_create_convex(&[
+        vec3(-1., -1., -1.),
+        vec3(1., -1., -1.),
+        vec3(1., 1., -1.),
+        vec3(-1., 1., -1.),
+        vec3(-1., -1., 1.),
+        vec3(1., -1., 1.),
+        vec3(1., 1., 1.),
+        vec3(-1., 1., 1.),
+      ])
+      .unwrap();
+
+    world.collider_set_position(near, vec3(0., 0., 2.)).unwrap();
+    world.collider_set_position(far, vec3(0., 0., -5.)).unwrap();
+
+    let mut picker = EntityPicker::new();
+
+    picker.register(near, 1);
+    picker.register(far, 2);
+
+    let center = VIEWPORT / 2.;
+
+    assert_eq!(picker.pick_entity_3d(&*world, &camera, center, VIEWPORT), Some(1));
+
+    world.collider_delete(near).unwrap();
+
+    let hit = pick_collider_3d(&*world, &camera, center, VIEWPORT).unwrap();
+
+    assert_eq!(picker.owner(hit.collider), Some(2));
+    assert!(hit.point.distance(vec3(0., 0., -4.)) < 0.01);
+    assert_eq!(picker.pick_entity_3d(&*world, &camera, Vec2::ZERO, VIEWPORT), None);
+  }
+}
@@ -0,0 +1,11 @@
+//! Generation of collision shapes from content.
+//!
+//! Hand-authoring colliders for every sprite and mesh is tedious; these
+//! utilities derive convex pieces from the content itself, ready to pass to
+//! [`PhysicsWorld::collider_create_compound`].
+
+pub use hulls::*;
+pub use outlines::*;
+
+mod hulls;
+mod outlines;
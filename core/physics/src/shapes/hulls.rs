@@ -0,0 +1,286 @@
+//! Convex hulls and approximate convex decomposition of 3D meshes.
+
+use common::{FastHashSet, Vec3};
+
+/// A closed convex hull in 3-space.
+#[derive(Clone, Debug)]
+pub struct ConvexHull {
+  pub vertices: Vec<Vec3>,
+  /// Triangles indexing into `vertices`, wound counter-clockwise when viewed
+  /// from outside the hull.
+  pub faces: Vec<[usize; 3]>,
+}
+
+impl ConvexHull {
+  /// Builds the convex hull of the given points incrementally.
+  ///
+  /// Returns `None` if the points are coplanar, and so enclose no volume.
+  pub fn from_points(points: &[Vec3]) -> Option<Self> {
+    let [a, b, c, d] = find_initial_tetrahedron(points)?;
+    let epsilon = hull_epsilon(points);
+    let centroid = (points[a] + points[b] + points[c] + points[d]) / 4.;
+
+    let mut faces = vec![[a, b, c], [a, d, b], [b, d, c], [c, d, a]];
+
+    for face in &mut faces {
+      if face_normal(points, face).dot(centroid - points[face[0]]) > 0. {
+        face.swap(1, 2);
+      }
+    }
+
+    for (index, &point) in points.iter().enumerate() {
+      let visible = faces
+        .iter()
+        .map(|face| face_normal(points, face).dot(point - points[face[0]]) > epsilon)
+        .collect::<Vec<_>>();
+
+      if !visible.contains(&true) {
+        continue;
+      }
+
+      // the horizon is the boundary of the faces this point can see; the
+      // point replaces those faces with a cone joining it to the horizon
+      let edges = faces
+        .iter()
+        .zip(&visible)
+        .filter(|(_, &visible)| visible)
+        .flat_map(|(face, _)| [(face[0], face[1]), (face[1], face[2]), (face[2], face[0])])
+        .collect::<FastHashSet<_>>();
+
+      let horizon = edges.iter().filter(|(from, to)| !edges.contains(&(*to, *from)));
+      let mut cone = horizon.map(|&(from, to)| [from, to, index]).collect::<Vec<_>>();
+
+      faces = faces
+        .iter()
+        .zip(&visible)
+        .filter(|(_, &visible)| !visible)
+        .map(|(face, _)| *face)
+        .collect();
+
+      faces.append(&mut cone);
+    }
+
+    // keep only the points that ended up on the hull
+    let mut remap = vec![usize::MAX; points.len()];
+    let mut vertices = Vec::new();
+
+    for face in &mut faces {
+      for index in face.iter_mut() {
+        if remap[*index] == usize::MAX {
+          remap[*index] = vertices.len();
+          vertices.push(points[*index]);
+        }
+
+        *index = remap[*index];
+      }
+    }
+
+    Some(Self { vertices, faces })
+  }
+
+  /// The volume enclosed by the hull.
+  pub fn volume(&self) -> f32 {
+    let volume = self
+      .faces
+      .iter()
+      .map(|&[a, b, c]| self.vertices[a].dot(self.vertices[b].cross(self.vertices[c])))
+      .sum::<f32>();
+
+    volume / 6.
+  }
+}
+
+/// Options for [`decompose_mesh`].
+#[derive(Clone, Debug)]
+pub struct DecompositionOptions {
+  /// How many times the mesh may be split; up to `2^max_depth` hulls result.
+  pub max_depth: u32,
+  /// The fraction of a hull's volume that splitting it must remove for the
+  /// split to be kept.
+  pub min_volume_reduction: f32,
+}
+
+impl Default for DecompositionOptions {
+  fn default() -> Self {
+    Self {
+      max_depth: 5,
+      min_volume_reduction: 0.1,
+    }
+  }
+}
+
+/// Decomposes a triangle mesh into approximately convex pieces.
+///
+/// In the spirit of V-HACD, but much cheaper: the mesh's triangles are split
+/// in half along the longest axis of their hull, and the split is kept if the
+/// two halves' hulls enclose noticeably less volume than the whole, i.e. the
+/// whole hull was covering a concavity. Pieces too flat to enclose any volume
+/// are dropped.
+pub fn decompose_mesh(vertices: &[Vec3], indices: &[u32], options: &DecompositionOptions) -> Vec<ConvexHull> {
+  let triangles = indices
+    .as_chunks::<3>()
+    .0
+    .iter()
+    .map(|triangle| triangle.map(|index| vertices[index as usize]))
+    .collect::<Vec<[Vec3; 3]>>();
+
+  let mut hulls = Vec::new();
+
+  decompose_triangles(&triangles, options, 0, &mut hulls);
+
+  hulls
+}
+
+/// Recursively splits the triangles while it reduces the hull volume.
+fn decompose_triangles(
+  triangles: &[[Vec3; 3]],
+  options: &DecompositionOptions,
+  depth: u32,
+  hulls: &mut Vec<ConvexHull>,
+) {
+  let Some(hull) = hull_of_triangles(triangles) else {
+    return;
+  };
+
+  if depth < options.max_depth {
+    let min = hull
+      .vertices
+      .iter()
+      .fold(Vec3::INFINITY, |min, vertex| min.min(*vertex));
+    let max = hull
+      .vertices
+      .iter()
+      .fold(Vec3::NEG_INFINITY, |max, vertex| max.max(*vertex));
+    let extent = max - min;
+
+    let axis = match extent.max_element() {
+      longest if longest == extent.x => 0,
+      longest if longest == extent.y => 1,
+      _ => 2,
+    };
+
+    let split = (min[axis] + max[axis]) / 2.;
+    let (below, above): (Vec<_>, Vec<_>) = triangles
+      .iter()
+      .copied()
+      .partition(|[a, b, c]| (a[axis] + b[axis] + c[axis]) / 3. < split);
+
+    if !below.is_empty() && !above.is_empty() {
+      let volume = |triangles: &[[Vec3; 3]]| hull_of_triangles(triangles).map_or(0., |hull| hull.volume());
+
+      if volume(&below) + volume(&above) < hull.volume() * (1. - options.min_volume_reduction) {
+        decompose_triangles(&below, options, depth + 1, hulls);
+        decompose_triangles(&above, options, depth + 1, hulls);
+
+        return;
+      }
+    }
+  }
+
+  hulls.push(hull);
+}
+
+/// The convex hull of all the vertices of the given triangles.
+fn hull_of_triangles(triangles: &[[Vec3; 3]]) -> Option<ConvexHull> {
+  ConvexHull::from_points(&triangles.iter().flatten().copied().collect::<Vec<_>>())
+}
+
+/// Finds four points spanning as much volume as possible to start the hull.
+fn find_initial_tetrahedron(points: &[Vec3]) -> Option<[usize; 4]> {
+  let epsilon = hull_epsilon(points);
+  let furthest = |distance: &dyn Fn(Vec3) -> f32| {
+    (0..points.len()).max_by(|&a, &b| distance(points[a]).total_cmp(&distance(points[b])))
+  };
+
+  let a = (0..points.len()).min_by(|&a, &b| points[a].x.total_cmp(&points[b].x))?;
+  let b = furthest(&|point| point.distance(points[a]))?;
+  let line = (points[b] - points[a]).try_normalize()?;
+  let c = furthest(&|point| (point - points[a]).cross(line).length())?;
+  let normal = (points[b] - points[a]).cross(points[c] - points[a]).try_normalize()?;
+  let d = furthest(&|point| normal.dot(point - points[a]).abs())?;
+
+  if normal.dot(points[d] - points[a]).abs() <= epsilon {
+    return None;
+  }
+
+  Some([a, b, c, d])
+}
+
+/// The unit normal of a face.
+fn face_normal(points: &[Vec3], [a, b, c]: &[usize; 3]) -> Vec3 {
+  (points[*b] - points[*a])
+    .cross(points[*c] - points[*a])
+    .normalize_or_zero()
+}
+
+/// A tolerance for coplanarity, relative to the size of the point cloud.
+fn hull_epsilon(points: &[Vec3]) -> f32 {
+  let size = points
+    .iter()
+    .fold(0f32, |size, point| size.max(point.abs().max_element()));
+
+  size.max(1.) * 1e-5
+}
+
+#[cfg(test)]
+mod tests {
+  use common::vec3;
+
+  use super::*;
+
+  fn create_cube(center: Vec3) -> (Vec<Vec3>, Vec<u32>) {
+    let vertices = (0..8)
+      .map(|i| {
+        let corner = vec3((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32);
+
+        center + corner - Vec3::splat(0.5)
+      })
+      .collect::<Vec<_>>();
+
+    let hull = ConvexHull::from_points(&vertices).unwrap();
+    let indices = hull.faces.iter().flatten().map(|&index| index as u32).collect();
+
+    (hull.vertices, indices)
+  }
+
+  #[test]
+  fn it_should_build_the_hull_of_a_point_cloud() {
+    let mut points = create_cube(Vec3::ZERO).0;
+
+    points.extend([vec3(0.1, 0.2, 0.), vec3(-0.3, 0., 0.4), Vec3::ZERO]);
+
+    let hull = ConvexHull::from_points(&points).unwrap();
+
+    assert_eq!(hull.vertices.len(), 8);
+    assert_eq!(hull.faces.len(), 12);
+    assert!((hull.volume() - 1.).abs() < 1e-5);
+  }
+
+  #[test]
+  fn it_should_reject_coplanar_points() {
+    let points = [Vec3::ZERO, Vec3::X, Vec3::Y, vec3(1., 1., 0.)];
+
+    assert!(ConvexHull::from_points(&points).is_none());
+  }
+
+  #[test]
+  fn it_should_split_concave_meshes_into_convex_hulls() {
+    let (mut vertices, mut indices) = create_cube(vec3(-2., 0., 0.));
+    let (other_vertices, other_indices) = create_cube(vec3(2., 0., 0.));
+
+    let offset = vertices.len() as u32;
+
+    vertices.extend(other_vertices);
+    indices.extend(other_indices.iter().map(|index| index + offset));
+
+    let hulls = decompose_mesh(&vertices, &indices, &DecompositionOptions::default());
+
+    assert_eq!(hulls.len(), 2);
+    assert!(hulls.iter().all(|hull| (hull.volume() - 1.).abs() < 1e-5));
+
+    let (vertices, indices) = create_cube(Vec3::ZERO);
+    let hulls = decompose_mesh(&vertices, &indices, &DecompositionOptions::default());
+
+    assert_eq!(hulls.len(), 1);
+  }
+}
@@ -0,0 +1,378 @@
+//! Polygon outlines traced from sprite masks.
+
+use common::{vec2, DenseGrid, FastHashMap, Vec2};
+
+/// The smallest cross product considered to turn a corner.
+const EPSILON: f32 = 1e-6;
+
+/// Traces convex collision pieces from the solid pixels of a mask.
+///
+/// This traces the outlines of the mask, simplifies them to the given
+/// tolerance (in pixels) and decomposes each into convex pieces. Holes are
+/// filled in. A mask is typically built from a sprite's alpha, e.g.
+/// `DenseGrid::from_slice(width, &pixels.map(|pixel| pixel.a > 127))`.
+pub fn convex_shapes_from_mask(mask: &DenseGrid<bool>, tolerance: f32) -> Vec<Vec<Vec2>> {
+  trace_outlines(mask)
+    .into_iter()
+    .filter(|outline| signed_area(outline) > 0.)
+    .flat_map(|outline| decompose_convex(&simplify_outline(&outline, tolerance)))
+    .collect()
+}
+
+/// Traces the outlines of the solid regions of a mask with marching squares.
+///
+/// Points are in pixel coordinates, on the boundary halfway between solid and
+/// empty pixel centres. The outlines of solid regions have a positive
+/// [`signed_area`], and those of holes within them a negative one.
+pub fn trace_outlines(mask: &DenseGrid<bool>) -> Vec<Vec<Vec2>> {
+  let is_solid = |x: i32, y: i32| mask.get(x, y).copied().unwrap_or(false);
+  let mut segments = FastHashMap::default();
+
+  for y in -1..mask.height() as i32 {
+    for x in -1..mask.width() as i32 {
+      // the corners of the cell clockwise from the top left, and the midpoints
+      // of the edges following each corner, doubled to keep them exact
+      let corners = [
+        is_solid(x, y),
+        is_solid(x + 1, y),
+        is_solid(x + 1, y + 1),
+        is_solid(x, y + 1),
+      ];
+      let edges = [
+        (2 * x + 1, 2 * y),
+        (2 * x + 2, 2 * y + 1),
+        (2 * x + 1, 2 * y + 2),
+        (2 * x, 2 * y + 1),
+      ];
+
+      for enter in 0..4 {
+        if corners[enter] || !corners[(enter + 1) % 4] {
+          continue;
+        }
+
+        // pairing with the next exit splits saddles into separate regions
+        let exit = (1..4)
+          .map(|offset| (enter + offset) % 4)
+          .find(|&exit| corners[exit] && !corners[(exit + 1) % 4]);
+
+        if let Some(exit) = exit {
+          segments.insert(edges[enter], edges[exit]);
+        }
+      }
+    }
+  }
+
+  let mut outlines = Vec::new();
+
+  while let Some(&start) = segments.keys().next() {
+    let mut outline = Vec::new();
+    let mut current = start;
+
+    while let Some(next) = segments.remove(&current) {
+      outline.push(vec2(current.0 as f32 / 2. + 0.5, current.1 as f32 / 2. + 0.5));
+      current = next;
+    }
+
+    outline.reverse();
+    outlines.push(outline);
+  }
+
+  outlines
+}
+
+/// Simplifies a closed outline with Ramer-Douglas-Peucker, dropping points
+/// within the given distance of the simplified outline.
+pub fn simplify_outline(outline: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+  if outline.len() < 4 {
+    return outline.to_vec();
+  }
+
+  // split the loop at the point furthest from the first, and simplify each half
+  let furthest = (1..outline.len())
+    .max_by(|&a, &b| {
+      let a = outline[0].distance_squared(outline[a]);
+      let b = outline[0].distance_squared(outline[b]);
+
+      a.total_cmp(&b)
+    })
+    .unwrap_or(0);
+
+  let closed = outline.iter().chain(&outline[..1]).copied().collect::<Vec<_>>();
+  let mut keep = vec![false; outline.len()];
+
+  keep[0] = true;
+  keep[furthest] = true;
+
+  simplify_range(&closed, 0, furthest, tolerance, &mut keep);
+  simplify_range(&closed, furthest, outline.len(), tolerance, &mut keep);
+
+  outline
+    .iter()
+    .zip(keep)
+    .filter_map(|(point, keep)| keep.then_some(*point))
+    .collect()
+}
+
+/// Keeps the point furthest from the line between `start` and `end`, if it's
+/// beyond the tolerance, and recurses either side of it.
+fn simplify_range(points: &[Vec2], start: usize, end: usize, tolerance: f32, keep: &mut [bool]) {
+  if end <= start + 1 {
+    return;
+  }
+
+  let (a, b) = (points[start], points[end]);
+  let furthest = (start + 1..end)
+    .map(|index| (index, distance_to_segment(points[index], a, b)))
+    .max_by(|a, b| a.1.total_cmp(&b.1));
+
+  if let Some((index, distance)) = furthest {
+    if distance > tolerance {
+      keep[index] = true;
+
+      simplify_range(points, start, index, tolerance, keep);
+      simplify_range(points, index, end, tolerance, keep);
+    }
+  }
+}
+
+/// Decomposes a simple polygon into convex pieces.
+///
+/// The polygon is triangulated by ear clipping, then triangles are merged
+/// across shared edges for as long as the result stays convex
+/// (Hertel-Mehlhorn). Pieces have a positive [`signed_area`].
+pub fn decompose_convex(polygon: &[Vec2]) -> Vec<Vec<Vec2>> {
+  let mut polygon = remove_collinear(polygon);
+
+  if polygon.len() < 3 {
+    return Vec::new();
+  }
+
+  if signed_area(&polygon) < 0. {
+    polygon.reverse();
+  }
+
+  let mut pieces = triangulate(&polygon)
+    .into_iter()
+    .map(|triangle| triangle.to_vec())
+    .collect::<Vec<_>>();
+
+  'merging: loop {
+    for i in 0..pieces.len() {
+      for j in i + 1..pieces.len() {
+        if let Some(merged) = merge_pieces(&pieces[i], &pieces[j], &polygon) {
+          pieces[i] = merged;
+          pieces.swap_remove(j);
+
+          continue 'merging;
+        }
+      }
+    }
+
+    break;
+  }
+
+  pieces
+    .into_iter()
+    .map(|piece| piece.into_iter().map(|index| polygon[index]).collect())
+    .collect()
+}
+
+/// The signed area of a polygon; positive if it winds counter-clockwise.
+pub fn signed_area(polygon: &[Vec2]) -> f32 {
+  let mut area = 0.;
+
+  for (i, a) in polygon.iter().enumerate() {
+    let b = polygon[(i + 1) % polygon.len()];
+
+    area += a.perp_dot(b);
+  }
+
+  area / 2.
+}
+
+/// Triangulates a counter-clockwise polygon by clipping ears.
+fn triangulate(polygon: &[Vec2]) -> Vec<[usize; 3]> {
+  let mut remaining = (0..polygon.len()).collect::<Vec<_>>();
+  let mut triangles = Vec::new();
+
+  while remaining.len() > 3 {
+    let count = remaining.len();
+    let corner = |i: usize| {
+      (
+        remaining[(i + count - 1) % count],
+        remaining[i],
+        remaining[(i + 1) % count],
+      )
+    };
+
+    let ear = (0..count).find(|&i| {
+      let (a, b, c) = corner(i);
+      let (pa, pb, pc) = (polygon[a], polygon[b], polygon[c]);
+
+      (pb - pa).perp_dot(pc - pb) > EPSILON
+        && !remaining
+          .iter()
+          .filter(|&&p| p != a && p != b && p != c)
+          .any(|&p| is_in_triangle(polygon[p], pa, pb, pc))
+    });
+
+    // only degenerate polygons have no ears; keep what was clipped so far
+    let Some(ear) = ear else {
+      return triangles;
+    };
+
+    let (a, b, c) = corner(ear);
+
+    triangles.push([a, b, c]);
+    remaining.remove(ear);
+  }
+
+  if let [a, b, c] = remaining[..] {
+    triangles.push([a, b, c]);
+  }
+
+  triangles
+}
+
+/// Merges two pieces sharing an edge, if the result is convex.
+fn merge_pieces(a: &[usize], b: &[usize], polygon: &[Vec2]) -> Option<Vec<usize>> {
+  for i in 0..a.len() {
+    let (from, to) = (a[i], a[(i + 1) % a.len()]);
+
+    let Some(j) = (0..b.len()).find(|&j| b[j] == to && b[(j + 1) % b.len()] == from) else {
+      continue;
+    };
+
+    // walk a from the end of the shared edge round to its start, then b back
+    let mut merged = (1..=a.len()).map(|k| a[(i + k) % a.len()]).collect::<Vec<_>>();
+
+    merged.extend((2..b.len()).map(|k| b[(j + k) % b.len()]));
+
+    let is_convex = (0..merged.len()).all(|k| {
+      let p0 = polygon[merged[k]];
+      let p1 = polygon[merged[(k + 1) % merged.len()]];
+      let p2 = polygon[merged[(k + 2) % merged.len()]];
+
+      (p1 - p0).perp_dot(p2 - p1) >= -EPSILON
+    });
+
+    return is_convex.then_some(merged);
+  }
+
+  None
+}
+
+/// Removes repeated points, and points on the line between their neighbours.
+fn remove_collinear(polygon: &[Vec2]) -> Vec<Vec2> {
+  let mut points = polygon.to_vec();
+  let mut i = 0;
+
+  while points.len() >= 3 && i < points.len() {
+    let count = points.len();
+    let previous = points[(i + count - 1) % count];
+    let next = points[(i + 1) % count];
+
+    if (points[i] - previous).perp_dot(next - points[i]).abs() <= EPSILON {
+      points.remove(i);
+      i = i.saturating_sub(1);
+    } else {
+      i += 1;
+    }
+  }
+
+  points
+}
+
+/// Determines if a point is inside or on the edge of a counter-clockwise
+/// triangle.
+fn is_in_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+  (b - a).perp_dot(point - a) >= 0. && (c - b).perp_dot(point - b) >= 0. && (a - c).perp_dot(point - c) >= 0.
+}
+
+/// The distance from a point to the closest point on a line segment.
+fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+  let edge = b - a;
+  let length_squared = edge.length_squared();
+
+  if length_squared == 0. {
+    return point.distance(a);
+  }
+
+  let t = ((point - a).dot(edge) / length_squared).clamp(0., 1.);
+
+  point.distance(a + edge * t)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn create_mask(width: usize, rows: &[&str]) -> DenseGrid<bool> {
+    let pixels = rows
+      .iter()
+      .flat_map(|row| row.chars().map(|pixel| pixel == '#'))
+      .collect::<Vec<_>>();
+
+    DenseGrid::from_slice(width, &pixels)
+  }
+
+  #[test]
+  fn it_should_trace_solid_regions_and_holes() {
+    let mask = create_mask(6, &[
+      "......", //
+      ".####.", ".#..#.", ".#..#.", ".####.", "......",
+    ]);
+
+    let mut areas = trace_outlines(&mask)
+      .iter()
+      .map(|outline| signed_area(outline))
+      .collect::<Vec<_>>();
+
+    areas.sort_by(f32::total_cmp);
+
+    // the outer outline cuts its corners, and the hole's rounds inwards
+    assert_eq!(areas, vec![-3.5, 15.5]);
+  }
+
+  #[test]
+  fn it_should_simplify_staircase_outlines() {
+    let outline = (0..=10)
+      .map(|i| vec2(i as f32, 0.))
+      .chain((1..10).map(|i| vec2(10., i as f32)))
+      .chain([vec2(10., 10.), vec2(0., 10.)])
+      .collect::<Vec<_>>();
+
+    let simplified = simplify_outline(&outline, 0.5);
+
+    assert_eq!(simplified.len(), 4);
+    assert_eq!(signed_area(&simplified), signed_area(&outline));
+  }
+
+  #[test]
+  fn it_should_decompose_concave_outlines_into_convex_pieces() {
+    let mask = create_mask(8, &[
+      "##......", //
+      "##......", "##......", "##......", "########", "########",
+    ]);
+
+    let outline = trace_outlines(&mask).remove(0);
+    let pieces = convex_shapes_from_mask(&mask, 0.25);
+
+    assert!(pieces.len() >= 2);
+
+    let total_area = pieces.iter().map(|piece| signed_area(piece)).sum::<f32>();
+
+    for piece in &pieces {
+      assert!(signed_area(piece) > 0.);
+
+      for (i, point) in piece.iter().enumerate() {
+        let next = piece[(i + 1) % piece.len()];
+        let after = piece[(i + 2) % piece.len()];
+
+        assert!((next - *point).perp_dot(after - next) >= -EPSILON);
+      }
+    }
+
+    assert!((total_area - signed_area(&outline)).abs() < 1.);
+  }
+}
@@ -0,0 +1,433 @@
+//! Arcade vehicle physics.
+//!
+//! A [`VehicleController`] simulates the chassis of a car as a single rigid
+//! body, held up by a raycast spring at each wheel. Tires grip sideways and
+//! roll forwards, engine and brake forces come from [`TorqueCurve`]s over
+//! speed, and anti-roll bars keep each axle level through corners. It's not a
+//! simulation of a real car, but it's enough for kart-style prototypes.
+
+use common::{vec2, vec3, Quat, Vec2};
+
+use super::*;
+
+/// Gravity applied to vehicles, in metres per second squared.
+const GRAVITY: Real3 = vec3(0., -9.81, 0.);
+
+/// Where a wheel's ray met the ground.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WheelContact {
+  pub distance: Real,
+  pub normal: Real3,
+}
+
+/// Something the wheels of a vehicle can drive on.
+pub trait VehicleGround {
+  /// Casts a ray down from a wheel, returning where it meets the ground.
+  fn cast_wheel(&self, origin: Real3, direction: Real3, max_distance: Real) -> Option<WheelContact>;
+}
+
+/// An infinite flat ground at the given height.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GroundPlane {
+  pub height: Real,
+}
+
+impl VehicleGround for GroundPlane {
+  fn cast_wheel(&self, origin: Real3, direction: Real3, max_distance: Real) -> Option<WheelContact> {
+    if direction.y >= 0. {
+      return None;
+    }
+
+    let distance = (origin.y - self.height) / -direction.y;
+
+    (0. ..=max_distance).contains(&distance).then_some(WheelContact {
+      distance,
+      normal: Real3::Y,
+    })
+  }
+}
+
+/// A force that varies with speed, as points of (speed, force) sorted by
+/// speed and linearly interpolated between.
+///
+/// Forces are at the tire's contact with the ground; that is, wheel torque
+/// over wheel radius.
+#[derive(Clone, Debug, Default)]
+pub struct TorqueCurve {
+  pub points: Vec<Vec2>,
+}
+
+impl TorqueCurve {
+  /// Creates a curve from (speed, force) points.
+  pub fn new(points: impl IntoIterator<Item = (Real, Real)>) -> Self {
+    let mut points = points
+      .into_iter()
+      .map(|(speed, force)| vec2(speed, force))
+      .collect::<Vec<_>>();
+
+    points.sort_by(|a, b| a.x.total_cmp(&b.x));
+
+    Self { points }
+  }
+
+  /// Creates a curve with the same force at every speed.
+  pub fn constant(force: Real) -> Self {
+    Self::new([(0., force)])
+  }
+
+  /// The force at the given speed, holding the first and last points beyond
+  /// either end of the curve.
+  pub fn sample(&self, speed: Real) -> Real {
+    let index = self.points.partition_point(|point| point.x < speed);
+
+    match (
+      index.checked_sub(1).map(|i| self.points[i]),
+      self.points.get(index).copied(),
+    ) {
+      (Some(a), Some(b)) => a.y + (b.y - a.y) * (speed - a.x) / (b.x - a.x),
+      (Some(point), None) | (None, Some(point)) => point.y,
+      (None, None) => 0.,
+    }
+  }
+}
+
+/// Settings for a single wheel of a vehicle.
+#[derive(Clone, Debug)]
+pub struct WheelSettings {
+  /// Where the top of the suspension attaches, relative to the chassis.
+  pub offset: Real3,
+  pub radius: Real,
+  pub steered: bool,
+  pub driven: bool,
+}
+
+/// Tunable parameters for a [`VehicleController`].
+///
+/// The chassis faces down its local negative Z axis, with X to the right.
+#[derive(Clone, Debug)]
+pub struct VehicleSettings {
+  pub mass: Real,
+  /// The moments of inertia of the chassis about its local axes.
+  pub inertia: Real3,
+  pub wheels: Vec<WheelSettings>,
+  /// How far each wheel can travel up into the chassis.
+  pub suspension_length: Real,
+  /// The force per metre of suspension compression.
+  pub spring_strength: Real,
+  /// The force per metre per second of suspension travel.
+  pub damping: Real,
+  /// The driving force shared between the driven wheels, by forward speed.
+  pub engine: TorqueCurve,
+  /// The braking force shared between all wheels, by forward speed.
+  pub brakes: TorqueCurve,
+  /// How quickly tires cancel sliding sideways, per second.
+  pub grip: Real,
+  /// How quickly free-rolling tires slow down, per second.
+  pub rolling_resistance: Real,
+  /// The force per metre of difference in compression across an axle.
+  pub anti_roll: Real,
+  /// How far steered wheels turn at full lock, in radians.
+  pub max_steering_angle: Real,
+}
+
+impl Default for VehicleSettings {
+  fn default() -> Self {
+    let wheel = |x: Real, z: Real| WheelSettings {
+      offset: vec3(x, 0., z),
+      radius: 0.3,
+      steered: z < 0.,
+      driven: z > 0.,
+    };
+
+    Self {
+      mass: 200.,
+      inertia: vec3(110., 140., 40.),
+      wheels: vec![wheel(-0.7, -1.), wheel(0.7, -1.), wheel(-0.7, 1.), wheel(0.7, 1.)],
+      suspension_length: 0.3,
+      spring_strength: 4000.,
+      damping: 500.,
+      engine: TorqueCurve::new([(0., 1200.), (10., 1000.), (20., 400.), (25., 0.)]),
+      brakes: TorqueCurve::constant(2000.),
+      grip: 10.,
+      rolling_resistance: 0.2,
+      anti_roll: 2000.,
+      max_steering_angle: 0.6,
+    }
+  }
+}
+
+/// The controls of a vehicle for a single step.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct VehicleInput {
+  /// From -1 for full reverse to 1 for full throttle.
+  pub throttle: Real,
+  /// From 0 to 1.
+  pub brake: Real,
+  /// From -1 for full left to 1 for full right.
+  pub steering: Real,
+}
+
+/// The state of a single wheel after a step.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct WheelState {
+  /// How far up the suspension has been pushed, in metres.
+  pub compression: Real,
+  pub contact: Option<WheelContact>,
+}
+
+/// An arcade vehicle, simulated as a chassis on raycast suspension.
+#[derive(Clone, Debug)]
+pub struct VehicleController {
+  pub settings: VehicleSettings,
+  pub position: Real3,
+  pub rotation: Quat,
+  pub linear_velocity: Real3,
+  pub angular_velocity: Real3,
+  wheels: Vec<WheelState>,
+}
+
+impl VehicleController {
+  /// Creates a vehicle at rest at the origin.
+  pub fn new(settings: VehicleSettings) -> Self {
+    Self {
+      wheels: vec![WheelState::default(); settings.wheels.len()],
+      settings,
+      position: Real3::ZERO,
+      rotation: Quat::IDENTITY,
+      linear_velocity: Real3::ZERO,
+      angular_velocity: Real3::ZERO,
+    }
+  }
+
+  /// The direction the vehicle faces.
+  pub fn forward(&self) -> Real3 {
+    self.rotation * Real3::NEG_Z
+  }
+
+  /// The speed along the direction the vehicle faces; negative in reverse.
+  pub fn forward_speed(&self) -> Real {
+    self.linear_velocity.dot(self.forward())
+  }
+
+  /// The state of each wheel after the last step.
+  pub fn wheels(&self) -> &[WheelState] {
+    &self.wheels
+  }
+
+  /// Determines if any wheel is touching the ground.
+  pub fn is_grounded(&self) -> bool {
+    self.wheels.iter().any(|wheel| wheel.contact.is_some())
+  }
+
+  /// Advances the vehicle by a step.
+  pub fn update(&mut self, input: &VehicleInput, delta_time: Real, ground: &dyn VehicleGround) {
+    if delta_time <= 0. {
+      return;
+    }
+
+    let settings = &self.settings;
+    let wheel_count = settings.wheels.len().max(1) as Real;
+    let driven_count = settings.wheels.iter().filter(|wheel| wheel.driven).count().max(1) as Real;
+    let mass_per_wheel = settings.mass / wheel_count;
+
+    let up = self.rotation * Real3::Y;
+    let steering = Quat::from_axis_angle(Real3::Y, -input.steering.clamp(-1., 1.) * settings.max_steering_angle);
+
+    let mut force = settings.mass * GRAVITY;
+    let mut torque = Real3::ZERO;
+
+    // suspension and tires, at each wheel
+    for (wheel, state) in settings.wheels.iter().zip(&mut self.wheels) {
+      let origin = self.position + self.rotation * wheel.offset;
+      let max_distance = settings.suspension_length + wheel.radius;
+
+      state.contact = ground.cast_wheel(origin, -up, max_distance);
+      state.compression = state.contact.map_or(0., |contact| max_distance - contact.distance);
+
+      let Some(contact) = state.contact else {
+        continue;
+      };
+
+      let arm = origin - self.position;
+      let velocity = self.linear_velocity + self.angular_velocity.cross(arm);
+
+      let spring = state.compression * settings.spring_strength - velocity.dot(up) * settings.damping;
+      let mut wheel_force = up * spring.max(0.);
+
+      // the tire pushes along the ground, facing wherever it's steered
+      let local_forward = if wheel.steered {
+        steering * Real3::NEG_Z
+      } else {
+        Real3::NEG_Z
+      };
+
+      let forward = (self.rotation * local_forward)
+        .reject_from_normalized(contact.normal)
+        .normalize_or_zero();
+      let side = forward.cross(contact.normal);
+
+      let forward_speed = velocity.dot(forward);
+      let side_speed = velocity.dot(side);
+
+      wheel_force -= side * side_speed * settings.grip * mass_per_wheel;
+      wheel_force -= forward * forward_speed * settings.rolling_resistance * mass_per_wheel;
+
+      if wheel.driven {
+        let throttle = input.throttle.clamp(-1., 1.);
+
+        wheel_force += forward * throttle * settings.engine.sample(forward_speed.abs()) / driven_count;
+      }
+
+      if input.brake > 0. {
+        // brakes stop the wheel, but never push it backwards
+        let limit = forward_speed.abs() * mass_per_wheel / delta_time;
+        let braking = (input.brake.min(1.) * settings.brakes.sample(forward_speed.abs()) / wheel_count).min(limit);
+
+        wheel_force -= forward * forward_speed.signum() * braking;
+      }
+
+      force += wheel_force;
+      torque += arm.cross(wheel_force);
+    }
+
+    // anti-roll bars push down on the more compressed wheel of each axle, and
+    // up on the other
+    for (i, left) in settings.wheels.iter().enumerate() {
+      let right = settings.wheels.iter().enumerate().skip(i + 1).find(|(_, right)| {
+        right.offset.z == left.offset.z && right.offset.y == left.offset.y && right.offset.x == -left.offset.x
+      });
+
+      let Some((j, right)) = right else {
+        continue;
+      };
+
+      if self.wheels[i].contact.is_none() || self.wheels[j].contact.is_none() {
+        continue;
+      }
+
+      let difference = (self.wheels[i].compression - self.wheels[j].compression) * settings.anti_roll;
+
+      torque += (self.rotation * left.offset).cross(up * -difference);
+      torque += (self.rotation * right.offset).cross(up * difference);
+    }
+
+    // torque is resisted by the inertia about each of the chassis' own axes
+    let local_torque = self.rotation.inverse() * torque;
+    let angular_acceleration = self.rotation * (local_torque / settings.inertia);
+
+    self.linear_velocity += force / settings.mass * delta_time;
+    self.angular_velocity += angular_acceleration * delta_time;
+
+    self.position += self.linear_velocity * delta_time;
+    self.rotation = (Quat::from_scaled_axis(self.angular_velocity * delta_time) * self.rotation).normalize();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DELTA_TIME: Real = 1. / 60.;
+
+  fn simulate(vehicle: &mut VehicleController, input: VehicleInput, seconds: Real) {
+    for _ in 0..(seconds / DELTA_TIME) as usize {
+      vehicle.update(&input, DELTA_TIME, &GroundPlane::default());
+    }
+  }
+
+  fn create_vehicle() -> VehicleController {
+    let mut vehicle = VehicleController::new(VehicleSettings::default());
+
+    vehicle.position = vec3(0., 0.6, 0.);
+    vehicle
+  }
+
+  #[test]
+  fn it_should_sample_torque_curves() {
+    let curve = TorqueCurve::new([(10., 500.), (0., 1000.)]);
+
+    assert_eq!(curve.sample(-1.), 1000.);
+    assert_eq!(curve.sample(5.), 750.);
+    assert_eq!(curve.sample(20.), 500.);
+    assert_eq!(TorqueCurve::default().sample(5.), 0.);
+  }
+
+  #[test]
+  fn it_should_settle_on_its_suspension() {
+    let mut vehicle = create_vehicle();
+
+    simulate(&mut vehicle, VehicleInput::default(), 5.);
+
+    // each wheel carries a quarter of the weight on its spring
+    let settings = &vehicle.settings;
+    let compression = settings.mass * -GRAVITY.y / 4. / settings.spring_strength;
+
+    assert!(vehicle.wheels().iter().all(|wheel| wheel.contact.is_some()));
+    assert!(vehicle.linear_velocity.length() < 0.01);
+    assert!((vehicle.wheels()[0].compression - compression).abs() < 0.01);
+    assert!(vehicle.rotation.angle_between(Quat::IDENTITY) < 0.01);
+  }
+
+  #[test]
+  fn it_should_accelerate_and_brake() {
+    let mut vehicle = create_vehicle();
+
+    simulate(&mut vehicle, VehicleInput::default(), 1.);
+    simulate(
+      &mut vehicle,
+      VehicleInput {
+        throttle: 1.,
+        ..Default::default()
+      },
+      3.,
+    );
+
+    let speed = vehicle.forward_speed();
+
+    assert!(speed > 5.);
+    assert!(vehicle.position.z < -5.);
+
+    simulate(
+      &mut vehicle,
+      VehicleInput {
+        brake: 1.,
+        ..Default::default()
+      },
+      3.,
+    );
+
+    assert!(vehicle.forward_speed().abs() < 0.1);
+  }
+
+  #[test]
+  fn it_should_steer_without_sliding() {
+    let mut vehicle = create_vehicle();
+
+    simulate(&mut vehicle, VehicleInput::default(), 1.);
+    simulate(
+      &mut vehicle,
+      VehicleInput {
+        throttle: 1.,
+        ..Default::default()
+      },
+      2.,
+    );
+    simulate(
+      &mut vehicle,
+      VehicleInput {
+        throttle: 0.5,
+        steering: 1.,
+        ..Default::default()
+      },
+      1.,
+    );
+
+    let forward = vehicle.forward();
+    let side_speed = vehicle.linear_velocity.dot(forward.cross(Real3::Y));
+
+    // steering right turns the nose towards positive X
+    assert!(forward.x > 0.3);
+    assert!(side_speed.abs() < vehicle.forward_speed() * 0.2);
+    assert!(vehicle.wheels().iter().all(|wheel| wheel.contact.is_some()));
+  }
+}
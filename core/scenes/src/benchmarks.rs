@@ -0,0 +1,610 @@
+//! Stress-test content, for measuring how a backend or platform scales.
+//!
+//! A [`BenchmarkScene`] fills a [`World`] with any number of sprites, meshes,
+//! lights or bodies, laid out in a pattern and set in motion, then records
+//! frame times while it runs so runs can be compared before and after
+//! optimization work:
+//!
+//! ```rust,ignore
+//! BenchmarkScene::add_systems(&mut schedule);
+//!
+//! let mut benchmark = BenchmarkScene::spawn(&mut world, BenchmarkSettings::parse(&["sprites", "10000", "grid", "orbit"])?);
+//!
+//! // each frame
+//! schedule.run(&mut world, delta_time);
+//! benchmark.record_frame(frame_time);
+//!
+//! println!("{}", benchmark.report());
+//! ```
+//!
+//! Renderers and physics backends draw or simulate the spawned components
+//! like any others. The same arguments work from the developer console, as
+//! `bench spawn sprites 10000 grid orbit`, and from the command line, as
+//! `--bench sprites 10000 grid orbit`.
+
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use common::{Color32, ConsoleError, DeveloperConsole, FrameStatistics, Random, Vec3};
+
+use super::*;
+
+/// What a benchmark spawns.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum BenchmarkContent {
+  #[default]
+  Sprites,
+  Meshes,
+  Lights,
+  Bodies,
+}
+
+/// How spawned content is laid out.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SpawnPattern {
+  /// Evenly spaced rows and columns.
+  #[default]
+  Grid,
+  /// Scattered uniformly.
+  Random,
+  /// Evenly spaced around a circle.
+  Ring,
+  /// Along a spiral out from the center.
+  Spiral,
+}
+
+/// How spawned content moves.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum BenchmarkMotion {
+  Static,
+  /// Drifts in a straight line, bouncing off the edges.
+  #[default]
+  Drift,
+  /// Circles around where it was spawned.
+  Orbit,
+  /// Bobs up and down in a wave across the area.
+  Wave,
+}
+
+macro_rules! impl_names {
+  ($type:ty, [$($variant:ident => $name:literal),* $(,)?]) => {
+    impl $type {
+      /// The names of every option, as typed in commands.
+      pub const NAMES: &'static [&'static str] = &[$($name),*];
+
+      /// Finds an option by name.
+      pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+          $($name => Some(Self::$variant),)*
+          _ => None,
+        }
+      }
+
+      /// The name of the option, as typed in commands.
+      pub fn name(self) -> &'static str {
+        match self {
+          $(Self::$variant => $name,)*
+        }
+      }
+    }
+  };
+}
+
+impl_names!(BenchmarkContent, [Sprites => "sprites", Meshes => "meshes", Lights => "lights", Bodies => "bodies"]);
+impl_names!(SpawnPattern, [Grid => "grid", Random => "random", Ring => "ring", Spiral => "spiral"]);
+impl_names!(BenchmarkMotion, [Static => "static", Drift => "drift", Orbit => "orbit", Wave => "wave"]);
+
+/// What to spawn for a benchmark, and how.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchmarkSettings {
+  pub content: BenchmarkContent,
+  pub count: usize,
+  pub pattern: SpawnPattern,
+  pub motion: BenchmarkMotion,
+  /// Half the width and height of the area content is spawned in.
+  pub extent: f32,
+  /// The seed for random placement and colors, so runs are repeatable.
+  pub seed: u64,
+}
+
+impl Default for BenchmarkSettings {
+  fn default() -> Self {
+    Self {
+      content: BenchmarkContent::default(),
+      count: 1000,
+      pattern: SpawnPattern::default(),
+      motion: BenchmarkMotion::default(),
+      extent: 100.,
+      seed: 0,
+    }
+  }
+}
+
+impl BenchmarkSettings {
+  /// How to use the arguments read by [`BenchmarkSettings::parse`].
+  pub const USAGE: &'static str =
+    "<sprites|meshes|lights|bodies> [count] [grid|random|ring|spiral] [static|drift|orbit|wave] [extent=<size>] [seed=<n>]";
+
+  /// Reads settings from arguments like `sprites 10000 grid orbit seed=3`.
+  ///
+  /// The content comes first, and everything after it is optional and can
+  /// come in any order.
+  pub fn parse(arguments: &[&str]) -> Result<Self, String> {
+    let (content, options) = arguments
+      .split_first()
+      .ok_or_else(|| format!("usage: {}", Self::USAGE))?;
+
+    let mut settings = Self {
+      content: BenchmarkContent::from_name(content).ok_or_else(|| {
+        format!(
+          "unknown content '{content}'; expected one of {:?}",
+          BenchmarkContent::NAMES
+        )
+      })?,
+      ..Self::default()
+    };
+
+    for option in options {
+      if let Ok(count) = option.parse() {
+        settings.count = count;
+      } else if let Some(pattern) = SpawnPattern::from_name(option) {
+        settings.pattern = pattern;
+      } else if let Some(motion) = BenchmarkMotion::from_name(option) {
+        settings.motion = motion;
+      } else if let Some(extent) = option.strip_prefix("extent=").and_then(|it| it.parse().ok()) {
+        settings.extent = extent;
+      } else if let Some(seed) = option.strip_prefix("seed=").and_then(|it| it.parse().ok()) {
+        settings.seed = seed;
+      } else {
+        return Err(format!("unknown option '{option}'; usage: {}", Self::USAGE));
+      }
+    }
+
+    Ok(settings)
+  }
+
+  /// Reads settings from command line arguments following `--bench`, if it's
+  /// there, up to the next flag.
+  pub fn from_command_line(arguments: &[String]) -> Option<Result<Self, String>> {
+    let start = arguments.iter().position(|argument| argument == "--bench")? + 1;
+    let arguments = arguments[start..]
+      .iter()
+      .take_while(|argument| !argument.starts_with("--"))
+      .map(String::as_str)
+      .collect::<Vec<_>>();
+
+    Some(Self::parse(&arguments))
+  }
+}
+
+/// Where a piece of benchmark content is, for renderers to draw it at.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BenchmarkTransform {
+  pub position: Vec3,
+  /// The rotation around the z axis, in radians.
+  pub rotation: f32,
+  pub scale: f32,
+}
+
+/// Moves benchmark content each frame; see [`BenchmarkMotion`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BenchmarkMover {
+  pub motion: BenchmarkMotion,
+  pub origin: Vec3,
+  pub velocity: Vec3,
+  /// How far through its orbit or wave the content is, in radians.
+  pub phase: f32,
+  /// Half the width and height of the area to stay in.
+  pub extent: f32,
+}
+
+/// A sprite to draw at the content's transform.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BenchmarkSprite {
+  pub color: Color32,
+  pub size: f32,
+}
+
+/// A mesh to draw at the content's transform.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BenchmarkMesh {
+  pub color: Color32,
+  pub size: f32,
+}
+
+/// A point light at the content's transform.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BenchmarkLight {
+  pub color: Color32,
+  pub radius: f32,
+  pub intensity: f32,
+}
+
+/// A dynamic body to simulate at the content's transform.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BenchmarkBody {
+  pub radius: f32,
+  pub mass: f32,
+  pub velocity: Vec3,
+}
+
+/// A summary of a benchmark run.
+#[derive(Clone, Debug)]
+pub struct BenchmarkReport {
+  pub settings: BenchmarkSettings,
+  pub entities: usize,
+  pub frames: usize,
+  /// Frame times, in milliseconds.
+  pub average_ms: f32,
+  pub p95_ms: f32,
+  pub p99_ms: f32,
+  pub max_ms: f32,
+  pub fps: f32,
+}
+
+impl Display for BenchmarkReport {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(
+      formatter,
+      "{} {} ({}, {}) over {} frames",
+      self.entities,
+      self.settings.content.name(),
+      self.settings.pattern.name(),
+      self.settings.motion.name(),
+      self.frames
+    )?;
+
+    write!(
+      formatter,
+      "avg {:.2}ms, p95 {:.2}ms, p99 {:.2}ms, max {:.2}ms, {:.1} fps",
+      self.average_ms, self.p95_ms, self.p99_ms, self.max_ms, self.fps
+    )
+  }
+}
+
+/// Stress-test content spawned into a [`World`], and its frame times.
+pub struct BenchmarkScene {
+  settings: BenchmarkSettings,
+  entities: Vec<WorldEntity>,
+  statistics: FrameStatistics,
+}
+
+impl BenchmarkScene {
+  /// The number of recent frames a report covers.
+  const FRAME_WINDOW: usize = 1000;
+
+  /// Spawns the content described by the settings into the world.
+  pub fn spawn(world: &mut World, settings: BenchmarkSettings) -> Self {
+    let mut random = Random::with_seed(settings.seed);
+    let mut entities = Vec::with_capacity(settings.count);
+
+    for index in 0..settings.count {
+      let position = spawn_position(&settings, index, &mut random);
+      let color = random.next::<Color32>();
+      let entity = world.spawn();
+
+      world.insert(entity, BenchmarkTransform {
+        position,
+        rotation: 0.,
+        scale: 1.,
+      });
+
+      let direction = random.next::<f32>() * std::f32::consts::TAU;
+      let velocity = Vec3::new(direction.cos(), direction.sin(), 0.) * settings.extent * 0.1;
+
+      if settings.motion != BenchmarkMotion::Static {
+        world.insert(entity, BenchmarkMover {
+          motion: settings.motion,
+          origin: position,
+          velocity,
+          phase: random.next::<f32>() * std::f32::consts::TAU,
+          extent: settings.extent,
+        });
+      }
+
+      match settings.content {
+        BenchmarkContent::Sprites => world.insert(entity, BenchmarkSprite { color, size: 1. }),
+        BenchmarkContent::Meshes => world.insert(entity, BenchmarkMesh { color, size: 1. }),
+        BenchmarkContent::Lights => world.insert(entity, BenchmarkLight {
+          color,
+          radius: settings.extent * 0.1,
+          intensity: 1.,
+        }),
+        BenchmarkContent::Bodies => world.insert(entity, BenchmarkBody {
+          radius: 0.5,
+          mass: 1.,
+          velocity,
+        }),
+      };
+
+      entities.push(entity);
+    }
+
+    Self {
+      settings,
+      entities,
+      statistics: FrameStatistics::new(Self::FRAME_WINDOW),
+    }
+  }
+
+  /// Adds the system that moves benchmark content to the schedule.
+  ///
+  /// Bodies are left for the physics backend to move.
+  pub fn add_systems(schedule: &mut Schedule) {
+    schedule.add_fn(
+      "benchmark_motion",
+      SystemAccess::new()
+        .writes::<BenchmarkTransform>()
+        .writes::<BenchmarkMover>(),
+      |world, delta_time| {
+        for (_, (transform, mover)) in world.query::<(&mut BenchmarkTransform, &mut BenchmarkMover)>() {
+          update_mover(transform, mover, delta_time);
+        }
+      },
+    );
+  }
+
+  /// The settings the content was spawned with.
+  pub fn settings(&self) -> &BenchmarkSettings {
+    &self.settings
+  }
+
+  /// The entities that were spawned.
+  pub fn entities(&self) -> &[WorldEntity] {
+    &self.entities
+  }
+
+  /// Records how long a frame took, in seconds.
+  pub fn record_frame(&mut self, frame_time: f32) {
+    self.statistics.record_frame(frame_time);
+  }
+
+  /// Summarises the frames recorded so far.
+  pub fn report(&self) -> BenchmarkReport {
+    BenchmarkReport {
+      settings: self.settings.clone(),
+      entities: self.entities.len(),
+      frames: self.statistics.frame_count(),
+      average_ms: self.statistics.average_frame_time() * 1000.,
+      p95_ms: self.statistics.percentile_frame_time(0.95) * 1000.,
+      p99_ms: self.statistics.percentile_frame_time(0.99) * 1000.,
+      max_ms: self.statistics.max_frame_time() * 1000.,
+      fps: self.statistics.fps(),
+    }
+  }
+
+  /// Despawns the content from the world.
+  pub fn clear(self, world: &mut World) {
+    for entity in self.entities {
+      world.despawn(entity);
+    }
+  }
+
+  /// Registers the `bench` command with the given console, working on a
+  /// shared world and benchmark.
+  ///
+  /// `bench spawn <content> ...` replaces the current benchmark, `bench
+  /// report` summarises its frames and `bench clear` removes it.
+  pub fn register_commands(
+    world: Rc<RefCell<World>>,
+    benchmark: Rc<RefCell<Option<BenchmarkScene>>>,
+    console: &mut DeveloperConsole,
+  ) {
+    console.register(
+      "bench",
+      "spawns stress-test content: bench spawn <content> [count] [pattern] [motion] | report | clear",
+      move |arguments| match arguments {
+        ["spawn", settings @ ..] => {
+          let settings = BenchmarkSettings::parse(settings).map_err(ConsoleError::InvalidArguments)?;
+          let mut world = world.borrow_mut();
+
+          if let Some(previous) = benchmark.borrow_mut().take() {
+            previous.clear(&mut world);
+          }
+
+          let spawned = BenchmarkScene::spawn(&mut world, settings);
+          let message = format!("spawned {} {}", spawned.entities.len(), spawned.settings.content.name());
+
+          *benchmark.borrow_mut() = Some(spawned);
+
+          Ok(message)
+        }
+        ["report"] => match benchmark.borrow().as_ref() {
+          Some(benchmark) => Ok(benchmark.report().to_string()),
+          None => Ok("no benchmark running".to_string()),
+        },
+        ["clear"] => match benchmark.borrow_mut().take() {
+          Some(previous) => {
+            let count = previous.entities.len();
+
+            previous.clear(&mut world.borrow_mut());
+
+            Ok(format!("despawned {count} entities"))
+          }
+          None => Ok("no benchmark running".to_string()),
+        },
+        _ => Err(ConsoleError::InvalidArguments(format!(
+          "usage: bench spawn {} | bench report | bench clear",
+          BenchmarkSettings::USAGE
+        ))),
+      },
+    );
+  }
+}
+
+/// Where the content at the given index is spawned.
+fn spawn_position(settings: &BenchmarkSettings, index: usize, random: &mut Random) -> Vec3 {
+  let extent = settings.extent;
+  let fraction = index as f32 / settings.count.max(1) as f32;
+
+  match settings.pattern {
+    SpawnPattern::Grid => {
+      let columns = (settings.count as f32).sqrt().ceil().max(1.) as usize;
+      let spacing = extent * 2. / columns as f32;
+
+      Vec3::new(
+        -extent + spacing * ((index % columns) as f32 + 0.5),
+        -extent + spacing * ((index / columns) as f32 + 0.5),
+        0.,
+      )
+    }
+    SpawnPattern::Random => Vec3::new(
+      (random.next::<f32>() * 2. - 1.) * extent,
+      (random.next::<f32>() * 2. - 1.) * extent,
+      0.,
+    ),
+    SpawnPattern::Ring => {
+      let angle = fraction * std::f32::consts::TAU;
+
+      Vec3::new(angle.cos(), angle.sin(), 0.) * extent
+    }
+    SpawnPattern::Spiral => {
+      // the golden angle spreads points evenly over the disc
+      let angle = index as f32 * 2.399_963;
+      let radius = fraction.sqrt() * extent;
+
+      Vec3::new(angle.cos(), angle.sin(), 0.) * radius
+    }
+  }
+}
+
+/// Moves a piece of content by a frame.
+fn update_mover(transform: &mut BenchmarkTransform, mover: &mut BenchmarkMover, delta_time: f32) {
+  mover.phase = (mover.phase + delta_time) % std::f32::consts::TAU;
+
+  match mover.motion {
+    BenchmarkMotion::Static => {}
+    BenchmarkMotion::Drift => {
+      transform.position += mover.velocity * delta_time;
+
+      for axis in 0..2 {
+        if transform.position[axis].abs() > mover.extent {
+          transform.position[axis] = transform.position[axis].clamp(-mover.extent, mover.extent);
+          mover.velocity[axis] = -mover.velocity[axis];
+        }
+      }
+    }
+    BenchmarkMotion::Orbit => {
+      let radius = mover.extent * 0.05;
+
+      transform.position = mover.origin + Vec3::new(mover.phase.cos(), mover.phase.sin(), 0.) * radius;
+      transform.rotation = mover.phase;
+    }
+    BenchmarkMotion::Wave => {
+      let offset = (mover.phase + mover.origin.x / mover.extent.max(f32::EPSILON)).sin();
+
+      transform.position.y = mover.origin.y + offset * mover.extent * 0.05;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_parse_settings_from_the_console_and_command_line() {
+    let settings = BenchmarkSettings::parse(&["lights", "500", "orbit", "spiral", "seed=7"]).unwrap();
+
+    assert_eq!(settings.content, BenchmarkContent::Lights);
+    assert_eq!(settings.count, 500);
+    assert_eq!(settings.pattern, SpawnPattern::Spiral);
+    assert_eq!(settings.motion, BenchmarkMotion::Orbit);
+    assert_eq!(settings.seed, 7);
+
+    let arguments = ["game", "--bench", "bodies", "20", "--fullscreen"].map(String::from);
+
+    assert_eq!(
+      BenchmarkSettings::from_command_line(&arguments).unwrap().unwrap().count,
+      20
+    );
+    assert!(BenchmarkSettings::from_command_line(&arguments[..1]).is_none());
+    assert!(BenchmarkSettings::parse(&["sprites", "sideways"]).is_err());
+    assert!(BenchmarkSettings::parse(&["teapots"]).is_err());
+  }
+
+  #[test]
+  fn it_should_spawn_repeatable_content_inside_its_area() {
+    for pattern in [
+      SpawnPattern::Grid,
+      SpawnPattern::Random,
+      SpawnPattern::Ring,
+      SpawnPattern::Spiral,
+    ] {
+      let settings = BenchmarkSettings {
+        content: BenchmarkContent::Meshes,
+        count: 250,
+        pattern,
+        extent: 10.,
+        seed: 3,
+        ..Default::default()
+      };
+
+      let mut first = World::new();
+      let mut second = World::new();
+      let benchmark = BenchmarkScene::spawn(&mut first, settings.clone());
+
+      BenchmarkScene::spawn(&mut second, settings);
+
+      assert_eq!(first.len(), 250);
+      assert_eq!(
+        first.query::<(&BenchmarkTransform,)>().count(),
+        first.query::<(&BenchmarkMesh,)>().count()
+      );
+
+      for (&entity, (_, (transform,))) in benchmark
+        .entities()
+        .iter()
+        .zip(second.query::<(&BenchmarkTransform,)>())
+      {
+        let position = first.get::<BenchmarkTransform>(entity).unwrap().position;
+
+        assert_eq!(position, transform.position, "{pattern:?} isn't repeatable");
+        assert!(position.x.abs() <= 10. + 1e-4 && position.y.abs() <= 10. + 1e-4);
+      }
+
+      benchmark.clear(&mut first);
+
+      assert_eq!(first.len(), 0);
+    }
+  }
+
+  #[test]
+  fn it_should_move_content_and_report_frames_from_the_console() {
+    let world = Rc::new(RefCell::new(World::new()));
+    let benchmark = Rc::new(RefCell::new(None));
+    let mut console = DeveloperConsole::new();
+    let mut schedule = Schedule::new();
+
+    BenchmarkScene::add_systems(&mut schedule);
+    BenchmarkScene::register_commands(world.clone(), benchmark.clone(), &mut console);
+
+    assert_eq!(
+      console.execute("bench spawn sprites 100 random drift").unwrap(),
+      "spawned 100 sprites"
+    );
+
+    let before = world
+      .borrow_mut()
+      .query::<(&BenchmarkTransform,)>()
+      .map(|(_, (transform,))| transform.position)
+      .collect::<Vec<_>>();
+
+    for _ in 0..10 {
+      schedule.run(&mut world.borrow_mut(), 0.1);
+      benchmark.borrow_mut().as_mut().unwrap().record_frame(0.016);
+    }
+
+    let moved = world
+      .borrow_mut()
+      .query::<(&BenchmarkTransform,)>()
+      .zip(before)
+      .filter(|((_, (transform,)), before)| transform.position != *before)
+      .count();
+
+    assert_eq!(moved, 100);
+    assert!(console.execute("bench report").unwrap().contains("over 10 frames"));
+    assert_eq!(console.execute("bench clear").unwrap(), "despawned 100 entities");
+    assert_eq!(world.borrow().len(), 0);
+    assert!(console.execute("bench spawn").is_err());
+  }
+}
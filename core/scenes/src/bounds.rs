@@ -0,0 +1,276 @@
+//! Bounding volumes for entities, aggregated up the hierarchy.
+//!
+//! Components report their bounds in the entity's local space through
+//! [`Component::local_bounds`], and the scene places them in the world with
+//! the transforms of the entity and its ancestors:
+//!
+//! ```rust,ignore
+//! scene.set_transform(player, Mat4::from_translation(position));
+//!
+//! if let Some((entity, _)) = scene.pick(&camera.screen_to_ray(mouse, viewport)) {
+//!   selection.select(entity);
+//! }
+//!
+//! for (_, bounds) in scene.all_bounds() {
+//!   gizmos.bounds(DebugCategory::Gameplay, &bounds);
+//! }
+//! ```
+//!
+//! Bounds are cached until a transform, parent or component changes.
+//! Components whose bounds change on their own should call
+//! [`Scene::invalidate_bounds`].
+
+use std::cell::RefCell;
+
+use common::{Bounds, FastHashMap, Mat4, Ray3};
+
+use super::*;
+
+/// The cached bounds of a single entity.
+#[derive(Clone)]
+struct CachedBounds {
+  world_transform: Mat4,
+  /// The entity's own bounds, in world space.
+  own: Option<Bounds>,
+  /// The bounds of the entity and its descendants, if computed yet.
+  hierarchy: Option<Option<Bounds>>,
+}
+
+/// Bounds computed so far, by entity.
+#[derive(Default)]
+pub(crate) struct BoundsCache {
+  entries: RefCell<FastHashMap<EntityId, CachedBounds>>,
+}
+
+impl Scene {
+  /// The transform of an entity relative to its parent.
+  pub fn transform(&self, id: EntityId) -> Mat4 {
+    self.entities.get(id).map_or(Mat4::IDENTITY, |entity| entity.transform)
+  }
+
+  /// Moves an entity relative to its parent.
+  pub fn set_transform(&mut self, id: EntityId, transform: Mat4) {
+    if let Some(entity) = self.entities.get_mut(id) {
+      entity.transform = transform;
+    }
+
+    self.invalidate_bounds(id);
+  }
+
+  /// The transform of an entity in the world, including its ancestors.
+  pub fn world_transform(&self, id: EntityId) -> Mat4 {
+    self
+      .cached_bounds(id)
+      .map_or(Mat4::IDENTITY, |cached| cached.world_transform)
+  }
+
+  /// The bounds of an entity's own components in the world, if any of them
+  /// have bounds.
+  pub fn bounds(&self, id: EntityId) -> Option<Bounds> {
+    self.cached_bounds(id)?.own
+  }
+
+  /// The bounds of an entity and all of its descendants in the world.
+  pub fn hierarchy_bounds(&self, id: EntityId) -> Option<Bounds> {
+    let cached = self.cached_bounds(id)?;
+
+    if let Some(hierarchy) = cached.hierarchy {
+      return hierarchy;
+    }
+
+    let children = self
+      .entities
+      .enumerate()
+      .filter(|(_, entity)| entity.parent == Some(id))
+      .map(|(child, _)| child)
+      .collect::<Vec<_>>();
+
+    let hierarchy = children
+      .into_iter()
+      .filter_map(|child| self.hierarchy_bounds(child))
+      .fold(cached.own, |bounds, child| match bounds {
+        Some(bounds) => Some(bounds.union(&child)),
+        None => Some(child),
+      });
+
+    if let Some(entry) = self.bounds_cache.entries.borrow_mut().get_mut(&id) {
+      entry.hierarchy = Some(hierarchy.clone());
+    }
+
+    hierarchy
+  }
+
+  /// The world bounds of every entity that has them.
+  pub fn all_bounds(&self) -> impl Iterator<Item = (EntityId, Bounds)> + '_ {
+    self
+      .entities
+      .enumerate()
+      .filter_map(|(id, _)| Some((id, self.bounds(id)?)))
+  }
+
+  /// Finds the nearest entity whose bounds the ray hits, and how far along
+  /// the ray it is.
+  pub fn pick(&self, ray: &Ray3) -> Option<(EntityId, f32)> {
+    self
+      .all_bounds()
+      .filter_map(|(id, bounds)| Some((id, bounds.intersects_ray(ray)?)))
+      .min_by(|(_, a), (_, b)| a.total_cmp(b))
+  }
+
+  /// Forgets the cached bounds of an entity, its descendants and the
+  /// hierarchy bounds of its ancestors.
+  pub fn invalidate_bounds(&self, id: EntityId) {
+    let mut entries = self.bounds_cache.entries.borrow_mut();
+
+    if entries.is_empty() {
+      return;
+    }
+
+    for (other, _) in self.entities.enumerate() {
+      if self.is_descendant(other, id) {
+        entries.remove(&other);
+      }
+    }
+
+    entries.remove(&id);
+
+    let mut current = self.entities.get(id).and_then(|entity| entity.parent);
+
+    while let Some(ancestor) = current {
+      if let Some(entry) = entries.get_mut(&ancestor) {
+        entry.hierarchy = None;
+      }
+
+      current = self.entities.get(ancestor).and_then(|entity| entity.parent);
+    }
+  }
+
+  /// Determines if one entity is below another in the hierarchy.
+  fn is_descendant(&self, id: EntityId, ancestor: EntityId) -> bool {
+    let mut current = self.entities.get(id).and_then(|entity| entity.parent);
+
+    while let Some(parent) = current {
+      if parent == ancestor {
+        return true;
+      }
+
+      current = self.entities.get(parent).and_then(|entity| entity.parent);
+    }
+
+    false
+  }
+
+  /// Gets the cached bounds of an entity, computing them if needed.
+  fn cached_bounds(&self, id: EntityId) -> Option<CachedBounds> {
+    if let Some(cached) = self.bounds_cache.entries.borrow().get(&id) {
+      return Some(cached.clone());
+    }
+
+    let entity = self.entities.get(id)?;
+    let parent_transform = entity
+      .parent
+      .and_then(|parent| self.cached_bounds(parent))
+      .map_or(Mat4::IDENTITY, |parent| parent.world_transform);
+
+    let world_transform = parent_transform * entity.transform;
+    let own = entity
+      .components
+      .iter()
+      .filter_map(|component| component.local_bounds())
+      .reduce(|a, b| a.union(&b))
+      .map(|bounds| bounds.transform(&world_transform));
+
+    let cached = CachedBounds {
+      world_transform,
+      own,
+      hierarchy: None,
+    };
+
+    self.bounds_cache.entries.borrow_mut().insert(id, cached.clone());
+
+    Some(cached)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{ray3, vec3, Vec3, AABB};
+
+  use super::*;
+
+  struct Model {
+    size: f32,
+  }
+
+  impl Component for Model {
+    fn local_bounds(&self) -> Option<Bounds> {
+      let half = Vec3::splat(self.size / 2.);
+
+      Some(Bounds::from_aabb(AABB::from_min_max(-half, half)))
+    }
+  }
+
+  fn create_scene() -> (Scene, EntityId, EntityId, EntityId) {
+    let mut scene = Scene::new();
+
+    let car = scene.spawn_named("car");
+    let wheel = scene.spawn_named("wheel");
+    let marker = scene.spawn_named("marker");
+
+    scene.set_parent(wheel, Some(car));
+    scene.set_parent(marker, Some(car));
+    scene.add_component(car, Model { size: 2. });
+    scene.add_component(wheel, Model { size: 1. });
+    scene.set_transform(car, Mat4::from_translation(vec3(10., 0., 0.)));
+    scene.set_transform(wheel, Mat4::from_translation(vec3(2., 0., 0.)));
+
+    (scene, car, wheel, marker)
+  }
+
+  #[test]
+  fn it_should_place_bounds_in_the_world() {
+    let (scene, car, wheel, marker) = create_scene();
+
+    assert_eq!(
+      scene.bounds(wheel).unwrap().aabb,
+      AABB::from_min_max(vec3(11.5, -0.5, -0.5), vec3(12.5, 0.5, 0.5))
+    );
+    assert_eq!(scene.bounds(marker), None);
+    assert_eq!(
+      scene.hierarchy_bounds(car).unwrap().aabb,
+      AABB::from_min_max(vec3(9., -1., -1.), vec3(12.5, 1., 1.))
+    );
+  }
+
+  #[test]
+  fn it_should_update_when_transforms_change() {
+    let (mut scene, car, wheel, _) = create_scene();
+
+    assert_eq!(scene.bounds(wheel).unwrap().center(), vec3(12., 0., 0.));
+    assert_eq!(scene.hierarchy_bounds(car).unwrap().aabb.max.x, 12.5);
+
+    scene.set_transform(car, Mat4::from_translation(vec3(-10., 0., 0.)));
+
+    assert_eq!(scene.bounds(wheel).unwrap().center(), vec3(-8., 0., 0.));
+    assert_eq!(scene.hierarchy_bounds(car).unwrap().aabb.max.x, -7.5);
+
+    scene.set_transform(wheel, Mat4::from_translation(vec3(0., 5., 0.)));
+
+    assert_eq!(scene.hierarchy_bounds(car).unwrap().aabb.max.y, 5.5);
+  }
+
+  #[test]
+  fn it_should_pick_the_nearest_entity() {
+    let (scene, car, wheel, _) = create_scene();
+
+    let (picked, distance) = scene.pick(&ray3(vec3(0., 0., 0.), Vec3::X)).unwrap();
+
+    assert_eq!(picked, car);
+    assert_eq!(distance, 9.);
+
+    let (picked, _) = scene.pick(&ray3(vec3(12., 10., 0.), -Vec3::Y)).unwrap();
+
+    assert_eq!(picked, wheel);
+    assert_eq!(scene.pick(&ray3(vec3(0., 10., 0.), Vec3::X)), None);
+  }
+}
@@ -1,7 +1,11 @@
 //! Canvas nodes for 2D graphics.
 
+pub use flocking::*;
+pub use spawning::*;
 pub use sprites::*;
 
+mod flocking;
+mod spawning;
 mod sprites;
 
 use super::*;
@@ -0,0 +1,31 @@
+use std::cell::RefCell;
+
+use common::Flock;
+
+use super::*;
+
+/// A component that simulates a flock of boids, for birds, fish and swarms.
+///
+/// The flock is stepped by a fixed time step on every tick.
+pub struct FlockComponent {
+  pub flock: RefCell<Flock>,
+  pub time_step: f32,
+}
+
+impl FlockComponent {
+  /// Creates a new component around the given flock.
+  pub fn new(flock: Flock) -> Self {
+    Self {
+      flock: RefCell::new(flock),
+      time_step: 1. / 60.,
+    }
+  }
+}
+
+impl Component for FlockComponent {}
+
+impl EventListener<Tick> for FlockComponent {
+  fn on_event(&self, _event: &mut Tick) {
+    self.flock.borrow_mut().step(self.time_step);
+  }
+}
@@ -0,0 +1,50 @@
+use std::cell::{Cell, RefCell};
+
+use common::{Random, SpawnEvent, Spawner, Vec2};
+
+use super::*;
+
+/// A component that spawns and despawns pooled instances over time.
+///
+/// The spawner is stepped by a fixed time step on every tick, and the
+/// resulting events are queued for the game to create or recycle entities.
+pub struct SpawnerComponent<K> {
+  pub spawner: RefCell<Spawner<K>>,
+  /// The point despawn distances are measured from, e.g. the player.
+  pub focus: Cell<Vec2>,
+  pub time_step: f32,
+  random: RefCell<Random>,
+  events: RefCell<Vec<SpawnEvent<K>>>,
+}
+
+impl<K: Clone + Default + 'static> SpawnerComponent<K> {
+  /// Creates a new component around the given spawner.
+  pub fn new(spawner: Spawner<K>) -> Self {
+    Self {
+      spawner: RefCell::new(spawner),
+      focus: Cell::new(Vec2::ZERO),
+      time_step: 1. / 60.,
+      random: RefCell::new(Random::default()),
+      events: RefCell::new(Vec::new()),
+    }
+  }
+
+  /// Takes the events raised since the last call.
+  pub fn drain_events(&self) -> Vec<SpawnEvent<K>> {
+    self.events.take()
+  }
+}
+
+impl<K: 'static> Component for SpawnerComponent<K> {}
+
+impl<K: Clone + Default + 'static> EventListener<Tick> for SpawnerComponent<K> {
+  fn on_event(&self, _event: &mut Tick) {
+    let mut random = self.random.borrow_mut();
+    let events = self
+      .spawner
+      .borrow_mut()
+      .update(self.time_step, self.focus.get(), &mut random);
+
+    self.events.borrow_mut().extend(events);
+  }
+}
@@ -0,0 +1,61 @@
+//! Combat components.
+
+use common::{Chunk, FastHashMap, Health, StateHasher, StreamError, Variant};
+
+use super::*;
+
+impl Component for Health {
+  fn validate(&self, context: &mut ValidationContext) {
+    context.check_finite("health", &[self.current(), self.max(), self.invulnerability()]);
+
+    if self.max() <= 0. {
+      context.error(format!("maximum health must be positive, but is {}", self.max()));
+    }
+  }
+
+  fn checksum(&self, hasher: &mut StateHasher) {
+    hasher.write(self);
+  }
+
+  fn inspect(&self) -> Option<Chunk> {
+    let mut fields = FastHashMap::default();
+
+    fields.insert("current".to_string(), Chunk::Variant(Variant::F32(self.current())));
+    fields.insert("max".to_string(), Chunk::Variant(Variant::F32(self.max())));
+    fields.insert(
+      "invulnerability".to_string(),
+      Chunk::Variant(Variant::F32(self.invulnerability())),
+    );
+
+    Some(Chunk::Map(fields))
+  }
+
+  fn apply_inspected(&mut self, fields: &Chunk) -> Result<(), StreamError> {
+    let mut health = self.clone().with_invulnerability(fields.read_field("invulnerability")?);
+
+    health.set_max(fields.read_field("max")?);
+    health.set_current(fields.read_field("current")?);
+
+    *self = health;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_validate_health() {
+    let mut scene = Scene::new();
+    let player = scene.spawn_named("player");
+
+    scene.add_component(player, Health::new(0.));
+
+    let report = SceneValidator::new().validate(&scene);
+
+    assert_eq!(report.errors().count(), 1);
+    assert!(scene.entity(player).unwrap().has_component::<Health>());
+  }
+}
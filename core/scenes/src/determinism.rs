@@ -0,0 +1,75 @@
+//! Determinism checks for scenes.
+
+use common::DeterminismRecorder;
+
+use super::*;
+
+impl Scene {
+  /// Records a checksum of each component type into the current frame of
+  /// the given recorder, so a divergence points at the component that
+  /// caused it.
+  ///
+  /// Entities are hashed in arena order, so two runs that spawn and despawn
+  /// the same entities hash the same.
+  pub fn record_checksums(&self, recorder: &mut DeterminismRecorder) {
+    if !recorder.is_enabled() {
+      return;
+    }
+
+    recorder.record("entities", |hasher| {
+      for (id, entity) in self.entities.enumerate() {
+        hasher.write_u32(id.ordinal());
+        hasher.write(&entity.parent.map(|parent| parent.ordinal()));
+      }
+    });
+
+    for (id, entity) in self.entities.enumerate() {
+      for component in &entity.components {
+        recorder.record(component.component_name(), |hasher| {
+          hasher.write_u32(id.ordinal());
+          component.checksum(hasher);
+        });
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{DeterminismRecorder, Health};
+
+  use super::*;
+
+  fn record(damage: f32) -> DeterminismRecorder {
+    let mut scene = Scene::new();
+    let mut recorder = DeterminismRecorder::new();
+
+    let player = scene.spawn_named("player");
+    let enemy = scene.spawn_named("enemy");
+
+    scene.add_component(player, Health::new(100.));
+    scene.add_component(enemy, {
+      let mut health = Health::new(50.);
+      health.damage(damage);
+      health
+    });
+
+    recorder.begin_frame(0);
+    scene.record_checksums(&mut recorder);
+    recorder.end_frame();
+
+    recorder
+  }
+
+  #[test]
+  fn it_should_point_at_the_divergent_component() {
+    let expected = record(10.);
+    let actual = record(10.5);
+
+    let divergence = actual.compare(expected.frames()).unwrap();
+
+    assert_eq!(divergence.frame, 0);
+    assert_eq!(divergence.section, "Health");
+    assert_eq!(record(10.).compare(expected.frames()), None);
+  }
+}
@@ -0,0 +1,431 @@
+//! Moving entities along splines, for moving platforms, patrol routes and
+//! camera rails.
+//!
+//! A [`PathFollower`] moves along a shared [`SplinePath`] at an even speed,
+//! shaped by a [`SpeedProfile`], and writes where it is to the entity's
+//! [`PathPose`] for renderers, physics and cameras to pick up:
+//!
+//! ```rust,ignore
+//! let events = Rc::new(EventBus::new());
+//! let route = Arc::new(SplinePath::new(waypoints, true));
+//!
+//! PathFollower::add_systems(&mut schedule, events.clone());
+//!
+//! world.insert(guard, PathFollower::new(route, 2.).with_alignment(PathAlignment::Tangent { forward: Vec3::X }));
+//! world.insert(guard, PathPose::default());
+//!
+//! // each frame
+//! schedule.run(&mut world, delta_time);
+//!
+//! for event in events.iter() {
+//!   if let PathEventKind::Waypoint(index) = event.kind {
+//!     guard_look_around(event.entity, index);
+//!   }
+//! }
+//! ```
+
+use std::{rc::Rc, sync::Arc};
+
+use common::{CatmulRomSpline, EventBus, Quat, Spline, SplinePath, Vec3};
+
+use super::*;
+
+/// What a follower does when it reaches the end of its path.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PathMode {
+  /// Stops at the end.
+  #[default]
+  Once,
+  /// Jumps back to the start, or carries on round if the path is closed.
+  Loop,
+  /// Turns around and heads back the way it came.
+  PingPong,
+}
+
+/// How a follower's speed changes along its path.
+#[derive(Clone, Debug, Default)]
+pub enum SpeedProfile {
+  /// The same speed the whole way.
+  #[default]
+  Constant,
+  /// Eases up from, and down to, a fraction of the speed over a distance at
+  /// each end of the path, like a lift slowing for its floors.
+  EaseInOut { distance: f32, minimum: f32 },
+  /// Scales the speed by a spline over the progress along the path, from 0
+  /// at the start to 1 at the end.
+  Curve(CatmulRomSpline),
+}
+
+impl SpeedProfile {
+  /// The slowest a profile can make a follower go, as a fraction of its
+  /// speed, so it can't stall at the ends.
+  const MINIMUM: f32 = 0.01;
+
+  /// The fraction of the follower's speed at a distance along the path.
+  pub fn multiplier(&self, distance: f32, length: f32) -> f32 {
+    let multiplier = match self {
+      Self::Constant => 1.,
+      Self::EaseInOut {
+        distance: ease,
+        minimum,
+      } => {
+        let edge = distance.min(length - distance).max(0.);
+        let t = if *ease > 0. { (edge / ease).min(1.) } else { 1. };
+        let smooth = t * t * (3. - 2. * t);
+
+        minimum + (1. - minimum) * smooth
+      }
+      Self::Curve(spline) if spline.points.len() >= 2 => {
+        let progress = if length > 0. { distance / length } else { 0. };
+
+        // the last point has no segment after it to evaluate
+        spline.value(progress.clamp(0., 0.9999))
+      }
+      Self::Curve(_) => 1.,
+    };
+
+    multiplier.max(Self::MINIMUM)
+  }
+}
+
+/// Which way a follower faces.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum PathAlignment {
+  /// Keeps its rotation.
+  #[default]
+  None,
+  /// Turns the given local axis to face the way it's moving.
+  Tangent { forward: Vec3 },
+}
+
+/// Where a follower is, written each frame for whatever draws or simulates
+/// the entity.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PathPose {
+  pub position: Vec3,
+  pub rotation: Quat,
+}
+
+/// Something that happened to a follower as it moved.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PathEventKind {
+  /// Passed one of the path's points, by index.
+  Waypoint(usize),
+  /// Went round from the end of the path back to the start.
+  Looped,
+  /// Turned around at one end of the path.
+  Reversed,
+  /// Stopped at the end of the path.
+  Finished,
+}
+
+/// Something that happened to an entity's [`PathFollower`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PathEvent {
+  pub entity: WorldEntity,
+  pub kind: PathEventKind,
+}
+
+/// Moves an entity along a [`SplinePath`].
+#[derive(Clone, Debug)]
+pub struct PathFollower {
+  pub path: Arc<SplinePath>,
+  /// The speed along the path, in units per second.
+  pub speed: f32,
+  pub profile: SpeedProfile,
+  pub mode: PathMode,
+  pub alignment: PathAlignment,
+  /// How far along the path the follower is.
+  pub distance: f32,
+  /// Whether the follower is heading back towards the start.
+  pub reversed: bool,
+  pub paused: bool,
+  finished: bool,
+}
+
+impl PathFollower {
+  /// Creates a follower at the start of the path.
+  pub fn new(path: Arc<SplinePath>, speed: f32) -> Self {
+    Self {
+      path,
+      speed,
+      profile: SpeedProfile::default(),
+      mode: PathMode::default(),
+      alignment: PathAlignment::default(),
+      distance: 0.,
+      reversed: false,
+      paused: false,
+      finished: false,
+    }
+  }
+
+  /// Sets what happens at the end of the path.
+  pub fn with_mode(mut self, mode: PathMode) -> Self {
+    self.mode = mode;
+    self
+  }
+
+  /// Sets how the speed changes along the path.
+  pub fn with_profile(mut self, profile: SpeedProfile) -> Self {
+    self.profile = profile;
+    self
+  }
+
+  /// Sets which way the follower faces.
+  pub fn with_alignment(mut self, alignment: PathAlignment) -> Self {
+    self.alignment = alignment;
+    self
+  }
+
+  /// How far along the path the follower is, from 0 to 1.
+  pub fn progress(&self) -> f32 {
+    match self.path.length() > 0. {
+      true => self.distance / self.path.length(),
+      false => 0.,
+    }
+  }
+
+  /// Determines if the follower stopped at the end of its path.
+  pub fn is_finished(&self) -> bool {
+    self.finished
+  }
+
+  /// Sends the follower back to the start of its path.
+  pub fn restart(&mut self) {
+    self.distance = 0.;
+    self.reversed = false;
+    self.finished = false;
+  }
+
+  /// Where the follower is on its path, keeping the given rotation if it
+  /// isn't aligned to the path.
+  pub fn pose(&self, rotation: Quat) -> PathPose {
+    let rotation = match self.alignment {
+      PathAlignment::None => rotation,
+      PathAlignment::Tangent { forward } => {
+        let tangent = self.path.tangent_at(self.distance);
+        let heading = if self.reversed { -tangent } else { tangent };
+
+        match heading == Vec3::ZERO {
+          true => rotation,
+          false => Quat::from_rotation_arc(forward.normalize(), heading),
+        }
+      }
+    };
+
+    PathPose {
+      position: self.path.point_at(self.distance),
+      rotation,
+    }
+  }
+
+  /// Moves the follower along its path by a frame.
+  pub fn advance(&mut self, delta_time: f32, emit: &mut dyn FnMut(PathEventKind)) {
+    let length = self.path.length();
+
+    if self.paused || self.finished || length <= 0. {
+      return;
+    }
+
+    let step = self.speed * self.profile.multiplier(self.distance, length) * delta_time;
+    let start = self.distance;
+
+    if !self.reversed {
+      self.distance += step;
+
+      if self.distance < length {
+        self.emit_waypoints(start, self.distance, emit);
+        return;
+      }
+
+      self.emit_waypoints(start, length, emit);
+
+      match self.mode {
+        PathMode::Once => {
+          self.distance = length;
+          self.finished = true;
+          emit(PathEventKind::Finished);
+        }
+        PathMode::Loop => {
+          self.distance = (self.distance - length).min(length);
+          emit(PathEventKind::Looped);
+          self.emit_waypoints(0., self.distance, emit);
+        }
+        PathMode::PingPong => {
+          self.distance = (2. * length - self.distance).max(0.);
+          self.reversed = true;
+          emit(PathEventKind::Reversed);
+        }
+      }
+    } else {
+      self.distance -= step;
+
+      if self.distance > 0. {
+        self.emit_waypoints(start, self.distance, emit);
+        return;
+      }
+
+      self.emit_waypoints(start, 0., emit);
+
+      match self.mode {
+        PathMode::Once => {
+          self.distance = 0.;
+          self.finished = true;
+          emit(PathEventKind::Finished);
+        }
+        PathMode::Loop => {
+          self.distance = (self.distance + length).max(0.);
+          emit(PathEventKind::Looped);
+          self.emit_waypoints(length, self.distance, emit);
+        }
+        PathMode::PingPong => {
+          self.distance = (-self.distance).min(length);
+          self.reversed = false;
+          emit(PathEventKind::Reversed);
+        }
+      }
+    }
+  }
+
+  /// Sends events for the points passed moving from one distance to another,
+  /// in the order they were passed.
+  fn emit_waypoints(&self, from: f32, to: f32, emit: &mut dyn FnMut(PathEventKind)) {
+    let count = self.path.points().len();
+    let passed = |index: &usize| {
+      let distance = self.path.distance_to_point(*index);
+
+      match from <= to {
+        true => (distance > from && distance <= to) || (distance == 0. && from == 0. && to > 0.),
+        false => distance < from && distance >= to,
+      }
+    };
+
+    if from <= to {
+      (0..count)
+        .filter(passed)
+        .for_each(|index| emit(PathEventKind::Waypoint(index)));
+    } else {
+      (0..count)
+        .rev()
+        .filter(passed)
+        .for_each(|index| emit(PathEventKind::Waypoint(index)));
+    }
+  }
+
+  /// Adds the system that moves followers and updates their poses to the
+  /// schedule, sending what happens to them to the event bus.
+  pub fn add_systems(schedule: &mut Schedule, events: Rc<EventBus<PathEvent>>) {
+    schedule.add_fn(
+      "path_followers",
+      SystemAccess::new().writes::<PathFollower>().writes::<PathPose>(),
+      move |world, delta_time| {
+        for (entity, (follower, pose)) in world.query::<(&mut PathFollower, &mut PathPose)>() {
+          follower.advance(delta_time, &mut |kind| events.send(PathEvent { entity, kind }));
+
+          *pose = follower.pose(pose.rotation);
+        }
+      },
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::vec3;
+
+  use super::*;
+
+  fn create_path(closed: bool) -> Arc<SplinePath> {
+    Arc::new(SplinePath::new(
+      vec![vec3(0., 0., 0.), vec3(10., 0., 0.), vec3(20., 0., 0.)],
+      closed,
+    ))
+  }
+
+  fn run(follower: &mut PathFollower, steps: usize, delta_time: f32) -> Vec<PathEventKind> {
+    let mut events = Vec::new();
+
+    for _ in 0..steps {
+      follower.advance(delta_time, &mut |kind| events.push(kind));
+    }
+
+    events
+  }
+
+  #[test]
+  fn it_should_stop_at_the_end_of_the_path_once() {
+    let mut follower = PathFollower::new(create_path(false), 10.);
+
+    assert_eq!(run(&mut follower, 1, 0.5), vec![PathEventKind::Waypoint(0)]);
+    assert_eq!(run(&mut follower, 1, 1.), vec![PathEventKind::Waypoint(1)]);
+    assert_eq!(run(&mut follower, 2, 1.), vec![
+      PathEventKind::Waypoint(2),
+      PathEventKind::Finished
+    ]);
+
+    assert!(follower.is_finished());
+    assert_eq!(follower.progress(), 1.);
+    assert!(follower.pose(Quat::IDENTITY).position.distance(vec3(20., 0., 0.)) < 1e-3);
+  }
+
+  #[test]
+  fn it_should_turn_around_and_face_the_way_it_moves() {
+    let mut follower = PathFollower::new(create_path(false), 10.)
+      .with_mode(PathMode::PingPong)
+      .with_alignment(PathAlignment::Tangent { forward: Vec3::X });
+
+    run(&mut follower, 1, 1.5);
+
+    assert!((follower.pose(Quat::IDENTITY).rotation * Vec3::X).distance(Vec3::X) < 1e-3);
+
+    let events = run(&mut follower, 1, 1.);
+
+    assert_eq!(events, vec![PathEventKind::Waypoint(2), PathEventKind::Reversed]);
+    assert!(follower.reversed);
+    assert!((follower.distance - 15.).abs() < 1e-3);
+    assert!((follower.pose(Quat::IDENTITY).rotation * Vec3::X).distance(-Vec3::X) < 1e-3);
+  }
+
+  #[test]
+  fn it_should_ease_in_and_out_at_the_ends() {
+    let profile = SpeedProfile::EaseInOut {
+      distance: 5.,
+      minimum: 0.2,
+    };
+
+    assert!((profile.multiplier(0., 20.) - 0.2).abs() < 1e-5);
+    assert_eq!(profile.multiplier(10., 20.), 1.);
+    assert!((profile.multiplier(20., 20.) - 0.2).abs() < 1e-5);
+  }
+
+  #[test]
+  fn it_should_move_followers_in_the_world_and_send_events() {
+    let events = Rc::new(EventBus::new());
+    let mut world = World::new();
+    let mut schedule = Schedule::new();
+
+    PathFollower::add_systems(&mut schedule, events.clone());
+
+    let platform = world.spawn();
+
+    world.insert(
+      platform,
+      PathFollower::new(create_path(true), 10.).with_mode(PathMode::Loop),
+    );
+    world.insert(platform, PathPose::default());
+
+    for _ in 0..35 {
+      schedule.run(&mut world, 0.1);
+    }
+
+    let kinds = events
+      .iter()
+      .inspect(|event| assert_eq!(event.entity, platform))
+      .map(|event| event.kind)
+      .collect::<Vec<_>>();
+
+    assert!(kinds.contains(&PathEventKind::Waypoint(2)));
+    assert!(!kinds.contains(&PathEventKind::Looped));
+    assert_ne!(world.get::<PathPose>(platform).unwrap().position, Vec3::ZERO);
+  }
+}
@@ -0,0 +1,683 @@
+//! Inspection of live scenes from the developer console.
+//!
+//! Components opt in by exposing their fields as a [`Chunk`] through
+//! [`Component::inspect`], and accepting edited fields back through
+//! [`Component::apply_inspected`]. The [`SceneInspector`] builds on that to
+//! list entities, dump their components as text and set fields by path, and
+//! registers console commands to do it in a running game:
+//!
+//! ```text
+//! > entities
+//! #0 player (Health, Transform)
+//! > inspect player
+//! Health
+//!   current: 100
+//!   max: 100
+//! > set player.Transform.position.x 10
+//! ```
+//!
+//! Fields holding an [`AssetRef`](common::AssetRef) are shown as their asset
+//! type and ID, and are set by ID; [`SceneInspector::asset_fields`] lists them
+//! so editors can offer a picker of assets of the right type.
+
+use std::{cell::RefCell, fmt::Write, rc::Rc};
+
+use common::{AssetId, AssetRefField, Chunk, ConsoleError, DeveloperConsole, Variant, Vec2, Vec3, Vec4};
+
+use super::*;
+
+/// A potential error when inspecting a scene.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InspectError {
+  UnknownEntity(String),
+  UnknownComponent(String),
+  UnknownField(String),
+  NotInspectable(String),
+  InvalidValue(String),
+}
+
+impl std::fmt::Display for InspectError {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::UnknownEntity(name) => write!(formatter, "no entity named '{name}'"),
+      Self::UnknownComponent(name) => write!(formatter, "no component named '{name}'"),
+      Self::UnknownField(path) => write!(formatter, "no field at '{path}'"),
+      Self::NotInspectable(name) => write!(formatter, "{name} can't be inspected"),
+      Self::InvalidValue(value) => write!(formatter, "'{value}' isn't valid for that field"),
+    }
+  }
+}
+
+impl From<InspectError> for ConsoleError {
+  fn from(error: InspectError) -> Self {
+    ConsoleError::InvalidArguments(error.to_string())
+  }
+}
+
+impl Scene {
+  /// Finds an entity by its name, its path, or its index like `#3`.
+  pub fn find(&self, name: &str) -> Option<EntityId> {
+    let index = name.strip_prefix('#').and_then(|index| index.parse::<u32>().ok());
+
+    self.entities().map(|(id, _)| id).find(|id| match index {
+      Some(index) => id.ordinal() == index,
+      None => self.entity(*id).and_then(Entity::name) == Some(name) || self.path(*id) == name,
+    })
+  }
+}
+
+/// Lists, dumps and edits the entities of a scene, for debugging live
+/// sessions without the editor.
+pub struct SceneInspector;
+
+impl SceneInspector {
+  /// Lists every entity by path, with the names of its components.
+  pub fn list_entities(scene: &Scene) -> String {
+    let mut output = String::new();
+
+    for (id, entity) in scene.entities() {
+      let components = entity
+        .components()
+        .map(|component| component.component_name())
+        .collect::<Vec<_>>();
+
+      let path = scene.path(id);
+      let label = match path.starts_with('#') {
+        true => path,
+        false => format!("#{} {path}", id.ordinal()),
+      };
+
+      writeln!(output, "{label} ({})", components.join(", ")).unwrap();
+    }
+
+    output.trim_end().to_string()
+  }
+
+  /// Dumps every component of an entity, with their fields.
+  pub fn dump_entity(scene: &Scene, name: &str) -> Result<String, InspectError> {
+    let id = scene
+      .find(name)
+      .ok_or_else(|| InspectError::UnknownEntity(name.to_string()))?;
+    let mut output = String::new();
+
+    for component in scene.entity(id).unwrap().components() {
+      writeln!(output, "{}", component.component_name()).unwrap();
+
+      match component.inspect() {
+        Some(fields) => write_chunk(&mut output, &fields, 1),
+        None => writeln!(output, "  (not inspectable)").unwrap(),
+      }
+    }
+
+    Ok(output.trim_end().to_string())
+  }
+
+  /// Reads a field by path, like `player.Health.current`.
+  pub fn get(scene: &Scene, path: &str) -> Result<String, InspectError> {
+    let (entity, component, field) = split_path(path)?;
+    let id = scene
+      .find(entity)
+      .ok_or_else(|| InspectError::UnknownEntity(entity.to_string()))?;
+    let component = find_component(scene.entity(id).unwrap(), component)?;
+    let fields = component
+      .inspect()
+      .ok_or_else(|| InspectError::NotInspectable(component.component_name().to_string()))?;
+
+    let unknown = || InspectError::UnknownField(path.to_string());
+
+    match field_at(&fields, &field).ok_or_else(unknown)? {
+      (Chunk::Variant(variant), [swizzle]) => {
+        let value = swizzle_index(variant, swizzle).and_then(|index| vector_components(variant)?.get(index).copied());
+
+        Ok(value.ok_or_else(unknown)?.to_string())
+      }
+      (chunk, []) => {
+        let mut output = String::new();
+
+        write_chunk(&mut output, chunk, 0);
+
+        Ok(output.trim_end().to_string())
+      }
+      _ => Err(unknown()),
+    }
+  }
+
+  /// Sets a field by path, like `player.Transform.position.x`, parsing the
+  /// value to match the field's current type.
+  pub fn set(scene: &mut Scene, path: &str, value: &str) -> Result<(), InspectError> {
+    let (entity, component, field) = split_path(path)?;
+    let id = scene
+      .find(entity)
+      .ok_or_else(|| InspectError::UnknownEntity(entity.to_string()))?;
+    let entity = scene.entities.get_mut(id).unwrap();
+    let index = entity
+      .components
+      .iter()
+      .position(|it| it.component_name() == component)
+      .ok_or_else(|| InspectError::UnknownComponent(component.to_string()))?;
+
+    let component = &mut entity.components[index];
+    let mut fields = component
+      .inspect()
+      .ok_or_else(|| InspectError::NotInspectable(component.component_name().to_string()))?;
+
+    // asset references are replaced whole, by ID
+    let asset = field_at(&fields, &field).and_then(|(chunk, rest)| asset_field(chunk).filter(|_| rest.is_empty()));
+
+    if let Some(asset) = asset {
+      let id = value
+        .parse()
+        .map_err(|_| InspectError::InvalidValue(value.to_string()))?;
+
+      *chunk_at_mut(&mut fields, &field).unwrap() = AssetRefField { id, ..asset }.to_chunk();
+
+      return component
+        .apply_inspected(&fields)
+        .map_err(|_| InspectError::InvalidValue(value.to_string()));
+    }
+
+    let (variant, rest) =
+      field_at_mut(&mut fields, &field).ok_or_else(|| InspectError::UnknownField(path.to_string()))?;
+
+    *variant = match rest {
+      [] => parse_value(variant, value)?,
+      [swizzle] => {
+        let index = swizzle_index(variant, swizzle).ok_or_else(|| InspectError::UnknownField(path.to_string()))?;
+        let value = value
+          .parse::<f32>()
+          .map_err(|_| InspectError::InvalidValue(value.to_string()))?;
+
+        let mut components = vector_components(variant).ok_or_else(|| InspectError::UnknownField(path.to_string()))?;
+
+        components[index] = value;
+
+        vector_from_components(variant, &components)
+      }
+      _ => return Err(InspectError::UnknownField(path.to_string())),
+    };
+
+    component
+      .apply_inspected(&fields)
+      .map_err(|_| InspectError::InvalidValue(value.to_string()))
+  }
+
+  /// Lists the asset reference fields of an entity's components by path, like
+  /// `Sprite.texture`, for editors to offer asset pickers for.
+  pub fn asset_fields(scene: &Scene, name: &str) -> Result<Vec<(String, AssetRefField)>, InspectError> {
+    let id = scene
+      .find(name)
+      .ok_or_else(|| InspectError::UnknownEntity(name.to_string()))?;
+
+    let mut results = Vec::new();
+
+    for component in scene.entity(id).unwrap().components() {
+      if let Some(fields) = component.inspect() {
+        collect_asset_fields(&mut results, component.component_name(), &fields);
+      }
+    }
+
+    Ok(results)
+  }
+
+  /// Registers the `entities`, `inspect`, `get` and `set` commands with the
+  /// given console, working on a shared scene.
+  pub fn register_commands(scene: Rc<RefCell<Scene>>, console: &mut DeveloperConsole) {
+    console.register("entities", "lists every entity and its components", {
+      let scene = scene.clone();
+
+      move |_| Ok(Self::list_entities(&scene.borrow()))
+    });
+
+    console.register("inspect", "dumps the components of an entity: inspect <entity>", {
+      let scene = scene.clone();
+
+      move |arguments| match arguments {
+        [name] => Ok(Self::dump_entity(&scene.borrow(), name)?),
+        _ => Err(ConsoleError::InvalidArguments("usage: inspect <entity>".to_string())),
+      }
+    });
+
+    console.register("get", "reads a field: get <entity>.<component>.<field>", {
+      let scene = scene.clone();
+
+      move |arguments| match arguments {
+        [path] => Ok(Self::get(&scene.borrow(), path)?),
+        _ => Err(ConsoleError::InvalidArguments("usage: get <path>".to_string())),
+      }
+    });
+
+    console.register("set", "sets a field: set <entity>.<component>.<field> <value>", {
+      move |arguments| match arguments {
+        [path, value] => {
+          Self::set(&mut scene.borrow_mut(), path, value)?;
+          Ok(format!("{path} = {value}"))
+        }
+        _ => Err(ConsoleError::InvalidArguments("usage: set <path> <value>".to_string())),
+      }
+    });
+  }
+}
+
+/// Splits a field path into its entity, component and field path.
+fn split_path(path: &str) -> Result<(&str, &str, Vec<&str>), InspectError> {
+  let mut segments = path.split('.');
+
+  match (segments.next(), segments.next()) {
+    (Some(entity), Some(component)) => Ok((entity, component, segments.collect())),
+    _ => Err(InspectError::UnknownField(path.to_string())),
+  }
+}
+
+/// Finds a component of an entity by its name.
+fn find_component<'a>(entity: &'a Entity, name: &str) -> Result<&'a dyn Component, InspectError> {
+  entity
+    .components()
+    .find(|component| component.component_name() == name)
+    .ok_or_else(|| InspectError::UnknownComponent(name.to_string()))
+}
+
+/// Walks a field path through maps and sequences, returning the chunk it
+/// ends at, and any path left over once it reaches a value.
+fn field_at<'a, 'p>(chunk: &'a Chunk, path: &'p [&'p str]) -> Option<(&'a Chunk, &'p [&'p str])> {
+  let Some((segment, rest)) = path.split_first() else {
+    return Some((chunk, path));
+  };
+
+  match chunk {
+    Chunk::Map(fields) => field_at(fields.get(*segment)?, rest),
+    Chunk::Sequence(items) => field_at(items.get(segment.parse::<usize>().ok()?)?, rest),
+    Chunk::Variant(_) => Some((chunk, path)),
+  }
+}
+
+/// Walks a field path to a value, returning it and any path left over.
+fn field_at_mut<'a, 'p>(chunk: &'a mut Chunk, path: &'p [&'p str]) -> Option<(&'a mut Variant, &'p [&'p str])> {
+  match chunk {
+    Chunk::Variant(variant) => Some((variant, path)),
+    Chunk::Map(fields) => {
+      let (segment, rest) = path.split_first()?;
+
+      field_at_mut(fields.get_mut(*segment)?, rest)
+    }
+    Chunk::Sequence(items) => {
+      let (segment, rest) = path.split_first()?;
+
+      field_at_mut(items.get_mut(segment.parse::<usize>().ok()?)?, rest)
+    }
+  }
+}
+
+/// Walks a field path to the chunk it ends at.
+fn chunk_at_mut<'a>(chunk: &'a mut Chunk, path: &[&str]) -> Option<&'a mut Chunk> {
+  let Some((segment, rest)) = path.split_first() else {
+    return Some(chunk);
+  };
+
+  match chunk {
+    Chunk::Map(fields) => chunk_at_mut(fields.get_mut(*segment)?, rest),
+    Chunk::Sequence(items) => chunk_at_mut(items.get_mut(segment.parse::<usize>().ok()?)?, rest),
+    Chunk::Variant(_) => None,
+  }
+}
+
+/// The asset reference held in a chunk, if it's one.
+fn asset_field(chunk: &Chunk) -> Option<AssetRefField> {
+  match chunk {
+    Chunk::Map(_) => AssetRefField::from_chunk(chunk),
+    _ => None,
+  }
+}
+
+/// Collects the asset reference fields under a chunk, sorted by path.
+fn collect_asset_fields(results: &mut Vec<(String, AssetRefField)>, path: &str, chunk: &Chunk) {
+  if let Some(asset) = asset_field(chunk) {
+    return results.push((path.to_string(), asset));
+  }
+
+  match chunk {
+    Chunk::Map(fields) => {
+      let mut fields = fields.iter().collect::<Vec<_>>();
+
+      fields.sort_by_key(|(name, _)| name.as_str());
+
+      for (name, value) in fields {
+        collect_asset_fields(results, &format!("{path}.{name}"), value);
+      }
+    }
+    Chunk::Sequence(items) => {
+      for (index, item) in items.iter().enumerate() {
+        collect_asset_fields(results, &format!("{path}.{index}"), item);
+      }
+    }
+    Chunk::Variant(_) => {}
+  }
+}
+
+/// Formats an asset reference as its type and ID, like `Texture(key:player)`.
+fn format_asset(asset: &AssetRefField) -> String {
+  match &asset.id {
+    AssetId::None => format!("{}(none)", asset.asset_type),
+    id => format!("{}({id})", asset.asset_type),
+  }
+}
+
+/// The index of a named component of a vector or color, like `x` or `a`.
+fn swizzle_index(variant: &Variant, name: &str) -> Option<usize> {
+  let names = match variant {
+    Variant::Color(_) | Variant::Color32(_) => ["r", "g", "b", "a"],
+    _ => ["x", "y", "z", "w"],
+  };
+
+  let index = names.iter().position(|it| *it == name)?;
+
+  (index < vector_components(variant)?.len()).then_some(index)
+}
+
+/// The components of a vector value.
+fn vector_components(variant: &Variant) -> Option<Vec<f32>> {
+  match variant {
+    Variant::Vec2(value) => Some(value.to_array().to_vec()),
+    Variant::Vec3(value) => Some(value.to_array().to_vec()),
+    Variant::Vec4(value) => Some(value.to_array().to_vec()),
+    Variant::Quat(value) => Some(value.to_array().to_vec()),
+    Variant::Color(value) => Some(vec![value.r, value.g, value.b, value.a]),
+    _ => None,
+  }
+}
+
+/// Rebuilds a vector value of the same kind from its components.
+fn vector_from_components(variant: &Variant, components: &[f32]) -> Variant {
+  match variant {
+    Variant::Vec2(_) => Variant::Vec2(Vec2::from_slice(components)),
+    Variant::Vec3(_) => Variant::Vec3(Vec3::from_slice(components)),
+    Variant::Vec4(_) => Variant::Vec4(Vec4::from_slice(components)),
+    Variant::Quat(_) => Variant::Quat(common::Quat::from_slice(components)),
+    Variant::Color(_) => Variant::Color(common::Color::rgba(
+      components[0],
+      components[1],
+      components[2],
+      components[3],
+    )),
+    other => other.clone(),
+  }
+}
+
+/// Parses text into a value of the same kind as the given one.
+fn parse_value(current: &Variant, text: &str) -> Result<Variant, InspectError> {
+  let invalid = || InspectError::InvalidValue(text.to_string());
+
+  macro_rules! parse {
+    ($kind:ident) => {
+      Variant::$kind(text.parse().map_err(|_| invalid())?)
+    };
+  }
+
+  Ok(match current {
+    Variant::Bool(_) => parse!(Bool),
+    Variant::Char(_) => parse!(Char),
+    Variant::U8(_) => parse!(U8),
+    Variant::U16(_) => parse!(U16),
+    Variant::U32(_) => parse!(U32),
+    Variant::U64(_) => parse!(U64),
+    Variant::I8(_) => parse!(I8),
+    Variant::I16(_) => parse!(I16),
+    Variant::I32(_) => parse!(I32),
+    Variant::I64(_) => parse!(I64),
+    Variant::F32(_) => parse!(F32),
+    Variant::F64(_) => parse!(F64),
+    Variant::String(_) => Variant::String(text.to_string()),
+    Variant::StringName(_) => Variant::StringName(text.into()),
+    variant if vector_components(variant).is_some() => {
+      let components = text
+        .split(',')
+        .map(|it| it.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid())?;
+
+      if components.len() != vector_components(variant).unwrap().len() {
+        return Err(invalid());
+      }
+
+      vector_from_components(variant, &components)
+    }
+    _ => return Err(invalid()),
+  })
+}
+
+/// Formats a value for display.
+fn format_variant(variant: &Variant) -> String {
+  match variant {
+    Variant::Null => "null".to_string(),
+    Variant::Bool(value) => value.to_string(),
+    Variant::Char(value) => format!("{value:?}"),
+    Variant::U8(value) => value.to_string(),
+    Variant::U16(value) => value.to_string(),
+    Variant::U32(value) => value.to_string(),
+    Variant::U64(value) => value.to_string(),
+    Variant::I8(value) => value.to_string(),
+    Variant::I16(value) => value.to_string(),
+    Variant::I32(value) => value.to_string(),
+    Variant::I64(value) => value.to_string(),
+    Variant::F32(value) => value.to_string(),
+    Variant::F64(value) => value.to_string(),
+    Variant::String(value) => format!("{value:?}"),
+    Variant::StringName(value) => format!("{:?}", value.to_string()),
+    variant => match vector_components(variant) {
+      Some(components) => format!(
+        "({})",
+        components.iter().map(f32::to_string).collect::<Vec<_>>().join(", ")
+      ),
+      None => format!("<{:?}>", variant.kind()),
+    },
+  }
+}
+
+/// Writes a chunk as indented text, with map fields sorted by name.
+fn write_chunk(output: &mut String, chunk: &Chunk, depth: usize) {
+  let indent = "  ".repeat(depth);
+
+  if let Some(asset) = asset_field(chunk) {
+    return writeln!(output, "{indent}{}", format_asset(&asset)).unwrap();
+  }
+
+  match chunk {
+    Chunk::Variant(variant) => writeln!(output, "{indent}{}", format_variant(variant)).unwrap(),
+    Chunk::Map(fields) => {
+      let mut fields = fields.iter().collect::<Vec<_>>();
+
+      fields.sort_by_key(|(name, _)| name.as_str());
+
+      for (name, value) in fields {
+        if let Some(asset) = asset_field(value) {
+          writeln!(output, "{indent}{name}: {}", format_asset(&asset)).unwrap();
+          continue;
+        }
+
+        match value {
+          Chunk::Variant(variant) => writeln!(output, "{indent}{name}: {}", format_variant(variant)).unwrap(),
+          _ => {
+            writeln!(output, "{indent}{name}:").unwrap();
+            write_chunk(output, value, depth + 1);
+          }
+        }
+      }
+    }
+    Chunk::Sequence(items) => {
+      for (index, item) in items.iter().enumerate() {
+        match item {
+          Chunk::Variant(variant) => writeln!(output, "{indent}{index}: {}", format_variant(variant)).unwrap(),
+          _ => {
+            writeln!(output, "{indent}{index}:").unwrap();
+            write_chunk(output, item, depth + 1);
+          }
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{vec2, AssetRef, FastHashMap, Health, StreamError};
+
+  use super::*;
+
+  struct Transform {
+    position: Vec2,
+    layer: i64,
+  }
+
+  impl Component for Transform {
+    fn component_name(&self) -> &'static str {
+      "Transform"
+    }
+
+    fn inspect(&self) -> Option<Chunk> {
+      let mut fields = FastHashMap::default();
+
+      fields.insert("position".to_string(), Chunk::Variant(Variant::Vec2(self.position)));
+      fields.insert("layer".to_string(), Chunk::Variant(Variant::I64(self.layer)));
+
+      Some(Chunk::Map(fields))
+    }
+
+    fn apply_inspected(&mut self, fields: &Chunk) -> Result<(), StreamError> {
+      self.position = fields.read_field("position")?;
+      self.layer = fields.read_field("layer")?;
+
+      Ok(())
+    }
+  }
+
+  struct Texture;
+
+  struct Sprite {
+    texture: AssetRef<Texture>,
+  }
+
+  impl Component for Sprite {
+    fn component_name(&self) -> &'static str {
+      "Sprite"
+    }
+
+    fn inspect(&self) -> Option<Chunk> {
+      let mut fields = FastHashMap::default();
+
+      fields.insert("texture".to_string(), self.texture.to_chunk());
+
+      Some(Chunk::Map(fields))
+    }
+
+    fn apply_inspected(&mut self, fields: &Chunk) -> Result<(), StreamError> {
+      self.texture = AssetRef::from_chunk(fields.get("texture").ok_or(StreamError::InvalidData)?)?;
+
+      Ok(())
+    }
+  }
+
+  fn create_scene() -> Scene {
+    let mut scene = Scene::new();
+    let player = scene.spawn_named("player");
+
+    scene.add_component(player, Transform {
+      position: vec2(1., 2.),
+      layer: 3,
+    });
+    scene.add_component(player, Health::new(100.));
+    scene.spawn();
+
+    scene
+  }
+
+  #[test]
+  fn it_should_list_and_dump_entities() {
+    let scene = create_scene();
+
+    assert_eq!(
+      SceneInspector::list_entities(&scene),
+      "#0 player (Transform, Health)\n#1 ()"
+    );
+    assert_eq!(
+      SceneInspector::dump_entity(&scene, "player").unwrap(),
+      "Transform\n  layer: 3\n  position: (1, 2)\nHealth\n  current: 100\n  invulnerability: 0\n  max: 100"
+    );
+    assert_eq!(SceneInspector::get(&scene, "#0.Transform.position.y").unwrap(), "2");
+    assert_eq!(
+      SceneInspector::dump_entity(&scene, "enemy"),
+      Err(InspectError::UnknownEntity("enemy".to_string()))
+    );
+  }
+
+  #[test]
+  fn it_should_set_fields_by_path() {
+    let mut scene = create_scene();
+
+    SceneInspector::set(&mut scene, "player.Transform.position.x", "10").unwrap();
+    SceneInspector::set(&mut scene, "player.Transform.layer", "7").unwrap();
+    SceneInspector::set(&mut scene, "player.Health.current", "25").unwrap();
+
+    let player = scene.entity(scene.find("player").unwrap()).unwrap();
+
+    assert_eq!(player.get_component::<Transform>().unwrap().position, vec2(10., 2.));
+    assert_eq!(player.get_component::<Transform>().unwrap().layer, 7);
+    assert_eq!(player.get_component::<Health>().unwrap().current(), 25.);
+
+    assert_eq!(
+      SceneInspector::set(&mut scene, "player.Transform.layer", "seven"),
+      Err(InspectError::InvalidValue("seven".to_string()))
+    );
+    assert_eq!(
+      SceneInspector::set(&mut scene, "player.Transform.scale", "2"),
+      Err(InspectError::UnknownField("player.Transform.scale".to_string()))
+    );
+  }
+
+  #[test]
+  fn it_should_show_and_set_asset_fields() {
+    let mut scene = create_scene();
+    let player = scene.find("player").unwrap();
+
+    scene.add_component(player, Sprite {
+      texture: AssetRef::from_key("player"),
+    });
+
+    assert_eq!(
+      SceneInspector::get(&scene, "player.Sprite.texture").unwrap(),
+      "Texture(key:player)"
+    );
+    assert!(SceneInspector::dump_entity(&scene, "player")
+      .unwrap()
+      .ends_with("Sprite\n  texture: Texture(key:player)"));
+
+    let fields = SceneInspector::asset_fields(&scene, "player").unwrap();
+
+    assert_eq!(fields.len(), 1);
+    assert_eq!(fields[0].0, "Sprite.texture");
+    assert_eq!(fields[0].1.asset_type, "Texture");
+
+    SceneInspector::set(&mut scene, "player.Sprite.texture", "local://sprites/enemy.png").unwrap();
+
+    let sprite = scene.entity(player).unwrap().get_component::<Sprite>().unwrap();
+
+    assert_eq!(sprite.texture, AssetRef::from_path("local://sprites/enemy.png"));
+    assert_eq!(
+      SceneInspector::set(&mut scene, "player.Sprite.texture", "enemy"),
+      Err(InspectError::InvalidValue("enemy".to_string()))
+    );
+  }
+
+  #[test]
+  fn it_should_inspect_from_the_console() {
+    let scene = Rc::new(RefCell::new(create_scene()));
+    let mut console = DeveloperConsole::new();
+
+    SceneInspector::register_commands(scene.clone(), &mut console);
+
+    assert_eq!(
+      console.execute("set player.Transform.position.x 10").unwrap(),
+      "player.Transform.position.x = 10"
+    );
+    assert_eq!(console.execute("get player.Transform.position.x").unwrap(), "10");
+    assert!(console.execute("inspect ghost").is_err());
+  }
+}
@@ -2,30 +2,175 @@
 
 use std::any::Any;
 
+pub use benchmarks::*;
 pub use canvas::*;
+pub use followers::*;
+pub use inspector::*;
+pub use manager::*;
+pub use requirements::*;
+pub use schedule::*;
+pub use snapshots::*;
 pub use spatial::*;
+pub use templates::*;
+pub use validation::*;
+pub use world::*;
 
+mod benchmarks;
+mod bounds;
 mod canvas;
+mod combat;
+mod determinism;
+mod followers;
+mod inspector;
+mod manager;
+mod requirements;
+mod schedule;
+mod snapshots;
 mod spatial;
+mod templates;
+mod validation;
+mod world;
 
-use common::{impl_arena_index, Arena};
+use bounds::BoundsCache;
+use common::{
+  impl_arena_index, Arena, ArenaIndex, Bounds, Chunk, LayerMask, Mat4, StateHasher, StreamError, StringName, Tags,
+};
+pub use macros::Component;
 
-impl_arena_index!(EntityId);
+impl_arena_index!(pub EntityId, "Identifies an entity in a [`Scene`].");
 
+/// A hierarchy of named entities with boxed, inspectable components.
+///
+/// The scene is the authoritative store for authored content: what's saved,
+/// instantiated from templates, edited in the inspector and validated. Bulk
+/// plain-data simulation belongs in a [`World`] instead; the two don't share
+/// entities, see the [`World`] docs.
 pub struct Scene {
   entities: Arena<EntityId, Entity>,
+  bounds_cache: BoundsCache,
 }
 
 impl Scene {
   pub fn new() -> Self {
-    Self { entities: Arena::new() }
+    Self {
+      entities: Arena::new(),
+      bounds_cache: BoundsCache::default(),
+    }
   }
 
   pub fn spawn(&mut self) -> EntityId {
-    self.entities.insert(Entity { components: Vec::new() })
+    self.entities.insert(Entity {
+      name: None,
+      parent: None,
+      transform: Mat4::IDENTITY,
+      layers: LayerMask::DEFAULT,
+      tags: Tags::new(),
+      components: Vec::new(),
+    })
+  }
+
+  /// Spawns a new entity with the given name, for use in paths.
+  pub fn spawn_named(&mut self, name: impl Into<String>) -> EntityId {
+    let id = self.spawn();
+
+    if let Some(entity) = self.entities.get_mut(id) {
+      entity.name = Some(name.into());
+    }
+
+    id
+  }
+
+  /// Makes one entity the child of another.
+  pub fn set_parent(&mut self, id: EntityId, parent: Option<EntityId>) {
+    self.invalidate_bounds(id);
+
+    if let Some(entity) = self.entities.get_mut(id) {
+      entity.parent = parent;
+    }
+
+    self.invalidate_bounds(id);
+  }
+
+  /// Gets the entity with the given ID.
+  pub fn entity(&self, id: EntityId) -> Option<&Entity> {
+    self.entities.get(id)
+  }
+
+  /// Iterates over all entities in the scene.
+  pub fn entities(&self) -> impl Iterator<Item = (EntityId, &Entity)> {
+    self.entities.enumerate()
+  }
+
+  /// Builds the path to an entity from the names of its ancestors, like
+  /// `level/enemies/goblin`.
+  ///
+  /// Unnamed entities appear by their index, like `#3`.
+  pub fn path(&self, id: EntityId) -> String {
+    let mut segments = Vec::new();
+    let mut current = Some(id);
+
+    while let Some(id) = current {
+      let Some(entity) = self.entities.get(id) else {
+        break;
+      };
+
+      segments.push(match &entity.name {
+        Some(name) => name.clone(),
+        None => format!("#{}", id.ordinal()),
+      });
+
+      current = entity.parent;
+    }
+
+    segments.reverse();
+    segments.join("/")
+  }
+
+  /// Puts an entity on the given layers, replacing its current ones.
+  pub fn set_layers(&mut self, id: EntityId, layers: LayerMask) {
+    if let Some(entity) = self.entities.get_mut(id) {
+      entity.layers = layers;
+    }
+  }
+
+  /// Tags an entity, for finding it with [`Scene::find_tagged`].
+  pub fn add_tag(&mut self, id: EntityId, tag: impl Into<StringName>) {
+    if let Some(entity) = self.entities.get_mut(id) {
+      entity.tags.insert(tag);
+    }
+  }
+
+  /// Removes a tag from an entity.
+  pub fn remove_tag(&mut self, id: EntityId, tag: impl Into<StringName>) {
+    if let Some(entity) = self.entities.get_mut(id) {
+      entity.tags.remove(tag);
+    }
+  }
+
+  /// Finds the entities with the given tag.
+  pub fn find_tagged(&self, tag: impl Into<StringName>) -> Vec<EntityId> {
+    let tag = tag.into();
+
+    self
+      .entities
+      .enumerate()
+      .filter(|(_, entity)| entity.tags.contains(tag))
+      .map(|(id, _)| id)
+      .collect()
+  }
+
+  /// Finds the entities on any of the layers in the mask.
+  pub fn find_on_layers(&self, mask: LayerMask) -> Vec<EntityId> {
+    self
+      .entities
+      .enumerate()
+      .filter(|(_, entity)| entity.layers.intersects(mask))
+      .map(|(id, _)| id)
+      .collect()
   }
 
   pub fn despawn(&mut self, id: EntityId) {
+    self.invalidate_bounds(id);
     self.entities.remove(id);
   }
 
@@ -33,6 +178,9 @@ impl Scene {
     if let Some(entity) = self.entities.get_mut(id) {
       entity.components.push(Box::new(component));
     }
+
+    self.insert_required_components(id);
+    self.invalidate_bounds(id);
   }
 
   pub fn emit<E>(&mut self, event: &mut E) {
@@ -51,13 +199,95 @@ impl Scene {
 }
 
 pub struct Entity {
+  name: Option<String>,
+  parent: Option<EntityId>,
+  transform: Mat4,
+  layers: LayerMask,
+  tags: Tags,
   components: Vec<Box<dyn Component>>,
 }
 
+impl Entity {
+  /// The name of the entity, if it has one.
+  pub fn name(&self) -> Option<&str> {
+    self.name.as_deref()
+  }
+
+  /// The parent of the entity, if it has one.
+  pub fn parent(&self) -> Option<EntityId> {
+    self.parent
+  }
+
+  /// The layers the entity is on.
+  pub fn layers(&self) -> LayerMask {
+    self.layers
+  }
+
+  /// The entity's tags.
+  pub fn tags(&self) -> &Tags {
+    &self.tags
+  }
+
+  /// Gets the first component of the given type.
+  pub fn get_component<C: Component>(&self) -> Option<&C> {
+    self
+      .components
+      .iter()
+      .find_map(|component| (component.as_ref() as &dyn Any).downcast_ref::<C>())
+  }
+
+  /// Determines if the entity has a component of the given type.
+  pub fn has_component<C: Component>(&self) -> bool {
+    self.get_component::<C>().is_some()
+  }
+
+  /// Iterates over all components of the entity.
+  pub fn components(&self) -> impl Iterator<Item = &dyn Component> {
+    self.components.iter().map(|component| component.as_ref())
+  }
+}
+
 #[allow(unused_variables)]
-pub trait Component {
+pub trait Component: Any {
   fn on_attach(&self, node: &Entity) {}
   fn on_detach(&self, node: &Entity) {}
+
+  /// Checks the component for mistakes; see [`SceneValidator`].
+  fn validate(&self, context: &mut ValidationContext) {}
+
+  /// The components this component can't work without; see
+  /// [`ComponentRequirement`].
+  fn requires(&self) -> &'static [ComponentRequirement] {
+    &[]
+  }
+
+  /// The name of the component's type, for diagnostics.
+  fn component_name(&self) -> &'static str {
+    short_type_name::<Self>()
+  }
+
+  /// The component's bounds in the entity's local space, for culling and
+  /// picking; see [`Scene::bounds`].
+  fn local_bounds(&self) -> Option<Bounds> {
+    None
+  }
+
+  /// Hashes the component's simulation state; see [`Scene::record_checksums`].
+  ///
+  /// Components that don't affect the simulation can leave this empty.
+  fn checksum(&self, hasher: &mut StateHasher) {}
+
+  /// The component's fields, for [`SceneInspector`]; components that can't
+  /// be inspected return `None`.
+  fn inspect(&self) -> Option<Chunk> {
+    None
+  }
+
+  /// Applies fields edited through the [`SceneInspector`], in the shape
+  /// returned by [`Component::inspect`].
+  fn apply_inspected(&mut self, fields: &Chunk) -> Result<(), StreamError> {
+    Err(StreamError::InvalidData)
+  }
 }
 
 pub trait EventListener<E> {
@@ -90,4 +320,26 @@ mod tests {
 
     scene.emit(&mut Tick);
   }
+
+  #[test]
+  fn it_should_find_entities_by_layer_and_tag() {
+    let mut scene = Scene::new();
+    let enemies = LayerMask::layer(2);
+
+    let goblin = scene.spawn_named("goblin");
+    let chest = scene.spawn_named("chest");
+
+    scene.set_layers(goblin, enemies);
+    scene.add_tag(goblin, "hostile");
+    scene.add_tag(chest, "loot");
+
+    assert_eq!(scene.find_on_layers(enemies), vec![goblin]);
+    assert_eq!(scene.find_on_layers(LayerMask::DEFAULT), vec![chest]);
+    assert_eq!(scene.find_tagged("hostile"), vec![goblin]);
+    assert!(scene.entity(chest).unwrap().tags().contains("loot"));
+
+    scene.remove_tag(goblin, "hostile");
+
+    assert!(scene.find_tagged("hostile").is_empty());
+  }
 }
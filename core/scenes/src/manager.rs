@@ -0,0 +1,136 @@
+//! Switching between scenes, optionally through a fullscreen transition.
+//!
+//! ```rust,ignore
+//! let mut scenes = SceneManager::new(title_screen);
+//!
+//! scenes.switch_to(level1, Some(Transition::fade(1.)));
+//!
+//! loop {
+//!   scenes.update(time.delta_time);
+//!
+//!   render(scenes.current());
+//! }
+//! ```
+//!
+//! The new scene replaces the current one once the transition has covered
+//! the screen, so the switch is never seen.
+
+use common::{Transition, TransitionEvent};
+
+use super::*;
+
+/// Owns the current scene and switches to others.
+pub struct SceneManager {
+  current: Scene,
+  pending: Option<Scene>,
+  transition: Option<Transition>,
+}
+
+impl SceneManager {
+  /// Creates a manager starting with the given scene.
+  pub fn new(scene: Scene) -> Self {
+    Self {
+      current: scene,
+      pending: None,
+      transition: None,
+    }
+  }
+
+  /// The scene currently being played.
+  pub fn current(&self) -> &Scene {
+    &self.current
+  }
+
+  /// The scene currently being played, mutably.
+  pub fn current_mut(&mut self) -> &mut Scene {
+    &mut self.current
+  }
+
+  /// The transition being played, if any, for the renderer to draw.
+  pub fn transition(&self) -> Option<&Transition> {
+    self.transition.as_ref()
+  }
+
+  /// Determines if a transition is being played.
+  pub fn is_transitioning(&self) -> bool {
+    self.transition.is_some()
+  }
+
+  /// Switches to another scene, right away or once the transition has
+  /// covered the screen.
+  ///
+  /// Replaces any switch still waiting for its transition to cover the
+  /// screen.
+  pub fn switch_to(&mut self, scene: Scene, transition: Option<Transition>) {
+    match transition {
+      Some(transition) => {
+        self.pending = Some(scene);
+        self.transition = Some(transition);
+      }
+      None => {
+        self.pending = None;
+        self.transition = None;
+        self.current = scene;
+      }
+    }
+  }
+
+  /// Advances the transition, switching scenes once the screen is covered.
+  pub fn update(&mut self, delta_time: f32) {
+    let Some(transition) = &mut self.transition else {
+      return;
+    };
+
+    match transition.update(delta_time) {
+      Some(TransitionEvent::Covered) => {
+        if let Some(scene) = self.pending.take() {
+          self.current = scene;
+        }
+      }
+      Some(TransitionEvent::Finished) => {
+        self.transition = None;
+      }
+      None => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_switch_once_the_screen_is_covered() {
+    let mut level = Scene::new();
+    let player = level.spawn_named("player");
+
+    let mut scenes = SceneManager::new(Scene::new());
+
+    scenes.switch_to(level, Some(Transition::fade(1.)));
+    scenes.update(0.25);
+
+    assert!(scenes.is_transitioning());
+    assert!(scenes.current().entity(player).is_none());
+
+    scenes.update(0.25);
+
+    assert_eq!(scenes.current().entity(player).unwrap().name(), Some("player"));
+
+    scenes.update(0.5);
+
+    assert!(!scenes.is_transitioning());
+  }
+
+  #[test]
+  fn it_should_switch_right_away_without_a_transition() {
+    let mut level = Scene::new();
+    let player = level.spawn();
+
+    let mut scenes = SceneManager::new(Scene::new());
+
+    scenes.switch_to(level, None);
+
+    assert!(!scenes.is_transitioning());
+    assert!(scenes.current().entity(player).is_some());
+  }
+}
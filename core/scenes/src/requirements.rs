@@ -0,0 +1,198 @@
+//! Components that depend on other components.
+//!
+//! A component declares the companions it can't work without, and
+//! [`Scene::add_component`] adds defaults for any that are missing, so
+//! systems can rely on them being there:
+//!
+//! ```rust,ignore
+//! #[derive(Component)]
+//! #[component(requires(Transform, Visibility))]
+//! struct Sprite {
+//!   texture: String,
+//! }
+//!
+//! scene.add_component(entity, Sprite::default());
+//!
+//! let transform = scene.entity(entity).unwrap().require_component::<Transform>();
+//! ```
+//!
+//! Components that implement [`Component`] by hand declare their
+//! requirements in [`Component::requires`]; the [`SceneValidator`] reports
+//! any that are missing from entities built some other way.
+
+use std::any::TypeId;
+
+use super::*;
+
+/// A component that another component can't work without.
+#[derive(Copy, Clone)]
+pub struct ComponentRequirement {
+  type_id: fn() -> TypeId,
+  type_name: fn() -> &'static str,
+  create: fn() -> Box<dyn Component>,
+}
+
+impl ComponentRequirement {
+  /// Requires a component of the given type, added with its default value
+  /// when missing.
+  pub const fn of<C: Component + Default>() -> Self {
+    Self {
+      type_id: TypeId::of::<C>,
+      type_name: short_type_name::<C>,
+      create: create_default::<C>,
+    }
+  }
+
+  /// The name of the required component's type.
+  pub fn name(&self) -> &'static str {
+    (self.type_name)()
+  }
+
+  /// Determines if the entity has the required component.
+  pub fn is_met_by(&self, entity: &Entity) -> bool {
+    let type_id = (self.type_id)();
+
+    entity
+      .components
+      .iter()
+      .any(|component| (component.as_ref() as &dyn Any).type_id() == type_id)
+  }
+
+  /// Creates the required component with its default value.
+  pub fn create(&self) -> Box<dyn Component> {
+    (self.create)()
+  }
+}
+
+impl std::fmt::Debug for ComponentRequirement {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    formatter
+      .debug_tuple("ComponentRequirement")
+      .field(&self.name())
+      .finish()
+  }
+}
+
+fn create_default<C: Component + Default>() -> Box<dyn Component> {
+  Box::new(C::default())
+}
+
+impl Entity {
+  /// Gets a component the entity is known to have, like one required by
+  /// another of its components.
+  ///
+  /// # Panics
+  /// Panics if the entity doesn't have the component.
+  pub fn require_component<C: Component>(&self) -> &C {
+    self.get_component::<C>().unwrap_or_else(|| {
+      panic!(
+        "Entity {} is missing a required {}",
+        self.name().unwrap_or("<unnamed>"),
+        short_type_name::<C>()
+      )
+    })
+  }
+
+  /// The requirements of the entity's components that it doesn't meet.
+  pub fn missing_requirements(&self) -> Vec<(&'static str, ComponentRequirement)> {
+    let mut missing = Vec::new();
+
+    for component in &self.components {
+      for requirement in component.requires() {
+        if !requirement.is_met_by(self) {
+          missing.push((component.component_name(), *requirement));
+        }
+      }
+    }
+
+    missing
+  }
+}
+
+impl Scene {
+  /// Adds defaults for any components the entity's components require but
+  /// it doesn't have, including those required by the added defaults.
+  pub(crate) fn insert_required_components(&mut self, id: EntityId) {
+    let Some(entity) = self.entities.get_mut(id) else {
+      return;
+    };
+
+    loop {
+      let missing = entity.missing_requirements();
+
+      if missing.is_empty() {
+        break;
+      }
+
+      for (_, requirement) in missing {
+        // two components may require the same companion
+        if !requirement.is_met_by(entity) {
+          entity.components.push(requirement.create());
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Default)]
+  struct Transform;
+
+  impl Component for Transform {}
+
+  #[derive(Default, Component)]
+  #[component(requires(Transform))]
+  struct Visibility;
+
+  #[derive(Default, Component)]
+  #[component(requires(Transform, Visibility))]
+  struct Sprite;
+
+  #[test]
+  fn it_should_add_required_components() {
+    let mut scene = Scene::new();
+    let entity = scene.spawn();
+
+    scene.add_component(entity, Sprite);
+
+    let entity = scene.entity(entity).unwrap();
+
+    assert_eq!(entity.components().count(), 3);
+    assert!(entity.has_component::<Transform>());
+    assert!(entity.has_component::<Visibility>());
+    assert!(entity.missing_requirements().is_empty());
+  }
+
+  #[test]
+  fn it_should_keep_existing_components() {
+    let mut scene = Scene::new();
+    let entity = scene.spawn();
+
+    scene.add_component(entity, Transform);
+    scene.add_component(entity, Visibility);
+
+    assert_eq!(scene.entity(entity).unwrap().components().count(), 2);
+  }
+
+  #[test]
+  fn it_should_report_missing_requirements() {
+    let mut scene = Scene::new();
+    let entity = scene.spawn_named("player");
+
+    // bypass add_component, like a scene built by hand
+    scene
+      .entities
+      .get_mut(entity)
+      .unwrap()
+      .components
+      .push(Box::new(Visibility));
+
+    let report = SceneValidator::new().validate(&scene);
+
+    assert!(!report.is_ok());
+    assert_eq!(report.issues[0].message, "Visibility requires a Transform");
+  }
+}
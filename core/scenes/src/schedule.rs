@@ -0,0 +1,205 @@
+//! Systems that update a [`World`] each frame.
+//!
+//! A [`Schedule`] runs its systems in the order they were added, and each
+//! system declares the components it reads and writes so the schedule can
+//! point out systems that touch the same data:
+//!
+//! ```rust,ignore
+//! let mut schedule = Schedule::new();
+//!
+//! schedule.add_fn("movement", SystemAccess::new().writes::<Position>().reads::<Velocity>(), |world, delta_time| {
+//!   for (_, (position, velocity)) in world.query::<(&mut Position, &Velocity)>() {
+//!     position.0 += velocity.0 * delta_time;
+//!   }
+//! });
+//!
+//! schedule.run(&mut world, time.delta_time);
+//! ```
+
+use std::any::TypeId;
+
+use super::*;
+
+/// The components a system reads and writes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SystemAccess {
+  pub reads: Vec<TypeId>,
+  pub writes: Vec<TypeId>,
+}
+
+impl SystemAccess {
+  /// Creates an access that touches no components.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Reads components of the given type.
+  pub fn reads<T: 'static>(mut self) -> Self {
+    self.reads.push(TypeId::of::<T>());
+    self
+  }
+
+  /// Writes components of the given type.
+  pub fn writes<T: 'static>(mut self) -> Self {
+    self.writes.push(TypeId::of::<T>());
+    self
+  }
+
+  /// Every component type the system touches.
+  pub fn components(&self) -> impl Iterator<Item = TypeId> + '_ {
+    self.reads.iter().chain(self.writes.iter()).copied()
+  }
+
+  /// Determines if two systems touch the same component and at least one of
+  /// them writes it, so the order they run in matters.
+  pub fn conflicts_with(&self, other: &SystemAccess) -> bool {
+    self.writes.iter().any(|type_id| other.components().any(|it| it == *type_id))
+      || other.writes.iter().any(|type_id| self.reads.contains(type_id))
+  }
+}
+
+/// Something that updates a [`World`] each frame.
+pub trait System {
+  /// The name of the system, for diagnostics.
+  fn name(&self) -> &str;
+
+  /// The components the system reads and writes.
+  fn access(&self) -> SystemAccess;
+
+  /// Updates the world by a frame.
+  fn run(&mut self, world: &mut World, delta_time: f32);
+}
+
+/// A system made from a function.
+struct FnSystem<F> {
+  name: String,
+  access: SystemAccess,
+  function: F,
+}
+
+impl<F: FnMut(&mut World, f32)> System for FnSystem<F> {
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn access(&self) -> SystemAccess {
+    self.access.clone()
+  }
+
+  fn run(&mut self, world: &mut World, delta_time: f32) {
+    (self.function)(world, delta_time);
+  }
+}
+
+/// Runs systems over a [`World`] each frame, in the order they were added.
+#[derive(Default)]
+pub struct Schedule {
+  systems: Vec<Box<dyn System>>,
+}
+
+impl Schedule {
+  /// Creates an empty schedule.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a system to the end of the schedule.
+  pub fn add_system(&mut self, system: impl System + 'static) {
+    self.systems.push(Box::new(system));
+  }
+
+  /// Adds a function as a system to the end of the schedule.
+  pub fn add_fn(
+    &mut self,
+    name: impl Into<String>,
+    access: SystemAccess,
+    function: impl FnMut(&mut World, f32) + 'static,
+  ) {
+    self.add_system(FnSystem {
+      name: name.into(),
+      access,
+      function,
+    });
+  }
+
+  /// Removes the systems with the given name.
+  pub fn remove_system(&mut self, name: &str) {
+    self.systems.retain(|system| system.name() != name);
+  }
+
+  /// The names of the systems, in the order they run.
+  pub fn system_names(&self) -> impl Iterator<Item = &str> {
+    self.systems.iter().map(|system| system.name())
+  }
+
+  /// Pairs of systems whose order matters, because they touch the same
+  /// components and at least one writes them.
+  pub fn conflicts(&self) -> Vec<(&str, &str)> {
+    let mut conflicts = Vec::new();
+
+    for (index, first) in self.systems.iter().enumerate() {
+      for second in &self.systems[index + 1..] {
+        if first.access().conflicts_with(&second.access()) {
+          conflicts.push((first.name(), second.name()));
+        }
+      }
+    }
+
+    conflicts
+  }
+
+  /// Runs every system over the world, in order.
+  pub fn run(&mut self, world: &mut World, delta_time: f32) {
+    for system in &mut self.systems {
+      system.run(world, delta_time);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Clone)]
+  struct Position(f32);
+
+  #[derive(Clone)]
+  struct Velocity(f32);
+
+  #[test]
+  fn it_should_run_systems_in_order() {
+    let mut world = World::new();
+    let entity = world.spawn();
+
+    world.insert(entity, Position(0.));
+    world.insert(entity, Velocity(1.));
+
+    let mut schedule = Schedule::new();
+
+    schedule.add_fn("accelerate", SystemAccess::new().writes::<Velocity>(), |world, _| {
+      for (_, (velocity,)) in world.query::<(&mut Velocity,)>() {
+        velocity.0 *= 2.;
+      }
+    });
+
+    schedule.add_fn(
+      "movement",
+      SystemAccess::new().writes::<Position>().reads::<Velocity>(),
+      |world, delta_time| {
+        for (_, (position, velocity)) in world.query::<(&mut Position, &Velocity)>() {
+          position.0 += velocity.0 * delta_time;
+        }
+      },
+    );
+
+    schedule.add_fn("render", SystemAccess::new().reads::<Position>(), |_, _| {});
+
+    schedule.run(&mut world, 0.5);
+
+    assert_eq!(world.get::<Position>(entity).unwrap().0, 1.);
+    assert_eq!(
+      schedule.conflicts(),
+      vec![("accelerate", "movement"), ("movement", "render")]
+    );
+  }
+}
@@ -0,0 +1,265 @@
+//! Snapshots of a [`Scene`], for putting it back later.
+//!
+//! A scene's components are boxed trait objects that can't be cloned, so
+//! unlike a [`WorldSnapshot`] they're captured through [`Component::inspect`]
+//! and put back through [`Component::apply_inspected`], or rebuilt through a
+//! [`ComponentRegistry`] if they were removed in the meantime. Components are
+//! rebuilt under their [`Component::component_name`], so register them under
+//! that name.
+//!
+//! Components that can't be inspected aren't captured. Restoring leaves them
+//! as they are, and can't bring them back if they were removed.
+
+use common::{Chunk, FastHashMap, FastHashSet};
+
+use super::*;
+
+/// The entities and components of a [`Scene`] at a moment in time.
+#[derive(Clone, Default)]
+pub struct SceneSnapshot {
+  entities: Vec<EntitySnapshot>,
+}
+
+/// An entity in a [`SceneSnapshot`].
+#[derive(Clone)]
+struct EntitySnapshot {
+  id: EntityId,
+  name: Option<String>,
+  parent: Option<EntityId>,
+  transform: Mat4,
+  layers: LayerMask,
+  tags: Tags,
+  components: Vec<ComponentSnapshot>,
+}
+
+/// A component in a [`SceneSnapshot`]; `fields` is `None` if the component
+/// can't be inspected.
+#[derive(Clone)]
+struct ComponentSnapshot {
+  name: &'static str,
+  fields: Option<Chunk>,
+}
+
+impl SceneSnapshot {
+  /// The number of entities in the snapshot.
+  pub fn len(&self) -> usize {
+    self.entities.len()
+  }
+
+  /// Determines if the snapshot has no entities.
+  pub fn is_empty(&self) -> bool {
+    self.entities.is_empty()
+  }
+}
+
+impl Scene {
+  /// Takes a snapshot of every entity and its inspectable components.
+  pub fn snapshot(&self) -> SceneSnapshot {
+    let entities = self
+      .entities
+      .enumerate()
+      .map(|(id, entity)| EntitySnapshot {
+        id,
+        name: entity.name.clone(),
+        parent: entity.parent,
+        transform: entity.transform,
+        layers: entity.layers,
+        tags: entity.tags.clone(),
+        components: entity
+          .components
+          .iter()
+          .map(|component| ComponentSnapshot {
+            name: component.component_name(),
+            fields: component.inspect(),
+          })
+          .collect(),
+      })
+      .collect();
+
+    SceneSnapshot { entities }
+  }
+
+  /// Puts the scene back as it was in the snapshot.
+  ///
+  /// Entities spawned since are despawned, and entities despawned since are
+  /// spawned again; those come back with new ids, which their children are
+  /// re-parented to. Components that fail to rebuild are left out, and the
+  /// first failure is returned once everything else is restored.
+  pub fn restore(&mut self, snapshot: &SceneSnapshot, registry: &ComponentRegistry) -> Result<(), TemplateError> {
+    let kept = snapshot.entities.iter().map(|it| it.id).collect::<FastHashSet<_>>();
+    let spawned = self
+      .entities
+      .enumerate()
+      .map(|(id, _)| id)
+      .filter(|id| !kept.contains(id))
+      .collect::<Vec<_>>();
+
+    for id in spawned {
+      self.entities.remove(id);
+    }
+
+    let mut respawned = FastHashMap::default();
+
+    for entity in &snapshot.entities {
+      if !self.entities.contains(entity.id) {
+        respawned.insert(entity.id, self.spawn());
+      }
+    }
+
+    let remap = |id: EntityId| respawned.get(&id).copied().unwrap_or(id);
+    let mut first_error = None;
+
+    for snapshot in &snapshot.entities {
+      let Some(entity) = self.entities.get_mut(remap(snapshot.id)) else {
+        continue;
+      };
+
+      entity.name = snapshot.name.clone();
+      entity.parent = snapshot.parent.map(remap);
+      entity.transform = snapshot.transform;
+      entity.layers = snapshot.layers;
+      entity.tags = snapshot.tags.clone();
+
+      let mut previous = std::mem::take(&mut entity.components);
+
+      for component in &snapshot.components {
+        // prefer the live component of the same type, rather than rebuilding it
+        let existing = previous
+          .iter()
+          .position(|it| it.component_name() == component.name)
+          .map(|index| previous.remove(index));
+
+        let restored = match (existing, &component.fields) {
+          (Some(mut existing), Some(fields)) => match existing.apply_inspected(fields) {
+            Ok(()) => Ok(existing),
+            Err(_) => registry.create(component.name, fields),
+          },
+          (Some(existing), None) => Ok(existing),
+          (None, Some(fields)) => registry.create(component.name, fields),
+          (None, None) => continue,
+        };
+
+        match restored {
+          Ok(restored) => entity.components.push(restored),
+          Err(error) => {
+            first_error.get_or_insert(error);
+          }
+        }
+      }
+    }
+
+    self.bounds_cache = BoundsCache::default();
+
+    match first_error {
+      Some(error) => Err(error),
+      None => Ok(()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::Variant;
+
+  use super::*;
+
+  struct Health {
+    value: f32,
+  }
+
+  impl Component for Health {
+    fn component_name(&self) -> &'static str {
+      "Health"
+    }
+
+    fn inspect(&self) -> Option<Chunk> {
+      let mut fields = FastHashMap::default();
+
+      fields.insert("value".to_string(), Chunk::Variant(Variant::F32(self.value)));
+
+      Some(Chunk::Map(fields))
+    }
+
+    fn apply_inspected(&mut self, fields: &Chunk) -> Result<(), StreamError> {
+      self.value = fields.read_field("value")?;
+
+      Ok(())
+    }
+  }
+
+  fn create_registry() -> ComponentRegistry {
+    let mut registry = ComponentRegistry::new();
+
+    registry.register("Health", |chunk| {
+      Ok(Health {
+        value: chunk.read_field("value")?,
+      })
+    });
+
+    registry
+  }
+
+  fn health(scene: &Scene, name: &str) -> Option<f32> {
+    let (_, entity) = scene.entities().find(|(_, entity)| entity.name() == Some(name))?;
+
+    entity.get_component::<Health>().map(|it| it.value)
+  }
+
+  #[test]
+  fn it_should_restore_changed_and_spawned_entities() {
+    let mut scene = Scene::new();
+    let player = scene.spawn_named("player");
+
+    scene.add_component(player, Health { value: 10. });
+    scene.add_tag(player, "hero");
+
+    let snapshot = scene.snapshot();
+
+    SceneInspector::set(&mut scene, "player.Health.value", "3").unwrap();
+    scene.remove_tag(player, "hero");
+    scene.spawn_named("bullet");
+
+    scene.restore(&snapshot, &create_registry()).unwrap();
+
+    assert_eq!(scene.entities().count(), 1);
+    assert_eq!(health(&scene, "player"), Some(10.));
+    assert!(scene.entity(player).unwrap().tags().contains("hero"));
+  }
+
+  #[test]
+  fn it_should_respawn_despawned_entities() {
+    let mut scene = Scene::new();
+    let parent = scene.spawn_named("parent");
+    let child = scene.spawn_named("child");
+
+    scene.set_parent(child, Some(parent));
+    scene.add_component(parent, Health { value: 5. });
+
+    let snapshot = scene.snapshot();
+
+    scene.despawn(parent);
+    scene.restore(&snapshot, &create_registry()).unwrap();
+
+    let (respawned, _) = scene.entities().find(|(_, it)| it.name() == Some("parent")).unwrap();
+
+    assert_eq!(health(&scene, "parent"), Some(5.));
+    assert_eq!(scene.entity(child).unwrap().parent(), Some(respawned));
+  }
+
+  #[test]
+  fn it_should_report_components_it_cannot_rebuild() {
+    let mut scene = Scene::new();
+    let player = scene.spawn_named("player");
+
+    scene.add_component(player, Health { value: 10. });
+
+    let snapshot = scene.snapshot();
+
+    scene.despawn(player);
+
+    let result = scene.restore(&snapshot, &ComponentRegistry::new());
+
+    assert!(matches!(result, Err(TemplateError::UnknownComponent(name)) if name == "Health"));
+    assert_eq!(scene.entities().count(), 1);
+  }
+}
@@ -0,0 +1,411 @@
+//! Data-driven entity templates.
+//!
+//! A template lists the components an entity starts with and their initial
+//! values. Templates are authored in RON, keyed by name, and may extend one
+//! another; a template's components are merged over its parent's field by
+//! field, and a component set to `None` is removed:
+//!
+//! ```ron
+//! {
+//!   "enemy": (
+//!     components: {
+//!       "Health": (max: 10.0),
+//!       "Sprite": (),
+//!     },
+//!   ),
+//!   "goblin": (
+//!     extends: "enemy",
+//!     components: {
+//!       "Health": (max: 25.0, invulnerability: 0.5),
+//!     },
+//!   ),
+//!   "ghost": (
+//!     extends: "enemy",
+//!     components: { "Sprite": None },
+//!   ),
+//! }
+//! ```
+//!
+//! Component names are resolved through a [`ComponentRegistry`], which knows
+//! how to build each component from its values:
+//!
+//! ```rust,ignore
+//! let mut registry = ComponentRegistry::new();
+//!
+//! registry.register("Health", |chunk| {
+//!   Ok(Health::new(chunk.read_field("max")?).with_invulnerability(chunk.read_field_or("invulnerability", 0.)?))
+//! });
+//!
+//! let templates = TemplateLibrary::from_path("assets/enemies.ron")?;
+//! let goblin = templates.spawn(&mut scene, &registry, "goblin")?;
+//! ```
+
+use common::{Chunk, FastHashMap, Format, FromStream, InputStream, RonFormat, StreamError, Variant};
+
+use super::*;
+
+/// An error that occurs while loading or spawning a template.
+#[derive(Debug)]
+pub enum TemplateError {
+  StreamError(StreamError),
+  UnknownTemplate(String),
+  UnknownComponent(String),
+  /// A component's values couldn't be read.
+  InvalidComponent(String, StreamError),
+  /// The template extends itself, directly or through its ancestors.
+  InheritanceCycle(String),
+}
+
+common::impl_error_coercion!(StreamError into TemplateError);
+
+/// Builds a component from its values in a template.
+type ComponentFactory = Box<dyn Fn(&Chunk) -> Result<Box<dyn Component>, StreamError>>;
+
+/// Maps component names, as written in templates, to their types.
+#[derive(Default)]
+pub struct ComponentRegistry {
+  factories: FastHashMap<String, ComponentFactory>,
+}
+
+impl ComponentRegistry {
+  /// Creates an empty registry.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Registers a component under the given name, with a function that builds
+  /// it from its values.
+  pub fn register<C: Component>(
+    &mut self,
+    name: impl Into<String>,
+    factory: impl Fn(&Chunk) -> Result<C, StreamError> + 'static,
+  ) {
+    let factory = move |chunk: &Chunk| Ok(Box::new(factory(chunk)?) as Box<dyn Component>);
+
+    self.factories.insert(name.into(), Box::new(factory));
+  }
+
+  /// Determines if a component is registered under the given name.
+  pub fn contains(&self, name: &str) -> bool {
+    self.factories.contains_key(name)
+  }
+
+  /// Builds the named component from its values.
+  pub fn create(&self, name: &str, values: &Chunk) -> Result<Box<dyn Component>, TemplateError> {
+    let factory = self
+      .factories
+      .get(name)
+      .ok_or_else(|| TemplateError::UnknownComponent(name.to_string()))?;
+
+    factory(values).map_err(|error| TemplateError::InvalidComponent(name.to_string(), error))
+  }
+}
+
+/// A single template, as written; see [`TemplateLibrary::resolve`] for the
+/// result of applying its parents.
+#[derive(Clone, Debug, Default)]
+pub struct EntityTemplate {
+  pub extends: Option<String>,
+  /// Component values by component name; a null removes the component.
+  pub components: FastHashMap<String, Chunk>,
+}
+
+impl EntityTemplate {
+  /// Reads a template from a [`Chunk`], as produced by [`RonFormat`].
+  pub fn from_chunk(chunk: &Chunk) -> Result<Self, TemplateError> {
+    let components = match chunk.get("components") {
+      Some(Chunk::Map(components)) => components.clone(),
+      Some(_) => return Err(StreamError::InvalidData.into()),
+      None => FastHashMap::default(),
+    };
+
+    Ok(Self {
+      extends: match chunk.get("extends") {
+        Some(extends) if !is_null(extends) => Some(extends.read()?),
+        _ => None,
+      },
+      components,
+    })
+  }
+
+  /// Applies overrides to this template's components, merging maps field by
+  /// field.
+  pub fn apply(&mut self, overrides: &FastHashMap<String, Chunk>) {
+    for (name, values) in overrides {
+      match self.components.get_mut(name) {
+        Some(existing) => merge(existing, values),
+        None => {
+          self.components.insert(name.clone(), values.clone());
+        }
+      }
+    }
+  }
+
+  /// The components that remain once removals are applied, sorted by name.
+  fn live_components(&self) -> Vec<(&String, &Chunk)> {
+    let mut components = self
+      .components
+      .iter()
+      .filter(|(_, values)| !is_null(values))
+      .collect::<Vec<_>>();
+
+    components.sort_by_key(|(name, _)| *name);
+    components
+  }
+}
+
+/// A named set of [`EntityTemplate`]s.
+#[derive(Clone, Debug, Default)]
+pub struct TemplateLibrary {
+  templates: FastHashMap<String, EntityTemplate>,
+}
+
+impl TemplateLibrary {
+  /// Creates an empty library.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Reads a library from a map of templates by name.
+  pub fn from_chunk(chunk: &Chunk) -> Result<Self, TemplateError> {
+    let Chunk::Map(templates) = chunk else {
+      return Err(StreamError::InvalidData.into());
+    };
+
+    let templates = templates
+      .iter()
+      .map(|(name, template)| Ok((name.clone(), EntityTemplate::from_chunk(template)?)))
+      .collect::<Result<_, TemplateError>>()?;
+
+    Ok(Self { templates })
+  }
+
+  /// Reads a library from RON text.
+  pub fn from_ron_str(text: &str) -> Result<Self, TemplateError> {
+    Self::from_bytes(text.as_bytes())
+  }
+
+  /// Adds a template, replacing any with the same name.
+  pub fn insert(&mut self, name: impl Into<String>, template: EntityTemplate) {
+    self.templates.insert(name.into(), template);
+  }
+
+  /// Gets a template as written, without its parents applied.
+  pub fn get(&self, name: &str) -> Option<&EntityTemplate> {
+    self.templates.get(name)
+  }
+
+  /// Iterates over the names of the templates.
+  pub fn names(&self) -> impl Iterator<Item = &str> {
+    self.templates.keys().map(String::as_str)
+  }
+
+  /// Resolves a template by applying it over its ancestors.
+  pub fn resolve(&self, name: &str) -> Result<EntityTemplate, TemplateError> {
+    let mut lineage = Vec::new();
+    let mut current = Some(name);
+
+    while let Some(name) = current {
+      if lineage.iter().any(|(ancestor, _)| *ancestor == name) {
+        return Err(TemplateError::InheritanceCycle(name.to_string()));
+      }
+
+      let template = self
+        .templates
+        .get(name)
+        .ok_or_else(|| TemplateError::UnknownTemplate(name.to_string()))?;
+
+      lineage.push((name, template));
+      current = template.extends.as_deref();
+    }
+
+    let mut resolved = EntityTemplate::default();
+
+    for (_, template) in lineage.iter().rev() {
+      resolved.apply(&template.components);
+    }
+
+    Ok(resolved)
+  }
+
+  /// Checks that every template resolves and uses only registered
+  /// components, so mistakes surface at load rather than at spawn.
+  pub fn validate(&self, registry: &ComponentRegistry) -> Result<(), TemplateError> {
+    for name in self.names() {
+      for (component, _) in self.resolve(name)?.live_components() {
+        if !registry.contains(component) {
+          return Err(TemplateError::UnknownComponent(component.clone()));
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Spawns an entity from the named template, named after it.
+  pub fn spawn(&self, scene: &mut Scene, registry: &ComponentRegistry, name: &str) -> Result<EntityId, TemplateError> {
+    self.spawn_with(scene, registry, name, &FastHashMap::default())
+  }
+
+  /// Spawns an entity from the named template, with further overrides
+  /// applied on top, like a spawn point that tweaks the enemy it spawns.
+  pub fn spawn_with(
+    &self,
+    scene: &mut Scene,
+    registry: &ComponentRegistry,
+    name: &str,
+    overrides: &FastHashMap<String, Chunk>,
+  ) -> Result<EntityId, TemplateError> {
+    let mut template = self.resolve(name)?;
+
+    template.apply(overrides);
+
+    // build every component before spawning, so a bad one doesn't leave a
+    // half-built entity behind
+    let components = template
+      .live_components()
+      .into_iter()
+      .map(|(component, values)| registry.create(component, values))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let id = scene.spawn_named(name);
+
+    if let Some(entity) = scene.entities.get_mut(id) {
+      entity.components.extend(components);
+    }
+
+    Ok(id)
+  }
+}
+
+impl FromStream for TemplateLibrary {
+  type Error = TemplateError;
+
+  async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+    Self::from_chunk(&RonFormat::default().read_chunk(stream)?)
+  }
+}
+
+/// Merges overriding values into a component's values; maps are merged key
+/// by key, and anything else is replaced.
+fn merge(target: &mut Chunk, overrides: &Chunk) {
+  match (target, overrides) {
+    (Chunk::Map(target), Chunk::Map(overrides)) => {
+      for (key, value) in overrides {
+        match target.get_mut(key) {
+          Some(existing) => merge(existing, value),
+          None => {
+            target.insert(key.clone(), value.clone());
+          }
+        }
+      }
+    }
+    (target, overrides) => *target = overrides.clone(),
+  }
+}
+
+fn is_null(chunk: &Chunk) -> bool {
+  matches!(chunk, Chunk::Variant(Variant::Null))
+}
+
+#[cfg(test)]
+mod tests {
+  use common::Health;
+
+  use super::*;
+
+  const TEMPLATES: &str = r#"{
+    "enemy": (
+      components: {
+        "Health": (max: 10.0),
+        "Sprite": (),
+      },
+    ),
+    "goblin": (
+      extends: "enemy",
+      components: {
+        "Health": (invulnerability: 0.5),
+      },
+    ),
+    "ghost": (
+      extends: "goblin",
+      components: { "Sprite": None },
+    ),
+    "ouroboros": (extends: "ouroboros"),
+  }"#;
+
+  fn registry() -> ComponentRegistry {
+    let mut registry = ComponentRegistry::new();
+
+    registry.register("Health", |chunk| {
+      Ok(Health::new(chunk.read_field("max")?).with_invulnerability(chunk.read_field_or("invulnerability", 0.)?))
+    });
+    registry.register("Sprite", |_| Ok(SpriteComponent {}));
+
+    registry
+  }
+
+  #[test]
+  fn it_should_spawn_templates_with_inherited_components() {
+    let templates = TemplateLibrary::from_ron_str(TEMPLATES).unwrap();
+    let registry = registry();
+    let mut scene = Scene::new();
+
+    let goblin = templates.spawn(&mut scene, &registry, "goblin").unwrap();
+    let ghost = templates.spawn(&mut scene, &registry, "ghost").unwrap();
+
+    let goblin = scene.entity(goblin).unwrap();
+    let health = goblin.get_component::<Health>().unwrap();
+
+    assert_eq!(goblin.name(), Some("goblin"));
+    assert_eq!(health.max(), 10.);
+    assert_eq!(health.invulnerability(), 0.5);
+    assert!(goblin.has_component::<SpriteComponent>());
+
+    let ghost = scene.entity(ghost).unwrap();
+
+    assert!(ghost.has_component::<Health>());
+    assert!(!ghost.has_component::<SpriteComponent>());
+  }
+
+  #[test]
+  fn it_should_apply_overrides_at_spawn() {
+    let templates = TemplateLibrary::from_ron_str(TEMPLATES).unwrap();
+    let overrides = RonFormat::default()
+      .read_chunk(&mut std::io::Cursor::new(br#"{ "Health": (max: 50.0) }"#.as_slice()))
+      .unwrap();
+
+    let Chunk::Map(overrides) = overrides else {
+      panic!("expected a map");
+    };
+
+    let mut scene = Scene::new();
+    let boss = templates
+      .spawn_with(&mut scene, &registry(), "goblin", &overrides)
+      .unwrap();
+    let health = scene.entity(boss).unwrap().get_component::<Health>().unwrap();
+
+    assert_eq!(health.max(), 50.);
+    assert_eq!(health.invulnerability(), 0.5);
+  }
+
+  #[test]
+  fn it_should_report_bad_templates() {
+    let templates = TemplateLibrary::from_ron_str(TEMPLATES).unwrap();
+    let mut scene = Scene::new();
+
+    assert!(matches!(
+      templates.resolve("ouroboros"),
+      Err(TemplateError::InheritanceCycle(_))
+    ));
+    assert!(matches!(
+      templates.spawn(&mut scene, &registry(), "dragon"),
+      Err(TemplateError::UnknownTemplate(_))
+    ));
+    assert!(matches!(
+      templates.spawn(&mut scene, &ComponentRegistry::new(), "goblin"),
+      Err(TemplateError::UnknownComponent(_))
+    ));
+    assert_eq!(scene.entities().count(), 0);
+  }
+}
@@ -0,0 +1,352 @@
+//! Validation of scenes, to catch mistakes before they're played.
+//!
+//! A [`SceneValidator`] walks every entity in a scene, letting each component
+//! check itself via [`Component::validate`] and running any registered
+//! [`ValidationRule`]s, then reports what it found against the path of the
+//! offending entity.
+
+use std::{
+  fmt::{Display, Formatter},
+  marker::PhantomData,
+};
+
+use common::{AssetManifest, FastHashSet, ToVirtualPath};
+
+use super::*;
+
+/// How serious a validation issue is.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ValidationSeverity {
+  Warning,
+  Error,
+}
+
+/// A single problem found in a scene.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationIssue {
+  pub severity: ValidationSeverity,
+  /// The path of the entity with the problem, see [`Scene::path`].
+  pub path: String,
+  pub message: String,
+}
+
+/// Everything found by a [`SceneValidator`].
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport {
+  pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+  /// Determines if the scene has no errors; warnings are allowed.
+  pub fn is_ok(&self) -> bool {
+    self.errors().next().is_none()
+  }
+
+  /// Iterates over the errors in the report.
+  pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+    self
+      .issues
+      .iter()
+      .filter(|issue| issue.severity == ValidationSeverity::Error)
+  }
+
+  /// Iterates over the warnings in the report.
+  pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+    self
+      .issues
+      .iter()
+      .filter(|issue| issue.severity == ValidationSeverity::Warning)
+  }
+}
+
+impl Display for ValidationReport {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    for issue in &self.issues {
+      let severity = match issue.severity {
+        ValidationSeverity::Warning => "warning",
+        ValidationSeverity::Error => "error",
+      };
+
+      writeln!(formatter, "{severity}: {}: {}", issue.path, issue.message)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// A check that runs against every entity in a scene.
+///
+/// Rules cover problems that span components, like colliders without bodies;
+/// problems within a single component belong in [`Component::validate`].
+pub trait ValidationRule {
+  fn check(&self, context: &mut ValidationContext);
+}
+
+/// A rule that entities with one component must also have another, e.g.
+/// `RequiresComponent::<Collider, RigidBody>::new()`.
+pub struct RequiresComponent<A, B> {
+  _phantom: PhantomData<(A, B)>,
+}
+
+impl<A: Component, B: Component> RequiresComponent<A, B> {
+  pub fn new() -> Self {
+    Self { _phantom: PhantomData }
+  }
+}
+
+impl<A: Component, B: Component> Default for RequiresComponent<A, B> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<A: Component, B: Component> ValidationRule for RequiresComponent<A, B> {
+  fn check(&self, context: &mut ValidationContext) {
+    if context.entity().has_component::<A>() && !context.entity().has_component::<B>() {
+      context.error(format!(
+        "{} requires a {}",
+        short_type_name::<A>(),
+        short_type_name::<B>()
+      ));
+    }
+  }
+}
+
+/// The entity being validated, and a place to report problems with it.
+pub struct ValidationContext<'a> {
+  validator: &'a SceneValidator,
+  entity: &'a Entity,
+  path: &'a str,
+  issues: &'a mut Vec<ValidationIssue>,
+}
+
+impl<'a> ValidationContext<'a> {
+  /// The entity being validated.
+  pub fn entity(&self) -> &'a Entity {
+    self.entity
+  }
+
+  /// The path of the entity being validated.
+  pub fn path(&self) -> &str {
+    self.path
+  }
+
+  /// Reports an error with the entity.
+  pub fn error(&mut self, message: impl Into<String>) {
+    self.report(ValidationSeverity::Error, message.into());
+  }
+
+  /// Reports a warning about the entity.
+  pub fn warning(&mut self, message: impl Into<String>) {
+    self.report(ValidationSeverity::Warning, message.into());
+  }
+
+  /// Reports an error if the given asset doesn't exist.
+  pub fn check_asset(&mut self, path: &str) {
+    if !(self.validator.asset_exists)(path) {
+      self.error(format!("missing asset {path}"));
+    }
+  }
+
+  /// Reports an error if any of the given values are NaN or infinite, e.g.
+  /// `context.check_finite("position", &position.to_array())`.
+  pub fn check_finite(&mut self, label: &str, values: &[f32]) {
+    if values.iter().any(|value| !value.is_finite()) {
+      self.error(format!("{label} is not finite: {values:?}"));
+    }
+  }
+
+  /// Reports an error if the named script function isn't known.
+  ///
+  /// Does nothing unless the validator was given the set of functions the
+  /// scripts define.
+  pub fn check_function(&mut self, name: &str) {
+    if let Some(functions) = &self.validator.functions {
+      if !functions.contains(name) {
+        self.error(format!("unknown script function {name}"));
+      }
+    }
+  }
+
+  fn report(&mut self, severity: ValidationSeverity, message: String) {
+    self.issues.push(ValidationIssue {
+      severity,
+      path: self.path.to_string(),
+      message,
+    });
+  }
+}
+
+/// Checks scenes for common mistakes.
+pub struct SceneValidator {
+  rules: Vec<Box<dyn ValidationRule>>,
+  asset_exists: Box<dyn Fn(&str) -> bool>,
+  functions: Option<FastHashSet<String>>,
+}
+
+impl Default for SceneValidator {
+  fn default() -> Self {
+    Self {
+      rules: Vec::new(),
+      asset_exists: Box::new(|path| path.to_virtual_path().exists()),
+      functions: None,
+    }
+  }
+}
+
+impl SceneValidator {
+  /// Creates a validator that looks assets up on the virtual file system.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Looks assets up in the given manifest, e.g. when validating offline
+  /// against a packed build.
+  pub fn with_manifest(mut self, manifest: AssetManifest) -> Self {
+    self.asset_exists = Box::new(move |path| manifest.get(path).is_some());
+    self
+  }
+
+  /// Checks script function references against the given names.
+  pub fn with_functions(mut self, functions: impl IntoIterator<Item = impl Into<String>>) -> Self {
+    self.functions = Some(functions.into_iter().map(Into::into).collect());
+    self
+  }
+
+  /// Adds a rule to run against every entity.
+  pub fn add_rule(&mut self, rule: impl ValidationRule + 'static) {
+    self.rules.push(Box::new(rule));
+  }
+
+  /// Validates every entity in the scene.
+  pub fn validate(&self, scene: &Scene) -> ValidationReport {
+    let mut issues = Vec::new();
+
+    for (id, entity) in scene.entities() {
+      let path = scene.path(id);
+      let mut context = ValidationContext {
+        validator: self,
+        entity,
+        path: &path,
+        issues: &mut issues,
+      };
+
+      for component in entity.components() {
+        component.validate(&mut context);
+      }
+
+      for (component, requirement) in entity.missing_requirements() {
+        context.error(format!("{component} requires a {}", requirement.name()));
+      }
+
+      for rule in &self.rules {
+        rule.check(&mut context);
+      }
+    }
+
+    ValidationReport { issues }
+  }
+}
+
+/// The name of a type without its module path.
+pub(crate) fn short_type_name<T: ?Sized>() -> &'static str {
+  let name = std::any::type_name::<T>();
+
+  name.rsplit("::").next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{vec3, Vec3};
+
+  use super::*;
+
+  struct Transform {
+    position: Vec3,
+  }
+
+  impl Component for Transform {
+    fn validate(&self, context: &mut ValidationContext) {
+      context.check_finite("position", &self.position.to_array());
+    }
+  }
+
+  struct Collider;
+  struct RigidBody;
+
+  impl Component for Collider {}
+  impl Component for RigidBody {}
+
+  struct Script {
+    on_hit: &'static str,
+    texture: &'static str,
+  }
+
+  impl Component for Script {
+    fn validate(&self, context: &mut ValidationContext) {
+      context.check_function(self.on_hit);
+      context.check_asset(self.texture);
+    }
+  }
+
+  #[test]
+  fn it_should_report_issues_with_entity_paths() {
+    let mut scene = Scene::new();
+
+    let level = scene.spawn_named("level");
+    let crate_ = scene.spawn_named("crate");
+    let ghost = scene.spawn();
+
+    scene.set_parent(crate_, Some(level));
+    scene.set_parent(ghost, Some(level));
+
+    scene.add_component(crate_, Transform {
+      position: vec3(f32::NAN, 0., 0.),
+    });
+    scene.add_component(crate_, Collider);
+    scene.add_component(ghost, Collider);
+    scene.add_component(ghost, RigidBody);
+
+    let mut validator = SceneValidator::new();
+
+    validator.add_rule(RequiresComponent::<Collider, RigidBody>::new());
+
+    let report = validator.validate(&scene);
+    let paths = report.errors().map(|issue| issue.path.as_str()).collect::<Vec<_>>();
+
+    assert!(!report.is_ok());
+    assert_eq!(paths, vec!["level/crate", "level/crate"]);
+    assert!(report.to_string().contains("Collider requires a RigidBody"));
+    assert_eq!(scene.path(ghost), format!("level/#{}", ghost.ordinal()));
+  }
+
+  #[test]
+  fn it_should_check_assets_and_functions() {
+    let mut scene = Scene::new();
+    let player = scene.spawn_named("player");
+
+    scene.add_component(player, Script {
+      on_hit: "on_player_hit",
+      texture: "sprites/player.png",
+    });
+
+    let mut manifest = AssetManifest::new(common::HashAlgorithm::XxHash3);
+
+    manifest.insert("sprites/player.png", b"player");
+
+    let valid = SceneValidator::new()
+      .with_manifest(manifest.clone())
+      .with_functions(["on_player_hit"]);
+
+    assert!(valid.validate(&scene).is_ok());
+
+    let invalid = SceneValidator::new()
+      .with_manifest(AssetManifest::new(common::HashAlgorithm::XxHash3))
+      .with_functions(["on_enemy_hit"]);
+
+    let report = invalid.validate(&scene);
+
+    assert_eq!(report.errors().count(), 2);
+    assert!(report.issues.iter().all(|issue| issue.path == "player"));
+  }
+}
@@ -0,0 +1,765 @@
+//! Archetype-based storage for plain-data components.
+//!
+//! A [`World`] groups entities by the exact set of component types they have
+//! (their archetype) and stores each archetype as a table with one column
+//! per component type. Queries visit only the archetypes that have every
+//! component they ask for, and walk those columns in lockstep, so iterating
+//! many entities touches contiguous memory rather than looking each
+//! component up per entity:
+//!
+//! ```rust,ignore
+//! let mut world = World::new();
+//!
+//! let player = world.spawn();
+//!
+//! world.insert(player, Position(Vec2::ZERO));
+//! world.insert(player, Velocity(Vec2::X));
+//!
+//! for (_, (position, velocity)) in world.query::<(&mut Position, &Velocity)>() {
+//!   position.0 += velocity.0 * delta_time;
+//! }
+//! ```
+//!
+//! Adding or removing a component moves the entity's row to the table of its
+//! new archetype, so prefer components that change rarely over toggling
+//! marker components every frame.
+//!
+//! Entities are put on layers and tagged with the shared [`LayerMask`] and
+//! [`Tags`] components, which mean the same thing as on scene entities,
+//! cameras and colliders.
+//!
+//! Tables are shared copy-on-write, so a [`WorldSnapshot`] is cheap to take
+//! and only the tables changed afterwards are copied, which suits rollback
+//! netcode, undo and reverting the editor's play mode:
+//!
+//! ```rust,ignore
+//! let snapshot = world.snapshot();
+//!
+//! simulate(&mut world, inputs);
+//!
+//! if mispredicted {
+//!   world.rollback(&snapshot);
+//! }
+//! ```
+//!
+//! A world is a separate entity store from a [`Scene`](crate::Scene), and
+//! nothing keeps the two in step. The scene owns authored content, which
+//! needs hierarchies, names and components that can be inspected, templated
+//! and saved; the world owns bulk simulation state that wants contiguous
+//! storage and cheap snapshots, like particles, projectiles and crowds.
+//! Neither store does the other's job well, which is why both exist. Where a
+//! world entity stands in for a scene entity, record the link as a component
+//! and copy state across explicitly, once per frame:
+//!
+//! ```rust,ignore
+//! #[derive(Clone)]
+//! struct SceneLink(EntityId);
+//!
+//! for (_, (link, position)) in world.query::<(&SceneLink, &Position)>() {
+//!   scene.set_transform(link.0, Mat4::from_translation(position.0.extend(0.)));
+//! }
+//! ```
+
+use std::{any::TypeId, sync::Arc};
+
+use common::{impl_arena_index, Arena, FastHashMap, LayerMask, StringName, Tags};
+use sealed::Column;
+
+impl_arena_index!(pub WorldEntity, "An entity in a [`World`].");
+
+/// Entities and their components, stored by archetype.
+///
+/// Components must be [`Clone`] so that shared tables can be copied on
+/// write; see [`World::snapshot`].
+#[derive(Clone)]
+pub struct World {
+  entities: Arc<Arena<WorldEntity, EntityLocation>>,
+  archetypes: Vec<Archetype>,
+  archetype_index: FastHashMap<Vec<TypeId>, usize>,
+}
+
+/// Where an entity's components live.
+#[derive(Copy, Clone, Debug)]
+struct EntityLocation {
+  archetype: usize,
+  row: usize,
+}
+
+/// A table of the entities with exactly the same component types.
+struct Archetype {
+  /// The component types, sorted, parallel to the columns.
+  types: Vec<TypeId>,
+  columns: Vec<Box<dyn Column>>,
+  /// The entity in each row.
+  entities: Arc<Vec<WorldEntity>>,
+}
+
+impl Clone for Archetype {
+  fn clone(&self) -> Self {
+    Self {
+      types: self.types.clone(),
+      columns: self.columns.iter().map(|column| column.share()).collect(),
+      entities: self.entities.clone(),
+    }
+  }
+}
+
+impl Archetype {
+  fn column_index(&self, type_id: TypeId) -> Option<usize> {
+    self.types.binary_search(&type_id).ok()
+  }
+
+  fn contains_all(&self, types: &[TypeId]) -> bool {
+    types.iter().all(|type_id| self.column_index(*type_id).is_some())
+  }
+}
+
+/// Storage that the query traits need in their signatures, but which nothing
+/// outside this crate can name or implement.
+pub(crate) mod sealed {
+  /// Limits [`Query`](super::Query) and [`QueryTerm`](super::QueryTerm) to
+  /// the implementations here.
+  pub trait Sealed {}
+
+  /// A type-erased column of components, shared until written to.
+  pub trait Column {
+    /// Creates an empty column for the same type of component.
+    fn new_empty(&self) -> Box<dyn Column>;
+
+    /// Shares the column's storage with a new handle.
+    fn share(&self) -> Box<dyn Column>;
+
+    /// Removes a row, moving the last row into its place.
+    fn swap_remove(&mut self, row: usize);
+
+    /// Moves a row to the end of another column of the same type, moving the
+    /// last row into its place.
+    fn move_row(&mut self, row: usize, target: &mut dyn Column);
+
+    fn as_any(&self) -> &dyn std::any::Any;
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
+  }
+}
+
+impl<T: Clone + 'static> Column for Arc<Vec<T>> {
+  fn new_empty(&self) -> Box<dyn Column> {
+    Box::new(Arc::new(Vec::<T>::new()))
+  }
+
+  fn share(&self) -> Box<dyn Column> {
+    Box::new(self.clone())
+  }
+
+  fn swap_remove(&mut self, row: usize) {
+    Arc::make_mut(self).swap_remove(row);
+  }
+
+  fn move_row(&mut self, row: usize, target: &mut dyn Column) {
+    let value = Arc::make_mut(self).swap_remove(row);
+
+    column_mut::<T>(target).push(value);
+  }
+
+  fn as_any(&self) -> &dyn std::any::Any {
+    self
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+    self
+  }
+}
+
+fn column<T: 'static>(column: &dyn Column) -> &Vec<T> {
+  column
+    .as_any()
+    .downcast_ref::<Arc<Vec<T>>>()
+    .expect("Column should hold its archetype's type")
+}
+
+/// Borrows a column for writing, copying it first if it's shared.
+fn column_mut<T: Clone + 'static>(column: &mut dyn Column) -> &mut Vec<T> {
+  let column = column
+    .as_any_mut()
+    .downcast_mut::<Arc<Vec<T>>>()
+    .expect("Column should hold its archetype's type");
+
+  Arc::make_mut(column)
+}
+
+impl Default for World {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl World {
+  /// Creates an empty world.
+  pub fn new() -> Self {
+    let empty = Archetype {
+      types: Vec::new(),
+      columns: Vec::new(),
+      entities: Arc::default(),
+    };
+
+    Self {
+      entities: Arc::new(Arena::new()),
+      archetypes: vec![empty],
+      archetype_index: FastHashMap::from_iter([(Vec::new(), 0)]),
+    }
+  }
+
+  /// The number of entities in the world.
+  pub fn len(&self) -> usize {
+    self.entities.len()
+  }
+
+  /// Determines if the world has no entities.
+  pub fn is_empty(&self) -> bool {
+    self.entities.is_empty()
+  }
+
+  /// The number of distinct sets of component types seen so far.
+  pub fn archetype_count(&self) -> usize {
+    self.archetypes.len()
+  }
+
+  /// Spawns an entity without components.
+  pub fn spawn(&mut self) -> WorldEntity {
+    let row = self.archetypes[0].entities.len();
+    let entity = Arc::make_mut(&mut self.entities).insert(EntityLocation { archetype: 0, row });
+
+    Arc::make_mut(&mut self.archetypes[0].entities).push(entity);
+
+    entity
+  }
+
+  /// Determines if the entity is in the world.
+  pub fn contains(&self, entity: WorldEntity) -> bool {
+    self.entities.contains(entity)
+  }
+
+  /// Removes an entity and all of its components.
+  pub fn despawn(&mut self, entity: WorldEntity) -> bool {
+    let Some(location) = Arc::make_mut(&mut self.entities).remove(entity) else {
+      return false;
+    };
+
+    let archetype = &mut self.archetypes[location.archetype];
+
+    for column in &mut archetype.columns {
+      column.swap_remove(location.row);
+    }
+
+    self.remove_row(location);
+
+    true
+  }
+
+  /// Determines if the entity has a component of the given type.
+  pub fn has<T: 'static>(&self, entity: WorldEntity) -> bool {
+    self.entities.get(entity).is_some_and(|location| {
+      self.archetypes[location.archetype]
+        .column_index(TypeId::of::<T>())
+        .is_some()
+    })
+  }
+
+  /// Gets the entity's component of the given type.
+  pub fn get<T: 'static>(&self, entity: WorldEntity) -> Option<&T> {
+    let location = self.entities.get(entity)?;
+    let archetype = &self.archetypes[location.archetype];
+    let index = archetype.column_index(TypeId::of::<T>())?;
+
+    column::<T>(archetype.columns[index].as_ref()).get(location.row)
+  }
+
+  /// Gets the entity's component of the given type, mutably.
+  pub fn get_mut<T: Clone + 'static>(&mut self, entity: WorldEntity) -> Option<&mut T> {
+    let location = *self.entities.get(entity)?;
+    let archetype = &mut self.archetypes[location.archetype];
+    let index = archetype.column_index(TypeId::of::<T>())?;
+
+    column_mut::<T>(archetype.columns[index].as_mut()).get_mut(location.row)
+  }
+
+  /// Adds a component to the entity, replacing any of the same type.
+  ///
+  /// Returns `false` if the entity isn't in the world.
+  pub fn insert<T: Clone + 'static>(&mut self, entity: WorldEntity, component: T) -> bool {
+    let Some(location) = self.entities.get(entity).copied() else {
+      return false;
+    };
+
+    let type_id = TypeId::of::<T>();
+    let source = &mut self.archetypes[location.archetype];
+
+    if let Some(index) = source.column_index(type_id) {
+      column_mut::<T>(source.columns[index].as_mut())[location.row] = component;
+
+      return true;
+    }
+
+    let mut types = source.types.clone();
+    let position = types.binary_search(&type_id).unwrap_err();
+
+    types.insert(position, type_id);
+
+    let target = self.find_or_create_archetype(types, location.archetype, Some(Box::new(Arc::new(Vec::<T>::new()))));
+
+    self.move_entity(entity, location, target);
+
+    let archetype = &mut self.archetypes[target];
+    let index = archetype
+      .column_index(type_id)
+      .expect("Archetype should have the added type");
+
+    column_mut::<T>(archetype.columns[index].as_mut()).push(component);
+
+    true
+  }
+
+  /// Removes the entity's component of the given type, returning it.
+  pub fn remove<T: Clone + 'static>(&mut self, entity: WorldEntity) -> Option<T> {
+    let location = *self.entities.get(entity)?;
+    let type_id = TypeId::of::<T>();
+    let source = &mut self.archetypes[location.archetype];
+    let index = source.column_index(type_id)?;
+
+    let component = column_mut::<T>(source.columns[index].as_mut()).swap_remove(location.row);
+
+    let mut types = source.types.clone();
+
+    types.remove(index);
+
+    let target = self.find_or_create_archetype(types, location.archetype, None);
+
+    self.move_entity(entity, location, target);
+
+    Some(component)
+  }
+
+  /// Iterates over every entity with all the components in the query,
+  /// like `(&mut Position, &Velocity)`.
+  ///
+  /// # Panics
+  /// Panics if the query asks for the same component type twice.
+  pub fn query<'a, Q: Query + 'a>(&'a mut self) -> impl Iterator<Item = (WorldEntity, Q::Item<'a>)> + 'a {
+    let types = Q::type_ids();
+
+    self
+      .archetypes
+      .iter_mut()
+      .filter(move |archetype| !archetype.entities.is_empty() && archetype.contains_all(&types))
+      .flat_map(|archetype| {
+        let entities = archetype.entities.iter().copied();
+        let items = Q::fetch(&archetype.types, &mut archetype.columns);
+
+        entities.zip(items)
+      })
+  }
+
+  /// The entities with components of all the given types, for callers
+  /// that only know the types at runtime, like scripts.
+  pub fn entities_with(&self, types: &[TypeId]) -> Vec<WorldEntity> {
+    self
+      .archetypes
+      .iter()
+      .filter(|archetype| archetype.contains_all(types))
+      .flat_map(|archetype| archetype.entities.iter().copied())
+      .collect()
+  }
+
+  /// The entities with a [`LayerMask`] component on any of the layers.
+  pub fn entities_on_layers(&self, mask: LayerMask) -> Vec<WorldEntity> {
+    self
+      .entities_with(&[TypeId::of::<LayerMask>()])
+      .into_iter()
+      .filter(|&entity| {
+        self
+          .get::<LayerMask>(entity)
+          .is_some_and(|layers| layers.intersects(mask))
+      })
+      .collect()
+  }
+
+  /// The entities with the tag in their [`Tags`] component.
+  pub fn entities_tagged(&self, tag: impl Into<StringName>) -> Vec<WorldEntity> {
+    let tag = tag.into();
+
+    self
+      .entities_with(&[TypeId::of::<Tags>()])
+      .into_iter()
+      .filter(|&entity| self.get::<Tags>(entity).is_some_and(|tags| tags.contains(tag)))
+      .collect()
+  }
+
+  /// Takes a snapshot of every entity and component, sharing storage with
+  /// the world until either is changed.
+  pub fn snapshot(&self) -> WorldSnapshot {
+    WorldSnapshot { world: self.clone() }
+  }
+
+  /// Puts every entity and component back as they were in the snapshot.
+  ///
+  /// The snapshot can be rolled back to again, e.g. to resimulate from the
+  /// same frame more than once.
+  pub fn rollback(&mut self, snapshot: &WorldSnapshot) {
+    *self = snapshot.world.clone();
+  }
+
+  /// Finds the archetype with exactly the given types, creating it with
+  /// empty columns shaped like another archetype's if needed.
+  fn find_or_create_archetype(&mut self, types: Vec<TypeId>, like: usize, added: Option<Box<dyn Column>>) -> usize {
+    if let Some(index) = self.archetype_index.get(&types) {
+      return *index;
+    }
+
+    let source = &self.archetypes[like];
+    let mut added = added;
+    let columns = types
+      .iter()
+      .map(|type_id| match source.column_index(*type_id) {
+        Some(index) => source.columns[index].new_empty(),
+        None => added.take().expect("New archetype should only add the given column"),
+      })
+      .collect();
+
+    let index = self.archetypes.len();
+
+    self.archetypes.push(Archetype {
+      types: types.clone(),
+      columns,
+      entities: Arc::default(),
+    });
+    self.archetype_index.insert(types, index);
+
+    index
+  }
+
+  /// Moves an entity's row into another archetype, carrying over the
+  /// components the two have in common.
+  ///
+  /// Components only in the source must already have been taken out of
+  /// their columns.
+  fn move_entity(&mut self, entity: WorldEntity, location: EntityLocation, target: usize) {
+    let (source, destination) = get_two_mut(&mut self.archetypes, location.archetype, target);
+
+    for (type_id, column) in source.types.iter().zip(source.columns.iter_mut()) {
+      if let Some(index) = destination.column_index(*type_id) {
+        column.move_row(location.row, destination.columns[index].as_mut());
+      }
+    }
+
+    let row = destination.entities.len();
+
+    Arc::make_mut(&mut destination.entities).push(entity);
+
+    self.remove_row(location);
+
+    if let Some(moved) = Arc::make_mut(&mut self.entities).get_mut(entity) {
+      *moved = EntityLocation { archetype: target, row };
+    }
+  }
+
+  /// Removes an entity from its row, once its components have been taken
+  /// out, and fixes up the entity moved into its place.
+  fn remove_row(&mut self, location: EntityLocation) {
+    let archetype = &mut self.archetypes[location.archetype];
+
+    Arc::make_mut(&mut archetype.entities).swap_remove(location.row);
+
+    if let Some(moved) = archetype.entities.get(location.row).copied() {
+      if let Some(moved) = Arc::make_mut(&mut self.entities).get_mut(moved) {
+        moved.row = location.row;
+      }
+    }
+  }
+}
+
+/// The entities and components of a [`World`] at a moment in time.
+#[derive(Clone)]
+pub struct WorldSnapshot {
+  world: World,
+}
+
+impl WorldSnapshot {
+  /// The number of entities in the snapshot.
+  pub fn len(&self) -> usize {
+    self.world.len()
+  }
+
+  /// Determines if the snapshot has no entities.
+  pub fn is_empty(&self) -> bool {
+    self.world.is_empty()
+  }
+
+  /// Gets an entity's component as it was in the snapshot.
+  pub fn get<T: 'static>(&self, entity: WorldEntity) -> Option<&T> {
+    self.world.get(entity)
+  }
+}
+
+/// Borrows two different elements of a slice mutably.
+fn get_two_mut<T>(slice: &mut [T], a: usize, b: usize) -> (&mut T, &mut T) {
+  match slice.get_disjoint_mut([a, b]) {
+    Ok([a, b]) => (a, b),
+    Err(_) => panic!("Expected two different elements"),
+  }
+}
+
+/// A component in a [`Query`], borrowed either immutably or mutably.
+pub trait QueryTerm: sealed::Sealed {
+  type Item<'a>;
+
+  /// The type of component borrowed.
+  fn type_id() -> TypeId;
+
+  /// Iterates over a column of the component.
+  fn iter(column: &mut dyn Column) -> impl Iterator<Item = Self::Item<'_>>;
+}
+
+impl<T: 'static> sealed::Sealed for &T {}
+impl<T: Clone + 'static> sealed::Sealed for &mut T {}
+
+impl<T: 'static> QueryTerm for &T {
+  type Item<'a> = &'a T;
+
+  fn type_id() -> TypeId {
+    TypeId::of::<T>()
+  }
+
+  fn iter(column: &mut dyn Column) -> impl Iterator<Item = Self::Item<'_>> {
+    // reading doesn't need to copy shared storage
+    let column: &dyn Column = column;
+
+    self::column::<T>(column).iter()
+  }
+}
+
+impl<T: Clone + 'static> QueryTerm for &mut T {
+  type Item<'a> = &'a mut T;
+
+  fn type_id() -> TypeId {
+    TypeId::of::<T>()
+  }
+
+  fn iter(column: &mut dyn Column) -> impl Iterator<Item = Self::Item<'_>> {
+    column_mut::<T>(column).iter_mut()
+  }
+}
+
+/// A set of components to iterate over in a [`World`], like
+/// `(&mut Position, &Velocity)`.
+pub trait Query: sealed::Sealed {
+  type Item<'a>;
+
+  /// The types of component borrowed.
+  fn type_ids() -> Vec<TypeId>;
+
+  /// Iterates over the rows of an archetype that has every type.
+  fn fetch<'a>(types: &[TypeId], columns: &'a mut [Box<dyn Column>]) -> impl Iterator<Item = Self::Item<'a>>;
+}
+
+macro_rules! impl_query {
+  ($(($term:ident, $iter:ident)),*) => {
+    impl<$($term: QueryTerm),*> sealed::Sealed for ($($term,)*) {}
+
+    impl<$($term: QueryTerm),*> Query for ($($term,)*) {
+      type Item<'a> = ($($term::Item<'a>,)*);
+
+      fn type_ids() -> Vec<TypeId> {
+        vec![$($term::type_id()),*]
+      }
+
+      fn fetch<'a>(types: &[TypeId], columns: &'a mut [Box<dyn Column>]) -> impl Iterator<Item = Self::Item<'a>> {
+        let indices = [$(types.binary_search(&$term::type_id()).expect("Archetype should match the query")),*];
+        let [$($iter),*] = columns
+          .get_disjoint_mut(indices)
+          .expect("Queries can't borrow the same component twice");
+
+        $(let mut $iter = $term::iter($iter.as_mut());)*
+
+        std::iter::from_fn(move || Some(($($iter.next()?,)*)))
+      }
+    }
+  };
+}
+
+impl_query!((A, a));
+impl_query!((A, a), (B, b));
+impl_query!((A, a), (B, b), (C, c));
+impl_query!((A, a), (B, b), (C, c), (D, d));
+impl_query!((A, a), (B, b), (C, c), (D, d), (E, e));
+impl_query!((A, a), (B, b), (C, c), (D, d), (E, e), (F, f));
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Clone, Debug, PartialEq)]
+  struct Position(f32);
+
+  #[derive(Clone, Debug, PartialEq)]
+  struct Velocity(f32);
+
+  #[derive(Clone, Debug, PartialEq)]
+  struct Frozen;
+
+  #[test]
+  fn it_should_migrate_components_between_archetypes() {
+    let mut world = World::new();
+
+    let a = world.spawn();
+    let b = world.spawn();
+
+    world.insert(a, Position(1.));
+    world.insert(b, Position(2.));
+    world.insert(a, Velocity(3.));
+
+    assert_eq!(world.get::<Position>(a), Some(&Position(1.)));
+    assert_eq!(world.get::<Velocity>(a), Some(&Velocity(3.)));
+    assert_eq!(world.get::<Position>(b), Some(&Position(2.)));
+    assert_eq!(world.get::<Velocity>(b), None);
+
+    assert_eq!(world.remove::<Position>(a), Some(Position(1.)));
+    assert!(!world.has::<Position>(a));
+    assert_eq!(world.get::<Velocity>(a), Some(&Velocity(3.)));
+    assert_eq!(world.get::<Position>(b), Some(&Position(2.)));
+
+    // empty, {position}, {position, velocity} and {velocity}
+    assert_eq!(world.archetype_count(), 4);
+  }
+
+  #[test]
+  fn it_should_query_across_archetypes() {
+    let mut world = World::new();
+
+    for index in 0..4 {
+      let entity = world.spawn();
+
+      world.insert(entity, Position(0.));
+      world.insert(entity, Velocity(index as f32));
+
+      if index % 2 == 0 {
+        world.insert(entity, Frozen);
+      }
+    }
+
+    let still = world.spawn();
+
+    world.insert(still, Position(10.));
+
+    for (_, (position, velocity)) in world.query::<(&mut Position, &Velocity)>() {
+      position.0 += velocity.0;
+    }
+
+    let mut positions = world.query::<(&Position,)>().map(|(_, (it,))| it.0).collect::<Vec<_>>();
+
+    positions.sort_by(f32::total_cmp);
+
+    assert_eq!(positions, vec![0., 1., 2., 3., 10.]);
+    assert_eq!(world.query::<(&Frozen, &Velocity)>().count(), 2);
+  }
+
+  #[test]
+  fn it_should_find_entities_by_layer_and_tag() {
+    let mut world = World::new();
+    let enemies = LayerMask::layer(2);
+
+    let goblin = world.spawn();
+    let player = world.spawn();
+    let rock = world.spawn();
+
+    world.insert(goblin, enemies);
+    world.insert(goblin, Tags::new().with("goblin"));
+    world.insert(player, LayerMask::layer(1));
+    world.insert(player, Tags::new().with("player"));
+    world.insert(rock, Position(0.));
+
+    assert_eq!(world.entities_on_layers(enemies), vec![goblin]);
+    assert_eq!(world.entities_on_layers(LayerMask::ALL).len(), 2);
+    assert_eq!(world.entities_tagged("player"), vec![player]);
+    assert_eq!(world.entities_tagged("ghost"), vec![]);
+  }
+
+  #[test]
+  fn it_should_fix_up_locations_on_despawn() {
+    let mut world = World::new();
+
+    let a = world.spawn();
+    let b = world.spawn();
+
+    world.insert(a, Position(1.));
+    world.insert(b, Position(2.));
+
+    assert!(world.despawn(a));
+    assert!(!world.despawn(a));
+
+    assert_eq!(world.len(), 1);
+    assert_eq!(world.get::<Position>(b), Some(&Position(2.)));
+
+    world.insert(b, Velocity(1.));
+
+    assert_eq!(world.get::<Position>(b), Some(&Position(2.)));
+  }
+
+  #[test]
+  fn it_should_roll_back_to_a_snapshot() {
+    let mut world = World::new();
+
+    let a = world.spawn();
+
+    world.insert(a, Position(1.));
+
+    let snapshot = world.snapshot();
+
+    world.get_mut::<Position>(a).unwrap().0 = 5.;
+    world.insert(a, Velocity(1.));
+
+    let b = world.spawn();
+
+    world.insert(b, Position(2.));
+
+    assert_eq!(snapshot.get::<Position>(a), Some(&Position(1.)));
+
+    world.rollback(&snapshot);
+
+    assert_eq!(world.len(), 1);
+    assert!(!world.contains(b));
+    assert_eq!(world.get::<Position>(a), Some(&Position(1.)));
+    assert!(!world.has::<Velocity>(a));
+
+    // the snapshot can be rolled back to again
+    world.remove::<Position>(a);
+    world.rollback(&snapshot);
+
+    assert_eq!(world.get::<Position>(a), Some(&Position(1.)));
+  }
+
+  #[test]
+  fn it_should_only_copy_changed_storage() {
+    let mut world = World::new();
+
+    let a = world.spawn();
+
+    world.insert(a, Position(1.));
+    world.insert(a, Velocity(1.));
+
+    let snapshot = world.snapshot();
+
+    for (_, (position, _)) in world.query::<(&mut Position, &Velocity)>() {
+      position.0 += 1.;
+    }
+
+    let archetype = &world.archetypes[world.entities.get(a).unwrap().archetype];
+    let velocities = archetype.columns[archetype.column_index(TypeId::of::<Velocity>()).unwrap()]
+      .as_any()
+      .downcast_ref::<Arc<Vec<Velocity>>>()
+      .unwrap();
+
+    // velocity was only read, so it's still shared with the snapshot
+    assert_eq!(Arc::strong_count(velocities), 2);
+    assert_eq!(snapshot.get::<Position>(a), Some(&Position(1.)));
+    assert_eq!(world.get::<Position>(a), Some(&Position(2.)));
+  }
+}
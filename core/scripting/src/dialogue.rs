@@ -0,0 +1,556 @@
+//! Branching dialogue driven by node graphs.
+//!
+//! A [`DialogueGraph`] is a set of named nodes: spoken lines, player choices
+//! and conditional branches. Choices and branches may be guarded by script
+//! expressions (in Lox syntax, e.g. `gold >= 10 and !met_guard`), which are
+//! compiled when the graph is loaded and evaluated on the scripting
+//! [`VirtualMachine`] against the runner's variables.
+//!
+//! Every line and choice carries a localization key alongside its source
+//! text; the [`DialogueRunner`] looks keys up through a [`Localizer`] and
+//! falls back to the source text when no translation is available.
+//!
+//! Graphs are assets, authored in RON:
+//!
+//! ```ron
+//! Dialogue(
+//!   start: "greet",
+//!   nodes: {
+//!     "greet": Line(speaker: "Guard", key: "guard.greet", text: "Halt!", next: "ask"),
+//!     "ask": Choice(choices: [
+//!       (key: "guard.bribe", text: "Here's 10 gold.", guard: "gold >= 10", next: "pass"),
+//!       (key: "guard.leave", text: "Never mind."),
+//!     ]),
+//!     "pass": Line(speaker: "Guard", key: "guard.pass", text: "Move along."),
+//!   },
+//! )
+//! ```
+
+use common::{
+  Chunk, FastHashMap, Format, FromStream, FromVariant, InputStream, RonFormat, StreamError, ToVariant, Variant,
+};
+
+use crate::{
+  lang::lox,
+  runtime::{
+    compiler::compile_expression,
+    machine::{VirtualMachine, VirtualMachineError},
+    Opcode,
+  },
+};
+
+/// An error that occurs while loading or running dialogue.
+#[derive(Debug)]
+pub enum DialogueError {
+  StreamError(StreamError),
+  InvalidGuard(String),
+  GuardFailed(String, VirtualMachineError),
+  MissingNode(String),
+  InvalidChoice(usize),
+  /// Conditions branched back to the given node without reaching a line or
+  /// choice, and would keep doing so forever.
+  ConditionCycle(String),
+}
+
+common::impl_error_coercion!(StreamError into DialogueError);
+
+/// A graph of dialogue nodes.
+#[derive(Debug, Default)]
+pub struct DialogueGraph {
+  pub start: String,
+  pub nodes: FastHashMap<String, DialogueNode>,
+}
+
+/// A single node in a [`DialogueGraph`].
+#[derive(Debug)]
+pub enum DialogueNode {
+  /// A line spoken by a character.
+  Line {
+    speaker: String,
+    text: LocalizedText,
+    next: Option<String>,
+  },
+  /// A set of choices for the player.
+  Choice { choices: Vec<DialogueChoice> },
+  /// Branches on a guard without presenting anything to the player.
+  Condition {
+    guard: DialogueGuard,
+    then: Option<String>,
+    otherwise: Option<String>,
+  },
+}
+
+/// A single choice on a [`DialogueNode::Choice`].
+#[derive(Debug)]
+pub struct DialogueChoice {
+  pub text: LocalizedText,
+  pub guard: Option<DialogueGuard>,
+  pub next: Option<String>,
+}
+
+/// Text with a localization key and the source text to fall back to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LocalizedText {
+  pub key: String,
+  pub text: String,
+}
+
+/// A compiled script expression that guards part of a dialogue.
+#[derive(Debug)]
+pub struct DialogueGuard {
+  source: String,
+  instructions: Vec<Opcode>,
+}
+
+impl DialogueGuard {
+  /// Compiles the given expression into a guard.
+  pub fn compile(source: &str) -> Result<Self, DialogueError> {
+    let invalid = || DialogueError::InvalidGuard(source.to_string());
+
+    let expression = lox::parse(source).map_err(|_| invalid())?;
+    let mut instructions = compile_expression(&expression).map_err(|_| invalid())?;
+
+    instructions.push(Opcode::Return);
+
+    Ok(Self {
+      source: source.to_string(),
+      instructions,
+    })
+  }
+
+  /// The source expression of this guard.
+  pub fn source(&self) -> &str {
+    &self.source
+  }
+
+  /// Evaluates the guard; anything other than `true` fails it.
+  pub fn evaluate(&self, machine: &mut VirtualMachine) -> Result<bool, DialogueError> {
+    let result = machine
+      .execute(&self.instructions)
+      .map_err(|error| DialogueError::GuardFailed(self.source.clone(), error))?;
+
+    Ok(matches!(result, Some(Variant::Bool(true))))
+  }
+}
+
+/// Resolves localization keys into display text.
+pub trait Localizer {
+  /// Looks up the text for the given key, if there is any.
+  fn localize(&self, key: &str) -> Option<&str>;
+}
+
+impl Localizer for FastHashMap<String, String> {
+  fn localize(&self, key: &str) -> Option<&str> {
+    self.get(key).map(String::as_str)
+  }
+}
+
+impl DialogueGraph {
+  /// Reads a dialogue graph from a [`Chunk`], as produced by [`RonFormat`].
+  pub fn from_chunk(chunk: &Chunk) -> Result<Self, DialogueError> {
+    let Some(Chunk::Map(nodes)) = chunk.get("nodes") else {
+      return Err(StreamError::InvalidData.into());
+    };
+
+    let nodes = nodes
+      .iter()
+      .map(|(name, node)| Ok((name.clone(), DialogueNode::from_chunk(node)?)))
+      .collect::<Result<FastHashMap<_, _>, DialogueError>>()?;
+
+    let graph = Self {
+      start: chunk.read_field("start")?,
+      nodes,
+    };
+
+    graph.validate()?;
+
+    Ok(graph)
+  }
+
+  /// Checks that every node the graph refers to exists.
+  pub fn validate(&self) -> Result<(), DialogueError> {
+    let check = |name: &Option<String>| match name {
+      Some(name) if !self.nodes.contains_key(name) => Err(DialogueError::MissingNode(name.clone())),
+      _ => Ok(()),
+    };
+
+    check(&Some(self.start.clone()))?;
+
+    for node in self.nodes.values() {
+      match node {
+        DialogueNode::Line { next, .. } => check(next)?,
+        DialogueNode::Choice { choices } => {
+          for choice in choices {
+            check(&choice.next)?;
+          }
+        }
+        DialogueNode::Condition { then, otherwise, .. } => {
+          check(then)?;
+          check(otherwise)?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl FromStream for DialogueGraph {
+  type Error = DialogueError;
+
+  async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+    Self::from_chunk(&RonFormat::default().read_chunk(stream)?)
+  }
+}
+
+impl DialogueNode {
+  fn from_chunk(chunk: &Chunk) -> Result<Self, DialogueError> {
+    match chunk.type_name() {
+      Some("Line") => Ok(Self::Line {
+        speaker: chunk.read_field_or("speaker", String::new())?,
+        text: LocalizedText::from_chunk(chunk)?,
+        next: read_optional(chunk, "next")?,
+      }),
+      Some("Choice") => Ok(Self::Choice {
+        choices: chunk
+          .get("choices")
+          .and_then(Chunk::as_sequence)
+          .unwrap_or_default()
+          .iter()
+          .map(|choice| {
+            Ok(DialogueChoice {
+              text: LocalizedText::from_chunk(choice)?,
+              guard: read_optional::<String>(choice, "guard")?
+                .map(|guard| DialogueGuard::compile(&guard))
+                .transpose()?,
+              next: read_optional(choice, "next")?,
+            })
+          })
+          .collect::<Result<_, DialogueError>>()?,
+      }),
+      Some("Condition") => Ok(Self::Condition {
+        guard: DialogueGuard::compile(&chunk.read_field::<String>("guard")?)?,
+        then: read_optional(chunk, "then")?,
+        otherwise: read_optional(chunk, "otherwise")?,
+      }),
+      _ => Err(StreamError::InvalidData.into()),
+    }
+  }
+}
+
+impl LocalizedText {
+  fn from_chunk(chunk: &Chunk) -> Result<Self, DialogueError> {
+    Ok(Self {
+      key: chunk.read_field_or("key", String::new())?,
+      text: chunk.read_field_or("text", String::new())?,
+    })
+  }
+}
+
+/// Reads an optional field, treating `None` and missing fields alike.
+fn read_optional<T: FromVariant>(chunk: &Chunk, key: &str) -> Result<Option<T>, StreamError> {
+  match chunk.get(key) {
+    None | Some(Chunk::Variant(Variant::Null)) => Ok(None),
+    Some(value) => value.read().map(Some),
+  }
+}
+
+/// An event emitted by a [`DialogueRunner`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum DialogueEvent {
+  /// A character speaks a line; call [`DialogueRunner::advance`] to continue.
+  Line { speaker: String, key: String, text: String },
+  /// The player must pick an option with [`DialogueRunner::choose`].
+  Choices(Vec<DialogueOption>),
+  /// The dialogue has ended.
+  Finished,
+}
+
+/// A choice that is available to the player.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DialogueOption {
+  /// The index to pass to [`DialogueRunner::choose`].
+  pub index: usize,
+  pub key: String,
+  pub text: String,
+}
+
+/// Runs a [`DialogueGraph`], emitting [`DialogueEvent`]s.
+pub struct DialogueRunner<'a> {
+  graph: &'a DialogueGraph,
+  localizer: Option<&'a dyn Localizer>,
+  machine: VirtualMachine,
+  current: Option<String>,
+  awaiting_choice: bool,
+}
+
+impl<'a> DialogueRunner<'a> {
+  /// Creates a runner positioned at the start of the given graph.
+  pub fn new(graph: &'a DialogueGraph) -> Self {
+    Self {
+      graph,
+      localizer: None,
+      machine: VirtualMachine::default(),
+      current: Some(graph.start.clone()),
+      awaiting_choice: false,
+    }
+  }
+
+  /// Uses the given localizer to resolve line and choice text.
+  pub fn with_localizer(mut self, localizer: &'a dyn Localizer) -> Self {
+    self.localizer = Some(localizer);
+    self
+  }
+
+  /// Sets a variable visible to guard expressions.
+  pub fn set_variable(&mut self, name: impl Into<String>, value: impl ToVariant) {
+    self.machine.set_global(name, value.to_variant());
+  }
+
+  /// Has the dialogue ended?
+  pub fn is_finished(&self) -> bool {
+    self.current.is_none()
+  }
+
+  /// Advances to the next line or set of choices.
+  ///
+  /// While choices are pending this re-emits them rather than moving on.
+  pub fn advance(&mut self) -> Result<DialogueEvent, DialogueError> {
+    // guards see the same variables on every hop, so passing through more
+    // conditions than the graph has nodes means they're going round in circles
+    let mut hops = 0;
+
+    loop {
+      let Some(name) = self.current.clone() else {
+        return Ok(DialogueEvent::Finished);
+      };
+
+      let node = self
+        .graph
+        .nodes
+        .get(&name)
+        .ok_or_else(|| DialogueError::MissingNode(name.clone()))?;
+
+      match node {
+        DialogueNode::Line { speaker, text, next } => {
+          self.current = next.clone();
+
+          return Ok(DialogueEvent::Line {
+            speaker: speaker.clone(),
+            key: text.key.clone(),
+            text: self.localize(text),
+          });
+        }
+        DialogueNode::Choice { .. } => {
+          self.awaiting_choice = true;
+
+          return Ok(DialogueEvent::Choices(self.options()?));
+        }
+        DialogueNode::Condition { guard, then, otherwise } => {
+          hops += 1;
+
+          if hops > self.graph.nodes.len() {
+            return Err(DialogueError::ConditionCycle(name));
+          }
+
+          self.current = if guard.evaluate(&mut self.machine)? {
+            then.clone()
+          } else {
+            otherwise.clone()
+          };
+        }
+      }
+    }
+  }
+
+  /// Picks one of the options from the last [`DialogueEvent::Choices`].
+  pub fn choose(&mut self, index: usize) -> Result<(), DialogueError> {
+    if !self.awaiting_choice || !self.options()?.iter().any(|option| option.index == index) {
+      return Err(DialogueError::InvalidChoice(index));
+    }
+
+    if let Some(DialogueNode::Choice { choices }) = self.current.as_ref().and_then(|name| self.graph.nodes.get(name)) {
+      self.current = choices[index].next.clone();
+      self.awaiting_choice = false;
+    }
+
+    Ok(())
+  }
+
+  /// The choices on the current node whose guards pass.
+  fn options(&mut self) -> Result<Vec<DialogueOption>, DialogueError> {
+    let Some(DialogueNode::Choice { choices }) = self.current.as_ref().and_then(|name| self.graph.nodes.get(name))
+    else {
+      return Ok(Vec::new());
+    };
+
+    let mut options = Vec::new();
+
+    for (index, choice) in choices.iter().enumerate() {
+      if let Some(guard) = &choice.guard {
+        if !guard.evaluate(&mut self.machine)? {
+          continue;
+        }
+      }
+
+      options.push(DialogueOption {
+        index,
+        key: choice.text.key.clone(),
+        text: self.localize(&choice.text),
+      });
+    }
+
+    Ok(options)
+  }
+
+  fn localize(&self, text: &LocalizedText) -> String {
+    self
+      .localizer
+      .and_then(|localizer| localizer.localize(&text.key))
+      .unwrap_or(&text.text)
+      .to_string()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DIALOGUE: &str = r#"
+    Dialogue(
+      start: "greet",
+      nodes: {
+        "greet": Line(speaker: "Guard", key: "guard.greet", text: "Halt!", next: "check"),
+        "check": Condition(guard: "met_guard", then: "ask", otherwise: "intro"),
+        "intro": Line(speaker: "Guard", key: "guard.intro", text: "I haven't seen you before.", next: "ask"),
+        "ask": Choice(choices: [
+          (key: "guard.bribe", text: "Here's 10 gold.", guard: "gold >= 10", next: "pass"),
+          (key: "guard.leave", text: "Never mind."),
+        ]),
+        "pass": Line(speaker: "Guard", key: "guard.pass", text: "Move along."),
+      },
+    )
+  "#;
+
+  fn line(text: &str) -> DialogueEvent {
+    match text.split_once(": ") {
+      Some((speaker, text)) => DialogueEvent::Line {
+        speaker: speaker.to_string(),
+        key: String::new(),
+        text: text.to_string(),
+      },
+      None => unreachable!(),
+    }
+  }
+
+  fn without_key(event: DialogueEvent) -> DialogueEvent {
+    match event {
+      DialogueEvent::Line { speaker, text, .. } => DialogueEvent::Line {
+        speaker,
+        key: String::new(),
+        text,
+      },
+      event => event,
+    }
+  }
+
+  #[test]
+  fn it_should_load_dialogue_from_ron() {
+    let graph = DialogueGraph::from_bytes(DIALOGUE.as_bytes()).unwrap();
+
+    assert_eq!(graph.start, "greet");
+    assert_eq!(graph.nodes.len(), 5);
+  }
+
+  #[test]
+  fn it_should_reject_missing_nodes_and_bad_guards() {
+    let missing = r#"Dialogue(start: "a", nodes: { "a": Line(text: "Hi", next: "b") })"#;
+    let invalid = r#"Dialogue(start: "a", nodes: { "a": Condition(guard: "1 +") })"#;
+
+    assert!(matches!(
+      DialogueGraph::from_bytes(missing.as_bytes()),
+      Err(DialogueError::MissingNode(name)) if name == "b"
+    ));
+    assert!(matches!(
+      DialogueGraph::from_bytes(invalid.as_bytes()),
+      Err(DialogueError::InvalidGuard(_))
+    ));
+  }
+
+  #[test]
+  fn it_should_stop_conditions_that_branch_in_circles() {
+    let cycle = r#"
+      Dialogue(start: "a", nodes: {
+        "a": Condition(guard: "lost", then: "b", otherwise: "c"),
+        "b": Condition(guard: "lost", then: "a", otherwise: "c"),
+        "c": Line(text: "Found it."),
+      })
+    "#;
+
+    let graph = DialogueGraph::from_bytes(cycle.as_bytes()).unwrap();
+    let mut runner = DialogueRunner::new(&graph);
+
+    runner.set_variable("lost", true);
+
+    assert!(matches!(runner.advance(), Err(DialogueError::ConditionCycle(_))));
+
+    runner.set_variable("lost", false);
+
+    assert!(matches!(runner.advance().unwrap(), DialogueEvent::Line { text, .. } if text == "Found it."));
+  }
+
+  #[test]
+  fn it_should_run_branches_and_guarded_choices() {
+    let graph = DialogueGraph::from_bytes(DIALOGUE.as_bytes()).unwrap();
+    let mut runner = DialogueRunner::new(&graph);
+
+    runner.set_variable("met_guard", false);
+    runner.set_variable("gold", 5i64);
+
+    assert_eq!(without_key(runner.advance().unwrap()), line("Guard: Halt!"));
+    assert_eq!(
+      without_key(runner.advance().unwrap()),
+      line("Guard: I haven't seen you before.")
+    );
+
+    let DialogueEvent::Choices(options) = runner.advance().unwrap() else {
+      panic!("expected choices");
+    };
+
+    assert_eq!(options.len(), 1);
+    assert_eq!(options[0].index, 1);
+    assert!(matches!(runner.choose(0), Err(DialogueError::InvalidChoice(0))));
+
+    runner.choose(1).unwrap();
+
+    assert_eq!(runner.advance().unwrap(), DialogueEvent::Finished);
+    assert!(runner.is_finished());
+  }
+
+  #[test]
+  fn it_should_localize_lines_and_choices() {
+    let graph = DialogueGraph::from_bytes(DIALOGUE.as_bytes()).unwrap();
+    let mut strings = FastHashMap::default();
+
+    strings.insert("guard.greet".to_string(), "Halte !".to_string());
+    strings.insert("guard.bribe".to_string(), "Voici 10 pièces.".to_string());
+
+    let mut runner = DialogueRunner::new(&graph).with_localizer(&strings);
+
+    runner.set_variable("met_guard", true);
+    runner.set_variable("gold", 20i64);
+
+    assert_eq!(without_key(runner.advance().unwrap()), line("Guard: Halte !"));
+
+    let DialogueEvent::Choices(options) = runner.advance().unwrap() else {
+      panic!("expected choices");
+    };
+
+    assert_eq!(options[0].text, "Voici 10 pièces.");
+    assert_eq!(options[1].text, "Never mind.");
+
+    runner.choose(0).unwrap();
+
+    assert!(matches!(runner.advance().unwrap(), DialogueEvent::Line { key, .. } if key == "guard.pass"));
+  }
+}
@@ -0,0 +1,238 @@
+//! Lightweight expressions for data-driven conditions.
+//!
+//! Dialogue, quests and AI often need a little logic in their data, like
+//! `player.hp < 20 && has_item('key')`, without the weight of a whole script
+//! module. These expressions use Lox syntax and are evaluated by walking the
+//! syntax tree directly against an [`ExpressionContext`], so no virtual
+//! machine is needed:
+//!
+//! ```rust,ignore
+//! let mut context = FastHashMap::default();
+//!
+//! context.insert("player.hp".to_string(), Variant::I64(15));
+//! context.insert("has_item".to_string(), Variant::Callable(Callable::from_callback(has_item)));
+//!
+//! assert_eq!(eval("player.hp < 20 && has_item('key')", &context)?, Variant::Bool(true));
+//! ```
+//!
+//! Mixed integer and float operands are compared and combined as floats, and
+//! `and`/`or` short-circuit, so later operands may rely on earlier ones.
+
+use common::{CallbackError, FastHashMap, FromVariant, Variant};
+
+use crate::lang::{ast::*, lox};
+
+/// An error that occurs while parsing or evaluating an expression.
+#[derive(Debug)]
+pub enum ExpressionError {
+  ParseFailed(String),
+  UndefinedVariable(String),
+  NotCallable(String),
+  InvalidOperands(BinaryOp),
+  InvalidNegation,
+  CallFailed(String, CallbackError),
+}
+
+/// Resolves the names used in an expression to values.
+///
+/// Functions are resolved the same way, as [`Variant::Callable`]s. Dotted
+/// names like `player.hp` are resolved as a whole.
+pub trait ExpressionContext {
+  /// Looks up the value of the given name, if there is one.
+  fn resolve(&self, name: &str) -> Option<Variant>;
+}
+
+impl ExpressionContext for FastHashMap<String, Variant> {
+  fn resolve(&self, name: &str) -> Option<Variant> {
+    self.get(name).cloned()
+  }
+}
+
+impl<F: Fn(&str) -> Option<Variant>> ExpressionContext for F {
+  fn resolve(&self, name: &str) -> Option<Variant> {
+    self(name)
+  }
+}
+
+/// Parses and evaluates an expression in one step.
+pub fn eval(source: &str, context: &dyn ExpressionContext) -> Result<Variant, ExpressionError> {
+  ScriptExpression::parse(source)?.evaluate(context)
+}
+
+/// A parsed expression, for evaluating many times.
+#[derive(Debug, Clone)]
+pub struct ScriptExpression {
+  source: String,
+  expression: Expression,
+}
+
+impl ScriptExpression {
+  /// Parses the given expression.
+  pub fn parse(source: &str) -> Result<Self, ExpressionError> {
+    let expression = lox::parse(source).map_err(|_| ExpressionError::ParseFailed(source.to_string()))?;
+
+    Ok(Self {
+      source: source.to_string(),
+      expression,
+    })
+  }
+
+  /// The source of this expression.
+  pub fn source(&self) -> &str {
+    &self.source
+  }
+
+  /// Evaluates the expression against the given context.
+  pub fn evaluate(&self, context: &dyn ExpressionContext) -> Result<Variant, ExpressionError> {
+    evaluate(&self.expression, context)
+  }
+
+  /// Evaluates the expression as a condition; anything other than `true`
+  /// fails it.
+  pub fn is_true(&self, context: &dyn ExpressionContext) -> Result<bool, ExpressionError> {
+    Ok(matches!(self.evaluate(context)?, Variant::Bool(true)))
+  }
+}
+
+fn evaluate(expression: &Expression, context: &dyn ExpressionContext) -> Result<Variant, ExpressionError> {
+  match expression {
+    Expression::Literal(value) => Ok(value.clone()),
+    Expression::Variable(name) => context
+      .resolve(name)
+      .ok_or_else(|| ExpressionError::UndefinedVariable(name.clone())),
+    Expression::Unary(UnaryOp::Negate, value) => {
+      (-evaluate(value, context)?).map_err(|_| ExpressionError::InvalidNegation)
+    }
+    Expression::Binary(left, operator @ (BinaryOp::And | BinaryOp::Or), right) => {
+      let Variant::Bool(left) = evaluate(left, context)? else {
+        return Err(ExpressionError::InvalidOperands(*operator));
+      };
+
+      // skip the right hand side if the left already decides the result
+      if left == (*operator == BinaryOp::Or) {
+        return Ok(Variant::Bool(left));
+      }
+
+      match evaluate(right, context)? {
+        Variant::Bool(right) => Ok(Variant::Bool(right)),
+        _ => Err(ExpressionError::InvalidOperands(*operator)),
+      }
+    }
+    Expression::Binary(left, operator, right) => {
+      let (left, right) = promote(evaluate(left, context)?, evaluate(right, context)?);
+      let invalid = |_| ExpressionError::InvalidOperands(*operator);
+
+      match operator {
+        BinaryOp::Add => (left + right).map_err(invalid),
+        BinaryOp::Subtract => (left - right).map_err(invalid),
+        BinaryOp::Multiply => (left * right).map_err(invalid),
+        BinaryOp::Divide => (left / right).map_err(invalid),
+        BinaryOp::Modulo => (left % right).map_err(invalid),
+        BinaryOp::Equal => Ok(Variant::Bool(left == right)),
+        BinaryOp::NotEqual => Ok(Variant::Bool(left != right)),
+        BinaryOp::LessThan => Ok(Variant::Bool(left < right)),
+        BinaryOp::LessThanOrEqual => Ok(Variant::Bool(left <= right)),
+        BinaryOp::GreaterThan => Ok(Variant::Bool(left > right)),
+        BinaryOp::GreaterThanOrEqual => Ok(Variant::Bool(left >= right)),
+        BinaryOp::And | BinaryOp::Or => unreachable!("handled above"),
+      }
+    }
+    Expression::Call(callee, arguments) => {
+      let name = match callee.as_ref() {
+        Expression::Variable(name) => name.clone(),
+        _ => String::from("<expression>"),
+      };
+
+      let Variant::Callable(callable) = evaluate(callee, context)? else {
+        return Err(ExpressionError::NotCallable(name));
+      };
+
+      let arguments = arguments
+        .iter()
+        .map(|argument| evaluate(argument, context))
+        .collect::<Result<Vec<_>, _>>()?;
+
+      callable
+        .call(&arguments)
+        .map_err(|error| ExpressionError::CallFailed(name, error))
+    }
+  }
+}
+
+/// Widens mixed numeric operands to floats, so `hp < 20` works whatever kind
+/// of number `hp` holds.
+fn promote(left: Variant, right: Variant) -> (Variant, Variant) {
+  if left.is_scalar() && right.is_scalar() && left.kind() != right.kind() {
+    if let (Ok(left), Ok(right)) = (f64::from_variant(left.clone()), f64::from_variant(right.clone())) {
+      return (Variant::F64(left), Variant::F64(right));
+    }
+  }
+
+  (left, right)
+}
+
+#[cfg(test)]
+mod tests {
+  use common::Callable;
+
+  use super::*;
+
+  fn context() -> FastHashMap<String, Variant> {
+    let mut context = FastHashMap::default();
+
+    context.insert("player.hp".to_string(), Variant::F32(15.));
+    context.insert("player.name".to_string(), Variant::String("Ada".to_string()));
+    context.insert(
+      "has_item".to_string(),
+      Variant::Callable(Callable::from_callback(|item: String| item == "key")),
+    );
+
+    context
+  }
+
+  #[test]
+  fn it_should_evaluate_conditions_against_a_context() {
+    let context = context();
+
+    assert_eq!(
+      eval("player.hp < 20 && has_item('key')", &context).unwrap(),
+      Variant::Bool(true)
+    );
+    assert_eq!(
+      eval("player.hp * 2 >= 40 || has_item(\"sword\")", &context).unwrap(),
+      Variant::Bool(false)
+    );
+    assert_eq!(
+      eval("player.name == 'Ada' and !false", &context).unwrap(),
+      Variant::Bool(true)
+    );
+  }
+
+  #[test]
+  fn it_should_short_circuit_logical_operators() {
+    let context = context();
+
+    assert_eq!(eval("false and missing", &context).unwrap(), Variant::Bool(false));
+    assert_eq!(eval("true or missing()", &context).unwrap(), Variant::Bool(true));
+  }
+
+  #[test]
+  fn it_should_report_errors() {
+    let context = |name: &str| (name == "gold").then_some(Variant::I64(10));
+
+    assert!(matches!(
+      eval("gold >=", &context),
+      Err(ExpressionError::ParseFailed(_))
+    ));
+    assert!(matches!(eval("silver > 1", &context), Err(ExpressionError::UndefinedVariable(name)) if name == "silver"));
+    assert!(matches!(eval("gold(1)", &context), Err(ExpressionError::NotCallable(name)) if name == "gold"));
+    assert!(matches!(
+      eval("gold and true", &context),
+      Err(ExpressionError::InvalidOperands(BinaryOp::And))
+    ));
+
+    let condition = ScriptExpression::parse("gold / 5 == 2").unwrap();
+
+    assert!(condition.is_true(&context).unwrap());
+  }
+}
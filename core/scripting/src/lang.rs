@@ -1,5 +1,6 @@
 //! Scripting language abstractions
 
+pub mod analysis;
 pub mod lox;
 pub mod wren;
 
@@ -8,10 +9,40 @@ pub(crate) mod ast {
 
   use common::{ToVariant, Variant};
 
+  /// A whole script: its top-level [`Statement`]s and where each was found.
+  #[derive(Debug, Clone, Default)]
+  pub struct Module(pub Vec<(Statement, Span)>);
+
   /// A block of [`Statement`]s.
   #[derive(Debug, Clone)]
   pub struct Block(pub Vec<Statement>);
 
+  /// A region of source code.
+  #[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+  pub struct Span {
+    /// The byte offset of the start of the region.
+    pub start: usize,
+    /// The byte offset just past the end of the region.
+    pub end: usize,
+    /// The line the region starts on, from 1.
+    pub line: usize,
+    /// The column the region starts at, from 1.
+    pub column: usize,
+  }
+
+  impl Span {
+    /// Extends this span to the end of another.
+    pub fn to(self, other: Span) -> Span {
+      Span { end: other.end, ..self }
+    }
+  }
+
+  impl std::fmt::Display for Span {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(formatter, "{}:{}", self.line, self.column)
+    }
+  }
+
   /// A single statement.
   #[derive(Debug, Clone)]
   pub enum Statement {
@@ -24,8 +55,10 @@ pub(crate) mod ast {
   #[derive(Debug, Clone, PartialEq)]
   pub enum Expression {
     Literal(Variant),
+    Variable(String),
     Binary(Box<Expression>, BinaryOp, Box<Expression>),
     Unary(UnaryOp, Box<Expression>),
+    Call(Box<Expression>, Vec<Expression>),
   }
 
   /// A literal value.
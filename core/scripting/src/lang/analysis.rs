@@ -0,0 +1,410 @@
+//! Static analysis of scripts, to catch mistakes when they're loaded rather
+//! than part way through play.
+//!
+//! An [`Analyser`] resolves every name in a [`Module`] against the module's
+//! own variables and the globals it's been told about, infers the kind of
+//! value each expression produces where it can, and flags the operations the
+//! [`VirtualMachine`] would reject at runtime. It also warns about variables
+//! that are never read and code that follows a `return`.
+//!
+//! Analysis is optional; the compiler doesn't depend on it.
+
+use std::fmt::{Display, Formatter};
+
+use common::{FastHashMap, VariantKind};
+
+use super::ast::*;
+use crate::runtime::machine::VirtualMachine;
+
+/// How serious a diagnostic is.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum DiagnosticSeverity {
+  Warning,
+  Error,
+}
+
+/// A single problem found in a script.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+  pub severity: DiagnosticSeverity,
+  /// The statement with the problem.
+  pub span: Span,
+  pub message: String,
+}
+
+/// Everything found by an [`Analyser`].
+#[derive(Clone, Debug, Default)]
+pub struct AnalysisReport {
+  pub diagnostics: Vec<Diagnostic>,
+}
+
+impl AnalysisReport {
+  /// Determines if the script has no errors; warnings are allowed.
+  pub fn is_ok(&self) -> bool {
+    self.errors().next().is_none()
+  }
+
+  /// Iterates over the errors in the report.
+  pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+    self
+      .diagnostics
+      .iter()
+      .filter(|diagnostic| diagnostic.severity == DiagnosticSeverity::Error)
+  }
+
+  /// Iterates over the warnings in the report.
+  pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+    self
+      .diagnostics
+      .iter()
+      .filter(|diagnostic| diagnostic.severity == DiagnosticSeverity::Warning)
+  }
+}
+
+impl Display for AnalysisReport {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    for diagnostic in &self.diagnostics {
+      let severity = match diagnostic.severity {
+        DiagnosticSeverity::Warning => "warning",
+        DiagnosticSeverity::Error => "error",
+      };
+
+      writeln!(formatter, "{severity}: {}: {}", diagnostic.span, diagnostic.message)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Statically analyses script [`Module`]s.
+///
+/// Globals are known by the kind of value they hold, or `None` where that
+/// can't be known ahead of time.
+#[derive(Default)]
+pub struct Analyser {
+  globals: FastHashMap<String, Option<VariantKind>>,
+}
+
+impl Analyser {
+  /// Creates an analyser that knows of no globals.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Creates an analyser that knows of every global in the given machine,
+  /// including its standard library.
+  pub fn for_machine(machine: &VirtualMachine) -> Self {
+    let mut analyser = Self::new();
+
+    for (name, value) in machine.globals() {
+      analyser.define_global(name, known_kind(value.kind()));
+    }
+
+    analyser
+  }
+
+  /// Defines a global, and the kind of value it holds if known.
+  pub fn define_global(&mut self, name: impl Into<String>, kind: Option<VariantKind>) {
+    self.globals.insert(name.into(), kind);
+  }
+
+  /// Analyses the given module.
+  pub fn analyse(&self, module: &Module) -> AnalysisReport {
+    let mut context = AnalysisContext {
+      analyser: self,
+      locals: FastHashMap::default(),
+      report: AnalysisReport::default(),
+      span: Span::default(),
+    };
+
+    let mut has_returned = false;
+
+    for (statement, span) in &module.0 {
+      context.span = *span;
+
+      if has_returned {
+        context.warning("unreachable code".to_string());
+
+        // one warning covers everything after the return
+        has_returned = false;
+      }
+
+      match statement {
+        Statement::Expression(expression) => {
+          context.infer(expression);
+        }
+        Statement::Assignment(name, expression) => {
+          let kind = context.infer(expression);
+
+          context
+            .locals
+            .entry(name.clone())
+            .and_modify(|local| local.kind = kind)
+            .or_insert(Local {
+              kind,
+              span: *span,
+              is_used: false,
+            });
+        }
+        Statement::Return(expression) => {
+          context.infer(expression);
+
+          has_returned = true;
+        }
+      }
+    }
+
+    context.report_unused();
+    context.report
+  }
+}
+
+/// A variable assigned within a module.
+struct Local {
+  kind: Option<VariantKind>,
+  /// Where the variable was first assigned.
+  span: Span,
+  is_used: bool,
+}
+
+/// State for a single run of an [`Analyser`].
+struct AnalysisContext<'a> {
+  analyser: &'a Analyser,
+  locals: FastHashMap<String, Local>,
+  report: AnalysisReport,
+  /// The statement being analysed.
+  span: Span,
+}
+
+impl AnalysisContext<'_> {
+  /// Infers the kind of value the expression produces, if it can be known.
+  fn infer(&mut self, expression: &Expression) -> Option<VariantKind> {
+    match expression {
+      Expression::Literal(value) => known_kind(value.kind()),
+      Expression::Variable(name) => {
+        if let Some(local) = self.locals.get_mut(name) {
+          local.is_used = true;
+
+          return local.kind;
+        }
+
+        match self.analyser.globals.get(name) {
+          Some(kind) => *kind,
+          None => {
+            self.error(format!("`{name}` is not defined"));
+            None
+          }
+        }
+      }
+      Expression::Unary(UnaryOp::Negate, value) => {
+        let kind = self.infer(value);
+
+        match kind {
+          Some(kind) if !is_negatable(kind) => {
+            self.error(format!("a {kind:?} can't be negated"));
+            None
+          }
+          kind => kind,
+        }
+      }
+      Expression::Binary(left, operator, right) => {
+        let left = self.infer(left);
+        let right = self.infer(right);
+
+        self.infer_binary(*operator, left, right)
+      }
+      Expression::Call(callee, arguments) => {
+        let kind = self.infer(callee);
+
+        for argument in arguments {
+          self.infer(argument);
+        }
+
+        if let Some(kind) = kind.filter(|kind| *kind != VariantKind::Callable) {
+          self.error(format!("a {kind:?} can't be called"));
+        }
+
+        None
+      }
+    }
+  }
+
+  /// Infers the result of a binary operator, checking its operands.
+  fn infer_binary(
+    &mut self,
+    operator: BinaryOp,
+    left: Option<VariantKind>,
+    right: Option<VariantKind>,
+  ) -> Option<VariantKind> {
+    let symbol = operator_symbol(operator);
+
+    match operator {
+      BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => {
+        for kind in [left, right].into_iter().flatten() {
+          if !supports_arithmetic(operator, kind) {
+            self.error(format!("`{symbol}` can't be applied to a {kind:?}"));
+            return None;
+          }
+        }
+
+        match (left, right) {
+          (Some(left), Some(right)) if left != right => {
+            self.error(format!("`{symbol}` can't be applied to a {left:?} and a {right:?}"));
+            None
+          }
+          _ => left.or(right),
+        }
+      }
+      BinaryOp::And | BinaryOp::Or => {
+        for kind in [left, right].into_iter().flatten() {
+          if kind != VariantKind::Bool {
+            self.error(format!("`{symbol}` expects a Bool, found a {kind:?}"));
+          }
+        }
+
+        Some(VariantKind::Bool)
+      }
+      _ => Some(VariantKind::Bool),
+    }
+  }
+
+  /// Warns about every variable that was assigned but never read.
+  fn report_unused(&mut self) {
+    let mut unused = self
+      .locals
+      .iter()
+      .filter(|(name, local)| !local.is_used && !name.starts_with('_'))
+      .map(|(name, local)| (local.span, name.clone()))
+      .collect::<Vec<_>>();
+
+    unused.sort_by_key(|(span, _)| span.start);
+
+    for (span, name) in unused {
+      self.report.diagnostics.push(Diagnostic {
+        severity: DiagnosticSeverity::Warning,
+        span,
+        message: format!("`{name}` is assigned but never used"),
+      });
+    }
+  }
+
+  fn warning(&mut self, message: String) {
+    self.push(DiagnosticSeverity::Warning, message);
+  }
+
+  fn error(&mut self, message: String) {
+    self.push(DiagnosticSeverity::Error, message);
+  }
+
+  fn push(&mut self, severity: DiagnosticSeverity, message: String) {
+    self.report.diagnostics.push(Diagnostic {
+      severity,
+      span: self.span,
+      message,
+    });
+  }
+}
+
+/// The kind of a value, unless it's opaque (like arrays and matrices).
+fn known_kind(kind: VariantKind) -> Option<VariantKind> {
+  match kind {
+    VariantKind::Any | VariantKind::Pointer => None,
+    kind => Some(kind),
+  }
+}
+
+/// Determines if values of the given kind support negation.
+fn is_negatable(kind: VariantKind) -> bool {
+  use VariantKind::*;
+
+  matches!(
+    kind,
+    Bool | I8 | I16 | I32 | I64 | F32 | F64 | Vec2 | Vec3 | Vec4 | Quat
+  )
+}
+
+/// Determines if values of the given kind support an arithmetic operator.
+fn supports_arithmetic(operator: BinaryOp, kind: VariantKind) -> bool {
+  use VariantKind::*;
+
+  let is_integer = matches!(kind, U8 | U16 | U32 | U64 | I8 | I16 | I32 | I64);
+  let is_number = is_integer || matches!(kind, F32 | F64);
+
+  match operator {
+    BinaryOp::Modulo => is_integer,
+    BinaryOp::Divide => is_number,
+    _ => is_number || matches!(kind, Vec2 | Vec3 | Vec4 | Quat | Color | Color32),
+  }
+}
+
+fn operator_symbol(operator: BinaryOp) -> &'static str {
+  match operator {
+    BinaryOp::Add => "+",
+    BinaryOp::Subtract => "-",
+    BinaryOp::Multiply => "*",
+    BinaryOp::Divide => "/",
+    BinaryOp::Modulo => "%",
+    BinaryOp::Equal => "==",
+    BinaryOp::NotEqual => "!=",
+    BinaryOp::LessThan => "<",
+    BinaryOp::LessThanOrEqual => "<=",
+    BinaryOp::GreaterThan => ">",
+    BinaryOp::GreaterThanOrEqual => ">=",
+    BinaryOp::And => "and",
+    BinaryOp::Or => "or",
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lang::lox;
+
+  fn analyse(code: &str) -> AnalysisReport {
+    let module = lox::parse_module(code).unwrap();
+
+    Analyser::for_machine(&VirtualMachine::default()).analyse(&module)
+  }
+
+  #[test]
+  fn it_should_accept_well_formed_scripts() {
+    let report = analyse("var speed = math.sqrt(4.0) * 2.0;\nreturn -speed;");
+
+    assert!(report.diagnostics.is_empty(), "{report}");
+  }
+
+  #[test]
+  fn it_should_report_undefined_names_and_type_errors() {
+    let report = analyse("var a = 1 + 2.5;\nvar b = \"hi\" - 1;\nreturn missing(a, b) and true;\n");
+    let errors = report.errors().map(|error| error.message.as_str()).collect::<Vec<_>>();
+
+    assert!(!report.is_ok());
+    assert_eq!(errors, vec![
+      "`+` can't be applied to a I64 and a F64",
+      "`-` can't be applied to a String",
+      "`missing` is not defined",
+    ]);
+
+    assert_eq!(report.errors().nth(1).unwrap().span.line, 2);
+  }
+
+  #[test]
+  fn it_should_report_calls_to_non_callables() {
+    let report = analyse("return math.pi(1);");
+
+    assert_eq!(report.errors().next().unwrap().message, "a F64 can't be called");
+  }
+
+  #[test]
+  fn it_should_warn_about_unused_variables_and_unreachable_code() {
+    let report = analyse("var unused = 1;\nvar _ignored = 2;\nreturn 3;\nvar late = 4;\nreturn late;");
+    let warnings = report.warnings().collect::<Vec<_>>();
+
+    assert!(report.is_ok());
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].message, "unreachable code");
+    assert_eq!(warnings[0].span.line, 4);
+    assert_eq!(warnings[1].message, "`unused` is assigned but never used");
+    assert_eq!(warnings[1].span.line, 1);
+  }
+}
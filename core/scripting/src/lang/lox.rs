@@ -74,16 +74,84 @@ pub enum Keyword {
 
 /// Parses a list of tokens into an AST [`Expression`].
 pub fn parse(code: &str) -> Result<Expression, ParseError> {
-  Parser::from_code(code).parse_expression()
+  let mut parser = Parser::from_code(code);
+  let expression = parser.parse_expression()?;
+
+  match parser.peek() {
+    Some(_) => Err(ParseError::UnexpectedToken),
+    None => Ok(expression),
+  }
+}
+
+/// Parses a whole script into an AST [`Module`].
+///
+/// Scripts are a series of `var name = value;`, `name = value;`,
+/// `return value;` and `value;` statements.
+pub fn parse_module(code: &str) -> Result<Module, ParseError> {
+  let mut parser = Parser::from_code(code);
+  let mut statements = Vec::new();
+
+  while let Some(start) = parser.peek_span() {
+    let statement = parser.parse_statement()?;
+
+    statements.push((statement, start.to(parser.previous)));
+  }
+
+  Ok(Module(statements))
 }
 
 struct Parser {
-  tokens: Vec<Token>,
+  tokens: Vec<(Token, Span)>,
+  previous: Span,
 }
 
 impl Parser {
   fn from_code(code: &str) -> Self {
-    Self { tokens: tokenise(code) }
+    Self {
+      tokens: tokenise_spanned(code),
+      previous: Span::default(),
+    }
+  }
+
+  fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+    let statement = match self.peek() {
+      Some(Token::Keyword(Keyword::Var)) => {
+        self.advance();
+        self.parse_assignment()?
+      }
+      Some(Token::Keyword(Keyword::Return)) => {
+        self.advance();
+
+        match self.peek() {
+          Some(Token::Semicolon) => Statement::Return(Expression::Literal(().to_variant())),
+          _ => Statement::Return(self.parse_expression()?),
+        }
+      }
+      Some(Token::Identifier(_)) if matches!(self.peek_at(1), Some(Token::Operator(Operator::Equal))) => {
+        self.parse_assignment()?
+      }
+      _ => Statement::Expression(self.parse_expression()?),
+    };
+
+    match self.advance() {
+      Some(Token::Semicolon) => Ok(statement),
+      Some(_) => Err(ParseError::UnexpectedToken),
+      None => Err(ParseError::UnexpectedEndOfFile),
+    }
+  }
+
+  fn parse_assignment(&mut self) -> Result<Statement, ParseError> {
+    let name = match self.advance() {
+      Some(Token::Identifier(name)) => name,
+      Some(_) => return Err(ParseError::UnexpectedToken),
+      None => return Err(ParseError::UnexpectedEndOfFile),
+    };
+
+    match self.advance() {
+      Some(Token::Operator(Operator::Equal)) => Ok(Statement::Assignment(name, self.parse_expression()?)),
+      Some(_) => Err(ParseError::UnexpectedToken),
+      None => Err(ParseError::UnexpectedEndOfFile),
+    }
   }
 
   fn parse_expression(&mut self) -> Result<Expression, ParseError> {
@@ -158,7 +226,36 @@ impl Parser {
       }
     }
 
-    self.parse_primary()
+    self.parse_call()
+  }
+
+  fn parse_call(&mut self) -> Result<Expression, ParseError> {
+    let mut expr = self.parse_primary()?;
+
+    while let Some(Token::LeftParen) = self.peek() {
+      self.advance();
+
+      let mut arguments = Vec::new();
+
+      if let Some(Token::RightParen) = self.peek() {
+        self.advance();
+      } else {
+        loop {
+          arguments.push(self.parse_expression()?);
+
+          match self.advance() {
+            Some(Token::Comma) => continue,
+            Some(Token::RightParen) => break,
+            Some(_) => return Err(ParseError::UnexpectedToken),
+            None => return Err(ParseError::UnexpectedEndOfFile),
+          }
+        }
+      }
+
+      expr = Expression::Call(Box::new(expr), arguments);
+    }
+
+    Ok(expr)
   }
 
   fn parse_primary(&mut self) -> Result<Expression, ParseError> {
@@ -169,23 +266,78 @@ impl Parser {
       Some(Token::Keyword(Keyword::True)) => Ok(Expression::Literal(true.to_variant())),
       Some(Token::Keyword(Keyword::False)) => Ok(Expression::Literal(false.to_variant())),
       Some(Token::Keyword(Keyword::Nil)) => Ok(Expression::Literal(().to_variant())),
+      Some(Token::Identifier(mut name)) => {
+        // dotted names reach into the standard library's modules, e.g.
+        // `math.pi`
+        while let Some(Token::Dot) = self.peek() {
+          self.advance();
+
+          match self.advance() {
+            Some(Token::Identifier(member)) => {
+              name.push('.');
+              name.push_str(&member);
+            }
+            Some(_) => return Err(ParseError::UnexpectedToken),
+            None => return Err(ParseError::UnexpectedEndOfFile),
+          }
+        }
+
+        Ok(Expression::Variable(name))
+      }
+      None => Err(ParseError::UnexpectedEndOfFile),
+      Some(Token::LeftParen) => {
+        let expr = self.parse_expression()?;
+
+        match self.advance() {
+          Some(Token::RightParen) => Ok(expr),
+          Some(_) => Err(ParseError::UnexpectedToken),
+          None => Err(ParseError::UnexpectedEndOfFile),
+        }
+      }
       _ => Err(ParseError::UnexpectedToken),
     }
   }
 
   fn peek(&self) -> Option<&Token> {
-    self.tokens.first()
+    self.peek_at(0)
+  }
+
+  fn peek_at(&self, index: usize) -> Option<&Token> {
+    self.tokens.get(index).map(|(token, _)| token)
+  }
+
+  fn peek_span(&self) -> Option<Span> {
+    self.tokens.first().map(|(_, span)| *span)
   }
 
   fn advance(&mut self) -> Option<Token> {
-    self.tokens.drain(..1).next()
+    if self.tokens.is_empty() {
+      return None;
+    }
+
+    let (token, span) = self.tokens.remove(0);
+
+    self.previous = span;
+
+    Some(token)
   }
 }
 
+#[cfg(test)]
 fn tokenise(code: &str) -> Vec<Token> {
+  tokenise_spanned(code).into_iter().map(|(token, _)| token).collect()
+}
+
+/// Tokenises the code, noting where in the code each token came from.
+fn tokenise_spanned(code: &str) -> Vec<(Token, Span)> {
   let mut tokens = Vec::new();
   let mut characters = code.char_indices().peekable();
 
+  // the byte offsets at which each line starts, to locate tokens by line
+  let line_starts = std::iter::once(0)
+    .chain(code.match_indices('\n').map(|(index, _)| index + 1))
+    .collect::<Vec<_>>();
+
   while let Some((position, character)) = characters.next() {
     let token = match character {
       ' ' | '\t' | '\r' | '\n' => continue,
@@ -230,20 +382,29 @@ fn tokenise(code: &str) -> Vec<Token> {
         None => Token::Operator(Operator::Greater),
       },
 
-      '"' => {
+      '&' => match characters.next_if_eq(&(position + 1, '&')) {
+        Some(_) => Token::Operator(Operator::And),
+        None => Token::Invalid(character.to_string()),
+      },
+      '|' => match characters.next_if_eq(&(position + 1, '|')) {
+        Some(_) => Token::Operator(Operator::Or),
+        None => Token::Invalid(character.to_string()),
+      },
+
+      quote @ ('"' | '\'') => {
         let mut value = String::new();
         let mut last_matched = '\0';
 
         while let Some((_, ch)) = characters.next() {
           last_matched = ch;
-          if ch == '"' {
+          if ch == quote {
             break;
           }
           value.push(ch);
         }
 
         match last_matched {
-          '"' => Token::Literal(Literal::String(value)),
+          ch if ch == quote => Token::Literal(Literal::String(value)),
           _ => Token::Invalid(format!("Unterminated string: {}", value)),
         }
       }
@@ -306,6 +467,8 @@ fn tokenise(code: &str) -> Vec<Token> {
           "class" => Token::Keyword(Keyword::Class),
           "this" => Token::Keyword(Keyword::This),
           "super" => Token::Keyword(Keyword::Super),
+          "and" => Token::Operator(Operator::And),
+          "or" => Token::Operator(Operator::Or),
           _ => Token::Identifier(value),
         }
       }
@@ -313,7 +476,16 @@ fn tokenise(code: &str) -> Vec<Token> {
       _ => Token::Invalid(character.to_string()),
     };
 
-    tokens.push(token);
+    let end = characters.peek().map_or(code.len(), |(index, _)| *index);
+    let line = line_starts.partition_point(|start| *start <= position);
+    let column = code[line_starts[line - 1]..position].chars().count() + 1;
+
+    tokens.push((token, Span {
+      start: position,
+      end,
+      line,
+      column,
+    }));
   }
 
   tokens
@@ -341,13 +513,15 @@ mod tests {
     "+" => vec![Token::Operator(Operator::Plus)],
     "-" => vec![Token::Operator(Operator::Minus)],
     "*" => vec![Token::Operator(Operator::Star)],
-    "/" => vec![Token::Operator(Operator::Slash)]
+    "/" => vec![Token::Operator(Operator::Slash)],
+    "&& ||" => vec![Token::Operator(Operator::And), Token::Operator(Operator::Or)]
   );
 
   tokenise_test!(test_tokenise_literals,
     "123" => vec![Token::Literal(Literal::Integer(123))],
     "3.14" => vec![Token::Literal(Literal::Float(3.14))],
-    "\"hello\"" => vec![Token::Literal(Literal::String("hello".to_string()))]
+    "\"hello\"" => vec![Token::Literal(Literal::String("hello".to_string()))],
+    "'key'" => vec![Token::Literal(Literal::String("key".to_string()))]
   );
 
   tokenise_test!(test_tokenise_keywords,
@@ -398,10 +572,57 @@ mod tests {
     )
   );
 
+  parse_test!(test_parse_variables,
+    "gold >= 10 and (has_key or !locked)" => Expression::Binary(
+      Box::new(Expression::Binary(
+        Box::new(Expression::Variable("gold".to_string())),
+        BinaryOp::GreaterThanOrEqual,
+        Box::new(Expression::Literal(Variant::I64(10)))
+      )),
+      BinaryOp::And,
+      Box::new(Expression::Binary(
+        Box::new(Expression::Variable("has_key".to_string())),
+        BinaryOp::Or,
+        Box::new(Expression::Unary(UnaryOp::Negate, Box::new(Expression::Variable("locked".to_string()))))
+      ))
+    )
+  );
+
+  parse_test!(test_parse_calls,
+    "math.max(1, x)" => Expression::Call(
+      Box::new(Expression::Variable("math.max".to_string())),
+      vec![
+        Expression::Literal(Variant::I64(1)),
+        Expression::Variable("x".to_string()),
+      ]
+    ),
+    "random.float()" => Expression::Call(Box::new(Expression::Variable("random.float".to_string())), vec![])
+  );
+
   parse_test!(test_parse_unary_expressions,
     "-5" => Expression::Unary(
       UnaryOp::Negate,
       Box::new(Expression::Literal(Variant::I64(5)))
     )
   );
+
+  #[test]
+  fn test_parse_module() {
+    let module = parse_module("var speed = 2;\nspeed = speed * 2;\n  return speed;").unwrap();
+    let statements = module.0.iter().map(|(statement, _)| statement).collect::<Vec<_>>();
+
+    assert!(matches!(statements[0], Statement::Assignment(name, _) if name == "speed"));
+    assert!(matches!(statements[1], Statement::Assignment(name, Expression::Binary(..)) if name == "speed"));
+    assert!(matches!(statements[2], Statement::Return(Expression::Variable(name)) if name == "speed"));
+
+    assert_eq!(module.0[2].1, Span {
+      start: 36,
+      end: 49,
+      line: 3,
+      column: 3,
+    });
+
+    assert!(parse_module("var = 2;").is_err());
+    assert!(parse_module("return 1").is_err());
+  }
 }
@@ -1,4 +1,7 @@
 //! Scripting engine for Surreal
 
+pub mod dialogue;
+pub mod expressions;
 pub mod lang;
 pub mod runtime;
+pub mod systems;
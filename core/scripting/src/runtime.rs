@@ -3,6 +3,8 @@
 pub mod compiler;
 pub mod isolates;
 pub mod machine;
+pub mod modules;
+pub mod stdlib;
 
 /// A bytecode instruction for the virtual machine.
 #[derive(Debug, PartialEq)]
@@ -13,5 +15,9 @@ pub enum Opcode {
   Unary(crate::lang::ast::UnaryOp),
   Binary(crate::lang::ast::BinaryOp),
   Literal(common::Variant),
+  LoadGlobal(String),
+  StoreGlobal(String),
+  /// Calls the callable beneath the given number of arguments on the stack.
+  Call(u8),
   Print,
 }
@@ -2,7 +2,9 @@ use crate::{lang::ast::*, runtime::Opcode};
 
 /// An error that occurs when compiling.
 #[derive(Debug)]
-pub enum CompileError {}
+pub enum CompileError {
+  TooManyArguments,
+}
 
 /// Compiles a single expression into a sequence of opcodes
 pub fn compile_expression(expression: &Expression) -> Result<Vec<Opcode>, CompileError> {
@@ -38,11 +40,14 @@ impl Compiler {
         // Compile the expression and push the result onto the stack.
         self.compile_expression(expression)?
       }
+      Statement::Assignment(name, expression) => {
+        self.compile_expression(expression)?;
+        self.instructions.push(Opcode::StoreGlobal(name.clone()));
+      }
       Statement::Return(expression) => {
         self.compile_expression(expression)?;
         self.instructions.push(Opcode::Return);
       }
-      _ => todo!(),
     }
 
     Ok(())
@@ -55,6 +60,9 @@ impl Compiler {
         let value = literal.clone();
         self.instructions.push(Opcode::Literal(value))
       }
+      Expression::Variable(name) => {
+        self.instructions.push(Opcode::LoadGlobal(name.clone()));
+      }
       Expression::Binary(left, operator, right) => {
         self.compile_expression(left)?;
         self.compile_expression(right)?;
@@ -64,6 +72,17 @@ impl Compiler {
         self.compile_expression(value)?;
         self.instructions.push(Opcode::Unary(*operator));
       }
+      Expression::Call(callee, arguments) => {
+        let count = u8::try_from(arguments.len()).map_err(|_| CompileError::TooManyArguments)?;
+
+        self.compile_expression(callee)?;
+
+        for argument in arguments {
+          self.compile_expression(argument)?;
+        }
+
+        self.instructions.push(Opcode::Call(count));
+      }
     }
 
     Ok(())
@@ -1,4 +1,29 @@
-use crate::runtime::machine::{VirtualMachine, VirtualMachineConfig};
+use std::{
+  cell::RefCell,
+  collections::VecDeque,
+  rc::Rc,
+  sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+  thread::JoinHandle,
+};
+
+use common::{Callable, CallbackError, FromStream, TimeSpan, ToStream, Variant, VariantKind};
+
+use crate::runtime::{
+  machine::{VirtualMachine, VirtualMachineConfig},
+  modules::ScriptModule,
+};
+
+/// An error when working with an [`Isolate`].
+#[derive(Debug)]
+pub enum IsolateError {
+  /// The value can't leave the thread it was made on.
+  NotSendable(VariantKind),
+  /// The module couldn't be serialized for the isolate.
+  InvalidModule,
+  /// The isolate's thread has stopped.
+  Disconnected,
+  SpawnFailed(std::io::Error),
+}
 
 /// An isolate is a separate instance of the scripting runtime.
 ///
@@ -9,25 +34,302 @@ use crate::runtime::machine::{VirtualMachine, VirtualMachineConfig};
 /// Isolates are used to run scripts in parallel, and can be used to sandbox
 /// untrusted code. Consider Isolates as the main entry point for running
 /// scripts.
-#[derive(Default)]
+///
+/// Every isolate runs its own [`VirtualMachine`] on a worker thread, so
+/// expensive scripts don't block the frame. The owning thread queues
+/// [`ScriptModule`]s and [`IsolateMessage`]s for it, and polls for
+/// [`IsolateEvent`]s in return. Scripts talk back with two globals:
+/// `isolate.post(value)` sends a message to the owner, and
+/// `isolate.receive()` takes the next message from the owner, or `nil`.
+///
+/// Requests are handled in the order they're queued, so messages posted
+/// before a module is executed are waiting for it when it runs.
 pub struct Isolate {
-  _virtual_machine: VirtualMachine,
+  requests: Sender<IsolateRequest>,
+  events: Receiver<IsolateEvent>,
+  thread: Option<JoinHandle<()>>,
 }
 
 /// Configuration for creating a new isolate.
 #[derive(Default, Debug)]
 pub struct IsolateConfig {
-  _virtual_machine: VirtualMachineConfig,
-  _stack_size: usize,
+  pub virtual_machine: VirtualMachineConfig,
+  /// The stack size of the worker thread, in bytes; zero for the default.
+  pub stack_size: usize,
   _thread_count: usize,
   _memory_limit: usize,
 }
 
+/// A value that can be passed between an [`Isolate`] and its owner.
+///
+/// Plain data only; callables, pointers and arbitrary objects belong to the
+/// thread that made them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IsolateMessage(Variant);
+
+// SAFETY: construction rejects the variants that can't cross threads; the rest
+// own their data, and string names are interned in a thread-safe pool.
+unsafe impl Send for IsolateMessage {}
+
+impl IsolateMessage {
+  /// Wraps a value to send between threads, if it can be sent.
+  pub fn new(value: Variant) -> Result<Self, IsolateError> {
+    match value.kind() {
+      kind @ (VariantKind::Callable | VariantKind::Pointer | VariantKind::Any) => Err(IsolateError::NotSendable(kind)),
+      _ => Ok(Self(value)),
+    }
+  }
+
+  /// The value of the message.
+  pub fn value(&self) -> &Variant {
+    &self.0
+  }
+
+  /// Unwraps the value of the message.
+  pub fn into_value(self) -> Variant {
+    self.0
+  }
+}
+
+/// Something that happened in an [`Isolate`].
+#[derive(Debug, PartialEq)]
+pub enum IsolateEvent {
+  /// A script posted a message.
+  Message(IsolateMessage),
+  /// A module finished, with the value it returned, if any.
+  Completed(Option<IsolateMessage>),
+  /// A module failed to load or run.
+  Failed(String),
+}
+
+/// A request from an owner to its [`Isolate`].
+enum IsolateRequest {
+  Execute(Vec<u8>),
+  Post(IsolateMessage),
+  SetGlobal(String, IsolateMessage),
+  Stop,
+}
+
 impl Isolate {
-  /// Creates a new isolate.
-  pub fn new(config: IsolateConfig) -> Self {
-    Self {
-      _virtual_machine: VirtualMachine::new(config._virtual_machine),
+  /// Creates a new isolate, on a thread of its own.
+  pub fn new(config: IsolateConfig) -> Result<Self, IsolateError> {
+    Self::with_setup(config, |_| {})
+  }
+
+  /// Creates a new isolate, preparing its machine on the worker thread first;
+  /// for example to register native functions.
+  pub fn with_setup(
+    config: IsolateConfig,
+    setup: impl FnOnce(&mut VirtualMachine) + Send + 'static,
+  ) -> Result<Self, IsolateError> {
+    let (requests, request_receiver) = channel();
+    let (event_sender, events) = channel();
+
+    let mut builder = std::thread::Builder::new().name("script-isolate".to_string());
+
+    if config.stack_size > 0 {
+      builder = builder.stack_size(config.stack_size);
+    }
+
+    let thread = builder
+      .spawn(move || run_isolate(config.virtual_machine, setup, request_receiver, event_sender))
+      .map_err(IsolateError::SpawnFailed)?;
+
+    Ok(Self {
+      requests,
+      events,
+      thread: Some(thread),
+    })
+  }
+
+  /// Queues a module to execute.
+  pub fn execute(&self, module: &ScriptModule) -> Result<(), IsolateError> {
+    let bytecode = module.to_bytes().map_err(|_| IsolateError::InvalidModule)?;
+
+    self.send(IsolateRequest::Execute(bytecode))
+  }
+
+  /// Posts a message for scripts to take with `isolate.receive()`.
+  pub fn post(&self, value: Variant) -> Result<(), IsolateError> {
+    self.send(IsolateRequest::Post(IsolateMessage::new(value)?))
+  }
+
+  /// Sets a global in the isolate's machine.
+  pub fn set_global(&self, name: impl Into<String>, value: Variant) -> Result<(), IsolateError> {
+    self.send(IsolateRequest::SetGlobal(name.into(), IsolateMessage::new(value)?))
+  }
+
+  /// Takes the next event, if there is one, without blocking.
+  pub fn poll(&self) -> Option<IsolateEvent> {
+    self.events.try_recv().ok()
+  }
+
+  /// Waits up to the given time for the next event.
+  pub fn wait(&self, timeout: TimeSpan) -> Result<Option<IsolateEvent>, IsolateError> {
+    match self.events.recv_timeout(timeout.into()) {
+      Ok(event) => Ok(Some(event)),
+      Err(RecvTimeoutError::Timeout) => Ok(None),
+      Err(RecvTimeoutError::Disconnected) => Err(IsolateError::Disconnected),
     }
   }
+
+  fn send(&self, request: IsolateRequest) -> Result<(), IsolateError> {
+    self.requests.send(request).map_err(|_| IsolateError::Disconnected)
+  }
+}
+
+impl Drop for Isolate {
+  fn drop(&mut self) {
+    // a script that's still running finishes first
+    self.requests.send(IsolateRequest::Stop).ok();
+
+    if let Some(thread) = self.thread.take() {
+      thread.join().ok();
+    }
+  }
+}
+
+/// The main loop of an isolate's worker thread.
+fn run_isolate(
+  config: VirtualMachineConfig,
+  setup: impl FnOnce(&mut VirtualMachine),
+  requests: Receiver<IsolateRequest>,
+  events: Sender<IsolateEvent>,
+) {
+  let mut machine = VirtualMachine::new(config);
+  let inbox = Rc::new(RefCell::new(VecDeque::<Variant>::new()));
+
+  machine.set_global(
+    "isolate.post",
+    Variant::Callable(Callable::from_function({
+      let events = events.clone();
+
+      move |arguments| {
+        let value = arguments.first().cloned().unwrap_or_default();
+        let message = IsolateMessage::new(value).map_err(|_| CallbackError::InvalidArgument)?;
+
+        events
+          .send(IsolateEvent::Message(message))
+          .map_err(|_| CallbackError::ExecutionError("the isolate's owner has gone".to_string()))?;
+
+        Ok(Variant::Null)
+      }
+    })),
+  );
+
+  machine.set_global(
+    "isolate.receive",
+    Variant::Callable(Callable::from_function({
+      let inbox = inbox.clone();
+
+      move |_| Ok(inbox.borrow_mut().pop_front().unwrap_or_default())
+    })),
+  );
+
+  setup(&mut machine);
+
+  for request in requests {
+    let event = match request {
+      IsolateRequest::Execute(bytecode) => match ScriptModule::from_bytes(&bytecode) {
+        Ok(module) => match module.execute(&mut machine) {
+          Ok(result) => match result.map(IsolateMessage::new).transpose() {
+            Ok(result) => IsolateEvent::Completed(result),
+            Err(error) => IsolateEvent::Failed(format!("{error:?}")),
+          },
+          Err(error) => IsolateEvent::Failed(format!("{error:?}")),
+        },
+        Err(error) => IsolateEvent::Failed(error.to_string()),
+      },
+      IsolateRequest::Post(message) => {
+        inbox.borrow_mut().push_back(message.into_value());
+        continue;
+      }
+      IsolateRequest::SetGlobal(name, message) => {
+        machine.set_global(name, message.into_value());
+        continue;
+      }
+      IsolateRequest::Stop => break,
+    };
+
+    if events.send(event).is_err() {
+      break;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::VirtualPath;
+
+  use super::*;
+
+  fn compile(code: &str) -> ScriptModule {
+    ScriptModule::compile(&VirtualPath::new("local://test.lox"), code).unwrap()
+  }
+
+  fn next_event(isolate: &Isolate) -> IsolateEvent {
+    isolate.wait(TimeSpan::from_seconds(5.)).unwrap().expect("timed out")
+  }
+
+  #[test]
+  fn it_should_execute_modules_on_a_worker_thread() {
+    let isolate = Isolate::with_setup(IsolateConfig::default(), |machine| {
+      machine.set_global("thread", Variant::String(format!("{:?}", std::thread::current().id())));
+    })
+    .unwrap();
+
+    isolate.set_global("seed", Variant::I64(20)).unwrap();
+    isolate.execute(&compile("return seed + 22;")).unwrap();
+
+    assert_eq!(
+      next_event(&isolate),
+      IsolateEvent::Completed(Some(IsolateMessage::new(Variant::I64(42)).unwrap()))
+    );
+
+    isolate.execute(&compile("return thread;")).unwrap();
+
+    let IsolateEvent::Completed(Some(thread)) = next_event(&isolate) else {
+      panic!("expected a result");
+    };
+
+    assert_ne!(
+      thread.into_value(),
+      Variant::String(format!("{:?}", std::thread::current().id()))
+    );
+  }
+
+  #[test]
+  fn it_should_pass_messages_both_ways() {
+    let isolate = Isolate::new(IsolateConfig::default()).unwrap();
+
+    isolate.post(Variant::String("hello".to_string())).unwrap();
+    isolate
+      .execute(&compile(
+        "isolate.post(string.upper(isolate.receive())); return isolate.receive();",
+      ))
+      .unwrap();
+
+    assert_eq!(
+      next_event(&isolate),
+      IsolateEvent::Message(IsolateMessage::new(Variant::String("HELLO".to_string())).unwrap())
+    );
+    assert!(matches!(
+      next_event(&isolate),
+      IsolateEvent::Completed(Some(IsolateMessage(Variant::Null)))
+    ));
+  }
+
+  #[test]
+  fn it_should_report_failures_and_reject_unsendable_values() {
+    let isolate = Isolate::new(IsolateConfig::default()).unwrap();
+
+    isolate.execute(&compile("return missing;")).unwrap();
+
+    assert!(matches!(next_event(&isolate), IsolateEvent::Failed(_)));
+    assert!(matches!(
+      isolate.post(Variant::Callable(Callable::from_function(|_| Ok(Variant::Null)))),
+      Err(IsolateError::NotSendable(VariantKind::Callable))
+    ));
+    assert!(isolate.poll().is_none());
+  }
 }
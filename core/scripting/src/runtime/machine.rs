@@ -1,4 +1,4 @@
-use common::Variant;
+use common::{CallbackError, FastHashMap, TimeSpan, TimeStamp, Variant};
 
 use crate::{
   lang::ast::{BinaryOp, UnaryOp},
@@ -11,23 +11,74 @@ pub enum VirtualMachineError {
   InvalidInstruction,
   InvalidConstantIndex(TableIndex),
   InvalidValueIndex(TableIndex),
+  UndefinedGlobal(String),
+  NotCallable(Variant),
+  CallFailed(CallbackError),
   StackOverflow,
   StackUnderflow,
   CallStackOverflow,
+  /// Execution ran past its [`ExecutionBudget`].
+  BudgetExceeded,
+  /// There's no suspended execution to resume.
+  NotSuspended,
 }
 
 /// Configuration for the [`VirtualMachine`].
 #[derive(Debug)]
 pub struct VirtualMachineConfig {
   pub max_stack_size: usize,
+  pub budget: ExecutionBudget,
 }
 
 impl Default for VirtualMachineConfig {
   fn default() -> Self {
-    Self { max_stack_size: 256 }
+    Self {
+      max_stack_size: 256,
+      budget: ExecutionBudget::default(),
+    }
   }
 }
 
+/// Limits on how much work a single [`VirtualMachine::run`] or
+/// [`VirtualMachine::resume`] may do, so a runaway script can't hang the
+/// frame.
+///
+/// Native calls can't be interrupted; they count as a single instruction, and
+/// the clock is only checked between instructions.
+#[derive(Debug, Default)]
+pub struct ExecutionBudget {
+  /// The most instructions to interpret, if limited.
+  pub instructions: Option<usize>,
+  /// The most time to spend interpreting, if limited.
+  pub time: Option<TimeSpan>,
+  /// What to do when either limit is reached.
+  pub overrun: BudgetOverrun,
+}
+
+/// What a [`VirtualMachine`] does when a script exceeds its
+/// [`ExecutionBudget`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum BudgetOverrun {
+  /// Fail with [`VirtualMachineError::BudgetExceeded`].
+  #[default]
+  Abort,
+  /// Suspend, to be continued with [`VirtualMachine::resume`]; e.g. next
+  /// frame.
+  Yield,
+}
+
+/// The outcome of running instructions on a [`VirtualMachine`].
+#[derive(Debug, PartialEq)]
+pub enum ExecutionStatus {
+  /// The instructions finished, with the value they returned, if any.
+  Completed(Option<Variant>),
+  /// The budget ran out part way through; see [`BudgetOverrun::Yield`].
+  Yielded,
+}
+
+/// How often the clock is checked against a time budget, in instructions.
+const CLOCK_INTERVAL: usize = 64;
+
 /// A bytecode-interpreting Virtual Machine.
 ///
 /// This virtual machine is stack-based and uses a simple instruction set. All
@@ -41,23 +92,40 @@ impl Default for VirtualMachineConfig {
 /// The primary means of value interop is done via [`Variant`]s, which permit
 /// a wide range of core types to be used in the virtual machine (and scripting
 /// languages) efficiently.
-#[derive(Default)]
+///
+/// Every machine starts with the [`stdlib`](super::stdlib) registered as
+/// globals.
 pub struct VirtualMachine {
   stack: Vec<Variant>,
   constants: Table<Variant>,
   locals: Table<Variant>,
+  globals: FastHashMap<String, Variant>,
   config: VirtualMachineConfig,
+  /// Where a yielded execution will resume from.
+  suspended_at: Option<usize>,
+}
+
+impl Default for VirtualMachine {
+  fn default() -> Self {
+    Self::new(VirtualMachineConfig::default())
+  }
 }
 
 impl VirtualMachine {
   /// Creates a new virtual machine with the given configuration.
   pub fn new(config: VirtualMachineConfig) -> Self {
-    VirtualMachine {
+    let mut machine = VirtualMachine {
       stack: Vec::with_capacity(config.max_stack_size),
       constants: Table::default(),
       locals: Table::default(),
+      globals: FastHashMap::default(),
       config,
-    }
+      suspended_at: None,
+    };
+
+    super::stdlib::register(&mut machine);
+
+    machine
   }
 
   /// Pushes a value onto the stack.
@@ -75,15 +143,91 @@ impl VirtualMachine {
     self.stack.pop().ok_or(VirtualMachineError::StackUnderflow)
   }
 
-  /// Executes the given [`Opcode`]s.
+  /// Sets the value of a global variable.
+  pub fn set_global(&mut self, name: impl Into<String>, value: Variant) {
+    self.globals.insert(name.into(), value);
+  }
+
+  /// Iterates over the global variables.
+  pub fn globals(&self) -> impl Iterator<Item = (&str, &Variant)> {
+    self.globals.iter().map(|(name, value)| (name.as_str(), value))
+  }
+
+  /// Removes a global variable.
+  pub fn remove_global(&mut self, name: &str) -> Option<Variant> {
+    self.globals.remove(name)
+  }
+
+  /// Executes the given [`Opcode`]s to completion.
+  ///
+  /// Running out of budget is an error here, even if the machine is
+  /// configured to yield; use [`Self::run`] for scripts that may yield. The
+  /// stack is cleared when a script is aborted.
   pub fn execute(&mut self, instructions: &[Opcode]) -> Result<Option<Variant>, VirtualMachineError> {
-    for instruction in instructions {
+    match self.run(instructions)? {
+      ExecutionStatus::Completed(result) => Ok(result),
+      ExecutionStatus::Yielded => {
+        self.suspended_at = None;
+        self.stack.clear();
+
+        Err(VirtualMachineError::BudgetExceeded)
+      }
+    }
+  }
+
+  /// Runs the given [`Opcode`]s from the start, within the configured
+  /// [`ExecutionBudget`].
+  pub fn run(&mut self, instructions: &[Opcode]) -> Result<ExecutionStatus, VirtualMachineError> {
+    self.run_from(instructions, 0)
+  }
+
+  /// Continues a yielded execution with a fresh budget.
+  ///
+  /// The instructions must be the same as those that yielded.
+  pub fn resume(&mut self, instructions: &[Opcode]) -> Result<ExecutionStatus, VirtualMachineError> {
+    let start = self.suspended_at.ok_or(VirtualMachineError::NotSuspended)?;
+
+    self.run_from(instructions, start)
+  }
+
+  /// Determines if there's a yielded execution waiting to be resumed.
+  pub fn is_suspended(&self) -> bool {
+    self.suspended_at.is_some()
+  }
+
+  fn run_from(&mut self, instructions: &[Opcode], start: usize) -> Result<ExecutionStatus, VirtualMachineError> {
+    let started_at = TimeStamp::now();
+    let budget = &self.config.budget;
+    let (max_instructions, max_time, overrun) = (budget.instructions, budget.time, budget.overrun);
+
+    self.suspended_at = None;
+
+    for (count, (index, instruction)) in instructions.iter().enumerate().skip(start).enumerate() {
+      let is_over_budget = max_instructions.is_some_and(|limit| count >= limit)
+        || max_time.is_some_and(|limit| count % CLOCK_INTERVAL == 0 && TimeStamp::now() - started_at >= limit);
+
+      if is_over_budget {
+        return match overrun {
+          BudgetOverrun::Abort => {
+            // the partly-evaluated state is meaningless to later runs
+            self.stack.clear();
+
+            Err(VirtualMachineError::BudgetExceeded)
+          }
+          BudgetOverrun::Yield => {
+            self.suspended_at = Some(index);
+
+            Ok(ExecutionStatus::Yielded)
+          }
+        };
+      }
+
       if let Some(result) = self.interpret(instruction)? {
-        return Ok(Some(result));
+        return Ok(ExecutionStatus::Completed(Some(result)));
       }
     }
 
-    Ok(None)
+    Ok(ExecutionStatus::Completed(None))
   }
 
   /// Interpret the given [`Opcode`].
@@ -106,6 +250,32 @@ impl VirtualMachine {
       Opcode::Literal(value) => {
         self.push(value.clone())?;
       }
+      Opcode::LoadGlobal(name) => {
+        self.push(self.get_global(name)?.clone())?;
+      }
+      Opcode::StoreGlobal(name) => {
+        let value = self.pop()?;
+
+        self.globals.insert(name.clone(), value);
+      }
+      Opcode::Call(count) => {
+        let start = self
+          .stack
+          .len()
+          .checked_sub(*count as usize + 1)
+          .ok_or(VirtualMachineError::StackUnderflow)?;
+
+        let arguments = self.stack.split_off(start + 1);
+
+        match self.pop()? {
+          Variant::Callable(callable) => {
+            let result = callable.call(&arguments).map_err(VirtualMachineError::CallFailed)?;
+
+            self.push(result)?;
+          }
+          value => return Err(VirtualMachineError::NotCallable(value)),
+        }
+      }
       Opcode::Unary(operator) => match operator {
         UnaryOp::Negate => {
           let value = self.pop()?;
@@ -117,95 +287,111 @@ impl VirtualMachine {
       },
       Opcode::Binary(operator) => match operator {
         BinaryOp::Add => {
-          let a = self.pop()?;
           let b = self.pop()?;
+          let a = self.pop()?;
 
           let result = (a + b).map_err(|_| VirtualMachineError::InvalidInstruction)?;
 
           self.push(result)?;
         }
         BinaryOp::Subtract => {
-          let a = self.pop()?;
           let b = self.pop()?;
+          let a = self.pop()?;
 
           let result = (a - b).map_err(|_| VirtualMachineError::InvalidInstruction)?;
 
           self.push(result)?;
         }
         BinaryOp::Multiply => {
-          let a = self.pop()?;
           let b = self.pop()?;
+          let a = self.pop()?;
 
           let result = (a * b).map_err(|_| VirtualMachineError::InvalidInstruction)?;
 
           self.push(result)?;
         }
         BinaryOp::Divide => {
-          let a = self.pop()?;
           let b = self.pop()?;
+          let a = self.pop()?;
 
           let result = (a / b).map_err(|_| VirtualMachineError::InvalidInstruction)?;
 
           self.push(result)?;
         }
         BinaryOp::Modulo => {
-          let a = self.pop()?;
           let b = self.pop()?;
+          let a = self.pop()?;
 
           let result = (a % b).map_err(|_| VirtualMachineError::InvalidInstruction)?;
 
           self.push(result)?;
         }
         BinaryOp::Equal => {
-          let a = self.pop()?;
           let b = self.pop()?;
+          let a = self.pop()?;
 
           let result = a == b;
 
           self.push(Variant::Bool(result))?;
         }
         BinaryOp::NotEqual => {
-          let a = self.pop()?;
           let b = self.pop()?;
+          let a = self.pop()?;
 
           let result = a != b;
 
           self.push(Variant::Bool(result))?;
         }
         BinaryOp::LessThan => {
-          let a = self.pop()?;
           let b = self.pop()?;
+          let a = self.pop()?;
 
           let result = a < b;
 
           self.push(Variant::Bool(result))?;
         }
         BinaryOp::LessThanOrEqual => {
-          let a = self.pop()?;
           let b = self.pop()?;
+          let a = self.pop()?;
 
           let result = a <= b;
 
           self.push(Variant::Bool(result))?;
         }
         BinaryOp::GreaterThan => {
-          let a = self.pop()?;
           let b = self.pop()?;
+          let a = self.pop()?;
 
           let result = a > b;
 
           self.push(Variant::Bool(result))?;
         }
         BinaryOp::GreaterThanOrEqual => {
-          let a = self.pop()?;
           let b = self.pop()?;
+          let a = self.pop()?;
 
           let result = a >= b;
 
           self.push(Variant::Bool(result))?;
         }
-        BinaryOp::And => todo!(),
-        BinaryOp::Or => todo!(),
+        BinaryOp::And => {
+          let b = self.pop()?;
+          let a = self.pop()?;
+
+          match (a, b) {
+            (Variant::Bool(a), Variant::Bool(b)) => self.push(Variant::Bool(a && b))?,
+            _ => return Err(VirtualMachineError::InvalidInstruction),
+          }
+        }
+        BinaryOp::Or => {
+          let b = self.pop()?;
+          let a = self.pop()?;
+
+          match (a, b) {
+            (Variant::Bool(a), Variant::Bool(b)) => self.push(Variant::Bool(a || b))?,
+            _ => return Err(VirtualMachineError::InvalidInstruction),
+          }
+        }
       },
       Opcode::Print => {
         let value = self.pop()?;
@@ -224,6 +410,13 @@ impl VirtualMachine {
     value.ok_or(VirtualMachineError::InvalidConstantIndex(index))
   }
 
+  /// Gets the global value with the given name.
+  fn get_global(&self, name: &str) -> Result<&Variant, VirtualMachineError> {
+    let value = self.globals.get(name);
+
+    value.ok_or_else(|| VirtualMachineError::UndefinedGlobal(name.to_string()))
+  }
+
   /// Gets the local value at the given index.
   fn get_local(&self, index: TableIndex) -> Result<&Variant, VirtualMachineError> {
     let value = self.locals.get(index);
@@ -297,4 +490,102 @@ mod tests {
 
     assert_eq!(result, Variant::I64(0i64));
   }
+
+  #[test]
+  fn it_should_evaluate_globals_in_operand_order() {
+    let mut virtual_machine = VirtualMachine::default();
+
+    virtual_machine.set_global("gold", Variant::I64(5));
+
+    let instructions = [
+      Opcode::LoadGlobal("gold".to_string()),
+      Opcode::Literal(Variant::I64(10)),
+      Opcode::Binary(BinaryOp::GreaterThanOrEqual),
+      Opcode::Return,
+    ];
+
+    let result = virtual_machine.execute(&instructions).unwrap();
+
+    assert_eq!(result, Some(Variant::Bool(false)));
+    assert!(matches!(
+      virtual_machine.execute(&[Opcode::LoadGlobal("missing".to_string())]),
+      Err(VirtualMachineError::UndefinedGlobal(_))
+    ));
+  }
+
+  #[test]
+  fn it_should_abort_scripts_that_exceed_their_budget() {
+    let mut virtual_machine = VirtualMachine::new(VirtualMachineConfig {
+      budget: ExecutionBudget {
+        instructions: Some(3),
+        ..ExecutionBudget::default()
+      },
+      ..VirtualMachineConfig::default()
+    });
+
+    let instructions = [
+      Opcode::Literal(Variant::I64(1)),
+      Opcode::Literal(Variant::I64(2)),
+      Opcode::Binary(BinaryOp::Add),
+      Opcode::Return,
+    ];
+
+    assert!(matches!(
+      virtual_machine.execute(&instructions),
+      Err(VirtualMachineError::BudgetExceeded)
+    ));
+    assert!(matches!(
+      virtual_machine.pop(),
+      Err(VirtualMachineError::StackUnderflow)
+    ));
+  }
+
+  #[test]
+  fn it_should_yield_and_resume_when_out_of_budget() {
+    let mut virtual_machine = VirtualMachine::new(VirtualMachineConfig {
+      budget: ExecutionBudget {
+        instructions: Some(2),
+        overrun: BudgetOverrun::Yield,
+        ..ExecutionBudget::default()
+      },
+      ..VirtualMachineConfig::default()
+    });
+
+    let instructions = [
+      Opcode::Literal(Variant::I64(1)),
+      Opcode::Literal(Variant::I64(2)),
+      Opcode::Binary(BinaryOp::Add),
+      Opcode::Literal(Variant::I64(3)),
+      Opcode::Binary(BinaryOp::Multiply),
+      Opcode::Return,
+    ];
+
+    assert_eq!(virtual_machine.run(&instructions).unwrap(), ExecutionStatus::Yielded);
+    assert!(virtual_machine.is_suspended());
+    assert_eq!(virtual_machine.resume(&instructions).unwrap(), ExecutionStatus::Yielded);
+    assert_eq!(
+      virtual_machine.resume(&instructions).unwrap(),
+      ExecutionStatus::Completed(Some(Variant::I64(9)))
+    );
+    assert!(matches!(
+      virtual_machine.resume(&instructions),
+      Err(VirtualMachineError::NotSuspended)
+    ));
+  }
+
+  #[test]
+  fn it_should_abort_scripts_that_run_out_of_time() {
+    let mut virtual_machine = VirtualMachine::new(VirtualMachineConfig {
+      budget: ExecutionBudget {
+        time: Some(TimeSpan::ZERO),
+        ..ExecutionBudget::default()
+      },
+      ..VirtualMachineConfig::default()
+    });
+
+    assert!(matches!(
+      virtual_machine.execute(&[Opcode::NoOp]),
+      Err(VirtualMachineError::BudgetExceeded)
+    ));
+  }
 }
@@ -0,0 +1,290 @@
+//! Compiled script modules, and the importer that produces them.
+//!
+//! Source scripts are parsed by the language frontend matching their
+//! extension and compiled to bytecode. A [`ScriptImporter`] does this when
+//! they're packed or loaded, so the game only ever sees the compiled form:
+//!
+//! ```rust,ignore
+//! AssetDatabase::instance().register_importer(ScriptImporter);
+//!
+//! let module = AssetDatabase::load::<ScriptModule>("enemy.bsc")?;
+//! let result = module.execute(&mut machine)?;
+//! ```
+
+use common::{
+  AssetError, AssetImporter, FromStream, ImportedAsset, InputStream, OutputStream, StreamError, ToStream, Variant,
+  VirtualPath,
+};
+
+use super::{
+  compiler::{compile_statements, CompileError},
+  machine::{VirtualMachine, VirtualMachineError},
+  Opcode,
+};
+use crate::lang::{ast::*, lox};
+
+/// The magic number at the start of a serialized [`ScriptModule`].
+const MODULE_MAGIC: u32 = 0x43534253; // 'SBSC'
+
+/// The current version of the serialized [`ScriptModule`] format.
+const MODULE_VERSION: u16 = 1;
+
+/// An error that occurs when building a [`ScriptModule`] from source.
+#[derive(Debug)]
+pub enum ScriptModuleError {
+  UnsupportedLanguage(String),
+  ParseFailed(lox::ParseError),
+  CompileFailed(CompileError),
+}
+
+/// A compiled script, ready to execute on a [`VirtualMachine`].
+#[derive(Debug, Default, PartialEq)]
+pub struct ScriptModule {
+  instructions: Vec<Opcode>,
+}
+
+impl ScriptModule {
+  /// Compiles a module from source, picking the language by the path's
+  /// extension.
+  pub fn compile(path: &VirtualPath, code: &str) -> Result<Self, ScriptModuleError> {
+    let module = match path.extension() {
+      "lox" => lox::parse_module(code).map_err(ScriptModuleError::ParseFailed)?,
+      extension => return Err(ScriptModuleError::UnsupportedLanguage(extension.to_string())),
+    };
+
+    Self::from_module(&module)
+  }
+
+  /// Compiles a module from its AST.
+  pub fn from_module(module: &Module) -> Result<Self, ScriptModuleError> {
+    let statements = module
+      .0
+      .iter()
+      .map(|(statement, _)| statement.clone())
+      .collect::<Vec<_>>();
+    let instructions = compile_statements(&statements).map_err(ScriptModuleError::CompileFailed)?;
+
+    Ok(Self { instructions })
+  }
+
+  /// The compiled bytecode.
+  pub fn instructions(&self) -> &[Opcode] {
+    &self.instructions
+  }
+
+  /// Executes the module, returning the value of its `return` statement.
+  pub fn execute(&self, machine: &mut VirtualMachine) -> Result<Option<Variant>, VirtualMachineError> {
+    machine.execute(&self.instructions)
+  }
+}
+
+impl FromStream for ScriptModule {
+  async fn from_stream_async(stream: &mut dyn InputStream) -> Result<Self, Self::Error> {
+    if stream.read_u32()? != MODULE_MAGIC || stream.read_u16()? != MODULE_VERSION {
+      return Err(StreamError::InvalidData);
+    }
+
+    let count = stream.read_u32()?;
+    let mut instructions = Vec::with_capacity(count as usize);
+
+    for _ in 0..count {
+      instructions.push(read_opcode(stream)?);
+    }
+
+    Ok(Self { instructions })
+  }
+}
+
+impl ToStream for ScriptModule {
+  fn to_stream(&self, stream: &mut dyn OutputStream) -> Result<(), Self::Error> {
+    stream.write_u32(MODULE_MAGIC)?;
+    stream.write_u16(MODULE_VERSION)?;
+    stream.write_u32(self.instructions.len() as u32)?;
+
+    for instruction in &self.instructions {
+      write_opcode(stream, instruction)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Imports script sources as compiled [`ScriptModule`]s.
+///
+/// Already-compiled `.bsc` modules are checked and passed through as-is.
+pub struct ScriptImporter;
+
+impl AssetImporter for ScriptImporter {
+  fn name(&self) -> &str {
+    "script"
+  }
+
+  fn extensions(&self) -> &[&str] {
+    &["lox", "bsc"]
+  }
+
+  fn import(&self, path: &VirtualPath, data: &[u8]) -> Result<ImportedAsset, AssetError> {
+    let module = match data.starts_with(&MODULE_MAGIC.to_le_bytes()) {
+      true => ScriptModule::from_bytes(data).map_err(|error| AssetError::ImportFailed(format!("{path:?}: {error}")))?,
+      false => {
+        let code = std::str::from_utf8(data).map_err(|error| AssetError::ImportFailed(format!("{path:?}: {error}")))?;
+
+        ScriptModule::compile(path, code).map_err(|error| AssetError::ImportFailed(format!("{path:?}: {error:?}")))?
+      }
+    };
+
+    Ok(ImportedAsset {
+      data: module
+        .to_bytes()
+        .map_err(|error| AssetError::ImportFailed(format!("{path:?}: {error}")))?,
+      references: Vec::new(),
+    })
+  }
+}
+
+fn read_opcode(stream: &mut dyn InputStream) -> Result<Opcode, StreamError> {
+  Ok(match stream.read_u8()? {
+    0 => Opcode::NoOp,
+    1 => Opcode::Return,
+    2 => Opcode::Constant(stream.read_u16()?),
+    3 => Opcode::Unary(match stream.read_u8()? {
+      0 => UnaryOp::Negate,
+      _ => return Err(StreamError::InvalidData),
+    }),
+    4 => Opcode::Binary(
+      *BINARY_OPS
+        .get(stream.read_u8()? as usize)
+        .ok_or(StreamError::InvalidData)?,
+    ),
+    5 => Opcode::Literal(read_literal(stream)?),
+    6 => Opcode::LoadGlobal(stream.read_string()?),
+    7 => Opcode::StoreGlobal(stream.read_string()?),
+    8 => Opcode::Call(stream.read_u8()?),
+    9 => Opcode::Print,
+    _ => return Err(StreamError::InvalidData),
+  })
+}
+
+fn write_opcode(stream: &mut dyn OutputStream, opcode: &Opcode) -> Result<(), StreamError> {
+  match opcode {
+    Opcode::NoOp => stream.write_u8(0),
+    Opcode::Return => stream.write_u8(1),
+    Opcode::Constant(index) => {
+      stream.write_u8(2)?;
+      stream.write_u16(*index)
+    }
+    Opcode::Unary(UnaryOp::Negate) => {
+      stream.write_u8(3)?;
+      stream.write_u8(0)
+    }
+    Opcode::Binary(operator) => {
+      let index = BINARY_OPS.iter().position(|it| it == operator).unwrap();
+
+      stream.write_u8(4)?;
+      stream.write_u8(index as u8)
+    }
+    Opcode::Literal(value) => {
+      stream.write_u8(5)?;
+      write_literal(stream, value)
+    }
+    Opcode::LoadGlobal(name) => {
+      stream.write_u8(6)?;
+      stream.write_string(name)
+    }
+    Opcode::StoreGlobal(name) => {
+      stream.write_u8(7)?;
+      stream.write_string(name)
+    }
+    Opcode::Call(count) => {
+      stream.write_u8(8)?;
+      stream.write_u8(*count)
+    }
+    Opcode::Print => stream.write_u8(9),
+  }
+}
+
+/// Binary operators, in the order they're numbered in bytecode.
+const BINARY_OPS: [BinaryOp; 13] = [
+  BinaryOp::Add,
+  BinaryOp::Subtract,
+  BinaryOp::Multiply,
+  BinaryOp::Divide,
+  BinaryOp::Modulo,
+  BinaryOp::Equal,
+  BinaryOp::NotEqual,
+  BinaryOp::LessThan,
+  BinaryOp::LessThanOrEqual,
+  BinaryOp::GreaterThan,
+  BinaryOp::GreaterThanOrEqual,
+  BinaryOp::And,
+  BinaryOp::Or,
+];
+
+/// Reads a literal; only the kinds of value the frontends produce are stored.
+fn read_literal(stream: &mut dyn InputStream) -> Result<Variant, StreamError> {
+  Ok(match stream.read_u8()? {
+    0 => Variant::Null,
+    1 => Variant::Bool(stream.read_u8()? != 0),
+    2 => Variant::I64(stream.read_i64()?),
+    3 => Variant::F64(stream.read_f64()?),
+    4 => Variant::String(stream.read_string()?),
+    _ => return Err(StreamError::InvalidData),
+  })
+}
+
+fn write_literal(stream: &mut dyn OutputStream, value: &Variant) -> Result<(), StreamError> {
+  match value {
+    Variant::Null => stream.write_u8(0),
+    Variant::Bool(value) => {
+      stream.write_u8(1)?;
+      stream.write_u8(*value as u8)
+    }
+    Variant::I64(value) => {
+      stream.write_u8(2)?;
+      stream.write_i64(*value)
+    }
+    Variant::F64(value) => {
+      stream.write_u8(3)?;
+      stream.write_f64(*value)
+    }
+    Variant::String(value) => {
+      stream.write_u8(4)?;
+      stream.write_string(value)
+    }
+    _ => Err(StreamError::InvalidData),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_compile_and_execute_modules() {
+    let path = VirtualPath::new("local://enemy.lox");
+    let module = ScriptModule::compile(&path, "var health = 10;\nhealth = health - 4;\nreturn health > 5;").unwrap();
+
+    let mut machine = VirtualMachine::default();
+
+    assert_eq!(module.execute(&mut machine).unwrap(), Some(Variant::Bool(true)));
+    assert!(matches!(
+      ScriptModule::compile(&VirtualPath::new("local://enemy.py"), ""),
+      Err(ScriptModuleError::UnsupportedLanguage(_))
+    ));
+  }
+
+  #[test]
+  fn it_should_round_trip_modules_through_bytecode() {
+    let path = VirtualPath::new("local://enemy.lox");
+    let module = ScriptModule::compile(
+      &path,
+      r#"var name = string.upper("imp"); return -1.5 * 2.0 != 3.0 or false;"#,
+    )
+    .unwrap();
+
+    let imported = ScriptImporter.import(&path, &module.to_bytes().unwrap()).unwrap();
+
+    assert_eq!(ScriptModule::from_bytes(&imported.data).unwrap(), module);
+    assert!(ScriptImporter.import(&path, b"var = ;").is_err());
+  }
+}
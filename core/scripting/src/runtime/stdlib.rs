@@ -0,0 +1,486 @@
+//! The standard library for scripts.
+//!
+//! Registered as globals on every [`VirtualMachine`], grouped into modules by
+//! dotted names (e.g. `math.sqrt(2)` or `string.upper(name)`):
+//!
+//! * `math`: constants, rounding, powers, trigonometry and interpolation.
+//! * `vec2`, `vec3`, `vec4`: constructors, and `length`, `normalize`, `dot`,
+//!   `distance` and `lerp` for each.
+//! * `mat4`: transforms built from [`Mat4`], applied to points.
+//! * `string`: length, case, trimming, searching, splitting and joining.
+//! * `array`: growable arrays of values; see [`ScriptArray`].
+//! * `random`: seedable random numbers.
+
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use common::{downcast_arc, Callable, Callback, CallbackError, Mat4, Random, Variant, Vec2, Vec3, Vec4};
+
+use super::machine::VirtualMachine;
+
+/// Registers the standard library on the given machine.
+pub fn register(machine: &mut VirtualMachine) {
+  register_math(machine);
+  register_vectors(machine);
+  register_matrices(machine);
+  register_strings(machine);
+  register_arrays(machine);
+  register_random(machine);
+}
+
+/// A growable array of values, shared by reference between scripts.
+///
+/// Arrays are passed around as [`Variant::Any`], so changes made through one
+/// reference are seen by all of them.
+#[derive(Default, Debug)]
+pub struct ScriptArray {
+  values: RefCell<Vec<Variant>>,
+}
+
+impl ScriptArray {
+  /// Wraps the given values in a new array.
+  // variants hold `Arc<dyn Any>`, but scripts never leave their thread
+  #[allow(clippy::arc_with_non_send_sync)]
+  pub fn to_variant(values: Vec<Variant>) -> Variant {
+    Variant::Any(Arc::new(Self {
+      values: RefCell::new(values),
+    }))
+  }
+
+  /// Gets the array held by the given variant, if it holds one.
+  pub fn from_variant(value: &Variant) -> Option<Arc<Self>> {
+    match value {
+      Variant::Any(value) => downcast_arc(value.clone()).ok(),
+      _ => None,
+    }
+  }
+
+  /// A copy of the array's values.
+  pub fn values(&self) -> Vec<Variant> {
+    self.values.borrow().clone()
+  }
+
+  /// A copy of the value at the given index, if there is one.
+  pub fn get(&self, index: usize) -> Option<Variant> {
+    self.values.borrow().get(index).cloned()
+  }
+
+  /// Replaces the value at the given index, if there is one.
+  pub fn set(&self, index: usize, value: Variant) -> bool {
+    match self.values.borrow_mut().get_mut(index) {
+      Some(slot) => {
+        *slot = value;
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+/// Registers a function under the given name.
+fn function<R>(machine: &mut VirtualMachine, name: &str, callback: impl Callback<R> + 'static) {
+  machine.set_global(name, Variant::Callable(Callable::from_callback(callback)));
+}
+
+/// Registers a function that takes its arguments as raw [`Variant`]s.
+fn variadic(
+  machine: &mut VirtualMachine,
+  name: &str,
+  body: impl Fn(&[Variant]) -> Result<Variant, CallbackError> + 'static,
+) {
+  machine.set_global(name, Variant::Callable(Callable::from_function(body)));
+}
+
+/// Reads the argument at the given index.
+fn argument(arguments: &[Variant], index: usize) -> Result<&Variant, CallbackError> {
+  arguments.get(index).ok_or(CallbackError::InvalidArgument)
+}
+
+/// Reads the array argument at the given index.
+fn array_argument(arguments: &[Variant], index: usize) -> Result<Arc<ScriptArray>, CallbackError> {
+  ScriptArray::from_variant(argument(arguments, index)?).ok_or(CallbackError::InvalidArgument)
+}
+
+/// Reads the integer argument at the given index.
+fn integer_argument(arguments: &[Variant], index: usize) -> Result<i64, CallbackError> {
+  common::FromVariant::from_variant(argument(arguments, index)?.clone()).map_err(|_| CallbackError::InvalidArgument)
+}
+
+/// Reads the matrix argument at the given index.
+fn matrix_argument(arguments: &[Variant], index: usize) -> Result<Mat4, CallbackError> {
+  match argument(arguments, index)? {
+    Variant::Any(value) => value
+      .downcast_ref::<Mat4>()
+      .copied()
+      .ok_or(CallbackError::InvalidArgument),
+    _ => Err(CallbackError::InvalidArgument),
+  }
+}
+
+fn matrix(value: Mat4) -> Variant {
+  Variant::Any(Arc::new(value))
+}
+
+fn register_math(machine: &mut VirtualMachine) {
+  machine.set_global("math.pi", Variant::F64(std::f64::consts::PI));
+  machine.set_global("math.tau", Variant::F64(std::f64::consts::TAU));
+
+  function(machine, "math.abs", |x: f64| x.abs());
+  function(machine, "math.sign", |x: f64| x.signum());
+  function(machine, "math.floor", |x: f64| x.floor());
+  function(machine, "math.ceil", |x: f64| x.ceil());
+  function(machine, "math.round", |x: f64| x.round());
+  function(machine, "math.sqrt", |x: f64| x.sqrt());
+  function(machine, "math.pow", |x: f64, y: f64| x.powf(y));
+  function(machine, "math.sin", |x: f64| x.sin());
+  function(machine, "math.cos", |x: f64| x.cos());
+  function(machine, "math.tan", |x: f64| x.tan());
+  function(machine, "math.atan2", |y: f64, x: f64| y.atan2(x));
+  function(machine, "math.min", |a: f64, b: f64| a.min(b));
+  function(machine, "math.max", |a: f64, b: f64| a.max(b));
+  function(machine, "math.clamp", |x: f64, min: f64, max: f64| x.clamp(min, max));
+  function(machine, "math.lerp", |a: f64, b: f64, t: f64| a + (b - a) * t);
+}
+
+fn register_vectors(machine: &mut VirtualMachine) {
+  function(machine, "vec2", |x: f32, y: f32| Vec2::new(x, y));
+  function(machine, "vec3", |x: f32, y: f32, z: f32| Vec3::new(x, y, z));
+  function(machine, "vec4", |x: f32, y: f32, z: f32, w: f32| Vec4::new(x, y, z, w));
+
+  macro_rules! vector_functions {
+    ($module:literal, $type:ty) => {
+      function(machine, concat!($module, ".length"), |a: $type| a.length());
+      function(machine, concat!($module, ".normalize"), |a: $type| {
+        a.normalize_or_zero()
+      });
+      function(machine, concat!($module, ".dot"), |a: $type, b: $type| a.dot(b));
+      function(machine, concat!($module, ".distance"), |a: $type, b: $type| {
+        a.distance(b)
+      });
+      function(machine, concat!($module, ".lerp"), |a: $type, b: $type, t: f32| {
+        a.lerp(b, t)
+      });
+    };
+  }
+
+  vector_functions!("vec2", Vec2);
+  vector_functions!("vec3", Vec3);
+  vector_functions!("vec4", Vec4);
+}
+
+fn register_matrices(machine: &mut VirtualMachine) {
+  variadic(machine, "mat4.identity", |_| Ok(matrix(Mat4::IDENTITY)));
+  function(machine, "mat4.translation", |offset: Vec3| {
+    matrix(Mat4::from_translation(offset))
+  });
+  function(machine, "mat4.scale", |scale: Vec3| matrix(Mat4::from_scale(scale)));
+  function(machine, "mat4.rotation_z", |angle: f32| {
+    matrix(Mat4::from_rotation_z(angle))
+  });
+
+  variadic(machine, "mat4.mul", |arguments| {
+    Ok(matrix(matrix_argument(arguments, 0)? * matrix_argument(arguments, 1)?))
+  });
+
+  variadic(machine, "mat4.transform", |arguments| {
+    let point =
+      common::FromVariant::from_variant(argument(arguments, 1)?.clone()).map_err(|_| CallbackError::InvalidArgument)?;
+
+    Ok(Variant::Vec3(matrix_argument(arguments, 0)?.transform_point3(point)))
+  });
+}
+
+fn register_strings(machine: &mut VirtualMachine) {
+  function(machine, "string.len", |value: String| value.chars().count() as i64);
+  function(machine, "string.upper", |value: String| value.to_uppercase());
+  function(machine, "string.lower", |value: String| value.to_lowercase());
+  function(machine, "string.trim", |value: String| value.trim().to_string());
+  function(machine, "string.contains", |value: String, pattern: String| {
+    value.contains(&pattern)
+  });
+  function(machine, "string.starts_with", |value: String, pattern: String| {
+    value.starts_with(&pattern)
+  });
+  function(machine, "string.ends_with", |value: String, pattern: String| {
+    value.ends_with(&pattern)
+  });
+  function(machine, "string.replace", |value: String, from: String, to: String| {
+    value.replace(&from, &to)
+  });
+  function(machine, "string.substring", |value: String, start: i64, length: i64| {
+    value
+      .chars()
+      .skip(start.max(0) as usize)
+      .take(length.max(0) as usize)
+      .collect::<String>()
+  });
+
+  variadic(machine, "string.from", |arguments| {
+    Ok(Variant::String(match argument(arguments, 0)? {
+      Variant::String(value) => value.clone(),
+      Variant::Null => "nil".to_string(),
+      Variant::Bool(value) => value.to_string(),
+      Variant::I64(value) => value.to_string(),
+      Variant::F64(value) => value.to_string(),
+      value => format!("{value:?}"),
+    }))
+  });
+
+  function(machine, "string.split", |value: String, separator: String| {
+    ScriptArray::to_variant(
+      value
+        .split(&separator)
+        .map(|part| Variant::String(part.to_string()))
+        .collect(),
+    )
+  });
+
+  variadic(machine, "string.join", |arguments| {
+    let separator = match arguments.get(1) {
+      Some(Variant::String(separator)) => separator.as_str(),
+      None => "",
+      Some(_) => return Err(CallbackError::InvalidArgument),
+    };
+
+    let parts = array_argument(arguments, 0)?
+      .values
+      .borrow()
+      .iter()
+      .map(|value| match value {
+        Variant::String(value) => Ok(value.clone()),
+        _ => Err(CallbackError::InvalidArgument),
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Variant::String(parts.join(separator)))
+  });
+}
+
+fn register_arrays(machine: &mut VirtualMachine) {
+  variadic(machine, "array.new", |arguments| {
+    Ok(ScriptArray::to_variant(arguments.to_vec()))
+  });
+
+  variadic(machine, "array.len", |arguments| {
+    Ok(Variant::I64(array_argument(arguments, 0)?.values.borrow().len() as i64))
+  });
+
+  variadic(machine, "array.push", |arguments| {
+    array_argument(arguments, 0)?
+      .values
+      .borrow_mut()
+      .push(argument(arguments, 1)?.clone());
+
+    Ok(Variant::Null)
+  });
+
+  variadic(machine, "array.pop", |arguments| {
+    Ok(
+      array_argument(arguments, 0)?
+        .values
+        .borrow_mut()
+        .pop()
+        .unwrap_or_default(),
+    )
+  });
+
+  variadic(machine, "array.get", |arguments| {
+    let array = array_argument(arguments, 0)?;
+    let index = integer_argument(arguments, 1)?;
+    let values = array.values.borrow();
+
+    Ok(
+      usize::try_from(index)
+        .ok()
+        .and_then(|index| values.get(index).cloned())
+        .unwrap_or_default(),
+    )
+  });
+
+  variadic(machine, "array.set", |arguments| {
+    let array = array_argument(arguments, 0)?;
+    let index = integer_argument(arguments, 1)?;
+    let mut values = array.values.borrow_mut();
+    let slot = usize::try_from(index)
+      .ok()
+      .and_then(|index| values.get_mut(index))
+      .ok_or_else(|| CallbackError::ExecutionError(format!("Index {index} is out of bounds")))?;
+
+    *slot = argument(arguments, 2)?.clone();
+
+    Ok(Variant::Null)
+  });
+
+  variadic(machine, "array.remove", |arguments| {
+    let array = array_argument(arguments, 0)?;
+    let index = integer_argument(arguments, 1)?;
+    let mut values = array.values.borrow_mut();
+
+    match usize::try_from(index).ok().filter(|index| *index < values.len()) {
+      Some(index) => Ok(values.remove(index)),
+      None => Err(CallbackError::ExecutionError(format!("Index {index} is out of bounds"))),
+    }
+  });
+
+  variadic(machine, "array.contains", |arguments| {
+    let value = argument(arguments, 1)?;
+
+    Ok(Variant::Bool(
+      array_argument(arguments, 0)?.values.borrow().contains(value),
+    ))
+  });
+}
+
+fn register_random(machine: &mut VirtualMachine) {
+  let random = Rc::new(RefCell::new(Random::with_thread_local_seed()));
+
+  {
+    let random = random.clone();
+
+    function(machine, "random.seed", move |seed: u64| {
+      *random.borrow_mut() = Random::with_seed(seed);
+    });
+  }
+
+  {
+    let random = random.clone();
+
+    function(machine, "random.float", move || random.borrow_mut().next_f64());
+  }
+
+  {
+    let random = random.clone();
+
+    function(machine, "random.range", move |min: f64, max: f64| {
+      min + (max - min) * random.borrow_mut().next_f64()
+    });
+  }
+
+  {
+    let random = random.clone();
+
+    function(machine, "random.int", move |min: i64, max: i64| {
+      if max <= min {
+        return min;
+      }
+
+      let span = (max - min) as u64 + 1;
+
+      min + (random.borrow_mut().next_u64() % span) as i64
+    });
+  }
+
+  {
+    let random = random.clone();
+
+    function(machine, "random.chance", move |probability: f64| {
+      random.borrow_mut().next_f64() < probability
+    });
+  }
+
+  variadic(machine, "random.choose", move |arguments| {
+    let array = array_argument(arguments, 0)?;
+    let values = array.values.borrow();
+
+    Ok(random.borrow_mut().choose(values.iter().cloned()).unwrap_or_default())
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    lang::lox,
+    runtime::{compiler::compile_expression, machine::VirtualMachineError},
+  };
+
+  fn evaluate(machine: &mut VirtualMachine, code: &str) -> Result<Option<Variant>, VirtualMachineError> {
+    let mut instructions = compile_expression(&lox::parse(code).unwrap()).unwrap();
+
+    instructions.push(crate::runtime::Opcode::Return);
+
+    machine.execute(&instructions)
+  }
+
+  #[test]
+  fn it_should_call_math_and_vector_functions() {
+    let mut machine = VirtualMachine::default();
+
+    machine.set_global("x", Variant::F64(9.));
+
+    assert_eq!(
+      evaluate(&mut machine, "math.sqrt(x) + 1.0").unwrap(),
+      Some(Variant::F64(4.))
+    );
+    assert_eq!(
+      evaluate(&mut machine, "math.clamp(15, 0, 10)").unwrap(),
+      Some(Variant::F64(10.))
+    );
+    assert_eq!(
+      evaluate(&mut machine, "vec2.length(vec2(3, 4))").unwrap(),
+      Some(Variant::F32(5.))
+    );
+    assert_eq!(
+      evaluate(
+        &mut machine,
+        "mat4.transform(mat4.translation(vec3(1, 2, 3)), vec3(1, 1, 1))"
+      )
+      .unwrap(),
+      Some(Variant::Vec3(Vec3::new(2., 3., 4.)))
+    );
+  }
+
+  #[test]
+  fn it_should_manipulate_strings_and_arrays() {
+    let mut machine = VirtualMachine::default();
+
+    assert_eq!(
+      evaluate(&mut machine, r#"string.upper(string.trim("  hello "))"#).unwrap(),
+      Some(Variant::String("HELLO".to_string()))
+    );
+    assert_eq!(
+      evaluate(&mut machine, r#"string.join(string.split("a,b,c", ","), "-")"#).unwrap(),
+      Some(Variant::String("a-b-c".to_string()))
+    );
+
+    let items = evaluate(&mut machine, "array.new(1, 2)").unwrap().unwrap();
+
+    machine.set_global("items", items);
+
+    evaluate(&mut machine, "array.push(items, 3)").unwrap();
+
+    assert_eq!(
+      evaluate(&mut machine, "array.len(items)").unwrap(),
+      Some(Variant::I64(3))
+    );
+    assert_eq!(
+      evaluate(&mut machine, "array.get(items, 2)").unwrap(),
+      Some(Variant::I64(3))
+    );
+    assert_eq!(
+      evaluate(&mut machine, "array.contains(items, 5)").unwrap(),
+      Some(Variant::Bool(false))
+    );
+    assert!(matches!(
+      evaluate(&mut machine, "array.remove(items, 7)"),
+      Err(VirtualMachineError::CallFailed(_))
+    ));
+  }
+
+  #[test]
+  fn it_should_generate_seeded_random_numbers() {
+    let mut machine = VirtualMachine::default();
+
+    evaluate(&mut machine, "random.seed(42)").unwrap();
+    let first = evaluate(&mut machine, "random.int(1, 6)").unwrap().unwrap();
+
+    evaluate(&mut machine, "random.seed(42)").unwrap();
+    let second = evaluate(&mut machine, "random.int(1, 6)").unwrap().unwrap();
+
+    assert_eq!(first, second);
+    assert!(matches!(first, Variant::I64(1..=6)));
+    assert!(matches!(
+      evaluate(&mut machine, "math.pi(1)"),
+      Err(VirtualMachineError::NotCallable(_))
+    ));
+  }
+}
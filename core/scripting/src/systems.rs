@@ -0,0 +1,408 @@
+//! Systems written in scripts, run by a [`Schedule`] each frame.
+//!
+//! Scripts declare systems through `system.register`, naming the components
+//! they read and the ones they write (prefixed with `mut`):
+//!
+//! ```text
+//! system.register("movement", array.new("mut Position", "Velocity"), fn(delta_time, rows) {
+//!   ...
+//! })
+//! ```
+//!
+//! Each frame the function is called with the delta time and an array of
+//! rows, one per matching entity, each an array of the entity followed by
+//! its components in the declared order. Components declared with `mut` are
+//! written back from the rows once the function returns.
+//!
+//! The game registers the component types scripts may use, then moves the
+//! systems scripts declared into its schedule:
+//!
+//! ```rust,ignore
+//! let mut components = ScriptComponents::default();
+//!
+//! components.register::<Position>("Position");
+//! components.register::<Velocity>("Velocity");
+//!
+//! let systems = ScriptSystemRegistry::default();
+//!
+//! systems.install(&mut machine);
+//! machine.execute(&script)?;
+//! systems.drain_into(&mut schedule, &components)?;
+//! ```
+
+use std::{any::TypeId, cell::RefCell, rc::Rc};
+
+use common::{ArenaIndex, Callable, CallbackError, FastHashMap, FromVariant, ToVariant, Variant, VariantError};
+use scenes::{Schedule, System, SystemAccess, World, WorldEntity};
+
+use crate::runtime::{machine::VirtualMachine, stdlib::ScriptArray};
+
+/// An error with a script system.
+#[derive(Debug)]
+pub enum ScriptSystemError {
+  /// A system named a component that wasn't registered.
+  UnknownComponent(String),
+  /// `system.register` was called with the wrong arguments.
+  InvalidRegistration,
+}
+
+/// Converts a component between the world and script values.
+#[derive(Copy, Clone)]
+struct ComponentMarshaller {
+  type_id: TypeId,
+  read: fn(&World, WorldEntity) -> Option<Variant>,
+  write: fn(&mut World, WorldEntity, Variant) -> Result<(), VariantError>,
+}
+
+fn read_component<T: Clone + ToVariant + 'static>(world: &World, entity: WorldEntity) -> Option<Variant> {
+  world.get::<T>(entity).map(ToVariant::to_variant)
+}
+
+fn write_component<T: Clone + FromVariant + 'static>(
+  world: &mut World,
+  entity: WorldEntity,
+  value: Variant,
+) -> Result<(), VariantError> {
+  world.insert(entity, T::from_variant(value)?);
+
+  Ok(())
+}
+
+/// The component types scripts can use, by name.
+#[derive(Clone, Default)]
+pub struct ScriptComponents {
+  components: FastHashMap<String, ComponentMarshaller>,
+}
+
+impl ScriptComponents {
+  /// Lets scripts use components of the given type by name.
+  pub fn register<T: Clone + ToVariant + FromVariant + 'static>(&mut self, name: impl Into<String>) {
+    self.components.insert(name.into(), ComponentMarshaller {
+      type_id: TypeId::of::<T>(),
+      read: read_component::<T>,
+      write: write_component::<T>,
+    });
+  }
+
+  fn get(&self, name: &str) -> Result<ComponentMarshaller, ScriptSystemError> {
+    self
+      .components
+      .get(name)
+      .copied()
+      .ok_or_else(|| ScriptSystemError::UnknownComponent(name.to_string()))
+  }
+}
+
+/// A system declared by a script, before its components are resolved.
+#[derive(Clone, Debug)]
+pub struct ScriptSystemDeclaration {
+  pub name: String,
+  /// The components the system touches, prefixed with `mut` if it writes
+  /// them.
+  pub access: Vec<String>,
+  pub function: Callable<'static>,
+}
+
+/// Collects the systems scripts declare through `system.register`.
+#[derive(Clone, Default)]
+pub struct ScriptSystemRegistry {
+  pending: Rc<RefCell<Vec<ScriptSystemDeclaration>>>,
+}
+
+impl ScriptSystemRegistry {
+  /// Exposes `system.register` to scripts run on the machine.
+  pub fn install(&self, machine: &mut VirtualMachine) {
+    let registry = self.clone();
+    let register = Callable::from_function(move |arguments| {
+      let declaration = parse_declaration(arguments).map_err(|_| CallbackError::InvalidArgument)?;
+
+      registry.declare(declaration);
+
+      Ok(Variant::Null)
+    });
+
+    machine.set_global("system.register", Variant::Callable(register));
+  }
+
+  /// Declares a system, as if from a script.
+  pub fn declare(&self, declaration: ScriptSystemDeclaration) {
+    self.pending.borrow_mut().push(declaration);
+  }
+
+  /// Moves the systems declared so far into the schedule, replacing any
+  /// with the same name, e.g. when scripts are reloaded.
+  pub fn drain_into(&self, schedule: &mut Schedule, components: &ScriptComponents) -> Result<(), ScriptSystemError> {
+    let declarations = std::mem::take(&mut *self.pending.borrow_mut());
+
+    for declaration in declarations {
+      let system = ScriptSystem::new(declaration, components)?;
+
+      schedule.remove_system(&system.name);
+      schedule.add_system(system);
+    }
+
+    Ok(())
+  }
+}
+
+/// Reads the arguments of `system.register`.
+fn parse_declaration(arguments: &[Variant]) -> Result<ScriptSystemDeclaration, ScriptSystemError> {
+  let [Variant::String(name), access, Variant::Callable(function)] = arguments else {
+    return Err(ScriptSystemError::InvalidRegistration);
+  };
+
+  let access = ScriptArray::from_variant(access)
+    .ok_or(ScriptSystemError::InvalidRegistration)?
+    .values()
+    .into_iter()
+    .map(|value| match value {
+      Variant::String(component) => Ok(component),
+      _ => Err(ScriptSystemError::InvalidRegistration),
+    })
+    .collect::<Result<_, _>>()?;
+
+  Ok(ScriptSystemDeclaration {
+    name: name.clone(),
+    access,
+    function: function.clone(),
+  })
+}
+
+/// A component a script system touches.
+#[derive(Copy, Clone)]
+struct ScriptTerm {
+  marshaller: ComponentMarshaller,
+  writes: bool,
+}
+
+/// A system that runs a script function over the entities with its
+/// components.
+pub struct ScriptSystem {
+  name: String,
+  function: Callable<'static>,
+  terms: Vec<ScriptTerm>,
+}
+
+impl ScriptSystem {
+  /// Resolves the components a script declared.
+  pub fn new(declaration: ScriptSystemDeclaration, components: &ScriptComponents) -> Result<Self, ScriptSystemError> {
+    let terms = declaration
+      .access
+      .iter()
+      .map(|access| {
+        let (name, writes) = match access.strip_prefix("mut ") {
+          Some(name) => (name.trim(), true),
+          None => (access.trim(), false),
+        };
+
+        Ok(ScriptTerm {
+          marshaller: components.get(name)?,
+          writes,
+        })
+      })
+      .collect::<Result<_, ScriptSystemError>>()?;
+
+    Ok(Self {
+      name: declaration.name,
+      function: declaration.function,
+      terms,
+    })
+  }
+
+  /// Builds the rows the script sees.
+  fn rows(&self, world: &World, entities: &[WorldEntity]) -> Vec<Variant> {
+    entities
+      .iter()
+      .map(|entity| {
+        let mut row = Vec::with_capacity(self.terms.len() + 1);
+
+        row.push(entity_to_variant(*entity));
+
+        for term in &self.terms {
+          row.push((term.marshaller.read)(world, *entity).unwrap_or_default());
+        }
+
+        ScriptArray::to_variant(row)
+      })
+      .collect()
+  }
+}
+
+impl System for ScriptSystem {
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn access(&self) -> SystemAccess {
+    let mut access = SystemAccess::new();
+
+    for term in &self.terms {
+      match term.writes {
+        true => access.writes.push(term.marshaller.type_id),
+        false => access.reads.push(term.marshaller.type_id),
+      }
+    }
+
+    access
+  }
+
+  fn run(&mut self, world: &mut World, delta_time: f32) {
+    let types = self
+      .terms
+      .iter()
+      .map(|term| term.marshaller.type_id)
+      .collect::<Vec<_>>();
+    let entities = world.entities_with(&types);
+
+    if entities.is_empty() {
+      return;
+    }
+
+    let rows = self.rows(world, &entities);
+    let view = ScriptArray::to_variant(rows.clone());
+
+    if let Err(error) = self.function.call(&[Variant::F32(delta_time), view]) {
+      common::warn!("Script system {} failed: {error}", self.name);
+      return;
+    }
+
+    for (entity, row) in entities.iter().zip(&rows) {
+      let Some(row) = ScriptArray::from_variant(row) else {
+        continue;
+      };
+
+      for (index, term) in self.terms.iter().enumerate().filter(|(_, term)| term.writes) {
+        let value = row.get(index + 1).unwrap_or_default();
+
+        if (term.marshaller.write)(world, *entity, value).is_err() {
+          common::warn!("Script system {} wrote an invalid component", self.name);
+        }
+      }
+    }
+  }
+}
+
+/// Passes an entity to scripts as a number.
+fn entity_to_variant(entity: WorldEntity) -> Variant {
+  Variant::U64(entity.ordinal() as u64 | (entity.generation() as u64) << 32)
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{FromVariant, Vec2};
+
+  use super::*;
+
+  #[derive(Clone, Debug, PartialEq)]
+  struct Position(Vec2);
+
+  #[derive(Clone, Debug, PartialEq)]
+  struct Velocity(Vec2);
+
+  impl ToVariant for Position {
+    fn to_variant(&self) -> Variant {
+      Variant::Vec2(self.0)
+    }
+  }
+
+  impl FromVariant for Position {
+    fn from_variant(variant: Variant) -> Result<Self, VariantError> {
+      Vec2::from_variant(variant).map(Position)
+    }
+  }
+
+  impl ToVariant for Velocity {
+    fn to_variant(&self) -> Variant {
+      Variant::Vec2(self.0)
+    }
+  }
+
+  impl FromVariant for Velocity {
+    fn from_variant(variant: Variant) -> Result<Self, VariantError> {
+      Vec2::from_variant(variant).map(Velocity)
+    }
+  }
+
+  fn components() -> ScriptComponents {
+    let mut components = ScriptComponents::default();
+
+    components.register::<Position>("Position");
+    components.register::<Velocity>("Velocity");
+    components
+  }
+
+  /// Moves each position by its velocity, like a script would.
+  fn movement(arguments: &[Variant]) -> Result<Variant, CallbackError> {
+    let delta_time = f32::from_variant(arguments[0].clone()).map_err(|_| CallbackError::InvalidArgument)?;
+    let rows = ScriptArray::from_variant(&arguments[1]).ok_or(CallbackError::InvalidArgument)?;
+
+    for row in rows.values() {
+      let row = ScriptArray::from_variant(&row).ok_or(CallbackError::InvalidArgument)?;
+      let position = Vec2::from_variant(row.get(1).unwrap()).unwrap();
+      let velocity = Vec2::from_variant(row.get(2).unwrap()).unwrap();
+
+      row.set(1, Variant::Vec2(position + velocity * delta_time));
+      row.set(2, Variant::Vec2(Vec2::ZERO));
+    }
+
+    Ok(Variant::Null)
+  }
+
+  #[test]
+  fn it_should_run_script_systems_through_the_schedule() {
+    let mut world = World::new();
+    let moving = world.spawn();
+    let still = world.spawn();
+
+    world.insert(moving, Position(Vec2::ZERO));
+    world.insert(moving, Velocity(Vec2::new(2., 0.)));
+    world.insert(still, Position(Vec2::ONE));
+
+    let mut machine = VirtualMachine::new(Default::default());
+    let registry = ScriptSystemRegistry::default();
+
+    registry.install(&mut machine);
+
+    let register = match machine.globals().find(|(name, _)| *name == "system.register") {
+      Some((_, Variant::Callable(register))) => register.clone(),
+      _ => panic!("system.register should be installed"),
+    };
+
+    register
+      .call(&[
+        Variant::String("movement".to_string()),
+        ScriptArray::to_variant(vec![
+          Variant::String("mut Position".to_string()),
+          Variant::String("Velocity".to_string()),
+        ]),
+        Variant::Callable(Callable::from_function(movement)),
+      ])
+      .unwrap();
+
+    let mut schedule = Schedule::new();
+
+    registry.drain_into(&mut schedule, &components()).unwrap();
+    schedule.run(&mut world, 0.5);
+
+    assert_eq!(world.get::<Position>(moving), Some(&Position(Vec2::new(1., 0.))));
+    assert_eq!(world.get::<Position>(still), Some(&Position(Vec2::ONE)));
+
+    // only components declared with mut are written back
+    assert_eq!(world.get::<Velocity>(moving), Some(&Velocity(Vec2::new(2., 0.))));
+  }
+
+  #[test]
+  fn it_should_reject_unknown_components() {
+    let registry = ScriptSystemRegistry::default();
+
+    registry.declare(ScriptSystemDeclaration {
+      name: "gravity".to_string(),
+      access: vec!["mut Mass".to_string()],
+      function: Callable::from_function(|_| Ok(Variant::Null)),
+    });
+
+    let result = registry.drain_into(&mut Schedule::new(), &components());
+
+    assert!(matches!(result, Err(ScriptSystemError::UnknownComponent(name)) if name == "Mass"));
+  }
+}
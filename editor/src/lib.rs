@@ -4,8 +4,12 @@
 
 pub use documents::*;
 pub use hosting::*;
+pub use playmode::*;
 pub use projects::*;
+pub use settings::*;
 
 mod documents;
 mod hosting;
+mod playmode;
 mod projects;
+mod settings;
@@ -0,0 +1,408 @@
+//! Play-in-editor support.
+//!
+//! Pressing play snapshots the edited [`World`] and [`Scene`], and runs the
+//! game loop on them inside the editor, drawing into a viewport render target
+//! rather than the window. Stopping puts both back as they were, so nothing
+//! that happens while playing leaks into what's being edited:
+//!
+//! ```rust,ignore
+//! play_mode.play(&world, &scene)?;
+//!
+//! // each frame
+//! for event in events {
+//!   if let Some(event) = play_mode.route_input(&event) {
+//!     game_input.on_event(&event);
+//!   }
+//! }
+//!
+//! play_mode.update(&mut world, &mut schedule, delta_time);
+//! play_mode.render(&mut queue, |queue| draw_game(queue, &world));
+//!
+//! play_mode.stop(&mut world, &mut scene, &registry)?;
+//! ```
+//!
+//! Scene components can't be copied, so the scene is snapshotted through
+//! [`Component::inspect`](scenes::Component::inspect) and rebuilt through the
+//! [`ComponentRegistry`] on stop; see [`SceneSnapshot`] for what that can't
+//! put back.
+
+use common::{Color, Rectangle, UVec2, Vec2};
+use graphics::{
+  RenderQueue, RenderTarget, RenderTargetDescriptor, RenderTextureDescriptor, TargetError, Texture, TextureOptions,
+};
+use input::{InputEvent, MouseEvent};
+use scenes::{ComponentRegistry, Scene, SceneSnapshot, Schedule, TemplateError, World, WorldSnapshot};
+
+/// Whether the editor is editing the scene or playing it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PlayState {
+  #[default]
+  Editing,
+  Playing,
+  Paused,
+}
+
+/// Runs the game inside the editor, isolated from the edited [`World`] and
+/// [`Scene`].
+pub struct PlayMode {
+  state: PlayState,
+  snapshot: Option<(WorldSnapshot, SceneSnapshot)>,
+  viewport: Option<RenderTarget>,
+  viewport_size: UVec2,
+  /// Where the viewport is drawn in the editor window.
+  viewport_bounds: Rectangle,
+  is_viewport_focused: bool,
+  mouse_position: Vec2,
+  is_stepping: bool,
+  frame: u64,
+}
+
+impl PlayMode {
+  /// Creates a play mode that draws into a viewport of the given size.
+  pub fn new(width: u32, height: u32) -> Self {
+    Self {
+      state: PlayState::Editing,
+      snapshot: None,
+      viewport: None,
+      viewport_size: UVec2::new(width.max(1), height.max(1)),
+      viewport_bounds: Rectangle::from_corner_points(0., 0., width as f32, height as f32),
+      is_viewport_focused: false,
+      mouse_position: Vec2::ZERO,
+      is_stepping: false,
+      frame: 0,
+    }
+  }
+
+  /// The current [`PlayState`].
+  pub fn state(&self) -> PlayState {
+    self.state
+  }
+
+  /// Determines if the game is running, or paused mid-play.
+  pub fn is_playing(&self) -> bool {
+    self.state != PlayState::Editing
+  }
+
+  /// The number of frames simulated since play started.
+  pub fn frame(&self) -> u64 {
+    self.frame
+  }
+
+  /// Determines if input is routed to the game.
+  pub fn is_viewport_focused(&self) -> bool {
+    self.is_viewport_focused
+  }
+
+  /// The texture the game draws into, for the editor to present in its
+  /// viewport panel.
+  pub fn viewport_texture(&self) -> Option<Texture> {
+    self.viewport.as_ref().map(|it| it.color_attachment())
+  }
+
+  /// Starts playing the world and scene, or resumes them if paused.
+  ///
+  /// Both are snapshotted first, and restored by [`PlayMode::stop`].
+  pub fn play(&mut self, world: &World, scene: &Scene) -> Result<(), TargetError> {
+    match self.state {
+      PlayState::Editing => {
+        self.ensure_viewport()?;
+        self.snapshot = Some((world.snapshot(), scene.snapshot()));
+        self.frame = 0;
+        self.is_viewport_focused = true;
+        self.state = PlayState::Playing;
+      }
+      PlayState::Paused => self.state = PlayState::Playing,
+      PlayState::Playing => {}
+    }
+
+    Ok(())
+  }
+
+  /// Pauses the game, keeping its state until resumed or stopped.
+  pub fn pause(&mut self) {
+    if self.state == PlayState::Playing {
+      self.state = PlayState::Paused;
+    }
+  }
+
+  /// Advances a paused game by a single frame on the next update.
+  pub fn step(&mut self) {
+    if self.state == PlayState::Paused {
+      self.is_stepping = true;
+    }
+  }
+
+  /// Stops playing and restores the world and scene to how they were before
+  /// play.
+  ///
+  /// Scene components that were removed while playing are rebuilt through the
+  /// registry; if any can't be, the rest is still restored and the error is
+  /// returned.
+  pub fn stop(
+    &mut self,
+    world: &mut World,
+    scene: &mut Scene,
+    registry: &ComponentRegistry,
+  ) -> Result<(), TemplateError> {
+    self.state = PlayState::Editing;
+    self.is_viewport_focused = false;
+    self.is_stepping = false;
+
+    match self.snapshot.take() {
+      Some((world_snapshot, scene_snapshot)) => {
+        world.rollback(&world_snapshot);
+        scene.restore(&scene_snapshot, registry)
+      }
+      None => Ok(()),
+    }
+  }
+
+  /// Moves or resizes the viewport within the editor window.
+  ///
+  /// The render target is recreated at the new size before the next frame is
+  /// drawn.
+  pub fn set_viewport_bounds(&mut self, bounds: Rectangle) {
+    let size = UVec2::new(bounds.width().max(1.) as u32, bounds.height().max(1.) as u32);
+
+    if size != self.viewport_size {
+      self.viewport_size = size;
+      self.viewport = None;
+    }
+
+    self.viewport_bounds = bounds;
+  }
+
+  /// Focuses or unfocuses the viewport, e.g. when the editor's focus moves
+  /// to another panel.
+  pub fn set_viewport_focused(&mut self, is_focused: bool) {
+    self.is_viewport_focused = is_focused && self.is_playing();
+  }
+
+  /// Decides whether an input event from the editor window reaches the game.
+  ///
+  /// Clicking in the viewport focuses it, and clicking elsewhere gives focus
+  /// back to the editor. While focused, events are passed through with mouse
+  /// positions made relative to the viewport; otherwise they're left for the
+  /// editor.
+  pub fn route_input(&mut self, event: &InputEvent) -> Option<InputEvent> {
+    if let InputEvent::MouseEvent(MouseEvent::MouseMove { position, .. }) = event {
+      self.mouse_position = *position;
+    }
+
+    if !self.is_playing() {
+      return None;
+    }
+
+    if let InputEvent::MouseEvent(MouseEvent::MouseDown(_)) = event {
+      self.is_viewport_focused = self.viewport_bounds.contains_point(self.mouse_position);
+    }
+
+    if !self.is_viewport_focused {
+      return None;
+    }
+
+    match event {
+      InputEvent::MouseEvent(MouseEvent::MouseMove { position, delta }) => {
+        Some(InputEvent::MouseEvent(MouseEvent::MouseMove {
+          position: *position - self.viewport_bounds.min(),
+          delta: *delta,
+        }))
+      }
+      event => Some(event.clone()),
+    }
+  }
+
+  /// Runs a frame of the game on the world, if it's playing.
+  ///
+  /// Returns true if a frame was simulated.
+  pub fn update(&mut self, world: &mut World, schedule: &mut Schedule, delta_time: f32) -> bool {
+    let should_run = match self.state {
+      PlayState::Editing => false,
+      PlayState::Playing => true,
+      PlayState::Paused => std::mem::take(&mut self.is_stepping),
+    };
+
+    if should_run {
+      schedule.run(world, delta_time);
+      self.frame += 1;
+    }
+
+    should_run
+  }
+
+  /// Draws the game into the viewport, leaving the display as the active
+  /// target afterwards.
+  pub fn render(&mut self, queue: &mut RenderQueue, draw: impl FnOnce(&mut RenderQueue)) -> Result<(), TargetError> {
+    if !self.is_playing() {
+      return Ok(());
+    }
+
+    let viewport = self.ensure_viewport()?;
+
+    queue.set_render_target(viewport);
+    queue.clear_color_buffer(Color::BLACK);
+
+    draw(queue);
+
+    queue.set_render_target_to_display();
+
+    Ok(())
+  }
+
+  /// Creates the viewport render target, if it's missing or was resized.
+  fn ensure_viewport(&mut self) -> Result<&RenderTarget, TargetError> {
+    if self.viewport.is_none() {
+      self.viewport = Some(RenderTarget::new(&RenderTargetDescriptor {
+        color_attachment: RenderTextureDescriptor {
+          width: self.viewport_size.x,
+          height: self.viewport_size.y,
+          options: TextureOptions::default(),
+        },
+        depth_attachment: None,
+        stencil_attachment: None,
+      })?);
+    }
+
+    Ok(self.viewport.as_ref().unwrap())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::{vec2, Chunk, FastHashMap, StreamError, Variant};
+  use input::MouseButton;
+  use scenes::{Component, SceneInspector, SystemAccess};
+
+  use super::*;
+
+  #[derive(Clone, Debug, PartialEq)]
+  struct Position(f32);
+
+  struct Health {
+    value: f32,
+  }
+
+  impl Component for Health {
+    fn component_name(&self) -> &'static str {
+      "Health"
+    }
+
+    fn inspect(&self) -> Option<Chunk> {
+      let mut fields = FastHashMap::default();
+
+      fields.insert("value".to_string(), Chunk::Variant(Variant::F32(self.value)));
+
+      Some(Chunk::Map(fields))
+    }
+
+    fn apply_inspected(&mut self, fields: &Chunk) -> Result<(), StreamError> {
+      self.value = fields.read_field("value")?;
+
+      Ok(())
+    }
+  }
+
+  fn create_schedule() -> Schedule {
+    let mut schedule = Schedule::new();
+
+    schedule.add_fn(
+      "movement",
+      SystemAccess::new().writes::<Position>(),
+      |world, delta_time| {
+        for (_, (position,)) in world.query::<(&mut Position,)>() {
+          position.0 += delta_time;
+        }
+      },
+    );
+
+    schedule
+  }
+
+  #[test]
+  fn it_should_restore_the_world_and_scene_on_stop() {
+    let mut play_mode = PlayMode::new(64, 64);
+    let mut schedule = create_schedule();
+    let mut registry = ComponentRegistry::new();
+
+    registry.register("Health", |chunk| {
+      Ok(Health {
+        value: chunk.read_field("value")?,
+      })
+    });
+
+    let mut world = World::new();
+    let entity = world.spawn();
+
+    world.insert(entity, Position(0.));
+
+    let mut scene = Scene::new();
+    let player = scene.spawn_named("player");
+
+    scene.add_component(player, Health { value: 10. });
+
+    play_mode.play(&world, &scene).unwrap();
+
+    assert!(play_mode.update(&mut world, &mut schedule, 1.));
+    assert_eq!(world.get::<Position>(entity), Some(&Position(1.)));
+
+    SceneInspector::set(&mut scene, "player.Health.value", "3").unwrap();
+    scene.spawn_named("bullet");
+
+    play_mode.stop(&mut world, &mut scene, &registry).unwrap();
+
+    let health = scene.entity(player).unwrap().get_component::<Health>().unwrap();
+
+    assert_eq!(play_mode.state(), PlayState::Editing);
+    assert_eq!(world.get::<Position>(entity), Some(&Position(0.)));
+    assert_eq!(scene.entities().count(), 1);
+    assert_eq!(health.value, 10.);
+  }
+
+  #[test]
+  fn it_should_only_step_a_paused_game_once() {
+    let mut play_mode = PlayMode::new(64, 64);
+    let mut schedule = create_schedule();
+    let mut world = World::new();
+    let scene = Scene::new();
+
+    play_mode.play(&world, &scene).unwrap();
+    play_mode.pause();
+
+    assert!(!play_mode.update(&mut world, &mut schedule, 1.));
+
+    play_mode.step();
+
+    assert!(play_mode.update(&mut world, &mut schedule, 1.));
+    assert!(!play_mode.update(&mut world, &mut schedule, 1.));
+    assert_eq!(play_mode.frame(), 1);
+  }
+
+  #[test]
+  fn it_should_only_route_input_to_a_focused_viewport() {
+    let mut play_mode = PlayMode::new(64, 64);
+    let event = InputEvent::MouseEvent(MouseEvent::MouseDown(MouseButton::Left));
+
+    assert!(play_mode.route_input(&event).is_none());
+
+    play_mode.play(&World::new(), &Scene::new()).unwrap();
+    play_mode.set_viewport_bounds(Rectangle::from_corner_points(10., 10., 74., 74.));
+
+    let moved = play_mode.route_input(&InputEvent::MouseEvent(MouseEvent::MouseMove {
+      position: vec2(20., 30.),
+      delta: Vec2::ZERO,
+    }));
+
+    assert!(matches!(
+      moved,
+      Some(InputEvent::MouseEvent(MouseEvent::MouseMove { position, .. })) if position == vec2(10., 20.)
+    ));
+
+    play_mode.route_input(&InputEvent::MouseEvent(MouseEvent::MouseMove {
+      position: vec2(200., 200.),
+      delta: Vec2::ZERO,
+    }));
+
+    assert!(play_mode.route_input(&event).is_none());
+    assert!(!play_mode.is_viewport_focused());
+  }
+}
@@ -1,6 +1,6 @@
 //! Project configuration for the editor.
 
-use common::{info, ToVirtualPath, Version, VirtualPath};
+use common::{info, ProjectDefinition, ProjectDefinitionError, ToVirtualPath, Version, VirtualPath, PROJECT_FILE_NAME};
 
 /// The current [`Version`] of the [`Project`] schema.
 pub const DEFAULT_PROJECT_VERSION: Version = Version::new(0, 0, 1);
@@ -10,18 +10,19 @@ pub const DEFAULT_PROJECT_VERSION: Version = Version::new(0, 0, 1);
 pub enum ProjectError {
   InvalidVersion,
   GeneralIoError,
+  InvalidDefinition(ProjectDefinitionError),
 }
 
 /// Represents a project in the Surreal editor.
 ///
 /// A project is a collection of assets and settings that can be loaded and
 /// edited in the editor. Projects are stored in the _local_ file system and
-/// can be loaded from any location.
+/// can be loaded from any location; their structure is described by the
+/// `Surreal.toml` at their root, shared with the runtime and the tools.
 pub struct Project {
   /// The top-level details for this project.
   pub details: ProjectDetails,
-  pub asset_path: VirtualPath,
-  pub target_path: VirtualPath,
+  pub definition: ProjectDefinition,
 }
 
 /// Top-level details for a [`Project`].
@@ -34,22 +35,30 @@ pub struct ProjectDetails {
 
 impl Project {
   /// Opens a project at the given path, or creates a new one.
+  ///
+  /// New projects get a default `Surreal.toml`.
   pub fn open_or_create(name: &str, root_path: &str) -> Result<Self, ProjectError> {
     let root_path = root_path.to_virtual_path();
-
-    let asset_path = root_path.join("assets");
-    let target_path = root_path.join("target");
+    let project_file = root_path.join(PROJECT_FILE_NAME);
 
     info!("Opening project {} at path {}", name, root_path);
 
+    let definition = if project_file.exists() {
+      ProjectDefinition::from_path(&project_file).map_err(ProjectError::InvalidDefinition)?
+    } else {
+      let definition = ProjectDefinition::new(name, &root_path);
+
+      definition.save().map_err(|_| ProjectError::GeneralIoError)?;
+      definition
+    };
+
     let project = Self {
       details: ProjectDetails {
-        name: name.to_string(),
+        name: definition.name.clone(),
         path: root_path.to_string(),
         version: DEFAULT_PROJECT_VERSION,
       },
-      asset_path,
-      target_path,
+      definition,
     };
 
     // verify that the project is valid and the version is valid
@@ -71,6 +80,16 @@ impl Project {
     self.details.path.clone().to_virtual_path()
   }
 
+  /// The folders the project's assets live in.
+  pub fn asset_paths(&self) -> impl Iterator<Item = &VirtualPath> {
+    self.definition.asset_roots.iter().map(|root| &root.path)
+  }
+
+  /// The folder packed content is built into.
+  pub fn target_path(&self) -> &VirtualPath {
+    &self.definition.build.output
+  }
+
   /// Reads the [`Version`] of the project from the settings file.
   pub fn version(&self) -> Result<Version, ProjectError> {
     let path = self.root_path().join("/Settings/ProjectVersion.txt");
@@ -0,0 +1,10 @@
+//! User settings for the editor.
+
+use common::SnapSettings;
+
+/// Settings that apply across the editor, rather than to a project.
+#[derive(Clone, Debug, Default)]
+pub struct EditorSettings {
+  /// Snapping used by the transform gizmos and the placement tools.
+  pub snapping: SnapSettings,
+}
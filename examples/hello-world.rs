@@ -7,14 +7,14 @@ fn main() {
   })
   .expect("Failed to create window");
 
-  let mut clock = DeltaClock::default();
+  let mut pacer = FramePacer::default();
   let mut total_time = 0.0;
 
   let color1 = Color::random();
   let color2 = Color::random();
 
   while window.update() {
-    let delta_time = clock.tick();
+    let delta_time = pacer.tick().delta_time;
     total_time += delta_time;
 
     graphics().clear_color_buffer(Color::lerp(color1, color2, total_time.ping_pong()));
@@ -0,0 +1,157 @@
+//! Packing folders of sprites into atlases.
+//!
+//! Every folder named `*.atlas` is packed into a single power-of-two image,
+//! alongside a RON layout giving the rectangle of each sprite by name.
+//!
+//! Small textures elsewhere are packed automatically: each folder with enough
+//! of them gets an atlas of its own, and the pipeline writes redirects from
+//! the original paths to their regions, so they batch together at runtime
+//! without anyone managing an atlas by hand.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use image::{GenericImage, RgbaImage};
+
+/// The largest atlas the pipeline will produce.
+const MAX_ATLAS_SIZE: u32 = 8192;
+
+/// The name of the atlas that small textures in a folder are packed into.
+pub const AUTO_ATLAS_NAME: &str = "auto-atlas";
+
+/// A packed atlas, ready to be bundled.
+pub struct PackedAtlas {
+  pub image: Vec<u8>,
+  pub layout: String,
+  pub sprites: Vec<PackedSprite>,
+}
+
+/// Where a sprite was placed in a packed atlas.
+pub struct PackedSprite {
+  pub name: String,
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+}
+
+/// Packs the given named sprites into a single atlas.
+pub fn pack_atlas(mut sprites: Vec<(String, RgbaImage)>) -> Result<PackedAtlas, String> {
+  // tallest first packs shelves more tightly
+  sprites.sort_by(|(a_name, a), (b_name, b)| b.height().cmp(&a.height()).then(a_name.cmp(b_name)));
+
+  let mut size = 64;
+
+  let placements = loop {
+    if let Some(placements) = try_pack(&sprites, size) {
+      break placements;
+    }
+
+    size *= 2;
+
+    if size > MAX_ATLAS_SIZE {
+      return Err(format!(
+        "sprites don't fit in a {MAX_ATLAS_SIZE}x{MAX_ATLAS_SIZE} atlas"
+      ));
+    }
+  };
+
+  let mut atlas = RgbaImage::new(size, size);
+  let mut layout = String::from("{\n");
+  let mut packed = Vec::with_capacity(sprites.len());
+
+  for ((name, sprite), (x, y)) in sprites.iter().zip(placements) {
+    atlas.copy_from(sprite, x, y).map_err(|error| error.to_string())?;
+
+    writeln!(
+      layout,
+      "  \"{name}\": (x: {x}, y: {y}, width: {}, height: {}),",
+      sprite.width(),
+      sprite.height()
+    )
+    .unwrap();
+
+    packed.push(PackedSprite {
+      name: name.clone(),
+      x,
+      y,
+      width: sprite.width(),
+      height: sprite.height(),
+    });
+  }
+
+  layout.push('}');
+
+  let mut image = std::io::Cursor::new(Vec::new());
+
+  atlas
+    .write_to(&mut image, image::ImageFormat::Png)
+    .map_err(|error| error.to_string())?;
+
+  Ok(PackedAtlas {
+    image: image.into_inner(),
+    layout,
+    sprites: packed,
+  })
+}
+
+/// Finds the small PNG textures among the outputs, grouped by the folder
+/// they're in, keeping only folders with enough of them to be worth an
+/// atlas.
+///
+/// Textures are keyed by their full path, which their atlas layout keeps.
+pub fn find_small_textures(
+  outputs: &BTreeMap<String, Vec<u8>>,
+  max_size: u32,
+  min_sprites: usize,
+) -> BTreeMap<String, Vec<(String, RgbaImage)>> {
+  let mut folders = BTreeMap::<String, Vec<(String, RgbaImage)>>::new();
+
+  for (relative, data) in outputs {
+    if !relative.ends_with(".png") {
+      continue;
+    }
+
+    let Ok(image) = image::load_from_memory(data) else {
+      continue;
+    };
+
+    if image.width() > max_size || image.height() > max_size {
+      continue;
+    }
+
+    let folder = relative.rsplit_once('/').map_or("", |(folder, _)| folder);
+
+    folders
+      .entry(folder.to_string())
+      .or_default()
+      .push((relative.clone(), image.into_rgba8()));
+  }
+
+  folders.retain(|_, sprites| sprites.len() >= min_sprites.max(2));
+  folders
+}
+
+/// Packs sprites onto shelves in a square of the given size.
+fn try_pack(sprites: &[(String, RgbaImage)], size: u32) -> Option<Vec<(u32, u32)>> {
+  let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+  let mut placements = Vec::with_capacity(sprites.len());
+
+  for (_, sprite) in sprites {
+    if x + sprite.width() > size {
+      x = 0;
+      y += shelf_height;
+      shelf_height = 0;
+    }
+
+    if x + sprite.width() > size || y + sprite.height() > size {
+      return None;
+    }
+
+    placements.push((x, y));
+
+    x += sprite.width();
+    shelf_height = shelf_height.max(sprite.height());
+  }
+
+  Some(placements)
+}
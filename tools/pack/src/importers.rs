@@ -0,0 +1,312 @@
+//! The default importers run by the pipeline.
+
+use common::{
+  AssetError, AssetImporter, AssetImporterRegistry, Chunk, ColorPalette, Format, ImageTarget, ImportedAsset, RonFormat,
+  TargetProfile, VirtualPath,
+};
+
+/// The magic number at the start of indexed images.
+const INDEXED_MAGIC: &[u8; 4] = b"SIDX";
+
+/// Registers the importers for the asset kinds the engine ships with.
+pub fn register_defaults(registry: &mut AssetImporterRegistry) {
+  registry.register(ImageImporter);
+  registry.register(RonImporter);
+  registry.register(PaletteImporter);
+  registry.register(MaterialImporter);
+  registry.register(scripting::runtime::modules::ScriptImporter);
+  registry.register(audio::LoudnessImporter::default());
+}
+
+/// Validates images and normalizes them for the target.
+///
+/// Most targets get 8-bit RGBA PNGs. Targets with indexed images (like GBA)
+/// get an `SIDX` image instead:
+///
+/// * the magic `SIDX`, then the width and height as little-endian `u32`s;
+/// * the palette size as a `u16`, then each color as 15-bit BGR (`u16`), with
+///   index 0 reserved for transparency;
+/// * one index per pixel, packed two to a byte (low nibble first) when the
+///   palette fits in 16 colors.
+///
+/// Source art is expected to already fit the palette; images with too many
+/// colors fail to import rather than being quantized behind the artist's
+/// back.
+pub struct ImageImporter;
+
+impl AssetImporter for ImageImporter {
+  fn name(&self) -> &str {
+    "image"
+  }
+
+  fn extensions(&self) -> &[&str] {
+    &["png"]
+  }
+
+  fn version(&self) -> u32 {
+    2
+  }
+
+  fn import(&self, path: &VirtualPath, data: &[u8]) -> Result<ImportedAsset, AssetError> {
+    self.import_for_target(path, data, &TargetProfile::default())
+  }
+
+  fn import_for_target(
+    &self,
+    path: &VirtualPath,
+    data: &[u8],
+    target: &TargetProfile,
+  ) -> Result<ImportedAsset, AssetError> {
+    let image = image::load_from_memory(data)
+      .map_err(|error| AssetError::ImportFailed(format!("{path:?}: {error}")))?
+      .into_rgba8();
+
+    if let ImageTarget::Indexed { colors } = target.images {
+      return Ok(ImportedAsset {
+        data: encode_indexed(&image, colors).map_err(|error| AssetError::ImportFailed(format!("{path:?}: {error}")))?,
+        references: Vec::new(),
+      });
+    }
+
+    let mut output = std::io::Cursor::new(Vec::new());
+
+    image
+      .write_to(&mut output, image::ImageFormat::Png)
+      .map_err(|error| AssetError::ImportFailed(format!("{path:?}: {error}")))?;
+
+    Ok(ImportedAsset {
+      data: output.into_inner(),
+      references: Vec::new(),
+    })
+  }
+}
+
+/// Validates RON documents and collects the assets they refer to.
+///
+/// Any string field whose name ends in `path` is treated as a reference to
+/// another asset, relative to the assets root.
+pub struct RonImporter;
+
+impl AssetImporter for RonImporter {
+  fn name(&self) -> &str {
+    "ron"
+  }
+
+  fn extensions(&self) -> &[&str] {
+    &["ron"]
+  }
+
+  fn import(&self, path: &VirtualPath, data: &[u8]) -> Result<ImportedAsset, AssetError> {
+    let chunk = RonFormat::default()
+      .read_chunk(&mut std::io::Cursor::new(data))
+      .map_err(|error| AssetError::ImportFailed(format!("{path:?}: {error:?}")))?;
+
+    let mut references = Vec::new();
+
+    collect_references(&chunk, &mut references);
+
+    Ok(ImportedAsset {
+      data: data.to_vec(),
+      references,
+    })
+  }
+}
+
+/// Reads color palettes from common tools and normalizes them to JASC `.pal`.
+///
+/// Lospec palettes are plain `.json` files, so they're only picked up under
+/// the `.lospec` extension.
+pub struct PaletteImporter;
+
+impl AssetImporter for PaletteImporter {
+  fn name(&self) -> &str {
+    "palette"
+  }
+
+  fn extensions(&self) -> &[&str] {
+    &["pal", "gpl", "lospec"]
+  }
+
+  fn import(&self, path: &VirtualPath, data: &[u8]) -> Result<ImportedAsset, AssetError> {
+    let text = std::str::from_utf8(data).map_err(|error| AssetError::ImportFailed(format!("{path:?}: {error}")))?;
+    let palette =
+      ColorPalette::parse(text).map_err(|error| AssetError::ImportFailed(format!("{path:?}: {error:?}")))?;
+
+    Ok(ImportedAsset {
+      data: palette.to_jasc_pal().into_bytes(),
+      references: Vec::new(),
+    })
+  }
+}
+
+/// Validates `.material` documents and collects the shader and textures they
+/// refer to.
+///
+/// Only paths without a scheme are relative to the assets root, so only those
+/// are reported as references.
+pub struct MaterialImporter;
+
+impl AssetImporter for MaterialImporter {
+  fn name(&self) -> &str {
+    "material"
+  }
+
+  fn extensions(&self) -> &[&str] {
+    &["material"]
+  }
+
+  fn import(&self, path: &VirtualPath, data: &[u8]) -> Result<ImportedAsset, AssetError> {
+    let text = std::str::from_utf8(data).map_err(|error| AssetError::ImportFailed(format!("{path:?}: {error}")))?;
+    let descriptor = graphics::MaterialDescriptor::parse(text)
+      .map_err(|error| AssetError::ImportFailed(format!("{path:?}: {error:?}")))?;
+
+    Ok(ImportedAsset {
+      data: data.to_vec(),
+      references: descriptor
+        .references()
+        .filter(|reference| !reference.contains("://"))
+        .map(str::to_string)
+        .collect(),
+    })
+  }
+}
+
+/// Encodes an image as indexed 15-bit color, in the `SIDX` layout.
+fn encode_indexed(image: &image::RgbaImage, colors: usize) -> Result<Vec<u8>, String> {
+  let mut palette = vec![0u16]; // transparent
+  let mut indices = Vec::with_capacity(image.len() / 4);
+
+  for pixel in image.pixels() {
+    let [r, g, b, a] = pixel.0;
+
+    if a < 128 {
+      indices.push(0);
+      continue;
+    }
+
+    let color = (r as u16 >> 3) | ((g as u16 >> 3) << 5) | ((b as u16 >> 3) << 10);
+    let index = match palette[1..].iter().position(|it| *it == color) {
+      Some(index) => index + 1,
+      None => {
+        palette.push(color);
+        palette.len() - 1
+      }
+    };
+
+    if palette.len() > colors.min(256) {
+      return Err(format!("uses more than the target's {colors} colors"));
+    }
+
+    indices.push(index as u8);
+  }
+
+  let mut output = Vec::new();
+
+  output.extend_from_slice(INDEXED_MAGIC);
+  output.extend_from_slice(&image.width().to_le_bytes());
+  output.extend_from_slice(&image.height().to_le_bytes());
+  output.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+
+  for color in &palette {
+    output.extend_from_slice(&color.to_le_bytes());
+  }
+
+  if palette.len() <= 16 {
+    for pair in indices.chunks(2) {
+      output.push(pair[0] | pair.get(1).map_or(0, |high| high << 4));
+    }
+  } else {
+    output.extend_from_slice(&indices);
+  }
+
+  Ok(output)
+}
+
+/// Collects the values of path-like fields from the given chunk.
+fn collect_references(chunk: &Chunk, references: &mut Vec<String>) {
+  match chunk {
+    Chunk::Map(fields) => {
+      for (name, value) in fields {
+        match value {
+          Chunk::Variant(common::Variant::String(reference)) if name.ends_with("path") => {
+            references.push(reference.clone());
+          }
+          _ => collect_references(value, references),
+        }
+      }
+    }
+    Chunk::Sequence(items) => {
+      for item in items {
+        collect_references(item, references);
+      }
+    }
+    Chunk::Variant(_) => {}
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_should_normalize_palettes_to_jasc() {
+    let path = VirtualPath::new("local://palettes/sunset.gpl");
+    let imported = PaletteImporter
+      .import(&path, b"GIMP Palette\nName: Sunset\n#\n255 136 0\tOrange\n")
+      .unwrap();
+
+    assert_eq!(imported.data, b"JASC-PAL\r\n0100\r\n1\r\n255 136 0\r\n");
+    assert!(PaletteImporter.import(&path, b"not a palette").is_err());
+  }
+
+  #[test]
+  fn it_should_import_indexed_images_for_gba() {
+    let mut image = image::RgbaImage::from_pixel(3, 1, image::Rgba([255, 0, 0, 255]));
+
+    image.put_pixel(1, 0, image::Rgba([0, 0, 0, 0]));
+    image.put_pixel(2, 0, image::Rgba([0, 0, 255, 255]));
+
+    let mut png = std::io::Cursor::new(Vec::new());
+
+    image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+
+    let path = VirtualPath::new("local://sprites/hero.png");
+    let imported = ImageImporter
+      .import_for_target(&path, png.get_ref(), &TargetProfile::gba())
+      .unwrap();
+
+    assert_eq!(&imported.data[..4], INDEXED_MAGIC);
+    assert_eq!(&imported.data[4..12], &[3, 0, 0, 0, 1, 0, 0, 0]);
+    assert_eq!(&imported.data[12..20], &[3, 0, 0, 0, 0x1f, 0, 0, 0x7c]);
+    assert_eq!(&imported.data[20..], &[0x01, 0x02]);
+
+    // desktop builds keep RGBA
+    let imported = ImageImporter.import(&path, png.get_ref()).unwrap();
+
+    assert!(image::load_from_memory(&imported.data).is_ok());
+
+    // too many colors for the palette
+    let gradient = image::RgbaImage::from_fn(32, 1, |x, _| image::Rgba([x as u8 * 8, 0, 0, 255]));
+    let mut png = std::io::Cursor::new(Vec::new());
+
+    gradient.write_to(&mut png, image::ImageFormat::Png).unwrap();
+
+    assert!(ImageImporter
+      .import_for_target(&path, png.get_ref(), &TargetProfile::gba())
+      .is_err());
+  }
+
+  #[test]
+  fn it_should_collect_material_references() {
+    let path = VirtualPath::new("local://materials/water.material");
+    let imported = MaterialImporter
+      .import(
+        &path,
+        br#"Material(shader: "shaders/water.glsl", textures: { "u_texture": "local://water.png" })"#,
+      )
+      .unwrap();
+
+    assert_eq!(imported.references, vec!["shaders/water.glsl".to_string()]);
+    assert!(MaterialImporter.import(&path, b"Material(cull: Back)").is_err());
+  }
+}
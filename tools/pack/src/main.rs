@@ -0,0 +1,139 @@
+//! The Surreal asset pipeline.
+//!
+//! Imports everything in an assets folder ahead of time and packs it into
+//! bundles, so shipping builds never import at runtime.
+//!
+//! Usage: `surreal-pack <assets> <output> [--no-cache] [--no-auto-atlas]
+//! [--target <name>] [--size-report]`
+//!
+//! Targets are `desktop` (the default), `web` and `gba`. With
+//! `--size-report`, the packed bundles are measured afterwards; see
+//! [`SizeReport`].
+//!
+//! Without an assets folder and output, the project's `Surreal.toml` is found
+//! from the current folder (or given with `--project <path>`) and each of its
+//! asset roots is packed into a folder of the same name under its build
+//! output, using its build settings and default target.
+
+use std::path::{Path, PathBuf};
+
+use common::{AssetImporterRegistry, ProjectDefinition, TargetProfile};
+pub use pipeline::*;
+pub use sizes::*;
+
+mod atlases;
+mod importers;
+mod pipeline;
+mod sizes;
+
+const USAGE: &str =
+  "usage: surreal-pack <assets> <output> [--no-cache] [--no-auto-atlas] [--target <desktop|web|gba>] [--project <path>] [--size-report]";
+
+fn main() {
+  let mut arguments = Vec::new();
+  let mut use_cache = true;
+  let mut auto_atlas = AutoAtlasOptions::default();
+  let mut target = None;
+  let mut project_path = None;
+  let mut size_report = false;
+  let mut inputs = std::env::args().skip(1);
+
+  while let Some(argument) = inputs.next() {
+    match argument.as_str() {
+      "--no-cache" => use_cache = false,
+      "--no-auto-atlas" => auto_atlas.enabled = false,
+      "--target" => match inputs.next().as_deref().and_then(TargetProfile::from_name) {
+        Some(profile) => target = Some(profile),
+        None => {
+          eprintln!("unknown target; expected one of {:?}", TargetProfile::BUILT_IN);
+          std::process::exit(2);
+        }
+      },
+      "--project" => project_path = inputs.next().map(PathBuf::from),
+      "--size-report" => size_report = true,
+      _ => arguments.push(argument),
+    }
+  }
+
+  let jobs = match arguments.as_slice() {
+    [source, output] => vec![PackOptions {
+      source: PathBuf::from(source),
+      output: PathBuf::from(output),
+      use_cache,
+      target: target.unwrap_or_default(),
+      auto_atlas,
+    }],
+    [] => {
+      let Some(path) = project_path.or_else(|| ProjectDefinition::discover(std::env::current_dir().ok()?)) else {
+        eprintln!("no Surreal.toml found; {USAGE}");
+        std::process::exit(2);
+      };
+
+      let project = ProjectDefinition::from_path(path.to_string_lossy().as_ref()).unwrap_or_else(|error| {
+        eprintln!("failed to read {}: {error:?}", path.display());
+        std::process::exit(2);
+      });
+
+      auto_atlas.enabled &= project.build.auto_atlas;
+
+      let output = Path::new(project.build.output.location());
+      let target = target.unwrap_or_else(|| project.default_target());
+
+      project
+        .asset_roots
+        .iter()
+        .map(|root| PackOptions {
+          source: PathBuf::from(root.path.location()),
+          output: output.join(&root.name),
+          use_cache: use_cache && project.build.use_cache,
+          target: target.clone(),
+          auto_atlas: auto_atlas.clone(),
+        })
+        .collect()
+    }
+    _ => {
+      eprintln!("{USAGE}");
+      std::process::exit(2);
+    }
+  };
+
+  let mut has_errors = false;
+
+  for options in jobs {
+    has_errors |= pack(options, size_report);
+  }
+
+  if has_errors {
+    std::process::exit(1);
+  }
+}
+
+/// Packs a single assets folder, returning whether it failed.
+fn pack(options: PackOptions, size_report: bool) -> bool {
+  let mut registry = AssetImporterRegistry::new();
+
+  importers::register_defaults(&mut registry);
+
+  let output = options.output.clone();
+  let pipeline = Pipeline::new(registry, options);
+
+  match pipeline.run() {
+    Ok(report) => {
+      println!("{report}");
+
+      if size_report {
+        match SizeReport::measure_and_write(&output) {
+          Ok(sizes) => println!("{sizes}"),
+          Err(error) => eprintln!("failed to measure bundles: {error:?}"),
+        }
+      }
+
+      report.has_errors()
+    }
+    Err(error) => {
+      eprintln!("failed to pack assets: {error:?}");
+
+      true
+    }
+  }
+}
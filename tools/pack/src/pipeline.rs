@@ -0,0 +1,664 @@
+//! The pack pipeline: scan, import, atlas, validate, bundle and report.
+
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  fmt::{Display, Formatter, Write},
+  path::PathBuf,
+};
+
+use common::{
+  AssetBundle, AssetImporterRegistry, ContentHash, ContentHasher, DirectoryPatchSource, FileSystemError, HashAlgorithm,
+  ImageTarget, ImportedAsset, InputStream, OutputStream, PatchManifest, StreamError, TargetProfile, ToStream,
+  VirtualPath,
+};
+
+use crate::atlases::{find_small_textures, pack_atlas, AUTO_ATLAS_NAME};
+
+/// The folder, under the output, where imports are cached between runs.
+const CACHE_FOLDER: &str = ".cache";
+
+/// The name of the report written alongside the bundles.
+const REPORT_NAME: &str = "build-report.txt";
+
+/// The bundle that assets at the root of the assets folder are packed into.
+const ROOT_BUNDLE: &str = "core";
+
+/// The size of the chunks in the published patch manifest.
+const PATCH_CHUNK_SIZE: u32 = 64 * 1024;
+
+/// The extension of folders that are packed into atlases.
+const ATLAS_EXTENSION: &str = "atlas";
+
+/// The name of the redirects from automatically atlased textures to their
+/// atlases; see `graphics::AtlasRedirects`.
+const ATLAS_REDIRECTS_NAME: &str = "atlases.ron";
+
+/// An error that stops the pipeline entirely.
+#[derive(Debug)]
+pub enum PackError {
+  FileSystemError(FileSystemError),
+  StreamError(StreamError),
+  IoError(std::io::Error),
+}
+
+common::impl_error_coercion!(FileSystemError into PackError);
+common::impl_error_coercion!(StreamError into PackError);
+
+impl From<std::io::Error> for PackError {
+  #[inline]
+  fn from(error: std::io::Error) -> Self {
+    Self::IoError(error)
+  }
+}
+
+/// Options for a pipeline run.
+#[derive(Clone, Debug)]
+pub struct PackOptions {
+  /// The assets folder to import.
+  pub source: PathBuf,
+  /// The folder to write bundles, the cache and the report to.
+  pub output: PathBuf,
+  /// Re-use imports from previous runs when their source hasn't changed.
+  pub use_cache: bool,
+  /// The build target to import assets for.
+  pub target: TargetProfile,
+  /// How small textures are packed into atlases automatically.
+  pub auto_atlas: AutoAtlasOptions,
+}
+
+/// Options for packing small textures into shared atlases automatically.
+///
+/// Targets with indexed images are never atlased automatically, as their
+/// textures are already paletted to fit the hardware.
+#[derive(Clone, Debug)]
+pub struct AutoAtlasOptions {
+  /// Pack small textures at all.
+  pub enabled: bool,
+  /// The largest width or height of a texture that's packed.
+  pub max_sprite_size: u32,
+  /// The fewest small textures in a folder that are worth an atlas.
+  pub min_sprites: usize,
+}
+
+impl Default for AutoAtlasOptions {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      max_sprite_size: 64,
+      min_sprites: 4,
+    }
+  }
+}
+
+/// A summary of a pipeline run.
+#[derive(Debug, Default)]
+pub struct PackReport {
+  /// Assets run through an importer.
+  pub imported: usize,
+  /// Imported assets that were taken from the cache.
+  pub cached: usize,
+  /// Assets with no importer, bundled as-is.
+  pub copied: usize,
+  /// Assets replaced by a variant for the target.
+  pub variants: usize,
+  /// Atlases packed, with their sprite counts.
+  pub atlases: Vec<(String, usize)>,
+  /// Small textures packed into atlases automatically.
+  pub auto_atlased: usize,
+  /// Bundles written, with their asset counts and sizes in bytes.
+  pub bundles: Vec<(String, usize, usize)>,
+  /// Assets that failed to import.
+  pub errors: Vec<String>,
+  /// References to assets that don't exist, as (asset, reference).
+  pub missing_references: Vec<(String, String)>,
+}
+
+impl PackReport {
+  /// Did anything fail to import or validate?
+  pub fn has_errors(&self) -> bool {
+    !self.errors.is_empty() || !self.missing_references.is_empty()
+  }
+}
+
+impl Display for PackReport {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    writeln!(
+      formatter,
+      "imported {} assets ({} cached), copied {}, {} target variants",
+      self.imported, self.cached, self.copied, self.variants
+    )?;
+
+    for (name, sprites) in &self.atlases {
+      writeln!(formatter, "atlas {name}: {sprites} sprites")?;
+    }
+
+    if self.auto_atlased > 0 {
+      writeln!(formatter, "{} small textures atlased automatically", self.auto_atlased)?;
+    }
+
+    for (name, assets, size) in &self.bundles {
+      writeln!(formatter, "bundle {name}: {assets} assets, {size} bytes")?;
+    }
+
+    for error in &self.errors {
+      writeln!(formatter, "error: {error}")?;
+    }
+
+    for (asset, reference) in &self.missing_references {
+      writeln!(formatter, "error: {asset} refers to missing asset {reference}")?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Runs the importer registry over an assets folder and packs the results.
+pub struct Pipeline {
+  registry: AssetImporterRegistry,
+  options: PackOptions,
+}
+
+impl Pipeline {
+  /// Creates a new pipeline with the given importers.
+  pub fn new(registry: AssetImporterRegistry, options: PackOptions) -> Self {
+    Self { registry, options }
+  }
+
+  /// Runs the pipeline, writing bundles, a patch manifest and a report.
+  ///
+  /// Assets that fail to import are left out and recorded in the report,
+  /// rather than stopping the run.
+  pub fn run(&self) -> Result<PackReport, PackError> {
+    let mut report = PackReport::default();
+    let mut outputs = BTreeMap::<String, Vec<u8>>::new();
+    let mut references = Vec::new();
+
+    let root = to_virtual_path(&self.options.source);
+    let (files, atlases) = scan(&root);
+    let files = self.select_variants(files, &mut report);
+
+    std::fs::create_dir_all(self.options.output.join(CACHE_FOLDER))?;
+
+    for (relative, path) in &files {
+      let data = path.read_all_bytes()?;
+
+      let Some(importer) = self.registry.find(path) else {
+        outputs.insert(relative.clone(), data);
+        report.copied += 1;
+        continue;
+      };
+
+      let mut hasher = ContentHasher::new(HashAlgorithm::Blake3);
+
+      hasher.update(importer.name().as_bytes());
+      hasher.update(&importer.version().to_le_bytes());
+      hasher.update(self.options.target.name.as_bytes());
+      hasher.update(&data);
+
+      let cache_path = self.cache_path(&hasher.finish());
+
+      report.imported += 1;
+
+      let imported = match self.options.use_cache && cache_path.exists() {
+        true => match read_cached(&cache_path) {
+          Ok(imported) => {
+            report.cached += 1;
+            Some(imported)
+          }
+          Err(_) => self.import_and_cache(importer, path, &data, &cache_path, &mut report)?,
+        },
+        false => self.import_and_cache(importer, path, &data, &cache_path, &mut report)?,
+      };
+
+      let Some(imported) = imported else {
+        continue;
+      };
+
+      for reference in &imported.references {
+        references.push((relative.clone(), reference.clone()));
+      }
+
+      outputs.insert(relative.clone(), imported.data);
+    }
+
+    let redirected = self.pack_small_textures(&mut outputs, &mut report);
+
+    for (relative, sprites) in atlases {
+      let mut images = Vec::new();
+
+      for path in sprites {
+        let name = file_stem(&path);
+
+        match image::load_from_memory(&path.read_all_bytes()?) {
+          Ok(image) => images.push((name, image.into_rgba8())),
+          Err(error) => report.errors.push(format!("{relative}/{name}: {error}")),
+        }
+      }
+
+      let count = images.len();
+
+      match pack_atlas(images) {
+        Ok(atlas) => {
+          outputs.insert(format!("{relative}.png"), atlas.image);
+          outputs.insert(format!("{relative}.ron"), atlas.layout.into_bytes());
+          report.atlases.push((relative, count));
+        }
+        Err(error) => report.errors.push(format!("{relative}: {error}")),
+      }
+    }
+
+    let mut known = outputs.keys().cloned().collect::<BTreeSet<_>>();
+
+    known.extend(redirected);
+
+    for (asset, reference) in references {
+      if !known.contains(&reference) {
+        report.missing_references.push((asset, reference));
+      }
+    }
+
+    self.write_bundles(outputs, &mut report)?;
+
+    std::fs::write(self.options.output.join(REPORT_NAME), report.to_string())?;
+
+    Ok(report)
+  }
+
+  /// Packs small textures that share a folder into an atlas for the folder,
+  /// replacing them in the outputs with redirects to their regions.
+  ///
+  /// Returns the paths of the textures that were packed, which are still
+  /// valid references. Folders whose textures don't fit in an atlas are left
+  /// as they are.
+  fn pack_small_textures(&self, outputs: &mut BTreeMap<String, Vec<u8>>, report: &mut PackReport) -> Vec<String> {
+    let options = &self.options.auto_atlas;
+    let mut redirected = Vec::new();
+
+    if !options.enabled || matches!(self.options.target.images, ImageTarget::Indexed { .. }) {
+      return redirected;
+    }
+
+    let mut redirects = String::from("{\n");
+
+    for (folder, sprites) in find_small_textures(outputs, options.max_sprite_size, options.min_sprites) {
+      let name = match folder.is_empty() {
+        true => AUTO_ATLAS_NAME.to_string(),
+        false => format!("{folder}/{AUTO_ATLAS_NAME}"),
+      };
+
+      let count = sprites.len();
+      let Ok(atlas) = pack_atlas(sprites) else {
+        continue;
+      };
+
+      for sprite in &atlas.sprites {
+        writeln!(
+          redirects,
+          "  \"{}\": (atlas: \"{name}.png\", x: {}, y: {}, width: {}, height: {}),",
+          sprite.name, sprite.x, sprite.y, sprite.width, sprite.height
+        )
+        .unwrap();
+
+        outputs.remove(&sprite.name);
+        redirected.push(sprite.name.clone());
+      }
+
+      outputs.insert(format!("{name}.png"), atlas.image);
+      outputs.insert(format!("{name}.ron"), atlas.layout.into_bytes());
+
+      report.atlases.push((name, count));
+      report.auto_atlased += count;
+    }
+
+    if !redirected.is_empty() {
+      redirects.push('}');
+      outputs.insert(ATLAS_REDIRECTS_NAME.to_string(), redirects.into_bytes());
+    }
+
+    redirected
+  }
+
+  /// Imports a single asset, caching the result if it succeeds.
+  ///
+  /// Failures are recorded in the report, and the asset is left out.
+  fn import_and_cache(
+    &self,
+    importer: &dyn common::AssetImporter,
+    path: &VirtualPath,
+    data: &[u8],
+    cache_path: &VirtualPath,
+    report: &mut PackReport,
+  ) -> Result<Option<ImportedAsset>, PackError> {
+    match importer.import_for_target(path, data, &self.options.target) {
+      Ok(imported) => {
+        write_cached(cache_path, &imported)?;
+        Ok(Some(imported))
+      }
+      Err(error) => {
+        report.errors.push(format!("{error:?}"));
+        Ok(None)
+      }
+    }
+  }
+
+  /// Swaps source files for their variants on the target, and drops variants
+  /// for other targets.
+  fn select_variants(&self, files: Vec<(String, VirtualPath)>, report: &mut PackReport) -> Vec<(String, VirtualPath)> {
+    let target = &self.options.target;
+    let mut selected = BTreeMap::new();
+    let mut variants = Vec::new();
+
+    for (relative, path) in files {
+      match target.split_variant(&relative) {
+        Some((name, base)) if name == target.name => variants.push((base, path)),
+        Some(_) => {}
+        None => {
+          selected.insert(relative, path);
+        }
+      }
+    }
+
+    for (base, path) in variants {
+      if selected.insert(base, path).is_some() {
+        report.variants += 1;
+      }
+    }
+
+    selected.into_iter().collect()
+  }
+
+  /// Groups outputs by their top-level folder and writes a bundle for each.
+  fn write_bundles(&self, outputs: BTreeMap<String, Vec<u8>>, report: &mut PackReport) -> Result<(), PackError> {
+    let mut bundles = BTreeMap::<String, AssetBundle>::new();
+
+    for (relative, data) in outputs {
+      let name = match relative.split_once('/') {
+        Some((folder, _)) => folder.to_string(),
+        None => ROOT_BUNDLE.to_string(),
+      };
+
+      bundles.entry(name).or_default().insert(relative, data);
+    }
+
+    let output = to_virtual_path(&self.options.output);
+    let mut manifest = PatchManifest::new(PATCH_CHUNK_SIZE, HashAlgorithm::Blake3);
+
+    for (name, bundle) in bundles {
+      let file_name = format!("{name}.bundle");
+      let bytes = bundle.to_bytes()?;
+
+      std::fs::write(self.options.output.join(&file_name), &bytes)?;
+      manifest.insert(file_name.clone(), &bytes);
+      report.bundles.push((file_name, bundle.len(), bytes.len()));
+    }
+
+    manifest.to_path(output.join(DirectoryPatchSource::MANIFEST_NAME))?;
+
+    Ok(())
+  }
+
+  /// The path of the cached import with the given key.
+  fn cache_path(&self, key: &ContentHash) -> VirtualPath {
+    to_virtual_path(&self.options.output.join(CACHE_FOLDER).join(key.to_hex()))
+  }
+}
+
+/// Finds all source files, and the sprites of each atlas folder, keyed by
+/// their path relative to the root.
+#[allow(clippy::type_complexity)]
+fn scan(root: &VirtualPath) -> (Vec<(String, VirtualPath)>, Vec<(String, Vec<VirtualPath>)>) {
+  let mut files = Vec::new();
+  let mut atlases = Vec::new();
+  let mut pending = vec![root.clone()];
+
+  while let Some(directory) = pending.pop() {
+    for path in directory.files() {
+      files.push((relative_path(root, &path), path));
+    }
+
+    for path in directory.directories() {
+      match path.has_extension(ATLAS_EXTENSION) {
+        true => {
+          let relative = relative_path(root, &path);
+          let sprites = path.files().into_iter().filter(|it| it.has_extension("png")).collect();
+
+          atlases.push((relative.trim_end_matches(".atlas").to_string(), sprites));
+        }
+        false => pending.push(path),
+      }
+    }
+  }
+
+  files.sort_by(|(a, _), (b, _)| a.cmp(b));
+  atlases.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+  (files, atlases)
+}
+
+/// Reads a cached import.
+fn read_cached(path: &VirtualPath) -> Result<ImportedAsset, StreamError> {
+  let mut stream = path.open_input_stream().map_err(|_| StreamError::GeneralFailure)?;
+  let count = stream.read_u32()?;
+  let mut references = Vec::with_capacity(count as usize);
+
+  for _ in 0..count {
+    references.push(stream.read_string()?);
+  }
+
+  let length = stream.read_u64()?;
+  let data = stream.read_bytes(length as usize)?;
+
+  Ok(ImportedAsset { data, references })
+}
+
+/// Writes an import to the cache.
+fn write_cached(path: &VirtualPath, imported: &ImportedAsset) -> Result<(), StreamError> {
+  let mut stream = path.open_output_stream().map_err(|_| StreamError::GeneralFailure)?;
+
+  stream.write_u32(imported.references.len() as u32)?;
+
+  for reference in &imported.references {
+    stream.write_string(reference)?;
+  }
+
+  stream.write_u64(imported.data.len() as u64)?;
+  stream.write_bytes(&imported.data)?;
+
+  Ok(())
+}
+
+/// Converts a local path to a [`VirtualPath`].
+fn to_virtual_path(path: &std::path::Path) -> VirtualPath {
+  VirtualPath::new(&path.to_string_lossy())
+}
+
+/// Computes the path of the given file relative to the given root, with
+/// forward slashes.
+fn relative_path(root: &VirtualPath, path: &VirtualPath) -> String {
+  let root = root.location().replace('\\', "/");
+  let path = path.location().replace('\\', "/");
+
+  path
+    .strip_prefix(&root)
+    .map(|relative| relative.trim_start_matches('/'))
+    .unwrap_or(&path)
+    .to_string()
+}
+
+/// The file name of the given path, without its extension.
+fn file_stem(path: &VirtualPath) -> String {
+  let location = path.location().replace('\\', "/");
+  let name = location.rsplit('/').next().unwrap_or_default();
+
+  name.rsplit_once('.').map_or(name, |(stem, _)| stem).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use common::FromStream;
+
+  use super::*;
+  use crate::importers::register_defaults;
+
+  /// Creates a fresh temporary directory for a test.
+  fn temp_directory(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("surreal-pack-{}-{}", name, std::process::id()));
+
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+
+    path
+  }
+
+  /// Encodes a solid image of the given size as a PNG.
+  fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = std::io::Cursor::new(Vec::new());
+
+    image::RgbaImage::from_pixel(width, height, image::Rgba([255, 0, 0, 255]))
+      .write_to(&mut bytes, image::ImageFormat::Png)
+      .unwrap();
+
+    bytes.into_inner()
+  }
+
+  fn create_pipeline(source: &std::path::Path, output: &std::path::Path) -> Pipeline {
+    let mut registry = AssetImporterRegistry::new();
+
+    register_defaults(&mut registry);
+
+    Pipeline::new(registry, PackOptions {
+      source: source.to_path_buf(),
+      output: output.to_path_buf(),
+      use_cache: true,
+      target: TargetProfile::default(),
+      auto_atlas: AutoAtlasOptions::default(),
+    })
+  }
+
+  #[test]
+  fn it_should_pack_assets_into_bundles() {
+    let source = temp_directory("source");
+    let output = temp_directory("output");
+
+    std::fs::create_dir_all(source.join("sprites/heroes.atlas")).unwrap();
+    std::fs::write(source.join("sprites/heroes.atlas/knight.png"), png(16, 16)).unwrap();
+    std::fs::write(source.join("sprites/heroes.atlas/mage.png"), png(16, 32)).unwrap();
+    std::fs::write(source.join("sprites/tree.png"), png(8, 8)).unwrap();
+    std::fs::write(source.join("readme.txt"), b"hello").unwrap();
+    std::fs::write(
+      source.join("level.ron"),
+      br#"Level(tileset_path: "sprites/heroes.png", music_path: "audio/missing.ogg")"#,
+    )
+    .unwrap();
+
+    let report = create_pipeline(&source, &output).run().unwrap();
+
+    assert_eq!(report.imported, 2);
+    assert_eq!(report.copied, 1);
+    assert_eq!(report.atlases, vec![("sprites/heroes".to_string(), 2)]);
+    assert_eq!(report.missing_references, vec![(
+      "level.ron".to_string(),
+      "audio/missing.ogg".to_string()
+    )]);
+
+    let sprites = AssetBundle::from_bytes(&std::fs::read(output.join("sprites.bundle")).unwrap()).unwrap();
+
+    assert!(sprites.get("sprites/heroes.png").is_some());
+    assert!(sprites.get("sprites/heroes.ron").is_some());
+    assert!(sprites.get("sprites/tree.png").is_some());
+
+    let manifest = PatchManifest::from_bytes(&std::fs::read(output.join("patch.manifest")).unwrap()).unwrap();
+
+    assert!(manifest.get("core.bundle").is_some());
+    assert!(output.join(REPORT_NAME).exists());
+
+    std::fs::remove_dir_all(&source).unwrap();
+    std::fs::remove_dir_all(&output).unwrap();
+  }
+
+  #[test]
+  fn it_should_atlas_small_textures_automatically() {
+    let source = temp_directory("auto-atlas-source");
+    let output = temp_directory("auto-atlas-output");
+
+    std::fs::create_dir_all(source.join("icons")).unwrap();
+
+    for name in ["coin", "gem", "heart", "key"] {
+      std::fs::write(source.join(format!("icons/{name}.png")), png(16, 16)).unwrap();
+    }
+
+    std::fs::write(source.join("icons/banner.png"), png(256, 32)).unwrap();
+    std::fs::write(source.join("hud.ron"), br#"Hud(icon_path: "icons/coin.png")"#).unwrap();
+
+    let report = create_pipeline(&source, &output).run().unwrap();
+    let icons = AssetBundle::from_bytes(&std::fs::read(output.join("icons.bundle")).unwrap()).unwrap();
+    let core = AssetBundle::from_bytes(&std::fs::read(output.join("core.bundle")).unwrap()).unwrap();
+
+    std::fs::remove_dir_all(&source).unwrap();
+    std::fs::remove_dir_all(&output).unwrap();
+
+    assert_eq!(report.auto_atlased, 4);
+    assert_eq!(report.atlases, vec![("icons/auto-atlas".to_string(), 4)]);
+    assert!(report.missing_references.is_empty());
+
+    assert!(icons.get("icons/auto-atlas.png").is_some());
+    assert!(icons.get("icons/banner.png").is_some());
+    assert!(icons.get("icons/coin.png").is_none());
+
+    let redirects = std::str::from_utf8(core.get(ATLAS_REDIRECTS_NAME).unwrap()).unwrap();
+
+    assert!(
+      redirects.contains(r#""icons/coin.png": (atlas: "icons/auto-atlas.png", x: 0, y: 0, width: 16, height: 16)"#)
+    );
+  }
+
+  #[test]
+  fn it_should_select_variants_for_the_target() {
+    let source = temp_directory("variants-source");
+    let output = temp_directory("variants-output");
+
+    std::fs::write(source.join("hero.png"), png(8, 8)).unwrap();
+    std::fs::write(source.join("hero.gba.png"), png(4, 4)).unwrap();
+    std::fs::write(source.join("intro.web.txt"), b"web only").unwrap();
+
+    let mut pipeline = create_pipeline(&source, &output);
+
+    pipeline.options.target = TargetProfile::gba();
+
+    let report = pipeline.run().unwrap();
+    let bundle = AssetBundle::from_bytes(&std::fs::read(output.join("core.bundle")).unwrap()).unwrap();
+
+    std::fs::remove_dir_all(&source).unwrap();
+    std::fs::remove_dir_all(&output).unwrap();
+
+    assert_eq!(report.variants, 1);
+    assert_eq!(report.imported, 1);
+    assert_eq!(report.copied, 0);
+    assert_eq!(bundle.len(), 1);
+    assert!(bundle.get("hero.png").unwrap().starts_with(b"SIDX"));
+  }
+
+  #[test]
+  fn it_should_reuse_cached_imports() {
+    let source = temp_directory("cached-source");
+    let output = temp_directory("cached-output");
+
+    std::fs::write(source.join("tree.png"), png(8, 8)).unwrap();
+    std::fs::write(source.join("broken.png"), b"not an image").unwrap();
+
+    let pipeline = create_pipeline(&source, &output);
+
+    let first = pipeline.run().unwrap();
+    let second = pipeline.run().unwrap();
+
+    std::fs::remove_dir_all(&source).unwrap();
+    std::fs::remove_dir_all(&output).unwrap();
+
+    assert_eq!(first.cached, 0);
+    assert_eq!(first.errors.len(), 1);
+
+    // failed imports aren't cached, so they're retried
+    assert_eq!(second.cached, 1);
+    assert_eq!(second.errors.len(), 1);
+  }
+}
@@ -0,0 +1,268 @@
+//! Build-size and startup-time reports for packed bundles.
+//!
+//! Web and handheld targets ship every byte over the wire or onto a
+//! cartridge, so after packing, `--size-report` measures the bundles in the
+//! output: their sizes raw and compressed as they'd be served, the assets
+//! taking up the most room, content packed more than once across bundles,
+//! and how long each bundle takes to load from a cold start.
+
+use std::{
+  collections::HashMap,
+  fmt::{Display, Formatter},
+  path::Path,
+  time::{Duration, Instant},
+};
+
+use common::{AssetBundle, CompressionFormat, Compressor, ContentHash, FromStream, HashAlgorithm};
+
+use crate::PackError;
+
+/// The name of the size report written alongside the bundles.
+const SIZE_REPORT_NAME: &str = "size-report.txt";
+
+/// The number of largest assets listed in the report.
+const LARGEST_COUNT: usize = 10;
+
+/// The compression sizes are measured with, as bundles are served.
+const COMPRESSION: CompressionFormat = CompressionFormat::Zstd;
+
+/// The size of a single bundle, and how long it took to load.
+#[derive(Clone, Debug)]
+pub struct BundleSize {
+  pub name: String,
+  pub assets: usize,
+  pub bytes: usize,
+  pub compressed_bytes: usize,
+  /// How long reading and parsing the bundle took.
+  pub load_time: Duration,
+}
+
+/// The size of a single asset in a bundle.
+#[derive(Clone, Debug)]
+pub struct AssetSize {
+  pub path: String,
+  pub bundle: String,
+  pub bytes: usize,
+  pub compressed_bytes: usize,
+}
+
+/// The same content packed at several paths.
+#[derive(Clone, Debug)]
+pub struct DuplicateContent {
+  /// The copies, as (bundle, path).
+  pub copies: Vec<(String, String)>,
+  /// The size of each copy.
+  pub bytes: usize,
+}
+
+impl DuplicateContent {
+  /// The bytes that would be saved by packing the content once.
+  pub fn wasted_bytes(&self) -> usize {
+    self.bytes * (self.copies.len() - 1)
+  }
+}
+
+/// Sizes and load times of the bundles in a pack output.
+#[derive(Clone, Debug, Default)]
+pub struct SizeReport {
+  pub bundles: Vec<BundleSize>,
+  /// Every asset, largest compressed first.
+  pub assets: Vec<AssetSize>,
+  /// Content packed more than once, most wasteful first.
+  pub duplicates: Vec<DuplicateContent>,
+}
+
+impl SizeReport {
+  /// Measures every bundle in the output folder.
+  ///
+  /// Each bundle is loaded once, as it would be at startup, and its load
+  /// time is how long reading and parsing it took.
+  pub fn measure(output: &Path) -> Result<Self, PackError> {
+    let mut paths = std::fs::read_dir(output)?
+      .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+      .filter(|path| path.extension().is_some_and(|extension| extension == "bundle"))
+      .collect::<Vec<_>>();
+
+    paths.sort();
+
+    let mut report = Self::default();
+    let mut contents = HashMap::<ContentHash, DuplicateContent>::new();
+
+    for path in paths {
+      let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+      let started = Instant::now();
+      let bytes = std::fs::read(&path)?;
+      let bundle = AssetBundle::from_bytes(&bytes)?;
+      let load_time = started.elapsed();
+
+      report.bundles.push(BundleSize {
+        name: name.clone(),
+        assets: bundle.len(),
+        bytes: bytes.len(),
+        compressed_bytes: COMPRESSION.compress(&bytes)?.len(),
+        load_time,
+      });
+
+      for (asset, data) in bundle.iter() {
+        report.assets.push(AssetSize {
+          path: asset.to_string(),
+          bundle: name.clone(),
+          bytes: data.len(),
+          compressed_bytes: COMPRESSION.compress(data)?.len(),
+        });
+
+        contents
+          .entry(ContentHash::of(HashAlgorithm::Blake3, data))
+          .or_insert_with(|| DuplicateContent {
+            copies: Vec::new(),
+            bytes: data.len(),
+          })
+          .copies
+          .push((name.clone(), asset.to_string()));
+      }
+    }
+
+    report
+      .assets
+      .sort_by_key(|asset| std::cmp::Reverse(asset.compressed_bytes));
+
+    report.duplicates = contents.into_values().filter(|it| it.copies.len() > 1).collect();
+    report.duplicates.sort_by(|a, b| {
+      b.wasted_bytes()
+        .cmp(&a.wasted_bytes())
+        .then_with(|| a.copies.cmp(&b.copies))
+    });
+
+    Ok(report)
+  }
+
+  /// Measures the bundles in the output folder and writes the report
+  /// alongside them.
+  pub fn measure_and_write(output: &Path) -> Result<Self, PackError> {
+    let report = Self::measure(output)?;
+
+    std::fs::write(output.join(SIZE_REPORT_NAME), report.to_string())?;
+
+    Ok(report)
+  }
+
+  /// The size of every bundle together.
+  pub fn total_bytes(&self) -> usize {
+    self.bundles.iter().map(|bundle| bundle.bytes).sum()
+  }
+
+  /// The compressed size of every bundle together.
+  pub fn total_compressed_bytes(&self) -> usize {
+    self.bundles.iter().map(|bundle| bundle.compressed_bytes).sum()
+  }
+
+  /// How long loading every bundle took together.
+  pub fn total_load_time(&self) -> Duration {
+    self.bundles.iter().map(|bundle| bundle.load_time).sum()
+  }
+
+  /// The bytes that would be saved by packing duplicated content once.
+  pub fn wasted_bytes(&self) -> usize {
+    self.duplicates.iter().map(DuplicateContent::wasted_bytes).sum()
+  }
+}
+
+impl Display for SizeReport {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+    writeln!(
+      formatter,
+      "{} bundles: {} bytes, {} compressed, loaded in {:.2}ms",
+      self.bundles.len(),
+      self.total_bytes(),
+      self.total_compressed_bytes(),
+      self.total_load_time().as_secs_f64() * 1000.
+    )?;
+
+    for bundle in &self.bundles {
+      writeln!(
+        formatter,
+        "bundle {}: {} assets, {} bytes, {} compressed, loaded in {:.2}ms",
+        bundle.name,
+        bundle.assets,
+        bundle.bytes,
+        bundle.compressed_bytes,
+        bundle.load_time.as_secs_f64() * 1000.
+      )?;
+    }
+
+    for asset in self.assets.iter().take(LARGEST_COUNT) {
+      writeln!(
+        formatter,
+        "largest {} ({}): {} bytes, {} compressed",
+        asset.path, asset.bundle, asset.bytes, asset.compressed_bytes
+      )?;
+    }
+
+    for duplicate in &self.duplicates {
+      let copies = duplicate
+        .copies
+        .iter()
+        .map(|(bundle, path)| format!("{path} ({bundle})"))
+        .collect::<Vec<_>>();
+
+      writeln!(
+        formatter,
+        "duplicate {} bytes, {} wasted: {}",
+        duplicate.bytes,
+        duplicate.wasted_bytes(),
+        copies.join(", ")
+      )?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use common::ToStream;
+
+  use super::*;
+
+  #[test]
+  fn it_should_report_sizes_and_duplicates_across_bundles() {
+    let output = std::env::temp_dir().join(format!("surreal-pack-sizes-{}", std::process::id()));
+
+    let _ = std::fs::remove_dir_all(&output);
+    std::fs::create_dir_all(&output).unwrap();
+
+    let music = (0..4096).map(|index| (index % 251) as u8).collect::<Vec<_>>();
+    let mut core = AssetBundle::new();
+    let mut levels = AssetBundle::new();
+
+    core.insert("music.ogg", music.clone());
+    core.insert("readme.txt", b"hello".to_vec());
+    levels.insert("levels/music.ogg", music);
+    levels.insert("levels/one.ron", b"Level()".to_vec());
+
+    std::fs::write(output.join("core.bundle"), core.to_bytes().unwrap()).unwrap();
+    std::fs::write(output.join("levels.bundle"), levels.to_bytes().unwrap()).unwrap();
+    std::fs::write(output.join("patch.manifest"), b"not a bundle").unwrap();
+
+    let report = SizeReport::measure_and_write(&output).unwrap();
+    let written = std::fs::read_to_string(output.join(SIZE_REPORT_NAME)).unwrap();
+
+    std::fs::remove_dir_all(&output).unwrap();
+
+    assert_eq!(report.bundles.len(), 2);
+    assert_eq!(report.bundles[0].name, "core.bundle");
+    assert_eq!(report.assets.len(), 4);
+    assert_eq!(report.assets[0].bytes, 4096);
+    assert!(report.assets[0].compressed_bytes < 4096);
+
+    assert_eq!(report.duplicates.len(), 1);
+    assert_eq!(report.wasted_bytes(), 4096);
+    assert_eq!(report.duplicates[0].copies, vec![
+      ("core.bundle".to_string(), "music.ogg".to_string()),
+      ("levels.bundle".to_string(), "levels/music.ogg".to_string()),
+    ]);
+
+    assert!(written.contains("duplicate 4096 bytes, 4096 wasted"));
+  }
+}